@@ -10,8 +10,17 @@ mod esp32 {
         g_wifi_feature_caps, CONFIG_FEATURE_CACHE_TX_BUF_BIT,
     };
     use micro_rdk::{
-        common::{app_client::AppClientConfig, entry::RobotRepresentation},
-        esp32::{certificate::WebRtcCertificate, entry::serve_web, tls::Esp32TLSServerConfig},
+        common::{
+            app_client::AppClientConfig,
+            calibration::set_calibration_storage,
+            entry::RobotRepresentation,
+            nvs_schema::{self, NvsSchemaError, NvsSource},
+            secret::SecretString,
+        },
+        esp32::{
+            calibration::NvsCalibrationStorage, certificate::WebRtcCertificate, entry::serve_web,
+            tls::Esp32TLSServerConfig,
+        },
     };
 
     extern "C" {
@@ -54,6 +63,8 @@ mod esp32 {
         EspError(EspError),
         #[error("Error obtaining peripherals")]
         PeripheralsError,
+        #[error(transparent)]
+        NvsSchemaError(#[from] NvsSchemaError),
     }
 
     impl From<EspError> for ServerError {
@@ -73,6 +84,30 @@ mod esp32 {
             .to_string())
     }
 
+    struct ViamNvs<'a>(&'a EspDefaultNvs);
+
+    impl NvsSource for ViamNvs<'_> {
+        fn contains(&self, key: &str) -> bool {
+            self.0.contains(key).unwrap_or(false)
+        }
+        fn get_string(&self, key: &str) -> Option<String> {
+            get_str_from_nvs(self.0, key).ok()
+        }
+    }
+
+    /// Brings credentials written by an older installer to the layout this firmware expects
+    fn migrate_nvs(viam_nvs: &mut EspDefaultNvs) -> Result<(), ServerError> {
+        let entries = nvs_schema::migrate(&ViamNvs(viam_nvs)).map_err(|e| {
+            log::error!("credentials stored in NVS can't be used: {}", e);
+            e
+        })?;
+        for (key, value) in entries {
+            info!("migrating NVS data, setting {}", key);
+            viam_nvs.set_str(key, &value)?;
+        }
+        Ok(())
+    }
+
     fn get_blob_from_nvs(viam_nvs: &EspDefaultNvs, key: &str) -> Result<Vec<u8>, ServerError> {
         let mut buffer_ref = [0_u8; 4000];
         Ok(viam_nvs
@@ -85,8 +120,8 @@ mod esp32 {
         #[cfg(not(feature = "qemu"))]
         wifi_ssid: String,
         #[cfg(not(feature = "qemu"))]
-        wifi_pwd: String,
-        robot_secret: String,
+        wifi_pwd: SecretString,
+        robot_secret: SecretString,
         robot_id: String,
         robot_dtls_cert: Vec<u8>,
         robot_dtls_key_pair: Vec<u8>,
@@ -100,14 +135,21 @@ mod esp32 {
         fn new() -> Result<NvsStaticVars, ServerError> {
             let nvs = EspDefaultNvsPartition::take()?;
             info!("get namespace...");
-            let viam_nvs = EspNvs::new(nvs.clone(), VIAM_NVS_NAMESPACE, true)?;
+            let mut viam_nvs = EspNvs::new(nvs.clone(), VIAM_NVS_NAMESPACE, true)?;
+            migrate_nvs(&mut viam_nvs)?;
+            match NvsCalibrationStorage::new(nvs.clone()) {
+                Ok(storage) => {
+                    let _ = set_calibration_storage(storage);
+                }
+                Err(err) => warn!("sensor calibrations won't be persisted: {}", err),
+            }
             info!("loading creds...");
             Ok(NvsStaticVars {
                 #[cfg(not(feature = "qemu"))]
                 wifi_ssid: get_str_from_nvs(&viam_nvs, "WIFI_SSID")?,
                 #[cfg(not(feature = "qemu"))]
-                wifi_pwd: get_str_from_nvs(&viam_nvs, "WIFI_PASSWORD")?,
-                robot_secret: get_str_from_nvs(&viam_nvs, "ROBOT_SECRET")?,
+                wifi_pwd: get_str_from_nvs(&viam_nvs, "WIFI_PASSWORD")?.into(),
+                robot_secret: get_str_from_nvs(&viam_nvs, "ROBOT_SECRET")?.into(),
                 robot_id: get_str_from_nvs(&viam_nvs, "ROBOT_ID")?,
                 robot_dtls_cert: get_blob_from_nvs(&viam_nvs, "ROBOT_DTLS_CERT")?,
                 robot_dtls_key_pair: get_blob_from_nvs(&viam_nvs, "DTLS_KEY_PAIR")?,
@@ -122,7 +164,8 @@ mod esp32 {
     pub(crate) fn main_esp32() {
         micro_rdk::esp32::esp_idf_svc::sys::link_patches();
 
-        micro_rdk::esp32::esp_idf_svc::log::EspLogger::initialize_default();
+        // keep recent logs in memory so they can be tailed through TailRobotPartLogs
+        micro_rdk::common::log::init_esp32_logger().unwrap();
         let sys_loop_stack = EspSystemEventLoop::take().unwrap();
 
         #[cfg(not(feature = "qemu"))]
@@ -197,7 +240,7 @@ mod esp32 {
                 periph.modem,
                 sys_loop_stack,
                 &nvs_vars.wifi_ssid,
-                &nvs_vars.wifi_pwd,
+                nvs_vars.wifi_pwd.expose(),
             )
             .unwrap();
             (wifi.wifi().sta_netif().get_ip_info().unwrap().ip, wifi)
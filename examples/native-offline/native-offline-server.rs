@@ -0,0 +1,36 @@
+#[cfg(not(target_os = "espidf"))]
+mod native {
+    use micro_rdk::{
+        common::{
+            entry::RobotRepresentation, log::BufferedLogger,
+            static_config::config_response_from_json,
+        },
+        native::entry::serve_offline,
+    };
+
+    // The robot config is compiled into the binary, edit robot.json and rebuild to change it
+    static ROBOT_CONFIG: &str = include_str!("robot.json");
+
+    pub(crate) fn main_native() {
+        let logger = env_logger::Builder::from_default_env().build();
+        let level = logger.filter();
+        BufferedLogger::new(logger).init(level).unwrap();
+
+        let cfg = config_response_from_json(ROBOT_CONFIG).expect("invalid robot.json");
+        let repr = RobotRepresentation::WithRegistry(Box::default());
+
+        let ip = match local_ip_address::local_ip().unwrap() {
+            std::net::IpAddr::V4(ip) => ip,
+            _ => panic!("ouups expected ipv4"),
+        };
+
+        serve_offline(cfg, repr, ip);
+    }
+}
+
+fn main() {
+    #[cfg(not(target_os = "espidf"))]
+    {
+        native::main_native();
+    }
+}
@@ -4,12 +4,15 @@ mod native {
     include!(concat!(env!("OUT_DIR"), "/robot_secret.rs"));
 
     use micro_rdk::{
-        common::{app_client::AppClientConfig, entry::RobotRepresentation},
+        common::{app_client::AppClientConfig, entry::RobotRepresentation, log::BufferedLogger},
         native::{entry::serve_web, tls::NativeTlsServerConfig},
     };
 
     pub(crate) fn main_native() {
-        env_logger::init();
+        // keep recent logs in memory so they can be tailed through TailRobotPartLogs
+        let logger = env_logger::Builder::from_default_env().build();
+        let level = logger.filter();
+        BufferedLogger::new(logger).init(level).unwrap();
 
         let repr = RobotRepresentation::WithRegistry(Box::default());
 
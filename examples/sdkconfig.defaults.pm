@@ -0,0 +1,6 @@
+# Power management, required by the power profiles of the power management service. Dynamic
+# frequency scaling and tickless idle change the timing of every task, so they are left off by
+# default: add this file to the sdkconfig defaults of the project to enable them, for instance
+# ESP_IDF_SDKCONFIG_DEFAULTS="sdkconfig.defaults;sdkconfig.defaults.esp32;sdkconfig.defaults.pm"
+CONFIG_PM_ENABLE=y
+CONFIG_FREERTOS_USE_TICKLESS_IDLE=y
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::error::Error;
+use super::nvs::metadata::read_partition_table;
+
+// Layout of the asset image read by micro-rdk, see micro-rdk/src/common/assets.rs
+const ASSET_IMAGE_MAGIC: &[u8; 8] = b"MRDKASST";
+const ASSET_IMAGE_VERSION: u16 = 1;
+const HEADER_LEN: usize = 12;
+// what erased flash reads as
+const PADDING: u8 = 0xFF;
+
+/// Label of the partition micro-rdk loads assets from
+pub const ASSETS_PARTITION_LABEL: &str = "assets";
+
+pub struct AssetsMetadata {
+    pub size: u64,
+    pub start_address: u64,
+}
+
+/// Finds the partition labeled `label` in the partition table of a compiled binary
+pub fn read_assets_metadata(binary_path: PathBuf, label: &str) -> Result<AssetsMetadata, Error> {
+    for entry in read_partition_table(binary_path)? {
+        let entry_label = &entry[10..26];
+        let len = entry_label
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(entry_label.len());
+        if entry[0] == 0x01 && &entry_label[..len] == label.as_bytes() {
+            return Ok(AssetsMetadata {
+                start_address: u32::from_le_bytes(entry[2..6].try_into().unwrap()) as u64,
+                size: u32::from_le_bytes(entry[6..10].try_into().unwrap()) as u64,
+            });
+        }
+    }
+    Err(Error::AssetsPartitionMissingError(label.to_string()))
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).map_err(Error::FileError)? {
+        let path = entry.map_err(Error::FileError)?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let name = path
+                .strip_prefix(root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/");
+            files.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+/// Builds an asset image holding every file under `dir`, padded to the `size` of the partition
+pub fn build_asset_image(dir: &Path, size: usize) -> Result<Vec<u8>, Error> {
+    let mut files = vec![];
+    collect_files(dir, dir, &mut files)?;
+    files.sort();
+    if files.len() > u16::MAX as usize {
+        return Err(Error::AssetImageError(format!(
+            "too many files ({})",
+            files.len()
+        )));
+    }
+    if let Some((name, _)) = files.iter().find(|(name, _)| name.len() > u8::MAX as usize) {
+        return Err(Error::AssetImageError(format!(
+            "file name {} is too long",
+            name
+        )));
+    }
+
+    let toc_len: usize = files.iter().map(|(name, _)| 1 + name.len() + 12).sum();
+    let mut toc = Vec::with_capacity(HEADER_LEN + toc_len);
+    toc.extend_from_slice(ASSET_IMAGE_MAGIC);
+    toc.extend_from_slice(&ASSET_IMAGE_VERSION.to_le_bytes());
+    toc.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    let mut contents = vec![];
+    for (name, path) in &files {
+        let content = fs::read(path).map_err(Error::FileError)?;
+        let offset = HEADER_LEN + toc_len + contents.len();
+        if offset + content.len() > size {
+            return Err(Error::AssetImageError(format!(
+                "{} does not fit in a partition of {} bytes",
+                name, size
+            )));
+        }
+        log::info!("Adding {} ({} bytes)", name, content.len());
+        toc.push(name.len() as u8);
+        toc.extend_from_slice(name.as_bytes());
+        toc.extend_from_slice(&(offset as u32).to_le_bytes());
+        toc.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        toc.extend_from_slice(&crc32fast::hash(&content).to_le_bytes());
+        contents.extend_from_slice(&content);
+    }
+    if toc.len() + contents.len() > size {
+        return Err(Error::AssetImageError(format!(
+            "assets do not fit in a partition of {} bytes",
+            size
+        )));
+    }
+    let mut image = toc;
+    image.extend_from_slice(&contents);
+    image.resize(size, PADDING);
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_asset_image;
+
+    #[test]
+    fn test_build_asset_image() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("cal")).unwrap();
+        std::fs::write(dir.path().join("cal/imu.json"), "[1.0]").unwrap();
+        std::fs::write(dir.path().join("blob.bin"), [7; 3]).unwrap();
+
+        let image = build_asset_image(dir.path(), 4096).unwrap();
+        assert_eq!(image.len(), 4096);
+        assert_eq!(&image[..8], b"MRDKASST");
+        assert_eq!(u16::from_le_bytes([image[10], image[11]]), 2);
+        // entries are sorted by name
+        assert_eq!(image[12], 8);
+        assert_eq!(&image[13..21], b"blob.bin");
+        let offset = u32::from_le_bytes(image[21..25].try_into().unwrap()) as usize;
+        let len = u32::from_le_bytes(image[25..29].try_into().unwrap()) as usize;
+        assert_eq!(&image[offset..offset + len], &[7; 3]);
+        assert_eq!(
+            u32::from_le_bytes(image[29..33].try_into().unwrap()),
+            crc32fast::hash(&[7; 3])
+        );
+        assert_eq!(image[4095], 0xFF);
+
+        assert!(build_asset_image(dir.path(), 32).is_err());
+    }
+}
@@ -49,6 +49,10 @@ pub enum Error {
     SerialConfigError(String),
     #[error("No command received")]
     NoCommandError,
+    #[error("Partition {0} missing in partition table")]
+    AssetsPartitionMissingError(String),
+    #[error("Asset Image Error: {0}")]
+    AssetImageError(String),
 }
 
 impl From<RcgenError> for Error {
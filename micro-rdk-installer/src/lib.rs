@@ -1,7 +1,9 @@
+pub mod assets;
 pub mod nvs {
     pub mod data;
     pub mod metadata;
     pub mod partition;
+    pub mod reader;
     pub mod request;
 }
 pub mod error;
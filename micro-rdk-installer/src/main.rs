@@ -1,16 +1,20 @@
 use log::LevelFilter;
 use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{arg, command, Args, Parser, Subcommand};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Input, Password};
 use espflash::cli::{config::Config, connect, monitor::monitor, ConnectArgs, EspflashProgress};
+use micro_rdk_installer::assets::{
+    build_asset_image, read_assets_metadata, ASSETS_PARTITION_LABEL,
+};
 use micro_rdk_installer::error::Error;
 use micro_rdk_installer::nvs::data::{ViamFlashStorageData, WifiCredentials};
 use micro_rdk_installer::nvs::metadata::read_nvs_metadata;
 use micro_rdk_installer::nvs::partition::{NVSPartition, NVSPartitionData};
+use micro_rdk_installer::nvs::reader::NVSContents;
 use micro_rdk_installer::nvs::request::{
     download_micro_rdk_release, populate_nvs_storage_from_app,
 };
@@ -36,6 +40,9 @@ enum Commands {
     WriteCredentials(WriteCredentials),
     CreateNvsPartition(CreateNVSPartition),
     Monitor(Monitor),
+    InspectNvs(InspectNVS),
+    CreateAssetsPartition(CreateAssetsPartition),
+    WriteAssets(WriteAssets),
 }
 
 /// Write Wi-Fi and robot credentials to the NVS storage portion of a pre-compiled
@@ -92,6 +99,10 @@ struct WriteFlash {
     /// prompted for it
     #[arg(long = "wifi-password")]
     wifi_password: Option<Secret<String>>,
+    /// Directory whose files are written to the assets partition of the binary
+    /// before flashing
+    #[arg(long = "assets")]
+    assets_dir: Option<String>,
 }
 
 /// Generate a binary of a complete NVS data partition that conatins Wi-Fi and security
@@ -117,6 +128,36 @@ struct CreateNVSPartition {
     wifi_password: Option<Secret<String>>,
 }
 
+/// Generate a binary of an assets partition holding every file of a directory, components
+/// can read them back when micro-RDK is built with the `assets` feature
+#[derive(Args)]
+struct CreateAssetsPartition {
+    /// Directory holding the files to store in the partition
+    #[arg(long = "dir")]
+    dir: String,
+    #[arg(long = "output")]
+    file_name: String,
+    /// Size of the assets partition in bytes, as declared in the partition table
+    #[arg(long = "size", default_value = "1048576")]
+    size: usize,
+}
+
+/// Write every file of a directory to the assets partition of a pre-compiled binary
+/// running a micro-RDK server
+#[derive(Args)]
+struct WriteAssets {
+    /// Directory holding the files to store in the partition
+    #[arg(long = "dir")]
+    dir: String,
+    /// File path to the compiled micro-RDK binary, it must declare the assets partition
+    /// in its partition table
+    #[arg(long = "binary-path")]
+    binary_path: String,
+    /// Label of the partition in the partition table
+    #[arg(long = "label", default_value = ASSETS_PARTITION_LABEL)]
+    label: String,
+}
+
 /// Monitor a currently connected ESP32
 #[derive(Args)]
 struct Monitor {
@@ -128,6 +169,20 @@ struct Monitor {
     log_file_path: Option<String>,
 }
 
+/// Print the robot and Wi-Fi credentials stored in an NVS partition, secrets are redacted
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct InspectNVS {
+    /// File path to a compiled micro-RDK binary, the NVS data partition is located
+    /// using its partition table
+    #[arg(long = "binary-path")]
+    binary_path: Option<String>,
+    /// File path to a raw NVS partition, as produced by create-nvs-partition or read
+    /// back from the flash of an ESP32
+    #[arg(long = "nvs-partition")]
+    nvs_partition: Option<String>,
+}
+
 #[derive(Parser)]
 #[command(
     about = "A CLI that can flash a compilation of micro-RDK directly to an ESP32 provided configuration information",
@@ -222,6 +277,34 @@ fn write_credentials_to_app_binary(
     Ok(())
 }
 
+fn write_assets_to_app_binary(
+    binary_path: PathBuf,
+    assets_dir: &Path,
+    label: &str,
+) -> Result<(), Error> {
+    let metadata = read_assets_metadata(binary_path.clone(), label)?;
+    let image = build_asset_image(assets_dir, metadata.size as usize)?;
+    let mut app_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(binary_path)
+        .map_err(Error::FileError)?;
+    let file_len = app_file.metadata().map_err(Error::FileError)?.len();
+    // the binary usually ends with the app, pad it up to the assets partition
+    if file_len < metadata.start_address {
+        app_file.seek(SeekFrom::End(0)).map_err(Error::FileError)?;
+        app_file
+            .write_all(&vec![0xFF; (metadata.start_address - file_len) as usize])
+            .map_err(Error::FileError)?;
+    }
+    app_file
+        .seek(SeekFrom::Start(metadata.start_address))
+        .map_err(Error::FileError)?;
+    log::info!("Writing assets to binary.");
+    app_file.write_all(&image).map_err(Error::FileError)?;
+    Ok(())
+}
+
 fn flash(
     binary_path: PathBuf,
     should_monitor: bool,
@@ -275,6 +358,30 @@ fn monitor_esp32(baud_rate: Option<u32>, log_file_path: Option<String>) -> Resul
     Ok(())
 }
 
+fn read_nvs_partition(
+    binary_path: Option<String>,
+    nvs_partition: Option<String>,
+) -> Result<Vec<u8>, Error> {
+    if let Some(path) = nvs_partition {
+        return fs::read(path).map_err(Error::FileError);
+    }
+    let app_path = PathBuf::from(binary_path.ok_or(Error::NoCommandError)?);
+    let nvs_metadata = read_nvs_metadata(app_path.clone())?;
+    let mut app_file = File::open(app_path).map_err(Error::FileError)?;
+    let file_len = app_file.metadata().map_err(Error::FileError)?.len();
+    if (nvs_metadata.start_address + nvs_metadata.size) > file_len {
+        return Err(Error::BinaryEditError(file_len));
+    }
+    app_file
+        .seek(SeekFrom::Start(nvs_metadata.start_address))
+        .map_err(Error::FileError)?;
+    let mut nvs_data = vec![0; nvs_metadata.size as usize];
+    app_file
+        .read_exact(&mut nvs_data)
+        .map_err(Error::FileError)?;
+    Ok(nvs_data)
+}
+
 fn init_logger() {
     env_logger::Builder::new()
         .filter_level(LevelFilter::Off)
@@ -333,6 +440,13 @@ fn main() -> Result<(), Error> {
                 nvs_metadata.size,
                 nvs_metadata.start_address,
             )?;
+            if let Some(assets_dir) = &args.assets_dir {
+                write_assets_to_app_binary(
+                    app_path.clone(),
+                    Path::new(assets_dir),
+                    ASSETS_PARTITION_LABEL,
+                )?;
+            }
             flash(
                 app_path,
                 args.monitor,
@@ -351,6 +465,21 @@ fn main() -> Result<(), Error> {
             .map_err(Error::FileError)?;
         }
         Some(Commands::Monitor(args)) => monitor_esp32(args.baud_rate, args.log_file_path.clone())?,
+        Some(Commands::InspectNvs(args)) => {
+            let nvs_data =
+                read_nvs_partition(args.binary_path.clone(), args.nvs_partition.clone())?;
+            print!("{}", NVSContents::from_bytes(&nvs_data)?);
+        }
+        Some(Commands::CreateAssetsPartition(args)) => {
+            let mut file = File::create(&args.file_name).map_err(Error::FileError)?;
+            file.write_all(&build_asset_image(Path::new(&args.dir), args.size)?)
+                .map_err(Error::FileError)?;
+        }
+        Some(Commands::WriteAssets(args)) => write_assets_to_app_binary(
+            PathBuf::from(&args.binary_path),
+            Path::new(&args.dir),
+            &args.label,
+        )?,
         None => return Err(Error::NoCommandError),
     };
     Ok(())
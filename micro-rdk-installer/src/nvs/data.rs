@@ -3,6 +3,11 @@ use secrecy::{ExposeSecret, Secret};
 use super::super::error::Error;
 use super::partition::{NVSEntry, NVSKeyValuePair, NVSValue};
 
+/// Version of the layout written to the NVS partition, the firmware uses it to migrate older
+/// layouts and to reject newer ones. Must match `SCHEMA_VERSION` in micro-rdk's `nvs_schema`
+pub const NVS_SCHEMA_VERSION: u32 = 2;
+pub const NVS_SCHEMA_VERSION_KEY: &str = "SCHEMA_VERSION";
+
 #[derive(Clone, Debug)]
 pub struct WifiCredentials {
     pub ssid: String,
@@ -41,12 +46,17 @@ pub struct ViamFlashStorageData {
 }
 
 impl ViamFlashStorageData {
-    fn to_nvs_key_value_pairs(&self, namespace_idx: u8) -> Result<[NVSKeyValuePair; 14], Error> {
+    fn to_nvs_key_value_pairs(&self, namespace_idx: u8) -> Result<[NVSKeyValuePair; 15], Error> {
         let wifi_cred = self
             .wifi
             .clone()
             .ok_or(Error::NVSDataProcessingError("no wifi".to_string()))?;
         Ok([
+            NVSKeyValuePair {
+                key: NVS_SCHEMA_VERSION_KEY.to_string(),
+                value: NVSValue::String(NVS_SCHEMA_VERSION.to_string()),
+                namespace_idx,
+            },
             NVSKeyValuePair {
                 key: "WIFI_SSID".to_string(),
                 value: NVSValue::String(wifi_cred.ssid),
@@ -14,7 +14,8 @@ pub struct NVSMetadata {
 const PARTITION_TABLE_MAX_ENTRIES: usize = 95;
 const PARTITION_TABLE_ENTRY_MAGIC_BYTES: [u8; 2] = [0xAA, 0x50];
 
-pub fn read_nvs_metadata(binary_path: PathBuf) -> Result<NVSMetadata, Error> {
+/// Reads the entries of the partition table of a compiled binary, without their magic bytes
+pub fn read_partition_table(binary_path: PathBuf) -> Result<Vec<[u8; 30]>, Error> {
     let mut app_file = OpenOptions::new()
         .read(true)
         .open(binary_path)
@@ -22,9 +23,9 @@ pub fn read_nvs_metadata(binary_path: PathBuf) -> Result<NVSMetadata, Error> {
     app_file
         .seek(SeekFrom::Start(0x8000))
         .map_err(Error::FileError)?;
-    let mut entries_read = 0;
+    let mut entries = vec![];
     loop {
-        if entries_read == PARTITION_TABLE_MAX_ENTRIES {
+        if entries.len() == PARTITION_TABLE_MAX_ENTRIES {
             break;
         }
         let mut magic_bytes: [u8; 2] = [0xFF, 0xFF];
@@ -38,10 +39,16 @@ pub fn read_nvs_metadata(binary_path: PathBuf) -> Result<NVSMetadata, Error> {
         app_file
             .read(&mut table_entry[..])
             .map_err(Error::FileError)?;
+        entries.push(table_entry);
+    }
+    Ok(entries)
+}
+
+pub fn read_nvs_metadata(binary_path: PathBuf) -> Result<NVSMetadata, Error> {
+    for table_entry in read_partition_table(binary_path)? {
         if let Some(nvs_metadata) = get_nvs_metadata_from_entry(&table_entry)? {
             return Ok(nvs_metadata);
         }
-        entries_read += 1;
     }
     Err(Error::NVSMissingError)
 }
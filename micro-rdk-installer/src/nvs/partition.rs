@@ -5,12 +5,12 @@ use crc32fast::Hasher;
 
 use super::data::ViamFlashStorageData;
 
-const VIAM_NAMESPACE: &str = "VIAM_NS";
+pub(crate) const VIAM_NAMESPACE: &str = "VIAM_NS";
 const MAX_BLOB_SIZE: usize = 4000;
-const NAMESPACE_FORMAT: u8 = 0x01;
-const BLOB_DATA_FORMAT: u8 = 0x42;
-const STRING_VALUE_FORMAT: u8 = 0x21;
-const BLOB_IDX_FORMAT: u8 = 0x48;
+pub(crate) const NAMESPACE_FORMAT: u8 = 0x01;
+pub(crate) const BLOB_DATA_FORMAT: u8 = 0x42;
+pub(crate) const STRING_VALUE_FORMAT: u8 = 0x21;
+pub(crate) const BLOB_IDX_FORMAT: u8 = 0x48;
 const PAGE_VERSION: u8 = 0xFE; // Version 2
 
 const DEFAULT_BLOB_CHUNK_IDX: u8 = 0xFF;
@@ -27,15 +27,21 @@ const DEFAULT_BLOB_CHUNK_IDX: u8 = 0xFF;
 /// More information on the structure of NVS and its API can be found in Espressif's online documentation
 /// (https://docs.espressif.com/projects/esp-idf/en/release-v4.4/esp32/api-reference/storage/nvs_flash.html)
 
-// computes the checksum of the contents of the header and stores it at index 4
-// as a 32-bit integer (see the link above for more information)
-fn set_header_crc(header: &mut Vec<u8>) {
+// computes the checksum of the contents of an entry header, skipping the 4 bytes
+// at index 4 where the checksum itself is stored
+pub(crate) fn header_crc(header: &[u8]) -> u32 {
     let mut crc_data = std::iter::repeat(0).take(28).collect::<Vec<u8>>();
     crc_data[0..4].clone_from_slice(&header[0..4]);
     crc_data[4..28].clone_from_slice(&header[8..32]);
     let mut hasher = Hasher::new_with_initial(0xFFFFFFFF);
     hasher.update(&crc_data);
-    let checksum = hasher.finalize();
+    hasher.finalize()
+}
+
+// computes the checksum of the contents of the header and stores it at index 4
+// as a 32-bit integer (see the link above for more information)
+fn set_header_crc(header: &mut Vec<u8>) {
+    let checksum = header_crc(header);
     let _ = header.splice(4..8, checksum.to_le_bytes());
 }
 
@@ -111,6 +117,7 @@ impl NVSEntry {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum NVSValue {
     String(String),
     Bytes(Vec<u8>),
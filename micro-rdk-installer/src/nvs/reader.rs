@@ -0,0 +1,284 @@
+//! This module reads back an NVS partition binary, either one produced by `NVSPartitionData`
+//! or one dumped from the flash of an ESP32, so the credentials it holds can be inspected
+//! on the host when debugging provisioning problems. Only the entry formats written by
+//! this crate (namespaces, strings and blobs) are decoded, other entries are skipped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use super::super::error::Error;
+use super::data::NVS_SCHEMA_VERSION_KEY;
+use super::partition::{
+    header_crc, NVSValue, BLOB_DATA_FORMAT, BLOB_IDX_FORMAT, NAMESPACE_FORMAT, STRING_VALUE_FORMAT,
+    VIAM_NAMESPACE,
+};
+
+const PAGE_SIZE: usize = 4096;
+const ENTRY_SIZE: usize = 32;
+const ENTRIES_PER_PAGE: usize = 126;
+// the page header is 32 bytes, followed by 32 bytes of entry state bitmap
+const BITMAP_OFFSET: usize = 32;
+const FIRST_ENTRY_OFFSET: usize = 64;
+const PAGE_STATE_UNINITIALIZED: u32 = 0xFFFFFFFF;
+const ENTRY_STATE_WRITTEN: u8 = 0b10;
+
+/// Keys whose values must never be printed
+const SECRET_KEYS: [&str; 4] = [
+    "WIFI_PASSWORD",
+    "ROBOT_SECRET",
+    "SRV_DER_KEY",
+    "DTLS_KEY_PAIR",
+];
+const REDACTED: &str = "[REDACTED]";
+
+type EntryId = (u8, String);
+
+/// Key-value pairs read from an NVS partition, grouped by namespace
+#[derive(Debug, Default)]
+pub struct NVSContents {
+    namespaces: BTreeMap<String, BTreeMap<String, NVSValue>>,
+}
+
+// each entry uses 2 bits of the bitmap following the page header
+fn entry_state(page: &[u8], entry_idx: usize) -> u8 {
+    let bitnum = entry_idx * 2;
+    (page[BITMAP_OFFSET + bitnum / 8] >> (bitnum & 7)) & 0b11
+}
+
+fn key_from_header(header: &[u8]) -> String {
+    let key = &header[8..24];
+    let end = key.iter().position(|b| *b == 0).unwrap_or(key.len());
+    String::from_utf8_lossy(&key[..end]).to_string()
+}
+
+// returns the data following an entry header, the length being stored at index 24
+fn entry_data<'a>(page: &'a [u8], offset: usize, span: usize) -> Result<&'a [u8], Error> {
+    let header = &page[offset..offset + ENTRY_SIZE];
+    let len = u16::from_le_bytes([header[24], header[25]]) as usize;
+    let start = offset + ENTRY_SIZE;
+    if len > (span - 1) * ENTRY_SIZE || start + len > page.len() {
+        return Err(Error::NVSDataProcessingError(format!(
+            "data of entry {:?} overflows its page",
+            key_from_header(header)
+        )));
+    }
+    Ok(&page[start..start + len])
+}
+
+impl NVSContents {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.is_empty() || bytes.len() % PAGE_SIZE != 0 {
+            return Err(Error::NVSDataProcessingError(format!(
+                "NVS partition size {:?} is not a multiple of the page size",
+                bytes.len()
+            )));
+        }
+        let mut namespace_names: HashMap<u8, String> = HashMap::new();
+        let mut values: Vec<(EntryId, NVSValue)> = vec![];
+        let mut blob_chunks: HashMap<EntryId, Vec<(u8, Vec<u8>)>> = HashMap::new();
+        let mut blob_indices: Vec<(EntryId, u32)> = vec![];
+
+        for page in bytes.chunks(PAGE_SIZE) {
+            let state = u32::from_le_bytes([page[0], page[1], page[2], page[3]]);
+            if state == PAGE_STATE_UNINITIALIZED {
+                continue;
+            }
+            let mut entry_idx = 0;
+            while entry_idx < ENTRIES_PER_PAGE {
+                if entry_state(page, entry_idx) != ENTRY_STATE_WRITTEN {
+                    entry_idx += 1;
+                    continue;
+                }
+                let offset = FIRST_ENTRY_OFFSET + entry_idx * ENTRY_SIZE;
+                let header = &page[offset..offset + ENTRY_SIZE];
+                let span = header[2].max(1) as usize;
+                entry_idx += span;
+                let key = key_from_header(header);
+                if header_crc(header).to_le_bytes() != header[4..8] {
+                    log::warn!("skipping NVS entry {:?} with a corrupted header", key);
+                    continue;
+                }
+                let id = (header[0], key);
+                match header[1] {
+                    NAMESPACE_FORMAT if header[0] == 0 => {
+                        namespace_names.insert(header[24], id.1);
+                    }
+                    STRING_VALUE_FORMAT => {
+                        let data = entry_data(page, offset, span)?;
+                        // strings are stored with their NUL terminator
+                        let data = data.strip_suffix(&[0]).unwrap_or(data);
+                        let value = String::from_utf8(data.to_vec()).map_err(|_| {
+                            Error::NVSDataProcessingError(format!(
+                                "value of {:?} is not valid UTF-8",
+                                id.1
+                            ))
+                        })?;
+                        values.push((id, NVSValue::String(value)));
+                    }
+                    BLOB_DATA_FORMAT => {
+                        let data = entry_data(page, offset, span)?.to_vec();
+                        blob_chunks.entry(id).or_default().push((header[3], data));
+                    }
+                    BLOB_IDX_FORMAT => {
+                        let size =
+                            u32::from_le_bytes([header[24], header[25], header[26], header[27]]);
+                        blob_indices.push((id, size));
+                    }
+                    format => {
+                        log::warn!(
+                            "skipping NVS entry {:?} of unsupported format {:#04x}",
+                            id.1,
+                            format
+                        );
+                    }
+                }
+            }
+        }
+
+        // a blob is only complete once its index entry has been written
+        for (id, size) in blob_indices {
+            let mut chunks = blob_chunks.remove(&id).unwrap_or_default();
+            chunks.sort_by_key(|(chunk_idx, _)| *chunk_idx);
+            let blob = chunks
+                .into_iter()
+                .flat_map(|(_, data)| data)
+                .collect::<Vec<u8>>();
+            if blob.len() != size as usize {
+                log::warn!(
+                    "skipping NVS blob {:?}, expected {:?} bytes but found {:?}",
+                    id.1,
+                    size,
+                    blob.len()
+                );
+                continue;
+            }
+            values.push((id, NVSValue::Bytes(blob)));
+        }
+
+        let mut contents = Self::default();
+        for ((namespace_idx, key), value) in values {
+            let namespace = namespace_names
+                .get(&namespace_idx)
+                .cloned()
+                .unwrap_or_else(|| format!("#{}", namespace_idx));
+            contents
+                .namespaces
+                .entry(namespace)
+                .or_default()
+                .insert(key, value);
+        }
+        Ok(contents)
+    }
+
+    pub fn get(&self, namespace: &str, key: &str) -> Option<&NVSValue> {
+        self.namespaces.get(namespace)?.get(key)
+    }
+
+    pub fn get_string(&self, namespace: &str, key: &str) -> Option<&str> {
+        match self.get(namespace, key)? {
+            NVSValue::String(value) => Some(value),
+            NVSValue::Bytes(_) => None,
+        }
+    }
+
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        self.namespaces.keys().map(|ns| ns.as_str())
+    }
+
+    pub fn keys<'a>(&'a self, namespace: &str) -> impl Iterator<Item = &'a str> {
+        self.namespaces
+            .get(namespace)
+            .into_iter()
+            .flat_map(|values| values.keys().map(|key| key.as_str()))
+    }
+}
+
+/// Summary of the Viam credentials stored in the partition, secrets are redacted
+impl fmt::Display for NVSContents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let viam_str = |key: &str| self.get_string(VIAM_NAMESPACE, key).unwrap_or("<not set>");
+        let schema_version = self
+            .get_string(VIAM_NAMESPACE, NVS_SCHEMA_VERSION_KEY)
+            .unwrap_or("1 (unversioned)");
+        writeln!(f, "schema version: {}", schema_version)?;
+        writeln!(f, "robot id: {}", viam_str("ROBOT_ID"))?;
+        writeln!(f, "robot name: {}", viam_str("ROBOT_NAME"))?;
+        writeln!(f, "app address: {}", viam_str("APP_ADDRESS"))?;
+        writeln!(f, "wifi ssid: {}", viam_str("WIFI_SSID"))?;
+        for namespace in self.namespaces() {
+            writeln!(f, "namespace {}:", namespace)?;
+            for key in self.keys(namespace) {
+                let value = match self.get(namespace, key) {
+                    Some(_) if SECRET_KEYS.contains(&key) => REDACTED.to_string(),
+                    Some(NVSValue::String(value)) => format!("{:?}", value),
+                    Some(NVSValue::Bytes(value)) => format!("<{} bytes>", value.len()),
+                    None => continue,
+                };
+                writeln!(f, "  {}: {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::NVSContents;
+    use crate::nvs::data::{ViamFlashStorageData, WifiCredentials};
+    use crate::nvs::partition::{NVSPartition, NVSPartitionData, NVSValue, VIAM_NAMESPACE};
+
+    #[test]
+    fn test_read_written_partition() {
+        let mut storage_data = ViamFlashStorageData {
+            wifi: Some(WifiCredentials {
+                ssid: "my-network".to_string(),
+                password: Secret::new("wifi-password".to_string()),
+            }),
+            ..Default::default()
+        };
+        let creds = &mut storage_data.robot_credentials;
+        creds.robot_id = Some("robot-id".to_string());
+        creds.robot_secret = Some(Secret::new("robot-secret".to_string()));
+        creds.robot_name = Some("robot-name".to_string());
+        creds.app_address = Some("https://app.viam.com:443".to_string());
+        creds.local_fqdn = Some("robot.local.viam.cloud".to_string());
+        creds.fqdn = Some("robot.viam.cloud".to_string());
+        creds.robot_dtls_certificate_fp = Some("AB:CD".to_string());
+        creds.ca_crt = Some(vec![1; 1500]);
+        creds.der_key = Some(vec![2; 1200]);
+        creds.pem_chain = Some(vec![3; 3000]);
+        creds.robot_dtls_certificate = Some(vec![4; 600]);
+        creds.robot_dtls_key_pair = Some(vec![5; 200]);
+
+        let part = &mut NVSPartition::from_storage_data(storage_data, 32768).unwrap();
+        let bytes = NVSPartitionData::try_from(part).unwrap().to_bytes();
+        let contents = NVSContents::from_bytes(&bytes).unwrap();
+
+        assert_eq!(
+            contents.get_string(VIAM_NAMESPACE, "ROBOT_ID"),
+            Some("robot-id")
+        );
+        assert_eq!(
+            contents.get_string(VIAM_NAMESPACE, "WIFI_SSID"),
+            Some("my-network")
+        );
+        assert_eq!(
+            contents.get_string(VIAM_NAMESPACE, "SCHEMA_VERSION"),
+            Some("2")
+        );
+        assert_eq!(
+            contents.get(VIAM_NAMESPACE, "SRV_PEM_CHAIN"),
+            Some(&NVSValue::Bytes(vec![3; 3000]))
+        );
+        assert_eq!(contents.keys(VIAM_NAMESPACE).count(), 15);
+
+        let summary = contents.to_string();
+        assert!(summary.contains("wifi ssid: my-network"));
+        assert!(!summary.contains("robot-secret"));
+        assert!(!summary.contains("wifi-password"));
+
+        assert!(NVSContents::from_bytes(&bytes[..100]).is_err());
+    }
+}
@@ -14,6 +14,32 @@
 //! of the PowerSensor trait. `get_generic_readings` will return a struct containing the voltage (in volts),
 //! current (in amperes), power (in watts), and whether or not the power supply is AC.
 //!
+//! SensorReadings - provides a default implementation of the Readings trait for implementers
+//! of the `SensorT<f64>` trait. `get_generic_readings` will return the typed readings as numbers.
+//!
+//! Status - implements the Status trait from the fields of a struct annotated with `#[status]`,
+//! reported under the name of the field or the one given by `#[status(rename = "...")]`. The
+//! fields must implement `StatusValue`. A `ComponentHealth` field annotated with
+//! `#[status(health)]` adds the standard `ok`, `last_error` and `last_update` envelope.
+//!
+//! # Example using `Status`
+//!
+//! ```
+//! use micro_rdk::common::status::ComponentHealth;
+//! use micro_rdk::Status;
+//!
+//! #[derive(Status)]
+//! pub struct MyMotor {
+//!     #[status(rename = "position")]
+//!     pos: f64,
+//!     #[status]
+//!     position_reporting: bool,
+//!     #[status(health)]
+//!     health: ComponentHealth,
+//!     max_rpm: f64,
+//! }
+//! ```
+//!
 //! # Example using `MovementSensorReadings`
 //!
 //! ```
@@ -53,6 +79,7 @@
 //!             angular_velocity_supported: false,
 //!             linear_acceleration_supported: false,
 //!             compass_heading_supported: false,
+//!             orientation_supported: false,
 //!         }
 //!     }
 //! }
@@ -127,3 +154,101 @@ pub fn impl_readings_for_power_sensor(input: TokenStream) -> TokenStream {
 
     gen.into()
 }
+
+#[proc_macro_derive(SensorReadings)]
+pub fn impl_readings_for_sensor(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let crate_ident = get_micro_rdk_crate_ident();
+    let gen = quote! {
+        impl #impl_generics #crate_ident::common::sensor::Readings for #name #ty_generics #where_clause {
+            fn get_generic_readings(&mut self) -> Result<#crate_ident::common::sensor::GenericReadingsResult,#crate_ident::common::sensor::SensorError> {
+                #crate_ident::common::sensor::get_sensor_generic_readings(self)
+            }
+        }
+    };
+
+    gen.into()
+}
+
+#[proc_macro_derive(Status, attributes(status))]
+pub fn impl_status(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let fields = match &ast.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => panic!("Status can only be derived for structs"),
+    };
+    let mut keys = vec![];
+    let mut idents = vec![];
+    let mut health = None;
+    for field in fields.iter() {
+        for attr in field.attrs.iter().filter(|a| a.path.is_ident("status")) {
+            let ident = field
+                .ident
+                .as_ref()
+                .expect("#[status] is only supported on named fields");
+            match attr.parse_meta() {
+                Ok(syn::Meta::Path(_)) => {
+                    keys.push(ident.to_string());
+                    idents.push(ident.clone());
+                }
+                Ok(syn::Meta::List(list)) => {
+                    for nested in list.nested.iter() {
+                        match nested {
+                            syn::NestedMeta::Meta(syn::Meta::Path(path))
+                                if path.is_ident("health") =>
+                            {
+                                health = Some(ident.clone());
+                            }
+                            syn::NestedMeta::Meta(syn::Meta::NameValue(nv))
+                                if nv.path.is_ident("rename") =>
+                            {
+                                match &nv.lit {
+                                    syn::Lit::Str(key) => {
+                                        keys.push(key.value());
+                                        idents.push(ident.clone());
+                                    }
+                                    _ => panic!("#[status(rename = ...)] expects a string"),
+                                }
+                            }
+                            _ => panic!("unsupported #[status] attribute"),
+                        }
+                    }
+                }
+                _ => panic!("unsupported #[status] attribute"),
+            }
+        }
+    }
+
+    let crate_ident = get_micro_rdk_crate_ident();
+    let fields = quote! {
+        {
+            let fields: ::std::vec::Vec<(&str, #crate_ident::google::protobuf::value::Kind)> = ::std::vec![
+                #((#keys, #crate_ident::common::status::StatusValue::to_status_kind(&self.#idents))),*
+            ];
+            fields
+        }
+    };
+    let status = match health {
+        Some(health) => quote! {
+            #crate_ident::common::status::status_envelope(&self.#health, #fields)
+        },
+        None => quote! {
+            #crate_ident::common::status::status_from_fields(#fields)
+        },
+    };
+    let gen = quote! {
+        impl #impl_generics #crate_ident::common::status::Status for #name #ty_generics #where_clause {
+            fn get_status(&self) -> Result<Option<#crate_ident::google::protobuf::Struct>, #crate_ident::common::status::StatusError> {
+                Ok(Some(#status))
+            }
+        }
+    };
+
+    gen.into()
+}
@@ -3,10 +3,12 @@ use micro_rdk::common::movement_sensor::{
     GeoPosition, MovementSensor, MovementSensorSupportedMethods,
 };
 use micro_rdk::common::power_sensor::{Current, PowerSensor, PowerSupplyType, Voltage};
-use micro_rdk::common::sensor::{Readings, SensorError};
-use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::common::sensor::{Readings, Sensor, SensorError, SensorT, TypedReadingsResult};
+use micro_rdk::common::status::{ComponentHealth, Status, StatusError};
 use micro_rdk::google::protobuf::value::Kind;
-use micro_rdk_macros::{DoCommand, MovementSensorReadings, PowerSensorReadings};
+use micro_rdk_macros::{
+    DoCommand, MovementSensorReadings, PowerSensorReadings, SensorReadings, Status,
+};
 use std::collections::HashMap;
 
 #[derive(DoCommand)]
@@ -39,6 +41,7 @@ impl MovementSensor for TestMovementSensor {
             linear_velocity_supported: false,
             angular_velocity_supported: false,
             compass_heading_supported: true,
+            orientation_supported: false,
         }
     }
 
@@ -174,3 +177,60 @@ fn power_sensor_readings_derive() {
         assert!(is_ac)
     }
 }
+
+#[derive(DoCommand, SensorReadings, Status)]
+struct TestSensor {
+    #[status(rename = "reading")]
+    value: f64,
+    #[status]
+    name: String,
+    #[status]
+    calibrated: Option<bool>,
+    #[status(health)]
+    health: ComponentHealth,
+}
+
+impl Sensor for TestSensor {}
+
+impl SensorT<f64> for TestSensor {
+    fn get_readings(&self) -> Result<TypedReadingsResult<f64>, SensorError> {
+        Ok(HashMap::from([("value".to_string(), self.value)]))
+    }
+}
+
+#[test]
+fn sensor_readings_derive() {
+    let mut a = TestSensor {
+        value: 4.5,
+        name: "test".to_string(),
+        calibrated: None,
+        health: ComponentHealth::new(),
+    };
+    let res = a.get_generic_readings().unwrap();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res["value"].kind, Some(Kind::NumberValue(4.5)));
+}
+
+#[test]
+fn status_derive() {
+    let mut a = TestSensor {
+        value: 4.5,
+        name: "test".to_string(),
+        calibrated: None,
+        health: ComponentHealth::new(),
+    };
+    a.health.record::<(), _>(&Err("disconnected"));
+    let status = a.get_status().unwrap().unwrap();
+    assert_eq!(status.fields["reading"].kind, Some(Kind::NumberValue(4.5)));
+    assert_eq!(
+        status.fields["name"].kind,
+        Some(Kind::StringValue("test".to_string()))
+    );
+    assert_eq!(status.fields["calibrated"].kind, Some(Kind::NullValue(0)));
+    assert_eq!(status.fields["ok"].kind, Some(Kind::BoolValue(false)));
+    assert_eq!(
+        status.fields["last_error"].kind,
+        Some(Kind::StringValue("disconnected".to_string()))
+    );
+    assert!(!status.fields.contains_key("value"));
+}
@@ -1,4 +1,31 @@
+fn git(args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new("git").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // reported by `GetVersion`, builds outside of a git checkout simply don't have a revision
+    if let Some(rev) = git(&["rev-parse", "--short", "HEAD"]) {
+        println!("cargo:rustc-env=MICRO_RDK_GIT_REVISION={}", rev);
+        // HEAD changes on checkouts, the branch it points to on commits
+        let mut watched = vec![git(&["rev-parse", "--git-path", "HEAD"])];
+        if let Some(branch) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            watched.push(git(&["rev-parse", "--git-path", &branch]));
+            watched.push(git(&["rev-parse", "--git-path", "packed-refs"]));
+        }
+        for path in watched.into_iter().flatten() {
+            // cargo reruns the script on every build for a path that doesn't exist
+            if std::path::Path::new(&path).exists() {
+                println!("cargo:rerun-if-changed={}", path);
+            }
+        }
+    }
+
     if std::env::var("TARGET").unwrap() == "xtensa-esp32-espidf" {
         let cfg_args = embuild::build::CfgArgs::try_from_env("ESP_IDF_SVC").unwrap();
         cfg_args.output();
@@ -2,6 +2,8 @@ use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
 use super::board::BoardError;
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
+use crate::google::protobuf::Struct;
 
 #[derive(Debug, Error)]
 pub enum ActuatorError {
@@ -11,9 +13,23 @@ pub enum ActuatorError {
     BoardError(#[from] BoardError),
 }
 
+impl GrpcStatusHint for ActuatorError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::CouldntStop => GrpcError::RpcAborted,
+            Self::BoardError(err) => err.grpc_error(),
+        }
+    }
+}
+
 pub trait Actuator {
     fn is_moving(&mut self) -> Result<bool, ActuatorError>;
     fn stop(&mut self) -> Result<(), ActuatorError>;
+    /// Stops the actuator with implementation specific options passed in `extra` (such as
+    /// how a motor should be stopped), ignored by default
+    fn stop_with_extra(&mut self, _extra: Option<Struct>) -> Result<(), ActuatorError> {
+        self.stop()
+    }
 }
 
 impl<L> Actuator for Mutex<L>
@@ -26,6 +42,9 @@ where
     fn stop(&mut self) -> Result<(), ActuatorError> {
         self.get_mut().unwrap().stop()
     }
+    fn stop_with_extra(&mut self, extra: Option<Struct>) -> Result<(), ActuatorError> {
+        self.get_mut().unwrap().stop_with_extra(extra)
+    }
 }
 
 impl<A> Actuator for Arc<Mutex<A>>
@@ -38,4 +57,7 @@ where
     fn stop(&mut self) -> Result<(), ActuatorError> {
         self.lock().unwrap().stop()
     }
+    fn stop_with_extra(&mut self, extra: Option<Struct>) -> Result<(), ActuatorError> {
+        self.lock().unwrap().stop_with_extra(extra)
+    }
 }
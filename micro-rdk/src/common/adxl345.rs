@@ -2,19 +2,14 @@
 use crate::common::i2c::I2cHandleType;
 use crate::common::math_utils::Vector3;
 use crate::common::movement_sensor::{MovementSensor, MovementSensorSupportedMethods};
-use crate::common::status::StatusError;
-use crate::google;
 
 use super::board::Board;
 use super::config::ConfigType;
-use super::i2c::I2CHandle;
+use super::i2c::{BitField, Endian, RegisterMap};
 use super::movement_sensor::MovementSensorType;
 use super::registry::{get_board_from_dependencies, ComponentRegistry, Dependency};
 use super::sensor::SensorError;
-use super::status::Status;
 
-use std::collections::HashMap;
-use std::mem::size_of;
 use std::sync::{Arc, Mutex};
 
 // This module represents an implementation of the MPU-6050 gyroscope/accelerometer
@@ -31,21 +26,18 @@ pub(crate) fn register_models(registry: &mut ComponentRegistry) {
 
 const READING_START_REGISTER: u8 = 50;
 const STANDBY_MODE_REGISTER: u8 = 45;
+const MEASURE_MODE: BitField = BitField::bit(3);
 
-#[derive(DoCommand, MovementSensorReadings)]
+#[derive(DoCommand, MovementSensorReadings, Status)]
 pub struct ADXL345 {
-    i2c_handle: I2cHandleType,
-    i2c_address: u8,
+    registers: RegisterMap<I2cHandleType>,
 }
 
 impl ADXL345 {
-    pub fn new(mut i2c_handle: I2cHandleType, i2c_address: u8) -> Result<Self, SensorError> {
-        let bytes: [u8; 2] = [STANDBY_MODE_REGISTER, 8];
-        i2c_handle.write_i2c(i2c_address, &bytes)?;
-        Ok(Self {
-            i2c_handle,
-            i2c_address,
-        })
+    pub fn new(i2c_handle: I2cHandleType, i2c_address: u8) -> Result<Self, SensorError> {
+        let mut registers = RegisterMap::new(i2c_handle, i2c_address, Endian::Little);
+        registers.write_field(STANDBY_MODE_REGISTER, MEASURE_MODE, 1)?;
+        Ok(Self { registers })
     }
 
     #[allow(dead_code)]
@@ -75,9 +67,9 @@ impl ADXL345 {
     }
 
     pub fn close(&mut self) -> Result<(), SensorError> {
-        // put the MPU in the sleep state
-        let off_data: [u8; 2] = [STANDBY_MODE_REGISTER, 0];
-        self.i2c_handle.write_i2c(self.i2c_address, &off_data)?;
+        // put the ADXL in standby
+        self.registers
+            .write_field(STANDBY_MODE_REGISTER, MEASURE_MODE, 0)?;
         Ok(())
     }
 }
@@ -90,12 +82,8 @@ impl Drop for ADXL345 {
     }
 }
 
-fn get_linear_acceleration_from_reading(reading: &[u8; 6]) -> Vector3 {
-    let (x_bytes, y_z_bytes) = reading.split_at(size_of::<i16>());
-    let unscaled_x = i16::from_le_bytes(x_bytes.try_into().unwrap());
-    let (y_bytes, z_bytes) = y_z_bytes.split_at(size_of::<i16>());
-    let unscaled_y = i16::from_le_bytes(y_bytes.try_into().unwrap());
-    let unscaled_z = i16::from_le_bytes(z_bytes.try_into().unwrap());
+fn get_linear_acceleration_from_reading(reading: [i16; 3]) -> Vector3 {
+    let [unscaled_x, unscaled_y, unscaled_z] = reading;
 
     let max_acceleration: f64 = 2.0 * 9.81 * 1000.0;
     let max_i6: f64 = 512.0;
@@ -114,15 +102,13 @@ impl MovementSensor for ADXL345 {
             angular_velocity_supported: false,
             linear_acceleration_supported: true,
             compass_heading_supported: false,
+            orientation_supported: false,
         }
     }
 
     fn get_linear_acceleration(&mut self) -> Result<Vector3, SensorError> {
-        let register_write: [u8; 1] = [READING_START_REGISTER];
-        let mut result: [u8; 6] = [0; 6];
-        self.i2c_handle
-            .write_read_i2c(self.i2c_address, &register_write, &mut result)?;
-        Ok(get_linear_acceleration_from_reading(&result))
+        let reading = self.registers.read_i16s(READING_START_REGISTER)?;
+        Ok(get_linear_acceleration_from_reading(reading))
     }
 
     fn get_angular_velocity(&mut self) -> Result<Vector3, SensorError> {
@@ -148,22 +134,15 @@ impl MovementSensor for ADXL345 {
     }
 }
 
-impl Status for ADXL345 {
-    fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        Ok(Some(google::protobuf::Struct {
-            fields: HashMap::new(),
-        }))
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::get_linear_acceleration_from_reading;
+    use crate::common::i2c::Endian;
 
     #[test_log::test]
     fn test_read_linear_acceleration() {
         let reading: [u8; 6] = [12, 0, 208, 255, 239, 0];
-        let lin_acc = get_linear_acceleration_from_reading(&reading);
+        let lin_acc = get_linear_acceleration_from_reading(Endian::Little.i16s_from(&reading));
         assert_eq!(lin_acc.x, 459.84375);
         assert_eq!(lin_acc.y, -1839.375);
         assert_eq!(lin_acc.z, 9158.5546875);
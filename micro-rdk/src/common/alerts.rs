@@ -0,0 +1,518 @@
+//! Package alerts implements a sensor evaluating threshold rules over the readings of other
+//! sensors, raising alerts on the device without waiting for the readings to reach the cloud.
+//!
+//! ```json
+//! {
+//!     "rules": [
+//!         { "name": "overheat", "sensor": "motor_temp", "reading": "temperature_celsius", "above": 70, "hysteresis": 5, "severity": "critical" },
+//!         { "name": "low_battery", "sensor": "battery", "reading": "state_of_charge_percent", "below": 15 },
+//!         { "name": "humidity", "sensor": "env", "reading": "humidity", "below": 30, "above": 60, "hysteresis": 2 }
+//!     ],
+//!     "webhook_url": "http://192.168.1.10:8123/api/webhook/alerts",
+//!     "check_interval_secs": 1
+//! }
+//! ```
+//!
+//! A rule is raised when the reading goes `above` or `below` its thresholds and cleared once
+//! the reading is back within the thresholds by at least `hysteresis` (0 by default). Raising
+//! a rule is logged at its `severity` (`info`, `warning` by default, or `critical`), clearing
+//! it is logged as info.
+//!
+//! The rules are evaluated every time readings are requested or events are taken, so configuring
+//! data capture on the sensor records the alerts. The readings contain the `active` list of
+//! raised rules, the `raised` and `cleared` lists of the rules that changed since the previous
+//! readings and the `alerts` count of rules raised since startup. The readings and the taken
+//! events are kept apart, each of them sees every change.
+//!
+//! The [EventWatcher](super::webhook::EventWatcher), started with the robot whenever an alerts
+//! sensor is configured, evaluates every alerts sensor each `check_interval_secs` seconds (1 by
+//! default) and posts its events as JSON to its `webhook_url` when it is set:
+//!
+//! ```json
+//! {"alerts": "alarms", "rule": "overheat", "event": "raised", "severity": "critical", "value": 72.5}
+//! ```
+//!
+//! Events are kept until the watcher takes them with the `{"take_events": {}}` command, at most
+//! 16 events are kept.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use super::config::{AttributeError, ConfigType, Kind};
+use super::generic::{DoCommand, GenericError};
+use super::registry::{ComponentRegistry, Dependency, ResourceKey};
+use super::robot::{LocalRobot, Resource};
+use super::sensor::{
+    GenericReadingsResult, Readings, Sensor, SensorError, SensorType,
+    COMPONENT_NAME as SensorCompName,
+};
+use super::status::{Status, StatusError};
+use super::struct_builder::StructBuilder;
+use super::webhook::{EventQueue, EventWatcher};
+use crate::google::protobuf::{value::Kind as ProtoKind, ListValue, Struct, Value};
+use crate::proto::app::v1::ConfigResponse;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("alerts", &AlertSensor::from_config)
+        .is_err()
+    {
+        log::error!("alerts type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            SensorCompName,
+            "alerts",
+            &AlertSensor::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("failed to register dependency getter for alerts model")
+    }
+}
+
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl TryFrom<&Kind> for Severity {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        match value {
+            Kind::StringValue(s) if s == "info" => Ok(Self::Info),
+            Kind::StringValue(s) if s == "warning" => Ok(Self::Warning),
+            Kind::StringValue(s) if s == "critical" => Ok(Self::Critical),
+            _ => Err(AttributeError::ConversionImpossibleError),
+        }
+    }
+}
+
+/// An element of the `rules` attribute of an alerts sensor
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    pub name: String,
+    pub sensor: String,
+    pub reading: String,
+    pub above: Option<f64>,
+    pub below: Option<f64>,
+    pub hysteresis: f64,
+    pub severity: Severity,
+}
+
+impl AlertRule {
+    // whether the rule is raised after reading `value`, given whether it was raised before
+    fn next_state(&self, raised: bool, value: f64) -> bool {
+        // a raised rule only clears once the reading is back by the hysteresis
+        let margin = if raised { self.hysteresis } else { 0.0 };
+        self.above.is_some_and(|above| value > above - margin)
+            || self.below.is_some_and(|below| value < below + margin)
+    }
+}
+
+impl TryFrom<&Kind> for AlertRule {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let string = |key: &str| -> Result<String, AttributeError> {
+            value
+                .get(key)?
+                .ok_or(AttributeError::KeyNotFound(key.to_string()))?
+                .try_into()
+        };
+        let number = |key: &str| -> Result<Option<f64>, AttributeError> {
+            value.get(key)?.map(|v| v.try_into()).transpose()
+        };
+        let rule = AlertRule {
+            name: string("name")?,
+            sensor: string("sensor")?,
+            reading: string("reading")?,
+            above: number("above")?,
+            below: number("below")?,
+            hysteresis: number("hysteresis")?.unwrap_or(0.0),
+            severity: value
+                .get("severity")?
+                .map(|v| v.try_into())
+                .transpose()?
+                .unwrap_or(Severity::Warning),
+        };
+        if rule.above.is_none() && rule.below.is_none() {
+            return Err(AttributeError::KeyNotFound("above".to_string()));
+        }
+        if rule.hysteresis < 0.0 {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        Ok(rule)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertEventType {
+    Raised,
+    Cleared,
+}
+
+impl AlertEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Raised => "raised",
+            Self::Cleared => "cleared",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub event: AlertEventType,
+    pub severity: Severity,
+    pub value: f64,
+}
+
+impl From<&AlertEvent> for Value {
+    fn from(value: &AlertEvent) -> Self {
+        StructBuilder::with_capacity(4)
+            .field("rule", value.rule.as_str())
+            .field("event", value.event.as_str())
+            .field("severity", value.severity.as_str())
+            .field("value", value.value)
+            .into()
+    }
+}
+
+fn string_list<'a>(names: impl Iterator<Item = &'a str>) -> Value {
+    Value {
+        kind: Some(ProtoKind::ListValue(ListValue {
+            values: names
+                .map(|name| Value {
+                    kind: Some(ProtoKind::StringValue(name.to_string())),
+                })
+                .collect(),
+        })),
+    }
+}
+
+pub struct AlertSensor {
+    sensors: Vec<(String, SensorType)>,
+    rules: Vec<AlertRule>,
+    raised: Vec<bool>,
+    // events waiting to be taken with the command, and reported by the readings
+    pending: EventQueue<AlertEvent>,
+    changed: EventQueue<AlertEvent>,
+    alerts: u32,
+}
+
+impl AlertSensor {
+    /// Builds the sensor, `sensors` must contain every sensor named by the rules
+    pub fn new(
+        sensors: Vec<(String, SensorType)>,
+        rules: Vec<AlertRule>,
+    ) -> Result<Self, SensorError> {
+        if rules.is_empty() {
+            return Err(SensorError::ConfigError("alerts needs at least one rule"));
+        }
+        if rules
+            .iter()
+            .any(|rule| !sensors.iter().any(|(name, _)| *name == rule.sensor))
+        {
+            return Err(SensorError::ConfigError("alerts sensor not found"));
+        }
+        Ok(Self {
+            sensors,
+            raised: vec![false; rules.len()],
+            rules,
+            pending: EventQueue::default(),
+            changed: EventQueue::default(),
+            alerts: 0,
+        })
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let rules = cfg
+            .get_attribute::<Vec<AlertRule>>("rules")
+            .map_err(|_| SensorError::ConfigError("alerts missing or invalid rules"))?;
+        let sensors = deps
+            .into_iter()
+            .filter_map(|Dependency(key, res)| match res {
+                Resource::Sensor(sensor) => Some((key.1, sensor)),
+                _ => None,
+            })
+            .collect();
+        Ok(Arc::new(Mutex::new(Self::new(sensors, rules)?)))
+    }
+
+    pub(crate) fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        let mut r_keys: Vec<ResourceKey> = Vec::new();
+        if let Ok(rules) = cfg.get_attribute::<Vec<AlertRule>>("rules") {
+            for rule in rules {
+                if !r_keys.iter().any(|key| key.1 == rule.sensor) {
+                    r_keys.push(ResourceKey(SensorCompName, rule.sensor));
+                }
+            }
+        }
+        r_keys
+    }
+
+    // updates the state of the rules from the readings of their sensors and returns the rules
+    // raised or cleared, rules whose reading is missing keep their state
+    fn evaluate(&mut self, readings: &HashMap<String, GenericReadingsResult>) -> Vec<AlertEvent> {
+        let mut events = vec![];
+        for (rule, raised) in self.rules.iter().zip(self.raised.iter_mut()) {
+            let value = match readings
+                .get(rule.sensor.as_str())
+                .and_then(|readings| readings.get(&rule.reading))
+                .and_then(|value| value.kind.as_ref())
+            {
+                Some(ProtoKind::NumberValue(value)) => *value,
+                Some(ProtoKind::BoolValue(value)) => *value as u8 as f64,
+                _ => continue,
+            };
+            let next = rule.next_state(*raised, value);
+            if next == *raised {
+                continue;
+            }
+            *raised = next;
+            let event = if next {
+                match rule.severity {
+                    Severity::Info => log::info!("alert {} raised: {}", rule.name, value),
+                    Severity::Warning => log::warn!("alert {} raised: {}", rule.name, value),
+                    Severity::Critical => log::error!("alert {} raised: {}", rule.name, value),
+                }
+                AlertEventType::Raised
+            } else {
+                log::info!("alert {} cleared: {}", rule.name, value);
+                AlertEventType::Cleared
+            };
+            events.push(AlertEvent {
+                rule: rule.name.clone(),
+                event,
+                severity: rule.severity,
+                value,
+            });
+        }
+        self.alerts += events
+            .iter()
+            .filter(|event| event.event == AlertEventType::Raised)
+            .count() as u32;
+        for event in &events {
+            self.pending.push(event.clone());
+            self.changed.push(event.clone());
+        }
+        events
+    }
+
+    // evaluates the rules over the current readings of their sensors
+    fn update(&mut self) {
+        let mut readings = HashMap::new();
+        for (name, sensor) in self.sensors.iter() {
+            match sensor.lock().unwrap().get_generic_readings() {
+                Ok(r) => {
+                    readings.insert(name.clone(), r);
+                }
+                Err(err) => log::debug!("alerts: couldn't read {}: {}", name, err),
+            }
+        }
+        self.evaluate(&readings);
+    }
+
+    fn active_names(&self) -> impl Iterator<Item = &str> {
+        self.rules
+            .iter()
+            .zip(self.raised.iter())
+            .filter(|(_, raised)| **raised)
+            .map(|(rule, _)| rule.name.as_str())
+    }
+}
+
+impl Sensor for AlertSensor {}
+
+impl Readings for AlertSensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        self.update();
+        let events = self.changed.take();
+        let changed = |event_type| {
+            string_list(
+                events
+                    .iter()
+                    .filter(move |event| event.event == event_type)
+                    .map(|event| event.rule.as_str()),
+            )
+        };
+        Ok(StructBuilder::with_capacity(4)
+            .value("active", string_list(self.active_names()))
+            .value("raised", changed(AlertEventType::Raised))
+            .value("cleared", changed(AlertEventType::Cleared))
+            .field("alerts", self.alerts)
+            .into_fields())
+    }
+}
+
+impl Status for AlertSensor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(
+            StructBuilder::with_capacity(2)
+                .value("active", string_list(self.active_names()))
+                .field("alerts", self.alerts)
+                .build(),
+        ))
+    }
+}
+
+impl DoCommand for AlertSensor {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        if command.fields.contains_key("take_events") {
+            self.update();
+        }
+        match self.pending.take_events(&command) {
+            Some(events) => Ok(Some(events)),
+            None => Err(GenericError::MethodUnimplemented("do_command")),
+        }
+    }
+}
+
+/// Returns the [EventWatcher] of the alerts sensors of the robot, `None` when it has none
+pub fn watcher_from_robot_and_config(
+    cfg: &ConfigResponse,
+    robot: Arc<RwLock<LocalRobot>>,
+) -> Result<Option<EventWatcher>, SensorError> {
+    EventWatcher::from_robot_and_config(cfg, robot, "alerts", DEFAULT_CHECK_INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlertEventType, AlertRule, AlertSensor, Severity};
+    use crate::common::config::Kind;
+    use crate::common::generic::DoCommand;
+    use crate::common::sensor::{GenericReadingsResult, SensorResult};
+    use crate::google::protobuf::{value::Kind as ProtoKind, Struct, Value};
+    use std::collections::HashMap;
+
+    fn rule(above: Option<f64>, below: Option<f64>, hysteresis: f64) -> AlertRule {
+        AlertRule {
+            name: "rule".to_string(),
+            sensor: "temp".to_string(),
+            reading: "celsius".to_string(),
+            above,
+            below,
+            hysteresis,
+            severity: Severity::Warning,
+        }
+    }
+
+    #[test_log::test]
+    fn test_rule_states() {
+        let overheat = rule(Some(70.0), None, 5.0);
+        assert!(!overheat.next_state(false, 70.0));
+        assert!(overheat.next_state(false, 70.5));
+        // stays raised until the reading drops below 65
+        assert!(overheat.next_state(true, 66.0));
+        assert!(!overheat.next_state(true, 64.0));
+
+        let band = rule(Some(60.0), Some(30.0), 2.0);
+        assert!(band.next_state(false, 29.0));
+        assert!(band.next_state(true, 31.0));
+        assert!(!band.next_state(true, 33.0));
+        assert!(band.next_state(false, 61.0));
+        assert!(!band.next_state(false, 45.0));
+    }
+
+    #[test_log::test]
+    fn test_rule_config() {
+        let kind = |fields: Vec<(&str, Kind)>| {
+            Kind::StructValue(
+                fields
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            )
+        };
+        let config = kind(vec![
+            ("name", Kind::StringValue("rule".to_string())),
+            ("sensor", Kind::StringValue("temp".to_string())),
+            ("reading", Kind::StringValue("celsius".to_string())),
+            ("above", Kind::NumberValue(70.0)),
+            ("hysteresis", Kind::NumberValue(5.0)),
+        ]);
+        assert_eq!(
+            AlertRule::try_from(&config).unwrap(),
+            rule(Some(70.0), None, 5.0)
+        );
+        // a rule needs a threshold
+        let config = kind(vec![
+            ("name", Kind::StringValue("rule".to_string())),
+            ("sensor", Kind::StringValue("temp".to_string())),
+            ("reading", Kind::StringValue("celsius".to_string())),
+            ("severity", Kind::StringValue("critical".to_string())),
+        ]);
+        assert!(AlertRule::try_from(&config).is_err());
+    }
+
+    #[test_log::test]
+    fn test_alert_events() {
+        let mut alerts = AlertSensor {
+            sensors: vec![],
+            rules: vec![rule(Some(70.0), None, 5.0)],
+            raised: vec![false],
+            pending: Default::default(),
+            changed: Default::default(),
+            alerts: 0,
+        };
+        let readings = |celsius: f64| {
+            GenericReadingsResult::from([(
+                "celsius".to_string(),
+                SensorResult::<f64> { value: celsius }.into(),
+            )])
+        };
+        let mut evaluate = |celsius: f64| {
+            alerts.evaluate(&HashMap::from([("temp".to_string(), readings(celsius))]))
+        };
+        assert!(evaluate(60.0).is_empty());
+        let events = evaluate(75.0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, AlertEventType::Raised);
+        assert_eq!(events[0].value, 75.0);
+        assert!(evaluate(68.0).is_empty());
+        assert_eq!(evaluate(60.0)[0].event, AlertEventType::Cleared);
+        // a missing reading keeps the state of the rule
+        assert!(alerts.evaluate(&HashMap::new()).is_empty());
+        assert_eq!(alerts.alerts, 1);
+
+        let take = Struct {
+            fields: HashMap::from([(
+                "take_events".to_string(),
+                Value {
+                    kind: Some(ProtoKind::StructValue(Struct::default())),
+                },
+            )]),
+        };
+        let res = alerts.do_command(Some(take)).unwrap().unwrap();
+        match res.fields.get("events").and_then(|v| v.kind.as_ref()) {
+            Some(ProtoKind::ListValue(events)) => assert_eq!(events.values.len(), 2),
+            _ => panic!("events isn't a list"),
+        }
+        assert!(alerts.pending.is_empty());
+        // the readings still report the changes taken by the command
+        assert_eq!(alerts.changed.len(), 2);
+    }
+}
@@ -0,0 +1,354 @@
+//! Panasonic AMG8833 (Grid-EYE) 8x8 thermal camera, read as a sensor. Datasheet:
+//! https://industrial.panasonic.com/cdbs/www-data/pdf/ADI8000/ADI8000C66.pdf
+//!
+//! ```json
+//! {
+//!     "i2c_bus": "i2c0",
+//!     "i2c_address": 105,
+//!     "hot_spot_celsius": 45,
+//!     "hysteresis": 2
+//! }
+//! ```
+//!
+//! The readings contain the `pixels` grid, a list of 8 rows of 8 temperatures in celsius, and its
+//! `min_celsius`, `max_celsius` and `mean_celsius`. `i2c_address` defaults to 0x69 (0x68 when
+//! the AD_SELECT pin is grounded).
+//!
+//! When `hot_spot_celsius` is set the grid is checked for hot spots every time readings are
+//! requested: a hot spot is raised when a pixel goes above `hot_spot_celsius` and cleared once
+//! every pixel is back below it by at least `hysteresis` (1 by default). The readings then also
+//! contain `hot_spot`, whether a hot spot is raised, and the `hot_pixels` count of pixels above
+//! the threshold. Raising and clearing a hot spot are logged and kept as events, with the
+//! temperature and position of the hottest pixel, until taken with the `{"take_events": {}}`
+//! command, at most 16 events are kept:
+//!
+//! ```json
+//! {"events": [{"event": "raised", "value": 47.25, "row": 3, "column": 5}]}
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use super::board::Board;
+use super::config::{AttributeError, ConfigType};
+use super::generic::{DoCommand, GenericError};
+use super::i2c::{Endian, I2cHandleType, RegisterMap};
+use super::registry::{get_board_from_dependencies, ComponentRegistry, Dependency};
+use super::sensor::{GenericReadingsResult, Readings, Sensor, SensorError, SensorType};
+use super::status::{Status, StatusError};
+use super::struct_builder::StructBuilder;
+use super::webhook::EventQueue;
+use crate::google::protobuf::{value::Kind as ProtoKind, ListValue, Struct, Value};
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("amg8833", &Amg8833::from_config)
+        .is_err()
+    {
+        log::error!("amg8833 type is already registered");
+    }
+}
+
+const DEFAULT_I2C_ADDRESS: u8 = 0x69;
+const DEFAULT_HYSTERESIS: f64 = 1.0;
+
+const POWER_CONTROL_REGISTER: u8 = 0x00;
+const RESET_REGISTER: u8 = 0x01;
+const FRAME_RATE_REGISTER: u8 = 0x02;
+const PIXELS_REGISTER: u8 = 0x80;
+const NORMAL_MODE: u8 = 0x00;
+const INITIAL_RESET: u8 = 0x3F;
+const FRAME_RATE_10_FPS: u8 = 0x00;
+// 12 bits two's complement pixel temperatures
+const CELSIUS_PER_LSB: f64 = 0.25;
+
+pub type ThermalGrid = [[f64; 8]; 8];
+
+/// Converts the 64 raw pixel values, row by row, to temperatures in celsius
+fn grid_from_raw(raw: &[i16; 64]) -> ThermalGrid {
+    let mut grid = [[0.0; 8]; 8];
+    for (index, raw) in raw.iter().enumerate() {
+        // sign extend the 12 bits value
+        grid[index / 8][index % 8] = f64::from((raw << 4) >> 4) * CELSIUS_PER_LSB;
+    }
+    grid
+}
+
+fn number_list(values: impl Iterator<Item = f64>) -> Value {
+    Value {
+        kind: Some(ProtoKind::ListValue(ListValue {
+            values: values
+                .map(|value| Value {
+                    kind: Some(ProtoKind::NumberValue(value)),
+                })
+                .collect(),
+        })),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HotSpotEventType {
+    Raised,
+    Cleared,
+}
+
+impl HotSpotEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Raised => "raised",
+            Self::Cleared => "cleared",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HotSpotEvent {
+    pub event: HotSpotEventType,
+    // temperature and position of the hottest pixel
+    pub value: f64,
+    pub row: usize,
+    pub column: usize,
+}
+
+impl From<&HotSpotEvent> for Value {
+    fn from(value: &HotSpotEvent) -> Self {
+        StructBuilder::with_capacity(4)
+            .field("event", value.event.as_str())
+            .field("value", value.value)
+            .field("row", value.row as u32)
+            .field("column", value.column as u32)
+            .into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HotSpotDetection {
+    pub threshold: f64,
+    pub hysteresis: f64,
+}
+
+pub struct Amg8833 {
+    registers: RegisterMap<I2cHandleType>,
+    hot_spot_detection: Option<HotSpotDetection>,
+    hot_spot: bool,
+    pending: EventQueue<HotSpotEvent>,
+}
+
+impl Amg8833 {
+    pub fn new(
+        i2c_handle: I2cHandleType,
+        i2c_address: u8,
+        hot_spot_detection: Option<HotSpotDetection>,
+    ) -> Result<Self, SensorError> {
+        let mut registers = RegisterMap::new(i2c_handle, i2c_address, Endian::Little);
+        registers.write_u8(POWER_CONTROL_REGISTER, NORMAL_MODE)?;
+        registers.write_u8(RESET_REGISTER, INITIAL_RESET)?;
+        registers.write_u8(FRAME_RATE_REGISTER, FRAME_RATE_10_FPS)?;
+        Ok(Self {
+            registers,
+            hot_spot_detection,
+            hot_spot: false,
+            pending: EventQueue::default(),
+        })
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        dependencies: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let board = get_board_from_dependencies(dependencies)
+            .ok_or(SensorError::ConfigError("amg8833 missing board dependency"))?;
+        let i2c_bus = cfg
+            .get_attribute::<String>("i2c_bus")
+            .map_err(|_| SensorError::ConfigError("amg8833 missing i2c_bus attribute"))?;
+        let i2c_address = match cfg.get_attribute::<u8>("i2c_address") {
+            Ok(address) => address,
+            Err(AttributeError::KeyNotFound(_)) => DEFAULT_I2C_ADDRESS,
+            Err(_) => return Err(SensorError::ConfigError("amg8833 invalid i2c_address")),
+        };
+        let hot_spot_detection = match cfg.get_attribute::<f64>("hot_spot_celsius") {
+            Ok(threshold) => {
+                let hysteresis = match cfg.get_attribute::<f64>("hysteresis") {
+                    Ok(hysteresis) if hysteresis >= 0.0 => hysteresis,
+                    Err(AttributeError::KeyNotFound(_)) => DEFAULT_HYSTERESIS,
+                    _ => return Err(SensorError::ConfigError("amg8833 invalid hysteresis")),
+                };
+                Some(HotSpotDetection {
+                    threshold,
+                    hysteresis,
+                })
+            }
+            Err(AttributeError::KeyNotFound(_)) => None,
+            Err(_) => return Err(SensorError::ConfigError("amg8833 invalid hot_spot_celsius")),
+        };
+        let i2c_handle = board.get_i2c_by_name(i2c_bus)?;
+        Ok(Arc::new(Mutex::new(Self::new(
+            i2c_handle,
+            i2c_address,
+            hot_spot_detection,
+        )?)))
+    }
+
+    /// Reads the last frame of the camera, in celsius
+    pub fn read_grid(&mut self) -> Result<ThermalGrid, SensorError> {
+        Ok(grid_from_raw(
+            &self.registers.read_i16s::<64>(PIXELS_REGISTER)?,
+        ))
+    }
+
+    // updates the hot spot state from a frame, returns the event when it changed
+    fn detect_hot_spot(&mut self, grid: &ThermalGrid) -> Option<HotSpotEvent> {
+        let detection = self.hot_spot_detection?;
+        let (value, row, column) = grid
+            .iter()
+            .enumerate()
+            .flat_map(|(row, pixels)| {
+                pixels
+                    .iter()
+                    .enumerate()
+                    .map(move |(column, value)| (*value, row, column))
+            })
+            .fold((f64::MIN, 0, 0), |hottest, pixel| {
+                if pixel.0 > hottest.0 {
+                    pixel
+                } else {
+                    hottest
+                }
+            });
+        let event = if !self.hot_spot && value > detection.threshold {
+            log::warn!(
+                "amg8833 hot spot raised: {} at ({}, {})",
+                value,
+                row,
+                column
+            );
+            HotSpotEventType::Raised
+        } else if self.hot_spot && value < detection.threshold - detection.hysteresis {
+            log::info!("amg8833 hot spot cleared: {}", value);
+            HotSpotEventType::Cleared
+        } else {
+            return None;
+        };
+        self.hot_spot = event == HotSpotEventType::Raised;
+        let event = HotSpotEvent {
+            event,
+            value,
+            row,
+            column,
+        };
+        self.pending.push(event.clone());
+        Some(event)
+    }
+}
+
+impl Sensor for Amg8833 {}
+
+impl Readings for Amg8833 {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let grid = self.read_grid()?;
+        let pixels = grid.iter().flatten();
+        let min = pixels.clone().copied().fold(f64::MAX, f64::min);
+        let max = pixels.clone().copied().fold(f64::MIN, f64::max);
+        let mean = pixels.clone().sum::<f64>() / 64.0;
+        let mut readings = StructBuilder::with_capacity(6)
+            .value(
+                "pixels",
+                Value {
+                    kind: Some(ProtoKind::ListValue(ListValue {
+                        values: grid
+                            .iter()
+                            .map(|row| number_list(row.iter().copied()))
+                            .collect(),
+                    })),
+                },
+            )
+            .field("min_celsius", min)
+            .field("max_celsius", max)
+            .field("mean_celsius", mean);
+        if let Some(detection) = self.hot_spot_detection {
+            self.detect_hot_spot(&grid);
+            readings = readings.field("hot_spot", self.hot_spot).field(
+                "hot_pixels",
+                pixels.filter(|value| **value > detection.threshold).count() as u32,
+            );
+        }
+        Ok(readings.into_fields())
+    }
+}
+
+impl Status for Amg8833 {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        if self.hot_spot_detection.is_none() {
+            return Ok(Some(Struct::default()));
+        }
+        Ok(Some(
+            StructBuilder::with_capacity(1)
+                .field("hot_spot", self.hot_spot)
+                .build(),
+        ))
+    }
+}
+
+impl DoCommand for Amg8833 {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        match self.pending.take_events(&command) {
+            Some(events) => Ok(Some(events)),
+            None => Err(GenericError::MethodUnimplemented("do_command")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{grid_from_raw, Amg8833, HotSpotDetection, HotSpotEventType};
+    use crate::common::i2c::{Endian, FakeI2CHandle, RegisterMap};
+
+    #[test_log::test]
+    fn test_grid_from_raw() {
+        let mut raw = [0_i16; 64];
+        raw[0] = 100;
+        // -0.25 celsius, 12 bits two's complement
+        raw[9] = 0xFFF;
+        raw[63] = 0x1F4;
+        let grid = grid_from_raw(&raw);
+        assert_eq!(grid[0][0], 25.0);
+        assert_eq!(grid[1][1], -0.25);
+        assert_eq!(grid[7][7], 125.0);
+    }
+
+    #[test_log::test]
+    fn test_hot_spot_detection() {
+        let i2c = Arc::new(Mutex::new(FakeI2CHandle::new("i2c0".to_string())));
+        let mut camera = Amg8833 {
+            registers: RegisterMap::new(i2c, 0x69, Endian::Little),
+            hot_spot_detection: Some(HotSpotDetection {
+                threshold: 40.0,
+                hysteresis: 2.0,
+            }),
+            hot_spot: false,
+            pending: Default::default(),
+        };
+        let mut grid = [[20.0; 8]; 8];
+        assert!(camera.detect_hot_spot(&grid).is_none());
+
+        grid[3][5] = 42.0;
+        let event = camera.detect_hot_spot(&grid).unwrap();
+        assert_eq!(event.event, HotSpotEventType::Raised);
+        assert_eq!((event.value, event.row, event.column), (42.0, 3, 5));
+        assert!(camera.hot_spot);
+
+        // within the hysteresis
+        grid[3][5] = 39.0;
+        assert!(camera.detect_hot_spot(&grid).is_none());
+        grid[3][5] = 37.0;
+        let event = camera.detect_hot_spot(&grid).unwrap();
+        assert_eq!(event.event, HotSpotEventType::Cleared);
+        assert!(!camera.hot_spot);
+        assert_eq!(camera.pending.len(), 2);
+    }
+}
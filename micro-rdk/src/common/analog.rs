@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use super::config::{AttributeError, Kind};
+use crate::common::grpc::GrpcStatusHint;
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
@@ -10,20 +11,90 @@ pub enum AnalogError {
     AnalogReadError(i32),
 }
 
+impl GrpcStatusHint for AnalogError {}
+
+/// Stabilizes the readings of a noisy ADC by averaging `oversampling` raw samples per read and,
+/// when a smoothing factor is set, applying an exponential moving average across reads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalogFilter {
+    oversampling: u32,
+    smoothing_factor: Option<f64>,
+    smoothed: Option<f64>,
+}
+
+impl Default for AnalogFilter {
+    fn default() -> Self {
+        Self {
+            oversampling: 1,
+            smoothing_factor: None,
+            smoothed: None,
+        }
+    }
+}
+
+impl AnalogFilter {
+    /// `smoothing_factor` is the weight of the newest reading, between 0 (exclusive) and 1
+    pub fn new(oversampling: u32, smoothing_factor: Option<f64>) -> Result<Self, AttributeError> {
+        if oversampling == 0 {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        if smoothing_factor.map_or(false, |alpha| !(alpha > 0.0 && alpha <= 1.0)) {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        Ok(Self {
+            oversampling,
+            smoothing_factor,
+            smoothed: None,
+        })
+    }
+
+    /// Produces a filtered reading from the raw samples returned by `sample`
+    pub fn read<E>(&mut self, mut sample: impl FnMut() -> Result<u16, E>) -> Result<u16, E> {
+        // a u32 sum of u16 samples could overflow with a large oversampling
+        let mut sum = 0_u64;
+        for _ in 0..self.oversampling {
+            sum += sample()? as u64;
+        }
+        let average = sum as f64 / self.oversampling as f64;
+        let value = match (self.smoothing_factor, self.smoothed) {
+            (Some(alpha), Some(previous)) => alpha * average + (1.0 - alpha) * previous,
+            _ => average,
+        };
+        if self.smoothing_factor.is_some() {
+            self.smoothed = Some(value);
+        }
+        Ok(value.round() as u16)
+    }
+}
+
 pub struct FakeAnalogReader {
     name: String,
     value: u16,
+    filter: AnalogFilter,
 }
 
 impl FakeAnalogReader {
     pub fn new(name: String, value: u16) -> Self {
-        Self { name, value }
+        Self {
+            name,
+            value,
+            filter: Default::default(),
+        }
+    }
+    pub fn with_filter(mut self, filter: AnalogFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+    /// Changes the raw value sampled by the reader
+    pub fn set_value(&mut self, value: u16) {
+        self.value = value;
     }
     fn internal_name(&self) -> String {
         self.name.clone()
     }
-    fn internal_read(&self) -> Result<u16, AnalogError> {
-        Ok(self.value)
+    fn internal_read(&mut self) -> Result<u16, AnalogError> {
+        let value = self.value;
+        self.filter.read(|| Ok(value))
     }
 }
 
@@ -56,9 +127,60 @@ where
     }
 }
 
+/// Reads and validates the `oversampling` and `smoothing_factor` attributes of an analog reader
+fn filter_attributes(value: &Kind) -> Result<(u32, Option<f64>), AttributeError> {
+    let oversampling: u32 = match value.get("oversampling")? {
+        Some(oversampling) => oversampling.try_into()?,
+        None => 1,
+    };
+    let smoothing_factor: Option<f64> = match value.get("smoothing_factor")? {
+        Some(alpha) => Some(alpha.try_into()?),
+        None => None,
+    };
+    AnalogFilter::new(oversampling, smoothing_factor)?;
+    Ok((oversampling, smoothing_factor))
+}
+
+/// An analog reader of the fake board, either its value or an object with the `value` and the
+/// filter attributes of the reader
+pub(crate) struct FakeAnalogConfig {
+    pub(crate) value: u16,
+    pub(crate) filter: AnalogFilter,
+}
+
+impl TryFrom<&Kind> for FakeAnalogConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        if let Kind::NumberValue(v) = value {
+            return Ok(Self {
+                value: *v as u16,
+                filter: Default::default(),
+            });
+        }
+        let v: f64 = value
+            .get("value")?
+            .ok_or_else(|| AttributeError::KeyNotFound("value".to_string()))?
+            .try_into()?;
+        let (oversampling, smoothing_factor) = filter_attributes(value)?;
+        Ok(Self {
+            value: v as u16,
+            filter: AnalogFilter::new(oversampling, smoothing_factor)?,
+        })
+    }
+}
+
 pub(crate) struct AnalogReaderConfig {
     pub(crate) name: String,
     pub(crate) pin: i32,
+    pub(crate) oversampling: u32,
+    pub(crate) smoothing_factor: Option<f64>,
+}
+
+impl AnalogReaderConfig {
+    pub(crate) fn filter(&self) -> AnalogFilter {
+        // the values were validated when parsing the config
+        AnalogFilter::new(self.oversampling, self.smoothing_factor).unwrap_or_default()
+    }
 }
 
 impl TryFrom<&Kind> for AnalogReaderConfig {
@@ -72,7 +194,13 @@ impl TryFrom<&Kind> for AnalogReaderConfig {
         }
         let name = value.get("name")?.unwrap().try_into()?;
         let pin: i32 = value.get("pin")?.unwrap().try_into()?;
-        Ok(Self { name, pin })
+        let (oversampling, smoothing_factor) = filter_attributes(value)?;
+        Ok(Self {
+            name,
+            pin,
+            oversampling,
+            smoothing_factor,
+        })
     }
 }
 
@@ -84,7 +212,10 @@ mod tests {
 
     use crate::common::config::{Component, DynamicComponentConfig, Kind};
 
-    use super::AnalogReaderConfig;
+    use super::{
+        AnalogError, AnalogFilter, AnalogReader, AnalogReaderConfig, FakeAnalogConfig,
+        FakeAnalogReader,
+    };
     #[test_log::test]
     fn test_analog_reader_config() {
         let robot_config: &[DynamicComponentConfig] = &[DynamicComponentConfig {
@@ -111,6 +242,8 @@ mod tests {
                         Kind::StructValue(HashMap::from([
                             ("name".to_owned(), Kind::StringValue("string".to_owned())),
                             ("pin".to_owned(), Kind::StringValue("11".to_owned())),
+                            ("oversampling".to_owned(), Kind::NumberValue(8.0)),
+                            ("smoothing_factor".to_owned(), Kind::NumberValue(0.25)),
                         ])),
                     ]),
                 ),
@@ -130,5 +263,58 @@ mod tests {
         assert_eq!(val[1].name, "string");
         assert_eq!(val[0].pin, 12);
         assert_eq!(val[1].pin, 11);
+        assert_eq!(val[0].filter(), AnalogFilter::default());
+        assert_eq!(val[1].oversampling, 8);
+        assert_eq!(val[1].smoothing_factor, Some(0.25));
+
+        let invalid = Kind::StructValue(HashMap::from([
+            ("name".to_owned(), Kind::StringValue("string".to_owned())),
+            ("pin".to_owned(), Kind::StringValue("11".to_owned())),
+            ("smoothing_factor".to_owned(), Kind::NumberValue(1.5)),
+        ]));
+        assert!(AnalogReaderConfig::try_from(&invalid).is_err());
+    }
+
+    #[test_log::test]
+    fn test_analog_filter() {
+        assert!(AnalogFilter::new(0, None).is_err());
+
+        let mut samples = [10_u16, 20, 30, 40].into_iter();
+        let mut filter = AnalogFilter::new(4, None).unwrap();
+        assert_eq!(
+            filter
+                .read(|| Ok::<_, AnalogError>(samples.next().unwrap()))
+                .unwrap(),
+            25
+        );
+
+        let mut reader = FakeAnalogReader::new("a".to_string(), 100)
+            .with_filter(AnalogFilter::new(1, Some(0.5)).unwrap());
+        assert_eq!(reader.read().unwrap(), 100);
+        reader.set_value(200);
+        assert_eq!(reader.read().unwrap(), 150);
+        assert_eq!(reader.read().unwrap(), 175);
+    }
+
+    #[test_log::test]
+    fn test_fake_analog_config() {
+        let plain = FakeAnalogConfig::try_from(&Kind::NumberValue(512.0)).unwrap();
+        assert_eq!(plain.value, 512);
+        assert_eq!(plain.filter, AnalogFilter::default());
+
+        let filtered = FakeAnalogConfig::try_from(&Kind::StructValue(HashMap::from([
+            ("value".to_owned(), Kind::NumberValue(512.0)),
+            ("oversampling".to_owned(), Kind::NumberValue(4.0)),
+            ("smoothing_factor".to_owned(), Kind::NumberValue(0.5)),
+        ])))
+        .unwrap();
+        assert_eq!(filtered.value, 512);
+        assert_eq!(filtered.filter, AnalogFilter::new(4, Some(0.5)).unwrap());
+
+        let invalid = Kind::StructValue(HashMap::from([
+            ("value".to_owned(), Kind::NumberValue(512.0)),
+            ("oversampling".to_owned(), Kind::NumberValue(0.0)),
+        ]));
+        assert!(FakeAnalogConfig::try_from(&invalid).is_err());
     }
 }
@@ -2,16 +2,28 @@
 use bytes::{BufMut, Bytes, BytesMut};
 use chrono::{format::ParseError, DateTime, FixedOffset};
 use futures_lite::{Future, StreamExt};
+use http_body_util::combinators::BoxBody;
 use http_body_util::BodyExt;
 use http_body_util::Full;
 use http_body_util::StreamBody;
 use hyper::body::Frame;
 use prost::{DecodeError, EncodeError, Message};
-use std::{net::Ipv4Addr, pin::Pin, rc::Rc, time::SystemTime};
+use std::{borrow::Cow, net::Ipv4Addr, pin::Pin, rc::Rc, time::Duration, time::SystemTime};
 use thiserror::Error;
 
 use crate::proto::{
-    app::v1::{AgentInfo, ConfigRequest, ConfigResponse, LogRequest},
+    app::{
+        data_sync::v1::{
+            file_upload_request, streaming_data_capture_upload_request, DataCaptureUploadMetadata,
+            DataCaptureUploadRequest, DataCaptureUploadResponse, FileData, FileUploadRequest,
+            FileUploadResponse, StreamingDataCaptureUploadRequest,
+            StreamingDataCaptureUploadResponse, UploadMetadata,
+        },
+        v1::{
+            AgentInfo, ConfigRequest, ConfigResponse, LogRequest, NeedsRestartRequest,
+            NeedsRestartResponse,
+        },
+    },
     common::v1::LogEntry,
     rpc::{
         v1::{AuthenticateRequest, AuthenticateResponse, Credentials},
@@ -21,6 +33,7 @@ use crate::proto::{
 
 use super::{
     grpc_client::{GrpcClient, GrpcClientError, GrpcMessageSender, GrpcMessageStream},
+    secret::SecretString,
     webrtc::{
         api::{WebRtcApi, WebRtcError},
         certificate::Certificate,
@@ -29,6 +42,10 @@ use super::{
     },
 };
 
+/// Size of the chunks binary data and files are streamed in, matches the send buffer of the
+/// HTTP2 connection
+const UPLOAD_CHUNK_SIZE: usize = 4096;
+
 #[derive(Error, Debug)]
 pub enum AppClientError {
     #[error("wrong credentials")]
@@ -47,12 +64,14 @@ pub enum AppClientError {
     AppConfigHeaderDateMissingError,
     #[error(transparent)]
     AppGrpcClientError(#[from] GrpcClientError),
+    #[error("connection to app was closed")]
+    AppClientDisconnected,
 }
 
 #[derive(Debug, Clone)]
 pub struct AppClientConfig {
     robot_id: String,
-    robot_secret: String,
+    robot_secret: SecretString,
     ip: Ipv4Addr,
     rpc_host: String,
 }
@@ -61,7 +80,7 @@ impl Default for AppClientConfig {
     fn default() -> Self {
         Self {
             robot_id: "".to_owned(),
-            robot_secret: "".to_owned(),
+            robot_secret: SecretString::default(),
             ip: Ipv4Addr::new(0, 0, 0, 0),
             rpc_host: "".to_owned(),
         }
@@ -69,10 +88,15 @@ impl Default for AppClientConfig {
 }
 
 impl AppClientConfig {
-    pub fn new(robot_secret: String, robot_id: String, ip: Ipv4Addr, rpc_host: String) -> Self {
+    pub fn new(
+        robot_secret: impl Into<SecretString>,
+        robot_id: String,
+        ip: Ipv4Addr,
+        rpc_host: String,
+    ) -> Self {
         AppClientConfig {
             robot_id,
-            robot_secret,
+            robot_secret: robot_secret.into(),
             ip,
             rpc_host,
         }
@@ -109,6 +133,22 @@ where
     Ok(buf.into())
 }
 
+/// Encodes a sequence of messages as the body of a client streaming request
+fn encode_request_stream<T>(
+    reqs: impl IntoIterator<Item = T>,
+) -> Result<BoxBody<Bytes, hyper::Error>, AppClientError>
+where
+    T: Message,
+{
+    let frames = reqs
+        .into_iter()
+        .map(|req| Ok(Ok::<_, hyper::Error>(Frame::data(encode_request(req)?))))
+        .collect::<Result<Vec<_>, AppClientError>>()?;
+    Ok(BodyExt::boxed(StreamBody::new(futures_lite::stream::iter(
+        frames,
+    ))))
+}
+
 impl<'a> AppClientBuilder<'a> {
     /// Create a new AppClientBuilder
     pub fn new(grpc_client: Box<GrpcClient<'a>>, config: AppClientConfig) -> Self {
@@ -129,60 +169,90 @@ impl<'a> AppClientBuilder<'a> {
             config: self.config,
         })
     }
-    pub async fn get_jwt_token(&mut self) -> Result<String, AppClientError> {
-        let cred = Credentials {
-            r#type: "robot-secret".to_owned(),
-            payload: self.config.robot_secret.clone(),
-        };
+    pub async fn get_jwt_token(&mut self) -> Result<SecretString, AppClientError> {
+        authenticate(&mut self.grpc_client, &self.config).await
+    }
+}
 
-        let req = AuthenticateRequest {
-            entity: self.config.robot_id.clone(),
-            credentials: Some(cred),
-        };
+/// Authenticates the robot against app over an established connection, returning the bearer
+/// token to attach to subsequent requests
+async fn authenticate(
+    grpc_client: &mut GrpcClient<'_>,
+    config: &AppClientConfig,
+) -> Result<SecretString, AppClientError> {
+    let cred = Credentials {
+        r#type: "robot-secret".to_owned(),
+        payload: config.robot_secret.expose().to_owned(),
+    };
 
-        let body = encode_request(req)?;
-        let mut r = self
-            .grpc_client
-            .build_request(
-                "/proto.rpc.v1.AuthService/Authenticate",
-                None,
-                "",
-                Full::new(body).map_err(|never| match never {}).boxed(),
-            )
-            .map_err(AppClientError::AppGrpcClientError)?;
+    let req = AuthenticateRequest {
+        entity: config.robot_id.clone(),
+        credentials: Some(cred),
+    };
 
-        let mut r = self
-            .grpc_client
-            .send_request(r)
-            .await
-            .map_err(AppClientError::AppGrpcClientError)?
-            .0;
-        let r = r.split_off(5);
-        let r = AuthenticateResponse::decode(r).map_err(AppClientError::AppDecodeError)?;
-        Ok(format!("Bearer {}", r.access_token))
-    }
+    let body = encode_request(req)?;
+    let r = grpc_client
+        .build_request(
+            "/proto.rpc.v1.AuthService/Authenticate",
+            None,
+            "",
+            Full::new(body).map_err(|never| match never {}).boxed(),
+        )
+        .map_err(AppClientError::AppGrpcClientError)?;
+
+    let mut r = grpc_client
+        .send_request(r)
+        .await
+        .map_err(AppClientError::AppGrpcClientError)?
+        .0;
+    let r = r.split_off(5);
+    let r = AuthenticateResponse::decode(r).map_err(AppClientError::AppDecodeError)?;
+    Ok(format!("Bearer {}", r.access_token).into())
+}
+
+/// Returns the access token of `jwt` without its `Bearer ` prefix, as it could be logged alone
+fn access_token(jwt: &SecretString) -> SecretString {
+    let jwt = jwt.expose();
+    jwt.strip_prefix("Bearer ").unwrap_or(jwt).into()
 }
 
 pub struct AppClient<'a> {
     config: AppClientConfig,
-    jwt: String,
+    jwt: SecretString,
     grpc_client: Box<GrpcClient<'a>>,
     ip: Ipv4Addr,
 }
 
-pub(crate) struct AppSignaling(
+pub struct AppSignaling(
     pub(crate) GrpcMessageSender<AnswerResponse>,
     pub(crate) GrpcMessageStream<AnswerRequest>,
 );
 
 impl<'a> AppClient<'a> {
+    /// Returns false once the HTTP2 connection to app has been closed (GOAWAY, io error...),
+    /// in which case the AppClient has to be rebuilt
+    pub fn is_connected(&self) -> bool {
+        self.grpc_client.is_connected()
+    }
+
+    /// Re-establishes the signaling path after the answering stream ended while the
+    /// connection to app is still up: a fresh token is requested over the existing connection
+    /// so the next call to `connect_signaling` doesn't require a new TLS handshake
+    pub(crate) async fn reconnect_signaling(&mut self) -> Result<(), AppClientError> {
+        if !self.is_connected() {
+            return Err(AppClientError::AppClientDisconnected);
+        }
+        self.jwt = authenticate(&mut self.grpc_client, &self.config).await?;
+        Ok(())
+    }
+
     pub(crate) async fn connect_signaling(&mut self) -> Result<AppSignaling, AppClientError> {
         let (sender, receiver) = async_channel::bounded::<Bytes>(1);
         let r = self
             .grpc_client
             .build_request(
                 "/proto.rpc.webrtc.v1.SignalingService/Answer",
-                Some(&self.jwt),
+                Some(self.jwt.expose()),
                 &self.config.rpc_host,
                 BodyExt::boxed(StreamBody::new(receiver.map(|b| Ok(Frame::data(b))))),
             )
@@ -207,7 +277,9 @@ impl<'a> AppClient<'a> {
             host: "esp32".to_string(),
             ips: vec![self.ip.to_string()],
             version: env!("CARGO_PKG_VERSION").to_string(),
-            git_revision: "".to_string(),
+            git_revision: option_env!("MICRO_RDK_GIT_REVISION")
+                .unwrap_or_default()
+                .to_string(),
             platform: Some("esp32".to_string()),
         };
 
@@ -221,7 +293,7 @@ impl<'a> AppClient<'a> {
             .grpc_client
             .build_request(
                 "/viam.app.v1.RobotService/Config",
-                Some(&self.jwt),
+                Some(self.jwt.expose()),
                 "",
                 BodyExt::boxed(Full::new(body).map_err(|never| match never {})),
             )
@@ -243,7 +315,16 @@ impl<'a> AppClient<'a> {
         Ok((Box::new(ConfigResponse::decode(r)?), datetime))
     }
 
-    pub async fn push_logs(&mut self, logs: Vec<LogEntry>) -> Result<(), AppClientError> {
+    pub async fn push_logs(&mut self, mut logs: Vec<LogEntry>) -> Result<(), AppClientError> {
+        // logs leave the device, make sure no credential slipped into them
+        let token = access_token(&self.jwt);
+        for log in logs.iter_mut() {
+            for secret in [&self.config.robot_secret, &token] {
+                if let Cow::Owned(redacted) = secret.redact(&log.message) {
+                    log.message = redacted;
+                }
+            }
+        }
         let req = LogRequest {
             id: self.config.robot_id.clone(),
             logs,
@@ -254,7 +335,7 @@ impl<'a> AppClient<'a> {
             .grpc_client
             .build_request(
                 "/viam.app.v1.RobotService/Log",
-                Some(&self.jwt),
+                Some(self.jwt.expose()),
                 "",
                 BodyExt::boxed(Full::new(body).map_err(|never| match never {})),
             )
@@ -263,6 +344,167 @@ impl<'a> AppClient<'a> {
 
         Ok(())
     }
+
+    /// Asks app whether the robot must restart, to apply a new config for instance. Returns the
+    /// answer along with the interval at which app wants to be asked again, if it gave one.
+    pub async fn check_for_restart(&mut self) -> Result<(bool, Option<Duration>), AppClientError> {
+        let req = NeedsRestartRequest {
+            id: self.config.robot_id.clone(),
+        };
+        let body = encode_request(req)?;
+        let r = self
+            .grpc_client
+            .build_request(
+                "/viam.app.v1.RobotService/NeedsRestart",
+                Some(self.jwt.expose()),
+                "",
+                BodyExt::boxed(Full::new(body).map_err(|never| match never {})),
+            )
+            .map_err(AppClientError::AppGrpcClientError)?;
+        let (mut r, _) = self.grpc_client.send_request(r).await?;
+        let r = NeedsRestartResponse::decode(r.split_off(5))?;
+        let interval = r
+            .restart_check_interval
+            .filter(|d| d.seconds >= 0 && d.nanos >= 0)
+            .map(|d| Duration::new(d.seconds as u64, d.nanos as u32));
+        Ok((r.must_restart, interval))
+    }
+
+    /// Uploads the data captured by a resource method in a single request, used for tabular
+    /// data and binary captures small enough to fit in memory twice. Returns the id of the
+    /// file app stored the data in.
+    pub async fn upload_data(
+        &mut self,
+        req: DataCaptureUploadRequest,
+    ) -> Result<String, AppClientError> {
+        let body = encode_request(req)?;
+        let r = self
+            .grpc_client
+            .build_request(
+                "/viam.app.datasync.v1.DataSyncService/DataCaptureUpload",
+                Some(self.jwt.expose()),
+                "",
+                BodyExt::boxed(Full::new(body).map_err(|never| match never {})),
+            )
+            .map_err(AppClientError::AppGrpcClientError)?;
+        let (mut r, _) = self.grpc_client.send_request(r).await?;
+        let r = r.split_off(5);
+        Ok(DataCaptureUploadResponse::decode(r)?.file_id)
+    }
+
+    /// Streams a single binary capture (camera frame, audio clip) to app in chunks
+    pub async fn upload_binary_data(
+        &mut self,
+        metadata: DataCaptureUploadMetadata,
+        data: &[u8],
+    ) -> Result<String, AppClientError> {
+        use streaming_data_capture_upload_request::UploadPacket;
+        let packets = std::iter::once(UploadPacket::Metadata(metadata))
+            .chain(
+                data.chunks(UPLOAD_CHUNK_SIZE)
+                    .map(|chunk| UploadPacket::Data(chunk.to_vec())),
+            )
+            .map(|packet| StreamingDataCaptureUploadRequest {
+                upload_packet: Some(packet),
+            });
+        let r = self
+            .grpc_client
+            .build_request(
+                "/viam.app.datasync.v1.DataSyncService/StreamingDataCaptureUpload",
+                Some(self.jwt.expose()),
+                "",
+                encode_request_stream(packets)?,
+            )
+            .map_err(AppClientError::AppGrpcClientError)?;
+        let (mut r, _) = self.grpc_client.send_request(r).await?;
+        let r = r.split_off(5);
+        Ok(StreamingDataCaptureUploadResponse::decode(r)?.file_id)
+    }
+
+    /// Uploads an arbitrary file to app in chunks
+    pub async fn upload_file(
+        &mut self,
+        metadata: UploadMetadata,
+        contents: &[u8],
+    ) -> Result<String, AppClientError> {
+        use file_upload_request::UploadPacket;
+        let packets = std::iter::once(UploadPacket::Metadata(metadata))
+            .chain(contents.chunks(UPLOAD_CHUNK_SIZE).map(|chunk| {
+                UploadPacket::FileContents(FileData {
+                    data: chunk.to_vec(),
+                })
+            }))
+            .map(|packet| FileUploadRequest {
+                upload_packet: Some(packet),
+            });
+        let r = self
+            .grpc_client
+            .build_request(
+                "/viam.app.datasync.v1.DataSyncService/FileUpload",
+                Some(self.jwt.expose()),
+                "",
+                encode_request_stream(packets)?,
+            )
+            .map_err(AppClientError::AppGrpcClientError)?;
+        let (mut r, _) = self.grpc_client.send_request(r).await?;
+        let r = r.split_off(5);
+        Ok(FileUploadResponse::decode(r)?.file_id)
+    }
+}
+
+/// Requests a robot makes to app. Implemented by [AppClient], the code talking to app is
+/// generic over it so its reconnect and sync logic can be tested against a mock.
+pub trait AppClientApi {
+    /// Returns false once the connection to app is lost and the client has to be rebuilt
+    fn is_connected(&self) -> bool;
+    fn get_config(
+        &mut self,
+    ) -> impl Future<Output = Result<(Box<ConfigResponse>, Option<DateTime<FixedOffset>>), AppClientError>>;
+    fn push_logs(
+        &mut self,
+        logs: Vec<LogEntry>,
+    ) -> impl Future<Output = Result<(), AppClientError>>;
+    /// Uploads captured data, returns the id of the file app stored it in
+    fn push_sensor_data(
+        &mut self,
+        req: DataCaptureUploadRequest,
+    ) -> impl Future<Output = Result<String, AppClientError>>;
+    fn check_for_restart(
+        &mut self,
+    ) -> impl Future<Output = Result<(bool, Option<Duration>), AppClientError>>;
+    /// Opens the stream app sends WebRTC offers on
+    fn connect_signaling(&mut self) -> impl Future<Output = Result<AppSignaling, AppClientError>>;
+    /// Prepares the next call to `connect_signaling` after the answering stream ended
+    fn reconnect_signaling(&mut self) -> impl Future<Output = Result<(), AppClientError>>;
+}
+
+impl<'a> AppClientApi for AppClient<'a> {
+    fn is_connected(&self) -> bool {
+        AppClient::is_connected(self)
+    }
+    async fn get_config(
+        &mut self,
+    ) -> Result<(Box<ConfigResponse>, Option<DateTime<FixedOffset>>), AppClientError> {
+        AppClient::get_config(self).await
+    }
+    async fn push_logs(&mut self, logs: Vec<LogEntry>) -> Result<(), AppClientError> {
+        AppClient::push_logs(self, logs).await
+    }
+    async fn push_sensor_data(
+        &mut self,
+        req: DataCaptureUploadRequest,
+    ) -> Result<String, AppClientError> {
+        self.upload_data(req).await
+    }
+    async fn check_for_restart(&mut self) -> Result<(bool, Option<Duration>), AppClientError> {
+        AppClient::check_for_restart(self).await
+    }
+    async fn connect_signaling(&mut self) -> Result<AppSignaling, AppClientError> {
+        AppClient::connect_signaling(self).await
+    }
+    async fn reconnect_signaling(&mut self) -> Result<(), AppClientError> {
+        AppClient::reconnect_signaling(self).await
+    }
 }
 
 impl<'a> Drop for AppClient<'a> {
@@ -270,3 +512,112 @@ impl<'a> Drop for AppClient<'a> {
         log::debug!("dropping AppClient")
     }
 }
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use chrono::{DateTime, FixedOffset};
+
+    use super::{AppClientApi, AppClientError, AppSignaling};
+    use crate::proto::{
+        app::{data_sync::v1::DataCaptureUploadRequest, v1::ConfigResponse},
+        common::v1::LogEntry,
+    };
+
+    /// What a [MockAppClient] answers and the requests it received, shared with the test
+    #[derive(Default)]
+    pub(crate) struct MockAppState {
+        pub(crate) connected: bool,
+        pub(crate) config: ConfigResponse,
+        pub(crate) must_restart: bool,
+        pub(crate) restart_check_interval: Option<Duration>,
+        /// number of requests that fail and close the connection, from the next one
+        pub(crate) failures: usize,
+        pub(crate) logs: Vec<LogEntry>,
+        pub(crate) sensor_data: Vec<DataCaptureUploadRequest>,
+        pub(crate) restart_checks: usize,
+    }
+
+    #[derive(Clone)]
+    pub(crate) struct MockAppClient(pub(crate) Arc<Mutex<MockAppState>>);
+
+    impl MockAppClient {
+        pub(crate) fn new() -> Self {
+            Self(Arc::new(Mutex::new(MockAppState {
+                connected: true,
+                ..Default::default()
+            })))
+        }
+        fn request(&self) -> Result<(), AppClientError> {
+            let mut state = self.0.lock().unwrap();
+            if !state.connected {
+                return Err(AppClientError::AppClientDisconnected);
+            }
+            if state.failures > 0 {
+                state.failures -= 1;
+                state.connected = false;
+                return Err(AppClientError::AppClientDisconnected);
+            }
+            Ok(())
+        }
+    }
+
+    impl AppClientApi for MockAppClient {
+        fn is_connected(&self) -> bool {
+            self.0.lock().unwrap().connected
+        }
+        async fn get_config(
+            &mut self,
+        ) -> Result<(Box<ConfigResponse>, Option<DateTime<FixedOffset>>), AppClientError> {
+            self.request()?;
+            Ok((Box::new(self.0.lock().unwrap().config.clone()), None))
+        }
+        async fn push_logs(&mut self, logs: Vec<LogEntry>) -> Result<(), AppClientError> {
+            self.request()?;
+            self.0.lock().unwrap().logs.extend(logs);
+            Ok(())
+        }
+        async fn push_sensor_data(
+            &mut self,
+            req: DataCaptureUploadRequest,
+        ) -> Result<String, AppClientError> {
+            self.request()?;
+            let mut state = self.0.lock().unwrap();
+            state.sensor_data.push(req);
+            Ok(format!("file-{}", state.sensor_data.len()))
+        }
+        async fn check_for_restart(&mut self) -> Result<(bool, Option<Duration>), AppClientError> {
+            self.request()?;
+            let mut state = self.0.lock().unwrap();
+            state.restart_checks += 1;
+            Ok((state.must_restart, state.restart_check_interval))
+        }
+        // no offer ever comes from the mock
+        async fn connect_signaling(&mut self) -> Result<AppSignaling, AppClientError> {
+            self.request()?;
+            futures_lite::future::pending().await
+        }
+        async fn reconnect_signaling(&mut self) -> Result<(), AppClientError> {
+            self.request()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::access_token;
+    use crate::common::secret::SecretString;
+
+    #[test_log::test]
+    fn test_access_token_redaction() {
+        let token = access_token(&SecretString::from("Bearer abc.def.ghi"));
+        assert_eq!(token.expose(), "abc.def.ghi");
+        assert_eq!(
+            token.redact("sent Bearer abc.def.ghi, then abc.def.ghi"),
+            "sent Bearer [REDACTED], then [REDACTED]"
+        );
+        assert_eq!(access_token(&SecretString::default()).expose(), "");
+    }
+}
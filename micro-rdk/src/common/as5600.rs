@@ -0,0 +1,297 @@
+//! Package as5600 implements the encoder interface for the AMS AS5600 12-bit magnetic rotary
+//! position sensor over I2C. A datasheet for this chip is at
+//! https://ams.com/documents/20143/36005/AS5600_DS000365_5-00.pdf
+//!
+//! The raw angle register is used so that the zero position can be configured through the
+//! `zero_offset_degrees` attribute without permanently burning the ZPOS register. Complete
+//! rotations are accumulated in software (unless `multi_turn` is set to false), which requires
+//! the position to be read at least once every half turn of the magnet.
+//!
+//! The AS5600 has a fixed I2C address of 0x36.
+
+use crate::common::i2c::I2cHandleType;
+
+use super::board::Board;
+use super::config::ConfigType;
+use super::encoder::{
+    Encoder, EncoderError, EncoderPosition, EncoderPositionType, EncoderSupportedRepresentations,
+    EncoderType,
+};
+use super::i2c::I2CHandle;
+use super::registry::{get_board_from_dependencies, ComponentRegistry, Dependency};
+
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_encoder("as5600", &AS5600::from_config)
+        .is_err()
+    {
+        log::error!("as5600 type is already registered");
+    }
+}
+
+const DEFAULT_I2C_ADDRESS: u8 = 0x36;
+const STATUS_REGISTER: u8 = 0x0B;
+const RAW_ANGLE_REGISTER: u8 = 0x0C;
+// magnet detected, too weak and too strong bits of the status register
+const STATUS_MAGNET_DETECTED: u8 = 0x20;
+const STATUS_MAGNET_TOO_WEAK: u8 = 0x10;
+const STATUS_MAGNET_TOO_STRONG: u8 = 0x08;
+const TICKS_PER_ROTATION: i32 = 4096;
+
+// returns the number of complete rotations to add when moving from the previous angle to the
+// current one, assuming the magnet turned by less than half a rotation in between
+fn turns_delta(previous: u16, current: u16) -> i32 {
+    let delta = current as i32 - previous as i32;
+    if delta > TICKS_PER_ROTATION / 2 {
+        -1
+    } else if delta < -TICKS_PER_ROTATION / 2 {
+        1
+    } else {
+        0
+    }
+}
+
+#[derive(DoCommand, Status)]
+pub struct AS5600 {
+    i2c_handle: I2cHandleType,
+    i2c_address: u8,
+    // raw angle (in ticks) considered as the zero position
+    zero_offset: u16,
+    multi_turn: bool,
+    // last angle read, relative to the zero offset
+    last_angle: Cell<u16>,
+    turns: Cell<i32>,
+}
+
+impl AS5600 {
+    pub fn new(
+        i2c_handle: I2cHandleType,
+        i2c_address: u8,
+        zero_offset_degrees: f32,
+        multi_turn: bool,
+    ) -> Result<Self, EncoderError> {
+        if !(0.0..360.0).contains(&zero_offset_degrees) {
+            return Err(EncoderError::EncoderConfigError(
+                "as5600 zero_offset_degrees must be in [0, 360)",
+            ));
+        }
+        let enc = AS5600 {
+            i2c_handle,
+            i2c_address,
+            zero_offset: (zero_offset_degrees / 360.0 * TICKS_PER_ROTATION as f32) as u16,
+            multi_turn,
+            last_angle: Cell::new(0),
+            turns: Cell::new(0),
+        };
+        let status = enc.read_status()?;
+        if status & STATUS_MAGNET_DETECTED == 0 {
+            log::warn!("as5600: no magnet detected");
+        } else if status & STATUS_MAGNET_TOO_WEAK != 0 {
+            log::warn!("as5600: magnet too weak");
+        } else if status & STATUS_MAGNET_TOO_STRONG != 0 {
+            log::warn!("as5600: magnet too strong");
+        }
+        enc.last_angle.set(enc.read_angle()?);
+        Ok(enc)
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        dependencies: Vec<Dependency>,
+    ) -> Result<EncoderType, EncoderError> {
+        let board = get_board_from_dependencies(dependencies)
+            .ok_or(EncoderError::EncoderConfigError("as5600 missing board"))?;
+        let i2c_name = cfg
+            .get_attribute::<String>("i2c_bus")
+            .map_err(|_| EncoderError::EncoderConfigError("as5600 missing i2c_bus"))?;
+        let i2c_handle = board.get_i2c_by_name(i2c_name)?;
+        let i2c_address = cfg
+            .get_attribute::<u8>("i2c_address")
+            .unwrap_or(DEFAULT_I2C_ADDRESS);
+        let zero_offset_degrees = cfg
+            .get_attribute::<f32>("zero_offset_degrees")
+            .unwrap_or(0.0);
+        let multi_turn = cfg.get_attribute::<bool>("multi_turn").unwrap_or(true);
+        Ok(Arc::new(Mutex::new(AS5600::new(
+            i2c_handle,
+            i2c_address,
+            zero_offset_degrees,
+            multi_turn,
+        )?)))
+    }
+
+    fn read_status(&self) -> Result<u8, EncoderError> {
+        let mut status = [0_u8; 1];
+        self.i2c_handle.lock().unwrap().write_read_i2c(
+            self.i2c_address,
+            &[STATUS_REGISTER],
+            &mut status,
+        )?;
+        Ok(status[0])
+    }
+
+    fn read_raw_angle(&self) -> Result<u16, EncoderError> {
+        let mut raw = [0_u8; 2];
+        self.i2c_handle.lock().unwrap().write_read_i2c(
+            self.i2c_address,
+            &[RAW_ANGLE_REGISTER],
+            &mut raw,
+        )?;
+        Ok(u16::from_be_bytes(raw) & 0x0FFF)
+    }
+
+    // reads the current angle relative to the zero offset
+    fn read_angle(&self) -> Result<u16, EncoderError> {
+        let raw = self.read_raw_angle()?;
+        Ok((raw as i32 - self.zero_offset as i32).rem_euclid(TICKS_PER_ROTATION) as u16)
+    }
+
+    // reads the current angle and returns the accumulated position in ticks relative
+    // to the zero offset, turns are counted when the angle wraps around the zero position
+    fn read_ticks(&self) -> Result<i32, EncoderError> {
+        let angle = self.read_angle()?;
+        if self.multi_turn {
+            self.turns
+                .set(self.turns.get() + turns_delta(self.last_angle.get(), angle));
+        }
+        self.last_angle.set(angle);
+        Ok(self.turns.get() * TICKS_PER_ROTATION + angle as i32)
+    }
+}
+
+impl Encoder for AS5600 {
+    fn get_properties(&mut self) -> EncoderSupportedRepresentations {
+        EncoderSupportedRepresentations {
+            ticks_count_supported: true,
+            angle_degrees_supported: true,
+        }
+    }
+
+    fn get_position(
+        &self,
+        position_type: EncoderPositionType,
+    ) -> Result<EncoderPosition, EncoderError> {
+        let ticks = self.read_ticks()?;
+        match position_type {
+            EncoderPositionType::DEGREES | EncoderPositionType::UNSPECIFIED => {
+                Ok(EncoderPositionType::DEGREES
+                    .wrap_value(ticks as f32 * 360.0 / TICKS_PER_ROTATION as f32))
+            }
+            EncoderPositionType::TICKS => Ok(EncoderPositionType::TICKS.wrap_value(ticks as f32)),
+        }
+    }
+
+    fn ticks_per_rotation(&self) -> Option<u32> {
+        Some(TICKS_PER_ROTATION as u32)
+    }
+
+    // makes the current angle the new zero position
+    fn reset_position(&mut self) -> Result<(), EncoderError> {
+        let raw = self.read_raw_angle()?;
+        self.zero_offset = raw;
+        self.last_angle.set(0);
+        self.turns.set(0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{turns_delta, AS5600};
+    use crate::common::encoder::{Encoder, EncoderPositionType};
+    use crate::common::i2c::{I2CErrors, I2CHandle, I2cHandleType};
+    use std::sync::{Arc, Mutex};
+
+    struct FakeAS5600I2C {
+        raw_angle: Arc<Mutex<u16>>,
+    }
+
+    impl I2CHandle for FakeAS5600I2C {
+        fn name(&self) -> String {
+            "fake_as5600".to_string()
+        }
+        fn write_read_i2c(
+            &mut self,
+            _address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), I2CErrors> {
+            match bytes[0] {
+                0x0B => buffer[0] = 0x20,
+                0x0C => buffer.copy_from_slice(&self.raw_angle.lock().unwrap().to_be_bytes()),
+                _ => return Err(I2CErrors::I2CInvalidArgument("unexpected register")),
+            }
+            Ok(())
+        }
+    }
+
+    #[test_log::test]
+    fn test_turns_delta() {
+        assert_eq!(turns_delta(100, 200), 0);
+        assert_eq!(turns_delta(200, 100), 0);
+        assert_eq!(turns_delta(4000, 100), 1);
+        assert_eq!(turns_delta(100, 4000), -1);
+    }
+
+    #[test_log::test]
+    fn test_multi_turn_position() {
+        let raw_angle = Arc::new(Mutex::new(1024_u16));
+        let i2c: I2cHandleType = Arc::new(Mutex::new(FakeAS5600I2C {
+            raw_angle: raw_angle.clone(),
+        }));
+        // zero offset of 90 degrees
+        let mut enc = AS5600::new(i2c, 0x36, 90.0, true).unwrap();
+        let pos = enc.get_position(EncoderPositionType::DEGREES).unwrap();
+        assert_eq!(pos.value, 0.0);
+
+        // full turn forwards in steps of less than half a turn, the position doesn't jump
+        // when the raw angle wraps around
+        for (raw, degrees) in [(2048_u16, 90.0), (3072, 180.0), (0, 270.0), (1024, 360.0)] {
+            *raw_angle.lock().unwrap() = raw;
+            let pos = enc.get_position(EncoderPositionType::DEGREES).unwrap();
+            assert_eq!(pos.value, degrees);
+        }
+        let pos = enc.get_position(EncoderPositionType::DEGREES).unwrap();
+        assert_eq!(pos.value, 360.0);
+        let pos = enc.get_position(EncoderPositionType::TICKS).unwrap();
+        assert_eq!(pos.value, 4096.0);
+
+        // back past the zero position
+        for (raw, degrees) in [(0_u16, 270.0), (3072, 180.0)] {
+            *raw_angle.lock().unwrap() = raw;
+            let pos = enc.get_position(EncoderPositionType::DEGREES).unwrap();
+            assert_eq!(pos.value, degrees);
+        }
+        let pos = enc.get_position(EncoderPositionType::DEGREES).unwrap();
+        assert_eq!(pos.value, 180.0);
+
+        enc.reset_position().unwrap();
+        let pos = enc.get_position(EncoderPositionType::DEGREES).unwrap();
+        assert_eq!(pos.value, 0.0);
+    }
+
+    #[test_log::test]
+    fn test_single_turn_position() {
+        let raw_angle = Arc::new(Mutex::new(0_u16));
+        let i2c: I2cHandleType = Arc::new(Mutex::new(FakeAS5600I2C {
+            raw_angle: raw_angle.clone(),
+        }));
+        let enc = AS5600::new(i2c, 0x36, 0.0, false).unwrap();
+        for raw in [1024_u16, 2048, 3072, 0, 1024] {
+            *raw_angle.lock().unwrap() = raw;
+            let _ = enc.get_position(EncoderPositionType::DEGREES).unwrap();
+        }
+        let pos = enc.get_position(EncoderPositionType::DEGREES).unwrap();
+        assert_eq!(pos.value, 90.0);
+        assert!(AS5600::new(
+            Arc::new(Mutex::new(FakeAS5600I2C { raw_angle })),
+            0x36,
+            360.0,
+            false
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! Read only user assets (configuration blobs, calibration tables...) available to components
+//! when the `assets` feature is enabled. On the esp32 they are read from an `assets` data
+//! partition holding an asset image, which `micro-rdk-installer create-assets-partition` builds
+//! from a host directory. The partition has to be declared in the partition table of the
+//! project, for example:
+//!
+//! ```text
+//! assets,   data, 0x40, 0x300000, 0x100000,
+//! ```
+//!
+//! The image starts with a table of contents followed by the content of every file, all
+//! integers are little endian:
+//!
+//! ```text
+//! magic "MRDKASST" | version: u16 | count: u16
+//! count * (name_len: u8 | name | offset: u32 | len: u32 | crc32: u32)
+//! file contents
+//! ```
+//!
+//! Components look assets up by their path relative to the directory the image was built from:
+//!
+//! ```ignore
+//! if let Some(assets) = assets() {
+//!     let table: Vec<f64> = assets.read_json("thermistor/table.json")?;
+//! }
+//! ```
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+pub const ASSET_IMAGE_MAGIC: &[u8; 8] = b"MRDKASST";
+pub const ASSET_IMAGE_VERSION: u16 = 1;
+const HEADER_LEN: u32 = 12;
+
+static ASSETS: OnceLock<Box<dyn AssetStore>> = OnceLock::new();
+
+#[derive(Debug, Error)]
+pub enum AssetError {
+    #[error("asset {0} not found")]
+    NotFound(String),
+    #[error("invalid asset image: {0}")]
+    InvalidImage(&'static str),
+    #[error("asset {0} is corrupted")]
+    Corrupted(String),
+    #[error("failed to read flash, error {0}")]
+    FlashReadError(i32),
+    #[error("assets were already loaded")]
+    AlreadyLoaded,
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// Read access to the assets available to components
+pub trait AssetStore: Send + Sync {
+    /// Names of all the assets
+    fn names(&self) -> Vec<String>;
+    /// Content of the asset `name`
+    fn read(&self, name: &str) -> Result<Vec<u8>, AssetError>;
+}
+
+impl dyn AssetStore {
+    pub fn read_to_string(&self, name: &str) -> Result<String, AssetError> {
+        String::from_utf8(self.read(name)?).map_err(|_| AssetError::Corrupted(name.to_owned()))
+    }
+
+    pub fn read_json<T: DeserializeOwned>(&self, name: &str) -> Result<T, AssetError> {
+        Ok(serde_json::from_slice(&self.read(name)?)?)
+    }
+}
+
+/// Makes `store` the assets of the device, can only be done once
+pub fn load_assets(store: impl AssetStore + 'static) -> Result<(), AssetError> {
+    ASSETS
+        .set(Box::new(store))
+        .map_err(|_| AssetError::AlreadyLoaded)
+}
+
+/// Assets of the device, `None` when none were loaded
+pub fn assets() -> Option<&'static dyn AssetStore> {
+    ASSETS.get().map(|store| store.as_ref())
+}
+
+/// Random access storage an asset image is read from
+pub trait AssetSource: Send + Sync {
+    fn read_at(&self, offset: u32, buf: &mut [u8]) -> Result<(), AssetError>;
+}
+
+impl AssetSource for Vec<u8> {
+    fn read_at(&self, offset: u32, buf: &mut [u8]) -> Result<(), AssetError> {
+        let start = offset as usize;
+        let bytes = self
+            .get(start..start + buf.len())
+            .ok_or(AssetError::InvalidImage("read past the end of the image"))?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+struct AssetEntry {
+    name: String,
+    offset: u32,
+    len: u32,
+    crc: u32,
+}
+
+/// Assets stored in an asset image
+pub struct AssetImage<S> {
+    source: S,
+    entries: Vec<AssetEntry>,
+}
+
+impl<S: AssetSource> AssetImage<S> {
+    /// Reads the table of contents of the image held by `source`
+    pub fn open(source: S) -> Result<Self, AssetError> {
+        let mut header = [0_u8; HEADER_LEN as usize];
+        source.read_at(0, &mut header)?;
+        if &header[..8] != ASSET_IMAGE_MAGIC {
+            return Err(AssetError::InvalidImage("bad magic"));
+        }
+        if u16::from_le_bytes([header[8], header[9]]) != ASSET_IMAGE_VERSION {
+            return Err(AssetError::InvalidImage("unsupported version"));
+        }
+        let count = u16::from_le_bytes([header[10], header[11]]);
+
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut offset = HEADER_LEN;
+        for _ in 0..count {
+            let mut name_len = [0_u8; 1];
+            source.read_at(offset, &mut name_len)?;
+            let mut entry = vec![0_u8; name_len[0] as usize + 12];
+            source.read_at(offset + 1, &mut entry)?;
+            let (name, fields) = entry.split_at(name_len[0] as usize);
+            let field =
+                |idx: usize| u32::from_le_bytes(fields[idx * 4..(idx + 1) * 4].try_into().unwrap());
+            entries.push(AssetEntry {
+                name: String::from_utf8(name.to_vec())
+                    .map_err(|_| AssetError::InvalidImage("asset name is not utf8"))?,
+                offset: field(0),
+                len: field(1),
+                crc: field(2),
+            });
+            offset += 1 + entry.len() as u32;
+        }
+        Ok(Self { source, entries })
+    }
+}
+
+impl<S: AssetSource> AssetStore for AssetImage<S> {
+    fn names(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect()
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, AssetError> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| AssetError::NotFound(name.to_owned()))?;
+        let mut content = vec![0_u8; entry.len as usize];
+        self.source.read_at(entry.offset, &mut content)?;
+        if crc32fast::hash(&content) != entry.crc {
+            return Err(AssetError::Corrupted(name.to_owned()));
+        }
+        Ok(content)
+    }
+}
+
+/// Assets read from a directory, handy on native or to serve files from an SD card
+pub struct DirectoryAssets {
+    root: PathBuf,
+}
+
+impl DirectoryAssets {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn collect_names(&self, dir: &Path, names: &mut Vec<String>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                self.collect_names(&path, names);
+            } else if let Ok(name) = path.strip_prefix(&self.root) {
+                names.push(name.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+}
+
+impl AssetStore for DirectoryAssets {
+    fn names(&self) -> Vec<String> {
+        let mut names = vec![];
+        self.collect_names(&self.root, &mut names);
+        names.sort();
+        names
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, AssetError> {
+        let path = Path::new(name);
+        // assets can't escape the directory
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(AssetError::NotFound(name.to_owned()));
+        }
+        fs::read(self.root.join(path)).map_err(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => AssetError::NotFound(name.to_owned()),
+            _ => err.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AssetError, AssetImage, AssetStore, DirectoryAssets, ASSET_IMAGE_MAGIC, ASSET_IMAGE_VERSION,
+    };
+
+    fn image(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let toc_len: usize = files.iter().map(|(name, _)| name.len() + 13).sum();
+        let mut image = ASSET_IMAGE_MAGIC.to_vec();
+        image.extend_from_slice(&ASSET_IMAGE_VERSION.to_le_bytes());
+        image.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        let mut offset = (12 + toc_len) as u32;
+        for (name, content) in files {
+            image.push(name.len() as u8);
+            image.extend_from_slice(name.as_bytes());
+            image.extend_from_slice(&offset.to_le_bytes());
+            image.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            image.extend_from_slice(&crc32fast::hash(content).to_le_bytes());
+            offset += content.len() as u32;
+        }
+        for (_, content) in files {
+            image.extend_from_slice(content);
+        }
+        image
+    }
+
+    #[test_log::test]
+    fn test_asset_image() {
+        let bytes = image(&[("cal/imu.json", b"[1.0,2.5]"), ("blob.bin", &[0, 1, 2])]);
+        let assets: Box<dyn AssetStore> = Box::new(AssetImage::open(bytes.clone()).unwrap());
+        assert_eq!(assets.names(), vec!["cal/imu.json", "blob.bin"]);
+        assert_eq!(assets.read("blob.bin").unwrap(), vec![0, 1, 2]);
+        assert_eq!(
+            assets.read_json::<Vec<f64>>("cal/imu.json").unwrap(),
+            vec![1.0, 2.5]
+        );
+        assert!(matches!(
+            assets.read("missing"),
+            Err(AssetError::NotFound(_))
+        ));
+
+        let mut corrupted = bytes;
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xFF;
+        let assets = AssetImage::open(corrupted).unwrap();
+        assert!(matches!(
+            assets.read("blob.bin"),
+            Err(AssetError::Corrupted(_))
+        ));
+
+        assert!(matches!(
+            AssetImage::open(vec![0xFF; 64]),
+            Err(AssetError::InvalidImage(_))
+        ));
+    }
+
+    #[test_log::test]
+    fn test_directory_assets() {
+        let root = std::env::temp_dir().join(format!("micro-rdk-assets-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("cal")).unwrap();
+        std::fs::write(root.join("cal/mag.json"), "{}").unwrap();
+        std::fs::write(root.join("table.csv"), "1,2").unwrap();
+
+        let assets: Box<dyn AssetStore> = Box::new(DirectoryAssets::new(&root));
+        assert_eq!(assets.names(), vec!["cal/mag.json", "table.csv"]);
+        assert_eq!(assets.read_to_string("table.csv").unwrap(), "1,2");
+        assert!(matches!(
+            assets.read("../table.csv"),
+            Err(AssetError::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
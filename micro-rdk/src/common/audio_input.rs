@@ -0,0 +1,314 @@
+//! Audio input components capture 16-bit PCM samples from a microphone.
+//!
+//! Captured audio is exposed through DoCommand:
+//! - `{"read_chunk": {"max_samples": 1600}}` returns the oldest buffered samples, base64 encoded
+//!   as little endian 16-bit PCM, along with their sound level
+//! - `{"sound_level": {}}` returns the sound level of the buffered samples without consuming them
+//!
+//! ```json
+//! {
+//!     "data": "AAD//wEA...",
+//!     "samples": 1600,
+//!     "sample_rate": 16000,
+//!     "level_dbfs": -42.5
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::{engine::general_purpose, Engine};
+use thiserror::Error;
+
+use super::{
+    board::BoardError,
+    config::AttributeError,
+    generic::{DoCommand, GenericError},
+    status::{Status, StatusError},
+};
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
+use crate::google::protobuf::{value::Kind, Struct, Value};
+use crate::proto::component::audio_input;
+
+pub static COMPONENT_NAME: &str = "audio_input";
+
+/// Number of samples returned by `read_chunk` when `max_samples` isn't given
+pub const DEFAULT_CHUNK_SAMPLES: usize = 1024;
+
+#[derive(Debug, Error)]
+pub enum AudioInputError {
+    #[error(transparent)]
+    AudioInputBoardError(#[from] BoardError),
+    #[error("config error {0}")]
+    AudioInputConfigurationError(&'static str),
+    #[error(transparent)]
+    AudioInputConfigAttributeError(#[from] AttributeError),
+    #[error("audio capture failed {0}")]
+    AudioInputCaptureError(i32),
+}
+
+impl GrpcStatusHint for AudioInputError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::AudioInputBoardError(err) => err.grpc_error(),
+            Self::AudioInputConfigurationError(_) => GrpcError::RpcFailedPrecondition,
+            Self::AudioInputConfigAttributeError(err) => err.grpc_error(),
+            Self::AudioInputCaptureError(_) => GrpcError::RpcInternal,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioProperties {
+    pub channel_count: u32,
+    pub sample_rate: u32,
+    /// Time between the capture of a sample and its availability
+    pub latency: Duration,
+}
+
+impl From<AudioProperties> for audio_input::v1::PropertiesResponse {
+    fn from(props: AudioProperties) -> Self {
+        Self {
+            channel_count: props.channel_count,
+            latency: Some(crate::google::protobuf::Duration {
+                seconds: props.latency.as_secs() as i64,
+                nanos: props.latency.subsec_nanos() as i32,
+            }),
+            sample_rate: props.sample_rate,
+            sample_size: 16,
+            is_big_endian: false,
+            is_float: false,
+            is_interleaved: true,
+        }
+    }
+}
+
+pub trait AudioInput: Status + DoCommand {
+    fn get_properties(&self) -> AudioProperties;
+
+    /// Removes and returns up to `max_samples` of the oldest buffered samples
+    fn read_samples(&mut self, max_samples: usize) -> Result<Vec<i16>, AudioInputError>;
+
+    /// Returns the buffered samples without consuming them
+    fn peek_samples(&self) -> Result<Vec<i16>, AudioInputError>;
+}
+
+pub type AudioInputType = Arc<Mutex<dyn AudioInput>>;
+
+impl<L> AudioInput for Mutex<L>
+where
+    L: ?Sized + AudioInput,
+{
+    fn get_properties(&self) -> AudioProperties {
+        self.lock().unwrap().get_properties()
+    }
+    fn read_samples(&mut self, max_samples: usize) -> Result<Vec<i16>, AudioInputError> {
+        self.get_mut().unwrap().read_samples(max_samples)
+    }
+    fn peek_samples(&self) -> Result<Vec<i16>, AudioInputError> {
+        self.lock().unwrap().peek_samples()
+    }
+}
+
+impl<A> AudioInput for Arc<Mutex<A>>
+where
+    A: ?Sized + AudioInput,
+{
+    fn get_properties(&self) -> AudioProperties {
+        self.lock().unwrap().get_properties()
+    }
+    fn read_samples(&mut self, max_samples: usize) -> Result<Vec<i16>, AudioInputError> {
+        self.lock().unwrap().read_samples(max_samples)
+    }
+    fn peek_samples(&self) -> Result<Vec<i16>, AudioInputError> {
+        self.lock().unwrap().peek_samples()
+    }
+}
+
+/// Serializes samples as little endian 16-bit PCM
+pub fn pcm16_le_bytes(samples: &[i16]) -> Vec<u8> {
+    samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+}
+
+/// RMS level of the samples relative to full scale, -inf for silence
+pub fn sound_level_dbfs(samples: &[i16]) -> f64 {
+    if samples.is_empty() {
+        return f64::NEG_INFINITY;
+    }
+    let sum: f64 = samples.iter().map(|s| (*s as f64).powi(2)).sum();
+    let rms = (sum / samples.len() as f64).sqrt();
+    20.0 * (rms / i16::MAX as f64).log10()
+}
+
+fn number(value: f64) -> Value {
+    Value {
+        kind: Some(Kind::NumberValue(value)),
+    }
+}
+
+/// Handles the audio input DoCommand commands described in the module documentation,
+/// components call it from their [DoCommand] implementation
+pub fn audio_do_command(
+    input: &mut dyn AudioInput,
+    command_struct: Option<Struct>,
+) -> Result<Option<Struct>, GenericError> {
+    let command = command_struct.ok_or(GenericError::InvalidArgument("missing command"))?;
+    let props = input.get_properties();
+    let mut fields = HashMap::new();
+    if let Some(value) = command.fields.get("read_chunk") {
+        let max_samples = match &value.kind {
+            Some(Kind::StructValue(args)) => match args.fields.get("max_samples") {
+                Some(Value {
+                    kind: Some(Kind::NumberValue(n)),
+                }) if *n >= 1.0 => *n as usize,
+                Some(_) => return Err(GenericError::InvalidArgument("max_samples")),
+                None => DEFAULT_CHUNK_SAMPLES,
+            },
+            _ => DEFAULT_CHUNK_SAMPLES,
+        };
+        let samples = input
+            .read_samples(max_samples)
+            .map_err(|e| GenericError::Other(e.into()))?;
+        fields.insert(
+            "data".to_string(),
+            Value {
+                kind: Some(Kind::StringValue(
+                    general_purpose::STANDARD.encode(pcm16_le_bytes(&samples)),
+                )),
+            },
+        );
+        fields.insert("samples".to_string(), number(samples.len() as f64));
+        fields.insert("sample_rate".to_string(), number(props.sample_rate as f64));
+        fields.insert(
+            "level_dbfs".to_string(),
+            number(sound_level_dbfs(&samples).max(-120.0)),
+        );
+    } else if command.fields.contains_key("sound_level") {
+        let samples = input
+            .peek_samples()
+            .map_err(|e| GenericError::Other(e.into()))?;
+        fields.insert(
+            "level_dbfs".to_string(),
+            number(sound_level_dbfs(&samples).max(-120.0)),
+        );
+        fields.insert("samples".to_string(), number(samples.len() as f64));
+    } else {
+        return Err(GenericError::MethodUnimplemented(
+            "unknown audio input command",
+        ));
+    }
+    Ok(Some(Struct { fields }))
+}
+
+/// Audio input returning a fixed set of samples
+pub struct FakeAudioInput {
+    samples: Vec<i16>,
+}
+
+impl FakeAudioInput {
+    pub fn new(samples: Vec<i16>) -> Self {
+        Self { samples }
+    }
+}
+
+impl Status for FakeAudioInput {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(None)
+    }
+}
+
+impl DoCommand for FakeAudioInput {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        audio_do_command(self, command_struct)
+    }
+}
+
+impl AudioInput for FakeAudioInput {
+    fn get_properties(&self) -> AudioProperties {
+        AudioProperties {
+            channel_count: 1,
+            sample_rate: 16000,
+            latency: Duration::from_millis(10),
+        }
+    }
+    fn read_samples(&mut self, max_samples: usize) -> Result<Vec<i16>, AudioInputError> {
+        let n = max_samples.min(self.samples.len());
+        Ok(self.samples.drain(..n).collect())
+    }
+    fn peek_samples(&self) -> Result<Vec<i16>, AudioInputError> {
+        Ok(self.samples.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str, args: HashMap<String, Value>) -> Option<Struct> {
+        Some(Struct {
+            fields: HashMap::from([(
+                name.to_string(),
+                Value {
+                    kind: Some(Kind::StructValue(Struct { fields: args })),
+                },
+            )]),
+        })
+    }
+
+    #[test_log::test]
+    fn test_sound_level() {
+        assert_eq!(pcm16_le_bytes(&[1, -2]), vec![1, 0, 0xfe, 0xff]);
+        assert_eq!(sound_level_dbfs(&[]), f64::NEG_INFINITY);
+        assert!(sound_level_dbfs(&[i16::MAX, -i16::MAX]).abs() < 1e-9);
+        let half = sound_level_dbfs(&[i16::MAX / 2; 8]);
+        assert!((half + 6.02).abs() < 0.01);
+    }
+
+    #[test_log::test]
+    fn test_audio_do_command() {
+        let mut input = FakeAudioInput::new(vec![100, -100, 200, -200]);
+
+        let res = input
+            .do_command(command("sound_level", HashMap::new()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.fields["samples"].kind, Some(Kind::NumberValue(4.0)));
+
+        let res = input
+            .do_command(command(
+                "read_chunk",
+                HashMap::from([("max_samples".to_string(), number(3.0))]),
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.fields["samples"].kind, Some(Kind::NumberValue(3.0)));
+        assert_eq!(
+            res.fields["sample_rate"].kind,
+            Some(Kind::NumberValue(16000.0))
+        );
+        let data = match &res.fields["data"].kind {
+            Some(Kind::StringValue(data)) => general_purpose::STANDARD.decode(data).unwrap(),
+            _ => panic!("data should be a string"),
+        };
+        assert_eq!(data, pcm16_le_bytes(&[100, -100, 200]));
+        assert_eq!(input.samples, vec![-200]);
+
+        let res = input
+            .do_command(command("read_chunk", HashMap::new()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.fields["samples"].kind, Some(Kind::NumberValue(1.0)));
+
+        assert!(input
+            .do_command(command(
+                "read_chunk",
+                HashMap::from([("max_samples".to_string(), number(0.0))]),
+            ))
+            .is_err());
+        assert!(input.do_command(command("record", HashMap::new())).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+//! Authorization of the requests of local (HTTP2) connections.
+//!
+//! API keys are configured in the `api-key` handler of the `auth` section of the robot config.
+//! The `keys` listed in its attributes are operator keys, giving full control of the robot, the
+//! `viewer_keys` are viewer keys, only allowed to call the RPCs reading the state of the robot
+//! (`Get*`, `Is*`, `Read*`, streams, ...).
+//!
+//! ```json
+//! {
+//!     "auth": {
+//!         "handlers": [
+//!             {
+//!                 "type": "api-key",
+//!                 "config": {
+//!                     "keys": ["<operator key>"],
+//!                     "viewer_keys": ["<viewer key>"]
+//!                 }
+//!             }
+//!         ]
+//!     }
+//! }
+//! ```
+//!
+//! A client authenticates with `AuthService/Authenticate` and an `api-key` credential. The access
+//! token it gets back is then sent as an `authorization: Bearer <token>` header with every RPC, the
+//! connection it authenticated on also keeps its scope for the RPCs sent without a token. Without
+//! an `api-key` handler every local connection has full control, as before. WebRTC connections
+//! are authenticated by app during signaling and are not restricted.
+//!
+//! The HTTP routes of the JSON endpoint take the same `authorization: Bearer <token>` header, an
+//! API key can also be sent directly as the bearer token so that scrapers don't need to
+//! authenticate first.
+
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use super::grpc::{GrpcError, GrpcStatusHint};
+use super::secret::SecretString;
+use crate::google::protobuf::{value::Kind, Struct};
+use crate::proto::{
+    app::v1::{ConfigResponse, CredentialsType},
+    rpc::v1::Credentials,
+};
+
+/// Type of the credentials of an `AuthenticateRequest` checked against the configured keys
+pub const API_KEY_CREDENTIALS: &str = "api-key";
+/// Access tokens remembered at once, the least recently used one is forgotten past this
+const MAX_ACCESS_TOKENS: usize = 16;
+/// An access token not used for this long is forgotten
+const ACCESS_TOKEN_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// What a client is allowed to do, an operator can do everything a viewer can
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    /// Read-only access to the state of the robot
+    Viewer,
+    /// Full control of the robot
+    Operator,
+}
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("credentials of type {0:?} are not supported")]
+    UnsupportedCredentials(String),
+    #[error("invalid credentials")]
+    InvalidCredentials,
+    #[error("missing credentials")]
+    MissingCredentials,
+    #[error("operator credentials are required")]
+    InsufficientScope,
+}
+
+impl GrpcStatusHint for AuthError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::UnsupportedCredentials(_) => GrpcError::RpcInvalidArgument,
+            Self::InsufficientScope => GrpcError::RpcPermissionDenied,
+            _ => GrpcError::RpcUnauthenticated,
+        }
+    }
+}
+
+/// Scope needed to call the RPC `method`, `None` for the RPCs open to unauthenticated clients
+pub fn required_scope(method: &str) -> Option<Scope> {
+    if method == "/proto.rpc.v1.AuthService/Authenticate" {
+        return None;
+    }
+    let name = method.rsplit('/').next().unwrap_or(method);
+    let reads_state = ["Get", "Is", "Read", "Stream", "Tail"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        || matches!(
+            name,
+            "PWM"
+                | "PWMFrequency"
+                | "Status"
+                | "ResourceNames"
+                | "ResourceRPCSubtypes"
+                | "FrameSystemConfig"
+                | "Properties"
+                | "RenderFrame"
+        );
+    Some(if reads_state {
+        Scope::Viewer
+    } else {
+        Scope::Operator
+    })
+}
+
+#[derive(Debug)]
+struct AccessToken {
+    token: SecretString,
+    scope: Scope,
+    last_used: Instant,
+}
+
+/// Checks that a client authenticated with `scope`, `None` when it has no valid credentials, may
+/// use something needing `required`
+pub fn check_scope(scope: Option<Scope>, required: Scope) -> Result<(), AuthError> {
+    match scope {
+        Some(scope) if scope >= required => Ok(()),
+        Some(_) => Err(AuthError::InsufficientScope),
+        None => Err(AuthError::MissingCredentials),
+    }
+}
+
+/// The API keys of the robot and the access tokens handed out for them
+#[derive(Debug, Default)]
+pub struct AuthPolicy {
+    keys: Vec<(SecretString, Scope)>,
+    tokens: RefCell<Vec<AccessToken>>,
+}
+
+impl AuthPolicy {
+    pub fn new(operator_keys: Vec<String>, viewer_keys: Vec<String>) -> Self {
+        let keys = operator_keys
+            .into_iter()
+            .map(|key| (key, Scope::Operator))
+            .chain(viewer_keys.into_iter().map(|key| (key, Scope::Viewer)))
+            .filter(|(key, _)| !key.is_empty())
+            .map(|(key, scope)| (SecretString::new(key), scope))
+            .collect();
+        Self {
+            keys,
+            tokens: RefCell::default(),
+        }
+    }
+
+    /// Reads the keys of the `api-key` handler of the auth section of the robot config
+    pub fn from_config(config: &ConfigResponse) -> Self {
+        let handler = config
+            .config
+            .as_ref()
+            .and_then(|cfg| cfg.auth.as_ref())
+            .and_then(|auth| {
+                auth.handlers
+                    .iter()
+                    .find(|handler| handler.r#type == CredentialsType::ApiKey as i32)
+            })
+            .and_then(|handler| handler.config.as_ref());
+        match handler {
+            Some(attributes) => Self::new(
+                string_list(attributes, "keys"),
+                string_list(attributes, "viewer_keys"),
+            ),
+            None => Self::default(),
+        }
+    }
+
+    /// Whether keys are configured, connections are otherwise not restricted
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Checks `credentials` against the keys, returning a new access token and its scope
+    pub fn authenticate(
+        &self,
+        credentials: Option<&Credentials>,
+    ) -> Result<(String, Scope), AuthError> {
+        let credentials = credentials.ok_or(AuthError::MissingCredentials)?;
+        if credentials.r#type != API_KEY_CREDENTIALS {
+            return Err(AuthError::UnsupportedCredentials(
+                credentials.r#type.clone(),
+            ));
+        }
+        let scope = self
+            .key_scope(&credentials.payload)
+            .ok_or(AuthError::InvalidCredentials)?;
+        let token: String = (0..16)
+            .map(|_| format!("{:02x}", rand::random::<u8>()))
+            .collect();
+        let now = Instant::now();
+        let mut tokens = self.tokens.borrow_mut();
+        tokens.retain(|issued| now.duration_since(issued.last_used) < ACCESS_TOKEN_IDLE_TIMEOUT);
+        if tokens.len() == MAX_ACCESS_TOKENS {
+            let _ = tokens.remove(0);
+        }
+        tokens.push(AccessToken {
+            token: SecretString::new(token.clone()),
+            scope,
+            last_used: now,
+        });
+        Ok((token, scope))
+    }
+
+    /// Scope of the access token sent as the value of an `authorization` header
+    pub fn bearer_scope(&self, authorization: &str) -> Option<Scope> {
+        let token = authorization.strip_prefix("Bearer ")?;
+        let now = Instant::now();
+        let mut tokens = self.tokens.borrow_mut();
+        let mut found = None;
+        // every token is compared so the time taken doesn't tell which one matched
+        for (idx, issued) in tokens.iter().enumerate() {
+            if constant_time_eq(issued.token.expose().as_bytes(), token.as_bytes())
+                && now.duration_since(issued.last_used) < ACCESS_TOKEN_IDLE_TIMEOUT
+            {
+                found = Some(idx);
+            }
+        }
+        // the tokens are kept from the least to the most recently used
+        let mut issued = tokens.remove(found?);
+        issued.last_used = now;
+        let scope = issued.scope;
+        tokens.push(issued);
+        Some(scope)
+    }
+
+    /// Scope of the `authorization` header of an HTTP request, the bearer token being either an
+    /// access token or an API key
+    pub fn http_scope(&self, authorization: &str) -> Option<Scope> {
+        self.bearer_scope(authorization).or_else(|| {
+            authorization
+                .strip_prefix("Bearer ")
+                .and_then(|key| self.key_scope(key))
+        })
+    }
+
+    fn key_scope(&self, payload: &str) -> Option<Scope> {
+        self.keys
+            .iter()
+            .filter(|(key, _)| constant_time_eq(key.expose().as_bytes(), payload.as_bytes()))
+            .map(|(_, scope)| *scope)
+            .max()
+    }
+}
+
+/// Compares two secrets in a time only depending on their lengths
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn string_list(attributes: &Struct, key: &str) -> Vec<String> {
+    match attributes
+        .fields
+        .get(key)
+        .and_then(|value| value.kind.as_ref())
+    {
+        Some(Kind::ListValue(list)) => list
+            .values
+            .iter()
+            .filter_map(|value| match &value.kind {
+                Some(Kind::StringValue(s)) => Some(s.clone()),
+                _ => None,
+            })
+            .collect(),
+        Some(Kind::StringValue(s)) => vec![s.clone()],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_scope, constant_time_eq, required_scope, AuthError, AuthPolicy, Scope,
+        MAX_ACCESS_TOKENS,
+    };
+    use crate::proto::rpc::v1::Credentials;
+
+    fn api_key(key: &str) -> Credentials {
+        Credentials {
+            r#type: "api-key".to_owned(),
+            payload: key.to_owned(),
+        }
+    }
+
+    #[test_log::test]
+    fn test_required_scope() {
+        assert_eq!(
+            required_scope("/proto.rpc.v1.AuthService/Authenticate"),
+            None
+        );
+        for method in [
+            "/viam.component.motor.v1.MotorService/GetPosition",
+            "/viam.component.motor.v1.MotorService/IsMoving",
+            "/viam.component.board.v1.BoardService/PWM",
+            "/viam.component.board.v1.BoardService/ReadAnalogReader",
+            "/viam.component.board.v1.BoardService/StreamTicks",
+            "/viam.robot.v1.RobotService/ResourceNames",
+            "/viam.app.v1.RobotService/TailRobotPartLogs",
+        ] {
+            assert_eq!(required_scope(method), Some(Scope::Viewer), "{}", method);
+        }
+        for method in [
+            "/viam.component.motor.v1.MotorService/SetPower",
+            "/viam.component.motor.v1.MotorService/Stop",
+            "/viam.component.board.v1.BoardService/SetPWMFrequency",
+            "/viam.component.sensor.v1.SensorService/DoCommand",
+            "/viam.component.button.v1.ButtonService/Push",
+            "/viam.service.unknown.v1.UnknownService/Anything",
+        ] {
+            assert_eq!(required_scope(method), Some(Scope::Operator), "{}", method);
+        }
+    }
+
+    #[test_log::test]
+    fn test_auth_policy() {
+        assert!(!AuthPolicy::default().is_enabled());
+
+        let policy = AuthPolicy::new(vec!["op".to_owned()], vec!["view".to_owned()]);
+        assert!(policy.is_enabled());
+        assert!(matches!(
+            policy.authenticate(None),
+            Err(AuthError::MissingCredentials)
+        ));
+        assert!(matches!(
+            policy.authenticate(Some(&api_key("nope"))),
+            Err(AuthError::InvalidCredentials)
+        ));
+        assert!(matches!(
+            policy.authenticate(Some(&Credentials {
+                r#type: "robot-secret".to_owned(),
+                payload: "op".to_owned(),
+            })),
+            Err(AuthError::UnsupportedCredentials(_))
+        ));
+
+        let (operator, scope) = policy.authenticate(Some(&api_key("op"))).unwrap();
+        assert_eq!(scope, Scope::Operator);
+        let (viewer, scope) = policy.authenticate(Some(&api_key("view"))).unwrap();
+        assert_eq!(scope, Scope::Viewer);
+        assert_eq!(
+            policy.bearer_scope(&format!("Bearer {}", operator)),
+            Some(Scope::Operator)
+        );
+        assert_eq!(
+            policy.bearer_scope(&format!("Bearer {}", viewer)),
+            Some(Scope::Viewer)
+        );
+        assert_eq!(policy.bearer_scope(&operator), None);
+        assert_eq!(policy.bearer_scope("Bearer esp32"), None);
+
+        // API keys are only accepted as bearer tokens on HTTP routes
+        assert_eq!(policy.bearer_scope("Bearer op"), None);
+        assert_eq!(policy.http_scope("Bearer op"), Some(Scope::Operator));
+        assert_eq!(policy.http_scope("Bearer view"), Some(Scope::Viewer));
+        assert_eq!(
+            policy.http_scope(&format!("Bearer {}", viewer)),
+            Some(Scope::Viewer)
+        );
+        assert_eq!(policy.http_scope("op"), None);
+        assert_eq!(policy.http_scope("Bearer nope"), None);
+
+        // a token in use is kept, the least recently used ones are forgotten
+        for _ in 0..MAX_ACCESS_TOKENS {
+            assert_eq!(
+                policy.bearer_scope(&format!("Bearer {}", operator)),
+                Some(Scope::Operator)
+            );
+            let _ = policy.authenticate(Some(&api_key("view"))).unwrap();
+        }
+        assert_eq!(
+            policy.bearer_scope(&format!("Bearer {}", operator)),
+            Some(Scope::Operator)
+        );
+        assert_eq!(policy.bearer_scope(&format!("Bearer {}", viewer)), None);
+    }
+
+    #[test_log::test]
+    fn test_check_scope() {
+        assert!(check_scope(Some(Scope::Operator), Scope::Viewer).is_ok());
+        assert!(check_scope(Some(Scope::Viewer), Scope::Viewer).is_ok());
+        assert!(matches!(
+            check_scope(Some(Scope::Viewer), Scope::Operator),
+            Err(AuthError::InsufficientScope)
+        ));
+        assert!(matches!(
+            check_scope(None, Scope::Viewer),
+            Err(AuthError::MissingCredentials)
+        ));
+    }
+
+    #[test_log::test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secrets"));
+    }
+}
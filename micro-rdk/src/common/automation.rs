@@ -0,0 +1,659 @@
+//! Local automations run actions on the components of the robot on a schedule or when a sensor
+//! reading crosses a threshold, without requiring a connected client. They are configured
+//! through a service of type `automation` in the robot's config:
+//!
+//! ```json
+//! {
+//!     "name": "automations",
+//!     "type": "automation",
+//!     "attributes": {
+//!         "automations": [
+//!             {
+//!                 "name": "water_plant",
+//!                 "at": "08:00",
+//!                 "when": { "sensor": "moisture", "reading": "millivolts", "below": 1200 },
+//!                 "action": { "type": "motor", "name": "pump", "go_for": { "rpm": 60, "revolutions": 10 } }
+//!             },
+//!             {
+//!                 "name": "open_vent",
+//!                 "when": { "sensor": "thermometer", "reading": "celsius", "above": 30 },
+//!                 "action": { "type": "servo", "name": "vent", "move_to": 90 }
+//!             },
+//!             {
+//!                 "name": "heartbeat",
+//!                 "every_secs": 60,
+//!                 "action": { "type": "generic", "name": "led", "do_command": { "blink": 3 } }
+//!             }
+//!         ]
+//!     }
+//! }
+//! ```
+//!
+//! An automation runs daily at the local time given by `at` (the clock of the device has to be
+//! set), or every `every_secs` seconds. When `when` is set the action only runs if the condition
+//! is met at that time, and an automation without a schedule runs every time its condition
+//! becomes true. Actions can call `do_command` on motors, servos, sensors and generic
+//! components, `go_for` on motors and `move_to` on servos. Motors started by `go_for` are
+//! stopped once the requested revolutions are completed.
+
+use crate::google;
+use crate::proto::app::v1::ConfigResponse;
+
+use super::actuator::Actuator;
+use super::config::{AttributeError, Kind};
+use super::generic::{DoCommand, GenericError};
+use super::motor::{Motor, MotorError, MotorType};
+use super::robot::LocalRobot;
+use super::sensor::{Readings, SensorError};
+use super::servo::{Servo, ServoError};
+
+use async_io::Timer;
+use chrono::{Local, NaiveDate, NaiveDateTime, Timelike};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+pub static SERVICE_TYPE: &str = "automation";
+
+// how often schedules and conditions are evaluated
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Error)]
+pub enum AutomationError {
+    #[error("automation config error: {0}")]
+    ConfigError(&'static str),
+    #[error(transparent)]
+    ConfigAttributeError(#[from] AttributeError),
+    #[error("automation resource {0} not found")]
+    ResourceNotFound(String),
+    #[error("automation sensor {0} has no numeric reading {1}")]
+    ReadingNotFound(String, String),
+    #[error(transparent)]
+    MotorError(#[from] MotorError),
+    #[error(transparent)]
+    ServoError(#[from] ServoError),
+    #[error(transparent)]
+    SensorError(#[from] SensorError),
+    #[error(transparent)]
+    GenericError(#[from] GenericError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Schedule {
+    Daily { hour: u32, minute: u32 },
+    Interval(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Condition {
+    sensor: String,
+    reading: String,
+    below: Option<f64>,
+    above: Option<f64>,
+}
+
+impl Condition {
+    fn is_met(&self, value: f64) -> bool {
+        self.below.map_or(true, |below| value < below)
+            && self.above.map_or(true, |above| value > above)
+    }
+}
+
+impl TryFrom<&Kind> for Condition {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let sensor = value
+            .get("sensor")?
+            .ok_or(AttributeError::KeyNotFound("sensor".to_string()))?
+            .try_into()?;
+        let reading = value
+            .get("reading")?
+            .ok_or(AttributeError::KeyNotFound("reading".to_string()))?
+            .try_into()?;
+        let below = value.get("below")?.map(f64::try_from).transpose()?;
+        let above = value.get("above")?.map(f64::try_from).transpose()?;
+        if below.is_none() && above.is_none() {
+            return Err(AttributeError::KeyNotFound("below".to_string()));
+        }
+        Ok(Condition {
+            sensor,
+            reading,
+            below,
+            above,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Action {
+    DoCommand {
+        r#type: String,
+        name: String,
+        command: google::protobuf::Struct,
+    },
+    GoFor {
+        name: String,
+        rpm: f64,
+        revolutions: f64,
+    },
+    MoveTo {
+        name: String,
+        angle_deg: u32,
+    },
+}
+
+impl TryFrom<&Kind> for Action {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let r#type: String = value
+            .get("type")?
+            .ok_or(AttributeError::KeyNotFound("type".to_string()))?
+            .try_into()?;
+        let name: String = value
+            .get("name")?
+            .ok_or(AttributeError::KeyNotFound("name".to_string()))?
+            .try_into()?;
+        if let Some(command) = value.get("do_command")? {
+            return match google::protobuf::Value::from(command).kind {
+                Some(google::protobuf::value::Kind::StructValue(command)) => {
+                    Ok(Action::DoCommand {
+                        r#type,
+                        name,
+                        command,
+                    })
+                }
+                _ => Err(AttributeError::ConversionImpossibleError),
+            };
+        }
+        match r#type.as_str() {
+            "motor" => {
+                let go_for = value
+                    .get("go_for")?
+                    .ok_or(AttributeError::KeyNotFound("go_for".to_string()))?;
+                Ok(Action::GoFor {
+                    name,
+                    rpm: go_for
+                        .get("rpm")?
+                        .ok_or(AttributeError::KeyNotFound("rpm".to_string()))?
+                        .try_into()?,
+                    revolutions: go_for
+                        .get("revolutions")?
+                        .ok_or(AttributeError::KeyNotFound("revolutions".to_string()))?
+                        .try_into()?,
+                })
+            }
+            "servo" => Ok(Action::MoveTo {
+                name,
+                angle_deg: value
+                    .get("move_to")?
+                    .ok_or(AttributeError::KeyNotFound("move_to".to_string()))?
+                    .try_into()?,
+            }),
+            _ => Err(AttributeError::ConversionImpossibleError),
+        }
+    }
+}
+
+/// An element of the `automations` attribute of the automation service
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AutomationConfig {
+    name: String,
+    schedule: Option<Schedule>,
+    condition: Option<Condition>,
+    action: Action,
+}
+
+impl TryFrom<&Kind> for AutomationConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let name = value
+            .get("name")?
+            .ok_or(AttributeError::KeyNotFound("name".to_string()))?
+            .try_into()?;
+        let schedule = if let Some(at) = value.get("at")? {
+            let at: String = at.try_into()?;
+            let (hour, minute) = at
+                .split_once(':')
+                .ok_or(AttributeError::ConversionImpossibleError)?;
+            let (hour, minute) = (hour.parse::<u32>()?, minute.parse::<u32>()?);
+            if hour > 23 || minute > 59 {
+                return Err(AttributeError::ConversionImpossibleError);
+            }
+            Some(Schedule::Daily { hour, minute })
+        } else if let Some(every_secs) = value.get("every_secs")? {
+            let every_secs: u32 = every_secs.try_into()?;
+            if every_secs == 0 {
+                return Err(AttributeError::ConversionImpossibleError);
+            }
+            Some(Schedule::Interval(Duration::from_secs(every_secs as u64)))
+        } else {
+            None
+        };
+        let condition = value.get("when")?.map(Condition::try_from).transpose()?;
+        if schedule.is_none() && condition.is_none() {
+            return Err(AttributeError::KeyNotFound("when".to_string()));
+        }
+        let action = value
+            .get("action")?
+            .ok_or(AttributeError::KeyNotFound("action".to_string()))?
+            .try_into()?;
+        Ok(AutomationConfig {
+            name,
+            schedule,
+            condition,
+            action,
+        })
+    }
+}
+
+struct Automation {
+    config: AutomationConfig,
+    last_run_date: Option<NaiveDate>,
+    next_run: Option<Instant>,
+    condition_was_met: bool,
+}
+
+impl Automation {
+    fn new(config: AutomationConfig) -> Self {
+        Self {
+            config,
+            last_run_date: None,
+            next_run: None,
+            condition_was_met: false,
+        }
+    }
+
+    // returns whether the schedule of the automation is due, automations without a schedule
+    // are always due
+    fn is_due(&mut self, local_time: NaiveDateTime, now: Instant) -> bool {
+        match &self.config.schedule {
+            None => true,
+            Some(Schedule::Daily { hour, minute }) => {
+                let date = local_time.date();
+                if local_time.hour() == *hour
+                    && local_time.minute() == *minute
+                    && self.last_run_date != Some(date)
+                {
+                    self.last_run_date = Some(date);
+                    return true;
+                }
+                false
+            }
+            Some(Schedule::Interval(interval)) => match self.next_run {
+                Some(next_run) if now >= next_run => {
+                    self.next_run = Some(now + *interval);
+                    true
+                }
+                Some(_) => false,
+                None => {
+                    self.next_run = Some(now + *interval);
+                    false
+                }
+            },
+        }
+    }
+}
+
+/// Runs the automations configured for a robot
+pub struct AutomationEngine {
+    automations: Vec<Automation>,
+    robot: Arc<RwLock<LocalRobot>>,
+    // motors started by go_for, to be stopped at the given time
+    pending_stops: Vec<(Instant, MotorType)>,
+}
+
+impl AutomationEngine {
+    pub(crate) fn new(automations: Vec<AutomationConfig>, robot: Arc<RwLock<LocalRobot>>) -> Self {
+        Self {
+            automations: automations.into_iter().map(Automation::new).collect(),
+            robot,
+            pending_stops: vec![],
+        }
+    }
+
+    /// Returns the automation engine of the robot if an automation service is configured
+    pub fn from_robot_and_config(
+        cfg: &ConfigResponse,
+        robot: Arc<RwLock<LocalRobot>>,
+    ) -> Result<Option<Self>, AutomationError> {
+        let robot_config = cfg
+            .config
+            .as_ref()
+            .ok_or(AutomationError::ConfigError("missing robot config"))?;
+        let mut services = robot_config
+            .services
+            .iter()
+            .filter(|svc_cfg| svc_cfg.r#type == SERVICE_TYPE);
+        let svc_cfg = match services.next() {
+            Some(svc_cfg) => svc_cfg,
+            None => return Ok(None),
+        };
+        if services.next().is_some() {
+            return Err(AutomationError::ConfigError(
+                "multiple automation services configured",
+            ));
+        }
+        let attributes = svc_cfg
+            .attributes
+            .clone()
+            .ok_or(AutomationError::ConfigError("missing attributes"))?;
+        let attributes = Kind::try_from(google::protobuf::value::Kind::StructValue(attributes))?;
+        let automations: Vec<AutomationConfig> = attributes
+            .get("automations")?
+            .ok_or(AttributeError::KeyNotFound("automations".to_string()))?
+            .try_into()?;
+        Ok(Some(Self::new(automations, robot)))
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            self.run_inner(Local::now().naive_local(), Instant::now());
+            Timer::after(TICK_INTERVAL).await;
+        }
+    }
+
+    fn run_inner(&mut self, local_time: NaiveDateTime, now: Instant) {
+        let (due, pending): (Vec<_>, Vec<_>) = self
+            .pending_stops
+            .drain(..)
+            .partition(|(deadline, _)| now >= *deadline);
+        self.pending_stops = pending;
+        for (_, mut motor) in due {
+            if let Err(err) = motor.stop() {
+                log::error!("automation couldn't stop motor: {:?}", err);
+            }
+        }
+        for idx in 0..self.automations.len() {
+            if let Err(err) = self.run_automation(idx, local_time, now) {
+                log::error!(
+                    "automation {} failed: {:?}",
+                    self.automations[idx].config.name,
+                    err
+                );
+            }
+        }
+    }
+
+    fn run_automation(
+        &mut self,
+        idx: usize,
+        local_time: NaiveDateTime,
+        now: Instant,
+    ) -> Result<(), AutomationError> {
+        let automation = &mut self.automations[idx];
+        if !automation.is_due(local_time, now) {
+            return Ok(());
+        }
+        let run = match automation.config.condition.clone() {
+            None => true,
+            Some(condition) => {
+                let is_met = condition.is_met(self.read_condition_value(&condition)?);
+                let automation = &mut self.automations[idx];
+                let was_met = std::mem::replace(&mut automation.condition_was_met, is_met);
+                // without a schedule the action only runs when the condition becomes true
+                is_met && (automation.config.schedule.is_some() || !was_met)
+            }
+        };
+        if run {
+            log::info!("running automation {}", self.automations[idx].config.name);
+            let action = self.automations[idx].config.action.clone();
+            self.run_action(&action, now)?;
+        }
+        Ok(())
+    }
+
+    fn read_condition_value(&self, condition: &Condition) -> Result<f64, AutomationError> {
+        let mut sensor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_sensor_by_name(condition.sensor.clone())
+            .ok_or_else(|| AutomationError::ResourceNotFound(condition.sensor.clone()))?;
+        let readings = sensor.get_generic_readings()?;
+        match readings
+            .get(&condition.reading)
+            .and_then(|value| value.kind.as_ref())
+        {
+            Some(google::protobuf::value::Kind::NumberValue(value)) => Ok(*value),
+            _ => Err(AutomationError::ReadingNotFound(
+                condition.sensor.clone(),
+                condition.reading.clone(),
+            )),
+        }
+    }
+
+    fn run_action(&mut self, action: &Action, now: Instant) -> Result<(), AutomationError> {
+        let robot = self.robot.read().unwrap();
+        match action {
+            Action::DoCommand {
+                r#type,
+                name,
+                command,
+            } => {
+                let not_found = || AutomationError::ResourceNotFound(name.clone());
+                let command = Some(command.clone());
+                match r#type.as_str() {
+                    "motor" => robot
+                        .get_motor_by_name(name.clone())
+                        .ok_or_else(not_found)?
+                        .do_command(command)?,
+                    "servo" => robot
+                        .get_servo_by_name(name.clone())
+                        .ok_or_else(not_found)?
+                        .do_command(command)?,
+                    "sensor" => robot
+                        .get_sensor_by_name(name.clone())
+                        .ok_or_else(not_found)?
+                        .do_command(command)?,
+                    "generic" => robot
+                        .get_generic_component_by_name(name.clone())
+                        .ok_or_else(not_found)?
+                        .do_command(command)?,
+                    _ => {
+                        return Err(AutomationError::ConfigError(
+                            "do_command is supported on motor, servo, sensor and generic",
+                        ))
+                    }
+                };
+            }
+            Action::GoFor {
+                name,
+                rpm,
+                revolutions,
+            } => {
+                let mut motor = robot
+                    .get_motor_by_name(name.clone())
+                    .ok_or_else(|| AutomationError::ResourceNotFound(name.clone()))?;
+                if let Some(duration) = motor.go_for(*rpm, *revolutions)? {
+                    self.pending_stops.push((now + duration, motor));
+                }
+            }
+            Action::MoveTo { name, angle_deg } => {
+                robot
+                    .get_servo_by_name(name.clone())
+                    .ok_or_else(|| AutomationError::ResourceNotFound(name.clone()))?
+                    .move_to(*angle_deg)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, AutomationConfig, AutomationEngine, Schedule};
+    use crate::common::config::Kind;
+    use crate::common::motor::Motor;
+    use crate::common::registry::ComponentRegistry;
+    use crate::common::robot::LocalRobot;
+    use crate::google;
+    use crate::google::protobuf::Struct;
+    use crate::proto::app::v1::{ComponentConfig, ConfigResponse, RobotConfig, ServiceConfig};
+    use chrono::NaiveDate;
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+    use std::time::{Duration, Instant};
+
+    fn kind_struct(fields: Vec<(&str, Kind)>) -> Kind {
+        Kind::StructValue(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    fn component(name: &str, r#type: &str, fake_value: Option<f64>) -> ComponentConfig {
+        ComponentConfig {
+            name: name.to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: r#type.to_string(),
+            namespace: "rdk".to_string(),
+            attributes: Some(Struct {
+                fields: fake_value
+                    .map(|value| {
+                        HashMap::from([(
+                            "fake_value".to_string(),
+                            google::protobuf::Value {
+                                kind: Some(google::protobuf::value::Kind::NumberValue(value)),
+                            },
+                        )])
+                    })
+                    .unwrap_or_default(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test_log::test]
+    fn test_automation_config() {
+        let conf = kind_struct(vec![
+            ("name", Kind::StringValue("water".to_string())),
+            ("at", Kind::StringValue("08:30".to_string())),
+            (
+                "action",
+                kind_struct(vec![
+                    ("type", Kind::StringValue("motor".to_string())),
+                    ("name", Kind::StringValue("pump".to_string())),
+                    (
+                        "go_for",
+                        kind_struct(vec![
+                            ("rpm", Kind::NumberValue(60.0)),
+                            ("revolutions", Kind::NumberValue(10.0)),
+                        ]),
+                    ),
+                ]),
+            ),
+        ]);
+        let conf = AutomationConfig::try_from(&conf).unwrap();
+        assert_eq!(
+            conf.schedule,
+            Some(Schedule::Daily {
+                hour: 8,
+                minute: 30
+            })
+        );
+        assert_eq!(
+            conf.action,
+            Action::GoFor {
+                name: "pump".to_string(),
+                rpm: 60.0,
+                revolutions: 10.0
+            }
+        );
+
+        // automations need a schedule or a condition
+        let conf = kind_struct(vec![
+            ("name", Kind::StringValue("nothing".to_string())),
+            (
+                "action",
+                kind_struct(vec![
+                    ("type", Kind::StringValue("servo".to_string())),
+                    ("name", Kind::StringValue("vent".to_string())),
+                    ("move_to", Kind::NumberValue(90.0)),
+                ]),
+            ),
+        ]);
+        assert!(AutomationConfig::try_from(&conf).is_err());
+    }
+
+    #[test_log::test]
+    fn test_automation_engine() {
+        let automations = Kind::VecValue(vec![kind_struct(vec![
+            ("name", Kind::StringValue("water".to_string())),
+            ("at", Kind::StringValue("08:00".to_string())),
+            (
+                "when",
+                kind_struct(vec![
+                    ("sensor", Kind::StringValue("moisture".to_string())),
+                    ("reading", Kind::StringValue("fake_sensor".to_string())),
+                    ("below", Kind::NumberValue(50.0)),
+                ]),
+            ),
+            (
+                "action",
+                kind_struct(vec![
+                    ("type", Kind::StringValue("motor".to_string())),
+                    ("name", Kind::StringValue("pump".to_string())),
+                    (
+                        "go_for",
+                        kind_struct(vec![
+                            ("rpm", Kind::NumberValue(50.0)),
+                            ("revolutions", Kind::NumberValue(1.0)),
+                        ]),
+                    ),
+                ]),
+            ),
+        ])]);
+        let attributes =
+            match google::protobuf::Value::from(&kind_struct(vec![("automations", automations)]))
+                .kind
+            {
+                Some(google::protobuf::value::Kind::StructValue(attributes)) => attributes,
+                _ => unreachable!(),
+            };
+        let cfg = ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![
+                    component("pump", "motor", None),
+                    component("moisture", "sensor", Some(42.0)),
+                ],
+                services: vec![ServiceConfig {
+                    name: "automations".to_string(),
+                    r#type: "automation".to_string(),
+                    attributes: Some(attributes),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+        };
+        let robot =
+            LocalRobot::from_cloud_config(&cfg, Box::<ComponentRegistry>::default(), None).unwrap();
+        let robot = Arc::new(RwLock::new(robot));
+        let mut engine = AutomationEngine::from_robot_and_config(&cfg, robot.clone())
+            .unwrap()
+            .unwrap();
+        let mut pump = robot
+            .read()
+            .unwrap()
+            .get_motor_by_name("pump".to_string())
+            .unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let now = Instant::now();
+        engine.run_inner(day.and_hms_opt(7, 59, 0).unwrap(), now);
+        assert_eq!(pump.is_powered().unwrap(), (false, 0.0));
+
+        engine.run_inner(day.and_hms_opt(8, 0, 0).unwrap(), now);
+        assert_eq!(pump.is_powered().unwrap(), (true, 0.5));
+        assert_eq!(engine.pending_stops.len(), 1);
+
+        // the motor is stopped once go_for is complete, and the automation only runs once a day
+        engine.run_inner(
+            day.and_hms_opt(8, 0, 30).unwrap(),
+            now + Duration::from_secs(30),
+        );
+        assert_eq!(pump.is_powered().unwrap(), (false, 0.0));
+        assert!(engine.pending_stops.is_empty());
+    }
+}
@@ -4,15 +4,116 @@ use {super::actuator::ActuatorError, crate::google, log::*, std::collections::Ha
 
 use super::{config::AttributeError, generic::DoCommand, motor::MotorError};
 use crate::common::actuator::Actuator;
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 use crate::common::status::Status;
+use crate::google::protobuf::Struct;
 use crate::proto::common::v1::Vector3;
+use crate::proto::component::base::v1::GetPropertiesResponse;
+use async_io::Timer;
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use thiserror::Error;
 
+#[cfg(feature = "esp32")]
+use crate::esp32::exec::Esp32Executor;
+#[cfg(feature = "native")]
+use crate::native::exec::NativeExecutor;
+
+#[cfg(feature = "native")]
+type Executor = NativeExecutor;
+#[cfg(feature = "esp32")]
+type Executor = Esp32Executor;
+
 pub static COMPONENT_NAME: &str = "base";
 
+// sequence number of the last command each base received through the API
+static BASE_COMMANDS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+/// Records a command sent to the base `name`, cancelling a pending [stop_base_after]
+pub(crate) fn record_base_command(name: &str) -> u64 {
+    let mut commands = BASE_COMMANDS.lock().unwrap();
+    let seq = commands.entry(name.to_owned()).or_default();
+    *seq += 1;
+    *seq
+}
+
+/// Stops `base` once the motion it was just commanded elapsed, unless it received another
+/// command in the meantime
+pub(crate) fn stop_base_after(name: String, base: BaseType, duration: Duration) {
+    let seq = record_base_command(&name);
+    Executor::new()
+        .spawn(async move {
+            Timer::after(duration).await;
+            if BASE_COMMANDS.lock().unwrap().get(&name) != Some(&seq) {
+                return;
+            }
+            if let Err(err) = base.lock().unwrap().stop() {
+                log::error!("couldn't stop base {} after its motion: {}", name, err);
+            }
+        })
+        .detach();
+}
+
+/// Physical dimensions of a base, used by clients for motion planning
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BaseProperties {
+    pub width_meters: f64,
+    pub turning_radius_meters: f64,
+    pub wheel_circumference_meters: f64,
+}
+
+impl From<BaseProperties> for GetPropertiesResponse {
+    fn from(value: BaseProperties) -> Self {
+        GetPropertiesResponse {
+            width_meters: value.width_meters,
+            turning_radius_meters: value.turning_radius_meters,
+            wheel_circumference_meters: value.wheel_circumference_meters,
+        }
+    }
+}
+
 pub trait Base: Status + Actuator + DoCommand {
     fn set_power(&mut self, lin: &Vector3, ang: &Vector3) -> Result<(), BaseError>;
+    /// Sets the base's power as [Base::set_power], with implementation specific options
+    /// passed in `extra`
+    fn set_power_with_extra(
+        &mut self,
+        lin: &Vector3,
+        ang: &Vector3,
+        _extra: Option<Struct>,
+    ) -> Result<(), BaseError> {
+        self.set_power(lin, ang)
+    }
+    /// Returns the dimensions of the base
+    fn get_properties(&mut self) -> Result<BaseProperties, BaseError> {
+        Err(BaseError::BaseMethodUnimplemented("get_properties"))
+    }
+    /// Starts moving the base straight for `distance_mm` at `mm_per_sec`, if the motion is
+    /// time bound the duration after which the base should be stopped is returned.
+    fn move_straight(
+        &mut self,
+        _distance_mm: i64,
+        _mm_per_sec: f64,
+    ) -> Result<Option<Duration>, BaseError> {
+        Err(BaseError::BaseMethodUnimplemented("move_straight"))
+    }
+    /// Starts spinning the base in place by `angle_deg` at `degs_per_sec`, positive angles
+    /// turning left. If the motion is time bound the duration after which the base should be
+    /// stopped is returned.
+    fn spin(&mut self, _angle_deg: f64, _degs_per_sec: f64) -> Result<Option<Duration>, BaseError> {
+        Err(BaseError::BaseMethodUnimplemented("spin"))
+    }
+    /// Returns the linear velocity of the base in millimeters per second as measured by its
+    /// sensors, `y` pointing forward
+    fn get_linear_velocity(&mut self) -> Result<Vector3, BaseError> {
+        Err(BaseError::BaseMethodUnimplemented("get_linear_velocity"))
+    }
+    /// Returns the angular velocity of the base in degrees per second as measured by its
+    /// sensors, positive `z` turning left
+    fn get_angular_velocity(&mut self) -> Result<Vector3, BaseError> {
+        Err(BaseError::BaseMethodUnimplemented("get_angular_velocity"))
+    }
 }
 
 pub type BaseType = Arc<Mutex<dyn Base>>;
@@ -25,6 +126,28 @@ pub enum BaseError {
     BaseConfigAttributeError(#[from] AttributeError),
     #[error("config error: {0}")]
     BaseConfigError(&'static str),
+    #[error("config error: motors {0:?} not found")]
+    BaseMotorsNotFound(Vec<String>),
+    #[error("unimplemented: {0}")]
+    BaseMethodUnimplemented(&'static str),
+    #[error("base stopped by its watchdog, {0} wheel {1}")]
+    BaseWatchdogTripped(&'static str, &'static str),
+    #[error("spin timed out {0} degrees away from its target")]
+    BaseSpinIncomplete(f64),
+}
+
+impl GrpcStatusHint for BaseError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::BaseMotorError(err) => err.grpc_error(),
+            Self::BaseConfigAttributeError(err) => err.grpc_error(),
+            Self::BaseConfigError(_) => GrpcError::RpcFailedPrecondition,
+            Self::BaseMotorsNotFound(_) => GrpcError::RpcNotFound,
+            Self::BaseMethodUnimplemented(_) => GrpcError::RpcUnimplemented,
+            Self::BaseWatchdogTripped(_, _) => GrpcError::RpcFailedPrecondition,
+            Self::BaseSpinIncomplete(_) => GrpcError::RpcDeadlineExceeded,
+        }
+    }
 }
 
 // TODO(RSDK-5648) - Store power from set_power call on struct and register as "fake" model
@@ -38,6 +161,7 @@ impl FakeBase {
         FakeBase {}
     }
 }
+
 #[cfg(feature = "builtin-components")]
 impl Default for FakeBase {
     fn default() -> Self {
@@ -52,6 +176,37 @@ where
     fn set_power(&mut self, lin: &Vector3, ang: &Vector3) -> Result<(), BaseError> {
         self.get_mut().unwrap().set_power(lin, ang)
     }
+    fn set_power_with_extra(
+        &mut self,
+        lin: &Vector3,
+        ang: &Vector3,
+        extra: Option<Struct>,
+    ) -> Result<(), BaseError> {
+        self.get_mut()
+            .unwrap()
+            .set_power_with_extra(lin, ang, extra)
+    }
+    fn get_properties(&mut self) -> Result<BaseProperties, BaseError> {
+        self.get_mut().unwrap().get_properties()
+    }
+    fn move_straight(
+        &mut self,
+        distance_mm: i64,
+        mm_per_sec: f64,
+    ) -> Result<Option<Duration>, BaseError> {
+        self.get_mut()
+            .unwrap()
+            .move_straight(distance_mm, mm_per_sec)
+    }
+    fn spin(&mut self, angle_deg: f64, degs_per_sec: f64) -> Result<Option<Duration>, BaseError> {
+        self.get_mut().unwrap().spin(angle_deg, degs_per_sec)
+    }
+    fn get_linear_velocity(&mut self) -> Result<Vector3, BaseError> {
+        self.get_mut().unwrap().get_linear_velocity()
+    }
+    fn get_angular_velocity(&mut self) -> Result<Vector3, BaseError> {
+        self.get_mut().unwrap().get_angular_velocity()
+    }
 }
 
 impl<L> Base for Arc<Mutex<L>>
@@ -61,6 +216,33 @@ where
     fn set_power(&mut self, lin: &Vector3, ang: &Vector3) -> Result<(), BaseError> {
         self.lock().unwrap().set_power(lin, ang)
     }
+    fn set_power_with_extra(
+        &mut self,
+        lin: &Vector3,
+        ang: &Vector3,
+        extra: Option<Struct>,
+    ) -> Result<(), BaseError> {
+        self.lock().unwrap().set_power_with_extra(lin, ang, extra)
+    }
+    fn get_properties(&mut self) -> Result<BaseProperties, BaseError> {
+        self.lock().unwrap().get_properties()
+    }
+    fn move_straight(
+        &mut self,
+        distance_mm: i64,
+        mm_per_sec: f64,
+    ) -> Result<Option<Duration>, BaseError> {
+        self.lock().unwrap().move_straight(distance_mm, mm_per_sec)
+    }
+    fn spin(&mut self, angle_deg: f64, degs_per_sec: f64) -> Result<Option<Duration>, BaseError> {
+        self.lock().unwrap().spin(angle_deg, degs_per_sec)
+    }
+    fn get_linear_velocity(&mut self) -> Result<Vector3, BaseError> {
+        self.lock().unwrap().get_linear_velocity()
+    }
+    fn get_angular_velocity(&mut self) -> Result<Vector3, BaseError> {
+        self.lock().unwrap().get_angular_velocity()
+    }
 }
 
 #[cfg(feature = "builtin-components")]
@@ -0,0 +1,499 @@
+//! Package battery implements a sensor estimating the state of charge of a battery from the
+//! readings of a power sensor measuring the current flowing out of it.
+//!
+//! The charge drawn from the battery is integrated every time readings are requested or events
+//! are taken (coulomb counting), so the battery should be sampled regularly, by the watcher
+//! described below or by configuring data capture on the sensor. The initial state of charge, and its value whenever the battery is
+//! at rest (the current is below `rest_current_amps`), is looked up from the battery voltage in
+//! the `voltage_curve` table when it is configured.
+//!
+//! ```json
+//! {
+//!     "power_sensor": "ina",
+//!     "capacity_amp_hours": 2.2,
+//!     "voltage_curve": [{ "volts": 3.3, "percent": 0 }, { "volts": 3.7, "percent": 50 }, { "volts": 4.2, "percent": 100 }],
+//!     "low_battery_percent": 20,
+//!     "webhook_url": "http://192.168.1.10:8123/api/webhook/battery",
+//!     "check_interval_secs": 10
+//! }
+//! ```
+//!
+//! Without a voltage curve the battery is assumed to be full when the sensor is built, unless
+//! `initial_state_of_charge_percent` is set. A positive current is considered to be discharging
+//! the battery, `invert_current` can be set when the power sensor is wired the other way around.
+//!
+//! The readings contain `state_of_charge_percent`, `consumed_amp_hours`, `low_battery`,
+//! `low_battery_events` (the number of times the state of charge dropped below
+//! `low_battery_percent`) and, while discharging, `time_to_empty_seconds`.
+//!
+//! The [EventWatcher](super::webhook::EventWatcher), started with the robot whenever a battery
+//! is configured, samples every battery each `check_interval_secs` seconds (10 by default), which
+//! also keeps the coulomb counting going without readings being requested, and posts its low
+//! battery events as JSON to its `webhook_url` when it is set:
+//!
+//! ```json
+//! {"battery": "main", "event": "low_battery", "state_of_charge_percent": 19.5}
+//! ```
+//!
+//! Events are kept until the watcher takes them with the `{"take_events": {}}` command, at most
+//! 16 events are kept.
+
+use crate::google;
+use crate::google::protobuf::{Struct, Value};
+use crate::proto::app::v1::ConfigResponse;
+
+use super::config::{AttributeError, ConfigType, Kind};
+use super::generic::{DoCommand, GenericError};
+use super::power_sensor::{PowerSensor, PowerSensorType, COMPONENT_NAME as PowerSensorCompName};
+use super::registry::{ComponentRegistry, Dependency, ResourceKey};
+use super::robot::{LocalRobot, Resource};
+use super::sensor::{
+    GenericReadingsResult, Readings, Sensor, SensorError, SensorResult, SensorT, SensorType,
+    TypedReadingsResult, COMPONENT_NAME as SensorCompName,
+};
+use super::struct_builder::StructBuilder;
+use super::webhook::{EventQueue, EventWatcher};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("battery", &Battery::<PowerSensorType>::from_config)
+        .is_err()
+    {
+        log::error!("battery type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            SensorCompName,
+            "battery",
+            &Battery::<PowerSensorType>::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("failed to register dependency getter for battery model")
+    }
+}
+
+const DEFAULT_LOW_BATTERY_PERCENT: f64 = 20.0;
+const DEFAULT_REST_CURRENT_AMPS: f64 = 0.01;
+// the state of charge has to rise this much above the low battery threshold before another
+// low battery event can be reported
+const LOW_BATTERY_HYSTERESIS_PERCENT: f64 = 5.0;
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A point of the table mapping the voltage of the battery at rest to its state of charge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoltagePoint {
+    pub volts: f64,
+    pub percent: f64,
+}
+
+impl TryFrom<&Kind> for VoltagePoint {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let volts = value
+            .get("volts")?
+            .ok_or(AttributeError::KeyNotFound("volts".to_string()))?
+            .try_into()?;
+        let percent = value
+            .get("percent")?
+            .ok_or(AttributeError::KeyNotFound("percent".to_string()))?
+            .try_into()?;
+        Ok(VoltagePoint { volts, percent })
+    }
+}
+
+/// Returns the state of charge (in percent) for the given voltage by linear interpolation
+/// between the points of `curve`, which must be sorted by increasing voltage
+pub(crate) fn state_of_charge_from_voltage(curve: &[VoltagePoint], volts: f64) -> Option<f64> {
+    let first = curve.first()?;
+    let last = curve.last()?;
+    if volts <= first.volts {
+        return Some(first.percent);
+    }
+    if volts >= last.volts {
+        return Some(last.percent);
+    }
+    curve.windows(2).find_map(|w| {
+        (volts <= w[1].volts).then(|| {
+            let ratio = (volts - w[0].volts) / (w[1].volts - w[0].volts);
+            w[0].percent + ratio * (w[1].percent - w[0].percent)
+        })
+    })
+}
+
+/// Reported when the state of charge drops below `low_battery_percent`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LowBatteryEvent {
+    pub state_of_charge_percent: f64,
+}
+
+impl From<&LowBatteryEvent> for Value {
+    fn from(value: &LowBatteryEvent) -> Self {
+        StructBuilder::with_capacity(2)
+            .field("event", "low_battery")
+            .field("state_of_charge_percent", value.state_of_charge_percent)
+            .into()
+    }
+}
+
+#[derive(Status)]
+pub struct Battery<P> {
+    power_sensor: P,
+    capacity_amp_hours: f64,
+    voltage_curve: Vec<VoltagePoint>,
+    low_battery_percent: f64,
+    rest_current_amps: f64,
+    invert_current: bool,
+    // state of charge when the coulomb counting was last anchored
+    initial_percent: Option<f64>,
+    consumed_amp_hours: f64,
+    last_sample: Option<(Instant, f64)>,
+    #[status]
+    low_battery: bool,
+    #[status]
+    low_battery_events: u32,
+    // events waiting to be taken with the command
+    pending: EventQueue<LowBatteryEvent>,
+}
+
+impl<P> Battery<P>
+where
+    P: PowerSensor,
+{
+    pub fn new(
+        power_sensor: P,
+        capacity_amp_hours: f64,
+        mut voltage_curve: Vec<VoltagePoint>,
+        low_battery_percent: f64,
+    ) -> Result<Self, SensorError> {
+        if capacity_amp_hours <= 0.0 {
+            return Err(SensorError::ConfigError(
+                "battery capacity_amp_hours must be positive",
+            ));
+        }
+        voltage_curve.sort_by(|a, b| a.volts.total_cmp(&b.volts));
+        Ok(Battery {
+            power_sensor,
+            capacity_amp_hours,
+            voltage_curve,
+            low_battery_percent,
+            rest_current_amps: DEFAULT_REST_CURRENT_AMPS,
+            invert_current: false,
+            initial_percent: None,
+            consumed_amp_hours: 0.0,
+            last_sample: None,
+            low_battery: false,
+            low_battery_events: 0,
+            pending: EventQueue::default(),
+        })
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let power_sensor_name = cfg
+            .get_attribute::<String>("power_sensor")
+            .map_err(|_| SensorError::ConfigError("battery missing power_sensor attribute"))?;
+        let power_sensor = deps
+            .into_iter()
+            .find_map(|Dependency(key, res)| match res {
+                Resource::PowerSensor(ps) if key.1 == power_sensor_name => Some(ps),
+                _ => None,
+            })
+            .ok_or(SensorError::ConfigError("battery power sensor not found"))?;
+        let capacity_amp_hours = cfg
+            .get_attribute::<f64>("capacity_amp_hours")
+            .map_err(|_| SensorError::ConfigError("battery missing capacity_amp_hours"))?;
+        let voltage_curve = match cfg.get_attribute::<Vec<VoltagePoint>>("voltage_curve") {
+            Ok(curve) => curve,
+            Err(AttributeError::KeyNotFound(key)) if key == "voltage_curve" => vec![],
+            Err(_) => return Err(SensorError::ConfigError("battery invalid voltage_curve")),
+        };
+        let low_battery_percent = cfg
+            .get_attribute::<f64>("low_battery_percent")
+            .unwrap_or(DEFAULT_LOW_BATTERY_PERCENT);
+        let mut battery = Battery::new(
+            power_sensor,
+            capacity_amp_hours,
+            voltage_curve,
+            low_battery_percent,
+        )?;
+        battery.rest_current_amps = cfg
+            .get_attribute::<f64>("rest_current_amps")
+            .unwrap_or(DEFAULT_REST_CURRENT_AMPS);
+        battery.invert_current = cfg.get_attribute::<bool>("invert_current").unwrap_or(false);
+        if let Ok(percent) = cfg.get_attribute::<f64>("initial_state_of_charge_percent") {
+            battery.initial_percent = Some(percent.clamp(0.0, 100.0));
+        }
+        Ok(Arc::new(Mutex::new(battery)))
+    }
+
+    pub(crate) fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        let mut r_keys = Vec::new();
+        if let Ok(power_sensor_name) = cfg.get_attribute::<String>("power_sensor") {
+            r_keys.push(ResourceKey(PowerSensorCompName, power_sensor_name));
+        }
+        r_keys
+    }
+
+    fn state_of_charge(&self) -> f64 {
+        let initial = self.initial_percent.unwrap_or(100.0);
+        (initial - self.consumed_amp_hours / self.capacity_amp_hours * 100.0).clamp(0.0, 100.0)
+    }
+
+    // integrates the current drawn since the previous sample and returns the current
+    fn sample(&mut self, now: Instant) -> Result<f64, SensorError> {
+        let volts = self.power_sensor.get_voltage()?.volts;
+        let mut amps = self.power_sensor.get_current()?.amperes;
+        if self.invert_current {
+            amps = -amps;
+        }
+        if let Some((last_time, last_amps)) = self.last_sample {
+            let hours = now.duration_since(last_time).as_secs_f64() / 3600.0;
+            self.consumed_amp_hours += (last_amps + amps) / 2.0 * hours;
+        }
+        self.last_sample = Some((now, amps));
+        if self.initial_percent.is_none() || amps.abs() < self.rest_current_amps {
+            if let Some(percent) = state_of_charge_from_voltage(&self.voltage_curve, volts) {
+                self.initial_percent = Some(percent);
+                self.consumed_amp_hours = 0.0;
+            }
+        }
+
+        let percent = self.state_of_charge();
+        if !self.low_battery && percent < self.low_battery_percent {
+            self.low_battery = true;
+            self.low_battery_events += 1;
+            self.pending.push(LowBatteryEvent {
+                state_of_charge_percent: percent,
+            });
+            log::warn!("battery low: {:.1}% remaining", percent);
+        } else if self.low_battery
+            && percent > self.low_battery_percent + LOW_BATTERY_HYSTERESIS_PERCENT
+        {
+            self.low_battery = false;
+        }
+        Ok(amps)
+    }
+
+    fn readings_at(&mut self, now: Instant) -> Result<TypedReadingsResult<f64>, SensorError> {
+        let amps = self.sample(now)?;
+        let percent = self.state_of_charge();
+        let mut readings = HashMap::from([
+            ("state_of_charge_percent".to_string(), percent),
+            ("consumed_amp_hours".to_string(), self.consumed_amp_hours),
+            (
+                "low_battery_events".to_string(),
+                self.low_battery_events as f64,
+            ),
+        ]);
+        if amps >= self.rest_current_amps {
+            let remaining_amp_hours = percent / 100.0 * self.capacity_amp_hours;
+            readings.insert(
+                "time_to_empty_seconds".to_string(),
+                remaining_amp_hours / amps * 3600.0,
+            );
+        }
+        Ok(readings)
+    }
+}
+
+impl<P> Sensor for Battery<P> where P: PowerSensor {}
+
+impl<P> Readings for Battery<P>
+where
+    P: PowerSensor,
+{
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let mut readings: GenericReadingsResult = self
+            .readings_at(Instant::now())?
+            .into_iter()
+            .map(|v| (v.0, SensorResult::<f64> { value: v.1 }.into()))
+            .collect();
+        readings.insert(
+            "low_battery".to_string(),
+            google::protobuf::Value {
+                kind: Some(google::protobuf::value::Kind::BoolValue(self.low_battery)),
+            },
+        );
+        Ok(readings)
+    }
+}
+
+impl<P> SensorT<f64> for Battery<P>
+where
+    P: PowerSensor,
+{
+    fn get_readings(&self) -> Result<TypedReadingsResult<f64>, SensorError> {
+        Ok(HashMap::from([
+            (
+                "state_of_charge_percent".to_string(),
+                self.state_of_charge(),
+            ),
+            ("consumed_amp_hours".to_string(), self.consumed_amp_hours),
+        ]))
+    }
+}
+
+impl<P> DoCommand for Battery<P>
+where
+    P: PowerSensor,
+{
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        if command.fields.contains_key("take_events") {
+            if let Err(err) = self.sample(Instant::now()) {
+                log::debug!("battery: couldn't sample the power sensor: {}", err);
+            }
+        }
+        match self.pending.take_events(&command) {
+            Some(events) => Ok(Some(events)),
+            None => Err(GenericError::MethodUnimplemented("do_command")),
+        }
+    }
+}
+
+/// Returns the [EventWatcher] of the battery sensors of the robot, `None` when it has none
+pub fn watcher_from_robot_and_config(
+    cfg: &ConfigResponse,
+    robot: Arc<RwLock<LocalRobot>>,
+) -> Result<Option<EventWatcher>, SensorError> {
+    EventWatcher::from_robot_and_config(cfg, robot, "battery", DEFAULT_CHECK_INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{state_of_charge_from_voltage, Battery, VoltagePoint};
+    use crate::common::generic::DoCommand;
+    use crate::common::power_sensor::{Current, PowerSensor, PowerSupplyType, Voltage};
+    use crate::common::sensor::{GenericReadingsResult, Readings, SensorError};
+    use crate::common::status::{Status, StatusError};
+    use crate::common::struct_builder::StructBuilder;
+    use crate::google;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[derive(DoCommand)]
+    struct FakePowerSensor {
+        volts: f64,
+        amps: f64,
+    }
+
+    impl PowerSensor for FakePowerSensor {
+        fn get_voltage(&mut self) -> Result<Voltage, SensorError> {
+            Ok(Voltage {
+                volts: self.volts,
+                power_supply_type: PowerSupplyType::DC,
+            })
+        }
+        fn get_current(&mut self) -> Result<Current, SensorError> {
+            Ok(Current {
+                amperes: self.amps,
+                power_supply_type: PowerSupplyType::DC,
+            })
+        }
+        fn get_power(&mut self) -> Result<f64, SensorError> {
+            Ok(self.volts * self.amps)
+        }
+    }
+
+    impl Readings for FakePowerSensor {
+        fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+            Ok(GenericReadingsResult::new())
+        }
+    }
+
+    impl Status for FakePowerSensor {
+        fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
+            Ok(None)
+        }
+    }
+
+    fn curve() -> Vec<VoltagePoint> {
+        vec![
+            VoltagePoint {
+                volts: 4.2,
+                percent: 100.0,
+            },
+            VoltagePoint {
+                volts: 3.3,
+                percent: 0.0,
+            },
+            VoltagePoint {
+                volts: 3.7,
+                percent: 50.0,
+            },
+        ]
+    }
+
+    #[test_log::test]
+    fn test_voltage_lookup() {
+        let mut curve = curve();
+        curve.sort_by(|a, b| a.volts.total_cmp(&b.volts));
+        assert_eq!(state_of_charge_from_voltage(&curve, 3.0), Some(0.0));
+        assert_eq!(state_of_charge_from_voltage(&curve, 4.5), Some(100.0));
+        assert_eq!(state_of_charge_from_voltage(&curve, 3.7), Some(50.0));
+        let percent = state_of_charge_from_voltage(&curve, 3.5).unwrap();
+        assert!((percent - 25.0).abs() < 1e-9);
+        assert_eq!(state_of_charge_from_voltage(&[], 3.5), None);
+    }
+
+    #[test_log::test]
+    fn test_coulomb_counting() -> Result<(), SensorError> {
+        let ps = Arc::new(Mutex::new(FakePowerSensor {
+            volts: 3.7,
+            amps: 1.0,
+        }));
+        let mut battery = Battery::new(ps.clone(), 2.0, curve(), 20.0)?;
+        let start = Instant::now();
+
+        // state of charge is initialized from the voltage curve
+        let readings = battery.readings_at(start)?;
+        assert_eq!(readings.get("state_of_charge_percent"), Some(&50.0));
+        assert_eq!(readings.get("time_to_empty_seconds"), Some(&3600.0));
+
+        // 1A for 30 minutes consumes 0.5Ah, 25% of the capacity
+        let readings = battery.readings_at(start + Duration::from_secs(30 * 60))?;
+        let percent = *readings.get("state_of_charge_percent").unwrap();
+        assert!((percent - 25.0).abs() < 1e-9);
+        assert!(!battery.low_battery);
+
+        let readings = battery.readings_at(start + Duration::from_secs(42 * 60))?;
+        let percent = *readings.get("state_of_charge_percent").unwrap();
+        assert!((percent - 15.0).abs() < 1e-9);
+        assert!(battery.low_battery);
+        assert_eq!(readings.get("low_battery_events"), Some(&1.0));
+        assert_eq!(battery.pending.len(), 1);
+        let take = StructBuilder::new()
+            .sub("take_events", StructBuilder::new())
+            .build();
+        let res = battery.do_command(Some(take)).unwrap().unwrap();
+        match res.fields.get("events").and_then(|v| v.kind.as_ref()) {
+            Some(google::protobuf::value::Kind::ListValue(events)) => {
+                assert_eq!(events.values.len(), 1)
+            }
+            _ => panic!("events isn't a list"),
+        }
+        assert!(battery.pending.is_empty());
+
+        // charging at rest re-anchors the state of charge on the voltage
+        {
+            let mut ps = ps.lock().unwrap();
+            ps.amps = 0.0;
+            ps.volts = 4.2;
+        }
+        let readings = battery.readings_at(start + Duration::from_secs(60 * 60))?;
+        assert_eq!(readings.get("state_of_charge_percent"), Some(&100.0));
+        assert_eq!(readings.get("time_to_empty_seconds"), None);
+        assert!(!battery.low_battery);
+        Ok(())
+    }
+}
@@ -0,0 +1,467 @@
+//! Package ble_sensor implements a sensor reporting the values advertised by a Bluetooth LE
+//! device, such as a thermometer or a beacon, along with the signal strength of its
+//! advertisements.
+//!
+//! The platform scans for advertisements and hands them to [BLE_ADVERTISEMENTS], which only
+//! keeps the latest advertisement of the devices a `ble_sensor` is configured for. The
+//! `ble_sensor` model is registered on the ESP32 when Bluetooth is enabled, see
+//! `esp32::ble_sensor`.
+//!
+//! ```json
+//! {
+//!     "mac": "A4:C1:38:12:34:56",
+//!     "max_age_secs": 60
+//! }
+//! ```
+//!
+//! The readings contain the `rssi` (in dBm) of the last advertisement and its `age_ms`, plus the
+//! values parsed from the advertisements of the formats below, when the device uses one of them:
+//!
+//!  - thermometers running the ATC1441 or pvvx firmware (such as the Xiaomi LYWSD03MMC):
+//!    `temperature_c`, `humidity_pct`, `battery_pct` and `battery_mv`
+//!  - iBeacons: `uuid`, `major`, `minor`, `tx_power` and an estimated `distance_m`
+//!
+//! Readings fail when nothing was received from the device for `max_age_secs` (60 by default).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use super::config::{AttributeError, ConfigType};
+use super::sensor::{GenericReadingsResult, Readings, Sensor, SensorError};
+use super::status::{Status, StatusError};
+use super::struct_builder::StructBuilder;
+use crate::google::protobuf::Struct;
+
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60);
+
+// AD types of the advertisement data
+const AD_TYPE_SERVICE_DATA_16: u8 = 0x16;
+const AD_TYPE_MANUFACTURER_DATA: u8 = 0xFF;
+
+// Environmental Sensing service, used by the ATC1441 and pvvx thermometer firmwares
+const ENVIRONMENTAL_SENSING_UUID: [u8; 2] = [0x1A, 0x18];
+const ATC1441_LEN: usize = 13;
+const PVVX_LEN: usize = 15;
+
+// Apple's company identifier followed by the iBeacon type and length
+const IBEACON_PREFIX: [u8; 4] = [0x4C, 0x00, 0x02, 0x15];
+const IBEACON_LEN: usize = 25;
+// path loss exponent used to estimate the distance of a beacon
+const IBEACON_PATH_LOSS: f64 = 2.0;
+
+pub type BleAddress = [u8; 6];
+
+/// Parses a MAC address written as six hexadecimal bytes separated by colons
+pub fn parse_address(address: &str) -> Option<BleAddress> {
+    let bytes = address
+        .split(':')
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    bytes.try_into().ok()
+}
+
+pub fn format_address(address: &BleAddress) -> String {
+    address
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Values parsed from the advertisement of a known kind of device
+#[derive(Debug, Clone, PartialEq)]
+pub enum BleBeacon {
+    Thermometer {
+        temperature_c: f64,
+        humidity_pct: f64,
+        battery_pct: u8,
+        battery_mv: u16,
+    },
+    IBeacon {
+        uuid: String,
+        major: u16,
+        minor: u16,
+        tx_power: i8,
+    },
+}
+
+impl BleBeacon {
+    fn add_readings(&self, readings: StructBuilder, rssi: i8) -> StructBuilder {
+        match self {
+            Self::Thermometer {
+                temperature_c,
+                humidity_pct,
+                battery_pct,
+                battery_mv,
+            } => readings
+                .field("temperature_c", *temperature_c)
+                .field("humidity_pct", *humidity_pct)
+                .field("battery_pct", *battery_pct)
+                .field("battery_mv", *battery_mv),
+            Self::IBeacon {
+                uuid,
+                major,
+                minor,
+                tx_power,
+            } => readings
+                .field("uuid", uuid.as_str())
+                .field("major", *major)
+                .field("minor", *minor)
+                .field("tx_power", *tx_power)
+                .field("distance_m", estimate_distance_m(*tx_power, rssi)),
+        }
+    }
+}
+
+/// Estimates the distance to a beacon from the power it advertises to be received at 1m
+pub fn estimate_distance_m(tx_power: i8, rssi: i8) -> f64 {
+    10_f64.powf((tx_power as f64 - rssi as f64) / (10.0 * IBEACON_PATH_LOSS))
+}
+
+// iterates over the (type, data) of the AD structures of an advertisement, stopping at the
+// first malformed one
+fn ad_structures(payload: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    let mut rest = payload;
+    std::iter::from_fn(move || {
+        let len = *rest.first()? as usize;
+        if len == 0 || rest.len() < len + 1 {
+            return None;
+        }
+        let (structure, next) = rest[1..].split_at(len);
+        rest = next;
+        Some((structure[0], &structure[1..]))
+    })
+}
+
+fn parse_thermometer(data: &[u8]) -> Option<BleBeacon> {
+    match data.len() {
+        // the MAC is followed by big endian values, the temperature in 0.1°C
+        ATC1441_LEN => Some(BleBeacon::Thermometer {
+            temperature_c: i16::from_be_bytes([data[6], data[7]]) as f64 / 10.0,
+            humidity_pct: data[8] as f64,
+            battery_pct: data[9],
+            battery_mv: u16::from_be_bytes([data[10], data[11]]),
+        }),
+        // the MAC is followed by little endian values, the temperature and humidity in 0.01
+        PVVX_LEN => Some(BleBeacon::Thermometer {
+            temperature_c: i16::from_le_bytes([data[6], data[7]]) as f64 / 100.0,
+            humidity_pct: u16::from_le_bytes([data[8], data[9]]) as f64 / 100.0,
+            battery_mv: u16::from_le_bytes([data[10], data[11]]),
+            battery_pct: data[12],
+        }),
+        _ => None,
+    }
+}
+
+fn parse_ibeacon(data: &[u8]) -> Option<BleBeacon> {
+    if data.len() != IBEACON_LEN || data[..4] != IBEACON_PREFIX {
+        return None;
+    }
+    let uuid = &data[4..20];
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+    };
+    Some(BleBeacon::IBeacon {
+        uuid: format!(
+            "{}-{}-{}-{}-{}",
+            hex(&uuid[..4]),
+            hex(&uuid[4..6]),
+            hex(&uuid[6..8]),
+            hex(&uuid[8..10]),
+            hex(&uuid[10..])
+        ),
+        major: u16::from_be_bytes([data[20], data[21]]),
+        minor: u16::from_be_bytes([data[22], data[23]]),
+        tx_power: data[24] as i8,
+    })
+}
+
+/// Parses the advertisement data (and scan response) of a device, returns None when the
+/// device doesn't advertise in a known format
+pub fn parse_advertisement(payload: &[u8]) -> Option<BleBeacon> {
+    ad_structures(payload).find_map(|(ad_type, data)| match ad_type {
+        AD_TYPE_SERVICE_DATA_16 if data.starts_with(&ENVIRONMENTAL_SENSING_UUID) => {
+            parse_thermometer(&data[2..])
+        }
+        AD_TYPE_MANUFACTURER_DATA => parse_ibeacon(data),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Clone)]
+struct BleDevice {
+    rssi: i8,
+    last_seen: Instant,
+    beacon: Option<BleBeacon>,
+}
+
+#[derive(Default)]
+struct WatchedDevices {
+    // number of sensors configured for each address
+    watched: HashMap<BleAddress, usize>,
+    devices: HashMap<BleAddress, BleDevice>,
+}
+
+/// Latest advertisement received from each device a sensor is configured for
+#[derive(Default)]
+pub struct BleAdvertisements {
+    inner: Mutex<WatchedDevices>,
+}
+
+pub static BLE_ADVERTISEMENTS: Lazy<BleAdvertisements> = Lazy::new(BleAdvertisements::default);
+
+impl BleAdvertisements {
+    pub fn watch(&self, address: BleAddress) {
+        *self
+            .inner
+            .lock()
+            .unwrap()
+            .watched
+            .entry(address)
+            .or_default() += 1;
+    }
+
+    pub fn unwatch(&self, address: BleAddress) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(count) = inner.watched.get_mut(&address) {
+            *count -= 1;
+            if *count == 0 {
+                inner.watched.remove(&address);
+                inner.devices.remove(&address);
+            }
+        }
+    }
+
+    /// Whether a sensor is configured for `address`, the scanner can ignore the other devices
+    pub fn is_watched(&self, address: &BleAddress) -> bool {
+        self.inner.lock().unwrap().watched.contains_key(address)
+    }
+
+    /// Records an advertisement received by the scanner, advertisements of devices no
+    /// sensor is configured for are dropped
+    pub fn record(&self, address: BleAddress, rssi: i8, payload: &[u8]) {
+        self.record_at(address, rssi, payload, Instant::now())
+    }
+
+    fn record_at(&self, address: BleAddress, rssi: i8, payload: &[u8], now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.watched.contains_key(&address) {
+            return;
+        }
+        // devices alternate advertisements, the last values parsed are kept until new
+        // ones are received
+        let beacon = parse_advertisement(payload);
+        let device = inner.devices.entry(address).or_insert(BleDevice {
+            rssi,
+            last_seen: now,
+            beacon: None,
+        });
+        device.rssi = rssi;
+        device.last_seen = now;
+        if beacon.is_some() {
+            device.beacon = beacon;
+        }
+    }
+
+    fn device(&self, address: &BleAddress) -> Option<BleDevice> {
+        self.inner.lock().unwrap().devices.get(address).cloned()
+    }
+}
+
+#[derive(DoCommand)]
+pub struct BleSensor {
+    address: BleAddress,
+    max_age: Duration,
+}
+
+impl BleSensor {
+    pub fn new(address: BleAddress, max_age: Duration) -> Self {
+        BLE_ADVERTISEMENTS.watch(address);
+        BleSensor { address, max_age }
+    }
+
+    pub fn from_config(cfg: ConfigType) -> Result<Self, SensorError> {
+        let address = cfg
+            .get_attribute::<String>("mac")
+            .ok()
+            .and_then(|mac| parse_address(&mac))
+            .ok_or(SensorError::ConfigError(
+                "ble_sensor: `mac` must be a MAC address such as A4:C1:38:12:34:56",
+            ))?;
+        let max_age = match cfg.get_attribute::<f64>("max_age_secs") {
+            Ok(secs) if secs > 0.0 => Duration::from_secs_f64(secs),
+            Err(AttributeError::KeyNotFound(_)) => DEFAULT_MAX_AGE,
+            _ => {
+                return Err(SensorError::ConfigError(
+                    "ble_sensor: `max_age_secs` must be a positive number",
+                ))
+            }
+        };
+        Ok(Self::new(address, max_age))
+    }
+
+    fn readings_at(&self, now: Instant) -> Result<GenericReadingsResult, SensorError> {
+        let device = BLE_ADVERTISEMENTS
+            .device(&self.address)
+            .filter(|device| now.saturating_duration_since(device.last_seen) <= self.max_age)
+            .ok_or(SensorError::SensorGenericError(
+                "ble_sensor: no recent advertisement from the device",
+            ))?;
+        let mut readings = StructBuilder::with_capacity(7)
+            .field("rssi", device.rssi)
+            .field(
+                "age_ms",
+                now.saturating_duration_since(device.last_seen).as_millis() as u64,
+            );
+        if let Some(beacon) = device.beacon.as_ref() {
+            readings = beacon.add_readings(readings, device.rssi);
+        }
+        Ok(readings.into_fields())
+    }
+}
+
+impl Drop for BleSensor {
+    fn drop(&mut self) {
+        BLE_ADVERTISEMENTS.unwatch(self.address);
+    }
+}
+
+impl Sensor for BleSensor {}
+
+impl Readings for BleSensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        self.readings_at(Instant::now())
+    }
+}
+
+impl Status for BleSensor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        let device = BLE_ADVERTISEMENTS.device(&self.address);
+        Ok(Some(
+            StructBuilder::with_capacity(2)
+                .field("mac", format_address(&self.address))
+                .field("rssi", device.map(|device| device.rssi))
+                .build(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        estimate_distance_m, format_address, parse_address, parse_advertisement, BleBeacon,
+        BleSensor, BLE_ADVERTISEMENTS,
+    };
+    use crate::google::protobuf::value::Kind;
+    use std::time::{Duration, Instant};
+
+    fn atc1441() -> Vec<u8> {
+        // flags, then the service data of the environmental sensing service
+        let mut payload = vec![0x02, 0x01, 0x06, 0x10, 0x16, 0x1A, 0x18];
+        payload.extend([0xA4, 0xC1, 0x38, 0x12, 0x34, 0x56]);
+        // 23.0°C, 45%, 85%, 3000mV, frame 7
+        payload.extend([0x00, 0xE6, 0x2D, 0x55, 0x0B, 0xB8, 0x07]);
+        payload
+    }
+
+    #[test_log::test]
+    fn test_addresses() {
+        let address = parse_address("a4:C1:38:12:34:56").unwrap();
+        assert_eq!(address, [0xA4, 0xC1, 0x38, 0x12, 0x34, 0x56]);
+        assert_eq!(format_address(&address), "A4:C1:38:12:34:56");
+        assert!(parse_address("A4:C1:38:12:34").is_none());
+        assert!(parse_address("A4:C1:38:12:34:5G").is_none());
+    }
+
+    #[test_log::test]
+    fn test_parse_thermometers() {
+        assert_eq!(
+            parse_advertisement(&atc1441()),
+            Some(BleBeacon::Thermometer {
+                temperature_c: 23.0,
+                humidity_pct: 45.0,
+                battery_pct: 85,
+                battery_mv: 3000,
+            })
+        );
+
+        // -5.25°C, 50.5%, 2950mV, 80%
+        let mut pvvx = vec![0x12, 0x16, 0x1A, 0x18];
+        pvvx.extend([0x56, 0x34, 0x12, 0x38, 0xC1, 0xA4]);
+        pvvx.extend([0xF3, 0xFD, 0xBA, 0x13, 0x86, 0x0B, 0x50, 0x01, 0x04]);
+        assert_eq!(
+            parse_advertisement(&pvvx),
+            Some(BleBeacon::Thermometer {
+                temperature_c: -5.25,
+                humidity_pct: 50.5,
+                battery_pct: 80,
+                battery_mv: 2950,
+            })
+        );
+
+        // truncated advertisements are ignored
+        assert_eq!(parse_advertisement(&atc1441()[..10]), None);
+        assert_eq!(parse_advertisement(&[0x02, 0x01, 0x06]), None);
+    }
+
+    #[test_log::test]
+    fn test_parse_ibeacon() {
+        let mut payload = vec![0x02, 0x01, 0x06, 0x1A, 0xFF, 0x4C, 0x00, 0x02, 0x15];
+        payload.extend((0..16).map(|i| i * 0x11));
+        payload.extend([0x00, 0x01, 0x00, 0x2A, 0xC5]);
+        assert_eq!(
+            parse_advertisement(&payload),
+            Some(BleBeacon::IBeacon {
+                uuid: "00112233-4455-6677-8899-aabbccddeeff".to_string(),
+                major: 1,
+                minor: 42,
+                tx_power: -59,
+            })
+        );
+        assert!((estimate_distance_m(-59, -59) - 1.0).abs() < 1e-9);
+        assert!((estimate_distance_m(-59, -79) - 10.0).abs() < 1e-9);
+    }
+
+    #[test_log::test]
+    fn test_ble_sensor_readings() {
+        let address = [0xA4, 0xC1, 0x38, 0x00, 0x00, 0x01];
+        let now = Instant::now();
+        // nothing is recorded for devices without a sensor
+        BLE_ADVERTISEMENTS.record_at(address, -70, &atc1441(), now);
+        assert!(BLE_ADVERTISEMENTS.device(&address).is_none());
+
+        let sensor = BleSensor::new(address, Duration::from_secs(60));
+        assert!(BLE_ADVERTISEMENTS.is_watched(&address));
+        assert!(sensor.readings_at(now).is_err());
+
+        BLE_ADVERTISEMENTS.record_at(address, -70, &atc1441(), now);
+        // an advertisement in an unknown format only updates the signal strength
+        BLE_ADVERTISEMENTS.record_at(address, -65, &[0x02, 0x01, 0x06], now);
+        let readings = sensor.readings_at(now + Duration::from_secs(1)).unwrap();
+        assert_eq!(
+            readings.get("rssi").and_then(|v| v.kind.clone()),
+            Some(Kind::NumberValue(-65.0))
+        );
+        assert_eq!(
+            readings.get("age_ms").and_then(|v| v.kind.clone()),
+            Some(Kind::NumberValue(1000.0))
+        );
+        assert_eq!(
+            readings.get("temperature_c").and_then(|v| v.kind.clone()),
+            Some(Kind::NumberValue(23.0))
+        );
+
+        // stale advertisements fail the readings
+        assert!(sensor.readings_at(now + Duration::from_secs(61)).is_err());
+
+        drop(sensor);
+        assert!(!BLE_ADVERTISEMENTS.is_watched(&address));
+        assert!(BLE_ADVERTISEMENTS.device(&address).is_none());
+    }
+}
@@ -12,13 +12,17 @@ use log::*;
 use std::{collections::HashMap, sync::Arc, sync::Mutex, time::Duration};
 
 use super::{
-    analog::{AnalogReaderType, FakeAnalogReader},
-    config::ConfigType,
-    generic::DoCommand,
+    analog::{AnalogReaderType, FakeAnalogConfig, FakeAnalogReader},
+    calibration::number_arg,
+    config::{AttributeError, ConfigType, Kind},
+    digital_interrupt::{DigitalInterruptConfig, Tick},
+    generic::{DoCommand, GenericError},
     i2c::{FakeI2CHandle, FakeI2cConfig, I2CErrors, I2CHandle, I2cHandleType},
     registry::ComponentRegistry,
+    struct_builder::StructBuilder,
 };
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -39,10 +43,148 @@ pub enum BoardError {
     BoardMethodNotSupported(&'static str),
     #[error(transparent)]
     BoardI2CError(#[from] I2CErrors),
+    #[error("gpio expander {0} not found")]
+    GpioExpanderNotFound(String),
+    #[error("pin {0} cannot output pwm at {1}Hz with a duty cycle of {2}: {3}")]
+    PwmUnsupported(u32, f64, f64, &'static str),
+}
+
+impl GrpcStatusHint for BoardError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::AnalogReaderNotFound(_)
+            | Self::I2CBusNotFound(_)
+            | Self::GpioExpanderNotFound(_) => GrpcError::RpcNotFound,
+            Self::BoardUnsupportedArgument(_) | Self::PwmUnsupported(..) => {
+                GrpcError::RpcInvalidArgument
+            }
+            Self::BoardMethodNotSupported(_) => GrpcError::RpcUnimplemented,
+            Self::BoardI2CError(err) => err.grpc_error(),
+            Self::GpioPinError(_, _) | Self::GpioPinOtherError(_, _) | Self::OtherBoardError(_) => {
+                GrpcError::RpcInternal
+            }
+        }
+    }
 }
 
 pub static COMPONENT_NAME: &str = "board";
 
+/// Most pulses a [PulseTrain] may hold
+pub const MAX_PULSE_COUNT: u32 = 1000;
+
+/// `count` pulses emitted on a pin by [Board::pulse], each high for `high` then low for `low`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PulseTrain {
+    pub high: Duration,
+    pub low: Duration,
+    pub count: u32,
+}
+
+impl PulseTrain {
+    pub fn single(width: Duration) -> Self {
+        Self {
+            high: width,
+            low: Duration::ZERO,
+            count: 1,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), BoardError> {
+        if self.high.is_zero()
+            || self.count == 0
+            || self.count > MAX_PULSE_COUNT
+            || (self.count > 1 && self.low.is_zero())
+        {
+            return Err(BoardError::BoardUnsupportedArgument(
+                "pulse train must hold 1 to 1000 pulses of non zero high and low durations",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Time taken to emit the whole train
+    pub fn duration(&self) -> Duration {
+        (self.high + self.low) * self.count
+    }
+}
+
+/// Converts a PWM frequency to the whole number of Hz taken by [Board::set_pwm_frequency]
+pub(crate) fn whole_pwm_frequency(frequency_hz: f64) -> Result<u64, BoardError> {
+    if frequency_hz < 0.0 || frequency_hz.fract() != 0.0 {
+        return Err(BoardError::BoardUnsupportedArgument(
+            "pwm frequency must be a whole number of Hz",
+        ));
+    }
+    Ok(frequency_hz as u64)
+}
+
+/// Answers the DoCommands common to boards:
+///
+/// - `{"pulse": {"pin": 4, "high_us": 10, "low_us": 10, "count": 1}}` emits a [PulseTrain] with
+///   [Board::pulse], `low_us` defaults to `high_us` and `count` to 1
+/// - `{"set_pwm_frequency": {"pin": 4, "frequency_hz": 0.5}}` sets a PWM frequency with
+///   [Board::set_pwm_frequency_hz], the `SetPWMFrequency` method only takes whole numbers of Hz
+/// - `{"get_pwm_frequency": {"pin": 4}}` answers `{"frequency_hz": 0.5}`
+pub(crate) fn board_do_command<B>(
+    board: &mut B,
+    command_struct: Option<google::protobuf::Struct>,
+) -> Result<Option<google::protobuf::Struct>, GenericError>
+where
+    B: Board + ?Sized,
+{
+    let command = command_struct.unwrap_or_default();
+    if let Some(args) = command.fields.get("pulse") {
+        let args = match &args.kind {
+            Some(google::protobuf::value::Kind::StructValue(args)) => args,
+            _ => return Err(GenericError::InvalidArgument("pulse")),
+        };
+        let pin = number_arg(args, "pin").ok_or(GenericError::InvalidArgument("pin"))?;
+        let high_us = number_arg(args, "high_us")
+            .filter(|us| *us >= 0.0)
+            .ok_or(GenericError::InvalidArgument("high_us"))?;
+        let low_us = number_arg(args, "low_us").unwrap_or(high_us).max(0.0);
+        let count = number_arg(args, "count").unwrap_or(1.0).max(0.0);
+        let train = PulseTrain {
+            high: Duration::from_micros(high_us as u64),
+            low: Duration::from_micros(low_us as u64),
+            count: count as u32,
+        };
+        board
+            .pulse(pin as i32, &train)
+            .map_err(|err| GenericError::Other(Box::new(err)))?;
+        return Ok(None);
+    }
+    if let Some(args) = command.fields.get("set_pwm_frequency") {
+        let args = match &args.kind {
+            Some(google::protobuf::value::Kind::StructValue(args)) => args,
+            _ => return Err(GenericError::InvalidArgument("set_pwm_frequency")),
+        };
+        let pin = number_arg(args, "pin").ok_or(GenericError::InvalidArgument("pin"))?;
+        let frequency_hz = number_arg(args, "frequency_hz")
+            .ok_or(GenericError::InvalidArgument("frequency_hz"))?;
+        board
+            .set_pwm_frequency_hz(pin as i32, frequency_hz)
+            .map_err(|err| GenericError::Other(Box::new(err)))?;
+        return Ok(None);
+    }
+    if let Some(args) = command.fields.get("get_pwm_frequency") {
+        let pin = match &args.kind {
+            Some(google::protobuf::value::Kind::StructValue(args)) => number_arg(args, "pin"),
+            _ => None,
+        }
+        .ok_or(GenericError::InvalidArgument("pin"))?;
+        let frequency_hz = board
+            .get_pwm_frequency_hz(pin as i32)
+            .map_err(|err| GenericError::Other(Box::new(err)))?;
+        return Ok(Some(
+            StructBuilder::with_capacity(1)
+                .field("frequency_hz", frequency_hz)
+                .build(),
+        ));
+    }
+    Err(GenericError::MethodUnimplemented("do_command"))
+}
+
 pub(crate) fn register_models(registry: &mut ComponentRegistry) {
     if registry
         .register_board("fake", &FakeBoard::from_config)
@@ -73,6 +215,17 @@ pub trait Board: Status + DoCommand {
         duration: Option<Duration>,
     ) -> Result<(), BoardError>;
 
+    /// Set the board to the indicated [PowerMode](component::board::v1::PowerMode), with
+    /// implementation specific options passed in `extra` (such as the wake sources of a sleep)
+    fn set_power_mode_with_extra(
+        &self,
+        mode: component::board::v1::PowerMode,
+        duration: Option<Duration>,
+        _extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), BoardError> {
+        self.set_power_mode(mode, duration)
+    }
+
     /// Get a wrapped [I2CHandle] by name.
     fn get_i2c_by_name(&self, name: String) -> Result<I2cHandleType, BoardError>;
 
@@ -84,6 +237,19 @@ pub trait Board: Status + DoCommand {
         ))
     }
 
+    /// Return the edges seen on an interrupt pin after its first `since` events, oldest first
+    /// and timestamped when they happened, along with the amount of events. Only the most recent
+    /// edges are kept. Boards that only count the events don't support it
+    fn get_digital_interrupt_ticks(
+        &self,
+        _pin: i32,
+        _since: u32,
+    ) -> Result<(Vec<Tick>, u32), BoardError> {
+        Err(BoardError::BoardMethodNotSupported(
+            "get_digital_interrupt_ticks",
+        ))
+    }
+
     /// Get the pin's given duty cycle, returns percentage as float between 0.0 and 1.0
     fn get_pwm_duty(&self, pin: i32) -> f64;
 
@@ -97,19 +263,138 @@ pub trait Board: Status + DoCommand {
     /// When frequency is 0, the board will unregister the pin and PWM channel from
     /// the timer and removes the PWM signal.
     fn set_pwm_frequency(&mut self, pin: i32, frequency_hz: u64) -> Result<(), BoardError>;
+
+    /// Get the PWM frequency of the pin (in Hz), which can be a fraction of Hz
+    fn get_pwm_frequency_hz(&self, pin: i32) -> Result<f64, BoardError> {
+        self.get_pwm_frequency(pin)
+            .map(|frequency_hz| frequency_hz as f64)
+    }
+
+    /// Set the pin to the given PWM frequency (in Hz), boards supporting it accept fractions of
+    /// Hz. Fails with [BoardError::PwmUnsupported] when the board can't output the frequency at
+    /// the duty cycle of the pin.
+    fn set_pwm_frequency_hz(&mut self, pin: i32, frequency_hz: f64) -> Result<(), BoardError> {
+        self.set_pwm_frequency(pin, whole_pwm_frequency(frequency_hz)?)
+    }
+
+    /// Emits `train` on `pin` with microsecond resolution, returning once the last pulse is out.
+    /// The pin is left low.
+    fn pulse(&mut self, _pin: i32, _train: &PulseTrain) -> Result<(), BoardError> {
+        Err(BoardError::BoardMethodNotSupported("pulse"))
+    }
+
+    /// Returns the pin number used to address a [BoardPin] through this trait. Boards without
+    /// GPIO expanders only support [BoardPin::Gpio]
+    fn resolve_pin(&self, pin: &BoardPin) -> Result<i32, BoardError> {
+        match pin {
+            BoardPin::Gpio(pin) => Ok(*pin),
+            BoardPin::Expander(name, _) => Err(BoardError::GpioExpanderNotFound(name.clone())),
+        }
+    }
 }
 
 /// An alias for a thread-safe handle to a struct that implements the [Board] trait
 pub type BoardType = Arc<Mutex<dyn Board>>;
 
+/// A pin as referenced in a component config, either a GPIO number of the board or a pin
+/// of a GPIO expander attached to the board, named `<expander name>:<pin>` (e.g. `"mcp:3"`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BoardPin {
+    Gpio(i32),
+    Expander(String, u16),
+}
+
+impl TryFrom<&Kind> for BoardPin {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        if let Kind::StringValue(v) = value {
+            if let Some((name, pin)) = v.split_once(':') {
+                if name.is_empty() {
+                    return Err(AttributeError::ConversionImpossibleError);
+                }
+                return Ok(BoardPin::Expander(name.to_string(), pin.parse::<u16>()?));
+            }
+        }
+        Ok(BoardPin::Gpio(value.try_into()?))
+    }
+}
+
+impl PartialEq<i32> for BoardPin {
+    fn eq(&self, other: &i32) -> bool {
+        matches!(self, BoardPin::Gpio(pin) if pin == other)
+    }
+}
+
+/// Builds the [Struct](google::protobuf::Struct) reported as the status of a board from its
+/// [BoardStatus](common::v1::BoardStatus) and the levels of its configured pins
+pub(crate) fn board_status_to_struct(
+    status: &common::v1::BoardStatus,
+    pin_levels: &[(i32, bool)],
+) -> google::protobuf::Struct {
+    let value_struct = |value: f64| google::protobuf::Value {
+        kind: Some(google::protobuf::value::Kind::StructValue(
+            google::protobuf::Struct {
+                fields: HashMap::from([(
+                    "value".to_string(),
+                    google::protobuf::Value {
+                        kind: Some(google::protobuf::value::Kind::NumberValue(value)),
+                    },
+                )]),
+            },
+        )),
+    };
+    let analogs: HashMap<_, _> = status
+        .analogs
+        .iter()
+        .map(|(name, a)| (name.clone(), value_struct(a.value.into())))
+        .collect();
+    let digital_interrupts: HashMap<_, _> = status
+        .digital_interrupts
+        .iter()
+        .map(|(name, i)| (name.clone(), value_struct(i.value as f64)))
+        .collect();
+    let pins: HashMap<_, _> = pin_levels
+        .iter()
+        .map(|(pin, is_high)| {
+            (
+                pin.to_string(),
+                google::protobuf::Value {
+                    kind: Some(google::protobuf::value::Kind::BoolValue(*is_high)),
+                },
+            )
+        })
+        .collect();
+    let mut hm = HashMap::new();
+    for (key, fields) in [
+        ("analogs", analogs),
+        ("digital_interrupts", digital_interrupts),
+        ("pins", pins),
+    ] {
+        if !fields.is_empty() {
+            hm.insert(
+                key.to_string(),
+                google::protobuf::Value {
+                    kind: Some(google::protobuf::value::Kind::StructValue(
+                        google::protobuf::Struct { fields },
+                    )),
+                },
+            );
+        }
+    }
+    google::protobuf::Struct { fields: hm }
+}
+
 #[doc(hidden)]
 /// A test implementation of a generic compute board
-#[derive(DoCommand)]
 pub struct FakeBoard {
     analogs: Vec<AnalogReaderType<u16>>,
     i2cs: HashMap<String, Arc<Mutex<FakeI2CHandle>>>,
     pin_pwms: HashMap<i32, f64>,
-    pin_pwm_freq: HashMap<i32, u64>,
+    pin_pwm_freq: HashMap<i32, f64>,
+    pin_levels: HashMap<i32, bool>,
+    interrupts: HashMap<i32, u32>,
+    // last pulse train emitted on each pin
+    pulses: HashMap<i32, PulseTrain>,
 }
 
 impl FakeBoard {
@@ -124,16 +409,42 @@ impl FakeBoard {
             i2cs,
             pin_pwms: HashMap::new(),
             pin_pwm_freq: HashMap::new(),
+            pin_levels: HashMap::new(),
+            interrupts: HashMap::new(),
+            pulses: HashMap::new(),
         }
     }
 
+    /// Last pulse train emitted on `pin`
+    pub fn last_pulse(&self, pin: i32) -> Option<PulseTrain> {
+        self.pulses.get(&pin).copied()
+    }
+
+    /// Configures a pin as a digital interrupt, as the `digital_interrupts` attribute does
+    pub fn add_digital_interrupt(&mut self, pin: i32) {
+        self.interrupts.entry(pin).or_insert(0);
+    }
+
+    /// Registers an event on a digital interrupt pin, as if its level had changed
+    pub fn trigger_digital_interrupt(&mut self, pin: i32) -> Result<(), BoardError> {
+        let count = self
+            .interrupts
+            .get_mut(&pin)
+            .ok_or(BoardError::GpioPinError(pin as u32, "not an interrupt"))?;
+        *count += 1;
+        Ok(())
+    }
+
     pub(crate) fn from_config(cfg: ConfigType) -> Result<BoardType, BoardError> {
-        let analogs = if let Ok(analog_confs) = cfg.get_attribute::<HashMap<&str, f64>>("analogs") {
+        let analogs = if let Ok(analog_confs) =
+            cfg.get_attribute::<HashMap<&str, FakeAnalogConfig>>("analogs")
+        {
             analog_confs
-                .iter()
+                .into_iter()
                 .map(|(k, v)| {
-                    let a: AnalogReaderType<u16> =
-                        Arc::new(Mutex::new(FakeAnalogReader::new(k.to_string(), *v as u16)));
+                    let a: AnalogReaderType<u16> = Arc::new(Mutex::new(
+                        FakeAnalogReader::new(k.to_string(), v.value).with_filter(v.filter),
+                    ));
                     a
                 })
                 .collect()
@@ -155,11 +466,21 @@ impl FakeBoard {
             HashMap::new()
         };
 
+        let interrupts = if let Ok(interrupt_confs) =
+            cfg.get_attribute::<Vec<DigitalInterruptConfig>>("digital_interrupts")
+        {
+            interrupt_confs.iter().map(|v| (v.pin, 0)).collect()
+        } else {
+            HashMap::new()
+        };
+
         Ok(Arc::new(Mutex::new(FakeBoard {
             analogs,
             i2cs,
             pin_pwms: HashMap::new(),
             pin_pwm_freq: HashMap::new(),
+            pin_levels: HashMap::new(),
+            interrupts,
         })))
     }
 }
@@ -167,6 +488,7 @@ impl FakeBoard {
 impl Board for FakeBoard {
     fn set_gpio_pin_level(&mut self, pin: i32, is_high: bool) -> Result<(), BoardError> {
         info!("set pin {} to {}", pin, is_high);
+        self.pin_levels.insert(pin, is_high);
         Ok(())
     }
 
@@ -184,12 +506,20 @@ impl Board for FakeBoard {
                 },
             );
         });
+        self.interrupts.iter().for_each(|(pin, count)| {
+            b.digital_interrupts.insert(
+                pin.to_string(),
+                common::v1::DigitalInterruptStatus {
+                    value: *count as i64,
+                },
+            );
+        });
         Ok(b)
     }
 
     fn get_gpio_level(&self, pin: i32) -> Result<bool, BoardError> {
         info!("get pin {}", pin);
-        Ok(true)
+        Ok(*self.pin_levels.get(&pin).unwrap_or(&true))
     }
 
     fn get_analog_reader_by_name(&self, name: String) -> Result<AnalogReaderType<u16>, BoardError> {
@@ -232,50 +562,63 @@ impl Board for FakeBoard {
     }
 
     fn get_pwm_frequency(&self, pin: i32) -> Result<u64, BoardError> {
-        Ok(*self.pin_pwm_freq.get(&pin).unwrap_or(&0))
+        Ok(self.get_pwm_frequency_hz(pin)?.round() as u64)
     }
 
     fn set_pwm_frequency(&mut self, pin: i32, frequency_hz: u64) -> Result<(), BoardError> {
+        self.set_pwm_frequency_hz(pin, frequency_hz as f64)
+    }
+
+    fn get_pwm_frequency_hz(&self, pin: i32) -> Result<f64, BoardError> {
+        Ok(*self.pin_pwm_freq.get(&pin).unwrap_or(&0.0))
+    }
+
+    fn set_pwm_frequency_hz(&mut self, pin: i32, frequency_hz: f64) -> Result<(), BoardError> {
+        if frequency_hz < 0.0 || frequency_hz.is_nan() {
+            return Err(BoardError::PwmUnsupported(
+                pin as u32,
+                frequency_hz,
+                self.get_pwm_duty(pin),
+                "negative frequency",
+            ));
+        }
         self.pin_pwm_freq.insert(pin, frequency_hz);
         Ok(())
     }
+
+    fn get_digital_interrupt_value(&self, pin: i32) -> Result<u32, BoardError> {
+        self.interrupts
+            .get(&pin)
+            .copied()
+            .ok_or(BoardError::GpioPinError(pin as u32, "not an interrupt"))
+    }
+
+    fn pulse(&mut self, pin: i32, train: &PulseTrain) -> Result<(), BoardError> {
+        train.validate()?;
+        self.pulses.insert(pin, *train);
+        self.pin_levels.insert(pin, false);
+        Ok(())
+    }
+}
+
+impl DoCommand for FakeBoard {
+    fn do_command(
+        &mut self,
+        command_struct: Option<google::protobuf::Struct>,
+    ) -> Result<Option<google::protobuf::Struct>, GenericError> {
+        board_do_command(self, command_struct)
+    }
 }
 
 impl Status for FakeBoard {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        let mut hm = HashMap::new();
-        let mut analogs = HashMap::new();
-        self.analogs.iter().for_each(|a| {
-            let mut analog = a.clone();
-            analogs.insert(
-                analog.name(),
-                google::protobuf::Value {
-                    kind: Some(google::protobuf::value::Kind::StructValue(
-                        google::protobuf::Struct {
-                            fields: HashMap::from([(
-                                "value".to_string(),
-                                google::protobuf::Value {
-                                    kind: Some(google::protobuf::value::Kind::NumberValue(
-                                        analog.read().unwrap_or(0).into(),
-                                    )),
-                                },
-                            )]),
-                        },
-                    )),
-                },
-            );
-        });
-        if !analogs.is_empty() {
-            hm.insert(
-                "analogs".to_string(),
-                google::protobuf::Value {
-                    kind: Some(google::protobuf::value::Kind::StructValue(
-                        google::protobuf::Struct { fields: analogs },
-                    )),
-                },
-            );
-        }
-        Ok(Some(google::protobuf::Struct { fields: hm }))
+        let mut pin_levels: Vec<(i32, bool)> =
+            self.pin_levels.iter().map(|(p, l)| (*p, *l)).collect();
+        pin_levels.sort();
+        Ok(Some(board_status_to_struct(
+            &self.get_board_status().unwrap_or_default(),
+            &pin_levels,
+        )))
     }
 }
 
@@ -307,6 +650,17 @@ where
         self.lock().unwrap().set_power_mode(mode, duration)
     }
 
+    fn set_power_mode_with_extra(
+        &self,
+        mode: component::board::v1::PowerMode,
+        duration: Option<Duration>,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), BoardError> {
+        self.lock()
+            .unwrap()
+            .set_power_mode_with_extra(mode, duration, extra)
+    }
+
     fn get_i2c_by_name(&self, name: String) -> Result<I2cHandleType, BoardError> {
         self.lock().unwrap().get_i2c_by_name(name)
     }
@@ -315,6 +669,14 @@ where
         self.lock().unwrap().get_digital_interrupt_value(pin)
     }
 
+    fn get_digital_interrupt_ticks(
+        &self,
+        pin: i32,
+        since: u32,
+    ) -> Result<(Vec<Tick>, u32), BoardError> {
+        self.lock().unwrap().get_digital_interrupt_ticks(pin, since)
+    }
+
     fn get_pwm_duty(&self, pin: i32) -> f64 {
         self.lock().unwrap().get_pwm_duty(pin)
     }
@@ -330,4 +692,136 @@ where
     fn set_pwm_frequency(&mut self, pin: i32, frequency_hz: u64) -> Result<(), BoardError> {
         self.lock().unwrap().set_pwm_frequency(pin, frequency_hz)
     }
+
+    fn get_pwm_frequency_hz(&self, pin: i32) -> Result<f64, BoardError> {
+        self.lock().unwrap().get_pwm_frequency_hz(pin)
+    }
+
+    fn set_pwm_frequency_hz(&mut self, pin: i32, frequency_hz: f64) -> Result<(), BoardError> {
+        self.lock().unwrap().set_pwm_frequency_hz(pin, frequency_hz)
+    }
+
+    fn pulse(&mut self, pin: i32, train: &PulseTrain) -> Result<(), BoardError> {
+        self.lock().unwrap().pulse(pin, train)
+    }
+    fn resolve_pin(&self, pin: &BoardPin) -> Result<i32, BoardError> {
+        self.lock().unwrap().resolve_pin(pin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{whole_pwm_frequency, Board, BoardError, FakeBoard, PulseTrain};
+    use crate::common::generic::DoCommand;
+    use crate::common::status::Status;
+    use crate::common::struct_builder::StructBuilder;
+    use crate::google::protobuf::value::Kind;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test_log::test]
+    fn test_fake_board_status() -> Result<(), BoardError> {
+        let mut board = FakeBoard::new(vec![]);
+        board.interrupts = HashMap::from([(4, 0)]);
+        board.trigger_digital_interrupt(4)?;
+        board.trigger_digital_interrupt(4)?;
+        assert!(board.trigger_digital_interrupt(5).is_err());
+        board.set_gpio_pin_level(12, false)?;
+
+        let status = board.get_board_status()?;
+        assert_eq!(status.digital_interrupts.get("4").map(|i| i.value), Some(2));
+        assert_eq!(board.get_digital_interrupt_value(4)?, 2);
+        assert!(!board.get_gpio_level(12)?);
+
+        let status = board.get_status().unwrap().unwrap();
+        let field = |key: &str| match status.fields.get(key).and_then(|v| v.kind.clone()) {
+            Some(Kind::StructValue(s)) => s,
+            _ => panic!("missing {}", key),
+        };
+        let interrupt = match field("digital_interrupts")
+            .fields
+            .get("4")
+            .and_then(|v| v.kind.clone())
+        {
+            Some(Kind::StructValue(s)) => s.fields.get("value").and_then(|v| v.kind.clone()),
+            _ => None,
+        };
+        assert_eq!(interrupt, Some(Kind::NumberValue(2.0)));
+        assert_eq!(
+            field("pins").fields.get("12").and_then(|v| v.kind.clone()),
+            Some(Kind::BoolValue(false))
+        );
+        assert!(!status.fields.contains_key("analogs"));
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_fake_board_pulse() {
+        let mut board = FakeBoard::new(vec![]);
+        let command = StructBuilder::new()
+            .sub(
+                "pulse",
+                StructBuilder::new()
+                    .field("pin", 5)
+                    .field("high_us", 10)
+                    .field("count", 3),
+            )
+            .build();
+        assert!(board.do_command(Some(command)).unwrap().is_none());
+        let train = PulseTrain {
+            high: Duration::from_micros(10),
+            low: Duration::from_micros(10),
+            count: 3,
+        };
+        assert_eq!(board.last_pulse(5), Some(train));
+        assert_eq!(train.duration(), Duration::from_micros(60));
+        assert!(!board.get_gpio_level(5).unwrap());
+
+        let command = StructBuilder::new()
+            .sub(
+                "pulse",
+                StructBuilder::new().field("pin", 5).field("high_us", 0),
+            )
+            .build();
+        assert!(board.do_command(Some(command)).is_err());
+        assert!(board
+            .pulse(5, &PulseTrain::single(Duration::from_micros(5)))
+            .is_ok());
+        assert!(board.do_command(None).is_err());
+    }
+
+    #[test_log::test]
+    fn test_fake_board_pwm_frequency() -> Result<(), BoardError> {
+        let mut board = FakeBoard::new(vec![]);
+        let command = StructBuilder::new()
+            .sub(
+                "set_pwm_frequency",
+                StructBuilder::new()
+                    .field("pin", 4)
+                    .field("frequency_hz", 0.5),
+            )
+            .build();
+        assert!(board.do_command(Some(command)).unwrap().is_none());
+        assert_eq!(board.get_pwm_frequency_hz(4)?, 0.5);
+        assert_eq!(board.get_pwm_frequency(4)?, 1);
+
+        let command = StructBuilder::new()
+            .sub("get_pwm_frequency", StructBuilder::new().field("pin", 4))
+            .build();
+        let answer = board.do_command(Some(command)).unwrap().unwrap();
+        assert_eq!(
+            answer.fields.get("frequency_hz").unwrap().kind,
+            Some(Kind::NumberValue(0.5))
+        );
+
+        board.set_pwm_frequency(4, 1000)?;
+        assert_eq!(board.get_pwm_frequency_hz(4)?, 1000.0);
+        assert!(matches!(
+            board.set_pwm_frequency_hz(4, -1.0),
+            Err(BoardError::PwmUnsupported(4, _, _, _))
+        ));
+        assert!(whole_pwm_frequency(0.5).is_err());
+        assert_eq!(whole_pwm_frequency(20.0)?, 20);
+        Ok(())
+    }
 }
@@ -0,0 +1,134 @@
+//! Firmware identification reported through the `GetVersion` method of the robot service and
+//! the agent info sent with config requests, so deployed firmwares can be audited from a fleet
+//! dashboard.
+//!
+//! The git revision is captured by the build script when the crate is built from a git checkout.
+
+use prost::Message;
+
+/// Mirrors `viam.robot.v1.GetVersionRequest`
+#[derive(Clone, PartialEq, Message)]
+pub struct GetVersionRequest {}
+
+/// Mirrors `viam.robot.v1.GetVersionResponse`
+#[derive(Clone, PartialEq, Message)]
+pub struct GetVersionResponse {
+    #[prost(string, tag = "1")]
+    pub platform: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+    #[prost(string, tag = "3")]
+    pub api_version: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_revision: Option<&'static str>,
+    /// ESP-IDF version the firmware was linked against, `None` on native builds
+    pub idf_version: Option<String>,
+    pub chip_model: String,
+}
+
+#[cfg(feature = "esp32")]
+fn platform_info() -> (Option<String>, String) {
+    use crate::esp32::esp_idf_svc::sys::{
+        esp_chip_info, esp_chip_info_t, esp_chip_model_t_CHIP_ESP32, esp_chip_model_t_CHIP_ESP32C3,
+        esp_chip_model_t_CHIP_ESP32S2, esp_chip_model_t_CHIP_ESP32S3, esp_get_idf_version,
+    };
+    let (info, idf_version) = unsafe {
+        let mut info: esp_chip_info_t = core::mem::zeroed();
+        esp_chip_info(&mut info);
+        (
+            info,
+            core::ffi::CStr::from_ptr(esp_get_idf_version())
+                .to_string_lossy()
+                .into_owned(),
+        )
+    };
+    #[allow(non_upper_case_globals)]
+    let model = match info.model {
+        esp_chip_model_t_CHIP_ESP32 => "esp32",
+        esp_chip_model_t_CHIP_ESP32S2 => "esp32s2",
+        esp_chip_model_t_CHIP_ESP32S3 => "esp32s3",
+        esp_chip_model_t_CHIP_ESP32C3 => "esp32c3",
+        _ => "esp32-unknown",
+    };
+    (
+        Some(idf_version),
+        format!("{} rev {}", model, info.revision),
+    )
+}
+
+#[cfg(not(feature = "esp32"))]
+fn platform_info() -> (Option<String>, String) {
+    (
+        None,
+        format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+    )
+}
+
+impl BuildInfo {
+    pub fn get() -> Self {
+        let (idf_version, chip_model) = platform_info();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_revision: option_env!("MICRO_RDK_GIT_REVISION"),
+            idf_version,
+            chip_model,
+        }
+    }
+
+    /// Crate version with the git revision as semver build metadata, e.g. `0.1.7+3f2a9c1`
+    pub fn full_version(&self) -> String {
+        match self.git_revision {
+            Some(rev) => format!("{}+{}", self.version, rev),
+            None => self.version.to_string(),
+        }
+    }
+
+    /// Chip model followed by the ESP-IDF version if any, e.g. `esp32 rev 3 (esp-idf v4.4.6)`
+    pub fn platform(&self) -> String {
+        match self.idf_version.as_ref() {
+            Some(idf) => format!("{} (esp-idf {})", self.chip_model, idf),
+            None => self.chip_model.clone(),
+        }
+    }
+}
+
+impl From<BuildInfo> for GetVersionResponse {
+    fn from(info: BuildInfo) -> Self {
+        Self {
+            platform: info.platform(),
+            version: info.full_version(),
+            api_version: String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildInfo, GetVersionResponse};
+
+    #[test_log::test]
+    fn test_version_response() {
+        let info = BuildInfo {
+            version: "0.1.7",
+            git_revision: Some("3f2a9c1"),
+            idf_version: Some("v4.4.6".to_string()),
+            chip_model: "esp32 rev 3".to_string(),
+        };
+        let resp = GetVersionResponse::from(info.clone());
+        assert_eq!(resp.version, "0.1.7+3f2a9c1");
+        assert_eq!(resp.platform, "esp32 rev 3 (esp-idf v4.4.6)");
+
+        let native = BuildInfo {
+            git_revision: None,
+            idf_version: None,
+            ..info
+        };
+        assert_eq!(native.full_version(), "0.1.7");
+        assert_eq!(native.platform(), "esp32 rev 3");
+        assert_eq!(BuildInfo::get().version, env!("CARGO_PKG_VERSION"));
+    }
+}
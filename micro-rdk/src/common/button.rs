@@ -0,0 +1,484 @@
+//! Buttons are momentary inputs reporting presses and long presses. See
+//! [crate::common::gpio_button] for push buttons wired to a digital interrupt pin of the board.
+//!
+//! Buttons are sampled by the [ButtonWatcher], started with the robot whenever a button is
+//! configured. Detected events are counted in the status of the button, so clients following
+//! `StreamStatus` see them, and posted as JSON to the `webhook_url` of the button when it is set:
+//!
+//! ```json
+//! {"button": "doorbell", "event": "long_press"}
+//! ```
+//!
+//! Webhooks are plain `http://` urls, the events are queued and posted one at a time by a task
+//! of their own so a slow webhook doesn't delay the sampling. Each event is posted once and
+//! dropped if the request fails or the queue is full.
+
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use async_channel::{Receiver, Sender};
+use async_io::Timer;
+use serde::Serialize;
+use thiserror::Error;
+
+use super::{
+    board::BoardError,
+    config::{AttributeError, ConfigType, Kind},
+    generic::DoCommand,
+    grpc::{GrpcError, GrpcStatusHint},
+    registry::{ComponentRegistry, Dependency},
+    robot::LocalRobot,
+    status::{Status, StatusError},
+    struct_builder::StructBuilder,
+    webhook::{Webhook, WebhookError},
+};
+use crate::google::protobuf::{value::Kind as ProtoKind, Struct};
+use crate::proto::app::v1::ConfigResponse;
+
+#[cfg(feature = "esp32")]
+use crate::esp32::exec::Esp32Executor;
+#[cfg(feature = "native")]
+use crate::native::exec::NativeExecutor;
+
+#[cfg(feature = "native")]
+type Executor = NativeExecutor;
+#[cfg(feature = "esp32")]
+type Executor = Esp32Executor;
+
+pub static COMPONENT_NAME: &str = "button";
+
+// buttons are sampled often enough to time presses of a few tens of milliseconds
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+// events waiting for their webhook to be posted
+const WEBHOOK_QUEUE_LEN: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum ButtonError {
+    #[error(transparent)]
+    ButtonBoardError(#[from] BoardError),
+    #[error("config error {0}")]
+    ButtonConfigurationError(&'static str),
+    #[error(transparent)]
+    ButtonConfigAttributeError(#[from] AttributeError),
+    #[error(transparent)]
+    ButtonWebhookError(#[from] WebhookError),
+}
+
+impl GrpcStatusHint for ButtonError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::ButtonBoardError(err) => err.grpc_error(),
+            Self::ButtonConfigurationError(_) => GrpcError::RpcFailedPrecondition,
+            Self::ButtonConfigAttributeError(err) => err.grpc_error(),
+            Self::ButtonWebhookError(WebhookError::InvalidUrl(_)) => {
+                GrpcError::RpcFailedPrecondition
+            }
+            Self::ButtonWebhookError(WebhookError::PostFailed(_)) => GrpcError::RpcUnavailable,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Press,
+    LongPress,
+}
+
+impl ButtonEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Press => "press",
+            Self::LongPress => "long_press",
+        }
+    }
+}
+
+pub trait Button: Status + DoCommand {
+    /// Presses the button from software, reported as a [ButtonEvent::Press]
+    fn push(&mut self) -> Result<(), ButtonError>;
+
+    /// Samples the button, returning the events detected since the last call
+    fn poll(&mut self) -> Result<Vec<ButtonEvent>, ButtonError>;
+}
+
+pub type ButtonType = Arc<Mutex<dyn Button>>;
+
+impl<L> Button for Mutex<L>
+where
+    L: ?Sized + Button,
+{
+    fn push(&mut self) -> Result<(), ButtonError> {
+        self.get_mut().unwrap().push()
+    }
+    fn poll(&mut self) -> Result<Vec<ButtonEvent>, ButtonError> {
+        self.get_mut().unwrap().poll()
+    }
+}
+
+impl<A> Button for Arc<Mutex<A>>
+where
+    A: ?Sized + Button,
+{
+    fn push(&mut self) -> Result<(), ButtonError> {
+        self.lock().unwrap().push()
+    }
+    fn poll(&mut self) -> Result<Vec<ButtonEvent>, ButtonError> {
+        self.lock().unwrap().poll()
+    }
+}
+
+/// Debounce and long press thresholds of a button
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ButtonSettings {
+    pub debounce: Duration,
+    pub long_press: Duration,
+}
+
+impl Default for ButtonSettings {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(50),
+            long_press: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl ButtonSettings {
+    pub fn from_config(cfg: &ConfigType) -> Result<Self, ButtonError> {
+        let default = Self::default();
+        let millis = |key, default: Duration| match cfg.get_attribute::<u64>(key) {
+            Ok(ms) => Ok(Duration::from_millis(ms)),
+            Err(AttributeError::KeyNotFound(_)) => Ok(default),
+            Err(err) => Err(err),
+        };
+        let settings = Self {
+            debounce: millis("debounce_ms", default.debounce)?,
+            long_press: millis("long_press_ms", default.long_press)?,
+        };
+        if settings.long_press <= settings.debounce {
+            return Err(ButtonError::ButtonConfigurationError(
+                "long_press_ms should be greater than debounce_ms",
+            ));
+        }
+        Ok(settings)
+    }
+}
+
+/// Turns raw samples of a button into debounced events. A press is reported on release, a
+/// press held longer than the long press threshold is reported once as a long press instead.
+#[derive(Debug)]
+pub struct ButtonTracker {
+    settings: ButtonSettings,
+    pressed: bool,
+    // raw level differing from the debounced one, and since when
+    pending: Option<(bool, Instant)>,
+    pressed_since: Option<Instant>,
+    last_change: Option<Instant>,
+    long_press_reported: bool,
+    presses: u32,
+    long_presses: u32,
+    last_event: Option<ButtonEvent>,
+}
+
+impl ButtonTracker {
+    pub fn new(settings: ButtonSettings) -> Self {
+        Self {
+            settings,
+            pressed: false,
+            pending: None,
+            pressed_since: None,
+            last_change: None,
+            long_press_reported: false,
+            presses: 0,
+            long_presses: 0,
+            last_event: None,
+        }
+    }
+
+    /// Feeds the level of the button sampled at `now`
+    pub fn update(&mut self, pressed: bool, now: Instant) -> Option<ButtonEvent> {
+        if pressed == self.pressed {
+            self.pending = None;
+        } else {
+            match self.pending {
+                Some((level, since)) if level == pressed => {
+                    if now.duration_since(since) >= self.settings.debounce {
+                        self.pending = None;
+                        self.pressed = pressed;
+                        self.last_change = Some(now);
+                        if pressed {
+                            self.pressed_since = Some(since);
+                            self.long_press_reported = false;
+                        } else {
+                            self.pressed_since = None;
+                            if !self.long_press_reported {
+                                return Some(self.record(ButtonEvent::Press));
+                            }
+                        }
+                    }
+                }
+                _ => self.pending = Some((pressed, now)),
+            }
+        }
+        match self.pressed_since {
+            Some(since)
+                if !self.long_press_reported
+                    && now.duration_since(since) >= self.settings.long_press =>
+            {
+                self.long_press_reported = true;
+                Some(self.record(ButtonEvent::LongPress))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reports a press that started and ended between two samples, as seen by the interrupt
+    /// counter of the pin. Ignored unless the button has been released for longer than the
+    /// debounce time, so the bounces of the previous release or tap aren't counted.
+    pub fn tap(&mut self, now: Instant) -> Option<ButtonEvent> {
+        let quiet = self.last_change.map_or(true, |last| {
+            now.duration_since(last) >= self.settings.debounce
+        });
+        if self.pressed || self.pending.is_some() || !quiet {
+            return None;
+        }
+        self.last_change = Some(now);
+        Some(self.record(ButtonEvent::Press))
+    }
+
+    /// Records an event detected outside of the samples
+    pub fn record(&mut self, event: ButtonEvent) -> ButtonEvent {
+        match event {
+            ButtonEvent::Press => self.presses += 1,
+            ButtonEvent::LongPress => self.long_presses += 1,
+        }
+        self.last_event = Some(event);
+        event
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    pub(crate) fn status_fields(&self) -> [(&'static str, ProtoKind); 4] {
+        [
+            ("pressed", ProtoKind::BoolValue(self.pressed)),
+            ("presses", ProtoKind::NumberValue(self.presses as f64)),
+            (
+                "long_presses",
+                ProtoKind::NumberValue(self.long_presses as f64),
+            ),
+            (
+                "last_event",
+                self.last_event.map_or(ProtoKind::NullValue(0), |event| {
+                    ProtoKind::StringValue(event.as_str().to_string())
+                }),
+            ),
+        ]
+    }
+}
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_button("fake", &FakeButton::from_config)
+        .is_err()
+    {
+        log::error!("fake button type is already registered");
+    }
+}
+
+#[derive(DoCommand)]
+pub struct FakeButton {
+    presses: u32,
+    pending: Vec<ButtonEvent>,
+}
+
+impl FakeButton {
+    pub fn new() -> Self {
+        Self {
+            presses: 0,
+            pending: vec![],
+        }
+    }
+
+    pub(crate) fn from_config(
+        _: ConfigType,
+        _: Vec<Dependency>,
+    ) -> Result<ButtonType, ButtonError> {
+        Ok(Arc::new(Mutex::new(Self::new())))
+    }
+}
+
+impl Default for FakeButton {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Button for FakeButton {
+    fn push(&mut self) -> Result<(), ButtonError> {
+        self.presses += 1;
+        self.pending.push(ButtonEvent::Press);
+        Ok(())
+    }
+    fn poll(&mut self) -> Result<Vec<ButtonEvent>, ButtonError> {
+        Ok(std::mem::take(&mut self.pending))
+    }
+}
+
+impl Status for FakeButton {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(
+            StructBuilder::with_capacity(1)
+                .field("presses", self.presses)
+                .build(),
+        ))
+    }
+}
+
+/// Body of the webhook posts
+#[derive(Serialize)]
+struct ButtonNotification<'a> {
+    button: &'a str,
+    event: &'static str,
+}
+
+// posts the queued events one at a time
+async fn post_events(queue: Receiver<(Webhook, String, String)>) {
+    while let Ok((webhook, name, body)) = queue.recv().await {
+        if let Err(err) = webhook.post(&body).await {
+            log::warn!("button {} webhook failed: {}", name, err);
+        }
+    }
+}
+
+/// Samples the buttons of a robot and notifies their webhooks of the detected events
+pub struct ButtonWatcher {
+    buttons: Vec<(String, ButtonType, Option<Webhook>)>,
+}
+
+impl ButtonWatcher {
+    /// Returns a watcher for the buttons of the robot, `None` when it has no button
+    pub fn from_robot_and_config(
+        cfg: &ConfigResponse,
+        robot: Arc<RwLock<LocalRobot>>,
+    ) -> Result<Option<Self>, ButtonError> {
+        let robot = robot.read().unwrap();
+        let mut buttons = vec![];
+        for comp_cfg in cfg
+            .config
+            .iter()
+            .flat_map(|cfg| cfg.components.iter())
+            .filter(|comp_cfg| comp_cfg.r#type == COMPONENT_NAME)
+        {
+            // a button that failed to build is already reported by the robot
+            let button = match robot.get_button_by_name(comp_cfg.name.clone()) {
+                Some(button) => button,
+                None => continue,
+            };
+            let attributes = Kind::try_from(ProtoKind::StructValue(
+                comp_cfg.attributes.clone().unwrap_or_default(),
+            ))?;
+            let webhook = attributes
+                .get("webhook_url")?
+                .map(<&str>::try_from)
+                .transpose()?
+                .map(Webhook::try_from)
+                .transpose()?;
+            buttons.push((comp_cfg.name.clone(), button, webhook));
+        }
+        Ok((!buttons.is_empty()).then_some(Self { buttons }))
+    }
+
+    pub async fn run(&self) {
+        let (posts, queue) = async_channel::bounded(WEBHOOK_QUEUE_LEN);
+        Executor::new().spawn(post_events(queue)).detach();
+        loop {
+            for (name, button, webhook) in &self.buttons {
+                let events = match button.lock().unwrap().poll() {
+                    Ok(events) => events,
+                    Err(err) => {
+                        log::error!("couldn't sample button {}: {:?}", name, err);
+                        continue;
+                    }
+                };
+                for event in events {
+                    log::info!("button {}: {}", name, event.as_str());
+                    if let Some(webhook) = webhook {
+                        Self::queue_post(&posts, webhook, name, event);
+                    }
+                }
+            }
+            Timer::after(POLL_INTERVAL).await;
+        }
+    }
+
+    fn queue_post(
+        posts: &Sender<(Webhook, String, String)>,
+        webhook: &Webhook,
+        name: &str,
+        event: ButtonEvent,
+    ) {
+        let body = match serde_json::to_string(&ButtonNotification {
+            button: name,
+            event: event.as_str(),
+        }) {
+            Ok(body) => body,
+            Err(err) => {
+                log::warn!("button {} webhook failed: {:?}", name, err);
+                return;
+            }
+        };
+        if posts
+            .try_send((webhook.clone(), name.to_string(), body))
+            .is_err()
+        {
+            log::warn!("button {} webhook queue is full, dropping the event", name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{ButtonEvent, ButtonSettings, ButtonTracker};
+
+    #[test_log::test]
+    fn test_button_tracker() {
+        let mut tracker = ButtonTracker::new(ButtonSettings {
+            debounce: Duration::from_millis(50),
+            long_press: Duration::from_millis(500),
+        });
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // bounces shorter than the debounce time are ignored
+        assert_eq!(tracker.update(true, at(0)), None);
+        assert_eq!(tracker.update(false, at(10)), None);
+        assert_eq!(tracker.update(true, at(20)), None);
+        assert_eq!(tracker.update(true, at(60)), None);
+        assert!(!tracker.is_pressed());
+        assert_eq!(tracker.update(true, at(80)), None);
+        assert!(tracker.is_pressed());
+        assert_eq!(tracker.update(false, at(200)), None);
+        assert_eq!(tracker.update(false, at(260)), Some(ButtonEvent::Press));
+
+        assert_eq!(tracker.update(true, at(1000)), None);
+        assert_eq!(tracker.update(true, at(1100)), None);
+        assert_eq!(tracker.update(true, at(1500)), Some(ButtonEvent::LongPress));
+        assert_eq!(tracker.update(true, at(2000)), None);
+        assert_eq!(tracker.update(false, at(2100)), None);
+        assert_eq!(tracker.update(false, at(2200)), None);
+        assert!(!tracker.is_pressed());
+        assert_eq!((tracker.presses, tracker.long_presses), (1, 1));
+
+        // a tap between two samples is only counted once the release has settled
+        assert_eq!(tracker.tap(at(2210)), None);
+        assert_eq!(tracker.tap(at(2300)), Some(ButtonEvent::Press));
+        // and the bounces of a tap aren't counted as more taps
+        assert_eq!(tracker.tap(at(2310)), None);
+        assert_eq!(tracker.tap(at(2400)), Some(ButtonEvent::Press));
+        assert_eq!(tracker.presses, 3);
+    }
+}
@@ -0,0 +1,695 @@
+//! Package calculated implements a sensor whose readings are computed from the readings of other
+//! sensors with arithmetic expressions, for unit conversions or combining several sensors
+//! without building a custom firmware.
+//!
+//! ```json
+//! {
+//!     "outputs": [
+//!         { "name": "depth_m", "expression": "(pressure.voltage - 0.5) * 2.5" },
+//!         { "name": "depth_ft", "expression": "max((pressure.voltage - 0.5) * 8.2, 0)" },
+//!         { "name": "dew_point_c", "expression": "'env-1.temperature' - (100 - 'env-1.humidity') / 5" }
+//!     ]
+//! }
+//! ```
+//!
+//! Variables are written `sensor.reading`, the sensors they name are the dependencies of the
+//! calculated sensor. Names containing other characters than letters, digits, `_` and `.` are
+//! quoted with `'`. Readings must be numbers, booleans are read as 0 or 1.
+//!
+//! Expressions support `+`, `-`, `*`, `/`, `%`, `^` (power), parentheses and the functions
+//! `abs`, `sqrt`, `exp`, `ln`, `log10`, `round`, `floor`, `ceil`, `min`, `max` and
+//! `clamp(x, low, high)`. They are parsed when the sensor is built, so an invalid expression
+//! fails the configuration of the sensor instead of its readings. Expressions nest at most
+//! [MAX_EXPRESSION_DEPTH] levels deep, each parenthesis, function call, negation or operator
+//! adding a level.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+use super::config::{AttributeError, ConfigType, Kind};
+use super::registry::{ComponentRegistry, Dependency, ResourceKey};
+use super::robot::Resource;
+use super::sensor::{
+    GenericReadingsResult, Readings, Sensor, SensorError, SensorResult, SensorType,
+    COMPONENT_NAME as SensorCompName,
+};
+use super::status::ComponentHealth;
+use crate::google::protobuf::value::Kind as ProtoKind;
+
+/// Levels an expression may nest, bounding the recursion of the parser and of the evaluation
+/// on the small task stacks of the esp32
+pub const MAX_EXPRESSION_DEPTH: usize = 32;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("calculated", &CalculatedSensor::from_config)
+        .is_err()
+    {
+        log::error!("calculated type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            SensorCompName,
+            "calculated",
+            &CalculatedSensor::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("failed to register dependency getter for calculated model")
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("{reason} at position {position}")]
+pub struct ExpressionError {
+    pub position: usize,
+    pub reason: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Round,
+    Floor,
+    Ceil,
+    Min,
+    Max,
+    Clamp,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Self::Abs,
+            "sqrt" => Self::Sqrt,
+            "exp" => Self::Exp,
+            "ln" => Self::Ln,
+            "log10" => Self::Log10,
+            "round" => Self::Round,
+            "floor" => Self::Floor,
+            "ceil" => Self::Ceil,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "clamp" => Self::Clamp,
+            _ => return None,
+        })
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Self::Min | Self::Max => 2,
+            Self::Clamp => 3,
+            _ => 1,
+        }
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        match self {
+            Self::Abs => args[0].abs(),
+            Self::Sqrt => args[0].sqrt(),
+            Self::Exp => args[0].exp(),
+            Self::Ln => args[0].ln(),
+            Self::Log10 => args[0].log10(),
+            Self::Round => args[0].round(),
+            Self::Floor => args[0].floor(),
+            Self::Ceil => args[0].ceil(),
+            Self::Min => args[0].min(args[1]),
+            Self::Max => args[0].max(args[1]),
+            Self::Clamp => args[0].max(args[1]).min(args[2]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+/// A parsed arithmetic expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    /// A reading of a sensor, as (sensor name, reading name)
+    Variable(String, String),
+    Neg(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+impl Expression {
+    pub fn parse(input: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            position: 0,
+            depth: 0,
+        };
+        let expression = parser.expression()?;
+        parser.skip_whitespace();
+        if parser.position != input.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(expression)
+    }
+
+    /// Calls `f` with the sensor and reading name of every variable of the expression
+    pub fn for_each_variable(&self, f: &mut impl FnMut(&str, &str)) {
+        match self {
+            Self::Number(_) => {}
+            Self::Variable(sensor, reading) => f(sensor, reading),
+            Self::Neg(inner) => inner.for_each_variable(f),
+            Self::Binary(_, lhs, rhs) => {
+                lhs.for_each_variable(f);
+                rhs.for_each_variable(f);
+            }
+            Self::Call(_, args) => args.iter().for_each(|arg| arg.for_each_variable(f)),
+        }
+    }
+
+    /// Evaluates the expression, `lookup` returns the value of a variable or `None` when the
+    /// reading isn't available
+    pub fn evaluate(&self, lookup: &impl Fn(&str, &str) -> Option<f64>) -> Option<f64> {
+        Some(match self {
+            Self::Number(value) => *value,
+            Self::Variable(sensor, reading) => lookup(sensor, reading)?,
+            Self::Neg(inner) => -inner.evaluate(lookup)?,
+            Self::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(lookup)?, rhs.evaluate(lookup)?);
+                match op {
+                    Operator::Add => lhs + rhs,
+                    Operator::Sub => lhs - rhs,
+                    Operator::Mul => lhs * rhs,
+                    Operator::Div => lhs / rhs,
+                    Operator::Rem => lhs % rhs,
+                    Operator::Pow => lhs.powf(rhs),
+                }
+            }
+            Self::Call(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(lookup))
+                    .collect::<Option<Vec<_>>>()?;
+                function.apply(&args)
+            }
+        })
+    }
+}
+
+// recursive descent parser, from the lowest to the highest precedence:
+//   expression := term (('+' | '-') term)*
+//   term := unary (('*' | '/' | '%') unary)*
+//   unary := '-' unary | power
+//   power := atom ('^' unary)?
+//   atom := number | variable | function '(' expression (',' expression)* ')' | '(' expression ')'
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    // nesting of the expression being parsed, an operator chain like `1 + 2 + 3` nests too as
+    // it builds a tree as deep as the chain is long
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, reason: &'static str) -> ExpressionError {
+        ExpressionError {
+            position: self.position,
+            reason,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.position)
+            .is_some_and(|c| c.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    // next non whitespace character, without consuming it
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.position).copied()
+    }
+
+    fn descend(&mut self) -> Result<(), ExpressionError> {
+        self.depth += 1;
+        if self.depth > MAX_EXPRESSION_DEPTH {
+            return Err(self.error("expression nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, c: u8, reason: &'static str) -> Result<(), ExpressionError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(reason));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<Expression, ExpressionError> {
+        let depth = self.depth;
+        let mut lhs = self.term()?;
+        while let Some(op) = match self.peek() {
+            Some(b'+') => Some(Operator::Add),
+            Some(b'-') => Some(Operator::Sub),
+            _ => None,
+        } {
+            self.position += 1;
+            self.descend()?;
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expression, ExpressionError> {
+        let depth = self.depth;
+        let mut lhs = self.unary()?;
+        while let Some(op) = match self.peek() {
+            Some(b'*') => Some(Operator::Mul),
+            Some(b'/') => Some(Operator::Div),
+            Some(b'%') => Some(Operator::Rem),
+            _ => None,
+        } {
+            self.position += 1;
+            self.descend()?;
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        let depth = self.depth;
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+            self.descend()?;
+            let inner = self.unary()?;
+            self.depth = depth;
+            return Ok(Expression::Neg(Box::new(inner)));
+        }
+        let base = self.atom()?;
+        if self.peek() == Some(b'^') {
+            self.position += 1;
+            self.descend()?;
+            // right associative, 2^3^2 is 2^(3^2)
+            let exponent = self.unary()?;
+            self.depth = depth;
+            return Ok(Expression::Binary(
+                Operator::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expression, ExpressionError> {
+        match self.peek() {
+            Some(b'(') => {
+                self.position += 1;
+                self.descend()?;
+                let inner = self.expression()?;
+                self.expect(b')', "missing closing parenthesis")?;
+                self.depth -= 1;
+                Ok(inner)
+            }
+            Some(c) if c.is_ascii_digit() || c == b'.' => self.number(),
+            Some(b'\'') => {
+                self.position += 1;
+                let start = self.position;
+                while self.input.get(self.position).is_some_and(|c| *c != b'\'') {
+                    self.position += 1;
+                }
+                let name = self.name(start)?;
+                self.expect(b'\'', "missing closing quote")?;
+                self.variable(name, start)
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == b'_' => {
+                let start = self.position;
+                while self
+                    .input
+                    .get(self.position)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_' || *c == b'.')
+                {
+                    self.position += 1;
+                }
+                let name = self.name(start)?;
+                if self.peek() == Some(b'(') {
+                    let function = Function::from_name(name).ok_or(ExpressionError {
+                        position: start,
+                        reason: "unknown function",
+                    })?;
+                    self.call(function)
+                } else {
+                    self.variable(name, start)
+                }
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+
+    fn name(&self, start: usize) -> Result<&'a str, ExpressionError> {
+        let input: &'a [u8] = self.input;
+        std::str::from_utf8(&input[start..self.position]).map_err(|_| ExpressionError {
+            position: start,
+            reason: "invalid name",
+        })
+    }
+
+    fn variable(&self, name: &str, start: usize) -> Result<Expression, ExpressionError> {
+        match name.split_once('.') {
+            Some((sensor, reading)) if !sensor.is_empty() && !reading.is_empty() => Ok(
+                Expression::Variable(sensor.to_string(), reading.to_string()),
+            ),
+            _ => Err(ExpressionError {
+                position: start,
+                reason: "variables are written sensor.reading",
+            }),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expression, ExpressionError> {
+        let start = self.position;
+        while self.input.get(self.position).is_some_and(|c| {
+            c.is_ascii_digit()
+                || *c == b'.'
+                || ((*c == b'e' || *c == b'E')
+                    && self
+                        .input
+                        .get(self.position + 1)
+                        .is_some_and(|n| n.is_ascii_digit() || *n == b'-' || *n == b'+'))
+                || ((*c == b'-' || *c == b'+')
+                    && self.position > start
+                    && matches!(self.input[self.position - 1], b'e' | b'E'))
+        }) {
+            self.position += 1;
+        }
+        self.name(start)?
+            .parse::<f64>()
+            .map(Expression::Number)
+            .map_err(|_| ExpressionError {
+                position: start,
+                reason: "invalid number",
+            })
+    }
+
+    fn call(&mut self, function: Function) -> Result<Expression, ExpressionError> {
+        self.expect(b'(', "missing opening parenthesis")?;
+        self.descend()?;
+        let mut args = vec![self.expression()?];
+        while self.peek() == Some(b',') {
+            self.position += 1;
+            args.push(self.expression()?);
+        }
+        if args.len() != function.arity() {
+            return Err(self.error("wrong number of arguments"));
+        }
+        self.expect(b')', "missing closing parenthesis")?;
+        self.depth -= 1;
+        Ok(Expression::Call(function, args))
+    }
+}
+
+/// An element of the `outputs` attribute of a calculated sensor
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalculatedOutput {
+    pub name: String,
+    pub expression: Expression,
+}
+
+impl TryFrom<&Kind> for CalculatedOutput {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let name = value
+            .get("name")?
+            .ok_or(AttributeError::KeyNotFound("name".to_string()))?
+            .try_into()?;
+        let expression: &str = value
+            .get("expression")?
+            .ok_or(AttributeError::KeyNotFound("expression".to_string()))?
+            .try_into()?;
+        let expression = Expression::parse(expression).map_err(|err| {
+            log::error!("calculated sensor: `{}`: {}", expression, err);
+            AttributeError::ConversionImpossibleError
+        })?;
+        Ok(CalculatedOutput { name, expression })
+    }
+}
+
+#[derive(DoCommand, Status)]
+pub struct CalculatedSensor {
+    sensors: Vec<(String, SensorType)>,
+    outputs: Vec<CalculatedOutput>,
+    #[status(health)]
+    health: ComponentHealth,
+}
+
+impl CalculatedSensor {
+    /// Builds the sensor, `sensors` must contain every sensor named by the outputs
+    pub fn new(
+        sensors: Vec<(String, SensorType)>,
+        outputs: Vec<CalculatedOutput>,
+    ) -> Result<Self, SensorError> {
+        if outputs.is_empty() {
+            return Err(SensorError::ConfigError("calculated sensor has no outputs"));
+        }
+        let mut missing = false;
+        for output in outputs.iter() {
+            output.expression.for_each_variable(&mut |sensor, _| {
+                missing |= !sensors.iter().any(|(name, _)| name == sensor)
+            });
+        }
+        if missing {
+            return Err(SensorError::ConfigError(
+                "calculated sensor dependency not found",
+            ));
+        }
+        Ok(Self {
+            sensors,
+            outputs,
+            health: ComponentHealth::new(),
+        })
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let outputs = cfg
+            .get_attribute::<Vec<CalculatedOutput>>("outputs")
+            .map_err(|_| {
+                SensorError::ConfigError("calculated sensor missing or invalid outputs")
+            })?;
+        let sensors = deps
+            .into_iter()
+            .filter_map(|Dependency(key, res)| match res {
+                Resource::Sensor(sensor) => Some((key.1, sensor)),
+                _ => None,
+            })
+            .collect();
+        Ok(Arc::new(Mutex::new(Self::new(sensors, outputs)?)))
+    }
+
+    pub(crate) fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        let mut r_keys: Vec<ResourceKey> = Vec::new();
+        if let Ok(outputs) = cfg.get_attribute::<Vec<CalculatedOutput>>("outputs") {
+            for output in outputs.iter() {
+                output.expression.for_each_variable(&mut |sensor, _| {
+                    if !r_keys.iter().any(|key| key.1 == sensor) {
+                        r_keys.push(ResourceKey(SensorCompName, sensor.to_string()));
+                    }
+                });
+            }
+        }
+        r_keys
+    }
+
+    fn compute(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let mut readings = HashMap::new();
+        for (name, sensor) in self.sensors.iter_mut() {
+            readings.insert(name.as_str(), sensor.get_generic_readings()?);
+        }
+        let lookup = |sensor: &str, reading: &str| match readings
+            .get(sensor)?
+            .get(reading)?
+            .kind
+            .as_ref()?
+        {
+            ProtoKind::NumberValue(value) => Some(*value),
+            ProtoKind::BoolValue(value) => Some(if *value { 1.0 } else { 0.0 }),
+            _ => None,
+        };
+        self.outputs
+            .iter()
+            .map(|output| {
+                let value = output.expression.evaluate(&lookup).ok_or_else(|| {
+                    log::warn!(
+                        "calculated sensor: a reading used by {} is missing or isn't a number",
+                        output.name
+                    );
+                    SensorError::SensorGenericError("calculated sensor reading not found")
+                })?;
+                if !value.is_finite() {
+                    return Err(SensorError::SensorGenericError(
+                        "calculated sensor output isn't a finite number",
+                    ));
+                }
+                Ok((output.name.clone(), SensorResult::<f64> { value }.into()))
+            })
+            .collect()
+    }
+}
+
+impl Sensor for CalculatedSensor {}
+
+impl Readings for CalculatedSensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let res = self.compute();
+        self.health.record(&res);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CalculatedOutput, CalculatedSensor, Expression, ExpressionError, MAX_EXPRESSION_DEPTH,
+    };
+    use crate::common::sensor::{
+        GenericReadingsResult, Readings, Sensor, SensorError, SensorResult, SensorType,
+    };
+    use crate::common::status::{Status, StatusError};
+    use crate::google::protobuf::{value::Kind, Struct};
+    use std::sync::{Arc, Mutex};
+
+    fn evaluate(input: &str) -> Option<f64> {
+        Expression::parse(input)
+            .unwrap()
+            .evaluate(&|sensor, reading| match (sensor, reading) {
+                ("adc", "voltage") => Some(1.5),
+                ("env-1", "humidity") => Some(40.0),
+                _ => None,
+            })
+    }
+
+    #[test_log::test]
+    fn test_expressions() {
+        assert_eq!(evaluate("1 + 2 * 3"), Some(7.0));
+        assert_eq!(evaluate("(1 + 2) * 3"), Some(9.0));
+        assert_eq!(evaluate("-2 ^ 2"), Some(-4.0));
+        assert_eq!(evaluate("2 ^ 3 ^ 2"), Some(512.0));
+        assert_eq!(evaluate("10 - 4 - 3"), Some(3.0));
+        assert_eq!(evaluate("7 % 4 / 2"), Some(1.5));
+        assert_eq!(evaluate("1.5e3 + 5E-1"), Some(1500.5));
+        assert_eq!(evaluate("(adc.voltage - 0.5) * 2.5"), Some(2.5));
+        assert_eq!(evaluate("'env-1.humidity' / 100"), Some(0.4));
+        assert_eq!(evaluate("clamp(adc.voltage * 100, 0, 100)"), Some(100.0));
+        assert_eq!(evaluate("max(-sqrt(4), abs(-3))"), Some(3.0));
+        assert_eq!(evaluate("adc.current"), None);
+
+        let error = |input: &str| Expression::parse(input).unwrap_err();
+        assert_eq!(
+            error("(1 + 2"),
+            ExpressionError {
+                position: 6,
+                reason: "missing closing parenthesis"
+            }
+        );
+        assert_eq!(error("1 +").reason, "unexpected end of expression");
+        assert_eq!(
+            error("voltage * 2").reason,
+            "variables are written sensor.reading"
+        );
+        assert_eq!(error("pow(2, 3)").reason, "unknown function");
+        assert_eq!(error("min(2)").reason, "wrong number of arguments");
+        assert_eq!(error("1 2").reason, "unexpected character");
+
+        // deeply nested expressions are refused rather than overflowing the stack
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(evaluate(&nested(MAX_EXPRESSION_DEPTH)), Some(1.0));
+        assert_eq!(
+            error(&nested(MAX_EXPRESSION_DEPTH + 1)).reason,
+            "expression nested too deeply"
+        );
+        assert_eq!(
+            error(&"-".repeat(100_000)).reason,
+            "expression nested too deeply"
+        );
+        assert_eq!(
+            error(&format!("{}1", "abs(".repeat(10_000))).reason,
+            "expression nested too deeply"
+        );
+        assert_eq!(
+            error(&["1"; 1000].join(" + ")).reason,
+            "expression nested too deeply"
+        );
+        assert_eq!(evaluate(&["1"; 20].join(" + ")), Some(20.0));
+    }
+
+    #[derive(DoCommand)]
+    struct FakeAnalogSensor {
+        voltage: Arc<Mutex<f64>>,
+    }
+
+    impl Sensor for FakeAnalogSensor {}
+
+    impl Status for FakeAnalogSensor {
+        fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+            Ok(None)
+        }
+    }
+
+    impl Readings for FakeAnalogSensor {
+        fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+            Ok(GenericReadingsResult::from([(
+                "voltage".to_string(),
+                SensorResult::<f64> {
+                    value: *self.voltage.lock().unwrap(),
+                }
+                .into(),
+            )]))
+        }
+    }
+
+    #[test_log::test]
+    fn test_calculated_sensor() -> Result<(), SensorError> {
+        let voltage = Arc::new(Mutex::new(0.5));
+        let adc: SensorType = Arc::new(Mutex::new(FakeAnalogSensor {
+            voltage: voltage.clone(),
+        }));
+        let output = |name: &str, expression: &str| CalculatedOutput {
+            name: name.to_string(),
+            expression: Expression::parse(expression).unwrap(),
+        };
+        let outputs = vec![
+            output("depth_m", "(adc.voltage - 0.5) * 2.5"),
+            output("ratio", "1 / (adc.voltage - 0.5)"),
+        ];
+        assert!(CalculatedSensor::new(vec![], outputs.clone()).is_err());
+
+        let mut sensor = CalculatedSensor::new(vec![("adc".to_string(), adc)], outputs)?;
+        // dividing by zero fails the readings instead of reporting an infinite value
+        assert!(sensor.get_generic_readings().is_err());
+
+        *voltage.lock().unwrap() = 2.5;
+        let readings = sensor.get_generic_readings()?;
+        assert_eq!(readings["depth_m"].kind, Some(Kind::NumberValue(5.0)));
+        assert_eq!(readings["ratio"].kind, Some(Kind::NumberValue(0.5)));
+        Ok(())
+    }
+}
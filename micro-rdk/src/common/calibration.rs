@@ -0,0 +1,420 @@
+//! Calibrations of sensors (gyroscope bias, hard/soft iron, tare...) computed on the device and
+//! persisted across reboots. Calibrations are stored per component in a
+//! [CalibrationStorage], on the esp32 an NVS namespace (see
+//! [NvsCalibrationStorage](crate::esp32::calibration::NvsCalibrationStorage)) set with
+//! [set_calibration_storage] before the robot is built. Without it calibrations are kept in
+//! memory and lost on restart.
+//!
+//! Drivers supporting calibration answer the DoCommands parsed by [CalibrationCommand]:
+//!
+//! - `{"calibrate": {...}}` runs the calibration routine of the driver and persists its result,
+//!   the arguments depend on the driver
+//! - `{"get_calibration": {}}` returns the calibration in use
+//! - `{"clear_calibration": {}}` forgets the calibration
+//!
+//! The kinds of calibration are [ImuCalibration] (applied by the MPU6050),
+//! [MagnetometerCalibration] for hard and soft iron distortions and [LoadCellCalibration] for
+//! the tare and scale of a load cell.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use super::math_utils::Vector3;
+use super::struct_builder::json_to_value;
+use crate::google::protobuf::{value::Kind, Struct};
+
+/// Standard gravity, the acceleration read by a still and level accelerometer on its z axis
+pub const STANDARD_GRAVITY: f64 = 9.80665;
+
+static STORAGE: OnceLock<Box<dyn CalibrationStorage>> = OnceLock::new();
+
+#[derive(Debug, Error)]
+pub enum CalibrationError {
+    #[error("calibration storage error: {0}")]
+    StorageError(String),
+    #[error("calibration storage was already set")]
+    StorageAlreadySet,
+    #[error("not enough samples to calibrate")]
+    NotEnoughSamples,
+    #[error("samples don't cover the {0} axis, rotate the sensor while calibrating")]
+    AxisNotCovered(&'static str),
+    #[error(transparent)]
+    SerializationError(#[from] serde_json::Error),
+}
+
+/// Key-value storage the calibrations are persisted in
+pub trait CalibrationStorage: Send + Sync {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, CalibrationError>;
+    fn store(&self, key: &str, value: &[u8]) -> Result<(), CalibrationError>;
+    fn remove(&self, key: &str) -> Result<(), CalibrationError>;
+}
+
+#[derive(Default)]
+pub struct MemoryCalibrationStorage {
+    entries: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl CalibrationStorage for MemoryCalibrationStorage {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, CalibrationError> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+    fn store(&self, key: &str, value: &[u8]) -> Result<(), CalibrationError> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), value.to_vec());
+        Ok(())
+    }
+    fn remove(&self, key: &str) -> Result<(), CalibrationError> {
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// Persists the calibrations in `storage`, can only be done once
+pub fn set_calibration_storage(
+    storage: impl CalibrationStorage + 'static,
+) -> Result<(), CalibrationError> {
+    STORAGE
+        .set(Box::new(storage))
+        .map_err(|_| CalibrationError::StorageAlreadySet)
+}
+
+fn storage() -> &'static dyn CalibrationStorage {
+    STORAGE
+        .get_or_init(|| Box::<MemoryCalibrationStorage>::default())
+        .as_ref()
+}
+
+/// A calibration that can be persisted, serialized as JSON
+pub trait Calibration: Serialize + DeserializeOwned {
+    /// Tag of the kind of calibration, NVS keys are limited to 15 characters so it is at most
+    /// 7 characters long
+    const KIND: &'static str;
+
+    // keys are made of the kind and a hash of the name of the component
+    fn key(component: &str) -> String {
+        let hash = component.bytes().fold(0x811c9dc5_u32, |hash, b| {
+            (hash ^ b as u32).wrapping_mul(0x01000193)
+        });
+        format!("{}{:08x}", Self::KIND, hash)
+    }
+
+    /// Calibration persisted for `component`, `None` when it was never calibrated
+    fn load(component: &str) -> Result<Option<Self>, CalibrationError> {
+        storage()
+            .load(&Self::key(component))?
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn store(&self, component: &str) -> Result<(), CalibrationError> {
+        storage().store(&Self::key(component), &serde_json::to_vec(self)?)
+    }
+
+    fn clear(component: &str) -> Result<(), CalibrationError> {
+        storage().remove(&Self::key(component))
+    }
+
+    /// The calibration as the answer of a DoCommand
+    fn to_struct(&self) -> Result<Struct, CalibrationError> {
+        match json_to_value(serde_json::to_value(self)?).kind {
+            Some(Kind::StructValue(calibration)) => Ok(calibration),
+            _ => Ok(Struct::default()),
+        }
+    }
+}
+
+/// Calibration DoCommands understood by drivers
+#[derive(Debug, PartialEq)]
+pub enum CalibrationCommand {
+    Calibrate(Struct),
+    Get,
+    Clear,
+}
+
+impl CalibrationCommand {
+    /// Calibration command in `command`, `None` when it holds none
+    pub fn from_command(command: &Struct) -> Option<Self> {
+        if let Some(args) = command.fields.get("calibrate") {
+            return Some(Self::Calibrate(match &args.kind {
+                Some(Kind::StructValue(args)) => args.clone(),
+                _ => Struct::default(),
+            }));
+        }
+        if command.fields.contains_key("get_calibration") {
+            return Some(Self::Get);
+        }
+        if command.fields.contains_key("clear_calibration") {
+            return Some(Self::Clear);
+        }
+        None
+    }
+}
+
+/// Number argument `key` of a calibrate command
+pub fn number_arg(args: &Struct, key: &str) -> Option<f64> {
+    match args.fields.get(key)?.kind {
+        Some(Kind::NumberValue(value)) => Some(value),
+        _ => None,
+    }
+}
+
+fn mean(samples: &[Vector3]) -> Result<[f64; 3], CalibrationError> {
+    if samples.is_empty() {
+        return Err(CalibrationError::NotEnoughSamples);
+    }
+    let n = samples.len() as f64;
+    Ok(samples.iter().fold([0.0; 3], |acc, v| {
+        [acc[0] + v.x / n, acc[1] + v.y / n, acc[2] + v.z / n]
+    }))
+}
+
+/// Gyroscope bias and accelerometer offsets of an IMU
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImuCalibration {
+    pub gyro_bias: [f64; 3],
+    pub accel_offset: [f64; 3],
+}
+
+impl Calibration for ImuCalibration {
+    const KIND: &'static str = "imu";
+}
+
+impl ImuCalibration {
+    /// Calibration from samples taken while the IMU is still and level, z axis up
+    pub fn from_samples(gyro: &[Vector3], accel: &[Vector3]) -> Result<Self, CalibrationError> {
+        let gyro_bias = mean(gyro)?;
+        let accel_mean = mean(accel)?;
+        Ok(Self {
+            gyro_bias,
+            accel_offset: [
+                accel_mean[0],
+                accel_mean[1],
+                accel_mean[2] - STANDARD_GRAVITY,
+            ],
+        })
+    }
+
+    pub fn apply_gyro(&self, v: Vector3) -> Vector3 {
+        Vector3 {
+            x: v.x - self.gyro_bias[0],
+            y: v.y - self.gyro_bias[1],
+            z: v.z - self.gyro_bias[2],
+        }
+    }
+
+    pub fn apply_accel(&self, v: Vector3) -> Vector3 {
+        Vector3 {
+            x: v.x - self.accel_offset[0],
+            y: v.y - self.accel_offset[1],
+            z: v.z - self.accel_offset[2],
+        }
+    }
+}
+
+/// Hard iron offsets and soft iron scales (diagonal only) of a magnetometer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MagnetometerCalibration {
+    pub hard_iron: [f64; 3],
+    pub soft_iron: [f64; 3],
+}
+
+impl Default for MagnetometerCalibration {
+    fn default() -> Self {
+        Self {
+            hard_iron: [0.0; 3],
+            soft_iron: [1.0; 3],
+        }
+    }
+}
+
+impl Calibration for MagnetometerCalibration {
+    const KIND: &'static str = "mag";
+}
+
+impl MagnetometerCalibration {
+    /// Calibration from samples taken while rotating the sensor in every direction
+    pub fn from_samples(samples: &[Vector3]) -> Result<Self, CalibrationError> {
+        if samples.len() < 2 {
+            return Err(CalibrationError::NotEnoughSamples);
+        }
+        let axis = |get: fn(&Vector3) -> f64| {
+            samples
+                .iter()
+                .map(get)
+                .fold((f64::MAX, f64::MIN), |(min, max), v| {
+                    (min.min(v), max.max(v))
+                })
+        };
+        let ranges = [axis(|v| v.x), axis(|v| v.y), axis(|v| v.z)];
+        let mut radii = [0.0; 3];
+        for (idx, (min, max)) in ranges.iter().enumerate() {
+            radii[idx] = (max - min) / 2.0;
+            if radii[idx] <= f64::EPSILON {
+                return Err(CalibrationError::AxisNotCovered(["x", "y", "z"][idx]));
+            }
+        }
+        let radius = radii.iter().sum::<f64>() / 3.0;
+        Ok(Self {
+            hard_iron: ranges.map(|(min, max)| (max + min) / 2.0),
+            soft_iron: radii.map(|r| radius / r),
+        })
+    }
+
+    pub fn apply(&self, v: Vector3) -> Vector3 {
+        Vector3 {
+            x: (v.x - self.hard_iron[0]) * self.soft_iron[0],
+            y: (v.y - self.hard_iron[1]) * self.soft_iron[1],
+            z: (v.z - self.hard_iron[2]) * self.soft_iron[2],
+        }
+    }
+}
+
+/// Tare (raw reading with no load) and scale (weight per raw unit) of a load cell
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoadCellCalibration {
+    pub tare: f64,
+    pub scale: f64,
+}
+
+impl Default for LoadCellCalibration {
+    fn default() -> Self {
+        Self {
+            tare: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Calibration for LoadCellCalibration {
+    const KIND: &'static str = "load";
+}
+
+impl LoadCellCalibration {
+    /// Sets the tare from raw readings taken with no load
+    pub fn tare(&mut self, raw: &[f64]) -> Result<(), CalibrationError> {
+        if raw.is_empty() {
+            return Err(CalibrationError::NotEnoughSamples);
+        }
+        self.tare = raw.iter().sum::<f64>() / raw.len() as f64;
+        Ok(())
+    }
+
+    /// Sets the scale from raw readings taken with `known_weight` on the cell, after taring
+    pub fn scale(&mut self, raw: &[f64], known_weight: f64) -> Result<(), CalibrationError> {
+        if raw.is_empty() {
+            return Err(CalibrationError::NotEnoughSamples);
+        }
+        let loaded = raw.iter().sum::<f64>() / raw.len() as f64 - self.tare;
+        if loaded.abs() <= f64::EPSILON {
+            return Err(CalibrationError::NotEnoughSamples);
+        }
+        self.scale = known_weight / loaded;
+        Ok(())
+    }
+
+    pub fn apply(&self, raw: f64) -> f64 {
+        (raw - self.tare) * self.scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Calibration, CalibrationCommand, ImuCalibration, LoadCellCalibration,
+        MagnetometerCalibration, STANDARD_GRAVITY,
+    };
+    use crate::common::math_utils::Vector3;
+    use crate::common::struct_builder::StructBuilder;
+
+    fn v(x: f64, y: f64, z: f64) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    #[test_log::test]
+    fn test_imu_calibration() {
+        let gyro = [v(0.5, -1.0, 0.25), v(0.7, -1.2, 0.25)];
+        let accel = [v(0.1, 0.2, STANDARD_GRAVITY + 0.3)];
+        let calibration = ImuCalibration::from_samples(&gyro, &accel).unwrap();
+        let gyro = calibration.apply_gyro(v(0.6, -1.1, 0.25));
+        assert!(gyro.x.abs() < 1e-9 && gyro.y.abs() < 1e-9 && gyro.z.abs() < 1e-9);
+        let accel = calibration.apply_accel(v(0.1, 0.2, STANDARD_GRAVITY + 0.3));
+        assert!((accel.z - STANDARD_GRAVITY).abs() < 1e-9);
+        assert!(ImuCalibration::from_samples(&[], &[]).is_err());
+
+        assert_eq!(ImuCalibration::load("imu-test").unwrap(), None);
+        calibration.store("imu-test").unwrap();
+        assert_eq!(
+            ImuCalibration::load("imu-test").unwrap(),
+            Some(calibration.clone())
+        );
+        // calibrations of other components or kinds are kept apart
+        assert_eq!(ImuCalibration::load("imu-other").unwrap(), None);
+        assert_eq!(MagnetometerCalibration::load("imu-test").unwrap(), None);
+        ImuCalibration::clear("imu-test").unwrap();
+        assert_eq!(ImuCalibration::load("imu-test").unwrap(), None);
+        assert!(ImuCalibration::key("a-rather-long-component-name").len() <= 15);
+
+        let fields = calibration.to_struct().unwrap().fields;
+        assert!(fields.contains_key("gyro_bias") && fields.contains_key("accel_offset"));
+    }
+
+    #[test_log::test]
+    fn test_magnetometer_calibration() {
+        let samples = [v(10.0, -5.0, 2.0), v(-2.0, 3.0, 0.0), v(4.0, -1.0, 4.0)];
+        let calibration = MagnetometerCalibration::from_samples(&samples).unwrap();
+        assert_eq!(calibration.hard_iron, [4.0, -1.0, 2.0]);
+        // radii are 6, 4 and 2, their mean is 4
+        let corrected = calibration.apply(v(10.0, 3.0, 4.0));
+        assert!((corrected.x - 4.0).abs() < 1e-9);
+        assert!((corrected.y - 4.0).abs() < 1e-9);
+        assert!((corrected.z - 4.0).abs() < 1e-9);
+        assert!(
+            MagnetometerCalibration::from_samples(&[v(1.0, 1.0, 1.0), v(2.0, 1.0, 2.0)]).is_err()
+        );
+    }
+
+    #[test_log::test]
+    fn test_load_cell_calibration() {
+        let mut calibration = LoadCellCalibration::default();
+        calibration.tare(&[1000.0, 1002.0]).unwrap();
+        calibration.scale(&[3001.0], 500.0).unwrap();
+        assert!((calibration.apply(1001.0)).abs() < 1e-9);
+        assert!((calibration.apply(2001.0) - 250.0).abs() < 1e-9);
+        assert!(calibration.scale(&[1001.0], 500.0).is_err());
+
+        calibration.store("load-test").unwrap();
+        assert_eq!(
+            LoadCellCalibration::load("load-test").unwrap(),
+            Some(calibration)
+        );
+    }
+
+    #[test_log::test]
+    fn test_calibration_command() {
+        let command = StructBuilder::new()
+            .sub("calibrate", StructBuilder::new().field("samples", 50.0))
+            .build();
+        let Some(CalibrationCommand::Calibrate(args)) = CalibrationCommand::from_command(&command)
+        else {
+            panic!("expected a calibrate command")
+        };
+        assert_eq!(super::number_arg(&args, "samples"), Some(50.0));
+        let command = StructBuilder::new()
+            .sub("clear_calibration", StructBuilder::new())
+            .build();
+        assert_eq!(
+            CalibrationCommand::from_command(&command),
+            Some(CalibrationCommand::Clear)
+        );
+        assert_eq!(
+            CalibrationCommand::from_command(&StructBuilder::new().build()),
+            None
+        );
+    }
+}
@@ -5,6 +5,7 @@ use crate::proto::component::camera;
 use bytes::{Bytes, BytesMut};
 use prost::Message;
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,6 +18,16 @@ pub enum CameraError {
     CameraCouldntGetFrame,
 }
 
+impl GrpcStatusHint for CameraError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::CameraInitError(_) => GrpcError::RpcFailedPrecondition,
+            Self::CameraFrameTooBig => GrpcError::RpcResourceExhausted,
+            Self::CameraCouldntGetFrame => GrpcError::RpcUnavailable,
+        }
+    }
+}
+
 pub static COMPONENT_NAME: &str = "camera";
 
 pub trait Camera {
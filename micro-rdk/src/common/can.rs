@@ -0,0 +1,127 @@
+//! Package can implements a CAN bus shared by the components of a robot, such as motor
+//! controllers.
+//!
+//! The platform provides a [CanDriver] sending and receiving frames (the TWAI controller on the
+//! ESP32, see `esp32::can`). Devices on CAN typically broadcast their state periodically, so the
+//! [CanBus] keeps the latest frame received for each identifier: components read the last state
+//! of their device without consuming the frames of the other devices on the bus.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use thiserror::Error;
+
+use super::board::BoardError;
+use super::motor::MotorError;
+
+// a classic CAN frame carries at most 8 bytes
+pub const CAN_MAX_DATA_LEN: usize = 8;
+
+#[derive(Error, Debug)]
+pub enum CanError {
+    #[error("CAN frames carry at most 8 bytes, got {0}")]
+    FrameTooLong(usize),
+    #[error("CAN transmit failed with code {0}")]
+    TransmitError(i32),
+    #[error("CAN receive failed with code {0}")]
+    ReceiveError(i32),
+    #[error("CAN bus config error: {0}")]
+    ConfigError(&'static str),
+}
+
+impl From<CanError> for MotorError {
+    fn from(err: CanError) -> Self {
+        MotorError::BoardError(BoardError::OtherBoardError(Box::new(err)))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanFrame {
+    pub id: u32,
+    /// whether `id` is a 29 bit extended identifier
+    pub extended: bool,
+    pub data: Vec<u8>,
+}
+
+impl CanFrame {
+    pub fn new_extended(id: u32, data: &[u8]) -> Result<Self, CanError> {
+        if data.len() > CAN_MAX_DATA_LEN {
+            return Err(CanError::FrameTooLong(data.len()));
+        }
+        Ok(Self {
+            id,
+            extended: true,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Sends and receives the frames of a CAN controller
+pub trait CanDriver: Send {
+    fn transmit(&mut self, frame: &CanFrame) -> Result<(), CanError>;
+    /// Returns the next frame received, without blocking when none is pending
+    fn receive(&mut self) -> Result<Option<CanFrame>, CanError>;
+}
+
+/// A CAN bus shared by the components talking to devices on it
+pub struct CanBus {
+    driver: Box<dyn CanDriver>,
+    latest: HashMap<(u32, bool), (CanFrame, Instant)>,
+}
+
+pub type CanBusType = Arc<Mutex<CanBus>>;
+
+impl CanBus {
+    pub fn new(driver: Box<dyn CanDriver>) -> Self {
+        Self {
+            driver,
+            latest: HashMap::new(),
+        }
+    }
+
+    pub fn transmit(&mut self, frame: &CanFrame) -> Result<(), CanError> {
+        self.driver.transmit(frame)
+    }
+
+    /// Drains the frames received so far, keeping the latest one for each identifier
+    pub fn poll(&mut self) -> Result<(), CanError> {
+        while let Some(frame) = self.driver.receive()? {
+            self.latest
+                .insert((frame.id, frame.extended), (frame, Instant::now()));
+        }
+        Ok(())
+    }
+
+    /// The latest frame received with the extended identifier `id` and when it was received
+    pub fn latest_extended(&mut self, id: u32) -> Result<Option<(CanFrame, Instant)>, CanError> {
+        self.poll()?;
+        Ok(self.latest.get(&(id, true)).cloned())
+    }
+}
+
+/// A driver recording the frames transmitted and returning the frames queued with
+/// [FakeCanDriver::queue], shared with the test through `Arc<Mutex<_>>`
+#[derive(Default)]
+pub struct FakeCanDriver {
+    pub transmitted: Vec<CanFrame>,
+    pub pending: Vec<CanFrame>,
+}
+
+impl FakeCanDriver {
+    pub fn queue(&mut self, frame: CanFrame) {
+        self.pending.push(frame);
+    }
+}
+
+impl CanDriver for Arc<Mutex<FakeCanDriver>> {
+    fn transmit(&mut self, frame: &CanFrame) -> Result<(), CanError> {
+        self.lock().unwrap().transmitted.push(frame.clone());
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Option<CanFrame>, CanError> {
+        let mut driver = self.lock().unwrap();
+        Ok((!driver.pending.is_empty()).then(|| driver.pending.remove(0)))
+    }
+}
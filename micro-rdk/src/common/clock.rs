@@ -0,0 +1,125 @@
+//! Keeps the system clock on time with the time of GNSS receivers while app can't be reached, so
+//! the data captured by offline or duty-cycled robots is still timestamped correctly.
+//!
+//! The GPS movement sensors hand the UTC time of their fixes (`RMC` sentences for `gps-nmea`,
+//! `NAV-PVT` messages for `gps-ublox`) to [discipline_with_gnss], which sets the system clock
+//! when it is off by more than [MAX_CLOCK_ERROR]. While the robot is connected to app the clock
+//! is left alone. Only ESP32 builds set the clock, native hosts keep theirs with their operating
+//! system.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+/// Offset between the system clock and GNSS time tolerated before the clock is set
+pub const MAX_CLOCK_ERROR: Duration = Duration::from_secs(1);
+
+static APP_REACHABLE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Error, Debug)]
+pub enum ClockError {
+    #[error("couldn't set the system clock, errno {0}")]
+    SetFailed(i32),
+}
+
+/// Records whether app can be reached, updated by the server as it connects to app
+pub fn set_app_reachable(reachable: bool) {
+    APP_REACHABLE.store(reachable, Ordering::Relaxed);
+}
+
+pub fn is_app_reachable() -> bool {
+    APP_REACHABLE.load(Ordering::Relaxed)
+}
+
+/// Time to set a clock reading `system_now` to, given a GNSS fix at `gnss_utc` received `since`
+/// ago. `None` when the clock is within [MAX_CLOCK_ERROR] of GNSS time
+pub(crate) fn clock_correction(
+    system_now: DateTime<Utc>,
+    gnss_utc: DateTime<Utc>,
+    since: Duration,
+) -> Option<DateTime<Utc>> {
+    let gnss_now = gnss_utc + chrono::Duration::from_std(since).ok()?;
+    let error_ms = (system_now - gnss_now).num_milliseconds().unsigned_abs();
+    (u128::from(error_ms) > MAX_CLOCK_ERROR.as_millis()).then_some(gnss_now)
+}
+
+/// Sets the system clock from the UTC time of a GNSS fix received at `received_at`, when app is
+/// unreachable and the clock is off. Returns whether the clock was set
+pub fn discipline_with_gnss(
+    gnss_utc: DateTime<Utc>,
+    received_at: Instant,
+) -> Result<bool, ClockError> {
+    if is_app_reachable() {
+        return Ok(false);
+    }
+    let system_now = Utc::now();
+    match clock_correction(system_now, gnss_utc, received_at.elapsed()) {
+        Some(time) if set_system_time(time)? => {
+            log::info!(
+                "system clock set from GNSS time, {} -> {}",
+                system_now,
+                time
+            );
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+#[cfg(feature = "esp32")]
+fn set_system_time(time: DateTime<Utc>) -> Result<bool, ClockError> {
+    use crate::esp32::esp_idf_svc::sys::{settimeofday, timeval};
+    let tv = timeval {
+        tv_sec: time.timestamp() as _,
+        tv_usec: time.timestamp_subsec_micros() as _,
+    };
+    if unsafe { settimeofday(&tv, std::ptr::null()) } != 0 {
+        return Err(ClockError::SetFailed(
+            std::io::Error::last_os_error().raw_os_error().unwrap_or(0),
+        ));
+    }
+    Ok(true)
+}
+
+#[cfg(not(feature = "esp32"))]
+fn set_system_time(_time: DateTime<Utc>) -> Result<bool, ClockError> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clock_correction, discipline_with_gnss, set_app_reachable};
+    use chrono::{TimeZone, Utc};
+    use std::time::{Duration, Instant};
+
+    #[test_log::test]
+    fn test_clock_correction() {
+        let gnss = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        // the time elapsed since the fix was received is added
+        assert_eq!(
+            clock_correction(
+                Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 5).unwrap(),
+                gnss,
+                Duration::from_millis(1500)
+            ),
+            Some(gnss + chrono::Duration::milliseconds(1500))
+        );
+        assert_eq!(
+            clock_correction(
+                gnss + chrono::Duration::milliseconds(2300),
+                gnss,
+                Duration::from_secs(2)
+            ),
+            None
+        );
+        assert!(
+            clock_correction(gnss - chrono::Duration::seconds(2), gnss, Duration::ZERO).is_some()
+        );
+
+        // native builds leave the clock of the host alone
+        set_app_reachable(false);
+        assert!(!discipline_with_gnss(gnss, Instant::now()).unwrap());
+    }
+}
@@ -4,6 +4,7 @@ use crate::common::data_collector::DataCollectorConfig;
 use crate::google;
 use crate::proto::{app::v1::ComponentConfig, common::v1::ResourceName};
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 use std::collections::HashMap;
 use std::num::{ParseFloatError, ParseIntError};
 use thiserror::Error;
@@ -18,6 +19,13 @@ pub enum AttributeError {
     KeyNotFound(String),
 }
 
+impl GrpcStatusHint for AttributeError {
+    fn grpc_error(&self) -> GrpcError {
+        // attributes come from the robot's config, the call can't succeed until it is fixed
+        GrpcError::RpcFailedPrecondition
+    }
+}
+
 impl From<ParseIntError> for AttributeError {
     fn from(_: ParseIntError) -> AttributeError {
         AttributeError::ParseNumError
@@ -51,6 +59,7 @@ macro_rules! primitives
         )*
     }
 }
+
 primitives!(u32, i32, u8, u16, i16, i8);
 
 macro_rules! floats
@@ -260,6 +269,31 @@ impl TryFrom<&google::protobuf::value::Kind> for Kind {
     }
 }
 
+impl From<&Kind> for google::protobuf::Value {
+    fn from(value: &Kind) -> Self {
+        let kind = match value {
+            Kind::BoolValue(v) => google::protobuf::value::Kind::BoolValue(*v),
+            Kind::NullValue(v) => google::protobuf::value::Kind::NullValue(*v),
+            Kind::StringValue(v) => google::protobuf::value::Kind::StringValue(v.to_string()),
+            Kind::NumberValue(v) => google::protobuf::value::Kind::NumberValue(*v),
+            Kind::StructValue(v) => {
+                google::protobuf::value::Kind::StructValue(google::protobuf::Struct {
+                    fields: v
+                        .iter()
+                        .map(|(k, val)| (k.to_string(), val.into()))
+                        .collect(),
+                })
+            }
+            Kind::VecValue(v) => {
+                google::protobuf::value::Kind::ListValue(google::protobuf::ListValue {
+                    values: v.iter().map(|val| val.into()).collect(),
+                })
+            }
+        };
+        google::protobuf::Value { kind: Some(kind) }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct DynamicComponentConfig {
     pub name: String,
@@ -271,6 +305,19 @@ pub struct DynamicComponentConfig {
     pub data_collector_configs: Vec<DataCollectorConfig>,
 }
 
+/// Splits an API triplet such as `rdk:component:motor` into its namespace and subtype
+fn split_api(api: &str) -> Option<(&str, &str)> {
+    let mut parts = api.split(':');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(namespace), Some(_), Some(subtype), None)
+            if !namespace.is_empty() && !subtype.is_empty() =>
+        {
+            Some((namespace, subtype))
+        }
+        _ => None,
+    }
+}
+
 impl TryFrom<&ComponentConfig> for DynamicComponentConfig {
     type Error = AttributeError;
     fn try_from(value: &ComponentConfig) -> Result<Self, Self::Error> {
@@ -318,10 +365,23 @@ impl TryFrom<&ComponentConfig> for DynamicComponentConfig {
         } else {
             vec![]
         };
+        // configs relying on `api` (e.g. components merged from fragments) may leave the
+        // deprecated namespace and type fields empty
+        let (api_namespace, api_type) = split_api(&value.api).unwrap_or_default();
+        let namespace = if value.namespace.is_empty() {
+            api_namespace
+        } else {
+            &value.namespace
+        };
+        let r#type = if value.r#type.is_empty() {
+            api_type
+        } else {
+            &value.r#type
+        };
         Ok(Self {
             name: value.name.to_string(),
-            namespace: value.namespace.to_string(),
-            r#type: value.r#type.to_string(),
+            namespace: namespace.to_string(),
+            r#type: r#type.to_string(),
             model: value.model.to_string(),
             attributes: attrs_opt,
             #[cfg(feature = "data")]
@@ -349,6 +409,11 @@ impl<'a> ConfigType<'a> {
             Self::Dynamic(cfg) => cfg.get_type(),
         }
     }
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Dynamic(cfg) => cfg.get_name(),
+        }
+    }
 }
 
 pub trait Component {
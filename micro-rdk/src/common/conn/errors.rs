@@ -14,6 +14,8 @@ pub enum ServerError {
     ServerConnectionNotConfigured,
     #[error(transparent)]
     ServerAppClientError(AppClientError),
+    #[error("robot part: {0}")]
+    ServerPartAppClientError(AppClientError),
     #[error(transparent)]
     ServerWebRTCError(WebRtcError),
 }
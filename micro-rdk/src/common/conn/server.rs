@@ -7,11 +7,24 @@ use super::{
 use crate::esp32::exec::Esp32Executor;
 #[cfg(feature = "native")]
 use crate::native::exec::NativeExecutor;
+#[cfg(feature = "camera")]
+use crate::{common::webrtc::rtp::VideoTrack, proto::component::camera};
 use crate::{
     common::{
-        app_client::{AppClient, AppClientBuilder, AppClientConfig, AppClientError, AppSignaling},
-        grpc::{GrpcBody, GrpcServer},
+        app_client::{
+            AppClient, AppClientApi, AppClientBuilder, AppClientConfig, AppClientError,
+            AppSignaling,
+        },
+        authorization::AuthPolicy,
+        clock,
+        grpc::{
+            GrpcBody, GrpcError, GrpcServer, RequestLimits, DEFAULT_MAX_CONCURRENT_STREAMS,
+            DEFAULT_RPC_TIMEOUT,
+        },
         grpc_client::GrpcClient,
+        instrumentation::{increment_counter, Counter},
+        log::{apply_log_level, set_log_host},
+        power_management::ActiveConnection,
         robot::LocalRobot,
         webrtc::{
             api::{WebRtcApi, WebRtcError, WebRtcSdp},
@@ -23,11 +36,15 @@ use crate::{
     },
     proto::{self, app::v1::ConfigResponse},
 };
+#[cfg(feature = "camera")]
+use bytes::BytesMut;
+#[cfg(feature = "camera")]
+use prost::Message;
 
 use async_io::Timer;
 use futures_lite::prelude::*;
 use futures_lite::{future::Boxed, ready, Future};
-use hyper::{rt, server::conn::http2};
+use hyper::{body::Incoming, rt, server::conn::http2, service::Service, Request, Response};
 
 use async_executor::Task;
 use std::{
@@ -36,9 +53,9 @@ use std::{
     net::Ipv4Addr,
     pin::Pin,
     rc::Rc,
-    sync::{Arc, Mutex},
+    sync::{Arc, RwLock},
     task::Poll,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 #[cfg(feature = "native")]
@@ -46,12 +63,73 @@ type Executor = NativeExecutor;
 #[cfg(feature = "esp32")]
 type Executor = Esp32Executor;
 
+/// Interval at which app is asked whether the robot should restart, unless app asks for another
+const RESTART_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Checking for a restart interrupts signaling, app can't make us do it more often than this
+const MIN_RESTART_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Time spent waiting for a connection before starting over
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Restarts the device, reloading the config from app. Natively the process is replaced by a
+/// new instance of the same program
+fn restart() -> ! {
+    #[cfg(feature = "esp32")]
+    unsafe {
+        crate::esp32::esp_idf_svc::sys::esp_restart()
+    }
+    #[cfg(feature = "native")]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = match std::env::current_exe() {
+            Ok(exe) => std::process::Command::new(exe)
+                .args(std::env::args_os().skip(1))
+                .exec(),
+            Err(err) => err,
+        };
+        // exiting with an error lets a supervisor (e.g. systemd) restart us instead
+        log::error!("couldn't restart ({}), exiting", err);
+        std::process::exit(1)
+    }
+}
+
 pub trait TlsClientConnector {
     type Stream: rt::Read + rt::Write + Unpin + 'static;
 
     fn connect(&mut self) -> impl std::future::Future<Output = Result<Self::Stream, ServerError>>;
 }
 
+/// Opens the connection to app the server answers WebRTC offers and checks for restarts on
+pub trait AppConnector {
+    type Client: AppClientApi;
+
+    fn connect_app(
+        &mut self,
+        exec: Executor,
+        config: AppClientConfig,
+    ) -> impl std::future::Future<Output = Result<Self::Client, ServerError>>;
+}
+
+impl<C: TlsClientConnector> AppConnector for C {
+    type Client = AppClient<'static>;
+
+    async fn connect_app(
+        &mut self,
+        exec: Executor,
+        config: AppClientConfig,
+    ) -> Result<Self::Client, ServerError> {
+        let conn = self.connect().await?;
+        let grpc_client = Box::new(
+            GrpcClient::new(conn, exec, "https://app.viam.com:443")
+                .await
+                .map_err(|e| ServerError::ServerAppClientError(e.into()))?,
+        );
+        AppClientBuilder::new(grpc_client, config)
+            .build()
+            .await
+            .map_err(ServerError::ServerAppClientError)
+    }
+}
+
 pub struct RobotCloudConfig {
     local_fqdn: String,
     name: String,
@@ -88,6 +166,55 @@ impl From<&proto::app::v1::CloudConfig> for RobotCloudConfig {
     }
 }
 
+impl RobotCloudConfig {
+    fn from_config(config: &ConfigResponse) -> Self {
+        config
+            .config
+            .as_ref()
+            .unwrap()
+            .cloud
+            .as_ref()
+            .unwrap()
+            .into()
+    }
+}
+
+/// A second robot part served by the device next to the main one, it has its own credentials,
+/// connection to app, robot, access policy and restart checks. WebRTC offers reach it through its
+/// own signaling stream and are answered with its own certificate, while local HTTP2 requests are
+/// routed to it by the host they are addressed to
+struct RobotPart<D, CC> {
+    app_config: AppClientConfig,
+    cloud: RobotCloudConfig,
+    robot: Arc<RwLock<LocalRobot>>,
+    // keys restricting the local connections to the part
+    auth: Rc<AuthPolicy>,
+    webrtc: Option<Box<WebRtcConfiguration<D, CC>>>,
+    restart_check_interval: Duration,
+    next_restart_check: Instant,
+}
+
+impl<D, CC> RobotPart<D, CC> {
+    /// whether a request addressed to `host` is meant for this part
+    fn serves(&self, host: &str) -> bool {
+        host.eq_ignore_ascii_case(&self.cloud.fqdn)
+            || host.eq_ignore_ascii_case(&self.cloud.local_fqdn)
+    }
+
+    // the WebRTC configuration of the part is tied to the certificate type of the server
+    fn without_webrtc<D2, CC2>(self) -> RobotPart<D2, CC2> {
+        RobotPart {
+            app_config: self.app_config,
+            cloud: self.cloud,
+            robot: self.robot,
+            auth: self.auth,
+            webrtc: None,
+            restart_check_interval: self.restart_check_interval,
+            next_restart_check: self.next_restart_check,
+        }
+    }
+}
+
 pub struct ViamServerBuilder<M, C, T, CC = WebRtcNoOp, D = WebRtcNoOp, L = NoHttp2> {
     mdns: M,
     webrtc: Option<Box<WebRtcConfiguration<D, CC>>>,
@@ -98,12 +225,18 @@ pub struct ViamServerBuilder<M, C, T, CC = WebRtcNoOp, D = WebRtcNoOp, L = NoHtt
     app_connector: C,
     app_config: AppClientConfig,
     max_connections: usize,
+    rpc_timeout: Duration,
+    max_concurrent_streams: u32,
+    request_limits: Option<RequestLimits>,
+    offline: bool,
+    part: Option<RobotPart<D, CC>>,
+    auth: Option<Rc<AuthPolicy>>,
 }
 
 impl<M, C, T> ViamServerBuilder<M, C, T>
 where
     M: Mdns,
-    C: TlsClientConnector,
+    C: AppConnector,
     T: rt::Read + rt::Write + Unpin + 'static,
 {
     pub fn new(
@@ -123,6 +256,12 @@ where
             app_connector,
             app_config,
             max_connections,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            max_concurrent_streams: DEFAULT_MAX_CONCURRENT_STREAMS,
+            request_limits: None,
+            offline: false,
+            part: None,
+            auth: None,
         }
     }
 }
@@ -130,7 +269,7 @@ where
 impl<M, C, T, CC, D, L> ViamServerBuilder<M, C, T, CC, D, L>
 where
     M: Mdns,
-    C: TlsClientConnector,
+    C: AppConnector,
     T: rt::Read + rt::Write + Unpin + 'static,
     CC: Certificate + 'static,
     D: DtlsBuilder,
@@ -153,6 +292,12 @@ where
             app_connector: self.app_connector,
             app_config: self.app_config,
             max_connections: self.max_connections,
+            rpc_timeout: self.rpc_timeout,
+            max_concurrent_streams: self.max_concurrent_streams,
+            request_limits: self.request_limits,
+            offline: self.offline,
+            part: self.part,
+            auth: self.auth,
         }
     }
     pub fn with_webrtc<D2, CC2>(
@@ -169,26 +314,73 @@ where
             app_connector: self.app_connector,
             app_config: self.app_config,
             max_connections: self.max_connections,
+            rpc_timeout: self.rpc_timeout,
+            max_concurrent_streams: self.max_concurrent_streams,
+            request_limits: self.request_limits,
+            offline: self.offline,
+            part: self.part.map(RobotPart::without_webrtc),
+            auth: self.auth,
         }
     }
-    pub fn build(
+    /// Sets the deadline of gRPC requests made over HTTP2 that don't carry a grpc-timeout header
+    pub fn with_rpc_timeout(mut self, rpc_timeout: Duration) -> Self {
+        self.rpc_timeout = rpc_timeout;
+        self
+    }
+    /// Sets how many gRPC requests an HTTP2 connection may have in flight at once, so a slow
+    /// request doesn't hold up every other call of the connection
+    pub fn with_max_concurrent_streams(mut self, max_concurrent_streams: u32) -> Self {
+        self.max_concurrent_streams = max_concurrent_streams.max(1);
+        self
+    }
+    /// Sets the rate and size limits of the gRPC requests of each HTTP2 connection, protecting
+    /// the device from clients flooding it. Read from the config given to [build](Self::build)
+    /// otherwise
+    pub fn with_request_limits(mut self, request_limits: RequestLimits) -> Self {
+        self.request_limits = Some(request_limits);
+        self
+    }
+    /// Sets the keys restricting the local connections, shared with the other local endpoints of
+    /// the robot so that they accept the same access tokens. Read from the config given to
+    /// [build](Self::build) otherwise
+    pub fn with_auth_policy(mut self, auth: Rc<AuthPolicy>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+    /// The server never connects to app: only local HTTP2 connections are served, there is no
+    /// WebRTC signaling nor restart checks. For robots booted from a
+    /// [static config](crate::common::static_config)
+    pub fn without_app(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+    /// Serves a second robot part next to the one the server is built for, with its own
+    /// credentials and connection to app. `config` is the config app returned for that part,
+    /// which `robot` was built from, and `auth` restricts the local connections to the part.
+    /// WebRTC offers to the part are answered with `webrtc`, which has to be set after
+    /// [with_webrtc](Self::with_webrtc)
+    pub fn with_part(
         mut self,
+        mut app_config: AppClientConfig,
         config: &ConfigResponse,
-    ) -> Result<ViamServer<C, T, CC, D, L>, ServerError> {
-        let cfg: RobotCloudConfig = config
-            .config
-            .as_ref()
-            .unwrap()
-            .cloud
-            .as_ref()
-            .unwrap()
-            .into();
-
-        self.app_config.set_rpc_host(cfg.fqdn.clone());
-
-        self.mdns
-            .set_hostname(&cfg.name)
-            .map_err(|e| ServerError::Other(e.into()))?;
+        robot: Arc<RwLock<LocalRobot>>,
+        auth: Rc<AuthPolicy>,
+        webrtc: Option<Box<WebRtcConfiguration<D, CC>>>,
+    ) -> Self {
+        let cloud = RobotCloudConfig::from_config(config);
+        app_config.set_rpc_host(cloud.fqdn.clone());
+        let _ = self.part.insert(RobotPart {
+            app_config,
+            cloud,
+            robot,
+            auth,
+            webrtc,
+            restart_check_interval: RESTART_CHECK_INTERVAL,
+            next_restart_check: Instant::now() + RESTART_CHECK_INTERVAL,
+        });
+        self
+    }
+    fn advertise(&mut self, cfg: &RobotCloudConfig) -> Result<(), ServerError> {
         self.mdns
             .add_service(
                 &cfg.local_fqdn.replace('.', "-"),
@@ -206,7 +398,33 @@ where
                 self.port,
                 &[("grpc", "")],
             )
+            .map_err(|e| ServerError::Other(e.into()))
+    }
+    pub fn build(
+        mut self,
+        config: &ConfigResponse,
+    ) -> Result<ViamServer<C, T, CC, D, L>, ServerError> {
+        let cfg = RobotCloudConfig::from_config(config);
+
+        self.app_config.set_rpc_host(cfg.fqdn.clone());
+        apply_log_level(config);
+        set_log_host(&cfg.name);
+
+        self.mdns
+            .set_hostname(&cfg.name)
             .map_err(|e| ServerError::Other(e.into()))?;
+        self.advertise(&cfg)?;
+        if let Some(part) = self.part.take() {
+            self.advertise(&part.cloud)?;
+            let _ = self.part.insert(part);
+        }
+
+        let request_limits = self.request_limits.unwrap_or_else(|| {
+            RequestLimits::from_config(config).unwrap_or_else(|err| {
+                log::warn!("invalid request limits ({}), using the defaults", err);
+                RequestLimits::default()
+            })
+        });
 
         let cloned_exec = self.exec.clone();
         let http2_listener = HttpListener::new(self.http2_listener);
@@ -218,6 +436,13 @@ where
             self.app_connector,
             self.app_config,
             self.max_connections,
+            self.rpc_timeout,
+            self.max_concurrent_streams,
+            request_limits,
+            self.offline,
+            self.part,
+            self.auth
+                .unwrap_or_else(|| Rc::new(AuthPolicy::from_config(config))),
         );
 
         Ok(srv)
@@ -270,18 +495,28 @@ where
     }
 }
 
-pub struct ViamServer<'a, C, T, CC, D, L> {
+pub struct ViamServer<C: AppConnector, T, CC, D, L> {
     http_listener: HttpListener<L, T>,
     webrtc_config: Option<Box<WebRtcConfiguration<D, CC>>>,
     exec: Executor,
     app_connector: C,
     app_config: AppClientConfig,
-    app_client: Option<AppClient<'a>>,
+    app_client: Option<C::Client>,
     webrtc_manager: WebRTCConnectionManager,
+    restart_check_interval: Duration,
+    next_restart_check: Instant,
+    rpc_timeout: Duration,
+    max_concurrent_streams: u32,
+    request_limits: RequestLimits,
+    offline: bool,
+    part: Option<RobotPart<D, CC>>,
+    part_client: Option<C::Client>,
+    // keys restricting the local connections
+    auth: Rc<AuthPolicy>,
 }
-impl<'a, C, T, CC, D, L> ViamServer<'a, C, T, CC, D, L>
+impl<C, T, CC, D, L> ViamServer<C, T, CC, D, L>
 where
-    C: TlsClientConnector,
+    C: AppConnector,
     T: rt::Read + rt::Write + Unpin + 'static,
     CC: Certificate + 'static,
     D: DtlsBuilder,
@@ -289,6 +524,7 @@ where
     L: AsyncableTcpListener<T>,
     L::Output: Http2Connector<Stream = T>,
 {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         http_listener: HttpListener<L, T>,
         webrtc_config: Option<Box<WebRtcConfiguration<D, CC>>>,
@@ -296,6 +532,12 @@ where
         app_connector: C,
         app_config: AppClientConfig,
         max_concurent_connections: usize,
+        rpc_timeout: Duration,
+        max_concurrent_streams: u32,
+        request_limits: RequestLimits,
+        offline: bool,
+        part: Option<RobotPart<D, CC>>,
+        auth: Rc<AuthPolicy>,
     ) -> Self {
         Self {
             http_listener,
@@ -305,31 +547,98 @@ where
             app_config,
             app_client: None,
             webrtc_manager: WebRTCConnectionManager::new(max_concurent_connections),
+            restart_check_interval: RESTART_CHECK_INTERVAL,
+            next_restart_check: Instant::now() + RESTART_CHECK_INTERVAL,
+            rpc_timeout,
+            max_concurrent_streams,
+            request_limits,
+            offline,
+            part,
+            part_client: None,
+            auth,
         }
     }
-    pub async fn serve(&mut self, robot: Arc<Mutex<LocalRobot>>) {
+
+    /// Asks app whether the robot parts need to restart once their next check is due, each part
+    /// being checked at the interval app gives it. A change to the config of either part
+    /// restarts the device
+    async fn check_for_restarts(&mut self) {
+        if Instant::now() >= self.next_restart_check {
+            if let Some(app_client) = self.app_client.as_mut() {
+                check_for_restart(app_client, &mut self.restart_check_interval).await;
+            }
+            self.next_restart_check = Instant::now() + self.restart_check_interval;
+        }
+        if let Some(part) = self.part.as_mut() {
+            if Instant::now() >= part.next_restart_check {
+                if let Some(app_client) = self.part_client.as_mut() {
+                    check_for_restart(app_client, &mut part.restart_check_interval).await;
+                }
+                part.next_restart_check = Instant::now() + part.restart_check_interval;
+            }
+        }
+    }
+    fn next_restart_check(&self) -> Instant {
+        self.part.as_ref().map_or(self.next_restart_check, |part| {
+            part.next_restart_check.min(self.next_restart_check)
+        })
+    }
+    pub async fn serve(&mut self, robot: Arc<RwLock<LocalRobot>>) {
         let cloned_robot = robot.clone();
+        let mut connected_once = false;
         loop {
             let _ = async_io::Timer::after(std::time::Duration::from_millis(300)).await;
 
-            if self.app_client.is_none() {
-                let conn = self.app_connector.connect().await.unwrap();
-                let cloned_exec = self.exec.clone();
-                let grpc_client = Box::new(
-                    GrpcClient::new(conn, cloned_exec, "https://app.viam.com:443")
-                        .await
-                        .unwrap(),
-                );
-                let app_client = AppClientBuilder::new(grpc_client, self.app_config.clone())
-                    .build()
+            if self.app_client.is_none() && !self.offline {
+                match self
+                    .app_connector
+                    .connect_app(self.exec.clone(), self.app_config.clone())
                     .await
-                    .unwrap();
-                let _ = self.app_client.insert(app_client);
+                {
+                    Ok(app_client) => {
+                        let _ = self.app_client.insert(app_client);
+                        if connected_once {
+                            increment_counter(Counter::AppReconnects, 1);
+                        }
+                        connected_once = true;
+                    }
+                    Err(e) => {
+                        log::error!("couldn't connect to app ({}), retrying", e);
+                        clock::set_app_reachable(false);
+                        continue;
+                    }
+                }
+            }
+            // GNSS time keeps the clock on time while app can't be reached
+            clock::set_app_reachable(self.app_client.is_some());
+            if let (Some(part), None, false) =
+                (self.part.as_ref(), self.part_client.as_ref(), self.offline)
+            {
+                match self
+                    .app_connector
+                    .connect_app(self.exec.clone(), part.app_config.clone())
+                    .await
+                {
+                    Ok(app_client) => {
+                        let _ = self.part_client.insert(app_client);
+                    }
+                    Err(e) => {
+                        log::error!("robot part couldn't connect to app ({}), retrying", e);
+                        continue;
+                    }
+                }
             }
 
-            let sig = if let Some(webrtc_config) = self.webrtc_config.as_ref() {
+            self.check_for_restarts().await;
+            let timeout = self
+                .next_restart_check()
+                .min(Instant::now() + CONNECTION_TIMEOUT);
+
+            let sig = if let (Some(webrtc_config), Some(app_client)) =
+                (self.webrtc_config.as_ref(), self.app_client.as_mut())
+            {
                 let ip = self.app_config.get_ip();
-                let signaling = self.app_client.as_mut().unwrap().connect_signaling();
+                let signaling = app_client.connect_signaling();
                 futures_util::future::Either::Left(WebRTCSignalingAnswerer {
                     webrtc_config: Some(webrtc_config),
                     future: signaling,
@@ -343,6 +652,31 @@ where
                     futures_lite::future::Pending<Result<AppSignaling, AppClientError>>,
                 >::default())
             };
+            let part_sig = if let (
+                Some(RobotPart {
+                    webrtc: Some(webrtc_config),
+                    app_config,
+                    ..
+                }),
+                Some(app_client),
+            ) = (self.part.as_ref(), self.part_client.as_mut())
+            {
+                let ip = app_config.get_ip();
+                let signaling = app_client.connect_signaling();
+                futures_util::future::Either::Left(WebRTCSignalingAnswerer {
+                    webrtc_config: Some(webrtc_config),
+                    future: signaling,
+                    ip,
+                })
+            } else {
+                futures_util::future::Either::Right(WebRTCSignalingAnswerer::<
+                    '_,
+                    CC,
+                    D,
+                    futures_lite::future::Pending<Result<AppSignaling, AppClientError>>,
+                >::default())
+            };
+            let part_robot = self.part.as_ref().map(|part| part.robot.clone());
 
             let listener = self.http_listener.next_conn();
 
@@ -355,27 +689,30 @@ where
                         .map_err(|e| ServerError::Other(e.into()))
                 },
                 async {
-                    let mut api = sig.await?;
-
-                    let prio = self.webrtc_manager.get_lowest_prio();
-
-                    let sdp = api
-                        .answer(prio)
+                    answer_offer(sig, &self.webrtc_manager, cloned_robot.clone())
                         .await
-                        .map_err(ServerError::ServerWebRTCError)?;
-
-                    Ok(IncomingConnection::WebRtcConnection(WebRTCConnection {
-                        webrtc_api: api,
-                        sdp: sdp.0,
-                        server: None,
-                        robot: cloned_robot.clone(),
-                        prio: sdp.1,
-                    }))
+                        .map(IncomingConnection::WebRtcConnection)
                 },
             );
             let connection = connection
                 .or(async {
-                    Timer::after(Duration::from_secs(600)).await;
+                    // offers for the second part are answered the same way but served with
+                    // its robot
+                    let Some(part_robot) = part_robot else {
+                        return futures_lite::future::pending::<Result<_, ServerError>>().await;
+                    };
+                    answer_offer(part_sig, &self.webrtc_manager, part_robot)
+                        .await
+                        .map(IncomingConnection::WebRtcConnection)
+                        .map_err(|e| match e {
+                            ServerError::ServerAppClientError(e) => {
+                                ServerError::ServerPartAppClientError(e)
+                            }
+                            e => e,
+                        })
+                })
+                .or(async {
+                    Timer::at(timeout).await;
                     Err(ServerError::ServerConnectionTimeout)
                 })
                 .await;
@@ -387,6 +724,31 @@ where
                     // all webrtc/timeout errors don't require a tls renegotiation
                     continue;
                 }
+                Err(ServerError::ServerAppClientError(e)) => {
+                    // the answering stream ended, as long as the connection to app is still up
+                    // only the signaling stream needs to be re-established
+                    log::info!("signaling stream ended ({}), reconnecting", e);
+                    if let Some(app_client) = self.app_client.as_mut() {
+                        if let Err(e) = app_client.reconnect_signaling().await {
+                            log::warn!("couldn't reconnect signaling ({}), reconnecting to app", e);
+                            let _ = self.app_client.take();
+                        }
+                    }
+                    continue;
+                }
+                Err(ServerError::ServerPartAppClientError(e)) => {
+                    log::info!(
+                        "signaling stream of the robot part ended ({}), reconnecting",
+                        e
+                    );
+                    if let Some(app_client) = self.part_client.as_mut() {
+                        if let Err(e) = app_client.reconnect_signaling().await {
+                            log::warn!("couldn't reconnect signaling ({}), reconnecting to app", e);
+                            let _ = self.part_client.take();
+                        }
+                    }
+                    continue;
+                }
                 Err(_) => {
                     // http2 layer related errors (GOAWAY etc...) so we should renegotiate in this event
                     let _ = self.app_client.take();
@@ -395,13 +757,20 @@ where
             };
 
             if let Err(e) = match connection {
-                IncomingConnection::Http2Connection(c) => self.serve_http2(c, robot.clone()).await,
+                IncomingConnection::Http2Connection(c) => {
+                    let _active = ActiveConnection::new();
+                    self.serve_http2(c, robot.clone()).await
+                }
 
                 IncomingConnection::WebRtcConnection(mut c) => match c.open_data_channel().await {
                     Err(e) => Err(e),
                     Ok(_) => {
                         let prio = c.prio;
-                        let t = self.exec.spawn(async move { c.run().await });
+                        let active = ActiveConnection::new();
+                        let t = self.exec.spawn(async move {
+                            let _active = active;
+                            c.run().await
+                        });
                         self.webrtc_manager.insert_new_conn(t, prio).await;
                         Ok(())
                     }
@@ -414,12 +783,26 @@ where
     async fn serve_http2<U>(
         &self,
         mut c: U,
-        robot: Arc<Mutex<LocalRobot>>,
+        robot: Arc<RwLock<LocalRobot>>,
     ) -> Result<(), ServerError>
     where
         U: Http2Connector<Stream = T>,
     {
-        let srv = GrpcServer::new(robot.clone(), GrpcBody::new());
+        let srv = PartRouter {
+            main: GrpcServer::new(robot.clone(), GrpcBody::new())
+                .with_rpc_timeout(self.rpc_timeout)
+                .with_request_limits(self.request_limits)
+                .with_auth_policy(self.auth.clone()),
+            part: self.part.as_ref().map(|part| {
+                (
+                    part,
+                    GrpcServer::new(part.robot.clone(), GrpcBody::new())
+                        .with_rpc_timeout(self.rpc_timeout)
+                        .with_request_limits(self.request_limits)
+                        .with_auth_policy(part.auth.clone()),
+                )
+            }),
+        };
         let connection = c.accept().await.map_err(|e| ServerError::Other(e.into()))?;
 
         Box::new(
@@ -427,13 +810,81 @@ where
                 .initial_connection_window_size(2048)
                 .initial_stream_window_size(2048)
                 .max_send_buf_size(4096)
-                .max_concurrent_streams(1)
+                .max_concurrent_streams(self.max_concurrent_streams)
                 .serve_connection(connection, srv),
         )
         .await
         .map_err(|e| ServerError::Other(e.into()))
     }
 }
+
+/// Asks app whether the robot part `app_client` authenticates as needs to restart and restarts
+/// the device if so, app answers yes when the config of the part changed for instance
+async fn check_for_restart<A: AppClientApi>(app_client: &mut A, interval: &mut Duration) {
+    match app_client.check_for_restart().await {
+        Ok((true, _)) => {
+            log::warn!("app requested a restart, restarting");
+            restart();
+        }
+        Ok((false, Some(app_interval))) => *interval = app_interval.max(MIN_RESTART_CHECK_INTERVAL),
+        Ok((false, None)) => {}
+        Err(e) => log::error!("couldn't check whether a restart is needed: {:?}", e),
+    }
+}
+
+/// Answers the offer received through `signaling`, the connection is then served with `robot`
+async fn answer_offer<CC, D, F>(
+    signaling: F,
+    webrtc_manager: &WebRTCConnectionManager,
+    robot: Arc<RwLock<LocalRobot>>,
+) -> Result<WebRTCConnection<CC, D, Executor>, ServerError>
+where
+    CC: Certificate,
+    D: DtlsConnector,
+    F: Future<Output = Result<WebRtcApi<CC, D, Executor>, ServerError>>,
+{
+    let mut api = signaling.await?;
+
+    let prio = webrtc_manager.get_lowest_prio();
+
+    let sdp = api
+        .answer(prio)
+        .await
+        .map_err(ServerError::ServerWebRTCError)?;
+
+    Ok(WebRTCConnection {
+        webrtc_api: api,
+        sdp: sdp.0,
+        server: None,
+        robot,
+        prio: sdp.1,
+        #[cfg(feature = "camera")]
+        video: None,
+    })
+}
+
+/// Serves the requests of a local HTTP2 connection with the robot of the part they are
+/// addressed to, the main robot unless the host is one of the second part
+struct PartRouter<'a, D, CC> {
+    main: GrpcServer<GrpcBody>,
+    part: Option<(&'a RobotPart<D, CC>, GrpcServer<GrpcBody>)>,
+}
+
+impl<D, CC> Service<Request<Incoming>> for PartRouter<'_, D, CC> {
+    type Response = Response<GrpcBody>;
+    type Error = GrpcError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn call(&self, req: Request<Incoming>) -> Self::Future {
+        match &self.part {
+            Some((part, srv)) if req.uri().host().is_some_and(|host| part.serves(host)) => {
+                srv.call(req)
+            }
+            _ => self.main.call(req),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum IncomingConnection<L, U> {
     Http2Connection(L),
@@ -445,6 +896,7 @@ pub struct WebRtcConfiguration<D, CC> {
     pub dtls: D,
     pub cert: Rc<CC>,
     pub exec: Executor,
+    pub ice: IceConfig,
 }
 
 impl<D, CC> WebRtcConfiguration<D, CC>
@@ -453,17 +905,35 @@ where
     CC: Certificate,
 {
     pub fn new(cert: Rc<CC>, dtls: D, exec: Executor) -> Self {
-        Self { dtls, cert, exec }
+        Self {
+            dtls,
+            cert,
+            exec,
+            ice: IceConfig::default(),
+        }
+    }
+    /// Set which interfaces, ports and candidate priorities are used for ICE
+    pub fn with_ice_config(mut self, ice: IceConfig) -> Self {
+        self.ice = ice;
+        self
     }
 }
 struct WebRTCConnection<C, D, E> {
     webrtc_api: WebRtcApi<C, D, E>,
     sdp: Box<WebRtcSdp>,
     server: Option<WebRtcGrpcServer<GrpcServer<WebRtcGrpcBody>>>,
-    robot: Arc<Mutex<LocalRobot>>,
+    robot: Arc<RwLock<LocalRobot>>,
     prio: u32,
+    #[cfg(feature = "camera")]
+    video: Option<VideoTrack>,
 }
 
+// Frames are sent at most at this rate on the video track
+#[cfg(feature = "camera")]
+const VIDEO_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+#[cfg(feature = "camera")]
+const VIDEO_FRAME_BUFFER_SIZE: usize = 32 * 1024;
+
 impl<C, D, E> WebRTCConnection<C, D, E>
 where
     C: Certificate,
@@ -500,26 +970,83 @@ where
             GrpcServer::new(self.robot.clone(), WebRtcGrpcBody::default()),
         );
         let _ = self.server.insert(srv);
+        #[cfg(feature = "camera")]
+        {
+            self.video = self.webrtc_api.video_track();
+        }
         Ok(())
     }
     async fn run(&mut self) -> Result<(), ServerError> {
         if self.server.is_none() {
             return Err(ServerError::ServerConnectionNotConfigured);
         }
+        #[cfg(feature = "camera")]
+        let video = self.video.take();
+        let disconnected = self.webrtc_api.disconnected();
         let srv = self.server.as_mut().unwrap();
-        loop {
-            let req = srv
-                .next_request()
-                .or(async {
-                    Timer::after(Duration::from_secs(30)).await;
-                    Err(WebRtcError::OperationTiemout)
-                })
-                .await;
+        let requests = async move {
+            loop {
+                let req = srv
+                    .next_request()
+                    .or(async {
+                        Timer::after(Duration::from_secs(30)).await;
+                        Err(WebRtcError::OperationTiemout)
+                    })
+                    .await;
 
-            if let Err(e) = req {
-                return Err(ServerError::Other(Box::new(e)));
+                if let Err(e) = req {
+                    return Err(ServerError::Other(Box::new(e)));
+                }
             }
         }
+        .or(async {
+            disconnected.await;
+            Err(ServerError::Other(Box::new(WebRtcError::PeerUnreachable)))
+        });
+        #[cfg(feature = "camera")]
+        if let Some(video) = video {
+            return requests
+                .or(Self::stream_video(video, self.robot.clone()))
+                .await;
+        }
+        requests.await
+    }
+    // The video track ends when the connection does, so errors sending a frame are logged
+    // and the loop only stops if the camera is missing
+    #[cfg(feature = "camera")]
+    async fn stream_video(
+        mut video: VideoTrack,
+        robot: Arc<RwLock<LocalRobot>>,
+    ) -> Result<(), ServerError> {
+        let source = robot
+            .read()
+            .unwrap()
+            .get_camera_by_name(video.camera().to_owned());
+        let Some(source) = source else {
+            log::error!("no camera named {} to stream", video.camera());
+            return futures_lite::future::pending().await;
+        };
+        log::info!("streaming camera {} on the video track", video.camera());
+        let mut buffer = BytesMut::with_capacity(VIDEO_FRAME_BUFFER_SIZE);
+        loop {
+            buffer.clear();
+            let frame = source.lock().unwrap().get_frame(buffer.split_off(0));
+            match frame.map(|frame| {
+                let image = camera::v1::GetImageResponse::decode(&frame[..]);
+                buffer.unsplit(frame);
+                image
+            }) {
+                Ok(Ok(image)) if !image.image.is_empty() => {
+                    if let Err(e) = video.send_jpeg(&image.image).await {
+                        log::error!("couldn't send frame {:?}", e);
+                    }
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => log::error!("couldn't decode frame {:?}", e),
+                Err(e) => log::error!("couldn't get frame {:?}", e),
+            }
+            Timer::after(VIDEO_FRAME_INTERVAL).await;
+        }
     }
 }
 
@@ -558,14 +1085,18 @@ where
             Err(e) => return Poll::Ready(Err(ServerError::ServerAppClientError(e))),
             Ok(s) => s,
         };
-        Poll::Ready(Ok(WebRtcApi::new(
-            this.webrtc_config.as_ref().unwrap().exec.clone(),
-            s.0,
-            s.1,
-            this.webrtc_config.as_ref().unwrap().cert.clone(),
-            *this.ip,
-            this.webrtc_config.as_ref().unwrap().dtls.make().unwrap(),
-        )))
+        Poll::Ready(
+            WebRtcApi::new(
+                this.webrtc_config.as_ref().unwrap().exec.clone(),
+                s.0,
+                s.1,
+                this.webrtc_config.as_ref().unwrap().cert.clone(),
+                *this.ip,
+                this.webrtc_config.as_ref().unwrap().ice.clone(),
+                this.webrtc_config.as_ref().unwrap().dtls.make().unwrap(),
+            )
+            .map_err(ServerError::ServerWebRTCError),
+        )
     }
 }
 
@@ -631,3 +1162,199 @@ impl WebRTCConnectionManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::{Arc, RwLock};
+    use std::time::{Duration, Instant};
+
+    use async_io::Timer;
+    use futures_lite::FutureExt;
+
+    use super::{AppConnector, Executor, ViamServerBuilder, MIN_RESTART_CHECK_INTERVAL};
+    use crate::common::{
+        app_client::{mock::MockAppClient, AppClientConfig, AppClientError},
+        authorization::AuthPolicy,
+        conn::{errors::ServerError, mdns::NoMdns, utils::NoHttp2},
+        robot::LocalRobot,
+    };
+    use crate::proto::app::v1::{CloudConfig, ConfigResponse, RobotConfig};
+
+    // fails the first `failures` attempts, then hands out the mock
+    struct MockAppConnector {
+        client: MockAppClient,
+        failures: usize,
+        attempts: usize,
+    }
+
+    impl AppConnector for MockAppConnector {
+        type Client = MockAppClient;
+
+        async fn connect_app(
+            &mut self,
+            _: Executor,
+            _: AppClientConfig,
+        ) -> Result<MockAppClient, ServerError> {
+            self.attempts += 1;
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(ServerError::ServerAppClientError(
+                    AppClientError::AppClientDisconnected,
+                ));
+            }
+            self.client.0.lock().unwrap().connected = true;
+            Ok(self.client.clone())
+        }
+    }
+
+    fn config() -> ConfigResponse {
+        ConfigResponse {
+            config: Some(RobotConfig {
+                cloud: Some(CloudConfig {
+                    fqdn: "test.viam.cloud".to_owned(),
+                    local_fqdn: "test.local.viam.cloud".to_owned(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test_log::test]
+    fn test_reconnect_to_app() {
+        let exec = Executor::new();
+        let client = MockAppClient::new();
+        let connector = MockAppConnector {
+            client: client.clone(),
+            failures: 2,
+            attempts: 0,
+        };
+        let mut srv = ViamServerBuilder::<_, _, NoHttp2>::new(
+            NoMdns,
+            exec.clone(),
+            connector,
+            AppClientConfig::default(),
+            1,
+        )
+        .build(&config())
+        .unwrap();
+        let robot = Arc::new(RwLock::new(LocalRobot::default()));
+
+        exec.block_on(srv.serve(robot.clone()).or(async {
+            Timer::after(Duration::from_secs(2)).await;
+        }));
+        // connecting is retried until app is reachable
+        assert_eq!(srv.app_connector.attempts, 3);
+        assert!(srv.app_client.is_some());
+
+        // restart checks go through the client, app can't make them too frequent
+        client.0.lock().unwrap().restart_check_interval = Some(Duration::from_secs(1));
+        srv.next_restart_check = Instant::now();
+        exec.block_on(srv.check_for_restarts());
+        assert_eq!(client.0.lock().unwrap().restart_checks, 1);
+        assert_eq!(srv.restart_check_interval, MIN_RESTART_CHECK_INTERVAL);
+        // the next check isn't due yet
+        exec.block_on(srv.check_for_restarts());
+        assert_eq!(client.0.lock().unwrap().restart_checks, 1);
+
+        // errors are only logged
+        client.0.lock().unwrap().connected = false;
+        srv.next_restart_check = Instant::now();
+        exec.block_on(srv.check_for_restarts());
+        assert_eq!(client.0.lock().unwrap().restart_checks, 1);
+    }
+
+    #[test_log::test]
+    fn test_offline_never_connects() {
+        let exec = Executor::new();
+        let connector = MockAppConnector {
+            client: MockAppClient::new(),
+            failures: 0,
+            attempts: 0,
+        };
+        let mut srv = ViamServerBuilder::<_, _, NoHttp2>::new(
+            NoMdns,
+            exec.clone(),
+            connector,
+            AppClientConfig::default(),
+            1,
+        )
+        .without_app()
+        .build(&config())
+        .unwrap();
+        let robot = Arc::new(RwLock::new(LocalRobot::default()));
+
+        exec.block_on(srv.serve(robot).or(async {
+            Timer::after(Duration::from_secs(1)).await;
+        }));
+        assert_eq!(srv.app_connector.attempts, 0);
+        assert!(srv.app_client.is_none());
+    }
+
+    #[test_log::test]
+    fn test_second_part() {
+        let exec = Executor::new();
+        let client = MockAppClient::new();
+        let connector = MockAppConnector {
+            client: client.clone(),
+            failures: 0,
+            attempts: 0,
+        };
+        let part_config = ConfigResponse {
+            config: Some(RobotConfig {
+                cloud: Some(CloudConfig {
+                    fqdn: "part.viam.cloud".to_owned(),
+                    local_fqdn: "part.local.viam.cloud".to_owned(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        };
+        let mut srv = ViamServerBuilder::<_, _, NoHttp2>::new(
+            NoMdns,
+            exec.clone(),
+            connector,
+            AppClientConfig::default(),
+            1,
+        )
+        .with_part(
+            AppClientConfig::default(),
+            &part_config,
+            Arc::new(RwLock::new(LocalRobot::default())),
+            Rc::new(AuthPolicy::from_config(&part_config)),
+            None,
+        )
+        .build(&config())
+        .unwrap();
+        let robot = Arc::new(RwLock::new(LocalRobot::default()));
+
+        exec.block_on(srv.serve(robot).or(async {
+            Timer::after(Duration::from_secs(1)).await;
+        }));
+        // each part has its own session with app
+        assert_eq!(srv.app_connector.attempts, 2);
+        assert!(srv.app_client.is_some());
+        assert!(srv.part_client.is_some());
+
+        // app is asked about restarting on behalf of both parts, each on its own schedule
+        client.0.lock().unwrap().restart_check_interval = Some(Duration::from_secs(30));
+        srv.next_restart_check = Instant::now();
+        srv.part.as_mut().unwrap().next_restart_check = Instant::now();
+        exec.block_on(srv.check_for_restarts());
+        assert_eq!(client.0.lock().unwrap().restart_checks, 2);
+        assert_eq!(
+            srv.part.as_ref().unwrap().restart_check_interval,
+            Duration::from_secs(30)
+        );
+        srv.part.as_mut().unwrap().next_restart_check = Instant::now();
+        exec.block_on(srv.check_for_restarts());
+        assert_eq!(client.0.lock().unwrap().restart_checks, 3);
+
+        // local requests are routed by the host they are addressed to
+        let part = srv.part.as_ref().unwrap();
+        assert!(part.serves("part.viam.cloud"));
+        assert!(part.serves("PART.local.viam.cloud"));
+        assert!(!part.serves("test.viam.cloud"));
+    }
+}
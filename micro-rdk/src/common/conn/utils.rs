@@ -5,7 +5,8 @@ use hyper::rt;
 
 use crate::common::webrtc::{
     certificate::{Certificate, Fingerprint},
-    dtls::{DtlsBuilder, DtlsConnector, DtlsError},
+    dtls::{DtlsBuilder, DtlsConnector, DtlsError, DtlsSrtpKeying},
+    srtp::SRTP_KEYING_MATERIAL_LEN,
     udp_mux::UdpMux,
 };
 
@@ -135,6 +136,12 @@ impl DtlsConnector for WebRtcNoOp {
     fn set_transport(&mut self, _: UdpMux) {}
 }
 
+impl DtlsSrtpKeying for WebRtcNoOp {
+    fn srtp_keying_material(&self) -> Option<[u8; SRTP_KEYING_MATERIAL_LEN]> {
+        None
+    }
+}
+
 impl Certificate for WebRtcNoOp {
     fn get_der_certificate(&self) -> &'_ [u8] {
         &[0_u8; 0]
@@ -0,0 +1,335 @@
+//! Text commands to inspect and drive a robot without network connectivity, meant to be
+//! exposed over a serial console (see `esp32::console` when the `console` feature is enabled).
+//!
+//! ```text
+//! list-resources
+//! read-sensor <name>
+//! set-pin [board] <pin> <hi|lo>
+//! wifi-status
+//! help
+//! ```
+
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+
+use thiserror::Error;
+
+use super::board::{Board, BoardError, COMPONENT_NAME as BOARD_COMPONENT_NAME};
+use super::robot::{LocalRobot, RobotError};
+use super::sensor::{Readings, SensorError};
+use crate::google::protobuf::value::Kind;
+
+/// Name, arguments hint and help of every command understood by [Console]
+pub const COMMANDS: [(&str, &str, &str); 5] = [
+    ("list-resources", "", "List the resources of the robot"),
+    ("read-sensor", "<name>", "Print the readings of a sensor"),
+    (
+        "set-pin",
+        "[board] <pin> <hi|lo>",
+        "Set the level of a GPIO pin, using the first board unless one is named",
+    ),
+    ("wifi-status", "", "Print the state of the WiFi connection"),
+    ("help", "", "List the available commands"),
+];
+
+#[derive(Error, Debug)]
+pub enum ConsoleError {
+    #[error("unknown command `{0}`, try `help`")]
+    UnknownCommand(String),
+    #[error("usage: {0}")]
+    InvalidArguments(String),
+    #[error("no {0} named `{1}`")]
+    ResourceNotFound(&'static str, String),
+    #[error("no board configured")]
+    NoBoard,
+    #[error("wifi status is not available on this platform")]
+    WifiStatusUnavailable,
+    #[error(transparent)]
+    SensorError(#[from] SensorError),
+    #[error(transparent)]
+    BoardError(#[from] BoardError),
+    #[error(transparent)]
+    RobotError(#[from] RobotError),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsoleCommand {
+    ListResources,
+    ReadSensor(String),
+    SetPin {
+        board: Option<String>,
+        pin: i32,
+        is_high: bool,
+    },
+    WifiStatus,
+    Help,
+}
+
+fn usage(command: &str) -> ConsoleError {
+    let hint = COMMANDS
+        .iter()
+        .find(|(name, _, _)| *name == command)
+        .map(|(_, hint, _)| *hint)
+        .unwrap_or_default();
+    ConsoleError::InvalidArguments(format!("{} {}", command, hint))
+}
+
+impl FromStr for ConsoleCommand {
+    type Err = ConsoleError;
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut args = line.split_whitespace();
+        let command = args.next().unwrap_or_default();
+        let args: Vec<&str> = args.collect();
+        match (command, args.as_slice()) {
+            ("list-resources", []) => Ok(Self::ListResources),
+            ("read-sensor", [name]) => Ok(Self::ReadSensor(name.to_string())),
+            ("set-pin", [.., pin, level]) if args.len() <= 3 => {
+                let pin = pin.parse().map_err(|_| usage(command))?;
+                let is_high = match level.to_lowercase().as_str() {
+                    "hi" | "high" | "1" => true,
+                    "lo" | "low" | "0" => false,
+                    _ => return Err(usage(command)),
+                };
+                Ok(Self::SetPin {
+                    board: (args.len() == 3).then(|| args[0].to_string()),
+                    pin,
+                    is_high,
+                })
+            }
+            ("wifi-status", []) => Ok(Self::WifiStatus),
+            ("help", _) => Ok(Self::Help),
+            ("list-resources" | "read-sensor" | "set-pin" | "wifi-status", _) => {
+                Err(usage(command))
+            }
+            _ => Err(ConsoleError::UnknownCommand(command.to_string())),
+        }
+    }
+}
+
+fn format_value(kind: Option<&Kind>) -> String {
+    match kind {
+        Some(Kind::NumberValue(value)) => value.to_string(),
+        Some(Kind::StringValue(value)) => value.clone(),
+        Some(Kind::BoolValue(value)) => value.to_string(),
+        Some(Kind::NullValue(_)) | None => "null".to_string(),
+        Some(other) => format!("{:?}", other),
+    }
+}
+
+pub struct Console {
+    robot: Arc<RwLock<LocalRobot>>,
+    wifi_status: Option<Box<dyn Fn() -> String>>,
+}
+
+impl Console {
+    pub fn new(robot: Arc<RwLock<LocalRobot>>) -> Self {
+        Self {
+            robot,
+            wifi_status: None,
+        }
+    }
+
+    /// Platform specific report used by the `wifi-status` command
+    pub fn with_wifi_status(mut self, wifi_status: impl Fn() -> String + 'static) -> Self {
+        self.wifi_status = Some(Box::new(wifi_status));
+        self
+    }
+
+    /// Parses and runs a command line, returning the text to print back
+    pub fn execute(&self, line: &str) -> Result<String, ConsoleError> {
+        self.run(line.parse()?)
+    }
+
+    pub fn run(&self, command: ConsoleCommand) -> Result<String, ConsoleError> {
+        match command {
+            ConsoleCommand::ListResources => {
+                let mut names: Vec<String> = self
+                    .robot
+                    .read()
+                    .unwrap()
+                    .get_resource_names()?
+                    .into_iter()
+                    .map(|name| {
+                        format!(
+                            "{}:{}:{}/{}",
+                            name.namespace, name.r#type, name.subtype, name.name
+                        )
+                    })
+                    .collect();
+                names.sort();
+                Ok(names.join("\n"))
+            }
+            ConsoleCommand::ReadSensor(name) => {
+                let mut sensor = self
+                    .robot
+                    .read()
+                    .unwrap()
+                    .get_sensor_by_name(name.clone())
+                    .ok_or(ConsoleError::ResourceNotFound("sensor", name))?;
+                let readings = sensor.get_generic_readings()?;
+                let mut readings: Vec<String> = readings
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, format_value(value.kind.as_ref())))
+                    .collect();
+                readings.sort();
+                Ok(readings.join("\n"))
+            }
+            ConsoleCommand::SetPin {
+                board,
+                pin,
+                is_high,
+            } => {
+                let mut board = self.board(board)?;
+                board.set_gpio_pin_level(pin, is_high)?;
+                Ok(format!(
+                    "pin {} set {}",
+                    pin,
+                    if is_high { "high" } else { "low" }
+                ))
+            }
+            ConsoleCommand::WifiStatus => self
+                .wifi_status
+                .as_ref()
+                .map(|status| status())
+                .ok_or(ConsoleError::WifiStatusUnavailable),
+            ConsoleCommand::Help => Ok(COMMANDS
+                .iter()
+                .map(|(name, hint, help)| format!("{} {}\n    {}", name, hint, help))
+                .collect::<Vec<_>>()
+                .join("\n")),
+        }
+    }
+
+    fn board(&self, name: Option<String>) -> Result<Arc<Mutex<dyn Board>>, ConsoleError> {
+        let robot = self.robot.read().unwrap();
+        let name = match name {
+            Some(name) => name,
+            None => robot
+                .get_resource_names()?
+                .into_iter()
+                .find(|name| name.subtype == BOARD_COMPONENT_NAME)
+                .map(|name| name.name)
+                .ok_or(ConsoleError::NoBoard)?,
+        };
+        robot
+            .get_board_by_name(name.clone())
+            .ok_or(ConsoleError::ResourceNotFound("board", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Console, ConsoleCommand, ConsoleError};
+    use crate::common::board::Board;
+    use crate::common::registry::ComponentRegistry;
+    use crate::common::robot::LocalRobot;
+    use crate::google;
+    use crate::google::protobuf::Struct;
+    use crate::proto::app::v1::{ComponentConfig, ConfigResponse, RobotConfig};
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    fn component(name: &str, r#type: &str, attributes: Vec<(&str, f64)>) -> ComponentConfig {
+        ComponentConfig {
+            name: name.to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: r#type.to_string(),
+            namespace: "rdk".to_string(),
+            attributes: Some(Struct {
+                fields: attributes
+                    .into_iter()
+                    .map(|(key, value)| {
+                        (
+                            key.to_string(),
+                            google::protobuf::Value {
+                                kind: Some(google::protobuf::value::Kind::NumberValue(value)),
+                            },
+                        )
+                    })
+                    .collect::<HashMap<_, _>>(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test_log::test]
+    fn test_parse_console_command() {
+        assert_eq!(
+            "read-sensor moisture".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::ReadSensor("moisture".to_string())
+        );
+        assert_eq!(
+            "set-pin 12 hi".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::SetPin {
+                board: None,
+                pin: 12,
+                is_high: true
+            }
+        );
+        assert_eq!(
+            "set-pin  other 4 lo".parse::<ConsoleCommand>().unwrap(),
+            ConsoleCommand::SetPin {
+                board: Some("other".to_string()),
+                pin: 4,
+                is_high: false
+            }
+        );
+        assert!(matches!(
+            "set-pin 12 maybe".parse::<ConsoleCommand>(),
+            Err(ConsoleError::InvalidArguments(_))
+        ));
+        assert!(matches!(
+            "read-sensor".parse::<ConsoleCommand>(),
+            Err(ConsoleError::InvalidArguments(_))
+        ));
+        assert!(matches!(
+            "reboot".parse::<ConsoleCommand>(),
+            Err(ConsoleError::UnknownCommand(_))
+        ));
+    }
+
+    #[test_log::test]
+    fn test_console() {
+        let cfg = ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![
+                    component("board", "board", vec![]),
+                    component("moisture", "sensor", vec![("fake_value", 42.0)]),
+                ],
+                ..Default::default()
+            }),
+        };
+        let robot =
+            LocalRobot::from_cloud_config(&cfg, Box::<ComponentRegistry>::default(), None).unwrap();
+        let robot = Arc::new(RwLock::new(robot));
+        let console = Console::new(robot.clone());
+
+        assert_eq!(
+            console.execute("list-resources").unwrap(),
+            "rdk:component:board/board\nrdk:component:sensor/moisture"
+        );
+        assert_eq!(
+            console.execute("read-sensor moisture").unwrap(),
+            "fake_sensor: 42"
+        );
+        assert!(matches!(
+            console.execute("read-sensor pump"),
+            Err(ConsoleError::ResourceNotFound("sensor", _))
+        ));
+
+        console.execute("set-pin 5 lo").unwrap();
+        let board = robot
+            .read()
+            .unwrap()
+            .get_board_by_name("board".to_string())
+            .unwrap();
+        assert!(!board.get_gpio_level(5).unwrap());
+
+        assert!(matches!(
+            console.execute("wifi-status"),
+            Err(ConsoleError::WifiStatusUnavailable)
+        ));
+        let console = console.with_wifi_status(|| "connected".to_string());
+        assert_eq!(console.execute("wifi-status").unwrap(), "connected");
+    }
+}
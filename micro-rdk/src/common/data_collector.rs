@@ -1,17 +1,33 @@
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::time::Duration;
 
-use crate::google::protobuf::Timestamp;
-use crate::proto::app::data_sync::v1::{SensorData, SensorMetadata};
+use crate::google::protobuf::{value::Kind as PKind, Struct, Timestamp, Value};
+use crate::proto::app::data_sync::v1::{sensor_data::Data, DataType, SensorData, SensorMetadata};
+#[cfg(feature = "camera")]
+use crate::proto::component::camera::v1::GetImageResponse;
 
 use super::{
+    analog::{AnalogError, AnalogReader},
+    audio_input::{pcm16_le_bytes, AudioInputError},
+    base::{Base, BaseError},
+    board::{Board, BoardError},
     config::{AttributeError, Kind},
+    generic::{DoCommand, GenericError, GET_READINGS_COMMAND},
+    math_utils::Vector3,
+    motor::{Motor, MotorError},
     movement_sensor::MovementSensor,
     robot::ResourceType,
     sensor::{Readings, SensorError},
 };
 
+#[cfg(feature = "camera")]
+use super::camera::CameraError;
+#[cfg(feature = "camera")]
+use bytes::BytesMut;
 use chrono::offset::Local;
+#[cfg(feature = "camera")]
+use prost::Message;
 use thiserror::Error;
 
 /// A DataCollectorConfig instance is a representation of an element
@@ -23,6 +39,12 @@ use thiserror::Error;
 pub struct DataCollectorConfig {
     pub method: CollectionMethod,
     pub capture_frequency_hz: f32,
+    /// set by the "disabled" flag of the capture method, the collector is kept but no
+    /// data is captured until it is resumed
+    pub disabled: bool,
+    /// set by the "aggregation_window_secs" of the capture method, the numeric values captured
+    /// during each window are summarized (min/max/mean/stddev) into a single capture
+    pub aggregation_window_secs: Option<f32>,
 }
 
 impl TryFrom<&Kind> for DataCollectorConfig {
@@ -38,32 +60,110 @@ impl TryFrom<&Kind> for DataCollectorConfig {
                 "capture_frequency_hz".to_string(),
             ))?
             .try_into()?;
-        // TODO: RSDK-7127 - Collectors that take arguments (ex. Board Analogs)
         let method = match method_str.as_str() {
             "Readings" => CollectionMethod::Readings,
             "AngularVelocity" => CollectionMethod::AngularVelocity,
             "LinearAcceleration" => CollectionMethod::LinearAcceleration,
             "LinearVelocity" => CollectionMethod::LinearVelocity,
+            "Position" => CollectionMethod::Position,
+            "IsPowered" => CollectionMethod::IsPowered,
+            "Analogs" => CollectionMethod::Analogs(analog_reader_names_from_params(value)?),
+            "DoCommand" => CollectionMethod::DoCommand(do_command_payload_from_params(value)?),
+            "ReadImage" => CollectionMethod::ReadImage,
+            "ReadAudio" => CollectionMethod::ReadAudio,
             _ => {
                 return Err(AttributeError::ConversionImpossibleError);
             }
         };
+        let disabled = match value.get("disabled")? {
+            Some(disabled) => disabled.try_into()?,
+            None => false,
+        };
+        let aggregation_window_secs = value
+            .get("aggregation_window_secs")?
+            .map(f32::try_from)
+            .transpose()?;
+        if aggregation_window_secs.is_some_and(|secs| secs <= 0.0)
+            || (aggregation_window_secs.is_some() && method.data_type() == DataType::BinarySensor)
+        {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
         Ok(DataCollectorConfig {
             method,
             capture_frequency_hz,
+            disabled,
+            aggregation_window_secs,
         })
     }
 }
 
+/// Reads the names of the analog readers to capture from the "additional_params" of
+/// a collector config. Either a single "reader_name" or a list of "reader_names" may
+/// be given, the name `*` selects every analog reader on the board.
+fn analog_reader_names_from_params(value: &Kind) -> Result<Vec<String>, AttributeError> {
+    let params = value
+        .get("additional_params")?
+        .ok_or(AttributeError::KeyNotFound("additional_params".to_string()))?;
+    let names: Vec<String> = if let Some(names) = params.get("reader_names")? {
+        names.try_into()?
+    } else if let Some(name) = params.get("reader_name")? {
+        vec![name.try_into()?]
+    } else {
+        return Err(AttributeError::KeyNotFound("reader_names".to_string()));
+    };
+    if names.is_empty() {
+        return Err(AttributeError::ConversionImpossibleError);
+    }
+    Ok(names)
+}
+
+/// Reads the command to send to a generic component from the "docommand_input" entry of
+/// the "additional_params" of a collector config. When absent, the `{"get_readings": {}}`
+/// convention is used.
+fn do_command_payload_from_params(value: &Kind) -> Result<Struct, AttributeError> {
+    let input = match value.get("additional_params")? {
+        Some(params) => params.get("docommand_input")?,
+        None => None,
+    };
+    match input {
+        Some(input @ Kind::StructValue(_)) => match Value::from(input).kind {
+            Some(PKind::StructValue(payload)) => Ok(payload),
+            _ => Err(AttributeError::ConversionImpossibleError),
+        },
+        Some(_) => Err(AttributeError::ConversionImpossibleError),
+        None => Ok(Struct {
+            fields: HashMap::from([(
+                GET_READINGS_COMMAND.to_string(),
+                Value {
+                    kind: Some(PKind::StructValue(Struct::default())),
+                },
+            )]),
+        }),
+    }
+}
+
 /// A CollectionMethod is an enum whose values are associated with
 /// a method on one or more component traits
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum CollectionMethod {
     Readings,
-    // MovementSensor methods
+    // MovementSensor methods, the velocities are also captured from bases
     AngularVelocity,
     LinearAcceleration,
     LinearVelocity,
+    // Motor methods
+    Position,
+    IsPowered,
+    // Board methods, takes the names of the analog readers to capture
+    // (`*` captures all of them)
+    Analogs(Vec<String>),
+    // GenericComponent method, takes the command to send to `do_command`
+    DoCommand(Struct),
+    // Camera method, captures a single frame as binary data
+    ReadImage,
+    // AudioInput method, captures the samples buffered since the previous capture as binary
+    // little endian 16-bit PCM
+    ReadAudio,
     // TODO: RSDK-7127 - Implement collectors for all other applicable components/methods
 }
 
@@ -75,13 +175,38 @@ impl Display for CollectionMethod {
                 Self::AngularVelocity => "angularvelocity",
                 Self::LinearAcceleration => "linearacceleration",
                 Self::LinearVelocity => "linearvelocity",
+                Self::Position => "position",
+                Self::IsPowered => "ispowered",
+                Self::Analogs(_) => "analogs",
+                Self::DoCommand(_) => "docommand",
+                Self::ReadImage => "readimage",
+                Self::ReadAudio => "readaudio",
             },
             f,
         )
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl CollectionMethod {
+    /// Binary data is uploaded one capture per request while tabular data is batched
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Self::ReadImage | Self::ReadAudio => DataType::BinarySensor,
+            _ => DataType::TabularSensor,
+        }
+    }
+
+    /// Extension of the file app stores binary captures in
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::ReadImage => ".jpeg",
+            Self::ReadAudio => ".pcm",
+            _ => "",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ResourceMethodKey {
     pub r_name: String,
     pub component_type: String,
@@ -106,8 +231,27 @@ pub enum DataCollectionError {
     NoSupportedMethods,
     #[error(transparent)]
     SensorCollectionError(#[from] SensorError),
+    #[error(transparent)]
+    MotorCollectionError(#[from] MotorError),
+    #[error(transparent)]
+    BaseCollectionError(#[from] BaseError),
+    #[error(transparent)]
+    BoardCollectionError(#[from] BoardError),
+    #[error(transparent)]
+    AnalogCollectionError(#[from] AnalogError),
+    #[error(transparent)]
+    GenericCollectionError(#[from] GenericError),
+    #[error(transparent)]
+    AudioInputCollectionError(#[from] AudioInputError),
+    #[cfg(feature = "camera")]
+    #[error(transparent)]
+    CameraCollectionError(#[from] CameraError),
 }
 
+// frames larger than this are dropped by the camera
+#[cfg(feature = "camera")]
+const CAMERA_FRAME_BUFFER_LEN: usize = 32 * 1024;
+
 /// A DataCollector represents an association between a data collection method and
 /// a ResourceType (i.e. SensorType & Readings, BoardType & Analogs) and the frequency at
 /// which the results of the method should be stored.
@@ -117,6 +261,154 @@ pub struct DataCollector {
     resource: ResourceType,
     method: CollectionMethod,
     time_interval: Duration,
+    disabled: bool,
+    aggregator: Option<Aggregator>,
+}
+
+/// Running min/max/mean/stddev of a numeric value, the mean and variance are updated
+/// with Welford's algorithm so samples don't need to be kept around
+#[derive(Debug, Default)]
+struct RunningStats {
+    count: u64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningStats {
+    fn push(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    fn stddev(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.m2 / self.count as f64).sqrt()
+    }
+
+    fn to_value(&self) -> Value {
+        let number = |n: f64| Value {
+            kind: Some(PKind::NumberValue(n)),
+        };
+        Value {
+            kind: Some(PKind::StructValue(Struct {
+                fields: HashMap::from([
+                    ("min".to_string(), number(self.min)),
+                    ("max".to_string(), number(self.max)),
+                    ("mean".to_string(), number(self.mean)),
+                    ("stddev".to_string(), number(self.stddev())),
+                ]),
+            })),
+        }
+    }
+}
+
+/// Folds the tabular captures of a collector into windows of `samples_per_window` captures.
+/// Once a window is complete, a single capture is produced where every numeric value of the
+/// latest capture is replaced by the statistics of that value over the window, non numeric
+/// values are kept as they were last captured.
+#[derive(Debug)]
+struct Aggregator {
+    samples_per_window: usize,
+    samples: usize,
+    stats: HashMap<Vec<String>, RunningStats>,
+    window_requested: Option<Timestamp>,
+}
+
+impl Aggregator {
+    fn new(samples_per_window: usize) -> Self {
+        Self {
+            samples_per_window: samples_per_window.max(1),
+            samples: 0,
+            stats: HashMap::new(),
+            window_requested: None,
+        }
+    }
+
+    fn accumulate(
+        stats: &mut HashMap<Vec<String>, RunningStats>,
+        path: &mut Vec<String>,
+        data: &Struct,
+    ) {
+        for (key, value) in data.fields.iter() {
+            path.push(key.clone());
+            match &value.kind {
+                Some(PKind::NumberValue(n)) => stats.entry(path.clone()).or_default().push(*n),
+                Some(PKind::StructValue(inner)) => Self::accumulate(stats, path, inner),
+                _ => {}
+            }
+            path.pop();
+        }
+    }
+
+    fn summarize(
+        stats: &HashMap<Vec<String>, RunningStats>,
+        path: &mut Vec<String>,
+        data: &mut Struct,
+    ) {
+        for (key, value) in data.fields.iter_mut() {
+            path.push(key.clone());
+            if let Some(PKind::StructValue(inner)) = &mut value.kind {
+                Self::summarize(stats, path, inner);
+            } else if matches!(value.kind, Some(PKind::NumberValue(_))) {
+                if let Some(stats) = stats.get(path.as_slice()) {
+                    *value = stats.to_value();
+                }
+            }
+            path.pop();
+        }
+    }
+
+    /// Adds a capture to the current window, returns the aggregate when the window is complete.
+    /// Binary captures can't be aggregated and are passed through.
+    fn push(&mut self, capture: SensorData) -> Option<SensorData> {
+        let metadata = capture.metadata.unwrap_or_default();
+        let mut data = match capture.data {
+            Some(Data::Struct(data)) => data,
+            data => {
+                return Some(SensorData {
+                    metadata: Some(metadata),
+                    data,
+                })
+            }
+        };
+        if self.samples == 0 {
+            self.window_requested = metadata.time_requested.clone();
+        }
+        Self::accumulate(&mut self.stats, &mut vec![], &data);
+        self.samples += 1;
+        if self.samples < self.samples_per_window {
+            return None;
+        }
+        Self::summarize(&self.stats, &mut vec![], &mut data);
+        data.fields.insert(
+            "sample_count".to_string(),
+            Value {
+                kind: Some(PKind::NumberValue(self.samples as f64)),
+            },
+        );
+        self.samples = 0;
+        self.stats.clear();
+        Some(SensorData {
+            metadata: Some(SensorMetadata {
+                time_requested: self.window_requested.take(),
+                time_received: metadata.time_received,
+            }),
+            data: Some(Data::Struct(data)),
+        })
+    }
 }
 
 fn resource_method_pair_is_valid(resource: &ResourceType, method: &CollectionMethod) -> bool {
@@ -129,6 +421,19 @@ fn resource_method_pair_is_valid(resource: &ResourceType, method: &CollectionMet
                 | CollectionMethod::LinearAcceleration
                 | CollectionMethod::LinearVelocity
         ),
+        ResourceType::Motor(_) => matches!(
+            method,
+            CollectionMethod::Position | CollectionMethod::IsPowered
+        ),
+        ResourceType::Base(_) => matches!(
+            method,
+            CollectionMethod::AngularVelocity | CollectionMethod::LinearVelocity
+        ),
+        ResourceType::Board(_) => matches!(method, CollectionMethod::Analogs(_)),
+        ResourceType::Generic(_) => matches!(method, CollectionMethod::DoCommand(_)),
+        ResourceType::AudioInput(_) => matches!(method, CollectionMethod::ReadAudio),
+        #[cfg(feature = "camera")]
+        ResourceType::Camera(_) => matches!(method, CollectionMethod::ReadImage),
         _ => false,
     }
 }
@@ -155,6 +460,8 @@ impl DataCollector {
             resource,
             method,
             time_interval,
+            disabled: false,
+            aggregator: None,
         })
     }
 
@@ -163,12 +470,17 @@ impl DataCollector {
         resource: ResourceType,
         conf: &DataCollectorConfig,
     ) -> Result<Self, DataCollectionError> {
-        Self::new(
+        let mut collector = Self::new(
             name,
             resource,
             conf.method.clone(),
             conf.capture_frequency_hz,
-        )
+        )?;
+        collector.disabled = conf.disabled;
+        collector.aggregator = conf.aggregation_window_secs.map(|window_secs| {
+            Aggregator::new((window_secs * conf.capture_frequency_hz).round() as usize)
+        });
+        Ok(collector)
     }
 
     pub fn name(&self) -> String {
@@ -187,6 +499,11 @@ impl DataCollector {
         self.method.to_string()
     }
 
+    /// Whether capture was disabled for this collector in the config
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
     /// calls the method associated with the collector and returns the resulting data
     pub(crate) fn call_method(&mut self) -> Result<SensorData, DataCollectionError> {
         let reading_requested_dt = Local::now().fixed_offset();
@@ -220,6 +537,156 @@ impl DataCollector {
                     ))
                 }
             },
+            ResourceType::Motor(ref mut res) => match self.method {
+                CollectionMethod::Position => {
+                    let pos = res.get_position()?;
+                    Data::Struct(Struct {
+                        fields: HashMap::from([(
+                            "position".to_string(),
+                            Value {
+                                kind: Some(PKind::NumberValue(pos as f64)),
+                            },
+                        )]),
+                    })
+                }
+                CollectionMethod::IsPowered => {
+                    let (is_on, power_pct) = res.is_powered()?;
+                    Data::Struct(Struct {
+                        fields: HashMap::from([
+                            (
+                                "is_on".to_string(),
+                                Value {
+                                    kind: Some(PKind::BoolValue(is_on)),
+                                },
+                            ),
+                            (
+                                "power_pct".to_string(),
+                                Value {
+                                    kind: Some(PKind::NumberValue(power_pct)),
+                                },
+                            ),
+                        ]),
+                    })
+                }
+                _ => {
+                    return Err(DataCollectionError::UnsupportedMethod(
+                        self.method.clone(),
+                        "motor".to_string(),
+                    ))
+                }
+            },
+            ResourceType::Base(ref mut res) => {
+                let (velocity, key) = match self.method {
+                    CollectionMethod::AngularVelocity => (
+                        res.lock().unwrap().get_angular_velocity()?,
+                        "angular_velocity",
+                    ),
+                    CollectionMethod::LinearVelocity => (
+                        res.lock().unwrap().get_linear_velocity()?,
+                        "linear_velocity",
+                    ),
+                    _ => {
+                        return Err(DataCollectionError::UnsupportedMethod(
+                            self.method.clone(),
+                            "base".to_string(),
+                        ))
+                    }
+                };
+                Vector3 {
+                    x: velocity.x,
+                    y: velocity.y,
+                    z: velocity.z,
+                }
+                .to_data_struct(key)
+            }
+            ResourceType::Board(ref mut res) => match &self.method {
+                CollectionMethod::Analogs(names) => {
+                    let readings: HashMap<String, Value> = if names.iter().any(|n| n == "*") {
+                        res.get_board_status()?
+                            .analogs
+                            .into_iter()
+                            .map(|(name, status)| {
+                                (
+                                    name,
+                                    Value {
+                                        kind: Some(PKind::NumberValue(status.value as f64)),
+                                    },
+                                )
+                            })
+                            .collect()
+                    } else {
+                        names
+                            .iter()
+                            .map(|name| -> Result<(String, Value), DataCollectionError> {
+                                let mut reader = res.get_analog_reader_by_name(name.clone())?;
+                                let value = reader.read()?;
+                                Ok((
+                                    name.clone(),
+                                    Value {
+                                        kind: Some(PKind::NumberValue(value as f64)),
+                                    },
+                                ))
+                            })
+                            .collect::<Result<_, _>>()?
+                    };
+                    Data::Struct(Struct {
+                        fields: HashMap::from([(
+                            "readings".to_string(),
+                            Value {
+                                kind: Some(PKind::StructValue(Struct { fields: readings })),
+                            },
+                        )]),
+                    })
+                }
+                _ => {
+                    return Err(DataCollectionError::UnsupportedMethod(
+                        self.method.clone(),
+                        "board".to_string(),
+                    ))
+                }
+            },
+            ResourceType::Generic(ref mut res) => match &self.method {
+                CollectionMethod::DoCommand(command) => res
+                    .do_command(Some(command.clone()))?
+                    .unwrap_or_default()
+                    .fields
+                    .into(),
+                _ => {
+                    return Err(DataCollectionError::UnsupportedMethod(
+                        self.method.clone(),
+                        "generic".to_string(),
+                    ))
+                }
+            },
+            ResourceType::AudioInput(ref mut res) => match self.method {
+                CollectionMethod::ReadAudio => {
+                    Data::Binary(pcm16_le_bytes(&res.read_samples(usize::MAX)?))
+                }
+                _ => {
+                    return Err(DataCollectionError::UnsupportedMethod(
+                        self.method.clone(),
+                        "audio_input".to_string(),
+                    ))
+                }
+            },
+            #[cfg(feature = "camera")]
+            ResourceType::Camera(ref mut res) => match self.method {
+                CollectionMethod::ReadImage => {
+                    let frame = res
+                        .lock()
+                        .unwrap()
+                        .get_frame(BytesMut::with_capacity(CAMERA_FRAME_BUFFER_LEN))?;
+                    let frame = GetImageResponse::decode(frame)
+                        .map_err(|_| CameraError::CameraCouldntGetFrame)?;
+                    Data::Binary(frame.image.to_vec())
+                }
+                _ => {
+                    return Err(DataCollectionError::UnsupportedMethod(
+                        self.method.clone(),
+                        "camera".to_string(),
+                    ))
+                }
+            },
             _ => return Err(DataCollectionError::NoSupportedMethods),
         };
         let reading_received_dt = Local::now().fixed_offset();
@@ -238,6 +705,17 @@ impl DataCollector {
         })
     }
 
+    /// captures data with the collector's method. When an aggregation window is configured
+    /// the capture is added to the current window and data is only returned once the window
+    /// is complete
+    pub(crate) fn collect(&mut self) -> Result<Option<SensorData>, DataCollectionError> {
+        let data = self.call_method()?;
+        Ok(match self.aggregator.as_mut() {
+            Some(aggregator) => aggregator.push(data),
+            None => Some(data),
+        })
+    }
+
     pub fn resource_method_key(&self) -> ResourceMethodKey {
         ResourceMethodKey {
             r_name: self.name(),
@@ -254,11 +732,16 @@ mod tests {
     use std::time::Duration;
 
     use super::{CollectionMethod, DataCollectionError, DataCollector, DataCollectorConfig};
+    use crate::common::analog::{AnalogReaderType, FakeAnalogReader};
+    use crate::common::audio_input::FakeAudioInput;
+    use crate::common::board::FakeBoard;
     use crate::common::config::{AttributeError, Kind};
+    use crate::common::generic::FakeGenericComponent;
+    use crate::common::motor::{FakeMotor, Motor};
     use crate::common::robot::ResourceType;
     use crate::common::sensor::FakeSensor;
     use crate::google;
-    use crate::proto::app::data_sync::v1::sensor_data::Data;
+    use crate::proto::app::data_sync::v1::{sensor_data::Data, DataType};
 
     #[test_log::test]
     fn test_collector_config() -> Result<(), AttributeError> {
@@ -273,6 +756,7 @@ mod tests {
         let conf: DataCollectorConfig = (&conf_kind).try_into()?;
         assert!(matches!(conf.method, CollectionMethod::Readings));
         assert_eq!(conf.capture_frequency_hz, 100.0);
+        assert!(!conf.disabled);
 
         let kind_map = HashMap::from([
             (
@@ -286,6 +770,17 @@ mod tests {
         assert!(matches!(conf.method, CollectionMethod::AngularVelocity));
         assert_eq!(conf.capture_frequency_hz, 100.0);
 
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("Readings".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(100.0)),
+            ("disabled".to_string(), Kind::BoolValue(true)),
+        ]);
+        let conf: DataCollectorConfig = (&Kind::StructValue(kind_map)).try_into()?;
+        assert!(conf.disabled);
+
         let kind_map = HashMap::from([
             (
                 "method".to_string(),
@@ -349,4 +844,345 @@ mod tests {
         };
         Ok(())
     }
+
+    #[test_log::test]
+    fn test_collect_motor_data() -> Result<(), DataCollectionError> {
+        let motor = Arc::new(Mutex::new(FakeMotor::new()));
+        let resource = ResourceType::Motor(motor.clone());
+        assert!(matches!(
+            DataCollector::new(
+                "fake".to_string(),
+                resource.clone(),
+                CollectionMethod::Readings,
+                10.0
+            ),
+            Err(DataCollectionError::UnsupportedMethod(_, _))
+        ));
+
+        let mut coll = DataCollector::new(
+            "fake".to_string(),
+            resource.clone(),
+            CollectionMethod::Position,
+            10.0,
+        )?;
+        let data = coll.call_method()?.data;
+        match data {
+            Some(Data::Struct(d)) => {
+                assert_eq!(
+                    d.fields.get("position").and_then(|v| v.kind.clone()),
+                    Some(google::protobuf::value::Kind::NumberValue(10.0))
+                );
+            }
+            _ => panic!("expected struct data"),
+        };
+
+        motor.lock().unwrap().set_power(0.5)?;
+        let mut coll = DataCollector::new(
+            "fake".to_string(),
+            resource,
+            CollectionMethod::IsPowered,
+            10.0,
+        )?;
+        let data = coll.call_method()?.data;
+        match data {
+            Some(Data::Struct(d)) => {
+                assert_eq!(
+                    d.fields.get("is_on").and_then(|v| v.kind.clone()),
+                    Some(google::protobuf::value::Kind::BoolValue(true))
+                );
+                assert_eq!(
+                    d.fields.get("power_pct").and_then(|v| v.kind.clone()),
+                    Some(google::protobuf::value::Kind::NumberValue(0.5))
+                );
+            }
+            _ => panic!("expected struct data"),
+        };
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_collect_analogs() -> Result<(), DataCollectionError> {
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("Analogs".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(10.0)),
+            (
+                "additional_params".to_string(),
+                Kind::StructValue(HashMap::from([(
+                    "reader_names".to_string(),
+                    Kind::VecValue(vec![
+                        Kind::StringValue("a1".to_string()),
+                        Kind::StringValue("a2".to_string()),
+                    ]),
+                )])),
+            ),
+        ]);
+        let conf = DataCollectorConfig::try_from(&Kind::StructValue(kind_map))
+            .expect("data collector config parse failed");
+        assert_eq!(
+            conf.method,
+            CollectionMethod::Analogs(vec!["a1".to_string(), "a2".to_string()])
+        );
+
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("Analogs".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(10.0)),
+        ]);
+        assert!(DataCollectorConfig::try_from(&Kind::StructValue(kind_map)).is_err());
+
+        let analogs: Vec<AnalogReaderType<u16>> = vec![
+            Arc::new(Mutex::new(FakeAnalogReader::new("a1".to_string(), 11))),
+            Arc::new(Mutex::new(FakeAnalogReader::new("a2".to_string(), 22))),
+            Arc::new(Mutex::new(FakeAnalogReader::new("a3".to_string(), 33))),
+        ];
+        let board = Arc::new(Mutex::new(FakeBoard::new(analogs)));
+
+        let mut coll = DataCollector::from_config(
+            "board".to_string(),
+            ResourceType::Board(board.clone()),
+            &conf,
+        )?;
+        let readings = match coll.call_method()?.data {
+            Some(Data::Struct(d)) => match d.fields.get("readings").and_then(|v| v.kind.clone()) {
+                Some(google::protobuf::value::Kind::StructValue(s)) => s.fields,
+                _ => panic!("readings was not a struct"),
+            },
+            _ => panic!("expected struct data"),
+        };
+        assert_eq!(readings.len(), 2);
+        assert_eq!(
+            readings.get("a2").and_then(|v| v.kind.clone()),
+            Some(google::protobuf::value::Kind::NumberValue(22.0))
+        );
+
+        let mut coll = DataCollector::new(
+            "board".to_string(),
+            ResourceType::Board(board),
+            CollectionMethod::Analogs(vec!["*".to_string()]),
+            10.0,
+        )?;
+        let readings = match coll.call_method()?.data {
+            Some(Data::Struct(d)) => match d.fields.get("readings").and_then(|v| v.kind.clone()) {
+                Some(google::protobuf::value::Kind::StructValue(s)) => s.fields,
+                _ => panic!("readings was not a struct"),
+            },
+            _ => panic!("expected struct data"),
+        };
+        assert_eq!(readings.len(), 3);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_collect_generic_do_command() -> Result<(), DataCollectionError> {
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("DoCommand".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(10.0)),
+        ]);
+        let conf = DataCollectorConfig::try_from(&Kind::StructValue(kind_map))
+            .expect("data collector config parse failed");
+        let command = match &conf.method {
+            CollectionMethod::DoCommand(command) => command.clone(),
+            _ => panic!("expected DoCommand method"),
+        };
+        assert!(command.fields.contains_key("get_readings"));
+
+        let resource = ResourceType::Generic(Arc::new(Mutex::new(FakeGenericComponent {})));
+        let mut coll = DataCollector::from_config("generic".to_string(), resource.clone(), &conf)?;
+        let readings = match coll.call_method()?.data {
+            Some(Data::Struct(d)) => match d.fields.get("readings").and_then(|v| v.kind.clone()) {
+                Some(google::protobuf::value::Kind::StructValue(s)) => s.fields,
+                _ => panic!("readings was not a struct"),
+            },
+            _ => panic!("expected struct data"),
+        };
+        assert_eq!(
+            readings.get("fake_generic").and_then(|v| v.kind.clone()),
+            Some(google::protobuf::value::Kind::NumberValue(42.42))
+        );
+
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("DoCommand".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(10.0)),
+            (
+                "additional_params".to_string(),
+                Kind::StructValue(HashMap::from([(
+                    "docommand_input".to_string(),
+                    Kind::StructValue(HashMap::from([(
+                        "echo".to_string(),
+                        Kind::StringValue("hello".to_string()),
+                    )])),
+                )])),
+            ),
+        ]);
+        let conf = DataCollectorConfig::try_from(&Kind::StructValue(kind_map))
+            .expect("data collector config parse failed");
+        let mut coll = DataCollector::from_config("generic".to_string(), resource, &conf)?;
+        let readings = match coll.call_method()?.data {
+            Some(Data::Struct(d)) => match d.fields.get("readings").and_then(|v| v.kind.clone()) {
+                Some(google::protobuf::value::Kind::StructValue(s)) => s.fields,
+                _ => panic!("readings was not a struct"),
+            },
+            _ => panic!("expected struct data"),
+        };
+        assert_eq!(
+            readings.get("echoed").and_then(|v| v.kind.clone()),
+            Some(google::protobuf::value::Kind::StringValue(
+                "hello".to_string()
+            ))
+        );
+
+        let sensor = ResourceType::Sensor(Arc::new(Mutex::new(FakeSensor::new())));
+        assert!(matches!(
+            DataCollector::from_config("fake".to_string(), sensor, &conf),
+            Err(DataCollectionError::UnsupportedMethod(_, _))
+        ));
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_collect_audio() -> Result<(), DataCollectionError> {
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("ReadAudio".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(1.0)),
+        ]);
+        let conf = DataCollectorConfig::try_from(&Kind::StructValue(kind_map))
+            .expect("data collector config parse failed");
+        assert_eq!(conf.method, CollectionMethod::ReadAudio);
+        assert_eq!(conf.method.data_type(), DataType::BinarySensor);
+        assert_eq!(
+            CollectionMethod::Readings.data_type(),
+            DataType::TabularSensor
+        );
+
+        let resource =
+            ResourceType::AudioInput(Arc::new(Mutex::new(FakeAudioInput::new(vec![1, -2, 3]))));
+        let mut coll = DataCollector::from_config("mic".to_string(), resource, &conf)?;
+        assert_eq!(
+            coll.call_method()?.data,
+            Some(Data::Binary(vec![1, 0, 0xfe, 0xff, 3, 0]))
+        );
+        // the samples were consumed by the first capture
+        assert_eq!(coll.call_method()?.data, Some(Data::Binary(vec![])));
+
+        let sensor = ResourceType::Sensor(Arc::new(Mutex::new(FakeSensor::new())));
+        assert!(matches!(
+            DataCollector::from_config("fake".to_string(), sensor, &conf),
+            Err(DataCollectionError::UnsupportedMethod(_, _))
+        ));
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_collect_aggregated() -> Result<(), DataCollectionError> {
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("IsPowered".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(10.0)),
+            (
+                "aggregation_window_secs".to_string(),
+                Kind::NumberValue(0.3),
+            ),
+        ]);
+        let conf = DataCollectorConfig::try_from(&Kind::StructValue(kind_map))
+            .expect("data collector config parse failed");
+        assert_eq!(conf.aggregation_window_secs, Some(0.3));
+
+        let motor = Arc::new(Mutex::new(FakeMotor::new()));
+        let mut coll = DataCollector::from_config(
+            "fake".to_string(),
+            ResourceType::Motor(motor.clone()),
+            &conf,
+        )?;
+        motor.lock().unwrap().set_power(0.2)?;
+        assert!(coll.collect()?.is_none());
+        motor.lock().unwrap().set_power(0.4)?;
+        assert!(coll.collect()?.is_none());
+        motor.lock().unwrap().set_power(0.6)?;
+        let fields = match coll.collect()?.and_then(|d| d.data) {
+            Some(Data::Struct(d)) => d.fields,
+            _ => panic!("expected aggregated struct data"),
+        };
+        assert_eq!(
+            fields.get("is_on").and_then(|v| v.kind.clone()),
+            Some(google::protobuf::value::Kind::BoolValue(true))
+        );
+        assert_eq!(
+            fields.get("sample_count").and_then(|v| v.kind.clone()),
+            Some(google::protobuf::value::Kind::NumberValue(3.0))
+        );
+        let stats = match fields.get("power_pct").and_then(|v| v.kind.clone()) {
+            Some(google::protobuf::value::Kind::StructValue(s)) => s.fields,
+            _ => panic!("power_pct was not aggregated"),
+        };
+        let stat = |name: &str| match stats.get(name).and_then(|v| v.kind.clone()) {
+            Some(google::protobuf::value::Kind::NumberValue(n)) => n,
+            _ => panic!("missing {}", name),
+        };
+        assert!((stat("min") - 0.2).abs() < 1e-9);
+        assert!((stat("max") - 0.6).abs() < 1e-9);
+        assert!((stat("mean") - 0.4).abs() < 1e-9);
+        assert!((stat("stddev") - 0.163299).abs() < 1e-6);
+
+        // a new window starts after the aggregate was produced
+        assert!(coll.collect()?.is_none());
+
+        // nested numeric readings are aggregated as well
+        let sensor = ResourceType::Sensor(Arc::new(Mutex::new(FakeSensor::new())));
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("Readings".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(10.0)),
+            (
+                "aggregation_window_secs".to_string(),
+                Kind::NumberValue(0.1),
+            ),
+        ]);
+        let conf = DataCollectorConfig::try_from(&Kind::StructValue(kind_map))
+            .expect("data collector config parse failed");
+        let mut coll = DataCollector::from_config("fake".to_string(), sensor, &conf)?;
+        let readings = match coll.collect()?.and_then(|d| d.data) {
+            Some(Data::Struct(d)) => match d.fields.get("readings").and_then(|v| v.kind.clone()) {
+                Some(google::protobuf::value::Kind::StructValue(s)) => s.fields,
+                _ => panic!("readings was not a struct"),
+            },
+            _ => panic!("expected aggregated struct data"),
+        };
+        assert!(matches!(
+            readings.get("fake_sensor").and_then(|v| v.kind.clone()),
+            Some(google::protobuf::value::Kind::StructValue(_))
+        ));
+
+        // binary captures can't be aggregated
+        let kind_map = HashMap::from([
+            (
+                "method".to_string(),
+                Kind::StringValue("ReadAudio".to_string()),
+            ),
+            ("capture_frequency_hz".to_string(), Kind::NumberValue(1.0)),
+            (
+                "aggregation_window_secs".to_string(),
+                Kind::NumberValue(10.0),
+            ),
+        ]);
+        assert!(DataCollectorConfig::try_from(&Kind::StructValue(kind_map)).is_err());
+        Ok(())
+    }
 }
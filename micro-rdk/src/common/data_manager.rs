@@ -1,20 +1,47 @@
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use crate::common::data_collector::{DataCollectionError, DataCollector};
 use crate::common::data_store::DataStore;
-use crate::google::protobuf::value::Kind;
-use crate::proto::app::data_sync::v1::SensorData;
+use crate::google::protobuf::{value::Kind, Struct, Value};
+use crate::proto::app::data_sync::v1::{
+    sensor_data::Data, DataCaptureUploadRequest, SensorData, UploadMetadata,
+};
 use crate::proto::app::v1::ConfigResponse;
 
-use super::app_client::AppClientConfig;
-use super::data_collector::ResourceMethodKey;
+use super::app_client::{AppClient, AppClientApi, AppClientConfig};
+use super::conn::server::AppConnector;
+use super::data_collector::{CollectionMethod, ResourceMethodKey};
 use super::data_store::{DataStoreError, WriteMode};
-use super::robot::{LocalRobot, RobotError};
+use super::generic::{DoCommand, GenericError};
+use super::instrumentation::{
+    increment_counter, Counter, InstrumentationConfig, InstrumentationError, InstrumentationSensor,
+    COLLECTOR_NAME,
+};
+use super::power_management::set_next_capture;
+use super::robot::{LocalRobot, ResourceType, RobotError};
 use async_io::Timer;
-use bytes::BytesMut;
+use prost::Message;
 use thiserror::Error;
 
+#[cfg(feature = "builtin-components")]
+use {
+    super::config::ConfigType,
+    super::generic::{GenericComponent, GenericComponentType},
+    super::registry::{ComponentRegistry, Dependency},
+};
+
+#[cfg(feature = "esp32")]
+use crate::esp32::exec::Esp32Executor;
+#[cfg(feature = "native")]
+use crate::native::exec::NativeExecutor;
+
+#[cfg(feature = "native")]
+type Executor = NativeExecutor;
+#[cfg(feature = "esp32")]
+type Executor = Esp32Executor;
+
 #[derive(Debug, Error)]
 pub enum DataManagerError {
     #[error("no data collectors in manager")]
@@ -31,6 +58,17 @@ pub enum DataManagerError {
     MultipleConfigError,
     #[error(transparent)]
     InitializationRobotError(#[from] RobotError),
+    #[error(transparent)]
+    InstrumentationError(#[from] InstrumentationError),
+}
+
+#[derive(Debug, Error)]
+#[error("data upload failed: {0}")]
+pub struct DataUploadError(pub String);
+
+/// Destination captured data is sent to at every sync, e.g. an MQTT broker
+pub trait DataUploader {
+    fn upload(&mut self, requests: &[DataCaptureUploadRequest]) -> Result<(), DataUploadError>;
 }
 
 fn get_data_sync_interval(cfg: &ConfigResponse) -> Result<Option<Duration>, DataManagerError> {
@@ -68,17 +106,142 @@ fn get_data_sync_interval(cfg: &ConfigResponse) -> Result<Option<Duration>, Data
     )
 }
 
-pub struct DataManager<StoreType> {
+/// Reads the `capture_disabled` flag of the data manager service, which disables capture for
+/// every collector
+fn get_capture_disabled(cfg: &ConfigResponse) -> Result<bool, DataManagerError> {
+    let robot_config = cfg.config.as_ref().ok_or(DataManagerError::ConfigError)?;
+    let attrs = robot_config
+        .services
+        .iter()
+        .find(|svc_cfg| svc_cfg.r#type == *"data_manager")
+        .and_then(|data_cfg| data_cfg.attributes.as_ref());
+    match attrs.and_then(|attrs| attrs.fields.get("capture_disabled")) {
+        Some(Value {
+            kind: Some(Kind::BoolValue(disabled)),
+        }) => Ok(*disabled),
+        Some(_) => Err(DataManagerError::ConfigError),
+        None => Ok(false),
+    }
+}
+
+/// Capture state set at runtime (see [DataCaptureControl]), taking precedence over the config
+/// until cleared
+#[derive(Debug, Default)]
+struct CaptureOverrides {
+    all: Option<bool>,
+    collectors: BTreeMap<String, bool>,
+}
+
+impl CaptureOverrides {
+    /// A collector override wins, otherwise capture is disabled by the global override (or the
+    /// `capture_disabled` flag of the service) or by the `disabled` flag of the collector
+    fn is_disabled(
+        &self,
+        config_disabled: bool,
+        collector: &str,
+        collector_disabled: bool,
+    ) -> bool {
+        self.collectors
+            .get(collector)
+            .copied()
+            .unwrap_or_else(|| self.all.unwrap_or(config_disabled) || collector_disabled)
+    }
+}
+
+static CAPTURE_OVERRIDES: Mutex<CaptureOverrides> = Mutex::new(CaptureOverrides {
+    all: None,
+    collectors: BTreeMap::new(),
+});
+
+/// Returns the reserved collector capturing instrumentation samples if the instrumentation
+/// service asks for them to be captured
+fn instrumentation_collector(
+    cfg: &ConfigResponse,
+) -> Result<Option<DataCollector>, DataManagerError> {
+    let capture_frequency_hz =
+        match InstrumentationConfig::from_config(cfg)?.and_then(|c| c.capture_frequency_hz) {
+            Some(capture_frequency_hz) => capture_frequency_hz,
+            None => return Ok(None),
+        };
+    Ok(Some(DataCollector::new(
+        COLLECTOR_NAME.to_string(),
+        ResourceType::Sensor(Arc::new(Mutex::new(InstrumentationSensor))),
+        CollectionMethod::Readings,
+        capture_frequency_hz,
+    )?))
+}
+
+/// Identifies a collector as `<component name>/<method>`, e.g. `sensor1/readings`
+fn collector_id(collector_key: &ResourceMethodKey) -> String {
+    format!("{}/{}", collector_key.r_name, collector_key.method)
+}
+
+/// Overrides whether data capture is disabled for every collector when `collector` is `None`,
+/// or for a single collector identified as `<component name>/<method>`. A `disabled` of `None`
+/// clears the override, going back to the config. Takes effect at the next capture.
+pub fn set_capture_disabled(collector: Option<&str>, disabled: Option<bool>) {
+    let mut overrides = CAPTURE_OVERRIDES.lock().unwrap();
+    match (collector, disabled) {
+        (None, disabled) => overrides.all = disabled,
+        (Some(collector), Some(disabled)) => {
+            let _ = overrides.collectors.insert(collector.to_string(), disabled);
+        }
+        (Some(collector), None) => {
+            let _ = overrides.collectors.remove(collector);
+        }
+    }
+}
+
+/// Groups the messages stored for a collector into upload requests. Tabular readings are
+/// batched in a single request while app expects every binary capture in its own.
+pub(crate) fn upload_requests(
+    part_id: &str,
+    collector_key: &ResourceMethodKey,
+    messages: Vec<SensorData>,
+) -> Vec<DataCaptureUploadRequest> {
+    let metadata = UploadMetadata {
+        part_id: part_id.to_string(),
+        component_type: collector_key.component_type.clone(),
+        component_name: collector_key.r_name.clone(),
+        method_name: collector_key.method.to_string(),
+        r#type: collector_key.method.data_type() as i32,
+        file_extension: collector_key.method.file_extension().to_string(),
+        ..Default::default()
+    };
+    let (binary, tabular): (Vec<_>, Vec<_>) = messages
+        .into_iter()
+        .partition(|msg| matches!(msg.data, Some(Data::Binary(_))));
+    let mut requests: Vec<DataCaptureUploadRequest> = binary
+        .into_iter()
+        .map(|msg| DataCaptureUploadRequest {
+            metadata: Some(metadata.clone()),
+            sensor_contents: vec![msg],
+        })
+        .collect();
+    if !tabular.is_empty() {
+        requests.push(DataCaptureUploadRequest {
+            metadata: Some(metadata),
+            sensor_contents: tabular,
+        });
+    }
+    requests
+}
+
+pub struct DataManager<StoreType, App = AppClient<'static>> {
     collectors: Vec<DataCollector>,
     store: StoreType,
     sync_interval: Duration,
     min_interval: Duration,
     part_id: String,
+    capture_disabled: bool,
+    uploaders: Vec<Box<dyn DataUploader>>,
+    app_client: Option<App>,
 }
 
-impl<StoreType> DataManager<StoreType>
+impl<StoreType, App> DataManager<StoreType, App>
 where
     StoreType: DataStore,
+    App: AppClientApi,
 {
     pub fn new(
         collectors: Vec<DataCollector>,
@@ -94,22 +257,29 @@ where
             sync_interval,
             min_interval,
             part_id,
+            capture_disabled: false,
+            uploaders: vec![],
+            app_client: None,
         })
     }
 
     pub fn from_robot_and_config(
         cfg: &ConfigResponse,
         app_config: &AppClientConfig,
-        robot: Arc<Mutex<LocalRobot>>,
+        robot: Arc<RwLock<LocalRobot>>,
     ) -> Result<Option<Self>, DataManagerError> {
         let part_id = app_config.get_robot_id();
         let sync_interval = get_data_sync_interval(cfg)?;
         if let Some(sync_interval) = sync_interval {
-            let collectors = robot.lock().unwrap().data_collectors()?;
+            let mut collectors = robot.read().unwrap().data_collectors()?;
+            if let Some(collector) = instrumentation_collector(cfg)? {
+                collectors.push(collector);
+            }
             let collector_keys: Vec<ResourceMethodKey> =
                 collectors.iter().map(|c| c.resource_method_key()).collect();
             let store = StoreType::from_resource_method_keys(collector_keys)?;
-            let data_manager_svc = DataManager::new(collectors, store, sync_interval, part_id)?;
+            let mut data_manager_svc = DataManager::new(collectors, store, sync_interval, part_id)?;
+            data_manager_svc.capture_disabled = get_capture_disabled(cfg)?;
             Ok(Some(data_manager_svc))
         } else {
             Ok(None)
@@ -128,6 +298,16 @@ where
         self.part_id.clone()
     }
 
+    pub fn add_uploader(&mut self, uploader: Box<dyn DataUploader>) {
+        self.uploaders.push(uploader);
+    }
+
+    /// Sets the connection captured data is synced to app over. The client is dropped once the
+    /// connection is lost, until a new one is set.
+    pub fn set_app_client(&mut self, app_client: App) {
+        self.app_client = Some(app_client);
+    }
+
     pub(crate) fn collection_intervals(&self) -> Vec<u64> {
         let mut intervals: Vec<u64> = self
             .collectors
@@ -145,17 +325,68 @@ where
     pub async fn run(&mut self) -> Result<(), DataManagerError> {
         let mut loop_counter: u64 = 0;
         loop {
-            self.run_inner(loop_counter)?;
+            self.run_inner(loop_counter).await?;
+            loop_counter += 1;
+            set_next_capture(Some(Instant::now() + self.min_interval));
+            Timer::after(self.min_interval).await;
+        }
+    }
+
+    /// Like [run](Self::run), connecting to app with `connector` before a sync whenever the data
+    /// manager has no app client, so captured data is synced again once app is reachable
+    pub async fn run_with_app<C>(
+        &mut self,
+        mut connector: C,
+        exec: Executor,
+        app_config: AppClientConfig,
+    ) -> Result<(), DataManagerError>
+    where
+        C: AppConnector<Client = App>,
+    {
+        let mut loop_counter: u64 = 0;
+        loop {
+            if self.app_client.is_none() && self.is_sync_loop(loop_counter) {
+                match connector
+                    .connect_app(exec.clone(), app_config.clone())
+                    .await
+                {
+                    Ok(app_client) => self.set_app_client(app_client),
+                    Err(err) => log::warn!("couldn't connect to app to sync data: {:?}", err),
+                }
+            }
+            self.run_inner(loop_counter).await?;
             loop_counter += 1;
+            set_next_capture(Some(Instant::now() + self.min_interval));
             Timer::after(self.min_interval).await;
         }
     }
 
-    fn run_inner(&mut self, loop_counter: u64) -> Result<(), DataManagerError> {
+    /// Collects data until `deadline` is reached, then syncs what was collected. Used when the
+    /// device only stays awake for a limited time.
+    pub async fn run_until(&mut self, deadline: Instant) -> Result<(), DataManagerError> {
+        let mut loop_counter: u64 = 0;
+        while Instant::now() < deadline {
+            self.run_inner(loop_counter).await?;
+            loop_counter += 1;
+            set_next_capture(Some(Instant::now() + self.min_interval));
+            Timer::after(
+                self.min_interval
+                    .min(deadline.saturating_duration_since(Instant::now())),
+            )
+            .await;
+        }
+        self.sync().await
+    }
+
+    fn is_sync_loop(&self, loop_counter: u64) -> bool {
+        (loop_counter % (self.sync_interval_ms() / self.min_interval_ms())) == 0
+            && (loop_counter != 0)
+    }
+
+    async fn run_inner(&mut self, loop_counter: u64) -> Result<(), DataManagerError> {
         let min_interval_ms = self.min_interval_ms();
-        if (loop_counter % (self.sync_interval_ms() / min_interval_ms)) == 0 && (loop_counter != 0)
-        {
-            self.sync()?;
+        if self.is_sync_loop(loop_counter) {
+            self.sync().await?;
         }
         for interval in self.collection_intervals() {
             if loop_counter % (interval / min_interval_ms) == 0 {
@@ -165,24 +396,82 @@ where
         Ok(())
     }
 
-    fn sync(&mut self) -> Result<(), DataManagerError> {
+    async fn sync(&mut self) -> Result<(), DataManagerError> {
+        // without a destination the readings stay stored until one is set
+        if self.app_client.is_none() && self.uploaders.is_empty() {
+            return Ok(());
+        }
         for collector_key in self.collectors.iter().map(|c| c.resource_method_key()) {
             // TODO: check for internet access before attempting to read from store
-            let mut readings_to_upload: Vec<BytesMut> = vec![];
+            let mut readings_to_upload: Vec<SensorData> = vec![];
             loop {
                 match self.store.read_next_message(&collector_key) {
                     Ok(msg) => {
                         if msg.is_empty() {
                             break;
                         }
-                        readings_to_upload.push(msg);
+                        match SensorData::decode(msg) {
+                            Ok(reading) => readings_to_upload.push(reading),
+                            Err(err) => log::error!(
+                                "dropping malformed message stored for {}: {:?}",
+                                collector_key,
+                                err
+                            ),
+                        }
                     }
                     Err(err) => return Err(err.into()),
                 };
             }
-            // TODO: implement actual upload logic here, will likely have to change struct
-            // and make this function async
+            if readings_to_upload.is_empty() {
+                continue;
+            }
+            let requests = upload_requests(&self.part_id, &collector_key, readings_to_upload);
+            let mut uploaded = true;
+            for uploader in self.uploaders.iter_mut() {
+                if let Err(err) = uploader.upload(&requests) {
+                    log::error!("couldn't upload data of {}: {}", collector_key, err);
+                    uploaded = false;
+                }
+            }
+            // app is where captured data belongs when connected, the uploaders only mirror it
+            let unsent = match self.app_client.as_mut() {
+                Some(app_client) => {
+                    let mut unsent = vec![];
+                    for request in requests {
+                        if !app_client.is_connected() {
+                            unsent.push(request);
+                            continue;
+                        }
+                        let len = request.encoded_len() as u64;
+                        match app_client.push_sensor_data(request.clone()).await {
+                            Ok(_) => increment_counter(Counter::DataUploadBytes, len),
+                            Err(err) => {
+                                log::error!(
+                                    "couldn't sync data of {} to app: {}",
+                                    collector_key,
+                                    err
+                                );
+                                unsent.push(request);
+                            }
+                        }
+                    }
+                    if !app_client.is_connected() {
+                        log::warn!("lost the connection to app, data won't be synced");
+                        self.app_client = None;
+                    }
+                    unsent
+                }
+                None if uploaded => vec![],
+                None => requests,
+            };
+            // the readings that didn't make it are stored again for the next sync
+            for reading in unsent.into_iter().flat_map(|req| req.sensor_contents) {
+                self.store
+                    .write_message(&collector_key, reading, WriteMode::OverwriteOldest)?;
+            }
         }
+        // the device may reboot or go to deep sleep before the next sync
+        self.store.save_read_positions()?;
         Ok(())
     }
 
@@ -192,7 +481,8 @@ where
     ) -> Result<(), DataManagerError> {
         for (collector_key, reading) in self.collect_readings_for_interval(time_interval_ms)? {
             self.store
-                .write_message(&collector_key, reading, WriteMode::OverwriteOldest)?
+                .write_message(&collector_key, reading, WriteMode::OverwriteOldest)?;
+            increment_counter(Counter::DataCaptures, 1);
         }
         Ok(())
     }
@@ -211,30 +501,156 @@ where
                 min_interval_ms,
             ));
         }
+        // the lock isn't held while capturing, a collector could be controlling the capture
+        let disabled: Vec<bool> = {
+            let overrides = CAPTURE_OVERRIDES.lock().unwrap();
+            self.collectors
+                .iter()
+                .map(|coll| {
+                    overrides.is_disabled(
+                        self.capture_disabled,
+                        &collector_id(&coll.resource_method_key()),
+                        coll.is_disabled(),
+                    )
+                })
+                .collect()
+        };
         self.collectors
             .iter_mut()
-            .filter(|coll| {
-                (coll.time_interval().as_millis() as u64 / min_interval_ms)
-                    == (time_interval_ms / min_interval_ms)
+            .zip(disabled)
+            .filter(|(coll, disabled)| {
+                !disabled
+                    && (coll.time_interval().as_millis() as u64 / min_interval_ms)
+                        == (time_interval_ms / min_interval_ms)
+            })
+            .filter_map(|(coll, _)| {
+                coll.collect()
+                    .map(|data| data.map(|data| (coll.resource_method_key(), data)))
+                    .map_err(DataManagerError::from)
+                    .transpose()
             })
-            .map(|coll| Ok((coll.resource_method_key(), coll.call_method()?)))
             .collect()
     }
 }
 
+#[cfg(feature = "builtin-components")]
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_generic_component("data_capture", &DataCaptureControl::from_config)
+        .is_err()
+    {
+        log::error!("data_capture model is already registered");
+    }
+}
+
+/// Pauses and resumes data capture at runtime without restarting the device
+///
+/// ```json
+/// {
+///     "name": "capture",
+///     "model": "data_capture",
+///     "type": "generic",
+///     "attributes": {}
+/// }
+/// ```
+///
+/// Its DoCommand accepts `{"pause": {}}`, `{"resume": {}}` and `{"reset": {}}` (going back to
+/// the config), applying to every collector or to a single one with
+/// `{"pause": {"collector": "sensor1/readings"}}`, as well as `{"status": {}}` which returns the
+/// current overrides.
+#[derive(Status)]
+pub struct DataCaptureControl;
+
+#[cfg(feature = "builtin-components")]
+impl DataCaptureControl {
+    pub(crate) fn from_config(
+        _: ConfigType,
+        _: Vec<Dependency>,
+    ) -> Result<GenericComponentType, GenericError> {
+        Ok(Arc::new(Mutex::new(DataCaptureControl)))
+    }
+}
+
+#[cfg(feature = "builtin-components")]
+impl GenericComponent for DataCaptureControl {}
+
+impl DoCommand for DataCaptureControl {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.ok_or(GenericError::InvalidArgument("missing command"))?;
+        for (key, val) in &command.fields {
+            let disabled = match key.as_str() {
+                "pause" => Some(true),
+                "resume" => Some(false),
+                "reset" => None,
+                "status" => continue,
+                _ => return Err(GenericError::MethodUnimplemented("unknown capture command")),
+            };
+            let collector = match &val.kind {
+                Some(Kind::StructValue(args)) => match args.fields.get("collector") {
+                    Some(Value {
+                        kind: Some(Kind::StringValue(collector)),
+                    }) => Some(collector.as_str()),
+                    Some(_) => return Err(GenericError::InvalidArgument("collector")),
+                    None => None,
+                },
+                _ => None,
+            };
+            set_capture_disabled(collector, disabled);
+        }
+        let overrides = CAPTURE_OVERRIDES.lock().unwrap();
+        let mut fields = HashMap::from([(
+            "collectors".to_string(),
+            Value {
+                kind: Some(Kind::StructValue(Struct {
+                    fields: overrides
+                        .collectors
+                        .iter()
+                        .map(|(id, disabled)| {
+                            (
+                                id.clone(),
+                                Value {
+                                    kind: Some(Kind::BoolValue(*disabled)),
+                                },
+                            )
+                        })
+                        .collect(),
+                })),
+            },
+        )]);
+        if let Some(all) = overrides.all {
+            fields.insert(
+                "all".to_string(),
+                Value {
+                    kind: Some(Kind::BoolValue(all)),
+                },
+            );
+        }
+        Ok(Some(Struct { fields }))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{HashMap, VecDeque};
     use std::mem::MaybeUninit;
     use std::sync::{Arc, Mutex};
     use std::time::Duration;
 
     use bytes::{BufMut, BytesMut};
+    use futures_lite::future::block_on;
+    use prost::Message;
     use ringbuf::{LocalRb, Rb};
 
-    use super::DataManager;
+    use super::{
+        set_capture_disabled, upload_requests, CaptureOverrides, DataCaptureControl, DataManager,
+    };
+    use crate::common::app_client::mock::MockAppClient;
     use crate::common::data_store::WriteMode;
     use crate::common::encoder::EncoderError;
+    use crate::common::generic::DoCommand;
     use crate::common::{
         data_collector::{CollectionMethod, DataCollector, ResourceMethodKey},
         data_store::{DataStore, DataStoreError},
@@ -246,8 +662,10 @@ mod tests {
         status::{Status, StatusError},
     };
     use crate::google::protobuf::value::Kind;
-    use crate::google::protobuf::Struct;
-    use crate::proto::app::data_sync::v1::{sensor_data::Data, SensorData};
+    use crate::google::protobuf::{Struct, Value};
+    use crate::proto::app::data_sync::v1::{sensor_data::Data, DataType, SensorData};
+
+    type TestDataManager<StoreType> = DataManager<StoreType, MockAppClient>;
 
     #[derive(DoCommand)]
     struct TestSensorFailure {}
@@ -360,7 +778,7 @@ mod tests {
         let data_colls = vec![data_coll_1, data_coll_2, data_coll_3];
         let store = NoOpStore {};
 
-        let data_manager = DataManager::new(
+        let data_manager = TestDataManager::new(
             data_colls,
             store,
             Duration::from_millis(30),
@@ -412,7 +830,7 @@ mod tests {
         let data_colls = vec![data_coll_1, data_coll_2, data_coll_3];
         let store = NoOpStore {};
 
-        let data_manager = DataManager::new(
+        let data_manager = TestDataManager::new(
             data_colls,
             store,
             Duration::from_millis(30),
@@ -512,7 +930,7 @@ mod tests {
         assert!(data_coll_3.is_ok());
         let data_coll_3 = data_coll_3.unwrap();
 
-        let data_manager = DataManager::new(
+        let data_manager = TestDataManager::new(
             vec![data_coll_1, data_coll_3],
             store,
             Duration::from_millis(30),
@@ -618,7 +1036,41 @@ mod tests {
         }
     }
 
-    fn get_values_from_manager(manager: &DataManager<ReadSavingStore>) -> Vec<f64> {
+    // keeps the encoded messages, as the real stores do
+    #[derive(Default)]
+    struct EncodingStore(HashMap<String, VecDeque<BytesMut>>);
+
+    impl DataStore for EncodingStore {
+        fn write_message(
+            &mut self,
+            collector_key: &ResourceMethodKey,
+            message: SensorData,
+            _write_mode: WriteMode,
+        ) -> Result<(), DataStoreError> {
+            self.0
+                .entry(collector_key.to_string())
+                .or_default()
+                .push_back(BytesMut::from(&message.encode_to_vec()[..]));
+            Ok(())
+        }
+        fn read_next_message(
+            &mut self,
+            collector_key: &ResourceMethodKey,
+        ) -> Result<BytesMut, DataStoreError> {
+            Ok(self
+                .0
+                .get_mut(&collector_key.to_string())
+                .and_then(|messages| messages.pop_front())
+                .unwrap_or_default())
+        }
+        fn from_resource_method_keys(
+            _collector_keys: Vec<ResourceMethodKey>,
+        ) -> Result<Self, DataStoreError> {
+            Ok(Self::default())
+        }
+    }
+
+    fn get_values_from_manager(manager: &TestDataManager<ReadSavingStore>) -> Vec<f64> {
         let read_data = manager
             .store
             .read_messages()
@@ -665,7 +1117,7 @@ mod tests {
         assert!(data_coll_2.is_ok());
         let data_coll_2 = data_coll_2.unwrap();
 
-        let manager = DataManager::new(
+        let manager = TestDataManager::new(
             vec![data_coll_1, data_coll_2],
             ReadSavingStore::new(),
             Duration::from_millis(65),
@@ -678,9 +1130,206 @@ mod tests {
             42.42, 42.42, 42.42, 24.24, 24.24, 42.42, 42.42, 42.42, 24.24,
         ];
         for i in 0..7 {
-            assert!(manager.run_inner(i).is_ok());
+            assert!(block_on(manager.run_inner(i)).is_ok());
         }
         let read_data = get_values_from_manager(&manager);
         assert_eq!(read_data, expected_data);
     }
+
+    #[test_log::test]
+    fn test_upload_requests() {
+        let binary_key = ResourceMethodKey {
+            r_name: "mic".to_string(),
+            component_type: "rdk:component:audio_input".to_string(),
+            method: CollectionMethod::ReadAudio,
+        };
+        let binary = |data: Vec<u8>| SensorData {
+            metadata: None,
+            data: Some(Data::Binary(data)),
+        };
+        let requests = upload_requests(
+            "part",
+            &binary_key,
+            vec![binary(vec![1, 2]), binary(vec![3])],
+        );
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].sensor_contents, vec![binary(vec![3])]);
+        let metadata = requests[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata.part_id, "part");
+        assert_eq!(metadata.component_name, "mic");
+        assert_eq!(metadata.r#type, DataType::BinarySensor as i32);
+        assert_eq!(metadata.file_extension, ".pcm");
+
+        let tabular_key = ResourceMethodKey {
+            r_name: "r1".to_string(),
+            component_type: "rdk:component:sensor".to_string(),
+            method: CollectionMethod::Readings,
+        };
+        let tabular = SensorData {
+            metadata: None,
+            data: Some(Data::Struct(Struct::default())),
+        };
+        let requests = upload_requests("part", &tabular_key, vec![tabular.clone(); 3]);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].sensor_contents.len(), 3);
+        assert_eq!(
+            requests[0].metadata.as_ref().unwrap().r#type,
+            DataType::TabularSensor as i32
+        );
+
+        assert!(upload_requests("part", &tabular_key, vec![]).is_empty());
+    }
+
+    #[test_log::test]
+    fn test_capture_overrides() {
+        let mut overrides = CaptureOverrides::default();
+        assert!(!overrides.is_disabled(false, "s/readings", false));
+        assert!(overrides.is_disabled(true, "s/readings", false));
+        assert!(overrides.is_disabled(false, "s/readings", true));
+
+        overrides.all = Some(false);
+        assert!(!overrides.is_disabled(true, "s/readings", false));
+        assert!(overrides.is_disabled(true, "s/readings", true));
+        overrides.all = Some(true);
+        assert!(overrides.is_disabled(false, "s/readings", false));
+
+        overrides.collectors.insert("s/readings".to_string(), false);
+        assert!(!overrides.is_disabled(true, "s/readings", true));
+        assert!(overrides.is_disabled(false, "t/readings", false));
+    }
+
+    #[test_log::test]
+    fn test_capture_paused() {
+        let data_colls = ["capture_on", "capture_paused"]
+            .into_iter()
+            .map(|name| {
+                DataCollector::new(
+                    name.to_string(),
+                    ResourceType::Sensor(Arc::new(Mutex::new(TestSensor {}))),
+                    CollectionMethod::Readings,
+                    10.0,
+                )
+                .unwrap()
+            })
+            .collect();
+        let mut data_manager = TestDataManager::new(
+            data_colls,
+            NoOpStore {},
+            Duration::from_millis(30),
+            "1".to_string(),
+        )
+        .unwrap();
+
+        let command = |name: &str, collector: &str| {
+            Some(Struct {
+                fields: HashMap::from([(
+                    name.to_string(),
+                    Value {
+                        kind: Some(Kind::StructValue(Struct {
+                            fields: HashMap::from([(
+                                "collector".to_string(),
+                                Value {
+                                    kind: Some(Kind::StringValue(collector.to_string())),
+                                },
+                            )]),
+                        })),
+                    },
+                )]),
+            })
+        };
+        let status = DataCaptureControl
+            .do_command(command("pause", "capture_paused/readings"))
+            .unwrap()
+            .unwrap();
+        match &status.fields["collectors"].kind {
+            Some(Kind::StructValue(collectors)) => assert_eq!(
+                collectors.fields["capture_paused/readings"].kind,
+                Some(Kind::BoolValue(true))
+            ),
+            _ => panic!("collectors should be a struct"),
+        }
+        let readings = data_manager.collect_readings_for_interval(100).unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].0.r_name, "capture_on");
+
+        DataCaptureControl
+            .do_command(command("resume", "capture_paused/readings"))
+            .unwrap();
+        assert_eq!(
+            data_manager
+                .collect_readings_for_interval(100)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        set_capture_disabled(Some("capture_paused/readings"), None);
+        data_manager.capture_disabled = true;
+        assert!(data_manager
+            .collect_readings_for_interval(100)
+            .unwrap()
+            .is_empty());
+
+        assert!(DataCaptureControl
+            .do_command(command("stop", "capture_paused/readings"))
+            .is_err());
+    }
+
+    #[test_log::test]
+    fn test_sync_to_app() {
+        let collector = DataCollector::new(
+            "r1".to_string(),
+            ResourceType::Sensor(Arc::new(Mutex::new(TestSensor {}))),
+            CollectionMethod::Readings,
+            50.0,
+        )
+        .unwrap();
+        let mut manager = TestDataManager::new(
+            vec![collector],
+            EncodingStore::default(),
+            Duration::from_millis(40),
+            "part".to_string(),
+        )
+        .unwrap();
+        let client = MockAppClient::new();
+        manager.set_app_client(client.clone());
+
+        // readings of the first two loops are synced at the third
+        for i in 0..3 {
+            assert!(block_on(manager.run_inner(i)).is_ok());
+        }
+        {
+            let state = client.0.lock().unwrap();
+            assert_eq!(state.sensor_data.len(), 1);
+            assert_eq!(state.sensor_data[0].sensor_contents.len(), 2);
+            assert_eq!(
+                state.sensor_data[0].metadata.as_ref().unwrap().part_id,
+                "part"
+            );
+        }
+
+        // the client is dropped with the connection, capture goes on and the readings that
+        // couldn't be synced are kept
+        client.0.lock().unwrap().failures = 1;
+        for i in 3..5 {
+            assert!(block_on(manager.run_inner(i)).is_ok());
+        }
+        assert!(manager.app_client.is_none());
+        assert_eq!(client.0.lock().unwrap().sensor_data.len(), 1);
+
+        // nothing is lost while there is no client
+        for i in 5..7 {
+            assert!(block_on(manager.run_inner(i)).is_ok());
+        }
+
+        // the unsent readings are sent with the new ones once reconnected
+        let reconnected = MockAppClient::new();
+        manager.set_app_client(reconnected.clone());
+        for i in 7..9 {
+            assert!(block_on(manager.run_inner(i)).is_ok());
+        }
+        let state = reconnected.0.lock().unwrap();
+        assert_eq!(state.sensor_data.len(), 1);
+        assert_eq!(state.sensor_data[0].sensor_contents.len(), 6);
+    }
 }
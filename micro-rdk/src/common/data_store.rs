@@ -1,4 +1,5 @@
-//! Contains the DataStore trait and a usable StaticMemoryDataStore.
+//! Contains the DataStore trait, a usable StaticMemoryDataStore and an SdCardDataStore keeping
+//! messages in files for deployments buffering more data than fits in memory.
 //! Implementers of the trait are meant to be written to by DataCollectors (RSDK-6992, RSDK-6994)
 //! and read from by a task that uploads the data to app (RSDK-6995)
 
@@ -7,8 +8,15 @@ use bytes::{Buf, BufMut, BytesMut};
 use prost::{encoding::decode_varint, length_delimiter_len, DecodeError, EncodeError, Message};
 use ringbuf::{ring_buffer::RbBase, Consumer, LocalRb, Producer};
 use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
     mem::MaybeUninit,
-    sync::atomic::{AtomicBool, Ordering},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
 };
 use thiserror::Error;
 
@@ -48,6 +56,10 @@ pub enum DataStoreError {
     DecodeError(#[from] DecodeError),
     #[error("unimplemented")]
     Unimplemented,
+    #[error("no sd card mounted")]
+    NoStorage,
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
 }
 
 lazy_static::lazy_static! {
@@ -71,6 +83,13 @@ pub trait DataStore {
         collector_key: &ResourceMethodKey,
     ) -> Result<BytesMut, DataStoreError>;
 
+    /// Saves where reading stopped for every collector, so messages already read aren't read
+    /// again after a reboot or a deep sleep. Stores losing their messages on reboot have nothing
+    /// to save.
+    fn save_read_positions(&mut self) -> Result<(), DataStoreError> {
+        Ok(())
+    }
+
     /// Initializes from resource-method keys.
     fn from_resource_method_keys(
         collector_keys: Vec<ResourceMethodKey>,
@@ -137,6 +156,11 @@ impl DataStore for StaticMemoryDataStore {
         let buffer = &self.buffers[buffer_index];
         let encode_len = message.encoded_len();
         let total_encode_len = length_delimiter_len(encode_len) + encode_len;
+        // a binary capture (camera frame, audio clip) can exceed the whole region of the
+        // collector, evicting older messages would never make room for it
+        if total_encode_len > buffer.capacity_nonzero().get() {
+            return Err(DataStoreError::DataTooLarge);
+        }
 
         while total_encode_len > buffer.vacant_len() {
             if !matches!(write_mode, WriteMode::OverwriteOldest) {
@@ -202,6 +226,303 @@ impl Drop for StaticMemoryDataStore {
     }
 }
 
+/// Messages of a collector are appended to files of at most this size, the oldest file is
+/// removed whole once read or when space is needed
+const SEGMENT_SIZE: u64 = 64 * 1024;
+/// File of a collector directory holding the segment and offset reading stopped at
+const CURSOR_FILE_NAME: &str = "CURSOR.POS";
+/// Space each collector may use on the card unless the board configures otherwise
+pub const DEFAULT_SD_CARD_COLLECTOR_CAPACITY: u64 = 16 * 1024 * 1024;
+
+/// Where [SdCardDataStore] keeps its files, set once the card is mounted
+#[derive(Clone, Debug)]
+pub struct SdCardStorage {
+    pub directory: PathBuf,
+    pub capacity_per_collector: u64,
+}
+
+static SD_CARD_STORAGE: OnceLock<SdCardStorage> = OnceLock::new();
+
+// FAT without long file names only takes 8.3 names, collectors are named after a hash of their key
+fn collector_directory_name(key: &ResourceMethodKey) -> String {
+    let hash = key.to_string().bytes().fold(0x811c9dc5_u32, |hash, b| {
+        (hash ^ b as u32).wrapping_mul(0x01000193)
+    });
+    format!("{:08X}", hash)
+}
+
+struct CollectorFiles {
+    directory: PathBuf,
+    // numbers of the segment files on the card, oldest first
+    segments: VecDeque<u32>,
+    // offset of the next message to read in the oldest segment
+    read_offset: u64,
+    // length of the newest segment
+    write_len: u64,
+}
+
+impl CollectorFiles {
+    fn open(directory: PathBuf) -> Result<Self, DataStoreError> {
+        fs::create_dir_all(&directory)?;
+        let mut segments: Vec<u32> = fs::read_dir(&directory)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                let (number, extension) = name.to_str()?.split_once('.')?;
+                extension
+                    .eq_ignore_ascii_case("SEG")
+                    .then(|| number.parse().ok())
+                    .flatten()
+            })
+            .collect();
+        segments.sort_unstable();
+        // the saved position only applies if its segment wasn't removed since
+        let read_offset = match fs::read(directory.join(CURSOR_FILE_NAME)) {
+            Ok(cursor) if cursor.len() == 12 => {
+                let segment = u32::from_le_bytes(cursor[..4].try_into().unwrap());
+                let offset = u64::from_le_bytes(cursor[4..].try_into().unwrap());
+                if segments.first() == Some(&segment) {
+                    offset
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        };
+        Ok(Self {
+            directory,
+            segments: segments.into(),
+            read_offset,
+            // the last segment written before a reboot may end with a partial message, new
+            // messages are never appended to it
+            write_len: SEGMENT_SIZE,
+        })
+    }
+
+    fn segment_path(&self, segment: u32) -> PathBuf {
+        self.directory.join(format!("{:08}.SEG", segment))
+    }
+
+    /// writes the read position in the oldest segment to the cursor file of the collector
+    fn save_cursor(&self) -> Result<(), DataStoreError> {
+        if let Some(segment) = self.segments.front() {
+            let mut cursor = segment.to_le_bytes().to_vec();
+            cursor.extend_from_slice(&self.read_offset.to_le_bytes());
+            fs::write(self.directory.join(CURSOR_FILE_NAME), cursor)?;
+        }
+        Ok(())
+    }
+
+    fn remove_oldest(&mut self) -> Result<(), DataStoreError> {
+        if let Some(segment) = self.segments.pop_front() {
+            self.read_offset = 0;
+            if self.segments.is_empty() {
+                self.write_len = SEGMENT_SIZE;
+                // segment numbers start over, a saved position could match a new segment
+                let _ = fs::remove_file(self.directory.join(CURSOR_FILE_NAME));
+            }
+            fs::remove_file(self.segment_path(segment))?;
+        }
+        Ok(())
+    }
+
+    /// reads the message at `read_offset` in the oldest segment, `None` at the end of the segment
+    fn read_oldest(&mut self) -> Result<Option<BytesMut>, DataStoreError> {
+        let mut file = File::open(self.segment_path(self.segments[0]))?;
+        file.seek(SeekFrom::Start(self.read_offset))?;
+        let mut delimiter = [0_u8; 10];
+        let read = file.read(&mut delimiter)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let encoded_len = decode_varint(&mut &delimiter[..read])? as usize;
+        let advance = length_delimiter_len(encoded_len);
+        file.seek(SeekFrom::Start(self.read_offset + advance as u64))?;
+        let mut msg_bytes = BytesMut::zeroed(encoded_len);
+        file.read_exact(&mut msg_bytes)?;
+        self.read_offset += (advance + encoded_len) as u64;
+        Ok(Some(msg_bytes))
+    }
+}
+
+/// SdCardDataStore keeps the messages of each collector in files under the directory set with
+/// [SdCardDataStore::set_storage], typically on an SD card, so days of readings can be
+/// buffered while offline. Messages are appended to segment files of the collector, a segment
+/// is removed once every message in it was read. Read positions are saved on the card by
+/// [DataStore::save_read_positions], messages read after the last save are read again after a
+/// reboot.
+pub struct SdCardDataStore {
+    collectors: Vec<CollectorFiles>,
+    collector_keys: Vec<ResourceMethodKey>,
+    max_segments: usize,
+}
+
+impl SdCardDataStore {
+    /// Sets where stores are created from [DataStore::from_resource_method_keys], fails if
+    /// already set
+    pub fn set_storage(storage: SdCardStorage) -> Result<(), DataStoreError> {
+        SD_CARD_STORAGE
+            .set(storage)
+            .map_err(|_| DataStoreError::DataStoreInitialized)
+    }
+
+    pub fn storage() -> Option<&'static SdCardStorage> {
+        SD_CARD_STORAGE.get()
+    }
+
+    pub fn new(
+        directory: &Path,
+        collector_keys: Vec<ResourceMethodKey>,
+        capacity_per_collector: u64,
+    ) -> Result<Self, DataStoreError> {
+        let collectors = collector_keys
+            .iter()
+            .map(|key| CollectorFiles::open(directory.join(collector_directory_name(key))))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            collectors,
+            collector_keys,
+            max_segments: (capacity_per_collector / SEGMENT_SIZE).max(1) as usize,
+        })
+    }
+
+    fn get_index_for_collector(
+        &self,
+        collector_key: &ResourceMethodKey,
+    ) -> Result<usize, DataStoreError> {
+        self.collector_keys
+            .iter()
+            .position(|key| key == collector_key)
+            .ok_or(DataStoreError::UnknownCollectorKey(collector_key.clone()))
+    }
+}
+
+impl DataStore for SdCardDataStore {
+    fn write_message(
+        &mut self,
+        collector_key: &ResourceMethodKey,
+        message: SensorData,
+        write_mode: WriteMode,
+    ) -> Result<(), DataStoreError> {
+        let collector = &mut self.collectors[self.get_index_for_collector(collector_key)?];
+        let encoded = message.encode_length_delimited_to_vec();
+        if encoded.len() as u64 > SEGMENT_SIZE {
+            return Err(DataStoreError::DataTooLarge);
+        }
+        if collector.segments.is_empty()
+            || collector.write_len + encoded.len() as u64 > SEGMENT_SIZE
+        {
+            if collector.segments.len() >= self.max_segments {
+                if !matches!(write_mode, WriteMode::OverwriteOldest) {
+                    return Err(DataStoreError::DataBufferFull(
+                        collector_key.clone(),
+                        message,
+                    ));
+                }
+                collector.remove_oldest()?;
+            }
+            let next = collector.segments.back().map_or(0, |last| last + 1);
+            collector.segments.push_back(next);
+            collector.write_len = 0;
+        }
+        let path = collector.segment_path(*collector.segments.back().unwrap());
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(&encoded)
+            .map_err(|_| DataStoreError::DataWriteFailure)?;
+        collector.write_len += encoded.len() as u64;
+        Ok(())
+    }
+
+    fn read_next_message(
+        &mut self,
+        collector_key: &ResourceMethodKey,
+    ) -> Result<BytesMut, DataStoreError> {
+        let collector = &mut self.collectors[self.get_index_for_collector(collector_key)?];
+        while !collector.segments.is_empty() {
+            let newest = collector.segments.len() == 1;
+            match collector.read_oldest() {
+                Ok(Some(msg_bytes)) => return Ok(msg_bytes),
+                // the segment still being written to is kept until it's full
+                Ok(None) if newest => break,
+                Ok(None) => collector.remove_oldest()?,
+                Err(err) if newest => return Err(err),
+                Err(err) => {
+                    log::warn!("dropping the rest of a corrupted data segment: {}", err);
+                    collector.remove_oldest()?;
+                }
+            }
+        }
+        Ok(BytesMut::with_capacity(0))
+    }
+
+    fn save_read_positions(&mut self) -> Result<(), DataStoreError> {
+        self.collectors
+            .iter()
+            .try_for_each(|collector| collector.save_cursor())
+    }
+
+    fn from_resource_method_keys(
+        collector_keys: Vec<ResourceMethodKey>,
+    ) -> Result<Self, DataStoreError> {
+        let storage = Self::storage().ok_or(DataStoreError::NoStorage)?;
+        Self::new(
+            &storage.directory,
+            collector_keys,
+            storage.capacity_per_collector,
+        )
+    }
+}
+
+/// Store used by the data manager: an [SdCardDataStore] when an SD card was mounted, a
+/// [StaticMemoryDataStore] otherwise
+pub enum DefaultDataStore {
+    StaticMemory(StaticMemoryDataStore),
+    SdCard(SdCardDataStore),
+}
+
+impl DataStore for DefaultDataStore {
+    fn write_message(
+        &mut self,
+        collector_key: &ResourceMethodKey,
+        message: SensorData,
+        write_mode: WriteMode,
+    ) -> Result<(), DataStoreError> {
+        match self {
+            Self::StaticMemory(store) => store.write_message(collector_key, message, write_mode),
+            Self::SdCard(store) => store.write_message(collector_key, message, write_mode),
+        }
+    }
+
+    fn read_next_message(
+        &mut self,
+        collector_key: &ResourceMethodKey,
+    ) -> Result<BytesMut, DataStoreError> {
+        match self {
+            Self::StaticMemory(store) => store.read_next_message(collector_key),
+            Self::SdCard(store) => store.read_next_message(collector_key),
+        }
+    }
+
+    fn save_read_positions(&mut self) -> Result<(), DataStoreError> {
+        match self {
+            Self::StaticMemory(store) => store.save_read_positions(),
+            Self::SdCard(store) => store.save_read_positions(),
+        }
+    }
+
+    fn from_resource_method_keys(
+        collector_keys: Vec<ResourceMethodKey>,
+    ) -> Result<Self, DataStoreError> {
+        if SdCardDataStore::storage().is_some() {
+            return SdCardDataStore::from_resource_method_keys(collector_keys).map(Self::SdCard);
+        }
+        StaticMemoryDataStore::new(collector_keys).map(Self::StaticMemory)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -535,5 +856,109 @@ mod tests {
             );
             assert!(res.is_ok());
         }
+
+        // binary data larger than the region of the collector is rejected
+        let binary_message = SensorData {
+            metadata: None,
+            data: Some(Data::Binary(vec![0; unsafe { DATA_STORE.len() } / 2])),
+        };
+        assert!(matches!(
+            store.write_message(&collector_key, binary_message, WriteMode::OverwriteOldest),
+            Err(DataStoreError::DataTooLarge)
+        ));
+
+        let binary_message = SensorData {
+            metadata: None,
+            data: Some(Data::Binary(vec![0xab; 4096])),
+        };
+        assert!(store
+            .write_message(
+                &collector_key,
+                binary_message.clone(),
+                WriteMode::OverwriteOldest
+            )
+            .is_ok());
+        let mut last = None;
+        loop {
+            let msg = store.read_next_message(&collector_key).unwrap();
+            if msg.is_empty() {
+                break;
+            }
+            last = Some(SensorData::decode(msg).unwrap());
+        }
+        assert_eq!(last, Some(binary_message));
+    }
+
+    #[test_log::test]
+    fn test_sd_card_data_store() {
+        let directory = std::env::temp_dir().join(format!("sd-card-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let key = ResourceMethodKey {
+            r_name: "thing".to_string(),
+            component_type: "rdk::component::sensor".to_string(),
+            method: CollectionMethod::Readings,
+        };
+        let message = |i: u8| SensorData {
+            metadata: None,
+            data: Some(Data::Binary(vec![i; 20 * 1024])),
+        };
+        let read_all = |store: &mut super::SdCardDataStore| {
+            let mut messages = vec![];
+            loop {
+                let msg = store.read_next_message(&key).unwrap();
+                if msg.is_empty() {
+                    break messages;
+                }
+                messages.push(SensorData::decode(msg).unwrap());
+            }
+        };
+
+        // two segments of three messages
+        let capacity = 2 * super::SEGMENT_SIZE;
+        let mut store =
+            super::SdCardDataStore::new(&directory, vec![key.clone()], capacity).unwrap();
+        for i in 0..6 {
+            assert!(store
+                .write_message(&key, message(i), WriteMode::PreserveOrFail)
+                .is_ok());
+        }
+        assert!(matches!(
+            store.write_message(&key, message(6), WriteMode::PreserveOrFail),
+            Err(DataStoreError::DataBufferFull(_, _))
+        ));
+        // the oldest segment makes room for new messages
+        assert!(store
+            .write_message(&key, message(6), WriteMode::OverwriteOldest)
+            .is_ok());
+        assert_eq!(
+            read_all(&mut store),
+            (3..7).map(message).collect::<Vec<_>>()
+        );
+
+        // messages survive a reboot, new ones go to a new segment
+        assert!(store
+            .write_message(&key, message(7), WriteMode::PreserveOrFail)
+            .is_ok());
+        drop(store);
+        let mut store =
+            super::SdCardDataStore::new(&directory, vec![key.clone()], capacity).unwrap();
+        assert!(store
+            .write_message(&key, message(8), WriteMode::PreserveOrFail)
+            .is_ok());
+        let messages = read_all(&mut store);
+        assert_eq!(messages.last(), Some(&message(8)));
+        assert!(messages.contains(&message(7)));
+
+        // reading resumes where it was saved after a reboot
+        assert!(store.save_read_positions().is_ok());
+        drop(store);
+        let mut store =
+            super::SdCardDataStore::new(&directory, vec![key.clone()], capacity).unwrap();
+        assert!(store
+            .write_message(&key, message(9), WriteMode::PreserveOrFail)
+            .is_ok());
+        assert_eq!(read_all(&mut store), vec![message(9)]);
+
+        let _ = std::fs::remove_dir_all(&directory);
     }
 }
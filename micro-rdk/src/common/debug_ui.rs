@@ -0,0 +1,353 @@
+//! Single page debug UI served by the [json endpoint](super::json_endpoint) when the `debug-ui`
+//! feature is enabled, so installers can check a device without the Viam app. The page is
+//! embedded in the firmware, it lists the resources of the robot with their status and readings
+//! and offers simple controls for boards and motors, backed by a minimal JSON API:
+//!
+//! - `GET /` the page itself
+//! - `GET /resources` the name and type of every resource of the robot
+//! - `POST /board/<name>/gpio/<pin>?high=<true|false>` sets the level of a pin
+//! - `POST /motor/<name>/power?value=<-1.0..1.0>` sets the power of a motor
+//! - `POST /motor/<name>/stop` stops a motor
+//!
+//! Like the rest of the endpoint the routes are [authorized](super::authorization) when API keys
+//! are configured: `/resources` needs viewer credentials, the page asks for a key and sends it as
+//! a bearer token. The controls are off unless the `controls` attribute of the endpoint is set,
+//! they then need operator credentials and are refused on robots without API keys. To keep
+//! other sites open in the browser from driving the robot, control requests must carry an
+//! `X-Requested-With` header, which cross-origin pages can't add without a preflight the
+//! endpoint never grants, and an `Origin` header, when sent, must match the `Host` of the request.
+
+use std::borrow::Cow;
+use std::sync::RwLock;
+
+use serde_json::{json, Value as JsonValue};
+use thiserror::Error;
+
+use super::actuator::{Actuator, ActuatorError};
+use super::authorization::{check_scope, AuthError, Scope};
+use super::board::{Board, BoardError};
+use super::json_endpoint::{auth_error_response, HttpRequest};
+use super::motor::{Motor, MotorError};
+use super::robot::LocalRobot;
+
+static INDEX_HTML: &str = include_str!("debug_ui.html");
+
+#[derive(Debug, Error)]
+pub enum DebugUiError {
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("invalid parameter {0}")]
+    InvalidParameter(&'static str),
+    #[error(transparent)]
+    BoardError(#[from] BoardError),
+    #[error(transparent)]
+    MotorError(#[from] MotorError),
+    #[error(transparent)]
+    ActuatorError(#[from] ActuatorError),
+    #[error(transparent)]
+    AuthError(#[from] AuthError),
+    #[error("controls are disabled")]
+    ControlsDisabled,
+    #[error("cross-origin requests are not allowed")]
+    CrossOrigin,
+    #[error("missing X-Requested-With header")]
+    MissingRequestedWith,
+}
+
+/// Status line, content type and body answering a request
+pub(crate) type DebugUiResponse = (&'static str, &'static str, Cow<'static, str>);
+
+fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find_map(|(k, v)| (k == key).then_some(v))
+}
+
+fn parse_param<T: std::str::FromStr>(query: &str, key: &'static str) -> Result<T, DebugUiError> {
+    query_param(query, key)
+        .and_then(|value| value.parse().ok())
+        .ok_or(DebugUiError::InvalidParameter(key))
+}
+
+/// Name and type of every resource of the robot
+pub fn resources_json(robot: &LocalRobot) -> JsonValue {
+    let names = robot.get_resource_names().unwrap_or_default();
+    JsonValue::Array(
+        names
+            .into_iter()
+            .map(|name| json!({ "name": name.name, "type": name.subtype }))
+            .collect(),
+    )
+}
+
+/// Checks that a control request may change the state of the robot
+fn check_control(
+    request: &HttpRequest,
+    scope: Option<Scope>,
+    controls: bool,
+) -> Result<(), DebugUiError> {
+    if !controls {
+        return Err(DebugUiError::ControlsDisabled);
+    }
+    check_scope(scope, Scope::Operator)?;
+    if let Some(origin) = request.header("origin") {
+        let host = request.header("host").unwrap_or_default();
+        let same_origin = origin
+            .strip_prefix("http://")
+            .is_some_and(|origin| !host.is_empty() && origin.eq_ignore_ascii_case(host));
+        if !same_origin {
+            return Err(DebugUiError::CrossOrigin);
+        }
+    }
+    if request.header("x-requested-with").is_none() {
+        return Err(DebugUiError::MissingRequestedWith);
+    }
+    Ok(())
+}
+
+fn set_gpio(
+    robot: &LocalRobot,
+    name: &str,
+    pin: &str,
+    query: &str,
+) -> Result<JsonValue, DebugUiError> {
+    let board = robot
+        .get_board_by_name(name.to_owned())
+        .ok_or_else(|| DebugUiError::NotFound(format!("board {}", name)))?;
+    let pin = pin
+        .parse()
+        .map_err(|_| DebugUiError::InvalidParameter("pin"))?;
+    let high = parse_param(query, "high")?;
+    board.lock().unwrap().set_gpio_pin_level(pin, high)?;
+    Ok(json!({ "pin": pin, "high": high }))
+}
+
+fn set_power(robot: &LocalRobot, name: &str, query: &str) -> Result<JsonValue, DebugUiError> {
+    let motor = robot
+        .get_motor_by_name(name.to_owned())
+        .ok_or_else(|| DebugUiError::NotFound(format!("motor {}", name)))?;
+    let power: f64 = parse_param(query, "value")?;
+    if !(-1.0..=1.0).contains(&power) {
+        return Err(DebugUiError::InvalidParameter("value"));
+    }
+    motor.lock().unwrap().set_power(power)?;
+    Ok(json!({ "power": power }))
+}
+
+fn stop_motor(robot: &LocalRobot, name: &str) -> Result<JsonValue, DebugUiError> {
+    let motor = robot
+        .get_motor_by_name(name.to_owned())
+        .ok_or_else(|| DebugUiError::NotFound(format!("motor {}", name)))?;
+    motor.lock().unwrap().stop()?;
+    Ok(json!({ "power": 0.0 }))
+}
+
+/// Answers the requests of the debug UI from a client authenticated with `scope`, `None` when
+/// the request is for another part of the endpoint. The control routes are refused unless
+/// `controls` is set
+pub(crate) fn respond(
+    robot: &RwLock<LocalRobot>,
+    request: &HttpRequest,
+    scope: Option<Scope>,
+    controls: bool,
+) -> Option<DebugUiResponse> {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let query = request.query;
+    let result = match (request.method, segments.as_slice()) {
+        ("GET", [""]) => return Some(("200 OK", "text/html", Cow::Borrowed(INDEX_HTML))),
+        ("GET", ["resources"]) => check_scope(scope, Scope::Viewer)
+            .map_err(DebugUiError::from)
+            .map(|_| resources_json(&robot.read().unwrap())),
+        ("POST", ["board", name, "gpio", pin]) => check_control(request, scope, controls)
+            .and_then(|_| set_gpio(&robot.read().unwrap(), name, pin, query)),
+        ("POST", ["motor", name, "power"]) => check_control(request, scope, controls)
+            .and_then(|_| set_power(&robot.read().unwrap(), name, query)),
+        ("POST", ["motor", name, "stop"]) => check_control(request, scope, controls)
+            .and_then(|_| stop_motor(&robot.read().unwrap(), name)),
+        _ => return None,
+    };
+    let (status, body) = match result {
+        Ok(body) => ("200 OK", body),
+        Err(DebugUiError::AuthError(err)) => auth_error_response(&err),
+        Err(
+            err @ (DebugUiError::ControlsDisabled
+            | DebugUiError::CrossOrigin
+            | DebugUiError::MissingRequestedWith),
+        ) => ("403 Forbidden", json!({ "error": err.to_string() })),
+        Err(err @ DebugUiError::NotFound(_)) => {
+            ("404 Not Found", json!({ "error": err.to_string() }))
+        }
+        Err(err @ DebugUiError::InvalidParameter(_)) => {
+            ("400 Bad Request", json!({ "error": err.to_string() }))
+        }
+        Err(err) => (
+            "500 Internal Server Error",
+            json!({ "error": err.to_string() }),
+        ),
+    };
+    Some((status, "application/json", Cow::Owned(body.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+
+    use super::DebugUiResponse;
+    use crate::common::authorization::Scope;
+    use crate::common::board::Board;
+    use crate::common::json_endpoint::HttpRequest;
+    use crate::common::motor::Motor;
+    use crate::common::robot::LocalRobot;
+    use crate::proto::app::v1::{ComponentConfig, ConfigResponse, RobotConfig};
+
+    fn robot() -> RwLock<LocalRobot> {
+        let component = |name: &str, r#type: &str| ComponentConfig {
+            name: name.to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: r#type.to_string(),
+            namespace: "rdk".to_string(),
+            ..Default::default()
+        };
+        let cfg = ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![component("board", "board"), component("motor", "motor")],
+                ..Default::default()
+            }),
+        };
+        RwLock::new(LocalRobot::from_cloud_config(&cfg, Box::default(), None).unwrap())
+    }
+
+    fn respond(
+        robot: &RwLock<LocalRobot>,
+        scope: Option<Scope>,
+        request: &str,
+    ) -> Option<DebugUiResponse> {
+        super::respond(robot, &HttpRequest::parse(request), scope, true)
+    }
+
+    /// Status of a control request sent by the page
+    fn post(robot: &RwLock<LocalRobot>, scope: Option<Scope>, target: &str) -> &'static str {
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: robot.local:8080\r\nOrigin: http://robot.local:8080\r\nX-Requested-With: fetch\r\n\r\n",
+            target
+        );
+        respond(robot, scope, &request).unwrap().0
+    }
+
+    #[test_log::test]
+    fn test_debug_ui() {
+        let robot = robot();
+        let operator = Some(Scope::Operator);
+
+        let (status, content_type, _) = respond(&robot, None, "GET / HTTP/1.1").unwrap();
+        assert_eq!((status, content_type), ("200 OK", "text/html"));
+        let (_, _, body) = respond(&robot, operator, "GET /resources HTTP/1.1").unwrap();
+        assert!(body.contains(r#""name":"motor""#));
+        assert!(respond(&robot, operator, "GET /readings HTTP/1.1").is_none());
+
+        assert_eq!(
+            post(&robot, operator, "/board/board/gpio/12?high=false"),
+            "200 OK"
+        );
+        let board = robot
+            .read()
+            .unwrap()
+            .get_board_by_name("board".to_owned())
+            .unwrap();
+        assert!(!board.lock().unwrap().get_gpio_level(12).unwrap());
+
+        assert_eq!(
+            post(&robot, operator, "/motor/motor/power?value=0.5"),
+            "200 OK"
+        );
+        let motor = robot
+            .read()
+            .unwrap()
+            .get_motor_by_name("motor".to_owned())
+            .unwrap();
+        assert_eq!(motor.lock().unwrap().is_powered().unwrap(), (true, 0.5));
+        assert_eq!(post(&robot, operator, "/motor/motor/stop"), "200 OK");
+        assert!(!motor.lock().unwrap().is_powered().unwrap().0);
+
+        assert_eq!(
+            post(&robot, operator, "/motor/motor/power?value=2"),
+            "400 Bad Request"
+        );
+        assert_eq!(post(&robot, operator, "/motor/nope/stop"), "404 Not Found");
+    }
+
+    #[test_log::test]
+    fn test_debug_ui_scopes() {
+        let robot = robot();
+
+        let (status, _, _) = respond(&robot, None, "GET /resources HTTP/1.1").unwrap();
+        assert_eq!(status, "401 Unauthorized");
+        let (status, _, _) =
+            respond(&robot, Some(Scope::Viewer), "GET /resources HTTP/1.1").unwrap();
+        assert_eq!(status, "200 OK");
+
+        assert_eq!(
+            post(&robot, None, "/motor/motor/power?value=0.5"),
+            "401 Unauthorized"
+        );
+        assert_eq!(
+            post(&robot, Some(Scope::Viewer), "/motor/motor/power?value=0.5"),
+            "403 Forbidden"
+        );
+        let motor = robot
+            .read()
+            .unwrap()
+            .get_motor_by_name("motor".to_owned())
+            .unwrap();
+        assert!(!motor.lock().unwrap().is_powered().unwrap().0);
+    }
+
+    #[test_log::test]
+    fn test_debug_ui_controls() {
+        let robot = robot();
+        let operator = Some(Scope::Operator);
+        let motor = robot
+            .read()
+            .unwrap()
+            .get_motor_by_name("motor".to_owned())
+            .unwrap();
+
+        // controls not enabled in the config
+        let request = HttpRequest::parse(
+            "POST /motor/motor/power?value=0.5 HTTP/1.1\r\nX-Requested-With: fetch\r\n\r\n",
+        );
+        let (status, _, _) = super::respond(&robot, &request, operator, false).unwrap();
+        assert_eq!(status, "403 Forbidden");
+
+        // a simple cross-origin form post can't add a custom header
+        let (status, _, _) = respond(
+            &robot,
+            operator,
+            "POST /motor/motor/power?value=0.5 HTTP/1.1\r\nHost: robot.local\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(status, "403 Forbidden");
+
+        // requests from pages of other sites
+        for origin in ["http://evil.example", "null", "https://robot.local"] {
+            let request = format!(
+                "POST /motor/motor/power?value=0.5 HTTP/1.1\r\nHost: robot.local\r\nOrigin: {}\r\nX-Requested-With: fetch\r\n\r\n",
+                origin
+            );
+            let (status, _, _) = respond(&robot, operator, &request).unwrap();
+            assert_eq!(status, "403 Forbidden", "{}", origin);
+        }
+        assert!(!motor.lock().unwrap().is_powered().unwrap().0);
+
+        // clients other than browsers don't send an origin
+        let (status, _, _) = respond(
+            &robot,
+            operator,
+            "POST /motor/motor/power?value=0.5 HTTP/1.1\r\nX-Requested-With: curl\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(status, "200 OK");
+        assert!(motor.lock().unwrap().is_powered().unwrap().0);
+    }
+}
@@ -1,3 +1,15 @@
+//! Digital interrupts count the edges seen on a board pin. Clients either read the counts with
+//! `GetDigitalInterruptValue` or subscribe to the `StreamTicks` server stream of the board
+//! service, which pushes a message for every edge through a [TickStream]. Boards recording the
+//! edges from their interrupt handler in an [EdgeRing] timestamp the ticks with the time of the
+//! edge.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use prost::Message;
+
+use super::board::{Board, BoardError};
 use super::config::{AttributeError, Kind};
 
 #[derive(Copy, Clone, Debug)]
@@ -15,3 +27,258 @@ impl TryFrom<&Kind> for DigitalInterruptConfig {
         Ok(DigitalInterruptConfig { pin })
     }
 }
+
+/// Ticks a [TickStream] holds at most while they wait to be sent, the oldest ones are dropped
+/// when a client doesn't keep up
+pub const TICK_QUEUE_LEN: usize = 64;
+
+/// Mirrors `viam.component.board.v1.StreamTicksRequest`
+#[derive(Clone, PartialEq, Message)]
+pub struct StreamTicksRequest {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, repeated, tag = "2")]
+    pub pin_names: Vec<String>,
+    #[prost(message, optional, tag = "99")]
+    pub extra: Option<crate::google::protobuf::Struct>,
+}
+
+/// Mirrors `viam.component.board.v1.StreamTicksResponse`
+#[derive(Clone, PartialEq, Message)]
+pub struct StreamTicksResponse {
+    #[prost(string, tag = "1")]
+    pub pin_name: String,
+    /// Nanoseconds since the unix epoch
+    #[prost(uint64, tag = "2")]
+    pub time: u64,
+    #[prost(bool, tag = "3")]
+    pub high: bool,
+}
+
+/// An edge seen on a digital interrupt
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tick {
+    pub pin: i32,
+    /// Whether the pin went high, interrupts count rising edges
+    pub high: bool,
+    /// Nanoseconds since the unix epoch
+    pub time_ns: u64,
+}
+
+impl From<Tick> for StreamTicksResponse {
+    fn from(tick: Tick) -> Self {
+        Self {
+            pin_name: tick.pin.to_string(),
+            time: tick.time_ns,
+            high: tick.high,
+        }
+    }
+}
+
+/// Edges an [EdgeRing] keeps, a power of two so the ring stays aligned when its count wraps
+pub const EDGE_RING_LEN: usize = 32;
+
+/// Count and timestamps of the last edges of a digital interrupt, written by the interrupt
+/// handler and read by the board
+pub struct EdgeRing {
+    count: AtomicU32,
+    // microseconds timestamps of the last edges, the edge `n` being at `n % EDGE_RING_LEN`
+    edges: [AtomicU32; EDGE_RING_LEN],
+}
+
+impl Default for EdgeRing {
+    fn default() -> Self {
+        Self {
+            count: 0.into(),
+            edges: std::array::from_fn(|_| 0.into()),
+        }
+    }
+}
+
+impl EdgeRing {
+    /// Records an edge seen at `when_us`, only called from the interrupt handler
+    #[inline(always)]
+    pub fn record(&self, when_us: u32) {
+        let count = self.count.load(Ordering::Acquire);
+        self.edges[count as usize % EDGE_RING_LEN].store(when_us, Ordering::Release);
+        self.count.store(count.wrapping_add(1), Ordering::Release);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count.load(Ordering::Acquire)
+    }
+
+    pub fn reset(&self) {
+        self.count.store(0, Ordering::Release);
+    }
+
+    /// Returns the timestamps of the edges seen after the first `since` edges that are still in
+    /// the ring, oldest first, and the count of edges
+    pub fn edges_since(&self, since: u32) -> (Vec<u32>, u32) {
+        let count = self.count();
+        let available = count.wrapping_sub(since).min(EDGE_RING_LEN as u32);
+        let first = count.wrapping_sub(available);
+        let mut edges: Vec<u32> = (0..available)
+            .map(|i| {
+                self.edges[first.wrapping_add(i) as usize % EDGE_RING_LEN].load(Ordering::Acquire)
+            })
+            .collect();
+        // edges overwritten by the handler while they were read
+        let overwritten = (self.count().wrapping_sub(first) as usize)
+            .saturating_sub(EDGE_RING_LEN)
+            .min(edges.len());
+        (edges.split_off(overwritten), count)
+    }
+}
+
+/// Turns the counts of digital interrupts into ticks by sampling them. The ticks of boards
+/// timestamping edges carry the time of the edge, otherwise every edge counted since the previous
+/// sample is timestamped with the time of the sample. Ticks are queued until they are taken, up
+/// to [TICK_QUEUE_LEN].
+#[derive(Debug)]
+pub struct TickStream {
+    board: String,
+    // pins and their count at the previous sample
+    pins: Vec<(i32, Option<u32>)>,
+    queue: VecDeque<Tick>,
+    dropped: u64,
+}
+
+impl TickStream {
+    pub fn new(board: &str, pins: &[i32]) -> Self {
+        Self {
+            board: board.to_string(),
+            pins: pins.iter().map(|pin| (*pin, None)).collect(),
+            queue: VecDeque::with_capacity(TICK_QUEUE_LEN),
+            dropped: 0,
+        }
+    }
+
+    /// Whether the stream follows the `pins` of `board`
+    pub fn follows(&self, board: &str, pins: &[i32]) -> bool {
+        self.board == board
+            && self.pins.len() == pins.len()
+            && self
+                .pins
+                .iter()
+                .zip(pins)
+                .all(|((pin, _), other)| pin == other)
+    }
+
+    /// Queues a tick for every edge counted since the previous sample, the first sample only
+    /// records the counts
+    pub fn sample<B>(&mut self, board: &B, time_ns: u64) -> Result<(), BoardError>
+    where
+        B: Board + ?Sized,
+    {
+        for (pin, last) in self.pins.iter_mut() {
+            let Some(since) = *last else {
+                *last = Some(board.get_digital_interrupt_value(*pin)?);
+                continue;
+            };
+            let (ticks, count) = match board.get_digital_interrupt_ticks(*pin, since) {
+                Ok(ticks) => ticks,
+                Err(BoardError::BoardMethodNotSupported(_)) => {
+                    // interrupts count rising edges
+                    let count = board.get_digital_interrupt_value(*pin)?;
+                    let edges = count.wrapping_sub(since).min(TICK_QUEUE_LEN as u32);
+                    let tick = Tick {
+                        pin: *pin,
+                        high: true,
+                        time_ns,
+                    };
+                    (vec![tick; edges as usize], count)
+                }
+                Err(err) => return Err(err),
+            };
+            *last = Some(count);
+            self.dropped += u64::from(count.wrapping_sub(since)).saturating_sub(ticks.len() as u64);
+            for tick in ticks {
+                if self.queue.len() == TICK_QUEUE_LEN {
+                    self.queue.pop_front();
+                    self.dropped += 1;
+                }
+                self.queue.push_back(tick);
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the oldest queued tick
+    pub fn next_tick(&mut self) -> Option<Tick> {
+        self.queue.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Ticks dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EdgeRing, Tick, TickStream, EDGE_RING_LEN, TICK_QUEUE_LEN};
+    use crate::common::board::{BoardError, FakeBoard};
+
+    #[test_log::test]
+    fn test_tick_stream() -> Result<(), BoardError> {
+        let mut board = FakeBoard::new(vec![]);
+        board.add_digital_interrupt(4);
+        board.trigger_digital_interrupt(4)?;
+        let mut stream = TickStream::new("board", &[4]);
+        assert!(stream.follows("board", &[4]));
+        assert!(!stream.follows("board", &[4, 5]));
+
+        // edges counted before the first sample aren't ticks
+        stream.sample(&board, 10)?;
+        assert!(stream.is_empty());
+        board.trigger_digital_interrupt(4)?;
+        board.trigger_digital_interrupt(4)?;
+        stream.sample(&board, 20)?;
+        let tick = Tick {
+            pin: 4,
+            high: true,
+            time_ns: 20,
+        };
+        assert_eq!(stream.next_tick(), Some(tick.clone()));
+        assert_eq!(stream.next_tick(), Some(tick));
+        assert_eq!(stream.next_tick(), None);
+
+        for _ in 0..TICK_QUEUE_LEN + 6 {
+            board.trigger_digital_interrupt(4)?;
+        }
+        stream.sample(&board, 30)?;
+        assert_eq!(stream.dropped(), 6);
+        assert_eq!(
+            std::iter::from_fn(|| stream.next_tick()).count(),
+            TICK_QUEUE_LEN
+        );
+
+        let mut stream = TickStream::new("board", &[5]);
+        assert!(stream.sample(&board, 40).is_err());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_edge_ring() {
+        let ring = EdgeRing::default();
+        ring.record(10);
+        ring.record(20);
+        assert_eq!(ring.edges_since(0), (vec![10, 20], 2));
+        assert_eq!(ring.edges_since(1), (vec![20], 2));
+        assert_eq!(ring.edges_since(2), (vec![], 2));
+
+        // only the last edges are kept
+        for when in 0..EDGE_RING_LEN as u32 + 2 {
+            ring.record(100 + when);
+        }
+        let (edges, count) = ring.edges_since(2);
+        assert_eq!(count, EDGE_RING_LEN as u32 + 4);
+        assert_eq!(edges.len(), EDGE_RING_LEN);
+        assert_eq!(edges[0], 102);
+    }
+}
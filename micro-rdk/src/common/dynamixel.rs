@@ -0,0 +1,577 @@
+//! Package dynamixel implements Robotis Dynamixel smart servos (X series) speaking protocol 2.0
+//! over a half-duplex serial bus. Each servo on the bus is configured as its own component: a
+//! servo in position mode, or a motor in wheel (velocity) mode. The components configured on
+//! the same port share a [DynamixelBus], see `esp32::dynamixel` for the port configuration.
+//!
+//! ```json
+//! {
+//!     "id": 3,
+//!     "min_angle_deg": 0,
+//!     "max_angle_deg": 270
+//! }
+//! ```
+//!
+//! `id` (required) is the ID of the servo on the bus. As a servo, `min_angle_deg` and
+//! `max_angle_deg` (0 and 360 by default) limit the positions it is moved to. As a motor,
+//! `max_rpm` (required) is the velocity `set_power(1.0)` runs the servo at and the position
+//! is reported in ticks, 4096 per rotation.
+//!
+//! The status of both reports the `position_deg`, `velocity_rpm`, `load_pct`,
+//! `temperature_c` and `input_voltage` read from the servo.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::actuator::{Actuator, ActuatorError};
+use super::config::{AttributeError, ConfigType};
+use super::math_utils::go_for_math;
+use super::motor::{Motor, MotorError, MotorSupportedProperties, MotorType};
+use super::servo::{Servo, ServoError, ServoType};
+use super::status::{status_envelope, ComponentHealth, Status, StatusError};
+use super::uart::{UartError, UartType};
+use crate::google::protobuf::{value::Kind, Struct};
+
+const HEADER: [u8; 4] = [0xFF, 0xFF, 0xFD, 0x00];
+// header, id and length
+const PREFIX_LEN: usize = 7;
+const INST_READ: u8 = 0x02;
+const INST_WRITE: u8 = 0x03;
+const INST_STATUS: u8 = 0x55;
+// the alert bit of the error byte only reports a hardware error, the others a failed instruction
+const ERROR_ALERT: u8 = 0x80;
+const REPLY_TIMEOUT: Duration = Duration::from_millis(50);
+// bytes skipped looking for a status packet, such as the echo of the instruction
+const MAX_SKIPPED: usize = 64;
+
+// control table of the X series
+const ADDR_OPERATING_MODE: u16 = 11;
+const ADDR_TORQUE_ENABLE: u16 = 64;
+const ADDR_GOAL_VELOCITY: u16 = 104;
+const ADDR_GOAL_POSITION: u16 = 116;
+const ADDR_MOVING: u16 = 122;
+const ADDR_PRESENT_POSITION: u16 = 132;
+// Moving (122) up to Present Temperature (146)
+const FEEDBACK_LEN: u16 = 25;
+
+const MODE_VELOCITY: u8 = 1;
+const MODE_POSITION: u8 = 3;
+
+pub const TICKS_PER_ROTATION: u32 = 4096;
+const RPM_PER_VELOCITY_UNIT: f64 = 0.229;
+
+/// CRC16 (polynomial 0x8005) closing every packet
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0_u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+// a 0xFD is inserted after every 0xFF 0xFF 0xFD in the instruction and parameters so they
+// can't be mistaken for a header
+fn stuff(bytes: &[u8]) -> Vec<u8> {
+    let mut stuffed = Vec::with_capacity(bytes.len());
+    for byte in bytes {
+        stuffed.push(*byte);
+        if stuffed.ends_with(&HEADER[..3]) {
+            stuffed.push(0xFD);
+        }
+    }
+    stuffed
+}
+
+fn unstuff(bytes: &[u8]) -> Vec<u8> {
+    let mut unstuffed: Vec<u8> = Vec::with_capacity(bytes.len());
+    for byte in bytes {
+        if *byte == 0xFD && unstuffed.ends_with(&HEADER[..3]) {
+            continue;
+        }
+        unstuffed.push(*byte);
+    }
+    unstuffed
+}
+
+/// Builds an instruction packet for the servo `id`
+pub fn instruction_packet(id: u8, instruction: u8, params: &[u8]) -> Vec<u8> {
+    let mut body = vec![instruction];
+    body.extend_from_slice(params);
+    let body = stuff(&body);
+    let len = (body.len() + 2) as u16;
+    let mut packet = HEADER.to_vec();
+    packet.push(id);
+    packet.extend(len.to_le_bytes());
+    packet.extend(body);
+    packet.extend(crc16(&packet).to_le_bytes());
+    packet
+}
+
+/// A serial bus shared by the Dynamixel servos configured on it, each exchange holds the port
+/// until the status of the servo is received
+#[derive(Clone)]
+pub struct DynamixelBus {
+    uart: UartType,
+}
+
+impl DynamixelBus {
+    pub fn new(uart: UartType) -> Self {
+        Self { uart }
+    }
+
+    // sends an instruction and returns the parameters of the status packet the servo replies
+    fn transact(&self, id: u8, instruction: u8, params: &[u8]) -> Result<Vec<u8>, UartError> {
+        let mut uart = self.uart.lock().unwrap();
+        uart.clear_input()?;
+        uart.write(&instruction_packet(id, instruction, params))?;
+        // skip to the status of the servo, the instruction is echoed on some adapters
+        let mut skipped = 0;
+        let body = loop {
+            let mut prefix = [0_u8; PREFIX_LEN];
+            uart.read_exact(&mut prefix, REPLY_TIMEOUT)?;
+            while prefix[..4] != HEADER || prefix[4] != id {
+                if skipped >= MAX_SKIPPED {
+                    return Err(UartError::InvalidReply(
+                        "no dynamixel status packet received",
+                    ));
+                }
+                prefix.copy_within(1.., 0);
+                uart.read_exact(&mut prefix[PREFIX_LEN - 1..], REPLY_TIMEOUT)?;
+                skipped += 1;
+            }
+            let len = u16::from_le_bytes([prefix[5], prefix[6]]) as usize;
+            if len < 4 {
+                return Err(UartError::InvalidReply("dynamixel status packet too short"));
+            }
+            let mut rest = vec![0_u8; len];
+            uart.read_exact(&mut rest, REPLY_TIMEOUT)?;
+            let crc = u16::from_le_bytes([rest[len - 2], rest[len - 1]]);
+            let mut checked = prefix.to_vec();
+            checked.extend_from_slice(&rest[..len - 2]);
+            if crc16(&checked) != crc {
+                return Err(UartError::InvalidReply(
+                    "dynamixel status has an invalid CRC",
+                ));
+            }
+            let body = unstuff(&rest[..len - 2]);
+            if body[0] == INST_STATUS {
+                break body;
+            }
+        };
+        if body[1] & !ERROR_ALERT != 0 {
+            return Err(UartError::InvalidReply(
+                "dynamixel failed to run the instruction",
+            ));
+        }
+        if body[1] & ERROR_ALERT != 0 {
+            log::warn!("dynamixel {}: hardware error alert", id);
+        }
+        Ok(body[2..].to_vec())
+    }
+
+    pub fn read(&self, id: u8, address: u16, len: u16) -> Result<Vec<u8>, UartError> {
+        let mut params = address.to_le_bytes().to_vec();
+        params.extend(len.to_le_bytes());
+        let data = self.transact(id, INST_READ, &params)?;
+        if data.len() != len as usize {
+            return Err(UartError::InvalidReply("dynamixel returned a partial read"));
+        }
+        Ok(data)
+    }
+
+    pub fn write(&self, id: u8, address: u16, data: &[u8]) -> Result<(), UartError> {
+        let mut params = address.to_le_bytes().to_vec();
+        params.extend_from_slice(data);
+        self.transact(id, INST_WRITE, &params).map(|_| ())
+    }
+
+    // the operating mode can only be changed while the torque is disabled
+    fn set_operating_mode(&self, id: u8, mode: u8) -> Result<(), UartError> {
+        self.write(id, ADDR_TORQUE_ENABLE, &[0])?;
+        self.write(id, ADDR_OPERATING_MODE, &[mode])?;
+        self.write(id, ADDR_TORQUE_ENABLE, &[1])
+    }
+
+    fn present_position(&self, id: u8) -> Result<i32, UartError> {
+        let data = self.read(id, ADDR_PRESENT_POSITION, 4)?;
+        Ok(i32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+    }
+
+    fn is_moving(&self, id: u8) -> Result<bool, UartError> {
+        Ok(self.read(id, ADDR_MOVING, 1)?[0] != 0)
+    }
+
+    fn feedback(&self, id: u8) -> Result<Feedback, UartError> {
+        let data = self.read(id, ADDR_MOVING, FEEDBACK_LEN)?;
+        let at = |address: u16| (address - ADDR_MOVING) as usize;
+        let i32_at = |address: u16| {
+            let i = at(address);
+            i32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]])
+        };
+        let i16_at = |address: u16| i16::from_le_bytes([data[at(address)], data[at(address) + 1]]);
+        Ok(Feedback {
+            // Present Load, in 0.1%
+            load_pct: i16_at(126) as f64 / 10.0,
+            velocity_rpm: i32_at(128) as f64 * RPM_PER_VELOCITY_UNIT,
+            position: i32_at(ADDR_PRESENT_POSITION),
+            // Present Input Voltage, in 0.1V
+            input_voltage: i16_at(144) as f64 / 10.0,
+            temperature_c: data[at(146)] as f64,
+        })
+    }
+}
+
+struct Feedback {
+    load_pct: f64,
+    velocity_rpm: f64,
+    position: i32,
+    input_voltage: f64,
+    temperature_c: f64,
+}
+
+fn position_to_deg(position: i32) -> f64 {
+    position as f64 * 360.0 / TICKS_PER_ROTATION as f64
+}
+
+fn feedback_status(bus: &DynamixelBus, id: u8, health: &ComponentHealth) -> Struct {
+    let mut fields = vec![];
+    match bus.feedback(id) {
+        Ok(feedback) => fields.extend([
+            (
+                "position_deg",
+                Kind::NumberValue(position_to_deg(feedback.position)),
+            ),
+            ("velocity_rpm", Kind::NumberValue(feedback.velocity_rpm)),
+            ("load_pct", Kind::NumberValue(feedback.load_pct)),
+            ("temperature_c", Kind::NumberValue(feedback.temperature_c)),
+            ("input_voltage", Kind::NumberValue(feedback.input_voltage)),
+        ]),
+        Err(err) => log::debug!("dynamixel {}: failed to read feedback: {}", id, err),
+    }
+    status_envelope(health, fields)
+}
+
+fn id_from_config(cfg: &ConfigType) -> Result<u8, AttributeError> {
+    match cfg.get_attribute::<u8>("id")? {
+        // 253 and above are reserved, 254 being the broadcast ID
+        id if id < 253 => Ok(id),
+        _ => Err(AttributeError::ConversionImpossibleError),
+    }
+}
+
+#[derive(DoCommand)]
+pub struct DynamixelServo {
+    bus: DynamixelBus,
+    id: u8,
+    min_angle_deg: u32,
+    max_angle_deg: u32,
+    health: ComponentHealth,
+}
+
+impl DynamixelServo {
+    pub fn new(
+        bus: DynamixelBus,
+        id: u8,
+        min_angle_deg: u32,
+        max_angle_deg: u32,
+    ) -> Result<Self, ServoError> {
+        if min_angle_deg >= max_angle_deg || max_angle_deg > 360 {
+            return Err(ServoError::ServoConfigurationError(
+                "dynamixel angles should be between 0 and 360 with min_angle_deg < max_angle_deg",
+            ));
+        }
+        bus.set_operating_mode(id, MODE_POSITION)?;
+        Ok(Self {
+            bus,
+            id,
+            min_angle_deg,
+            max_angle_deg,
+            health: ComponentHealth::new(),
+        })
+    }
+
+    /// Builds the servo from the attributes of the `dynamixel` servo model, once the platform
+    /// has built the bus it is connected to
+    pub fn from_bus_and_config(
+        bus: DynamixelBus,
+        cfg: ConfigType,
+    ) -> Result<ServoType, ServoError> {
+        let id = id_from_config(&cfg)?;
+        let min_angle_deg = cfg.get_attribute::<u32>("min_angle_deg").unwrap_or(0);
+        let max_angle_deg = cfg.get_attribute::<u32>("max_angle_deg").unwrap_or(360);
+        Ok(Arc::new(Mutex::new(Self::new(
+            bus,
+            id,
+            min_angle_deg,
+            max_angle_deg,
+        )?)))
+    }
+
+    fn goal_position(&mut self, angle_deg: u32) -> Result<(), ServoError> {
+        let angle_deg = angle_deg.clamp(self.min_angle_deg, self.max_angle_deg);
+        // 360° wraps around to the last tick of the rotation
+        let position = (angle_deg * TICKS_PER_ROTATION / 360).min(TICKS_PER_ROTATION - 1);
+        self.bus.write(
+            self.id,
+            ADDR_GOAL_POSITION,
+            &(position as i32).to_le_bytes(),
+        )?;
+        Ok(())
+    }
+}
+
+impl Servo for DynamixelServo {
+    fn move_to(&mut self, angle_deg: u32) -> Result<(), ServoError> {
+        let res = self.goal_position(angle_deg);
+        self.health.record(&res);
+        res
+    }
+
+    fn get_position(&mut self) -> Result<u32, ServoError> {
+        let res = self.bus.present_position(self.id);
+        self.health.record(&res);
+        Ok(position_to_deg(res?).round() as u32)
+    }
+}
+
+impl Actuator for DynamixelServo {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        self.bus
+            .is_moving(self.id)
+            .map_err(|_| ActuatorError::CouldntStop)
+    }
+
+    // holds the position the servo is at
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        let res = self.bus.present_position(self.id).and_then(|position| {
+            self.bus
+                .write(self.id, ADDR_GOAL_POSITION, &position.to_le_bytes())
+        });
+        self.health.record(&res);
+        res.map_err(|_| ActuatorError::CouldntStop)
+    }
+}
+
+impl Status for DynamixelServo {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(feedback_status(&self.bus, self.id, &self.health)))
+    }
+}
+
+/// A Dynamixel servo in wheel mode, turning continuously at the velocity set
+#[derive(DoCommand)]
+pub struct DynamixelWheel {
+    bus: DynamixelBus,
+    id: u8,
+    max_rpm: f64,
+    power: f64,
+    health: ComponentHealth,
+}
+
+impl DynamixelWheel {
+    pub fn new(bus: DynamixelBus, id: u8, max_rpm: f64) -> Result<Self, MotorError> {
+        bus.set_operating_mode(id, MODE_VELOCITY)?;
+        Ok(Self {
+            bus,
+            id,
+            max_rpm,
+            power: 0.0,
+            health: ComponentHealth::new(),
+        })
+    }
+
+    /// Builds the motor from the attributes of the `dynamixel` motor model, once the platform
+    /// has built the bus it is connected to
+    pub fn from_bus_and_config(
+        bus: DynamixelBus,
+        cfg: ConfigType,
+    ) -> Result<MotorType, MotorError> {
+        let id = id_from_config(&cfg)
+            .map_err(|_| MotorError::ConfigError("dynamixel: missing or invalid `id`"))?;
+        let max_rpm = cfg
+            .get_attribute::<f64>("max_rpm")
+            .map_err(|_| MotorError::ConfigError("dynamixel: missing `max_rpm`"))?;
+        Ok(Arc::new(Mutex::new(Self::new(bus, id, max_rpm)?)))
+    }
+
+    fn apply_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&pct) {
+            return Err(MotorError::PowerSetError);
+        }
+        let velocity = (pct * self.max_rpm / RPM_PER_VELOCITY_UNIT).round() as i32;
+        self.bus
+            .write(self.id, ADDR_GOAL_VELOCITY, &velocity.to_le_bytes())?;
+        self.power = pct;
+        Ok(())
+    }
+}
+
+impl Motor for DynamixelWheel {
+    fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        let res = self.apply_power(pct);
+        self.health.record(&res);
+        res
+    }
+
+    fn get_position(&mut self) -> Result<i32, MotorError> {
+        let res = self.bus.present_position(self.id);
+        self.health.record(&res);
+        Ok(res?)
+    }
+
+    fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        let (pwr, dur) = go_for_math(self.max_rpm, rpm, revolutions)?;
+        self.set_power(pwr)?;
+        Ok(dur)
+    }
+
+    fn get_properties(&mut self) -> MotorSupportedProperties {
+        MotorSupportedProperties {
+            position_reporting: true,
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: Some(TICKS_PER_ROTATION),
+        }
+    }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
+}
+
+impl Actuator for DynamixelWheel {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(self.power != 0.0)
+    }
+
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        let res = self.apply_power(0.0);
+        self.health.record(&res);
+        res.map_err(|_| ActuatorError::CouldntStop)
+    }
+}
+
+impl Status for DynamixelWheel {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(feedback_status(&self.bus, self.id, &self.health)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        crc16, instruction_packet, stuff, unstuff, DynamixelBus, DynamixelServo, DynamixelWheel,
+        INST_STATUS,
+    };
+    use crate::common::motor::{Motor, MotorError};
+    use crate::common::servo::{Servo, ServoError};
+    use crate::common::status::Status;
+    use crate::common::uart::FakeUart;
+    use crate::google::protobuf::value::Kind;
+    use std::sync::{Arc, Mutex};
+
+    fn status_packet(id: u8, params: &[u8]) -> Vec<u8> {
+        let mut body = vec![INST_STATUS, 0];
+        body.extend_from_slice(params);
+        let mut packet = instruction_packet(id, body[0], &body[1..]);
+        // the echo of an instruction is skipped
+        let mut echoed = instruction_packet(id, 0x02, &[0x84, 0x00, 0x04, 0x00]);
+        echoed.append(&mut packet);
+        echoed
+    }
+
+    #[test_log::test]
+    fn test_packets() {
+        // ping of ID 1, from the protocol documentation
+        assert_eq!(
+            instruction_packet(1, 0x01, &[]),
+            vec![0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x03, 0x00, 0x01, 0x19, 0x4E]
+        );
+        // read of the present position of ID 1
+        assert_eq!(
+            instruction_packet(1, 0x02, &[0x84, 0x00, 0x04, 0x00]),
+            vec![
+                0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x07, 0x00, 0x02, 0x84, 0x00, 0x04, 0x00, 0x1D, 0x15
+            ]
+        );
+        assert_eq!(crc16(&[]), 0);
+        let stuffed = stuff(&[0x03, 0xFF, 0xFF, 0xFD, 0x01]);
+        assert_eq!(stuffed, vec![0x03, 0xFF, 0xFF, 0xFD, 0xFD, 0x01]);
+        assert_eq!(unstuff(&stuffed), vec![0x03, 0xFF, 0xFF, 0xFD, 0x01]);
+    }
+
+    #[test_log::test]
+    fn test_dynamixel_servo() -> Result<(), ServoError> {
+        let uart = Arc::new(Mutex::new(FakeUart::default()));
+        let bus = DynamixelBus::new(uart.clone());
+        for _ in 0..3 {
+            uart.lock().unwrap().replies.extend(status_packet(3, &[]));
+        }
+        let mut servo = DynamixelServo::new(bus, 3, 0, 180)?;
+        // torque off, position mode, torque on
+        let written = std::mem::take(&mut uart.lock().unwrap().written);
+        assert!(written.ends_with(&instruction_packet(3, 0x03, &[64, 0, 1])));
+
+        uart.lock().unwrap().replies.extend(status_packet(3, &[]));
+        // moves are limited to max_angle_deg
+        servo.move_to(270)?;
+        let written = std::mem::take(&mut uart.lock().unwrap().written);
+        assert_eq!(
+            written,
+            instruction_packet(3, 0x03, &[116, 0, 0x00, 0x08, 0, 0])
+        );
+
+        uart.lock()
+            .unwrap()
+            .replies
+            .extend(status_packet(3, &1024_i32.to_le_bytes()));
+        assert_eq!(servo.get_position()?, 90);
+
+        // a reply from another servo isn't taken for the status
+        uart.lock()
+            .unwrap()
+            .replies
+            .extend(status_packet(4, &1024_i32.to_le_bytes()));
+        assert!(servo.get_position().is_err());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_dynamixel_wheel() -> Result<(), MotorError> {
+        let uart = Arc::new(Mutex::new(FakeUart::default()));
+        let bus = DynamixelBus::new(uart.clone());
+        for _ in 0..3 {
+            uart.lock().unwrap().replies.extend(status_packet(5, &[]));
+        }
+        let mut wheel = DynamixelWheel::new(bus, 5, 22.9)?;
+        uart.lock().unwrap().written.clear();
+
+        uart.lock().unwrap().replies.extend(status_packet(5, &[]));
+        wheel.set_power(-0.5)?;
+        let written = std::mem::take(&mut uart.lock().unwrap().written);
+        let mut params = vec![104, 0];
+        params.extend((-50_i32).to_le_bytes());
+        assert_eq!(written, instruction_packet(5, 0x03, &params));
+
+        let mut feedback = vec![0_u8; 25];
+        // 25% load, 10 units of velocity, position 2048, 12.0V, 40°C
+        feedback[4..6].copy_from_slice(&250_i16.to_le_bytes());
+        feedback[6..10].copy_from_slice(&10_i32.to_le_bytes());
+        feedback[10..14].copy_from_slice(&2048_i32.to_le_bytes());
+        feedback[22..24].copy_from_slice(&120_i16.to_le_bytes());
+        feedback[24] = 40;
+        uart.lock()
+            .unwrap()
+            .replies
+            .extend(status_packet(5, &feedback));
+        let status = wheel.get_status().unwrap().unwrap();
+        let field = |name: &str| status.fields.get(name).and_then(|v| v.kind.clone());
+        assert_eq!(field("position_deg"), Some(Kind::NumberValue(180.0)));
+        assert_eq!(field("load_pct"), Some(Kind::NumberValue(25.0)));
+        assert_eq!(field("temperature_c"), Some(Kind::NumberValue(40.0)));
+        assert_eq!(field("input_voltage"), Some(Kind::NumberValue(12.0)));
+        Ok(())
+    }
+}
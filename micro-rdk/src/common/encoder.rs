@@ -2,8 +2,6 @@
 use {
     super::config::ConfigType,
     super::registry::{ComponentRegistry, Dependency},
-    crate::google,
-    std::collections::HashMap,
 };
 
 use std::sync::Arc;
@@ -13,10 +11,13 @@ use crate::proto::component::encoder::v1::GetPositionResponse;
 use crate::proto::component::encoder::v1::GetPropertiesResponse;
 use crate::proto::component::encoder::v1::PositionType;
 
+use super::board::BoardError;
 use super::config::AttributeError;
 use super::generic::DoCommand;
+use super::i2c::I2CErrors;
 use super::status::Status;
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -31,6 +32,28 @@ pub enum EncoderError {
     EncoderConfigAttributeError(#[from] AttributeError),
     #[error("encoder error code: {0}")]
     EncoderCodeError(i32),
+    #[error("encoder config error: {0}")]
+    EncoderConfigError(&'static str),
+    #[error(transparent)]
+    EncoderI2CError(#[from] I2CErrors),
+    #[error(transparent)]
+    EncoderBoardError(#[from] BoardError),
+}
+
+impl GrpcStatusHint for EncoderError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::EncoderMethodUnimplemented | Self::EncoderAngularNotSupported => {
+                GrpcError::RpcUnimplemented
+            }
+            Self::EncoderUnspecified => GrpcError::RpcInvalidArgument,
+            Self::EncoderConfigAttributeError(err) => err.grpc_error(),
+            Self::EncoderConfigError(_) => GrpcError::RpcFailedPrecondition,
+            Self::EncoderI2CError(err) => err.grpc_error(),
+            Self::EncoderBoardError(err) => err.grpc_error(),
+            Self::EncoderCodeError(_) => GrpcError::RpcInternal,
+        }
+    }
 }
 
 pub static COMPONENT_NAME: &str = "encoder";
@@ -125,6 +148,10 @@ pub trait Encoder: Status + DoCommand {
     fn reset_position(&mut self) -> Result<(), EncoderError> {
         Err(EncoderError::EncoderMethodUnimplemented)
     }
+    /// Returns the number of ticks counted over a full rotation, if the encoder knows it
+    fn ticks_per_rotation(&self) -> Option<u32> {
+        None
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -149,7 +176,7 @@ pub trait SingleEncoder: Encoder {
 pub(crate) type EncoderType = Arc<Mutex<dyn Encoder>>;
 
 #[cfg(feature = "builtin-components")]
-#[derive(DoCommand)]
+#[derive(DoCommand, Status)]
 pub struct FakeIncrementalEncoder {
     pub ticks: f32,
 }
@@ -204,18 +231,7 @@ impl Encoder for FakeIncrementalEncoder {
 }
 
 #[cfg(feature = "builtin-components")]
-impl Status for FakeIncrementalEncoder {
-    fn get_status(
-        &self,
-    ) -> Result<Option<google::protobuf::Struct>, crate::common::status::StatusError> {
-        Ok(Some(google::protobuf::Struct {
-            fields: HashMap::new(),
-        }))
-    }
-}
-
-#[cfg(feature = "builtin-components")]
-#[derive(DoCommand)]
+#[derive(DoCommand, Status)]
 pub struct FakeEncoder {
     pub angle_degrees: f32,
     pub ticks_per_rotation: u32,
@@ -273,16 +289,8 @@ impl Encoder for FakeEncoder {
             }
         }
     }
-}
-
-#[cfg(feature = "builtin-components")]
-impl Status for FakeEncoder {
-    fn get_status(
-        &self,
-    ) -> Result<Option<google::protobuf::Struct>, crate::common::status::StatusError> {
-        Ok(Some(google::protobuf::Struct {
-            fields: HashMap::new(),
-        }))
+    fn ticks_per_rotation(&self) -> Option<u32> {
+        Some(self.ticks_per_rotation)
     }
 }
 
@@ -296,6 +304,9 @@ where
     fn reset_position(&mut self) -> Result<(), EncoderError> {
         self.get_mut().unwrap().reset_position()
     }
+    fn ticks_per_rotation(&self) -> Option<u32> {
+        self.lock().unwrap().ticks_per_rotation()
+    }
     fn get_position(
         &self,
         position_type: EncoderPositionType,
@@ -314,6 +325,9 @@ where
     fn reset_position(&mut self) -> Result<(), EncoderError> {
         self.lock().unwrap().reset_position()
     }
+    fn ticks_per_rotation(&self) -> Option<u32> {
+        self.lock().unwrap().ticks_per_rotation()
+    }
     fn get_position(
         &self,
         position_type: EncoderPositionType,
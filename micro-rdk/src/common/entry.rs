@@ -1,3 +1,4 @@
+use super::app_client::AppClientConfig;
 use super::registry::ComponentRegistry;
 use super::robot::LocalRobot;
 
@@ -5,3 +6,10 @@ pub enum RobotRepresentation {
     WithRobot(LocalRobot),
     WithRegistry(Box<ComponentRegistry>),
 }
+
+/// A second robot part served by the device next to the main one, with its own credentials
+/// and connection to app
+pub struct RobotPartRepresentation {
+    pub app_config: AppClientConfig,
+    pub repr: RobotRepresentation,
+}
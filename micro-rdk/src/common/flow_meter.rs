@@ -0,0 +1,266 @@
+//! Package flow_meter implements the sensor interface for hall effect flow meters (such as the
+//! YF-S201), which output a pulse for every fixed volume of liquid going through them. The pulses
+//! are counted by the platform, see `esp32::flow_meter`.
+//!
+//! ```json
+//! {
+//!     "k_factor": 450,
+//!     "persist_every_liters": 1.0
+//! }
+//! ```
+//!
+//! `k_factor` is the number of pulses per liter given by the datasheet of the meter (450 by
+//! default, the one of the YF-S201). The readings are the `flow_rate_lpm` in liters per minute,
+//! averaged since the previous readings, and the cumulative `total_liters`.
+//!
+//! The total is persisted with the [calibrations](crate::common::calibration) of the robot so it
+//! survives restarts. To spare the flash it is only written once it grew by
+//! `persist_every_liters` (1 liter by default) since it was last written, at most this volume is
+//! lost on a power loss. The total is reset with the `{"reset_total": {}}` command.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::calibration::Calibration;
+use super::config::{AttributeError, ConfigType};
+use super::generic::{DoCommand, GenericError};
+use super::sensor::{GenericReadingsResult, Readings, Sensor, SensorError, SensorType};
+use super::status::{Status, StatusError};
+use super::struct_builder::StructBuilder;
+use crate::google::protobuf::Struct;
+
+const DEFAULT_K_FACTOR: f64 = 450.0;
+const DEFAULT_PERSIST_EVERY_LITERS: f64 = 1.0;
+// readings closer than this report the previous flow rate, too few pulses were counted in
+// between to be accurate
+const MIN_RATE_WINDOW: Duration = Duration::from_millis(500);
+
+/// Source of the pulses of the meter, the count only ever grows
+pub trait PulseCounter: Send {
+    fn pulses(&mut self) -> Result<u64, SensorError>;
+}
+
+/// Cumulative volume of a meter, persisted as a calibration of the component
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlowTotal {
+    pub liters: f64,
+}
+
+impl Calibration for FlowTotal {
+    const KIND: &'static str = "flow";
+}
+
+pub struct FlowMeter {
+    counter: Box<dyn PulseCounter>,
+    k_factor: f64,
+    persist_every_liters: f64,
+    // name of the component the total is persisted for, `None` when it is not persisted
+    name: Option<String>,
+    // total at a pulse count of 0
+    base_liters: f64,
+    persisted_liters: f64,
+    last_sample: Option<(Instant, u64)>,
+    flow_rate_lpm: f64,
+}
+
+impl FlowMeter {
+    pub fn new(
+        counter: Box<dyn PulseCounter>,
+        k_factor: f64,
+        persist_every_liters: f64,
+    ) -> Result<Self, SensorError> {
+        if k_factor <= 0.0 {
+            return Err(SensorError::ConfigError(
+                "flow-meter k_factor must be positive",
+            ));
+        }
+        Ok(Self {
+            counter,
+            k_factor,
+            persist_every_liters,
+            name: None,
+            base_liters: 0.0,
+            persisted_liters: 0.0,
+            last_sample: None,
+            flow_rate_lpm: 0.0,
+        })
+    }
+
+    /// Persists the total for the component `name`, starting from the total persisted for it
+    pub fn with_persisted_total(mut self, name: &str) -> Self {
+        match FlowTotal::load(name) {
+            Ok(total) => {
+                self.base_liters = total.unwrap_or_default().liters;
+                self.persisted_liters = self.base_liters;
+            }
+            Err(err) => log::warn!("flow-meter {} total not loaded: {}", name, err),
+        }
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Builds the sensor from the attributes of the `flow-meter` model, once the platform has
+    /// set up the counting of the pulses
+    pub fn from_counter_and_config(
+        counter: Box<dyn PulseCounter>,
+        cfg: ConfigType,
+    ) -> Result<SensorType, SensorError> {
+        let optional = |name: &str, default: f64| match cfg.get_attribute::<f64>(name) {
+            Ok(value) => Ok(value),
+            Err(AttributeError::KeyNotFound(_)) => Ok(default),
+            Err(_) => Err(SensorError::ConfigError("flow-meter invalid attribute")),
+        };
+        let k_factor = optional("k_factor", DEFAULT_K_FACTOR)?;
+        let persist_every_liters = optional("persist_every_liters", DEFAULT_PERSIST_EVERY_LITERS)?;
+        Ok(Arc::new(Mutex::new(
+            Self::new(counter, k_factor, persist_every_liters)?
+                .with_persisted_total(cfg.get_name()),
+        )))
+    }
+
+    fn total_liters(&self, pulses: u64) -> f64 {
+        self.base_liters + pulses as f64 / self.k_factor
+    }
+
+    // updates the flow rate and persists the total when needed, returns the total
+    fn update(&mut self, now: Instant) -> Result<f64, SensorError> {
+        let pulses = self.counter.pulses()?;
+        match self.last_sample {
+            Some((at, _)) if now.duration_since(at) < MIN_RATE_WINDOW => {}
+            Some((at, previous)) => {
+                let minutes = now.duration_since(at).as_secs_f64() / 60.0;
+                self.flow_rate_lpm =
+                    pulses.saturating_sub(previous) as f64 / self.k_factor / minutes;
+                self.last_sample = Some((now, pulses));
+            }
+            None => self.last_sample = Some((now, pulses)),
+        }
+        let total = self.total_liters(pulses);
+        if total - self.persisted_liters >= self.persist_every_liters {
+            self.persist(total);
+        }
+        Ok(total)
+    }
+
+    fn persist(&mut self, liters: f64) {
+        if let Some(name) = &self.name {
+            if let Err(err) = (FlowTotal { liters }).store(name) {
+                log::warn!("flow-meter {} total not persisted: {}", name, err);
+                return;
+            }
+        }
+        self.persisted_liters = liters;
+    }
+
+    /// Resets the cumulative total to 0
+    pub fn reset_total(&mut self) -> Result<(), SensorError> {
+        let pulses = self.counter.pulses()?;
+        self.base_liters = -(pulses as f64) / self.k_factor;
+        self.persist(0.0);
+        Ok(())
+    }
+}
+
+impl Drop for FlowMeter {
+    // keeps what flowed since the total was last persisted when the robot is reconfigured
+    fn drop(&mut self) {
+        if let Ok(pulses) = self.counter.pulses() {
+            let total = self.total_liters(pulses);
+            if total != self.persisted_liters {
+                self.persist(total);
+            }
+        }
+    }
+}
+
+impl Sensor for FlowMeter {}
+
+impl Readings for FlowMeter {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let total = self.update(Instant::now())?;
+        Ok(StructBuilder::with_capacity(2)
+            .field("flow_rate_lpm", self.flow_rate_lpm)
+            .field("total_liters", total)
+            .into_fields())
+    }
+}
+
+impl Status for FlowMeter {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(
+            StructBuilder::with_capacity(1)
+                .field("persisted_liters", self.persisted_liters)
+                .build(),
+        ))
+    }
+}
+
+impl DoCommand for FlowMeter {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        if command.fields.contains_key("reset_total") {
+            self.reset_total()
+                .map_err(|err| GenericError::Other(Box::new(err)))?;
+            return Ok(None);
+        }
+        Err(GenericError::MethodUnimplemented("do_command"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use super::{FlowMeter, FlowTotal, PulseCounter};
+    use crate::common::calibration::Calibration;
+    use crate::common::sensor::SensorError;
+
+    struct SharedCounter(Arc<Mutex<u64>>);
+
+    impl PulseCounter for SharedCounter {
+        fn pulses(&mut self) -> Result<u64, SensorError> {
+            Ok(*self.0.lock().unwrap())
+        }
+    }
+
+    #[test_log::test]
+    fn test_flow_meter() -> Result<(), SensorError> {
+        let pulses = Arc::new(Mutex::new(0));
+        FlowTotal { liters: 10.0 }.store("test_flow_meter").unwrap();
+        let mut meter = FlowMeter::new(Box::new(SharedCounter(pulses.clone())), 450.0, 1.0)?
+            .with_persisted_total("test_flow_meter");
+
+        let start = Instant::now();
+        assert_eq!(meter.update(start)?, 10.0);
+        assert_eq!(meter.flow_rate_lpm, 0.0);
+
+        // 3 liters per minute
+        *pulses.lock().unwrap() = 450 * 3 / 2;
+        let total = meter.update(start + Duration::from_secs(30))?;
+        assert_eq!(total, 11.5);
+        assert!((meter.flow_rate_lpm - 3.0).abs() < 1e-9);
+        assert_eq!(
+            FlowTotal::load("test_flow_meter").unwrap(),
+            Some(FlowTotal { liters: 11.5 })
+        );
+
+        // too close to the previous sample, the rate is kept
+        *pulses.lock().unwrap() += 450;
+        assert_eq!(meter.update(start + Duration::from_millis(30100))?, 12.5);
+        assert!((meter.flow_rate_lpm - 3.0).abs() < 1e-9);
+
+        meter.reset_total()?;
+        assert_eq!(meter.update(start + Duration::from_secs(40))?, 0.0);
+        *pulses.lock().unwrap() += 45;
+        drop(meter);
+        let total = FlowTotal::load("test_flow_meter").unwrap().unwrap();
+        assert!((total.liters - 0.1).abs() < 1e-9);
+        Ok(())
+    }
+}
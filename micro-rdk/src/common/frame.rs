@@ -0,0 +1,369 @@
+//! Static frame metadata of components, taken from the `frame` of their config:
+//!
+//! ```json
+//! {
+//!     "name": "lidar",
+//!     "type": "sensor",
+//!     "frame": {
+//!         "parent": "base",
+//!         "translation": { "x": 100, "y": 0, "z": 250 },
+//!         "orientation": { "type": "euler_angles", "value": { "roll": 0, "pitch": 0, "yaw": 1.57 } },
+//!         "geometry": { "type": "box", "x": 60, "y": 60, "z": 40 }
+//!     }
+//! }
+//! ```
+//!
+//! The geometry is reported by the `GetGeometries` method of the component and the pose of the
+//! component in its parent frame by the `FrameSystemConfig` method of the robot, so motion and
+//! vision services running on the RDK side can account for micro-rdk peripherals. Translations
+//! are in millimeters and orientations are converted to orientation vectors in degrees, the
+//! representation used by `common.v1.Pose`.
+
+use std::f64::consts::PI;
+
+use thiserror::Error;
+
+use crate::proto::app::v1::{orientation, Frame, Orientation};
+use crate::proto::common::v1::{Geometry, Pose, PoseInFrame, Transform};
+use crate::proto::robot::v1::FrameSystemConfig;
+
+/// Frame of components without a configured parent
+pub static WORLD_FRAME: &str = "world";
+
+const ANGLE_EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Error)]
+pub enum FrameError {
+    #[error("orientation axis has no direction")]
+    ZeroOrientationAxis,
+    #[error("quaternion has no norm")]
+    ZeroQuaternion,
+}
+
+type Matrix3 = [[f64; 3]; 3];
+
+fn mul(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut res = [[0.0; 3]; 3];
+    for (i, row) in res.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    res
+}
+
+fn rot_x(angle: f64) -> Matrix3 {
+    let (s, c) = angle.sin_cos();
+    [[1.0, 0.0, 0.0], [0.0, c, -s], [0.0, s, c]]
+}
+
+fn rot_y(angle: f64) -> Matrix3 {
+    let (s, c) = angle.sin_cos();
+    [[c, 0.0, s], [0.0, 1.0, 0.0], [-s, 0.0, c]]
+}
+
+fn rot_z(angle: f64) -> Matrix3 {
+    let (s, c) = angle.sin_cos();
+    [[c, -s, 0.0], [s, c, 0.0], [0.0, 0.0, 1.0]]
+}
+
+fn quaternion_matrix(w: f64, x: f64, y: f64, z: f64) -> Result<Matrix3, FrameError> {
+    let norm = (w * w + x * x + y * y + z * z).sqrt();
+    if norm < ANGLE_EPSILON {
+        return Err(FrameError::ZeroQuaternion);
+    }
+    let (w, x, y, z) = (w / norm, x / norm, y / norm, z / norm);
+    Ok([
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+        ],
+        [
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+        ],
+        [
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ])
+}
+
+fn unit_axis(x: f64, y: f64, z: f64) -> Result<(f64, f64, f64), FrameError> {
+    let norm = (x * x + y * y + z * z).sqrt();
+    if norm < ANGLE_EPSILON {
+        return Err(FrameError::ZeroOrientationAxis);
+    }
+    Ok((x / norm, y / norm, z / norm))
+}
+
+/// Orientation vector, the axis the frame's Z axis points to and the rotation around it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrientationVector {
+    pub o_x: f64,
+    pub o_y: f64,
+    pub o_z: f64,
+    pub theta_deg: f64,
+}
+
+impl Default for OrientationVector {
+    fn default() -> Self {
+        Self {
+            o_x: 0.0,
+            o_y: 0.0,
+            o_z: 1.0,
+            theta_deg: 0.0,
+        }
+    }
+}
+
+impl OrientationVector {
+    /// Orientation vector of the rotation described by the quaternion `w + xi + yj + zk`
+    pub fn from_quaternion(w: f64, x: f64, y: f64, z: f64) -> Result<Self, FrameError> {
+        Ok(Self::from_matrix(&quaternion_matrix(w, x, y, z)?))
+    }
+
+    // the rotation is Rz(lon) * Ry(lat) * Rz(theta), lat and lon locating the Z axis on the sphere
+    fn from_matrix(r: &Matrix3) -> Self {
+        let (o_x, o_y, o_z) = (r[0][2], r[1][2], r[2][2].clamp(-1.0, 1.0));
+        let lat = o_z.acos();
+        let lon = if 1.0 - o_z.abs() > ANGLE_EPSILON {
+            o_y.atan2(o_x)
+        } else {
+            0.0
+        };
+        let m = mul(&mul(&rot_y(-lat), &rot_z(-lon)), r);
+        Self {
+            o_x,
+            o_y,
+            o_z,
+            theta_deg: m[1][0].atan2(m[0][0]) * 180.0 / PI,
+        }
+    }
+}
+
+impl TryFrom<&Orientation> for OrientationVector {
+    type Error = FrameError;
+    fn try_from(value: &Orientation) -> Result<Self, Self::Error> {
+        let matrix = match &value.r#type {
+            None | Some(orientation::Type::NoOrientation(_)) => return Ok(Self::default()),
+            Some(orientation::Type::VectorDegrees(ov)) => {
+                let (o_x, o_y, o_z) = unit_axis(ov.x, ov.y, ov.z)?;
+                return Ok(Self {
+                    o_x,
+                    o_y,
+                    o_z,
+                    theta_deg: ov.theta,
+                });
+            }
+            Some(orientation::Type::VectorRadians(ov)) => {
+                let (o_x, o_y, o_z) = unit_axis(ov.x, ov.y, ov.z)?;
+                return Ok(Self {
+                    o_x,
+                    o_y,
+                    o_z,
+                    theta_deg: ov.theta * 180.0 / PI,
+                });
+            }
+            Some(orientation::Type::EulerAngles(angles)) => mul(
+                &mul(&rot_z(angles.yaw), &rot_y(angles.pitch)),
+                &rot_x(angles.roll),
+            ),
+            Some(orientation::Type::AxisAngles(aa)) => {
+                let (x, y, z) = unit_axis(aa.x, aa.y, aa.z)?;
+                let (s, c) = (aa.theta / 2.0).sin_cos();
+                quaternion_matrix(c, x * s, y * s, z * s)?
+            }
+            Some(orientation::Type::Quaternion(q)) => quaternion_matrix(q.w, q.x, q.y, q.z)?,
+        };
+        Ok(Self::from_matrix(&matrix))
+    }
+}
+
+/// Pose of a component in its parent frame and its geometry
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComponentFrame {
+    pub parent: String,
+    pub pose: Pose,
+    pub geometry: Option<Geometry>,
+}
+
+impl TryFrom<&Frame> for ComponentFrame {
+    type Error = FrameError;
+    fn try_from(value: &Frame) -> Result<Self, Self::Error> {
+        let ov = value
+            .orientation
+            .as_ref()
+            .map(OrientationVector::try_from)
+            .transpose()?
+            .unwrap_or_default();
+        let translation = value.translation.clone().unwrap_or_default();
+        Ok(Self {
+            parent: match value.parent.is_empty() {
+                true => WORLD_FRAME.to_string(),
+                false => value.parent.clone(),
+            },
+            pose: Pose {
+                x: translation.x,
+                y: translation.y,
+                z: translation.z,
+                o_x: ov.o_x,
+                o_y: ov.o_y,
+                o_z: ov.o_z,
+                theta: ov.theta_deg,
+            },
+            geometry: value.geometry.clone(),
+        })
+    }
+}
+
+impl ComponentFrame {
+    /// Geometries of the component in its own frame, labelled with its name unless configured
+    pub fn geometries(&self, name: &str) -> Vec<Geometry> {
+        self.geometry
+            .iter()
+            .cloned()
+            .map(|mut geometry| {
+                if geometry.label.is_empty() {
+                    geometry.label = name.to_string();
+                }
+                geometry
+            })
+            .collect()
+    }
+
+    pub fn frame_system_config(&self, name: &str) -> FrameSystemConfig {
+        FrameSystemConfig {
+            frame: Some(Transform {
+                reference_frame: name.to_string(),
+                pose_in_observer_frame: Some(PoseInFrame {
+                    reference_frame: self.parent.clone(),
+                    pose: Some(self.pose.clone()),
+                }),
+                physical_object: self.geometries(name).pop(),
+            }),
+            kinematics: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ComponentFrame, OrientationVector, WORLD_FRAME};
+    use crate::proto::app::v1::{orientation, Frame, Orientation, Translation};
+    use crate::proto::common::v1::{geometry::GeometryType, Geometry, RectangularPrism, Vector3};
+
+    fn assert_ov(orientation: orientation::Type, expected: (f64, f64, f64, f64)) {
+        let ov = OrientationVector::try_from(&Orientation {
+            r#type: Some(orientation),
+        })
+        .unwrap();
+        let got = (ov.o_x, ov.o_y, ov.o_z, ov.theta_deg);
+        assert!(
+            [
+                got.0 - expected.0,
+                got.1 - expected.1,
+                got.2 - expected.2,
+                got.3 - expected.3
+            ]
+            .iter()
+            .all(|d| d.abs() < 1e-6),
+            "{:?} != {:?}",
+            got,
+            expected
+        );
+    }
+
+    #[test_log::test]
+    fn test_orientation_vector_conversion() {
+        assert_ov(
+            orientation::Type::Quaternion(orientation::Quaternion {
+                w: 1.0,
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            }),
+            (0.0, 0.0, 1.0, 0.0),
+        );
+        assert_ov(
+            orientation::Type::EulerAngles(orientation::EulerAngles {
+                roll: 0.0,
+                pitch: 0.0,
+                yaw: std::f64::consts::FRAC_PI_2,
+            }),
+            (0.0, 0.0, 1.0, 90.0),
+        );
+        // pointing the Z axis along X
+        assert_ov(
+            orientation::Type::AxisAngles(orientation::AxisAngles {
+                theta: std::f64::consts::FRAC_PI_2,
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            }),
+            (1.0, 0.0, 0.0, 0.0),
+        );
+        assert_ov(
+            orientation::Type::VectorRadians(orientation::OrientationVectorRadians {
+                theta: std::f64::consts::PI,
+                x: 0.0,
+                y: 0.0,
+                z: -3.0,
+            }),
+            (0.0, 0.0, -1.0, 180.0),
+        );
+        assert!(OrientationVector::try_from(&Orientation {
+            r#type: Some(orientation::Type::VectorDegrees(
+                orientation::OrientationVectorDegrees::default()
+            )),
+        })
+        .is_err());
+    }
+
+    #[test_log::test]
+    fn test_component_frame() {
+        let geometry = Geometry {
+            center: None,
+            label: String::new(),
+            geometry_type: Some(GeometryType::Box(RectangularPrism {
+                dims_mm: Some(Vector3 {
+                    x: 60.0,
+                    y: 60.0,
+                    z: 40.0,
+                }),
+            })),
+        };
+        let frame = ComponentFrame::try_from(&Frame {
+            parent: String::new(),
+            translation: Some(Translation {
+                x: 100.0,
+                y: 0.0,
+                z: 250.0,
+            }),
+            orientation: None,
+            geometry: Some(geometry),
+        })
+        .unwrap();
+        assert_eq!(frame.parent, WORLD_FRAME);
+        assert_eq!(
+            (frame.pose.x, frame.pose.z, frame.pose.o_z),
+            (100.0, 250.0, 1.0)
+        );
+
+        let geometries = frame.geometries("lidar");
+        assert_eq!(geometries.len(), 1);
+        assert_eq!(geometries[0].label, "lidar");
+
+        let config = frame.frame_system_config("lidar");
+        let transform = config.frame.unwrap();
+        assert_eq!(transform.reference_frame, "lidar");
+        assert_eq!(
+            transform.pose_in_observer_frame.unwrap().reference_frame,
+            WORLD_FRAME
+        );
+        assert!(transform.physical_object.is_some());
+    }
+}
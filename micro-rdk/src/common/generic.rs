@@ -14,15 +14,36 @@ use {
     std::collections::HashMap,
 };
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 use thiserror::Error;
 
 pub static COMPONENT_NAME: &str = "generic";
 
+/// Command by which a generic component can expose readings, a component handling
+/// `{"get_readings": {}}` in `do_command` should answer with a struct of its readings.
+/// This is the default command used when capturing data from generic components.
+pub static GET_READINGS_COMMAND: &str = "get_readings";
+
 #[derive(Debug, Error)]
 pub enum GenericError {
     #[error("Generic: method {0} unimplemented")]
     MethodUnimplemented(&'static str),
+    #[error("Generic: invalid command argument {0}")]
+    InvalidArgument(&'static str),
+    #[error(transparent)]
+    Other(#[from] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl GrpcStatusHint for GenericError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::MethodUnimplemented(_) => GrpcError::RpcUnimplemented,
+            Self::InvalidArgument(_) => GrpcError::RpcInvalidArgument,
+            Self::Other(_) => GrpcError::RpcInternal,
+        }
+    }
 }
+
 #[cfg(feature = "builtin-components")]
 pub(crate) fn register_models(registry: &mut ComponentRegistry) {
     if registry
@@ -77,6 +98,7 @@ impl<L> GenericComponent for Mutex<L> where L: ?Sized + GenericComponent {}
 impl<A> GenericComponent for Arc<Mutex<A>> where A: ?Sized + GenericComponent {}
 
 #[cfg(feature = "builtin-components")]
+#[derive(Status)]
 pub struct FakeGenericComponent {}
 
 #[cfg(feature = "builtin-components")]
@@ -113,6 +135,14 @@ impl DoCommand for FakeGenericComponent {
                     "echo" => {
                         res.insert("echoed".to_string(), val.to_owned());
                     }
+                    "get_readings" => {
+                        res.insert(
+                            "fake_generic".to_string(),
+                            Value {
+                                kind: Some(Kind::NumberValue(42.42)),
+                            },
+                        );
+                    }
                     _ => {}
                 };
             }
@@ -120,14 +150,3 @@ impl DoCommand for FakeGenericComponent {
         Ok(Some(Struct { fields: res }))
     }
 }
-
-#[cfg(feature = "builtin-components")]
-impl Status for FakeGenericComponent {
-    fn get_status(
-        &self,
-    ) -> Result<Option<crate::google::protobuf::Struct>, crate::common::status::StatusError> {
-        Ok(Some(Struct {
-            fields: HashMap::new(),
-        }))
-    }
-}
@@ -0,0 +1,560 @@
+//! Package geofence implements a sensor reporting whether the position of a movement sensor
+//! is inside a set of circular or polygonal areas, and the events generated when it enters or
+//! leaves them.
+//!
+//! ```json
+//! {
+//!     "movement_sensor": "gps",
+//!     "fences": [
+//!         { "name": "yard", "circle": { "lat": 40.7128, "lon": -74.006, "radius_m": 50 } },
+//!         { "name": "depot", "polygon": [{ "lat": 40.71, "lon": -74.01 }, { "lat": 40.72, "lon": -74.01 }, { "lat": 40.72, "lon": -74.0 }] }
+//!     ],
+//!     "webhook_url": "http://192.168.1.10:8123/api/webhook/fence",
+//!     "check_interval_secs": 5
+//! }
+//! ```
+//!
+//! The position is evaluated every time readings are requested or events are taken. The first
+//! position only sets which fences the sensor is inside of, following positions generate an
+//! `enter` or `exit` event for every fence crossed, which is logged. The readings contain `lat`,
+//! `lon`, the `inside` list of fence names and the `entered` and `exited` lists of the fences
+//! crossed since the previous readings, so readings captured by data capture are tagged with the
+//! events. The readings and the taken events are kept apart, each of them sees every crossing.
+//!
+//! The [EventWatcher](super::webhook::EventWatcher), started with the robot whenever a geofence
+//! is configured, evaluates every geofence each `check_interval_secs` seconds (5 by default) and
+//! posts its events as JSON to its `webhook_url` when it is set:
+//!
+//! ```json
+//! {"geofence": "fences", "fence": "yard", "event": "enter", "lat": 40.7128, "lon": -74.006}
+//! ```
+//!
+//! Events are kept until the watcher takes them with the `{"take_events": {}}` command, at most
+//! 16 events are kept.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use super::config::{AttributeError, ConfigType, Kind};
+use super::generic::{DoCommand, GenericError};
+use super::movement_sensor::{
+    GeoPosition, MovementSensor, MovementSensorType, COMPONENT_NAME as MovementSensorCompName,
+};
+use super::registry::{ComponentRegistry, Dependency, ResourceKey};
+use super::robot::{LocalRobot, Resource};
+use super::sensor::{
+    GenericReadingsResult, Readings, Sensor, SensorError, SensorType,
+    COMPONENT_NAME as SensorCompName,
+};
+use super::status::{Status, StatusError};
+use super::struct_builder::StructBuilder;
+use super::webhook::{EventQueue, EventWatcher};
+use crate::google::protobuf::{value::Kind as ProtoKind, ListValue, Struct, Value};
+use crate::proto::app::v1::ConfigResponse;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("geofence", &Geofence::<MovementSensorType>::from_config)
+        .is_err()
+    {
+        log::error!("geofence type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            SensorCompName,
+            "geofence",
+            &Geofence::<MovementSensorType>::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("failed to register dependency getter for geofence model")
+    }
+}
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A point of a polygonal fence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl TryFrom<&Kind> for LatLon {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let lat = value
+            .get("lat")?
+            .ok_or(AttributeError::KeyNotFound("lat".to_string()))?
+            .try_into()?;
+        let lon = value
+            .get("lon")?
+            .ok_or(AttributeError::KeyNotFound("lon".to_string()))?
+            .try_into()?;
+        Ok(LatLon { lat, lon })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Area {
+    Circle { center: LatLon, radius_m: f64 },
+    Polygon(Vec<LatLon>),
+}
+
+/// Great-circle distance in meters between two points
+pub(crate) fn distance_m(a: LatLon, b: LatLon) -> f64 {
+    let (lat_a, lat_b) = (a.lat.to_radians(), b.lat.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.lon - a.lon).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+impl Area {
+    /// Whether `point` is inside the area, polygons are treated as planar which is accurate
+    /// enough for fences of a few kilometers away from the poles
+    pub fn contains(&self, point: LatLon) -> bool {
+        match self {
+            Self::Circle { center, radius_m } => distance_m(*center, point) <= *radius_m,
+            Self::Polygon(vertices) => {
+                let mut inside = false;
+                let mut j = vertices.len() - 1;
+                for (i, vi) in vertices.iter().enumerate() {
+                    let vj = vertices[j];
+                    if (vi.lat > point.lat) != (vj.lat > point.lat)
+                        && point.lon
+                            < (vj.lon - vi.lon) * (point.lat - vi.lat) / (vj.lat - vi.lat) + vi.lon
+                    {
+                        inside = !inside;
+                    }
+                    j = i;
+                }
+                inside
+            }
+        }
+    }
+}
+
+/// An element of the `fences` attribute of a geofence
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fence {
+    pub name: String,
+    pub area: Area,
+}
+
+impl TryFrom<&Kind> for Fence {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let name = value
+            .get("name")?
+            .ok_or(AttributeError::KeyNotFound("name".to_string()))?
+            .try_into()?;
+        let area = if let Some(circle) = value.get("circle")? {
+            let radius_m: f64 = circle
+                .get("radius_m")?
+                .ok_or(AttributeError::KeyNotFound("radius_m".to_string()))?
+                .try_into()?;
+            if radius_m <= 0.0 {
+                return Err(AttributeError::ConversionImpossibleError);
+            }
+            Area::Circle {
+                center: circle.try_into()?,
+                radius_m,
+            }
+        } else if let Some(polygon) = value.get("polygon")? {
+            let vertices: Vec<LatLon> = polygon.try_into()?;
+            if vertices.len() < 3 {
+                return Err(AttributeError::ConversionImpossibleError);
+            }
+            Area::Polygon(vertices)
+        } else {
+            return Err(AttributeError::KeyNotFound("circle".to_string()));
+        };
+        Ok(Fence { name, area })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeofenceEventType {
+    Enter,
+    Exit,
+}
+
+impl GeofenceEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Enter => "enter",
+            Self::Exit => "exit",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeofenceEvent {
+    pub fence: String,
+    pub event: GeofenceEventType,
+    pub position: LatLon,
+}
+
+impl From<&GeofenceEvent> for Value {
+    fn from(value: &GeofenceEvent) -> Self {
+        StructBuilder::with_capacity(4)
+            .field("fence", value.fence.as_str())
+            .field("event", value.event.as_str())
+            .field("lat", value.position.lat)
+            .field("lon", value.position.lon)
+            .into()
+    }
+}
+
+fn string_list<'a>(names: impl Iterator<Item = &'a str>) -> Value {
+    Value {
+        kind: Some(ProtoKind::ListValue(ListValue {
+            values: names
+                .map(|name| Value {
+                    kind: Some(ProtoKind::StringValue(name.to_string())),
+                })
+                .collect(),
+        })),
+    }
+}
+
+pub struct Geofence<M> {
+    movement_sensor: M,
+    fences: Vec<Fence>,
+    // whether the last position was inside each fence, unknown until the first position
+    inside: Option<Vec<bool>>,
+    last_position: Option<LatLon>,
+    // events waiting to be taken with the command, and reported by the readings
+    pending: EventQueue<GeofenceEvent>,
+    crossed: EventQueue<GeofenceEvent>,
+    events: u32,
+}
+
+impl<M> Geofence<M>
+where
+    M: MovementSensor,
+{
+    pub fn new(movement_sensor: M, fences: Vec<Fence>) -> Result<Self, SensorError> {
+        if fences.is_empty() {
+            return Err(SensorError::ConfigError(
+                "geofence needs at least one fence",
+            ));
+        }
+        Ok(Self {
+            movement_sensor,
+            fences,
+            inside: None,
+            last_position: None,
+            pending: EventQueue::default(),
+            crossed: EventQueue::default(),
+            events: 0,
+        })
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let ms_name = cfg
+            .get_attribute::<String>("movement_sensor")
+            .map_err(|_| SensorError::ConfigError("geofence missing movement_sensor attribute"))?;
+        let movement_sensor = deps
+            .into_iter()
+            .find_map(|Dependency(key, res)| match res {
+                Resource::MovementSensor(ms) if key.1 == ms_name => Some(ms),
+                _ => None,
+            })
+            .ok_or(SensorError::ConfigError(
+                "geofence movement sensor not found",
+            ))?;
+        let fences = cfg
+            .get_attribute::<Vec<Fence>>("fences")
+            .map_err(|_| SensorError::ConfigError("geofence invalid fences"))?;
+        Ok(Arc::new(Mutex::new(Geofence::new(
+            movement_sensor,
+            fences,
+        )?)))
+    }
+
+    pub(crate) fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        let mut r_keys = Vec::new();
+        if let Ok(ms_name) = cfg.get_attribute::<String>("movement_sensor") {
+            r_keys.push(ResourceKey(MovementSensorCompName, ms_name));
+        }
+        r_keys
+    }
+
+    // updates which fences contain `position` and returns the fences crossed since the
+    // previous position
+    fn evaluate(&mut self, position: LatLon) -> Vec<GeofenceEvent> {
+        let inside: Vec<bool> = self
+            .fences
+            .iter()
+            .map(|fence| fence.area.contains(position))
+            .collect();
+        let mut events = vec![];
+        if let Some(was_inside) = self.inside.as_ref() {
+            for ((fence, was), is) in self.fences.iter().zip(was_inside).zip(&inside) {
+                let event = match (was, is) {
+                    (false, true) => GeofenceEventType::Enter,
+                    (true, false) => GeofenceEventType::Exit,
+                    _ => continue,
+                };
+                log::info!("geofence: {} {}", event.as_str(), fence.name);
+                events.push(GeofenceEvent {
+                    fence: fence.name.clone(),
+                    event,
+                    position,
+                });
+            }
+        }
+        self.inside = Some(inside);
+        self.last_position = Some(position);
+        self.events += events.len() as u32;
+        for event in &events {
+            self.pending.push(event.clone());
+            self.crossed.push(event.clone());
+        }
+        events
+    }
+
+    // evaluates the current position of the movement sensor
+    fn update(&mut self) -> Result<LatLon, SensorError> {
+        let GeoPosition { lat, lon, .. } = self.movement_sensor.get_position()?;
+        let position = LatLon { lat, lon };
+        self.evaluate(position);
+        Ok(position)
+    }
+
+    fn inside_names(&self) -> impl Iterator<Item = &str> {
+        self.fences
+            .iter()
+            .zip(self.inside.iter().flatten())
+            .filter(|(_, inside)| **inside)
+            .map(|(fence, _)| fence.name.as_str())
+    }
+}
+
+impl<M> Sensor for Geofence<M> where M: MovementSensor {}
+
+impl<M> Readings for Geofence<M>
+where
+    M: MovementSensor,
+{
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let LatLon { lat, lon } = self.update()?;
+        let events = self.crossed.take();
+        let crossed = |event_type| {
+            string_list(
+                events
+                    .iter()
+                    .filter(move |event| event.event == event_type)
+                    .map(|event| event.fence.as_str()),
+            )
+        };
+        Ok(StructBuilder::with_capacity(6)
+            .field("lat", lat)
+            .field("lon", lon)
+            .value("inside", string_list(self.inside_names()))
+            .value("entered", crossed(GeofenceEventType::Enter))
+            .value("exited", crossed(GeofenceEventType::Exit))
+            .field("events", self.events)
+            .into_fields())
+    }
+}
+
+impl<M> Status for Geofence<M>
+where
+    M: MovementSensor,
+{
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        let mut status = StructBuilder::with_capacity(2)
+            .value("inside", string_list(self.inside_names()))
+            .field("events", self.events);
+        if let Some(position) = self.last_position {
+            status = status.sub(
+                "position",
+                StructBuilder::with_capacity(2)
+                    .field("lat", position.lat)
+                    .field("lon", position.lon),
+            );
+        }
+        Ok(Some(status.build()))
+    }
+}
+
+impl<M> DoCommand for Geofence<M>
+where
+    M: MovementSensor,
+{
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        if command.fields.contains_key("take_events") {
+            if let Err(err) = self.update() {
+                log::debug!("geofence: couldn't read the position: {}", err);
+            }
+        }
+        match self.pending.take_events(&command) {
+            Some(events) => Ok(Some(events)),
+            None => Err(GenericError::MethodUnimplemented("do_command")),
+        }
+    }
+}
+
+/// Returns the [EventWatcher] of the geofence sensors of the robot, `None` when it has none
+pub fn watcher_from_robot_and_config(
+    cfg: &ConfigResponse,
+    robot: Arc<RwLock<LocalRobot>>,
+) -> Result<Option<EventWatcher>, SensorError> {
+    EventWatcher::from_robot_and_config(cfg, robot, "geofence", DEFAULT_CHECK_INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{distance_m, Area, Fence, Geofence, GeofenceEventType, LatLon};
+    use crate::common::config::Kind;
+    use crate::common::generic::DoCommand;
+    use crate::common::movement_sensor::FakeMovementSensor;
+    use crate::common::sensor::Readings;
+    use crate::google::protobuf::{value::Kind as ProtoKind, Struct, Value};
+    use std::collections::HashMap;
+
+    fn point(lat: f64, lon: f64) -> LatLon {
+        LatLon { lat, lon }
+    }
+
+    fn square() -> Area {
+        Area::Polygon(vec![
+            point(40.0, -74.0),
+            point(40.0, -73.0),
+            point(41.0, -73.0),
+            point(41.0, -74.0),
+        ])
+    }
+
+    #[test_log::test]
+    fn test_areas() {
+        // one degree of latitude is about 111km
+        let d = distance_m(point(40.0, -74.0), point(41.0, -74.0));
+        assert!((d - 111_195.0).abs() < 10.0);
+
+        let circle = Area::Circle {
+            center: point(40.0, -74.0),
+            radius_m: 100.0,
+        };
+        assert!(circle.contains(point(40.0005, -74.0)));
+        assert!(!circle.contains(point(40.001, -74.0)));
+
+        assert!(square().contains(point(40.5, -73.5)));
+        assert!(!square().contains(point(41.5, -73.5)));
+        assert!(!square().contains(point(40.5, -72.5)));
+    }
+
+    #[test_log::test]
+    fn test_fence_config() {
+        let kind = |fields: Vec<(&str, Kind)>| {
+            Kind::StructValue(
+                fields
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            )
+        };
+        let circle = kind(vec![
+            ("name", Kind::StringValue("yard".to_string())),
+            (
+                "circle",
+                kind(vec![
+                    ("lat", Kind::NumberValue(40.0)),
+                    ("lon", Kind::NumberValue(-74.0)),
+                    ("radius_m", Kind::NumberValue(50.0)),
+                ]),
+            ),
+        ]);
+        assert_eq!(
+            Fence::try_from(&circle).unwrap(),
+            Fence {
+                name: "yard".to_string(),
+                area: Area::Circle {
+                    center: point(40.0, -74.0),
+                    radius_m: 50.0
+                }
+            }
+        );
+        let vertex = |lat, lon| {
+            kind(vec![
+                ("lat", Kind::NumberValue(lat)),
+                ("lon", Kind::NumberValue(lon)),
+            ])
+        };
+        let polygon = kind(vec![
+            ("name", Kind::StringValue("depot".to_string())),
+            (
+                "polygon",
+                Kind::VecValue(vec![vertex(40.0, -74.0), vertex(40.0, -73.0)]),
+            ),
+        ]);
+        // a polygon needs at least 3 vertices
+        assert!(Fence::try_from(&polygon).is_err());
+        assert!(
+            Fence::try_from(&kind(vec![("name", Kind::StringValue("none".to_string()))])).is_err()
+        );
+    }
+
+    #[test_log::test]
+    fn test_geofence_events() {
+        let mut geofence = Geofence::new(
+            FakeMovementSensor::new(),
+            vec![Fence {
+                name: "square".to_string(),
+                area: square(),
+            }],
+        )
+        .unwrap();
+
+        // the first position doesn't generate events
+        assert!(geofence.evaluate(point(40.5, -73.5)).is_empty());
+        assert_eq!(geofence.inside_names().collect::<Vec<_>>(), ["square"]);
+
+        let events = geofence.evaluate(point(42.0, -73.5));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, GeofenceEventType::Exit);
+        assert!(geofence.evaluate(point(42.0, -73.0)).is_empty());
+        assert_eq!(geofence.inside_names().count(), 0);
+
+        // the fake movement sensor is at 27.33, 29.45, outside of the square
+        geofence.evaluate(point(40.5, -73.5));
+        let readings = geofence.get_generic_readings().unwrap();
+        let list_len = |key: &str| match readings.get(key).and_then(|v| v.kind.as_ref()) {
+            Some(ProtoKind::ListValue(list)) => list.values.len(),
+            _ => panic!("{key} isn't a list"),
+        };
+        // every crossing since the previous readings
+        assert_eq!(list_len("inside"), 0);
+        assert_eq!(list_len("entered"), 1);
+        assert_eq!(list_len("exited"), 2);
+        assert_eq!(geofence.events, 3);
+        assert_eq!(geofence.pending.len(), 3);
+
+        let take = Struct {
+            fields: HashMap::from([(
+                "take_events".to_string(),
+                Value {
+                    kind: Some(ProtoKind::StructValue(Struct::default())),
+                },
+            )]),
+        };
+        let res = geofence.do_command(Some(take)).unwrap().unwrap();
+        match res.fields.get("events").and_then(|v| v.kind.as_ref()) {
+            Some(ProtoKind::ListValue(events)) => assert_eq!(events.values.len(), 3),
+            _ => panic!("events isn't a list"),
+        }
+        assert!(geofence.pending.is_empty());
+    }
+}
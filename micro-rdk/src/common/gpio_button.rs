@@ -0,0 +1,178 @@
+//! Push button wired to a GPIO pin configured as a digital interrupt of the board.
+//!
+//! ```json
+//! {
+//!     "name": "doorbell",
+//!     "model": "gpio",
+//!     "type": "button",
+//!     "attributes": {
+//!         "board": "board",
+//!         "pin": 14,
+//!         "active_low": true,
+//!         "debounce_ms": 50,
+//!         "long_press_ms": 1000,
+//!         "webhook_url": "http://192.168.1.10:8123/api/webhook/doorbell"
+//!     }
+//! }
+//! ```
+//!
+//! The pin has to be listed in the `digital_interrupts` of the board. Its level is sampled by
+//! the [ButtonWatcher](crate::common::button::ButtonWatcher) and the interrupt counter catches
+//! presses shorter than the sampling interval. `active_low` (the default) is for buttons pulling
+//! the pin to ground, `debounce_ms` and `long_press_ms` default to 50ms and 1s.
+
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use super::{
+    board::{Board, BoardType},
+    button::{Button, ButtonError, ButtonEvent, ButtonSettings, ButtonTracker, ButtonType},
+    config::ConfigType,
+    registry::{get_board_from_dependencies, ComponentRegistry, Dependency},
+    status::{status_envelope, ComponentHealth, Status, StatusError},
+};
+use crate::google::protobuf::Struct;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry.register_button("gpio", &from_config).is_err() {
+        log::error!("gpio model is already registered")
+    }
+}
+
+pub(crate) fn from_config(
+    cfg: ConfigType,
+    dependencies: Vec<Dependency>,
+) -> Result<ButtonType, ButtonError> {
+    let board = get_board_from_dependencies(dependencies).ok_or(
+        ButtonError::ButtonConfigurationError("missing board attribute"),
+    )?;
+    let pin = cfg.get_attribute::<i32>("pin")?;
+    let active_low = cfg.get_attribute::<bool>("active_low").unwrap_or(true);
+    let settings = ButtonSettings::from_config(&cfg)?;
+    Ok(Arc::new(Mutex::new(GpioButton::<BoardType>::new(
+        board.clone(),
+        pin,
+        active_low,
+        settings,
+    )?)))
+}
+
+#[derive(DoCommand)]
+pub struct GpioButton<B> {
+    board: B,
+    pin: i32,
+    active_low: bool,
+    tracker: ButtonTracker,
+    interrupt_count: u32,
+    // presses from software, reported at the next poll
+    pushed: u32,
+    health: ComponentHealth,
+}
+
+impl<B> GpioButton<B>
+where
+    B: Board,
+{
+    pub fn new(
+        board: B,
+        pin: i32,
+        active_low: bool,
+        settings: ButtonSettings,
+    ) -> Result<Self, ButtonError> {
+        // fails when the pin isn't a digital interrupt of the board
+        let interrupt_count = board.get_digital_interrupt_value(pin)?;
+        Ok(Self {
+            board,
+            pin,
+            active_low,
+            tracker: ButtonTracker::new(settings),
+            interrupt_count,
+            pushed: 0,
+            health: ComponentHealth::new(),
+        })
+    }
+
+    fn sample(&mut self, now: Instant) -> Result<Option<ButtonEvent>, ButtonError> {
+        let pressed = self.board.get_gpio_level(self.pin)? != self.active_low;
+        let count = self.board.get_digital_interrupt_value(self.pin)?;
+        let edges = count.wrapping_sub(self.interrupt_count);
+        self.interrupt_count = count;
+        let event = self.tracker.update(pressed, now);
+        if event.is_none() && !pressed && edges > 0 {
+            return Ok(self.tracker.tap(now));
+        }
+        Ok(event)
+    }
+}
+
+impl<B> Button for GpioButton<B>
+where
+    B: Board,
+{
+    fn push(&mut self) -> Result<(), ButtonError> {
+        self.pushed += 1;
+        Ok(())
+    }
+    fn poll(&mut self) -> Result<Vec<ButtonEvent>, ButtonError> {
+        let mut events: Vec<_> = (0..std::mem::take(&mut self.pushed))
+            .map(|_| self.tracker.record(ButtonEvent::Press))
+            .collect();
+        let res = self.sample(Instant::now());
+        self.health.record(&res);
+        events.extend(res?);
+        Ok(events)
+    }
+}
+
+impl<B> Status for GpioButton<B>
+where
+    B: Board,
+{
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(status_envelope(
+            &self.health,
+            self.tracker.status_fields(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use crate::common::board::{Board, FakeBoard};
+    use crate::common::button::{Button, ButtonError, ButtonEvent, ButtonSettings};
+    use crate::common::gpio_button::GpioButton;
+
+    #[test_log::test]
+    fn test_gpio_button() -> Result<(), ButtonError> {
+        let mut board = FakeBoard::new(vec![]);
+        board.add_digital_interrupt(14);
+        let mut board = Arc::new(Mutex::new(board));
+        let settings = ButtonSettings {
+            debounce: Duration::from_millis(20),
+            long_press: Duration::from_millis(200),
+        };
+        assert!(GpioButton::new(board.clone(), 15, true, settings).is_err());
+        let mut button = GpioButton::new(board.clone(), 14, true, settings)?;
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        board.set_gpio_pin_level(14, false)?;
+        assert_eq!(button.sample(at(0))?, None);
+        assert_eq!(button.sample(at(30))?, None);
+        board.set_gpio_pin_level(14, true)?;
+        board.lock().unwrap().trigger_digital_interrupt(14)?;
+        assert_eq!(button.sample(at(100))?, None);
+        assert_eq!(button.sample(at(130))?, Some(ButtonEvent::Press));
+
+        // press and release between two samples, only seen by the interrupt counter
+        board.lock().unwrap().trigger_digital_interrupt(14)?;
+        assert_eq!(button.sample(at(300))?, Some(ButtonEvent::Press));
+
+        button.push()?;
+        assert_eq!(button.poll()?, vec![ButtonEvent::Press]);
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+//! GPIO expanders extend the pins of a board with the pins of external chips (I2C port
+//! expanders, shift registers, PWM drivers...). Expanders are declared in the board's config
+//! under the `gpio_expanders` attribute:
+//!
+//! ```json
+//! "gpio_expanders": [
+//!     { "name": "mcp", "model": "mcp23017", "i2c_bus": "i2c0", "interrupt_pin": 4, "interrupt_pins": [0, 1] },
+//!     { "name": "leds", "model": "74hc595", "data_pin": 12, "clock_pin": 13, "latch_pin": 14, "num_chips": 2 },
+//!     { "name": "pwm", "model": "pca9685", "i2c_bus": "i2c0" }
+//! ]
+//! ```
+//!
+//! The pins of an expander can then be referenced in the config of other components as
+//! `<expander name>:<pin>` (e.g. `"mcp:3"`), see [BoardPin]. The name of an expander defaults to
+//! its model, so a single PCA9685 declared without a name, or with the `pca9685` attribute of the
+//! board (see [super::pca9685]), has its channels referenced as `"pca9685:<channel>"`.
+//! Internally each expander is given a range of pin numbers starting at `EXPANDER_PIN_OFFSET` so
+//! that components can keep addressing pins through the [Board] trait.
+
+use crate::common::i2c::I2cHandleType;
+use crate::google;
+use crate::proto::{common, component};
+
+use super::analog::AnalogReaderType;
+use super::board::{
+    board_status_to_struct, whole_pwm_frequency, Board, BoardError, BoardPin, BoardType, PulseTrain,
+};
+use super::config::{AttributeError, ConfigType, Kind};
+use super::digital_interrupt::Tick;
+use super::generic::{DoCommand, GenericError};
+use super::mcp23017::{Mcp23017, Mcp23017Config};
+use super::pca9685::{Pca9685, Pca9685Config};
+use super::shift_register::{ShiftRegister, ShiftRegisterConfig};
+use super::status::{Status, StatusError};
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Pin number of the first pin of the first expander of a board
+pub const EXPANDER_PIN_OFFSET: i32 = 1000;
+/// Range of pin numbers reserved for each expander
+pub const EXPANDER_PIN_STRIDE: i32 = 100;
+
+/// A chip providing additional pins to a board. Pins are numbered from 0 to `num_pins() - 1`
+pub trait GpioExpander {
+    /// Number of pins provided by the expander
+    fn num_pins(&self) -> u16;
+
+    /// Set a pin to high or low
+    fn set_gpio_pin_level(&mut self, pin: u16, is_high: bool) -> Result<(), BoardError>;
+
+    /// Get the state of a pin, high(`true`) or low(`false`)
+    fn get_gpio_level(&mut self, pin: u16) -> Result<bool, BoardError>;
+
+    /// Get the pin's given duty cycle, returns percentage as float between 0.0 and 1.0
+    fn get_pwm_duty(&self, _pin: u16) -> f64 {
+        0.0
+    }
+
+    /// Set the pin to the given duty cycle , `duty_cycle_pct` is a float between 0.0 and 1.0.
+    fn set_pwm_duty(&mut self, _pin: u16, _duty_cycle_pct: f64) -> Result<(), BoardError> {
+        Err(BoardError::BoardMethodNotSupported("set_pwm_duty"))
+    }
+
+    /// Get the PWM frequency of the pin
+    fn get_pwm_frequency(&self, _pin: u16) -> Result<u64, BoardError> {
+        Err(BoardError::BoardMethodNotSupported("get_pwm_frequency"))
+    }
+
+    /// Set the pin to the given PWM frequency (in Hz), 0 removes the PWM signal
+    fn set_pwm_frequency(&mut self, _pin: u16, _frequency_hz: u64) -> Result<(), BoardError> {
+        Err(BoardError::BoardMethodNotSupported("set_pwm_frequency"))
+    }
+
+    /// Pins configured as digital interrupts
+    fn interrupt_pins(&self) -> Vec<u16> {
+        vec![]
+    }
+
+    /// Return the amount of detected interrupt events on a pin. Should error if the
+    /// pin has not been configured as an interrupt
+    fn get_digital_interrupt_value(&mut self, _pin: u16) -> Result<u32, BoardError> {
+        Err(BoardError::BoardMethodNotSupported(
+            "get_digital_interupt_value",
+        ))
+    }
+}
+
+pub type GpioExpanderType = Arc<Mutex<dyn GpioExpander>>;
+
+pub(crate) enum GpioExpanderModelConfig {
+    Mcp23017(Mcp23017Config),
+    Pca9685(Pca9685Config),
+    ShiftRegister(ShiftRegisterConfig),
+}
+
+/// An element of the `gpio_expanders` attribute of a board config
+pub(crate) struct GpioExpanderConfig {
+    pub(crate) name: String,
+    pub(crate) model: GpioExpanderModelConfig,
+}
+
+impl TryFrom<&Kind> for GpioExpanderConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let model: String = value
+            .get("model")?
+            .ok_or(AttributeError::KeyNotFound("model".to_string()))?
+            .try_into()?;
+        let name: String = match value.get("name")? {
+            Some(name) => name.try_into()?,
+            None => model.clone(),
+        };
+        if name.is_empty() || name.contains(':') {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        let model = match model.as_str() {
+            "mcp23017" => GpioExpanderModelConfig::Mcp23017(value.try_into()?),
+            "pca9685" => GpioExpanderModelConfig::Pca9685(value.try_into()?),
+            "74hc595" => GpioExpanderModelConfig::ShiftRegister(value.try_into()?),
+            _ => return Err(AttributeError::ConversionImpossibleError),
+        };
+        Ok(GpioExpanderConfig { name, model })
+    }
+}
+
+fn build_expander(
+    model: GpioExpanderModelConfig,
+    board: &BoardType,
+) -> Result<GpioExpanderType, BoardError> {
+    Ok(match model {
+        GpioExpanderModelConfig::Mcp23017(conf) => {
+            let i2c_handle: I2cHandleType = board.get_i2c_by_name(conf.i2c_bus.clone())?;
+            let interrupt_line = conf.interrupt_pin.map(|pin| (board.clone(), pin));
+            Arc::new(Mutex::new(Mcp23017::new(
+                i2c_handle,
+                conf.i2c_address,
+                &conf.interrupt_pins,
+                interrupt_line,
+            )?))
+        }
+        GpioExpanderModelConfig::Pca9685(conf) => {
+            let i2c_handle = board.get_i2c_by_name(conf.i2c_bus.clone())?;
+            Arc::new(Mutex::new(Pca9685::new(
+                i2c_handle,
+                conf.i2c_address,
+                conf.frequency_hz,
+            )?))
+        }
+        GpioExpanderModelConfig::ShiftRegister(conf) => {
+            Arc::new(Mutex::new(ShiftRegister::new(board.clone(), &conf)?))
+        }
+    })
+}
+
+/// A board extended with GPIO expanders, pins within the range of an expander are handled by
+/// that expander while every other pin is handled by the wrapped board
+pub struct ExpandedBoard {
+    board: BoardType,
+    expanders: Vec<(String, GpioExpanderType)>,
+}
+
+impl ExpandedBoard {
+    pub fn new(board: BoardType, expanders: Vec<(String, GpioExpanderType)>) -> Self {
+        Self { board, expanders }
+    }
+
+    /// Wraps the board with the expanders declared in the `gpio_expanders` attribute of
+    /// its config, the board is returned as is when there are none
+    pub(crate) fn wrap_from_config(
+        board: BoardType,
+        cfg: ConfigType,
+    ) -> Result<BoardType, BoardError> {
+        let mut confs = match cfg.get_attribute::<Vec<GpioExpanderConfig>>("gpio_expanders") {
+            Ok(confs) => confs,
+            Err(AttributeError::KeyNotFound(key)) if key == "gpio_expanders" => vec![],
+            Err(_) => {
+                return Err(BoardError::BoardUnsupportedArgument(
+                    "invalid gpio_expanders config",
+                ))
+            }
+        };
+        // shorthand for a single PCA9685, addressed as `pca9685:<channel>`
+        match cfg.get_attribute::<Pca9685Config>("pca9685") {
+            Ok(conf) => confs.push(GpioExpanderConfig {
+                name: "pca9685".to_string(),
+                model: GpioExpanderModelConfig::Pca9685(conf),
+            }),
+            Err(AttributeError::KeyNotFound(key)) if key == "pca9685" => {}
+            Err(_) => {
+                return Err(BoardError::BoardUnsupportedArgument(
+                    "invalid pca9685 config",
+                ))
+            }
+        }
+        if confs.is_empty() {
+            return Ok(board);
+        }
+        let mut expanders = Vec::with_capacity(confs.len());
+        for conf in confs {
+            if expanders.iter().any(|(name, _)| name == &conf.name) {
+                return Err(BoardError::BoardUnsupportedArgument(
+                    "duplicate gpio expander name",
+                ));
+            }
+            let expander = build_expander(conf.model, &board)?;
+            expanders.push((conf.name, expander));
+        }
+        Ok(Arc::new(Mutex::new(Self::new(board, expanders))))
+    }
+
+    fn expander_for_pin(&self, pin: i32) -> Option<(&GpioExpanderType, u16)> {
+        if pin < EXPANDER_PIN_OFFSET {
+            return None;
+        }
+        let idx = ((pin - EXPANDER_PIN_OFFSET) / EXPANDER_PIN_STRIDE) as usize;
+        let expander_pin = ((pin - EXPANDER_PIN_OFFSET) % EXPANDER_PIN_STRIDE) as u16;
+        self.expanders
+            .get(idx)
+            .map(|(_, expander)| (expander, expander_pin))
+    }
+}
+
+impl Board for ExpandedBoard {
+    fn set_gpio_pin_level(&mut self, pin: i32, is_high: bool) -> Result<(), BoardError> {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander.lock().unwrap().set_gpio_pin_level(pin, is_high),
+            None => self.board.set_gpio_pin_level(pin, is_high),
+        }
+    }
+
+    fn get_board_status(&self) -> Result<common::v1::BoardStatus, BoardError> {
+        let mut status = self.board.get_board_status()?;
+        for (name, expander) in &self.expanders {
+            let mut expander = expander.lock().unwrap();
+            for pin in expander.interrupt_pins() {
+                let value = expander.get_digital_interrupt_value(pin)?;
+                status.digital_interrupts.insert(
+                    format!("{}:{}", name, pin),
+                    common::v1::DigitalInterruptStatus {
+                        value: value as i64,
+                    },
+                );
+            }
+        }
+        Ok(status)
+    }
+
+    fn get_gpio_level(&self, pin: i32) -> Result<bool, BoardError> {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander.lock().unwrap().get_gpio_level(pin),
+            None => self.board.get_gpio_level(pin),
+        }
+    }
+
+    fn get_analog_reader_by_name(&self, name: String) -> Result<AnalogReaderType<u16>, BoardError> {
+        self.board.get_analog_reader_by_name(name)
+    }
+
+    fn set_power_mode(
+        &self,
+        mode: component::board::v1::PowerMode,
+        duration: Option<Duration>,
+    ) -> Result<(), BoardError> {
+        self.board.set_power_mode(mode, duration)
+    }
+
+    fn set_power_mode_with_extra(
+        &self,
+        mode: component::board::v1::PowerMode,
+        duration: Option<Duration>,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), BoardError> {
+        self.board.set_power_mode_with_extra(mode, duration, extra)
+    }
+
+    fn get_i2c_by_name(&self, name: String) -> Result<I2cHandleType, BoardError> {
+        self.board.get_i2c_by_name(name)
+    }
+
+    fn get_digital_interrupt_value(&self, pin: i32) -> Result<u32, BoardError> {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander.lock().unwrap().get_digital_interrupt_value(pin),
+            None => self.board.get_digital_interrupt_value(pin),
+        }
+    }
+
+    fn get_digital_interrupt_ticks(
+        &self,
+        pin: i32,
+        since: u32,
+    ) -> Result<(Vec<Tick>, u32), BoardError> {
+        match self.expander_for_pin(pin) {
+            // expanders only count the events of their pins
+            Some(_) => Err(BoardError::BoardMethodNotSupported(
+                "get_digital_interrupt_ticks",
+            )),
+            None => self.board.get_digital_interrupt_ticks(pin, since),
+        }
+    }
+
+    fn get_pwm_duty(&self, pin: i32) -> f64 {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander.lock().unwrap().get_pwm_duty(pin),
+            None => self.board.get_pwm_duty(pin),
+        }
+    }
+
+    fn set_pwm_duty(&mut self, pin: i32, duty_cycle_pct: f64) -> Result<(), BoardError> {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander.lock().unwrap().set_pwm_duty(pin, duty_cycle_pct),
+            None => self.board.set_pwm_duty(pin, duty_cycle_pct),
+        }
+    }
+
+    fn get_pwm_frequency(&self, pin: i32) -> Result<u64, BoardError> {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander.lock().unwrap().get_pwm_frequency(pin),
+            None => self.board.get_pwm_frequency(pin),
+        }
+    }
+
+    fn set_pwm_frequency(&mut self, pin: i32, frequency_hz: u64) -> Result<(), BoardError> {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander
+                .lock()
+                .unwrap()
+                .set_pwm_frequency(pin, frequency_hz),
+            None => self.board.set_pwm_frequency(pin, frequency_hz),
+        }
+    }
+
+    fn get_pwm_frequency_hz(&self, pin: i32) -> Result<f64, BoardError> {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander
+                .lock()
+                .unwrap()
+                .get_pwm_frequency(pin)
+                .map(|frequency_hz| frequency_hz as f64),
+            None => self.board.get_pwm_frequency_hz(pin),
+        }
+    }
+
+    fn set_pwm_frequency_hz(&mut self, pin: i32, frequency_hz: f64) -> Result<(), BoardError> {
+        match self.expander_for_pin(pin) {
+            Some((expander, pin)) => expander
+                .lock()
+                .unwrap()
+                .set_pwm_frequency(pin, whole_pwm_frequency(frequency_hz)?),
+            None => self.board.set_pwm_frequency_hz(pin, frequency_hz),
+        }
+    }
+
+    fn pulse(&mut self, pin: i32, train: &PulseTrain) -> Result<(), BoardError> {
+        match self.expander_for_pin(pin) {
+            Some(_) => Err(BoardError::BoardMethodNotSupported("pulse")),
+            None => self.board.pulse(pin, train),
+        }
+    }
+
+    fn resolve_pin(&self, pin: &BoardPin) -> Result<i32, BoardError> {
+        match pin {
+            BoardPin::Gpio(pin) => Ok(*pin),
+            BoardPin::Expander(name, pin) => {
+                let (idx, (_, expander)) = self
+                    .expanders
+                    .iter()
+                    .enumerate()
+                    .find(|(_, (n, _))| n == name)
+                    .ok_or_else(|| BoardError::GpioExpanderNotFound(name.clone()))?;
+                if *pin >= expander.lock().unwrap().num_pins() {
+                    return Err(BoardError::BoardUnsupportedArgument(
+                        "gpio expander pin out of range",
+                    ));
+                }
+                Ok(EXPANDER_PIN_OFFSET + idx as i32 * EXPANDER_PIN_STRIDE + *pin as i32)
+            }
+        }
+    }
+}
+
+impl Status for ExpandedBoard {
+    fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
+        let mut status = self.board.get_status()?.unwrap_or_default();
+        // the interrupts of the board are reported along with the ones of the expanders
+        let interrupts = common::v1::BoardStatus {
+            digital_interrupts: self
+                .get_board_status()
+                .unwrap_or_default()
+                .digital_interrupts,
+            ..Default::default()
+        };
+        status
+            .fields
+            .extend(board_status_to_struct(&interrupts, &[]).fields);
+        Ok(Some(status))
+    }
+}
+
+impl DoCommand for ExpandedBoard {
+    fn do_command(
+        &mut self,
+        command_struct: Option<google::protobuf::Struct>,
+    ) -> Result<Option<google::protobuf::Struct>, GenericError> {
+        self.board.do_command(command_struct)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ExpandedBoard, GpioExpander, GpioExpanderConfig, GpioExpanderModelConfig, GpioExpanderType,
+        EXPANDER_PIN_OFFSET,
+    };
+    use crate::common::board::{Board, BoardError, BoardPin, BoardType, FakeBoard};
+    use crate::common::config::Kind;
+    use crate::common::status::Status;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct FakeExpander {
+        levels: [bool; 8],
+        interrupt_count: u32,
+    }
+
+    impl GpioExpander for FakeExpander {
+        fn num_pins(&self) -> u16 {
+            8
+        }
+        fn set_gpio_pin_level(&mut self, pin: u16, is_high: bool) -> Result<(), BoardError> {
+            self.levels[pin as usize] = is_high;
+            Ok(())
+        }
+        fn get_gpio_level(&mut self, pin: u16) -> Result<bool, BoardError> {
+            Ok(self.levels[pin as usize])
+        }
+        fn interrupt_pins(&self) -> Vec<u16> {
+            vec![2]
+        }
+        fn get_digital_interrupt_value(&mut self, _pin: u16) -> Result<u32, BoardError> {
+            self.interrupt_count += 1;
+            Ok(self.interrupt_count)
+        }
+    }
+
+    #[test_log::test]
+    fn test_board_pin_names() {
+        let pin = BoardPin::try_from(&Kind::StringValue("mcp:7".to_string()));
+        assert_eq!(pin, Ok(BoardPin::Expander("mcp".to_string(), 7)));
+        let pin = BoardPin::try_from(&Kind::StringValue("12".to_string()));
+        assert_eq!(pin, Ok(BoardPin::Gpio(12)));
+        let pin = BoardPin::try_from(&Kind::NumberValue(12.0));
+        assert_eq!(pin, Ok(BoardPin::Gpio(12)));
+        assert!(BoardPin::try_from(&Kind::StringValue(":7".to_string())).is_err());
+        assert!(BoardPin::try_from(&Kind::StringValue("mcp:a".to_string())).is_err());
+    }
+
+    #[test_log::test]
+    fn test_expander_default_name() {
+        let conf = GpioExpanderConfig::try_from(&Kind::StructValue(HashMap::from([
+            (
+                "model".to_string(),
+                Kind::StringValue("pca9685".to_string()),
+            ),
+            ("i2c_bus".to_string(), Kind::StringValue("i2c0".to_string())),
+        ])))
+        .unwrap();
+        assert_eq!(conf.name, "pca9685");
+        assert!(matches!(conf.model, GpioExpanderModelConfig::Pca9685(_)));
+
+        let conf = GpioExpanderConfig::try_from(&Kind::StructValue(HashMap::from([
+            ("name".to_string(), Kind::StringValue("pwm".to_string())),
+            (
+                "model".to_string(),
+                Kind::StringValue("pca9685".to_string()),
+            ),
+            ("i2c_bus".to_string(), Kind::StringValue("i2c0".to_string())),
+        ])))
+        .unwrap();
+        assert_eq!(conf.name, "pwm");
+    }
+
+    #[test_log::test]
+    fn test_expanded_board() -> Result<(), BoardError> {
+        let fake_board: BoardType = Arc::new(Mutex::new(FakeBoard::new(vec![])));
+        let first: GpioExpanderType = Arc::new(Mutex::new(FakeExpander::default()));
+        let second: GpioExpanderType = Arc::new(Mutex::new(FakeExpander::default()));
+        let mut board = ExpandedBoard::new(
+            fake_board.clone(),
+            vec![
+                ("first".to_string(), first.clone()),
+                ("second".to_string(), second.clone()),
+            ],
+        );
+
+        assert_eq!(board.resolve_pin(&BoardPin::Gpio(4))?, 4);
+        let pin = board.resolve_pin(&BoardPin::Expander("second".to_string(), 3))?;
+        assert_eq!(pin, EXPANDER_PIN_OFFSET + 103);
+        assert!(matches!(
+            board.resolve_pin(&BoardPin::Expander("second".to_string(), 8)),
+            Err(BoardError::BoardUnsupportedArgument(_))
+        ));
+        assert!(matches!(
+            board.resolve_pin(&BoardPin::Expander("third".to_string(), 0)),
+            Err(BoardError::GpioExpanderNotFound(_))
+        ));
+
+        board.set_gpio_pin_level(pin, true)?;
+        assert!(board.get_gpio_level(pin)?);
+        assert!(second.lock().unwrap().get_gpio_level(3)?);
+        assert!(!first.lock().unwrap().get_gpio_level(3)?);
+
+        // pins outside of the expanders are handled by the wrapped board
+        board.set_pwm_duty(3, 0.25)?;
+        assert_eq!(fake_board.get_pwm_duty(3), 0.25);
+        // expanders without PWM support
+        assert!(board.set_pwm_duty(pin, 0.25).is_err());
+
+        let status = board.get_board_status()?;
+        assert_eq!(status.digital_interrupts.len(), 2);
+        assert_eq!(
+            status
+                .digital_interrupts
+                .get("first:2")
+                .map(|status| status.value),
+            Some(1)
+        );
+        let status = board.get_status().unwrap().unwrap();
+        assert!(status.fields.contains_key("digital_interrupts"));
+        Ok(())
+    }
+}
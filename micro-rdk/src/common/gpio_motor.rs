@@ -37,7 +37,6 @@
 //! ```
 //!
 
-use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -45,16 +44,16 @@ use super::actuator::{Actuator, ActuatorError};
 use super::board::{Board, BoardType};
 use super::config::ConfigType;
 use super::encoder::{
-    Encoder, EncoderPositionType, EncoderType, COMPONENT_NAME as EncoderCompName,
+    Encoder, EncoderError, EncoderPositionType, EncoderType, COMPONENT_NAME as EncoderCompName,
 };
 use super::math_utils::go_for_math;
 use super::motor::{
-    Motor, MotorError, MotorPinType, MotorPinsConfig, MotorSupportedProperties, MotorType,
-    COMPONENT_NAME as MotorCompName,
+    GoForTarget, Motor, MotorError, MotorPinType, MotorPinsConfig, MotorSupportedProperties,
+    MotorType, COMPONENT_NAME as MotorCompName,
 };
 use super::registry::{get_board_from_dependencies, ComponentRegistry, Dependency, ResourceKey};
 use super::robot::Resource;
-use super::status::Status;
+use super::status::{status_envelope, ComponentHealth, Status};
 use crate::common::status::StatusError;
 
 use crate::google;
@@ -123,10 +122,14 @@ pub(crate) fn gpio_motor_from_config(
 // of forcing the user to supply a PWM frequency in the motor config)
 const MOTOR_PWM_FREQUENCY: u64 = 1000;
 
+/// A motor whose position is read from an encoder. While a `go_for` is in progress its
+/// status reports `go_for_progress`, the fraction (between `0.0` and `1.0`) of the requested
+/// revolutions covered so far, when the encoder knows its ticks per rotation.
 #[derive(DoCommand)]
 pub struct EncodedMotor<M, Enc> {
     motor: M,
     enc: Enc,
+    go_for_target: Option<GoForTarget>,
 }
 
 impl<M, Enc> EncodedMotor<M, Enc>
@@ -135,7 +138,19 @@ where
     Enc: Encoder,
 {
     pub fn new(motor: M, enc: Enc) -> Self {
-        Self { motor, enc }
+        Self {
+            motor,
+            enc,
+            go_for_target: None,
+        }
+    }
+
+    fn ticks(&self) -> Result<f64, EncoderError> {
+        Ok(self.enc.get_position(EncoderPositionType::TICKS)?.value as f64)
+    }
+
+    fn go_for_progress(&self) -> Option<f64> {
+        Some(self.go_for_target?.progress(self.ticks().ok()?))
     }
 }
 
@@ -153,16 +168,43 @@ where
 
     /// Accepts percentage as a float, e.g. `0.5` equals `50%` power.
     fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
-        self.motor.set_power(pct)
+        self.set_power_with_extra(pct, None)
+    }
+    fn set_power_with_extra(
+        &mut self,
+        pct: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), MotorError> {
+        self.go_for_target = None;
+        self.motor.set_power_with_extra(pct, extra)
     }
     fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
-        self.motor.go_for(rpm, revolutions)
+        self.go_for_with_extra(rpm, revolutions, None)
+    }
+    fn go_for_with_extra(
+        &mut self,
+        rpm: f64,
+        revolutions: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<Option<Duration>, MotorError> {
+        self.go_for_target = None;
+        let dur = self.motor.go_for_with_extra(rpm, revolutions, extra)?;
+        if let (Some(ticks_per_rotation), Ok(start)) = (self.enc.ticks_per_rotation(), self.ticks())
+        {
+            self.go_for_target = GoForTarget::new(start, rpm, revolutions, ticks_per_rotation);
+        }
+        Ok(dur)
     }
     fn get_properties(&mut self) -> MotorSupportedProperties {
         MotorSupportedProperties {
             position_reporting: true,
+            ticks_per_rotation: self.enc.ticks_per_rotation(),
+            ..self.motor.get_properties()
         }
     }
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        self.motor.is_powered()
+    }
 }
 
 impl<M, Enc> Actuator for EncodedMotor<M, Enc>
@@ -174,7 +216,14 @@ where
         self.motor.is_moving()
     }
     fn stop(&mut self) -> Result<(), ActuatorError> {
-        self.motor.stop()
+        self.stop_with_extra(None)
+    }
+    fn stop_with_extra(
+        &mut self,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), ActuatorError> {
+        self.go_for_target = None;
+        self.motor.stop_with_extra(extra)
     }
 }
 
@@ -184,18 +233,26 @@ where
     Enc: Encoder,
 {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        let mut hm = HashMap::new();
+        let mut status = self.motor.get_status()?.unwrap_or_default();
         let pos = self
             .enc
             .get_position(EncoderPositionType::UNSPECIFIED)?
             .value as f64;
-        hm.insert(
+        status.fields.insert(
             "position".to_string(),
             google::protobuf::Value {
                 kind: Some(google::protobuf::value::Kind::NumberValue(pos)),
             },
         );
-        Ok(Some(google::protobuf::Struct { fields: hm }))
+        if let Some(progress) = self.go_for_progress() {
+            status.fields.insert(
+                "go_for_progress".to_string(),
+                google::protobuf::Value {
+                    kind: Some(google::protobuf::value::Kind::NumberValue(progress)),
+                },
+            );
+        }
+        Ok(Some(status))
     }
 }
 
@@ -208,6 +265,9 @@ pub(crate) struct PwmABMotor<B> {
     pwm_pin: i32,
     max_rpm: f64,
     dir_flip: bool,
+    // last power applied, the duty cycle read back from the board is unsigned
+    power: f64,
+    health: ComponentHealth,
 }
 
 impl<B> PwmABMotor<B>
@@ -229,6 +289,8 @@ where
             pwm_pin,
             max_rpm,
             dir_flip,
+            power: 0.0,
+            health: ComponentHealth::new(),
         };
         // we start with this because we want to reserve a timer and PWM channel early
         // for boards where these are a limited resource
@@ -245,6 +307,23 @@ where
         r_keys
     }
 
+    fn apply_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&pct) {
+            return Err(MotorError::PowerSetError);
+        }
+        let set_forwards = (pct > 0.0) && !self.dir_flip;
+        if set_forwards {
+            self.board.set_gpio_pin_level(self.a_pin, false)?;
+            self.board.set_gpio_pin_level(self.b_pin, true)?;
+        } else {
+            self.board.set_gpio_pin_level(self.a_pin, true)?;
+            self.board.set_gpio_pin_level(self.b_pin, false)?;
+        }
+        self.board.set_pwm_duty(self.pwm_pin, pct)?;
+        self.power = pct;
+        Ok(())
+    }
+
     pub(crate) fn from_config(cfg: ConfigType, board: BoardType) -> Result<MotorType, MotorError> {
         let pins =
             cfg.get_attribute::<MotorPinsConfig>("pins")
@@ -261,6 +340,9 @@ where
         let pwm_pin = pins
             .pwm
             .ok_or(MotorError::ConfigError("PwmABMotor, need 'pwm' pin"))?;
+        let a_pin = board.resolve_pin(&a_pin)?;
+        let b_pin = board.resolve_pin(&b_pin)?;
+        let pwm_pin = board.resolve_pin(&pwm_pin)?;
         let max_rpm: f64 = cfg.get_attribute::<f64>("max_rpm").unwrap_or(100.0);
         let dir_flip: bool = cfg.get_attribute::<bool>("dir_flip").unwrap_or_default();
 
@@ -275,19 +357,9 @@ where
     B: Board,
 {
     fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
-        if !(-1.0..=1.0).contains(&pct) {
-            return Err(MotorError::PowerSetError);
-        }
-        let set_forwards = (pct > 0.0) && !self.dir_flip;
-        if set_forwards {
-            self.board.set_gpio_pin_level(self.a_pin, false)?;
-            self.board.set_gpio_pin_level(self.b_pin, true)?;
-        } else {
-            self.board.set_gpio_pin_level(self.a_pin, true)?;
-            self.board.set_gpio_pin_level(self.b_pin, false)?;
-        }
-        self.board.set_pwm_duty(self.pwm_pin, pct)?;
-        Ok(())
+        let res = self.apply_power(pct);
+        self.health.record(&res);
+        res
     }
 
     fn get_position(&mut self) -> Result<i32, MotorError> {
@@ -310,8 +382,14 @@ where
     fn get_properties(&mut self) -> MotorSupportedProperties {
         MotorSupportedProperties {
             position_reporting: false,
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: None,
         }
     }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
 }
 
 impl<B> Status for PwmABMotor<B>
@@ -319,15 +397,10 @@ where
     B: Board,
 {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        let mut hm = HashMap::new();
-        let pos = 0.0;
-        hm.insert(
-            "position".to_string(),
-            google::protobuf::Value {
-                kind: Some(google::protobuf::value::Kind::NumberValue(pos)),
-            },
-        );
-        Ok(Some(google::protobuf::Struct { fields: hm }))
+        Ok(Some(status_envelope(
+            &self.health,
+            [("position", google::protobuf::value::Kind::NumberValue(0.0))],
+        )))
     }
 }
 
@@ -351,6 +424,9 @@ pub(crate) struct PwmDirectionMotor<B> {
     pwm_pin: i32,
     max_rpm: f64,
     dir_flip: bool,
+    // last power applied, the duty cycle read back from the board is unsigned
+    power: f64,
+    health: ComponentHealth,
 }
 
 impl<B> PwmDirectionMotor<B>
@@ -370,6 +446,8 @@ where
             pwm_pin,
             max_rpm,
             dir_flip,
+            power: 0.0,
+            health: ComponentHealth::new(),
         };
         // we start with this because we want to reserve a timer and PWM channel early
         // for boards where these are a limited resource
@@ -377,6 +455,17 @@ where
         Ok(res)
     }
 
+    fn apply_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&pct) {
+            return Err(MotorError::MissingEncoder);
+        }
+        let set_high = (pct > 0.0) && !self.dir_flip;
+        self.board.set_gpio_pin_level(self.dir_pin, set_high)?;
+        self.board.set_pwm_duty(self.pwm_pin, pct)?;
+        self.power = pct;
+        Ok(())
+    }
+
     pub(crate) fn from_config(cfg: ConfigType, board: BoardType) -> Result<MotorType, MotorError> {
         let pins =
             cfg.get_attribute::<MotorPinsConfig>("pins")
@@ -389,6 +478,8 @@ where
         let pwm_pin = pins
             .pwm
             .ok_or(MotorError::ConfigError("PwmDirectionMotor, need 'pwm' pin"))?;
+        let dir_pin = board.resolve_pin(&dir_pin)?;
+        let pwm_pin = board.resolve_pin(&pwm_pin)?;
         let max_rpm: f64 = cfg.get_attribute::<f64>("max_rpm").unwrap_or(100.0);
         let dir_flip: bool = cfg.get_attribute::<bool>("dir_flip").unwrap_or_default();
         Ok(Arc::new(Mutex::new(PwmDirectionMotor::new(
@@ -402,13 +493,9 @@ where
     B: Board,
 {
     fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
-        if !(-1.0..=1.0).contains(&pct) {
-            return Err(MotorError::MissingEncoder);
-        }
-        let set_high = (pct > 0.0) && !self.dir_flip;
-        self.board.set_gpio_pin_level(self.dir_pin, set_high)?;
-        self.board.set_pwm_duty(self.pwm_pin, pct)?;
-        Ok(())
+        let res = self.apply_power(pct);
+        self.health.record(&res);
+        res
     }
 
     fn get_position(&mut self) -> Result<i32, MotorError> {
@@ -428,8 +515,14 @@ where
     fn get_properties(&mut self) -> MotorSupportedProperties {
         MotorSupportedProperties {
             position_reporting: false,
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: None,
         }
     }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
 }
 
 impl<B> Status for PwmDirectionMotor<B>
@@ -437,15 +530,10 @@ where
     B: Board,
 {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        let mut hm = HashMap::new();
-        let pos = 0.0;
-        hm.insert(
-            "position".to_string(),
-            google::protobuf::Value {
-                kind: Some(google::protobuf::value::Kind::NumberValue(pos)),
-            },
-        );
-        Ok(Some(google::protobuf::Struct { fields: hm }))
+        Ok(Some(status_envelope(
+            &self.health,
+            [("position", google::protobuf::value::Kind::NumberValue(0.0))],
+        )))
     }
 }
 
@@ -474,6 +562,9 @@ pub(crate) struct AbMotor<B> {
     dir_flip: bool,
     is_on: bool,
     pwm_pin: i32,
+    // last power applied, the duty cycle read back from the board is unsigned
+    power: f64,
+    health: ComponentHealth,
 }
 
 impl<B> AbMotor<B>
@@ -495,6 +586,8 @@ where
             dir_flip,
             is_on: false,
             pwm_pin: a_pin,
+            power: 0.0,
+            health: ComponentHealth::new(),
         };
         // we start with this because we want to reserve a timer and PWM channel early
         // for boards where these are a limited resource
@@ -503,6 +596,30 @@ where
         Ok(res)
     }
 
+    fn apply_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&pct) {
+            return Err(MotorError::PowerSetError);
+        }
+        if pct.abs() <= 0.001 {
+            return Ok(self.stop()?);
+        }
+        let (pwm_pin, high_pin) = if (pct >= 0.001) == self.dir_flip {
+            (self.b_pin, self.a_pin)
+        } else {
+            (self.a_pin, self.b_pin)
+        };
+        if pwm_pin != self.pwm_pin {
+            self.board.set_pwm_frequency(pwm_pin, MOTOR_PWM_FREQUENCY)?;
+            self.board.set_pwm_frequency(self.pwm_pin, 0)?;
+        }
+        self.pwm_pin = pwm_pin;
+        self.board.set_gpio_pin_level(high_pin, true)?;
+        self.board.set_pwm_duty(pwm_pin, pct)?;
+        self.is_on = true;
+        self.power = pct;
+        Ok(())
+    }
+
     pub(crate) fn from_config(cfg: ConfigType, board: BoardType) -> Result<MotorType, MotorError> {
         let pins =
             cfg.get_attribute::<MotorPinsConfig>("pins")
@@ -515,6 +632,8 @@ where
         let b_pin = pins
             .b
             .ok_or(MotorError::ConfigError("ABMotor, need 'b' pin"))?;
+        let a_pin = board.resolve_pin(&a_pin)?;
+        let b_pin = board.resolve_pin(&b_pin)?;
         let max_rpm: f64 = cfg.get_attribute::<f64>("max_rpm").unwrap_or(100.0);
         let dir_flip: bool = cfg.get_attribute::<bool>("dir_flip").unwrap_or_default();
         Ok(Arc::new(Mutex::new(AbMotor::new(
@@ -528,26 +647,9 @@ where
     B: Board,
 {
     fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
-        if !(-1.0..=1.0).contains(&pct) {
-            return Err(MotorError::PowerSetError);
-        }
-        if pct.abs() <= 0.001 {
-            return Ok(self.stop()?);
-        }
-        let (pwm_pin, high_pin) = if (pct >= 0.001) == self.dir_flip {
-            (self.b_pin, self.a_pin)
-        } else {
-            (self.a_pin, self.b_pin)
-        };
-        if pwm_pin != self.pwm_pin {
-            self.board.set_pwm_frequency(pwm_pin, MOTOR_PWM_FREQUENCY)?;
-            self.board.set_pwm_frequency(self.pwm_pin, 0)?;
-        }
-        self.pwm_pin = pwm_pin;
-        self.board.set_gpio_pin_level(high_pin, true)?;
-        self.board.set_pwm_duty(pwm_pin, pct)?;
-        self.is_on = true;
-        Ok(())
+        let res = self.apply_power(pct);
+        self.health.record(&res);
+        res
     }
 
     fn get_position(&mut self) -> Result<i32, MotorError> {
@@ -566,8 +668,14 @@ where
     fn get_properties(&mut self) -> MotorSupportedProperties {
         MotorSupportedProperties {
             position_reporting: false,
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: None,
         }
     }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.is_on, self.power))
+    }
 }
 
 impl<B> Status for AbMotor<B>
@@ -575,15 +683,10 @@ where
     B: Board,
 {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        let mut hm = HashMap::new();
-        let pos = 0.0;
-        hm.insert(
-            "position".to_string(),
-            google::protobuf::Value {
-                kind: Some(google::protobuf::value::Kind::NumberValue(pos)),
-            },
-        );
-        Ok(Some(google::protobuf::Struct { fields: hm }))
+        Ok(Some(status_envelope(
+            &self.health,
+            [("position", google::protobuf::value::Kind::NumberValue(0.0))],
+        )))
     }
 }
 
@@ -600,6 +703,43 @@ where
         self.board.set_gpio_pin_level(self.a_pin, false)?;
         self.board.set_gpio_pin_level(self.b_pin, false)?;
         self.is_on = false;
+        self.power = 0.0;
         Ok(())
     }
 }
+
+#[cfg(feature = "builtin-components")]
+#[cfg(test)]
+mod tests {
+    use crate::common::actuator::Actuator;
+    use crate::common::encoder::FakeEncoder;
+    use crate::common::gpio_motor::EncodedMotor;
+    use crate::common::motor::{FakeMotor, Motor};
+
+    #[test_log::test]
+    fn test_encoded_motor_go_for_progress() {
+        let mut enc = FakeEncoder::new();
+        enc.ticks_per_rotation = 100;
+        let mut motor = EncodedMotor::new(FakeMotor::new(), enc);
+        assert_eq!(motor.go_for_progress(), None);
+
+        assert!(motor.go_for(50.0, 2.0).is_ok());
+        assert_eq!(motor.go_for_progress(), Some(0.0));
+        motor.enc.angle_degrees = 360.0;
+        assert_eq!(motor.go_for_progress(), Some(0.5));
+        motor.enc.angle_degrees = 1080.0;
+        assert_eq!(motor.go_for_progress(), Some(1.0));
+
+        // going backwards counts the ticks down
+        assert!(motor.go_for(-50.0, 1.0).is_ok());
+        motor.enc.angle_degrees = 900.0;
+        assert_eq!(motor.go_for_progress(), Some(0.5));
+
+        assert!(motor.stop().is_ok());
+        assert_eq!(motor.go_for_progress(), None);
+
+        // an indefinite go_for has no progress to report
+        assert!(motor.go_for(50.0, 0.0).is_ok());
+        assert_eq!(motor.go_for_progress(), None);
+    }
+}
@@ -14,6 +14,7 @@
 //!     max_period_us: 2500,
 //!     pwm_resolution: 0,
 //!     frequency: 300,
+//!     continuous_neutral_us: None,
 //! }
 //!
 //! let mut servo = GpioServo::new(board, 12, servo_settings);
@@ -21,17 +22,30 @@
 //! servo.move_to(90).unwrap()
 //!
 //! ```
+//!
+//! # Continuous rotation servos
+//!
+//! With `continuous: true` the pulse width sets the speed of the servo instead of its position:
+//! `neutral_width_us` (the middle of `min_width_us` and `max_width_us` by default) stops the
+//! servo, `max_width_us` is full speed forward and `min_width_us` full speed in reverse. The
+//! speed is set as a signed percentage of the maximum with the `set_speed_pct` command
+//! (`{"set_speed_pct": -50}`) and read back with `get_speed_pct`. `move_to` maps the angle range
+//! onto the speed range, the middle angle stopping the servo, and `stop` sends the neutral
+//! pulse rather than turning the PWM off so the servo holds still.
 
 use crate::common::status::StatusError;
+use crate::google::protobuf::{value::Kind, Struct};
 use std::sync::{Arc, Mutex};
 
 use super::{
     actuator::{Actuator, ActuatorError},
-    board::{Board, BoardType},
-    config::ConfigType,
+    board::{Board, BoardPin, BoardType},
+    config::{AttributeError, ConfigType},
+    generic::{DoCommand, GenericError},
     registry::{get_board_from_dependencies, ComponentRegistry, Dependency},
     servo::{Servo, ServoError, ServoType},
-    status::Status,
+    status::{status_envelope, ComponentHealth, Status},
+    struct_builder::StructBuilder,
 };
 
 /// Minimum and maximum period widths that should be safe limits for
@@ -61,7 +75,7 @@ pub(crate) fn from_config(
         ServoError::ServoConfigurationError("missing board attribute"),
     )?;
     let servo_settings = GpioServoSettings::from_config(&cfg)?;
-    let pin = cfg.get_attribute::<i32>("pin")?;
+    let pin = board.resolve_pin(&cfg.get_attribute::<BoardPin>("pin")?)?;
     Ok(Arc::new(Mutex::new(GpioServo::<BoardType>::new(
         board.clone(),
         pin,
@@ -79,6 +93,8 @@ pub(crate) struct GpioServoSettings {
     /// when 0, pwm_resolution is not considered when calculating the PWM duty cycle
     /// necessary to move the servo to particular angular position
     pub pwm_resolution: u32,
+    /// pulse width stopping a continuous rotation servo, `None` for positional servos
+    pub continuous_neutral_us: Option<u32>,
 }
 
 impl GpioServoSettings {
@@ -101,6 +117,21 @@ impl GpioServoSettings {
         let pwm_resolution = cfg
             .get_attribute::<u32>("pwm_resolution")
             .unwrap_or_default();
+        let continuous_neutral_us = if cfg.get_attribute::<bool>("continuous").unwrap_or(false) {
+            let neutral_us = match cfg.get_attribute::<u32>("neutral_width_us") {
+                Ok(neutral_us) => neutral_us,
+                Err(AttributeError::KeyNotFound(_)) => (min_period_us + max_period_us) / 2,
+                Err(err) => return Err(err.into()),
+            };
+            if neutral_us <= min_period_us || neutral_us >= max_period_us {
+                return Err(ServoError::ServoConfigurationError(
+                    "neutral_width_us should be between min_width_us and max_width_us",
+                ));
+            }
+            Some(neutral_us)
+        } else {
+            None
+        };
         Ok(Self {
             min_angle_deg,
             max_angle_deg,
@@ -108,11 +139,11 @@ impl GpioServoSettings {
             max_period_us,
             frequency,
             pwm_resolution,
+            continuous_neutral_us,
         })
     }
 }
 
-#[derive(DoCommand)]
 pub struct GpioServo<B> {
     board: B,
     pin: i32,
@@ -122,6 +153,8 @@ pub struct GpioServo<B> {
     max_period_us: u32,
     frequency: u32,
     pwm_resolution: u32,
+    continuous_neutral_us: Option<u32>,
+    health: ComponentHealth,
 }
 
 impl<B> GpioServo<B>
@@ -143,11 +176,79 @@ where
             max_period_us: settings.max_period_us,
             frequency: settings.frequency,
             pwm_resolution: settings.pwm_resolution,
+            continuous_neutral_us: settings.continuous_neutral_us,
+            health: ComponentHealth::new(),
         };
         res.board.set_pwm_frequency(pin, res.frequency as u64)?;
         Ok(res)
     }
 
+    fn width_us_to_duty_pct(&self, width_us: f64) -> f64 {
+        width_us * self.frequency as f64 / 1000000.0
+    }
+
+    fn set_duty_pct(&mut self, mut duty_cycle_pct: f64) -> Result<(), ServoError> {
+        if self.pwm_resolution != 0 {
+            let real_tick = (duty_cycle_pct * (self.pwm_resolution as f64)).round();
+            duty_cycle_pct = real_tick / (self.pwm_resolution as f64);
+        }
+        let res = self.board.set_pwm_duty(self.pin, duty_cycle_pct);
+        self.health.record(&res);
+        Ok(res?)
+    }
+
+    /// Sets the speed of a continuous rotation servo, as a percentage of its maximum speed
+    /// clamped to [-100, 100], negative speeds turning in reverse
+    pub fn set_speed_pct(&mut self, speed_pct: f64) -> Result<(), ServoError> {
+        let neutral_us = self
+            .continuous_neutral_us
+            .ok_or(ServoError::ServoConfigurationError(
+                "speed control requires a continuous rotation servo",
+            ))? as f64;
+        let speed_pct = speed_pct.clamp(-100.0, 100.0);
+        let end_us = if speed_pct >= 0.0 {
+            self.max_period_us
+        } else {
+            self.min_period_us
+        } as f64;
+        let width_us = neutral_us + (end_us - neutral_us) * speed_pct.abs() / 100.0;
+        self.set_duty_pct(self.width_us_to_duty_pct(width_us))
+    }
+
+    /// Speed of a continuous rotation servo, 0 when its PWM is off
+    pub fn speed_pct(&self) -> Result<f64, ServoError> {
+        let neutral_us = self
+            .continuous_neutral_us
+            .ok_or(ServoError::ServoConfigurationError(
+                "speed control requires a continuous rotation servo",
+            ))? as f64;
+        let duty_pct = self.board.get_pwm_duty(self.pin);
+        if duty_pct == 0.0 {
+            return Ok(0.0);
+        }
+        let width_us = (duty_pct * 1000000.0 / self.frequency as f64)
+            .clamp(self.min_period_us as f64, self.max_period_us as f64);
+        let end_us = if width_us >= neutral_us {
+            self.max_period_us
+        } else {
+            self.min_period_us
+        } as f64;
+        Ok((width_us - neutral_us) / (end_us - neutral_us).abs() * 100.0)
+    }
+
+    // maps the angle range onto the speed range of a continuous rotation servo
+    fn angle_to_speed_pct(&self, angle_deg: u32) -> f64 {
+        let half_range = (self.max_angle_deg - self.min_angle_deg) as f64 / 2.0;
+        let middle = self.min_angle_deg as f64 + half_range;
+        (angle_deg as f64 - middle) / half_range * 100.0
+    }
+
+    fn speed_pct_to_angle(&self, speed_pct: f64) -> u32 {
+        let half_range = (self.max_angle_deg - self.min_angle_deg) as f64 / 2.0;
+        let middle = self.min_angle_deg as f64 + half_range;
+        (middle + speed_pct / 100.0 * half_range).round() as u32
+    }
+
     pub fn angle_to_duty_pct(&self, angle_deg: u32) -> f64 {
         let period = 1.0 / (self.frequency as f64);
         let angle_range = (self.max_angle_deg - self.min_angle_deg) as f64;
@@ -180,15 +281,15 @@ where
     // values
     fn move_to(&mut self, angle_deg: u32) -> Result<(), ServoError> {
         let angle_deg = angle_deg.clamp(self.min_angle_deg, self.max_angle_deg);
-        let mut duty_cycle_pct = self.angle_to_duty_pct(angle_deg);
-        if self.pwm_resolution != 0 {
-            let real_tick = (duty_cycle_pct * (self.pwm_resolution as f64)).round();
-            duty_cycle_pct = real_tick / (self.pwm_resolution as f64);
+        if self.continuous_neutral_us.is_some() {
+            return self.set_speed_pct(self.angle_to_speed_pct(angle_deg));
         }
-        self.board.set_pwm_duty(self.pin, duty_cycle_pct)?;
-        Ok(())
+        self.set_duty_pct(self.angle_to_duty_pct(angle_deg))
     }
     fn get_position(&mut self) -> Result<u32, ServoError> {
+        if self.continuous_neutral_us.is_some() {
+            return Ok(self.speed_pct_to_angle(self.speed_pct()?));
+        }
         let duty_pct = self.board.get_pwm_duty(self.pin);
         Ok(self.duty_pct_to_angle(duty_pct))
     }
@@ -199,27 +300,87 @@ where
     B: Board,
 {
     fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        if self.continuous_neutral_us.is_some() {
+            // the neutral pulse may not be exactly reachable with the PWM resolution
+            return Ok(self
+                .speed_pct()
+                .map_err(|_| ActuatorError::CouldntStop)?
+                .abs()
+                >= 1.0);
+        }
         Ok(self.board.get_pwm_duty(self.pin) != 0.0)
     }
     fn stop(&mut self) -> Result<(), ActuatorError> {
+        if self.continuous_neutral_us.is_some() {
+            return self.set_speed_pct(0.0).map_err(|err| match err {
+                ServoError::ServoBoardError(err) => err.into(),
+                _ => ActuatorError::CouldntStop,
+            });
+        }
         Ok(self.board.set_pwm_duty(self.pin, 0.0)?)
     }
 }
 
+impl<B> DoCommand for GpioServo<B>
+where
+    B: Board,
+{
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        if let Some(value) = command.fields.get("set_speed_pct") {
+            let speed_pct = match value.kind {
+                Some(Kind::NumberValue(speed_pct)) => speed_pct,
+                _ => return Err(GenericError::InvalidArgument("set_speed_pct")),
+            };
+            self.set_speed_pct(speed_pct)
+                .map_err(|err| GenericError::Other(err.into()))?;
+            return Ok(None);
+        }
+        if command.fields.contains_key("get_speed_pct") {
+            let speed_pct = self
+                .speed_pct()
+                .map_err(|err| GenericError::Other(err.into()))?;
+            return Ok(Some(
+                StructBuilder::with_capacity(1)
+                    .field("speed_pct", speed_pct)
+                    .build(),
+            ));
+        }
+        Err(GenericError::MethodUnimplemented("do_command"))
+    }
+}
+
 impl<B> Status for GpioServo<B>
 where
     B: Board,
 {
     fn get_status(&self) -> Result<Option<crate::google::protobuf::Struct>, StatusError> {
-        Ok(None)
+        if let Ok(speed_pct) = self.speed_pct() {
+            return Ok(Some(status_envelope(
+                &self.health,
+                [("speed_pct", Kind::NumberValue(speed_pct))],
+            )));
+        }
+        let position_deg = self.duty_pct_to_angle(self.board.get_pwm_duty(self.pin));
+        Ok(Some(status_envelope(
+            &self.health,
+            [("position_deg", Kind::NumberValue(position_deg as f64))],
+        )))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::common::actuator::Actuator;
     use crate::common::board::{Board, FakeBoard};
+    use crate::common::generic::DoCommand;
     use crate::common::gpio_servo::{GpioServo, GpioServoSettings};
     use crate::common::servo::{Servo, ServoError};
+    use crate::common::struct_builder::StructBuilder;
+    use crate::google::protobuf::value::Kind;
     use std::sync::{Arc, Mutex};
 
     #[test_log::test]
@@ -232,6 +393,7 @@ mod tests {
             max_period_us: 2500,
             frequency: 300,
             pwm_resolution: 0,
+            continuous_neutral_us: None,
         };
         let mut servo = GpioServo::new(board.clone(), 2, servo_settings)?;
 
@@ -262,6 +424,7 @@ mod tests {
             max_period_us: 2500,
             frequency: 300,
             pwm_resolution: 0,
+            continuous_neutral_us: None,
         };
         let mut servo = GpioServo::new(board.clone(), 2, servo_settings)?;
 
@@ -286,6 +449,7 @@ mod tests {
             max_period_us: 2500,
             frequency: 300,
             pwm_resolution: 10,
+            continuous_neutral_us: None,
         };
         let mut servo = GpioServo::new(board.clone(), 2, servo_settings)?;
 
@@ -300,4 +464,51 @@ mod tests {
         assert_eq!(board.get_pwm_duty(2), 0.8);
         Ok(())
     }
+
+    #[test_log::test]
+    fn test_continuous_rotation() -> Result<(), ServoError> {
+        let board = Arc::new(Mutex::new(FakeBoard::new(vec![])));
+        let servo_settings = GpioServoSettings {
+            min_angle_deg: 0,
+            max_angle_deg: 180,
+            min_period_us: 1000,
+            max_period_us: 2000,
+            frequency: 50,
+            pwm_resolution: 0,
+            continuous_neutral_us: Some(1500),
+        };
+        let mut servo = GpioServo::new(board.clone(), 2, servo_settings)?;
+
+        servo.set_speed_pct(50.0)?;
+        assert_eq!(board.get_pwm_duty(2), 0.0875);
+        assert!((servo.speed_pct()? - 50.0).abs() < 0.0001);
+        assert!(servo.is_moving().unwrap());
+
+        // full reverse at the lowest angle, stopped in the middle
+        servo.move_to(0)?;
+        assert_eq!(board.get_pwm_duty(2), 0.05);
+        assert_eq!(servo.get_position()?, 0);
+        servo.move_to(90)?;
+        assert_eq!(board.get_pwm_duty(2), 0.075);
+        assert!(!servo.is_moving().unwrap());
+
+        servo.set_speed_pct(250.0)?;
+        assert_eq!(servo.speed_pct()?, 100.0);
+        servo.stop().unwrap();
+        assert_eq!(board.get_pwm_duty(2), 0.075);
+
+        let command = StructBuilder::with_capacity(1)
+            .field("set_speed_pct", -25.0)
+            .build();
+        assert!(servo.do_command(Some(command)).unwrap().is_none());
+        let command = StructBuilder::with_capacity(1)
+            .field("get_speed_pct", true)
+            .build();
+        let res = servo.do_command(Some(command)).unwrap().unwrap();
+        match res.fields["speed_pct"].kind {
+            Some(Kind::NumberValue(speed_pct)) => assert!((speed_pct + 25.0).abs() < 0.0001),
+            _ => panic!("speed_pct missing"),
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,181 @@
+//! Relays and selectors driven by GPIO pins of a board.
+//!
+//! ```json
+//! {
+//!     "name": "pump",
+//!     "model": "gpio",
+//!     "type": "switch",
+//!     "attributes": {
+//!         "board": "board",
+//!         "pin": 18,
+//!         "active_low": true
+//!     }
+//! }
+//! ```
+//!
+//! With a single `pin` the switch has two positions: 0 (off) and 1 (on). With a list of `pins`
+//! the switch has one position per pin plus position 0 where every pin is off, setting position
+//! `n` turns on the `n`th pin only, so a bank of relays never has two of them on at once. Pins of
+//! a GPIO expander can be used (e.g. `"mcp:3"`). `active_low` is for relay modules switched on by
+//! a low level, `labels` optionally names each position. Every pin is turned off when the
+//! switch is built.
+
+use std::sync::{Arc, Mutex};
+
+use super::{
+    board::{Board, BoardPin, BoardType},
+    config::{AttributeError, ConfigType},
+    registry::{get_board_from_dependencies, ComponentRegistry, Dependency},
+    status::{status_envelope, ComponentHealth, Status, StatusError},
+    switch::{labels_from_config, Switch, SwitchError, SwitchType},
+};
+use crate::google::protobuf::{value::Kind, Struct};
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry.register_switch("gpio", &from_config).is_err() {
+        log::error!("gpio model is already registered")
+    }
+}
+
+pub(crate) fn from_config(
+    cfg: ConfigType,
+    dependencies: Vec<Dependency>,
+) -> Result<SwitchType, SwitchError> {
+    let board = get_board_from_dependencies(dependencies).ok_or(
+        SwitchError::SwitchConfigurationError("missing board attribute"),
+    )?;
+    let pins = match cfg.get_attribute::<Vec<BoardPin>>("pins") {
+        Ok(pins) => pins,
+        Err(AttributeError::KeyNotFound(_)) => vec![cfg.get_attribute::<BoardPin>("pin")?],
+        Err(err) => return Err(err.into()),
+    };
+    let pins = pins
+        .iter()
+        .map(|pin| board.resolve_pin(pin))
+        .collect::<Result<Vec<_>, _>>()?;
+    let active_low = cfg.get_attribute::<bool>("active_low").unwrap_or(false);
+    let mut switch = GpioSwitch::<BoardType>::new(board.clone(), pins, active_low)?;
+    switch.labels = labels_from_config(&cfg, switch.number_of_positions())?;
+    Ok(Arc::new(Mutex::new(switch)))
+}
+
+#[derive(DoCommand)]
+pub struct GpioSwitch<B> {
+    board: B,
+    pins: Vec<i32>,
+    active_low: bool,
+    labels: Vec<String>,
+    position: u32,
+    health: ComponentHealth,
+}
+
+impl<B> GpioSwitch<B>
+where
+    B: Board,
+{
+    pub fn new(board: B, pins: Vec<i32>, active_low: bool) -> Result<Self, SwitchError> {
+        if pins.is_empty() {
+            return Err(SwitchError::SwitchConfigurationError(
+                "GpioSwitch: at least one pin is required",
+            ));
+        }
+        let mut res = Self {
+            board,
+            pins,
+            active_low,
+            labels: vec![],
+            position: 0,
+            health: ComponentHealth::new(),
+        };
+        res.set_position(0)?;
+        Ok(res)
+    }
+
+    fn number_of_positions(&self) -> u32 {
+        self.pins.len() as u32 + 1
+    }
+}
+
+impl<B> Switch for GpioSwitch<B>
+where
+    B: Board,
+{
+    fn set_position(&mut self, position: u32) -> Result<(), SwitchError> {
+        if position >= self.number_of_positions() {
+            return Err(SwitchError::SwitchPositionOutOfRange(
+                position,
+                self.number_of_positions(),
+            ));
+        }
+        // turn the current pin off before turning the next one on
+        let mut res = Ok(());
+        for (idx, pin) in self.pins.iter().enumerate() {
+            if idx as u32 + 1 != position {
+                res = res.and_then(|_| self.board.set_gpio_pin_level(*pin, self.active_low));
+            }
+        }
+        if position > 0 {
+            let pin = self.pins[position as usize - 1];
+            res = res.and_then(|_| self.board.set_gpio_pin_level(pin, !self.active_low));
+        }
+        self.health.record(&res);
+        if res.is_ok() {
+            self.position = position;
+        }
+        Ok(res?)
+    }
+    fn get_position(&mut self) -> Result<u32, SwitchError> {
+        Ok(self.position)
+    }
+    fn get_number_of_positions(&mut self) -> Result<(u32, Vec<String>), SwitchError> {
+        Ok((self.number_of_positions(), self.labels.clone()))
+    }
+}
+
+impl<B> Status for GpioSwitch<B>
+where
+    B: Board,
+{
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(status_envelope(
+            &self.health,
+            [("position", Kind::NumberValue(self.position as f64))],
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::board::{Board, FakeBoard};
+    use crate::common::gpio_switch::GpioSwitch;
+    use crate::common::switch::{Switch, SwitchError};
+    use std::sync::{Arc, Mutex};
+
+    #[test_log::test]
+    fn test_gpio_switch() -> Result<(), SwitchError> {
+        let board = Arc::new(Mutex::new(FakeBoard::new(vec![])));
+
+        let mut relay = GpioSwitch::new(board.clone(), vec![4], true)?;
+        assert!(board.get_gpio_level(4)?);
+        relay.set_position(1)?;
+        assert!(!board.get_gpio_level(4)?);
+        assert_eq!(relay.get_position()?, 1);
+        assert!(matches!(
+            relay.set_position(2),
+            Err(SwitchError::SwitchPositionOutOfRange(2, 2))
+        ));
+
+        let mut selector = GpioSwitch::new(board.clone(), vec![5, 6, 7], false)?;
+        assert_eq!(selector.get_number_of_positions()?.0, 4);
+        selector.set_position(2)?;
+        assert!(!board.get_gpio_level(5)?);
+        assert!(board.get_gpio_level(6)?);
+        assert!(!board.get_gpio_level(7)?);
+        selector.set_position(3)?;
+        assert!(!board.get_gpio_level(6)?);
+        assert!(board.get_gpio_level(7)?);
+        selector.set_position(0)?;
+        assert!(!board.get_gpio_level(7)?);
+        Ok(())
+    }
+}
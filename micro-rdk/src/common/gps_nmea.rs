@@ -0,0 +1,676 @@
+//! Package gps_nmea implements the movementsensor interface for GNSS receivers streaming NMEA
+//! sentences over a serial port, including dual-antenna RTK receivers (such as the Unicore
+//! UM982) measuring the true heading of the vehicle from the baseline between their antennas.
+//! See `esp32::gps_nmea` for the serial port configuration.
+//!
+//! ```json
+//! {
+//!     "init_commands": ["GPGGA 0.2", "GPRMC 0.2", "GPGST 1", "GPHDT 0.2", "UNIHEADINGA 0.2"]
+//! }
+//! ```
+//!
+//! The following sentences are used, with any talker ID:
+//! - `GGA`: position, fix quality, number of satellites, HDOP and age of the corrections
+//! - `RMC`: speed and course over ground
+//! - `GST`: standard deviation of the latitude, longitude and altitude errors
+//! - `HDT` and Unicore `HPR`: true heading measured by a dual-antenna receiver
+//! - Unicore `#UNIHEADINGA` logs: true heading and its standard deviation
+//!
+//! The serial port is read every 50ms by a task running alongside the robot, sentences are
+//! timestamped when received rather than when the sensor is queried. The UTC time of valid
+//! `RMC` sentences sets the system clock while app is unreachable, see `common::clock`.
+//!
+//! `init_commands` are sent once, each followed by CRLF, when the sensor is built. They are
+//! receiver specific; the example enables the sentences above on a UM982 at 5Hz.
+//!
+//! `get_compass_heading` reports the true heading of the dual-antenna solution when one was
+//! received in the last 2 seconds, and otherwise the course over ground while moving.
+//! `get_accuracy` reports the GGA fix quality and HDOP, the GST standard deviations as
+//! `lat_stddev_m`, `lon_stddev_m` and `alt_stddev_m`, the number of satellites used and the
+//! age of the differential corrections as `satellites` and `correction_age_s`, and the
+//! standard deviation of the heading as the compass error.
+//!
+//! Corrections from an NTRIP caster are forwarded to the receiver when the `ntrip_*`
+//! attributes are set, see `common::ntrip`. Besides the position, velocity and heading, the
+//! readings report `fix_type` (`none`, `gps`, `dgps`, `rtk_fixed`, `rtk_float`...),
+//! `satellites` and, when known, `correction_age_s` as reported by the receiver; with NTRIP,
+//! `ntrip_connected` and the time since the last corrections in `ntrip_correction_age_s`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+
+use super::clock;
+use super::config::{AttributeError, ConfigType};
+use super::math_utils::Vector3;
+use super::movement_sensor::{
+    GeoPosition, MovementSensor, MovementSensorAccuracy, MovementSensorSupportedMethods,
+    MovementSensorType,
+};
+use super::ntrip::{NtripClient, NtripConfig, NtripStatusType};
+use super::periodic::spawn_periodic;
+use super::sensor::{GenericReadingsResult, Readings, SensorError};
+use super::struct_builder::StructBuilder;
+use super::uart::UartType;
+
+// bytes kept while waiting for the end of a sentence, NMEA sentences are at most 82 bytes
+// long but Unicore logs are longer
+const MAX_LINE_LEN: usize = 512;
+const READ_CHUNK: usize = 256;
+const HEADING_MAX_AGE: Duration = Duration::from_secs(2);
+// a sentence waits at most this long in the serial port before being timestamped
+const READ_PERIOD: Duration = Duration::from_millis(50);
+// below this speed the course over ground is meaningless
+const MIN_COURSE_SPEED_MPS: f64 = 0.2;
+const KNOTS_TO_MPS: f64 = 0.514444;
+
+/// XOR of the bytes between `$` and `*`
+fn nmea_checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |acc, b| acc ^ b)
+}
+
+/// CRC32 closing Unicore (and NovAtel) ASCII logs, reflected polynomial 0xEDB88320 without
+/// initial or final inversion
+fn unicore_crc32(body: &str) -> u32 {
+    body.bytes().fold(0_u32, |crc, byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// A sentence received from the receiver, in SI units and degrees
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Sentence {
+    Gga {
+        position: Option<GeoPosition>,
+        quality: u8,
+        satellites: u8,
+        hdop: Option<f32>,
+        correction_age_secs: Option<f64>,
+    },
+    Rmc {
+        valid: bool,
+        speed_mps: f64,
+        course: Option<f64>,
+        time: Option<DateTime<Utc>>,
+    },
+    Gst {
+        lat_stddev_m: f64,
+        lon_stddev_m: f64,
+        alt_stddev_m: f64,
+    },
+    Heading {
+        heading: f64,
+        stddev: Option<f64>,
+    },
+}
+
+// ddmm.mmmm (or dddmm.mmmm) and hemisphere to signed degrees
+fn parse_coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let raw: f64 = value.parse().ok()?;
+    let degrees = (raw / 100.0).trunc();
+    let degrees = degrees + (raw - degrees * 100.0) / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(degrees),
+        "S" | "W" => Some(-degrees),
+        _ => None,
+    }
+}
+
+// hhmmss.sss and ddmmyy fields to UTC
+fn parse_utc(time: &str, date: &str) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(date, "%d%m%y").ok()?;
+    let time = NaiveTime::parse_from_str(time, "%H%M%S%.f").ok()?;
+    Some(Utc.from_utc_datetime(&date.and_time(time)))
+}
+
+/// Parses a sentence, returning `None` for corrupted or unsupported sentences
+pub(crate) fn parse_sentence(line: &str) -> Option<Sentence> {
+    let line = line.trim_end();
+    let (body, checksum) = line.get(1..)?.rsplit_once('*')?;
+    if line.starts_with('$') {
+        if u8::from_str_radix(checksum, 16).ok()? != nmea_checksum(body) {
+            return None;
+        }
+        parse_nmea(body)
+    } else if line.starts_with('#') {
+        if u32::from_str_radix(checksum, 16).ok()? != unicore_crc32(body) {
+            return None;
+        }
+        parse_unicore(body)
+    } else {
+        None
+    }
+}
+
+fn parse_nmea(body: &str) -> Option<Sentence> {
+    let fields: Vec<&str> = body.split(',').collect();
+    let number = |i: usize| fields.get(i).and_then(|f| f.parse::<f64>().ok());
+    // the sentence type follows the 2 characters of the talker ID
+    match fields[0].get(2..)? {
+        "GGA" => {
+            let quality = fields.get(6)?.parse().unwrap_or(0);
+            let position = match (
+                parse_coordinate(fields.get(2)?, fields.get(3)?),
+                parse_coordinate(fields.get(4)?, fields.get(5)?),
+            ) {
+                (Some(lat), Some(lon)) if quality > 0 => Some(GeoPosition {
+                    lat,
+                    lon,
+                    alt: number(9).unwrap_or(0.0) as f32,
+                }),
+                _ => None,
+            };
+            Some(Sentence::Gga {
+                position,
+                quality,
+                satellites: fields.get(7)?.parse().unwrap_or(0),
+                hdop: number(8).map(|hdop| hdop as f32),
+                correction_age_secs: number(13),
+            })
+        }
+        "RMC" => Some(Sentence::Rmc {
+            valid: *fields.get(2)? == "A",
+            speed_mps: number(7).unwrap_or(0.0) * KNOTS_TO_MPS,
+            course: number(8),
+            time: parse_utc(fields.get(1)?, fields.get(9)?),
+        }),
+        "GST" => Some(Sentence::Gst {
+            lat_stddev_m: number(6)?,
+            lon_stddev_m: number(7)?,
+            alt_stddev_m: number(8)?,
+        }),
+        "HDT" => Some(Sentence::Heading {
+            heading: number(1)?,
+            stddev: None,
+        }),
+        // Unicore heading, pitch and roll, the quality is 0 without a heading solution
+        "HPR" => match number(5)? as u8 {
+            0 => None,
+            _ => Some(Sentence::Heading {
+                heading: number(2)?,
+                stddev: None,
+            }),
+        },
+        _ => None,
+    }
+}
+
+fn parse_unicore(body: &str) -> Option<Sentence> {
+    let (header, data) = body.split_once(';')?;
+    if header.split(',').next()? != "UNIHEADINGA" {
+        return None;
+    }
+    // solution status, position type, baseline length, heading, pitch, reserved, heading
+    // standard deviation...
+    let fields: Vec<&str> = data.split(',').collect();
+    if *fields.first()? != "SOL_COMPUTED" {
+        return None;
+    }
+    Some(Sentence::Heading {
+        heading: fields.get(3)?.parse().ok()?,
+        stddev: fields.get(6).and_then(|f| f.parse().ok()),
+    })
+}
+
+/// Name of a GGA fix quality
+pub(crate) fn fix_type(quality: u8) -> &'static str {
+    match quality {
+        0 => "none",
+        1 => "gps",
+        2 => "dgps",
+        3 => "pps",
+        4 => "rtk_fixed",
+        5 => "rtk_float",
+        6 => "dead_reckoning",
+        7 => "manual",
+        8 => "simulation",
+        _ => "unknown",
+    }
+}
+
+/// The latest state reported by the receiver
+#[derive(Clone, Debug, Default)]
+pub(crate) struct NmeaState {
+    pub(crate) position: Option<GeoPosition>,
+    pub(crate) quality: u8,
+    pub(crate) satellites: u8,
+    pub(crate) hdop: Option<f32>,
+    pub(crate) correction_age_secs: Option<f64>,
+    // speed over ground in meters per second and course in degrees
+    pub(crate) velocity: Option<(f64, Option<f64>)>,
+    pub(crate) position_stddev: Option<(f64, f64, f64)>,
+    // true heading in degrees, its standard deviation and when it was received
+    pub(crate) heading: Option<(f64, Option<f64>, Instant)>,
+}
+
+impl NmeaState {
+    pub(crate) fn apply(&mut self, sentence: Sentence, now: Instant) {
+        match sentence {
+            Sentence::Gga {
+                position,
+                quality,
+                satellites,
+                hdop,
+                correction_age_secs,
+            } => {
+                self.position = position;
+                self.quality = quality;
+                self.satellites = satellites;
+                self.hdop = hdop;
+                self.correction_age_secs = correction_age_secs;
+            }
+            Sentence::Rmc {
+                valid,
+                speed_mps,
+                course,
+                ..
+            } => self.velocity = valid.then_some((speed_mps, course)),
+            Sentence::Gst {
+                lat_stddev_m,
+                lon_stddev_m,
+                alt_stddev_m,
+            } => self.position_stddev = Some((lat_stddev_m, lon_stddev_m, alt_stddev_m)),
+            Sentence::Heading { heading, stddev } => {
+                // HDT doesn't report the standard deviation, keep the one of the last log
+                let stddev = stddev.or(self.heading.and_then(|(_, stddev, _)| stddev));
+                self.heading = Some((heading.rem_euclid(360.0), stddev, now));
+            }
+        }
+    }
+
+    fn true_heading(&self, now: Instant) -> Option<(f64, Option<f64>)> {
+        self.heading
+            .filter(|(_, _, at)| now.duration_since(*at) <= HEADING_MAX_AGE)
+            .map(|(heading, stddev, _)| (heading, stddev))
+    }
+
+    // meters per second, x pointing east and y pointing north
+    pub(crate) fn linear_velocity(&self) -> Option<Vector3> {
+        self.velocity.map(|(speed, course)| {
+            let course = course.unwrap_or(0.0).to_radians();
+            Vector3 {
+                x: speed * course.sin(),
+                y: speed * course.cos(),
+                z: 0.0,
+            }
+        })
+    }
+
+    pub(crate) fn compass_heading(&self, now: Instant) -> Option<f64> {
+        if let Some((heading, _)) = self.true_heading(now) {
+            return Some(heading);
+        }
+        match self.velocity {
+            Some((speed, course)) if speed >= MIN_COURSE_SPEED_MPS => course,
+            _ => None,
+        }
+    }
+
+    pub(crate) fn accuracy(&self, now: Instant) -> MovementSensorAccuracy {
+        let mut accuracy = HashMap::new();
+        if let Some((lat, lon, alt)) = self.position_stddev {
+            accuracy.insert("lat_stddev_m".to_string(), lat as f32);
+            accuracy.insert("lon_stddev_m".to_string(), lon as f32);
+            accuracy.insert("alt_stddev_m".to_string(), alt as f32);
+        }
+        accuracy.insert("satellites".to_string(), self.satellites as f32);
+        if let Some(age) = self.correction_age_secs {
+            accuracy.insert("correction_age_s".to_string(), age as f32);
+        }
+        MovementSensorAccuracy {
+            accuracy,
+            position_hdop: self.hdop,
+            position_vdop: None,
+            position_nmea_gga_fix: Some(self.quality as i32),
+            compass_degrees_error: self
+                .true_heading(now)
+                .and_then(|(_, stddev)| stddev)
+                .map(|stddev| stddev as f32),
+        }
+    }
+}
+
+#[derive(DoCommand, Status)]
+pub struct NmeaGps {
+    uart: UartType,
+    buffer: Vec<u8>,
+    state: NmeaState,
+    ntrip: Option<NtripStatusType>,
+}
+
+impl NmeaGps {
+    pub fn new(uart: UartType, init_commands: &[String]) -> Result<Self, SensorError> {
+        {
+            let mut uart = uart.lock().unwrap();
+            for command in init_commands {
+                uart.write(command.as_bytes())?;
+                uart.write(b"\r\n")?;
+            }
+        }
+        Ok(Self {
+            uart,
+            buffer: Vec::with_capacity(MAX_LINE_LEN),
+            state: NmeaState::default(),
+            ntrip: None,
+        })
+    }
+
+    /// Builds the sensor from the attributes of the `gps-nmea` model, once the platform has
+    /// opened the serial port the receiver is connected to
+    pub fn from_uart_and_config(
+        uart: UartType,
+        cfg: ConfigType,
+    ) -> Result<MovementSensorType, SensorError> {
+        let init_commands = match cfg.get_attribute::<Vec<String>>("init_commands") {
+            Ok(commands) => commands,
+            Err(AttributeError::KeyNotFound(_)) => vec![],
+            Err(_) => return Err(SensorError::ConfigError("gps-nmea invalid init_commands")),
+        };
+        let ntrip = NtripConfig::from_config(&cfg).map_err(|err| {
+            log::error!("gps-nmea: {}", err);
+            SensorError::ConfigError("gps-nmea invalid ntrip attributes")
+        })?;
+        let mut gps = Self::new(uart.clone(), &init_commands)?;
+        gps.ntrip = ntrip.map(|config| NtripClient::spawn(config, uart));
+        let gps = Arc::new(Mutex::new(gps));
+        spawn_periodic(&gps, READ_PERIOD, |gps: &mut Self| {
+            if let Err(err) = gps.update() {
+                log::debug!("gps-nmea: couldn't read the serial port: {}", err);
+            }
+        });
+        Ok(gps)
+    }
+
+    // reads the sentences received since the last call
+    fn update(&mut self) -> Result<(), SensorError> {
+        let mut chunk = [0_u8; READ_CHUNK];
+        loop {
+            let len = self.uart.lock().unwrap().read_available(&mut chunk)?;
+            if len == 0 {
+                break;
+            }
+            for byte in &chunk[..len] {
+                if *byte != b'\n' {
+                    if self.buffer.len() >= MAX_LINE_LEN {
+                        // no line ending, resynchronize on the next sentence
+                        self.buffer.clear();
+                    }
+                    self.buffer.push(*byte);
+                    continue;
+                }
+                let line = std::str::from_utf8(&self.buffer).ok();
+                if let Some(sentence) = line.and_then(parse_sentence) {
+                    let now = Instant::now();
+                    if let Sentence::Rmc {
+                        valid: true,
+                        time: Some(time),
+                        ..
+                    } = sentence
+                    {
+                        if let Err(err) = clock::discipline_with_gnss(time, now) {
+                            log::warn!("gps-nmea: {}", err);
+                        }
+                    }
+                    // casters serving network solutions need the position of the rover
+                    if let (Sentence::Gga { .. }, Some(ntrip)) = (&sentence, &self.ntrip) {
+                        ntrip.lock().unwrap().gga = line.map(|line| line.trim_end().to_string());
+                    }
+                    self.state.apply(sentence, now);
+                }
+                self.buffer.clear();
+            }
+        }
+        Ok(())
+    }
+
+    fn position(&mut self) -> Result<GeoPosition, SensorError> {
+        self.update()?;
+        self.state
+            .position
+            .ok_or(SensorError::SensorGenericError("gps-nmea has no fix"))
+    }
+}
+
+impl MovementSensor for NmeaGps {
+    fn get_properties(&self) -> MovementSensorSupportedMethods {
+        MovementSensorSupportedMethods {
+            position_supported: true,
+            linear_velocity_supported: true,
+            angular_velocity_supported: false,
+            linear_acceleration_supported: false,
+            compass_heading_supported: true,
+            orientation_supported: false,
+        }
+    }
+
+    fn get_position(&mut self) -> Result<GeoPosition, SensorError> {
+        self.position()
+    }
+
+    fn get_linear_velocity(&mut self) -> Result<Vector3, SensorError> {
+        self.update()?;
+        self.state
+            .linear_velocity()
+            .ok_or(SensorError::SensorGenericError("gps-nmea has no velocity"))
+    }
+
+    fn get_compass_heading(&mut self) -> Result<f64, SensorError> {
+        self.update()?;
+        self.state
+            .compass_heading(Instant::now())
+            .ok_or(SensorError::SensorGenericError("gps-nmea has no heading"))
+    }
+
+    fn get_accuracy(&mut self) -> Result<MovementSensorAccuracy, SensorError> {
+        self.update()?;
+        Ok(self.state.accuracy(Instant::now()))
+    }
+
+    fn get_angular_velocity(&mut self) -> Result<Vector3, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented(
+            "get_angular_velocity",
+        ))
+    }
+
+    fn get_linear_acceleration(&mut self) -> Result<Vector3, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented(
+            "get_linear_acceleration",
+        ))
+    }
+}
+
+impl Readings for NmeaGps {
+    // unlike the default movement sensor readings, the fix is reported while there is no
+    // position, velocity or heading
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        self.update()?;
+        let now = Instant::now();
+        let state = &self.state;
+        let mut readings = StructBuilder::with_capacity(8)
+            .field("fix_type", fix_type(state.quality))
+            .field("satellites", state.satellites);
+        if let Some(position) = state.position {
+            readings = readings.value("position", position.into());
+        }
+        if let Some(velocity) = state.linear_velocity() {
+            readings = readings.value("linear_velocity", velocity.into());
+        }
+        if let Some(heading) = state.compass_heading(now) {
+            readings = readings.field("compass_heading", heading);
+        }
+        if let Some(age) = state.correction_age_secs {
+            readings = readings.field("correction_age_s", age);
+        }
+        if let Some(ntrip) = &self.ntrip {
+            let ntrip = ntrip.lock().unwrap();
+            readings = readings.field("ntrip_connected", ntrip.connected);
+            if let Some(at) = ntrip.last_correction {
+                readings = readings.field(
+                    "ntrip_correction_age_s",
+                    now.duration_since(at).as_secs_f64(),
+                );
+            }
+        }
+        Ok(readings.into_fields())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nmea_checksum, parse_sentence, unicore_crc32, NmeaGps, NmeaState, Sentence};
+    use crate::common::movement_sensor::MovementSensor;
+    use crate::common::ntrip::NtripStatus;
+    use crate::common::sensor::{Readings, SensorError};
+    use crate::common::uart::FakeUart;
+    use crate::google::protobuf::value::Kind;
+    use chrono::{TimeZone, Utc};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    const GGA: &str = "$GPGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,*76";
+
+    #[test_log::test]
+    fn test_parse_sentences() {
+        match parse_sentence(GGA) {
+            Some(Sentence::Gga {
+                position: Some(position),
+                quality,
+                satellites,
+                hdop,
+                correction_age_secs,
+            }) => {
+                assert!((position.lat - 53.36133667).abs() < 1e-8);
+                assert!((position.lon + 6.50562).abs() < 1e-8);
+                assert_eq!(position.alt, 61.7);
+                assert_eq!((quality, satellites), (1, 8));
+                assert_eq!(hdop, Some(1.03));
+                assert_eq!(correction_age_secs, None);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        // corrupted checksum
+        assert!(parse_sentence(&GGA.replace("*76", "*77")).is_none());
+
+        let rmc = "GPRMC,092750.250,A,5321.6802,N,00630.3372,W,0.02,31.66,280511,,,A";
+        assert_eq!(
+            parse_sentence(&format!("${}*{:02X}", rmc, nmea_checksum(rmc))),
+            Some(Sentence::Rmc {
+                valid: true,
+                speed_mps: 0.02 * 0.514444,
+                course: Some(31.66),
+                time: Some(
+                    Utc.with_ymd_and_hms(2011, 5, 28, 9, 27, 50).unwrap()
+                        + chrono::Duration::milliseconds(250)
+                ),
+            })
+        );
+
+        assert_eq!(
+            parse_sentence("$GNHDT,274.07,T*1D"),
+            Some(Sentence::Heading {
+                heading: 274.07,
+                stddev: None
+            })
+        );
+
+        let body = "UNIHEADINGA,97,GPS,FINE,2190,365174000,0,0,18,15;SOL_COMPUTED,NARROW_INT,1.1042,89.5,-0.87,0.0,0.1562,0.3211,\"999\",49,37,37,0,3,00,1,f3";
+        let log = format!("#{}*{:08x}", body, unicore_crc32(body));
+        assert_eq!(
+            parse_sentence(&log),
+            Some(Sentence::Heading {
+                heading: 89.5,
+                stddev: Some(0.1562)
+            })
+        );
+    }
+
+    #[test_log::test]
+    fn test_heading_and_accuracy() {
+        let now = Instant::now();
+        let mut state = NmeaState::default();
+        assert_eq!(state.compass_heading(now), None);
+        // the course over ground is used while moving without a dual-antenna heading
+        state.apply(
+            Sentence::Rmc {
+                valid: true,
+                speed_mps: 2.0,
+                course: Some(45.0),
+                time: None,
+            },
+            now,
+        );
+        assert_eq!(state.compass_heading(now), Some(45.0));
+
+        state.apply(
+            Sentence::Heading {
+                heading: 89.5,
+                stddev: Some(0.25),
+            },
+            now,
+        );
+        state.apply(
+            Sentence::Heading {
+                heading: -90.0,
+                stddev: None,
+            },
+            now,
+        );
+        assert_eq!(state.compass_heading(now), Some(270.0));
+        let accuracy = state.accuracy(now);
+        assert_eq!(accuracy.compass_degrees_error, Some(0.25));
+
+        // a stale heading falls back to the course
+        let later = now + Duration::from_secs(3);
+        assert_eq!(state.compass_heading(later), Some(45.0));
+        assert_eq!(state.accuracy(later).compass_degrees_error, None);
+    }
+
+    #[test_log::test]
+    fn test_nmea_gps() -> Result<(), SensorError> {
+        let uart = Arc::new(Mutex::new(FakeUart::default()));
+        let mut gps = NmeaGps::new(uart.clone(), &["GPGGA 1".to_string()])?;
+        assert_eq!(uart.lock().unwrap().written, b"GPGGA 1\r\n");
+        assert!(gps.get_position().is_err());
+        let ntrip = Arc::new(Mutex::new(NtripStatus::default()));
+        gps.ntrip = Some(ntrip.clone());
+        let readings = gps.get_generic_readings()?;
+        assert_eq!(
+            readings.get("fix_type").and_then(|v| v.kind.clone()),
+            Some(Kind::StringValue("none".to_string()))
+        );
+        assert!(!readings.contains_key("position"));
+
+        // a sentence split across reads
+        let (start, end) = GGA.split_at(30);
+        uart.lock().unwrap().replies.extend(start.as_bytes());
+        assert!(gps.get_position().is_err());
+        uart.lock()
+            .unwrap()
+            .replies
+            .extend(format!("{}\r\n$GNHDT,274.07,T*1D\r\n", end).as_bytes());
+        assert!((gps.get_position()?.lat - 53.36133667).abs() < 1e-8);
+        assert_eq!(gps.get_compass_heading()?, 274.07);
+        assert_eq!(gps.get_accuracy()?.position_nmea_gga_fix, Some(1));
+        // the GGA sentence is sent to the caster
+        assert_eq!(ntrip.lock().unwrap().gga.as_deref(), Some(GGA));
+        let readings = gps.get_generic_readings()?;
+        assert_eq!(
+            readings.get("fix_type").and_then(|v| v.kind.clone()),
+            Some(Kind::StringValue("gps".to_string()))
+        );
+        assert!(readings.contains_key("position"));
+        assert_eq!(
+            readings.get("ntrip_connected").and_then(|v| v.kind.clone()),
+            Some(Kind::BoolValue(false))
+        );
+        Ok(())
+    }
+}
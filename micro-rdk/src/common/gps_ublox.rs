@@ -0,0 +1,515 @@
+//! Package gps_ublox implements the movementsensor interface for u-blox GNSS modules (M8 and
+//! later) connected over I2C (which u-blox calls DDC). The UBX binary protocol is described at
+//! https://content.u-blox.com/sites/default/files/products/documents/u-blox8-M8_ReceiverDescrProtSpec_UBX-13003221.pdf
+//!
+//! On startup the DDC port is configured to output UBX only, the navigation update rate is set
+//! and the module is asked to periodically output NAV-PVT messages. A task running alongside the
+//! robot drains the module's output buffer every 100ms, so solutions are timestamped when they
+//! are output, and keeps the latest NAV-PVT solution, which is used to report position, velocity
+//! and heading. The UTC time of the solution sets the system clock
+//! while app is unreachable, see `common::clock`.
+//!
+//! The default I2C address of u-blox modules is 0x42.
+
+use crate::common::i2c::I2cHandleType;
+use crate::common::math_utils::Vector3;
+use crate::common::movement_sensor::{GeoPosition, MovementSensor, MovementSensorSupportedMethods};
+
+use super::board::Board;
+use super::clock;
+use super::config::ConfigType;
+use super::i2c::I2CHandle;
+use super::movement_sensor::MovementSensorType;
+use super::periodic::spawn_periodic;
+use super::registry::{get_board_from_dependencies, ComponentRegistry, Dependency};
+use super::sensor::SensorError;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_movement_sensor("gps-ublox", &UbloxGps::from_config)
+        .is_err()
+    {
+        log::error!("gps-ublox type is already registered");
+    }
+}
+
+const DEFAULT_I2C_ADDRESS: u8 = 0x42;
+const DEFAULT_UPDATE_RATE_HZ: f64 = 1.0;
+const READ_PERIOD: Duration = Duration::from_millis(100);
+// register holding the number of bytes available to read (big endian u16 over 0xFD-0xFE)
+const BYTES_AVAILABLE_REGISTER: u8 = 0xFD;
+// register from which the message stream is read
+const DATA_STREAM_REGISTER: u8 = 0xFF;
+// maximum number of bytes read in a single I2C transaction
+const MAX_READ_CHUNK: usize = 128;
+// bytes kept in the parser buffer before older data gets discarded
+const MAX_BUFFER_LEN: usize = 1024;
+
+const UBX_SYNC_1: u8 = 0xB5;
+const UBX_SYNC_2: u8 = 0x62;
+// sync chars, class, id and length
+const UBX_HEADER_LEN: usize = 6;
+const UBX_CHECKSUM_LEN: usize = 2;
+
+const UBX_CLASS_NAV: u8 = 0x01;
+const UBX_CLASS_CFG: u8 = 0x06;
+const UBX_NAV_PVT: u8 = 0x07;
+const UBX_CFG_PRT: u8 = 0x00;
+const UBX_CFG_MSG: u8 = 0x01;
+const UBX_CFG_RATE: u8 = 0x08;
+const NAV_PVT_PAYLOAD_LEN: usize = 92;
+
+/// Computes the 8-bit Fletcher checksum used by UBX over the class, id, length and payload
+/// bytes of a message
+pub(crate) fn ubx_checksum(bytes: &[u8]) -> (u8, u8) {
+    bytes.iter().fold((0_u8, 0_u8), |(ck_a, ck_b), byte| {
+        let ck_a = ck_a.wrapping_add(*byte);
+        (ck_a, ck_b.wrapping_add(ck_a))
+    })
+}
+
+/// Builds a complete UBX frame (sync chars, header, payload and checksum)
+pub(crate) fn ubx_frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(UBX_HEADER_LEN + payload.len() + UBX_CHECKSUM_LEN);
+    frame.extend_from_slice(&[UBX_SYNC_1, UBX_SYNC_2, class, id]);
+    frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    frame.extend_from_slice(payload);
+    let (ck_a, ck_b) = ubx_checksum(&frame[2..]);
+    frame.extend_from_slice(&[ck_a, ck_b]);
+    frame
+}
+
+/// A UBX message extracted from the byte stream of the module
+#[derive(Debug, PartialEq)]
+pub(crate) struct UbxMessage {
+    pub(crate) class: u8,
+    pub(crate) id: u8,
+    pub(crate) payload: Vec<u8>,
+}
+
+/// Extracts every complete and valid UBX message from the buffer. Bytes that were consumed,
+/// including noise and frames with invalid checksums, are removed from the buffer while a
+/// trailing incomplete frame is left in place for the next call.
+pub(crate) fn extract_ubx_messages(buffer: &mut Vec<u8>) -> Vec<UbxMessage> {
+    let mut messages = vec![];
+    let mut pos = 0;
+    while pos < buffer.len() {
+        if buffer[pos] != UBX_SYNC_1 {
+            pos += 1;
+            continue;
+        }
+        if buffer.len() - pos < UBX_HEADER_LEN {
+            break;
+        }
+        if buffer[pos + 1] != UBX_SYNC_2 {
+            pos += 1;
+            continue;
+        }
+        let payload_len = u16::from_le_bytes([buffer[pos + 4], buffer[pos + 5]]) as usize;
+        let frame_len = UBX_HEADER_LEN + payload_len + UBX_CHECKSUM_LEN;
+        if frame_len > MAX_BUFFER_LEN {
+            // corrupted length, resynchronize on the next sync char
+            pos += 1;
+            continue;
+        }
+        if buffer.len() - pos < frame_len {
+            break;
+        }
+        let frame = &buffer[pos..pos + frame_len];
+        let checksum = ubx_checksum(&frame[2..frame_len - UBX_CHECKSUM_LEN]);
+        if checksum != (frame[frame_len - 2], frame[frame_len - 1]) {
+            log::debug!("discarding UBX frame with invalid checksum");
+            pos += 1;
+            continue;
+        }
+        messages.push(UbxMessage {
+            class: frame[2],
+            id: frame[3],
+            payload: frame[UBX_HEADER_LEN..frame_len - UBX_CHECKSUM_LEN].to_vec(),
+        });
+        pos += frame_len;
+    }
+    buffer.drain(..pos);
+    messages
+}
+
+/// The navigation solution reported by a UBX NAV-PVT message, in SI units
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct NavPvt {
+    pub(crate) fix_type: u8,
+    pub(crate) fix_ok: bool,
+    pub(crate) num_satellites: u8,
+    pub(crate) lat: f64,
+    pub(crate) lon: f64,
+    // height above mean sea level in meters
+    pub(crate) alt: f64,
+    // velocity in the north-east-down frame in meters per second
+    pub(crate) vel_north: f64,
+    pub(crate) vel_east: f64,
+    pub(crate) vel_down: f64,
+    // heading of motion in degrees
+    pub(crate) heading_motion: f64,
+    // heading of the vehicle in degrees, only available on modules with sensor fusion
+    pub(crate) heading_vehicle: Option<f64>,
+    // time of the solution, once the module resolved both the date and the time of day
+    pub(crate) utc: Option<DateTime<Utc>>,
+}
+
+fn read_i32(payload: &[u8], offset: usize) -> i32 {
+    i32::from_le_bytes(payload[offset..offset + 4].try_into().unwrap())
+}
+
+impl TryFrom<&[u8]> for NavPvt {
+    type Error = SensorError;
+    fn try_from(payload: &[u8]) -> Result<Self, Self::Error> {
+        if payload.len() < NAV_PVT_PAYLOAD_LEN {
+            return Err(SensorError::SensorGenericError(
+                "gps-ublox NAV-PVT payload too short",
+            ));
+        }
+        let flags = payload[21];
+        let head_veh_valid = flags & 0x20 != 0;
+        // validDate, validTime and fullyResolved
+        let utc = if payload[11] & 0x07 == 0x07 {
+            NaiveDate::from_ymd_opt(
+                u16::from_le_bytes([payload[4], payload[5]]) as i32,
+                payload[6] as u32,
+                payload[7] as u32,
+            )
+            .and_then(|date| {
+                date.and_hms_opt(payload[8] as u32, payload[9] as u32, payload[10] as u32)
+            })
+            // the nanoseconds are signed, they correct the rounded seconds
+            .map(|time| {
+                Utc.from_utc_datetime(&time)
+                    + chrono::Duration::nanoseconds(read_i32(payload, 16) as i64)
+            })
+        } else {
+            None
+        };
+        Ok(NavPvt {
+            fix_type: payload[20],
+            fix_ok: flags & 0x01 != 0,
+            num_satellites: payload[23],
+            lon: read_i32(payload, 24) as f64 * 1e-7,
+            lat: read_i32(payload, 28) as f64 * 1e-7,
+            alt: read_i32(payload, 36) as f64 / 1000.0,
+            vel_north: read_i32(payload, 48) as f64 / 1000.0,
+            vel_east: read_i32(payload, 52) as f64 / 1000.0,
+            vel_down: read_i32(payload, 56) as f64 / 1000.0,
+            heading_motion: read_i32(payload, 64) as f64 * 1e-5,
+            heading_vehicle: head_veh_valid.then(|| read_i32(payload, 84) as f64 * 1e-5),
+            utc,
+        })
+    }
+}
+
+impl NavPvt {
+    // 2D or 3D fix (including GNSS + dead reckoning) validated by the module
+    fn has_fix(&self) -> bool {
+        self.fix_ok && (2..=4).contains(&self.fix_type)
+    }
+}
+
+#[derive(DoCommand, MovementSensorReadings, Status)]
+pub struct UbloxGps {
+    i2c_handle: I2cHandleType,
+    i2c_address: u8,
+    buffer: Vec<u8>,
+    last_pvt: Option<NavPvt>,
+}
+
+impl UbloxGps {
+    pub fn new(
+        mut i2c_handle: I2cHandleType,
+        i2c_address: u8,
+        update_rate_hz: f64,
+    ) -> Result<Self, SensorError> {
+        if update_rate_hz <= 0.0 || update_rate_hz > 40.0 {
+            return Err(SensorError::ConfigError(
+                "gps-ublox update_rate_hz must be in (0, 40]",
+            ));
+        }
+        // DDC port (id 0): UBX+NMEA+RTCM in, UBX only out
+        let mut prt_payload = [0_u8; 20];
+        prt_payload[4..8].copy_from_slice(&((i2c_address as u32) << 1).to_le_bytes());
+        prt_payload[12..14].copy_from_slice(&0x07_u16.to_le_bytes());
+        prt_payload[14..16].copy_from_slice(&0x01_u16.to_le_bytes());
+        i2c_handle.write_i2c(
+            i2c_address,
+            &ubx_frame(UBX_CLASS_CFG, UBX_CFG_PRT, &prt_payload),
+        )?;
+
+        // measurement period in ms, one navigation solution per measurement, aligned to GPS time
+        let meas_rate = (1000.0 / update_rate_hz) as u16;
+        let mut rate_payload = [0_u8; 6];
+        rate_payload[0..2].copy_from_slice(&meas_rate.to_le_bytes());
+        rate_payload[2..4].copy_from_slice(&1_u16.to_le_bytes());
+        rate_payload[4..6].copy_from_slice(&1_u16.to_le_bytes());
+        i2c_handle.write_i2c(
+            i2c_address,
+            &ubx_frame(UBX_CLASS_CFG, UBX_CFG_RATE, &rate_payload),
+        )?;
+
+        // output NAV-PVT on the current port with every navigation solution
+        i2c_handle.write_i2c(
+            i2c_address,
+            &ubx_frame(UBX_CLASS_CFG, UBX_CFG_MSG, &[UBX_CLASS_NAV, UBX_NAV_PVT, 1]),
+        )?;
+
+        Ok(UbloxGps {
+            i2c_handle,
+            i2c_address,
+            buffer: Vec::with_capacity(MAX_BUFFER_LEN),
+            last_pvt: None,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        dependencies: Vec<Dependency>,
+    ) -> Result<MovementSensorType, SensorError> {
+        let board = get_board_from_dependencies(dependencies);
+        if board.is_none() {
+            return Err(SensorError::ConfigError(
+                "gps-ublox missing board attribute",
+            ));
+        }
+        let board_unwrapped = board.unwrap();
+        let i2c_handle: I2cHandleType;
+        if let Ok(i2c_name) = cfg.get_attribute::<String>("i2c_bus") {
+            i2c_handle = board_unwrapped.get_i2c_by_name(i2c_name)?;
+        } else {
+            return Err(SensorError::ConfigError("gps-ublox missing i2c_bus"));
+        }
+        let i2c_address = cfg
+            .get_attribute::<u8>("i2c_address")
+            .unwrap_or(DEFAULT_I2C_ADDRESS);
+        let update_rate_hz = cfg
+            .get_attribute::<f64>("update_rate_hz")
+            .unwrap_or(DEFAULT_UPDATE_RATE_HZ);
+        let gps = Arc::new(Mutex::new(UbloxGps::new(
+            i2c_handle,
+            i2c_address,
+            update_rate_hz,
+        )?));
+        spawn_periodic(&gps, READ_PERIOD, |gps: &mut Self| {
+            if let Err(err) = gps.update() {
+                log::debug!("gps-ublox: couldn't read the module: {}", err);
+            }
+        });
+        Ok(gps)
+    }
+
+    // drains the output buffer of the module and keeps the most recent NAV-PVT solution
+    fn update(&mut self) -> Result<(), SensorError> {
+        let mut available = [0_u8; 2];
+        self.i2c_handle.write_read_i2c(
+            self.i2c_address,
+            &[BYTES_AVAILABLE_REGISTER],
+            &mut available,
+        )?;
+        let mut available = u16::from_be_bytes(available) as usize;
+        // the module reports 0xFFFF when the count is not ready
+        if available == 0xFFFF {
+            available = 0;
+        }
+        let mut chunk = [0_u8; MAX_READ_CHUNK];
+        while available > 0 {
+            let len = available.min(MAX_READ_CHUNK);
+            self.i2c_handle.write_read_i2c(
+                self.i2c_address,
+                &[DATA_STREAM_REGISTER],
+                &mut chunk[..len],
+            )?;
+            if self.buffer.len() + len > MAX_BUFFER_LEN {
+                self.buffer.clear();
+            }
+            self.buffer.extend_from_slice(&chunk[..len]);
+            available -= len;
+        }
+        let mut utc = None;
+        for msg in extract_ubx_messages(&mut self.buffer) {
+            if msg.class == UBX_CLASS_NAV && msg.id == UBX_NAV_PVT {
+                let pvt = NavPvt::try_from(msg.payload.as_slice())?;
+                utc = pvt.utc.or(utc);
+                self.last_pvt = Some(pvt);
+            }
+        }
+        if let Some(utc) = utc {
+            if let Err(err) = clock::discipline_with_gnss(utc, Instant::now()) {
+                log::warn!("gps-ublox: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_fix(&mut self) -> Result<NavPvt, SensorError> {
+        self.update()?;
+        match self.last_pvt {
+            Some(pvt) if pvt.has_fix() => Ok(pvt),
+            _ => Err(SensorError::SensorGenericError("gps-ublox has no fix")),
+        }
+    }
+}
+
+impl MovementSensor for UbloxGps {
+    fn get_properties(&self) -> MovementSensorSupportedMethods {
+        MovementSensorSupportedMethods {
+            position_supported: true,
+            linear_velocity_supported: true,
+            angular_velocity_supported: false,
+            linear_acceleration_supported: false,
+            compass_heading_supported: true,
+            orientation_supported: false,
+        }
+    }
+
+    fn get_position(&mut self) -> Result<GeoPosition, SensorError> {
+        let pvt = self.get_fix()?;
+        Ok(GeoPosition {
+            lat: pvt.lat,
+            lon: pvt.lon,
+            alt: pvt.alt as f32,
+        })
+    }
+
+    // reported in meters per second, x pointing east, y pointing north and z pointing up
+    fn get_linear_velocity(&mut self) -> Result<Vector3, SensorError> {
+        let pvt = self.get_fix()?;
+        Ok(Vector3 {
+            x: pvt.vel_east,
+            y: pvt.vel_north,
+            z: -pvt.vel_down,
+        })
+    }
+
+    fn get_compass_heading(&mut self) -> Result<f64, SensorError> {
+        let pvt = self.get_fix()?;
+        Ok(pvt.heading_vehicle.unwrap_or(pvt.heading_motion))
+    }
+
+    fn get_angular_velocity(&mut self) -> Result<Vector3, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented(
+            "get_angular_velocity",
+        ))
+    }
+
+    fn get_linear_acceleration(&mut self) -> Result<Vector3, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented(
+            "get_linear_acceleration",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        extract_ubx_messages, ubx_checksum, ubx_frame, NavPvt, NAV_PVT_PAYLOAD_LEN, UBX_CFG_RATE,
+        UBX_CLASS_CFG, UBX_CLASS_NAV, UBX_NAV_PVT,
+    };
+    use chrono::{TimeZone, Utc};
+
+    fn nav_pvt_payload() -> Vec<u8> {
+        let mut payload = vec![0_u8; NAV_PVT_PAYLOAD_LEN];
+        // 3D fix, gnssFixOK and headVehValid
+        payload[20] = 3;
+        payload[21] = 0x21;
+        payload[23] = 9;
+        // 2024-03-01 12:00:01 minus 250ms, date and time valid and fully resolved
+        payload[4..6].copy_from_slice(&2024_u16.to_le_bytes());
+        payload[6..11].copy_from_slice(&[3, 1, 12, 0, 1]);
+        payload[11] = 0x07;
+        payload[16..20].copy_from_slice(&(-250_000_000_i32).to_le_bytes());
+        payload[24..28].copy_from_slice(&(-739_856_170_i32).to_le_bytes());
+        payload[28..32].copy_from_slice(&(407_484_450_i32).to_le_bytes());
+        payload[36..40].copy_from_slice(&(12_500_i32).to_le_bytes());
+        payload[48..52].copy_from_slice(&(1_500_i32).to_le_bytes());
+        payload[52..56].copy_from_slice(&(-250_i32).to_le_bytes());
+        payload[56..60].copy_from_slice(&(100_i32).to_le_bytes());
+        payload[64..68].copy_from_slice(&(9_000_000_i32).to_le_bytes());
+        payload[84..88].copy_from_slice(&(18_050_000_i32).to_le_bytes());
+        payload
+    }
+
+    #[test_log::test]
+    fn test_ubx_frame() {
+        // CFG-RATE 1Hz example from the protocol specification
+        let frame = ubx_frame(
+            UBX_CLASS_CFG,
+            UBX_CFG_RATE,
+            &[0xE8, 0x03, 0x01, 0x00, 0x01, 0x00],
+        );
+        assert_eq!(
+            frame,
+            vec![
+                0xB5, 0x62, 0x06, 0x08, 0x06, 0x00, 0xE8, 0x03, 0x01, 0x00, 0x01, 0x00, 0x01, 0x39
+            ]
+        );
+        assert_eq!(ubx_checksum(&frame[2..frame.len() - 2]), (0x01, 0x39));
+    }
+
+    #[test_log::test]
+    fn test_extract_ubx_messages() {
+        let pvt_frame = ubx_frame(UBX_CLASS_NAV, UBX_NAV_PVT, &nav_pvt_payload());
+        let mut corrupted = ubx_frame(UBX_CLASS_CFG, UBX_CFG_RATE, &[1, 2, 3, 4, 5, 6]);
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xFF;
+
+        let mut buffer = vec![0x24, 0x47, 0xB5, 0x00];
+        buffer.extend_from_slice(&corrupted);
+        buffer.extend_from_slice(&pvt_frame);
+        buffer.extend_from_slice(&pvt_frame[..10]);
+
+        let messages = extract_ubx_messages(&mut buffer);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].class, UBX_CLASS_NAV);
+        assert_eq!(messages[0].id, UBX_NAV_PVT);
+        assert_eq!(messages[0].payload, nav_pvt_payload());
+        // the incomplete trailing frame is kept for later
+        assert_eq!(buffer, pvt_frame[..10].to_vec());
+
+        buffer.extend_from_slice(&pvt_frame[10..]);
+        let messages = extract_ubx_messages(&mut buffer);
+        assert_eq!(messages.len(), 1);
+        assert!(buffer.is_empty());
+    }
+
+    #[test_log::test]
+    fn test_parse_nav_pvt() {
+        let pvt = NavPvt::try_from(nav_pvt_payload().as_slice()).unwrap();
+        assert!(pvt.has_fix());
+        assert_eq!(pvt.num_satellites, 9);
+        assert!((pvt.lat - 40.748445).abs() < 1e-9);
+        assert!((pvt.lon + 73.985617).abs() < 1e-9);
+        assert_eq!(pvt.alt, 12.5);
+        assert_eq!(pvt.vel_north, 1.5);
+        assert_eq!(pvt.vel_east, -0.25);
+        assert_eq!(pvt.vel_down, 0.1);
+        assert!((pvt.heading_motion - 90.0).abs() < 1e-9);
+        assert!((pvt.heading_vehicle.unwrap() - 180.5).abs() < 1e-9);
+        assert_eq!(
+            pvt.utc,
+            Some(
+                Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()
+                    + chrono::Duration::milliseconds(750)
+            )
+        );
+
+        let mut payload = nav_pvt_payload();
+        payload[21] = 0;
+        payload[11] = 0x03;
+        let pvt = NavPvt::try_from(payload.as_slice()).unwrap();
+        assert!(!pvt.has_fix());
+        assert!(pvt.heading_vehicle.is_none());
+        // the time isn't used until it is fully resolved
+        assert!(pvt.utc.is_none());
+
+        assert!(NavPvt::try_from(&payload[..40]).is_err());
+    }
+}
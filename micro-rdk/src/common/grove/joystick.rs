@@ -0,0 +1,120 @@
+//! M5Stack joystick unit, an I2C joystick with a push button reporting its position as one byte
+//! per axis, centered on 128.
+//!
+//! The axes are reported in [-1, 1], positions within `dead_zone` (default 0.05) of the center
+//! are reported as 0 so the joystick at rest reads as centered. `pressed` is 1 while the button is
+//! pushed down.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::common::config::{AttributeError, ConfigType};
+use crate::common::i2c::{I2CHandle, I2cHandleType};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::sensor::{Sensor, SensorError, SensorT, SensorType, TypedReadingsResult};
+
+const DEFAULT_I2C_ADDRESS: u8 = 0x52;
+const DEFAULT_DEAD_ZONE: f64 = 0.05;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("m5-joystick", &Joystick::from_config)
+        .is_err()
+    {
+        log::error!("m5-joystick type is already registered");
+    }
+}
+
+#[derive(DoCommand, SensorReadings, Status)]
+pub struct Joystick {
+    i2c_handle: I2cHandleType,
+    i2c_address: u8,
+    dead_zone: f64,
+}
+
+impl Joystick {
+    pub fn new(i2c_handle: I2cHandleType, i2c_address: u8, dead_zone: f64) -> Self {
+        Self {
+            i2c_handle,
+            i2c_address,
+            dead_zone,
+        }
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        dependencies: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let dead_zone = match cfg.get_attribute::<f64>("dead_zone") {
+            Ok(dead_zone) if (0.0..1.0).contains(&dead_zone) => dead_zone,
+            Err(AttributeError::KeyNotFound(_)) => DEFAULT_DEAD_ZONE,
+            _ => {
+                return Err(SensorError::ConfigError(
+                    "m5-joystick dead_zone should be in [0, 1)",
+                ))
+            }
+        };
+        let (i2c_handle, i2c_address) =
+            super::i2c_from_config(&cfg, dependencies, DEFAULT_I2C_ADDRESS)?;
+        Ok(Arc::new(Mutex::new(Self::new(
+            i2c_handle,
+            i2c_address,
+            dead_zone,
+        ))))
+    }
+
+    fn axis(&self, raw: u8) -> f64 {
+        let position = ((f64::from(raw) - 128.0) / 127.0).clamp(-1.0, 1.0);
+        if position.abs() < self.dead_zone {
+            0.0
+        } else {
+            position
+        }
+    }
+}
+
+impl Sensor for Joystick {}
+
+impl SensorT<f64> for Joystick {
+    fn get_readings(&self) -> Result<TypedReadingsResult<f64>, SensorError> {
+        let mut state = [0_u8; 3];
+        self.i2c_handle
+            .lock()
+            .unwrap()
+            .read_i2c(self.i2c_address, &mut state)?;
+        Ok(HashMap::from([
+            ("x".to_string(), self.axis(state[0])),
+            ("y".to_string(), self.axis(state[1])),
+            ("pressed".to_string(), if state[2] != 0 { 1.0 } else { 0.0 }),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::Joystick;
+    use crate::common::i2c::FakeI2CHandle;
+    use crate::common::sensor::SensorT;
+
+    #[test_log::test]
+    fn test_joystick_readings() {
+        let i2c = Arc::new(Mutex::new(FakeI2CHandle::new_with_value(
+            "i2c0".to_string(),
+            [255, 130, 1],
+        )));
+        let joystick = Joystick::new(i2c.clone(), 0x52, 0.05);
+        let readings = joystick.get_readings().unwrap();
+        assert_eq!(readings.get("x"), Some(&1.0));
+        // within the dead zone
+        assert_eq!(readings.get("y"), Some(&0.0));
+        assert_eq!(readings.get("pressed"), Some(&1.0));
+
+        *i2c.lock().unwrap() = FakeI2CHandle::new_with_value("i2c0".to_string(), [0, 64, 0]);
+        let readings = joystick.get_readings().unwrap();
+        assert_eq!(readings.get("x"), Some(&-1.0));
+        assert!((readings.get("y").unwrap() + 64.0 / 127.0).abs() < 1e-9);
+        assert_eq!(readings.get("pressed"), Some(&0.0));
+    }
+}
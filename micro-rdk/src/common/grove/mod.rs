@@ -0,0 +1,47 @@
+//! Drivers for common Grove/M5Stack I2C units, enabled by the `grove` feature. Every unit is a
+//! sensor depending on a board and configured with the `i2c_bus` it is plugged on and an optional
+//! `i2c_address` when it was changed from the default one:
+//!
+//! - [sht30]: `sht30` temperature and humidity sensor (ENV unit)
+//! - [vl53l0x]: `vl53l0x` time of flight distance sensor (ToF unit)
+//! - [joystick]: `m5-joystick` joystick unit
+
+pub mod joystick;
+pub mod sht30;
+pub mod vl53l0x;
+
+use super::board::Board;
+use super::config::{AttributeError, ConfigType};
+use super::i2c::I2cHandleType;
+use super::registry::{get_board_from_dependencies, ComponentRegistry, Dependency};
+use super::sensor::SensorError;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    sht30::register_models(registry);
+    vl53l0x::register_models(registry);
+    joystick::register_models(registry);
+}
+
+/// Returns the I2C bus and address of a unit from its config
+pub(crate) fn i2c_from_config(
+    cfg: &ConfigType,
+    dependencies: Vec<Dependency>,
+    default_address: u8,
+) -> Result<(I2cHandleType, u8), SensorError> {
+    let board = get_board_from_dependencies(dependencies).ok_or(SensorError::ConfigError(
+        "grove unit missing board dependency",
+    ))?;
+    let i2c_bus = cfg
+        .get_attribute::<String>("i2c_bus")
+        .map_err(|_| SensorError::ConfigError("grove unit missing i2c_bus attribute"))?;
+    let i2c_address = match cfg.get_attribute::<u8>("i2c_address") {
+        Ok(address) => address,
+        Err(AttributeError::KeyNotFound(_)) => default_address,
+        Err(_) => {
+            return Err(SensorError::ConfigError(
+                "grove unit invalid i2c_address attribute",
+            ))
+        }
+    };
+    Ok((board.get_i2c_by_name(i2c_bus)?, i2c_address))
+}
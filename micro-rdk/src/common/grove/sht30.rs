@@ -0,0 +1,111 @@
+//! Sensirion SHT30 temperature and humidity sensor, found in the M5Stack ENV units. Datasheet:
+//! https://sensirion.com/media/documents/213E6A3B/63A5A569/Datasheet_SHT3x_DIS.pdf
+//!
+//! Each reading triggers a single shot, high repeatability measurement without clock stretching.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::common::config::ConfigType;
+use crate::common::i2c::{I2CHandle, I2cHandleType};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::sensirion::words_from_bytes;
+use crate::common::sensor::{Sensor, SensorError, SensorT, SensorType, TypedReadingsResult};
+
+const DEFAULT_I2C_ADDRESS: u8 = 0x44;
+const SINGLE_SHOT_HIGH_REPEATABILITY: [u8; 2] = [0x24, 0x00];
+// maximum duration of a high repeatability measurement
+const MEASUREMENT_DURATION: Duration = Duration::from_millis(16);
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("sht30", &Sht30::from_config)
+        .is_err()
+    {
+        log::error!("sht30 type is already registered");
+    }
+}
+
+#[derive(DoCommand, SensorReadings, Status)]
+pub struct Sht30 {
+    i2c_handle: I2cHandleType,
+    i2c_address: u8,
+}
+
+impl Sht30 {
+    pub fn new(i2c_handle: I2cHandleType, i2c_address: u8) -> Self {
+        Self {
+            i2c_handle,
+            i2c_address,
+        }
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        dependencies: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let (i2c_handle, i2c_address) =
+            super::i2c_from_config(&cfg, dependencies, DEFAULT_I2C_ADDRESS)?;
+        Ok(Arc::new(Mutex::new(Self::new(i2c_handle, i2c_address))))
+    }
+
+    /// Measures the temperature in celsius and the relative humidity in percent
+    pub fn measure(&self) -> Result<(f64, f64), SensorError> {
+        let mut i2c_handle = self.i2c_handle.lock().unwrap();
+        i2c_handle.write_i2c(self.i2c_address, &SINGLE_SHOT_HIGH_REPEATABILITY)?;
+        std::thread::sleep(MEASUREMENT_DURATION);
+        let mut measurement = [0_u8; 6];
+        i2c_handle.read_i2c(self.i2c_address, &mut measurement)?;
+        parse_measurement(&measurement)
+    }
+}
+
+// temperature and humidity words, each followed by its CRC
+fn parse_measurement(measurement: &[u8; 6]) -> Result<(f64, f64), SensorError> {
+    let [temperature, humidity] = words_from_bytes::<2>(measurement).ok_or(
+        SensorError::SensorGenericError("sht30 measurement CRC mismatch"),
+    )?;
+    Ok((
+        -45.0 + 175.0 * f64::from(temperature) / 65535.0,
+        100.0 * f64::from(humidity) / 65535.0,
+    ))
+}
+
+impl Sensor for Sht30 {}
+
+impl SensorT<f64> for Sht30 {
+    fn get_readings(&self) -> Result<TypedReadingsResult<f64>, SensorError> {
+        let (temperature, humidity) = self.measure()?;
+        Ok(HashMap::from([
+            ("temperature_celsius".to_string(), temperature),
+            ("relative_humidity_pct".to_string(), humidity),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_measurement;
+    use crate::common::sensirion::crc8;
+
+    #[test_log::test]
+    fn test_parse_measurement() {
+        let temperature = 0x6666_u16.to_be_bytes();
+        let humidity = 0x8000_u16.to_be_bytes();
+        let mut measurement = [
+            temperature[0],
+            temperature[1],
+            crc8(&temperature),
+            humidity[0],
+            humidity[1],
+            crc8(&humidity),
+        ];
+        let (celsius, relative_humidity) = parse_measurement(&measurement).unwrap();
+        assert!((celsius - 25.0).abs() < 0.01);
+        assert!((relative_humidity - 50.0).abs() < 0.01);
+
+        measurement[5] ^= 0xFF;
+        assert!(parse_measurement(&measurement).is_err());
+    }
+}
@@ -0,0 +1,334 @@
+//! ST VL53L0X time of flight distance sensor, found in the M5Stack ToF unit. Datasheet:
+//! https://www.st.com/resource/en/datasheet/vl53l0x.pdf
+//!
+//! ST only documents the sensor through its API, the initialization sequence and the register
+//! writes below are the ones of the API as distilled by the Pololu VL53L0X library. The default
+//! measurement timing budget (about 33ms) is kept, readings are single shot measurements reported
+//! as `distance_mm`. Nothing in range reads as an error.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::common::config::ConfigType;
+use crate::common::i2c::{Endian, I2cHandleType, RegisterMap};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::sensor::{
+    GenericReadingsResult, Readings, Sensor, SensorError, SensorResult, SensorType,
+};
+
+const DEFAULT_I2C_ADDRESS: u8 = 0x29;
+const TIMEOUT: Duration = Duration::from_millis(500);
+// the sensor reports 8190 or 8191 when nothing is in range
+const OUT_OF_RANGE_MM: u16 = 8190;
+
+const SYSRANGE_START: u8 = 0x00;
+const SYSTEM_SEQUENCE_CONFIG: u8 = 0x01;
+const SYSTEM_INTERRUPT_CONFIG_GPIO: u8 = 0x0A;
+const SYSTEM_INTERRUPT_CLEAR: u8 = 0x0B;
+const RESULT_INTERRUPT_STATUS: u8 = 0x13;
+const RESULT_RANGE_STATUS: u8 = 0x14;
+const FINAL_RANGE_CONFIG_MIN_COUNT_RATE_RTN_LIMIT: u8 = 0x44;
+const DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD: u8 = 0x4E;
+const DYNAMIC_SPAD_REF_EN_START_OFFSET: u8 = 0x4F;
+const MSRC_CONFIG_CONTROL: u8 = 0x60;
+const GPIO_HV_MUX_ACTIVE_HIGH: u8 = 0x84;
+const VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV: u8 = 0x89;
+const GLOBAL_CONFIG_SPAD_ENABLES_REF_0: u8 = 0xB0;
+const GLOBAL_CONFIG_REF_EN_START_SELECT: u8 = 0xB6;
+// selects the register page of the undocumented registers
+const PAGE_SELECT: u8 = 0xFF;
+
+// Register writes of the default tuning settings, `DefaultTuningSettings` of the ST API
+const DEFAULT_TUNING: &[(u8, u8)] = &[
+    (0xFF, 0x01),
+    (0x00, 0x00),
+    (0xFF, 0x00),
+    (0x09, 0x00),
+    (0x10, 0x00),
+    (0x11, 0x00),
+    (0x24, 0x01),
+    (0x25, 0xFF),
+    (0x75, 0x00),
+    (0xFF, 0x01),
+    (0x4E, 0x2C),
+    (0x48, 0x00),
+    (0x30, 0x20),
+    (0xFF, 0x00),
+    (0x30, 0x09),
+    (0x54, 0x00),
+    (0x31, 0x04),
+    (0x32, 0x03),
+    (0x40, 0x83),
+    (0x46, 0x25),
+    (0x60, 0x00),
+    (0x27, 0x00),
+    (0x50, 0x06),
+    (0x51, 0x00),
+    (0x52, 0x96),
+    (0x56, 0x08),
+    (0x57, 0x30),
+    (0x61, 0x00),
+    (0x62, 0x00),
+    (0x64, 0x00),
+    (0x65, 0x00),
+    (0x66, 0xA0),
+    (0xFF, 0x01),
+    (0x22, 0x32),
+    (0x47, 0x14),
+    (0x49, 0xFF),
+    (0x4A, 0x00),
+    (0xFF, 0x00),
+    (0x7A, 0x0A),
+    (0x7B, 0x00),
+    (0x78, 0x21),
+    (0xFF, 0x01),
+    (0x23, 0x34),
+    (0x42, 0x00),
+    (0x44, 0xFF),
+    (0x45, 0x26),
+    (0x46, 0x05),
+    (0x40, 0x40),
+    (0x0E, 0x06),
+    (0x20, 0x1A),
+    (0x43, 0x40),
+    (0xFF, 0x00),
+    (0x34, 0x03),
+    (0x35, 0x44),
+    (0xFF, 0x01),
+    (0x31, 0x04),
+    (0x4B, 0x09),
+    (0x4C, 0x05),
+    (0x4D, 0x04),
+    (0xFF, 0x00),
+    (0x44, 0x00),
+    (0x45, 0x20),
+    (0x47, 0x08),
+    (0x48, 0x28),
+    (0x67, 0x00),
+    (0x70, 0x04),
+    (0x71, 0x01),
+    (0x72, 0xFE),
+    (0x76, 0x00),
+    (0x77, 0x00),
+    (0xFF, 0x01),
+    (0x0D, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x01),
+    (0x01, 0xF8),
+    (0xFF, 0x01),
+    (0x8E, 0x01),
+    (0x00, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x00),
+];
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("vl53l0x", &Vl53l0x::from_config)
+        .is_err()
+    {
+        log::error!("vl53l0x type is already registered");
+    }
+}
+
+#[derive(DoCommand, Status)]
+pub struct Vl53l0x {
+    registers: RegisterMap<I2cHandleType>,
+    // read during initialization, written back before every measurement
+    stop_variable: u8,
+}
+
+impl Vl53l0x {
+    pub fn new(i2c_handle: I2cHandleType, i2c_address: u8) -> Result<Self, SensorError> {
+        let mut sensor = Self {
+            registers: RegisterMap::new(i2c_handle, i2c_address, Endian::Big),
+            stop_variable: 0,
+        };
+        sensor.init()?;
+        Ok(sensor)
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        dependencies: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let (i2c_handle, i2c_address) =
+            super::i2c_from_config(&cfg, dependencies, DEFAULT_I2C_ADDRESS)?;
+        Ok(Arc::new(Mutex::new(Self::new(i2c_handle, i2c_address)?)))
+    }
+
+    fn init(&mut self) -> Result<(), SensorError> {
+        // 2.8V I/O, I2C standard mode
+        self.registers
+            .modify_u8(VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV, |value| value | 0x01)?;
+        self.registers.write_u8(0x88, 0x00)?;
+
+        self.write_all(&[(0x80, 0x01), (PAGE_SELECT, 0x01), (0x00, 0x00)])?;
+        self.stop_variable = self.registers.read_u8(0x91)?;
+        self.write_all(&[(0x00, 0x01), (PAGE_SELECT, 0x00), (0x80, 0x00)])?;
+
+        // disable the MSRC and pre range signal rate limit checks, the final range signal rate
+        // limit is 0.25 MCPS in 9.7 fixed point
+        self.registers
+            .modify_u8(MSRC_CONFIG_CONTROL, |value| value | 0x12)?;
+        self.registers
+            .write_u16(FINAL_RANGE_CONFIG_MIN_COUNT_RATE_RTN_LIMIT, 32)?;
+        self.registers.write_u8(SYSTEM_SEQUENCE_CONFIG, 0xFF)?;
+
+        let (spad_count, spad_type_is_aperture) = self.read_spad_info()?;
+        let mut spad_map = [0_u8; 6];
+        self.registers
+            .read_bytes(GLOBAL_CONFIG_SPAD_ENABLES_REF_0, &mut spad_map)?;
+        self.write_all(&[
+            (PAGE_SELECT, 0x01),
+            (DYNAMIC_SPAD_REF_EN_START_OFFSET, 0x00),
+            (DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD, 0x2C),
+            (PAGE_SELECT, 0x00),
+            (GLOBAL_CONFIG_REF_EN_START_SELECT, 0xB4),
+        ])?;
+        let spad_map = reference_spad_map(spad_map, spad_count, spad_type_is_aperture);
+        self.registers
+            .write_bytes(GLOBAL_CONFIG_SPAD_ENABLES_REF_0, &spad_map)?;
+
+        self.write_all(DEFAULT_TUNING)?;
+
+        // new sample ready interrupt, active low
+        self.registers
+            .write_u8(SYSTEM_INTERRUPT_CONFIG_GPIO, 0x04)?;
+        self.registers
+            .modify_u8(GPIO_HV_MUX_ACTIVE_HIGH, |value| value & !0x10)?;
+        self.registers.write_u8(SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+
+        // VHV and phase calibrations, then enable the DSS, pre range and final range steps
+        self.registers.write_u8(SYSTEM_SEQUENCE_CONFIG, 0x01)?;
+        self.single_reference_calibration(0x40)?;
+        self.registers.write_u8(SYSTEM_SEQUENCE_CONFIG, 0x02)?;
+        self.single_reference_calibration(0x00)?;
+        self.registers.write_u8(SYSTEM_SEQUENCE_CONFIG, 0xE8)?;
+        Ok(())
+    }
+
+    fn write_all(&mut self, writes: &[(u8, u8)]) -> Result<(), SensorError> {
+        for (register, value) in writes {
+            self.registers.write_u8(*register, *value)?;
+        }
+        Ok(())
+    }
+
+    fn wait_for(&mut self, register: u8, ready: impl Fn(u8) -> bool) -> Result<(), SensorError> {
+        let start = Instant::now();
+        while !ready(self.registers.read_u8(register)?) {
+            if start.elapsed() > TIMEOUT {
+                return Err(SensorError::SensorGenericError("vl53l0x timed out"));
+            }
+        }
+        Ok(())
+    }
+
+    // number and type of the reference SPADs, from the NVM of the sensor
+    fn read_spad_info(&mut self) -> Result<(u8, bool), SensorError> {
+        self.write_all(&[(0x80, 0x01), (PAGE_SELECT, 0x01), (0x00, 0x00)])?;
+        self.registers.write_u8(PAGE_SELECT, 0x06)?;
+        self.registers.modify_u8(0x83, |value| value | 0x04)?;
+        self.write_all(&[
+            (PAGE_SELECT, 0x07),
+            (0x81, 0x01),
+            (0x80, 0x01),
+            (0x94, 0x6B),
+            (0x83, 0x00),
+        ])?;
+        self.wait_for(0x83, |value| value != 0)?;
+        self.registers.write_u8(0x83, 0x01)?;
+        let info = self.registers.read_u8(0x92)?;
+
+        self.write_all(&[(0x81, 0x00), (PAGE_SELECT, 0x06)])?;
+        self.registers.modify_u8(0x83, |value| value & !0x04)?;
+        self.write_all(&[
+            (PAGE_SELECT, 0x01),
+            (0x00, 0x01),
+            (PAGE_SELECT, 0x00),
+            (0x80, 0x00),
+        ])?;
+        Ok((info & 0x7F, info & 0x80 != 0))
+    }
+
+    fn single_reference_calibration(&mut self, vhv_init: u8) -> Result<(), SensorError> {
+        self.registers.write_u8(SYSRANGE_START, 0x01 | vhv_init)?;
+        self.wait_for(RESULT_INTERRUPT_STATUS, |status| status & 0x07 != 0)?;
+        self.registers.write_u8(SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+        self.registers.write_u8(SYSRANGE_START, 0x00)?;
+        Ok(())
+    }
+
+    /// Measures the distance to the closest target in millimeters
+    pub fn read_distance_mm(&mut self) -> Result<u16, SensorError> {
+        self.write_all(&[(0x80, 0x01), (PAGE_SELECT, 0x01), (0x00, 0x00)])?;
+        self.registers.write_u8(0x91, self.stop_variable)?;
+        self.write_all(&[
+            (0x00, 0x01),
+            (PAGE_SELECT, 0x00),
+            (0x80, 0x00),
+            (SYSRANGE_START, 0x01),
+        ])?;
+        self.wait_for(SYSRANGE_START, |value| value & 0x01 == 0)?;
+        self.wait_for(RESULT_INTERRUPT_STATUS, |status| status & 0x07 != 0)?;
+        let distance = self.registers.read_u16(RESULT_RANGE_STATUS + 10)?;
+        self.registers.write_u8(SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+        if distance >= OUT_OF_RANGE_MM {
+            return Err(SensorError::SensorGenericError("vl53l0x out of range"));
+        }
+        Ok(distance)
+    }
+}
+
+/// Enables the first `spad_count` reference SPADs of the map read from the sensor, the aperture
+/// SPADs start at index 12
+fn reference_spad_map(mut spad_map: [u8; 6], spad_count: u8, aperture: bool) -> [u8; 6] {
+    let first_spad = if aperture { 12 } else { 0 };
+    let mut enabled = 0;
+    for spad in 0..48 {
+        let (byte, bit) = (spad / 8, spad % 8);
+        if spad < first_spad || enabled == spad_count {
+            spad_map[byte] &= !(1 << bit);
+        } else if spad_map[byte] & (1 << bit) != 0 {
+            enabled += 1;
+        }
+    }
+    spad_map
+}
+
+impl Sensor for Vl53l0x {}
+
+impl Readings for Vl53l0x {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let distance = self.read_distance_mm()?;
+        Ok(HashMap::from([(
+            "distance_mm".to_string(),
+            SensorResult::<f64> {
+                value: f64::from(distance),
+            }
+            .into(),
+        )]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reference_spad_map;
+
+    #[test_log::test]
+    fn test_reference_spad_map() {
+        let spad_map = [0xFF; 6];
+        assert_eq!(
+            reference_spad_map(spad_map, 5, false),
+            [0x1F, 0, 0, 0, 0, 0]
+        );
+        // aperture SPADs start at 12, holes in the map are skipped
+        let spad_map = [0xFF, 0xFF, 0x55, 0xFF, 0xFF, 0xFF];
+        assert_eq!(
+            reference_spad_map(spad_map, 6, true),
+            [0, 0xF0, 0x05, 0, 0, 0]
+        );
+    }
+}
@@ -1,9 +1,10 @@
 use core::fmt;
 use std::{
+    collections::HashMap,
     convert::Infallible,
     fmt::Debug,
     marker::PhantomData,
-    sync::{Arc, Mutex},
+    sync::{Arc, RwLock},
     time::{Duration, Instant},
 };
 
@@ -11,12 +12,19 @@ use crate::{
     common::analog::AnalogReader,
     common::board::Board,
     common::robot::LocalRobot,
-    google::rpc::Status,
-    proto::{self, component, robot},
+    google::{
+        self,
+        rpc::{ErrorInfo, Status},
+    },
+    proto::{self, app::v1::ConfigResponse, component, robot},
 };
+use async_io::Timer;
 use bytes::{BufMut, BytesMut};
-use futures_lite::{future, Future};
-use http_body_util::BodyExt;
+use futures_lite::{
+    future::{self, BoxedLocal},
+    Future,
+};
+use http_body_util::{BodyExt, LengthLimitError, Limited};
 use hyper::body::{Body, Frame};
 use hyper::{
     body::{self, Bytes},
@@ -26,14 +34,52 @@ use hyper::{
 };
 use log::*;
 use prost::Message;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::pin::Pin;
 use std::rc::Rc;
 use std::task::{Context, Poll};
 use thiserror::Error;
 
+use super::authorization::{required_scope, AuthPolicy, Scope};
+use super::base::{record_base_command, stop_base_after};
+use super::build_info::{BuildInfo, GetVersionRequest, GetVersionResponse};
+use super::config::{AttributeError, Kind};
+use super::digital_interrupt::{StreamTicksRequest, StreamTicksResponse, TickStream};
+use super::generic::GenericError;
+use super::log::LOG_BUFFER;
+use super::motor::StopOnDrop;
+use super::self_test::{do_motor_self_test, do_servo_self_test, self_test_arguments};
 use super::webrtc::grpc::WebRtcGrpcService;
 
+/// Deadline applied to requests that don't carry a grpc-timeout header
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Log entries sent at most by each message of a TailRobotPartLogs stream, keeping messages
+/// within a WebRTC data channel packet
+const LOG_TAIL_MAX_ENTRIES: usize = 4;
+/// Interval at which a TailRobotPartLogs stream checks for new log entries
+const LOG_TAIL_INTERVAL: Duration = Duration::from_millis(500);
+/// Interval at which a StreamTicks stream samples the digital interrupts, the time of a tick is
+/// accurate to this interval on boards that don't timestamp the edges
+const TICK_STREAM_INTERVAL: Duration = Duration::from_millis(20);
+/// StreamTicks streams a connection follows at once, the least recently polled one is dropped
+/// to follow a new request
+const MAX_TICK_STREAMS: usize = DEFAULT_MAX_CONCURRENT_STREAMS as usize;
+
+/// Number of requests an HTTP2 connection may have in flight at once. Each stream holds at most
+/// one response buffer, so this bounds the memory used by a connection.
+pub const DEFAULT_MAX_CONCURRENT_STREAMS: u32 = 4;
+
+/// Requests an HTTP2 connection may make per second on average, the rate isn't limited unless
+/// the robot config sets one
+pub const DEFAULT_MAX_REQUEST_RATE: u32 = 0;
+/// Requests an HTTP2 connection may make at once on top of its average rate
+pub const DEFAULT_MAX_REQUEST_BURST: u32 = 50;
+/// Size in bytes of the largest request body accepted from an HTTP2 connection
+pub const DEFAULT_MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// Type of the service whose attributes set the [RequestLimits] of the HTTP2 connections
+pub static REQUEST_LIMITS_SERVICE_TYPE: &str = "request_limits";
+
 #[cfg(feature = "camera")]
 static GRPC_BUFFER_SIZE: usize = 10240;
 #[cfg(not(feature = "camera"))]
@@ -74,10 +120,10 @@ impl GrpcResponse for GrpcBody {
             .unwrap()
             .insert("grpc-status", code.into());
         if let Some(message) = message {
-            self.trailers
-                .as_mut()
-                .unwrap()
-                .insert("grpc-message", message.parse().unwrap());
+            self.trailers.as_mut().unwrap().insert(
+                "grpc-message",
+                encode_grpc_message(&message).parse().unwrap(),
+            );
         }
     }
     fn get_data(&mut self) -> Bytes {
@@ -85,6 +131,61 @@ impl GrpcResponse for GrpcBody {
     }
 }
 
+/// Runs `handler` until `deadline`, returning whether it completed before the deadline.
+///
+/// Handlers that await the hardware (GoFor, self tests) are dropped when the deadline passes.
+/// The other handlers call the components synchronously on the single threaded executor of the
+/// connection: a call blocked on the hardware, such as a hung I2C transaction, can't be
+/// cancelled and holds the connection until it returns, the request then only fails with
+/// DEADLINE_EXCEEDED.
+async fn run_until<F>(handler: F, deadline: Instant) -> Result<bool, GrpcError>
+where
+    F: Future<Output = Result<(), GrpcError>>,
+{
+    let completed = future::or(async { handler.await.map(|_| true) }, async {
+        Timer::at(deadline).await;
+        Ok(false)
+    })
+    .await?;
+    Ok(completed && Instant::now() < deadline)
+}
+
+// Per https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md, grpc-timeout is at most
+// 8 digits followed by a unit
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    if !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount = amount.parse::<u64>().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 3600)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+// Per https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md, grpc-message is
+// percent-encoded so error messages can carry any character a header value can't
+fn encode_grpc_message(message: &str) -> String {
+    let mut encoded = String::with_capacity(message.len());
+    for byte in message.bytes() {
+        if (0x20..=0x7e).contains(&byte) && byte != b'%' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
 impl Default for GrpcBody {
     fn default() -> Self {
         Self::new()
@@ -123,11 +224,127 @@ pub trait GrpcResponse {
     fn get_data(&mut self) -> Bytes;
 }
 
+/// Limits protecting the device from HTTP2 clients flooding it with requests. Requests beyond
+/// them are answered with a `RESOURCE_EXHAUSTED` status without being processed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RequestLimits {
+    /// Requests a connection may make per second on average, 0 doesn't limit the rate
+    pub max_rate: u32,
+    /// Requests a connection may make at once on top of its average rate
+    pub max_burst: u32,
+    /// Size in bytes of the largest request body, larger bodies are not buffered
+    pub max_request_size: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_rate: DEFAULT_MAX_REQUEST_RATE,
+            max_burst: DEFAULT_MAX_REQUEST_BURST,
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+        }
+    }
+}
+
+impl TryFrom<&Kind> for RequestLimits {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        Ok(Self {
+            max_rate: value
+                .get("max_request_rate")?
+                .map(u32::try_from)
+                .transpose()?
+                .unwrap_or(DEFAULT_MAX_REQUEST_RATE),
+            max_burst: value
+                .get("max_request_burst")?
+                .map(u32::try_from)
+                .transpose()?
+                .unwrap_or(DEFAULT_MAX_REQUEST_BURST),
+            max_request_size: value
+                .get("max_request_size_bytes")?
+                .map(u32::try_from)
+                .transpose()?
+                .map_or(DEFAULT_MAX_REQUEST_SIZE, |size| size as usize),
+        })
+    }
+}
+
+impl RequestLimits {
+    /// Reads the limits from the attributes of the `request_limits` service, the defaults apply
+    /// when the service isn't configured
+    pub fn from_config(cfg: &ConfigResponse) -> Result<Self, AttributeError> {
+        let svc_cfg = cfg.config.as_ref().and_then(|robot_config| {
+            robot_config
+                .services
+                .iter()
+                .find(|svc_cfg| svc_cfg.r#type == REQUEST_LIMITS_SERVICE_TYPE)
+        });
+        match svc_cfg {
+            Some(svc_cfg) => Self::try_from(&Kind::try_from(
+                google::protobuf::value::Kind::StructValue(
+                    svc_cfg.attributes.clone().unwrap_or_default(),
+                ),
+            )?),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+/// Token bucket bounding the rate of the requests of a connection
+#[derive(Debug)]
+struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    fn new(limits: &RequestLimits) -> Self {
+        let burst = f64::from(limits.max_burst.max(1));
+        Self {
+            rate: f64::from(limits.max_rate),
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    // takes a token for a request made at `now`, false when the connection is over its rate
+    fn try_acquire(&mut self, now: Instant) -> bool {
+        if self.rate == 0.0 {
+            return true;
+        }
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
 #[derive(Clone)]
 pub struct GrpcServer<R> {
     pub(crate) response: R,
     pub(crate) buffer: Rc<RefCell<BytesMut>>,
-    robot: Arc<Mutex<LocalRobot>>,
+    robot: Arc<RwLock<LocalRobot>>,
+    rpc_timeout: Duration,
+    // sequence number of the next log entry sent by a TailRobotPartLogs stream
+    log_tail_seq: u64,
+    // StreamTicks streams, the most recently polled last
+    tick_streams: Vec<TickStream>,
+    // keys of the robot, `None` when requests are not restricted
+    auth: Option<Rc<AuthPolicy>>,
+    // scope the connection authenticated with, shared by the requests of the connection
+    connection_scope: Rc<Cell<Option<Scope>>>,
+    // scope of the access token of the request being processed
+    bearer_scope: Option<Scope>,
+    max_request_size: usize,
+    // shared by the requests of the connection
+    rate_limiter: Rc<RefCell<RateLimiter>>,
 }
 
 impl<R> Debug for GrpcServer<R>
@@ -147,14 +364,43 @@ impl<R> GrpcServer<R>
 where
     R: GrpcResponse,
 {
-    pub fn new(robot: Arc<Mutex<LocalRobot>>, body: R) -> Self {
+    pub fn new(robot: Arc<RwLock<LocalRobot>>, body: R) -> Self {
         GrpcServer {
             response: body,
             buffer: Rc::new(RefCell::new(BytesMut::with_capacity(GRPC_BUFFER_SIZE))),
             robot,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            log_tail_seq: 0,
+            tick_streams: Vec::new(),
+            auth: None,
+            connection_scope: Rc::new(Cell::new(None)),
+            bearer_scope: None,
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+            rate_limiter: Rc::new(RefCell::new(RateLimiter::new(&RequestLimits::default()))),
         }
     }
 
+    /// Sets the rate and size limits of the requests received through HTTP2
+    pub fn with_request_limits(mut self, limits: RequestLimits) -> Self {
+        self.max_request_size = limits.max_request_size;
+        self.rate_limiter = Rc::new(RefCell::new(RateLimiter::new(&limits)));
+        self
+    }
+
+    /// Restricts the requests to the scope of the credentials of the client when `auth` has
+    /// keys, see [authorization](crate::common::authorization)
+    pub fn with_auth_policy(mut self, auth: Rc<AuthPolicy>) -> Self {
+        self.auth = auth.is_enabled().then_some(auth);
+        self
+    }
+
+    /// Sets the deadline of requests that don't specify one through a grpc-timeout header, see
+    /// [run_until] for the handlers it can interrupt
+    pub fn with_rpc_timeout(mut self, rpc_timeout: Duration) -> Self {
+        self.rpc_timeout = rpc_timeout;
+        self
+    }
+
     fn validate_rpc(message: &Bytes) -> Result<&[u8], GrpcError> {
         // Per https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md, we're expecting a
         // 5-byte header followed by the actual protocol buffer data. The 5 bytes in the header are
@@ -175,18 +421,45 @@ where
         Ok(rest)
     }
 
+    fn authorize(&self, path: &str) -> Result<(), ServerError> {
+        let Some(required) = required_scope(path) else {
+            return Ok(());
+        };
+        if self.auth.is_none() {
+            return Ok(());
+        }
+        match self.bearer_scope.or(self.connection_scope.get()) {
+            Some(scope) if scope >= required => Ok(()),
+            Some(_) => Err(ServerError::new(
+                GrpcError::RpcPermissionDenied,
+                Some(format!("{} requires operator credentials", path).into()),
+            )),
+            None => Err(ServerError::from(GrpcError::RpcUnauthenticated)),
+        }
+    }
+
+    /// Polls the server stream `path`, returning whether a message was produced and when the
+    /// stream should be polled next
     pub(crate) fn handle_rpc_stream(
         &mut self,
         path: &str,
         payload: &[u8],
-    ) -> Result<std::time::Instant, ServerError> {
+    ) -> Result<(bool, std::time::Instant), ServerError> {
+        self.authorize(path)?;
         match path {
-            "/viam.robot.v1.RobotService/StreamStatus" => self.robot_status_stream(payload),
-            _ => Err(ServerError::from(GrpcError::RpcUnavailable)),
+            "/viam.robot.v1.RobotService/StreamStatus" => {
+                self.robot_status_stream(payload).map(|next| (true, next))
+            }
+            "/viam.app.v1.RobotService/TailRobotPartLogs" => {
+                self.tail_logs_stream(payload).map(|next| (true, next))
+            }
+            "/viam.component.board.v1.BoardService/StreamTicks" => self.board_stream_ticks(payload),
+            _ => Err(ServerError::method_not_implemented(path)),
         }
     }
 
     pub(crate) fn handle_request(&mut self, path: &str, payload: &[u8]) -> Result<(), ServerError> {
+        self.authorize(path)?;
         match path {
             "/viam.component.base.v1.BaseService/SetPower" => self.base_set_power(payload),
             "/viam.component.base.v1.BaseService/Stop" => self.base_stop(payload),
@@ -194,6 +467,9 @@ where
             "/viam.component.base.v1.BaseService/Spin" => self.base_spin(payload),
             "/viam.component.base.v1.BaseService/SetVelocity" => self.base_set_velocity(payload),
             "/viam.component.base.v1.BaseService/IsMoving" => self.base_is_moving(payload),
+            "/viam.component.base.v1.BaseService/GetProperties" => {
+                self.base_get_properties(payload)
+            }
             "/viam.component.board.v1.BoardService/GetDigitalInterruptValue" => {
                 self.board_get_digital_interrupt_value(payload)
             }
@@ -236,7 +512,6 @@ where
             "/viam.component.motor.v1.MotorService/GetProperties" => {
                 self.motor_get_properties(payload)
             }
-            "/viam.component.motor.v1.MotorService/GoFor" => self.motor_go_for(payload),
             "/viam.component.motor.v1.MotorService/GoTo" => self.motor_go_to(payload),
             "/viam.component.motor.v1.MotorService/IsPowered" => self.motor_is_powered(payload),
             "/viam.component.motor.v1.MotorService/IsMoving" => self.motor_is_moving(payload),
@@ -247,8 +522,52 @@ where
             "/viam.component.motor.v1.MotorService/Stop" => self.motor_stop(payload),
             "/viam.component.motor.v1.MotorService/DoCommand" => self.motor_do_command(payload),
             "/viam.robot.v1.RobotService/ResourceNames" => self.resource_names(payload),
+            "/viam.robot.v1.RobotService/ResourceRPCSubtypes" => {
+                self.resource_rpc_subtypes(payload)
+            }
             "/viam.robot.v1.RobotService/GetStatus" => self.robot_status(payload),
             "/viam.robot.v1.RobotService/GetOperations" => self.robot_get_oprations(payload),
+            "/viam.robot.v1.RobotService/GetVersion" => self.robot_get_version(payload),
+            "/viam.robot.v1.RobotService/FrameSystemConfig" => {
+                self.robot_frame_system_config(payload)
+            }
+            "/viam.component.base.v1.BaseService/GetGeometries" => {
+                self.get_geometries("base", payload)
+            }
+            "/viam.component.board.v1.BoardService/GetGeometries" => {
+                self.get_geometries("board", payload)
+            }
+            #[cfg(feature = "camera")]
+            "/viam.component.camera.v1.CameraService/GetGeometries" => {
+                self.get_geometries("camera", payload)
+            }
+            "/viam.component.encoder.v1.EncoderService/GetGeometries" => {
+                self.get_geometries("encoder", payload)
+            }
+            "/viam.component.generic.v1.GenericService/GetGeometries" => {
+                self.get_geometries("generic", payload)
+            }
+            "/viam.component.motor.v1.MotorService/GetGeometries" => {
+                self.get_geometries("motor", payload)
+            }
+            "/viam.component.movementsensor.v1.MovementSensorService/GetGeometries" => {
+                self.get_geometries("movement_sensor", payload)
+            }
+            "/viam.component.powersensor.v1.PowerSensorService/GetGeometries" => {
+                self.get_geometries("power_sensor", payload)
+            }
+            "/viam.component.sensor.v1.SensorService/GetGeometries" => {
+                self.get_geometries("sensor", payload)
+            }
+            "/viam.component.servo.v1.ServoService/GetGeometries" => {
+                self.get_geometries("servo", payload)
+            }
+            "/viam.component.switch.v1.SwitchService/GetGeometries" => {
+                self.get_geometries("switch", payload)
+            }
+            "/viam.component.button.v1.ButtonService/GetGeometries" => {
+                self.get_geometries("button", payload)
+            }
             "/proto.rpc.v1.AuthService/Authenticate" => self.auth_service_authentificate(payload),
             "/viam.component.sensor.v1.SensorService/GetReadings" => {
                 self.sensor_get_readings(payload)
@@ -310,33 +629,109 @@ where
             "/viam.component.servo.v1.ServoService/IsMoving" => self.servo_is_moving(payload),
             "/viam.component.servo.v1.ServoService/Stop" => self.servo_stop(payload),
             "/viam.component.servo.v1.ServoService/DoCommand" => self.servo_do_command(payload),
-            _ => Err(ServerError::from(GrpcError::RpcUnimplemented)),
+            "/viam.component.switch.v1.SwitchService/SetPosition" => {
+                self.switch_set_position(payload)
+            }
+            "/viam.component.switch.v1.SwitchService/GetPosition" => {
+                self.switch_get_position(payload)
+            }
+            "/viam.component.switch.v1.SwitchService/GetNumberOfPositions" => {
+                self.switch_get_number_of_positions(payload)
+            }
+            "/viam.component.switch.v1.SwitchService/DoCommand" => self.switch_do_command(payload),
+            "/viam.component.button.v1.ButtonService/Push" => self.button_push(payload),
+            "/viam.component.button.v1.ButtonService/DoCommand" => self.button_do_command(payload),
+            "/viam.component.audioinput.v1.AudioInputService/Properties" => {
+                self.audio_input_properties(payload)
+            }
+            "/viam.component.audioinput.v1.AudioInputService/DoCommand" => {
+                self.audio_input_do_command(payload)
+            }
+            _ => Err(ServerError::method_not_implemented(path)),
         }
     }
 
-    fn process_request(&mut self, path: &str, msg: Bytes) {
-        let payload = Self::validate_rpc(&msg).map_err(ServerError::from);
-        match payload.and_then(|payload| self.handle_request(path, payload)) {
-            Ok(_) => {}
-            Err(e) => {
-                let message = Some(e.to_string());
-                self.response.set_status(e.status_code(), message);
+    async fn process_request(&mut self, path: &str, msg: Bytes) {
+        let res = match Self::validate_rpc(&msg) {
+            Ok(payload) => self.handle_request_async(path, payload).await,
+            Err(err) => Err(ServerError::from(err)),
+        };
+        if let Err(e) = res {
+            let message = Some(e.to_string());
+            self.response.set_status(e.status_code(), message);
+        }
+    }
+
+    /// Handles the request like [handle_request](Self::handle_request), awaiting the handlers
+    /// that wait for the hardware instead of blocking the executor
+    pub(crate) async fn handle_request_async(
+        &mut self,
+        path: &str,
+        payload: &[u8],
+    ) -> Result<(), ServerError> {
+        if path == "/viam.component.motor.v1.MotorService/GoFor" {
+            self.authorize(path)?;
+            return self.motor_go_for(payload).await;
+        }
+        match self.self_test_request(path, payload)? {
+            Some(self_test) => {
+                let result = self_test.await.map_err(ServerError::from_component_error)?;
+                self.encode_message(proto::common::v1::DoCommandResponse {
+                    result: Some(result),
+                })
             }
+            None => self.handle_request(path, payload),
         }
     }
 
+    // returns the self test a motor or servo DoCommand request asks for, see
+    // [self_test](crate::common::self_test)
+    fn self_test_request(
+        &self,
+        path: &str,
+        payload: &[u8],
+    ) -> Result<Option<BoxedLocal<Result<google::protobuf::Struct, GenericError>>>, ServerError>
+    {
+        let is_motor = match path {
+            "/viam.component.motor.v1.MotorService/DoCommand" => true,
+            "/viam.component.servo.v1.ServoService/DoCommand" => false,
+            _ => return Ok(None),
+        };
+        let req = proto::common::v1::DoCommandRequest::decode(payload)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let Some(args) = self_test_arguments(req.command.as_ref()) else {
+            return Ok(None);
+        };
+        self.authorize(path)?;
+        let args = args.map_err(ServerError::from_component_error)?;
+        let robot = self.robot.read().unwrap();
+        Ok(Some(if is_motor {
+            let motor = robot
+                .get_motor_by_name(req.name.clone())
+                .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
+            Box::pin(do_motor_self_test(motor, args))
+        } else {
+            let servo = robot
+                .get_servo_by_name(req.name.clone())
+                .ok_or_else(|| ServerError::resource_not_found("servo", req.name))?;
+            Box::pin(do_servo_self_test(servo, args))
+        }))
+    }
+
     fn motor_get_position(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::motor::v1::GetPositionRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let motor = match self.robot.lock().unwrap().get_motor_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let motor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_motor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
         let pos = motor
             .lock()
             .unwrap()
             .get_position()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::motor::v1::GetPositionResponse {
             position: pos as f64,
         };
@@ -345,60 +740,86 @@ where
 
     fn motor_get_properties(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::motor::v1::GetPropertiesRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let motor = match self.robot.lock().unwrap().get_motor_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let motor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_motor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
         let props: component::motor::v1::GetPropertiesResponse =
             motor.lock().unwrap().get_properties().into();
         self.encode_message(props)
     }
 
-    fn motor_go_for(&mut self, _message: &[u8]) -> Result<(), ServerError> {
-        // TODO: internal go_for can't wait without blocking executor, must be waited from here.
-        // requires refactoring this function (and its callers) to be async
-        /*
+    // the motor is only locked by each of its calls, so that another request can stop it while
+    // the revolutions are awaited
+    async fn motor_go_for(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::motor::v1::GoForRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let motor = match self.robot.lock().unwrap().get_motor_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
-        let mut motor = motor.lock().unwrap();
-
-        if let Some(dur) =  motor.go_for(req.rpm, req.revolutions).map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err)))? {
-            // async wait for duration
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let mut motor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_motor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
+        let duration = motor
+            .lock()
+            .unwrap()
+            .go_for_with_extra(req.rpm, req.revolutions, req.extra.clone())
+            .map_err(ServerError::from_component_error)?;
+        if let Some(duration) = duration {
+            // the motor is stopped if the request is dropped before the revolutions complete
+            let guard = StopOnDrop::new(&mut motor);
+            Timer::after(duration).await;
+            guard.disarm();
+            motor
+                .lock()
+                .unwrap()
+                .stop_with_extra(req.extra)
+                .map_err(ServerError::from_component_error)?;
         }
-        motor.lock().unwrap();
-
         let resp = component::motor::v1::GoForResponse {};
         self.encode_message(resp)
-        */
-        Err(ServerError::from(GrpcError::RpcUnimplemented))
     }
 
     fn motor_go_to(&mut self, _message: &[u8]) -> Result<(), ServerError> {
         Err(ServerError::from(GrpcError::RpcUnimplemented))
     }
 
-    fn motor_is_powered(&mut self, _message: &[u8]) -> Result<(), ServerError> {
-        Err(ServerError::from(GrpcError::RpcUnimplemented))
+    fn motor_is_powered(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::motor::v1::IsPoweredRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let motor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_motor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
+        let (is_on, power_pct) = motor
+            .lock()
+            .unwrap()
+            .is_powered()
+            .map_err(ServerError::from_component_error)?;
+        let resp = component::motor::v1::IsPoweredResponse { is_on, power_pct };
+        self.encode_message(resp)
     }
 
     fn motor_is_moving(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::motor::v1::IsMovingRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let motor = match self.robot.lock().unwrap().get_motor_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let motor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_motor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
         let resp = component::motor::v1::IsMovingResponse {
             is_moving: motor
                 .lock()
                 .unwrap()
                 .is_moving()
-                .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?,
+                .map_err(ServerError::from_component_error)?,
         };
         self.encode_message(resp)
     }
@@ -409,156 +830,325 @@ where
 
     fn motor_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::DoCommandRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let motor = match self.robot.lock().unwrap().get_motor_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let motor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_motor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
         let res = motor
             .lock()
             .unwrap()
             .do_command(req.command)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::DoCommandResponse { result: res };
         self.encode_message(resp)
     }
 
     fn auth_service_authentificate(&mut self, message: &[u8]) -> Result<(), ServerError> {
-        let _req = proto::rpc::v1::AuthenticateRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let resp = proto::rpc::v1::AuthenticateResponse {
-            access_token: "esp32".to_string(),
+        let req = proto::rpc::v1::AuthenticateRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let access_token = match self.auth.as_ref() {
+            Some(auth) => {
+                let (token, scope) = auth
+                    .authenticate(req.credentials.as_ref())
+                    .map_err(ServerError::from_component_error)?;
+                self.connection_scope.set(Some(scope));
+                token
+            }
+            None => "esp32".to_string(),
         };
+        let resp = proto::rpc::v1::AuthenticateResponse { access_token };
         self.encode_message(resp)
     }
 
     fn motor_set_power(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::motor::v1::SetPowerRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let motor = match self.robot.lock().unwrap().get_motor_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let motor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_motor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
         motor
             .lock()
             .unwrap()
-            .set_power(req.power_pct)
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .set_power_with_extra(req.power_pct, req.extra)
+            .map_err(ServerError::from_component_error)?;
         let resp = component::motor::v1::SetPowerResponse {};
         self.encode_message(resp)
     }
 
     fn motor_stop(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::motor::v1::StopRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let motor = match self.robot.lock().unwrap().get_motor_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let motor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_motor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("motor", req.name))?;
         motor
             .lock()
             .unwrap()
-            .stop()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .stop_with_extra(req.extra)
+            .map_err(ServerError::from_component_error)?;
         let resp = component::motor::v1::StopResponse {};
         self.encode_message(resp)
     }
 
     fn servo_move(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::servo::v1::MoveRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let servo = match self.robot.lock().unwrap().get_servo_by_name(req.name) {
-            Some(s) => s,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let servo = self
+            .robot
+            .read()
+            .unwrap()
+            .get_servo_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("servo", req.name))?;
         servo
             .lock()
             .unwrap()
-            .move_to(req.angle_deg)
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .move_to_with_extra(req.angle_deg, req.extra)
+            .map_err(ServerError::from_component_error)?;
         let resp = component::servo::v1::MoveResponse {};
         self.encode_message(resp)
     }
 
     fn servo_get_position(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::servo::v1::GetPositionRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let servo = match self.robot.lock().unwrap().get_servo_by_name(req.name) {
-            Some(s) => s,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let servo = self
+            .robot
+            .read()
+            .unwrap()
+            .get_servo_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("servo", req.name))?;
         let pos = servo
             .lock()
             .unwrap()
             .get_position()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::servo::v1::GetPositionResponse { position_deg: pos };
         self.encode_message(resp)
     }
 
     fn servo_is_moving(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::servo::v1::IsMovingRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let servo = match self.robot.lock().unwrap().get_servo_by_name(req.name) {
-            Some(s) => s,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let servo = self
+            .robot
+            .read()
+            .unwrap()
+            .get_servo_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("servo", req.name))?;
         let resp = component::servo::v1::IsMovingResponse {
             is_moving: servo
                 .lock()
                 .unwrap()
                 .is_moving()
-                .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?,
+                .map_err(ServerError::from_component_error)?,
         };
         self.encode_message(resp)
     }
 
     fn servo_stop(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::servo::v1::StopRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let servo = match self.robot.lock().unwrap().get_servo_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let servo = self
+            .robot
+            .read()
+            .unwrap()
+            .get_servo_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("servo", req.name))?;
         servo
             .lock()
             .unwrap()
-            .stop()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .stop_with_extra(req.extra)
+            .map_err(ServerError::from_component_error)?;
         let resp = component::servo::v1::StopResponse {};
         self.encode_message(resp)
     }
 
     fn servo_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::DoCommandRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let servo = match self.robot.lock().unwrap().get_servo_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let servo = self
+            .robot
+            .read()
+            .unwrap()
+            .get_servo_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("servo", req.name))?;
         let res = servo
             .lock()
             .unwrap()
             .do_command(req.command)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(ServerError::from_component_error)?;
+        let resp = proto::common::v1::DoCommandResponse { result: res };
+        self.encode_message(resp)
+    }
+
+    fn switch_set_position(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::switch::v1::SetPositionRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let switch = self
+            .robot
+            .read()
+            .unwrap()
+            .get_switch_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("switch", req.name))?;
+        switch
+            .lock()
+            .unwrap()
+            .set_position(req.position)
+            .map_err(ServerError::from_component_error)?;
+        let resp = component::switch::v1::SetPositionResponse {};
+        self.encode_message(resp)
+    }
+
+    fn switch_get_position(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::switch::v1::GetPositionRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let switch = self
+            .robot
+            .read()
+            .unwrap()
+            .get_switch_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("switch", req.name))?;
+        let position = switch
+            .lock()
+            .unwrap()
+            .get_position()
+            .map_err(ServerError::from_component_error)?;
+        let resp = component::switch::v1::GetPositionResponse { position };
+        self.encode_message(resp)
+    }
+
+    fn switch_get_number_of_positions(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::switch::v1::GetNumberOfPositionsRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let switch = self
+            .robot
+            .read()
+            .unwrap()
+            .get_switch_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("switch", req.name))?;
+        let (number_of_positions, labels) = switch
+            .lock()
+            .unwrap()
+            .get_number_of_positions()
+            .map_err(ServerError::from_component_error)?;
+        let resp = component::switch::v1::GetNumberOfPositionsResponse {
+            number_of_positions,
+            labels,
+        };
+        self.encode_message(resp)
+    }
+
+    fn switch_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = proto::common::v1::DoCommandRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let switch = self
+            .robot
+            .read()
+            .unwrap()
+            .get_switch_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("switch", req.name))?;
+        let res = switch
+            .lock()
+            .unwrap()
+            .do_command(req.command)
+            .map_err(ServerError::from_component_error)?;
+        let resp = proto::common::v1::DoCommandResponse { result: res };
+        self.encode_message(resp)
+    }
+
+    fn button_push(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::button::v1::PushRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let button = self
+            .robot
+            .read()
+            .unwrap()
+            .get_button_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("button", req.name))?;
+        button
+            .lock()
+            .unwrap()
+            .push()
+            .map_err(ServerError::from_component_error)?;
+        let resp = component::button::v1::PushResponse {};
+        self.encode_message(resp)
+    }
+
+    fn button_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = proto::common::v1::DoCommandRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let button = self
+            .robot
+            .read()
+            .unwrap()
+            .get_button_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("button", req.name))?;
+        let res = button
+            .lock()
+            .unwrap()
+            .do_command(req.command)
+            .map_err(ServerError::from_component_error)?;
+        let resp = proto::common::v1::DoCommandResponse { result: res };
+        self.encode_message(resp)
+    }
+
+    fn audio_input_properties(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::audio_input::v1::PropertiesRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let audio_input = self
+            .robot
+            .read()
+            .unwrap()
+            .get_audio_input_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("audio_input", req.name))?;
+        let props = audio_input.lock().unwrap().get_properties();
+        let resp: component::audio_input::v1::PropertiesResponse = props.into();
+        self.encode_message(resp)
+    }
+
+    fn audio_input_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = proto::common::v1::DoCommandRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let audio_input = self
+            .robot
+            .read()
+            .unwrap()
+            .get_audio_input_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("audio_input", req.name))?;
+        let res = audio_input
+            .lock()
+            .unwrap()
+            .do_command(req.command)
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::DoCommandResponse { result: res };
         self.encode_message(resp)
     }
 
     fn board_get_digital_interrupt_value(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::GetDigitalInterruptValueRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.board_name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.board_name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.board_name))?;
         let interrupt_pin = req
             .digital_interrupt_name
             .parse::<i32>()
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
         let value = board
             .get_digital_interrupt_value(interrupt_pin)
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?
+            .map_err(ServerError::from_component_error)?
             .into();
         let resp = component::board::v1::GetDigitalInterruptValueResponse { value };
         self.encode_message(resp)
@@ -566,16 +1156,18 @@ where
 
     fn board_status(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::StatusRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
         let status = board
             .lock()
             .unwrap()
             .get_board_status()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let status = component::board::v1::StatusResponse {
             status: Some(status),
         };
@@ -584,15 +1176,17 @@ where
 
     fn board_pwm(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::PwmRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
         let pin: i32 = req
             .pin
             .parse::<i32>()
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
         let duty_cycle_pct = board.get_pwm_duty(pin);
         let resp = component::board::v1::PwmResponse { duty_cycle_pct };
         self.encode_message(resp)
@@ -600,48 +1194,51 @@ where
 
     fn board_pwm_frequency(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::PwmFrequencyRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
         let pin: i32 = req
             .pin
             .parse::<i32>()
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
         let frequency_hz = board
             .get_pwm_frequency(pin)
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::board::v1::PwmFrequencyResponse { frequency_hz };
         self.encode_message(resp)
     }
 
     fn board_read_analog_reader(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::ReadAnalogReaderRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.board_name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.board_name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.board_name))?;
         let mut reader = board
             .get_analog_reader_by_name(req.analog_reader_name)
-            .map_err(|err| ServerError::new(GrpcError::RpcUnavailable, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::board::v1::ReadAnalogReaderResponse {
-            value: reader
-                .read()
-                .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?
-                as i32,
+            value: reader.read().map_err(ServerError::from_component_error)? as i32,
         };
         self.encode_message(resp)
     }
 
     fn board_set_pin(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::SetGpioRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
 
         let pin: i32 = req.pin.parse::<i32>().unwrap();
         let is_high = req.high;
@@ -649,18 +1246,20 @@ where
             .lock()
             .unwrap()
             .set_gpio_pin_level(pin, is_high)
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::board::v1::SetGpioResponse {};
         self.encode_message(resp)
     }
 
     fn board_set_pwm(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::SetPwmRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let mut board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let mut board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
         let pin: i32 = req.pin.parse::<i32>().unwrap();
 
         // ignore error to match behavior on RDK
@@ -672,11 +1271,13 @@ where
 
     fn board_set_pwm_frequency(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::SetPwmFrequencyRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let mut board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let mut board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
         let pin: i32 = req.pin.parse::<i32>().unwrap();
 
         // ignore error to match behavior on RDK
@@ -687,7 +1288,7 @@ where
 
     fn board_set_power_mode(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::SetPowerModeRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
         let pm = req.power_mode();
 
         if pm == component::board::v1::PowerMode::Unspecified {
@@ -702,16 +1303,18 @@ where
             None => None,
         };
 
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
 
         board
             .lock()
             .unwrap()
-            .set_power_mode(pm, dur)
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .set_power_mode_with_extra(pm, dur, req.extra)
+            .map_err(ServerError::from_component_error)?;
 
         let resp = component::board::v1::SetPowerModeResponse {};
         self.encode_message(resp)
@@ -719,130 +1322,129 @@ where
 
     fn board_get_pin(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::board::v1::GetGpioRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
 
         let pin: i32 = req.pin.parse::<i32>().unwrap();
         let level = board
             .lock()
             .unwrap()
             .get_gpio_level(pin)
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::board::v1::GetGpioResponse { high: level };
         self.encode_message(resp)
     }
 
     fn board_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::DoCommandRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let board = match self.robot.lock().unwrap().get_board_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name))?;
         let res = board
             .lock()
             .unwrap()
             .do_command(req.command)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::DoCommandResponse { result: res };
         self.encode_message(resp)
     }
 
     fn generic_component_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::DoCommandRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let component = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let component = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_generic_component_by_name(req.name)
-        {
-            Some(c) => c,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_generic_component_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("generic", req.name))?;
         let res = component
             .lock()
             .unwrap()
             .do_command(req.command)
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::DoCommandResponse { result: res };
         self.encode_message(resp)
     }
 
     fn sensor_get_readings(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::GetReadingsRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let sensor = match self.robot.lock().unwrap().get_sensor_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let sensor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("sensor", req.name))?;
 
         let readings = sensor
             .lock()
             .unwrap()
             .get_generic_readings()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::GetReadingsResponse { readings };
         self.encode_message(resp)
     }
 
     fn sensor_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::DoCommandRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let sensor = match self.robot.lock().unwrap().get_sensor_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let sensor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("sensor", req.name))?;
         let res = sensor
             .lock()
             .unwrap()
             .do_command(req.command)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::DoCommandResponse { result: res };
         self.encode_message(resp)
     }
 
     fn movement_sensor_get_position(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::movement_sensor::v1::GetPositionRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let m_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let m_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_movement_sensor_by_name(req.name)
-        {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
         let position = m_sensor
             .lock()
             .unwrap()
             .get_position()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::movement_sensor::v1::GetPositionResponse::from(position);
         self.encode_message(resp)
     }
 
     fn movement_sensor_get_linear_velocity(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::movement_sensor::v1::GetLinearVelocityRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let m_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let m_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_movement_sensor_by_name(req.name)
-        {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
         let l_vel = m_sensor
             .lock()
             .unwrap()
             .get_linear_velocity()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let l_vel_msg = proto::common::v1::Vector3::from(l_vel);
         let resp = component::movement_sensor::v1::GetLinearVelocityResponse {
             linear_velocity: Some(l_vel_msg),
@@ -852,21 +1454,18 @@ where
 
     fn movement_sensor_get_angular_velocity(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::movement_sensor::v1::GetAngularVelocityRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let m_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let m_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_movement_sensor_by_name(req.name)
-        {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
         let a_vel = m_sensor
             .lock()
             .unwrap()
             .get_angular_velocity()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let a_vel_msg = proto::common::v1::Vector3::from(a_vel);
         let resp = component::movement_sensor::v1::GetAngularVelocityResponse {
             angular_velocity: Some(a_vel_msg),
@@ -879,21 +1478,18 @@ where
         message: &[u8],
     ) -> Result<(), ServerError> {
         let req = component::movement_sensor::v1::GetLinearAccelerationRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let m_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let m_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_movement_sensor_by_name(req.name)
-        {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
         let l_acc = m_sensor
             .lock()
             .unwrap()
             .get_linear_acceleration()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let l_acc_msg = proto::common::v1::Vector3::from(l_acc);
         let resp = component::movement_sensor::v1::GetLinearAccelerationResponse {
             linear_acceleration: Some(l_acc_msg),
@@ -903,77 +1499,134 @@ where
 
     fn movement_sensor_get_compass_heading(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::movement_sensor::v1::GetCompassHeadingRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let m_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let m_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_movement_sensor_by_name(req.name)
-        {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
         let heading = m_sensor
             .lock()
             .unwrap()
             .get_compass_heading()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::movement_sensor::v1::GetCompassHeadingResponse { value: heading };
         self.encode_message(resp)
     }
 
     fn movement_sensor_get_properties(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::movement_sensor::v1::GetPropertiesRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let m_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let m_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_movement_sensor_by_name(req.name)
-        {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
         let props = m_sensor.lock().unwrap().get_properties();
         let resp = component::movement_sensor::v1::GetPropertiesResponse::from(props);
         self.encode_message(resp)
     }
 
-    fn movement_sensor_get_accuracy(&mut self, _message: &[u8]) -> Result<(), ServerError> {
-        Err(ServerError::from(GrpcError::RpcUnimplemented))
+    fn movement_sensor_get_accuracy(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::movement_sensor::v1::GetAccuracyRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let m_sensor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
+        let accuracy = m_sensor
+            .lock()
+            .unwrap()
+            .get_accuracy()
+            .map_err(ServerError::from_component_error)?;
+        let resp = component::movement_sensor::v1::GetAccuracyResponse::from(accuracy);
+        self.encode_message(resp)
     }
 
-    fn movement_sensor_get_orientation(&mut self, _message: &[u8]) -> Result<(), ServerError> {
-        Err(ServerError::from(GrpcError::RpcUnimplemented))
+    fn movement_sensor_get_orientation(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::movement_sensor::v1::GetOrientationRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let m_sensor = self
+            .robot
+            .read()
+            .unwrap()
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
+        let orientation = m_sensor
+            .lock()
+            .unwrap()
+            .get_orientation()
+            .map_err(ServerError::from_component_error)?;
+        let resp = component::movement_sensor::v1::GetOrientationResponse {
+            orientation: Some(orientation.into()),
+        };
+        self.encode_message(resp)
     }
 
     fn movement_sensor_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::DoCommandRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let movement_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let movement_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_movement_sensor_by_name(req.name)
-        {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_movement_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("movement_sensor", req.name))?;
         let res = movement_sensor
             .lock()
             .unwrap()
             .do_command(req.command)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::DoCommandResponse { result: res };
         self.encode_message(resp)
     }
 
-    fn base_move_straight(&mut self, _message: &[u8]) -> Result<(), ServerError> {
-        Err(ServerError::from(GrpcError::RpcUnimplemented))
+    fn base_move_straight(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::base::v1::MoveStraightRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let base = self
+            .robot
+            .read()
+            .unwrap()
+            .get_base_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("base", req.name.clone()))?;
+        record_base_command(&req.name);
+        let duration = base
+            .lock()
+            .unwrap()
+            .move_straight(req.distance_mm, req.mm_per_sec)
+            .map_err(ServerError::from_component_error)?;
+        if let Some(duration) = duration {
+            stop_base_after(req.name, base, duration);
+        }
+        let resp = component::base::v1::MoveStraightResponse {};
+        self.encode_message(resp)
     }
 
-    fn base_spin(&mut self, _message: &[u8]) -> Result<(), ServerError> {
-        Err(ServerError::from(GrpcError::RpcUnimplemented))
+    fn base_spin(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::base::v1::SpinRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let base = self
+            .robot
+            .read()
+            .unwrap()
+            .get_base_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("base", req.name.clone()))?;
+        record_base_command(&req.name);
+        let duration = base
+            .lock()
+            .unwrap()
+            .spin(req.angle_deg, req.degs_per_sec)
+            .map_err(ServerError::from_component_error)?;
+        if let Some(duration) = duration {
+            stop_base_after(req.name, base, duration);
+        }
+        let resp = component::base::v1::SpinResponse {};
+        self.encode_message(resp)
     }
 
     fn base_set_velocity(&mut self, _: &[u8]) -> Result<(), ServerError> {
@@ -982,62 +1635,90 @@ where
 
     fn base_is_moving(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::base::v1::IsMovingRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let base = match self.robot.lock().unwrap().get_base_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let base = self
+            .robot
+            .read()
+            .unwrap()
+            .get_base_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("base", req.name))?;
         let resp = component::base::v1::IsMovingResponse {
             is_moving: base
                 .lock()
                 .unwrap()
                 .is_moving()
-                .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?,
+                .map_err(ServerError::from_component_error)?,
         };
         self.encode_message(resp)
     }
 
+    fn base_get_properties(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let req = component::base::v1::GetPropertiesRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let base = self
+            .robot
+            .read()
+            .unwrap()
+            .get_base_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("base", req.name))?;
+        let props = base
+            .lock()
+            .unwrap()
+            .get_properties()
+            .map_err(ServerError::from_component_error)?;
+        let resp = component::base::v1::GetPropertiesResponse::from(props);
+        self.encode_message(resp)
+    }
+
     fn base_set_power(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::base::v1::SetPowerRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let base = match self.robot.lock().unwrap().get_base_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let base = self
+            .robot
+            .read()
+            .unwrap()
+            .get_base_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("base", req.name.clone()))?;
+        record_base_command(&req.name);
         base.lock()
             .unwrap()
-            .set_power(
+            .set_power_with_extra(
                 &req.linear.unwrap_or_default(),
                 &req.angular.unwrap_or_default(),
+                req.extra,
             )
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::base::v1::SetPowerResponse {};
         self.encode_message(resp)
     }
 
     fn base_stop(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::base::v1::StopRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let base = match self.robot.lock().unwrap().get_base_by_name(req.name) {
-            Some(b) => b,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
-
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let base = self
+            .robot
+            .read()
+            .unwrap()
+            .get_base_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("base", req.name.clone()))?;
+        record_base_command(&req.name);
         base.lock()
             .unwrap()
-            .stop()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .stop_with_extra(req.extra)
+            .map_err(ServerError::from_component_error)?;
         let resp = component::base::v1::StopResponse {};
         self.encode_message(resp)
     }
 
     fn encoder_get_properties(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::encoder::v1::GetPropertiesRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let enc = match self.robot.lock().unwrap().get_encoder_by_name(req.name) {
-            Some(e) => e,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let enc = self
+            .robot
+            .read()
+            .unwrap()
+            .get_encoder_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("encoder", req.name))?;
 
         let props = enc.lock().unwrap().get_properties();
         let resp = component::encoder::v1::GetPropertiesResponse::from(props);
@@ -1046,141 +1727,133 @@ where
 
     fn encoder_get_position(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::encoder::v1::GetPositionRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
         let name = req.name.clone();
         let pos_type = req.position_type();
-        let enc = match self.robot.lock().unwrap().get_encoder_by_name(name) {
+        let enc = match self.robot.read().unwrap().get_encoder_by_name(name) {
             Some(e) => e,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
+            None => return Err(ServerError::resource_not_found("encoder", req.name)),
         };
         let pos = enc
             .lock()
             .unwrap()
             .get_position(pos_type.into())
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::encoder::v1::GetPositionResponse::from(pos);
         self.encode_message(resp)
     }
 
     fn encoder_reset_position(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::encoder::v1::ResetPositionRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let enc = match self.robot.lock().unwrap().get_encoder_by_name(req.name) {
-            Some(e) => e,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let enc = self
+            .robot
+            .read()
+            .unwrap()
+            .get_encoder_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("encoder", req.name))?;
         enc.lock()
             .unwrap()
             .reset_position()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = component::encoder::v1::ResetPositionResponse {};
         self.encode_message(resp)
     }
 
     fn encoder_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::DoCommandRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let encoder = match self.robot.lock().unwrap().get_encoder_by_name(req.name) {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let encoder = self
+            .robot
+            .read()
+            .unwrap()
+            .get_encoder_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("encoder", req.name))?;
         let res = encoder
             .lock()
             .unwrap()
             .do_command(req.command)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::DoCommandResponse { result: res };
         self.encode_message(resp)
     }
 
     fn power_sensor_get_voltage(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::power_sensor::v1::GetVoltageRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let power_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let power_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_power_sensor_by_name(req.name)
-        {
-            Some(s) => s,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_power_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("power_sensor", req.name))?;
         let resp: component::power_sensor::v1::GetVoltageResponse = power_sensor
             .lock()
             .unwrap()
             .get_voltage()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?
+            .map_err(ServerError::from_component_error)?
             .into();
         self.encode_message(resp)
     }
 
     fn power_sensor_get_current(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::power_sensor::v1::GetCurrentRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let power_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let power_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_power_sensor_by_name(req.name)
-        {
-            Some(s) => s,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_power_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("power_sensor", req.name))?;
         let resp: component::power_sensor::v1::GetCurrentResponse = power_sensor
             .lock()
             .unwrap()
             .get_current()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?
+            .map_err(ServerError::from_component_error)?
             .into();
         self.encode_message(resp)
     }
 
     fn power_sensor_get_power(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::power_sensor::v1::GetPowerRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let power_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let power_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_power_sensor_by_name(req.name)
-        {
-            Some(s) => s,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_power_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("power_sensor", req.name))?;
         let resp = component::power_sensor::v1::GetPowerResponse {
             watts: power_sensor
                 .lock()
                 .unwrap()
                 .get_power()
-                .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?,
+                .map_err(ServerError::from_component_error)?,
         };
         self.encode_message(resp)
     }
 
     fn power_sensor_do_command(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = proto::common::v1::DoCommandRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        let power_sensor = match self
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let power_sensor = self
             .robot
-            .lock()
+            .read()
             .unwrap()
-            .get_power_sensor_by_name(req.name)
-        {
-            Some(m) => m,
-            None => return Err(ServerError::from(GrpcError::RpcUnavailable)),
-        };
+            .get_power_sensor_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("power_sensor", req.name))?;
         let res = power_sensor
             .lock()
             .unwrap()
             .do_command(req.command)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(ServerError::from_component_error)?;
         let resp = proto::common::v1::DoCommandResponse { result: res };
         self.encode_message(resp)
     }
 
     fn robot_status_stream(&mut self, message: &[u8]) -> Result<std::time::Instant, ServerError> {
         let req = robot::v1::StreamStatusRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
         let duration = Instant::now()
             + TryInto::<Duration>::try_into(req.every.unwrap())
                 .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
@@ -1191,14 +1864,109 @@ where
         let status = robot::v1::StreamStatusResponse {
             status: self
                 .robot
-                .lock()
+                .read()
                 .unwrap()
                 .get_status(req)
-                .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?,
+                .map_err(ServerError::from_component_error)?,
         };
         self.encode_message(status).map(|_| duration)
     }
 
+    /// Sends the log entries captured by [BufferedLogger](super::log::BufferedLogger) since the
+    /// previous message of the stream, the first message starting with the oldest entry still
+    /// buffered. A single tail is expected per connection.
+    fn tail_logs_stream(&mut self, message: &[u8]) -> Result<std::time::Instant, ServerError> {
+        let req = proto::app::v1::TailRobotPartLogsRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let (entries, next_seq) = LOG_BUFFER.read_from(self.log_tail_seq, LOG_TAIL_MAX_ENTRIES);
+        self.log_tail_seq = next_seq;
+        // a client that fell behind is caught up without waiting for the next interval
+        let next = if entries.len() == LOG_TAIL_MAX_ENTRIES {
+            Instant::now()
+        } else {
+            Instant::now() + LOG_TAIL_INTERVAL
+        };
+        let logs = entries
+            .into_iter()
+            .filter(|entry| !req.errors_only || entry.level == "error")
+            .filter(|entry| {
+                req.filter
+                    .as_ref()
+                    .map_or(true, |filter| entry.message.contains(filter.as_str()))
+            })
+            .collect();
+        let resp = proto::app::v1::TailRobotPartLogsResponse { logs };
+        self.encode_message(resp).map(|_| next)
+    }
+
+    /// Sends a message for every edge of the requested digital interrupts, the interrupts being
+    /// sampled every [TICK_STREAM_INTERVAL]. No message is sent while there is no new edge. Each
+    /// request, a board and its pins, follows its own [TickStream].
+    fn board_stream_ticks(
+        &mut self,
+        message: &[u8],
+    ) -> Result<(bool, std::time::Instant), ServerError> {
+        let req = StreamTicksRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        if req.pin_names.is_empty() {
+            return Err(GrpcError::RpcInvalidArgument.into());
+        }
+        let pins = req
+            .pin_names
+            .iter()
+            .map(|name| name.parse::<i32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let board = self
+            .robot
+            .read()
+            .unwrap()
+            .get_board_by_name(req.name.clone())
+            .ok_or_else(|| ServerError::resource_not_found("board", req.name.clone()))?;
+        let stream = match self
+            .tick_streams
+            .iter()
+            .position(|stream| stream.follows(&req.name, &pins))
+        {
+            Some(index) => self.tick_streams.remove(index),
+            None => {
+                if self.tick_streams.len() == MAX_TICK_STREAMS {
+                    self.tick_streams.remove(0);
+                }
+                TickStream::new(&req.name, &pins)
+            }
+        };
+        self.tick_streams.push(stream);
+        let stream = self.tick_streams.last_mut().unwrap();
+        let dropped = stream.dropped();
+        let time_ns = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        stream
+            .sample(&board, time_ns)
+            .map_err(ServerError::from_component_error)?;
+        if stream.dropped() > dropped {
+            log::warn!(
+                "{} ticks of board {} dropped, the client isn't keeping up",
+                stream.dropped() - dropped,
+                req.name
+            );
+        }
+        let tick = stream.next_tick();
+        // queued ticks are sent without waiting for the next sample
+        let next = if stream.is_empty() {
+            Instant::now() + TICK_STREAM_INTERVAL
+        } else {
+            Instant::now()
+        };
+        match tick {
+            Some(tick) => self
+                .encode_message(StreamTicksResponse::from(tick))
+                .map(|_| (true, next)),
+            None => Ok((false, next)),
+        }
+    }
+
     // robot_get_operations returns an empty response since operations are not yet
     // supported on micro-rdk
     fn robot_get_oprations(&mut self, _: &[u8]) -> Result<(), ServerError> {
@@ -1206,16 +1974,51 @@ where
         self.encode_message(operation)
     }
 
+    fn robot_get_version(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        let _ = GetVersionRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        self.encode_message(GetVersionResponse::from(BuildInfo::get()))
+    }
+
+    fn robot_frame_system_config(&mut self, message: &[u8]) -> Result<(), ServerError> {
+        // supplemental transforms are only used by frame system consumers, nothing to merge here
+        let _ = robot::v1::FrameSystemConfigRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let resp = robot::v1::FrameSystemConfigResponse {
+            frame_system_configs: self.robot.read().unwrap().get_frame_system_config(),
+        };
+        self.encode_message(resp)
+    }
+
+    // GetGeometries has the same request and response for every component API
+    fn get_geometries(&mut self, subtype: &str, message: &[u8]) -> Result<(), ServerError> {
+        let req = proto::common::v1::GetGeometriesRequest::decode(message)
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        let name = proto::common::v1::ResourceName {
+            namespace: "rdk".to_string(),
+            r#type: "component".to_string(),
+            subtype: subtype.to_string(),
+            name: req.name,
+        };
+        let geometries = self
+            .robot
+            .read()
+            .unwrap()
+            .get_geometries(&name)
+            .map_err(ServerError::from_component_error)?;
+        self.encode_message(proto::common::v1::GetGeometriesResponse { geometries })
+    }
+
     fn robot_status(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = robot::v1::GetStatusRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
         let status = robot::v1::GetStatusResponse {
             status: self
                 .robot
-                .lock()
+                .read()
                 .unwrap()
                 .get_status(req)
-                .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?,
+                .map_err(ServerError::from_component_error)?,
         };
         self.encode_message(status)
     }
@@ -1223,12 +2026,17 @@ where
     #[cfg(feature = "camera")]
     fn camera_get_frame(&mut self, message: &[u8]) -> Result<(), ServerError> {
         let req = component::camera::v1::GetImageRequest::decode(message)
-            .map_err(|_| ServerError::from(GrpcError::RpcInvalidArgument))?;
-        if let Some(camera) = self.robot.lock().unwrap().get_camera_by_name(req.name) {
+            .map_err(|err| ServerError::new(GrpcError::RpcInvalidArgument, Some(err.into())))?;
+        if let Some(camera) = self
+            .robot
+            .read()
+            .unwrap()
+            .get_camera_by_name(req.name.clone())
+        {
             // TODO: Modify `get_frame` to return a data structure that can be passed into
             // `encode_message`, rather than re-implementing `encode_message` here. See
             // https://viam.atlassian.net/browse/RSDK-824
-            let mut buffer = RefCell::borrow_mut(&self.buffer).split_off(0);
+            let mut buffer = self.take_buffer();
             buffer.put_u8(0);
             buffer.put_u32(0.try_into().unwrap());
             let msg = buffer.split_off(5);
@@ -1236,7 +2044,7 @@ where
                 .lock()
                 .unwrap()
                 .get_frame(msg)
-                .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+                .map_err(ServerError::from_component_error)?;
             let len = msg.len().to_be_bytes();
             buffer[1] = len[0];
             buffer[2] = len[1];
@@ -1246,7 +2054,7 @@ where
             self.response.put_data(buffer.freeze());
             return Ok(());
         }
-        Err(ServerError::from(GrpcError::RpcUnavailable))
+        Err(ServerError::resource_not_found("camera", req.name))
     }
 
     #[cfg(feature = "camera")]
@@ -1267,16 +2075,32 @@ where
     fn resource_names(&mut self, _unused_message: &[u8]) -> Result<(), ServerError> {
         let rr = self
             .robot
-            .lock()
+            .read()
             .unwrap()
             .get_resource_names()
-            .map_err(|err| ServerError::new(GrpcError::RpcInternal, Some(err.into())))?;
+            .map_err(ServerError::from_component_error)?;
         let rr = robot::v1::ResourceNamesResponse { resources: rr };
         self.encode_message(rr)
     }
 
-    fn encode_message<M: Message>(&mut self, m: M) -> Result<(), ServerError> {
+    fn resource_rpc_subtypes(&mut self, _unused_message: &[u8]) -> Result<(), ServerError> {
+        let resource_rpc_subtypes = self.robot.read().unwrap().get_resource_rpc_subtypes();
+        let resp = robot::v1::ResourceRpcSubtypesResponse {
+            resource_rpc_subtypes,
+        };
+        self.encode_message(resp)
+    }
+
+    // The buffer is shared by every stream of the connection, so the space reserved when the
+    // request came in may have been taken by another stream's response in the meantime.
+    fn take_buffer(&self) -> BytesMut {
         let mut buffer = RefCell::borrow_mut(&self.buffer).split_off(0);
+        buffer.reserve(GRPC_BUFFER_SIZE);
+        buffer
+    }
+
+    fn encode_message<M: Message>(&mut self, m: M) -> Result<(), ServerError> {
+        let mut buffer = self.take_buffer();
         // The buffer will have a null byte, then 4 bytes containing the big-endian length of the
         // data (*not* including this 5-byte header), and then the data from the message itself.
         if 5 + m.encoded_len() > buffer.capacity() {
@@ -1297,24 +2121,33 @@ impl<R> WebRtcGrpcService for GrpcServer<R>
 where
     R: GrpcResponse + 'static,
 {
-    fn unary_rpc(&mut self, method: &str, data: &Bytes) -> Result<Bytes, ServerError> {
+    fn is_server_stream(&self, method: &str) -> bool {
+        matches!(
+            method,
+            "/viam.robot.v1.RobotService/StreamStatus"
+                | "/viam.app.v1.RobotService/TailRobotPartLogs"
+                | "/viam.component.board.v1.BoardService/StreamTicks"
+        )
+    }
+    async fn unary_rpc(&mut self, method: &str, data: &Bytes) -> Result<Bytes, ServerError> {
         {
             RefCell::borrow_mut(&self.buffer).reserve(GRPC_BUFFER_SIZE);
         }
-        self.handle_request(method, data)
+        self.handle_request_async(method, data)
+            .await
             .map(|_| self.response.get_data().split_off(5))
     }
     fn server_stream_rpc(
         &mut self,
         method: &str,
         data: &Bytes,
-    ) -> Result<(Bytes, Instant), ServerError> {
+    ) -> Result<(Option<Bytes>, Instant), ServerError> {
         {
             RefCell::borrow_mut(&self.buffer).reserve(GRPC_BUFFER_SIZE);
         }
         log::debug!("stream req is {:?}, ", method);
         self.handle_rpc_stream(method, data)
-            .map(|dur| (self.response.get_data().split_off(5), dur))
+            .map(|(sent, next)| (sent.then(|| self.response.get_data().split_off(5)), next))
     }
 }
 
@@ -1327,6 +2160,21 @@ where
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
 
     fn call(&self, req: Request<body::Incoming>) -> Self::Future {
+        if !RefCell::borrow_mut(&self.rate_limiter).try_acquire(Instant::now()) {
+            log::warn!("request rate exceeded, refusing {}", req.uri().path());
+            let mut response = self.response.clone();
+            response.set_status(
+                GrpcError::RpcResourceExhausted as i32,
+                Some("request rate exceeded".to_owned()),
+            );
+            return Box::pin(future::ready(
+                Response::builder()
+                    .header("content-type", "application/grpc")
+                    .status(200)
+                    .body(response)
+                    .map_err(|_| GrpcError::RpcFailedPrecondition),
+            ));
+        }
         #[cfg(debug_assertions)]
         debug!("clone in Servive GRPC");
         {
@@ -1335,19 +2183,65 @@ where
         let mut svc = self.clone();
         #[cfg(debug_assertions)]
         log::debug!("processing {:?}", req);
+        let timeout = req
+            .headers()
+            .get("grpc-timeout")
+            .and_then(|timeout| timeout.to_str().ok())
+            .and_then(parse_grpc_timeout)
+            .unwrap_or(self.rpc_timeout);
+        svc.bearer_scope = self.auth.as_ref().and_then(|auth| {
+            req.headers()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| auth.bearer_scope(value))
+        });
+        let deadline = Instant::now() + timeout;
+        let max_request_size = self.max_request_size;
         Box::pin(async move {
-            let (path, body) = req.into_parts();
-            let msg = body
-                .collect()
-                .await
-                .map_err(|_| GrpcError::RpcFailedPrecondition)?
-                .to_bytes();
-
-            let path = match path.uri.path_and_query() {
+            let (parts, body) = req.into_parts();
+            let path = match parts.uri.path_and_query() {
                 Some(path) => path.as_str(),
                 None => return Err(GrpcError::RpcInvalidArgument),
             };
-            svc.process_request(path, msg);
+            // the deadline covers the whole request: a client that never finishes sending its
+            // request would otherwise hold the connection's only stream forever
+            let handled = run_until(
+                async {
+                    let msg = Limited::new(body, max_request_size)
+                        .collect()
+                        .await
+                        .map_err(|err| {
+                            if err.is::<LengthLimitError>() {
+                                GrpcError::RpcResourceExhausted
+                            } else {
+                                GrpcError::RpcFailedPrecondition
+                            }
+                        })?;
+                    svc.process_request(path, msg.to_bytes()).await;
+                    Ok(())
+                },
+                deadline,
+            )
+            .await;
+
+            match handled {
+                Err(GrpcError::RpcResourceExhausted) => {
+                    log::warn!("request to {} exceeds {} bytes", path, max_request_size);
+                    svc.response.set_status(
+                        GrpcError::RpcResourceExhausted as i32,
+                        Some(format!("request exceeds {} bytes", max_request_size)),
+                    );
+                }
+                Err(err) => return Err(err),
+                Ok(true) => {}
+                Ok(false) => {
+                    log::warn!("deadline of {:?} exceeded for {}", timeout, path);
+                    svc.response.set_status(
+                        GrpcError::RpcDeadlineExceeded as i32,
+                        Some(GrpcError::RpcDeadlineExceeded.to_string()),
+                    );
+                }
+            }
             Response::builder()
                 .header("content-type", "application/grpc")
                 .status(200)
@@ -1361,7 +2255,7 @@ impl<R> Drop for GrpcServer<R> {
         debug!("Server dropped");
     }
 }
-#[derive(Error, Debug, Clone, Copy)]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GrpcError {
     #[error("canceled rpc")]
     RpcCanceled = 1,
@@ -1405,6 +2299,36 @@ impl GrpcError {
             details: vec![],
         }
     }
+
+    /// The canonical name of the status code, as defined by google.rpc.Code
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::RpcCanceled => "CANCELLED",
+            Self::Unknown => "UNKNOWN",
+            Self::RpcInvalidArgument => "INVALID_ARGUMENT",
+            Self::RpcDeadlineExceeded => "DEADLINE_EXCEEDED",
+            Self::RpcNotFound => "NOT_FOUND",
+            Self::RpcAlreadyExists => "ALREADY_EXISTS",
+            Self::RpcPermissionDenied => "PERMISSION_DENIED",
+            Self::RpcResourceExhausted => "RESOURCE_EXHAUSTED",
+            Self::RpcFailedPrecondition => "FAILED_PRECONDITION",
+            Self::RpcAborted => "ABORTED",
+            Self::RpcOutOfRange => "OUT_OF_RANGE",
+            Self::RpcUnimplemented => "UNIMPLEMENTED",
+            Self::RpcInternal => "INTERNAL",
+            Self::RpcUnavailable => "UNAVAILABLE",
+            Self::RpcDataLoss => "DATA_LOSS",
+            Self::RpcUnauthenticated => "UNAUTHENTICATED",
+        }
+    }
+}
+
+/// Implemented by component error types to hint at the gRPC status code a failing call should
+/// be answered with. Errors default to `RpcInternal` when no better code applies.
+pub trait GrpcStatusHint {
+    fn grpc_error(&self) -> GrpcError {
+        GrpcError::RpcInternal
+    }
 }
 
 impl From<Infallible> for GrpcError {
@@ -1413,11 +2337,17 @@ impl From<Infallible> for GrpcError {
     }
 }
 
+static ERROR_INFO_DOMAIN: &str = "micro-rdk";
+static ERROR_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+
 #[derive(Debug, Error)]
 pub struct ServerError {
     grpc_error: GrpcError,
     #[source]
     cause: Option<Box<dyn std::error::Error + Send + Sync>>,
+    // reported to clients in a google.rpc.ErrorInfo detail
+    reason: Option<&'static str>,
+    metadata: HashMap<String, String>,
 }
 
 impl ServerError {
@@ -1425,11 +2355,64 @@ impl ServerError {
         grpc_error: GrpcError,
         cause: Option<Box<dyn std::error::Error + Send + Sync>>,
     ) -> Self {
-        Self { grpc_error, cause }
+        Self {
+            grpc_error,
+            cause,
+            reason: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// The resource `name` of type `subtype` targeted by a request doesn't exist on this robot
+    pub fn resource_not_found(subtype: &'static str, name: String) -> Self {
+        let mut err = Self::new(
+            GrpcError::RpcNotFound,
+            Some(format!("{} {:?} not found", subtype, name).into()),
+        );
+        err.reason = Some("RESOURCE_NOT_FOUND");
+        err.metadata
+            .insert("subtype".to_owned(), subtype.to_owned());
+        err.metadata.insert("name".to_owned(), name);
+        err
+    }
+
+    /// The rpc `method` isn't served by micro-rdk
+    pub fn method_not_implemented(method: &str) -> Self {
+        let mut err = Self::new(
+            GrpcError::RpcUnimplemented,
+            Some(format!("method {} is not implemented", method).into()),
+        );
+        err.reason = Some("METHOD_NOT_IMPLEMENTED");
+        err.metadata.insert("method".to_owned(), method.to_owned());
+        err
+    }
+
+    /// Wraps a component error, answering with the gRPC status code it hints at
+    pub fn from_component_error<E>(err: E) -> Self
+    where
+        E: GrpcStatusHint + std::error::Error + Send + Sync + 'static,
+    {
+        Self::new(err.grpc_error(), Some(err.into()))
     }
 
     pub fn to_status(&self) -> Status {
-        self.grpc_error.to_status(self.to_string())
+        let mut status = self.grpc_error.to_status(self.to_string());
+        status.details.push(google::protobuf::Any {
+            type_url: ERROR_INFO_TYPE_URL.to_owned(),
+            value: self.error_info().encode_to_vec(),
+        });
+        status
+    }
+
+    pub fn error_info(&self) -> ErrorInfo {
+        ErrorInfo {
+            reason: self
+                .reason
+                .unwrap_or_else(|| self.grpc_error.reason())
+                .to_owned(),
+            domain: ERROR_INFO_DOMAIN.to_owned(),
+            metadata: self.metadata.clone(),
+        }
     }
 
     pub fn status_code(&self) -> i32 {
@@ -1439,10 +2422,7 @@ impl ServerError {
 
 impl From<GrpcError> for ServerError {
     fn from(grpc_error: GrpcError) -> Self {
-        Self {
-            grpc_error,
-            cause: None,
-        }
+        Self::new(grpc_error, None)
     }
 }
 
@@ -1461,7 +2441,7 @@ pub struct MakeSvcGrpcServer {
 
 impl MakeSvcGrpcServer {
     #[allow(dead_code)]
-    pub fn new(robot: Arc<Mutex<LocalRobot>>) -> Self {
+    pub fn new(robot: Arc<RwLock<LocalRobot>>) -> Self {
         MakeSvcGrpcServer {
             server: GrpcServer::new(robot, GrpcBody::new()),
         }
@@ -1481,3 +2461,281 @@ impl<T> Service<T> for MakeSvcGrpcServer {
         future::ready(Ok(self.server.clone()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encode_grpc_message, parse_grpc_timeout, run_until, GrpcBody, GrpcError, GrpcServer,
+        RateLimiter, RequestLimits, ServerError,
+    };
+    use crate::common::authorization::{AuthPolicy, Scope};
+    use crate::common::config::Kind;
+    use crate::common::robot::LocalRobot;
+    use crate::google::rpc::ErrorInfo;
+    use crate::proto::rpc::v1::{AuthenticateRequest, Credentials};
+    use prost::Message;
+    use std::rc::Rc;
+    use std::sync::{Arc, RwLock};
+    use std::time::{Duration, Instant};
+
+    #[test_log::test]
+    fn test_encode_grpc_message() {
+        assert_eq!(
+            encode_grpc_message("motor m1 not found"),
+            "motor m1 not found"
+        );
+        assert_eq!(encode_grpc_message("100%\nnot é"), "100%25%0Anot %C3%A9");
+    }
+
+    #[test_log::test]
+    fn test_parse_grpc_timeout() {
+        assert_eq!(parse_grpc_timeout("5S"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_grpc_timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_grpc_timeout("2H"), Some(Duration::from_secs(7200)));
+        assert_eq!(
+            parse_grpc_timeout("99999999n"),
+            Some(Duration::from_nanos(99999999))
+        );
+        assert_eq!(parse_grpc_timeout("S"), None);
+        assert_eq!(parse_grpc_timeout("123456789S"), None);
+        assert_eq!(parse_grpc_timeout("-1S"), None);
+        assert_eq!(parse_grpc_timeout("10s"), None);
+    }
+
+    #[test_log::test]
+    fn test_server_error_status() {
+        let err = ServerError::resource_not_found("motor", "m1".to_owned());
+        let status = err.to_status();
+        assert_eq!(status.code, GrpcError::RpcNotFound as i32);
+        assert_eq!(status.message, "rpc not found: motor \"m1\" not found");
+        assert_eq!(status.details.len(), 1);
+        let info = ErrorInfo::decode(status.details[0].value.as_slice()).unwrap();
+        assert_eq!(info.reason, "RESOURCE_NOT_FOUND");
+        assert_eq!(info.metadata.get("name").unwrap(), "m1");
+        assert_eq!(info.metadata.get("subtype").unwrap(), "motor");
+
+        let status = ServerError::from(GrpcError::RpcInternal).to_status();
+        let info = ErrorInfo::decode(status.details[0].value.as_slice()).unwrap();
+        assert_eq!(info.reason, "INTERNAL");
+        assert!(info.metadata.is_empty());
+    }
+
+    #[test_log::test]
+    fn test_run_until_deadline() {
+        let deadline = || Instant::now() + Duration::from_millis(50);
+        assert_eq!(
+            async_io::block_on(run_until(async { Ok(()) }, deadline())),
+            Ok(true)
+        );
+        assert_eq!(
+            async_io::block_on(run_until(
+                async { Err(GrpcError::RpcResourceExhausted) },
+                deadline()
+            )),
+            Err(GrpcError::RpcResourceExhausted)
+        );
+
+        // a handler awaiting the hardware is dropped at the deadline
+        let start = Instant::now();
+        let waiting = async {
+            async_io::Timer::after(Duration::from_secs(5)).await;
+            Ok(())
+        };
+        assert_eq!(
+            async_io::block_on(run_until(waiting, deadline())),
+            Ok(false)
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // a handler blocked in a component call runs to completion, but the request still
+        // misses its deadline
+        let start = Instant::now();
+        let blocking = async {
+            std::thread::sleep(Duration::from_millis(100));
+            Ok(())
+        };
+        assert_eq!(
+            async_io::block_on(run_until(blocking, deadline())),
+            Ok(false)
+        );
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[cfg(feature = "builtin-components")]
+    #[test_log::test]
+    fn test_motor_go_for() {
+        use crate::google::protobuf::{value::Kind as ValueKind, Struct, Value};
+        use crate::proto::app::v1::{ComponentConfig, ConfigResponse, RobotConfig};
+        use crate::proto::component::motor::v1::GoForRequest;
+
+        let cfg = ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![ComponentConfig {
+                    name: "m1".to_string(),
+                    model: "rdk:builtin:fake".to_string(),
+                    api: "rdk:component:motor".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+        };
+        let robot = Arc::new(RwLock::new(
+            LocalRobot::from_cloud_config(&cfg, Box::default(), None).unwrap(),
+        ));
+        let mut srv = GrpcServer::new(robot.clone(), GrpcBody::new());
+
+        let extra = Struct {
+            fields: [(
+                "accel_rpm_per_sec".to_string(),
+                Value {
+                    kind: Some(ValueKind::NumberValue(50.0)),
+                },
+            )]
+            .into(),
+        };
+        // 0.1 revolution at 100rpm takes 60ms
+        let req = GoForRequest {
+            name: "m1".to_string(),
+            rpm: 100.0,
+            revolutions: 0.1,
+            extra: Some(extra.clone()),
+        };
+        let start = Instant::now();
+        async_io::block_on(srv.handle_request_async(
+            "/viam.component.motor.v1.MotorService/GoFor",
+            &req.encode_to_vec(),
+        ))
+        .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(60));
+
+        let motor = robot
+            .read()
+            .unwrap()
+            .get_motor_by_name("m1".to_string())
+            .unwrap();
+        let mut motor = motor.lock().unwrap();
+        // the motor is stopped once the revolutions complete
+        assert_eq!(motor.is_powered().unwrap(), (false, 0.0));
+        let status = motor.get_status().unwrap().unwrap();
+        assert_eq!(
+            status.fields.get("extra").and_then(|v| v.kind.clone()),
+            Some(ValueKind::StructValue(extra))
+        );
+
+        let req = GoForRequest {
+            name: "m2".to_string(),
+            ..req
+        };
+        assert_eq!(
+            async_io::block_on(srv.handle_request_async(
+                "/viam.component.motor.v1.MotorService/GoFor",
+                &req.encode_to_vec(),
+            ))
+            .map_err(|err| err.grpc_error),
+            Err(GrpcError::RpcNotFound)
+        );
+    }
+
+    #[test_log::test]
+    fn test_authorize() {
+        let mut srv = GrpcServer::new(
+            Arc::new(RwLock::new(LocalRobot::default())),
+            GrpcBody::new(),
+        )
+        .with_auth_policy(Rc::new(AuthPolicy::new(
+            vec!["op".to_owned()],
+            vec!["view".to_owned()],
+        )));
+        let read = "/viam.robot.v1.RobotService/ResourceNames";
+        let control = "/viam.component.motor.v1.MotorService/SetPower";
+        let code = |res: Result<(), ServerError>| res.map_err(|err| err.grpc_error);
+
+        assert_eq!(
+            code(srv.authorize(read)),
+            Err(GrpcError::RpcUnauthenticated)
+        );
+
+        let req = AuthenticateRequest {
+            entity: "key-id".to_owned(),
+            credentials: Some(Credentials {
+                r#type: "api-key".to_owned(),
+                payload: "view".to_owned(),
+            }),
+        };
+        srv.handle_request(
+            "/proto.rpc.v1.AuthService/Authenticate",
+            &req.encode_to_vec(),
+        )
+        .unwrap();
+        assert_eq!(code(srv.authorize(read)), Ok(()));
+        assert_eq!(
+            code(srv.authorize(control)),
+            Err(GrpcError::RpcPermissionDenied)
+        );
+
+        // an operator token sent with the request overrides the scope of the connection
+        srv.bearer_scope = Some(Scope::Operator);
+        assert_eq!(code(srv.authorize(control)), Ok(()));
+
+        // without keys nothing is restricted
+        let srv = GrpcServer::new(
+            Arc::new(RwLock::new(LocalRobot::default())),
+            GrpcBody::new(),
+        )
+        .with_auth_policy(Rc::new(AuthPolicy::default()));
+        assert_eq!(code(srv.authorize(control)), Ok(()));
+    }
+
+    #[test_log::test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(&RequestLimits {
+            max_rate: 10,
+            max_burst: 3,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        assert!((0..3).all(|_| limiter.try_acquire(start)));
+        assert!(!limiter.try_acquire(start));
+        // a token every 100ms
+        assert!(!limiter.try_acquire(start + Duration::from_millis(50)));
+        assert!(limiter.try_acquire(start + Duration::from_millis(110)));
+        assert!(!limiter.try_acquire(start + Duration::from_millis(120)));
+        // the burst is refilled after an idle period but doesn't grow past its size
+        let later = start + Duration::from_secs(10);
+        assert!((0..3).all(|_| limiter.try_acquire(later)));
+        assert!(!limiter.try_acquire(later));
+
+        let mut unlimited = RateLimiter::new(&RequestLimits {
+            max_rate: 0,
+            ..Default::default()
+        });
+        assert!((0..1000).all(|_| unlimited.try_acquire(start)));
+    }
+
+    #[test_log::test]
+    fn test_request_limits_config() {
+        let conf = Kind::StructValue(
+            [
+                ("max_request_rate", Kind::NumberValue(100.0)),
+                ("max_request_size_bytes", Kind::NumberValue(4096.0)),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+        );
+        let limits = RequestLimits::try_from(&conf).unwrap();
+        assert_eq!(
+            limits,
+            RequestLimits {
+                max_rate: 100,
+                max_request_size: 4096,
+                ..Default::default()
+            }
+        );
+
+        // no rate limit unless one is configured
+        let limits = RequestLimits::try_from(&Kind::StructValue(Default::default())).unwrap();
+        assert_eq!(limits, RequestLimits::default());
+        assert_eq!(limits.max_rate, 0);
+    }
+}
@@ -1,13 +1,39 @@
 #![allow(dead_code)]
+//! HTTP2 gRPC client, used to talk to app and available to components making outbound calls.
+//!
+//! [PeerGrpcClient] makes unary calls to another gRPC server (another robot part, an
+//! inference server on the local network...). The calls are made by a task running on the
+//! executor of the robot, which reconnects on the next call once the connection is lost. Async
+//! code awaits [PeerGrpcClient::call], synchronous code such as a DoCommand handler must not
+//! block the executor and starts the call with [PeerGrpcClient::spawn_call] instead, collecting
+//! the response from a later request.
+//!
+//! ```ignore
+//! let mut client = PeerGrpcClient::new("192.168.1.20:8080".parse()?);
+//! let mut pending: PendingCall<EchoResponse> = client.spawn_call(
+//!     "/proto.rpc.examples.echo.v1.EchoService/Echo",
+//!     EchoRequest { message: "hello".to_owned() },
+//! );
+//! // ...later
+//! if let Some(resp) = pending.try_take() {
+//!     let resp = resp?;
+//! }
+//! ```
 #[cfg(feature = "esp32")]
 use crate::esp32::exec::Esp32Executor;
+#[cfg(feature = "esp32")]
+use crate::esp32::tcp::Esp32Stream as PlainStream;
 #[cfg(feature = "native")]
 use crate::native::exec::NativeExecutor;
-use async_channel::Sender;
-use bytes::{BufMut, Bytes, BytesMut};
-use futures_lite::Stream;
+#[cfg(feature = "native")]
+use crate::native::tcp::NativeStream as PlainStream;
+use async_channel::{Receiver, Sender};
+use async_io::{Async, Timer};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures_lite::{FutureExt, Stream};
 use http_body_util::combinators::BoxBody;
 use http_body_util::BodyExt;
+use http_body_util::Full;
 use hyper::body::{Body, Incoming};
 use hyper::client::conn::http2::SendRequest;
 use hyper::header::HeaderMap;
@@ -15,15 +41,34 @@ use hyper::rt;
 use hyper::{http::status, Method, Request};
 
 use async_executor::Task;
-use std::{marker::PhantomData, task::Poll};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    future::Future,
+    marker::PhantomData,
+    net::{SocketAddr, TcpStream},
+    rc::Rc,
+    task::Poll,
+    time::Duration,
+};
 use thiserror::Error;
 
+use super::secret::SecretString;
+
 #[derive(Error, Debug)]
 pub enum GrpcClientError {
     #[error(transparent)]
     ConversionError(#[from] std::num::TryFromIntError),
     #[error(transparent)]
     MessageEncodingError(#[from] prost::EncodeError),
+    #[error(transparent)]
+    MessageDecodingError(#[from] prost::DecodeError),
+    #[error("malformed grpc message")]
+    MalformedMessage,
+    #[error(transparent)]
+    ConnectionError(#[from] std::io::Error),
+    #[error("no response within {0:?}")]
+    Timeout(Duration),
     #[error("http request error {0}")]
     HttpStatusError(status::StatusCode),
     #[error(transparent)]
@@ -34,6 +79,34 @@ pub enum GrpcClientError {
     GrpcError { code: i8, message: String },
     #[error(transparent)]
     ErrorSendingToAStream(#[from] async_channel::SendError<Bytes>),
+    #[error("the grpc client was dropped before the call completed")]
+    ClientDropped,
+}
+
+/// Encodes a message with the length prefix of the gRPC framing
+pub(crate) fn encode_message<T: prost::Message>(message: &T) -> Result<Bytes, GrpcClientError> {
+    let mut buf = BytesMut::with_capacity(message.encoded_len() + 5);
+    buf.put_u8(0);
+    buf.put_u32(message.encoded_len().try_into()?);
+    let mut msg = buf.split_off(5);
+    message.encode(&mut msg)?;
+    buf.unsplit(msg);
+    Ok(buf.into())
+}
+
+/// Decodes the single, uncompressed, message of a unary response body
+pub(crate) fn decode_message<T: prost::Message + Default>(
+    mut body: Bytes,
+) -> Result<T, GrpcClientError> {
+    if body.len() < 5 || body[0] != 0 {
+        return Err(GrpcClientError::MalformedMessage);
+    }
+    body.advance(1);
+    let len = body.get_u32() as usize;
+    if body.len() != len {
+        return Err(GrpcClientError::MalformedMessage);
+    }
+    Ok(T::decode(body)?)
 }
 
 pub(crate) struct GrpcMessageSender<T> {
@@ -52,15 +125,7 @@ where
         }
     }
     pub(crate) async fn send_message(&mut self, message: T) -> Result<(), GrpcClientError> {
-        let body: Bytes = {
-            let mut buf = BytesMut::with_capacity(message.encoded_len() + 5);
-            buf.put_u8(0);
-            buf.put_u32(message.encoded_len().try_into()?);
-            let mut msg = buf.split_off(5);
-            message.encode(&mut msg)?;
-            buf.unsplit(msg);
-            buf.into()
-        };
+        let body = encode_message(&message)?;
         self.sender_half
             .send(body)
             .await
@@ -147,14 +212,14 @@ pub struct GrpcClient<'a> {
     http2_connection: SendRequest<BoxBody<Bytes, hyper::Error>>,
     #[allow(dead_code)]
     http2_task: Option<Task<()>>,
-    uri: &'a str,
+    uri: Cow<'a, str>,
 }
 
 impl<'a> GrpcClient<'a> {
     pub async fn new<T>(
         io: T,
         executor: Executor,
-        uri: &'a str,
+        uri: impl Into<Cow<'a, str>>,
     ) -> Result<GrpcClient<'a>, GrpcClientError>
     where
         T: rt::Read + rt::Write + Unpin + 'static,
@@ -180,10 +245,29 @@ impl<'a> GrpcClient<'a> {
             executor,
             http2_connection,
             http2_task: Some(http2_task),
-            uri,
+            uri: uri.into(),
         })
     }
 
+    /// Opens a plaintext (h2c) connection to the gRPC server listening on `addr`
+    pub async fn connect(
+        addr: SocketAddr,
+        executor: Executor,
+    ) -> Result<GrpcClient<'static>, GrpcClientError> {
+        let stream = Async::<TcpStream>::connect(addr).await?;
+        GrpcClient::new(
+            PlainStream::LocalPlain(stream),
+            executor,
+            format!("http://{}", addr),
+        )
+        .await
+    }
+
+    /// Returns true while the underlying HTTP2 connection can still open new streams
+    pub(crate) fn is_connected(&self) -> bool {
+        !self.http2_connection.is_closed()
+    }
+
     pub(crate) fn build_request<B: Body>(
         &self,
         path: &str,
@@ -191,7 +275,7 @@ impl<'a> GrpcClient<'a> {
         rpc_host: &str,
         body: B,
     ) -> Result<Request<B>, GrpcClientError> {
-        let mut uri = self.uri.to_owned();
+        let mut uri = self.uri.to_string();
         uri.push_str(path);
 
         let mut r = Request::builder()
@@ -290,4 +374,268 @@ impl<'a> GrpcClient<'a> {
         }
         Ok((body.to_bytes(), part.headers))
     }
+
+    /// Calls the unary method `path` (e.g. `/package.Service/Method`) with `request`
+    pub async fn unary<Req, Resp>(
+        &mut self,
+        path: &str,
+        authorization: Option<&str>,
+        rpc_host: &str,
+        request: Req,
+    ) -> Result<Resp, GrpcClientError>
+    where
+        Req: prost::Message,
+        Resp: prost::Message + Default,
+    {
+        let body = encode_message(&request)?;
+        let r = self.build_request(
+            path,
+            authorization,
+            rpc_host,
+            BodyExt::boxed(Full::new(body).map_err(|never| match never {})),
+        )?;
+        let (body, _) = self.send_request(r).await?;
+        decode_message(body)
+    }
+}
+
+// A unary call queued for the task of a [PeerGrpcClient]
+struct PeerCall {
+    path: String,
+    body: Bytes,
+    reply: Sender<Result<Bytes, GrpcClientError>>,
+}
+
+// Settings of a [PeerGrpcClient], moved to its task on the first call
+#[derive(Clone)]
+struct PeerConfig {
+    addr: SocketAddr,
+    authorization: Option<SecretString>,
+    rpc_host: String,
+    timeout: Duration,
+}
+
+/// A gRPC client making unary calls to another server from a task of the robot's executor,
+/// each call fails after `timeout` (5s by default). It must be used on the thread running the
+/// robot's executor, which is the case of components.
+pub struct PeerGrpcClient {
+    config: PeerConfig,
+    calls: Option<Sender<PeerCall>>,
+    // dropping the client cancels the task along with the calls it didn't make yet
+    task: Option<Task<()>>,
+}
+
+impl PeerGrpcClient {
+    /// Creates a client for the server listening on `addr`, connecting on the first call
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            config: PeerConfig {
+                addr,
+                authorization: None,
+                rpc_host: String::new(),
+                timeout: Duration::from_secs(5),
+            },
+            calls: None,
+            task: None,
+        }
+    }
+
+    /// Sends `authorization` (e.g. `Bearer <token>`) with every call
+    pub fn with_authorization(mut self, authorization: impl Into<SecretString>) -> Self {
+        self.config.authorization = Some(authorization.into());
+        self
+    }
+
+    /// Sets the `rpc-host` header, the FQDN of the part when calling another robot part
+    pub fn with_rpc_host(mut self, rpc_host: impl Into<String>) -> Self {
+        self.config.rpc_host = rpc_host.into();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    fn calls(&mut self) -> Sender<PeerCall> {
+        if let Some(calls) = &self.calls {
+            return calls.clone();
+        }
+        let (calls, queue) = async_channel::bounded(4);
+        self.task = Some(Executor::new().spawn(Self::run(self.config.clone(), queue)));
+        self.calls.insert(calls).clone()
+    }
+
+    // Makes the queued calls one after the other, reconnecting when the connection is lost
+    async fn run(config: PeerConfig, queue: Receiver<PeerCall>) {
+        let mut client: Option<GrpcClient<'static>> = None;
+        while let Ok(PeerCall { path, body, reply }) = queue.recv().await {
+            let res = async {
+                let connected = match client.take() {
+                    Some(connected) if connected.is_connected() => connected,
+                    _ => GrpcClient::connect(config.addr, Executor::new()).await?,
+                };
+                let connected = client.insert(connected);
+                let request = connected.build_request(
+                    &path,
+                    config.authorization.as_ref().map(|a| a.expose()),
+                    &config.rpc_host,
+                    BodyExt::boxed(Full::new(body).map_err(|never| match never {})),
+                )?;
+                connected.send_request(request).await.map(|(body, _)| body)
+            }
+            .or(async {
+                Timer::after(config.timeout).await;
+                Err(GrpcClientError::Timeout(config.timeout))
+            })
+            .await;
+            // a call interrupted by the timeout may have left the stream in an unknown state
+            if matches!(
+                res,
+                Err(GrpcClientError::Timeout(_)
+                    | GrpcClientError::HyperError(_)
+                    | GrpcClientError::ConnectionError(_))
+            ) {
+                client = None;
+            }
+            let _ = reply.send(res).await;
+        }
+    }
+
+    // Queues a call, the returned future doesn't borrow the client
+    fn queue<Resp>(
+        &mut self,
+        path: &str,
+        body: Result<Bytes, GrpcClientError>,
+    ) -> impl Future<Output = Result<Resp, GrpcClientError>>
+    where
+        Resp: prost::Message + Default,
+    {
+        let calls = self.calls();
+        let path = path.to_owned();
+        async move {
+            let (reply, response) = async_channel::bounded(1);
+            let call = PeerCall {
+                path,
+                body: body?,
+                reply,
+            };
+            calls
+                .send(call)
+                .await
+                .map_err(|_| GrpcClientError::ClientDropped)?;
+            let body = response
+                .recv()
+                .await
+                .map_err(|_| GrpcClientError::ClientDropped)??;
+            decode_message(body)
+        }
+    }
+
+    /// Calls the unary method `path` (e.g. `/package.Service/Method`) with `request`
+    pub async fn call<Req, Resp>(
+        &mut self,
+        path: &str,
+        request: Req,
+    ) -> Result<Resp, GrpcClientError>
+    where
+        Req: prost::Message,
+        Resp: prost::Message + Default,
+    {
+        self.queue(path, encode_message(&request)).await
+    }
+
+    /// Starts calling the unary method `path` with `request` without waiting for the response,
+    /// which is collected from the returned [PendingCall]
+    pub fn spawn_call<Req, Resp>(&mut self, path: &str, request: Req) -> PendingCall<Resp>
+    where
+        Req: prost::Message,
+        Resp: prost::Message + Default + 'static,
+    {
+        let call = self.queue(path, encode_message(&request));
+        let result = Rc::new(RefCell::new(None));
+        let slot = result.clone();
+        let task = Executor::new().spawn(async move {
+            let res = call.await;
+            slot.borrow_mut().replace(res);
+        });
+        PendingCall {
+            _task: task,
+            result,
+        }
+    }
+}
+
+/// A call started by [PeerGrpcClient::spawn_call], dropping it cancels the call
+pub struct PendingCall<Resp> {
+    _task: Task<()>,
+    result: Rc<RefCell<Option<Result<Resp, GrpcClientError>>>>,
+}
+
+impl<Resp> PendingCall<Resp> {
+    /// Returns true once the response (or the error) is available
+    pub fn is_finished(&self) -> bool {
+        self.result.borrow().is_some()
+    }
+
+    /// Takes the response once available, never blocks
+    pub fn try_take(&mut self) -> Option<Result<Resp, GrpcClientError>> {
+        self.result.borrow_mut().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_io::Timer;
+
+    use super::{decode_message, encode_message, Executor, GrpcClientError, PeerGrpcClient};
+    use crate::proto::rpc::examples::echo::v1::{EchoRequest, EchoResponse};
+
+    #[test_log::test]
+    fn test_message_framing() -> Result<(), GrpcClientError> {
+        let request = EchoRequest {
+            message: "hello".to_owned(),
+        };
+        let framed = encode_message(&request)?;
+        assert_eq!(&framed[..5], &[0, 0, 0, 0, 7]);
+        assert_eq!(decode_message::<EchoRequest>(framed.clone())?, request);
+
+        assert!(matches!(
+            decode_message::<EchoRequest>(framed.slice(..8)),
+            Err(GrpcClientError::MalformedMessage)
+        ));
+        // compressed messages aren't supported
+        let mut compressed = framed.to_vec();
+        compressed[0] = 1;
+        assert!(decode_message::<EchoRequest>(compressed.into()).is_err());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_peer_spawn_call() {
+        // nothing listens on the discard port, the call fails without blocking the caller
+        let mut client = PeerGrpcClient::new("127.0.0.1:9".parse().unwrap())
+            .with_timeout(Duration::from_secs(1));
+        let mut pending = client.spawn_call::<_, EchoResponse>(
+            "/proto.rpc.examples.echo.v1.EchoService/Echo",
+            EchoRequest {
+                message: "hello".to_owned(),
+            },
+        );
+        assert!(pending.try_take().is_none());
+        Executor::new().block_on(async {
+            while !pending.is_finished() {
+                Timer::after(Duration::from_millis(10)).await;
+            }
+        });
+        assert!(matches!(
+            pending.try_take(),
+            Some(Err(
+                GrpcClientError::ConnectionError(_) | GrpcClientError::Timeout(_)
+            ))
+        ));
+        assert!(pending.try_take().is_none());
+    }
 }
@@ -3,6 +3,7 @@
 use super::config::{AttributeError, Kind};
 use std::sync::{Arc, Mutex};
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -21,6 +22,19 @@ pub enum I2CErrors {
     I2COtherError(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
 
+impl GrpcStatusHint for I2CErrors {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::I2CInvalidArgument(_) => GrpcError::RpcInvalidArgument,
+            Self::I2CUnimplemented(_) => GrpcError::RpcUnimplemented,
+            Self::I2CReadError(_, _)
+            | Self::I2CWriteError(_, _)
+            | Self::I2CReadWriteError(_, _)
+            | Self::I2COtherError(_) => GrpcError::RpcInternal,
+        }
+    }
+}
+
 // A trait representing blocking I2C communication for a board. TODO: replace with the
 // embedded_hal I2C trait when supporting boards beyond ESP32.
 pub trait I2CHandle {
@@ -47,6 +61,169 @@ pub trait I2CHandle {
 
 pub type I2cHandleType = Arc<Mutex<dyn I2CHandle + Send>>;
 
+/// Byte order of the multi-byte registers of a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+impl Endian {
+    pub fn u16_from(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Self::Big => u16::from_be_bytes(bytes),
+            Self::Little => u16::from_le_bytes(bytes),
+        }
+    }
+
+    pub fn i16_from(self, bytes: [u8; 2]) -> i16 {
+        self.u16_from(bytes) as i16
+    }
+
+    pub fn u16_to(self, value: u16) -> [u8; 2] {
+        match self {
+            Self::Big => value.to_be_bytes(),
+            Self::Little => value.to_le_bytes(),
+        }
+    }
+
+    /// Decodes the `N` consecutive 16 bits integers at the start of `bytes`, which must hold at
+    /// least `2 * N` bytes
+    pub fn i16s_from<const N: usize>(self, bytes: &[u8]) -> [i16; N] {
+        let mut values = [0; N];
+        for (value, chunk) in values.iter_mut().zip(bytes.chunks_exact(2)) {
+            *value = self.i16_from([chunk[0], chunk[1]]);
+        }
+        values
+    }
+}
+
+/// `width` bits of an 8 bits register starting at bit `shift`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitField {
+    shift: u8,
+    width: u8,
+}
+
+impl BitField {
+    pub const fn new(shift: u8, width: u8) -> Self {
+        assert!(width > 0 && shift + width <= 8);
+        Self { shift, width }
+    }
+
+    /// A single bit flag
+    pub const fn bit(shift: u8) -> Self {
+        Self::new(shift, 1)
+    }
+
+    fn mask(&self) -> u8 {
+        (((1_u16 << self.width) - 1) as u8) << self.shift
+    }
+
+    /// Value of the field in `register`
+    pub fn get(&self, register: u8) -> u8 {
+        (register & self.mask()) >> self.shift
+    }
+
+    /// `register` with the field set to `value`, which is truncated to the width of the field
+    pub fn set(&self, register: u8, value: u8) -> u8 {
+        (register & !self.mask()) | ((value << self.shift) & self.mask())
+    }
+}
+
+/// Typed access to the registers of a device on an I2C bus, registers are addressed by writing
+/// their number before reading or writing their content. Reads of several bytes rely on the
+/// device incrementing the register address on its own, as most do.
+pub struct RegisterMap<H> {
+    i2c_handle: H,
+    i2c_address: u8,
+    endian: Endian,
+}
+
+impl<H: I2CHandle> RegisterMap<H> {
+    pub fn new(i2c_handle: H, i2c_address: u8, endian: Endian) -> Self {
+        Self {
+            i2c_handle,
+            i2c_address,
+            endian,
+        }
+    }
+
+    pub fn i2c_address(&self) -> u8 {
+        self.i2c_address
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    pub fn read_bytes(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), I2CErrors> {
+        self.i2c_handle
+            .write_read_i2c(self.i2c_address, &[register], buffer)
+    }
+
+    pub fn write_bytes(&mut self, register: u8, bytes: &[u8]) -> Result<(), I2CErrors> {
+        let mut buffer = Vec::with_capacity(bytes.len() + 1);
+        buffer.push(register);
+        buffer.extend_from_slice(bytes);
+        self.i2c_handle.write_i2c(self.i2c_address, &buffer)
+    }
+
+    pub fn read_u8(&mut self, register: u8) -> Result<u8, I2CErrors> {
+        let mut buffer = [0_u8; 1];
+        self.read_bytes(register, &mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    pub fn write_u8(&mut self, register: u8, value: u8) -> Result<(), I2CErrors> {
+        self.i2c_handle
+            .write_i2c(self.i2c_address, &[register, value])
+    }
+
+    pub fn read_u16(&mut self, register: u8) -> Result<u16, I2CErrors> {
+        let mut buffer = [0_u8; 2];
+        self.read_bytes(register, &mut buffer)?;
+        Ok(self.endian.u16_from(buffer))
+    }
+
+    pub fn read_i16(&mut self, register: u8) -> Result<i16, I2CErrors> {
+        Ok(self.read_u16(register)? as i16)
+    }
+
+    pub fn write_u16(&mut self, register: u8, value: u16) -> Result<(), I2CErrors> {
+        let [first, second] = self.endian.u16_to(value);
+        self.i2c_handle
+            .write_i2c(self.i2c_address, &[register, first, second])
+    }
+
+    /// Reads `N` 16 bits integers stored in consecutive registers starting at `register`
+    pub fn read_i16s<const N: usize>(&mut self, register: u8) -> Result<[i16; N], I2CErrors> {
+        let mut buffer = vec![0_u8; 2 * N];
+        self.read_bytes(register, &mut buffer)?;
+        Ok(self.endian.i16s_from(&buffer))
+    }
+
+    /// Reads `register`, applies `f` to its value and writes the result back
+    pub fn modify_u8(&mut self, register: u8, f: impl FnOnce(u8) -> u8) -> Result<(), I2CErrors> {
+        let value = self.read_u8(register)?;
+        self.write_u8(register, f(value))
+    }
+
+    pub fn read_field(&mut self, register: u8, field: BitField) -> Result<u8, I2CErrors> {
+        Ok(field.get(self.read_u8(register)?))
+    }
+
+    /// Sets `field` of `register` to `value`, leaving the other bits untouched
+    pub fn write_field(
+        &mut self,
+        register: u8,
+        field: BitField,
+        value: u8,
+    ) -> Result<(), I2CErrors> {
+        self.modify_u8(register, |current| field.set(current, value))
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct FakeI2cConfig<'a> {
     pub(crate) name: &'a str,
@@ -122,6 +299,16 @@ impl I2CHandle for FakeI2CHandle {
         }
         Ok(())
     }
+
+    fn write_read_i2c(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), I2CErrors> {
+        self.write_i2c(address, bytes)?;
+        self.read_i2c(address, buffer)
+    }
 }
 
 impl<A> I2CHandle for Arc<Mutex<A>>
@@ -149,3 +336,90 @@ where
         self.lock().unwrap().write_read_i2c(address, bytes, buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BitField, Endian, I2CErrors, I2CHandle, RegisterMap};
+
+    // a device exposing 256 auto incremented registers
+    struct RegisterDevice {
+        registers: [u8; 256],
+        pointer: u8,
+    }
+
+    impl I2CHandle for RegisterDevice {
+        fn name(&self) -> String {
+            "registers".to_string()
+        }
+
+        fn read_i2c(&mut self, _address: u8, buffer: &mut [u8]) -> Result<(), I2CErrors> {
+            for byte in buffer.iter_mut() {
+                *byte = self.registers[self.pointer as usize];
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+            Ok(())
+        }
+
+        fn write_i2c(&mut self, _address: u8, bytes: &[u8]) -> Result<(), I2CErrors> {
+            self.pointer = bytes[0];
+            for byte in &bytes[1..] {
+                self.registers[self.pointer as usize] = *byte;
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+            Ok(())
+        }
+
+        fn write_read_i2c(
+            &mut self,
+            address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), I2CErrors> {
+            self.write_i2c(address, bytes)?;
+            self.read_i2c(address, buffer)
+        }
+    }
+
+    fn device() -> RegisterDevice {
+        RegisterDevice {
+            registers: [0; 256],
+            pointer: 0,
+        }
+    }
+
+    #[test_log::test]
+    fn test_register_map_endian() {
+        let mut big = RegisterMap::new(device(), 0x40, Endian::Big);
+        big.write_u16(0x05, 0x1234).unwrap();
+        assert_eq!(big.read_u8(0x05).unwrap(), 0x12);
+        assert_eq!(big.read_u16(0x05).unwrap(), 0x1234);
+
+        let mut little = RegisterMap::new(device(), 0x53, Endian::Little);
+        little
+            .write_bytes(0x32, &[12, 0, 208, 255, 239, 0])
+            .unwrap();
+        assert_eq!(little.read_i16(0x34).unwrap(), -48);
+        assert_eq!(little.read_i16s::<3>(0x32).unwrap(), [12, -48, 239]);
+        assert_eq!(
+            Endian::Big.i16s_from::<2>(&[0xFF, 0xFE, 0x01, 0x00]),
+            [-2, 256]
+        );
+    }
+
+    #[test_log::test]
+    fn test_register_map_fields() {
+        let range = BitField::new(3, 2);
+        assert_eq!(range.get(0b1101_1010), 0b11);
+        assert_eq!(range.set(0b1101_1010, 0b01), 0b1100_1010);
+        // values wider than the field are truncated
+        assert_eq!(range.set(0, 0xFF), 0b0001_1000);
+
+        let mut registers = RegisterMap::new(device(), 0x68, Endian::Big);
+        registers.write_u8(0x6B, 0b0000_0001).unwrap();
+        registers.write_field(0x6B, BitField::bit(6), 1).unwrap();
+        assert_eq!(registers.read_u8(0x6B).unwrap(), 0b0100_0001);
+        assert_eq!(registers.read_field(0x6B, BitField::bit(6)).unwrap(), 1);
+        registers.modify_u8(0x6B, |value| value & 0x0F).unwrap();
+        assert_eq!(registers.read_u8(0x6B).unwrap(), 0b0000_0001);
+    }
+}
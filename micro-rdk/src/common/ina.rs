@@ -12,7 +12,7 @@
 ///
 /// The calibration register is programmed to measure current and power properly.
 /// The calibration register is set to: calibratescale / (current_lsb * sense_resistor)
-use crate::common::i2c::I2CHandle;
+use crate::common::i2c::{Endian, I2CHandle, RegisterMap};
 use crate::common::status::StatusError;
 
 use core::fmt;
@@ -37,9 +37,9 @@ const INA_226_CALIBRATION_SCALE: f64 = 0.00512;
 const DEFAULT_CONFIG_REGISTER_VALUE: u16 = 0x399F;
 const CALIBRATION_REGISTER: u8 = 0x05;
 const CONFIG_REGISTER: u8 = 0x00;
-const VOLTAGE_REGISTER: [u8; 1] = [0x02];
-const CURRENT_AMPERES_REGISTER: [u8; 1] = [0x04];
-const POWER_REGISTER: [u8; 1] = [0x03];
+const VOLTAGE_REGISTER: u8 = 0x02;
+const CURRENT_AMPERES_REGISTER: u8 = 0x04;
+const POWER_REGISTER: u8 = 0x03;
 
 pub(crate) fn register_models(registry: &mut ComponentRegistry) {
     if registry
@@ -129,8 +129,7 @@ impl fmt::Display for Model {
 #[derive(DoCommand, PowerSensorReadings)]
 struct Ina<H: I2CHandle> {
     model: Model,
-    i2c_handle: H,
-    i2c_address: u8,
+    registers: RegisterMap<H>,
     max_current_nano_amperes: i64,
     power_reading_lsb: i64,
 }
@@ -159,43 +158,34 @@ impl<H: I2CHandle> Ina<H> {
                 "ina calibration scale exceeds limit of 1 << 16",
             ));
         }
-        let mut calibration_scale_bytes = (calibration_scale as u16).to_be_bytes();
         let mut res = Self {
             model,
-            i2c_handle,
-            i2c_address,
+            registers: RegisterMap::new(i2c_handle, i2c_address, Endian::Big),
             max_current_nano_amperes,
             power_reading_lsb,
         };
-        res.calibrate(&mut calibration_scale_bytes)?;
+        res.calibrate(calibration_scale as u16)?;
         Ok(res)
     }
 
-    fn calibrate(&mut self, calibration_scale_bytes: &mut [u8; 2]) -> Result<(), I2CErrors> {
+    fn calibrate(&mut self, calibration_scale: u16) -> Result<(), I2CErrors> {
         // set scaling factor for current and power registers by writing adjusted
         // calibration scale to the appropriate register
-        self.write_to_register(CALIBRATION_REGISTER, calibration_scale_bytes)?;
+        self.registers
+            .write_u16(CALIBRATION_REGISTER, calibration_scale)?;
         // set the sensor into its normal operating mode 111 (continously reading voltage, current and power),
         // 0s indicate that the corresponding measurement will onyl be made in response to an event
-        let default_config_register_bytes = DEFAULT_CONFIG_REGISTER_VALUE.to_be_bytes();
-        self.write_to_register(CONFIG_REGISTER, &default_config_register_bytes)
-    }
-
-    fn write_to_register(&mut self, register: u8, buffer: &[u8]) -> Result<(), I2CErrors> {
-        let mut byte_vec: Vec<u8> = vec![register];
-        byte_vec.extend_from_slice(buffer);
-        self.i2c_handle.write_i2c(self.i2c_address, &byte_vec)
+        self.registers
+            .write_u16(CONFIG_REGISTER, DEFAULT_CONFIG_REGISTER_VALUE)
     }
 }
 
 impl<H: I2CHandle> PowerSensor for Ina<H> {
     fn get_voltage(&mut self) -> Result<Voltage, SensorError> {
-        let mut voltage_bytes: [u8; 2] = [0; 2];
-        self.i2c_handle
-            .write_read_i2c(self.i2c_address, &VOLTAGE_REGISTER, &mut voltage_bytes)?;
+        let voltage = self.registers.read_i16(VOLTAGE_REGISTER)?;
         let volts = match self.model {
-            Model::Ina226 => (i16::from_be_bytes(voltage_bytes) as f64) * 1.25e-3,
-            Model::Ina219 => ((i16::from_be_bytes(voltage_bytes) >> 3) as f64) / 250.0,
+            Model::Ina226 => (voltage as f64) * 1.25e-3,
+            Model::Ina219 => ((voltage >> 3) as f64) / 250.0,
         };
         Ok(Voltage {
             volts,
@@ -205,14 +195,8 @@ impl<H: I2CHandle> PowerSensor for Ina<H> {
 
     fn get_current(&mut self) -> Result<Current, SensorError> {
         let current_reading_lsb = self.max_current_nano_amperes / (1 << 15);
-        let mut current_amperes_bytes: [u8; 2] = [0; 2];
-        self.i2c_handle.write_read_i2c(
-            self.i2c_address,
-            &CURRENT_AMPERES_REGISTER,
-            &mut current_amperes_bytes,
-        )?;
         let current_nano_amperes =
-            (i16::from_be_bytes(current_amperes_bytes) as i64) * current_reading_lsb;
+            (self.registers.read_i16(CURRENT_AMPERES_REGISTER)? as i64) * current_reading_lsb;
         let amperes = (current_nano_amperes as f64) * 1e-9;
         Ok(Current {
             amperes,
@@ -221,10 +205,8 @@ impl<H: I2CHandle> PowerSensor for Ina<H> {
     }
 
     fn get_power(&mut self) -> Result<f64, SensorError> {
-        let mut power_bytes: [u8; 2] = [0; 2];
-        self.i2c_handle
-            .write_read_i2c(self.i2c_address, &POWER_REGISTER, &mut power_bytes)?;
-        let power_nano_watts = (i16::from_be_bytes(power_bytes) as i64) * self.power_reading_lsb;
+        let power_nano_watts =
+            (self.registers.read_i16(POWER_REGISTER)? as i64) * self.power_reading_lsb;
         Ok((power_nano_watts as f64) * 1e-9)
     }
 }
@@ -0,0 +1,548 @@
+//! Runtime instrumentation to catch leaks in long-running deployments, configured through a
+//! service of type `instrumentation` in the robot's config:
+//!
+//! ```json
+//! {
+//!     "name": "instrumentation",
+//!     "type": "instrumentation",
+//!     "attributes": {
+//!         "sample_interval_secs": 60,
+//!         "free_heap_warning_bytes": 30000,
+//!         "min_free_heap_warning_bytes": 15000,
+//!         "executor_tasks_warning": 64,
+//!         "capture_frequency_hz": 0.0166
+//!     }
+//! }
+//! ```
+//!
+//! Every `sample_interval_secs` seconds (60 by default) the free heap, the minimum free heap
+//! ever seen and the number of tasks alive on the executor are sampled and reported by the
+//! `telemetry` sensor. A warning is logged when a sample crosses one of the optional thresholds
+//! and once it is back within bounds. Heap statistics are only available on the ESP32.
+//!
+//! When `capture_frequency_hz` is set and the data manager is configured, samples are also
+//! captured and synced under the reserved collector [COLLECTOR_NAME].
+//!
+//! Whether or not the service is configured, micro-rdk also counts a few events since boot
+//! ([Counter]). With the samples and the numeric telemetry they are rendered in the Prometheus
+//! text format by [prometheus_metrics], served at `/metrics` by the
+//! [json endpoint](super::json_endpoint) so monitoring infrastructure can scrape the device.
+
+use crate::google;
+use crate::proto::app::v1::ConfigResponse;
+
+use super::config::{AttributeError, Kind};
+use super::telemetry::{numeric_telemetry, record_telemetry};
+
+use async_io::Timer;
+use futures_lite::Future;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+#[cfg(feature = "data")]
+use {
+    super::sensor::{GenericReadingsResult, Readings, Sensor, SensorError},
+    std::collections::HashMap,
+};
+
+pub static SERVICE_TYPE: &str = "instrumentation";
+/// Name of the data collector capturing instrumentation samples, components can't use it
+pub static COLLECTOR_NAME: &str = "micro-rdk-instrumentation";
+
+const DEFAULT_SAMPLE_INTERVAL_SECS: f64 = 60.0;
+
+#[derive(Debug, Error)]
+pub enum InstrumentationError {
+    #[error("instrumentation config error: {0}")]
+    ConfigError(&'static str),
+    #[error(transparent)]
+    ConfigAttributeError(#[from] AttributeError),
+}
+
+static EXECUTOR_TASKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Prefix of the names of the metrics exported by [prometheus_metrics]
+const METRICS_PREFIX: &str = "micro_rdk_";
+
+/// Events counted since boot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counter {
+    /// Connections to app established again after the previous one was lost
+    AppReconnects,
+    /// Readings captured by the data manager
+    DataCaptures,
+    /// Bytes of captured data synced to app
+    DataUploadBytes,
+}
+
+impl Counter {
+    const ALL: [Counter; 3] = [
+        Counter::AppReconnects,
+        Counter::DataCaptures,
+        Counter::DataUploadBytes,
+    ];
+
+    fn name_and_help(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::AppReconnects => (
+                "app_reconnects_total",
+                "Connections to app established again after being lost",
+            ),
+            Self::DataCaptures => (
+                "data_captures_total",
+                "Readings captured by the data manager",
+            ),
+            Self::DataUploadBytes => (
+                "data_upload_bytes_total",
+                "Bytes of captured data synced to app",
+            ),
+        }
+    }
+}
+
+// 64 bits atomics aren't available on every target
+static COUNTERS: Mutex<[u64; Counter::ALL.len()]> = Mutex::new([0; Counter::ALL.len()]);
+
+/// Adds `by` to a counter
+pub fn increment_counter(counter: Counter, by: u64) {
+    let mut counters = COUNTERS.lock().unwrap();
+    counters[counter as usize] = counters[counter as usize].saturating_add(by);
+}
+
+pub fn counter_value(counter: Counter) -> u64 {
+    COUNTERS.lock().unwrap()[counter as usize]
+}
+
+// the exposition format spells the special values as Go does
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// turns a telemetry key into a metric name, unique among the `names` already written
+fn metric_name(key: &str, names: &HashSet<String>) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    if !names.contains(&name) {
+        return name;
+    }
+    (2..)
+        .map(|suffix| format!("{}_{}", name, suffix))
+        .find(|name| !names.contains(name))
+        .unwrap()
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {}{} {}", METRICS_PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}{} {}", METRICS_PREFIX, name, kind);
+    let _ = writeln!(out, "{}{} {}", METRICS_PREFIX, name, format_value(value));
+}
+
+/// Renders the counters, a new sample and the numeric telemetry entries in the Prometheus text
+/// exposition format. Telemetry keys are turned into valid metric names, suffixed with `_2`,
+/// `_3`... when several keys give the same name
+pub fn prometheus_metrics() -> String {
+    let mut out = String::new();
+    let mut names = HashSet::new();
+    for counter in Counter::ALL {
+        let (name, help) = counter.name_and_help();
+        names.insert(name.to_string());
+        write_metric(
+            &mut out,
+            name,
+            "counter",
+            help,
+            counter_value(counter) as f64,
+        );
+    }
+    let sample = Sample::now();
+    let gauges = [
+        (
+            "executor_tasks",
+            "Tasks alive on the executor",
+            Some(sample.executor_tasks as f64),
+        ),
+        (
+            "free_heap_bytes",
+            "Free heap",
+            sample.free_heap.map(f64::from),
+        ),
+        (
+            "min_free_heap_bytes",
+            "Minimum free heap since boot",
+            sample.min_free_heap.map(f64::from),
+        ),
+    ];
+    for (name, help, value) in gauges {
+        names.insert(name.to_string());
+        if let Some(value) = value {
+            write_metric(&mut out, name, "gauge", help, value);
+        }
+    }
+    for (key, value) in numeric_telemetry() {
+        // the samples recorded by the instrumentation service were just exported
+        if gauges.iter().any(|(name, _, _)| *name == key) {
+            continue;
+        }
+        let name = metric_name(&key, &names);
+        write_metric(&mut out, &name, "gauge", "Telemetry entry", value);
+        names.insert(name);
+    }
+    out
+}
+
+/// Counts a task spawned on the executor for as long as it is held
+struct ExecutorTask;
+
+impl ExecutorTask {
+    fn new() -> Self {
+        EXECUTOR_TASKS.fetch_add(1, Ordering::AcqRel);
+        Self
+    }
+}
+
+impl Drop for ExecutorTask {
+    fn drop(&mut self) {
+        EXECUTOR_TASKS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Wraps a future about to be spawned so it is counted until it completes or is cancelled
+pub(crate) fn track_task<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let task = ExecutorTask::new();
+    async move {
+        let _task = task;
+        future.await
+    }
+}
+
+#[cfg(feature = "esp32")]
+fn heap_stats() -> (Option<u32>, Option<u32>) {
+    use crate::esp32::esp_idf_svc::sys::{esp_get_free_heap_size, esp_get_minimum_free_heap_size};
+    unsafe {
+        (
+            Some(esp_get_free_heap_size()),
+            Some(esp_get_minimum_free_heap_size()),
+        )
+    }
+}
+
+#[cfg(not(feature = "esp32"))]
+fn heap_stats() -> (Option<u32>, Option<u32>) {
+    (None, None)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sample {
+    pub free_heap: Option<u32>,
+    pub min_free_heap: Option<u32>,
+    pub executor_tasks: usize,
+}
+
+impl Sample {
+    pub fn now() -> Self {
+        let (free_heap, min_free_heap) = heap_stats();
+        Self {
+            free_heap,
+            min_free_heap,
+            executor_tasks: EXECUTOR_TASKS.load(Ordering::Acquire),
+        }
+    }
+
+    #[cfg(feature = "data")]
+    fn readings(&self) -> Vec<(&'static str, google::protobuf::Value)> {
+        let value = |v: f64| google::protobuf::Value {
+            kind: Some(google::protobuf::value::Kind::NumberValue(v)),
+        };
+        let mut readings = vec![("executor_tasks", value(self.executor_tasks as f64))];
+        if let Some(free_heap) = self.free_heap {
+            readings.push(("free_heap_bytes", value(free_heap as f64)));
+        }
+        if let Some(min_free_heap) = self.min_free_heap {
+            readings.push(("min_free_heap_bytes", value(min_free_heap as f64)));
+        }
+        readings
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstrumentationConfig {
+    pub sample_interval: Duration,
+    pub free_heap_warning: Option<u32>,
+    pub min_free_heap_warning: Option<u32>,
+    pub executor_tasks_warning: Option<u32>,
+    pub capture_frequency_hz: Option<f32>,
+}
+
+impl TryFrom<&Kind> for InstrumentationConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let sample_interval_secs: f64 = value
+            .get("sample_interval_secs")?
+            .map(f64::try_from)
+            .transpose()?
+            .unwrap_or(DEFAULT_SAMPLE_INTERVAL_SECS);
+        if sample_interval_secs <= 0.0 {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        let capture_frequency_hz: Option<f32> = value
+            .get("capture_frequency_hz")?
+            .map(f32::try_from)
+            .transpose()?;
+        if capture_frequency_hz.is_some_and(|hz| hz <= 0.0) {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        Ok(Self {
+            sample_interval: Duration::from_secs_f64(sample_interval_secs),
+            free_heap_warning: value
+                .get("free_heap_warning_bytes")?
+                .map(u32::try_from)
+                .transpose()?,
+            min_free_heap_warning: value
+                .get("min_free_heap_warning_bytes")?
+                .map(u32::try_from)
+                .transpose()?,
+            executor_tasks_warning: value
+                .get("executor_tasks_warning")?
+                .map(u32::try_from)
+                .transpose()?,
+            capture_frequency_hz,
+        })
+    }
+}
+
+impl InstrumentationConfig {
+    /// Returns the instrumentation configuration if the service is configured
+    pub fn from_config(cfg: &ConfigResponse) -> Result<Option<Self>, InstrumentationError> {
+        let robot_config = cfg
+            .config
+            .as_ref()
+            .ok_or(InstrumentationError::ConfigError("missing robot config"))?;
+        let mut services = robot_config
+            .services
+            .iter()
+            .filter(|svc_cfg| svc_cfg.r#type == SERVICE_TYPE);
+        let svc_cfg = match services.next() {
+            Some(svc_cfg) => svc_cfg,
+            None => return Ok(None),
+        };
+        if services.next().is_some() {
+            return Err(InstrumentationError::ConfigError(
+                "multiple instrumentation services configured",
+            ));
+        }
+        let attributes = Kind::try_from(google::protobuf::value::Kind::StructValue(
+            svc_cfg.attributes.clone().unwrap_or_default(),
+        ))?;
+        Ok(Some(InstrumentationConfig::try_from(&attributes)?))
+    }
+}
+
+/// Periodically samples the runtime and warns when a threshold is crossed
+pub struct Instrumentation {
+    config: InstrumentationConfig,
+    // whether each watermark (free heap, min free heap, executor tasks) is currently crossed
+    alerts: [bool; 3],
+}
+
+impl Instrumentation {
+    pub fn new(config: InstrumentationConfig) -> Self {
+        Self {
+            config,
+            alerts: [false; 3],
+        }
+    }
+
+    pub fn from_config(cfg: &ConfigResponse) -> Result<Option<Self>, InstrumentationError> {
+        Ok(InstrumentationConfig::from_config(cfg)?.map(Self::new))
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            self.run_inner(Sample::now());
+            Timer::after(self.config.sample_interval).await;
+        }
+    }
+
+    fn run_inner(&mut self, sample: Sample) {
+        record_telemetry("executor_tasks", sample.executor_tasks);
+        if let Some(free_heap) = sample.free_heap {
+            record_telemetry("free_heap_bytes", free_heap);
+        }
+        if let Some(min_free_heap) = sample.min_free_heap {
+            record_telemetry("min_free_heap_bytes", min_free_heap);
+        }
+        let watermarks = [
+            (
+                "free heap",
+                sample.free_heap,
+                self.config.free_heap_warning,
+                true,
+            ),
+            (
+                "minimum free heap",
+                sample.min_free_heap,
+                self.config.min_free_heap_warning,
+                true,
+            ),
+            (
+                "executor tasks",
+                Some(sample.executor_tasks as u32),
+                self.config.executor_tasks_warning,
+                false,
+            ),
+        ];
+        for (idx, (name, value, threshold, below)) in watermarks.into_iter().enumerate() {
+            let (Some(value), Some(threshold)) = (value, threshold) else {
+                continue;
+            };
+            let crossed = if below {
+                value < threshold
+            } else {
+                value > threshold
+            };
+            if crossed && !self.alerts[idx] {
+                log::warn!(
+                    "{} crossed its watermark: {} (threshold {})",
+                    name,
+                    value,
+                    threshold
+                );
+            } else if !crossed && self.alerts[idx] {
+                log::warn!("{} back within its watermark: {}", name, value);
+            }
+            self.alerts[idx] = crossed;
+        }
+    }
+}
+
+/// Reports the current sample, used by the reserved data collector
+#[cfg(feature = "data")]
+#[derive(DoCommand, Status)]
+pub struct InstrumentationSensor;
+
+#[cfg(feature = "data")]
+impl Sensor for InstrumentationSensor {}
+
+#[cfg(feature = "data")]
+impl Readings for InstrumentationSensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        Ok(Sample::now()
+            .readings()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect::<HashMap<_, _>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        counter_value, increment_counter, prometheus_metrics, Counter, Instrumentation,
+        InstrumentationConfig, Sample,
+    };
+    use crate::common::config::Kind;
+    use crate::common::telemetry::{get_telemetry, record_telemetry};
+    use crate::google;
+    use std::time::Duration;
+
+    #[test_log::test]
+    fn test_instrumentation_config() {
+        let conf = Kind::StructValue(
+            [
+                ("sample_interval_secs", Kind::NumberValue(10.0)),
+                ("free_heap_warning_bytes", Kind::NumberValue(30000.0)),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+        );
+        let conf = InstrumentationConfig::try_from(&conf).unwrap();
+        assert_eq!(conf.sample_interval, Duration::from_secs(10));
+        assert_eq!(conf.free_heap_warning, Some(30000));
+        assert_eq!(conf.executor_tasks_warning, None);
+        assert_eq!(conf.capture_frequency_hz, None);
+
+        let conf = Kind::StructValue(
+            [("sample_interval_secs".to_string(), Kind::NumberValue(0.0))]
+                .into_iter()
+                .collect(),
+        );
+        assert!(InstrumentationConfig::try_from(&conf).is_err());
+    }
+
+    #[test_log::test]
+    fn test_instrumentation_watermarks() {
+        let mut instrumentation = Instrumentation::new(InstrumentationConfig {
+            sample_interval: Duration::from_secs(1),
+            free_heap_warning: Some(1000),
+            min_free_heap_warning: None,
+            executor_tasks_warning: Some(4),
+            capture_frequency_hz: None,
+        });
+        let sample = |free_heap, executor_tasks| Sample {
+            free_heap: Some(free_heap),
+            min_free_heap: Some(free_heap),
+            executor_tasks,
+        };
+        instrumentation.run_inner(sample(2000, 1));
+        assert_eq!(instrumentation.alerts, [false; 3]);
+        instrumentation.run_inner(sample(500, 8));
+        assert_eq!(instrumentation.alerts, [true, false, true]);
+        assert_eq!(
+            get_telemetry("free_heap_bytes").unwrap().kind,
+            Some(google::protobuf::value::Kind::NumberValue(500.0))
+        );
+        instrumentation.run_inner(sample(1500, 2));
+        assert_eq!(instrumentation.alerts, [false; 3]);
+    }
+
+    #[test_log::test]
+    fn test_prometheus_metrics() {
+        let uploaded = counter_value(Counter::DataUploadBytes);
+        increment_counter(Counter::DataUploadBytes, 512);
+        assert_eq!(counter_value(Counter::DataUploadBytes), uploaded + 512);
+        record_telemetry("test metric.rtt_ms", 12.5);
+        record_telemetry("test_metric_profile", "low".to_string());
+
+        let metrics = prometheus_metrics();
+        assert!(metrics.contains("# TYPE micro_rdk_data_upload_bytes_total counter\n"));
+        assert!(metrics.contains(&format!(
+            "\nmicro_rdk_data_upload_bytes_total {}\n",
+            counter_value(Counter::DataUploadBytes)
+        )));
+        assert!(metrics.contains("# TYPE micro_rdk_executor_tasks gauge\n"));
+        assert!(metrics.contains("\nmicro_rdk_test_metric_rtt_ms 12.5\n"));
+        record_telemetry("test_metric:rtt_ms", f64::INFINITY);
+        record_telemetry("9test", f64::NAN);
+        let metrics = prometheus_metrics();
+        // keys sanitized to the same name are exported under distinct names
+        assert!(metrics.contains("\nmicro_rdk_test_metric_rtt_ms_2 +Inf\n"));
+        assert!(metrics.contains("\nmicro_rdk__9test NaN\n"));
+        assert_eq!(
+            metrics
+                .matches("# TYPE micro_rdk_test_metric_rtt_ms ")
+                .count(),
+            1
+        );
+        assert!(!metrics.contains("test_metric_profile"));
+        // heap statistics are only available on the ESP32
+        assert!(!metrics.contains("free_heap_bytes"));
+    }
+}
@@ -0,0 +1,525 @@
+//! Tiny HTTP endpoint serving JSON snapshots of the robot on the local network, so dashboards
+//! (Grafana, Home Assistant, ...) can scrape a device without a gRPC client. Configured through
+//! a service of type `json_endpoint` in the robot's config:
+//!
+//! ```json
+//! {
+//!     "name": "dashboard",
+//!     "type": "json_endpoint",
+//!     "attributes": {
+//!         "port": 8080,
+//!         "metrics": true,
+//!         "controls": false
+//!     }
+//! }
+//! ```
+//!
+//! `GET /readings` returns the readings of every sensor, movement sensor and power sensor
+//! grouped by component type, a component failing to produce readings reports an `error`
+//! instead:
+//!
+//! ```json
+//! {"sensor": {"thermo": {"temperature": 21.5}}, "power_sensor": {"ina": {"error": "..."}}}
+//! ```
+//!
+//! `GET /status` returns the status of every component in the same layout. When `metrics` is
+//! set, `GET /metrics` returns the [instrumentation](super::instrumentation) counters and gauges
+//! in the Prometheus text format.
+//!
+//! When API keys are configured (see [authorization](super::authorization)) every route needs
+//! viewer credentials, sent as an `Authorization: Bearer <key or access token>` header; requests
+//! without them are answered with `401 Unauthorized`. The endpoint is plain HTTP/1.1 on its own
+//! port (8080 by default), the credentials travel in clear: only enable it on trusted networks.
+//! Requests are served one at a time and connections are closed after each response.
+//!
+//! With the `debug-ui` feature the endpoint also serves the [debug UI](super::debug_ui) at `/`,
+//! its controls are only served when `controls` is set and API keys are configured.
+
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_io::{Async, Timer};
+use futures_lite::{AsyncReadExt, AsyncWriteExt, FutureExt};
+use serde_json::{json, Map, Value as JsonValue};
+use thiserror::Error;
+
+use crate::google::protobuf::{value::Kind as ProtoKind, Value};
+use crate::proto::app::v1::ConfigResponse;
+use crate::proto::common::v1::ResourceName;
+use crate::proto::robot::v1::GetStatusRequest;
+
+use super::authorization::{check_scope, AuthError, AuthPolicy, Scope};
+use super::config::{AttributeError, Kind};
+#[cfg(feature = "debug-ui")]
+use super::debug_ui;
+use super::instrumentation::prometheus_metrics;
+use super::robot::LocalRobot;
+use super::struct_builder::value_to_json;
+
+pub static SERVICE_TYPE: &str = "json_endpoint";
+
+const DEFAULT_PORT: u16 = 8080;
+// requests are a request line and a few headers, anything longer isn't a dashboard
+const MAX_REQUEST_LEN: usize = 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Debug, Error)]
+pub enum JsonEndpointError {
+    #[error("json endpoint config error: {0}")]
+    ConfigError(&'static str),
+    #[error(transparent)]
+    ConfigAttributeError(#[from] AttributeError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonEndpointConfig {
+    pub port: u16,
+    /// Whether `/metrics` is served
+    pub metrics: bool,
+    /// Whether the controls of the debug UI are served
+    pub controls: bool,
+}
+
+impl JsonEndpointConfig {
+    /// Returns the endpoint configuration if the service is configured
+    pub fn from_config(cfg: &ConfigResponse) -> Result<Option<Self>, JsonEndpointError> {
+        let robot_config = cfg
+            .config
+            .as_ref()
+            .ok_or(JsonEndpointError::ConfigError("missing robot config"))?;
+        let mut services = robot_config
+            .services
+            .iter()
+            .filter(|svc_cfg| svc_cfg.r#type == SERVICE_TYPE);
+        let svc_cfg = match services.next() {
+            Some(svc_cfg) => svc_cfg,
+            None => return Ok(None),
+        };
+        if services.next().is_some() {
+            return Err(JsonEndpointError::ConfigError(
+                "multiple json endpoints configured",
+            ));
+        }
+        let attributes = Kind::try_from(ProtoKind::StructValue(
+            svc_cfg.attributes.clone().unwrap_or_default(),
+        ))?;
+        let port = attributes
+            .get("port")?
+            .map(u16::try_from)
+            .transpose()?
+            .unwrap_or(DEFAULT_PORT);
+        let metrics = attributes
+            .get("metrics")?
+            .map(bool::try_from)
+            .transpose()?
+            .unwrap_or(false);
+        let controls = attributes
+            .get("controls")?
+            .map(bool::try_from)
+            .transpose()?
+            .unwrap_or(false);
+        Ok(Some(Self {
+            port,
+            metrics,
+            controls,
+        }))
+    }
+}
+
+fn insert(snapshot: &mut Map<String, JsonValue>, name: &ResourceName, value: JsonValue) {
+    if let JsonValue::Object(components) = snapshot
+        .entry(name.subtype.clone())
+        .or_insert_with(|| JsonValue::Object(Map::new()))
+    {
+        components.insert(name.name.clone(), value);
+    }
+}
+
+fn fields_to_json<'a>(fields: impl Iterator<Item = (&'a String, &'a Value)>) -> JsonValue {
+    JsonValue::Object(fields.map(|(k, v)| (k.clone(), value_to_json(v))).collect())
+}
+
+/// Readings of every component reporting some, grouped by component type
+pub fn readings_json(robot: &LocalRobot) -> JsonValue {
+    let mut snapshot = Map::new();
+    for (name, readings) in robot.get_all_readings() {
+        let value = match readings {
+            Ok(readings) => fields_to_json(readings.iter()),
+            Err(err) => json!({ "error": err.to_string() }),
+        };
+        insert(&mut snapshot, &name, value);
+    }
+    JsonValue::Object(snapshot)
+}
+
+/// Status of every component, grouped by component type
+pub fn status_json(robot: &LocalRobot) -> JsonValue {
+    let statuses = match robot.get_status(GetStatusRequest::default()) {
+        Ok(statuses) => statuses,
+        Err(err) => return json!({ "error": err.to_string() }),
+    };
+    let mut snapshot = Map::new();
+    for status in statuses {
+        if let Some(name) = status.name.as_ref() {
+            let value = status
+                .status
+                .as_ref()
+                .map(|status| fields_to_json(status.fields.iter()))
+                .unwrap_or(JsonValue::Null);
+            insert(&mut snapshot, name, value);
+        }
+    }
+    JsonValue::Object(snapshot)
+}
+
+/// Request line and headers of an HTTP request
+#[derive(Debug)]
+pub(crate) struct HttpRequest<'a> {
+    pub(crate) method: &'a str,
+    pub(crate) path: &'a str,
+    pub(crate) query: &'a str,
+    headers: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> HttpRequest<'a> {
+    pub(crate) fn parse(request: &'a str) -> Self {
+        let mut lines = request.lines();
+        let mut parts = lines.next().unwrap_or_default().split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let (path, query) = parts
+            .next()
+            .map(|p| p.split_once('?').unwrap_or((p, "")))
+            .unwrap_or_default();
+        let headers = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .collect();
+        Self {
+            method,
+            path,
+            query,
+            headers,
+        }
+    }
+
+    /// Value of the header `name`, compared case insensitively
+    pub(crate) fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Serves `/readings`, `/status` and optionally `/metrics` until the listener fails
+pub struct JsonEndpoint {
+    config: JsonEndpointConfig,
+    robot: Arc<RwLock<LocalRobot>>,
+    auth: Rc<AuthPolicy>,
+}
+
+impl JsonEndpoint {
+    /// `auth` holds the keys of the robot, it should be the policy given to the gRPC server so
+    /// that the access tokens it hands out are accepted here
+    pub fn from_robot_and_config(
+        cfg: &ConfigResponse,
+        robot: Arc<RwLock<LocalRobot>>,
+        auth: Rc<AuthPolicy>,
+    ) -> Result<Option<Self>, JsonEndpointError> {
+        let config = JsonEndpointConfig::from_config(cfg)?;
+        if config
+            .as_ref()
+            .is_some_and(|config| config.controls && !auth.is_enabled())
+        {
+            log::warn!("the debug UI controls stay disabled until API keys are configured");
+        }
+        Ok(config.map(|config| Self {
+            config,
+            robot,
+            auth,
+        }))
+    }
+
+    pub async fn run(&self) -> Result<(), JsonEndpointError> {
+        let listener = Async::<TcpListener>::bind(SocketAddr::from((
+            Ipv4Addr::UNSPECIFIED,
+            self.config.port,
+        )))?;
+        log::info!("json endpoint listening on port {}", self.config.port);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let timeout = async {
+                Timer::after(REQUEST_TIMEOUT).await;
+                Err::<(), _>(std::io::ErrorKind::TimedOut.into())
+            };
+            if let Err(err) = self.serve(stream).or(timeout).await {
+                log::debug!("json endpoint request failed: {:?}", err);
+            }
+        }
+    }
+
+    async fn serve(&self, mut stream: Async<TcpStream>) -> std::io::Result<()> {
+        let mut request = Vec::with_capacity(256);
+        let mut buffer = [0_u8; 128];
+        while !request.ends_with(b"\r\n\r\n") && request.len() < MAX_REQUEST_LEN {
+            let len = stream.read(&mut buffer).await?;
+            if len == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..len]);
+        }
+        let request = String::from_utf8_lossy(&request);
+        let response = self.respond(&request);
+        stream.write_all(response.as_bytes()).await?;
+        stream.flush().await
+    }
+
+    /// Scope of the client sending `request`, every client is an operator without keys
+    fn scope(&self, request: &HttpRequest) -> Option<Scope> {
+        if !self.auth.is_enabled() {
+            return Some(Scope::Operator);
+        }
+        request
+            .header("authorization")
+            .and_then(|authorization| self.auth.http_scope(authorization))
+    }
+
+    fn respond(&self, request: &str) -> String {
+        let request = HttpRequest::parse(request);
+        let scope = self.scope(&request);
+        #[cfg(feature = "debug-ui")]
+        if let Some((status, content_type, body)) = debug_ui::respond(
+            &self.robot,
+            &request,
+            scope,
+            self.config.controls && self.auth.is_enabled(),
+        ) {
+            return http_response(status, content_type, &body);
+        }
+        let required = match (request.method, request.path) {
+            ("GET", "/readings" | "/status") => Some(Scope::Viewer),
+            ("GET", "/metrics") if self.config.metrics => Some(Scope::Viewer),
+            _ => None,
+        };
+        if let Some(Err(err)) = required.map(|required| check_scope(scope, required)) {
+            let (status, body) = auth_error_response(&err);
+            return http_response(status, "application/json", &body.to_string());
+        }
+        let (status, body) = match (request.method, request.path) {
+            ("GET", "/metrics") if self.config.metrics => {
+                return http_response("200 OK", METRICS_CONTENT_TYPE, &prometheus_metrics());
+            }
+            ("GET", "/readings") => ("200 OK", readings_json(&self.robot.read().unwrap())),
+            ("GET", "/status") => ("200 OK", status_json(&self.robot.read().unwrap())),
+            ("GET", _) => ("404 Not Found", json!({ "error": "not found" })),
+            _ => (
+                "405 Method Not Allowed",
+                json!({ "error": "method not allowed" }),
+            ),
+        };
+        http_response(status, "application/json", &body.to_string())
+    }
+}
+
+/// Status line and body answering a request refused for `err`
+pub(crate) fn auth_error_response(err: &AuthError) -> (&'static str, JsonValue) {
+    let status = match err {
+        AuthError::InsufficientScope => "403 Forbidden",
+        _ => "401 Unauthorized",
+    };
+    (status, json!({ "error": err.to_string() }))
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    let challenge = if status.starts_with("401") {
+        "WWW-Authenticate: Bearer\r\n"
+    } else {
+        ""
+    };
+    format!(
+        "HTTP/1.1 {}\r\n{}Content-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        challenge,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::sync::{Arc, RwLock};
+
+    use super::{readings_json, HttpRequest, JsonEndpoint, JsonEndpointConfig};
+    use crate::common::authorization::AuthPolicy;
+    use crate::common::robot::LocalRobot;
+    use crate::google::protobuf::{value::Kind, Struct, Value};
+    use crate::proto::app::v1::{ComponentConfig, ConfigResponse, RobotConfig, ServiceConfig};
+
+    fn robot_config() -> ConfigResponse {
+        let sensor = ComponentConfig {
+            name: "thermo".to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: "sensor".to_string(),
+            namespace: "rdk".to_string(),
+            ..Default::default()
+        };
+        let endpoint = ServiceConfig {
+            name: "dashboard".to_string(),
+            r#type: "json_endpoint".to_string(),
+            attributes: Some(Struct {
+                fields: HashMap::from([(
+                    "port".to_string(),
+                    Value {
+                        kind: Some(Kind::NumberValue(9090.0)),
+                    },
+                )]),
+            }),
+            ..Default::default()
+        };
+        ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![sensor],
+                services: vec![endpoint],
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test_log::test]
+    fn test_json_endpoint() {
+        let cfg = robot_config();
+        assert_eq!(
+            JsonEndpointConfig::from_config(&cfg).unwrap(),
+            Some(JsonEndpointConfig {
+                port: 9090,
+                metrics: false,
+                controls: false,
+            })
+        );
+        assert!(JsonEndpointConfig::from_config(&ConfigResponse {
+            config: Some(RobotConfig::default())
+        })
+        .unwrap()
+        .is_none());
+
+        let robot = LocalRobot::from_cloud_config(&cfg, Box::default(), None).unwrap();
+        let readings = readings_json(&robot);
+        assert!(readings["sensor"]["thermo"].is_object());
+
+        let endpoint = JsonEndpoint::from_robot_and_config(
+            &cfg,
+            Arc::new(RwLock::new(robot)),
+            Rc::new(AuthPolicy::default()),
+        )
+        .unwrap()
+        .unwrap();
+        let response = endpoint.respond("GET /readings?pretty HTTP/1.1");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body, readings_json(&endpoint.robot.read().unwrap()));
+
+        assert!(endpoint
+            .respond("GET /status HTTP/1.1")
+            .starts_with("HTTP/1.1 200 OK"));
+        assert!(endpoint
+            .respond("GET /metrics HTTP/1.1")
+            .starts_with("HTTP/1.1 404"));
+        assert!(endpoint
+            .respond("POST /readings HTTP/1.1")
+            .starts_with("HTTP/1.1 405"));
+
+        let endpoint = JsonEndpoint {
+            config: JsonEndpointConfig {
+                port: 9090,
+                metrics: true,
+                controls: false,
+            },
+            robot: endpoint.robot.clone(),
+            auth: endpoint.auth.clone(),
+        };
+        let response = endpoint.respond("GET /metrics HTTP/1.1");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+        assert!(response.contains("# TYPE micro_rdk_app_reconnects_total counter\n"));
+    }
+
+    #[test_log::test]
+    fn test_json_endpoint_auth() {
+        let cfg = robot_config();
+        let robot = LocalRobot::from_cloud_config(&cfg, Box::default(), None).unwrap();
+        let auth = Rc::new(AuthPolicy::new(
+            vec!["op".to_owned()],
+            vec!["view".to_owned()],
+        ));
+        let endpoint = JsonEndpoint {
+            config: JsonEndpointConfig {
+                port: 9090,
+                metrics: true,
+                controls: false,
+            },
+            robot: Arc::new(RwLock::new(robot)),
+            auth: auth.clone(),
+        };
+
+        for path in ["/readings", "/status", "/metrics"] {
+            let response = endpoint.respond(&format!("GET {} HTTP/1.1\r\n\r\n", path));
+            assert!(
+                response.starts_with("HTTP/1.1 401 Unauthorized\r\n"),
+                "{}",
+                path
+            );
+            assert!(response.contains("WWW-Authenticate: Bearer\r\n"));
+            let response = endpoint.respond(&format!(
+                "GET {} HTTP/1.1\r\nAuthorization: Bearer nope\r\n\r\n",
+                path
+            ));
+            assert!(
+                response.starts_with("HTTP/1.1 401 Unauthorized\r\n"),
+                "{}",
+                path
+            );
+            let response = endpoint.respond(&format!(
+                "GET {} HTTP/1.1\r\nHost: robot\r\nauthorization: Bearer view\r\n\r\n",
+                path
+            ));
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", path);
+        }
+
+        // access tokens handed out by the gRPC server are accepted too
+        let (token, _) = auth
+            .authenticate(Some(&crate::proto::rpc::v1::Credentials {
+                r#type: "api-key".to_owned(),
+                payload: "op".to_owned(),
+            }))
+            .unwrap();
+        let response = endpoint.respond(&format!(
+            "GET /status HTTP/1.1\r\nAuthorization: Bearer {}\r\n\r\n",
+            token
+        ));
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test_log::test]
+    fn test_http_request() {
+        let request = HttpRequest::parse(
+            "POST /motor/m/power?value=0.5 HTTP/1.1\r\nHost: robot.local\r\nX-Requested-With:fetch\r\n\r\nbody: ignored",
+        );
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/motor/m/power");
+        assert_eq!(request.query, "value=0.5");
+        assert_eq!(request.header("host"), Some("robot.local"));
+        assert_eq!(request.header("x-requested-with"), Some("fetch"));
+        assert_eq!(request.header("body"), None);
+        assert_eq!(HttpRequest::parse("").method, "");
+    }
+}
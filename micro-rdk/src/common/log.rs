@@ -1,12 +1,72 @@
 use crate::{
     google::protobuf::{value::Kind, Struct, Timestamp, Value},
-    proto::common::v1::LogEntry,
+    proto::{app::v1::ConfigResponse, common::v1::LogEntry},
 };
+use async_channel::{Receiver, Sender};
 use chrono::{DateTime, FixedOffset};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
 
 use super::robot::RobotError;
 
+#[cfg(feature = "esp32")]
+use crate::esp32::exec::Esp32Executor;
+#[cfg(feature = "native")]
+use crate::native::exec::NativeExecutor;
+
+#[cfg(feature = "native")]
+type Executor = NativeExecutor;
+#[cfg(feature = "esp32")]
+type Executor = Esp32Executor;
+
+/// Host reported by the log entries until the robot config names it
+const DEFAULT_LOG_HOST: &str = "micro-rdk";
+
+/// Name of the robot part reported as the host of its log entries
+static LOG_HOST: Mutex<String> = Mutex::new(String::new());
+
+/// Sets the host reported by the log entries, the name of the robot part (also its mDNS hostname)
+pub fn set_log_host(host: &str) {
+    let mut log_host = LOG_HOST.lock().unwrap();
+    log_host.clear();
+    log_host.push_str(host);
+}
+
+fn log_host() -> String {
+    let host = LOG_HOST.lock().unwrap();
+    if host.is_empty() {
+        DEFAULT_LOG_HOST.to_string()
+    } else {
+        host.clone()
+    }
+}
+
+/// Log level requested by the robot config, the `debug` flag of the robot (set in app) enables
+/// debug logs
+pub fn log_level_from_config(cfg: &ConfigResponse) -> log::LevelFilter {
+    match cfg.config.as_ref().and_then(|cfg| cfg.debug) {
+        Some(true) => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Info,
+    }
+}
+
+/// Applies the log level of the robot config to the logger, unless the level was chosen locally
+/// with the `RUST_LOG` environment variable
+pub fn apply_log_level(cfg: &ConfigResponse) {
+    if std::env::var_os("RUST_LOG").is_some() {
+        return;
+    }
+    let level = log_level_from_config(cfg);
+    if level != log::max_level() {
+        log::info!("setting log level to {}", level);
+        log::set_max_level(level);
+    }
+}
+
 pub fn config_log_entry(time: DateTime<FixedOffset>, err: Option<RobotError>) -> LogEntry {
     let secs = time.timestamp();
     let nanos = time.timestamp_subsec_nanos();
@@ -19,7 +79,7 @@ pub fn config_log_entry(time: DateTime<FixedOffset>, err: Option<RobotError>) ->
         None => "successfully created robot from config".to_string(),
     };
     LogEntry {
-        host: "esp32".to_string(),
+        host: log_host(),
         level,
         time: Some(Timestamp {
             seconds: secs,
@@ -39,3 +99,288 @@ pub fn config_log_entry(time: DateTime<FixedOffset>, err: Option<RobotError>) ->
         fields: vec![],
     }
 }
+
+/// Number of recent log entries kept in memory to be tailed by clients
+pub const LOG_BUFFER_CAPACITY: usize = 64;
+
+/// Recent log entries captured by [BufferedLogger], tailed through the TailRobotPartLogs RPC
+pub static LOG_BUFFER: LogBuffer = LogBuffer::new(LOG_BUFFER_CAPACITY);
+
+struct LogBufferEntries {
+    // sequence number of the oldest entry kept
+    first_seq: u64,
+    entries: VecDeque<LogEntry>,
+}
+
+/// Ring buffer of the most recent log entries. Entries are numbered in the order they were
+/// logged so readers can resume where they left off, the oldest entries being dropped once
+/// the buffer is full.
+pub struct LogBuffer {
+    capacity: usize,
+    inner: Mutex<LogBufferEntries>,
+}
+
+impl LogBuffer {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(LogBufferEntries {
+                first_seq: 0,
+                entries: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn push(&self, entry: LogEntry) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.len() >= self.capacity {
+            inner.entries.pop_front();
+            inner.first_seq += 1;
+        }
+        inner.entries.push_back(entry);
+    }
+
+    /// Returns at most `max` entries starting at sequence number `seq` along with the sequence
+    /// number to read from next. Entries dropped from the buffer before being read are skipped.
+    pub fn read_from(&self, seq: u64, max: usize) -> (Vec<LogEntry>, u64) {
+        let inner = self.inner.lock().unwrap();
+        let start = seq.max(inner.first_seq);
+        let entries: Vec<LogEntry> = inner
+            .entries
+            .iter()
+            .skip((start - inner.first_seq) as usize)
+            .take(max)
+            .cloned()
+            .collect();
+        let next = start + entries.len() as u64;
+        (entries, next)
+    }
+}
+
+/// Lines waiting to be written to the log files set with [log_to_files], logging never waits
+/// for the card: lines are dropped while the queue is full
+static LOG_LINES: Mutex<Option<Sender<String>>> = Mutex::new(None);
+const LOG_LINES_CAPACITY: usize = 64;
+
+/// Log records appended to `LOG0.TXT` in a directory, typically on an SD card. Once the file
+/// reaches `max_size` bytes it's renamed `LOG1.TXT`, `LOG1.TXT` becomes `LOG2.TXT` and so on,
+/// only the `max_files` most recent files are kept.
+pub struct RotatingLogFiles {
+    directory: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    len: u64,
+}
+
+impl RotatingLogFiles {
+    pub fn new(directory: PathBuf, max_size: u64, max_files: usize) -> std::io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+        let path = directory.join("LOG0.TXT");
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            directory,
+            max_size,
+            max_files: max_files.max(1),
+            file,
+            len,
+        })
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        self.directory.join(format!("LOG{}.TXT", index))
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let _ = fs::remove_file(self.path(self.max_files - 1));
+        for index in (0..self.max_files - 1).rev() {
+            let _ = fs::rename(self.path(index), self.path(index + 1));
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(0))?;
+        self.len = 0;
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.len > 0 && self.len + line.len() as u64 + 1 > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.len += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// Makes [BufferedLogger] write every record it logs to `files` as well, the lines are written
+/// by a task of the executor of the calling thread
+pub fn log_to_files(files: RotatingLogFiles) {
+    let (lines, queue) = async_channel::bounded(LOG_LINES_CAPACITY);
+    // replacing the sender ends the task writing to the previous files
+    let _ = LOG_LINES.lock().unwrap().insert(lines);
+    Executor::new()
+        .spawn(write_log_files(files, queue))
+        .detach();
+}
+
+async fn write_log_files(mut files: RotatingLogFiles, queue: Receiver<String>) {
+    while let Ok(line) = queue.recv().await {
+        // a failing card can't be reported through the logger writing to it
+        let _ = files.write_line(&line);
+    }
+}
+
+fn log_entry_from_record(record: &log::Record) -> LogEntry {
+    let time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    LogEntry {
+        host: log_host(),
+        level: record.level().as_str().to_lowercase(),
+        time: Some(Timestamp {
+            seconds: time.as_secs() as i64,
+            nanos: time.subsec_nanos() as i32,
+        }),
+        logger_name: record.target().to_string(),
+        message: record.args().to_string(),
+        caller: None,
+        stack: "".to_string(),
+        fields: vec![],
+    }
+}
+
+/// Installs the ESP-IDF logger, at the level set by the sdkconfig, wrapped in a [BufferedLogger]
+#[cfg(feature = "esp32")]
+pub fn init_esp32_logger() -> Result<(), log::SetLoggerError> {
+    let logger = crate::esp32::esp_idf_svc::log::EspLogger::new();
+    let level = logger.get_max_level();
+    BufferedLogger::new(logger).init(level)
+}
+
+/// Logger copying the records enabled by the logger it wraps into [LOG_BUFFER], so they can
+/// be tailed remotely, and into the log files set with [log_to_files]
+pub struct BufferedLogger<L> {
+    inner: L,
+}
+
+impl<L> BufferedLogger<L>
+where
+    L: log::Log + 'static,
+{
+    pub fn new(inner: L) -> Self {
+        Self { inner }
+    }
+
+    /// Installs the logger as the global logger, logging up to `level`
+    pub fn init(self, level: log::LevelFilter) -> Result<(), log::SetLoggerError> {
+        log::set_logger(Box::leak(Box::new(self)))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<L> log::Log for BufferedLogger<L>
+where
+    L: log::Log,
+{
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.inner.enabled(record.metadata()) {
+            let entry = log_entry_from_record(record);
+            if let Some(lines) = LOG_LINES.lock().unwrap().as_ref() {
+                let seconds = entry.time.as_ref().map_or(0, |time| time.seconds);
+                let _ = lines.try_send(format!(
+                    "{} {} {}: {}",
+                    seconds, entry.level, entry.logger_name, entry.message
+                ));
+            }
+            LOG_BUFFER.push(entry);
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::app::v1::RobotConfig;
+
+    #[test_log::test]
+    fn test_log_buffer() {
+        let buffer = LogBuffer::new(3);
+        let entry = |message: &str| LogEntry {
+            message: message.to_string(),
+            ..Default::default()
+        };
+        let messages = |entries: Vec<LogEntry>| {
+            entries
+                .into_iter()
+                .map(|entry| entry.message)
+                .collect::<Vec<_>>()
+        };
+
+        let (entries, next) = buffer.read_from(0, 10);
+        assert!(entries.is_empty());
+        assert_eq!(next, 0);
+
+        buffer.push(entry("a"));
+        buffer.push(entry("b"));
+        let (entries, next) = buffer.read_from(0, 1);
+        assert_eq!(messages(entries), ["a"]);
+        let (entries, next) = buffer.read_from(next, 10);
+        assert_eq!(messages(entries), ["b"]);
+        assert_eq!(next, 2);
+
+        // "a" and "b" are dropped, a reader that fell behind resumes at the oldest entry
+        buffer.push(entry("c"));
+        buffer.push(entry("d"));
+        buffer.push(entry("e"));
+        let (entries, next) = buffer.read_from(1, 10);
+        assert_eq!(messages(entries), ["c", "d", "e"]);
+        assert_eq!(next, 5);
+        assert!(buffer.read_from(next, 10).0.is_empty());
+    }
+
+    #[test_log::test]
+    fn test_rotating_log_files() {
+        let directory = std::env::temp_dir().join(format!("log-files-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let mut files = RotatingLogFiles::new(directory.clone(), 16, 2).unwrap();
+        files.write_line("first line").unwrap();
+        files.write_line("second line").unwrap();
+        files.write_line("third line").unwrap();
+        assert_eq!(
+            fs::read_to_string(directory.join("LOG0.TXT")).unwrap(),
+            "third line\n"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("LOG1.TXT")).unwrap(),
+            "second line\n"
+        );
+        // only the 2 most recent files are kept
+        assert!(!directory.join("LOG2.TXT").exists());
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test_log::test]
+    fn test_log_level_from_config() {
+        let mut cfg = ConfigResponse {
+            config: Some(RobotConfig::default()),
+        };
+        assert_eq!(log_level_from_config(&cfg), log::LevelFilter::Info);
+        cfg.config.as_mut().unwrap().debug = Some(true);
+        assert_eq!(log_level_from_config(&cfg), log::LevelFilter::Debug);
+        cfg.config.as_mut().unwrap().debug = Some(false);
+        assert_eq!(log_level_from_config(&cfg), log::LevelFilter::Info);
+    }
+}
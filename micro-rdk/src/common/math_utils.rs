@@ -1,11 +1,10 @@
 #![allow(dead_code)]
-use crate::{
-    google::protobuf::{value::Kind, Struct, Value},
-    proto::common,
-};
-use std::{collections::HashMap, time::Duration};
+use crate::{google::protobuf::Value, proto::common};
+use std::time::Duration;
 use thiserror::Error;
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
+use crate::common::struct_builder::StructBuilder;
 #[cfg(feature = "data")]
 use crate::proto::app::data_sync::v1::sensor_data::Data;
 
@@ -13,6 +12,12 @@ use crate::proto::app::data_sync::v1::sensor_data::Data;
 #[error("invalid argument")]
 pub struct UtilsInvalidArg;
 
+impl GrpcStatusHint for UtilsInvalidArg {
+    fn grpc_error(&self) -> GrpcError {
+        GrpcError::RpcInvalidArgument
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Vector3 {
     pub x: f64,
@@ -30,36 +35,11 @@ impl Vector3 {
     }
     #[cfg(feature = "data")]
     pub fn to_data_struct(self, key: &str) -> Data {
-        let data_struct = Struct {
-            fields: HashMap::from([
-                (
-                    "x".to_string(),
-                    Value {
-                        kind: Some(Kind::NumberValue(self.x)),
-                    },
-                ),
-                (
-                    "y".to_string(),
-                    Value {
-                        kind: Some(Kind::NumberValue(self.y)),
-                    },
-                ),
-                (
-                    "z".to_string(),
-                    Value {
-                        kind: Some(Kind::NumberValue(self.z)),
-                    },
-                ),
-            ]),
-        };
-        Data::Struct(Struct {
-            fields: HashMap::from([(
-                key.to_string(),
-                Value {
-                    kind: Some(Kind::StructValue(data_struct)),
-                },
-            )]),
-        })
+        Data::Struct(
+            StructBuilder::with_capacity(1)
+                .value(key, self.into())
+                .build(),
+        )
     }
 }
 
@@ -75,29 +55,11 @@ impl From<Vector3> for common::v1::Vector3 {
 
 impl From<Vector3> for Value {
     fn from(value: Vector3) -> Self {
-        let fields = HashMap::from([
-            (
-                "x".to_string(),
-                Value {
-                    kind: Some(Kind::NumberValue(value.x)),
-                },
-            ),
-            (
-                "y".to_string(),
-                Value {
-                    kind: Some(Kind::NumberValue(value.y)),
-                },
-            ),
-            (
-                "z".to_string(),
-                Value {
-                    kind: Some(Kind::NumberValue(value.z)),
-                },
-            ),
-        ]);
-        Self {
-            kind: Some(Kind::StructValue(Struct { fields })),
-        }
+        StructBuilder::with_capacity(3)
+            .field("x", value.x)
+            .field("y", value.y)
+            .field("z", value.z)
+            .into()
     }
 }
 
@@ -0,0 +1,311 @@
+//! Package mcp23017 implements a driver for the Microchip MCP23017 16-bit I/O expander over
+//! I2C. A datasheet for this chip is at
+//! https://ww1.microchip.com/downloads/en/devicedoc/20001952c.pdf
+//!
+//! The expander is attached to the board by adding it to the `gpio_expanders` attribute of
+//! the board's config:
+//!
+//! ```json
+//! "gpio_expanders": [{
+//!     "name": "mcp", "model": "mcp23017", "i2c_bus": "i2c0", "i2c_address": 32,
+//!     "interrupt_pin": 4, "interrupt_pins": [0, 8]
+//! }]
+//! ```
+//!
+//! Pins GPA0-GPA7 are addressed as `"mcp:0"` to `"mcp:7"` and GPB0-GPB7 as `"mcp:8"` to
+//! `"mcp:15"`. Every pin is an input until it is set to a level, at which point it becomes an
+//! output.
+//!
+//! Pins listed in `interrupt_pins` raise an interrupt on every change of their level and are
+//! reported as digital interrupts of the board. When `interrupt_pin` is set, it should be a
+//! pin of the board wired to the INTA or INTB output of the expander and configured as a
+//! digital interrupt of the board: the expander's registers are then only read when this pin
+//! has registered an event. Otherwise the interrupt flags are read every time an interrupt
+//! count is requested. In both cases only the changes that happened since the previous
+//! request are counted, so interrupts should be polled at least as often as they occur.
+
+use crate::common::i2c::I2cHandleType;
+
+use super::board::{Board, BoardError, BoardType};
+use super::config::{AttributeError, Kind};
+use super::gpio_expander::GpioExpander;
+use super::i2c::I2CHandle;
+
+const DEFAULT_I2C_ADDRESS: u8 = 0x20;
+const NUM_PINS: u16 = 16;
+
+// register addresses of port A with IOCON.BANK = 0, the register of port B
+// immediately follows
+const IODIRA_REGISTER: u8 = 0x00;
+const GPINTENA_REGISTER: u8 = 0x04;
+const INTCONA_REGISTER: u8 = 0x08;
+const IOCON_REGISTER: u8 = 0x0A;
+const INTFA_REGISTER: u8 = 0x0E;
+const INTCAPA_REGISTER: u8 = 0x10;
+const GPIOA_REGISTER: u8 = 0x12;
+const OLATA_REGISTER: u8 = 0x14;
+
+// INTA and INTB are internally connected
+const IOCON_MIRROR: u8 = 0x40;
+
+/// Parses a `mcp23017` element of the `gpio_expanders` attribute of a board config
+#[derive(Debug)]
+pub(crate) struct Mcp23017Config {
+    pub(crate) i2c_bus: String,
+    pub(crate) i2c_address: u8,
+    pub(crate) interrupt_pin: Option<i32>,
+    pub(crate) interrupt_pins: Vec<u16>,
+}
+
+impl TryFrom<&Kind> for Mcp23017Config {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let i2c_bus = value
+            .get("i2c_bus")?
+            .ok_or(AttributeError::KeyNotFound("i2c_bus".to_string()))?
+            .try_into()?;
+        let i2c_address = match value.get("i2c_address")? {
+            Some(val) => val.try_into()?,
+            None => DEFAULT_I2C_ADDRESS,
+        };
+        let interrupt_pin = match value.get("interrupt_pin")? {
+            Some(val) => Some(val.try_into()?),
+            None => None,
+        };
+        let interrupt_pins = match value.get("interrupt_pins")? {
+            Some(val) => val.try_into()?,
+            None => vec![],
+        };
+        Ok(Mcp23017Config {
+            i2c_bus,
+            i2c_address,
+            interrupt_pin,
+            interrupt_pins,
+        })
+    }
+}
+
+pub struct Mcp23017 {
+    i2c_handle: I2cHandleType,
+    i2c_address: u8,
+    // a bit set to 1 configures the pin as an input
+    direction: u16,
+    output_latch: u16,
+    interrupt_mask: u16,
+    interrupt_counts: [u32; NUM_PINS as usize],
+    // board pin wired to the INT output of the expander, with the last event count seen
+    interrupt_line: Option<(BoardType, i32)>,
+    last_line_count: u32,
+}
+
+impl Mcp23017 {
+    pub fn new(
+        i2c_handle: I2cHandleType,
+        i2c_address: u8,
+        interrupt_pins: &[u16],
+        interrupt_line: Option<(BoardType, i32)>,
+    ) -> Result<Self, BoardError> {
+        let mut interrupt_mask = 0_u16;
+        for pin in interrupt_pins {
+            if *pin >= NUM_PINS {
+                return Err(BoardError::BoardUnsupportedArgument(
+                    "mcp23017 invalid interrupt pin",
+                ));
+            }
+            interrupt_mask |= 1 << pin;
+        }
+        let mut expander = Mcp23017 {
+            i2c_handle,
+            i2c_address,
+            direction: 0xFFFF,
+            output_latch: 0,
+            interrupt_mask,
+            interrupt_counts: [0; NUM_PINS as usize],
+            interrupt_line,
+            last_line_count: 0,
+        };
+        expander.write_register(IOCON_REGISTER, &[IOCON_MIRROR])?;
+        expander.write_pair(IODIRA_REGISTER, expander.direction)?;
+        expander.write_pair(OLATA_REGISTER, expander.output_latch)?;
+        // interrupt on change from the previous pin value
+        expander.write_pair(INTCONA_REGISTER, 0)?;
+        expander.write_pair(GPINTENA_REGISTER, interrupt_mask)?;
+        if let Some((board, pin)) = &expander.interrupt_line {
+            expander.last_line_count = board.get_digital_interrupt_value(*pin)?;
+        }
+        // reading the captured values clears any pending interrupt
+        let _ = expander.read_pair(INTCAPA_REGISTER)?;
+        Ok(expander)
+    }
+
+    fn write_register(&mut self, register: u8, values: &[u8]) -> Result<(), BoardError> {
+        let mut bytes = vec![register];
+        bytes.extend_from_slice(values);
+        self.i2c_handle.write_i2c(self.i2c_address, &bytes)?;
+        Ok(())
+    }
+
+    fn write_pair(&mut self, register_a: u8, value: u16) -> Result<(), BoardError> {
+        self.write_register(register_a, &value.to_le_bytes())
+    }
+
+    fn read_pair(&mut self, register_a: u8) -> Result<u16, BoardError> {
+        let mut bytes = [0_u8; 2];
+        self.i2c_handle
+            .write_read_i2c(self.i2c_address, &[register_a], &mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn check_pin(pin: u16) -> Result<(), BoardError> {
+        if pin >= NUM_PINS {
+            return Err(BoardError::BoardUnsupportedArgument("mcp23017 invalid pin"));
+        }
+        Ok(())
+    }
+
+    // updates the interrupt counts with the pins flagged by the expander
+    fn poll_interrupts(&mut self) -> Result<(), BoardError> {
+        if let Some((board, pin)) = &self.interrupt_line {
+            let count = board.get_digital_interrupt_value(*pin)?;
+            if count == self.last_line_count {
+                return Ok(());
+            }
+            self.last_line_count = count;
+        }
+        let flags = self.read_pair(INTFA_REGISTER)? & self.interrupt_mask;
+        if flags == 0 {
+            return Ok(());
+        }
+        let _ = self.read_pair(INTCAPA_REGISTER)?;
+        for (pin, count) in self.interrupt_counts.iter_mut().enumerate() {
+            if flags & (1 << pin) != 0 {
+                *count += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GpioExpander for Mcp23017 {
+    fn num_pins(&self) -> u16 {
+        NUM_PINS
+    }
+
+    fn set_gpio_pin_level(&mut self, pin: u16, is_high: bool) -> Result<(), BoardError> {
+        Self::check_pin(pin)?;
+        let output_latch = if is_high {
+            self.output_latch | (1 << pin)
+        } else {
+            self.output_latch & !(1 << pin)
+        };
+        self.write_pair(OLATA_REGISTER, output_latch)?;
+        self.output_latch = output_latch;
+        if self.direction & (1 << pin) != 0 {
+            let direction = self.direction & !(1 << pin);
+            self.write_pair(IODIRA_REGISTER, direction)?;
+            self.direction = direction;
+        }
+        Ok(())
+    }
+
+    fn get_gpio_level(&mut self, pin: u16) -> Result<bool, BoardError> {
+        Self::check_pin(pin)?;
+        Ok(self.read_pair(GPIOA_REGISTER)? & (1 << pin) != 0)
+    }
+
+    fn interrupt_pins(&self) -> Vec<u16> {
+        (0..NUM_PINS)
+            .filter(|pin| self.interrupt_mask & (1 << pin) != 0)
+            .collect()
+    }
+
+    fn get_digital_interrupt_value(&mut self, pin: u16) -> Result<u32, BoardError> {
+        Self::check_pin(pin)?;
+        if self.interrupt_mask & (1 << pin) == 0 {
+            return Err(BoardError::BoardUnsupportedArgument(
+                "mcp23017 pin is not configured as an interrupt",
+            ));
+        }
+        self.poll_interrupts()?;
+        Ok(self.interrupt_counts[pin as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mcp23017;
+    use crate::common::board::BoardError;
+    use crate::common::gpio_expander::GpioExpander;
+    use crate::common::i2c::{I2CErrors, I2CHandle, I2cHandleType};
+    use std::sync::{Arc, Mutex};
+
+    // emulates the register file of the expander, reading INTCAP clears INTF
+    struct FakeMcp23017I2C {
+        registers: Arc<Mutex<[u8; 0x16]>>,
+    }
+
+    impl I2CHandle for FakeMcp23017I2C {
+        fn name(&self) -> String {
+            "fake_mcp23017".to_string()
+        }
+        fn write_i2c(&mut self, _address: u8, bytes: &[u8]) -> Result<(), I2CErrors> {
+            let mut registers = self.registers.lock().unwrap();
+            let start = bytes[0] as usize;
+            registers[start..start + bytes.len() - 1].copy_from_slice(&bytes[1..]);
+            Ok(())
+        }
+        fn write_read_i2c(
+            &mut self,
+            _address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+        ) -> Result<(), I2CErrors> {
+            let mut registers = self.registers.lock().unwrap();
+            let start = bytes[0] as usize;
+            buffer.copy_from_slice(&registers[start..start + buffer.len()]);
+            if start == 0x10 {
+                registers[0x0E] = 0;
+                registers[0x0F] = 0;
+            }
+            Ok(())
+        }
+    }
+
+    #[test_log::test]
+    fn test_mcp23017() -> Result<(), BoardError> {
+        let registers = Arc::new(Mutex::new([0_u8; 0x16]));
+        let i2c: I2cHandleType = Arc::new(Mutex::new(FakeMcp23017I2C {
+            registers: registers.clone(),
+        }));
+        let mut expander = Mcp23017::new(i2c, 0x20, &[1, 9], None)?;
+        {
+            let registers = registers.lock().unwrap();
+            // every pin starts as an input, with interrupts on pins 1 and 9
+            assert_eq!(registers[0x00..0x02], [0xFF, 0xFF]);
+            assert_eq!(registers[0x04..0x06], [0x02, 0x02]);
+            assert_eq!(registers[0x0A], 0x40);
+        }
+        assert_eq!(expander.interrupt_pins(), vec![1, 9]);
+
+        expander.set_gpio_pin_level(10, true)?;
+        {
+            let registers = registers.lock().unwrap();
+            assert_eq!(registers[0x00..0x02], [0xFF, 0xFB]);
+            assert_eq!(registers[0x14..0x16], [0x00, 0x04]);
+        }
+
+        registers.lock().unwrap()[0x12] = 0x08;
+        assert!(expander.get_gpio_level(3)?);
+        assert!(!expander.get_gpio_level(4)?);
+        assert!(expander.get_gpio_level(16).is_err());
+
+        assert_eq!(expander.get_digital_interrupt_value(9)?, 0);
+        registers.lock().unwrap()[0x0F] = 0x02;
+        assert_eq!(expander.get_digital_interrupt_value(9)?, 1);
+        // flags were cleared by reading the captured values
+        assert_eq!(expander.get_digital_interrupt_value(9)?, 1);
+        assert_eq!(expander.get_digital_interrupt_value(1)?, 0);
+        assert!(expander.get_digital_interrupt_value(2).is_err());
+        Ok(())
+    }
+}
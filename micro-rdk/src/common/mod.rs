@@ -3,65 +3,207 @@
 //!
 //! # Components
 //! - [actuator]
+//! - [audio_input]
 //! - [base]
 //! - [board]
+//! - [button]
 //! - [camera]
 //! - [encoder]
 //! - [motor]
 //! - [movement_sensor]
 //! - [sensor]
 //! - [servo]
+//! - [switch]
 //!
 //! # Utils
+//! - [assets]
+//! - [authorization]
+//! - [automation]
+//! - [build_info]
+//! - [calibration]
+//! - [can]
+//! - [console]
+//! - [debug_ui]
+//! - [frame]
 //! - [grpc]
 //! - [grpc_client]
 //! - [i2c]
+//! - [instrumentation]
+//! - [json_endpoint]
+//! - [mqtt]
+//! - [periodic]
+//! - [power_management]
+//! - [secret]
+//! - [self_test]
+//! - [static_config]
+//! - [struct_builder]
+//! - [uart]
 //! - [webrtc]
 //! - [conn]
 //!
 //!
 //! General Purpose Drivers
 //! - [adxl345]
+//! - [alerts]
+//! - [amg8833]
+//! - [as5600]
+//! - [battery]
+//! - [ble_sensor]
+//! - [calculated]
+//! - [dynamixel]
+//! - [flow_meter]
+//! - [geofence]
+//! - [gpio_button]
+//! - [gpio_expander]
 //! - [gpio_motor]
+//! - [gpio_switch]
+//! - [gps_nmea]
+//! - [gps_ublox]
+//! - [grove]
 //! - [ina]
+//! - [mcp23017]
+//! - [motor_group]
 //! - [mpu6050]
+//! - [ntrip]
+//! - [particulate]
+//! - [pca9685]
+//! - [reference_sensor]
+//! - [rc_receiver]
+//! - [replay]
+//! - [roboclaw]
+//! - [sabertooth]
+//! - [scd]
+//! - [shift_register]
+//! - [sim]
+//! - [telemetry]
+//! - [thermal_protection]
+//! - [vesc_can]
 
 pub mod actuator;
 #[cfg(feature = "builtin-components")]
 pub mod adxl345;
+#[cfg(feature = "builtin-components")]
+pub mod alerts;
+#[cfg(feature = "builtin-components")]
+pub mod amg8833;
 pub mod analog;
 pub mod app_client;
+#[cfg(feature = "builtin-components")]
+pub mod as5600;
+#[cfg(feature = "assets")]
+pub mod assets;
+pub mod audio_input;
+pub mod authorization;
+pub mod automation;
 pub mod base;
+#[cfg(feature = "builtin-components")]
+pub mod battery;
+#[cfg(feature = "builtin-components")]
+pub mod ble_sensor;
 pub mod board;
+pub mod build_info;
+pub mod button;
+#[cfg(feature = "builtin-components")]
+pub mod calculated;
+pub mod calibration;
 pub mod camera;
+pub mod can;
+pub mod clock;
 pub mod config;
+pub mod console;
+#[cfg(feature = "debug-ui")]
+pub mod debug_ui;
 pub mod digital_interrupt;
+#[cfg(feature = "builtin-components")]
+pub mod dynamixel;
 pub mod encoder;
 pub mod entry;
+#[cfg(feature = "builtin-components")]
+pub mod flow_meter;
+pub mod frame;
 pub mod generic;
 #[cfg(feature = "builtin-components")]
+pub mod geofence;
+#[cfg(feature = "builtin-components")]
+pub mod gpio_button;
+#[cfg(feature = "builtin-components")]
+pub mod gpio_expander;
+#[cfg(feature = "builtin-components")]
 pub mod gpio_motor;
 #[cfg(feature = "builtin-components")]
 pub mod gpio_servo;
+#[cfg(feature = "builtin-components")]
+pub mod gpio_switch;
+#[cfg(feature = "builtin-components")]
+pub mod gps_nmea;
+#[cfg(feature = "builtin-components")]
+pub mod gps_ublox;
+#[cfg(feature = "grove")]
+pub mod grove;
 pub mod grpc;
 pub mod grpc_client;
 pub mod i2c;
 #[cfg(feature = "builtin-components")]
 pub mod ina;
+pub mod instrumentation;
+pub mod json_endpoint;
 pub mod log;
 pub mod math_utils;
 #[cfg(feature = "builtin-components")]
+pub mod mcp23017;
+#[cfg(feature = "builtin-components")]
 pub mod moisture_sensor;
 pub mod motor;
+#[cfg(feature = "builtin-components")]
+pub mod motor_group;
 pub mod movement_sensor;
 #[cfg(feature = "builtin-components")]
 pub mod mpu6050;
+#[cfg(feature = "builtin-components")]
+pub mod ntrip;
+pub mod nvs_schema;
+#[cfg(feature = "builtin-components")]
+pub mod particulate;
+#[cfg(feature = "builtin-components")]
+pub mod pca9685;
+pub mod periodic;
+pub mod power_management;
 pub mod power_sensor;
+#[cfg(feature = "builtin-components")]
+pub mod rc_receiver;
+#[cfg(feature = "builtin-components")]
+pub mod reference_sensor;
 pub mod registry;
+#[cfg(feature = "builtin-components")]
+pub mod replay;
+#[cfg(feature = "builtin-components")]
+pub mod roboclaw;
 pub mod robot;
+#[cfg(feature = "builtin-components")]
+pub mod sabertooth;
+#[cfg(feature = "builtin-components")]
+pub mod scd;
+pub mod secret;
+pub mod self_test;
+#[cfg(feature = "builtin-components")]
+pub mod sensirion;
 pub mod sensor;
 pub mod servo;
+#[cfg(feature = "builtin-components")]
+pub mod shift_register;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod static_config;
 pub mod status;
+pub mod struct_builder;
+pub mod switch;
+pub mod telemetry;
+#[cfg(feature = "builtin-components")]
+pub mod thermal_protection;
+pub mod uart;
+#[cfg(feature = "builtin-components")]
+pub mod vesc_can;
+pub mod webhook;
 #[cfg(feature = "builtin-components")]
 pub mod wheeled_base;
 pub mod webrtc {
@@ -73,7 +215,9 @@ pub mod webrtc {
     pub mod grpc;
     pub mod ice;
     pub mod io;
+    pub mod rtp;
     pub mod sctp;
+    pub mod srtp;
     pub mod udp_mux;
 }
 pub mod conn {
@@ -88,6 +232,8 @@ pub mod data_collector;
 pub mod data_manager;
 #[cfg(feature = "data")]
 pub mod data_store;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
 
 #[cfg(feature = "provisioning")]
 pub mod provisioning;
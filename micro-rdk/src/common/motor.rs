@@ -11,23 +11,25 @@ use {
         registry::{ComponentRegistry, Dependency, ResourceKey},
         robot::Resource,
     },
-    crate::common::status::StatusError,
-    crate::google,
+    crate::common::status::{status_envelope, ComponentHealth, StatusError},
     std::collections::HashMap,
 };
 
 use crate::common::status::Status;
+use crate::google;
 use crate::proto::component::motor::v1::GetPropertiesResponse;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use super::actuator::{Actuator, ActuatorError};
-use super::board::BoardError;
+use super::board::{BoardError, BoardPin};
 use super::config::{AttributeError, Kind};
 use super::encoder::EncoderError;
 use super::generic::DoCommand;
 use super::math_utils::UtilsInvalidArg;
+use super::sensor::SensorError;
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 use thiserror::Error;
 
 pub static COMPONENT_NAME: &str = "motor";
@@ -52,6 +54,25 @@ pub enum MotorError {
     ActuatorError(#[from] ActuatorError),
     #[error("unimplemented: {0}")]
     MotorMethodUnimplemented(&'static str),
+    #[error(transparent)]
+    SensorError(#[from] SensorError),
+    #[error("motor stopped by thermal protection at {0}°C")]
+    MotorThermalShutdown(f64),
+}
+
+impl GrpcStatusHint for MotorError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::InvalidMotorConfig | Self::ConfigError(_) => GrpcError::RpcFailedPrecondition,
+            Self::EncoderError(err) => err.grpc_error(),
+            Self::BoardError(err) => err.grpc_error(),
+            Self::PowerSetError | Self::InvalidArgument(_) => GrpcError::RpcInvalidArgument,
+            Self::MissingEncoder | Self::MotorMethodUnimplemented(_) => GrpcError::RpcUnimplemented,
+            Self::ActuatorError(err) => err.grpc_error(),
+            Self::SensorError(err) => err.grpc_error(),
+            Self::MotorThermalShutdown(_) => GrpcError::RpcFailedPrecondition,
+        }
+    }
 }
 
 #[cfg(feature = "builtin-components")]
@@ -80,8 +101,34 @@ pub(crate) fn register_models(registry: &mut ComponentRegistry) {
     }
 }
 
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct MotorSupportedProperties {
     pub position_reporting: bool,
+    /// Highest speed the motor can be driven at, when configured
+    pub max_rpm: Option<f64>,
+    /// Resolution of the encoder tracking the motor's position, when known
+    pub ticks_per_rotation: Option<u32>,
+}
+
+impl MotorSupportedProperties {
+    /// The motor API's GetProperties only carries `position_reporting`, the known limits are
+    /// added to the motor's status instead so clients can plan motions with them.
+    pub fn extend_status(&self, status: &mut google::protobuf::Struct) {
+        let limits = [
+            ("max_rpm", self.max_rpm),
+            ("ticks_per_rotation", self.ticks_per_rotation.map(f64::from)),
+        ];
+        for (key, value) in limits {
+            if let Some(value) = value {
+                status.fields.insert(
+                    key.to_string(),
+                    google::protobuf::Value {
+                        kind: Some(google::protobuf::value::Kind::NumberValue(value)),
+                    },
+                );
+            }
+        }
+    }
 }
 
 impl From<MotorSupportedProperties> for GetPropertiesResponse {
@@ -97,6 +144,15 @@ pub trait Motor: Status + Actuator + DoCommand {
     /// expressed a value between `-1.0` and `1.0` where negative values indicate a backwards
     /// direction and positive values a forward direction.
     fn set_power(&mut self, pct: f64) -> Result<(), MotorError>;
+    /// Sets the motor's power as [Motor::set_power], with implementation specific options
+    /// passed in `extra` (such as an acceleration override)
+    fn set_power_with_extra(
+        &mut self,
+        pct: f64,
+        _extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), MotorError> {
+        self.set_power(pct)
+    }
     /// Reports the position of the robot's motor relative to its zero position.
     /// This method will return an error if position reporting is not supported.
     fn get_position(&mut self) -> Result<i32, MotorError>;
@@ -104,15 +160,118 @@ pub trait Motor: Status + Actuator + DoCommand {
     /// for a specified number of rotations relative to its starting position.
     /// This method will return an error if position reporting is not supported.
     /// If revolutions is 0, this will run the motor at rpm indefinitely.
-    /// If revolutions != 0, this will block until the number of revolutions has been completed or another operation comes in.
+    /// If revolutions != 0, this returns the time the revolutions take, after which the caller
+    /// stops the motor. Motors with an encoder report the fraction of the revolutions covered
+    /// so far as `go_for_progress` in their status until the motor is stopped.
     fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError>;
+    /// Turns the motor as [Motor::go_for], with implementation specific options passed in
+    /// `extra`
+    fn go_for_with_extra(
+        &mut self,
+        rpm: f64,
+        revolutions: f64,
+        _extra: Option<google::protobuf::Struct>,
+    ) -> Result<Option<Duration>, MotorError> {
+        self.go_for(rpm, revolutions)
+    }
     /// Returns an instance of MotorSupportedProperties indicating the optional properties
     /// supported by this motor
     fn get_properties(&mut self) -> MotorSupportedProperties;
+    /// Returns whether the motor is currently powered along with the power percentage
+    /// (between `-1.0` and `1.0`) based on the last command sent to the motor.
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Err(MotorError::MotorMethodUnimplemented("is_powered"))
+    }
 }
 
 pub type MotorType = Arc<Mutex<dyn Motor>>;
 
+/// Encoder position a [Motor::go_for] started from and the ticks it has to cover, used by
+/// motors with an encoder to report its progress
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct GoForTarget {
+    start: f64,
+    ticks: f64,
+}
+
+impl GoForTarget {
+    /// Target of a go_for starting at the encoder position `start`, `None` when it runs
+    /// indefinitely
+    pub(crate) fn new(
+        start: f64,
+        rpm: f64,
+        revolutions: f64,
+        ticks_per_rotation: u32,
+    ) -> Option<Self> {
+        (revolutions != 0.0).then(|| Self {
+            start,
+            ticks: revolutions * rpm.signum() * ticks_per_rotation as f64,
+        })
+    }
+
+    /// Fraction of the revolutions covered when the encoder reads `position`, between `0.0`
+    /// and `1.0`
+    pub(crate) fn progress(&self, position: f64) -> f64 {
+        ((position - self.start) / self.ticks).clamp(0.0, 1.0)
+    }
+}
+
+/// Stops the motor when dropped unless it was disarmed, so that a motion awaited by a future
+/// that is dropped, for example when the deadline of its request expires, doesn't leave the
+/// motor running
+pub(crate) struct StopOnDrop<'a, M: Motor + ?Sized> {
+    motor: &'a mut M,
+    armed: bool,
+}
+
+impl<'a, M: Motor + ?Sized> StopOnDrop<'a, M> {
+    pub(crate) fn new(motor: &'a mut M) -> Self {
+        Self { motor, armed: true }
+    }
+
+    pub(crate) fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl<M: Motor + ?Sized> Drop for StopOnDrop<'_, M> {
+    fn drop(&mut self) {
+        if self.armed {
+            let _ = self.motor.stop();
+        }
+    }
+}
+
+/// How a driver stops a motor: coasting lets it spin down with its terminals floating, braking
+/// shorts its terminals so it stops quickly and resists being turned
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StopMode {
+    #[default]
+    Coast,
+    Brake,
+}
+
+impl TryFrom<&str> for StopMode {
+    type Error = AttributeError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "coast" => Ok(Self::Coast),
+            "brake" => Ok(Self::Brake),
+            _ => Err(AttributeError::ConversionImpossibleError),
+        }
+    }
+}
+
+impl TryFrom<&Kind> for StopMode {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        match value {
+            Kind::StringValue(v) => v.as_str().try_into(),
+            _ => Err(AttributeError::ConversionImpossibleError),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum MotorPinType {
     PwmAB,
@@ -122,10 +281,10 @@ pub enum MotorPinType {
 
 #[derive(Debug, Default)]
 pub struct MotorPinsConfig {
-    pub(crate) a: Option<i32>,
-    pub(crate) b: Option<i32>,
-    pub(crate) dir: Option<i32>,
-    pub(crate) pwm: Option<i32>,
+    pub(crate) a: Option<BoardPin>,
+    pub(crate) b: Option<BoardPin>,
+    pub(crate) dir: Option<BoardPin>,
+    pub(crate) pwm: Option<BoardPin>,
 }
 
 impl MotorPinsConfig {
@@ -147,6 +306,12 @@ pub struct FakeMotor {
     pos: f64,
     power: f64,
     max_rpm: f64,
+    health: ComponentHealth,
+    // extra of the last command, reported in the status
+    extra: Option<google::protobuf::Struct>,
+    // with the `sim` feature the position advances by `power * max_rpm` since the last update
+    #[cfg(feature = "sim")]
+    last_update: std::time::Instant,
 }
 
 impl TryFrom<&Kind> for MotorPinsConfig {
@@ -211,8 +376,25 @@ impl FakeMotor {
             pos: 10.0,
             power: 0.0,
             max_rpm: 100.0,
+            health: ComponentHealth::new(),
+            extra: None,
+            #[cfg(feature = "sim")]
+            last_update: std::time::Instant::now(),
         }
     }
+
+    /// Position of the motor in revolutions
+    #[cfg(feature = "sim")]
+    fn position(&self) -> f64 {
+        let elapsed = self.last_update.elapsed().as_secs_f64();
+        self.pos + self.power * self.max_rpm * elapsed / 60.0
+    }
+
+    /// Position of the motor in revolutions
+    #[cfg(not(feature = "sim"))]
+    fn position(&self) -> f64 {
+        self.pos
+    }
     pub(crate) fn from_config(
         cfg: ConfigType,
         _: Vec<Dependency>,
@@ -227,6 +409,7 @@ impl FakeMotor {
         Ok(Arc::new(Mutex::new(motor)))
     }
 }
+
 #[cfg(feature = "builtin-components")]
 impl Default for FakeMotor {
     fn default() -> Self {
@@ -244,12 +427,32 @@ where
     fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
         self.get_mut().unwrap().set_power(pct)
     }
+    fn set_power_with_extra(
+        &mut self,
+        pct: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), MotorError> {
+        self.get_mut().unwrap().set_power_with_extra(pct, extra)
+    }
     fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
         self.get_mut().unwrap().go_for(rpm, revolutions)
     }
+    fn go_for_with_extra(
+        &mut self,
+        rpm: f64,
+        revolutions: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<Option<Duration>, MotorError> {
+        self.get_mut()
+            .unwrap()
+            .go_for_with_extra(rpm, revolutions, extra)
+    }
     fn get_properties(&mut self) -> MotorSupportedProperties {
         self.get_mut().unwrap().get_properties()
     }
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        self.get_mut().unwrap().is_powered()
+    }
 }
 
 impl<A> Motor for Arc<Mutex<A>>
@@ -262,55 +465,112 @@ where
     fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
         self.lock().unwrap().set_power(pct)
     }
+    fn set_power_with_extra(
+        &mut self,
+        pct: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), MotorError> {
+        self.lock().unwrap().set_power_with_extra(pct, extra)
+    }
     fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
         self.lock().unwrap().go_for(rpm, revolutions)
     }
+    fn go_for_with_extra(
+        &mut self,
+        rpm: f64,
+        revolutions: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<Option<Duration>, MotorError> {
+        self.lock()
+            .unwrap()
+            .go_for_with_extra(rpm, revolutions, extra)
+    }
     fn get_properties(&mut self) -> MotorSupportedProperties {
         self.lock().unwrap().get_properties()
     }
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        self.lock().unwrap().is_powered()
+    }
 }
 
 #[cfg(feature = "builtin-components")]
 impl Motor for FakeMotor {
     fn get_position(&mut self) -> Result<i32, MotorError> {
-        Ok(self.pos as i32)
+        Ok(self.position() as i32)
     }
     fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
         log::debug!("setting power to {}", pct);
+        #[cfg(feature = "sim")]
+        {
+            self.pos = self.position();
+            self.last_update = std::time::Instant::now();
+        }
         self.power = pct;
+        self.health.record::<_, MotorError>(&Ok(()));
         Ok(())
     }
+    fn set_power_with_extra(
+        &mut self,
+        pct: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), MotorError> {
+        self.extra = extra;
+        self.set_power(pct)
+    }
     fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
         // get_max_rpm
-        let (pwr, dur) = go_for_math(self.max_rpm, rpm, revolutions)?;
+        let res = go_for_math(self.max_rpm, rpm, revolutions);
+        self.health.record(&res);
+        let (pwr, dur) = res?;
         self.set_power(pwr)?;
         Ok(dur)
     }
+    fn go_for_with_extra(
+        &mut self,
+        rpm: f64,
+        revolutions: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<Option<Duration>, MotorError> {
+        self.extra = extra;
+        self.go_for(rpm, revolutions)
+    }
     fn get_properties(&mut self) -> MotorSupportedProperties {
         MotorSupportedProperties {
             position_reporting: true,
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: None,
         }
     }
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
 }
 
 #[cfg(feature = "builtin-components")]
 impl Status for FakeMotor {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        let mut hm = HashMap::new();
-        hm.insert(
-            "position".to_string(),
-            google::protobuf::Value {
-                kind: Some(google::protobuf::value::Kind::NumberValue(self.pos)),
-            },
-        );
-        hm.insert(
-            "position_reporting".to_string(),
-            google::protobuf::Value {
-                kind: Some(google::protobuf::value::Kind::BoolValue(true)),
-            },
+        let mut status = status_envelope(
+            &self.health,
+            [
+                (
+                    "position",
+                    google::protobuf::value::Kind::NumberValue(self.position()),
+                ),
+                (
+                    "position_reporting",
+                    google::protobuf::value::Kind::BoolValue(true),
+                ),
+            ],
         );
-
-        Ok(Some(google::protobuf::Struct { fields: hm }))
+        if let Some(extra) = self.extra.as_ref() {
+            status.fields.insert(
+                "extra".to_string(),
+                google::protobuf::Value {
+                    kind: Some(google::protobuf::value::Kind::StructValue(extra.clone())),
+                },
+            );
+        }
+        Ok(Some(status))
     }
 }
 
@@ -320,6 +580,13 @@ impl Actuator for FakeMotor {
         log::debug!("stopping motor");
         self.set_power(0.0).map_err(|_| ActuatorError::CouldntStop)
     }
+    fn stop_with_extra(
+        &mut self,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), ActuatorError> {
+        self.extra = extra;
+        self.stop()
+    }
     fn is_moving(&mut self) -> Result<bool, ActuatorError> {
         Ok(self.power > 0.0)
     }
@@ -390,8 +657,16 @@ impl Motor for FakeMotorWithDependency {
     fn get_properties(&mut self) -> MotorSupportedProperties {
         MotorSupportedProperties {
             position_reporting: true,
+            max_rpm: None,
+            ticks_per_rotation: self
+                .encoder
+                .as_ref()
+                .and_then(|enc| enc.ticks_per_rotation()),
         }
     }
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
 }
 
 #[cfg(feature = "builtin-components")]
@@ -419,7 +694,21 @@ mod tests {
     use std::collections::HashMap;
 
     use crate::common::config::{Component, DynamicComponentConfig, Kind};
-    use crate::common::motor::{ConfigType, FakeMotor, MotorPinType, MotorPinsConfig};
+    use crate::common::motor::{
+        ConfigType, FakeMotor, Motor, MotorPinType, MotorPinsConfig, StopMode,
+    };
+
+    #[test_log::test]
+    fn test_stop_mode() {
+        assert_eq!(
+            StopMode::try_from(&Kind::StringValue("brake".to_owned())).unwrap(),
+            StopMode::Brake
+        );
+        assert_eq!(StopMode::try_from("coast").unwrap(), StopMode::Coast);
+        assert!(StopMode::try_from(&Kind::StringValue("hold".to_owned())).is_err());
+        assert!(StopMode::try_from(&Kind::NumberValue(1.0)).is_err());
+    }
+
     #[test_log::test]
     fn test_motor_config() {
         let robot_config: [Option<DynamicComponentConfig>; 1] = [Some(DynamicComponentConfig {
@@ -462,7 +751,11 @@ mod tests {
         assert_eq!(val.dir.unwrap(), 14);
 
         let dyn_conf = ConfigType::Dynamic(robot_config[0].as_ref().unwrap());
-        assert!(FakeMotor::from_config(dyn_conf, Vec::new()).is_ok());
+        let motor = FakeMotor::from_config(dyn_conf, Vec::new());
+        assert!(motor.is_ok());
+        let props = motor.unwrap().get_properties();
+        assert_eq!(props.max_rpm, Some(10000.0));
+        assert_eq!(props.ticks_per_rotation, None);
     }
 
     #[test_log::test]
@@ -574,4 +867,29 @@ mod tests {
         assert!(motor_type_4.is_ok());
         assert!(matches!(motor_type_4.unwrap(), MotorPinType::AB));
     }
+
+    #[test_log::test]
+    fn test_motor_error_grpc_status() {
+        use crate::common::board::BoardError;
+        use crate::common::grpc::{GrpcError, GrpcStatusHint, ServerError};
+        use crate::common::motor::MotorError;
+
+        assert_eq!(
+            MotorError::MotorMethodUnimplemented("go_to").grpc_error(),
+            GrpcError::RpcUnimplemented
+        );
+        assert_eq!(
+            MotorError::ConfigError("bad config").grpc_error(),
+            GrpcError::RpcFailedPrecondition
+        );
+        assert_eq!(
+            MotorError::PowerSetError.grpc_error(),
+            GrpcError::RpcInvalidArgument
+        );
+        // nested errors keep the hint of the error they wrap
+        let err = MotorError::BoardError(BoardError::AnalogReaderNotFound("A1".to_owned()));
+        assert_eq!(err.grpc_error(), GrpcError::RpcNotFound);
+        let err = ServerError::from_component_error(err);
+        assert_eq!(err.status_code(), GrpcError::RpcNotFound as i32);
+    }
 }
@@ -0,0 +1,381 @@
+//! Package motor_group implements a motor fanning out every command to a group of child motors,
+//! so motors mechanically coupled together (for example the two motors on each side of a four
+//! wheeled base) can be driven as a single motor.
+//!
+//! ```json
+//! {
+//!     "motors": ["left_front", "left_rear"],
+//!     "inverted": ["left_rear"]
+//! }
+//! ```
+//!
+//! Commands sent to motors listed in `inverted` have their direction flipped, to account for
+//! motors mounted facing the other way. The first motor of the group is the leader: the
+//! position, power state and status of the group are the ones it reports.
+
+use crate::common::status::{Status, StatusError};
+use crate::google;
+
+use super::actuator::{Actuator, ActuatorError};
+use super::config::{AttributeError, ConfigType};
+use super::motor::{
+    Motor, MotorError, MotorSupportedProperties, MotorType, COMPONENT_NAME as MotorCompName,
+};
+use super::registry::{ComponentRegistry, Dependency, ResourceKey};
+use super::robot::Resource;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_motor("motor_group", &MotorGroup::<MotorType>::from_config)
+        .is_err()
+    {
+        log::error!("motor_group type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            MotorCompName,
+            "motor_group",
+            &MotorGroup::<MotorType>::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("failed to register dependency getter for motor_group model")
+    }
+}
+
+struct GroupedMotor<M> {
+    motor: M,
+    inverted: bool,
+}
+
+impl<M> GroupedMotor<M> {
+    fn direction(&self) -> f64 {
+        if self.inverted {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(DoCommand)]
+pub struct MotorGroup<M> {
+    // never empty, the first motor is the leader
+    motors: Vec<GroupedMotor<M>>,
+}
+
+impl<M> MotorGroup<M>
+where
+    M: Motor,
+{
+    /// Groups `motors`, each paired with whether its direction is inverted
+    pub fn new(motors: Vec<(M, bool)>) -> Result<Self, MotorError> {
+        if motors.is_empty() {
+            return Err(MotorError::ConfigError(
+                "motor group requires at least one motor",
+            ));
+        }
+        Ok(Self {
+            motors: motors
+                .into_iter()
+                .map(|(motor, inverted)| GroupedMotor { motor, inverted })
+                .collect(),
+        })
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<MotorType, MotorError> {
+        let names = cfg
+            .get_attribute::<Vec<String>>("motors")
+            .map_err(|_| MotorError::ConfigError("motor group missing motors"))?;
+        let inverted = match cfg.get_attribute::<Vec<String>>("inverted") {
+            Ok(inverted) => inverted,
+            Err(AttributeError::KeyNotFound(_)) => vec![],
+            Err(_) => return Err(MotorError::ConfigError("motor group invalid inverted")),
+        };
+        if let Some(name) = inverted.iter().find(|name| !names.contains(name)) {
+            log::error!("inverted motor {} is not part of the motor group", name);
+            return Err(MotorError::ConfigError(
+                "motor group inverted motors must be listed in motors",
+            ));
+        }
+        let mut found: Vec<Option<MotorType>> = vec![None; names.len()];
+        for Dependency(key, res) in deps {
+            if let Resource::Motor(motor) = res {
+                if let Some(pos) = names.iter().position(|name| *name == key.1) {
+                    found[pos] = Some(motor);
+                }
+            }
+        }
+        let missing: Vec<&String> = names
+            .iter()
+            .zip(found.iter())
+            .filter_map(|(name, motor)| motor.is_none().then_some(name))
+            .collect();
+        if !missing.is_empty() {
+            log::error!("motor group motors {:?} couldn't be found", missing);
+            return Err(MotorError::ConfigError(
+                "motor group motors couldn't be found",
+            ));
+        }
+        let motors = names
+            .iter()
+            .zip(found)
+            .map(|(name, motor)| (motor.unwrap(), inverted.contains(name)))
+            .collect();
+        Ok(Arc::new(Mutex::new(Self::new(motors)?)))
+    }
+
+    pub(crate) fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        cfg.get_attribute::<Vec<String>>("motors")
+            .map(|names| {
+                names
+                    .into_iter()
+                    .map(|name| ResourceKey(MotorCompName, name))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn leader(&mut self) -> &mut GroupedMotor<M> {
+        &mut self.motors[0]
+    }
+
+    // Applies `f` to every motor of the group, even if some of them fail, so that a failing
+    // motor doesn't leave the others running. Returns the first error encountered.
+    fn for_each<T, E>(
+        &mut self,
+        mut f: impl FnMut(&mut GroupedMotor<M>) -> Result<T, E>,
+    ) -> Result<Vec<T>, E> {
+        let mut results = Vec::with_capacity(self.motors.len());
+        let mut first_err = None;
+        for motor in self.motors.iter_mut() {
+            match f(motor) {
+                Ok(res) => results.push(res),
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(results),
+        }
+    }
+}
+
+impl<M> Motor for MotorGroup<M>
+where
+    M: Motor,
+{
+    fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        self.set_power_with_extra(pct, None)
+    }
+    fn set_power_with_extra(
+        &mut self,
+        pct: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), MotorError> {
+        self.for_each(|m| {
+            m.motor
+                .set_power_with_extra(pct * m.direction(), extra.clone())
+        })
+        .map(|_| ())
+    }
+    fn get_position(&mut self) -> Result<i32, MotorError> {
+        let leader = self.leader();
+        let pos = leader.motor.get_position()?;
+        Ok(if leader.inverted { -pos } else { pos })
+    }
+    fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        self.go_for_with_extra(rpm, revolutions, None)
+    }
+    fn go_for_with_extra(
+        &mut self,
+        rpm: f64,
+        revolutions: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<Option<Duration>, MotorError> {
+        let durations = self.for_each(|m| {
+            m.motor
+                .go_for_with_extra(rpm * m.direction(), revolutions, extra.clone())
+        })?;
+        Ok(durations.into_iter().max().flatten())
+    }
+    fn get_properties(&mut self) -> MotorSupportedProperties {
+        // the group can't go faster than its slowest motor
+        let max_rpm = self
+            .motors
+            .iter_mut()
+            .map(|m| m.motor.get_properties().max_rpm)
+            .try_fold(f64::INFINITY, |acc, rpm| rpm.map(|rpm| acc.min(rpm)));
+        MotorSupportedProperties {
+            max_rpm,
+            ..self.leader().motor.get_properties()
+        }
+    }
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        let leader = self.leader();
+        let (powered, pct) = leader.motor.is_powered()?;
+        Ok((powered, pct * leader.direction()))
+    }
+}
+
+impl<M> Actuator for MotorGroup<M>
+where
+    M: Motor,
+{
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(self
+            .for_each(|m| m.motor.is_moving())?
+            .into_iter()
+            .any(|moving| moving))
+    }
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        self.stop_with_extra(None)
+    }
+    fn stop_with_extra(
+        &mut self,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), ActuatorError> {
+        self.for_each(|m| m.motor.stop_with_extra(extra.clone()))
+            .map(|_| ())
+    }
+}
+
+impl<M> Status for MotorGroup<M>
+where
+    M: Motor,
+{
+    fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
+        let mut status = self.motors[0].motor.get_status()?.unwrap_or_default();
+        status.fields.insert(
+            "motor_count".to_string(),
+            google::protobuf::Value {
+                kind: Some(google::protobuf::value::Kind::NumberValue(
+                    self.motors.len() as f64,
+                )),
+            },
+        );
+        Ok(Some(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::MotorGroup;
+    use crate::common::actuator::{Actuator, ActuatorError};
+    use crate::common::generic::DoCommand;
+    use crate::common::motor::{FakeMotor, Motor, MotorError, MotorSupportedProperties};
+    use crate::common::status::{Status, StatusError};
+    use crate::google::protobuf::{value::Kind, Struct, Value};
+
+    #[derive(Default)]
+    struct ExtraRecordingMotor {
+        extras: Vec<Option<Struct>>,
+    }
+
+    impl Motor for ExtraRecordingMotor {
+        fn set_power(&mut self, _: f64) -> Result<(), MotorError> {
+            self.set_power_with_extra(0.0, None)
+        }
+        fn set_power_with_extra(
+            &mut self,
+            _: f64,
+            extra: Option<Struct>,
+        ) -> Result<(), MotorError> {
+            self.extras.push(extra);
+            Ok(())
+        }
+        fn get_position(&mut self) -> Result<i32, MotorError> {
+            Ok(0)
+        }
+        fn go_for(&mut self, _: f64, _: f64) -> Result<Option<Duration>, MotorError> {
+            Ok(None)
+        }
+        fn get_properties(&mut self) -> MotorSupportedProperties {
+            MotorSupportedProperties::default()
+        }
+    }
+
+    impl Actuator for ExtraRecordingMotor {
+        fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+            Ok(false)
+        }
+        fn stop(&mut self) -> Result<(), ActuatorError> {
+            self.stop_with_extra(None)
+        }
+        fn stop_with_extra(&mut self, extra: Option<Struct>) -> Result<(), ActuatorError> {
+            self.extras.push(extra);
+            Ok(())
+        }
+    }
+
+    impl Status for ExtraRecordingMotor {
+        fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+            Ok(None)
+        }
+    }
+
+    impl DoCommand for ExtraRecordingMotor {}
+
+    #[test_log::test]
+    fn test_motor_group() {
+        assert!(MotorGroup::<FakeMotor>::new(vec![]).is_err());
+
+        let mut group =
+            MotorGroup::new(vec![(FakeMotor::new(), false), (FakeMotor::new(), true)]).unwrap();
+
+        assert!(group.set_power(0.5).is_ok());
+        assert_eq!(group.motors[0].motor.is_powered().unwrap(), (true, 0.5));
+        assert_eq!(group.motors[1].motor.is_powered().unwrap(), (true, -0.5));
+        assert_eq!(group.is_powered().unwrap(), (true, 0.5));
+
+        // 10 revolutions at 60 rpm
+        let dur = group.go_for(60.0, 10.0).unwrap().unwrap();
+        assert!((dur.as_secs_f64() - 10.0).abs() < 1e-6);
+        assert_eq!(group.motors[0].motor.is_powered().unwrap(), (true, 0.6));
+        assert_eq!(group.motors[1].motor.is_powered().unwrap(), (true, -0.6));
+
+        assert_eq!(group.get_properties().max_rpm, Some(100.0));
+
+        assert!(group.stop().is_ok());
+        assert!(!group.motors[0].motor.is_powered().unwrap().0);
+        assert!(!group.motors[1].motor.is_powered().unwrap().0);
+    }
+
+    #[test_log::test]
+    fn test_motor_group_forwards_extra() {
+        let mut group = MotorGroup::new(vec![
+            (ExtraRecordingMotor::default(), false),
+            (ExtraRecordingMotor::default(), true),
+        ])
+        .unwrap();
+        let extra = Struct {
+            fields: [(
+                "mode".to_owned(),
+                Value {
+                    kind: Some(Kind::StringValue("brake".to_owned())),
+                },
+            )]
+            .into(),
+        };
+
+        assert!(group.set_power_with_extra(0.5, Some(extra.clone())).is_ok());
+        assert!(group.stop().is_ok());
+        assert!(group.stop_with_extra(Some(extra.clone())).is_ok());
+        for m in group.motors.iter() {
+            assert_eq!(
+                m.motor.extras,
+                vec![Some(extra.clone()), None, Some(extra.clone())]
+            );
+        }
+    }
+}
@@ -6,13 +6,14 @@ use {
     super::registry::{ComponentRegistry, Dependency},
 };
 
+use super::frame::OrientationVector;
 use super::generic::DoCommand;
 use super::math_utils::Vector3;
 use super::sensor::{GenericReadingsResult, Readings, SensorError};
 use super::status::Status;
-use crate::google;
-use crate::google::protobuf::{value::Kind, Struct, Value};
-use crate::proto::common::v1::GeoPoint;
+use super::struct_builder::StructBuilder;
+use crate::google::protobuf::Value;
+use crate::proto::common::v1::{GeoPoint, Orientation};
 use crate::proto::component::movement_sensor;
 
 use std::collections::HashMap;
@@ -31,14 +32,14 @@ pub(crate) fn register_models(registry: &mut ComponentRegistry) {
 }
 
 // A local struct representation of the supported methods indicated by the
-// GetProperties method of the Movement Sensor API. TODO: add a boolean for
-// orientation when it is supportable.
+// GetProperties method of the Movement Sensor API.
 pub struct MovementSensorSupportedMethods {
     pub position_supported: bool,
     pub linear_velocity_supported: bool,
     pub angular_velocity_supported: bool,
     pub linear_acceleration_supported: bool,
     pub compass_heading_supported: bool,
+    pub orientation_supported: bool,
 }
 
 impl From<MovementSensorSupportedMethods> for movement_sensor::v1::GetPropertiesResponse {
@@ -49,13 +50,24 @@ impl From<MovementSensorSupportedMethods> for movement_sensor::v1::GetProperties
             angular_velocity_supported: props.angular_velocity_supported,
             linear_acceleration_supported: props.linear_acceleration_supported,
             compass_heading_supported: props.compass_heading_supported,
-            orientation_supported: false,
+            orientation_supported: props.orientation_supported,
+        }
+    }
+}
+
+impl From<OrientationVector> for Orientation {
+    fn from(ov: OrientationVector) -> Self {
+        Orientation {
+            o_x: ov.o_x,
+            o_y: ov.o_y,
+            o_z: ov.o_z,
+            theta: ov.theta_deg,
         }
     }
 }
 
 // A struct representing geographic coordinates (latitude-longitude-altitude)
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct GeoPosition {
     pub lat: f64,
     pub lon: f64,
@@ -64,30 +76,11 @@ pub struct GeoPosition {
 
 impl From<GeoPosition> for Value {
     fn from(value: GeoPosition) -> Self {
-        let mut fields = HashMap::new();
-        fields.insert(
-            "lat".to_string(),
-            Value {
-                kind: Some(google::protobuf::value::Kind::NumberValue(value.lat)),
-            },
-        );
-        fields.insert(
-            "lon".to_string(),
-            Value {
-                kind: Some(google::protobuf::value::Kind::NumberValue(value.lon)),
-            },
-        );
-        fields.insert(
-            "alt".to_string(),
-            Value {
-                kind: Some(google::protobuf::value::Kind::NumberValue(value.alt as f64)),
-            },
-        );
-        Value {
-            kind: Some(google::protobuf::value::Kind::StructValue(Struct {
-                fields,
-            })),
-        }
+        StructBuilder::with_capacity(3)
+            .field("lat", value.lat)
+            .field("lon", value.lon)
+            .field("alt", value.alt)
+            .into()
     }
 }
 
@@ -104,8 +97,32 @@ impl From<GeoPosition> for movement_sensor::v1::GetPositionResponse {
     }
 }
 
-// A trait for implementing a movement sensor component driver. TODO: add
-// get_orientation and get_accuracy if/when they become supportable.
+// The accuracy of the measurements of a movement sensor, as reported by GetAccuracy. Each
+// measurement is None when the sensor doesn't know its accuracy.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MovementSensorAccuracy {
+    // driver specific accuracies, such as the standard deviation of the latitude in meters
+    pub accuracy: HashMap<String, f32>,
+    pub position_hdop: Option<f32>,
+    pub position_vdop: Option<f32>,
+    // fix quality of the position, as reported by NMEA GGA sentences
+    pub position_nmea_gga_fix: Option<i32>,
+    pub compass_degrees_error: Option<f32>,
+}
+
+impl From<MovementSensorAccuracy> for movement_sensor::v1::GetAccuracyResponse {
+    fn from(acc: MovementSensorAccuracy) -> movement_sensor::v1::GetAccuracyResponse {
+        movement_sensor::v1::GetAccuracyResponse {
+            accuracy: acc.accuracy,
+            position_hdop: acc.position_hdop,
+            position_vdop: acc.position_vdop,
+            position_nmea_gga_fix: acc.position_nmea_gga_fix,
+            compass_degrees_error: acc.compass_degrees_error,
+        }
+    }
+}
+
+// A trait for implementing a movement sensor component driver.
 pub trait MovementSensor: Status + Readings + DoCommand {
     fn get_position(&mut self) -> Result<GeoPosition, SensorError>;
     fn get_linear_velocity(&mut self) -> Result<Vector3, SensorError>;
@@ -113,6 +130,12 @@ pub trait MovementSensor: Status + Readings + DoCommand {
     fn get_linear_acceleration(&mut self) -> Result<Vector3, SensorError>;
     fn get_compass_heading(&mut self) -> Result<f64, SensorError>;
     fn get_properties(&self) -> MovementSensorSupportedMethods;
+    fn get_accuracy(&mut self) -> Result<MovementSensorAccuracy, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented("get_accuracy"))
+    }
+    fn get_orientation(&mut self) -> Result<OrientationVector, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented("get_orientation"))
+    }
 }
 
 pub type MovementSensorType = Arc<Mutex<dyn MovementSensor>>;
@@ -120,42 +143,39 @@ pub type MovementSensorType = Arc<Mutex<dyn MovementSensor>>;
 pub fn get_movement_sensor_generic_readings(
     ms: &mut dyn MovementSensor,
 ) -> Result<GenericReadingsResult, SensorError> {
-    let mut res = std::collections::HashMap::new();
+    let mut res = StructBuilder::with_capacity(6);
     let supported_methods = ms.get_properties();
     if supported_methods.position_supported {
-        res.insert("position".to_string(), ms.get_position()?.into());
+        res = res.value("position", ms.get_position()?.into());
     }
     if supported_methods.linear_velocity_supported {
-        res.insert(
-            "linear_velocity".to_string(),
-            ms.get_linear_velocity()?.into(),
-        );
+        res = res.value("linear_velocity", ms.get_linear_velocity()?.into());
     }
     if supported_methods.linear_acceleration_supported {
-        res.insert(
-            "linear_acceleration".to_string(),
-            ms.get_linear_acceleration()?.into(),
-        );
+        res = res.value("linear_acceleration", ms.get_linear_acceleration()?.into());
     }
     if supported_methods.angular_velocity_supported {
-        res.insert(
-            "angular_velocity".to_string(),
-            ms.get_angular_velocity()?.into(),
-        );
+        res = res.value("angular_velocity", ms.get_angular_velocity()?.into());
     }
     if supported_methods.compass_heading_supported {
-        res.insert(
-            "compass_heading".to_string(),
-            Value {
-                kind: Some(Kind::NumberValue(ms.get_compass_heading()?)),
-            },
+        res = res.field("compass_heading", ms.get_compass_heading()?);
+    }
+    if supported_methods.orientation_supported {
+        let orientation = ms.get_orientation()?;
+        res = res.sub(
+            "orientation",
+            StructBuilder::with_capacity(4)
+                .field("o_x", orientation.o_x)
+                .field("o_y", orientation.o_y)
+                .field("o_z", orientation.o_z)
+                .field("theta", orientation.theta_deg),
         );
     }
-    Ok(res)
+    Ok(res.into_fields())
 }
 
 #[cfg(feature = "builtin-components")]
-#[derive(DoCommand, MovementSensorReadings)]
+#[derive(DoCommand, MovementSensorReadings, Status)]
 pub struct FakeMovementSensor {
     pos: GeoPosition,
     linear_acc: Vector3,
@@ -234,6 +254,7 @@ impl MovementSensor for FakeMovementSensor {
             linear_velocity_supported: false,
             angular_velocity_supported: false,
             compass_heading_supported: false,
+            orientation_supported: false,
         }
     }
 
@@ -256,17 +277,6 @@ impl MovementSensor for FakeMovementSensor {
     }
 }
 
-#[cfg(feature = "builtin-components")]
-impl Status for FakeMovementSensor {
-    fn get_status(
-        &self,
-    ) -> Result<Option<google::protobuf::Struct>, crate::common::status::StatusError> {
-        Ok(Some(google::protobuf::Struct {
-            fields: HashMap::new(),
-        }))
-    }
-}
-
 impl<A> MovementSensor for Mutex<A>
 where
     A: ?Sized + MovementSensor,
@@ -291,6 +301,14 @@ where
         self.get_mut().unwrap().get_compass_heading()
     }
 
+    fn get_accuracy(&mut self) -> Result<MovementSensorAccuracy, SensorError> {
+        self.get_mut().unwrap().get_accuracy()
+    }
+
+    fn get_orientation(&mut self) -> Result<OrientationVector, SensorError> {
+        self.get_mut().unwrap().get_orientation()
+    }
+
     fn get_properties(&self) -> MovementSensorSupportedMethods {
         self.lock().unwrap().get_properties()
     }
@@ -320,6 +338,14 @@ where
         self.lock().unwrap().get_compass_heading()
     }
 
+    fn get_accuracy(&mut self) -> Result<MovementSensorAccuracy, SensorError> {
+        self.lock().unwrap().get_accuracy()
+    }
+
+    fn get_orientation(&mut self) -> Result<OrientationVector, SensorError> {
+        self.lock().unwrap().get_orientation()
+    }
+
     fn get_properties(&self) -> MovementSensorSupportedMethods {
         self.lock().unwrap().get_properties()
     }
@@ -9,28 +9,50 @@
 //! nor do we yet support using the secondary I2C connection to add an external clock or
 //! magnetometer.
 //!
+//! The gyroscope bias and accelerometer offsets can be calibrated with the
+//! [calibration](crate::common::calibration) DoCommands, `{"calibrate": {"samples": 100}}` averages
+//! `samples` readings (at most 200, taken 5ms apart) while the sensor is still and level (z axis
+//! up) and persists the result, which is applied to the readings from then on.
+//!
+//! The onboard Digital Motion Processor (DMP) can fuse the gyroscope and accelerometer data into an
+//! orientation, which is then available through `get_orientation`. The DMP firmware is not bundled
+//! with micro-rdk, it is read from the [assets](crate::common::assets) of the device (so requires
+//! the `assets` feature) and loaded onto the chip when the `dmp` attribute is set:
+//!
+//! ```text
+//! "dmp": { "firmware": "mpu6050/dmp.bin", "start_address": 1024 }
+//! ```
+//!
+//! `start_address` defaults to 0x0400. The firmware has to output 28 bytes packets (quaternion,
+//! acceleration and angular velocity) such as the MotionApps 6.12 one. Readings are then taken
+//! from the newest packet of the FIFO filled by the DMP at 50Hz instead of polling the sensor
+//! registers. The FIFO holds 0.7s of packets, when polled less often it overflows and the next
+//! reading waits for the DMP to write a fresh packet.
+//!
 //! The chip has two possible I2C addresses, which can be selected by wiring the AD0 pin to either
 //! hot or ground:
 //!   - if AD0 is wired to ground, it uses the default I2C address of 0x68
 //!   - if AD0 is wired to hot, it uses the alternate I2C address of 0x69
 //!
 
+use crate::common::calibration::{number_arg, Calibration, CalibrationCommand, ImuCalibration};
 use crate::common::i2c::I2cHandleType;
 use crate::common::math_utils::Vector3;
 use crate::common::movement_sensor::{MovementSensor, MovementSensorSupportedMethods};
-use crate::google;
 
 use super::board::Board;
-use super::config::ConfigType;
-use super::i2c::I2CHandle;
+use super::config::{AttributeError, ConfigType, Kind};
+use super::frame::OrientationVector;
+use super::generic::{DoCommand, GenericError};
+use super::i2c::{BitField, Endian, RegisterMap};
 use super::movement_sensor::MovementSensorType;
 use super::registry::{get_board_from_dependencies, ComponentRegistry, Dependency};
 use super::sensor::SensorError;
-use super::status::{Status, StatusError};
 
-use std::collections::HashMap;
-use std::mem::size_of;
+use crate::google::protobuf::Struct;
+
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 // This module represents an implementation of the MPU-6050 gyroscope/accelerometer
 // as a Movement Sensor component
@@ -47,23 +69,117 @@ pub(crate) fn register_models(registry: &mut ComponentRegistry) {
 const READING_START_REGISTER: u8 = 59;
 const STANDBY_MODE_REGISTER: u8 = 107;
 const MAX_I16: f64 = 32768.0;
+const MAX_ANGULAR_VELOCITY: f64 = 250.0;
+const MAX_DMP_ANGULAR_VELOCITY: f64 = 2000.0;
+const DEFAULT_CALIBRATION_SAMPLES: usize = 100;
+// the readings are taken while the sensor is locked, this bounds the calibration to a second
+const MAX_CALIBRATION_SAMPLES: usize = 200;
+const CALIBRATION_SAMPLE_INTERVAL: Duration = Duration::from_millis(5);
+
+const SAMPLE_RATE_DIVIDER_REGISTER: u8 = 0x19;
+const CONFIG_REGISTER: u8 = 0x1A;
+const GYRO_CONFIG_REGISTER: u8 = 0x1B;
+const ACCEL_CONFIG_REGISTER: u8 = 0x1C;
+const INT_STATUS_REGISTER: u8 = 0x3A;
+const USER_CTRL_REGISTER: u8 = 0x6A;
+const BANK_SELECT_REGISTER: u8 = 0x6D;
+const MEMORY_START_ADDRESS_REGISTER: u8 = 0x6E;
+const MEMORY_READ_WRITE_REGISTER: u8 = 0x6F;
+const DMP_PROGRAM_START_REGISTER: u8 = 0x70;
+const FIFO_COUNT_REGISTER: u8 = 0x72;
+const FIFO_READ_WRITE_REGISTER: u8 = 0x74;
+
+const SLEEP_MODE: BitField = BitField::bit(6);
+const LOW_PASS_FILTER: BitField = BitField::new(0, 3);
+const FULL_SCALE_RANGE: BitField = BitField::new(3, 2);
+const FIFO_OVERFLOW: BitField = BitField::bit(4);
+const USER_CTRL_DMP_ENABLE: u8 = 0x80;
+const USER_CTRL_FIFO_ENABLE: u8 = 0x40;
+const USER_CTRL_DMP_RESET: u8 = 0x08;
+const USER_CTRL_FIFO_RESET: u8 = 0x04;
+
+const DEFAULT_DMP_START_ADDRESS: u16 = 0x0400;
+const DMP_MEMORY_CHUNK_SIZE: usize = 16;
+const DMP_MEMORY_BANK_SIZE: usize = 256;
+const DMP_PACKET_SIZE: usize = 28;
+const FIFO_SIZE: usize = 1024;
+// 1kHz / (1 + 19), the DMP writes a packet every 20ms
+const DMP_SAMPLE_RATE_DIVIDER: u8 = 19;
+const DMP_PACKET_TIMEOUT: Duration = Duration::from_millis(60);
+const DMP_POLL_INTERVAL: Duration = Duration::from_millis(5);
+// the DMP outputs quaternions in Q30 fixed point
+const QUATERNION_SCALE: f64 = (1 << 30) as f64;
+
+#[derive(Clone, Debug)]
+pub struct DmpConfig {
+    /// Name of the asset holding the DMP firmware
+    pub firmware: String,
+    pub start_address: u16,
+}
 
-#[derive(DoCommand, MovementSensorReadings)]
+impl TryFrom<&Kind> for DmpConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let firmware: &str = value
+            .get("firmware")?
+            .ok_or_else(|| AttributeError::KeyNotFound("firmware".to_string()))?
+            .try_into()?;
+        let start_address = match value.get("start_address")?.map(u32::try_from).transpose()? {
+            Some(address) => {
+                u16::try_from(address).map_err(|_| AttributeError::ConversionImpossibleError)?
+            }
+            None => DEFAULT_DMP_START_ADDRESS,
+        };
+        Ok(Self {
+            firmware: firmware.to_string(),
+            start_address,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DmpPacket {
+    // (w, x, y, z)
+    quaternion: [f64; 4],
+    linear_acceleration: Vector3,
+    angular_velocity: Vector3,
+}
+
+#[derive(MovementSensorReadings, Status)]
 pub struct MPU6050 {
-    i2c_handle: I2cHandleType,
-    i2c_address: u8,
+    registers: RegisterMap<I2cHandleType>,
+    name: String,
+    calibration: ImuCalibration,
+    max_angular_velocity: f64,
+    // `Some` once the DMP runs, holding the last packet it produced
+    dmp: Option<Option<DmpPacket>>,
 }
 
 impl MPU6050 {
-    pub fn new(mut i2c_handle: I2cHandleType, i2c_address: u8) -> Result<Self, SensorError> {
-        let bytes: [u8; 2] = [STANDBY_MODE_REGISTER, 0];
-        i2c_handle.write_i2c(i2c_address, &bytes)?;
+    pub fn new(i2c_handle: I2cHandleType, i2c_address: u8) -> Result<Self, SensorError> {
+        let mut registers = RegisterMap::new(i2c_handle, i2c_address, Endian::Big);
+        // wake up on the internal oscillator
+        registers.write_u8(STANDBY_MODE_REGISTER, 0)?;
         Ok(MPU6050 {
-            i2c_handle,
-            i2c_address,
+            registers,
+            name: String::new(),
+            calibration: ImuCalibration::default(),
+            max_angular_velocity: MAX_ANGULAR_VELOCITY,
+            dmp: None,
         })
     }
 
+    /// Applies the calibration persisted for the component `name`, calibrating the sensor will
+    /// persist it under that name
+    pub fn with_persisted_calibration(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        match ImuCalibration::load(name) {
+            Ok(calibration) => self.calibration = calibration.unwrap_or_default(),
+            Err(err) => log::warn!("mpu6050 {} calibration not loaded: {}", name, err),
+        }
+        self
+    }
+
     #[allow(dead_code)]
     pub(crate) fn from_config(
         cfg: ConfigType,
@@ -82,20 +198,161 @@ impl MPU6050 {
                 "MPU6050 missing i2c_bus attribute",
             ));
         };
-        if let Ok(use_alt_address) = cfg.get_attribute::<bool>("use_alt_i2c_address") {
-            if use_alt_address {
-                return Ok(Arc::new(Mutex::new(MPU6050::new(i2c_handle, 105)?)));
+        let i2c_address = match cfg.get_attribute::<bool>("use_alt_i2c_address") {
+            Ok(true) => 105,
+            _ => 104,
+        };
+        let dmp = match cfg.get_attribute::<DmpConfig>("dmp") {
+            Ok(dmp) => Some(dmp),
+            Err(AttributeError::KeyNotFound(_)) => None,
+            Err(_) => return Err(SensorError::ConfigError("MPU6050 invalid dmp attribute")),
+        };
+        let mut mpu =
+            MPU6050::new(i2c_handle, i2c_address)?.with_persisted_calibration(cfg.get_name());
+        if let Some(dmp) = dmp {
+            mpu.enable_dmp(&load_dmp_firmware(&dmp.firmware)?, dmp.start_address)?;
+        }
+        Ok(Arc::new(Mutex::new(mpu)))
+    }
+
+    fn select_dmp_memory(&mut self, address: usize) -> Result<(), SensorError> {
+        self.registers
+            .write_u8(BANK_SELECT_REGISTER, (address / DMP_MEMORY_BANK_SIZE) as u8)?;
+        self.registers.write_u8(
+            MEMORY_START_ADDRESS_REGISTER,
+            (address % DMP_MEMORY_BANK_SIZE) as u8,
+        )?;
+        Ok(())
+    }
+
+    fn write_dmp_firmware(&mut self, firmware: &[u8]) -> Result<(), SensorError> {
+        if firmware.is_empty() || firmware.len() > u8::MAX as usize * DMP_MEMORY_BANK_SIZE {
+            return Err(SensorError::ConfigError(
+                "MPU6050 invalid dmp firmware size",
+            ));
+        }
+        // chunks never cross a memory bank
+        for (idx, chunk) in firmware.chunks(DMP_MEMORY_CHUNK_SIZE).enumerate() {
+            let address = idx * DMP_MEMORY_CHUNK_SIZE;
+            self.select_dmp_memory(address)?;
+            self.registers
+                .write_bytes(MEMORY_READ_WRITE_REGISTER, chunk)?;
+
+            let mut written = [0_u8; DMP_MEMORY_CHUNK_SIZE];
+            self.select_dmp_memory(address)?;
+            self.registers
+                .read_bytes(MEMORY_READ_WRITE_REGISTER, &mut written[..chunk.len()])?;
+            if &written[..chunk.len()] != chunk {
+                return Err(SensorError::SensorGenericError(
+                    "MPU6050 dmp firmware verification failed",
+                ));
             }
-            Ok(Arc::new(Mutex::new(MPU6050::new(i2c_handle, 104)?)))
-        } else {
-            Ok(Arc::new(Mutex::new(MPU6050::new(i2c_handle, 104)?)))
         }
+        Ok(())
+    }
+
+    /// Loads `firmware` onto the DMP and starts it, readings are then taken from the packets it
+    /// writes to the FIFO
+    pub fn enable_dmp(&mut self, firmware: &[u8], start_address: u16) -> Result<(), SensorError> {
+        // 200Hz sample rate, 188Hz low pass filter, +-2000 deg/s and +-2g full scales
+        self.registers
+            .write_u8(SAMPLE_RATE_DIVIDER_REGISTER, DMP_SAMPLE_RATE_DIVIDER)?;
+        self.registers
+            .write_field(CONFIG_REGISTER, LOW_PASS_FILTER, 1)?;
+        self.registers
+            .write_field(GYRO_CONFIG_REGISTER, FULL_SCALE_RANGE, 3)?;
+        self.registers
+            .write_field(ACCEL_CONFIG_REGISTER, FULL_SCALE_RANGE, 0)?;
+        self.write_dmp_firmware(firmware)?;
+        self.registers
+            .write_u16(DMP_PROGRAM_START_REGISTER, start_address)?;
+        self.registers.write_u8(
+            USER_CTRL_REGISTER,
+            USER_CTRL_FIFO_RESET | USER_CTRL_DMP_RESET,
+        )?;
+        self.registers.write_u8(
+            USER_CTRL_REGISTER,
+            USER_CTRL_DMP_ENABLE | USER_CTRL_FIFO_ENABLE,
+        )?;
+        self.max_angular_velocity = MAX_DMP_ANGULAR_VELOCITY;
+        self.dmp = Some(None);
+        log::info!("mpu6050 dmp enabled ({} bytes firmware)", firmware.len());
+        Ok(())
+    }
+
+    // drains the FIFO and returns the most recent packet written by the DMP
+    fn read_dmp_packet(&mut self) -> Result<DmpPacket, SensorError> {
+        let mut count = self.registers.read_u16(FIFO_COUNT_REGISTER)? as usize;
+        let overflow = self
+            .registers
+            .read_field(INT_STATUS_REGISTER, FIFO_OVERFLOW)?;
+        if count >= FIFO_SIZE || overflow != 0 {
+            // packets are no longer aligned and the last one read is stale, start over
+            log::debug!("mpu6050 fifo overflow");
+            self.registers.write_u8(
+                USER_CTRL_REGISTER,
+                USER_CTRL_DMP_ENABLE | USER_CTRL_FIFO_ENABLE | USER_CTRL_FIFO_RESET,
+            )?;
+            self.dmp = Some(None);
+            count = 0;
+        }
+        if matches!(self.dmp, Some(None)) {
+            // wait for the DMP to write its first packet, a reading is due within a period
+            let deadline = Instant::now() + DMP_PACKET_TIMEOUT;
+            while count < DMP_PACKET_SIZE && Instant::now() < deadline {
+                std::thread::sleep(DMP_POLL_INTERVAL);
+                count = self.registers.read_u16(FIFO_COUNT_REGISTER)? as usize;
+            }
+        }
+        // a partial packet being written stays in the FIFO for the next read
+        let mut packet = [0_u8; DMP_PACKET_SIZE];
+        for _ in 0..count / DMP_PACKET_SIZE {
+            self.registers
+                .read_bytes(FIFO_READ_WRITE_REGISTER, &mut packet)?;
+        }
+        if count >= DMP_PACKET_SIZE {
+            self.dmp = Some(Some(parse_dmp_packet(&packet)));
+        }
+        self.dmp
+            .clone()
+            .flatten()
+            .ok_or(SensorError::SensorGenericError(
+                "MPU6050 dmp has not produced a reading yet",
+            ))
+    }
+
+    // acceleration, temperature and angular velocity
+    fn read_registers(&mut self) -> Result<[i16; 7], SensorError> {
+        Ok(self.registers.read_i16s(READING_START_REGISTER)?)
+    }
+
+    fn calibrate(&mut self, samples: usize) -> Result<(), GenericError> {
+        let mut gyro = Vec::with_capacity(samples);
+        let mut accel = Vec::with_capacity(samples);
+        for _ in 0..samples {
+            let reading = self
+                .read_registers()
+                .map_err(|err| GenericError::Other(Box::new(err)))?;
+            gyro.push(get_angular_velocity_from_reading(
+                &reading,
+                self.max_angular_velocity,
+            ));
+            accel.push(get_linear_acceleration_from_reading(&reading));
+            std::thread::sleep(CALIBRATION_SAMPLE_INTERVAL);
+        }
+        let calibration = ImuCalibration::from_samples(&gyro, &accel)
+            .map_err(|err| GenericError::Other(Box::new(err)))?;
+        calibration
+            .store(&self.name)
+            .map_err(|err| GenericError::Other(Box::new(err)))?;
+        self.calibration = calibration;
+        Ok(())
     }
 
     pub fn close(&mut self) -> Result<(), SensorError> {
         // put the MPU in the sleep state
-        let off_data: [u8; 2] = [STANDBY_MODE_REGISTER, 64];
-        self.i2c_handle.write_i2c(self.i2c_address, &off_data)?;
+        self.registers
+            .write_field(STANDBY_MODE_REGISTER, SLEEP_MODE, 1)?;
         Ok(())
     }
 }
@@ -110,14 +367,43 @@ impl Drop for MPU6050 {
     }
 }
 
-fn get_angular_velocity_from_reading(reading: &[u8; 14]) -> Vector3 {
-    let (x_bytes, y_z_bytes) = reading[8..14].split_at(size_of::<i16>());
-    let unscaled_x = i16::from_be_bytes(x_bytes.try_into().unwrap());
-    let (y_bytes, z_bytes) = y_z_bytes.split_at(size_of::<i16>());
-    let unscaled_y = i16::from_be_bytes(y_bytes.try_into().unwrap());
-    let unscaled_z = i16::from_be_bytes(z_bytes.try_into().unwrap());
+#[cfg(feature = "assets")]
+fn load_dmp_firmware(name: &str) -> Result<Vec<u8>, SensorError> {
+    let assets = crate::common::assets::assets().ok_or(SensorError::ConfigError(
+        "MPU6050 dmp firmware requires assets",
+    ))?;
+    assets.read(name).map_err(|err| {
+        log::error!("mpu6050 dmp firmware {} not loaded: {}", name, err);
+        SensorError::ConfigError("MPU6050 dmp firmware could not be read")
+    })
+}
+
+#[cfg(not(feature = "assets"))]
+fn load_dmp_firmware(_name: &str) -> Result<Vec<u8>, SensorError> {
+    Err(SensorError::ConfigError(
+        "MPU6050 dmp requires the assets feature",
+    ))
+}
+
+// packets hold the quaternion (4 * i32), then the acceleration and angular velocity (3 * i16)
+fn parse_dmp_packet(packet: &[u8; DMP_PACKET_SIZE]) -> DmpPacket {
+    let mut quaternion = [0.0; 4];
+    for (idx, value) in quaternion.iter_mut().enumerate() {
+        let bytes = packet[idx * 4..(idx + 1) * 4].try_into().unwrap();
+        *value = f64::from(i32::from_be_bytes(bytes)) / QUATERNION_SCALE;
+    }
+    let [accel_x, accel_y, accel_z, gyro_x, gyro_y, gyro_z] = Endian::Big.i16s_from(&packet[16..]);
+    // laid out like the sensor registers, without the temperature
+    let reading = [accel_x, accel_y, accel_z, 0, gyro_x, gyro_y, gyro_z];
+    DmpPacket {
+        quaternion,
+        linear_acceleration: get_linear_acceleration_from_reading(&reading),
+        angular_velocity: get_angular_velocity_from_reading(&reading, MAX_DMP_ANGULAR_VELOCITY),
+    }
+}
 
-    let max_velocity: f64 = 250.0;
+fn get_angular_velocity_from_reading(reading: &[i16; 7], max_velocity: f64) -> Vector3 {
+    let [_, _, _, _, unscaled_x, unscaled_y, unscaled_z] = *reading;
 
     let x = f64::from(unscaled_x) * max_velocity / MAX_I16;
     let y = f64::from(unscaled_y) * max_velocity / MAX_I16;
@@ -125,12 +411,8 @@ fn get_angular_velocity_from_reading(reading: &[u8; 14]) -> Vector3 {
     Vector3 { x, y, z }
 }
 
-fn get_linear_acceleration_from_reading(reading: &[u8; 14]) -> Vector3 {
-    let (x_bytes, y_z_bytes) = reading[0..6].split_at(size_of::<i16>());
-    let unscaled_x = i16::from_be_bytes(x_bytes.try_into().unwrap());
-    let (y_bytes, z_bytes) = y_z_bytes.split_at(size_of::<i16>());
-    let unscaled_y = i16::from_be_bytes(y_bytes.try_into().unwrap());
-    let unscaled_z = i16::from_be_bytes(z_bytes.try_into().unwrap());
+fn get_linear_acceleration_from_reading(reading: &[i16; 7]) -> Vector3 {
+    let [unscaled_x, unscaled_y, unscaled_z, ..] = *reading;
 
     let max_acceleration: f64 = 2.0 * 9.81;
 
@@ -148,23 +430,38 @@ impl MovementSensor for MPU6050 {
             angular_velocity_supported: true,
             linear_acceleration_supported: true,
             compass_heading_supported: false,
+            orientation_supported: self.dmp.is_some(),
         }
     }
 
     fn get_angular_velocity(&mut self) -> Result<Vector3, SensorError> {
-        let register_write: [u8; 1] = [READING_START_REGISTER];
-        let mut result: [u8; 14] = [0; 14];
-        self.i2c_handle
-            .write_read_i2c(self.i2c_address, &register_write, &mut result)?;
-        Ok(get_angular_velocity_from_reading(&result))
+        let angular_velocity = if self.dmp.is_some() {
+            self.read_dmp_packet()?.angular_velocity
+        } else {
+            let result = self.read_registers()?;
+            get_angular_velocity_from_reading(&result, self.max_angular_velocity)
+        };
+        Ok(self.calibration.apply_gyro(angular_velocity))
     }
 
     fn get_linear_acceleration(&mut self) -> Result<Vector3, SensorError> {
-        let register_write: [u8; 1] = [READING_START_REGISTER];
-        let mut result: [u8; 14] = [0; 14];
-        self.i2c_handle
-            .write_read_i2c(self.i2c_address, &register_write, &mut result)?;
-        Ok(get_linear_acceleration_from_reading(&result))
+        let linear_acceleration = if self.dmp.is_some() {
+            self.read_dmp_packet()?.linear_acceleration
+        } else {
+            let result = self.read_registers()?;
+            get_linear_acceleration_from_reading(&result)
+        };
+        Ok(self.calibration.apply_accel(linear_acceleration))
+    }
+
+    fn get_orientation(&mut self) -> Result<OrientationVector, SensorError> {
+        if self.dmp.is_none() {
+            return Err(SensorError::SensorMethodUnimplemented("get_orientation"));
+        }
+        let [w, x, y, z] = self.read_dmp_packet()?.quaternion;
+        OrientationVector::from_quaternion(w, x, y, z).map_err(|_| {
+            SensorError::SensorGenericError("MPU6050 dmp produced an invalid quaternion")
+        })
     }
 
     fn get_position(&mut self) -> Result<super::movement_sensor::GeoPosition, SensorError> {
@@ -184,28 +481,62 @@ impl MovementSensor for MPU6050 {
     }
 }
 
-impl Status for MPU6050 {
-    fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        Ok(Some(google::protobuf::Struct {
-            fields: HashMap::new(),
-        }))
+impl DoCommand for MPU6050 {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        let calibration = match CalibrationCommand::from_command(&command) {
+            Some(CalibrationCommand::Calibrate(args)) => {
+                let samples = number_arg(&args, "samples")
+                    .map_or(DEFAULT_CALIBRATION_SAMPLES, |samples| samples as usize);
+                if samples == 0 || samples > MAX_CALIBRATION_SAMPLES {
+                    return Err(GenericError::InvalidArgument("samples"));
+                }
+                self.calibrate(samples)?;
+                &self.calibration
+            }
+            Some(CalibrationCommand::Get) => &self.calibration,
+            Some(CalibrationCommand::Clear) => {
+                ImuCalibration::clear(&self.name)
+                    .map_err(|err| GenericError::Other(Box::new(err)))?;
+                self.calibration = ImuCalibration::default();
+                &self.calibration
+            }
+            None => return Err(GenericError::MethodUnimplemented("do_command")),
+        };
+        calibration
+            .to_struct()
+            .map(Some)
+            .map_err(|err| GenericError::Other(Box::new(err)))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{get_angular_velocity_from_reading, get_linear_acceleration_from_reading};
+    use std::sync::{Arc, Mutex};
+
+    use super::{
+        get_angular_velocity_from_reading, get_linear_acceleration_from_reading, parse_dmp_packet,
+        DMP_PACKET_SIZE, MPU6050,
+    };
+    use crate::common::calibration::{Calibration, ImuCalibration, STANDARD_GRAVITY};
+    use crate::common::generic::DoCommand;
+    use crate::common::i2c::{Endian, FakeI2CHandle};
+    use crate::common::movement_sensor::MovementSensor;
+    use crate::common::struct_builder::StructBuilder;
 
     #[test_log::test]
     fn test_read_linear_acceleration() {
         let reading: [u8; 14] = [64, 0, 32, 0, 16, 0, 0, 0, 0, 0, 0, 0, 0, 0];
-        let lin_acc = get_linear_acceleration_from_reading(&reading);
+        let lin_acc = get_linear_acceleration_from_reading(&Endian::Big.i16s_from(&reading));
         assert_eq!(lin_acc.x, 9.81);
         assert_eq!(lin_acc.y, 4.905);
         assert_eq!(lin_acc.z, 2.4525);
 
         let reading: [u8; 14] = [64, 0, 130, 0, 16, 0, 0, 0, 0, 0, 0, 0, 0, 0];
-        let lin_acc = get_linear_acceleration_from_reading(&reading);
+        let lin_acc = get_linear_acceleration_from_reading(&Endian::Big.i16s_from(&reading));
 
         assert_eq!(lin_acc.x, 9.81);
         assert!((lin_acc.y - -19.3134375).abs() < 0.000001);
@@ -215,15 +546,60 @@ mod tests {
     #[test_log::test]
     fn test_read_angular_velocity() {
         let reading: [u8; 14] = [0, 0, 0, 0, 0, 0, 0, 0, 64, 0, 32, 0, 16, 0];
-        let ang_vel = get_angular_velocity_from_reading(&reading);
+        let ang_vel = get_angular_velocity_from_reading(&Endian::Big.i16s_from(&reading), 250.0);
         assert_eq!(ang_vel.x, 125.0);
         assert_eq!(ang_vel.y, 62.5);
         assert_eq!(ang_vel.z, 31.25);
 
         let reading: [u8; 14] = [0, 0, 0, 0, 0, 0, 0, 0, 64, 0, 130, 0, 16, 0];
-        let ang_vel = get_angular_velocity_from_reading(&reading);
+        let ang_vel = get_angular_velocity_from_reading(&Endian::Big.i16s_from(&reading), 250.0);
         assert_eq!(ang_vel.x, 125.0);
         assert_eq!(ang_vel.y, -246.09375);
         assert_eq!(ang_vel.z, 31.25);
     }
+
+    #[test_log::test]
+    fn test_parse_dmp_packet() {
+        let mut packet = [0_u8; DMP_PACKET_SIZE];
+        // w = 0.5, x = -0.5
+        packet[0..4].copy_from_slice(&(1_i32 << 29).to_be_bytes());
+        packet[4..8].copy_from_slice(&(-(1_i32 << 29)).to_be_bytes());
+        packet[16..18].copy_from_slice(&16384_i16.to_be_bytes());
+        packet[26..28].copy_from_slice(&(-8192_i16).to_be_bytes());
+        let packet = parse_dmp_packet(&packet);
+        assert_eq!(packet.quaternion, [0.5, -0.5, 0.0, 0.0]);
+        assert_eq!(packet.linear_acceleration.x, 9.81);
+        assert_eq!(packet.linear_acceleration.z, 0.0);
+        assert_eq!(packet.angular_velocity.z, -500.0);
+    }
+
+    #[test_log::test]
+    fn test_calibration() {
+        let i2c = Arc::new(Mutex::new(FakeI2CHandle::new("i2c".to_string())));
+        let mut mpu = MPU6050::new(i2c, 104)
+            .unwrap()
+            .with_persisted_calibration("mpu-calibration-test");
+        // the fake bus reads back a constant acceleration on x
+        assert!(mpu.get_linear_acceleration().unwrap().x > 1.0);
+
+        let command = StructBuilder::new()
+            .sub("calibrate", StructBuilder::new().field("samples", 3.0))
+            .build();
+        let answer = mpu.do_command(Some(command)).unwrap().unwrap();
+        assert!(answer.fields.contains_key("gyro_bias"));
+        let acceleration = mpu.get_linear_acceleration().unwrap();
+        assert!(acceleration.x.abs() < 1e-9);
+        assert!((acceleration.z - STANDARD_GRAVITY).abs() < 1e-9);
+
+        // the calibration is persisted for the next instance of the component
+        let stored = ImuCalibration::load("mpu-calibration-test").unwrap();
+        assert_eq!(stored.as_ref(), Some(&mpu.calibration));
+
+        let command = StructBuilder::new()
+            .sub("clear_calibration", StructBuilder::new())
+            .build();
+        mpu.do_command(Some(command)).unwrap();
+        assert_eq!(ImuCalibration::load("mpu-calibration-test").unwrap(), None);
+        assert!(mpu.get_linear_acceleration().unwrap().x > 1.0);
+    }
 }
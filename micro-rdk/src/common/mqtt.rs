@@ -0,0 +1,328 @@
+//! Publishes captured data to an MQTT broker, for users integrating with an existing IoT
+//! backend alongside or instead of app.viam.com. Enabled with the `mqtt` feature and configured
+//! through the `mqtt` attribute of the data manager service:
+//!
+//! ```json
+//! {
+//!     "sync_interval_mins": 1,
+//!     "mqtt": {
+//!         "url": "mqtts://broker.example.com:8883",
+//!         "topic_prefix": "greenhouse",
+//!         "client_id": "esp32-greenhouse-1",
+//!         "username": "user",
+//!         "password": "pass",
+//!         "qos": 1
+//!     }
+//! }
+//! ```
+//!
+//! Readings are published at every sync to `<topic_prefix>/<part id>/<component name>/<method>`
+//! as a JSON object:
+//!
+//! ```json
+//! {
+//!     "component_type": "rdk:component:sensor",
+//!     "time_requested_ms": 1700000000000,
+//!     "time_received_ms": 1700000000012,
+//!     "readings": {"temperature": 21.5}
+//! }
+//! ```
+//!
+//! Binary captures (e.g. camera frames) are published as is to the same topic followed by
+//! `/binary`. `topic_prefix` defaults to `micro-rdk`, `client_id` to the part id and `qos` to 1
+//! (at least once). TLS is used with `mqtts://` urls.
+
+use serde_json::json;
+use thiserror::Error;
+
+use crate::google::protobuf::{value::Kind, Timestamp, Value};
+use crate::proto::app::data_sync::v1::{sensor_data::Data, DataCaptureUploadRequest};
+use crate::proto::app::v1::ConfigResponse;
+
+use super::data_manager::{DataUploadError, DataUploader};
+use super::secret::SecretString;
+use super::struct_builder::value_to_json;
+
+#[derive(Debug, Error)]
+pub enum MqttError {
+    #[error("mqtt config error: {0}")]
+    ConfigError(&'static str),
+    #[error("couldn't connect to the broker: {0}")]
+    ConnectionError(String),
+    #[error("couldn't publish to {0}: {1}")]
+    PublishError(String, String),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MqttQos {
+    AtMostOnce,
+    #[default]
+    AtLeastOnce,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MqttConfig {
+    pub url: String,
+    pub topic_prefix: String,
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<SecretString>,
+    pub qos: MqttQos,
+}
+
+fn string_attribute(
+    fields: &std::collections::HashMap<String, Value>,
+    key: &str,
+) -> Result<Option<String>, MqttError> {
+    match fields.get(key).and_then(|v| v.kind.as_ref()) {
+        None => Ok(None),
+        Some(Kind::StringValue(v)) => Ok(Some(v.clone())),
+        Some(_) => Err(MqttError::ConfigError("string attribute expected")),
+    }
+}
+
+impl MqttConfig {
+    /// Reads the `mqtt` attribute of the data manager service, `None` when it isn't set
+    pub fn from_config(cfg: &ConfigResponse) -> Result<Option<Self>, MqttError> {
+        let mqtt = cfg
+            .config
+            .as_ref()
+            .and_then(|cfg| {
+                cfg.services
+                    .iter()
+                    .find(|svc_cfg| svc_cfg.r#type == *"data_manager")
+            })
+            .and_then(|data_cfg| data_cfg.attributes.as_ref())
+            .and_then(|attrs| attrs.fields.get("mqtt"));
+        let fields = match mqtt.and_then(|v| v.kind.as_ref()) {
+            None => return Ok(None),
+            Some(Kind::StructValue(mqtt)) => &mqtt.fields,
+            Some(_) => return Err(MqttError::ConfigError("`mqtt` should be a struct")),
+        };
+        let url =
+            string_attribute(fields, "url")?.ok_or(MqttError::ConfigError("url is missing"))?;
+        if !url.starts_with("mqtt://") && !url.starts_with("mqtts://") {
+            return Err(MqttError::ConfigError(
+                "url should start with mqtt:// or mqtts://",
+            ));
+        }
+        let qos = match fields.get("qos").and_then(|v| v.kind.as_ref()) {
+            None => MqttQos::default(),
+            Some(Kind::NumberValue(qos)) if *qos == 0.0 => MqttQos::AtMostOnce,
+            Some(Kind::NumberValue(qos)) if *qos == 1.0 => MqttQos::AtLeastOnce,
+            Some(_) => return Err(MqttError::ConfigError("qos should be 0 or 1")),
+        };
+        Ok(Some(Self {
+            url,
+            topic_prefix: string_attribute(fields, "topic_prefix")?
+                .unwrap_or_else(|| "micro-rdk".to_string()),
+            client_id: string_attribute(fields, "client_id")?,
+            username: string_attribute(fields, "username")?,
+            password: string_attribute(fields, "password")?.map(SecretString::from),
+            qos,
+        }))
+    }
+}
+
+/// Connection to a broker, implemented for each platform
+pub trait MqttPublisher {
+    fn publish(&mut self, topic: &str, qos: MqttQos, payload: &[u8]) -> Result<(), MqttError>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+fn timestamp_ms(ts: Option<&Timestamp>) -> Option<i64> {
+    ts.map(|ts| ts.seconds * 1000 + (ts.nanos / 1_000_000) as i64)
+}
+
+/// Turns an upload request into the messages published for it
+pub fn mqtt_messages(topic_prefix: &str, request: &DataCaptureUploadRequest) -> Vec<MqttMessage> {
+    let metadata = request.metadata.clone().unwrap_or_default();
+    let topic = format!(
+        "{}/{}/{}/{}",
+        topic_prefix, metadata.part_id, metadata.component_name, metadata.method_name
+    );
+    request
+        .sensor_contents
+        .iter()
+        .filter_map(|data| match data.data.as_ref()? {
+            Data::Binary(bytes) => Some(MqttMessage {
+                topic: format!("{}/binary", topic),
+                payload: bytes.clone(),
+            }),
+            Data::Struct(readings) => {
+                let sensor_metadata = data.metadata.clone().unwrap_or_default();
+                let readings: serde_json::Map<String, serde_json::Value> = readings
+                    .fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), value_to_json(v)))
+                    .collect();
+                let payload = json!({
+                    "component_type": metadata.component_type,
+                    "time_requested_ms": timestamp_ms(sensor_metadata.time_requested.as_ref()),
+                    "time_received_ms": timestamp_ms(sensor_metadata.time_received.as_ref()),
+                    "readings": readings,
+                });
+                Some(MqttMessage {
+                    topic: topic.clone(),
+                    payload: payload.to_string().into_bytes(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Uploads captured data by publishing it through an [MqttPublisher]
+pub struct MqttUploader<P> {
+    publisher: P,
+    topic_prefix: String,
+    qos: MqttQos,
+}
+
+impl<P: MqttPublisher> MqttUploader<P> {
+    pub fn new(publisher: P, config: &MqttConfig) -> Self {
+        Self {
+            publisher,
+            topic_prefix: config.topic_prefix.clone(),
+            qos: config.qos,
+        }
+    }
+}
+
+impl<P: MqttPublisher> DataUploader for MqttUploader<P> {
+    fn upload(&mut self, requests: &[DataCaptureUploadRequest]) -> Result<(), DataUploadError> {
+        for request in requests {
+            for message in mqtt_messages(&self.topic_prefix, request) {
+                self.publisher
+                    .publish(&message.topic, self.qos, &message.payload)
+                    .map_err(|err| DataUploadError(err.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{mqtt_messages, MqttConfig, MqttError, MqttPublisher, MqttQos, MqttUploader};
+    use crate::common::data_manager::DataUploader;
+    use crate::google::protobuf::{value::Kind, Struct, Timestamp, Value};
+    use crate::proto::app::data_sync::v1::{
+        sensor_data::Data, DataCaptureUploadRequest, SensorData, SensorMetadata, UploadMetadata,
+    };
+    use crate::proto::app::v1::{ConfigResponse, RobotConfig, ServiceConfig};
+
+    fn string(v: &str) -> Value {
+        Value {
+            kind: Some(Kind::StringValue(v.to_string())),
+        }
+    }
+
+    #[test_log::test]
+    fn test_mqtt_config() {
+        let config = |mqtt: HashMap<String, Value>| ConfigResponse {
+            config: Some(RobotConfig {
+                services: vec![ServiceConfig {
+                    r#type: "data_manager".to_string(),
+                    attributes: Some(Struct {
+                        fields: HashMap::from([(
+                            "mqtt".to_string(),
+                            Value {
+                                kind: Some(Kind::StructValue(Struct { fields: mqtt })),
+                            },
+                        )]),
+                    }),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+        };
+        let cfg = MqttConfig::from_config(&config(HashMap::from([
+            ("url".to_string(), string("mqtts://broker:8883")),
+            ("password".to_string(), string("hunter2")),
+        ])))
+        .unwrap()
+        .unwrap();
+        assert_eq!(cfg.topic_prefix, "micro-rdk");
+        assert_eq!(cfg.qos, MqttQos::AtLeastOnce);
+        assert!(cfg.client_id.is_none());
+        assert_eq!(cfg.password.as_ref().map(|p| p.expose()), Some("hunter2"));
+        assert!(!format!("{:?}", cfg).contains("hunter2"));
+
+        assert!(matches!(
+            MqttConfig::from_config(&config(HashMap::from([(
+                "url".to_string(),
+                string("http://broker")
+            )]))),
+            Err(MqttError::ConfigError(_))
+        ));
+        assert!(MqttConfig::from_config(&ConfigResponse::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[derive(Default)]
+    struct TestPublisher(Vec<(String, Vec<u8>)>);
+
+    impl MqttPublisher for TestPublisher {
+        fn publish(&mut self, topic: &str, _: MqttQos, payload: &[u8]) -> Result<(), MqttError> {
+            self.0.push((topic.to_string(), payload.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[test_log::test]
+    fn test_mqtt_messages() {
+        let request = DataCaptureUploadRequest {
+            metadata: Some(UploadMetadata {
+                part_id: "part".to_string(),
+                component_type: "rdk:component:sensor".to_string(),
+                component_name: "thermo".to_string(),
+                method_name: "Readings".to_string(),
+                ..Default::default()
+            }),
+            sensor_contents: vec![SensorData {
+                metadata: Some(SensorMetadata {
+                    time_requested: Some(Timestamp {
+                        seconds: 10,
+                        nanos: 5_000_000,
+                    }),
+                    time_received: None,
+                }),
+                data: Some(Data::Struct(Struct {
+                    fields: HashMap::from([(
+                        "temperature".to_string(),
+                        Value {
+                            kind: Some(Kind::NumberValue(21.5)),
+                        },
+                    )]),
+                })),
+            }],
+        };
+        let messages = mqtt_messages("greenhouse", &request);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].topic, "greenhouse/part/thermo/Readings");
+        let payload: serde_json::Value = serde_json::from_slice(&messages[0].payload).unwrap();
+        assert_eq!(payload["readings"]["temperature"], 21.5);
+        assert_eq!(payload["time_requested_ms"], 10005);
+        assert!(payload["time_received_ms"].is_null());
+
+        let config = MqttConfig {
+            url: "mqtt://broker".to_string(),
+            topic_prefix: "greenhouse".to_string(),
+            client_id: None,
+            username: None,
+            password: None,
+            qos: MqttQos::AtLeastOnce,
+        };
+        let mut uploader = MqttUploader::new(TestPublisher::default(), &config);
+        uploader.upload(&[request]).unwrap();
+        assert_eq!(uploader.publisher.0.len(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+//! Layout of the credentials written to the NVS partition by `micro-rdk-installer` and read
+//! back by the firmware.
+//!
+//! The installer stores the version of the layout it wrote under [SCHEMA_VERSION_KEY]. Layouts
+//! written before the key existed are treated as version 1. [migrate] checks a stored layout
+//! against the version supported by the firmware and returns the entries to write to bring it
+//! up to date, or an error naming what needs fixing when it can't.
+
+use thiserror::Error;
+
+pub const SCHEMA_VERSION_KEY: &str = "SCHEMA_VERSION";
+/// Version of the layout written by the installer, it must match `NVS_SCHEMA_VERSION` in
+/// micro-rdk-installer
+pub const SCHEMA_VERSION: u32 = 2;
+/// Address of app used by layouts that predate the `APP_ADDRESS` key
+pub const DEFAULT_APP_ADDRESS: &str = "https://app.viam.com:443";
+
+/// Keys present in every layout
+const REQUIRED_KEYS: &[&str] = &[
+    "WIFI_SSID",
+    "WIFI_PASSWORD",
+    "ROBOT_ID",
+    "ROBOT_SECRET",
+    "DTLS_CERT_FP",
+    "SRV_DER_KEY",
+    "SRV_PEM_CHAIN",
+    "CA_CRT",
+    "DTLS_KEY_PAIR",
+    "ROBOT_DTLS_CERT",
+];
+
+/// Keys that became mandatory with version 2, version 1 layouts get these defaults on migration
+const V2_KEYS: &[(&str, &str)] = &[
+    ("APP_ADDRESS", DEFAULT_APP_ADDRESS),
+    ("ROBOT_NAME", ""),
+    ("LOCAL_FQDN", ""),
+    ("FQDN", ""),
+];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum NvsSchemaError {
+    #[error("invalid NVS schema version `{0}`")]
+    InvalidVersion(String),
+    #[error(
+        "NVS data was written with schema version {0} but this firmware supports up to version {}, update the firmware or re-run a matching installer",
+        SCHEMA_VERSION
+    )]
+    UnsupportedVersion(u32),
+    #[error("NVS data (schema version {version}) is missing `{key}`, re-run the installer")]
+    MissingKey { key: &'static str, version: u32 },
+}
+
+/// Read access to the NVS namespace holding the credentials
+pub trait NvsSource {
+    fn contains(&self, key: &str) -> bool;
+    fn get_string(&self, key: &str) -> Option<String>;
+}
+
+/// Version of the layout stored in `source`
+pub fn schema_version(source: &impl NvsSource) -> Result<u32, NvsSchemaError> {
+    match source.get_string(SCHEMA_VERSION_KEY) {
+        None => Ok(1),
+        Some(version) => version
+            .trim_matches(char::from(0))
+            .parse::<u32>()
+            .ok()
+            .filter(|v| *v > 0)
+            .ok_or(NvsSchemaError::InvalidVersion(version)),
+    }
+}
+
+/// Checks the layout stored in `source` and returns the `(key, value)` string entries that must
+/// be written to migrate it to [SCHEMA_VERSION], the list is empty for an up to date layout
+pub fn migrate(source: &impl NvsSource) -> Result<Vec<(&'static str, String)>, NvsSchemaError> {
+    let version = schema_version(source)?;
+    if version > SCHEMA_VERSION {
+        return Err(NvsSchemaError::UnsupportedVersion(version));
+    }
+    if let Some(key) = REQUIRED_KEYS
+        .iter()
+        .copied()
+        .find(|key| !source.contains(key))
+    {
+        return Err(NvsSchemaError::MissingKey { key, version });
+    }
+    let mut entries = vec![];
+    for &(key, default) in V2_KEYS {
+        if source.contains(key) {
+            continue;
+        }
+        if version >= 2 {
+            return Err(NvsSchemaError::MissingKey { key, version });
+        }
+        entries.push((key, default.to_string()));
+    }
+    if version < SCHEMA_VERSION {
+        entries.push((SCHEMA_VERSION_KEY, SCHEMA_VERSION.to_string()));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{
+        migrate, schema_version, NvsSchemaError, NvsSource, DEFAULT_APP_ADDRESS, REQUIRED_KEYS,
+        SCHEMA_VERSION, SCHEMA_VERSION_KEY, V2_KEYS,
+    };
+
+    struct MemoryNvs(HashMap<String, String>);
+
+    impl NvsSource for MemoryNvs {
+        fn contains(&self, key: &str) -> bool {
+            self.0.contains_key(key)
+        }
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+    }
+
+    fn v1_layout() -> MemoryNvs {
+        MemoryNvs(
+            REQUIRED_KEYS
+                .iter()
+                .map(|k| (k.to_string(), "value".to_string()))
+                .collect(),
+        )
+    }
+
+    #[test_log::test]
+    fn test_migrate_v1_layout() {
+        let mut nvs = v1_layout();
+        assert_eq!(schema_version(&nvs), Ok(1));
+        let entries = migrate(&nvs).unwrap();
+        assert!(entries.contains(&("APP_ADDRESS", DEFAULT_APP_ADDRESS.to_string())));
+        assert!(entries.contains(&(SCHEMA_VERSION_KEY, SCHEMA_VERSION.to_string())));
+
+        for (k, v) in entries {
+            nvs.0.insert(k.to_string(), v);
+        }
+        assert_eq!(schema_version(&nvs), Ok(SCHEMA_VERSION));
+        assert!(migrate(&nvs).unwrap().is_empty());
+    }
+
+    #[test_log::test]
+    fn test_schema_errors() {
+        let mut nvs = v1_layout();
+        nvs.0.remove("ROBOT_SECRET");
+        assert_eq!(
+            migrate(&nvs),
+            Err(NvsSchemaError::MissingKey {
+                key: "ROBOT_SECRET",
+                version: 1
+            })
+        );
+
+        let mut nvs = v1_layout();
+        nvs.0
+            .insert(SCHEMA_VERSION_KEY.to_string(), "2\0".to_string());
+        for (k, _) in V2_KEYS.iter().skip(1) {
+            nvs.0.insert(k.to_string(), "".to_string());
+        }
+        assert_eq!(
+            migrate(&nvs),
+            Err(NvsSchemaError::MissingKey {
+                key: "APP_ADDRESS",
+                version: 2
+            })
+        );
+
+        nvs.0
+            .insert(SCHEMA_VERSION_KEY.to_string(), "3".to_string());
+        assert_eq!(migrate(&nvs), Err(NvsSchemaError::UnsupportedVersion(3)));
+
+        nvs.0
+            .insert(SCHEMA_VERSION_KEY.to_string(), "two".to_string());
+        assert!(matches!(
+            migrate(&nvs),
+            Err(NvsSchemaError::InvalidVersion(_))
+        ));
+    }
+}
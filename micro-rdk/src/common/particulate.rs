@@ -0,0 +1,426 @@
+//! Package particulate implements the sensor interface for particulate matter sensors streaming
+//! binary frames over a serial port: the Plantower PMS5003 family (`pms5003` model, also
+//! PMS7003 and PMSA003) and the Nova Fitness SDS011 (`sds011` model). See `esp32::particulate`
+//! for the serial port configuration.
+//!
+//! ```json
+//! {
+//!     "warm_up_secs": 30
+//! }
+//! ```
+//!
+//! Both sensors report a measurement about every second, the serial port is read every 500ms by a
+//! task running alongside the robot so the port never overflows between two queries. The readings
+//! are the concentrations of the last valid frame in µg/m³: `pm2_5` and `pm10`, plus `pm1_0` and the `particles_*um` counts
+//! of particles per 0.1L of air larger than 0.3, 0.5, 1, 2.5, 5 and 10µm for the PMS5003. The
+//! PMS5003 concentrations are the ones corrected for atmospheric conditions.
+//!
+//! The fan and laser of the sensors wear out, putting them to sleep between measurements
+//! extends their life. The sensor is put to sleep with the `{"sleep": {}}` command and woken up
+//! with `{"wake_up": {}}`. Readings fail while it sleeps and for `warm_up_secs` (30 by default,
+//! as recommended by both manufacturers) after it woke up or the sensor was built, until the
+//! airflow is stable. The bytes received before the sensor woke up are discarded and only frames
+//! received after the warm up are reported.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::config::{AttributeError, ConfigType};
+use super::generic::{DoCommand, GenericError};
+use super::periodic::spawn_periodic;
+use super::sensor::{GenericReadingsResult, Readings, Sensor, SensorError, SensorType};
+use super::status::{Status, StatusError};
+use super::struct_builder::StructBuilder;
+use super::uart::UartType;
+use crate::google::protobuf::Struct;
+
+const DEFAULT_WARM_UP: Duration = Duration::from_secs(30);
+const READ_CHUNK: usize = 64;
+// the sensors send about 32 bytes per second, far from filling the input buffer of the port
+const READ_PERIOD: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticulateProtocol {
+    Pms5003,
+    Sds011,
+}
+
+const PMS5003_FRAME_LEN: usize = 32;
+const SDS011_FRAME_LEN: usize = 10;
+
+impl ParticulateProtocol {
+    fn frame_len(&self) -> usize {
+        match self {
+            Self::Pms5003 => PMS5003_FRAME_LEN,
+            Self::Sds011 => SDS011_FRAME_LEN,
+        }
+    }
+
+    fn header(&self) -> [u8; 2] {
+        match self {
+            Self::Pms5003 => [0x42, 0x4D],
+            Self::Sds011 => [0xAA, 0xC0],
+        }
+    }
+
+    /// Decodes a frame starting with the header, `None` when its checksum is wrong
+    fn parse_frame(&self, frame: &[u8]) -> Option<ParticulateMeasurement> {
+        match self {
+            Self::Pms5003 => {
+                let word = |index: usize| u16::from_be_bytes([frame[index], frame[index + 1]]);
+                let sum = frame[..30]
+                    .iter()
+                    .fold(0_u16, |sum, byte| sum.wrapping_add(u16::from(*byte)));
+                if word(2) != 28 || word(30) != sum {
+                    return None;
+                }
+                let mut particles = [0; 6];
+                for (i, count) in particles.iter_mut().enumerate() {
+                    *count = word(16 + 2 * i);
+                }
+                Some(ParticulateMeasurement {
+                    pm1_0: Some(f64::from(word(10))),
+                    pm2_5: f64::from(word(12)),
+                    pm10: f64::from(word(14)),
+                    particles: Some(particles),
+                })
+            }
+            Self::Sds011 => {
+                let sum = frame[2..8]
+                    .iter()
+                    .fold(0_u8, |sum, byte| sum.wrapping_add(*byte));
+                if frame[8] != sum || frame[9] != 0xAB {
+                    return None;
+                }
+                let tenths = |index: usize| {
+                    f64::from(u16::from_le_bytes([frame[index], frame[index + 1]])) / 10.0
+                };
+                Some(ParticulateMeasurement {
+                    pm1_0: None,
+                    pm2_5: tenths(2),
+                    pm10: tenths(4),
+                    particles: None,
+                })
+            }
+        }
+    }
+
+    /// Command putting the sensor to sleep or waking it up
+    fn sleep_command(&self, sleep: bool) -> Vec<u8> {
+        match self {
+            Self::Pms5003 => {
+                let mut command = vec![0x42, 0x4D, 0xE4, 0x00, if sleep { 0 } else { 1 }];
+                let sum = command
+                    .iter()
+                    .fold(0_u16, |sum, byte| sum + u16::from(*byte));
+                command.extend_from_slice(&sum.to_be_bytes());
+                command
+            }
+            Self::Sds011 => {
+                let mut command = vec![0xAA, 0xB4, 0x06, 0x01, if sleep { 0 } else { 1 }];
+                command.extend_from_slice(&[0; 10]);
+                // to any sensor
+                command.extend_from_slice(&[0xFF, 0xFF]);
+                let sum = command[2..]
+                    .iter()
+                    .fold(0_u8, |sum, byte| sum.wrapping_add(*byte));
+                command.extend_from_slice(&[sum, 0xAB]);
+                command
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticulateMeasurement {
+    pub pm1_0: Option<f64>,
+    pub pm2_5: f64,
+    pub pm10: f64,
+    // counts of particles larger than 0.3, 0.5, 1, 2.5, 5 and 10µm
+    pub particles: Option<[u16; 6]>,
+}
+
+const PARTICLE_COUNT_NAMES: [&str; 6] = [
+    "particles_0_3um",
+    "particles_0_5um",
+    "particles_1_0um",
+    "particles_2_5um",
+    "particles_5_0um",
+    "particles_10um",
+];
+
+/// Consumes the complete frames at the start of `buffer` and returns the last valid one,
+/// skipping the bytes preceding a header
+fn parse_frames(
+    protocol: ParticulateProtocol,
+    buffer: &mut Vec<u8>,
+) -> Option<ParticulateMeasurement> {
+    let header = protocol.header();
+    let frame_len = protocol.frame_len();
+    let mut measurement = None;
+    let mut start = 0;
+    while buffer.len() - start >= frame_len {
+        if buffer[start..start + 2] != header {
+            start += 1;
+            continue;
+        }
+        match protocol.parse_frame(&buffer[start..start + frame_len]) {
+            Some(parsed) => {
+                measurement = Some(parsed);
+                start += frame_len;
+            }
+            // a header within the data of a frame, or a corrupted frame
+            None => start += 1,
+        }
+    }
+    buffer.drain(..start);
+    measurement
+}
+
+pub struct ParticulateSensor {
+    uart: UartType,
+    protocol: ParticulateProtocol,
+    buffer: Vec<u8>,
+    // the last measurement and when it was received
+    measurement: Option<(ParticulateMeasurement, Instant)>,
+    warm_up: Duration,
+    // None while sleeping
+    awake_since: Option<Instant>,
+}
+
+impl ParticulateSensor {
+    pub fn new(uart: UartType, protocol: ParticulateProtocol, warm_up: Duration) -> Self {
+        Self {
+            uart,
+            protocol,
+            buffer: Vec::with_capacity(2 * protocol.frame_len()),
+            measurement: None,
+            warm_up,
+            awake_since: Some(Instant::now()),
+        }
+    }
+
+    /// Builds the sensor from the attributes of the `pms5003` and `sds011` models, once the
+    /// platform has opened the serial port the sensor is connected to
+    pub fn from_uart_and_config(
+        uart: UartType,
+        protocol: ParticulateProtocol,
+        cfg: ConfigType,
+    ) -> Result<SensorType, SensorError> {
+        let warm_up = match cfg.get_attribute::<f64>("warm_up_secs") {
+            Ok(secs) if secs >= 0.0 => Duration::from_secs_f64(secs),
+            Err(AttributeError::KeyNotFound(_)) => DEFAULT_WARM_UP,
+            _ => return Err(SensorError::ConfigError("invalid warm_up_secs")),
+        };
+        let sensor = Arc::new(Mutex::new(Self::new(uart, protocol, warm_up)));
+        spawn_periodic(&sensor, READ_PERIOD, |sensor: &mut Self| {
+            if let Err(err) = sensor.update() {
+                log::debug!("particulate: couldn't read the serial port: {}", err);
+            }
+        });
+        Ok(sensor)
+    }
+
+    /// Puts the sensor to sleep, or wakes it up and restarts the warm up period
+    pub fn set_sleep(&mut self, sleep: bool) -> Result<(), SensorError> {
+        let mut uart = self.uart.lock().unwrap();
+        uart.write(&self.protocol.sleep_command(sleep))?;
+        if !sleep {
+            // frames sent before the sensor went to sleep
+            uart.clear_input()?;
+            self.buffer.clear();
+        }
+        self.measurement = None;
+        self.awake_since = (!sleep).then(Instant::now);
+        Ok(())
+    }
+
+    // reads the frames received since the last call
+    fn update(&mut self) -> Result<(), SensorError> {
+        let mut chunk = [0_u8; READ_CHUNK];
+        loop {
+            let len = self.uart.lock().unwrap().read_available(&mut chunk)?;
+            if len == 0 {
+                break;
+            }
+            self.buffer.extend_from_slice(&chunk[..len]);
+            if let Some(measurement) = parse_frames(self.protocol, &mut self.buffer) {
+                self.measurement = Some((measurement, Instant::now()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the last measurement, failing while the sensor sleeps or warms up
+    pub fn measurement(&mut self) -> Result<ParticulateMeasurement, SensorError> {
+        self.update()?;
+        let awake_since = self
+            .awake_since
+            .ok_or(SensorError::SensorGenericError("the sensor is asleep"))?;
+        if awake_since.elapsed() < self.warm_up {
+            return Err(SensorError::SensorGenericError("the sensor is warming up"));
+        }
+        match self.measurement {
+            Some((measurement, received)) if received >= awake_since + self.warm_up => {
+                Ok(measurement)
+            }
+            _ => Err(SensorError::SensorGenericError("no measurement received")),
+        }
+    }
+}
+
+impl Sensor for ParticulateSensor {}
+
+impl Readings for ParticulateSensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let measurement = self.measurement()?;
+        let mut readings = StructBuilder::with_capacity(9)
+            .field("pm2_5", measurement.pm2_5)
+            .field("pm10", measurement.pm10);
+        if let Some(pm1_0) = measurement.pm1_0 {
+            readings = readings.field("pm1_0", pm1_0);
+        }
+        if let Some(particles) = measurement.particles {
+            for (name, count) in PARTICLE_COUNT_NAMES.iter().zip(particles) {
+                readings = readings.field(name, count);
+            }
+        }
+        Ok(readings.into_fields())
+    }
+}
+
+impl Status for ParticulateSensor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(
+            StructBuilder::with_capacity(1)
+                .field("sleeping", self.awake_since.is_none())
+                .build(),
+        ))
+    }
+}
+
+impl DoCommand for ParticulateSensor {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        let sleep = if command.fields.contains_key("sleep") {
+            true
+        } else if command.fields.contains_key("wake_up") {
+            false
+        } else {
+            return Err(GenericError::MethodUnimplemented("do_command"));
+        };
+        self.set_sleep(sleep)
+            .map_err(|err| GenericError::Other(Box::new(err)))?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_frames, ParticulateProtocol, ParticulateSensor};
+    use crate::common::generic::DoCommand;
+    use crate::common::sensor::Readings;
+    use crate::common::struct_builder::StructBuilder;
+    use crate::common::uart::FakeUart;
+    use crate::google::protobuf::value::Kind;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn pms5003_frame(pm2_5: u16) -> Vec<u8> {
+        let mut frame = vec![0x42, 0x4D, 0, 28];
+        for word in [5, pm2_5, 12, 4, pm2_5, 11, 900, 300, 60, 8, 2, 1, 0] {
+            frame.extend_from_slice(&u16::to_be_bytes(word));
+        }
+        let sum = frame.iter().map(|byte| u16::from(*byte)).sum::<u16>();
+        frame.extend_from_slice(&sum.to_be_bytes());
+        frame
+    }
+
+    #[test_log::test]
+    fn test_parse_frames() {
+        // garbage, a frame and the start of the next one
+        let mut buffer = vec![0x4D, 0x00];
+        buffer.extend(pms5003_frame(9));
+        buffer.extend(&pms5003_frame(10)[..12]);
+        let measurement = parse_frames(ParticulateProtocol::Pms5003, &mut buffer).unwrap();
+        assert_eq!(measurement.pm1_0, Some(4.0));
+        assert_eq!(measurement.pm2_5, 9.0);
+        assert_eq!(measurement.pm10, 11.0);
+        assert_eq!(measurement.particles, Some([900, 300, 60, 8, 2, 1]));
+        assert_eq!(buffer.len(), 12);
+
+        // a corrupted frame
+        let mut frame = pms5003_frame(10);
+        frame[12] ^= 0xFF;
+        let mut buffer = frame;
+        assert!(parse_frames(ParticulateProtocol::Pms5003, &mut buffer).is_none());
+
+        // example of the SDS011 datasheet
+        let mut buffer = vec![0xAA, 0xC0, 0xD4, 0x04, 0x3A, 0x0A, 0xA1, 0x60, 0x1D, 0xAB];
+        let measurement = parse_frames(ParticulateProtocol::Sds011, &mut buffer).unwrap();
+        assert_eq!(measurement.pm2_5, 123.6);
+        assert_eq!(measurement.pm10, 261.8);
+        assert!(buffer.is_empty());
+    }
+
+    #[test_log::test]
+    fn test_sleep_commands() {
+        assert_eq!(
+            ParticulateProtocol::Pms5003.sleep_command(true),
+            [0x42, 0x4D, 0xE4, 0x00, 0x00, 0x01, 0x73]
+        );
+        assert_eq!(
+            ParticulateProtocol::Pms5003.sleep_command(false),
+            [0x42, 0x4D, 0xE4, 0x00, 0x01, 0x01, 0x74]
+        );
+        let command = ParticulateProtocol::Sds011.sleep_command(true);
+        assert_eq!(command.len(), 19);
+        assert_eq!(command[17..], [0x05, 0xAB]);
+    }
+
+    #[test_log::test]
+    fn test_particulate_sensor() {
+        let uart = Arc::new(Mutex::new(FakeUart::default()));
+        let mut sensor =
+            ParticulateSensor::new(uart.clone(), ParticulateProtocol::Pms5003, Duration::ZERO);
+        assert!(sensor.get_generic_readings().is_err());
+
+        uart.lock().unwrap().replies.extend(pms5003_frame(9));
+        let readings = sensor.get_generic_readings().unwrap();
+        assert_eq!(
+            readings.get("pm2_5").and_then(|v| v.kind.clone()),
+            Some(Kind::NumberValue(9.0))
+        );
+        assert_eq!(
+            readings.get("particles_10um").and_then(|v| v.kind.clone()),
+            Some(Kind::NumberValue(1.0))
+        );
+
+        let command = StructBuilder::new()
+            .sub("sleep", StructBuilder::new())
+            .build();
+        assert!(sensor.do_command(Some(command)).unwrap().is_none());
+        assert_eq!(
+            uart.lock().unwrap().written,
+            [0x42, 0x4D, 0xE4, 0x00, 0x00, 0x01, 0x73]
+        );
+        uart.lock().unwrap().replies.extend(pms5003_frame(9));
+        assert!(sensor.get_generic_readings().is_err());
+
+        // the frame received while sleeping isn't reported after waking up
+        let command = StructBuilder::new()
+            .sub("wake_up", StructBuilder::new())
+            .build();
+        assert!(sensor.do_command(Some(command)).unwrap().is_none());
+        assert!(sensor.get_generic_readings().is_err());
+        uart.lock().unwrap().replies.extend(pms5003_frame(12));
+        let readings = sensor.get_generic_readings().unwrap();
+        assert_eq!(
+            readings.get("pm2_5").and_then(|v| v.kind.clone()),
+            Some(Kind::NumberValue(12.0))
+        );
+    }
+}
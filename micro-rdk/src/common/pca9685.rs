@@ -0,0 +1,299 @@
+//! Package pca9685 implements a driver for the NXP PCA9685 16-channel, 12-bit PWM expander
+//! over I2C. A datasheet for this chip is at
+//! https://www.nxp.com/docs/en/data-sheet/PCA9685.pdf
+//!
+//! The expander is attached to the board by adding a `pca9685` attribute to the board's config:
+//!
+//! ```json
+//! "pca9685": { "i2c_bus": "i2c0", "i2c_address": 64 }
+//! ```
+//!
+//! Its channels can then be referenced by other components (such as `gpio` servos and motors)
+//! as the pins `"pca9685:0"` to `"pca9685:15"`. Like any GPIO expander it can also be declared
+//! in the `gpio_expanders` attribute of the board (see [super::gpio_expander]), which is needed
+//! to attach more than one PCA9685, the channels then being referenced by the name of the
+//! expander (`"<name>:<channel>"`). All channels of the chip share the same PWM frequency,
+//! which can only be changed while no other channel is producing a signal.
+
+use crate::common::i2c::I2cHandleType;
+
+use super::board::BoardError;
+use super::config::{AttributeError, Kind};
+use super::gpio_expander::GpioExpander;
+use super::i2c::I2CHandle;
+
+use std::time::Duration;
+
+const DEFAULT_I2C_ADDRESS: u8 = 0x40;
+const DEFAULT_FREQUENCY_HZ: u64 = 50;
+const NUM_CHANNELS: usize = 16;
+const OSCILLATOR_FREQUENCY_HZ: f64 = 25_000_000.0;
+const STEPS: f64 = 4096.0;
+// range of the prescaler, corresponding to roughly 1526Hz down to 24Hz
+const MIN_PRESCALE: f64 = 3.0;
+const MAX_PRESCALE: f64 = 255.0;
+
+const MODE1_REGISTER: u8 = 0x00;
+const MODE2_REGISTER: u8 = 0x01;
+const LED0_ON_L_REGISTER: u8 = 0x06;
+const PRESCALE_REGISTER: u8 = 0xFE;
+
+const MODE1_RESTART: u8 = 0x80;
+const MODE1_AUTO_INCREMENT: u8 = 0x20;
+const MODE1_SLEEP: u8 = 0x10;
+const MODE2_TOTEM_POLE: u8 = 0x04;
+// bit of the ON_H/OFF_H registers forcing the output fully on/off
+const FULL_ON_OFF: u8 = 0x10;
+
+/// Returns the prescaler value producing the closest frequency to `frequency_hz`
+pub(crate) fn prescale_for_frequency(frequency_hz: u64) -> Result<u8, BoardError> {
+    let prescale = (OSCILLATOR_FREQUENCY_HZ / (STEPS * frequency_hz as f64)).round() - 1.0;
+    if !(MIN_PRESCALE..=MAX_PRESCALE).contains(&prescale) {
+        return Err(BoardError::BoardUnsupportedArgument(
+            "pca9685 frequency out of range (24Hz-1526Hz)",
+        ));
+    }
+    Ok(prescale as u8)
+}
+
+/// Returns the values of the ON_L, ON_H, OFF_L and OFF_H registers of a channel for the
+/// given duty cycle
+pub(crate) fn channel_registers_for_duty(duty_cycle_pct: f64) -> [u8; 4] {
+    let off = (duty_cycle_pct * STEPS).round();
+    if off <= 0.0 {
+        [0, 0, 0, FULL_ON_OFF]
+    } else if off >= STEPS {
+        [0, FULL_ON_OFF, 0, 0]
+    } else {
+        let [off_l, off_h] = (off as u16).to_le_bytes();
+        [0, 0, off_l, off_h]
+    }
+}
+
+/// Parses a `pca9685` element of the `gpio_expanders` attribute of a board config
+#[derive(Debug)]
+pub(crate) struct Pca9685Config {
+    pub(crate) i2c_bus: String,
+    pub(crate) i2c_address: u8,
+    pub(crate) frequency_hz: u64,
+}
+
+impl TryFrom<&Kind> for Pca9685Config {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let i2c_bus = value
+            .get("i2c_bus")?
+            .ok_or(AttributeError::KeyNotFound("i2c_bus".to_string()))?
+            .try_into()?;
+        let i2c_address = match value.get("i2c_address")? {
+            Some(val) => val.try_into()?,
+            None => DEFAULT_I2C_ADDRESS,
+        };
+        let frequency_hz = match value.get("frequency_hz")? {
+            Some(val) => u32::try_from(val)? as u64,
+            None => DEFAULT_FREQUENCY_HZ,
+        };
+        Ok(Pca9685Config {
+            i2c_bus,
+            i2c_address,
+            frequency_hz,
+        })
+    }
+}
+
+pub struct Pca9685 {
+    i2c_handle: I2cHandleType,
+    i2c_address: u8,
+    frequency_hz: u64,
+    duty_cycles: [f64; NUM_CHANNELS],
+}
+
+impl Pca9685 {
+    pub fn new(
+        mut i2c_handle: I2cHandleType,
+        i2c_address: u8,
+        frequency_hz: u64,
+    ) -> Result<Self, BoardError> {
+        i2c_handle.write_i2c(i2c_address, &[MODE2_REGISTER, MODE2_TOTEM_POLE])?;
+        let mut expander = Pca9685 {
+            i2c_handle,
+            i2c_address,
+            frequency_hz: 0,
+            duty_cycles: [0.0; NUM_CHANNELS],
+        };
+        expander.set_frequency(frequency_hz)?;
+        // start with every output off
+        for channel in 0..NUM_CHANNELS {
+            expander.set_duty(channel, 0.0)?;
+        }
+        Ok(expander)
+    }
+
+    pub fn frequency(&self) -> u64 {
+        self.frequency_hz
+    }
+
+    pub fn duty(&self, channel: usize) -> Result<f64, BoardError> {
+        self.duty_cycles
+            .get(channel)
+            .copied()
+            .ok_or(BoardError::BoardUnsupportedArgument(
+                "pca9685 invalid channel",
+            ))
+    }
+
+    pub fn set_duty(&mut self, channel: usize, duty_cycle_pct: f64) -> Result<(), BoardError> {
+        if channel >= NUM_CHANNELS {
+            return Err(BoardError::BoardUnsupportedArgument(
+                "pca9685 invalid channel",
+            ));
+        }
+        let duty_cycle_pct = duty_cycle_pct.clamp(0.0, 1.0);
+        let mut bytes = [0_u8; 5];
+        bytes[0] = LED0_ON_L_REGISTER + 4 * channel as u8;
+        bytes[1..].copy_from_slice(&channel_registers_for_duty(duty_cycle_pct));
+        self.i2c_handle.write_i2c(self.i2c_address, &bytes)?;
+        self.duty_cycles[channel] = duty_cycle_pct;
+        Ok(())
+    }
+
+    /// Changes the PWM frequency shared by all channels
+    pub fn set_frequency(&mut self, frequency_hz: u64) -> Result<(), BoardError> {
+        if frequency_hz == self.frequency_hz {
+            return Ok(());
+        }
+        let prescale = prescale_for_frequency(frequency_hz)?;
+        // the prescaler can only be written while the oscillator is stopped
+        self.i2c_handle.write_i2c(
+            self.i2c_address,
+            &[MODE1_REGISTER, MODE1_AUTO_INCREMENT | MODE1_SLEEP],
+        )?;
+        self.i2c_handle
+            .write_i2c(self.i2c_address, &[PRESCALE_REGISTER, prescale])?;
+        self.i2c_handle
+            .write_i2c(self.i2c_address, &[MODE1_REGISTER, MODE1_AUTO_INCREMENT])?;
+        // the oscillator needs at most 500us to stabilize before restarting the outputs
+        std::thread::sleep(Duration::from_micros(500));
+        self.i2c_handle.write_i2c(
+            self.i2c_address,
+            &[MODE1_REGISTER, MODE1_AUTO_INCREMENT | MODE1_RESTART],
+        )?;
+        self.frequency_hz = frequency_hz;
+        Ok(())
+    }
+}
+
+impl GpioExpander for Pca9685 {
+    fn num_pins(&self) -> u16 {
+        NUM_CHANNELS as u16
+    }
+
+    fn set_gpio_pin_level(&mut self, pin: u16, is_high: bool) -> Result<(), BoardError> {
+        self.set_duty(pin as usize, if is_high { 1.0 } else { 0.0 })
+    }
+
+    fn get_gpio_level(&mut self, pin: u16) -> Result<bool, BoardError> {
+        Ok(self.duty(pin as usize)? >= 1.0)
+    }
+
+    fn get_pwm_duty(&self, pin: u16) -> f64 {
+        self.duty(pin as usize).unwrap_or(0.0)
+    }
+
+    fn set_pwm_duty(&mut self, pin: u16, duty_cycle_pct: f64) -> Result<(), BoardError> {
+        self.set_duty(pin as usize, duty_cycle_pct)
+    }
+
+    fn get_pwm_frequency(&self, _pin: u16) -> Result<u64, BoardError> {
+        Ok(self.frequency())
+    }
+
+    fn set_pwm_frequency(&mut self, pin: u16, frequency_hz: u64) -> Result<(), BoardError> {
+        let channel = pin as usize;
+        if frequency_hz == 0 {
+            return self.set_duty(channel, 0.0);
+        }
+        if frequency_hz != self.frequency()
+            && self
+                .duty_cycles
+                .iter()
+                .enumerate()
+                .any(|(i, duty)| i != channel && *duty != 0.0)
+        {
+            return Err(BoardError::BoardUnsupportedArgument(
+                "pca9685 frequency is shared by all channels and already in use",
+            ));
+        }
+        self.set_frequency(frequency_hz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel_registers_for_duty, prescale_for_frequency, Pca9685};
+    use crate::common::board::{Board, BoardError, BoardPin, BoardType, FakeBoard};
+    use crate::common::gpio_expander::{ExpandedBoard, GpioExpanderType};
+    use crate::common::i2c::{I2CErrors, I2CHandle, I2cHandleType};
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingI2C {
+        writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl I2CHandle for RecordingI2C {
+        fn name(&self) -> String {
+            "recording".to_string()
+        }
+        fn write_i2c(&mut self, _address: u8, bytes: &[u8]) -> Result<(), I2CErrors> {
+            self.writes.lock().unwrap().push(bytes.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test_log::test]
+    fn test_register_values() {
+        assert_eq!(prescale_for_frequency(50).unwrap(), 121);
+        assert_eq!(prescale_for_frequency(1000).unwrap(), 5);
+        assert!(prescale_for_frequency(10).is_err());
+        assert!(prescale_for_frequency(2000).is_err());
+
+        assert_eq!(channel_registers_for_duty(0.0), [0, 0, 0, 0x10]);
+        assert_eq!(channel_registers_for_duty(1.0), [0, 0x10, 0, 0]);
+        assert_eq!(channel_registers_for_duty(0.5), [0, 0, 0x00, 0x08]);
+        assert_eq!(channel_registers_for_duty(0.075), [0, 0, 0x33, 0x01]);
+    }
+
+    #[test_log::test]
+    fn test_pca9685_board() -> Result<(), BoardError> {
+        let writes = Arc::new(Mutex::new(vec![]));
+        let i2c: I2cHandleType = Arc::new(Mutex::new(RecordingI2C {
+            writes: writes.clone(),
+        }));
+        let expander: GpioExpanderType = Arc::new(Mutex::new(Pca9685::new(i2c, 0x40, 50)?));
+        assert!(writes.lock().unwrap().contains(&vec![0xFE, 121]));
+
+        let fake_board: BoardType = Arc::new(Mutex::new(FakeBoard::new(vec![])));
+        let mut board = ExpandedBoard::new(fake_board, vec![("pwm".to_string(), expander)]);
+        let pin = |channel| board.resolve_pin(&BoardPin::Expander("pwm".to_string(), channel));
+        let (pin3, pin4, pin15) = (pin(3)?, pin(4)?, pin(15)?);
+        assert!(pin(16).is_err());
+
+        writes.lock().unwrap().clear();
+        board.set_pwm_duty(pin3, 0.5)?;
+        assert_eq!(board.get_pwm_duty(pin3), 0.5);
+        assert_eq!(
+            writes.lock().unwrap().as_slice(),
+            &[vec![0x06 + 4 * 3, 0, 0, 0x00, 0x08]]
+        );
+        assert_eq!(board.get_pwm_frequency(pin3)?, 50);
+
+        // frequency can't change while another channel is in use
+        assert!(board.set_pwm_frequency(pin4, 300).is_err());
+        assert!(board.set_pwm_frequency(pin3, 300).is_ok());
+        assert_eq!(board.get_pwm_frequency(pin4)?, 300);
+
+        board.set_gpio_pin_level(pin15, true)?;
+        assert!(board.get_gpio_level(pin15)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+//! Runs the periodic work of a component (watchdogs, failsafes, keepalives) on the executor
+//! of the robot instead of piggybacking on the requests it receives.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_io::Timer;
+
+#[cfg(feature = "esp32")]
+use crate::esp32::exec::Esp32Executor;
+#[cfg(feature = "native")]
+use crate::native::exec::NativeExecutor;
+
+#[cfg(feature = "native")]
+type Executor = NativeExecutor;
+#[cfg(feature = "esp32")]
+type Executor = Esp32Executor;
+
+/// Calls `tick` with the locked component every `period`, the task stops once the
+/// component is dropped (for example when the robot is rebuilt)
+pub fn spawn_periodic<T, F>(component: &Arc<Mutex<T>>, period: Duration, mut tick: F)
+where
+    T: ?Sized + 'static,
+    F: FnMut(&mut T) + 'static,
+{
+    let component = Arc::downgrade(component);
+    Executor::new()
+        .spawn(async move {
+            loop {
+                Timer::after(period).await;
+                let Some(component) = component.upgrade() else {
+                    break;
+                };
+                let mut component = component.lock().unwrap();
+                tick(&mut component);
+            }
+        })
+        .detach();
+}
@@ -0,0 +1,509 @@
+//! Power management of the robot, configured through a service of type `power_management` in
+//! the robot's config:
+//!
+//! ```json
+//! {
+//!     "name": "power",
+//!     "type": "power_management",
+//!     "attributes": {
+//!         "duty_cycle": { "board": "board", "awake_secs": 120, "sleep_secs": 3600 }
+//!     }
+//! }
+//! ```
+//!
+//! With `duty_cycle` the robot stays awake for `awake_secs` seconds after booting, capturing and
+//! syncing data as configured by the data manager, then puts `board` in deep sleep for
+//! `sleep_secs` seconds. A final sync is done before going to sleep. The device reboots when
+//! waking up from deep sleep, so every wake up goes through connecting to app and fetching the
+//! config again before the next awake window starts.
+//!
+//! With `light_sleep` (ESP32 only) the device enters light sleep whenever no client is connected
+//! and no data capture is due within `idle_ms` milliseconds, for at most `max_sleep_ms`
+//! milliseconds. It wakes up on network activity and timers, staying connected to the network.
+//!
+//! ```json
+//! "light_sleep": { "idle_ms": 50, "max_sleep_ms": 1000 }
+//! ```
+//!
+//! With `profile` (ESP32 only) the CPU frequency is scaled between a minimum and a maximum
+//! frequency (80, 160 or 240 MHz) depending on the load, optionally letting esp-idf enter light
+//! sleep automatically when idle. The profile is applied at boot and reported by the `telemetry`
+//! sensor. It is either one of `performance` (240 MHz), `balanced` (80 to 240 MHz) and
+//! `low_power` (80 to 160 MHz with automatic light sleep) or given explicitly:
+//!
+//! ```json
+//! "profile": { "min_freq_mhz": 80, "max_freq_mhz": 240, "auto_light_sleep": false }
+//! ```
+//!
+//! This requires `CONFIG_PM_ENABLE` (and `CONFIG_FREERTOS_USE_TICKLESS_IDLE` for automatic
+//! light sleep) in the sdkconfig of the project. They are off in the shared sdkconfig defaults,
+//! `sdkconfig.defaults.pm` enables them when added to the sdkconfig defaults of the project.
+
+use crate::google;
+use crate::proto::{app::v1::ConfigResponse, component::board::v1::PowerMode};
+
+use super::board::{BoardError, BoardType};
+use super::config::{AttributeError, Kind};
+use super::robot::LocalRobot;
+
+use async_io::Timer;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, RwLock,
+};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+pub static SERVICE_TYPE: &str = "power_management";
+
+#[derive(Debug, Error)]
+pub enum PowerManagementError {
+    #[error("power management config error: {0}")]
+    ConfigError(&'static str),
+    #[error(transparent)]
+    ConfigAttributeError(#[from] AttributeError),
+    #[error("power management board {0} not found")]
+    BoardNotFound(String),
+    #[error(transparent)]
+    BoardError(#[from] BoardError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DutyCycleConfig {
+    board: String,
+    awake_window: Duration,
+    sleep_interval: Duration,
+}
+
+impl TryFrom<&Kind> for DutyCycleConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let board = value
+            .get("board")?
+            .ok_or(AttributeError::KeyNotFound("board".to_string()))?
+            .try_into()?;
+        let awake_secs: f64 = value
+            .get("awake_secs")?
+            .ok_or(AttributeError::KeyNotFound("awake_secs".to_string()))?
+            .try_into()?;
+        let sleep_secs: f64 = value
+            .get("sleep_secs")?
+            .ok_or(AttributeError::KeyNotFound("sleep_secs".to_string()))?
+            .try_into()?;
+        if awake_secs <= 0.0 || sleep_secs <= 0.0 {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        Ok(DutyCycleConfig {
+            board,
+            awake_window: Duration::from_secs_f64(awake_secs),
+            sleep_interval: Duration::from_secs_f64(sleep_secs),
+        })
+    }
+}
+
+static ACTIVE_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+static NEXT_CAPTURE: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Marks a client connection as active for as long as it is held, preventing light sleep
+pub(crate) struct ActiveConnection;
+
+impl ActiveConnection {
+    pub(crate) fn new() -> Self {
+        ACTIVE_CONNECTIONS.fetch_add(1, Ordering::AcqRel);
+        Self
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        ACTIVE_CONNECTIONS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Records when the data manager will next capture data
+pub(crate) fn set_next_capture(at: Option<Instant>) {
+    *NEXT_CAPTURE.lock().unwrap() = at;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightSleepConfig {
+    idle: Duration,
+    max_sleep: Duration,
+}
+
+impl TryFrom<&Kind> for LightSleepConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let idle_ms: f64 = value
+            .get("idle_ms")?
+            .map(f64::try_from)
+            .transpose()?
+            .unwrap_or(50.0);
+        let max_sleep_ms: f64 = value
+            .get("max_sleep_ms")?
+            .map(f64::try_from)
+            .transpose()?
+            .unwrap_or(1000.0);
+        if idle_ms <= 0.0 || max_sleep_ms <= 0.0 {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        Ok(LightSleepConfig {
+            idle: Duration::from_secs_f64(idle_ms / 1000.0),
+            max_sleep: Duration::from_secs_f64(max_sleep_ms / 1000.0),
+        })
+    }
+}
+
+impl LightSleepConfig {
+    /// Returns the light sleep configuration if the power management service enables it
+    pub fn from_config(cfg: &ConfigResponse) -> Result<Option<Self>, PowerManagementError> {
+        match power_management_attributes(cfg)? {
+            Some(attributes) => Ok(attributes
+                .get("light_sleep")?
+                .map(LightSleepConfig::try_from)
+                .transpose()?),
+            None => Ok(None),
+        }
+    }
+
+    /// How often to check whether the device can go to sleep
+    pub fn idle(&self) -> Duration {
+        self.idle
+    }
+
+    /// How long the device can sleep right now, if it can sleep at all
+    pub fn sleep_duration(&self, now: Instant) -> Option<Duration> {
+        self.sleep_duration_inner(
+            now,
+            ACTIVE_CONNECTIONS.load(Ordering::Acquire),
+            *NEXT_CAPTURE.lock().unwrap(),
+        )
+    }
+
+    fn sleep_duration_inner(
+        &self,
+        now: Instant,
+        active_connections: usize,
+        next_capture: Option<Instant>,
+    ) -> Option<Duration> {
+        if active_connections > 0 {
+            return None;
+        }
+        let until_capture =
+            next_capture.map_or(self.max_sleep, |at| at.saturating_duration_since(now));
+        if until_capture <= self.idle {
+            return None;
+        }
+        Some(until_capture.min(self.max_sleep))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerProfile {
+    pub name: String,
+    pub min_freq_mhz: u32,
+    pub max_freq_mhz: u32,
+    pub auto_light_sleep: bool,
+}
+
+impl PowerProfile {
+    fn new(name: &str, min_freq_mhz: u32, max_freq_mhz: u32, auto_light_sleep: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            min_freq_mhz,
+            max_freq_mhz,
+            auto_light_sleep,
+        }
+    }
+
+    /// Returns the power profile if the power management service configures one
+    pub fn from_config(cfg: &ConfigResponse) -> Result<Option<Self>, PowerManagementError> {
+        match power_management_attributes(cfg)? {
+            Some(attributes) => Ok(attributes
+                .get("profile")?
+                .map(PowerProfile::try_from)
+                .transpose()?),
+            None => Ok(None),
+        }
+    }
+}
+
+impl TryFrom<&Kind> for PowerProfile {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        if let Kind::StringValue(name) = value {
+            return match name.as_str() {
+                "performance" => Ok(Self::new(name, 240, 240, false)),
+                "balanced" => Ok(Self::new(name, 80, 240, false)),
+                "low_power" => Ok(Self::new(name, 80, 160, true)),
+                _ => Err(AttributeError::ConversionImpossibleError),
+            };
+        }
+        let min_freq_mhz: u32 = value
+            .get("min_freq_mhz")?
+            .ok_or(AttributeError::KeyNotFound("min_freq_mhz".to_string()))?
+            .try_into()?;
+        let max_freq_mhz: u32 = value
+            .get("max_freq_mhz")?
+            .ok_or(AttributeError::KeyNotFound("max_freq_mhz".to_string()))?
+            .try_into()?;
+        let auto_light_sleep = value
+            .get("auto_light_sleep")?
+            .map(bool::try_from)
+            .transpose()?
+            .unwrap_or(false);
+        let valid_freq = |freq| [80, 160, 240].contains(&freq);
+        if !valid_freq(min_freq_mhz) || !valid_freq(max_freq_mhz) || min_freq_mhz > max_freq_mhz {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        Ok(Self::new(
+            "custom",
+            min_freq_mhz,
+            max_freq_mhz,
+            auto_light_sleep,
+        ))
+    }
+}
+
+/// Returns the attributes of the power management service if one is configured
+pub(crate) fn power_management_attributes(
+    cfg: &ConfigResponse,
+) -> Result<Option<Kind>, PowerManagementError> {
+    let robot_config = cfg
+        .config
+        .as_ref()
+        .ok_or(PowerManagementError::ConfigError("missing robot config"))?;
+    let mut services = robot_config
+        .services
+        .iter()
+        .filter(|svc_cfg| svc_cfg.r#type == SERVICE_TYPE);
+    let svc_cfg = match services.next() {
+        Some(svc_cfg) => svc_cfg,
+        None => return Ok(None),
+    };
+    if services.next().is_some() {
+        return Err(PowerManagementError::ConfigError(
+            "multiple power management services configured",
+        ));
+    }
+    let attributes = svc_cfg.attributes.clone().unwrap_or_default();
+    Ok(Some(Kind::try_from(
+        google::protobuf::value::Kind::StructValue(attributes),
+    )?))
+}
+
+/// Alternates between an awake window and a deep sleep of the board
+pub struct DutyCycle {
+    board: BoardType,
+    awake_window: Duration,
+    sleep_interval: Duration,
+}
+
+impl DutyCycle {
+    /// Returns the duty cycle of the robot if the power management service configures one
+    pub fn from_robot_and_config(
+        cfg: &ConfigResponse,
+        robot: Arc<RwLock<LocalRobot>>,
+    ) -> Result<Option<Self>, PowerManagementError> {
+        let attributes = match power_management_attributes(cfg)? {
+            Some(attributes) => attributes,
+            None => return Ok(None),
+        };
+        let config: DutyCycleConfig = match attributes.get("duty_cycle")? {
+            Some(duty_cycle) => duty_cycle.try_into()?,
+            None => return Ok(None),
+        };
+        let board = robot
+            .read()
+            .unwrap()
+            .get_board_by_name(config.board.clone())
+            .ok_or(PowerManagementError::BoardNotFound(config.board))?;
+        Ok(Some(Self {
+            board,
+            awake_window: config.awake_window,
+            sleep_interval: config.sleep_interval,
+        }))
+    }
+
+    pub fn awake_window(&self) -> Duration {
+        self.awake_window
+    }
+
+    pub fn sleep_interval(&self) -> Duration {
+        self.sleep_interval
+    }
+
+    /// Waits until `deadline`, then puts the board to sleep. On a board supporting deep sleep
+    /// this only returns on error.
+    pub async fn sleep_at(&self, deadline: Instant) -> Result<(), PowerManagementError> {
+        Timer::at(deadline).await;
+        self.sleep()
+    }
+
+    pub(crate) fn sleep(&self) -> Result<(), PowerManagementError> {
+        log::info!(
+            "duty cycle: sleeping for {} seconds",
+            self.sleep_interval.as_secs()
+        );
+        self.board
+            .lock()
+            .unwrap()
+            .set_power_mode(PowerMode::OfflineDeep, Some(self.sleep_interval))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::registry::ComponentRegistry;
+    use crate::proto::app::v1::{ComponentConfig, ConfigResponse, RobotConfig, ServiceConfig};
+
+    fn kind_struct(fields: Vec<(&str, Kind)>) -> Kind {
+        Kind::StructValue(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    #[test_log::test]
+    fn test_duty_cycle_config() {
+        let conf = kind_struct(vec![
+            ("board", Kind::StringValue("board".to_string())),
+            ("awake_secs", Kind::NumberValue(30.0)),
+            ("sleep_secs", Kind::NumberValue(600.0)),
+        ]);
+        let conf = DutyCycleConfig::try_from(&conf).unwrap();
+        assert_eq!(conf.board, "board");
+        assert_eq!(conf.awake_window, Duration::from_secs(30));
+        assert_eq!(conf.sleep_interval, Duration::from_secs(600));
+
+        let conf = kind_struct(vec![
+            ("board", Kind::StringValue("board".to_string())),
+            ("awake_secs", Kind::NumberValue(30.0)),
+        ]);
+        assert!(DutyCycleConfig::try_from(&conf).is_err());
+
+        let conf = kind_struct(vec![
+            ("board", Kind::StringValue("board".to_string())),
+            ("awake_secs", Kind::NumberValue(0.0)),
+            ("sleep_secs", Kind::NumberValue(600.0)),
+        ]);
+        assert!(DutyCycleConfig::try_from(&conf).is_err());
+    }
+
+    #[test_log::test]
+    fn test_light_sleep() {
+        let conf = kind_struct(vec![
+            ("idle_ms", Kind::NumberValue(100.0)),
+            ("max_sleep_ms", Kind::NumberValue(2000.0)),
+        ]);
+        let conf = LightSleepConfig::try_from(&conf).unwrap();
+        assert_eq!(conf.idle(), Duration::from_millis(100));
+
+        let now = Instant::now();
+        assert_eq!(
+            conf.sleep_duration_inner(now, 0, None),
+            Some(Duration::from_secs(2))
+        );
+        // never sleep with a client connected
+        assert_eq!(conf.sleep_duration_inner(now, 1, None), None);
+        // wake up in time for the next capture
+        assert_eq!(
+            conf.sleep_duration_inner(now, 0, Some(now + Duration::from_millis(500))),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            conf.sleep_duration_inner(now, 0, Some(now + Duration::from_millis(50))),
+            None
+        );
+        assert_eq!(
+            conf.sleep_duration_inner(now, 0, Some(now + Duration::from_secs(10))),
+            Some(Duration::from_secs(2))
+        );
+
+        let conf = LightSleepConfig::try_from(&kind_struct(vec![])).unwrap();
+        assert_eq!(conf.idle(), Duration::from_millis(50));
+    }
+
+    #[test_log::test]
+    fn test_power_profile() {
+        let profile = PowerProfile::try_from(&Kind::StringValue("balanced".to_string())).unwrap();
+        assert_eq!(profile, PowerProfile::new("balanced", 80, 240, false));
+        assert!(PowerProfile::try_from(&Kind::StringValue("turbo".to_string())).is_err());
+
+        let conf = kind_struct(vec![
+            ("min_freq_mhz", Kind::NumberValue(160.0)),
+            ("max_freq_mhz", Kind::NumberValue(240.0)),
+            ("auto_light_sleep", Kind::BoolValue(true)),
+        ]);
+        let profile = PowerProfile::try_from(&conf).unwrap();
+        assert_eq!(profile, PowerProfile::new("custom", 160, 240, true));
+
+        let conf = kind_struct(vec![
+            ("min_freq_mhz", Kind::NumberValue(240.0)),
+            ("max_freq_mhz", Kind::NumberValue(80.0)),
+        ]);
+        assert!(PowerProfile::try_from(&conf).is_err());
+        let conf = kind_struct(vec![
+            ("min_freq_mhz", Kind::NumberValue(40.0)),
+            ("max_freq_mhz", Kind::NumberValue(240.0)),
+        ]);
+        assert!(PowerProfile::try_from(&conf).is_err());
+    }
+
+    #[test_log::test]
+    fn test_duty_cycle_from_config() {
+        let duty_cycle = kind_struct(vec![
+            ("board", Kind::StringValue("board".to_string())),
+            ("awake_secs", Kind::NumberValue(60.0)),
+            ("sleep_secs", Kind::NumberValue(3600.0)),
+        ]);
+        let attributes = match google::protobuf::Value::from(&kind_struct(vec![(
+            "duty_cycle",
+            duty_cycle,
+        )]))
+        .kind
+        {
+            Some(google::protobuf::value::Kind::StructValue(attributes)) => attributes,
+            _ => unreachable!(),
+        };
+        let cfg = ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![ComponentConfig {
+                    name: "board".to_string(),
+                    model: "rdk:builtin:fake".to_string(),
+                    r#type: "board".to_string(),
+                    namespace: "rdk".to_string(),
+                    ..Default::default()
+                }],
+                services: vec![ServiceConfig {
+                    name: "power".to_string(),
+                    r#type: SERVICE_TYPE.to_string(),
+                    attributes: Some(attributes),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+        };
+        let robot = Arc::new(RwLock::new(
+            LocalRobot::from_cloud_config(&cfg, Box::<ComponentRegistry>::default(), None).unwrap(),
+        ));
+        let duty_cycle = DutyCycle::from_robot_and_config(&cfg, robot.clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(duty_cycle.awake_window(), Duration::from_secs(60));
+        assert_eq!(duty_cycle.sleep_interval(), Duration::from_secs(3600));
+        // the fake board accepts any power mode
+        assert!(duty_cycle.sleep().is_ok());
+
+        let cfg = ConfigResponse {
+            config: Some(RobotConfig::default()),
+        };
+        assert!(DutyCycle::from_robot_and_config(&cfg, robot)
+            .unwrap()
+            .is_none());
+    }
+}
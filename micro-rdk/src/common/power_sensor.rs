@@ -1,14 +1,12 @@
 use std::sync::{Arc, Mutex};
 
-use crate::{
-    google::protobuf::{value::Kind, Value},
-    proto::component,
-};
+use crate::proto::component;
 
 use super::{
     generic::DoCommand,
     sensor::{GenericReadingsResult, Readings, SensorError},
     status::Status,
+    struct_builder::StructBuilder,
 };
 
 pub static COMPONENT_NAME: &str = "power_sensor";
@@ -73,36 +71,15 @@ pub fn get_power_sensor_generic_readings(
     let current = ps.get_current()?;
     let power = ps.get_power()?;
 
-    let res = std::collections::HashMap::from([
-        (
-            "volts".to_string(),
-            Value {
-                kind: Some(Kind::NumberValue(voltage.volts)),
-            },
-        ),
-        (
-            "amps".to_string(),
-            Value {
-                kind: Some(Kind::NumberValue(current.amperes)),
-            },
-        ),
-        (
-            "is_ac".to_string(),
-            Value {
-                kind: Some(Kind::BoolValue(matches!(
-                    voltage.power_supply_type,
-                    PowerSupplyType::AC
-                ))),
-            },
-        ),
-        (
-            "watts".to_string(),
-            Value {
-                kind: Some(Kind::NumberValue(power)),
-            },
-        ),
-    ]);
-    Ok(res)
+    Ok(StructBuilder::with_capacity(4)
+        .field("volts", voltage.volts)
+        .field("amps", current.amperes)
+        .field(
+            "is_ac",
+            matches!(voltage.power_supply_type, PowerSupplyType::AC),
+        )
+        .field("watts", power)
+        .into_fields())
 }
 
 impl<P> PowerSensor for Mutex<P>
@@ -1,17 +1,17 @@
 #![allow(dead_code)]
 use std::{convert::Infallible, rc::Rc, sync::Mutex};
 
-use crate::proto::provisioning::v1::CloudConfig;
+use crate::{common::secret::SecretString, proto::provisioning::v1::CloudConfig};
 
-#[derive(Clone, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct RobotCredentials {
-    robot_secret: String,
+    robot_secret: SecretString,
     robot_id: String,
 }
 
 impl RobotCredentials {
     pub(crate) fn robot_secret(&self) -> &str {
-        &self.robot_secret
+        self.robot_secret.expose()
     }
     pub(crate) fn robot_id(&self) -> &str {
         &self.robot_id
@@ -23,7 +23,7 @@ impl From<CloudConfig> for RobotCredentials {
         // TODO: make ticket : ignore app_address for now but need to add it later
         Self {
             robot_id: value.id,
-            robot_secret: value.secret,
+            robot_secret: value.secret.into(),
         }
     }
 }
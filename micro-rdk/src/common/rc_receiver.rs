@@ -0,0 +1,599 @@
+//! Package rc_receiver implements a sensor reporting the channels of an RC receiver, decoded
+//! from either a PPM pulse train or an SBUS serial stream.
+//!
+//! The platform specific part, reading the pulses or bytes from the receiver, is behind the
+//! [RcInput] trait. The `rc_receiver` model is registered on the ESP32, see
+//! `esp32::rc_receiver` for the input configuration.
+//!
+//! ```json
+//! {
+//!     "protocol": "sbus",
+//!     "pin": 16,
+//!     "channel_names": ["steering", "throttle", "aux", "override"],
+//!     "base": "my-base",
+//!     "throttle_channel": "throttle",
+//!     "steering_channel": "steering",
+//!     "override_channel": "override"
+//! }
+//! ```
+//!
+//! Readings contain one value between -1.0 and 1.0 per channel, named after `channel_names`
+//! (channels without a name are reported as `channel_<n>`, starting from 1), and `failsafe`,
+//! which is true when the receiver lost the transmitter or no frame was received within
+//! `failsafe_timeout_ms` (defaults to 500ms).
+//!
+//! When `base` is set, the throttle and steering channels are applied to the linear Y and
+//! angular Z power of the base while the override channel is above its midpoint (always, when
+//! no override channel is configured). The base is stopped when the override is released or
+//! the receiver enters failsafe. The channels are read at 20Hz by a background task, so the
+//! base is driven, and stopped on failsafe, whether or not readings are requested.
+
+use crate::common::status::{Status, StatusError};
+use crate::google;
+use crate::proto::common::v1::Vector3;
+
+use super::base::{Base, BaseType, COMPONENT_NAME as BaseCompName};
+use super::config::{AttributeError, ConfigType};
+use super::periodic::spawn_periodic;
+use super::registry::{Dependency, ResourceKey};
+use super::robot::Resource;
+use super::sensor::{
+    GenericReadingsResult, Readings, Sensor, SensorError, SensorResult, SensorT, SensorType,
+    TypedReadingsResult,
+};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const SBUS_FRAME_LEN: usize = 25;
+const SBUS_HEADER: u8 = 0x0F;
+const SBUS_NUM_CHANNELS: usize = 16;
+const SBUS_FLAG_CH17: u8 = 0x01;
+const SBUS_FLAG_CH18: u8 = 0x02;
+const SBUS_FLAG_FRAME_LOST: u8 = 0x04;
+const SBUS_FLAG_FAILSAFE: u8 = 0x08;
+
+// any gap longer than this separates two PPM frames
+pub const PPM_SYNC_MIN_US: u32 = 2500;
+const PPM_MIN_PULSE_US: u32 = 500;
+const PPM_MAX_CHANNELS: usize = 16;
+const PPM_MIN_CHANNELS: usize = 4;
+
+const CENTER_US: f64 = 1500.0;
+const HALF_RANGE_US: f64 = 500.0;
+const DEFAULT_FAILSAFE_TIMEOUT: Duration = Duration::from_millis(500);
+// period at which the channels are read and applied to the base
+const UPDATE_PERIOD: Duration = Duration::from_millis(50);
+
+/// The channels of a frame received from an RC receiver, as pulse widths in microseconds
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RcFrame {
+    pub channels: Vec<u16>,
+    pub failsafe: bool,
+}
+
+/// Source of the frames of an RC receiver
+pub trait RcInput {
+    /// Returns the most recent frame received since the previous call, if any
+    fn read_frame(&mut self) -> Result<Option<RcFrame>, SensorError>;
+}
+
+// SBUS values span 172..1811 for 988..2012us
+fn sbus_to_us(raw: u16) -> u16 {
+    ((raw as i32 - 992) * 5 / 8 + 1500).clamp(0, u16::MAX as i32) as u16
+}
+
+/// Decodes a complete SBUS frame: a 0x0F header, 16 channels of 11 bits packed little-endian,
+/// a flags byte and a footer
+pub fn decode_sbus_frame(frame: &[u8; SBUS_FRAME_LEN]) -> Option<RcFrame> {
+    // SBUS2 receivers rotate the footer through 0x04, 0x14, 0x24 and 0x34
+    let footer = frame[SBUS_FRAME_LEN - 1];
+    if frame[0] != SBUS_HEADER || (footer != 0x00 && footer & 0x0F != 0x04) {
+        return None;
+    }
+    let payload = &frame[1..23];
+    let mut channels = Vec::with_capacity(SBUS_NUM_CHANNELS + 2);
+    for ch in 0..SBUS_NUM_CHANNELS {
+        let bit = ch * 11;
+        let (byte, shift) = (bit / 8, bit % 8);
+        let mut raw = (payload[byte] as u32) >> shift | (payload[byte + 1] as u32) << (8 - shift);
+        if shift > 5 {
+            raw |= (payload[byte + 2] as u32) << (16 - shift);
+        }
+        channels.push(sbus_to_us((raw & 0x07FF) as u16));
+    }
+    let flags = frame[23];
+    for flag in [SBUS_FLAG_CH17, SBUS_FLAG_CH18] {
+        channels.push(if flags & flag != 0 { 2000 } else { 1000 });
+    }
+    Some(RcFrame {
+        channels,
+        failsafe: flags & (SBUS_FLAG_FAILSAFE | SBUS_FLAG_FRAME_LOST) != 0,
+    })
+}
+
+/// Reassembles SBUS frames from the bytes received on the serial port
+#[derive(Default)]
+pub struct SbusDecoder {
+    buffer: Vec<u8>,
+}
+
+impl SbusDecoder {
+    /// Consumes `bytes` and returns the last complete frame they contained
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Option<RcFrame> {
+        let mut last = None;
+        for byte in bytes {
+            if self.buffer.is_empty() && *byte != SBUS_HEADER {
+                continue;
+            }
+            self.buffer.push(*byte);
+            if self.buffer.len() < SBUS_FRAME_LEN {
+                continue;
+            }
+            let frame: &[u8; SBUS_FRAME_LEN] = self.buffer[..].try_into().unwrap();
+            match decode_sbus_frame(frame) {
+                Some(decoded) => {
+                    last = Some(decoded);
+                    self.buffer.clear();
+                }
+                None => {
+                    // out of sync, look for the next header in what was received
+                    let next = self.buffer[1..]
+                        .iter()
+                        .position(|b| *b == SBUS_HEADER)
+                        .map_or(self.buffer.len(), |pos| pos + 1);
+                    self.buffer.drain(..next);
+                }
+            }
+        }
+        last
+    }
+}
+
+/// Reassembles PPM frames from the time elapsed between consecutive pulses
+#[derive(Default)]
+pub struct PpmDecoder {
+    channels: Vec<u16>,
+    synced: bool,
+}
+
+impl PpmDecoder {
+    /// Consumes the width of a pulse, returns a frame when `width_us` is a sync gap ending a
+    /// complete frame
+    pub fn push_pulse(&mut self, width_us: u32) -> Option<RcFrame> {
+        if width_us >= PPM_SYNC_MIN_US {
+            let complete = self.synced && self.channels.len() >= PPM_MIN_CHANNELS;
+            let channels = std::mem::take(&mut self.channels);
+            self.synced = true;
+            return complete.then_some(RcFrame {
+                channels,
+                failsafe: false,
+            });
+        }
+        if !self.synced {
+            return None;
+        }
+        if width_us < PPM_MIN_PULSE_US || self.channels.len() == PPM_MAX_CHANNELS {
+            // noise, wait for the next sync gap
+            self.synced = false;
+            self.channels.clear();
+            return None;
+        }
+        self.channels.push(width_us as u16);
+        None
+    }
+}
+
+/// Maps a pulse width to a value between -1.0 and 1.0
+pub fn normalize_channel(width_us: u16) -> f64 {
+    ((width_us as f64 - CENTER_US) / HALF_RANGE_US).clamp(-1.0, 1.0)
+}
+
+// a channel is referenced either by its name or as `channel_<n>`
+fn channel_index(channel_names: &[String], channel: &str) -> Option<usize> {
+    channel_names.iter().position(|n| n == channel).or_else(|| {
+        channel
+            .strip_prefix("channel_")
+            .and_then(|n| n.parse::<usize>().ok())
+            .and_then(|n| n.checked_sub(1))
+    })
+}
+
+struct BaseOverride {
+    base: BaseType,
+    throttle_channel: usize,
+    steering_channel: usize,
+    override_channel: Option<usize>,
+    active: bool,
+}
+
+impl BaseOverride {
+    fn update(&mut self, frame: Option<&RcFrame>) -> Result<(), SensorError> {
+        let channel = |frame: &RcFrame, idx: usize| {
+            frame
+                .channels
+                .get(idx)
+                .map(|v| normalize_channel(*v))
+                .ok_or(SensorError::SensorGenericError(
+                    "rc_receiver override channel missing from frame",
+                ))
+        };
+        let engaged = match frame {
+            Some(frame) => match self.override_channel {
+                Some(idx) => channel(frame, idx)? > 0.0,
+                None => true,
+            },
+            None => false,
+        };
+        let (lin, ang) = match (engaged, frame) {
+            (true, Some(frame)) => (
+                channel(frame, self.throttle_channel)?,
+                channel(frame, self.steering_channel)?,
+            ),
+            _ if self.active => (0.0, 0.0),
+            // the base is not ours to drive
+            _ => return Ok(()),
+        };
+        self.base
+            .set_power(
+                &Vector3 {
+                    x: 0.0,
+                    y: lin,
+                    z: 0.0,
+                },
+                &Vector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: ang,
+                },
+            )
+            .map_err(|_| SensorError::SensorGenericError("rc_receiver failed to drive the base"))?;
+        self.active = engaged;
+        Ok(())
+    }
+}
+
+#[derive(DoCommand)]
+pub struct RcReceiver {
+    input: Box<dyn RcInput>,
+    channel_names: Vec<String>,
+    failsafe_timeout: Duration,
+    last_frame: Option<(RcFrame, Instant)>,
+    base_override: Option<BaseOverride>,
+}
+
+impl RcReceiver {
+    pub fn new(input: Box<dyn RcInput>, channel_names: Vec<String>) -> Self {
+        RcReceiver {
+            input,
+            channel_names,
+            failsafe_timeout: DEFAULT_FAILSAFE_TIMEOUT,
+            last_frame: None,
+            base_override: None,
+        }
+    }
+
+    /// Builds the receiver from the common attributes of the `rc_receiver` model, once the
+    /// platform has built `input` from the protocol specific ones, and starts reading the
+    /// channels in the background
+    pub fn from_input_and_config(
+        input: Box<dyn RcInput>,
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        let channel_names = match cfg.get_attribute::<Vec<String>>("channel_names") {
+            Ok(names) => names,
+            Err(AttributeError::KeyNotFound(key)) if key == "channel_names" => vec![],
+            Err(_) => {
+                return Err(SensorError::ConfigError(
+                    "rc_receiver invalid channel_names",
+                ))
+            }
+        };
+        let mut receiver = RcReceiver::new(input, channel_names);
+        if let Ok(timeout) = cfg.get_attribute::<u32>("failsafe_timeout_ms") {
+            receiver.failsafe_timeout = Duration::from_millis(timeout as u64);
+        }
+        if let Ok(base_name) = cfg.get_attribute::<String>("base") {
+            let base = deps
+                .into_iter()
+                .find_map(|Dependency(key, res)| match res {
+                    Resource::Base(b) if key.1 == base_name => Some(b),
+                    _ => None,
+                })
+                .ok_or(SensorError::ConfigError("rc_receiver base not found"))?;
+            let resolve = |attribute: &str| -> Result<Option<usize>, SensorError> {
+                match cfg.get_attribute::<String>(attribute) {
+                    Ok(channel) => channel_index(&receiver.channel_names, &channel)
+                        .map(Some)
+                        .ok_or(SensorError::ConfigError("rc_receiver unknown channel")),
+                    Err(AttributeError::KeyNotFound(_)) => Ok(None),
+                    Err(_) => Err(SensorError::ConfigError("rc_receiver invalid channel")),
+                }
+            };
+            let throttle_channel = resolve("throttle_channel")?.ok_or(SensorError::ConfigError(
+                "rc_receiver missing throttle_channel",
+            ))?;
+            let steering_channel = resolve("steering_channel")?.ok_or(SensorError::ConfigError(
+                "rc_receiver missing steering_channel",
+            ))?;
+            let override_channel = resolve("override_channel")?;
+            receiver.base_override = Some(BaseOverride {
+                base,
+                throttle_channel,
+                steering_channel,
+                override_channel,
+                active: false,
+            });
+        }
+        let receiver = Arc::new(Mutex::new(receiver));
+        spawn_periodic(&receiver, UPDATE_PERIOD, |receiver: &mut Self| {
+            if let Err(err) = receiver.update(Instant::now()) {
+                log::debug!("rc_receiver: couldn't update the channels: {:?}", err);
+            }
+        });
+        Ok(receiver)
+    }
+
+    pub fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        let mut r_keys = Vec::new();
+        if let Ok(base_name) = cfg.get_attribute::<String>("base") {
+            r_keys.push(ResourceKey(BaseCompName, base_name));
+        }
+        r_keys
+    }
+
+    fn channel_name(&self, idx: usize) -> String {
+        self.channel_names
+            .get(idx)
+            .cloned()
+            .unwrap_or_else(|| format!("channel_{}", idx + 1))
+    }
+
+    // reads the pending frame and returns the current one, which is None in failsafe
+    fn update(&mut self, now: Instant) -> Result<Option<&RcFrame>, SensorError> {
+        if let Some(frame) = self.input.read_frame()? {
+            self.last_frame = Some((frame, now));
+        }
+        let current = self.last_frame.as_ref().and_then(|(frame, received)| {
+            (!frame.failsafe && now.duration_since(*received) < self.failsafe_timeout)
+                .then_some(frame)
+        });
+        if let Some(base_override) = self.base_override.as_mut() {
+            base_override.update(current)?;
+        }
+        Ok(current)
+    }
+
+    // the channels of the last frame received
+    fn channel_readings(&self) -> Result<HashMap<String, f64>, SensorError> {
+        let (frame, _) = self
+            .last_frame
+            .as_ref()
+            .ok_or(SensorError::SensorGenericError(
+                "rc_receiver no frame received yet",
+            ))?;
+        Ok(frame
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(idx, v)| (self.channel_name(idx), normalize_channel(*v)))
+            .collect())
+    }
+
+    fn readings_at(&mut self, now: Instant) -> Result<(HashMap<String, f64>, bool), SensorError> {
+        let failsafe = self.update(now)?.is_none();
+        Ok((self.channel_readings()?, failsafe))
+    }
+}
+
+impl Sensor for RcReceiver {}
+
+impl Readings for RcReceiver {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let (readings, failsafe) = self.readings_at(Instant::now())?;
+        let mut readings: GenericReadingsResult = readings
+            .into_iter()
+            .map(|v| (v.0, SensorResult::<f64> { value: v.1 }.into()))
+            .collect();
+        readings.insert(
+            "failsafe".to_string(),
+            google::protobuf::Value {
+                kind: Some(google::protobuf::value::Kind::BoolValue(failsafe)),
+            },
+        );
+        Ok(readings)
+    }
+}
+
+impl SensorT<f64> for RcReceiver {
+    fn get_readings(&self) -> Result<TypedReadingsResult<f64>, SensorError> {
+        self.channel_readings()
+    }
+}
+
+impl Status for RcReceiver {
+    fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
+        let mut fields = HashMap::new();
+        if let Some(base_override) = self.base_override.as_ref() {
+            fields.insert(
+                "override_active".to_string(),
+                google::protobuf::Value {
+                    kind: Some(google::protobuf::value::Kind::BoolValue(
+                        base_override.active,
+                    )),
+                },
+            );
+        }
+        Ok(Some(google::protobuf::Struct { fields }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_sbus_frame, normalize_channel, PpmDecoder, RcFrame, RcInput, RcReceiver,
+        SbusDecoder, SBUS_FRAME_LEN,
+    };
+    use crate::common::actuator::{Actuator, ActuatorError};
+    use crate::common::base::{Base, BaseError};
+    use crate::common::sensor::SensorError;
+    use crate::common::status::{Status, StatusError};
+    use crate::google;
+    use crate::proto::common::v1::Vector3;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    // packs 16 raw 11 bit values into an SBUS frame
+    fn sbus_frame(raw: [u16; 16], flags: u8) -> [u8; SBUS_FRAME_LEN] {
+        let mut frame = [0_u8; SBUS_FRAME_LEN];
+        frame[0] = 0x0F;
+        for (ch, value) in raw.iter().enumerate() {
+            for bit in 0..11 {
+                if value & (1 << bit) != 0 {
+                    let pos = ch * 11 + bit;
+                    frame[1 + pos / 8] |= 1 << (pos % 8);
+                }
+            }
+        }
+        frame[23] = flags;
+        frame
+    }
+
+    #[test_log::test]
+    fn test_decode_sbus() {
+        let mut raw = [992_u16; 16];
+        raw[0] = 172;
+        raw[1] = 1811;
+        raw[15] = 2047;
+        let frame = decode_sbus_frame(&sbus_frame(raw, 0x01)).unwrap();
+        assert_eq!(frame.channels.len(), 18);
+        assert_eq!(frame.channels[0], 988);
+        assert_eq!(frame.channels[1], 2011);
+        assert_eq!(frame.channels[2], 1500);
+        assert_eq!(frame.channels[15], 2159);
+        assert_eq!(frame.channels[16..], [2000, 1000]);
+        assert!(!frame.failsafe);
+
+        assert!(decode_sbus_frame(&sbus_frame(raw, 0x08)).unwrap().failsafe);
+        let mut bad = sbus_frame(raw, 0);
+        bad[24] = 0xFF;
+        assert!(decode_sbus_frame(&bad).is_none());
+    }
+
+    #[test_log::test]
+    fn test_sbus_decoder_resyncs() {
+        let mut decoder = SbusDecoder::default();
+        let frame = sbus_frame([992; 16], 0);
+        assert!(decoder.push_bytes(&[0x01, 0x0F, 0x02]).is_none());
+        assert!(decoder.push_bytes(&frame[..10]).is_none());
+        let decoded = decoder.push_bytes(&frame[10..]).unwrap();
+        assert_eq!(decoded.channels[0], 1500);
+        assert!(decoder.push_bytes(&frame).is_some());
+    }
+
+    #[test_log::test]
+    fn test_ppm_decoder() {
+        let mut decoder = PpmDecoder::default();
+        // widths received before the first sync gap are dropped
+        assert!(decoder.push_pulse(1500).is_none());
+        assert!(decoder.push_pulse(8000).is_none());
+        for width in [1000, 1500, 2000, 1200] {
+            assert!(decoder.push_pulse(width).is_none());
+        }
+        let frame = decoder.push_pulse(9000).unwrap();
+        assert_eq!(frame.channels, vec![1000, 1500, 2000, 1200]);
+        // a glitch drops the frame
+        for width in [1000, 100, 2000, 1200] {
+            assert!(decoder.push_pulse(width).is_none());
+        }
+        assert!(decoder.push_pulse(9000).is_none());
+        assert_eq!(normalize_channel(1000), -1.0);
+        assert_eq!(normalize_channel(2100), 1.0);
+    }
+
+    struct FakeInput {
+        frames: Arc<Mutex<VecDeque<RcFrame>>>,
+    }
+
+    impl RcInput for FakeInput {
+        fn read_frame(&mut self) -> Result<Option<RcFrame>, SensorError> {
+            Ok(self.frames.lock().unwrap().pop_front())
+        }
+    }
+
+    #[derive(DoCommand, Default)]
+    struct RecordingBase {
+        calls: Vec<(f64, f64)>,
+    }
+
+    impl Base for RecordingBase {
+        fn set_power(&mut self, lin: &Vector3, ang: &Vector3) -> Result<(), BaseError> {
+            self.calls.push((lin.y, ang.z));
+            Ok(())
+        }
+    }
+
+    impl Actuator for RecordingBase {
+        fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+            Ok(false)
+        }
+        fn stop(&mut self) -> Result<(), ActuatorError> {
+            Ok(())
+        }
+    }
+
+    impl Status for RecordingBase {
+        fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
+            Ok(None)
+        }
+    }
+
+    #[test_log::test]
+    fn test_rc_receiver_base_override() -> Result<(), SensorError> {
+        let frames = Arc::new(Mutex::new(VecDeque::new()));
+        let base = Arc::new(Mutex::new(RecordingBase::default()));
+        let mut receiver = RcReceiver::new(
+            Box::new(FakeInput {
+                frames: frames.clone(),
+            }),
+            vec!["steering".to_string(), "throttle".to_string()],
+        );
+        receiver.base_override = Some(super::BaseOverride {
+            base: base.clone(),
+            throttle_channel: 1,
+            steering_channel: 0,
+            override_channel: Some(2),
+            active: false,
+        });
+        let start = Instant::now();
+        assert!(receiver.readings_at(start).is_err());
+
+        let frame = |override_us| RcFrame {
+            channels: vec![1750, 2000, override_us],
+            failsafe: false,
+        };
+        frames.lock().unwrap().push_back(frame(1000));
+        let (readings, failsafe) = receiver.readings_at(start)?;
+        assert!(!failsafe);
+        assert_eq!(readings["steering"], 0.5);
+        assert_eq!(readings["throttle"], 1.0);
+        assert_eq!(readings["channel_3"], -1.0);
+        // override is released, the base is left alone
+        assert!(base.lock().unwrap().calls.is_empty());
+
+        frames.lock().unwrap().push_back(frame(2000));
+        receiver.readings_at(start)?;
+        assert_eq!(base.lock().unwrap().calls, vec![(1.0, 0.5)]);
+
+        // no frame within the timeout stops the base once
+        let later = start + Duration::from_secs(1);
+        let (_, failsafe) = receiver.readings_at(later)?;
+        assert!(failsafe);
+        receiver.readings_at(later)?;
+        assert_eq!(base.lock().unwrap().calls, vec![(1.0, 0.5), (0.0, 0.0)]);
+        Ok(())
+    }
+}
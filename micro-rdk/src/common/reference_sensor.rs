@@ -0,0 +1,313 @@
+//! Reference sensor driver, the template to start from when writing a driver for micro-rdk.
+//!
+//! It reads a 16 bits register of an I2C device (by default the temperature register of a
+//! TMP102/LM75 style thermometer) and reports it scaled as a reading. Every integration point of a
+//! driver is exercised and annotated:
+//!
+//! - registering a model with the [ComponentRegistry]
+//! - parsing config attributes, required and optional
+//! - getting the board it depends on and an I2C bus from it
+//! - talking to the device through a [RegisterMap]
+//! - reporting readings ([Readings]), status ([Status]) and answering DoCommands ([DoCommand])
+//! - data capture
+//!
+//! A component using it, once a project calls [register_models], is configured as:
+//!
+//! ```json
+//! {
+//!   "name": "thermometer",
+//!   "type": "sensor",
+//!   "model": "reference-sensor",
+//!   "depends_on": ["board"],
+//!   "attributes": {
+//!     "i2c_bus": "i2c0",
+//!     "i2c_address": 72,
+//!     "register": 0,
+//!     "scale": 0.00390625,
+//!     "offset": 0.0,
+//!     "reading_name": "celsius"
+//!   },
+//!   "service_configs": [{
+//!     "type": "data_manager",
+//!     "attributes": {
+//!       "capture_methods": [{ "method": "Readings", "capture_frequency_hz": 1 }]
+//!     }
+//!   }]
+//! }
+//! ```
+//!
+//! Data capture needs no code in the driver: every sensor can be captured with the `Readings`
+//! method, [DataCollector](crate::common::data_collector::DataCollector)s are built by the robot
+//! from the `capture_methods` of the component and call [Readings::get_generic_readings] at the
+//! configured frequency. Other resource types support their own methods (for example
+//! `AngularVelocity` for movement sensors), see
+//! [CollectionMethod](crate::common::data_collector::CollectionMethod).
+//!
+//! `make module-template` copies this file into the module template
+//! (`templates/module/src/reference_sensor.rs`), keep it free of crate internals so it still
+//! builds outside of micro-rdk.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::common::board::Board;
+use crate::common::calibration::number_arg;
+use crate::common::config::{AttributeError, ConfigType, Kind};
+use crate::common::generic::{DoCommand, GenericError};
+use crate::common::i2c::{Endian, I2cHandleType, RegisterMap};
+use crate::common::registry::{get_board_from_dependencies, ComponentRegistry, Dependency};
+use crate::common::sensor::{
+    GenericReadingsResult, Readings, Sensor, SensorError, SensorResult, SensorType,
+};
+use crate::common::status::{Status, StatusError};
+use crate::common::struct_builder::StructBuilder;
+use crate::google::protobuf::{value::Kind as ProtoKind, Struct};
+
+// Defaults of the optional attributes, they read the temperature of a TMP102 in celsius
+const DEFAULT_I2C_ADDRESS: u8 = 0x48;
+const DEFAULT_REGISTER: u8 = 0x00;
+const DEFAULT_SCALE: f64 = 1.0 / 256.0;
+const DEFAULT_READING_NAME: &str = "value";
+
+/// Registers the model, `register_models` functions are called once when the registry is built
+/// (see [ComponentRegistry::default]) or by the robot project for models defined in modules.
+/// The model name is what the `model` field of a component config refers to. Being an example,
+/// this model isn't part of the default registry.
+pub fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("reference-sensor", &ReferenceSensor::from_config)
+        .is_err()
+    {
+        log::error!("reference-sensor type is already registered");
+    }
+}
+
+pub struct ReferenceSensor {
+    // the device is only accessed through its registers, the map owns the I2C bus handle
+    registers: RegisterMap<I2cHandleType>,
+    register: u8,
+    scale: f64,
+    offset: f64,
+    reading_name: String,
+    // kept for the status
+    last_reading: Option<f64>,
+    read_errors: u32,
+}
+
+impl ReferenceSensor {
+    /// Constructor usable without a config, handy in tests and for users building robots in code
+    pub fn new(
+        i2c_handle: I2cHandleType,
+        i2c_address: u8,
+        register: u8,
+        scale: f64,
+        offset: f64,
+    ) -> Self {
+        Self {
+            registers: RegisterMap::new(i2c_handle, i2c_address, Endian::Big),
+            register,
+            scale,
+            offset,
+            reading_name: DEFAULT_READING_NAME.to_string(),
+            last_reading: None,
+            read_errors: 0,
+        }
+    }
+
+    /// Names the reading reported by the sensor
+    pub fn with_reading_name(mut self, name: &str) -> Self {
+        self.reading_name = name.to_string();
+        self
+    }
+
+    /// Builds the sensor from its component config, this is the function given to the registry.
+    /// `dependencies` holds the resources listed in `depends_on`, resolved by the robot before
+    /// this is called.
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        dependencies: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        // Required attributes are reported with a `ConfigError`, which makes the robot log the
+        // component as failed rather than running it half configured
+        let i2c_bus = cfg
+            .get_attribute::<String>("i2c_bus")
+            .map_err(|_| SensorError::ConfigError("reference-sensor missing i2c_bus attribute"))?;
+        let board = get_board_from_dependencies(dependencies).ok_or(SensorError::ConfigError(
+            "reference-sensor missing board dependency",
+        ))?;
+        // a `BoardError` converts to a `SensorError`
+        let i2c_handle = board.get_i2c_by_name(i2c_bus)?;
+
+        // Optional attributes fall back to their default when absent. `get_attribute` converts
+        // the JSON value to the requested type, a value that can't be converted is a config error
+        // too rather than silently replaced by the default.
+        let i2c_address = optional_attribute(&cfg, "i2c_address", DEFAULT_I2C_ADDRESS)?;
+        let register = optional_attribute(&cfg, "register", DEFAULT_REGISTER)?;
+        let scale = optional_attribute(&cfg, "scale", DEFAULT_SCALE)?;
+        let offset = optional_attribute(&cfg, "offset", 0.0)?;
+        let reading_name =
+            optional_attribute(&cfg, "reading_name", DEFAULT_READING_NAME.to_string())?;
+
+        // The robot shares the sensor between its services, hence the `Arc<Mutex<_>>`
+        Ok(Arc::new(Mutex::new(
+            ReferenceSensor::new(i2c_handle, i2c_address, register, scale, offset)
+                .with_reading_name(&reading_name),
+        )))
+    }
+
+    fn read_raw(&mut self) -> Result<i16, SensorError> {
+        // an `I2CErrors` converts to a `SensorError`
+        Ok(self.registers.read_i16(self.register)?)
+    }
+
+    fn read_value(&mut self) -> Result<f64, SensorError> {
+        match self.read_raw() {
+            Ok(raw) => {
+                let value = f64::from(raw) * self.scale + self.offset;
+                self.last_reading = Some(value);
+                Ok(value)
+            }
+            Err(err) => {
+                self.read_errors += 1;
+                Err(err)
+            }
+        }
+    }
+}
+
+fn optional_attribute<'a, T>(
+    cfg: &'a ConfigType<'a>,
+    key: &str,
+    default: T,
+) -> Result<T, SensorError>
+where
+    T: TryFrom<&'a Kind, Error = AttributeError>,
+{
+    match cfg.get_attribute::<T>(key) {
+        Ok(value) => Ok(value),
+        Err(AttributeError::KeyNotFound(_)) => Ok(default),
+        Err(_) => Err(SensorError::ConfigError(
+            "reference-sensor invalid attribute",
+        )),
+    }
+}
+
+// `Sensor` only bundles the traits below, it's what makes `ReferenceSensor` a `SensorType`
+impl Sensor for ReferenceSensor {}
+
+/// Readings are what `GetReadings` answers and what data capture records. Drivers reporting
+/// numbers only can implement `SensorT<f64>` and `#[derive(SensorReadings)]` instead.
+impl Readings for ReferenceSensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        let value = self.read_value()?;
+        Ok(HashMap::from([(
+            self.reading_name.clone(),
+            SensorResult::<f64> { value }.into(),
+        )]))
+    }
+}
+
+/// The status is reported to app with the other resources of the robot, it must not touch the
+/// device: it is read often and can't fail because of the bus. Drivers without any status can
+/// use `#[derive(Status)]`.
+impl Status for ReferenceSensor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(
+            StructBuilder::with_capacity(2)
+                .field("last_reading", self.last_reading)
+                .field("read_errors", self.read_errors)
+                .build(),
+        ))
+    }
+}
+
+/// DoCommand exposes what the component API doesn't cover. Commands are structs whose single key
+/// names the command, its value holding the arguments. Drivers without commands can use
+/// `#[derive(DoCommand)]`.
+///
+/// - `{"read_raw": {}}` answers `{"raw": <register value>}`
+/// - `{"set_offset": {"offset": 1.5}}` changes the offset applied to the readings
+impl DoCommand for ReferenceSensor {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        if command.fields.contains_key("read_raw") {
+            let raw = self
+                .read_raw()
+                .map_err(|err| GenericError::Other(Box::new(err)))?;
+            return Ok(Some(
+                StructBuilder::with_capacity(1)
+                    .field("raw", f64::from(raw))
+                    .build(),
+            ));
+        }
+        if let Some(args) = command.fields.get("set_offset") {
+            let offset = match &args.kind {
+                Some(ProtoKind::StructValue(args)) => number_arg(args, "offset"),
+                _ => None,
+            }
+            .ok_or(GenericError::InvalidArgument("offset"))?;
+            self.offset = offset;
+            return Ok(None);
+        }
+        Err(GenericError::MethodUnimplemented("do_command"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::ReferenceSensor;
+    use crate::common::generic::DoCommand;
+    use crate::common::i2c::FakeI2CHandle;
+    use crate::common::sensor::Readings;
+    use crate::common::status::Status;
+    use crate::common::struct_builder::StructBuilder;
+    use crate::google::protobuf::value::Kind;
+
+    #[test_log::test]
+    fn test_reference_sensor() {
+        // the fake bus reads back [register, value, value], so the register number ends up in
+        // the most significant byte
+        let i2c = Arc::new(Mutex::new(FakeI2CHandle::new_with_value(
+            "i2c0".to_string(),
+            [0, 0x80, 0],
+        )));
+        let mut sensor =
+            ReferenceSensor::new(i2c, 0x48, 0x19, 1.0 / 256.0, 0.0).with_reading_name("celsius");
+
+        let readings = sensor.get_generic_readings().unwrap();
+        assert_eq!(
+            readings.get("celsius").unwrap().kind,
+            Some(Kind::NumberValue(25.5))
+        );
+        let status = sensor.get_status().unwrap().unwrap();
+        assert_eq!(
+            status.fields.get("last_reading").unwrap().kind,
+            Some(Kind::NumberValue(25.5))
+        );
+
+        let command = StructBuilder::new()
+            .sub("set_offset", StructBuilder::new().field("offset", 1.5))
+            .build();
+        assert!(sensor.do_command(Some(command)).unwrap().is_none());
+        let readings = sensor.get_generic_readings().unwrap();
+        assert_eq!(
+            readings.get("celsius").unwrap().kind,
+            Some(Kind::NumberValue(27.0))
+        );
+
+        let command = StructBuilder::new()
+            .sub("read_raw", StructBuilder::new())
+            .build();
+        let answer = sensor.do_command(Some(command)).unwrap().unwrap();
+        assert_eq!(
+            answer.fields.get("raw").unwrap().kind,
+            Some(Kind::NumberValue(6528.0))
+        );
+        assert!(sensor.do_command(None).is_err());
+    }
+}
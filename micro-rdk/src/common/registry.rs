@@ -3,8 +3,10 @@ use std::collections::HashMap as Map;
 use thiserror::Error;
 
 use super::{
+    audio_input::{AudioInputError, AudioInputType},
     base::{BaseError, BaseType},
     board::{BoardError, BoardType},
+    button::{ButtonError, ButtonType},
     config::ConfigType,
     encoder::{EncoderError, EncoderType},
     generic::{GenericComponentType, GenericError},
@@ -14,6 +16,7 @@ use super::{
     robot::Resource,
     sensor::{SensorError, SensorType},
     servo::{ServoError, ServoType},
+    switch::{SwitchError, SwitchType},
 };
 use crate::proto::common::v1::ResourceName;
 
@@ -41,6 +44,14 @@ pub fn get_board_from_dependencies(deps: Vec<Dependency>) -> Option<BoardType> {
     None
 }
 
+/// Strips the qualifiers clients and configs may put in front of a resource name, either the
+/// resource's API (`rdk:component:motor/left`) or a remote prefix (`remote:left`), leaving the
+/// short name the resource is registered under
+pub fn short_resource_name(name: &str) -> &str {
+    let name = name.rsplit_once('/').map_or(name, |(_, name)| name);
+    name.rsplit_once(':').map_or(name, |(_, name)| name)
+}
+
 // ResourceKey is an identifier for a component to be registered to a robot. The
 // first element is a string representing the component type (arm, motor, etc.)
 // and the second element is its name.
@@ -57,7 +68,10 @@ impl ResourceKey {
             "sensor" => crate::common::sensor::COMPONENT_NAME,
             "base" => crate::common::base::COMPONENT_NAME,
             "servo" => crate::common::servo::COMPONENT_NAME,
+            "switch" => crate::common::switch::COMPONENT_NAME,
+            "button" => crate::common::button::COMPONENT_NAME,
             "power_sensor" => crate::common::power_sensor::COMPONENT_NAME,
+            "audio_input" => crate::common::audio_input::COMPONENT_NAME,
             "generic" => crate::common::generic::COMPONENT_NAME,
             &_ => {
                 return Err(RegistryError::ModelNotFound(model.to_string()));
@@ -65,6 +79,10 @@ impl ResourceKey {
         };
         Ok(Self(model_str, name))
     }
+    /// Name of the resource with any API or remote qualifier removed
+    pub fn short_name(&self) -> &str {
+        short_resource_name(&self.1)
+    }
 }
 
 impl TryFrom<ResourceName> for ResourceKey {
@@ -78,7 +96,10 @@ impl TryFrom<ResourceName> for ResourceKey {
             "encoder" => crate::common::encoder::COMPONENT_NAME,
             "base" => crate::common::base::COMPONENT_NAME,
             "servo" => crate::common::servo::COMPONENT_NAME,
+            "switch" => crate::common::switch::COMPONENT_NAME,
+            "button" => crate::common::button::COMPONENT_NAME,
             "power_sensor" => crate::common::power_sensor::COMPONENT_NAME,
+            "audio_input" => crate::common::audio_input::COMPONENT_NAME,
             "generic" => crate::common::generic::COMPONENT_NAME,
             _ => {
                 return Err(RegistryError::ModelNotFound(comp_type.to_string()));
@@ -112,10 +133,20 @@ type BaseConstructor = dyn Fn(ConfigType, Vec<Dependency>) -> Result<BaseType, B
 /// Fn that returns a `ServoType`, `Arc<Mutex<dyn Servo>>`
 type ServoConstructor = dyn Fn(ConfigType, Vec<Dependency>) -> Result<ServoType, ServoError>;
 
+/// Fn that returns a `SwitchType`, `Arc<Mutex<dyn Switch>>`
+type SwitchConstructor = dyn Fn(ConfigType, Vec<Dependency>) -> Result<SwitchType, SwitchError>;
+
+/// Fn that returns a `ButtonType`, `Arc<Mutex<dyn Button>>`
+type ButtonConstructor = dyn Fn(ConfigType, Vec<Dependency>) -> Result<ButtonType, ButtonError>;
+
 /// Fn that returns a `PowerSensorType`, `Arc<Mutex<dyn PowerSensor>>`
 type PowerSensorConstructor =
     dyn Fn(ConfigType, Vec<Dependency>) -> Result<PowerSensorType, SensorError>;
 
+/// Fn that returns an `AudioInputType`, `Arc<Mutex<dyn AudioInput>>`
+type AudioInputConstructor =
+    dyn Fn(ConfigType, Vec<Dependency>) -> Result<AudioInputType, AudioInputError>;
+
 /// Fn that returns a `GenericComponentType`, `Arc<Mutex<dyn GenericComponentType>>`
 type GenericComponentConstructor =
     dyn Fn(ConfigType, Vec<Dependency>) -> Result<GenericComponentType, GenericError>;
@@ -130,8 +161,11 @@ pub struct ComponentRegistry {
     encoders: Map<&'static str, &'static EncoderConstructor>,
     bases: Map<&'static str, &'static BaseConstructor>,
     servos: Map<&'static str, &'static ServoConstructor>,
+    switches: Map<&'static str, &'static SwitchConstructor>,
+    buttons: Map<&'static str, &'static ButtonConstructor>,
     power_sensors: Map<&'static str, &'static PowerSensorConstructor>,
     generic_components: Map<&'static str, &'static GenericComponentConstructor>,
+    audio_inputs: Map<&'static str, &'static AudioInputConstructor>,
     dependencies: Map<&'static str, Map<&'static str, &'static DependenciesFromConfig>>,
 }
 
@@ -142,25 +176,59 @@ impl Default for ComponentRegistry {
         #[cfg(feature = "builtin-components")]
         {
             crate::common::encoder::register_models(&mut r);
+            crate::common::as5600::register_models(&mut r);
             crate::common::motor::register_models(&mut r);
             crate::common::gpio_motor::register_models(&mut r);
             crate::common::gpio_servo::register_models(&mut r);
+            crate::common::switch::register_models(&mut r);
+            crate::common::gpio_switch::register_models(&mut r);
+            crate::common::button::register_models(&mut r);
+            crate::common::gpio_button::register_models(&mut r);
             crate::common::sensor::register_models(&mut r);
             crate::common::movement_sensor::register_models(&mut r);
             crate::common::mpu6050::register_models(&mut r);
             crate::common::adxl345::register_models(&mut r);
+            crate::common::amg8833::register_models(&mut r);
+            crate::common::gps_ublox::register_models(&mut r);
             crate::common::generic::register_models(&mut r);
             crate::common::ina::register_models(&mut r);
+            crate::common::scd::register_models(&mut r);
+            crate::common::alerts::register_models(&mut r);
+            crate::common::battery::register_models(&mut r);
+            crate::common::calculated::register_models(&mut r);
+            crate::common::geofence::register_models(&mut r);
             crate::common::wheeled_base::register_models(&mut r);
+            crate::common::thermal_protection::register_models(&mut r);
+            crate::common::motor_group::register_models(&mut r);
+            crate::common::telemetry::register_models(&mut r);
+            crate::common::replay::register_models(&mut r);
+            #[cfg(feature = "sim")]
+            crate::common::sim::register_models(&mut r);
+            #[cfg(feature = "grove")]
+            crate::common::grove::register_models(&mut r);
+            #[cfg(feature = "data")]
+            crate::common::data_manager::register_models(&mut r);
         }
         #[cfg(esp32)]
         {
             crate::esp32::board::register_models(&mut r);
             #[cfg(feature = "builtin-components")]
             {
+                #[cfg(esp_idf_bt_bluedroid_enabled)]
+                crate::esp32::ble_sensor::register_models(&mut r);
+                crate::esp32::dynamixel::register_models(&mut r);
                 crate::esp32::encoder::register_models(&mut r);
+                crate::esp32::flow_meter::register_models(&mut r);
+                crate::esp32::gps_nmea::register_models(&mut r);
                 crate::esp32::hcsr04::register_models(&mut r);
+                crate::esp32::i2s_audio::register_models(&mut r);
+                crate::esp32::internal_sensor::register_models(&mut r);
+                crate::esp32::mcpwm_motor::register_models(&mut r);
+                crate::esp32::particulate::register_models(&mut r);
+                crate::esp32::rc_receiver::register_models(&mut r);
+                crate::esp32::serial_motors::register_models(&mut r);
                 crate::esp32::single_encoder::register_models(&mut r);
+                crate::esp32::vesc_can::register_models(&mut r);
             }
         }
         r
@@ -176,8 +244,11 @@ impl ComponentRegistry {
         dependency_func_map.insert(crate::common::sensor::COMPONENT_NAME, Map::new());
         dependency_func_map.insert(crate::common::base::COMPONENT_NAME, Map::new());
         dependency_func_map.insert(crate::common::servo::COMPONENT_NAME, Map::new());
+        dependency_func_map.insert(crate::common::switch::COMPONENT_NAME, Map::new());
+        dependency_func_map.insert(crate::common::button::COMPONENT_NAME, Map::new());
         dependency_func_map.insert(crate::common::power_sensor::COMPONENT_NAME, Map::new());
         dependency_func_map.insert(crate::common::generic::COMPONENT_NAME, Map::new());
+        dependency_func_map.insert(crate::common::audio_input::COMPONENT_NAME, Map::new());
         Self {
             motors: Map::new(),
             board: Map::new(),
@@ -186,8 +257,11 @@ impl ComponentRegistry {
             encoders: Map::new(),
             bases: Map::new(),
             servos: Map::new(),
+            switches: Map::new(),
+            buttons: Map::new(),
             power_sensors: Map::new(),
             generic_components: Map::new(),
+            audio_inputs: Map::new(),
             dependencies: dependency_func_map,
         }
     }
@@ -287,6 +361,30 @@ impl ComponentRegistry {
         Ok(())
     }
 
+    pub fn register_switch(
+        &mut self,
+        model: &'static str,
+        constructor: &'static SwitchConstructor,
+    ) -> Result<(), RegistryError> {
+        if self.switches.contains_key(model) {
+            return Err(RegistryError::ModelAlreadyRegistered(model));
+        }
+        let _ = self.switches.insert(model, constructor);
+        Ok(())
+    }
+
+    pub fn register_button(
+        &mut self,
+        model: &'static str,
+        constructor: &'static ButtonConstructor,
+    ) -> Result<(), RegistryError> {
+        if self.buttons.contains_key(model) {
+            return Err(RegistryError::ModelAlreadyRegistered(model));
+        }
+        let _ = self.buttons.insert(model, constructor);
+        Ok(())
+    }
+
     pub fn register_generic_component(
         &mut self,
         model: &'static str,
@@ -299,6 +397,18 @@ impl ComponentRegistry {
         Ok(())
     }
 
+    pub fn register_audio_input(
+        &mut self,
+        model: &'static str,
+        constructor: &'static AudioInputConstructor,
+    ) -> Result<(), RegistryError> {
+        if self.audio_inputs.contains_key(model) {
+            return Err(RegistryError::ModelAlreadyRegistered(model));
+        }
+        let _ = self.audio_inputs.insert(model, constructor);
+        Ok(())
+    }
+
     pub fn register_dependency_getter(
         &mut self,
         component_type: &'static str,
@@ -426,6 +536,28 @@ impl ComponentRegistry {
         Err(RegistryError::ModelNotFound(model))
     }
 
+    pub(crate) fn get_switch_constructor(
+        &self,
+        model: String,
+    ) -> Result<&'static SwitchConstructor, RegistryError> {
+        let model_name: &str = &model;
+        if let Some(ctor) = self.switches.get(model_name) {
+            return Ok(*ctor);
+        }
+        Err(RegistryError::ModelNotFound(model))
+    }
+
+    pub(crate) fn get_button_constructor(
+        &self,
+        model: String,
+    ) -> Result<&'static ButtonConstructor, RegistryError> {
+        let model_name: &str = &model;
+        if let Some(ctor) = self.buttons.get(model_name) {
+            return Ok(*ctor);
+        }
+        Err(RegistryError::ModelNotFound(model))
+    }
+
     pub(crate) fn get_generic_component_constructor(
         &self,
         model: String,
@@ -436,6 +568,17 @@ impl ComponentRegistry {
         }
         Err(RegistryError::ModelNotFound(model))
     }
+
+    pub(crate) fn get_audio_input_constructor(
+        &self,
+        model: String,
+    ) -> Result<&'static AudioInputConstructor, RegistryError> {
+        let model_name: &str = &model;
+        if let Some(ctor) = self.audio_inputs.get(model_name) {
+            return Ok(*ctor);
+        }
+        Err(RegistryError::ModelNotFound(model))
+    }
 }
 #[cfg(test)]
 mod tests {
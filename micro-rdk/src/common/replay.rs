@@ -0,0 +1,516 @@
+//! Record and replay of sensor readings, used to exercise the data pipeline and application
+//! logic deterministically without hardware.
+//!
+//! A recording is a JSON lines file, each line holding the readings of one sample and the
+//! milliseconds elapsed since the first one:
+//!
+//! ```json
+//! {"t_ms": 0, "readings": {"temperature": 21.5, "humidity": 40}}
+//! {"t_ms": 1000, "readings": {"temperature": 21.7, "humidity": 41}}
+//! ```
+//!
+//! [Recorder] writes recordings in this format from the readings of any sensor. The `replay`
+//! sensor and movement sensor play them back, either from a file or from a recording embedded
+//! in the firmware with [Recording::from_json_lines] and `include_str!`:
+//!
+//! ```json
+//! {
+//!     "path": "/data/drive.jsonl",
+//!     "speed": 2.0,
+//!     "loop": true,
+//!     "step": false
+//! }
+//! ```
+//!
+//! Samples are replayed with their original timing, scaled by `speed` (defaults to 1.0),
+//! starting when the sensor is first read. Playback starts over after the last sample when
+//! `loop` is set (the default) and otherwise stays on the last sample. With `step` set every
+//! read returns the next sample regardless of timing, which makes tests fully deterministic.
+//!
+//! The replay movement sensor expects the readings of a movement sensor (`position`,
+//! `linear_velocity`, `angular_velocity`, `linear_acceleration` and `compass_heading`) and
+//! supports the methods for which the first sample has a reading. Its readings are all taken
+//! from the same sample, a GetReadings request advances one step like any other method.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use thiserror::Error;
+
+use crate::google::protobuf::{value::Kind, Value};
+
+use super::config::ConfigType;
+use super::math_utils::Vector3;
+use super::movement_sensor::{
+    get_movement_sensor_generic_readings, GeoPosition, MovementSensor,
+    MovementSensorSupportedMethods, MovementSensorType,
+};
+use super::registry::{ComponentRegistry, Dependency};
+use super::sensor::{GenericReadingsResult, Readings, Sensor, SensorError, SensorType};
+use super::struct_builder::{json_to_value, value_to_json};
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("replay", &ReplaySensor::from_config)
+        .is_err()
+    {
+        log::error!("replay sensor type is already registered");
+    }
+    if registry
+        .register_movement_sensor("replay", &ReplayMovementSensor::from_config)
+        .is_err()
+    {
+        log::error!("replay movement sensor type is already registered");
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("recording line {0}: {1}")]
+    InvalidLine(usize, String),
+    #[error("recording has no samples")]
+    EmptyRecording,
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+#[derive(Clone, Debug)]
+pub struct Sample {
+    /// Time elapsed since the first sample of the recording
+    pub offset: Duration,
+    pub readings: GenericReadingsResult,
+}
+
+/// Samples of a recording, ordered by time
+#[derive(Clone, Debug)]
+pub struct Recording {
+    samples: Vec<Sample>,
+}
+
+impl Recording {
+    pub fn from_json_lines(lines: &str) -> Result<Self, ReplayError> {
+        let mut samples = lines
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                let invalid = |msg: &str| ReplayError::InvalidLine(idx + 1, msg.to_string());
+                let mut json: serde_json::Value =
+                    serde_json::from_str(line).map_err(|err| invalid(&err.to_string()))?;
+                let t_ms = json
+                    .get("t_ms")
+                    .and_then(|t| t.as_f64())
+                    .filter(|t| *t >= 0.0)
+                    .ok_or_else(|| invalid("missing or negative t_ms"))?;
+                let readings = match json.get_mut("readings").map(|r| r.take()) {
+                    Some(serde_json::Value::Object(readings)) => readings
+                        .into_iter()
+                        .map(|(k, v)| (k, json_to_value(v)))
+                        .collect(),
+                    _ => return Err(invalid("readings should be an object")),
+                };
+                Ok(Sample {
+                    offset: Duration::from_secs_f64(t_ms / 1000.0),
+                    readings,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if samples.is_empty() {
+            return Err(ReplayError::EmptyRecording);
+        }
+        samples.sort_by_key(|sample| sample.offset);
+        Ok(Self { samples })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, ReplayError> {
+        Self::from_json_lines(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    fn duration(&self) -> Duration {
+        self.samples.last().map(|s| s.offset).unwrap_or_default()
+    }
+}
+
+/// Writes the readings of a sensor as a recording
+pub struct Recorder<W: Write> {
+    writer: W,
+    start: Option<Instant>,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            start: None,
+        }
+    }
+
+    /// Appends a sample, timed from the first one recorded
+    pub fn record(&mut self, readings: &GenericReadingsResult) -> Result<(), ReplayError> {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+        self.record_at(now.duration_since(start), readings)
+    }
+
+    pub fn record_at(
+        &mut self,
+        offset: Duration,
+        readings: &GenericReadingsResult,
+    ) -> Result<(), ReplayError> {
+        let readings: serde_json::Map<String, serde_json::Value> = readings
+            .iter()
+            .map(|(k, v)| (k.clone(), value_to_json(v)))
+            .collect();
+        let line = json!({ "t_ms": offset.as_millis() as u64, "readings": readings });
+        writeln!(self.writer, "{}", line)?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Plays a recording back following its timing, or one sample per read in step mode
+pub struct Player {
+    recording: Recording,
+    speed: f64,
+    looping: bool,
+    step: bool,
+    start: Option<Instant>,
+    next_step: usize,
+}
+
+impl Player {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            speed: 1.0,
+            looping: true,
+            step: false,
+            start: None,
+            next_step: 0,
+        }
+    }
+
+    fn from_config(cfg: &ConfigType) -> Result<Self, SensorError> {
+        let path = cfg
+            .get_attribute::<String>("path")
+            .map_err(|_| SensorError::ConfigError("replay sensor requires a path"))?;
+        let recording = Recording::from_file(&path).map_err(|err| {
+            log::error!("couldn't load recording {}: {}", path, err);
+            SensorError::ConfigError("replay sensor couldn't load its recording")
+        })?;
+        let mut player = Self::new(recording);
+        if let Ok(speed) = cfg.get_attribute::<f64>("speed") {
+            if speed <= 0.0 {
+                return Err(SensorError::ConfigError(
+                    "replay sensor speed should be positive",
+                ));
+            }
+            player.speed = speed;
+        }
+        if let Ok(looping) = cfg.get_attribute::<bool>("loop") {
+            player.looping = looping;
+        }
+        if let Ok(step) = cfg.get_attribute::<bool>("step") {
+            player.step = step;
+        }
+        Ok(player)
+    }
+
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_loop(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn with_step(mut self, step: bool) -> Self {
+        self.step = step;
+        self
+    }
+
+    fn first(&self) -> &Sample {
+        &self.recording.samples[0]
+    }
+
+    /// Returns the sample to report at `now`
+    pub fn sample_at(&mut self, now: Instant) -> &Sample {
+        let samples = &self.recording.samples;
+        if self.step {
+            let idx = match self.looping {
+                true => self.next_step % samples.len(),
+                false => self.next_step.min(samples.len() - 1),
+            };
+            self.next_step += 1;
+            return &samples[idx];
+        }
+        let start = *self.start.get_or_insert(now);
+        let mut elapsed = now.duration_since(start).mul_f64(self.speed);
+        let duration = self.recording.duration();
+        if elapsed > duration {
+            if !self.looping || duration.is_zero() {
+                return &samples[samples.len() - 1];
+            }
+            elapsed = Duration::from_nanos((elapsed.as_nanos() % duration.as_nanos()) as u64);
+        }
+        // the last sample recorded at or before the elapsed time
+        let idx = samples.partition_point(|sample| sample.offset <= elapsed);
+        &samples[idx.saturating_sub(1)]
+    }
+}
+
+/// Sensor replaying the readings of a recording
+#[derive(DoCommand, Status)]
+pub struct ReplaySensor {
+    player: Player,
+}
+
+impl ReplaySensor {
+    pub fn new(player: Player) -> Self {
+        Self { player }
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        _: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        Ok(Arc::new(Mutex::new(Self::new(Player::from_config(&cfg)?))))
+    }
+}
+
+impl Sensor for ReplaySensor {}
+
+impl Readings for ReplaySensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        Ok(self.player.sample_at(Instant::now()).readings.clone())
+    }
+}
+
+fn number(value: Option<&Value>) -> Option<f64> {
+    match value?.kind.as_ref()? {
+        Kind::NumberValue(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn fields(value: Option<&Value>) -> Option<&HashMap<String, Value>> {
+    match value?.kind.as_ref()? {
+        Kind::StructValue(v) => Some(&v.fields),
+        _ => None,
+    }
+}
+
+/// Movement sensor replaying the readings of a recording
+#[derive(DoCommand, Status)]
+pub struct ReplayMovementSensor {
+    player: Player,
+    // sample reported by the readings being built, which query every supported method
+    held: Option<GenericReadingsResult>,
+}
+
+impl ReplayMovementSensor {
+    pub fn new(player: Player) -> Self {
+        Self { player, held: None }
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        _: Vec<Dependency>,
+    ) -> Result<MovementSensorType, SensorError> {
+        Ok(Arc::new(Mutex::new(Self::new(Player::from_config(&cfg)?))))
+    }
+
+    fn reading(&mut self, key: &'static str) -> Result<Value, SensorError> {
+        let readings = match self.held.as_ref() {
+            Some(readings) => readings,
+            None => &self.player.sample_at(Instant::now()).readings,
+        };
+        readings
+            .get(key)
+            .cloned()
+            .ok_or(SensorError::SensorGenericError(
+                "replay sample is missing a reading",
+            ))
+    }
+
+    fn vector(&mut self, key: &'static str) -> Result<Vector3, SensorError> {
+        let value = self.reading(key)?;
+        let fields = fields(Some(&value)).ok_or(SensorError::SensorGenericError(
+            "replay vector should be a struct",
+        ))?;
+        Ok(Vector3 {
+            x: number(fields.get("x")).unwrap_or_default(),
+            y: number(fields.get("y")).unwrap_or_default(),
+            z: number(fields.get("z")).unwrap_or_default(),
+        })
+    }
+}
+
+impl Readings for ReplayMovementSensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        self.held = Some(self.player.sample_at(Instant::now()).readings.clone());
+        let readings = get_movement_sensor_generic_readings(self);
+        self.held = None;
+        readings
+    }
+}
+
+impl MovementSensor for ReplayMovementSensor {
+    fn get_position(&mut self) -> Result<GeoPosition, SensorError> {
+        let value = self.reading("position")?;
+        let fields = fields(Some(&value)).ok_or(SensorError::SensorGenericError(
+            "replay position should be a struct",
+        ))?;
+        Ok(GeoPosition {
+            lat: number(fields.get("lat")).unwrap_or_default(),
+            lon: number(fields.get("lon")).unwrap_or_default(),
+            alt: number(fields.get("alt")).unwrap_or_default() as f32,
+        })
+    }
+    fn get_linear_velocity(&mut self) -> Result<Vector3, SensorError> {
+        self.vector("linear_velocity")
+    }
+    fn get_angular_velocity(&mut self) -> Result<Vector3, SensorError> {
+        self.vector("angular_velocity")
+    }
+    fn get_linear_acceleration(&mut self) -> Result<Vector3, SensorError> {
+        self.vector("linear_acceleration")
+    }
+    fn get_compass_heading(&mut self) -> Result<f64, SensorError> {
+        number(Some(&self.reading("compass_heading")?)).ok_or(SensorError::SensorGenericError(
+            "replay compass heading should be a number",
+        ))
+    }
+    fn get_properties(&self) -> MovementSensorSupportedMethods {
+        let first = &self.player.first().readings;
+        MovementSensorSupportedMethods {
+            position_supported: first.contains_key("position"),
+            linear_velocity_supported: first.contains_key("linear_velocity"),
+            angular_velocity_supported: first.contains_key("angular_velocity"),
+            linear_acceleration_supported: first.contains_key("linear_acceleration"),
+            compass_heading_supported: first.contains_key("compass_heading"),
+            orientation_supported: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Player, Recorder, Recording, ReplayMovementSensor};
+    use crate::common::movement_sensor::MovementSensor;
+    use crate::common::sensor::{GenericReadingsResult, Readings};
+    use crate::google::protobuf::{value::Kind, Value};
+    use std::time::{Duration, Instant};
+
+    const RECORDING: &str = r#"
+{"t_ms": 0, "readings": {"temperature": 20.0}}
+{"t_ms": 1000, "readings": {"temperature": 21.0}}
+{"t_ms": 2000, "readings": {"temperature": 22.0}}
+"#;
+
+    fn temperature(readings: &GenericReadingsResult) -> Option<Kind> {
+        readings.get("temperature").unwrap().kind.clone()
+    }
+
+    #[test_log::test]
+    fn test_replay_timing() {
+        let recording = Recording::from_json_lines(RECORDING).unwrap();
+        assert_eq!(recording.samples().len(), 3);
+
+        let mut player = Player::new(recording.clone());
+        let start = Instant::now();
+        let at = |player: &mut Player, ms| {
+            temperature(&player.sample_at(start + Duration::from_millis(ms)).readings)
+        };
+        assert_eq!(at(&mut player, 0), Some(Kind::NumberValue(20.0)));
+        assert_eq!(at(&mut player, 1500), Some(Kind::NumberValue(21.0)));
+        // loops back to the beginning
+        assert_eq!(at(&mut player, 2500), Some(Kind::NumberValue(20.0)));
+
+        let mut player = Player::new(recording.clone())
+            .with_speed(2.0)
+            .with_loop(false);
+        assert_eq!(at(&mut player, 0), Some(Kind::NumberValue(20.0)));
+        assert_eq!(at(&mut player, 600), Some(Kind::NumberValue(21.0)));
+        assert_eq!(at(&mut player, 5000), Some(Kind::NumberValue(22.0)));
+
+        let mut player = Player::new(recording).with_step(true);
+        let values: Vec<_> = (0..4).map(|_| at(&mut player, 0)).collect();
+        assert_eq!(
+            values,
+            [20.0, 21.0, 22.0, 20.0].map(|v| Some(Kind::NumberValue(v)))
+        );
+
+        assert!(Recording::from_json_lines("").is_err());
+        assert!(Recording::from_json_lines(r#"{"readings": {}}"#).is_err());
+    }
+
+    #[test_log::test]
+    fn test_record_and_replay_movement_sensor() {
+        let mut recorder = Recorder::new(Vec::new());
+        let position = Value {
+            kind: Some(Kind::StructValue(crate::google::protobuf::Struct {
+                fields: [("lat", 40.5), ("lon", -74.25), ("alt", 10.0)]
+                    .into_iter()
+                    .map(|(k, v)| {
+                        (
+                            k.to_string(),
+                            Value {
+                                kind: Some(Kind::NumberValue(v)),
+                            },
+                        )
+                    })
+                    .collect(),
+            })),
+        };
+        for (offset, heading) in [(0, 90.0), (100, 180.0)] {
+            let readings: GenericReadingsResult = [
+                ("position".to_string(), position.clone()),
+                (
+                    "compass_heading".to_string(),
+                    Value {
+                        kind: Some(Kind::NumberValue(heading)),
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect();
+            recorder
+                .record_at(Duration::from_millis(offset), &readings)
+                .unwrap();
+        }
+        let lines = String::from_utf8(recorder.into_inner()).unwrap();
+        let recording = Recording::from_json_lines(&lines).unwrap();
+
+        let mut sensor = ReplayMovementSensor::new(Player::new(recording).with_step(true));
+        let props = sensor.get_properties();
+        assert!(props.position_supported && props.compass_heading_supported);
+        assert!(!props.linear_velocity_supported);
+        // the readings are those of the first sample only
+        let readings = sensor.get_generic_readings().unwrap();
+        assert_eq!(
+            readings.get("compass_heading").unwrap().kind,
+            Some(Kind::NumberValue(90.0))
+        );
+        assert!(readings.contains_key("position"));
+        let position = sensor.get_position().unwrap();
+        assert_eq!(
+            (position.lat, position.lon, position.alt),
+            (40.5, -74.25, 10.0)
+        );
+        // every call advances a step, looping back to the first sample
+        assert_eq!(sensor.get_compass_heading().unwrap(), 90.0);
+    }
+}
@@ -0,0 +1,343 @@
+//! Package roboclaw implements a motor driven by one channel of a Basicmicro Roboclaw
+//! controller in packet serial mode. The two channels of a controller are configured as two
+//! motors sharing the serial port, see `esp32::serial_motors` for the port configuration.
+//!
+//! ```json
+//! {
+//!     "address": 128,
+//!     "channel": 1,
+//!     "max_rpm": 300,
+//!     "ticks_per_rotation": 1024
+//! }
+//! ```
+//!
+//! `address` is the packet serial address of the controller (128 by default), `channel` the
+//! output driving the motor (1 or 2). `set_power` sets the duty cycle of the channel. When the
+//! motor has an encoder connected to the controller, `ticks_per_rotation` enables position
+//! reporting and `go_for` is run with the speed control of the controller, otherwise `go_for`
+//! sets a duty cycle proportional to `max_rpm` (required).
+//!
+//! The status reports the `current_a` of the channel, the `temperature_c` of the controller and
+//! the `battery_v` of its main battery.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::actuator::{Actuator, ActuatorError};
+use super::config::{AttributeError, ConfigType};
+use super::math_utils::go_for_math;
+use super::motor::{Motor, MotorError, MotorSupportedProperties, MotorType};
+use super::status::{status_envelope, ComponentHealth, Status, StatusError};
+use super::uart::{UartError, UartType};
+use crate::google::protobuf::{value::Kind, Struct};
+
+pub const DEFAULT_ADDRESS: u8 = 128;
+const ACK: u8 = 0xFF;
+const REPLY_TIMEOUT: Duration = Duration::from_millis(50);
+
+// packet serial commands, the M2 variant of a channel command follows the M1 one
+const CMD_READ_ENCODER_M1: u8 = 16;
+const CMD_READ_MAIN_BATTERY: u8 = 24;
+const CMD_DUTY_M1: u8 = 32;
+const CMD_SPEED_M1: u8 = 35;
+const CMD_READ_CURRENTS: u8 = 49;
+const CMD_READ_TEMPERATURE: u8 = 82;
+
+const MAX_DUTY: f64 = 32767.0;
+
+/// CRC16-CCITT (XMODEM) used to check packet serial messages
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0_u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[derive(DoCommand)]
+pub struct RoboclawMotor {
+    uart: UartType,
+    address: u8,
+    // 0 for M1, 1 for M2
+    channel: u8,
+    max_rpm: f64,
+    ticks_per_rotation: Option<u32>,
+    power: f64,
+    health: ComponentHealth,
+}
+
+impl RoboclawMotor {
+    pub fn new(uart: UartType, address: u8, channel: u8, max_rpm: f64) -> Result<Self, MotorError> {
+        if !(1..=2).contains(&channel) {
+            return Err(MotorError::ConfigError(
+                "roboclaw: `channel` must be 1 or 2",
+            ));
+        }
+        Ok(Self {
+            uart,
+            address,
+            channel: channel - 1,
+            max_rpm,
+            ticks_per_rotation: None,
+            power: 0.0,
+            health: ComponentHealth::new(),
+        })
+    }
+
+    /// Builds the motor from the attributes of the `roboclaw` model, once the platform has
+    /// opened the serial port the controller is connected to
+    pub fn from_uart_and_config(uart: UartType, cfg: ConfigType) -> Result<MotorType, MotorError> {
+        let address = match cfg.get_attribute::<u8>("address") {
+            Ok(address) => address,
+            Err(AttributeError::KeyNotFound(_)) => DEFAULT_ADDRESS,
+            Err(_) => return Err(MotorError::ConfigError("roboclaw: invalid `address`")),
+        };
+        let channel = cfg
+            .get_attribute::<u8>("channel")
+            .map_err(|_| MotorError::ConfigError("roboclaw: missing `channel`"))?;
+        let max_rpm = cfg
+            .get_attribute::<f64>("max_rpm")
+            .map_err(|_| MotorError::ConfigError("roboclaw: missing `max_rpm`"))?;
+        let mut motor = Self::new(uart, address, channel, max_rpm)?;
+        motor.ticks_per_rotation = match cfg.get_attribute::<u32>("ticks_per_rotation") {
+            Ok(ticks) if ticks > 0 => Some(ticks),
+            Err(AttributeError::KeyNotFound(_)) => None,
+            _ => {
+                return Err(MotorError::ConfigError(
+                    "roboclaw: `ticks_per_rotation` must be a positive integer",
+                ))
+            }
+        };
+        Ok(Arc::new(Mutex::new(motor)))
+    }
+
+    // sends a command and waits for the controller to acknowledge it
+    fn write_command(&self, command: u8, data: &[u8]) -> Result<(), UartError> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.extend([self.address, command]);
+        packet.extend_from_slice(data);
+        packet.extend(crc16(&packet).to_be_bytes());
+        let mut uart = self.uart.lock().unwrap();
+        uart.clear_input()?;
+        uart.write(&packet)?;
+        let mut ack = [0_u8];
+        uart.read_exact(&mut ack, REPLY_TIMEOUT)?;
+        if ack[0] != ACK {
+            return Err(UartError::InvalidReply(
+                "roboclaw didn't acknowledge command",
+            ));
+        }
+        Ok(())
+    }
+
+    // sends a read command and returns the `len` bytes of its reply once its CRC is checked
+    fn read_command(&self, command: u8, len: usize) -> Result<Vec<u8>, UartError> {
+        let mut uart = self.uart.lock().unwrap();
+        uart.clear_input()?;
+        uart.write(&[self.address, command])?;
+        let mut reply = vec![0_u8; len + 2];
+        uart.read_exact(&mut reply, REPLY_TIMEOUT)?;
+        let crc = u16::from_be_bytes([reply[len], reply[len + 1]]);
+        reply.truncate(len);
+        let mut checked = vec![self.address, command];
+        checked.extend_from_slice(&reply);
+        if crc16(&checked) != crc {
+            return Err(UartError::InvalidReply("roboclaw reply has an invalid CRC"));
+        }
+        Ok(reply)
+    }
+
+    fn apply_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&pct) {
+            return Err(MotorError::PowerSetError);
+        }
+        let duty = (pct * MAX_DUTY).round() as i16;
+        self.write_command(CMD_DUTY_M1 + self.channel, &duty.to_be_bytes())?;
+        self.power = pct;
+        Ok(())
+    }
+
+    fn apply_rpm(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        let (pct, dur) = go_for_math(self.max_rpm, rpm, revolutions)?;
+        match self.ticks_per_rotation {
+            Some(ticks) => {
+                // the controller regulates the speed in encoder ticks per second
+                let qpps = (pct * self.max_rpm * ticks as f64 / 60.0).round() as i32;
+                self.write_command(CMD_SPEED_M1 + self.channel, &qpps.to_be_bytes())?;
+                self.power = pct;
+            }
+            None => self.apply_power(pct)?,
+        }
+        Ok(dur)
+    }
+
+    fn current_a(&self) -> Result<f64, UartError> {
+        let reply = self.read_command(CMD_READ_CURRENTS, 4)?;
+        let offset = self.channel as usize * 2;
+        // in units of 10mA
+        Ok(i16::from_be_bytes([reply[offset], reply[offset + 1]]) as f64 / 100.0)
+    }
+
+    fn temperature_c(&self) -> Result<f64, UartError> {
+        let reply = self.read_command(CMD_READ_TEMPERATURE, 2)?;
+        Ok(u16::from_be_bytes([reply[0], reply[1]]) as f64 / 10.0)
+    }
+
+    fn battery_v(&self) -> Result<f64, UartError> {
+        let reply = self.read_command(CMD_READ_MAIN_BATTERY, 2)?;
+        Ok(u16::from_be_bytes([reply[0], reply[1]]) as f64 / 10.0)
+    }
+}
+
+impl Motor for RoboclawMotor {
+    fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        let res = self.apply_power(pct);
+        self.health.record(&res);
+        res
+    }
+
+    fn get_position(&mut self) -> Result<i32, MotorError> {
+        if self.ticks_per_rotation.is_none() {
+            return Err(MotorError::MissingEncoder);
+        }
+        let res = self.read_command(CMD_READ_ENCODER_M1 + self.channel, 5);
+        self.health.record(&res);
+        let reply = res?;
+        Ok(i32::from_be_bytes([reply[0], reply[1], reply[2], reply[3]]))
+    }
+
+    fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        let res = self.apply_rpm(rpm, revolutions);
+        self.health.record(&res);
+        res
+    }
+
+    fn get_properties(&mut self) -> MotorSupportedProperties {
+        MotorSupportedProperties {
+            position_reporting: self.ticks_per_rotation.is_some(),
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: self.ticks_per_rotation,
+        }
+    }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
+}
+
+impl Actuator for RoboclawMotor {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(self.power != 0.0)
+    }
+
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        let res = self.apply_power(0.0);
+        self.health.record(&res);
+        res.map_err(|_| ActuatorError::CouldntStop)
+    }
+}
+
+impl Status for RoboclawMotor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        let mut fields = vec![("power", Kind::NumberValue(self.power))];
+        // telemetry the controller doesn't answer is left out
+        for (name, value) in [
+            ("current_a", self.current_a()),
+            ("temperature_c", self.temperature_c()),
+            ("battery_v", self.battery_v()),
+        ] {
+            match value {
+                Ok(value) => fields.push((name, Kind::NumberValue(value))),
+                Err(err) => log::debug!("roboclaw: failed to read {}: {}", name, err),
+            }
+        }
+        Ok(Some(status_envelope(&self.health, fields)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc16, RoboclawMotor};
+    use crate::common::actuator::Actuator;
+    use crate::common::motor::{Motor, MotorError};
+    use crate::common::status::Status;
+    use crate::common::uart::FakeUart;
+    use crate::google::protobuf::value::Kind;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // a reply to a read command of the controller at 128, followed by its CRC
+    fn reply(command: u8, data: &[u8]) -> Vec<u8> {
+        let mut checked = vec![128, command];
+        checked.extend_from_slice(data);
+        let mut reply = data.to_vec();
+        reply.extend(crc16(&checked).to_be_bytes());
+        reply
+    }
+
+    #[test_log::test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+    }
+
+    #[test_log::test]
+    fn test_roboclaw_commands() -> Result<(), MotorError> {
+        let uart = Arc::new(Mutex::new(FakeUart::default()));
+        let mut m2 = RoboclawMotor::new(uart.clone(), 128, 2, 300.0)?;
+        assert!(RoboclawMotor::new(uart.clone(), 128, 3, 300.0).is_err());
+
+        uart.lock().unwrap().replies.push_back(0xFF);
+        m2.set_power(-0.5)?;
+        let written = std::mem::take(&mut uart.lock().unwrap().written);
+        let duty = (-16384_i16).to_be_bytes();
+        let crc = crc16(&[128, 33, duty[0], duty[1]]).to_be_bytes();
+        assert_eq!(written, vec![128, 33, duty[0], duty[1], crc[0], crc[1]]);
+        assert_eq!(m2.is_powered()?, (true, -0.5));
+
+        // without acknowledgement the command fails
+        assert!(m2.stop().is_err());
+        uart.lock().unwrap().written.clear();
+
+        // with an encoder go_for uses the speed control, 60rpm at 1024 ticks per rotation
+        m2.ticks_per_rotation = Some(1024);
+        uart.lock().unwrap().replies.push_back(0xFF);
+        assert_eq!(m2.go_for(60.0, 15.0)?, Some(Duration::from_secs(15)));
+        let written = std::mem::take(&mut uart.lock().unwrap().written);
+        assert_eq!(written[..6], [128, 36, 0, 0, 4, 0]);
+
+        uart.lock()
+            .unwrap()
+            .replies
+            .extend(reply(17, &[0, 0, 0x10, 0, 0]));
+        assert_eq!(m2.get_position()?, 4096);
+        // a corrupted reply is rejected
+        let mut corrupted = reply(17, &[0, 0, 0x10, 0, 0]);
+        corrupted[6] ^= 1;
+        uart.lock().unwrap().replies.extend(corrupted);
+        assert!(m2.get_position().is_err());
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_roboclaw_telemetry() -> Result<(), MotorError> {
+        let uart = Arc::new(Mutex::new(FakeUart::default()));
+        let m1 = RoboclawMotor::new(uart.clone(), 128, 1, 300.0)?;
+        {
+            let mut uart = uart.lock().unwrap();
+            // 1.5A on M1, 0.2A on M2
+            uart.replies.extend(reply(49, &[0, 150, 0, 20]));
+            uart.replies.extend(reply(82, &[1, 0x2C]));
+            // no answer about the battery
+        }
+        let status = m1.get_status().unwrap().unwrap();
+        let field = |name: &str| status.fields.get(name).and_then(|v| v.kind.clone());
+        assert_eq!(field("current_a"), Some(Kind::NumberValue(1.5)));
+        assert_eq!(field("temperature_c"), Some(Kind::NumberValue(30.0)));
+        assert_eq!(field("battery_v"), None);
+        Ok(())
+    }
+}
@@ -2,8 +2,9 @@
 
 use chrono::{DateTime, FixedOffset};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 #[cfg(feature = "camera")]
@@ -31,20 +32,28 @@ use log::*;
 use super::data_collector::{DataCollectionError, DataCollector, DataCollectorConfig};
 use super::{
     actuator::ActuatorError,
+    audio_input::{AudioInput, AudioInputType},
     base::BaseType,
     board::BoardType,
+    button::{Button, ButtonType},
     config::{AttributeError, Component, ConfigType, DynamicComponentConfig},
     encoder::EncoderType,
+    frame::ComponentFrame,
     generic::{GenericComponent, GenericComponentType},
+    grpc::{GrpcError, GrpcStatusHint},
+    instrumentation::COLLECTOR_NAME as INSTRUMENTATION_COLLECTOR_NAME,
     motor::MotorType,
     movement_sensor::MovementSensorType,
     power_sensor::{PowerSensor, PowerSensorType},
     registry::{
-        get_board_from_dependencies, ComponentRegistry, Dependency, RegistryError, ResourceKey,
+        get_board_from_dependencies, short_resource_name, ComponentRegistry, Dependency,
+        RegistryError, ResourceKey,
     },
-    sensor::SensorType,
+    sensor::{GenericReadingsResult, Readings, SensorError, SensorType},
     servo::{Servo, ServoType},
     status::StatusError,
+    switch::{Switch, SwitchType},
+    telemetry::record_telemetry,
 };
 
 use thiserror::Error;
@@ -61,7 +70,10 @@ pub enum ResourceType {
     Encoder(EncoderType),
     PowerSensor(PowerSensorType),
     Servo(ServoType),
+    Switch(SwitchType),
+    Button(ButtonType),
     Generic(GenericComponentType),
+    AudioInput(AudioInputType),
     #[cfg(feature = "camera")]
     Camera(CameraType),
 }
@@ -80,17 +92,107 @@ impl ResourceType {
             Self::PowerSensor(_) => "rdk:component:power_sensor",
             Self::Sensor(_) => "rdk:component:sensor",
             Self::Servo(_) => "rdk:component:servo",
+            Self::Switch(_) => "rdk:component:switch",
+            Self::Button(_) => "rdk:component:button",
+            Self::AudioInput(_) => "rdk:component:audio_input",
+            #[cfg(feature = "camera")]
+            Self::Camera(_) => "rdk:component:camera",
         }
         .to_string()
     }
+
+    /// Returns the fully qualified name of the gRPC service implementing the resource's API
+    pub fn proto_service(&self) -> &'static str {
+        match self {
+            Self::Base(_) => "viam.component.base.v1.BaseService",
+            Self::Board(_) => "viam.component.board.v1.BoardService",
+            Self::Encoder(_) => "viam.component.encoder.v1.EncoderService",
+            Self::Generic(_) => "viam.component.generic.v1.GenericService",
+            Self::Motor(_) => "viam.component.motor.v1.MotorService",
+            Self::MovementSensor(_) => "viam.component.movementsensor.v1.MovementSensorService",
+            Self::PowerSensor(_) => "viam.component.powersensor.v1.PowerSensorService",
+            Self::Sensor(_) => "viam.component.sensor.v1.SensorService",
+            Self::Servo(_) => "viam.component.servo.v1.ServoService",
+            Self::Switch(_) => "viam.component.switch.v1.SwitchService",
+            Self::Button(_) => "viam.component.button.v1.ButtonService",
+            Self::AudioInput(_) => "viam.component.audioinput.v1.AudioInputService",
+            #[cfg(feature = "camera")]
+            Self::Camera(_) => "viam.component.camera.v1.CameraService",
+        }
+    }
+}
+
+/// Describes which driver backs a resource, see [LocalRobot::get_resource_metadata]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourceMetadata {
+    pub model: String,
+    pub api: &'static str,
+}
+
+/// Components reported by the boot profile summary logged once the robot is built
+const BOOT_PROFILE_SLOWEST: usize = 5;
+
+/// Time spent building the robot from its config, to find the drivers slowing startup
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BootProfile {
+    /// Time taken by [LocalRobot::from_cloud_config]
+    pub total: Duration,
+    /// Time spent constructing each component that was built, attempts made before its
+    /// dependencies were ready included, in the order they were built
+    pub components: Vec<(String, Duration)>,
+}
+
+impl BootProfile {
+    fn log_summary(&self) {
+        let mut slowest: Vec<&(String, Duration)> = self.components.iter().collect();
+        slowest.sort_by(|a, b| b.1.cmp(&a.1));
+        log::info!(
+            "robot built in {} ms, {} components",
+            self.total.as_millis(),
+            self.components.len()
+        );
+        for (name, duration) in slowest.into_iter().take(BOOT_PROFILE_SLOWEST) {
+            log::info!("  {}: {} ms", name, duration.as_millis());
+        }
+    }
+
+    /// Records the profile as the `robot_build_ms` and `boot_profile` telemetry entries
+    fn record(&self) {
+        let components = self
+            .components
+            .iter()
+            .map(|(name, duration)| {
+                (
+                    name.clone(),
+                    google::protobuf::Value {
+                        kind: Some(google::protobuf::value::Kind::NumberValue(
+                            duration.as_secs_f64() * 1000.0,
+                        )),
+                    },
+                )
+            })
+            .collect();
+        record_telemetry("robot_build_ms", self.total.as_secs_f64() * 1000.0);
+        record_telemetry(
+            "boot_profile",
+            google::protobuf::Struct { fields: components },
+        );
+    }
 }
 
 #[derive(Default)]
 pub struct LocalRobot {
     resources: ResourceMap,
+    resource_metadata: HashMap<ResourceName, ResourceMetadata>,
+    // alternative names declared through the `aliases` attribute, mapped to the resource's name
+    aliases: HashMap<ResourceName, ResourceName>,
     build_time: Option<DateTime<FixedOffset>>,
+    // frames declared in the config of components, reported through `GetGeometries` and
+    // `FrameSystemConfig`
+    frames: HashMap<ResourceName, ComponentFrame>,
     #[cfg(feature = "data")]
     data_collector_configs: Vec<(ResourceName, DataCollectorConfig)>,
+    boot_profile: BootProfile,
 }
 
 #[derive(Error, Debug)]
@@ -115,11 +217,58 @@ pub enum RobotError {
     RobotActuatorError(#[from] ActuatorError),
     #[error("resource not found with name {0} and component_type {1}")]
     ResourceNotFound(String, String),
+    /// Invalid config of the component named first, contributed by the fragment whose id
+    /// follows when it is known
+    #[error(
+        "invalid config for component `{0}`{}: {2}",
+        .1.as_ref().map(|id| format!(" from fragment {}", id)).unwrap_or_default()
+    )]
+    RobotInvalidComponentConfig(String, Option<String>, String),
     #[cfg(feature = "data")]
     #[error(transparent)]
     DataCollectorInitError(#[from] DataCollectionError),
 }
 
+impl GrpcStatusHint for RobotError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::ResourceNotFound(_, _) => GrpcError::RpcNotFound,
+            Self::RobotComponentTypeNotSupported(_) => GrpcError::RpcUnimplemented,
+            Self::RobotParseConfigError(err) => err.grpc_error(),
+            Self::RobotActuatorError(err) => err.grpc_error(),
+            Self::RobotNoBoard
+            | Self::RobotModelWrongPrefix(_)
+            | Self::RobotModelAbsent
+            | Self::RobotDependencyMissing(_, _)
+            | Self::RobotInvalidComponentConfig(_, _, _) => GrpcError::RpcFailedPrecondition,
+            _ => GrpcError::RpcInternal,
+        }
+    }
+}
+
+// Catches configs missing fields every component needs before any driver is involved, this
+// typically happens with components coming from fragments that rely on defaults
+fn validate_component_config(cfg: &DynamicComponentConfig) -> Result<(), RobotError> {
+    let reason = if cfg.name.is_empty() {
+        "name is missing"
+    } else if cfg.r#type.is_empty() {
+        "neither `type` nor `api` is set"
+    } else if cfg.model.is_empty() {
+        "model is missing"
+    } else if !cfg.model.starts_with(NAMESPACE_PREFIX) {
+        "model should be prefixed with 'rdk:builtin:'"
+    } else if cfg.name == INSTRUMENTATION_COLLECTOR_NAME {
+        "name is reserved for instrumentation"
+    } else {
+        return Ok(());
+    };
+    Err(RobotError::RobotInvalidComponentConfig(
+        cfg.name.clone(),
+        None,
+        reason.to_owned(),
+    ))
+}
+
 fn resource_name_from_component_cfg(cfg: &DynamicComponentConfig) -> ResourceName {
     ResourceName {
         namespace: cfg.namespace.to_string(),
@@ -168,8 +317,18 @@ impl LocalRobot {
             let constructor = registry
                 .get_board_constructor(model)
                 .map_err(RobotError::RobotRegistryError)?;
+            let start = Instant::now();
             let board = constructor(ConfigType::Dynamic(config))
                 .map_err(|e| RobotError::RobotResourceBuildError(e.into()))?;
+            #[cfg(feature = "builtin-components")]
+            let board = crate::common::gpio_expander::ExpandedBoard::wrap_from_config(
+                board,
+                ConfigType::Dynamic(config),
+            )
+            .map_err(|e| RobotError::RobotResourceBuildError(e.into()))?;
+            self.boot_profile
+                .components
+                .push((config.name.clone(), start.elapsed()));
             (Some(board), board_key)
         } else {
             (None, None)
@@ -178,32 +337,47 @@ impl LocalRobot {
         let max_iteration = resource_to_build * 2;
         let mut num_iteration = 0;
         let mut iter = (0..resource_to_build).cycle();
+        // last error met while building each component, reported if it never gets built
+        let mut build_errors: Vec<Option<RobotError>> = std::iter::repeat_with(|| None)
+            .take(resource_to_build)
+            .collect();
+        let mut build_durations = vec![Duration::ZERO; resource_to_build];
         while resource_to_build > 0 && num_iteration < max_iteration {
             num_iteration += 1;
-            let cfg = &mut components[iter.next().unwrap()];
+            let idx = iter.next().unwrap();
+            let cfg = &mut components[idx];
             if let Some(cfg) = cfg.as_ref() {
-                // capture the error and make it available to LocalRobot so it can be pushed in the logs?
-                if self
-                    .build_resource(cfg, board.clone(), board_key.clone(), &mut registry)
-                    .is_err()
-                {
+                let start = Instant::now();
+                let built =
+                    self.build_resource(cfg, board.clone(), board_key.clone(), &mut registry);
+                build_durations[idx] += start.elapsed();
+                if let Err(e) = built {
+                    build_errors[idx] = Some(e);
                     continue;
                 }
             } else {
                 continue;
             }
-            let _ = cfg.take();
+            let name = cfg.take().unwrap().name;
+            self.boot_profile
+                .components
+                .push((name, build_durations[idx]));
             resource_to_build -= 1;
         }
         if resource_to_build > 0 {
             log::error!(
                 "These components couldn't be built {:?}. Check for errors, missing or circular dependencies in the config.",
                 components
-                    .into_iter()
+                    .iter()
                     .flatten()
-                    .map(|x| x.name)
+                    .map(|x| x.name.clone())
                     .collect::<Vec<String>>()
-            )
+            );
+            for (cfg, err) in components.iter().zip(build_errors) {
+                if let (Some(cfg), Some(err)) = (cfg, err) {
+                    log::error!("component `{}` ({}): {}", cfg.name, cfg.model, err);
+                }
+            }
         }
         Ok(())
     }
@@ -211,35 +385,82 @@ impl LocalRobot {
     // Creates a robot from the response of a gRPC call to acquire the robot configuration. The individual
     // component configs within the response are consumed and the corresponding components are generated
     // and added to the created robot.
+    // An invalid component config fails the creation of the robot, unless the config sets
+    // `disable_partial_start` to false: the component is then skipped with an error log.
     pub fn from_cloud_config(
         config_resp: &ConfigResponse,
         registry: Box<ComponentRegistry>,
         build_time: Option<DateTime<FixedOffset>>,
     ) -> Result<Self, RobotError> {
+        let start = Instant::now();
         let mut robot = LocalRobot {
             resources: ResourceMap::new(),
+            resource_metadata: HashMap::new(),
+            aliases: HashMap::new(),
             // Use date time pulled off gRPC header as the `build_time` returned in the status of
             // every resource as `last_reconfigured`.
             build_time,
+            frames: HashMap::new(),
             #[cfg(feature = "data")]
             data_collector_configs: vec![],
+            boot_profile: BootProfile::default(),
         };
 
-        let components: Result<Vec<Option<DynamicComponentConfig>>, AttributeError> = config_resp
-            .config
-            .as_ref()
-            .unwrap()
-            .components
-            .iter()
-            .map(|x| x.try_into().map(Option::Some))
-            .collect();
-        robot.process_components(
-            components.map_err(RobotError::RobotParseConfigError)?,
-            registry,
-        )?;
+        let config = config_resp.config.as_ref().unwrap();
+        // an invalid component fails the robot unless the config explicitly turns partial start
+        // on, in which case it is skipped and the rest of the robot is built
+        let partial_start = config.disable_partial_start == Some(false);
+        let mut seen = HashSet::new();
+        let mut components = Vec::with_capacity(config.components.len());
+        for cfg in config.components.iter() {
+            // the merged config doesn't say which fragment a component comes from, so problems
+            // are reported against the component's name without a fragment
+            let component = DynamicComponentConfig::try_from(cfg)
+                .map_err(|e| {
+                    RobotError::RobotInvalidComponentConfig(cfg.name.clone(), None, e.to_string())
+                })
+                .and_then(|component| {
+                    validate_component_config(&component)?;
+                    if !seen.insert((component.r#type.clone(), component.name.clone())) {
+                        return Err(RobotError::RobotInvalidComponentConfig(
+                            component.name,
+                            None,
+                            "declared more than once, check the part's fragments".to_owned(),
+                        ));
+                    }
+                    Ok(component)
+                });
+            // a bad frame only affects motion planning, it doesn't prevent building the component
+            if let Ok(component) = component.as_ref() {
+                match cfg.frame.as_ref().map(ComponentFrame::try_from) {
+                    Some(Ok(frame)) => {
+                        robot
+                            .frames
+                            .insert(resource_name_from_component_cfg(component), frame);
+                    }
+                    Some(Err(e)) => log::error!("ignoring frame of {}: {}", component.name, e),
+                    None => {}
+                }
+            }
+            match component {
+                Ok(component) => components.push(Some(component)),
+                Err(e) if partial_start => log::error!("skipping component: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        robot.process_components(components, registry)?;
+        robot.boot_profile.total = start.elapsed();
+        robot.boot_profile.log_summary();
+        robot.boot_profile.record();
         Ok(robot)
     }
 
+    /// Time spent building the robot from its config, empty unless it was built with
+    /// [Self::from_cloud_config]
+    pub fn boot_profile(&self) -> &BootProfile {
+        &self.boot_profile
+    }
+
     fn build_resource(
         &mut self,
         config: &DynamicComponentConfig,
@@ -287,7 +508,10 @@ impl LocalRobot {
             "base" => crate::common::base::COMPONENT_NAME,
             "power_sensor" => crate::common::power_sensor::COMPONENT_NAME,
             "servo" => crate::common::servo::COMPONENT_NAME,
+            "switch" => crate::common::switch::COMPONENT_NAME,
+            "button" => crate::common::button::COMPONENT_NAME,
             "generic" => crate::common::generic::COMPONENT_NAME,
+            "audio_input" => crate::common::audio_input::COMPONENT_NAME,
             &_ => {
                 return Err(RobotError::RobotComponentTypeNotSupported(
                     config.get_type().to_owned(),
@@ -309,7 +533,7 @@ impl LocalRobot {
                     name: key.1.clone(),
                 };
 
-                let res = match self.resources.get(&r_name) {
+                let res = match self.get_resource(&r_name) {
                     Some(r) => r.clone(),
                     None => {
                         return Err(RobotError::RobotDependencyMissing(
@@ -332,6 +556,10 @@ impl LocalRobot {
         registry: &mut ComponentRegistry,
     ) -> Result<(), RobotError> {
         let r_type = cfg.get_type();
+        let aliases = cfg
+            .get_attribute::<Vec<String>>("aliases")
+            .unwrap_or_default();
+        let model_name = model.clone();
         let res = match r_type {
             "motor" => {
                 let ctor = registry
@@ -396,6 +624,22 @@ impl LocalRobot {
                     ctor(cfg, deps).map_err(|e| RobotError::RobotResourceBuildError(e.into()))?,
                 )
             }
+            "switch" => {
+                let ctor = registry
+                    .get_switch_constructor(model)
+                    .map_err(RobotError::RobotRegistryError)?;
+                ResourceType::Switch(
+                    ctor(cfg, deps).map_err(|e| RobotError::RobotResourceBuildError(e.into()))?,
+                )
+            }
+            "button" => {
+                let ctor = registry
+                    .get_button_constructor(model)
+                    .map_err(RobotError::RobotRegistryError)?;
+                ResourceType::Button(
+                    ctor(cfg, deps).map_err(|e| RobotError::RobotResourceBuildError(e.into()))?,
+                )
+            }
             "generic" => {
                 let ctor = registry
                     .get_generic_component_constructor(model)
@@ -404,16 +648,65 @@ impl LocalRobot {
                     ctor(cfg, deps).map_err(|e| RobotError::RobotResourceBuildError(e.into()))?,
                 )
             }
+            "audio_input" => {
+                let ctor = registry
+                    .get_audio_input_constructor(model)
+                    .map_err(RobotError::RobotRegistryError)?;
+                ResourceType::AudioInput(
+                    ctor(cfg, deps).map_err(|e| RobotError::RobotResourceBuildError(e.into()))?,
+                )
+            }
             &_ => {
                 return Err(RobotError::RobotComponentTypeNotSupported(
                     r_type.to_owned(),
                 ));
             }
         };
+        self.resource_metadata.insert(
+            r_name.clone(),
+            ResourceMetadata {
+                model: model_name,
+                api: res.proto_service(),
+            },
+        );
+        for alias in aliases {
+            let alias = ResourceName {
+                name: alias,
+                ..r_name.clone()
+            };
+            if self.resources.contains_key(&alias) || self.aliases.contains_key(&alias) {
+                log::warn!(
+                    "alias {} of {} is already used by another resource, ignoring it",
+                    alias.name,
+                    r_name.name
+                );
+                continue;
+            }
+            self.aliases.insert(alias, r_name.clone());
+        }
         self.resources.insert(r_name, res);
         Ok(())
     }
 
+    /// Maps a name received from a client or a config to the name the resource was registered
+    /// under, accepting short, remote-prefixed or fully qualified names as well as aliases
+    fn resolve_name(&self, name: &ResourceName) -> Option<ResourceName> {
+        let short = ResourceName {
+            name: short_resource_name(&name.name).to_owned(),
+            ..name.clone()
+        };
+        if self.resources.contains_key(&short) {
+            return Some(short);
+        }
+        self.aliases.get(&short).cloned()
+    }
+
+    fn get_resource(&self, name: &ResourceName) -> Option<&ResourceType> {
+        self.resources
+            .get(name)
+            .or_else(|| self.resources.get(&self.resolve_name(name)?))
+    }
+
     #[cfg(feature = "data")]
     pub fn data_collectors(&self) -> Result<Vec<DataCollector>, RobotError> {
         let mut res = Vec::new();
@@ -431,7 +724,7 @@ impl LocalRobot {
     }
 
     pub fn get_status(
-        &mut self,
+        &self,
         mut msg: robot::v1::GetStatusRequest,
     ) -> Result<Vec<robot::v1::Status>, StatusError> {
         let last_reconfigured_proto = self.build_time.map(|bt| google::protobuf::Timestamp {
@@ -440,10 +733,14 @@ impl LocalRobot {
         });
         if msg.resource_names.is_empty() {
             let mut vec = Vec::with_capacity(self.resources.len());
-            for (name, val) in self.resources.iter_mut() {
+            // components are queried through cloned handles, so only their own locks are held
+            for (name, val) in self.resources.clone().iter_mut() {
                 match val {
                     ResourceType::Motor(m) => {
-                        let status = m.get_status()?;
+                        let mut status = m.get_status()?;
+                        if let Some(status) = status.as_mut() {
+                            m.get_properties().extend_status(status);
+                        }
                         vec.push(robot::v1::Status {
                             name: Some(name.clone()),
                             last_reconfigured: last_reconfigured_proto.clone(),
@@ -506,6 +803,22 @@ impl LocalRobot {
                             status,
                         });
                     }
+                    ResourceType::Switch(b) => {
+                        let status = b.get_status()?;
+                        vec.push(robot::v1::Status {
+                            name: Some(name.clone()),
+                            last_reconfigured: last_reconfigured_proto.clone(),
+                            status,
+                        });
+                    }
+                    ResourceType::Button(b) => {
+                        let status = b.get_status()?;
+                        vec.push(robot::v1::Status {
+                            name: Some(name.clone()),
+                            last_reconfigured: last_reconfigured_proto.clone(),
+                            status,
+                        });
+                    }
                     ResourceType::Generic(b) => {
                         let status = b.get_status()?;
                         vec.push(robot::v1::Status {
@@ -514,6 +827,14 @@ impl LocalRobot {
                             status,
                         });
                     }
+                    ResourceType::AudioInput(b) => {
+                        let status = b.get_status()?;
+                        vec.push(robot::v1::Status {
+                            name: Some(name.clone()),
+                            last_reconfigured: last_reconfigured_proto.clone(),
+                            status,
+                        });
+                    }
                     #[cfg(feature = "camera")]
                     _ => continue,
                 };
@@ -523,11 +844,14 @@ impl LocalRobot {
         let mut vec = Vec::with_capacity(msg.resource_names.len());
         for name in msg.resource_names.drain(0..) {
             debug!("processing {:?}", name);
-            match self.resources.get_mut(&name) {
-                Some(val) => {
-                    match val {
+            match self.get_resource(&name).cloned() {
+                Some(mut val) => {
+                    match &mut val {
                         ResourceType::Motor(m) => {
-                            let status = m.get_status()?;
+                            let mut status = m.get_status()?;
+                            if let Some(status) = status.as_mut() {
+                                m.get_properties().extend_status(status);
+                            }
                             vec.push(robot::v1::Status {
                                 name: Some(name),
                                 last_reconfigured: last_reconfigured_proto.clone(),
@@ -590,6 +914,22 @@ impl LocalRobot {
                                 status,
                             });
                         }
+                        ResourceType::Switch(b) => {
+                            let status = b.get_status()?;
+                            vec.push(robot::v1::Status {
+                                name: Some(name),
+                                last_reconfigured: last_reconfigured_proto.clone(),
+                                status,
+                            });
+                        }
+                        ResourceType::Button(b) => {
+                            let status = b.get_status()?;
+                            vec.push(robot::v1::Status {
+                                name: Some(name),
+                                last_reconfigured: last_reconfigured_proto.clone(),
+                                status,
+                            });
+                        }
                         ResourceType::Generic(b) => {
                             let status = b.get_status()?;
                             vec.push(robot::v1::Status {
@@ -598,6 +938,14 @@ impl LocalRobot {
                                 status,
                             });
                         }
+                        ResourceType::AudioInput(b) => {
+                            let status = b.get_status()?;
+                            vec.push(robot::v1::Status {
+                                name: Some(name),
+                                last_reconfigured: last_reconfigured_proto.clone(),
+                                status,
+                            });
+                        }
                         #[cfg(feature = "camera")]
                         _ => continue,
                     };
@@ -607,6 +955,78 @@ impl LocalRobot {
         }
         Ok(vec)
     }
+
+    /// Model and API of the resource named `name`, which can be one of its aliases
+    pub fn get_resource_metadata(&self, name: &ResourceName) -> Option<&ResourceMetadata> {
+        self.resource_metadata
+            .get(self.resolve_name(name).as_ref()?)
+    }
+
+    // Lists the API implemented by each type of resource present on the robot.
+    pub fn get_resource_rpc_subtypes(&self) -> Vec<robot::v1::ResourceRpcSubtype> {
+        let mut subtypes: HashMap<String, robot::v1::ResourceRpcSubtype> = HashMap::new();
+        for (name, res) in self.resources.iter() {
+            subtypes
+                .entry(name.subtype.clone())
+                .or_insert_with(|| robot::v1::ResourceRpcSubtype {
+                    subtype: Some(ResourceName {
+                        namespace: name.namespace.clone(),
+                        r#type: name.r#type.clone(),
+                        subtype: name.subtype.clone(),
+                        name: "".to_string(),
+                    }),
+                    proto_service: res.proto_service().to_string(),
+                });
+        }
+        subtypes.into_values().collect()
+    }
+
+    /// Readings of every sensor, movement sensor and power sensor, a failure only affects the
+    /// component it comes from
+    pub fn get_all_readings(
+        &self,
+    ) -> Vec<(ResourceName, Result<GenericReadingsResult, SensorError>)> {
+        // components are queried through cloned handles, so only their own locks are held
+        let mut readings = Vec::new();
+        for (name, res) in self.resources.clone().iter_mut() {
+            let res = match res {
+                ResourceType::Sensor(s) => s.get_generic_readings(),
+                ResourceType::MovementSensor(s) => s.get_generic_readings(),
+                ResourceType::PowerSensor(s) => s.get_generic_readings(),
+                _ => continue,
+            };
+            readings.push((name.clone(), res));
+        }
+        readings
+    }
+
+    /// Geometries configured in the frame of a resource, empty when it has no frame
+    pub fn get_geometries(
+        &self,
+        name: &ResourceName,
+    ) -> Result<Vec<common::v1::Geometry>, RobotError> {
+        let name = match self.resources.contains_key(name) {
+            true => name.clone(),
+            false => self.resolve_name(name).ok_or_else(|| {
+                RobotError::ResourceNotFound(name.name.clone(), name.subtype.clone())
+            })?,
+        };
+        Ok(self
+            .frames
+            .get(&name)
+            .map(|frame| frame.geometries(&name.name))
+            .unwrap_or_default())
+    }
+
+    /// Frames of the resources that were built, relative to their parent frame
+    pub fn get_frame_system_config(&self) -> Vec<robot::v1::FrameSystemConfig> {
+        self.frames
+            .iter()
+            .filter(|(name, _)| self.resources.contains_key(name))
+            .map(|(name, frame)| frame.frame_system_config(&name.name))
+            .collect()
+    }
+
     pub fn get_resource_names(&self) -> Result<Vec<common::v1::ResourceName>, RobotError> {
         let mut name = Vec::with_capacity(self.resources.len());
         for k in self.resources.keys() {
@@ -621,7 +1041,7 @@ impl LocalRobot {
             subtype: "motor".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::Motor(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
@@ -635,7 +1055,7 @@ impl LocalRobot {
             subtype: "camera".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::Camera(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
@@ -648,7 +1068,7 @@ impl LocalRobot {
             subtype: "base".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::Base(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
@@ -661,7 +1081,7 @@ impl LocalRobot {
             subtype: "board".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::Board(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
@@ -674,7 +1094,7 @@ impl LocalRobot {
             subtype: "sensor".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::Sensor(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
@@ -691,7 +1111,7 @@ impl LocalRobot {
             subtype: "movement_sensor".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::MovementSensor(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
@@ -705,7 +1125,7 @@ impl LocalRobot {
             subtype: "encoder".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::Encoder(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
@@ -719,7 +1139,7 @@ impl LocalRobot {
             subtype: "power_sensor".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::PowerSensor(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
@@ -733,13 +1153,54 @@ impl LocalRobot {
             subtype: "servo".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::Servo(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
         }
     }
 
+    pub fn get_switch_by_name(&self, name: String) -> Option<Arc<Mutex<dyn Switch>>> {
+        let name = ResourceName {
+            namespace: "rdk".to_string(),
+            r#type: "component".to_string(),
+            subtype: "switch".to_string(),
+            name,
+        };
+        match self.get_resource(&name) {
+            Some(ResourceType::Switch(r)) => Some(r.clone()),
+            Some(_) => None,
+            None => None,
+        }
+    }
+
+    pub fn get_button_by_name(&self, name: String) -> Option<Arc<Mutex<dyn Button>>> {
+        let name = ResourceName {
+            namespace: "rdk".to_string(),
+            r#type: "component".to_string(),
+            subtype: "button".to_string(),
+            name,
+        };
+        match self.get_resource(&name) {
+            Some(ResourceType::Button(r)) => Some(r.clone()),
+            Some(_) => None,
+            None => None,
+        }
+    }
+    pub fn get_audio_input_by_name(&self, name: String) -> Option<Arc<Mutex<dyn AudioInput>>> {
+        let name = ResourceName {
+            namespace: "rdk".to_string(),
+            r#type: "component".to_string(),
+            subtype: "audio_input".to_string(),
+            name,
+        };
+        match self.get_resource(&name) {
+            Some(ResourceType::AudioInput(r)) => Some(r.clone()),
+            Some(_) => None,
+            None => None,
+        }
+    }
+
     pub fn get_generic_component_by_name(
         &self,
         name: String,
@@ -750,17 +1211,17 @@ impl LocalRobot {
             subtype: "generic".to_string(),
             name,
         };
-        match self.resources.get(&name) {
+        match self.get_resource(&name) {
             Some(ResourceType::Generic(r)) => Some(r.clone()),
             Some(_) => None,
             None => None,
         }
     }
 
-    pub fn stop_all(&mut self) -> Result<(), RobotError> {
+    pub fn stop_all(&self) -> Result<(), RobotError> {
         let mut stop_errors: Vec<ActuatorError> = vec![];
-        for resource in self.resources.values_mut() {
-            match resource {
+        for mut resource in self.resources.values().cloned() {
+            match &mut resource {
                 ResourceType::Base(b) => {
                     match b.stop() {
                         Ok(_) => {}
@@ -798,11 +1259,14 @@ mod tests {
     use crate::common::i2c::I2CHandle;
     use crate::common::motor::Motor;
     use crate::common::movement_sensor::MovementSensor;
-    use crate::common::robot::LocalRobot;
+    use crate::common::robot::{LocalRobot, ResourceMetadata, RobotError};
     use crate::common::sensor::Readings;
+    use crate::common::telemetry::get_telemetry;
     use crate::google;
     use crate::google::protobuf::Struct;
-    use crate::proto::app::v1::{ComponentConfig, ConfigResponse, RobotConfig};
+    use crate::proto::app::v1::{ComponentConfig, ConfigResponse, Frame, RobotConfig, Translation};
+    use crate::proto::common::v1::{geometry::GeometryType, Geometry, Sphere};
+    use crate::proto::{common::v1::ResourceName, robot};
     #[cfg(feature = "data")]
     use {crate::common::data_collector::DataCollectorConfig, std::time::Duration};
 
@@ -1274,6 +1738,20 @@ mod tests {
 
         let robot = robot.unwrap();
 
+        let profile = robot.boot_profile();
+        let mut built: Vec<&str> = profile
+            .components
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        built.sort();
+        assert_eq!(built, ["enc1", "enc2", "m1", "m2"]);
+        assert!(profile
+            .components
+            .iter()
+            .all(|(_, duration)| *duration <= profile.total));
+        assert!(get_telemetry("robot_build_ms").is_some());
+
         let m1 = robot.get_motor_by_name("m1".to_string());
 
         assert!(m1.is_some());
@@ -1293,6 +1771,40 @@ mod tests {
         assert!(position.is_ok());
 
         assert_eq!(position.ok().unwrap(), 180);
+
+        let m1_name = ResourceName {
+            namespace: "rdk".to_string(),
+            r#type: "component".to_string(),
+            subtype: "motor".to_string(),
+            name: "m1".to_string(),
+        };
+        let metadata = robot.get_resource_metadata(&m1_name);
+        assert_eq!(
+            metadata,
+            Some(&ResourceMetadata {
+                model: "fake_with_dep".to_string(),
+                api: "viam.component.motor.v1.MotorService",
+            })
+        );
+
+        let subtypes = robot.get_resource_rpc_subtypes();
+        assert!(subtypes
+            .iter()
+            .any(|s| s.subtype.as_ref().unwrap().subtype == "encoder"
+                && s.proto_service == "viam.component.encoder.v1.EncoderService"));
+
+        let statuses = robot
+            .get_status(robot::v1::GetStatusRequest {
+                resource_names: vec![m1_name],
+            })
+            .unwrap();
+        assert_eq!(statuses.len(), 1);
+        let status = statuses[0].status.as_ref().unwrap();
+        assert_eq!(
+            status.fields["ticks_per_rotation"].kind,
+            Some(google::protobuf::value::Kind::NumberValue(1.0))
+        );
+        assert!(!status.fields.contains_key("max_rpm"));
     }
 
     #[test_log::test]
@@ -1391,4 +1903,220 @@ mod tests {
 
         assert!(enc.is_some());
     }
+
+    #[test_log::test]
+    fn test_resource_aliases() {
+        let enc = ComponentConfig {
+            name: "enc1".to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: "encoder".to_string(),
+            namespace: "rdk".to_string(),
+            attributes: Some(Struct {
+                fields: HashMap::from([(
+                    "aliases".to_string(),
+                    google::protobuf::Value {
+                        kind: Some(google::protobuf::value::Kind::ListValue(
+                            google::protobuf::ListValue {
+                                values: vec![google::protobuf::Value {
+                                    kind: Some(google::protobuf::value::Kind::StringValue(
+                                        "left_encoder".to_string(),
+                                    )),
+                                }],
+                            },
+                        )),
+                    },
+                )]),
+            }),
+            ..Default::default()
+        };
+        // the dependency is referenced through the alias of the encoder
+        let motor = ComponentConfig {
+            name: "m1".to_string(),
+            model: "rdk:builtin:fake_with_dep".to_string(),
+            r#type: "motor".to_string(),
+            namespace: "rdk".to_string(),
+            attributes: Some(Struct {
+                fields: HashMap::from([(
+                    "encoder".to_string(),
+                    google::protobuf::Value {
+                        kind: Some(google::protobuf::value::Kind::StringValue(
+                            "left_encoder".to_string(),
+                        )),
+                    },
+                )]),
+            }),
+            ..Default::default()
+        };
+
+        let robot_cfg = ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![enc, motor],
+                ..Default::default()
+            }),
+        };
+
+        let robot = LocalRobot::from_cloud_config(&robot_cfg, Box::default(), None).unwrap();
+
+        assert!(robot.get_motor_by_name("m1".to_string()).is_some());
+        assert!(robot.get_encoder_by_name("enc1".to_string()).is_some());
+        assert!(robot
+            .get_encoder_by_name("left_encoder".to_string())
+            .is_some());
+        assert!(robot
+            .get_encoder_by_name("rdk:component:encoder/enc1".to_string())
+            .is_some());
+        assert!(robot
+            .get_encoder_by_name("esp32:left_encoder".to_string())
+            .is_some());
+        assert!(robot.get_encoder_by_name("enc2".to_string()).is_none());
+        // an alias only applies to the resource's own subtype
+        assert!(robot
+            .get_motor_by_name("left_encoder".to_string())
+            .is_none());
+        assert_eq!(robot.get_resource_names().unwrap().len(), 2);
+
+        let alias = ResourceName {
+            namespace: "rdk".to_string(),
+            r#type: "component".to_string(),
+            subtype: "encoder".to_string(),
+            name: "left_encoder".to_string(),
+        };
+        assert!(robot.get_resource_metadata(&alias).is_some());
+    }
+
+    #[test_log::test]
+    fn test_component_frames() {
+        let motor = ComponentConfig {
+            name: "m1".to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: "motor".to_string(),
+            namespace: "rdk".to_string(),
+            frame: Some(Frame {
+                parent: "base".to_string(),
+                translation: Some(Translation {
+                    x: 10.0,
+                    y: 20.0,
+                    z: 0.0,
+                }),
+                orientation: None,
+                geometry: Some(Geometry {
+                    center: None,
+                    label: String::new(),
+                    geometry_type: Some(GeometryType::Sphere(Sphere { radius_mm: 15.0 })),
+                }),
+            }),
+            ..Default::default()
+        };
+        let enc = ComponentConfig {
+            name: "enc1".to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: "encoder".to_string(),
+            namespace: "rdk".to_string(),
+            ..Default::default()
+        };
+        let robot_cfg = ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![motor, enc],
+                ..Default::default()
+            }),
+        };
+        let robot = LocalRobot::from_cloud_config(&robot_cfg, Box::default(), None).unwrap();
+
+        let name = |subtype: &str, name: &str| ResourceName {
+            namespace: "rdk".to_string(),
+            r#type: "component".to_string(),
+            subtype: subtype.to_string(),
+            name: name.to_string(),
+        };
+        let geometries = robot.get_geometries(&name("motor", "m1")).unwrap();
+        assert_eq!(geometries.len(), 1);
+        assert_eq!(geometries[0].label, "m1");
+        assert!(robot
+            .get_geometries(&name("encoder", "enc1"))
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            robot.get_geometries(&name("encoder", "enc2")),
+            Err(RobotError::ResourceNotFound(_, _))
+        ));
+
+        let configs = robot.get_frame_system_config();
+        assert_eq!(configs.len(), 1);
+        let transform = configs[0].frame.as_ref().unwrap();
+        assert_eq!(transform.reference_frame, "m1");
+        let pose_in_frame = transform.pose_in_observer_frame.as_ref().unwrap();
+        assert_eq!(pose_in_frame.reference_frame, "base");
+        assert_eq!(pose_in_frame.pose.as_ref().unwrap().y, 20.0);
+    }
+
+    #[test_log::test]
+    fn test_cloud_config_component_validation() {
+        // relies on `api` rather than the deprecated namespace and type fields
+        let enc = ComponentConfig {
+            name: "enc1".to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            api: "rdk:component:encoder".to_string(),
+            ..Default::default()
+        };
+        let duplicate = ComponentConfig {
+            name: "enc1".to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: "encoder".to_string(),
+            namespace: "rdk".to_string(),
+            ..Default::default()
+        };
+        let no_type = ComponentConfig {
+            name: "m1".to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            ..Default::default()
+        };
+        let bad_attribute = ComponentConfig {
+            name: "m2".to_string(),
+            model: "rdk:builtin:fake".to_string(),
+            r#type: "motor".to_string(),
+            namespace: "rdk".to_string(),
+            attributes: Some(Struct {
+                fields: HashMap::from([(
+                    "max_rpm".to_string(),
+                    google::protobuf::Value { kind: None },
+                )]),
+            }),
+            ..Default::default()
+        };
+
+        let mut robot_cfg = ConfigResponse {
+            config: Some(RobotConfig {
+                components: vec![enc, duplicate, no_type, bad_attribute],
+                ..Default::default()
+            }),
+        };
+
+        // invalid components fail the robot by default
+        let robot = LocalRobot::from_cloud_config(&robot_cfg, Box::default(), None);
+        let Err(err) = robot else {
+            panic!("expected an invalid component config")
+        };
+        assert!(matches!(
+            &err,
+            RobotError::RobotInvalidComponentConfig(name, None, _) if name == "enc1"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "invalid config for component `enc1`: declared more than once, check the part's fragments"
+        );
+        let err = RobotError::RobotInvalidComponentConfig(
+            "enc1".to_owned(),
+            Some("frag-1".to_owned()),
+            "model is missing".to_owned(),
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid config for component `enc1` from fragment frag-1: model is missing"
+        );
+
+        robot_cfg.config.as_mut().unwrap().disable_partial_start = Some(false);
+        let robot = LocalRobot::from_cloud_config(&robot_cfg, Box::default(), None).unwrap();
+        assert!(robot.get_encoder_by_name("enc1".to_string()).is_some());
+        assert_eq!(robot.get_resource_names().unwrap().len(), 1);
+    }
 }
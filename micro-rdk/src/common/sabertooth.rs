@@ -0,0 +1,193 @@
+//! Package sabertooth implements a motor driven by one channel of a Dimension Engineering
+//! Sabertooth controller in packetized serial mode. The two channels of a controller are
+//! configured as two motors sharing the serial port, see `esp32::serial_motors` for the port
+//! configuration.
+//!
+//! ```json
+//! {
+//!     "address": 128,
+//!     "channel": 2,
+//!     "max_rpm": 150
+//! }
+//! ```
+//!
+//! `address` is the address selected with the DIP switches of the controller (128 by default)
+//! and `channel` the output driving the motor (1 or 2). `go_for` sets a power proportional to
+//! `max_rpm` (required). The controller is open loop and doesn't report its state, the status
+//! only contains the power last set.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::actuator::{Actuator, ActuatorError};
+use super::config::{AttributeError, ConfigType};
+use super::math_utils::go_for_math;
+use super::motor::{Motor, MotorError, MotorSupportedProperties, MotorType};
+use super::status::{status_envelope, ComponentHealth, Status, StatusError};
+use super::uart::UartType;
+use crate::google::protobuf::{value::Kind, Struct};
+
+pub const DEFAULT_ADDRESS: u8 = 128;
+// sent once so the controller detects the baud rate
+const BAUDING_CHARACTER: u8 = 0xAA;
+// forward command of each channel, the backward command follows it
+const CMD_FORWARD_M1: u8 = 0;
+const CMD_FORWARD_M2: u8 = 4;
+const MAX_SPEED: f64 = 127.0;
+
+/// Builds the packet setting the speed of a channel (0 for M1, 1 for M2)
+pub fn speed_packet(address: u8, channel: u8, pct: f64) -> [u8; 4] {
+    let forward = if channel == 0 {
+        CMD_FORWARD_M1
+    } else {
+        CMD_FORWARD_M2
+    };
+    let command = if pct < 0.0 { forward + 1 } else { forward };
+    let speed = (pct.abs().min(1.0) * MAX_SPEED).round() as u8;
+    let checksum = address.wrapping_add(command).wrapping_add(speed) & 0x7F;
+    [address, command, speed, checksum]
+}
+
+#[derive(DoCommand)]
+pub struct SabertoothMotor {
+    uart: UartType,
+    address: u8,
+    // 0 for M1, 1 for M2
+    channel: u8,
+    max_rpm: f64,
+    power: f64,
+    health: ComponentHealth,
+}
+
+impl SabertoothMotor {
+    pub fn new(uart: UartType, address: u8, channel: u8, max_rpm: f64) -> Result<Self, MotorError> {
+        if !(1..=2).contains(&channel) {
+            return Err(MotorError::ConfigError(
+                "sabertooth: `channel` must be 1 or 2",
+            ));
+        }
+        uart.lock().unwrap().write(&[BAUDING_CHARACTER])?;
+        Ok(Self {
+            uart,
+            address,
+            channel: channel - 1,
+            max_rpm,
+            power: 0.0,
+            health: ComponentHealth::new(),
+        })
+    }
+
+    /// Builds the motor from the attributes of the `sabertooth` model, once the platform has
+    /// opened the serial port the controller is connected to
+    pub fn from_uart_and_config(uart: UartType, cfg: ConfigType) -> Result<MotorType, MotorError> {
+        let address = match cfg.get_attribute::<u8>("address") {
+            Ok(address) => address,
+            Err(AttributeError::KeyNotFound(_)) => DEFAULT_ADDRESS,
+            Err(_) => return Err(MotorError::ConfigError("sabertooth: invalid `address`")),
+        };
+        let channel = cfg
+            .get_attribute::<u8>("channel")
+            .map_err(|_| MotorError::ConfigError("sabertooth: missing `channel`"))?;
+        let max_rpm = cfg
+            .get_attribute::<f64>("max_rpm")
+            .map_err(|_| MotorError::ConfigError("sabertooth: missing `max_rpm`"))?;
+        Ok(Arc::new(Mutex::new(Self::new(
+            uart, address, channel, max_rpm,
+        )?)))
+    }
+
+    fn apply_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&pct) {
+            return Err(MotorError::PowerSetError);
+        }
+        let packet = speed_packet(self.address, self.channel, pct);
+        self.uart.lock().unwrap().write(&packet)?;
+        self.power = pct;
+        Ok(())
+    }
+}
+
+impl Motor for SabertoothMotor {
+    fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        let res = self.apply_power(pct);
+        self.health.record(&res);
+        res
+    }
+
+    fn get_position(&mut self) -> Result<i32, MotorError> {
+        Err(MotorError::MissingEncoder)
+    }
+
+    fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        let (pwr, dur) = go_for_math(self.max_rpm, rpm, revolutions)?;
+        self.set_power(pwr)?;
+        Ok(dur)
+    }
+
+    fn get_properties(&mut self) -> MotorSupportedProperties {
+        MotorSupportedProperties {
+            position_reporting: false,
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: None,
+        }
+    }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
+}
+
+impl Actuator for SabertoothMotor {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(self.power != 0.0)
+    }
+
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        let res = self.apply_power(0.0);
+        self.health.record(&res);
+        res.map_err(|_| ActuatorError::CouldntStop)
+    }
+}
+
+impl Status for SabertoothMotor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(status_envelope(
+            &self.health,
+            [("power", Kind::NumberValue(self.power))],
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{speed_packet, SabertoothMotor};
+    use crate::common::motor::{Motor, MotorError};
+    use crate::common::uart::FakeUart;
+    use std::sync::{Arc, Mutex};
+
+    #[test_log::test]
+    fn test_speed_packets() {
+        assert_eq!(speed_packet(128, 0, 1.0), [128, 0, 127, 127]);
+        // M2 backward at half speed
+        assert_eq!(speed_packet(128, 1, -0.5), [128, 5, 64, 69]);
+        assert_eq!(speed_packet(130, 1, 0.0), [130, 4, 0, 6]);
+    }
+
+    #[test_log::test]
+    fn test_sabertooth_channels_share_port() -> Result<(), MotorError> {
+        let uart = Arc::new(Mutex::new(FakeUart::default()));
+        let mut m1 = SabertoothMotor::new(uart.clone(), 128, 1, 100.0)?;
+        let mut m2 = SabertoothMotor::new(uart.clone(), 128, 2, 100.0)?;
+        uart.lock().unwrap().written.clear();
+
+        m1.set_power(1.0)?;
+        m2.go_for(-50.0, 0.0)?;
+        assert_eq!(
+            uart.lock().unwrap().written,
+            vec![128, 0, 127, 127, 128, 5, 64, 69]
+        );
+        assert_eq!(m2.is_powered()?, (true, -0.5));
+        assert!(m1.set_power(-1.5).is_err());
+        Ok(())
+    }
+}
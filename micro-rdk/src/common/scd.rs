@@ -0,0 +1,453 @@
+//! Sensirion SCD30 (`scd30` model) and SCD4x (`scd41` model, also SCD40) photoacoustic CO2
+//! sensors over I2C. Datasheets:
+//! https://sensirion.com/media/documents/4EAF6AF8/61652C3C/Sensirion_CO2_Sensors_SCD30_Datasheet.pdf
+//! https://sensirion.com/media/documents/48C4B7FB/64C134E7/Sensirion_SCD4x_Datasheet.pdf
+//!
+//! ```json
+//! {
+//!     "i2c_bus": "i2c0",
+//!     "automatic_self_calibration": true,
+//!     "temperature_offset_celsius": 4.0,
+//!     "altitude_m": 120,
+//!     "ambient_pressure_hpa": 1013
+//! }
+//! ```
+//!
+//! The sensors measure continuously (every 2 seconds for the SCD30, 5 seconds for the SCD41),
+//! the readings are the `co2_ppm`, `temperature_celsius` and `relative_humidity_pct` of the last
+//! measurement. They fail until the first measurement is available.
+//!
+//! The optional attributes are applied when the sensor is built, settings left out keep the
+//! value stored by the sensor:
+//! - `automatic_self_calibration`: whether the sensor recalibrates itself assuming it sees fresh
+//!   air (400ppm) regularly
+//! - `temperature_offset_celsius`: heating of the sensor by its surroundings, subtracted from the
+//!   temperature, it also corrects the humidity
+//! - `altitude_m` and `ambient_pressure_hpa`: the CO2 concentration is compensated for the air
+//!   pressure, given by the altitude of the sensor or, more precisely, by the ambient pressure
+//!   which supersedes the altitude
+//!
+//! Commands:
+//! - `{"set_ambient_pressure": {"hpa": 1002.5}}` updates the pressure compensation, for
+//!   instance from the readings of a barometer
+//! - `{"set_automatic_self_calibration": {"enabled": false}}`
+//! - `{"forced_recalibration": {"ppm": 420}}` recalibrates the sensor to a known concentration,
+//!   after it ran for a few minutes in a stable environment
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::board::Board;
+use super::calibration::number_arg;
+use super::config::{AttributeError, ConfigType};
+use super::generic::{DoCommand, GenericError};
+use super::i2c::I2cHandleType;
+use super::registry::{get_board_from_dependencies, ComponentRegistry, Dependency};
+use super::sensirion::{command_bytes, words_from_bytes};
+use super::sensor::{GenericReadingsResult, Readings, Sensor, SensorError, SensorType};
+use super::status::{Status, StatusError};
+use super::struct_builder::StructBuilder;
+use crate::google::protobuf::{value::Kind as ProtoKind, Struct};
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("scd30", &scd30_from_config)
+        .is_err()
+    {
+        log::error!("scd30 type is already registered");
+    }
+    if registry
+        .register_sensor("scd41", &scd41_from_config)
+        .is_err()
+    {
+        log::error!("scd41 type is already registered");
+    }
+}
+
+fn scd30_from_config(cfg: ConfigType, deps: Vec<Dependency>) -> Result<SensorType, SensorError> {
+    Scd::from_config(cfg, deps, ScdModel::Scd30)
+}
+
+fn scd41_from_config(cfg: ConfigType, deps: Vec<Dependency>) -> Result<SensorType, SensorError> {
+    Scd::from_config(cfg, deps, ScdModel::Scd41)
+}
+
+// SCD30 commands
+const SCD30_START_CONTINUOUS_MEASUREMENT: u16 = 0x0010;
+const SCD30_GET_DATA_READY: u16 = 0x0202;
+const SCD30_READ_MEASUREMENT: u16 = 0x0300;
+const SCD30_SET_ALTITUDE: u16 = 0x5102;
+const SCD30_FORCED_RECALIBRATION: u16 = 0x5204;
+const SCD30_SET_ASC: u16 = 0x5306;
+const SCD30_SET_TEMPERATURE_OFFSET: u16 = 0x5403;
+// the SCD30 needs some time between a command and reading its answer
+const SCD30_READ_DELAY: Duration = Duration::from_millis(3);
+
+// SCD4x commands
+const SCD4X_START_PERIODIC_MEASUREMENT: u16 = 0x21B1;
+const SCD4X_READ_MEASUREMENT: u16 = 0xEC05;
+const SCD4X_STOP_PERIODIC_MEASUREMENT: u16 = 0x3F86;
+const SCD4X_GET_DATA_READY: u16 = 0xE4B8;
+const SCD4X_SET_TEMPERATURE_OFFSET: u16 = 0x241D;
+const SCD4X_SET_ALTITUDE: u16 = 0x2427;
+const SCD4X_SET_AMBIENT_PRESSURE: u16 = 0xE000;
+const SCD4X_SET_ASC: u16 = 0x2416;
+const SCD4X_FORCED_RECALIBRATION: u16 = 0x362F;
+const SCD4X_READ_DELAY: Duration = Duration::from_millis(1);
+const SCD4X_STOP_DELAY: Duration = Duration::from_millis(500);
+const SCD4X_RECALIBRATION_DELAY: Duration = Duration::from_millis(400);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScdModel {
+    Scd30,
+    Scd41,
+}
+
+impl ScdModel {
+    fn i2c_address(&self) -> u8 {
+        match self {
+            Self::Scd30 => 0x61,
+            Self::Scd41 => 0x62,
+        }
+    }
+}
+
+/// Settings applied when the sensor is built, `None` keeps the setting of the sensor
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScdSettings {
+    pub automatic_self_calibration: Option<bool>,
+    pub temperature_offset_celsius: Option<f64>,
+    pub altitude_m: Option<u16>,
+    pub ambient_pressure_hpa: Option<f64>,
+}
+
+impl ScdSettings {
+    fn from_config(cfg: &ConfigType) -> Result<Self, SensorError> {
+        fn optional<T>(value: Result<T, AttributeError>) -> Result<Option<T>, SensorError> {
+            match value {
+                Ok(value) => Ok(Some(value)),
+                Err(AttributeError::KeyNotFound(_)) => Ok(None),
+                Err(_) => Err(SensorError::ConfigError("scd invalid attribute")),
+            }
+        }
+        let settings = Self {
+            automatic_self_calibration: optional(
+                cfg.get_attribute::<bool>("automatic_self_calibration"),
+            )?,
+            temperature_offset_celsius: optional(
+                cfg.get_attribute::<f64>("temperature_offset_celsius"),
+            )?,
+            altitude_m: optional(cfg.get_attribute::<u16>("altitude_m"))?,
+            ambient_pressure_hpa: optional(cfg.get_attribute::<f64>("ambient_pressure_hpa"))?,
+        };
+        if settings
+            .temperature_offset_celsius
+            .is_some_and(|offset| !(0.0..=20.0).contains(&offset))
+        {
+            return Err(SensorError::ConfigError(
+                "scd temperature_offset_celsius should be in [0, 20]",
+            ));
+        }
+        if let Some(pressure) = settings.ambient_pressure_hpa {
+            check_pressure(pressure)?;
+        }
+        Ok(settings)
+    }
+}
+
+fn check_pressure(hpa: f64) -> Result<(), SensorError> {
+    if !(700.0..=1400.0).contains(&hpa) {
+        return Err(SensorError::ConfigError(
+            "scd ambient pressure should be in [700, 1400] hPa",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Co2Measurement {
+    pub co2_ppm: f64,
+    pub temperature_celsius: f64,
+    pub relative_humidity_pct: f64,
+}
+
+impl Co2Measurement {
+    // three big endian floats
+    fn from_scd30(words: &[u16; 6]) -> Self {
+        let float = |i: usize| {
+            f64::from(f32::from_bits(
+                (u32::from(words[i]) << 16) | u32::from(words[i + 1]),
+            ))
+        };
+        Self {
+            co2_ppm: float(0),
+            temperature_celsius: float(2),
+            relative_humidity_pct: float(4),
+        }
+    }
+
+    fn from_scd4x(words: &[u16; 3]) -> Self {
+        Self {
+            co2_ppm: f64::from(words[0]),
+            temperature_celsius: -45.0 + 175.0 * f64::from(words[1]) / 65535.0,
+            relative_humidity_pct: 100.0 * f64::from(words[2]) / 65535.0,
+        }
+    }
+}
+
+pub struct Scd {
+    i2c_handle: I2cHandleType,
+    i2c_address: u8,
+    model: ScdModel,
+    // the SCD30 takes the pressure with the start of the measurements
+    ambient_pressure_hpa: Option<f64>,
+    measurement: Option<Co2Measurement>,
+}
+
+impl Scd {
+    pub fn new(
+        i2c_handle: I2cHandleType,
+        model: ScdModel,
+        settings: ScdSettings,
+    ) -> Result<Self, SensorError> {
+        let mut sensor = Self {
+            i2c_handle,
+            i2c_address: model.i2c_address(),
+            model,
+            ambient_pressure_hpa: settings.ambient_pressure_hpa,
+            measurement: None,
+        };
+        sensor.stop()?;
+        if let Some(enabled) = settings.automatic_self_calibration {
+            sensor.write_automatic_self_calibration(enabled)?;
+        }
+        if let Some(offset) = settings.temperature_offset_celsius {
+            let (command, ticks) = match model {
+                ScdModel::Scd30 => (SCD30_SET_TEMPERATURE_OFFSET, offset * 100.0),
+                ScdModel::Scd41 => (SCD4X_SET_TEMPERATURE_OFFSET, offset * 65535.0 / 175.0),
+            };
+            sensor.command(command, Some(ticks.round() as u16))?;
+        }
+        if let Some(altitude) = settings.altitude_m {
+            let command = match model {
+                ScdModel::Scd30 => SCD30_SET_ALTITUDE,
+                ScdModel::Scd41 => SCD4X_SET_ALTITUDE,
+            };
+            sensor.command(command, Some(altitude))?;
+        }
+        sensor.start()?;
+        Ok(sensor)
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        dependencies: Vec<Dependency>,
+        model: ScdModel,
+    ) -> Result<SensorType, SensorError> {
+        let board = get_board_from_dependencies(dependencies)
+            .ok_or(SensorError::ConfigError("scd missing board dependency"))?;
+        let i2c_bus = cfg
+            .get_attribute::<String>("i2c_bus")
+            .map_err(|_| SensorError::ConfigError("scd missing i2c_bus attribute"))?;
+        let settings = ScdSettings::from_config(&cfg)?;
+        let i2c_handle = board.get_i2c_by_name(i2c_bus)?;
+        Ok(Arc::new(Mutex::new(Self::new(
+            i2c_handle, model, settings,
+        )?)))
+    }
+
+    fn command(&mut self, command: u16, argument: Option<u16>) -> Result<(), SensorError> {
+        Ok(self
+            .i2c_handle
+            .lock()
+            .unwrap()
+            .write_i2c(self.i2c_address, &command_bytes(command, argument))?)
+    }
+
+    fn read<const N: usize>(&mut self, command: u16) -> Result<[u16; N], SensorError> {
+        self.command(command, None)?;
+        std::thread::sleep(match self.model {
+            ScdModel::Scd30 => SCD30_READ_DELAY,
+            ScdModel::Scd41 => SCD4X_READ_DELAY,
+        });
+        let mut bytes = vec![0; 3 * N];
+        self.i2c_handle
+            .lock()
+            .unwrap()
+            .read_i2c(self.i2c_address, &mut bytes)?;
+        words_from_bytes(&bytes).ok_or(SensorError::SensorGenericError("scd CRC mismatch"))
+    }
+
+    // the SCD4x ignores most commands while it measures, the SCD30 can be configured at any time
+    fn stop(&mut self) -> Result<(), SensorError> {
+        if self.model == ScdModel::Scd41 {
+            self.command(SCD4X_STOP_PERIODIC_MEASUREMENT, None)?;
+            std::thread::sleep(SCD4X_STOP_DELAY);
+        }
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<(), SensorError> {
+        match self.model {
+            ScdModel::Scd30 => {
+                // 0 disables the pressure compensation
+                let pressure = self.ambient_pressure_hpa.unwrap_or(0.0).round() as u16;
+                self.command(SCD30_START_CONTINUOUS_MEASUREMENT, Some(pressure))
+            }
+            ScdModel::Scd41 => {
+                if let Some(pressure) = self.ambient_pressure_hpa {
+                    self.command(SCD4X_SET_AMBIENT_PRESSURE, Some(pressure.round() as u16))?;
+                }
+                self.command(SCD4X_START_PERIODIC_MEASUREMENT, None)
+            }
+        }
+    }
+
+    fn write_automatic_self_calibration(&mut self, enabled: bool) -> Result<(), SensorError> {
+        let command = match self.model {
+            ScdModel::Scd30 => SCD30_SET_ASC,
+            ScdModel::Scd41 => SCD4X_SET_ASC,
+        };
+        self.command(command, Some(enabled as u16))
+    }
+
+    /// Updates the pressure the CO2 concentration is compensated for
+    pub fn set_ambient_pressure(&mut self, hpa: f64) -> Result<(), SensorError> {
+        check_pressure(hpa)?;
+        self.ambient_pressure_hpa = Some(hpa);
+        match self.model {
+            ScdModel::Scd30 => self.start(),
+            ScdModel::Scd41 => self.command(SCD4X_SET_AMBIENT_PRESSURE, Some(hpa.round() as u16)),
+        }
+    }
+
+    pub fn set_automatic_self_calibration(&mut self, enabled: bool) -> Result<(), SensorError> {
+        self.stop()?;
+        self.write_automatic_self_calibration(enabled)?;
+        self.start()
+    }
+
+    /// Recalibrates the sensor to a known CO2 concentration
+    pub fn forced_recalibration(&mut self, ppm: u16) -> Result<(), SensorError> {
+        match self.model {
+            ScdModel::Scd30 => self.command(SCD30_FORCED_RECALIBRATION, Some(ppm)),
+            ScdModel::Scd41 => {
+                self.stop()?;
+                self.command(SCD4X_FORCED_RECALIBRATION, Some(ppm))?;
+                std::thread::sleep(SCD4X_RECALIBRATION_DELAY);
+                let mut bytes = [0; 3];
+                self.i2c_handle
+                    .lock()
+                    .unwrap()
+                    .read_i2c(self.i2c_address, &mut bytes)?;
+                let correction = words_from_bytes::<1>(&bytes);
+                self.start()?;
+                match correction {
+                    Some([0xFFFF]) => Err(SensorError::SensorGenericError(
+                        "scd forced recalibration failed",
+                    )),
+                    Some(_) => Ok(()),
+                    None => Err(SensorError::SensorGenericError("scd CRC mismatch")),
+                }
+            }
+        }
+    }
+
+    // reads the new measurement, if any
+    fn update(&mut self) -> Result<(), SensorError> {
+        match self.model {
+            ScdModel::Scd30 => {
+                if self.read::<1>(SCD30_GET_DATA_READY)?[0] == 1 {
+                    let words = self.read::<6>(SCD30_READ_MEASUREMENT)?;
+                    self.measurement = Some(Co2Measurement::from_scd30(&words));
+                }
+            }
+            ScdModel::Scd41 => {
+                if self.read::<1>(SCD4X_GET_DATA_READY)?[0] & 0x07FF != 0 {
+                    let words = self.read::<3>(SCD4X_READ_MEASUREMENT)?;
+                    self.measurement = Some(Co2Measurement::from_scd4x(&words));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Sensor for Scd {}
+
+impl Readings for Scd {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        self.update()?;
+        let measurement = self.measurement.ok_or(SensorError::SensorGenericError(
+            "scd has no measurement yet",
+        ))?;
+        Ok(StructBuilder::with_capacity(3)
+            .field("co2_ppm", measurement.co2_ppm)
+            .field("temperature_celsius", measurement.temperature_celsius)
+            .field("relative_humidity_pct", measurement.relative_humidity_pct)
+            .into_fields())
+    }
+}
+
+impl Status for Scd {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(
+            StructBuilder::with_capacity(1)
+                .field("ambient_pressure_hpa", self.ambient_pressure_hpa)
+                .build(),
+        ))
+    }
+}
+
+impl DoCommand for Scd {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        let args = |name: &str| match command.fields.get(name).map(|value| &value.kind) {
+            Some(Some(ProtoKind::StructValue(args))) => Some(args.clone()),
+            _ => None,
+        };
+        let result = if let Some(args) = args("set_ambient_pressure") {
+            let hpa = number_arg(&args, "hpa").ok_or(GenericError::InvalidArgument("hpa"))?;
+            self.set_ambient_pressure(hpa)
+        } else if let Some(args) = args("set_automatic_self_calibration") {
+            let enabled = match args.fields.get("enabled").map(|value| &value.kind) {
+                Some(Some(ProtoKind::BoolValue(enabled))) => *enabled,
+                _ => return Err(GenericError::InvalidArgument("enabled")),
+            };
+            self.set_automatic_self_calibration(enabled)
+        } else if let Some(args) = args("forced_recalibration") {
+            let ppm = number_arg(&args, "ppm")
+                .filter(|ppm| (400.0..=2000.0).contains(ppm))
+                .ok_or(GenericError::InvalidArgument("ppm"))?;
+            self.forced_recalibration(ppm.round() as u16)
+        } else {
+            return Err(GenericError::MethodUnimplemented("do_command"));
+        };
+        result.map_err(|err| GenericError::Other(Box::new(err)))?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Co2Measurement;
+
+    #[test_log::test]
+    fn test_measurements() {
+        // example of the SCD30 interface description: 439 ppm, 27.2 C, 48.8 %
+        let measurement =
+            Co2Measurement::from_scd30(&[0x43DB, 0x8C2E, 0x41D9, 0xE7FF, 0x4243, 0x3A1B]);
+        assert!((measurement.co2_ppm - 439.09).abs() < 0.01);
+        assert!((measurement.temperature_celsius - 27.24).abs() < 0.01);
+        assert!((measurement.relative_humidity_pct - 48.81).abs() < 0.01);
+
+        // example of the SCD4x datasheet: 500 ppm, 25 C, 37 %
+        let measurement = Co2Measurement::from_scd4x(&[0x01F4, 0x6667, 0x5EB9]);
+        assert_eq!(measurement.co2_ppm, 500.0);
+        assert!((measurement.temperature_celsius - 25.0).abs() < 0.01);
+        assert!((measurement.relative_humidity_pct - 37.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,80 @@
+//! Wrapper keeping credentials out of logs and status output.
+//!
+//! Robot secrets, WiFi passwords, API keys or tokens stored in a [SecretString] are never
+//! printed through `Debug` or `Display`, the value has to be explicitly requested with
+//! [SecretString::expose]. Messages that may contain a secret (e.g. logs pushed to app) can be
+//! scrubbed with [SecretString::redact].
+
+use std::{borrow::Cow, fmt};
+
+/// Text substituted to a secret when it is printed or redacted
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+    /// Returns the secret, only use it where the credential is actually needed
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// Replaces every occurrence of the secret in `message`
+    pub fn redact<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        if self.0.is_empty() || !message.contains(&self.0) {
+            return Cow::Borrowed(message);
+        }
+        Cow::Owned(message.replace(&self.0, REDACTED))
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SecretString, REDACTED};
+
+    #[test_log::test]
+    fn test_secret_string_redaction() {
+        let secret = SecretString::new("hunter2");
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(format!("{}", secret), REDACTED);
+        assert_eq!(format!("{:?}", secret), REDACTED);
+        assert!(!format!("{:?}", Some(secret.clone())).contains("hunter2"));
+
+        assert_eq!(
+            secret.redact("auth failed with hunter2 twice hunter2"),
+            "auth failed with [REDACTED] twice [REDACTED]"
+        );
+        assert_eq!(secret.redact("nothing to hide"), "nothing to hide");
+        // an empty secret must not redact every position of the message
+        assert_eq!(SecretString::default().redact("message"), "message");
+    }
+}
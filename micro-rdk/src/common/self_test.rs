@@ -0,0 +1,510 @@
+//! Hardware test routine for bringing up motors and servos, run by sending the standard
+//! [SELF_TEST_COMMAND] through `do_command`:
+//!
+//! ```json
+//! { "self_test": { "power": 0.2, "duration_ms": 200, "min_position_delta": 1 } }
+//! { "self_test": { "min_deg": 0, "max_deg": 180, "tolerance_deg": 2, "settle_ms": 500 } }
+//! ```
+//!
+//! Motors are pulsed briefly at low power, the encoder delta is checked when the motor reports
+//! its position and the motor is always stopped afterwards. Servos are swept to both ends of
+//! their range, checking the reported position, then moved back to where they started.
+//! The test answers with a report `{"passed": bool, "steps": [...]}` where every step has a
+//! `name`, a `status` (`pass`, `fail` or `skip`) and a `detail`.
+//!
+//! Power and durations are capped so the test stays safe on a partially assembled build. The
+//! test waits for the hardware on a timer, the component is only locked while it is commanded
+//! or read so other requests are served in the meantime.
+
+use std::time::Duration;
+
+use async_io::Timer;
+
+use crate::google::protobuf::{value::Kind as ValueKind, ListValue, Struct, Value};
+
+use super::config::{AttributeError, Kind};
+use super::generic::GenericError;
+use super::motor::{Motor, MotorType, StopOnDrop};
+use super::servo::{Servo, ServoType};
+use super::struct_builder::StructBuilder;
+
+/// Command by which motors and servos run their hardware test, see [motor_self_test] and
+/// [servo_self_test] for the arguments they accept.
+pub static SELF_TEST_COMMAND: &str = "self_test";
+
+const MAX_MOTOR_POWER: f64 = 0.5;
+const MAX_PULSE_DURATION_MS: u32 = 2000;
+const MAX_SETTLE_MS: u32 = 2000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+impl StepStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelfTestStep {
+    pub name: &'static str,
+    pub status: StepStatus,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SelfTestReport {
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    fn step(&mut self, name: &'static str, status: StepStatus, detail: String) {
+        self.steps.push(SelfTestStep {
+            name,
+            status,
+            detail,
+        });
+    }
+
+    fn check(&mut self, name: &'static str, passed: bool, detail: String) -> bool {
+        let status = if passed {
+            StepStatus::Pass
+        } else {
+            StepStatus::Fail
+        };
+        self.step(name, status, detail);
+        passed
+    }
+
+    /// A report passes when none of its steps failed
+    pub fn passed(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| step.status != StepStatus::Fail)
+    }
+}
+
+impl From<SelfTestReport> for Struct {
+    fn from(report: SelfTestReport) -> Self {
+        let passed = report.passed();
+        let steps = report
+            .steps
+            .into_iter()
+            .map(|step| {
+                Value::from(
+                    StructBuilder::with_capacity(3)
+                        .field("name", step.name)
+                        .field("status", step.status.as_str())
+                        .field("detail", step.detail),
+                )
+            })
+            .collect();
+        StructBuilder::with_capacity(2)
+            .field("passed", passed)
+            .value(
+                "steps",
+                Value {
+                    kind: Some(ValueKind::ListValue(ListValue { values: steps })),
+                },
+            )
+            .build()
+    }
+}
+
+/// Returns the arguments of the self test if the command asks for it
+pub fn self_test_arguments(command: Option<&Struct>) -> Option<Result<Kind, GenericError>> {
+    let value = command?.fields.get(SELF_TEST_COMMAND)?;
+    Some(match &value.kind {
+        Some(ValueKind::StructValue(args)) => Kind::try_from(ValueKind::StructValue(args.clone()))
+            .map_err(|_| GenericError::InvalidArgument("self_test")),
+        // `{"self_test": true}` or `{"self_test": null}` run the test with its defaults
+        _ => Ok(Kind::StructValue(Default::default())),
+    })
+}
+
+/// Runs the self test of a motor with the arguments of the command
+pub async fn do_motor_self_test(mut motor: MotorType, args: Kind) -> Result<Struct, GenericError> {
+    let config = MotorSelfTestConfig::try_from(&args)
+        .map_err(|_| GenericError::InvalidArgument("self_test"))?;
+    Ok(motor_self_test(&mut motor, &config).await.into())
+}
+
+/// Runs the self test of a servo with the arguments of the command
+pub async fn do_servo_self_test(mut servo: ServoType, args: Kind) -> Result<Struct, GenericError> {
+    let config = ServoSelfTestConfig::try_from(&args)
+        .map_err(|_| GenericError::InvalidArgument("self_test"))?;
+    Ok(servo_self_test(&mut servo, &config).await.into())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MotorSelfTestConfig {
+    pub power: f64,
+    pub duration: Duration,
+    pub min_position_delta: i32,
+}
+
+impl Default for MotorSelfTestConfig {
+    fn default() -> Self {
+        Self {
+            power: 0.2,
+            duration: Duration::from_millis(200),
+            min_position_delta: 1,
+        }
+    }
+}
+
+impl TryFrom<&Kind> for MotorSelfTestConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let default = Self::default();
+        let power = value
+            .get("power")?
+            .map(f64::try_from)
+            .transpose()?
+            .unwrap_or(default.power);
+        if power == 0.0 || power.abs() > MAX_MOTOR_POWER {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        let duration_ms = value
+            .get("duration_ms")?
+            .map(u32::try_from)
+            .transpose()?
+            .map(|ms| ms.min(MAX_PULSE_DURATION_MS) as u64);
+        Ok(Self {
+            power,
+            duration: duration_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.duration),
+            min_position_delta: value
+                .get("min_position_delta")?
+                .map(i32::try_from)
+                .transpose()?
+                .unwrap_or(default.min_position_delta),
+        })
+    }
+}
+
+/// Pulses the motor at low power, checks the encoder moved in the expected direction and
+/// that the motor stops. The test is aborted if the motor is already running.
+pub async fn motor_self_test<M>(motor: &mut M, config: &MotorSelfTestConfig) -> SelfTestReport
+where
+    M: Motor + ?Sized,
+{
+    let mut report = SelfTestReport::default();
+    match motor.is_powered() {
+        Ok((false, _)) => report.step("idle", StepStatus::Pass, "motor is idle".to_string()),
+        Ok((true, power)) => {
+            report.step(
+                "idle",
+                StepStatus::Fail,
+                format!("motor is running at power {}, test aborted", power),
+            );
+            return report;
+        }
+        Err(err) => {
+            report.step("idle", StepStatus::Fail, err.to_string());
+            return report;
+        }
+    }
+
+    let position_reporting = motor.get_properties().position_reporting;
+    let start = if position_reporting {
+        match motor.get_position() {
+            Ok(position) => Some(position),
+            Err(err) => {
+                report.step("encoder", StepStatus::Fail, err.to_string());
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let pulse = motor.set_power(config.power);
+    if pulse.is_ok() {
+        // stops the motor if the test is dropped while the motor is pulsed
+        let guard = StopOnDrop::new(&mut *motor);
+        Timer::after(config.duration).await;
+        guard.disarm();
+    }
+    let end = start.map(|_| motor.get_position());
+    // the motor must be stopped whatever happened during the pulse
+    let stop = motor.stop();
+    report.check(
+        "pulse",
+        pulse.is_ok(),
+        match pulse {
+            Ok(()) => format!(
+                "powered at {} for {}ms",
+                config.power,
+                config.duration.as_millis()
+            ),
+            Err(err) => err.to_string(),
+        },
+    );
+
+    match (start, end) {
+        (Some(start), Some(Ok(end))) => {
+            let delta = end - start;
+            let expected_direction = delta.signum() == config.power.signum() as i32;
+            report.check(
+                "encoder",
+                expected_direction && delta.abs() >= config.min_position_delta,
+                format!("position moved by {} (from {} to {})", delta, start, end),
+            );
+        }
+        (Some(_), Some(Err(err))) => report.step("encoder", StepStatus::Fail, err.to_string()),
+        _ if !position_reporting => report.step(
+            "encoder",
+            StepStatus::Skip,
+            "motor doesn't report its position".to_string(),
+        ),
+        _ => {}
+    }
+
+    match stop {
+        Ok(()) => match motor.is_powered() {
+            Ok((powered, power)) => {
+                report.check(
+                    "stop",
+                    !powered,
+                    match powered {
+                        true => format!("motor still powered at {}", power),
+                        false => "motor stopped".to_string(),
+                    },
+                );
+            }
+            Err(err) => report.step("stop", StepStatus::Fail, err.to_string()),
+        },
+        Err(err) => report.step("stop", StepStatus::Fail, err.to_string()),
+    }
+    report
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServoSelfTestConfig {
+    pub min_deg: u32,
+    pub max_deg: u32,
+    pub tolerance_deg: u32,
+    pub settle: Duration,
+}
+
+impl Default for ServoSelfTestConfig {
+    fn default() -> Self {
+        Self {
+            min_deg: 0,
+            max_deg: 180,
+            tolerance_deg: 2,
+            settle: Duration::from_millis(500),
+        }
+    }
+}
+
+impl TryFrom<&Kind> for ServoSelfTestConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let default = Self::default();
+        let get = |key: &str, default: u32| -> Result<u32, AttributeError> {
+            Ok(value
+                .get(key)?
+                .map(u32::try_from)
+                .transpose()?
+                .unwrap_or(default))
+        };
+        let min_deg = get("min_deg", default.min_deg)?;
+        let max_deg = get("max_deg", default.max_deg)?;
+        if min_deg >= max_deg {
+            return Err(AttributeError::ConversionImpossibleError);
+        }
+        let settle_ms = value
+            .get("settle_ms")?
+            .map(u32::try_from)
+            .transpose()?
+            .map(|ms| ms.min(MAX_SETTLE_MS) as u64);
+        Ok(Self {
+            min_deg,
+            max_deg,
+            tolerance_deg: get("tolerance_deg", default.tolerance_deg)?,
+            settle: settle_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.settle),
+        })
+    }
+}
+
+async fn servo_move_step<S>(
+    servo: &mut S,
+    report: &mut SelfTestReport,
+    name: &'static str,
+    angle_deg: u32,
+    config: &ServoSelfTestConfig,
+) -> bool
+where
+    S: Servo + ?Sized,
+{
+    if let Err(err) = servo.move_to(angle_deg) {
+        return report.check(name, false, err.to_string());
+    }
+    Timer::after(config.settle).await;
+    match servo.get_position() {
+        Ok(position) => report.check(
+            name,
+            position.abs_diff(angle_deg) <= config.tolerance_deg,
+            format!("moved to {} degrees, reported {}", angle_deg, position),
+        ),
+        Err(err) => report.check(name, false, err.to_string()),
+    }
+}
+
+/// Sweeps the servo to both ends of its range of motion, checking the reported position,
+/// then moves it back to its initial position
+pub async fn servo_self_test<S>(servo: &mut S, config: &ServoSelfTestConfig) -> SelfTestReport
+where
+    S: Servo + ?Sized,
+{
+    let mut report = SelfTestReport::default();
+    let initial = match servo.get_position() {
+        Ok(position) => position,
+        Err(err) => {
+            report.step("position", StepStatus::Fail, err.to_string());
+            return report;
+        }
+    };
+    report.step(
+        "position",
+        StepStatus::Pass,
+        format!("starting at {} degrees", initial),
+    );
+    if servo_move_step(servo, &mut report, "min", config.min_deg, config).await {
+        servo_move_step(servo, &mut report, "max", config.max_deg, config).await;
+    }
+    servo_move_step(servo, &mut report, "restore", initial, config).await;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        motor_self_test, self_test_arguments, servo_self_test, MotorSelfTestConfig,
+        ServoSelfTestConfig, StepStatus,
+    };
+    use crate::common::actuator::{Actuator, ActuatorError};
+    use crate::common::motor::{FakeMotor, Motor};
+    use crate::common::servo::{Servo, ServoError};
+    use crate::google::protobuf::{value::Kind, Struct, Value};
+    use futures_lite::future::block_on;
+    use std::time::Duration;
+
+    #[derive(DoCommand, Status)]
+    struct TestServo {
+        position: u32,
+        max_deg: u32,
+    }
+
+    impl Servo for TestServo {
+        fn move_to(&mut self, angle_deg: u32) -> Result<(), ServoError> {
+            self.position = angle_deg.min(self.max_deg);
+            Ok(())
+        }
+        fn get_position(&mut self) -> Result<u32, ServoError> {
+            Ok(self.position)
+        }
+    }
+
+    impl Actuator for TestServo {
+        fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+            Ok(false)
+        }
+        fn stop(&mut self) -> Result<(), ActuatorError> {
+            Ok(())
+        }
+    }
+
+    #[test_log::test]
+    fn test_self_test_arguments() {
+        let command = Struct {
+            fields: [(
+                "self_test".to_string(),
+                Value {
+                    kind: Some(Kind::BoolValue(true)),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let args = self_test_arguments(Some(&command)).unwrap().unwrap();
+        assert_eq!(
+            MotorSelfTestConfig::try_from(&args).unwrap(),
+            MotorSelfTestConfig::default()
+        );
+        assert!(self_test_arguments(Some(&Struct::default())).is_none());
+        assert!(self_test_arguments(None).is_none());
+    }
+
+    #[test_log::test]
+    fn test_motor_self_test() {
+        let config = MotorSelfTestConfig {
+            duration: Duration::from_millis(1),
+            ..Default::default()
+        };
+        // the fake motor's position never changes
+        let mut motor = FakeMotor::new();
+        let report = block_on(motor_self_test(&mut motor, &config));
+        let statuses: Vec<_> = report.steps.iter().map(|s| (s.name, s.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("idle", StepStatus::Pass),
+                ("pulse", StepStatus::Pass),
+                ("encoder", StepStatus::Fail),
+                ("stop", StepStatus::Pass),
+            ]
+        );
+        assert!(!report.passed());
+        assert!(!motor.is_powered().unwrap().0);
+
+        motor.set_power(0.3).unwrap();
+        let report = block_on(motor_self_test(&mut motor, &config));
+        assert_eq!(report.steps.len(), 1);
+        assert!(!report.passed());
+    }
+
+    #[test_log::test]
+    fn test_servo_self_test() {
+        let config = ServoSelfTestConfig {
+            settle: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut servo = TestServo {
+            position: 90,
+            max_deg: 180,
+        };
+        let report = block_on(servo_self_test(&mut servo, &config));
+        assert!(report.passed());
+        assert_eq!(report.steps.len(), 4);
+        assert_eq!(servo.position, 90);
+
+        let mut servo = TestServo {
+            position: 45,
+            max_deg: 120,
+        };
+        let report = block_on(servo_self_test(&mut servo, &config));
+        assert!(!report.passed());
+        assert_eq!(report.steps[2].status, StepStatus::Fail);
+        assert_eq!(servo.position, 45);
+
+        let report: Struct = report.into();
+        assert_eq!(
+            report.fields.get("passed").unwrap().kind,
+            Some(Kind::BoolValue(false))
+        );
+    }
+}
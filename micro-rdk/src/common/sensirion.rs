@@ -0,0 +1,60 @@
+//! Framing shared by the I2C sensors of Sensirion: commands and data are 16 bits words sent most
+//! significant byte first, every word is followed by its CRC-8 (polynomial 0x31 initialized to
+//! 0xFF).
+
+/// CRC-8 of a word
+pub(crate) fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0xFF, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// A command followed by its argument, if any
+pub(crate) fn command_bytes(command: u16, argument: Option<u16>) -> Vec<u8> {
+    let mut bytes = command.to_be_bytes().to_vec();
+    if let Some(argument) = argument {
+        let argument = argument.to_be_bytes();
+        bytes.extend_from_slice(&argument);
+        bytes.push(crc8(&argument));
+    }
+    bytes
+}
+
+/// Decodes the words read from a sensor, `None` when a CRC doesn't match
+pub(crate) fn words_from_bytes<const N: usize>(bytes: &[u8]) -> Option<[u16; N]> {
+    let mut words = [0; N];
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(3)) {
+        if crc8(&chunk[..2]) != chunk[2] {
+            return None;
+        }
+        *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+    }
+    Some(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command_bytes, crc8, words_from_bytes};
+
+    #[test_log::test]
+    fn test_sensirion_framing() {
+        // example of the datasheets
+        assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+        assert_eq!(command_bytes(0x3F86, None), [0x3F, 0x86]);
+        assert_eq!(
+            command_bytes(0x5306, Some(0xBEEF)),
+            [0x53, 0x06, 0xBE, 0xEF, 0x92]
+        );
+        assert_eq!(
+            words_from_bytes::<2>(&[0xBE, 0xEF, 0x92, 0x00, 0x00, 0x81]),
+            Some([0xBEEF, 0])
+        );
+        assert_eq!(words_from_bytes::<1>(&[0xBE, 0xEF, 0x93]), None);
+    }
+}
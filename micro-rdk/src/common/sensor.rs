@@ -19,6 +19,7 @@ use super::i2c::I2CErrors;
 
 use thiserror::Error;
 
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 #[cfg(feature = "data")]
 use crate::{
     google::protobuf::Timestamp,
@@ -45,6 +46,19 @@ pub enum SensorError {
     SensorCodeError(i32),
 }
 
+impl GrpcStatusHint for SensorError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::AnalogError(err) => err.grpc_error(),
+            Self::ConfigError(_) => GrpcError::RpcFailedPrecondition,
+            Self::SensorI2CError(err) => err.grpc_error(),
+            Self::SensorMethodUnimplemented(_) => GrpcError::RpcUnimplemented,
+            Self::SensorBoardError(err) => err.grpc_error(),
+            Self::SensorGenericError(_) | Self::SensorCodeError(_) => GrpcError::RpcInternal,
+        }
+    }
+}
+
 #[cfg(feature = "builtin-components")]
 pub(crate) fn register_models(registry: &mut ComponentRegistry) {
     if registry
@@ -121,8 +135,21 @@ impl From<SensorResult<f64>> for google::protobuf::Value {
     }
 }
 
+/// Converts the typed readings of a sensor to its generic readings, this is the implementation of
+/// [Readings] provided by `#[derive(SensorReadings)]`
+pub fn get_sensor_generic_readings<S>(sensor: &S) -> Result<GenericReadingsResult, SensorError>
+where
+    S: ?Sized + SensorT<f64>,
+{
+    Ok(sensor
+        .get_readings()?
+        .into_iter()
+        .map(|v| (v.0, SensorResult::<f64> { value: v.1 }.into()))
+        .collect())
+}
+
 #[cfg(feature = "builtin-components")]
-#[derive(DoCommand)]
+#[derive(DoCommand, SensorReadings, Status)]
 pub struct FakeSensor {
     fake_reading: f64,
 }
@@ -155,17 +182,6 @@ impl Default for FakeSensor {
 #[cfg(feature = "builtin-components")]
 impl Sensor for FakeSensor {}
 
-#[cfg(feature = "builtin-components")]
-impl Readings for FakeSensor {
-    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
-        Ok(self
-            .get_readings()?
-            .into_iter()
-            .map(|v| (v.0, SensorResult::<f64> { value: v.1 }.into()))
-            .collect())
-    }
-}
-
 #[cfg(feature = "builtin-components")]
 impl SensorT<f64> for FakeSensor {
     fn get_readings(&self) -> Result<TypedReadingsResult<f64>, SensorError> {
@@ -196,14 +212,3 @@ where
         self.lock().unwrap().get_generic_readings()
     }
 }
-
-#[cfg(feature = "builtin-components")]
-impl Status for FakeSensor {
-    fn get_status(
-        &self,
-    ) -> Result<Option<google::protobuf::Struct>, crate::common::status::StatusError> {
-        Ok(Some(google::protobuf::Struct {
-            fields: HashMap::new(),
-        }))
-    }
-}
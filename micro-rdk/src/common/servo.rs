@@ -1,5 +1,7 @@
 use super::{actuator::Actuator, config::AttributeError, generic::DoCommand, status::Status};
 use crate::common::board::BoardError;
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
+use crate::google::protobuf::Struct;
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
 pub static COMPONENT_NAME: &str = "servo";
@@ -13,11 +15,31 @@ pub enum ServoError {
     ServoConfigAttributeError(#[from] AttributeError),
 }
 
+impl GrpcStatusHint for ServoError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::ServoBoardError(err) => err.grpc_error(),
+            Self::ServoConfigurationError(_) => GrpcError::RpcFailedPrecondition,
+            Self::ServoConfigAttributeError(err) => err.grpc_error(),
+        }
+    }
+}
+
 pub trait Servo: Status + Actuator + DoCommand {
     /// Moves the servo to an angular position of `angle_deg` away
     /// from the home position
     fn move_to(&mut self, angle_deg: u32) -> Result<(), ServoError>;
 
+    /// Moves the servo as [Servo::move_to], with implementation specific options passed
+    /// in `extra`
+    fn move_to_with_extra(
+        &mut self,
+        angle_deg: u32,
+        _extra: Option<Struct>,
+    ) -> Result<(), ServoError> {
+        self.move_to(angle_deg)
+    }
+
     /// Gets the current angular position of the servo in degrees
     fn get_position(&mut self) -> Result<u32, ServoError>;
 }
@@ -31,6 +53,13 @@ where
     fn move_to(&mut self, angle_deg: u32) -> Result<(), ServoError> {
         self.get_mut().unwrap().move_to(angle_deg)
     }
+    fn move_to_with_extra(
+        &mut self,
+        angle_deg: u32,
+        extra: Option<Struct>,
+    ) -> Result<(), ServoError> {
+        self.get_mut().unwrap().move_to_with_extra(angle_deg, extra)
+    }
     fn get_position(&mut self) -> Result<u32, ServoError> {
         self.get_mut().unwrap().get_position()
     }
@@ -43,6 +72,13 @@ where
     fn move_to(&mut self, angle_deg: u32) -> Result<(), ServoError> {
         self.lock().unwrap().move_to(angle_deg)
     }
+    fn move_to_with_extra(
+        &mut self,
+        angle_deg: u32,
+        extra: Option<Struct>,
+    ) -> Result<(), ServoError> {
+        self.lock().unwrap().move_to_with_extra(angle_deg, extra)
+    }
     fn get_position(&mut self) -> Result<u32, ServoError> {
         self.lock().unwrap().get_position()
     }
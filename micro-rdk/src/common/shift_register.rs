@@ -0,0 +1,196 @@
+//! Package shift_register implements output-only GPIO expansion with daisy-chained 74HC595
+//! serial-in parallel-out shift registers. A datasheet for this chip is at
+//! https://www.ti.com/lit/ds/symlink/sn74hc595.pdf
+//!
+//! The chips are driven by bit-banging three pins of the board, and are attached to the board
+//! by adding them to the `gpio_expanders` attribute of the board's config:
+//!
+//! ```json
+//! "gpio_expanders": [{
+//!     "name": "leds", "model": "74hc595", "data_pin": 12, "clock_pin": 13, "latch_pin": 14,
+//!     "num_chips": 2
+//! }]
+//! ```
+//!
+//! Outputs QA-QH of the chip connected to the board are addressed as `"leds:0"` to `"leds:7"`,
+//! the outputs of the next chip in the chain as `"leds:8"` to `"leds:15"` and so on. Every
+//! change of a single output shifts out the state of the whole chain.
+
+use super::board::{Board, BoardError, BoardType};
+use super::config::{AttributeError, Kind};
+use super::gpio_expander::{GpioExpander, EXPANDER_PIN_STRIDE};
+
+const PINS_PER_CHIP: u16 = 8;
+
+/// Parses a `74hc595` element of the `gpio_expanders` attribute of a board config
+#[derive(Debug)]
+pub(crate) struct ShiftRegisterConfig {
+    pub(crate) data_pin: i32,
+    pub(crate) clock_pin: i32,
+    pub(crate) latch_pin: i32,
+    pub(crate) num_chips: u8,
+}
+
+impl TryFrom<&Kind> for ShiftRegisterConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let data_pin = value
+            .get("data_pin")?
+            .ok_or(AttributeError::KeyNotFound("data_pin".to_string()))?
+            .try_into()?;
+        let clock_pin = value
+            .get("clock_pin")?
+            .ok_or(AttributeError::KeyNotFound("clock_pin".to_string()))?
+            .try_into()?;
+        let latch_pin = value
+            .get("latch_pin")?
+            .ok_or(AttributeError::KeyNotFound("latch_pin".to_string()))?
+            .try_into()?;
+        let num_chips = match value.get("num_chips")? {
+            Some(val) => val.try_into()?,
+            None => 1,
+        };
+        Ok(ShiftRegisterConfig {
+            data_pin,
+            clock_pin,
+            latch_pin,
+            num_chips,
+        })
+    }
+}
+
+/// Returns the bits to shift out for the given output states (one byte per chip, starting with
+/// the chip connected to the board), the first bit ends up on the last output of the last chip
+pub(crate) fn shift_out_bits(outputs: &[u8]) -> Vec<bool> {
+    outputs
+        .iter()
+        .rev()
+        .flat_map(|byte| {
+            (0..PINS_PER_CHIP)
+                .rev()
+                .map(move |bit| byte & (1 << bit) != 0)
+        })
+        .collect()
+}
+
+pub struct ShiftRegister {
+    board: BoardType,
+    data_pin: i32,
+    clock_pin: i32,
+    latch_pin: i32,
+    outputs: Vec<u8>,
+}
+
+impl ShiftRegister {
+    pub(crate) fn new(board: BoardType, conf: &ShiftRegisterConfig) -> Result<Self, BoardError> {
+        if conf.num_chips == 0 || conf.num_chips as i32 * PINS_PER_CHIP as i32 > EXPANDER_PIN_STRIDE
+        {
+            return Err(BoardError::BoardUnsupportedArgument(
+                "74hc595 num_chips must be between 1 and 12",
+            ));
+        }
+        let mut expander = ShiftRegister {
+            board,
+            data_pin: conf.data_pin,
+            clock_pin: conf.clock_pin,
+            latch_pin: conf.latch_pin,
+            outputs: vec![0; conf.num_chips as usize],
+        };
+        expander
+            .board
+            .set_gpio_pin_level(expander.clock_pin, false)?;
+        expander
+            .board
+            .set_gpio_pin_level(expander.latch_pin, false)?;
+        // start with every output low
+        expander.shift_out(&expander.outputs.clone())?;
+        Ok(expander)
+    }
+
+    fn shift_out(&mut self, outputs: &[u8]) -> Result<(), BoardError> {
+        for bit in shift_out_bits(outputs) {
+            self.board.set_gpio_pin_level(self.data_pin, bit)?;
+            self.board.set_gpio_pin_level(self.clock_pin, true)?;
+            self.board.set_gpio_pin_level(self.clock_pin, false)?;
+        }
+        // transfer the shifted bits to the outputs
+        self.board.set_gpio_pin_level(self.latch_pin, true)?;
+        self.board.set_gpio_pin_level(self.latch_pin, false)
+    }
+
+    fn check_pin(&self, pin: u16) -> Result<(), BoardError> {
+        if pin >= self.num_pins() {
+            return Err(BoardError::BoardUnsupportedArgument("74hc595 invalid pin"));
+        }
+        Ok(())
+    }
+}
+
+impl GpioExpander for ShiftRegister {
+    fn num_pins(&self) -> u16 {
+        self.outputs.len() as u16 * PINS_PER_CHIP
+    }
+
+    fn set_gpio_pin_level(&mut self, pin: u16, is_high: bool) -> Result<(), BoardError> {
+        self.check_pin(pin)?;
+        let mut outputs = self.outputs.clone();
+        let (chip, bit) = ((pin / PINS_PER_CHIP) as usize, pin % PINS_PER_CHIP);
+        if is_high {
+            outputs[chip] |= 1 << bit;
+        } else {
+            outputs[chip] &= !(1 << bit);
+        }
+        self.shift_out(&outputs)?;
+        self.outputs = outputs;
+        Ok(())
+    }
+
+    // the outputs can't be read back, the last state shifted out is returned
+    fn get_gpio_level(&mut self, pin: u16) -> Result<bool, BoardError> {
+        self.check_pin(pin)?;
+        Ok(self.outputs[(pin / PINS_PER_CHIP) as usize] & (1 << (pin % PINS_PER_CHIP)) != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shift_out_bits, ShiftRegister, ShiftRegisterConfig};
+    use crate::common::board::{BoardError, BoardType, FakeBoard};
+    use crate::common::gpio_expander::GpioExpander;
+    use std::sync::{Arc, Mutex};
+
+    #[test_log::test]
+    fn test_shift_out_bits() {
+        let bits = shift_out_bits(&[0b0000_0001, 0b1000_0010]);
+        let expected = [
+            true, false, false, false, false, false, true, false, false, false, false, false,
+            false, false, false, true,
+        ];
+        assert_eq!(bits, expected);
+    }
+
+    #[test_log::test]
+    fn test_shift_register() -> Result<(), BoardError> {
+        let board: BoardType = Arc::new(Mutex::new(FakeBoard::new(vec![])));
+        let conf = ShiftRegisterConfig {
+            data_pin: 12,
+            clock_pin: 13,
+            latch_pin: 14,
+            num_chips: 2,
+        };
+        let mut expander = ShiftRegister::new(board.clone(), &conf)?;
+        assert_eq!(expander.num_pins(), 16);
+        expander.set_gpio_pin_level(9, true)?;
+        assert!(expander.get_gpio_level(9)?);
+        assert!(!expander.get_gpio_level(1)?);
+        assert_eq!(expander.outputs, vec![0, 0b10]);
+        assert!(expander.set_gpio_pin_level(16, true).is_err());
+
+        let conf = ShiftRegisterConfig {
+            num_chips: 0,
+            ..conf
+        };
+        assert!(ShiftRegister::new(board, &conf).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,632 @@
+//! Simulated components, enabled by the `sim` feature, so client applications can be developed
+//! end-to-end against micro-rdk running natively without any hardware. The kinematics are
+//! physics free: commanded velocities are applied instantly and integrated over time.
+//!
+//! - a `sim` base integrating its commanded linear and angular velocities into a pose
+//! - a `sim` movement sensor acting as a GPS and compass mounted on a base
+//! - a `sim` encoder counting the revolutions of a motor (the `fake` motor advances its
+//!   position by `power * max_rpm` when the `sim` feature is enabled)
+//!
+//! ```json
+//! [
+//!     {
+//!         "name": "base",
+//!         "type": "base",
+//!         "model": "sim",
+//!         "attributes": { "max_speed_mm_per_sec": 500, "max_degs_per_sec": 90 }
+//!     },
+//!     {
+//!         "name": "gps",
+//!         "type": "movement_sensor",
+//!         "model": "sim",
+//!         "attributes": { "base": "base", "lat": 40.7128, "lon": -74.006 }
+//!     },
+//!     {
+//!         "name": "motor",
+//!         "type": "motor",
+//!         "model": "fake",
+//!         "attributes": { "max_rpm": 60 }
+//!     },
+//!     {
+//!         "name": "encoder",
+//!         "type": "encoder",
+//!         "model": "sim",
+//!         "attributes": { "motor": "motor", "ticks_per_rotation": 100 }
+//!     }
+//! ]
+//! ```
+//!
+//! The base starts at the origin facing north. Its pose can be read with the `get_pose`
+//! command, `{"get_pose": {}}`, answering with `x_m` (east), `y_m` (north), `theta_deg`
+//! (counterclockwise from north) and the current velocities.
+
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::google::protobuf::{value::Kind, Struct};
+use crate::proto::common::v1::Vector3 as ProtoVector3;
+
+use super::actuator::{Actuator, ActuatorError};
+use super::base::{Base, BaseError, BaseProperties, BaseType, COMPONENT_NAME as BaseCompName};
+use super::config::ConfigType;
+use super::encoder::{
+    Encoder, EncoderError, EncoderPosition, EncoderPositionType, EncoderSupportedRepresentations,
+    EncoderType, COMPONENT_NAME as EncoderCompName,
+};
+use super::generic::{DoCommand, GenericError};
+use super::math_utils::Vector3;
+use super::motor::{MotorType, COMPONENT_NAME as MotorCompName};
+use super::movement_sensor::{
+    GeoPosition, MovementSensor, MovementSensorSupportedMethods, MovementSensorType,
+    COMPONENT_NAME as MovementSensorCompName,
+};
+use super::registry::{ComponentRegistry, Dependency, ResourceKey};
+use super::robot::Resource;
+use super::sensor::SensorError;
+use super::status::{Status, StatusError};
+use super::struct_builder::StructBuilder;
+
+/// Command returning the pose of a simulated base
+pub static GET_POSE_COMMAND: &str = "get_pose";
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_base("sim", &SimBase::from_config)
+        .is_err()
+    {
+        log::error!("sim base type is already registered");
+    }
+    if registry
+        .register_movement_sensor("sim", &SimMovementSensor::from_config)
+        .is_err()
+    {
+        log::error!("sim movement sensor type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            MovementSensorCompName,
+            "sim",
+            &SimMovementSensor::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("sim movement sensor dependency function is already registered");
+    }
+    if registry
+        .register_encoder("sim", &SimEncoder::from_config)
+        .is_err()
+    {
+        log::error!("sim encoder type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            EncoderCompName,
+            "sim",
+            &SimEncoder::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("sim encoder dependency function is already registered");
+    }
+}
+
+/// Position of a simulated base on a flat ground
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pose {
+    /// Meters east of the origin
+    pub x_m: f64,
+    /// Meters north of the origin
+    pub y_m: f64,
+    /// Heading counterclockwise from north, in degrees
+    pub theta_deg: f64,
+}
+
+impl Pose {
+    /// Advances the pose by moving at constant velocities for `dt`
+    fn integrate(&mut self, mm_per_sec: f64, degs_per_sec: f64, dt: Duration) {
+        let dt = dt.as_secs_f64();
+        let v = mm_per_sec / 1000.0;
+        let theta = self.theta_deg * PI / 180.0;
+        let omega = degs_per_sec * PI / 180.0;
+        if omega.abs() < 1e-9 {
+            self.x_m -= v * dt * theta.sin();
+            self.y_m += v * dt * theta.cos();
+        } else {
+            // the base follows an arc of radius v / omega
+            let end = theta + omega * dt;
+            self.x_m += v / omega * (end.cos() - theta.cos());
+            self.y_m += v / omega * (end.sin() - theta.sin());
+        }
+        self.theta_deg = (self.theta_deg + degs_per_sec * dt).rem_euclid(360.0);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Velocities {
+    mm_per_sec: f64,
+    degs_per_sec: f64,
+}
+
+/// Base integrating its commanded velocities into a [Pose]
+pub struct SimBase {
+    pose: Pose,
+    velocities: Velocities,
+    // end of the current move_straight or spin, the base stops by itself afterwards
+    motion_end: Option<Instant>,
+    last_update: Instant,
+    max_mm_per_sec: f64,
+    max_degs_per_sec: f64,
+    properties: BaseProperties,
+}
+
+impl SimBase {
+    pub fn new(max_mm_per_sec: f64, max_degs_per_sec: f64) -> Self {
+        Self {
+            pose: Pose::default(),
+            velocities: Velocities::default(),
+            motion_end: None,
+            last_update: Instant::now(),
+            max_mm_per_sec,
+            max_degs_per_sec,
+            properties: BaseProperties {
+                width_meters: 0.4,
+                turning_radius_meters: 0.0,
+                wheel_circumference_meters: 0.22,
+            },
+        }
+    }
+
+    pub(crate) fn from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<BaseType, BaseError> {
+        let mut base = Self::new(
+            cfg.get_attribute::<f64>("max_speed_mm_per_sec")
+                .unwrap_or(500.0),
+            cfg.get_attribute::<f64>("max_degs_per_sec").unwrap_or(90.0),
+        );
+        if base.max_mm_per_sec <= 0.0 || base.max_degs_per_sec <= 0.0 {
+            return Err(BaseError::BaseConfigError(
+                "sim base maximum velocities should be positive",
+            ));
+        }
+        if let Ok(width_mm) = cfg.get_attribute::<f64>("width_mm") {
+            base.properties.width_meters = width_mm / 1000.0;
+        }
+        if let Ok(circumference_mm) = cfg.get_attribute::<f64>("wheel_circumference_mm") {
+            base.properties.wheel_circumference_meters = circumference_mm / 1000.0;
+        }
+        Ok(Arc::new(Mutex::new(base)))
+    }
+
+    /// Integrates the motion up to `now`
+    fn advance(&mut self, now: Instant) {
+        if let Some(end) = self.motion_end.filter(|end| *end <= now) {
+            let dt = end.saturating_duration_since(self.last_update);
+            self.pose
+                .integrate(self.velocities.mm_per_sec, self.velocities.degs_per_sec, dt);
+            self.velocities = Velocities::default();
+            self.motion_end = None;
+            self.last_update = end;
+        }
+        let dt = now.saturating_duration_since(self.last_update);
+        self.pose
+            .integrate(self.velocities.mm_per_sec, self.velocities.degs_per_sec, dt);
+        self.last_update = now;
+    }
+
+    fn command(&mut self, velocities: Velocities, duration: Option<Duration>, now: Instant) {
+        self.advance(now);
+        self.velocities = velocities;
+        self.motion_end = duration.map(|duration| now + duration);
+    }
+
+    pub fn pose(&mut self) -> Pose {
+        self.advance(Instant::now());
+        self.pose
+    }
+
+    fn move_straight_at(
+        &mut self,
+        distance_mm: i64,
+        mm_per_sec: f64,
+        now: Instant,
+    ) -> Option<Duration> {
+        let speed = mm_per_sec.abs().min(self.max_mm_per_sec);
+        if distance_mm == 0 || speed == 0.0 {
+            self.command(Velocities::default(), None, now);
+            return None;
+        }
+        // moving backwards when either the distance or the speed is negative
+        let direction = (distance_mm.signum() as f64) * mm_per_sec.signum();
+        let duration = Duration::from_secs_f64(distance_mm.unsigned_abs() as f64 / speed);
+        let velocities = Velocities {
+            mm_per_sec: direction * speed,
+            degs_per_sec: 0.0,
+        };
+        self.command(velocities, Some(duration), now);
+        Some(duration)
+    }
+
+    fn spin_at(&mut self, angle_deg: f64, degs_per_sec: f64, now: Instant) -> Option<Duration> {
+        let speed = degs_per_sec.abs().min(self.max_degs_per_sec);
+        if angle_deg == 0.0 || speed == 0.0 {
+            self.command(Velocities::default(), None, now);
+            return None;
+        }
+        let duration = Duration::from_secs_f64(angle_deg.abs() / speed);
+        let velocities = Velocities {
+            mm_per_sec: 0.0,
+            degs_per_sec: angle_deg.signum() * degs_per_sec.signum() * speed,
+        };
+        self.command(velocities, Some(duration), now);
+        Some(duration)
+    }
+}
+
+impl Base for SimBase {
+    fn set_power(&mut self, lin: &ProtoVector3, ang: &ProtoVector3) -> Result<(), BaseError> {
+        let velocities = Velocities {
+            mm_per_sec: lin.y.clamp(-1.0, 1.0) * self.max_mm_per_sec,
+            degs_per_sec: ang.z.clamp(-1.0, 1.0) * self.max_degs_per_sec,
+        };
+        self.command(velocities, None, Instant::now());
+        Ok(())
+    }
+    fn get_properties(&mut self) -> Result<BaseProperties, BaseError> {
+        Ok(self.properties)
+    }
+    fn move_straight(
+        &mut self,
+        distance_mm: i64,
+        mm_per_sec: f64,
+    ) -> Result<Option<Duration>, BaseError> {
+        Ok(self.move_straight_at(distance_mm, mm_per_sec, Instant::now()))
+    }
+    fn spin(&mut self, angle_deg: f64, degs_per_sec: f64) -> Result<Option<Duration>, BaseError> {
+        Ok(self.spin_at(angle_deg, degs_per_sec, Instant::now()))
+    }
+}
+
+impl Actuator for SimBase {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        self.advance(Instant::now());
+        Ok(self.velocities != Velocities::default())
+    }
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        self.command(Velocities::default(), None, Instant::now());
+        Ok(())
+    }
+}
+
+impl Status for SimBase {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(
+            StructBuilder::with_capacity(1)
+                .field("is_moving", self.velocities != Velocities::default())
+                .build(),
+        ))
+    }
+}
+
+impl DoCommand for SimBase {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        match command_struct {
+            Some(cmd) if cmd.fields.contains_key(GET_POSE_COMMAND) => {
+                let pose = self.pose();
+                Ok(Some(
+                    StructBuilder::with_capacity(5)
+                        .field("x_m", pose.x_m)
+                        .field("y_m", pose.y_m)
+                        .field("theta_deg", pose.theta_deg)
+                        .field("linear_mm_per_sec", self.velocities.mm_per_sec)
+                        .field("angular_degs_per_sec", self.velocities.degs_per_sec)
+                        .build(),
+                ))
+            }
+            _ => Err(GenericError::MethodUnimplemented("do_command")),
+        }
+    }
+}
+
+fn pose_field(pose: &Struct, key: &'static str) -> Result<f64, SensorError> {
+    match pose.fields.get(key).and_then(|v| v.kind.as_ref()) {
+        Some(Kind::NumberValue(value)) => Ok(*value),
+        _ => Err(SensorError::SensorGenericError(
+            "sim movement sensor: base didn't report its pose",
+        )),
+    }
+}
+
+/// GPS and compass following a base answering the `get_pose` command, usually a [SimBase]
+#[derive(DoCommand, MovementSensorReadings, Status)]
+pub struct SimMovementSensor {
+    base: BaseType,
+    origin: GeoPosition,
+}
+
+impl SimMovementSensor {
+    pub fn new(base: BaseType, origin: GeoPosition) -> Self {
+        Self { base, origin }
+    }
+
+    pub(crate) fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        let mut r_keys = Vec::new();
+        if let Ok(base_name) = cfg.get_attribute::<String>("base") {
+            r_keys.push(ResourceKey(BaseCompName, base_name));
+        }
+        r_keys
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<MovementSensorType, SensorError> {
+        let base = deps
+            .into_iter()
+            .find_map(|Dependency(_, dep)| match dep {
+                Resource::Base(base) => Some(base),
+                _ => None,
+            })
+            .ok_or(SensorError::ConfigError(
+                "sim movement sensor requires a base",
+            ))?;
+        let origin = GeoPosition {
+            lat: cfg.get_attribute::<f64>("lat").unwrap_or(40.7128),
+            lon: cfg.get_attribute::<f64>("lon").unwrap_or(-74.006),
+            alt: cfg.get_attribute::<f32>("alt").unwrap_or(0.0),
+        };
+        Ok(Arc::new(Mutex::new(Self::new(base, origin))))
+    }
+
+    fn base_pose(&mut self) -> Result<Struct, SensorError> {
+        let command = StructBuilder::new()
+            .sub(GET_POSE_COMMAND, StructBuilder::new())
+            .build();
+        self.base
+            .lock()
+            .unwrap()
+            .do_command(Some(command))
+            .ok()
+            .flatten()
+            .ok_or(SensorError::SensorGenericError(
+                "sim movement sensor: base didn't report its pose",
+            ))
+    }
+}
+
+impl MovementSensor for SimMovementSensor {
+    fn get_position(&mut self) -> Result<GeoPosition, SensorError> {
+        let pose = self.base_pose()?;
+        let (x_m, y_m) = (pose_field(&pose, "x_m")?, pose_field(&pose, "y_m")?);
+        let lat_rad = self.origin.lat * PI / 180.0;
+        Ok(GeoPosition {
+            lat: self.origin.lat + (y_m / EARTH_RADIUS_M) * 180.0 / PI,
+            lon: self.origin.lon + (x_m / (EARTH_RADIUS_M * lat_rad.cos())) * 180.0 / PI,
+            alt: self.origin.alt,
+        })
+    }
+    fn get_linear_velocity(&mut self) -> Result<Vector3, SensorError> {
+        let pose = self.base_pose()?;
+        Ok(Vector3 {
+            x: 0.0,
+            y: pose_field(&pose, "linear_mm_per_sec")? / 1000.0,
+            z: 0.0,
+        })
+    }
+    fn get_angular_velocity(&mut self) -> Result<Vector3, SensorError> {
+        let pose = self.base_pose()?;
+        Ok(Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: pose_field(&pose, "angular_degs_per_sec")?,
+        })
+    }
+    fn get_linear_acceleration(&mut self) -> Result<Vector3, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented(
+            "get_linear_acceleration",
+        ))
+    }
+    fn get_compass_heading(&mut self) -> Result<f64, SensorError> {
+        let pose = self.base_pose()?;
+        // compass headings turn clockwise
+        Ok((360.0 - pose_field(&pose, "theta_deg")?).rem_euclid(360.0))
+    }
+    fn get_properties(&self) -> MovementSensorSupportedMethods {
+        MovementSensorSupportedMethods {
+            position_supported: true,
+            linear_velocity_supported: true,
+            angular_velocity_supported: true,
+            linear_acceleration_supported: false,
+            compass_heading_supported: true,
+            orientation_supported: false,
+        }
+    }
+}
+
+/// Encoder counting the revolutions reported in the `position` field of a motor's status
+#[derive(DoCommand, Status)]
+pub struct SimEncoder {
+    motor: MotorType,
+    ticks_per_rotation: u32,
+    // revolutions of the motor at the last reset
+    offset: f64,
+}
+
+impl SimEncoder {
+    pub fn new(motor: MotorType, ticks_per_rotation: u32) -> Self {
+        Self {
+            motor,
+            ticks_per_rotation,
+            offset: 0.0,
+        }
+    }
+
+    pub(crate) fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        let mut r_keys = Vec::new();
+        if let Ok(motor_name) = cfg.get_attribute::<String>("motor") {
+            r_keys.push(ResourceKey(MotorCompName, motor_name));
+        }
+        r_keys
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<EncoderType, EncoderError> {
+        let motor = deps
+            .into_iter()
+            .find_map(|Dependency(_, dep)| match dep {
+                Resource::Motor(motor) => Some(motor),
+                _ => None,
+            })
+            .ok_or(EncoderError::EncoderConfigError(
+                "sim encoder requires a motor",
+            ))?;
+        let ticks_per_rotation = cfg
+            .get_attribute::<u32>("ticks_per_rotation")
+            .unwrap_or(100);
+        if ticks_per_rotation == 0 {
+            return Err(EncoderError::EncoderConfigError(
+                "ticks_per_rotation should be positive",
+            ));
+        }
+        let mut encoder = Self::new(motor, ticks_per_rotation);
+        encoder.offset = encoder.motor_revolutions()?;
+        Ok(Arc::new(Mutex::new(encoder)))
+    }
+
+    fn motor_revolutions(&self) -> Result<f64, EncoderError> {
+        let status =
+            self.motor.lock().unwrap().get_status().map_err(|_| {
+                EncoderError::EncoderConfigError("sim encoder couldn't read its motor")
+            })?;
+        match status
+            .as_ref()
+            .and_then(|status| status.fields.get("position"))
+            .and_then(|position| position.kind.as_ref())
+        {
+            Some(Kind::NumberValue(revolutions)) => Ok(*revolutions),
+            _ => Err(EncoderError::EncoderConfigError(
+                "sim encoder motor doesn't report its position",
+            )),
+        }
+    }
+}
+
+impl Encoder for SimEncoder {
+    fn get_properties(&mut self) -> EncoderSupportedRepresentations {
+        EncoderSupportedRepresentations {
+            ticks_count_supported: true,
+            angle_degrees_supported: true,
+        }
+    }
+    fn get_position(
+        &self,
+        position_type: EncoderPositionType,
+    ) -> Result<EncoderPosition, EncoderError> {
+        let revolutions = self.motor_revolutions()? - self.offset;
+        match position_type {
+            EncoderPositionType::UNSPECIFIED => Err(EncoderError::EncoderUnspecified),
+            EncoderPositionType::DEGREES => {
+                Ok(position_type.wrap_value((revolutions * 360.0) as f32))
+            }
+            EncoderPositionType::TICKS => Ok(position_type
+                .wrap_value((revolutions * self.ticks_per_rotation as f64).floor() as f32)),
+        }
+    }
+    fn reset_position(&mut self) -> Result<(), EncoderError> {
+        self.offset = self.motor_revolutions()?;
+        Ok(())
+    }
+    fn ticks_per_rotation(&self) -> Option<u32> {
+        Some(self.ticks_per_rotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pose, SimBase, SimEncoder, SimMovementSensor};
+    use crate::common::encoder::{Encoder, EncoderPositionType};
+    use crate::common::motor::{FakeMotor, Motor};
+    use crate::common::movement_sensor::{GeoPosition, MovementSensor};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[test_log::test]
+    fn test_pose_integration() {
+        let mut pose = Pose::default();
+        pose.integrate(1000.0, 0.0, Duration::from_secs(2));
+        assert!((pose.y_m - 2.0).abs() < 1e-9);
+        assert!(pose.x_m.abs() < 1e-9);
+
+        // a half turn to the left while moving ends 2r west of the start, facing south
+        let mut pose = Pose::default();
+        pose.integrate(
+            1000.0 * std::f64::consts::PI / 180.0,
+            1.0,
+            Duration::from_secs(180),
+        );
+        assert!((pose.x_m + 2.0).abs() < 1e-6);
+        assert!(pose.y_m.abs() < 1e-6);
+        assert!((pose.theta_deg - 180.0).abs() < 1e-6);
+    }
+
+    #[test_log::test]
+    fn test_sim_base_and_gps() {
+        let mut base = SimBase::new(500.0, 90.0);
+        let start = Instant::now();
+        base.last_update = start;
+        // 1000mm at 500mm/s takes 2 seconds, the base stops by itself afterwards
+        assert_eq!(
+            base.move_straight_at(1000, 800.0, start),
+            Some(Duration::from_secs(2))
+        );
+        base.advance(start + Duration::from_secs(1));
+        assert!((base.pose.y_m - 0.5).abs() < 1e-9);
+        base.advance(start + Duration::from_secs(5));
+        assert!((base.pose.y_m - 1.0).abs() < 1e-9);
+        assert_eq!(base.velocities, Default::default());
+
+        let now = start + Duration::from_secs(5);
+        base.spin_at(-90.0, 45.0, now);
+        base.advance(now + Duration::from_secs(3));
+        assert!((base.pose.theta_deg - 270.0).abs() < 1e-9);
+        base.last_update = Instant::now();
+
+        let base = Arc::new(Mutex::new(base));
+        let mut gps = SimMovementSensor::new(
+            base.clone(),
+            GeoPosition {
+                lat: 40.0,
+                lon: -74.0,
+                alt: 0.0,
+            },
+        );
+        let position = gps.get_position().unwrap();
+        assert!(position.lat > 40.0);
+        assert!((position.lon + 74.0).abs() < 1e-9);
+        assert!((gps.get_compass_heading().unwrap() - 90.0).abs() < 1e-6);
+    }
+
+    #[test_log::test]
+    fn test_sim_encoder() {
+        let motor = Arc::new(Mutex::new(FakeMotor::new()));
+        let mut encoder = SimEncoder::new(motor.clone(), 100);
+        encoder.reset_position().unwrap();
+        let ticks = encoder.get_position(EncoderPositionType::TICKS).unwrap();
+        assert_eq!(ticks.value, 0.0);
+
+        motor.lock().unwrap().set_power(1.0).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        motor.lock().unwrap().set_power(0.0).unwrap();
+        // 100 rpm for 50ms is at least 8 ticks
+        let ticks = encoder.get_position(EncoderPositionType::TICKS).unwrap();
+        assert!(ticks.value >= 8.0);
+        let degrees = encoder.get_position(EncoderPositionType::DEGREES).unwrap();
+        assert!(degrees.value >= 30.0);
+    }
+}
@@ -0,0 +1,225 @@
+//! Robot configuration compiled into the firmware, for products running without app (air
+//! gapped installations, devices without network access to app.viam.com...).
+//!
+//! The configuration uses the JSON format of the robot's config in app ("Raw JSON" mode), it
+//! is embedded with `include_str!` and converted to the [ConfigResponse] app would have
+//! returned. The robot is then built from it as usual and served without any cloud feature, see
+//! `native::entry::serve_offline` and `esp32::entry::serve_offline`.
+//!
+//! ```json
+//! {
+//!     "name": "my-robot",
+//!     "components": [
+//!         {
+//!             "name": "board",
+//!             "type": "board",
+//!             "model": "esp32",
+//!             "attributes": { "pins": [15] }
+//!         },
+//!         {
+//!             "name": "motor",
+//!             "type": "motor",
+//!             "model": "gpio",
+//!             "attributes": { "pins": { "pwm": 15 }, "board": "board" },
+//!             "depends_on": ["board"]
+//!         }
+//!     ],
+//!     "services": [
+//!         { "name": "automations", "type": "automation", "model": "builtin", "attributes": {} }
+//!     ]
+//! }
+//! ```
+//!
+//! Models without a namespace are builtin models, as in app: `gpio` stands for
+//! `rdk:builtin:gpio`.
+//!
+//! `name` is advertised over mDNS as `<name>.local` (defaults to `micro-rdk`). Besides
+//! `components` and `services`, `debug` and `disable_partial_start` are supported; other fields
+//! of the config (remotes, modules, frames...) are ignored. An invalid component fails the
+//! robot unless `disable_partial_start` is set to `false`, which skips it instead.
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::struct_builder::json_to_value;
+use crate::google::protobuf::{value::Kind, Struct};
+use crate::proto::app::v1::{
+    CloudConfig, ComponentConfig, ConfigResponse, RobotConfig, ServiceConfig,
+};
+
+const DEFAULT_ROBOT_NAME: &str = "micro-rdk";
+const DEFAULT_NAMESPACE: &str = "rdk";
+const BUILTIN_MODEL_PREFIX: &str = "rdk:builtin:";
+
+#[derive(Error, Debug)]
+pub enum StaticConfigError {
+    #[error("invalid robot config: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("attributes of `{0}` should be an object")]
+    InvalidAttributes(String),
+}
+
+#[derive(Deserialize)]
+struct JsonRobotConfig {
+    name: Option<String>,
+    #[serde(default)]
+    components: Vec<JsonResourceConfig>,
+    #[serde(default)]
+    services: Vec<JsonResourceConfig>,
+    debug: Option<bool>,
+    disable_partial_start: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct JsonResourceConfig {
+    name: String,
+    #[serde(default)]
+    namespace: String,
+    #[serde(rename = "type", default)]
+    r#type: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    api: String,
+    attributes: Option<serde_json::Value>,
+    #[serde(default)]
+    depends_on: Vec<String>,
+}
+
+impl JsonResourceConfig {
+    fn namespace(&self) -> String {
+        if self.namespace.is_empty() {
+            DEFAULT_NAMESPACE.to_string()
+        } else {
+            self.namespace.clone()
+        }
+    }
+
+    // app sends the full name of the models
+    fn model(&self) -> String {
+        if self.model.is_empty() || self.model.contains(':') {
+            self.model.clone()
+        } else {
+            format!("{}{}", BUILTIN_MODEL_PREFIX, self.model)
+        }
+    }
+
+    fn attributes(&mut self) -> Result<Option<Struct>, StaticConfigError> {
+        match self.attributes.take().map(|json| json_to_value(json).kind) {
+            None => Ok(None),
+            Some(Some(Kind::StructValue(attributes))) => Ok(Some(attributes)),
+            Some(_) => Err(StaticConfigError::InvalidAttributes(self.name.clone())),
+        }
+    }
+}
+
+/// Converts the JSON config of a robot to the response app returns to `Config` requests
+pub fn config_response_from_json(json: &str) -> Result<ConfigResponse, StaticConfigError> {
+    let config: JsonRobotConfig = serde_json::from_str(json)?;
+    let name = config.name.as_deref().unwrap_or(DEFAULT_ROBOT_NAME);
+    let fqdn = format!("{}.local", name);
+    let components = config
+        .components
+        .into_iter()
+        .map(|mut component| {
+            Ok(ComponentConfig {
+                attributes: component.attributes()?,
+                namespace: component.namespace(),
+                model: component.model(),
+                name: component.name,
+                r#type: component.r#type,
+                api: component.api,
+                depends_on: component.depends_on,
+                ..Default::default()
+            })
+        })
+        .collect::<Result<_, StaticConfigError>>()?;
+    let services = config
+        .services
+        .into_iter()
+        .map(|mut service| {
+            Ok(ServiceConfig {
+                attributes: service.attributes()?,
+                namespace: service.namespace(),
+                model: service.model(),
+                name: service.name,
+                r#type: service.r#type,
+                api: service.api,
+                depends_on: service.depends_on,
+                ..Default::default()
+            })
+        })
+        .collect::<Result<_, StaticConfigError>>()?;
+    Ok(ConfigResponse {
+        config: Some(RobotConfig {
+            cloud: Some(CloudConfig {
+                fqdn: fqdn.clone(),
+                local_fqdn: fqdn,
+                ..Default::default()
+            }),
+            components,
+            services,
+            debug: config.debug,
+            disable_partial_start: config.disable_partial_start,
+            ..Default::default()
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{config_response_from_json, StaticConfigError};
+    use crate::common::robot::LocalRobot;
+    use crate::google::protobuf::value::Kind;
+
+    const CONFIG: &str = r#"{
+        "name": "offline",
+        "components": [
+            { "name": "board", "type": "board", "model": "fake", "attributes": { "pins": [1] } },
+            {
+                "name": "motor",
+                "type": "motor",
+                "namespace": "rdk",
+                "model": "rdk:builtin:fake",
+                "attributes": { "board": "board", "max_rpm": 100 },
+                "depends_on": ["board"]
+            }
+        ],
+        "services": [{ "name": "probe", "type": "instrumentation", "attributes": {} }],
+        "remotes": []
+    }"#;
+
+    #[test_log::test]
+    fn test_static_config() {
+        let response = config_response_from_json(CONFIG).unwrap();
+        let config = response.config.as_ref().unwrap();
+        assert_eq!(config.cloud.as_ref().unwrap().fqdn, "offline.local");
+        assert_eq!(config.components.len(), 2);
+        assert_eq!(config.components[0].model, "rdk:builtin:fake");
+        assert_eq!(config.components[0].namespace, "rdk");
+        assert_eq!(config.components[1].depends_on, vec!["board".to_string()]);
+        assert_eq!(
+            config.components[1]
+                .attributes
+                .as_ref()
+                .unwrap()
+                .fields
+                .get("max_rpm")
+                .and_then(|v| v.kind.clone()),
+            Some(Kind::NumberValue(100.0))
+        );
+        assert_eq!(config.services[0].r#type, "instrumentation");
+
+        let robot = LocalRobot::from_cloud_config(&response, Box::default(), None).unwrap();
+        assert!(robot.get_board_by_name("board".to_string()).is_some());
+        assert!(robot.get_motor_by_name("motor".to_string()).is_some());
+
+        assert!(matches!(
+            config_response_from_json(
+                r#"{"components": [{"name": "m", "type": "motor", "attributes": [1]}]}"#
+            ),
+            Err(StaticConfigError::InvalidAttributes(_))
+        ));
+        assert!(config_response_from_json("{").is_err());
+    }
+}
@@ -1,10 +1,13 @@
+use std::fmt::Display;
 use std::sync::{Arc, Mutex};
 
 use crate::google;
+use crate::google::protobuf::value::Kind;
 
 use thiserror::Error;
 
 use super::encoder::EncoderError;
+use crate::common::grpc::{GrpcError, GrpcStatusHint};
 
 #[derive(Error, Debug)]
 pub enum StatusError {
@@ -12,6 +15,14 @@ pub enum StatusError {
     EncoderError(#[from] EncoderError),
 }
 
+impl GrpcStatusHint for StatusError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::EncoderError(err) => err.grpc_error(),
+        }
+    }
+}
+
 pub trait Status {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError>;
 }
@@ -33,3 +44,172 @@ where
         (**self).get_status()
     }
 }
+
+/// Tracks the outcome of the operations of a component, reported in the envelope added to its
+/// status by [status_envelope]
+#[derive(Debug, Clone)]
+pub struct ComponentHealth {
+    ok: bool,
+    last_error: Option<String>,
+    last_update: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ComponentHealth {
+    pub fn new() -> Self {
+        ComponentHealth {
+            ok: true,
+            last_error: None,
+            last_update: None,
+        }
+    }
+
+    /// Records the result of an operation, an error is kept as the last error until another
+    /// one occurs
+    pub fn record<T, E: Display>(&mut self, result: &Result<T, E>) {
+        self.last_update = Some(chrono::Utc::now());
+        self.ok = result.is_ok();
+        if let Err(err) = result {
+            self.last_error = Some(err.to_string());
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.ok
+    }
+
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+}
+
+impl Default for ComponentHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Conversion of the struct fields reported by `#[derive(Status)]`
+pub trait StatusValue {
+    fn to_status_kind(&self) -> Kind;
+}
+
+macro_rules! impl_status_value_for_number {
+    ($($t:ty),*) => {
+        $(
+            impl StatusValue for $t {
+                fn to_status_kind(&self) -> Kind {
+                    Kind::NumberValue(*self as f64)
+                }
+            }
+        )*
+    };
+}
+
+impl_status_value_for_number!(f64, f32, i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl StatusValue for bool {
+    fn to_status_kind(&self) -> Kind {
+        Kind::BoolValue(*self)
+    }
+}
+
+impl StatusValue for String {
+    fn to_status_kind(&self) -> Kind {
+        Kind::StringValue(self.clone())
+    }
+}
+
+impl StatusValue for &str {
+    fn to_status_kind(&self) -> Kind {
+        Kind::StringValue(self.to_string())
+    }
+}
+
+impl StatusValue for google::protobuf::Struct {
+    fn to_status_kind(&self) -> Kind {
+        Kind::StructValue(self.clone())
+    }
+}
+
+impl<T: StatusValue> StatusValue for Option<T> {
+    fn to_status_kind(&self) -> Kind {
+        match self {
+            Some(v) => v.to_status_kind(),
+            None => Kind::NullValue(0),
+        }
+    }
+}
+
+/// Builds the status of a component from its driver specific `fields`
+pub fn status_from_fields<'a>(
+    fields: impl IntoIterator<Item = (&'a str, Kind)>,
+) -> google::protobuf::Struct {
+    google::protobuf::Struct {
+        fields: fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), google::protobuf::Value { kind: Some(v) }))
+            .collect(),
+    }
+}
+
+/// Builds the status of a component from its driver specific `fields` and the standard
+/// envelope: `ok`, `last_error` and `last_update` (RFC 3339, omitted until the first operation)
+pub fn status_envelope<'a>(
+    health: &ComponentHealth,
+    fields: impl IntoIterator<Item = (&'a str, Kind)>,
+) -> google::protobuf::Struct {
+    let mut status = status_from_fields(fields);
+    let hm = &mut status.fields;
+    hm.insert(
+        "ok".to_string(),
+        google::protobuf::Value {
+            kind: Some(Kind::BoolValue(health.ok)),
+        },
+    );
+    hm.insert(
+        "last_error".to_string(),
+        google::protobuf::Value {
+            kind: Some(health.last_error.to_status_kind()),
+        },
+    );
+    if let Some(last_update) = health.last_update {
+        hm.insert(
+            "last_update".to_string(),
+            google::protobuf::Value {
+                kind: Some(Kind::StringValue(last_update.to_rfc3339())),
+            },
+        );
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{status_envelope, ComponentHealth};
+    use crate::google::protobuf::value::Kind;
+
+    #[test_log::test]
+    fn test_status_envelope() {
+        let mut health = ComponentHealth::new();
+        let status = status_envelope(&health, [("position", Kind::NumberValue(2.0))]);
+        assert_eq!(status.fields.len(), 3);
+        assert_eq!(status.fields["ok"].kind, Some(Kind::BoolValue(true)));
+        assert_eq!(status.fields["last_error"].kind, Some(Kind::NullValue(0)));
+
+        health.record::<(), _>(&Err("stalled"));
+        health.record::<_, &str>(&Ok(()));
+        assert!(health.is_ok());
+        assert_eq!(health.last_error(), Some("stalled"));
+        health.record::<(), _>(&Err("unplugged"));
+        let status = status_envelope(&health, []);
+        assert_eq!(status.fields["ok"].kind, Some(Kind::BoolValue(false)));
+        assert_eq!(
+            status.fields["last_error"].kind,
+            Some(Kind::StringValue("unplugged".to_string()))
+        );
+        assert!(status.fields.contains_key("last_update"));
+
+        // a component is healthy until an operation fails
+        assert!(ComponentHealth::default().is_ok());
+    }
+}
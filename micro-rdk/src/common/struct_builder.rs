@@ -0,0 +1,159 @@
+//! Builder for `google::protobuf::Struct` values, used by drivers to report readings without
+//! going through intermediate `HashMap::from` arrays for every nested struct, e.g.
+//! `StructBuilder::with_capacity(2).field("lat", 40.7).sub("accuracy", accuracy).build()`.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+
+use crate::google::protobuf::{value::Kind, ListValue, Struct, Value};
+
+use super::status::StatusValue;
+
+#[derive(Debug, Default)]
+pub struct StructBuilder {
+    fields: HashMap<String, Value>,
+}
+
+impl StructBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pre-sizes the struct for `capacity` fields
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            fields: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Starts from the fields of a previous struct, keeping the allocation of its map
+    pub fn reuse(mut fields: HashMap<String, Value>) -> Self {
+        fields.clear();
+        Self { fields }
+    }
+
+    /// Adds a number, boolean or string field
+    pub fn field<V: StatusValue>(self, key: &str, value: V) -> Self {
+        self.value(
+            key,
+            Value {
+                kind: Some(value.to_status_kind()),
+            },
+        )
+    }
+
+    /// Adds an already built value
+    pub fn value(mut self, key: &str, value: Value) -> Self {
+        self.fields.insert(key.to_string(), value);
+        self
+    }
+
+    /// Adds a nested struct
+    pub fn sub(self, key: &str, sub: StructBuilder) -> Self {
+        self.value(key, sub.into())
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn build(self) -> Struct {
+        Struct {
+            fields: self.fields,
+        }
+    }
+
+    /// Returns the fields alone, e.g. as the result of `get_generic_readings`
+    pub fn into_fields(self) -> HashMap<String, Value> {
+        self.fields
+    }
+}
+
+impl From<StructBuilder> for Value {
+    fn from(builder: StructBuilder) -> Self {
+        Value {
+            kind: Some(Kind::StructValue(builder.build())),
+        }
+    }
+}
+
+impl From<StructBuilder> for Struct {
+    fn from(builder: StructBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Converts JSON, e.g. read from a file, to a protobuf value
+pub(crate) fn json_to_value(json: serde_json::Value) -> Value {
+    let kind = match json {
+        serde_json::Value::Null => Kind::NullValue(0),
+        serde_json::Value::Bool(v) => Kind::BoolValue(v),
+        serde_json::Value::Number(v) => Kind::NumberValue(v.as_f64().unwrap_or_default()),
+        serde_json::Value::String(v) => Kind::StringValue(v),
+        serde_json::Value::Array(v) => Kind::ListValue(ListValue {
+            values: v.into_iter().map(json_to_value).collect(),
+        }),
+        serde_json::Value::Object(v) => Kind::StructValue(Struct {
+            fields: v.into_iter().map(|(k, v)| (k, json_to_value(v))).collect(),
+        }),
+    };
+    Value { kind: Some(kind) }
+}
+
+/// Converts a protobuf value to JSON, e.g. to publish readings outside of app
+pub(crate) fn value_to_json(value: &Value) -> serde_json::Value {
+    match &value.kind {
+        None | Some(Kind::NullValue(_)) => serde_json::Value::Null,
+        Some(Kind::BoolValue(v)) => json!(v),
+        Some(Kind::NumberValue(v)) => json!(v),
+        Some(Kind::StringValue(v)) => json!(v),
+        Some(Kind::ListValue(v)) => v.values.iter().map(value_to_json).collect(),
+        Some(Kind::StructValue(v)) => serde_json::Value::Object(
+            v.fields
+                .iter()
+                .map(|(k, v)| (k.clone(), value_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StructBuilder;
+    use crate::google::protobuf::value::Kind;
+
+    #[test_log::test]
+    fn test_struct_builder() {
+        let built = StructBuilder::with_capacity(3)
+            .field("volts", 3.3)
+            .field("is_ac", false)
+            .sub("position", StructBuilder::new().field("x", 1_i32))
+            .build();
+        assert_eq!(
+            built.fields.get("volts").unwrap().kind,
+            Some(Kind::NumberValue(3.3))
+        );
+        assert_eq!(
+            built.fields.get("is_ac").unwrap().kind,
+            Some(Kind::BoolValue(false))
+        );
+        match &built.fields.get("position").unwrap().kind {
+            Some(Kind::StructValue(position)) => assert_eq!(
+                position.fields.get("x").unwrap().kind,
+                Some(Kind::NumberValue(1.0))
+            ),
+            _ => panic!("position should be a struct"),
+        }
+
+        let fields = built.fields;
+        let capacity = fields.capacity();
+        let reused = StructBuilder::reuse(fields);
+        assert!(reused.is_empty());
+        assert!(reused.into_fields().capacity() >= capacity);
+    }
+}
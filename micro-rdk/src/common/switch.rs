@@ -0,0 +1,225 @@
+//! Switches are components set to one of a fixed number of discrete positions, e.g. a relay
+//! (off/on) or a multi-position selector, without going through the pin APIs of the board.
+//! See [crate::common::gpio_switch] for relays driven by GPIO pins.
+
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+use super::{
+    board::BoardError,
+    config::{AttributeError, ConfigType},
+    generic::DoCommand,
+    grpc::{GrpcError, GrpcStatusHint},
+    registry::{ComponentRegistry, Dependency},
+    status::{Status, StatusError},
+    struct_builder::StructBuilder,
+};
+use crate::google::protobuf::Struct;
+
+pub static COMPONENT_NAME: &str = "switch";
+
+#[derive(Debug, Error)]
+pub enum SwitchError {
+    #[error(transparent)]
+    SwitchBoardError(#[from] BoardError),
+    #[error("config error {0}")]
+    SwitchConfigurationError(&'static str),
+    #[error(transparent)]
+    SwitchConfigAttributeError(#[from] AttributeError),
+    #[error("position {0} is out of range, the switch has {1} positions")]
+    SwitchPositionOutOfRange(u32, u32),
+}
+
+impl GrpcStatusHint for SwitchError {
+    fn grpc_error(&self) -> GrpcError {
+        match self {
+            Self::SwitchBoardError(err) => err.grpc_error(),
+            Self::SwitchConfigurationError(_) => GrpcError::RpcFailedPrecondition,
+            Self::SwitchConfigAttributeError(err) => err.grpc_error(),
+            Self::SwitchPositionOutOfRange(_, _) => GrpcError::RpcInvalidArgument,
+        }
+    }
+}
+
+pub trait Switch: Status + DoCommand {
+    /// Sets the switch to `position`, between 0 and the number of positions excluded
+    fn set_position(&mut self, position: u32) -> Result<(), SwitchError>;
+
+    fn get_position(&mut self) -> Result<u32, SwitchError>;
+
+    /// Returns the number of positions and, optionally, one label per position
+    fn get_number_of_positions(&mut self) -> Result<(u32, Vec<String>), SwitchError>;
+}
+
+pub type SwitchType = Arc<Mutex<dyn Switch>>;
+
+impl<L> Switch for Mutex<L>
+where
+    L: ?Sized + Switch,
+{
+    fn set_position(&mut self, position: u32) -> Result<(), SwitchError> {
+        self.get_mut().unwrap().set_position(position)
+    }
+    fn get_position(&mut self) -> Result<u32, SwitchError> {
+        self.get_mut().unwrap().get_position()
+    }
+    fn get_number_of_positions(&mut self) -> Result<(u32, Vec<String>), SwitchError> {
+        self.get_mut().unwrap().get_number_of_positions()
+    }
+}
+
+impl<A> Switch for Arc<Mutex<A>>
+where
+    A: ?Sized + Switch,
+{
+    fn set_position(&mut self, position: u32) -> Result<(), SwitchError> {
+        self.lock().unwrap().set_position(position)
+    }
+    fn get_position(&mut self) -> Result<u32, SwitchError> {
+        self.lock().unwrap().get_position()
+    }
+    fn get_number_of_positions(&mut self) -> Result<(u32, Vec<String>), SwitchError> {
+        self.lock().unwrap().get_number_of_positions()
+    }
+}
+
+/// Reads the optional `labels` attribute, which should have one label per position
+pub(crate) fn labels_from_config(
+    cfg: &ConfigType,
+    positions: u32,
+) -> Result<Vec<String>, SwitchError> {
+    match cfg.get_attribute::<Vec<String>>("labels") {
+        Ok(labels) if labels.len() != positions as usize => Err(
+            SwitchError::SwitchConfigurationError("labels should have one label per position"),
+        ),
+        Ok(labels) => Ok(labels),
+        Err(AttributeError::KeyNotFound(_)) => Ok(vec![]),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_switch("fake", &FakeSwitch::from_config)
+        .is_err()
+    {
+        log::error!("fake switch type is already registered");
+    }
+}
+
+#[derive(DoCommand)]
+pub struct FakeSwitch {
+    position: u32,
+    positions: u32,
+    labels: Vec<String>,
+}
+
+impl FakeSwitch {
+    pub fn new(positions: u32) -> Self {
+        Self {
+            position: 0,
+            positions,
+            labels: vec![],
+        }
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        _: Vec<Dependency>,
+    ) -> Result<SwitchType, SwitchError> {
+        let positions = cfg.get_attribute::<u32>("positions").unwrap_or(2);
+        if positions < 2 {
+            return Err(SwitchError::SwitchConfigurationError(
+                "a switch has at least 2 positions",
+            ));
+        }
+        let mut switch = Self::new(positions);
+        switch.labels = labels_from_config(&cfg, positions)?;
+        Ok(Arc::new(Mutex::new(switch)))
+    }
+}
+
+impl Switch for FakeSwitch {
+    fn set_position(&mut self, position: u32) -> Result<(), SwitchError> {
+        if position >= self.positions {
+            return Err(SwitchError::SwitchPositionOutOfRange(
+                position,
+                self.positions,
+            ));
+        }
+        self.position = position;
+        Ok(())
+    }
+    fn get_position(&mut self) -> Result<u32, SwitchError> {
+        Ok(self.position)
+    }
+    fn get_number_of_positions(&mut self) -> Result<(u32, Vec<String>), SwitchError> {
+        Ok((self.positions, self.labels.clone()))
+    }
+}
+
+impl Status for FakeSwitch {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(
+            StructBuilder::with_capacity(1)
+                .field("position", self.position)
+                .build(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::common::config::{ConfigType, DynamicComponentConfig, Kind};
+    use crate::common::switch::{FakeSwitch, Switch, SwitchError};
+
+    #[test_log::test]
+    fn test_fake_switch() -> Result<(), SwitchError> {
+        let config = |labels: Vec<&str>| DynamicComponentConfig {
+            name: "selector".to_owned(),
+            namespace: "rdk".to_owned(),
+            r#type: "switch".to_owned(),
+            model: "fake".to_owned(),
+            attributes: Some(HashMap::from([
+                ("positions".to_owned(), Kind::NumberValue(3f64)),
+                (
+                    "labels".to_owned(),
+                    Kind::VecValue(
+                        labels
+                            .into_iter()
+                            .map(|l| Kind::StringValue(l.to_owned()))
+                            .collect(),
+                    ),
+                ),
+            ])),
+            ..Default::default()
+        };
+
+        let cfg = config(vec!["off", "low", "high"]);
+        let switch = FakeSwitch::from_config(ConfigType::Dynamic(&cfg), vec![])?;
+        let mut switch = switch.lock().unwrap();
+        assert_eq!(
+            switch.get_number_of_positions()?,
+            (
+                3,
+                vec!["off".to_owned(), "low".to_owned(), "high".to_owned()]
+            )
+        );
+        switch.set_position(2)?;
+        assert_eq!(switch.get_position()?, 2);
+        assert!(matches!(
+            switch.set_position(3),
+            Err(SwitchError::SwitchPositionOutOfRange(3, 3))
+        ));
+
+        let cfg = config(vec!["off", "on"]);
+        assert!(matches!(
+            FakeSwitch::from_config(ConfigType::Dynamic(&cfg), vec![]),
+            Err(SwitchError::SwitchConfigurationError(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Runtime information about the device (power profile, connection statistics...) recorded by
+//! the rest of micro-rdk with [record_telemetry] and reported by the builtin `telemetry` sensor.
+//!
+//! ```json
+//! {
+//!     "name": "telemetry",
+//!     "model": "telemetry",
+//!     "type": "sensor",
+//!     "attributes": {}
+//! }
+//! ```
+
+use crate::google;
+
+use super::status::StatusValue;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[cfg(feature = "builtin-components")]
+use {
+    super::config::ConfigType,
+    super::registry::{ComponentRegistry, Dependency},
+    super::sensor::{GenericReadingsResult, Readings, Sensor, SensorError, SensorType},
+    std::sync::Arc,
+};
+
+static TELEMETRY: Mutex<BTreeMap<String, google::protobuf::Value>> = Mutex::new(BTreeMap::new());
+
+/// Records the latest value of a telemetry entry, replacing the previous one
+pub fn record_telemetry<V: StatusValue>(key: &str, value: V) {
+    TELEMETRY.lock().unwrap().insert(
+        key.to_string(),
+        google::protobuf::Value {
+            kind: Some(value.to_status_kind()),
+        },
+    );
+}
+
+/// Returns the value of a telemetry entry if it was recorded
+pub fn get_telemetry(key: &str) -> Option<google::protobuf::Value> {
+    TELEMETRY.lock().unwrap().get(key).cloned()
+}
+
+/// Numeric and boolean telemetry entries as numbers, booleans are 0 or 1
+pub(crate) fn numeric_telemetry() -> Vec<(String, f64)> {
+    TELEMETRY
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|(k, v)| match v.kind {
+            Some(google::protobuf::value::Kind::NumberValue(n)) => Some((k.clone(), n)),
+            Some(google::protobuf::value::Kind::BoolValue(b)) => {
+                Some((k.clone(), if b { 1.0 } else { 0.0 }))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(feature = "builtin-components")]
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("telemetry", &TelemetrySensor::from_config)
+        .is_err()
+    {
+        log::error!("telemetry sensor type is already registered");
+    }
+}
+
+/// Reports every recorded telemetry entry as a reading
+#[cfg(feature = "builtin-components")]
+#[derive(DoCommand, Status)]
+pub struct TelemetrySensor;
+
+#[cfg(feature = "builtin-components")]
+impl TelemetrySensor {
+    pub(crate) fn from_config(
+        _: ConfigType,
+        _: Vec<Dependency>,
+    ) -> Result<SensorType, SensorError> {
+        Ok(Arc::new(Mutex::new(TelemetrySensor)))
+    }
+}
+
+#[cfg(feature = "builtin-components")]
+impl Sensor for TelemetrySensor {}
+
+#[cfg(feature = "builtin-components")]
+impl Readings for TelemetrySensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        Ok(TELEMETRY
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google::protobuf::value::Kind;
+
+    #[test_log::test]
+    fn test_telemetry() {
+        record_telemetry("test_telemetry_entry", 80_u32);
+        record_telemetry("test_telemetry_entry", 160_u32);
+        assert_eq!(
+            get_telemetry("test_telemetry_entry").unwrap().kind,
+            Some(Kind::NumberValue(160.0))
+        );
+        assert!(get_telemetry("test_telemetry_missing").is_none());
+
+        let readings = TelemetrySensor.get_generic_readings().unwrap();
+        assert_eq!(
+            readings.get("test_telemetry_entry").unwrap().kind,
+            Some(Kind::NumberValue(160.0))
+        );
+    }
+}
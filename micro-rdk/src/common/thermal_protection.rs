@@ -0,0 +1,479 @@
+//! Package thermal_protection implements a motor supervising another motor with the readings
+//! of a temperature sensor. When the temperature reaches `derate_celsius` the power (or speed)
+//! of the motor is scaled down by `derate_factor`, and when it reaches `stop_celsius` the motor
+//! is stopped and refuses commands. Normal operation, including the last power set on the motor,
+//! is restored once the temperature has cooled down to `resume_celsius`.
+//!
+//! ```json
+//! {
+//!     "motor": "left",
+//!     "temperature_sensor": "left_temp",
+//!     "reading_key": "temperature_celsius",
+//!     "derate_celsius": 60,
+//!     "stop_celsius": 80,
+//!     "resume_celsius": 50,
+//!     "derate_factor": 0.5
+//! }
+//! ```
+//!
+//! The temperature is read from the `reading_key` reading of the sensor every second, whether or
+//! not a client is using the motor, and every time the motor is used. State changes are logged
+//! and reported, along with the last temperature, in the status of the motor.
+
+use crate::common::status::{Status, StatusError};
+use crate::google;
+
+use super::actuator::{Actuator, ActuatorError};
+use super::config::ConfigType;
+use super::motor::{
+    Motor, MotorError, MotorSupportedProperties, MotorType, COMPONENT_NAME as MotorCompName,
+};
+use super::periodic::spawn_periodic;
+use super::registry::{ComponentRegistry, Dependency, ResourceKey};
+use super::robot::Resource;
+use super::sensor::{Readings, SensorType, COMPONENT_NAME as SensorCompName};
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_motor(
+            "thermal_protected",
+            &ThermalProtectedMotor::<MotorType, SensorType>::from_config,
+        )
+        .is_err()
+    {
+        log::error!("thermal_protected type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            MotorCompName,
+            "thermal_protected",
+            &ThermalProtectedMotor::<MotorType, SensorType>::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("failed to register dependency getter for thermal_protected model")
+    }
+}
+
+const DEFAULT_READING_KEY: &str = "temperature_celsius";
+const DEFAULT_DERATE_FACTOR: f64 = 0.5;
+// default gap between the lowest threshold and resume_celsius
+const DEFAULT_COOLDOWN_CELSIUS: f64 = 10.0;
+// period at which the temperature is read when the motor isn't used
+const SUPERVISE_PERIOD: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThermalState {
+    Normal,
+    Derated,
+    Stopped,
+}
+
+impl ThermalState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Derated => "derated",
+            Self::Stopped => "stopped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermalThresholds {
+    pub derate_celsius: Option<f64>,
+    pub stop_celsius: f64,
+    pub resume_celsius: f64,
+}
+
+impl ThermalThresholds {
+    fn validate(&self) -> Result<(), MotorError> {
+        let derate = self.derate_celsius.unwrap_or(self.stop_celsius);
+        if !(self.resume_celsius < derate && derate <= self.stop_celsius) {
+            return Err(MotorError::ConfigError(
+                "thermal protection requires resume_celsius < derate_celsius <= stop_celsius",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the state of the motor after measuring `celsius` in state `state`
+    pub(crate) fn next_state(&self, state: ThermalState, celsius: f64) -> ThermalState {
+        let derated = self
+            .derate_celsius
+            .map_or(false, |derate| celsius >= derate);
+        match state {
+            _ if celsius >= self.stop_celsius => ThermalState::Stopped,
+            ThermalState::Normal if derated => ThermalState::Derated,
+            ThermalState::Derated | ThermalState::Stopped if celsius <= self.resume_celsius => {
+                ThermalState::Normal
+            }
+            state => state,
+        }
+    }
+}
+
+#[derive(DoCommand)]
+pub struct ThermalProtectedMotor<M, S> {
+    motor: M,
+    sensor: S,
+    reading_key: String,
+    thresholds: ThermalThresholds,
+    derate_factor: f64,
+    state: ThermalState,
+    last_celsius: Option<f64>,
+    // power requested through set_power, re-applied when the state changes
+    requested_power: f64,
+    thermal_events: u32,
+}
+
+impl<M, S> ThermalProtectedMotor<M, S>
+where
+    M: Motor,
+    S: Readings,
+{
+    pub fn new(
+        motor: M,
+        sensor: S,
+        reading_key: String,
+        thresholds: ThermalThresholds,
+        derate_factor: f64,
+    ) -> Result<Self, MotorError> {
+        thresholds.validate()?;
+        if !(0.0..=1.0).contains(&derate_factor) {
+            return Err(MotorError::ConfigError(
+                "thermal protection derate_factor must be between 0 and 1",
+            ));
+        }
+        Ok(Self {
+            motor,
+            sensor,
+            reading_key,
+            thresholds,
+            derate_factor,
+            state: ThermalState::Normal,
+            last_celsius: None,
+            requested_power: 0.0,
+            thermal_events: 0,
+        })
+    }
+
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<MotorType, MotorError> {
+        let motor_name = cfg
+            .get_attribute::<String>("motor")
+            .map_err(|_| MotorError::ConfigError("thermal protection missing motor"))?;
+        let sensor_name = cfg
+            .get_attribute::<String>("temperature_sensor")
+            .map_err(|_| {
+                MotorError::ConfigError("thermal protection missing temperature_sensor")
+            })?;
+        let mut motor: Option<MotorType> = None;
+        let mut sensor: Option<SensorType> = None;
+        for Dependency(key, res) in deps {
+            match res {
+                Resource::Motor(found) if key.1 == motor_name => motor = Some(found),
+                Resource::Sensor(found) if key.1 == sensor_name => sensor = Some(found),
+                _ => {}
+            }
+        }
+        let motor = motor.ok_or(MotorError::ConfigError(
+            "thermal protection motor couldn't be found",
+        ))?;
+        let sensor = sensor.ok_or(MotorError::ConfigError(
+            "thermal protection temperature sensor couldn't be found",
+        ))?;
+        let reading_key = cfg
+            .get_attribute::<String>("reading_key")
+            .unwrap_or_else(|_| DEFAULT_READING_KEY.to_string());
+        let stop_celsius = cfg
+            .get_attribute::<f64>("stop_celsius")
+            .map_err(|_| MotorError::ConfigError("thermal protection missing stop_celsius"))?;
+        let derate_celsius = cfg.get_attribute::<f64>("derate_celsius").ok();
+        let resume_celsius = cfg.get_attribute::<f64>("resume_celsius").unwrap_or(
+            derate_celsius.unwrap_or(stop_celsius).min(stop_celsius) - DEFAULT_COOLDOWN_CELSIUS,
+        );
+        let derate_factor = cfg
+            .get_attribute::<f64>("derate_factor")
+            .unwrap_or(DEFAULT_DERATE_FACTOR);
+        let motor = Arc::new(Mutex::new(ThermalProtectedMotor::new(
+            motor,
+            sensor,
+            reading_key,
+            ThermalThresholds {
+                derate_celsius,
+                stop_celsius,
+                resume_celsius,
+            },
+            derate_factor,
+        )?));
+        spawn_periodic(&motor, SUPERVISE_PERIOD, |motor| {
+            if let Err(err) = motor.supervise() {
+                log::error!("thermal protection couldn't read temperature: {:?}", err);
+            }
+        });
+        Ok(motor)
+    }
+
+    pub(crate) fn dependencies_from_config(cfg: ConfigType) -> Vec<ResourceKey> {
+        let mut r_keys = Vec::new();
+        if let Ok(motor_name) = cfg.get_attribute::<String>("motor") {
+            r_keys.push(ResourceKey(MotorCompName, motor_name));
+        }
+        if let Ok(sensor_name) = cfg.get_attribute::<String>("temperature_sensor") {
+            r_keys.push(ResourceKey(SensorCompName, sensor_name));
+        }
+        r_keys
+    }
+
+    fn read_celsius(&mut self) -> Result<f64, MotorError> {
+        let readings = self.sensor.get_generic_readings()?;
+        match readings
+            .get(&self.reading_key)
+            .and_then(|v| v.kind.as_ref())
+        {
+            Some(google::protobuf::value::Kind::NumberValue(celsius)) => Ok(*celsius),
+            _ => Err(MotorError::ConfigError(
+                "thermal protection sensor doesn't report the configured reading_key",
+            )),
+        }
+    }
+
+    fn scale(&self) -> f64 {
+        match self.state {
+            ThermalState::Normal => 1.0,
+            ThermalState::Derated => self.derate_factor,
+            ThermalState::Stopped => 0.0,
+        }
+    }
+
+    // reads the temperature and applies the resulting state to the motor
+    fn supervise(&mut self) -> Result<(), MotorError> {
+        let celsius = self.read_celsius()?;
+        self.last_celsius = Some(celsius);
+        let next = self.thresholds.next_state(self.state, celsius);
+        if next == self.state {
+            return Ok(());
+        }
+        match next {
+            ThermalState::Normal => log::info!("motor cooled down to {celsius}°C, resuming"),
+            ThermalState::Derated => log::warn!("motor at {celsius}°C, derating"),
+            ThermalState::Stopped => log::error!("motor at {celsius}°C, stopping"),
+        }
+        if next != ThermalState::Normal {
+            self.thermal_events += 1;
+        }
+        self.state = next;
+        if self.state == ThermalState::Stopped {
+            self.motor.stop()?;
+        } else if self.requested_power != 0.0 {
+            self.motor.set_power(self.requested_power * self.scale())?;
+        }
+        Ok(())
+    }
+
+    fn check_not_stopped(&self) -> Result<(), MotorError> {
+        if self.state == ThermalState::Stopped {
+            return Err(MotorError::MotorThermalShutdown(
+                self.last_celsius.unwrap_or(self.thresholds.stop_celsius),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<M, S> Motor for ThermalProtectedMotor<M, S>
+where
+    M: Motor,
+    S: Readings,
+{
+    fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        self.set_power_with_extra(pct, None)
+    }
+    fn set_power_with_extra(
+        &mut self,
+        pct: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), MotorError> {
+        self.supervise()?;
+        if pct != 0.0 {
+            self.check_not_stopped()?;
+        }
+        self.requested_power = pct;
+        self.motor.set_power_with_extra(pct * self.scale(), extra)
+    }
+    fn get_position(&mut self) -> Result<i32, MotorError> {
+        self.supervise()?;
+        self.motor.get_position()
+    }
+    fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        self.go_for_with_extra(rpm, revolutions, None)
+    }
+    fn go_for_with_extra(
+        &mut self,
+        rpm: f64,
+        revolutions: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<Option<Duration>, MotorError> {
+        self.supervise()?;
+        self.check_not_stopped()?;
+        self.requested_power = 0.0;
+        self.motor
+            .go_for_with_extra(rpm * self.scale(), revolutions, extra)
+    }
+    fn get_properties(&mut self) -> MotorSupportedProperties {
+        self.motor.get_properties()
+    }
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        self.supervise()?;
+        self.motor.is_powered()
+    }
+}
+
+impl<M, S> Actuator for ThermalProtectedMotor<M, S>
+where
+    M: Motor,
+    S: Readings,
+{
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        if let Err(err) = self.supervise() {
+            log::error!("thermal protection couldn't read temperature: {:?}", err);
+        }
+        self.motor.is_moving()
+    }
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        self.stop_with_extra(None)
+    }
+    fn stop_with_extra(
+        &mut self,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), ActuatorError> {
+        self.requested_power = 0.0;
+        self.motor.stop_with_extra(extra)
+    }
+}
+
+impl<M, S> Status for ThermalProtectedMotor<M, S>
+where
+    M: Motor,
+    S: Readings,
+{
+    fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
+        let mut status = self.motor.get_status()?.unwrap_or_default();
+        status.fields.insert(
+            "thermal_state".to_string(),
+            google::protobuf::Value {
+                kind: Some(google::protobuf::value::Kind::StringValue(
+                    self.state.as_str().to_string(),
+                )),
+            },
+        );
+        status.fields.insert(
+            "thermal_events".to_string(),
+            google::protobuf::Value {
+                kind: Some(google::protobuf::value::Kind::NumberValue(
+                    self.thermal_events as f64,
+                )),
+            },
+        );
+        if let Some(celsius) = self.last_celsius {
+            status.fields.insert(
+                "temperature_celsius".to_string(),
+                google::protobuf::Value {
+                    kind: Some(google::protobuf::value::Kind::NumberValue(celsius)),
+                },
+            );
+        }
+        Ok(Some(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ThermalProtectedMotor, ThermalState, ThermalThresholds};
+    use crate::common::motor::{FakeMotor, Motor, MotorError};
+    use crate::common::sensor::{GenericReadingsResult, Readings, SensorError, SensorResult};
+    use std::sync::{Arc, Mutex};
+
+    struct FakeTemperatureSensor {
+        celsius: Arc<Mutex<f64>>,
+    }
+
+    impl Readings for FakeTemperatureSensor {
+        fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+            let value = *self.celsius.lock().unwrap();
+            Ok(GenericReadingsResult::from([(
+                "temperature_celsius".to_string(),
+                SensorResult::<f64> { value }.into(),
+            )]))
+        }
+    }
+
+    const THRESHOLDS: ThermalThresholds = ThermalThresholds {
+        derate_celsius: Some(60.0),
+        stop_celsius: 80.0,
+        resume_celsius: 50.0,
+    };
+
+    #[test_log::test]
+    fn test_next_state() {
+        use ThermalState::*;
+        assert_eq!(THRESHOLDS.next_state(Normal, 59.0), Normal);
+        assert_eq!(THRESHOLDS.next_state(Normal, 60.0), Derated);
+        assert_eq!(THRESHOLDS.next_state(Normal, 85.0), Stopped);
+        assert_eq!(THRESHOLDS.next_state(Derated, 55.0), Derated);
+        assert_eq!(THRESHOLDS.next_state(Derated, 50.0), Normal);
+        assert_eq!(THRESHOLDS.next_state(Stopped, 70.0), Stopped);
+        assert_eq!(THRESHOLDS.next_state(Stopped, 45.0), Normal);
+        assert!(ThermalThresholds {
+            derate_celsius: Some(90.0),
+            ..THRESHOLDS
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test_log::test]
+    fn test_thermal_protection() -> Result<(), MotorError> {
+        let celsius = Arc::new(Mutex::new(25.0));
+        let inner = Arc::new(Mutex::new(FakeMotor::new()));
+        let sensor = FakeTemperatureSensor {
+            celsius: celsius.clone(),
+        };
+        let mut motor = ThermalProtectedMotor::new(
+            inner.clone(),
+            sensor,
+            "temperature_celsius".to_string(),
+            THRESHOLDS,
+            0.5,
+        )?;
+
+        motor.set_power(0.8)?;
+        assert_eq!(inner.lock().unwrap().is_powered()?, (true, 0.8));
+
+        *celsius.lock().unwrap() = 65.0;
+        motor.is_powered()?;
+        assert_eq!(inner.lock().unwrap().is_powered()?, (true, 0.4));
+
+        *celsius.lock().unwrap() = 82.0;
+        motor.is_powered()?;
+        assert_eq!(inner.lock().unwrap().is_powered()?, (false, 0.0));
+        assert!(matches!(
+            motor.set_power(0.5),
+            Err(MotorError::MotorThermalShutdown(_))
+        ));
+        assert_eq!(motor.thermal_events, 2);
+
+        // the last requested power is restored after cooling down
+        *celsius.lock().unwrap() = 45.0;
+        motor.is_powered()?;
+        assert_eq!(motor.state, ThermalState::Normal);
+        assert_eq!(inner.lock().unwrap().is_powered()?, (true, 0.8));
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+//! Package uart implements a serial port shared by the components talking to devices over it,
+//! such as motor controllers addressing several motors on a single line.
+//!
+//! The platform provides the [Uart] (see `esp32::uart`), components hold the port for the whole
+//! exchange with their device by locking the [UartType].
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use thiserror::Error;
+
+use super::board::BoardError;
+use super::motor::MotorError;
+use super::sensor::SensorError;
+use super::servo::ServoError;
+
+#[derive(Error, Debug)]
+pub enum UartError {
+    #[error("no reply received within {0:?}")]
+    Timeout(Duration),
+    #[error("uart driver error {0}")]
+    DriverError(i32),
+    #[error("uart config error: {0}")]
+    ConfigError(&'static str),
+    #[error("invalid reply: {0}")]
+    InvalidReply(&'static str),
+}
+
+impl From<UartError> for MotorError {
+    fn from(err: UartError) -> Self {
+        MotorError::BoardError(BoardError::OtherBoardError(Box::new(err)))
+    }
+}
+
+impl From<UartError> for ServoError {
+    fn from(err: UartError) -> Self {
+        ServoError::ServoBoardError(BoardError::OtherBoardError(Box::new(err)))
+    }
+}
+
+impl From<UartError> for SensorError {
+    fn from(err: UartError) -> Self {
+        SensorError::SensorBoardError(BoardError::OtherBoardError(Box::new(err)))
+    }
+}
+
+pub trait Uart: Send {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), UartError>;
+    /// Fills `buf` with the bytes received, failing when they don't arrive within `timeout`
+    fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), UartError>;
+    /// Reads the bytes already received without waiting, up to the length of `buf`, and
+    /// returns how many were read
+    fn read_available(&mut self, buf: &mut [u8]) -> Result<usize, UartError>;
+    /// Discards the bytes received so far, such as the leftovers of a failed exchange
+    fn clear_input(&mut self) -> Result<(), UartError>;
+}
+
+pub type UartType = Arc<Mutex<dyn Uart>>;
+
+/// A port recording the bytes written and replying with the bytes queued in `replies`
+#[derive(Default)]
+pub struct FakeUart {
+    pub written: Vec<u8>,
+    pub replies: VecDeque<u8>,
+}
+
+impl Uart for FakeUart {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), UartError> {
+        self.written.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), UartError> {
+        if self.replies.len() < buf.len() {
+            return Err(UartError::Timeout(timeout));
+        }
+        for byte in buf.iter_mut() {
+            *byte = self.replies.pop_front().unwrap();
+        }
+        Ok(())
+    }
+
+    fn read_available(&mut self, buf: &mut [u8]) -> Result<usize, UartError> {
+        let len = buf.len().min(self.replies.len());
+        for byte in buf[..len].iter_mut() {
+            *byte = self.replies.pop_front().unwrap();
+        }
+        Ok(len)
+    }
+
+    fn clear_input(&mut self) -> Result<(), UartError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,421 @@
+//! Package vesc_can implements a motor driven by a VESC speed controller over CAN.
+//!
+//! The platform builds the [CanBus] the controllers are connected to, the `vesc-can` model is
+//! registered on the ESP32, see `esp32::vesc_can` for the bus configuration.
+//!
+//! ```json
+//! {
+//!     "controller_id": 12,
+//!     "pole_pairs": 7,
+//!     "max_rpm": 3000,
+//!     "stop_mode": "brake",
+//!     "brake_current_a": 8
+//! }
+//! ```
+//!
+//! `controller_id` is the CAN ID set in the app settings of the VESC, several controllers can
+//! share a bus. `set_power` sets the duty cycle of the controller and `go_for` its speed, the
+//! electrical RPM sent being the requested RPM times `pole_pairs` (1 by default). `max_rpm`
+//! (required) caps the speed of `go_for`. Stopping releases the motor (`coast`, the default) or
+//! brakes it with `brake_current_a` (5A by default).
+//!
+//! The position and status are read from the status messages the VESC broadcasts, which must
+//! be enabled in its app settings (`STATUS_1`, `STATUS_4` and `STATUS_5`), by a task draining
+//! the bus every 50ms. The position is the
+//! tachometer of the controller, which counts `6 * pole_pairs` ticks per rotation. The status
+//! reports the `rpm`, `current_a`, `duty`, `temp_fet_c`, `temp_motor_c` and `input_voltage`
+//! last broadcast.
+//!
+//! The VESC stops the motor when it doesn't receive a command within the timeout of its app
+//! settings, the same task sends the last command again every 50ms while the motor runs so the
+//! timeout can stay enabled (above 50ms).
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::actuator::{Actuator, ActuatorError};
+use super::can::{CanBusType, CanError, CanFrame};
+use super::config::{AttributeError, ConfigType};
+use super::math_utils::go_for_math;
+use super::motor::{Motor, MotorError, MotorSupportedProperties, MotorType, StopMode};
+use super::periodic::spawn_periodic;
+use super::status::{status_envelope, ComponentHealth, Status, StatusError};
+use crate::google::protobuf::{value::Kind, Struct};
+
+// command identifiers of the VESC CAN protocol, sent as `command << 8 | controller_id`
+const CAN_PACKET_SET_DUTY: u32 = 0;
+const CAN_PACKET_SET_CURRENT: u32 = 1;
+const CAN_PACKET_SET_CURRENT_BRAKE: u32 = 2;
+const CAN_PACKET_SET_RPM: u32 = 3;
+const CAN_PACKET_STATUS: u32 = 9;
+const CAN_PACKET_STATUS_4: u32 = 16;
+const CAN_PACKET_STATUS_5: u32 = 27;
+
+const DEFAULT_BRAKE_CURRENT_A: f64 = 5.0;
+// the tachometer counts the 6 commutation steps of each electrical revolution
+const TACHO_STEPS_PER_POLE_PAIR: u32 = 6;
+// the status is read and the last command sent again this often
+const KEEPALIVE_PERIOD: Duration = Duration::from_millis(50);
+
+fn packet_id(command: u32, controller_id: u8) -> u32 {
+    (command << 8) | controller_id as u32
+}
+
+fn be_i16(data: &[u8], offset: usize) -> Option<f64> {
+    Some(i16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?) as f64)
+}
+
+fn be_i32(data: &[u8], offset: usize) -> Option<i32> {
+    Some(i32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// State of the controller decoded from its status messages
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VescStatus {
+    pub erpm: i32,
+    pub current_a: f64,
+    pub duty: f64,
+    pub temp_fet_c: f64,
+    pub temp_motor_c: f64,
+    pub tachometer: i32,
+    pub input_voltage: f64,
+}
+
+impl VescStatus {
+    // each message carries part of the status, the fields of malformed messages are left as is
+    fn update(&mut self, command: u32, data: &[u8]) {
+        match command {
+            CAN_PACKET_STATUS => {
+                if let (Some(erpm), Some(current), Some(duty)) =
+                    (be_i32(data, 0), be_i16(data, 4), be_i16(data, 6))
+                {
+                    self.erpm = erpm;
+                    self.current_a = current / 10.0;
+                    self.duty = duty / 1000.0;
+                }
+            }
+            CAN_PACKET_STATUS_4 => {
+                if let (Some(temp_fet), Some(temp_motor)) = (be_i16(data, 0), be_i16(data, 2)) {
+                    self.temp_fet_c = temp_fet / 10.0;
+                    self.temp_motor_c = temp_motor / 10.0;
+                }
+            }
+            CAN_PACKET_STATUS_5 => {
+                if let (Some(tachometer), Some(voltage)) = (be_i32(data, 0), be_i16(data, 4)) {
+                    self.tachometer = tachometer;
+                    self.input_voltage = voltage / 10.0;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(DoCommand)]
+pub struct VescCanMotor {
+    bus: CanBusType,
+    controller_id: u8,
+    pole_pairs: u32,
+    max_rpm: f64,
+    stop_mode: StopMode,
+    brake_current_a: f64,
+    power: f64,
+    // command and value keeping the motor running
+    running: Option<(u32, i32)>,
+    status: VescStatus,
+    health: ComponentHealth,
+}
+
+impl VescCanMotor {
+    pub fn new(bus: CanBusType, controller_id: u8, pole_pairs: u32, max_rpm: f64) -> Self {
+        Self {
+            bus,
+            controller_id,
+            pole_pairs: pole_pairs.max(1),
+            max_rpm,
+            stop_mode: StopMode::Coast,
+            brake_current_a: DEFAULT_BRAKE_CURRENT_A,
+            power: 0.0,
+            running: None,
+            status: VescStatus::default(),
+            health: ComponentHealth::new(),
+        }
+    }
+
+    /// Builds the motor from the attributes of the `vesc-can` model, once the platform has
+    /// built the bus the controller is connected to
+    pub fn from_bus_and_config(bus: CanBusType, cfg: ConfigType) -> Result<MotorType, MotorError> {
+        let controller_id = cfg
+            .get_attribute::<u8>("controller_id")
+            .map_err(|_| MotorError::ConfigError("vesc-can: missing or invalid `controller_id`"))?;
+        let max_rpm = cfg
+            .get_attribute::<f64>("max_rpm")
+            .map_err(|_| MotorError::ConfigError("vesc-can: missing or invalid `max_rpm`"))?;
+        if max_rpm <= 0.0 {
+            return Err(MotorError::ConfigError(
+                "vesc-can: `max_rpm` must be positive",
+            ));
+        }
+        let pole_pairs = match cfg.get_attribute::<u32>("pole_pairs") {
+            Ok(pole_pairs) if pole_pairs > 0 => pole_pairs,
+            Err(AttributeError::KeyNotFound(_)) => 1,
+            _ => {
+                return Err(MotorError::ConfigError(
+                    "vesc-can: `pole_pairs` must be a positive integer",
+                ))
+            }
+        };
+        let mut motor = Self::new(bus, controller_id, pole_pairs, max_rpm);
+        motor.stop_mode = match cfg.get_attribute::<StopMode>("stop_mode") {
+            Ok(mode) => mode,
+            Err(AttributeError::KeyNotFound(_)) => StopMode::Coast,
+            Err(_) => {
+                return Err(MotorError::ConfigError(
+                    "vesc-can: `stop_mode` must be coast or brake",
+                ))
+            }
+        };
+        if let Ok(current) = cfg.get_attribute::<f64>("brake_current_a") {
+            motor.brake_current_a = current.abs();
+        }
+        let motor = Arc::new(Mutex::new(motor));
+        spawn_periodic(&motor, KEEPALIVE_PERIOD, |motor| motor.tick());
+        Ok(motor)
+    }
+
+    /// Reads the status messages broadcast since the last tick and keeps the motor running
+    pub fn tick(&mut self) {
+        let mut res = self.update_status();
+        if let (Ok(()), Some((command, value))) = (&res, self.running) {
+            res = self.send(command, value);
+        }
+        if let Err(err) = &res {
+            log::debug!("vesc-can {}: {}", self.controller_id, err);
+        }
+        self.health.record(&res);
+    }
+
+    fn send(&mut self, command: u32, value: i32) -> Result<(), CanError> {
+        let frame =
+            CanFrame::new_extended(packet_id(command, self.controller_id), &value.to_be_bytes())?;
+        self.bus.lock().unwrap().transmit(&frame)
+    }
+
+    // reads the status messages broadcast since the last update
+    fn update_status(&mut self) -> Result<(), CanError> {
+        let mut bus = self.bus.lock().unwrap();
+        for command in [CAN_PACKET_STATUS, CAN_PACKET_STATUS_4, CAN_PACKET_STATUS_5] {
+            if let Some((frame, _)) = bus.latest_extended(packet_id(command, self.controller_id))? {
+                self.status.update(command, &frame.data);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&pct) {
+            return Err(MotorError::PowerSetError);
+        }
+        let duty = (pct * 100000.0).round() as i32;
+        self.send(CAN_PACKET_SET_DUTY, duty)?;
+        self.power = pct;
+        self.running = (duty != 0).then_some((CAN_PACKET_SET_DUTY, duty));
+        Ok(())
+    }
+
+    fn apply_rpm(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        let (pct, dur) = go_for_math(self.max_rpm, rpm, revolutions)?;
+        let erpm = (pct * self.max_rpm * self.pole_pairs as f64).round() as i32;
+        self.send(CAN_PACKET_SET_RPM, erpm)?;
+        self.power = pct;
+        self.running = (erpm != 0).then_some((CAN_PACKET_SET_RPM, erpm));
+        Ok(dur)
+    }
+
+    fn stop_with(&mut self, mode: StopMode) -> Result<(), MotorError> {
+        match mode {
+            StopMode::Coast => self.send(CAN_PACKET_SET_CURRENT, 0)?,
+            StopMode::Brake => self.send(
+                CAN_PACKET_SET_CURRENT_BRAKE,
+                (self.brake_current_a * 1000.0).round() as i32,
+            )?,
+        }
+        self.power = 0.0;
+        self.running = None;
+        Ok(())
+    }
+}
+
+impl Motor for VescCanMotor {
+    fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        let res = self.apply_power(pct);
+        self.health.record(&res);
+        res
+    }
+
+    fn get_position(&mut self) -> Result<i32, MotorError> {
+        Ok(self.status.tachometer)
+    }
+
+    fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        let res = self.apply_rpm(rpm, revolutions);
+        self.health.record(&res);
+        res
+    }
+
+    fn get_properties(&mut self) -> MotorSupportedProperties {
+        MotorSupportedProperties {
+            position_reporting: true,
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: Some(TACHO_STEPS_PER_POLE_PAIR * self.pole_pairs),
+        }
+    }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
+}
+
+impl Actuator for VescCanMotor {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(self.power != 0.0)
+    }
+
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        self.stop_with_extra(None)
+    }
+
+    fn stop_with_extra(&mut self, extra: Option<Struct>) -> Result<(), ActuatorError> {
+        let mode = match extra
+            .as_ref()
+            .and_then(|extra| extra.fields.get("mode"))
+            .and_then(|v| v.kind.as_ref())
+        {
+            None => self.stop_mode,
+            Some(mode) => StopMode::try_from(mode).unwrap_or_else(|_| {
+                log::warn!("vesc-can: ignoring invalid stop mode {:?}", mode);
+                self.stop_mode
+            }),
+        };
+        let res = self.stop_with(mode);
+        self.health.record(&res);
+        res.map_err(|_| ActuatorError::CouldntStop)
+    }
+}
+
+impl Status for VescCanMotor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(status_envelope(
+            &self.health,
+            [
+                ("position", Kind::NumberValue(self.status.tachometer as f64)),
+                ("power", Kind::NumberValue(self.power)),
+                (
+                    "rpm",
+                    Kind::NumberValue(self.status.erpm as f64 / self.pole_pairs as f64),
+                ),
+                ("current_a", Kind::NumberValue(self.status.current_a)),
+                ("duty", Kind::NumberValue(self.status.duty)),
+                ("temp_fet_c", Kind::NumberValue(self.status.temp_fet_c)),
+                ("temp_motor_c", Kind::NumberValue(self.status.temp_motor_c)),
+                (
+                    "input_voltage",
+                    Kind::NumberValue(self.status.input_voltage),
+                ),
+            ],
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VescCanMotor, CAN_PACKET_STATUS, CAN_PACKET_STATUS_5};
+    use crate::common::actuator::Actuator;
+    use crate::common::can::{CanBus, CanFrame, FakeCanDriver};
+    use crate::common::motor::{Motor, MotorError, StopMode};
+    use crate::common::status::Status;
+    use crate::google::protobuf::value::Kind;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn motor() -> (VescCanMotor, Arc<Mutex<FakeCanDriver>>) {
+        let driver = Arc::new(Mutex::new(FakeCanDriver::default()));
+        let bus = Arc::new(Mutex::new(CanBus::new(Box::new(driver.clone()))));
+        (VescCanMotor::new(bus, 12, 7, 3000.0), driver)
+    }
+
+    fn last_sent(driver: &Arc<Mutex<FakeCanDriver>>) -> (u32, i32) {
+        let frame = driver.lock().unwrap().transmitted.last().cloned().unwrap();
+        assert!(frame.extended);
+        (
+            frame.id,
+            i32::from_be_bytes(frame.data[..].try_into().unwrap()),
+        )
+    }
+
+    #[test_log::test]
+    fn test_vesc_commands() -> Result<(), MotorError> {
+        let (mut motor, driver) = motor();
+        motor.set_power(-0.5)?;
+        assert_eq!(last_sent(&driver), (12, -50000));
+        assert_eq!(motor.is_powered()?, (true, -0.5));
+        assert!(matches!(
+            motor.set_power(1.5),
+            Err(MotorError::PowerSetError)
+        ));
+
+        // 100rpm on a 7 pole pairs motor for 50 revolutions
+        let dur = motor.go_for(100.0, 50.0)?;
+        assert_eq!(dur, Some(Duration::from_secs(30)));
+        assert_eq!(last_sent(&driver), (3 << 8 | 12, 700));
+        motor.go_for(100.0, -1.0)?;
+        assert_eq!(last_sent(&driver), (3 << 8 | 12, -700));
+        // the running command is sent again until the motor stops
+        driver.lock().unwrap().transmitted.clear();
+        motor.tick();
+        assert_eq!(last_sent(&driver), (3 << 8 | 12, -700));
+
+        motor.stop().unwrap();
+        assert_eq!(last_sent(&driver), (1 << 8 | 12, 0));
+        assert!(!motor.is_moving().unwrap());
+        driver.lock().unwrap().transmitted.clear();
+        motor.tick();
+        assert!(driver.lock().unwrap().transmitted.is_empty());
+        motor.stop_mode = StopMode::Brake;
+        motor.stop().unwrap();
+        assert_eq!(last_sent(&driver), (2 << 8 | 12, 5000));
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_vesc_status() -> Result<(), MotorError> {
+        let (mut motor, driver) = motor();
+        assert_eq!(motor.get_properties().ticks_per_rotation, Some(42));
+        assert_eq!(motor.get_position()?, 0);
+
+        let mut status = 7000_i32.to_be_bytes().to_vec();
+        status.extend(125_i16.to_be_bytes());
+        status.extend(500_i16.to_be_bytes());
+        let mut status_5 = 420_i32.to_be_bytes().to_vec();
+        status_5.extend(242_i16.to_be_bytes());
+        {
+            let mut driver = driver.lock().unwrap();
+            driver.queue(CanFrame::new_extended(CAN_PACKET_STATUS << 8 | 12, &status).unwrap());
+            driver.queue(CanFrame::new_extended(CAN_PACKET_STATUS_5 << 8 | 12, &status_5).unwrap());
+            // the status of another controller on the bus
+            driver.queue(CanFrame::new_extended(CAN_PACKET_STATUS_5 << 8 | 13, &[0; 6]).unwrap());
+        }
+        motor.tick();
+        assert_eq!(motor.get_position()?, 420);
+
+        let status = motor.get_status().unwrap().unwrap();
+        let field = |name: &str| status.fields.get(name).and_then(|v| v.kind.clone());
+        assert_eq!(field("rpm"), Some(Kind::NumberValue(1000.0)));
+        assert_eq!(field("current_a"), Some(Kind::NumberValue(12.5)));
+        assert_eq!(field("duty"), Some(Kind::NumberValue(0.5)));
+        assert_eq!(field("input_voltage"), Some(Kind::NumberValue(24.2)));
+        Ok(())
+    }
+}
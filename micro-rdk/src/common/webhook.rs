@@ -0,0 +1,377 @@
+//! Webhooks notified of the events detected on the device, and the [EventWatcher] posting the
+//! events kept by sensors (alerts, geofences) to them.
+//!
+//! Webhooks are plain `http://` urls, the events are posted as JSON objects. A sensor keeps its
+//! events in an [EventQueue] until they are taken with the `{"take_events": {}}` command, at most
+//! 16 events are kept:
+//!
+//! ```json
+//! {"events": [{"fence": "yard", "event": "enter", "lat": 40.7128, "lon": -74.006}]}
+//! ```
+//!
+//! The watcher posts each event with the name of its sensor under the key of the model of the
+//! sensor:
+//!
+//! ```json
+//! {"geofence": "fences", "fence": "yard", "event": "enter", "lat": 40.7128, "lon": -74.006}
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_io::{Async, Timer};
+use futures_lite::{AsyncReadExt, AsyncWriteExt, FutureExt};
+use thiserror::Error;
+
+use super::config::Kind;
+use super::robot::LocalRobot;
+use super::sensor::{SensorError, SensorType, COMPONENT_NAME as SensorCompName};
+use super::struct_builder::{value_to_json, StructBuilder};
+use crate::google::protobuf::{value::Kind as ProtoKind, ListValue, Struct, Value};
+use crate::proto::app::v1::ConfigResponse;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_PENDING_EVENTS: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("invalid webhook_url: {0}")]
+    InvalidUrl(&'static str),
+    #[error("webhook failed: {0}")]
+    PostFailed(String),
+}
+
+/// `host:port` and path of an `http://` webhook
+#[derive(Clone, Debug, PartialEq)]
+pub struct Webhook {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl TryFrom<&str> for Webhook {
+    type Error = WebhookError;
+    fn try_from(url: &str) -> Result<Self, Self::Error> {
+        let url = url
+            .strip_prefix("http://")
+            .ok_or(WebhookError::InvalidUrl("should start with http://"))?;
+        let (authority, path) = match url.find('/') {
+            Some(idx) => url.split_at(idx),
+            None => (url, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse::<u16>()
+                    .map_err(|_| WebhookError::InvalidUrl("invalid port"))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(WebhookError::InvalidUrl("missing a host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Webhook {
+    fn request(&self, body: &str) -> String {
+        format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        )
+    }
+
+    async fn send(&self, body: &str) -> Result<(), WebhookError> {
+        let io_err = |err: std::io::Error| WebhookError::PostFailed(err.to_string());
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(io_err)?
+            .next()
+            .ok_or_else(|| WebhookError::PostFailed(format!("can't resolve {}", self.host)))?;
+        let mut stream = Async::<TcpStream>::connect(addr).await.map_err(io_err)?;
+        stream
+            .write_all(self.request(body).as_bytes())
+            .await
+            .map_err(io_err)?;
+        // only the status line matters, "HTTP/1.1 200 OK"
+        let mut status = [0_u8; 12];
+        stream.read_exact(&mut status).await.map_err(io_err)?;
+        match status.get(9) {
+            Some(b'2') => Ok(()),
+            _ => Err(WebhookError::PostFailed(
+                String::from_utf8_lossy(&status).to_string(),
+            )),
+        }
+    }
+
+    /// Posts `body`, failing when the webhook doesn't answer within 2 seconds
+    pub async fn post(&self, body: &str) -> Result<(), WebhookError> {
+        let timeout = async {
+            Timer::after(WEBHOOK_TIMEOUT).await;
+            Err(WebhookError::PostFailed("timed out".to_string()))
+        };
+        self.send(body).or(timeout).await
+    }
+}
+
+/// Events kept by a sensor until they are taken, the oldest events are dropped when more than
+/// 16 events are waiting
+#[derive(Debug)]
+pub struct EventQueue<E> {
+    events: VecDeque<E>,
+}
+
+impl<E> Default for EventQueue<E> {
+    fn default() -> Self {
+        Self {
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl<E> EventQueue<E>
+where
+    for<'a> &'a E: Into<Value>,
+{
+    pub fn push(&mut self, event: E) {
+        if self.events.len() >= MAX_PENDING_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Takes the events, oldest first
+    pub fn take(&mut self) -> Vec<E> {
+        self.events.drain(..).collect()
+    }
+
+    /// Answers the `{"take_events": {}}` command, `None` for any other command
+    pub fn take_events(&mut self, command: &Struct) -> Option<Struct> {
+        if !command.fields.contains_key("take_events") {
+            return None;
+        }
+        let events = self.take().iter().map(Into::into).collect();
+        Some(
+            StructBuilder::with_capacity(1)
+                .value(
+                    "events",
+                    Value {
+                        kind: Some(ProtoKind::ListValue(ListValue { values: events })),
+                    },
+                )
+                .build(),
+        )
+    }
+}
+
+/// Builds the body posted for `event`, taken from the sensor `name` of `model`
+fn event_body(model: &str, name: &str, event: Value) -> Option<String> {
+    let mut body = match value_to_json(&event) {
+        serde_json::Value::Object(body) => body,
+        _ => return None,
+    };
+    body.insert(model.to_string(), name.into());
+    serde_json::to_string(&body).ok()
+}
+
+struct WatchedSensor {
+    name: String,
+    sensor: SensorType,
+    webhook: Option<Webhook>,
+    check_interval: Duration,
+}
+
+/// Takes the events of the sensors of a model regularly and posts them to the `webhook_url` of
+/// the sensor when it is set. The sensors evaluate their state when their events are taken, the
+/// watcher doesn't request readings so it doesn't take the events reported by the readings
+pub struct EventWatcher {
+    model: &'static str,
+    sensors: Vec<WatchedSensor>,
+    // the shortest check interval of the sensors
+    interval: Duration,
+}
+
+impl EventWatcher {
+    /// Returns a watcher for the sensors of `model` configured on the robot, checked every
+    /// `check_interval_secs` of their attributes (`default_check_interval` when unset), `None`
+    /// when the robot has none
+    pub fn from_robot_and_config(
+        cfg: &ConfigResponse,
+        robot: Arc<RwLock<LocalRobot>>,
+        model: &'static str,
+        default_check_interval: Duration,
+    ) -> Result<Option<Self>, SensorError> {
+        let robot = robot.read().unwrap();
+        let mut sensors = vec![];
+        for comp_cfg in cfg
+            .config
+            .iter()
+            .flat_map(|cfg| cfg.components.iter())
+            .filter(|comp_cfg| {
+                comp_cfg.r#type == SensorCompName
+                    && comp_cfg.model.rsplit(':').next() == Some(model)
+            })
+        {
+            // a sensor that failed to build is already reported by the robot
+            let sensor = match robot.get_sensor_by_name(comp_cfg.name.clone()) {
+                Some(sensor) => sensor,
+                None => continue,
+            };
+            let attributes = Kind::try_from(ProtoKind::StructValue(
+                comp_cfg.attributes.clone().unwrap_or_default(),
+            ))
+            .map_err(|_| SensorError::ConfigError("invalid attributes"))?;
+            let webhook = match attributes.get("webhook_url") {
+                Ok(Some(Kind::StringValue(url))) => Some(
+                    Webhook::try_from(url.as_str())
+                        .map_err(|_| SensorError::ConfigError("invalid webhook_url"))?,
+                ),
+                Ok(None) => None,
+                _ => return Err(SensorError::ConfigError("invalid webhook_url")),
+            };
+            let check_interval = match attributes.get("check_interval_secs") {
+                Ok(Some(Kind::NumberValue(secs))) if *secs > 0.0 => Duration::from_secs_f64(*secs),
+                Ok(None) => default_check_interval,
+                _ => return Err(SensorError::ConfigError("invalid check_interval_secs")),
+            };
+            sensors.push(WatchedSensor {
+                name: comp_cfg.name.clone(),
+                sensor,
+                webhook,
+                check_interval,
+            });
+        }
+        Ok(sensors
+            .iter()
+            .map(|sensor| sensor.check_interval)
+            .min()
+            .map(|interval| Self {
+                model,
+                sensors,
+                interval,
+            }))
+    }
+
+    pub async fn run(&self) {
+        let interval = self.interval;
+        let mut elapsed: HashMap<&str, Duration> = HashMap::new();
+        loop {
+            for sensor in &self.sensors {
+                let since_check = elapsed.entry(sensor.name.as_str()).or_default();
+                *since_check += interval;
+                if *since_check < sensor.check_interval {
+                    continue;
+                }
+                *since_check = Duration::ZERO;
+                if let Err(err) = self.check(sensor).await {
+                    log::error!("couldn't check {} {}: {:?}", self.model, sensor.name, err);
+                }
+            }
+            Timer::after(interval).await;
+        }
+    }
+
+    async fn check(&self, watched: &WatchedSensor) -> Result<(), SensorError> {
+        let events = watched.sensor.lock().unwrap().do_command(Some(
+            StructBuilder::with_capacity(1)
+                .sub("take_events", StructBuilder::new())
+                .build(),
+        ));
+        let webhook = match &watched.webhook {
+            Some(webhook) => webhook,
+            None => return Ok(()),
+        };
+        let events = match events
+            .ok()
+            .flatten()
+            .and_then(|mut res| res.fields.remove("events"))
+        {
+            Some(Value {
+                kind: Some(ProtoKind::ListValue(events)),
+            }) => events.values,
+            _ => return Ok(()),
+        };
+        for event in events {
+            let Some(body) = event_body(self.model, &watched.name, event) else {
+                continue;
+            };
+            if let Err(err) = webhook.post(&body).await {
+                log::warn!("{} {} webhook failed: {}", self.model, watched.name, err);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{event_body, EventQueue, Webhook};
+    use crate::common::struct_builder::StructBuilder;
+    use crate::google::protobuf::{value::Kind, Value};
+
+    struct Event(u32);
+
+    impl From<&Event> for Value {
+        fn from(value: &Event) -> Self {
+            StructBuilder::with_capacity(1).field("id", value.0).into()
+        }
+    }
+
+    #[test_log::test]
+    fn test_webhook_url() {
+        let webhook = Webhook::try_from("http://192.168.1.10:8123/api/webhook/door").unwrap();
+        assert_eq!(webhook.host, "192.168.1.10");
+        assert_eq!(webhook.port, 8123);
+        assert_eq!(webhook.path, "/api/webhook/door");
+        let webhook = Webhook::try_from("http://example.com").unwrap();
+        assert_eq!((webhook.port, webhook.path.as_str()), (80, "/"));
+        assert!(webhook
+            .request("{}")
+            .starts_with("POST / HTTP/1.1\r\nHost: example.com\r\n"));
+        assert!(Webhook::try_from("https://example.com").is_err());
+    }
+
+    #[test_log::test]
+    fn test_event_queue() {
+        let mut queue = EventQueue::default();
+        for id in 0..20 {
+            queue.push(Event(id));
+        }
+        assert_eq!(queue.len(), 16);
+        assert!(queue.take_events(&StructBuilder::new().build()).is_none());
+        let command = StructBuilder::new()
+            .sub("take_events", StructBuilder::new())
+            .build();
+        let events = queue.take_events(&command).unwrap();
+        let Some(Kind::ListValue(events)) = events.fields["events"].kind.clone() else {
+            panic!("events should be a list");
+        };
+        // the oldest events were dropped
+        assert_eq!(events.values.len(), 16);
+        assert_eq!(events.values[0], (&Event(4)).into());
+        assert!(queue.is_empty());
+
+        let body: serde_json::Value =
+            serde_json::from_str(&event_body("alerts", "alarms", (&Event(3)).into()).unwrap())
+                .unwrap();
+        assert_eq!(body, serde_json::json!({"alerts": "alarms", "id": 3.0}));
+    }
+}
@@ -2,7 +2,7 @@
 use std::{
     fmt::Debug,
     io::{self, Cursor},
-    net::{Ipv4Addr, UdpSocket},
+    net::Ipv4Addr,
     pin::Pin,
     rc::Rc,
     sync::{atomic::AtomicBool, Arc},
@@ -41,11 +41,13 @@ use thiserror::Error;
 use super::{
     candidates::Candidate,
     certificate::Certificate,
-    dtls::DtlsConnector,
+    dtls::{DtlsConnector, DtlsSrtpKeying},
     exec::WebRtcExecutor,
-    ice::{ICEAgent, ICECredentials},
+    ice::{ICEAgent, ICECredentials, IceConfig},
     io::WebRtcTransport,
+    rtp::{VideoTrack, JPEG_PAYLOAD_TYPE, VIDEO_CLOCK_RATE},
     sctp::{Channel, SctpConnector, SctpHandle},
+    srtp::{SrtpContext, SRTP_KEYING_MATERIAL_LEN},
 };
 
 #[derive(Error, Debug)]
@@ -76,6 +78,8 @@ pub enum WebRtcError {
     CannotParseCandidate,
     #[error("Operation timeout")]
     OperationTiemout,
+    #[error("remote peer unreachable")]
+    PeerUnreachable,
 }
 
 pub(crate) struct WebRtcSignalingChannel {
@@ -307,6 +311,13 @@ type Executor = NativeExecutor;
 #[cfg(feature = "esp32")]
 type Executor = Esp32Executor;
 
+// A video m-line accepted in the answer, the peer names the camera to stream with the
+// stream id of its `msid` attribute (rfc8830) or with a `label` attribute (rfc4574)
+struct VideoTrackConfig {
+    camera: String,
+    ssrc: u32,
+}
+
 pub struct WebRtcApi<S, D, E> {
     executor: E,
     signaling: Option<WebRtcSignalingChannel>,
@@ -316,9 +327,13 @@ pub struct WebRtcApi<S, D, E> {
     local_creds: ICECredentials,
     remote_creds: Option<ICECredentials>,
     local_ip: Ipv4Addr,
+    ice_config: IceConfig,
     dtls: Option<D>,
     sctp_handle: Option<SctpHandle>,
     ice_agent: AtomicSync,
+    ice_disconnected: AtomicSync,
+    video: Option<VideoTrackConfig>,
+    srtp_keying_material: Option<[u8; SRTP_KEYING_MATERIAL_LEN]>,
 }
 
 impl<C, D, E> Drop for WebRtcApi<C, D, E> {
@@ -342,13 +357,14 @@ where
         rx_half: GrpcMessageStream<AnswerRequest>,
         certificate: Rc<C>,
         local_ip: Ipv4Addr,
+        ice_config: IceConfig,
         dtls: D,
-    ) -> Self {
-        let udp = Arc::new(async_io::Async::<UdpSocket>::bind(([0, 0, 0, 0], 0)).unwrap());
+    ) -> Result<Self, WebRtcError> {
+        let udp = Arc::new(async_io::Async::new(ice_config.bind()?)?);
 
         let transport = WebRtcTransport::new(udp);
 
-        Self {
+        Ok(Self {
             executor,
             signaling: Some(WebRtcSignalingChannel {
                 signaling_tx: tx_half,
@@ -361,10 +377,14 @@ where
             remote_creds: None,
             local_creds: Default::default(),
             local_ip,
+            ice_config,
             dtls: Some(dtls),
             sctp_handle: None,
             ice_agent: AtomicSync::default(),
-        }
+            ice_disconnected: AtomicSync::default(),
+            video: None,
+            srtp_keying_material: None,
+        })
     }
 
     pub async fn run_ice_until_connected(&mut self, answer: &WebRtcSdp) -> Result<(), WebRtcError> {
@@ -378,6 +398,7 @@ where
             self.local_creds.clone(),
             self.remote_creds.as_ref().unwrap().clone(),
             self.local_ip,
+            self.ice_config.clone(),
         );
 
         self.signaling
@@ -409,8 +430,9 @@ where
         let sync = AtomicSync::default();
         let sync_clone = sync.clone();
         let die_clone = self.ice_agent.clone();
+        let disconnected_clone = self.ice_disconnected.clone();
         self.executor.execute(Box::pin(async move {
-            ice_agent.run(sync, die_clone).await;
+            ice_agent.run(sync, die_clone, disconnected_clone).await;
         }));
 
         while !sync_clone.get() {
@@ -444,6 +466,12 @@ where
         Ok(())
     }
 
+    /// Resolves once the ICE agent stopped, typically because the remote peer stopped answering
+    /// connectivity checks, so a half-open connection can be torn down
+    pub fn disconnected(&self) -> impl Future<Output = ()> {
+        self.ice_disconnected.clone()
+    }
+
     pub async fn open_data_channel(&mut self) -> Result<Channel, WebRtcError> {
         let mut dtls = self.dtls.take().unwrap();
 
@@ -457,6 +485,7 @@ where
             .map_err(|e| WebRtcError::DtlsError(Box::new(e)))?
             .await
         {
+            self.srtp_keying_material = dtls_stream.srtp_keying_material();
             let (c_tx, c_rx) = async_channel::unbounded();
 
             let sctp = Box::new(SctpConnector::new(dtls_stream, c_tx));
@@ -523,21 +552,118 @@ where
         let _ = self.remote_creds.insert(remote_creds);
         let _ = self.uuid.insert(offer.uuid);
 
-        // rfc8839 section 4.3.2
-        let data_track_name = MediaName {
-            media: "application".to_owned(),
-            port: RangedPort {
-                value: 9,
-                range: None,
-            },
-            protos: vec!["UDP".to_owned(), "DTLS".to_owned(), "SCTP".to_owned()],
-            formats: vec!["webrtc-datachannel".to_owned()],
-        };
-
         let fp = self.certificate.get_fingerprint();
+        let mut bundle = vec![];
+        let mut medias = vec![];
 
-        let media = MediaDescription {
-            media_name: data_track_name,
+        // rfc8829 section 5.3.1, the answer has one m-line per m-line of the offer
+        for (idx, offer_media) in offer.sdp.media_descriptions.iter().enumerate() {
+            let mid = offer_media
+                .attribute("mid")
+                .flatten()
+                .map_or_else(|| idx.to_string(), str::to_owned);
+            let media = match offer_media.media_name.media.as_str() {
+                "application" => {
+                    // rfc8839 section 4.3.2
+                    let data_track_name = MediaName {
+                        media: "application".to_owned(),
+                        port: RangedPort {
+                            value: 9,
+                            range: None,
+                        },
+                        protos: vec!["UDP".to_owned(), "DTLS".to_owned(), "SCTP".to_owned()],
+                        formats: vec!["webrtc-datachannel".to_owned()],
+                    };
+                    Some(
+                        Self::media_description(data_track_name)
+                            .with_property_attribute("sendrecv".to_owned())
+                            .with_property_attribute("sctp-port:5000".to_owned()),
+                    )
+                }
+                "video" if self.video.is_none() && Self::offers_jpeg(offer_media) => {
+                    match Self::requested_camera(offer_media) {
+                        Some(camera) => {
+                            let video = VideoTrackConfig {
+                                camera,
+                                ssrc: rand::random(),
+                            };
+                            let video_track_name = MediaName {
+                                media: "video".to_owned(),
+                                port: RangedPort {
+                                    value: 9,
+                                    range: None,
+                                },
+                                protos: offer_media.media_name.protos.clone(),
+                                formats: vec![JPEG_PAYLOAD_TYPE.to_string()],
+                            };
+                            let media = Self::media_description(video_track_name)
+                                .with_property_attribute("sendonly".to_owned())
+                                .with_property_attribute("rtcp-mux".to_owned())
+                                .with_value_attribute(
+                                    "rtpmap".to_owned(),
+                                    format!("{} JPEG/{}", JPEG_PAYLOAD_TYPE, VIDEO_CLOCK_RATE),
+                                )
+                                .with_value_attribute(
+                                    "msid".to_owned(),
+                                    format!("{} video", video.camera),
+                                )
+                                .with_value_attribute(
+                                    "ssrc".to_owned(),
+                                    format!("{} cname:micro-rdk", video.ssrc),
+                                );
+                            let _ = self.video.insert(video);
+                            Some(media)
+                        }
+                        None => None,
+                    }
+                }
+                _ => None,
+            };
+            match media {
+                Some(media) => {
+                    bundle.push(mid.clone());
+                    medias.push(
+                        media
+                            .with_value_attribute("setup".to_owned(), "passive".to_owned())
+                            .with_value_attribute("mid".to_string(), mid)
+                            .with_ice_credentials(
+                                self.local_creds.u_frag.clone(),
+                                self.local_creds.pwd.clone(),
+                            )
+                            .with_fingerprint(fp.get_algo().to_string(), fp.get_hash().to_string()),
+                    );
+                }
+                None => {
+                    // rejected m-lines have their port set to 0 (rfc3264 section 6)
+                    let mut media_name = offer_media.media_name.clone();
+                    media_name.port = RangedPort {
+                        value: 0,
+                        range: None,
+                    };
+                    medias.push(
+                        Self::media_description(media_name)
+                            .with_value_attribute("mid".to_string(), mid)
+                            .with_property_attribute("inactive".to_owned()),
+                    );
+                }
+            }
+        }
+
+        let mut answer =
+            answer.with_value_attribute("group".to_owned(), format!("BUNDLE {}", bundle.join(" ")));
+        for media in medias {
+            answer = answer.with_media(media);
+        }
+
+        Ok((
+            Box::new(WebRtcSdp::new(answer, self.uuid.as_ref().unwrap().clone())),
+            caller_prio,
+        ))
+    }
+
+    fn media_description(media_name: MediaName) -> MediaDescription {
+        MediaDescription {
+            media_name,
             media_title: None,
             // rfc8839 section 4.3.2
             connection_information: Some(ConnectionInformation {
@@ -553,23 +679,36 @@ where
             encryption_key: None,
             attributes: vec![],
         }
-        .with_value_attribute("setup".to_owned(), "passive".to_owned())
-        .with_value_attribute("mid".to_string(), "0".to_owned())
-        .with_property_attribute("sendrecv".to_owned())
-        .with_property_attribute("sctp-port:5000".to_owned())
-        .with_ice_credentials(
-            self.local_creds.u_frag.clone(),
-            self.local_creds.pwd.clone(),
-        )
-        .with_fingerprint(fp.get_algo().to_string(), fp.get_hash().to_string());
+    }
 
-        let answer = answer.with_value_attribute("group".to_owned(), "BUNDLE 0".to_owned());
+    // the camera a video m-line asks for, a `-` stream id means the track has no stream
+    fn requested_camera(media: &MediaDescription) -> Option<String> {
+        media
+            .attribute("msid")
+            .flatten()
+            .and_then(|msid| msid.split_whitespace().next())
+            .filter(|stream_id| *stream_id != "-")
+            .or_else(|| media.attribute("label").flatten())
+            .map(str::to_owned)
+    }
 
-        let answer = answer.with_media(media);
+    fn offers_jpeg(media: &MediaDescription) -> bool {
+        let rtpmap = format!("{} JPEG/{}", JPEG_PAYLOAD_TYPE, VIDEO_CLOCK_RATE);
+        media
+            .attributes
+            .iter()
+            .any(|a| a.key == "rtpmap" && a.value.as_deref() == Some(rtpmap.as_str()))
+    }
 
-        Ok((
-            Box::new(WebRtcSdp::new(answer, self.uuid.as_ref().unwrap().clone())),
-            caller_prio,
+    /// Returns the video track negotiated with the peer, once the DTLS handshake completed
+    pub fn video_track(&self) -> Option<VideoTrack> {
+        let video = self.video.as_ref()?;
+        let material = self.srtp_keying_material.as_ref()?;
+        Some(VideoTrack::new(
+            video.camera.clone(),
+            video.ssrc,
+            SrtpContext::from_keying_material(material),
+            self.transport.get_rtp_sender(),
         ))
     }
 }
@@ -161,6 +161,17 @@ impl Candidate {
         self.address.port()
     }
 
+    /// Sets the priority of a local candidate from its local preference, see
+    /// 4.1.2.1.  Recommended Formula
+    pub(crate) fn with_local_preference(mut self, local_preference: u16) -> Self {
+        self.priority = Some(
+            u32::from(self.candidate_type.preference()) << 24
+                | (u32::from(local_preference) << 8)
+                | (256 - u32::from(self.component)),
+        );
+        self
+    }
+
     fn priority(&self) -> u32 {
         if let Some(p) = self.priority {
             return p;
@@ -299,6 +310,13 @@ impl TryFrom<String> for Candidate {
     }
 }
 
+/// Once a pair succeeded, connectivity checks keep being sent on it at this interval to verify the
+/// remote peer still consents to receive traffic (see RFC 7675)
+pub(crate) const CONSENT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// A succeeded pair that got no answer to its connectivity checks for this long has lost consent,
+/// the remote peer is considered gone
+pub(crate) const CONSENT_TIMEOUT: Duration = Duration::from_secs(6);
+
 /// Represent the state of a candidate pair
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(crate) enum CandidatePairState {
@@ -337,6 +355,8 @@ pub struct CandidatePair {
     binding_req_sent: u32,
     /// successful binding requests on this pair
     pub(crate) binding_resp_recv: u32,
+    /// when the last binding response was received on this pair
+    last_consent: Option<Instant>,
 }
 
 impl CandidatePair {
@@ -366,6 +386,7 @@ impl CandidatePair {
             current_binding_request: None, // store last 4 attempts
             binding_resp_recv: 0,
             binding_req_sent: 0,
+            last_consent: None,
         })
     }
     pub(crate) fn state(&self) -> &CandidatePairState {
@@ -386,8 +407,14 @@ impl CandidatePair {
             }
             CandidatePairState::InProgress | CandidatePairState::Succeeded => {
                 if let Some(req) = self.current_binding_request.as_mut() {
-                    // Retry while pair is InProgress, Ta is set a 500ms.
-                    if now - req.req_time < Duration::from_millis(500) {
+                    // Retry while pair is InProgress, Ta is set a 500ms. Succeeded pairs are only
+                    // checked for consent freshness.
+                    let interval = if self.state == CandidatePairState::Succeeded {
+                        CONSENT_CHECK_INTERVAL
+                    } else {
+                        Duration::from_millis(500)
+                    };
+                    if now - req.req_time < interval {
                         return None;
                     }
                     if !req.resp_recv {
@@ -420,11 +447,12 @@ impl CandidatePair {
         }
     }
     /// Check if a binding response belongs to this Pair
-    pub fn binding_response(&mut self, _now: &Instant, id: &TransactionId) -> bool {
+    pub fn binding_response(&mut self, now: &Instant, id: &TransactionId) -> bool {
         if let Some(req) = self.current_binding_request.as_mut() {
             if req.id == *id {
                 req.resp_recv = true;
                 self.binding_req_recv += 1;
+                self.last_consent = Some(*now);
                 self.state = CandidatePairState::Succeeded;
                 log::debug!("Pair succeeded {:?}", self);
                 return true;
@@ -434,6 +462,16 @@ impl CandidatePair {
     }
 }
 
+impl CandidatePair {
+    /// Whether the remote peer stopped answering connectivity checks on this succeeded pair
+    pub(crate) fn consent_expired(&self, now: Instant) -> bool {
+        self.state == CandidatePairState::Succeeded
+            && self
+                .last_consent
+                .is_some_and(|last| now.saturating_duration_since(last) > CONSENT_TIMEOUT)
+    }
+}
+
 impl PartialEq for CandidatePair {
     fn eq(&self, other: &Self) -> bool {
         self.local == other.local && self.remote == other.remote && self.prio == other.prio
@@ -469,9 +507,11 @@ impl PartialEq for BindingRequests {
 #[cfg(test)]
 mod tests {
     use std::net::SocketAddrV4;
+    use std::time::Instant;
 
     use super::Candidate;
     use super::CandidateType;
+    use super::{CandidatePair, CandidatePairState, CONSENT_CHECK_INTERVAL, CONSENT_TIMEOUT};
 
     #[test_log::test]
     fn test_parse_candidate_string() {
@@ -539,4 +579,31 @@ mod tests {
             r
         );
     }
+
+    #[test_log::test]
+    fn test_pair_consent_freshness() {
+        let local =
+            Candidate::new_host_candidate(SocketAddrV4::new("10.1.2.3".parse().unwrap(), 61322));
+        let remote =
+            Candidate::new_host_candidate(SocketAddrV4::new("10.1.2.4".parse().unwrap(), 54182));
+        let mut pair = CandidatePair::new(&local, &remote, 0, 0).unwrap();
+
+        let now = Instant::now();
+        let id = pair.create_new_binding_request(now).unwrap();
+        assert!(!pair.consent_expired(now));
+        assert!(pair.binding_response(&now, &id));
+        assert_eq!(*pair.state(), CandidatePairState::Succeeded);
+
+        // no consent check until the interval elapsed
+        assert!(pair
+            .create_new_binding_request(now + CONSENT_CHECK_INTERVAL / 2)
+            .is_none());
+        let later = now + CONSENT_CHECK_INTERVAL;
+        let id = pair.create_new_binding_request(later).unwrap();
+        assert!(!pair.consent_expired(later + CONSENT_TIMEOUT / 2));
+        assert!(pair.binding_response(&later, &id));
+
+        // the peer stopped answering
+        assert!(pair.consent_expired(later + CONSENT_TIMEOUT * 2));
+    }
 }
@@ -2,7 +2,7 @@ use futures_lite::{AsyncRead, AsyncWrite, Future};
 
 use thiserror::Error;
 
-use super::udp_mux::UdpMux;
+use super::{srtp::SRTP_KEYING_MATERIAL_LEN, udp_mux::UdpMux};
 
 #[derive(Error, Debug)]
 pub enum DtlsError {
@@ -10,8 +10,15 @@ pub enum DtlsError {
     DtlsError(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
 
+/// Access to the DTLS-SRTP keying material (rfc5764) of an established DTLS session
+pub trait DtlsSrtpKeying {
+    /// Returns the keying material exported with the "EXTRACTOR-dtls_srtp" label, None if the
+    /// handshake didn't complete
+    fn srtp_keying_material(&self) -> Option<[u8; SRTP_KEYING_MATERIAL_LEN]>;
+}
+
 pub trait DtlsConnector {
-    type Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+    type Stream: AsyncRead + AsyncWrite + DtlsSrtpKeying + Send + Unpin + 'static;
     type Error: std::error::Error + Send + Sync + 'static;
     type Future: Future<Output = Result<Self::Stream, Self::Error>>;
 
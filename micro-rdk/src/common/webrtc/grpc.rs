@@ -6,7 +6,7 @@ use std::{
 };
 
 use bytes::{Bytes, BytesMut};
-use futures_lite::AsyncReadExt;
+use futures_lite::{AsyncReadExt, Future};
 use prost::Message;
 
 use crate::{
@@ -20,6 +20,10 @@ use crate::{
 
 use super::{api::WebRtcError, sctp::Channel};
 
+/// Responses are split in packets of at most this many bytes, each write waits for the peer to
+/// acknowledge enough of the previous ones so a large response is paced to the link
+const MAX_PACKET_DATA_SIZE: usize = 4096;
+
 #[derive(Debug, Default)]
 pub struct WebRtcGrpcBody {
     data: Option<Bytes>,
@@ -74,12 +78,21 @@ pub struct WebRtcGrpcServer<S> {
 }
 
 pub trait WebRtcGrpcService {
-    fn unary_rpc(&mut self, method: &str, data: &Bytes) -> Result<Bytes, ServerError>;
+    /// Whether `method` is a server streaming RPC, answered through [Self::server_stream_rpc]
+    fn is_server_stream(&self, method: &str) -> bool {
+        method.contains("Stream")
+    }
+    fn unary_rpc(
+        &mut self,
+        method: &str,
+        data: &Bytes,
+    ) -> impl Future<Output = Result<Bytes, ServerError>>;
+    /// Polls a server stream, returning the message to send if any and when to poll it next
     fn server_stream_rpc(
         &mut self,
         method: &str,
         data: &Bytes,
-    ) -> Result<(Bytes, Instant), ServerError>;
+    ) -> Result<(Option<Bytes>, Instant), ServerError>;
 }
 
 impl<S> WebRtcGrpcServer<S>
@@ -116,10 +129,12 @@ where
         let method = &hdr.method;
         log::debug!("processing req {:?}", method);
         let ret = if let Some(pkt) = msg.packet_message.as_ref() {
-            if method.contains("Stream") {
+            if self.service.is_server_stream(method) {
                 match self.service.server_stream_rpc(method, &pkt.data) {
                     Ok(data) => {
-                        self.send_rpc_response(data.0, stream).await?;
+                        if let Some(message) = data.0 {
+                            self.send_rpc_response(message, stream).await?;
+                        }
                         (
                             Status {
                                 code: 0,
@@ -128,10 +143,13 @@ where
                             Some(data.1),
                         )
                     }
-                    Err(e) => (e.to_status(), None),
+                    Err(e) => {
+                        log::debug!("rpc {:?} failed: {}", method, e);
+                        (e.to_status(), None)
+                    }
                 }
             } else {
-                match self.service.unary_rpc(method, &pkt.data) {
+                match self.service.unary_rpc(method, &pkt.data).await {
                     Ok(data) => {
                         self.send_rpc_response(data, stream).await?;
                         (
@@ -142,7 +160,10 @@ where
                             None,
                         )
                     }
-                    Err(e) => (e.to_status(), None),
+                    Err(e) => {
+                        log::debug!("rpc {:?} failed: {}", method, e);
+                        (e.to_status(), None)
+                    }
                 }
             }
         } else {
@@ -156,16 +177,27 @@ where
         };
         Ok(ret)
     }
-    async fn send_rpc_response(&mut self, data: Bytes, stream: Stream) -> Result<(), WebRtcError> {
-        let message_response = webrtc::v1::Response {
-            stream: Some(stream),
-            r#type: Some(webrtc::v1::response::Type::Message(
-                webrtc::v1::ResponseMessage {
-                    packet_message: Some(webrtc::v1::PacketMessage { data, eom: true }),
-                },
-            )),
-        };
-        self.send_response(message_response).await
+    async fn send_rpc_response(
+        &mut self,
+        mut data: Bytes,
+        stream: Stream,
+    ) -> Result<(), WebRtcError> {
+        loop {
+            let packet = data.split_to(data.len().min(MAX_PACKET_DATA_SIZE));
+            let eom = data.is_empty();
+            let message_response = webrtc::v1::Response {
+                stream: Some(stream.clone()),
+                r#type: Some(webrtc::v1::response::Type::Message(
+                    webrtc::v1::ResponseMessage {
+                        packet_message: Some(webrtc::v1::PacketMessage { data: packet, eom }),
+                    },
+                )),
+            };
+            self.send_response(message_response).await?;
+            if eom {
+                return Ok(());
+            }
+        }
     }
     async fn send_trailers(&mut self, stream: Stream, status: Status) -> Result<(), WebRtcError> {
         let trailer_response = webrtc::v1::Response {
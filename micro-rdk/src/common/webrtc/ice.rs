@@ -1,8 +1,10 @@
 #![allow(dead_code)]
 use std::{
     io,
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs, UdpSocket},
+    ops::RangeInclusive,
     pin::Pin,
+    str::FromStr,
     time::{Duration, Instant},
 };
 
@@ -52,6 +54,123 @@ impl ICECredentials {
     }
 }
 
+/// An IPv4 network in CIDR notation (e.g `192.168.71.0/24`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ipv4Network {
+    addr: Ipv4Addr,
+    prefix_len: u8,
+}
+
+impl Ipv4Network {
+    pub fn new(addr: Ipv4Addr, prefix_len: u8) -> Self {
+        Self {
+            addr,
+            prefix_len: prefix_len.min(32),
+        }
+    }
+    fn mask(&self) -> u32 {
+        u32::MAX
+            .checked_shl(32 - u32::from(self.prefix_len))
+            .unwrap_or(0)
+    }
+    pub fn contains(&self, ip: &Ipv4Addr) -> bool {
+        (u32::from(*ip) ^ u32::from(self.addr)) & self.mask() == 0
+    }
+}
+
+impl FromStr for Ipv4Network {
+    type Err = IceError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = s.split_once('/').unwrap_or((s, "32"));
+        let addr = addr
+            .parse::<Ipv4Addr>()
+            .map_err(|_| IceError::IceInvalidNetwork(s.to_owned()))?;
+        let prefix_len = prefix_len
+            .parse::<u8>()
+            .ok()
+            .filter(|len| *len <= 32)
+            .ok_or_else(|| IceError::IceInvalidNetwork(s.to_owned()))?;
+        Ok(Self::new(addr, prefix_len))
+    }
+}
+
+/// Controls which interfaces and ports the ICE agent uses and how its candidates are
+/// prioritized. On devices with several interfaces (e.g. station and SoftAP) it lets WebRTC
+/// traffic be kept on the interface that can actually reach the peer
+#[derive(Clone, Debug)]
+pub struct IceConfig {
+    bind_ip: Option<Ipv4Addr>,
+    port_range: Option<RangeInclusive<u16>>,
+    excluded_networks: Vec<Ipv4Network>,
+    host_preference: u16,
+    srflx_preference: u16,
+}
+
+impl Default for IceConfig {
+    fn default() -> Self {
+        Self {
+            bind_ip: None,
+            port_range: None,
+            excluded_networks: vec![],
+            host_preference: 0xFFFF,
+            srflx_preference: 0xFFFF,
+        }
+    }
+}
+
+impl IceConfig {
+    /// Bind the ICE socket to the interface owning `ip` rather than to every interface, the
+    /// host candidate will advertise this address
+    pub fn with_bind_ip(mut self, ip: Ipv4Addr) -> Self {
+        self.bind_ip = Some(ip);
+        self
+    }
+    /// Only bind the ICE socket to a UDP port within `range`
+    pub fn with_port_range(mut self, range: RangeInclusive<u16>) -> Self {
+        self.port_range = Some(range);
+        self
+    }
+    /// Addresses within `network` are neither gathered as local candidates nor paired with
+    /// remote candidates
+    pub fn with_excluded_network(mut self, network: Ipv4Network) -> Self {
+        self.excluded_networks.push(network);
+        self
+    }
+    /// Set the local preference (RFC5245 4.1.2.1) of host and server reflexive candidates, a
+    /// higher value makes the peer favor the corresponding candidate
+    pub fn with_local_preferences(mut self, host: u16, srflx: u16) -> Self {
+        self.host_preference = host;
+        self.srflx_preference = srflx;
+        self
+    }
+    pub(crate) fn is_excluded(&self, ip: &Ipv4Addr) -> bool {
+        self.excluded_networks.iter().any(|net| net.contains(ip))
+    }
+    /// Address advertised by the host candidate, `default` is used unless the socket is bound
+    /// to a specific interface
+    pub(crate) fn host_ip(&self, default: Ipv4Addr) -> Ipv4Addr {
+        self.bind_ip
+            .filter(|ip| !ip.is_unspecified())
+            .unwrap_or(default)
+    }
+    /// Create the UDP socket used by the WebRTC transport, honoring the interface and port range
+    pub(crate) fn bind(&self) -> io::Result<UdpSocket> {
+        let ip = self.bind_ip.unwrap_or(Ipv4Addr::UNSPECIFIED);
+        let Some(range) = self.port_range.clone() else {
+            return UdpSocket::bind((ip, 0));
+        };
+        range
+            .into_iter()
+            .find_map(|port| UdpSocket::bind((ip, port)).ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "no free port within the ICE port range",
+                )
+            })
+    }
+}
+
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum IceError {
     #[error("candidate channel closed")]
@@ -82,6 +201,10 @@ pub enum IceError {
     IceStunDecodingError,
     #[error("ice operation timeout")]
     IceTimeout,
+    #[error("remote peer stopped answering connectivity checks")]
+    IceConsentExpired,
+    #[error("invalid network {0}")]
+    IceInvalidNetwork(String),
     #[error(transparent)]
     IceCandidateError(#[from] CandidateError),
 }
@@ -111,6 +234,7 @@ pub struct ICEAgent {
     remote_credentials: ICECredentials,
     state: ICEAgentState,
     local_ip: Ipv4Addr,
+    config: IceConfig,
 }
 
 impl Drop for ICEAgent {
@@ -132,6 +256,7 @@ impl ICEAgent {
         local_credentials: ICECredentials,
         remote_credentials: ICECredentials,
         local_ip: Ipv4Addr,
+        config: IceConfig,
     ) -> Self {
         Self {
             local_candidates: vec![],
@@ -143,6 +268,7 @@ impl ICEAgent {
             local_credentials,
             remote_credentials,
             state: ICEAgentState::Checking,
+            config,
         }
     }
 
@@ -217,14 +343,22 @@ impl ICEAgent {
             SocketAddr::V6(_) => return Err(IceError::IceXorMappedAddressIsIPV6),
         };
 
-        // the host ip was set when creating the ICEagent
-        let our_ip = SocketAddrV4::new(self.local_ip, rflx_addr.port());
-
-        let local_cand = Candidate::new_host_candidate(our_ip);
+        // the host ip was set when creating the ICEagent unless the socket is bound to an interface
+        let our_ip = SocketAddrV4::new(self.config.host_ip(self.local_ip), rflx_addr.port());
 
-        self.local_candidates.push(local_cand);
+        if self.config.is_excluded(our_ip.ip()) {
+            log::debug!(
+                "not gathering host candidate {}, network is excluded",
+                our_ip
+            );
+        } else {
+            let local_cand = Candidate::new_host_candidate(our_ip)
+                .with_local_preference(self.config.host_preference);
+            self.local_candidates.push(local_cand);
+        }
 
-        let srflx_candidate = Candidate::new_srflx_candidate(rflx_addr, our_ip);
+        let srflx_candidate = Candidate::new_srflx_candidate(rflx_addr, our_ip)
+            .with_local_preference(self.config.srflx_preference);
         self.local_candidates.push(srflx_candidate);
 
         Ok(())
@@ -232,7 +366,13 @@ impl ICEAgent {
 
     /// run the ice agent, processing incoming STUN packet and emitting STUN request
     // TODO remove dependency on &mut self so ICEAgent can be closed without relying on the AtomicSync
-    pub(crate) async fn run(&mut self, done: AtomicSync, stop: AtomicSync) {
+    /// `disconnected` is signaled once the agent stops, so the connection relying on it can be closed
+    pub(crate) async fn run(
+        &mut self,
+        done: AtomicSync,
+        stop: AtomicSync,
+        disconnected: AtomicSync,
+    ) {
         log::debug!("Running ICE Agent");
 
         let error = loop {
@@ -253,6 +393,16 @@ impl ICEAgent {
                 }
             }
 
+            // once connected, losing consent on every succeeded pair means the peer is gone
+            let now = Instant::now();
+            if self.state == ICEAgentState::Connected
+                && !self.candidate_pairs.iter().any(|pair| {
+                    *pair.state() == CandidatePairState::Succeeded && !pair.consent_expired(now)
+                })
+            {
+                break IceError::IceConsentExpired;
+            }
+
             let req = self.next_stun_request();
             if let Some(req) = req {
                 if let Ok(msg) = self.make_stun_request(req.0) {
@@ -311,6 +461,9 @@ impl ICEAgent {
                 }
             };
             match event {
+                IceEvent::CandidateReceived(c) if self.config.is_excluded(c.address().ip()) => {
+                    log::debug!("ignoring remote candidate {:?}, network is excluded", c);
+                }
                 IceEvent::CandidateReceived(c) => {
                     self.remote_candidates.push(c);
                     self.form_pairs(self.remote_candidates.len() - 1);
@@ -364,6 +517,7 @@ impl ICEAgent {
         };
 
         log::error!("closing ice agent with error {:?}", error);
+        disconnected.done();
     }
 
     /// next_stun_request finds the next suitable pair to do a connection check on
@@ -646,10 +800,10 @@ mod tests {
     use async_executor::Executor;
     use async_io::Async;
     use futures_lite::future::block_on;
-    use std::net::UdpSocket;
+    use std::net::{Ipv4Addr, UdpSocket};
     use std::sync::Arc;
 
-    use crate::common::webrtc::ice::{ICEAgent, ICECredentials};
+    use crate::common::webrtc::ice::{ICEAgent, ICECredentials, IceConfig, Ipv4Network};
 
     use crate::common::webrtc::{candidates::Candidate, io::WebRtcTransport};
 
@@ -688,6 +842,7 @@ mod tests {
             ICECredentials::default(),
             ICECredentials::default(),
             our_ip,
+            IceConfig::default(),
         );
         let ret = block_on(executor.run(async { ice_agent.local_candidates().await }));
 
@@ -701,4 +856,39 @@ mod tests {
 
         Ok(())
     }
+
+    #[test_log::test]
+    fn test_ice_config() {
+        let net: Ipv4Network = "192.168.71.0/24".parse().unwrap();
+        assert!(net.contains(&Ipv4Addr::new(192, 168, 71, 1)));
+        assert!(!net.contains(&Ipv4Addr::new(192, 168, 72, 1)));
+        let any: Ipv4Network = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(&Ipv4Addr::new(10, 1, 2, 3)));
+        let single: Ipv4Network = "10.1.2.3".parse().unwrap();
+        assert!(single.contains(&Ipv4Addr::new(10, 1, 2, 3)));
+        assert!(!single.contains(&Ipv4Addr::new(10, 1, 2, 4)));
+        assert!("10.1.2.3/33".parse::<Ipv4Network>().is_err());
+        assert!("10.1.2/24".parse::<Ipv4Network>().is_err());
+
+        let config = IceConfig::default()
+            .with_bind_ip(Ipv4Addr::LOCALHOST)
+            .with_excluded_network(net);
+        assert!(config.is_excluded(&Ipv4Addr::new(192, 168, 71, 20)));
+        assert!(!config.is_excluded(&Ipv4Addr::new(10, 1, 2, 3)));
+        assert_eq!(
+            config.host_ip(Ipv4Addr::new(10, 1, 2, 3)),
+            Ipv4Addr::LOCALHOST
+        );
+
+        let sock = config
+            .clone()
+            .with_port_range(40000..=40100)
+            .bind()
+            .unwrap();
+        let port = sock.local_addr().unwrap().port();
+        assert!((40000..=40100).contains(&port));
+        // the port in use is skipped
+        let other = config.with_port_range(port..=40101).bind().unwrap();
+        assert_ne!(other.local_addr().unwrap().port(), port);
+    }
 }
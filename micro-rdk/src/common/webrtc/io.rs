@@ -4,7 +4,7 @@ use async_io::Async;
 
 use std::{net::UdpSocket, sync::Arc};
 
-use super::udp_mux::{UdpMux, UdpMuxer};
+use super::udp_mux::{RtpSender, UdpMux, UdpMuxer};
 #[derive(Clone)]
 pub struct WebRtcTransport {
     mux: UdpMuxer,
@@ -23,4 +23,7 @@ impl WebRtcTransport {
     pub fn get_dtls_channel(&self) -> Option<UdpMux> {
         self.mux.get_dtls_mux()
     }
+    pub fn get_rtp_sender(&self) -> RtpSender {
+        self.mux.get_rtp_sender()
+    }
 }
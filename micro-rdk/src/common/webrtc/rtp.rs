@@ -0,0 +1,367 @@
+//! RTP packetization of camera frames for the video track.
+//!
+//! Frames are sent as RTP/JPEG (rfc2435): baseline JPEG images with 8-bit quantization tables,
+//! standard Huffman tables and no restart markers, which is what the ESP32 camera driver
+//! produces. The quantization tables are sent in-band with every frame (Q = 255).
+//!
+//! The ESP32 variants supported by micro-rdk have no hardware H.264 encoder so MJPEG is the
+//! only codec offered. Note that most browsers can't decode RTP/JPEG, the track is meant for
+//! clients (the Viam app stream service or gstreamer based viewers) that do.
+
+use std::time::Instant;
+
+use bytes::{BufMut, BytesMut};
+use thiserror::Error;
+
+use super::{
+    srtp::{SrtpContext, SrtpError},
+    udp_mux::RtpSender,
+};
+
+/// Static payload type of JPEG (rfc3551)
+pub const JPEG_PAYLOAD_TYPE: u8 = 26;
+/// Clock rate of video RTP timestamps
+pub const VIDEO_CLOCK_RATE: u32 = 90000;
+
+const RTP_HEADER_LEN: usize = 12;
+const JPEG_HEADER_LEN: usize = 8;
+const QTABLE_HEADER_LEN: usize = 4;
+// leaves room for the SRTP authentication tag and the IP/UDP headers within a 1280 bytes
+// IPv6 minimum MTU
+const VIDEO_MTU: usize = 1180;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RtpError {
+    #[error("not a JPEG image")]
+    NotJpeg,
+    #[error("truncated JPEG image")]
+    TruncatedJpeg,
+    #[error("unsupported JPEG image: {0}")]
+    UnsupportedJpeg(&'static str),
+    #[error("mtu too small")]
+    MtuTooSmall,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtpHeader {
+    pub payload_type: u8,
+    pub marker: bool,
+    pub sequence_number: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+}
+
+impl RtpHeader {
+    fn write(&self, buf: &mut BytesMut) {
+        // version 2, no padding, no extension, no CSRC
+        buf.put_u8(0x80);
+        buf.put_u8(((self.marker as u8) << 7) | (self.payload_type & 0x7f));
+        buf.put_u16(self.sequence_number);
+        buf.put_u32(self.timestamp);
+        buf.put_u32(self.ssrc);
+    }
+}
+
+/// The parts of a baseline JPEG image needed to build its RTP payload
+#[derive(Debug, PartialEq)]
+pub(crate) struct JpegFrame<'a> {
+    /// rfc2435 type, 0 for 4:2:2 and 1 for 4:2:0 chroma subsampling
+    r#type: u8,
+    width: u16,
+    height: u16,
+    qtables: Vec<&'a [u8]>,
+    scan: &'a [u8],
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, RtpError> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(RtpError::TruncatedJpeg)
+}
+
+impl<'a> JpegFrame<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Result<Self, RtpError> {
+        if data.len() < 4 || data[0..2] != [0xff, 0xd8] {
+            return Err(RtpError::NotJpeg);
+        }
+        let mut qtables: Vec<(u8, &[u8])> = vec![];
+        let mut size = None;
+        let mut pos = 2;
+        loop {
+            if *data.get(pos).ok_or(RtpError::TruncatedJpeg)? != 0xff {
+                return Err(RtpError::NotJpeg);
+            }
+            let marker = *data.get(pos + 1).ok_or(RtpError::TruncatedJpeg)?;
+            let len = read_u16(data, pos + 2)? as usize;
+            let segment = data
+                .get(pos + 4..pos + 2 + len)
+                .ok_or(RtpError::TruncatedJpeg)?;
+            match marker {
+                // DQT, may contain several tables
+                0xdb => {
+                    let mut table = segment;
+                    while !table.is_empty() {
+                        if table[0] >> 4 != 0 {
+                            return Err(RtpError::UnsupportedJpeg("16-bit quantization table"));
+                        }
+                        let values = table.get(1..65).ok_or(RtpError::TruncatedJpeg)?;
+                        qtables.push((table[0] & 0x0f, values));
+                        table = &table[65..];
+                    }
+                }
+                // SOF0, baseline
+                0xc0 => {
+                    if segment.len() < 15 || segment[5] != 3 {
+                        return Err(RtpError::UnsupportedJpeg("expected 3 components"));
+                    }
+                    let height = read_u16(segment, 1)?;
+                    let width = read_u16(segment, 3)?;
+                    let r#type = match (segment[7], segment[10], segment[13]) {
+                        (0x21, 0x11, 0x11) => 0,
+                        (0x22, 0x11, 0x11) => 1,
+                        _ => return Err(RtpError::UnsupportedJpeg("chroma subsampling")),
+                    };
+                    size = Some((r#type, width, height));
+                }
+                0xc1..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                    return Err(RtpError::UnsupportedJpeg("not a baseline JPEG"));
+                }
+                // DRI
+                0xdd if read_u16(segment, 0)? != 0 => {
+                    return Err(RtpError::UnsupportedJpeg("restart markers"));
+                }
+                // SOS, the entropy coded data follows up to the EOI marker
+                0xda => {
+                    let start = pos + 2 + len;
+                    let mut end = data.len();
+                    if data.len() >= start + 2 && data[data.len() - 2..] == [0xff, 0xd9] {
+                        end -= 2;
+                    }
+                    let (r#type, width, height) =
+                        size.ok_or(RtpError::UnsupportedJpeg("missing frame header"))?;
+                    if width > 2040 || height > 2040 {
+                        return Err(RtpError::UnsupportedJpeg("image larger than 2040 pixels"));
+                    }
+                    qtables.sort_by_key(|(id, _)| *id);
+                    return Ok(Self {
+                        r#type,
+                        width,
+                        height,
+                        qtables: qtables.into_iter().map(|(_, table)| table).collect(),
+                        scan: &data[start.min(end)..end],
+                    });
+                }
+                _ => {}
+            }
+            pos += 2 + len;
+        }
+    }
+}
+
+/// Splits JPEG frames into RTP packets of at most `mtu` bytes
+pub struct JpegPacketizer {
+    mtu: usize,
+    ssrc: u32,
+    sequence_number: u16,
+}
+
+impl JpegPacketizer {
+    pub fn new(mtu: usize, ssrc: u32) -> Self {
+        Self {
+            mtu,
+            ssrc,
+            sequence_number: rand::random(),
+        }
+    }
+
+    /// Returns the RTP packets carrying the JPEG image `jpeg` captured at `timestamp` (in
+    /// [VIDEO_CLOCK_RATE] units)
+    pub fn packetize(&mut self, jpeg: &[u8], timestamp: u32) -> Result<Vec<BytesMut>, RtpError> {
+        let frame = JpegFrame::parse(jpeg)?;
+        let qtables_len: usize = frame.qtables.iter().map(|t| t.len()).sum();
+        let first_overhead = RTP_HEADER_LEN + JPEG_HEADER_LEN + QTABLE_HEADER_LEN + qtables_len;
+        if self.mtu <= first_overhead {
+            return Err(RtpError::MtuTooSmall);
+        }
+        let mut packets = vec![];
+        let mut offset = 0;
+        loop {
+            let first = offset == 0;
+            let overhead = if first {
+                first_overhead
+            } else {
+                RTP_HEADER_LEN + JPEG_HEADER_LEN
+            };
+            let len = (self.mtu - overhead).min(frame.scan.len() - offset);
+            let last = offset + len == frame.scan.len();
+            let mut packet = BytesMut::with_capacity(overhead + len);
+            RtpHeader {
+                payload_type: JPEG_PAYLOAD_TYPE,
+                marker: last,
+                sequence_number: self.sequence_number,
+                timestamp,
+                ssrc: self.ssrc,
+            }
+            .write(&mut packet);
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+            // type specific, then the 24-bit fragment offset
+            packet.put_u32(offset as u32 & 0x00ff_ffff);
+            packet.put_u8(frame.r#type);
+            // Q = 255, quantization tables are sent in the first packet
+            packet.put_u8(255);
+            packet.put_u8((frame.width / 8) as u8);
+            packet.put_u8((frame.height / 8) as u8);
+            if first {
+                // MBZ, 8-bit precision for all tables
+                packet.put_u8(0);
+                packet.put_u8(0);
+                packet.put_u16(qtables_len as u16);
+                for table in frame.qtables.iter() {
+                    packet.put_slice(table);
+                }
+            }
+            packet.put_slice(&frame.scan[offset..offset + len]);
+            packets.push(packet);
+            offset += len;
+            if last {
+                return Ok(packets);
+            }
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VideoTrackError {
+    #[error(transparent)]
+    RtpError(#[from] RtpError),
+    #[error(transparent)]
+    SrtpError(#[from] SrtpError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// A sendonly video track negotiated in the SDP answer, streaming the frames of a camera
+pub struct VideoTrack {
+    camera: String,
+    packetizer: JpegPacketizer,
+    srtp: SrtpContext,
+    sender: RtpSender,
+    start: Instant,
+    timestamp_offset: u32,
+}
+
+impl VideoTrack {
+    pub(crate) fn new(camera: String, ssrc: u32, srtp: SrtpContext, sender: RtpSender) -> Self {
+        Self {
+            camera,
+            packetizer: JpegPacketizer::new(VIDEO_MTU, ssrc),
+            srtp,
+            sender,
+            start: Instant::now(),
+            timestamp_offset: rand::random(),
+        }
+    }
+
+    /// Name of the camera requested by the peer
+    pub fn camera(&self) -> &str {
+        &self.camera
+    }
+
+    /// Packetizes, encrypts and sends a JPEG frame, timestamped with the current time
+    pub async fn send_jpeg(&mut self, jpeg: &[u8]) -> Result<(), VideoTrackError> {
+        let elapsed = self.start.elapsed().as_millis() as u64 * (VIDEO_CLOCK_RATE / 1000) as u64;
+        let timestamp = self.timestamp_offset.wrapping_add(elapsed as u32);
+        for mut packet in self.packetizer.packetize(jpeg, timestamp)? {
+            self.srtp.protect(&mut packet)?;
+            self.sender.send(&packet).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // smallest structure of a baseline 4:2:0 JPEG with two quantization tables
+    fn jpeg(width: u16, height: u16, scan: &[u8]) -> Vec<u8> {
+        let mut jpeg = vec![0xff, 0xd8];
+        // DQT with both tables
+        jpeg.extend_from_slice(&[0xff, 0xdb, 0x00, 132]);
+        jpeg.push(0x00);
+        jpeg.extend_from_slice(&[1; 64]);
+        jpeg.push(0x01);
+        jpeg.extend_from_slice(&[2; 64]);
+        // SOF0
+        jpeg.extend_from_slice(&[0xff, 0xc0, 0x00, 17, 8]);
+        jpeg.extend_from_slice(&height.to_be_bytes());
+        jpeg.extend_from_slice(&width.to_be_bytes());
+        jpeg.extend_from_slice(&[3, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        // SOS
+        jpeg.extend_from_slice(&[0xff, 0xda, 0x00, 12, 3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+        jpeg.extend_from_slice(scan);
+        jpeg.extend_from_slice(&[0xff, 0xd9]);
+        jpeg
+    }
+
+    #[test_log::test]
+    fn test_parse_jpeg() {
+        let scan = [0xaa; 10];
+        let data = jpeg(320, 240, &scan);
+        let frame = JpegFrame::parse(&data).unwrap();
+        assert_eq!(frame.r#type, 1);
+        assert_eq!(frame.width, 320);
+        assert_eq!(frame.height, 240);
+        assert_eq!(frame.qtables, vec![&[1_u8; 64][..], &[2_u8; 64][..]]);
+        assert_eq!(frame.scan, &scan);
+
+        assert_eq!(
+            JpegFrame::parse(&[0x89, 0x50, 0x4e, 0x47]),
+            Err(RtpError::NotJpeg)
+        );
+        assert_eq!(JpegFrame::parse(&data[..40]), Err(RtpError::TruncatedJpeg));
+    }
+
+    #[test_log::test]
+    fn test_packetize_jpeg() {
+        let scan: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let data = jpeg(640, 480, &scan);
+        let mut packetizer = JpegPacketizer::new(500, 0x1234);
+        let first_seq = packetizer.sequence_number;
+        let packets = packetizer.packetize(&data, 9000).unwrap();
+
+        // 500 - 12 - 8 - 4 - 128 = 348 bytes of scan data in the first packet, then 480
+        assert_eq!(packets.len(), 3);
+        let mut reassembled = vec![];
+        for (i, packet) in packets.iter().enumerate() {
+            assert!(packet.len() <= 500);
+            assert_eq!(packet[0], 0x80);
+            assert_eq!(packet[1] & 0x7f, JPEG_PAYLOAD_TYPE);
+            // marker bit on the last packet only
+            assert_eq!(packet[1] >> 7 == 1, i == packets.len() - 1);
+            assert_eq!(
+                u16::from_be_bytes([packet[2], packet[3]]),
+                first_seq.wrapping_add(i as u16)
+            );
+            assert_eq!(u32::from_be_bytes(packet[4..8].try_into().unwrap()), 9000);
+            assert_eq!(
+                u32::from_be_bytes(packet[8..12].try_into().unwrap()),
+                0x1234
+            );
+            let offset = u32::from_be_bytes(packet[12..16].try_into().unwrap());
+            assert_eq!(offset as usize, reassembled.len());
+            assert_eq!(&packet[16..20], &[1, 255, 80, 60]);
+            let payload = if i == 0 {
+                assert_eq!(u16::from_be_bytes([packet[22], packet[23]]), 128);
+                &packet[24 + 128..]
+            } else {
+                &packet[20..]
+            };
+            reassembled.extend_from_slice(payload);
+        }
+        assert_eq!(reassembled, scan);
+
+        let mut packetizer = JpegPacketizer::new(100, 0x1234);
+        assert_eq!(packetizer.packetize(&data, 0), Err(RtpError::MtuTooSmall));
+    }
+}
@@ -1,24 +1,161 @@
 #![allow(dead_code)]
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     fmt::Debug,
     io,
     net::SocketAddr,
     sync::{Arc, Mutex},
     task::{Poll, Waker},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use async_channel::Sender;
 use bytes::Bytes;
 use thiserror::Error;
 
-use futures_lite::{future::poll_fn, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use futures_lite::{
+    future::poll_fn, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, FutureExt,
+};
 use sctp_proto::{
     Association, AssociationHandle, ClientConfig, DatagramEvent, Endpoint, EndpointConfig, Event,
     Payload, ServerConfig, StreamEvent, StreamId, Transmit,
 };
 
+use crate::common::telemetry::record_telemetry;
+
+/// Writes wait while the peer hasn't acknowledged this many bytes, so a large response doesn't
+/// pile up in the association's queues and the small socket buffers of the ESP32
+const MAX_BUFFERED_AMOUNT: usize = 8 * 1024;
+/// A write waiting for the peer to acknowledge data fails after this long
+const WRITE_STALL_TIMEOUT: Duration = Duration::from_secs(10);
+/// Interval at which the statistics of the association are recorded for the telemetry sensor
+const TELEMETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+const SCTP_COMMON_HEADER_LEN: usize = 12;
+const DATA_CHUNK_HEADER_LEN: usize = 16;
+const CHUNK_TYPE_DATA: u8 = 0;
+const CHUNK_TYPE_SACK: u8 = 3;
+
+/// Statistics of an association, see [Channel::stats]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SctpStats {
+    /// Smoothed round trip time, measured on data chunks acknowledged without being resent
+    pub rtt: Option<Duration>,
+    /// Number of data chunks sent again
+    pub retransmissions: u64,
+    /// Bytes written to the channel the peer hasn't acknowledged yet
+    pub buffered_amount: usize,
+}
+
+struct InFlightChunk {
+    tsn: u32,
+    sent_at: Instant,
+    len: usize,
+    retransmitted: bool,
+}
+
+// whether TSN `a` comes before or is `b`, TSNs wrap around (RFC 4960 section 1.6)
+fn tsn_lte(a: u32, b: u32) -> bool {
+    b.wrapping_sub(a) < 1 << 31
+}
+
+// iterates over the type and content of the chunks of an SCTP packet
+fn chunks(packet: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    let mut offset = SCTP_COMMON_HEADER_LEN;
+    std::iter::from_fn(move || {
+        let header = packet.get(offset..offset + 4)?;
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let chunk = packet.get(offset..offset + len).filter(|_| len >= 4)?;
+        // chunks are padded to 4 bytes
+        offset += (len + 3) & !3;
+        Some((header[0], chunk))
+    })
+}
+
+/// Follows the data chunks sent on the association and their acknowledgments. The packets are
+/// inspected as they go through the transport, sctp-proto doesn't expose these figures.
+#[derive(Default)]
+struct SctpMetrics {
+    in_flight: VecDeque<InFlightChunk>,
+    highest_tsn: Option<u32>,
+    srtt: Option<Duration>,
+    retransmissions: u64,
+    written: usize,
+    acked: usize,
+    writer: Option<Waker>,
+}
+
+impl SctpMetrics {
+    fn buffered_amount(&self) -> usize {
+        self.written.saturating_sub(self.acked)
+    }
+
+    fn stats(&self) -> SctpStats {
+        SctpStats {
+            rtt: self.srtt,
+            retransmissions: self.retransmissions,
+            buffered_amount: self.buffered_amount(),
+        }
+    }
+
+    fn on_packet_sent(&mut self, packet: &[u8], now: Instant) {
+        for (_, chunk) in chunks(packet).filter(|(kind, _)| *kind == CHUNK_TYPE_DATA) {
+            let Some(tsn) = chunk.get(4..8) else {
+                continue;
+            };
+            let tsn = u32::from_be_bytes([tsn[0], tsn[1], tsn[2], tsn[3]]);
+            match self.highest_tsn {
+                Some(highest) if tsn_lte(tsn, highest) => {
+                    self.retransmissions += 1;
+                    if let Some(chunk) = self.in_flight.iter_mut().find(|c| c.tsn == tsn) {
+                        chunk.retransmitted = true;
+                    }
+                }
+                _ => {
+                    self.highest_tsn = Some(tsn);
+                    self.in_flight.push_back(InFlightChunk {
+                        tsn,
+                        sent_at: now,
+                        len: chunk.len().saturating_sub(DATA_CHUNK_HEADER_LEN),
+                        retransmitted: false,
+                    });
+                }
+            }
+        }
+    }
+
+    fn on_packet_received(&mut self, packet: &[u8], now: Instant) {
+        for (_, chunk) in chunks(packet).filter(|(kind, _)| *kind == CHUNK_TYPE_SACK) {
+            let Some(ack) = chunk.get(4..8) else {
+                continue;
+            };
+            let cumulative_ack = u32::from_be_bytes([ack[0], ack[1], ack[2], ack[3]]);
+            let mut sample = None;
+            while let Some(chunk) = self
+                .in_flight
+                .front()
+                .filter(|c| tsn_lte(c.tsn, cumulative_ack))
+            {
+                // Karn's algorithm, the ack of a resent chunk can't be matched to a send
+                if !chunk.retransmitted {
+                    sample = Some(now.saturating_duration_since(chunk.sent_at));
+                }
+                self.acked += chunk.len;
+                self.in_flight.pop_front();
+            }
+            if let Some(sample) = sample {
+                // RFC 6298 smoothing
+                self.srtt = Some(self.srtt.map_or(sample, |srtt| srtt * 7 / 8 + sample / 8));
+            }
+            if self.buffered_amount() < MAX_BUFFERED_AMOUNT {
+                if let Some(writer) = self.writer.take() {
+                    writer.wake();
+                }
+            }
+        }
+    }
+}
+
 //#[derive(Clone)]
 struct SctpStream {
     waker: Option<Waker>,
@@ -39,19 +176,44 @@ pub struct Channel {
     rx_channel: Arc<Mutex<SctpStream>>,
     association: Arc<Mutex<Association>>,
     closed: Arc<Mutex<bool>>,
+    metrics: Arc<Mutex<SctpMetrics>>,
 }
 
 impl Channel {
+    /// Writes a message on the channel, waiting first for the peer to acknowledge enough of
+    /// the data already written
     pub async fn write(&self, buf: &[u8]) -> std::io::Result<()> {
-        if *self.closed.lock().unwrap() {
-            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
-        }
+        self.wait_buffered_amount_low().await?;
+        self.metrics.lock().unwrap().written += buf.len();
         let bytes = Bytes::copy_from_slice(buf);
         self.tx_event
             .send(SctpEvent::OutgoingStreamData((self.tx_stream_id, bytes)))
             .await
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
     }
+
+    async fn wait_buffered_amount_low(&self) -> std::io::Result<()> {
+        poll_fn(|cx| {
+            if *self.closed.lock().unwrap() {
+                return Poll::Ready(Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+            }
+            let mut metrics = self.metrics.lock().unwrap();
+            if metrics.buffered_amount() < MAX_BUFFERED_AMOUNT {
+                return Poll::Ready(Ok(()));
+            }
+            let _ = metrics.writer.insert(cx.waker().clone());
+            Poll::Pending
+        })
+        .or(async {
+            async_io::Timer::after(WRITE_STALL_TIMEOUT).await;
+            Err(std::io::Error::from(std::io::ErrorKind::TimedOut))
+        })
+        .await
+    }
+
+    pub fn stats(&self) -> SctpStats {
+        self.metrics.lock().unwrap().stats()
+    }
 }
 
 impl AsyncRead for Channel {
@@ -184,6 +346,7 @@ where
             sctp_event_tx,
             channels: HashMap::new(),
             channels_rx: self.channels_rx,
+            metrics: Arc::new(Mutex::new(SctpMetrics::default())),
         })
     }
 
@@ -223,6 +386,7 @@ where
             sctp_event_tx,
             channels: HashMap::new(),
             channels_rx: self.channels_rx,
+            metrics: Arc::new(Mutex::new(SctpMetrics::default())),
         })
     }
 }
@@ -250,6 +414,7 @@ pub struct SctpProto<S> {
     sctp_event_tx: async_channel::Sender<SctpEvent>,
     channels: HashMap<ChannelId, Channel>,
     channels_rx: Sender<Channel>,
+    metrics: Arc<Mutex<SctpMetrics>>,
 }
 
 impl<S> Drop for SctpProto<S> {
@@ -277,6 +442,10 @@ where
                 let mut ret = 0;
                 {
                     for payload in data {
+                        self.metrics
+                            .lock()
+                            .unwrap()
+                            .on_packet_sent(&payload, Instant::now());
                         let mut transport =
                             unsafe { std::pin::Pin::new_unchecked(&mut self.transport) };
                         ret += poll_fn(|cx| transport.as_mut().poll_write(cx, &payload)).await?;
@@ -317,6 +486,7 @@ where
                                 rx_channel: Arc::new(Mutex::new(SctpStream { waker: None })),
                                 closed: Arc::new(Mutex::new(false)),
                                 association: self.association.clone(),
+                                metrics: self.metrics.clone(),
                             };
                             self.channels.insert(ChannelId(0), c.clone());
                             if let Err(e) = self.channels_rx.try_send(c) {
@@ -350,6 +520,19 @@ where
         Ok(())
     }
 
+    pub fn stats(&self) -> SctpStats {
+        self.metrics.lock().unwrap().stats()
+    }
+
+    fn record_telemetry(&self) {
+        let stats = self.stats();
+        if let Some(rtt) = stats.rtt {
+            record_telemetry("webrtc_rtt_ms", rtt.as_secs_f64() * 1000.0);
+        }
+        record_telemetry("webrtc_retransmissions", stats.retransmissions);
+        record_telemetry("webrtc_buffered_bytes", stats.buffered_amount);
+    }
+
     pub fn get_handle(&self) -> SctpHandle {
         SctpHandle {
             sctp_event_tx: self.sctp_event_tx.clone(),
@@ -389,6 +572,7 @@ where
     }
     pub async fn run(&mut self) {
         let mut sctp_timeout = None;
+        let mut next_report = Instant::now() + TELEMETRY_INTERVAL;
         loop {
             let mut buf = [0; 1500];
             let timeout = sctp_timeout
@@ -433,6 +617,10 @@ where
 
             match event {
                 SctpEvent::IncomingData((from, data)) => {
+                    self.metrics
+                        .lock()
+                        .unwrap()
+                        .on_packet_received(&data, Instant::now());
                     if let Some((hnd, ev)) =
                         self.endpoint.handle(Instant::now(), from, None, None, data)
                     {
@@ -478,6 +666,11 @@ where
                 //log::error!("next timeout {:?}", timeout);
                 let _ = sctp_timeout.insert(timeout);
             }
+
+            if Instant::now() >= next_report {
+                self.record_telemetry();
+                next_report = Instant::now() + TELEMETRY_INTERVAL;
+            }
         }
 
         for channel in &self.channels {
@@ -486,6 +679,9 @@ where
                 waker.wake_by_ref();
             }
         }
+        if let Some(writer) = self.metrics.lock().unwrap().writer.take() {
+            writer.wake();
+        }
         let _ = self.sctp_event_tx.close();
         let _ = self.sctp_event_rx.close();
     }
@@ -504,9 +700,11 @@ mod tests {
     use std::pin::Pin;
     use std::sync::Arc;
     use std::task::Poll;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
-    use crate::common::webrtc::sctp::SctpConnector;
+    use crate::common::webrtc::sctp::{
+        tsn_lte, SctpConnector, SctpMetrics, CHUNK_TYPE_DATA, CHUNK_TYPE_SACK,
+    };
     use async_io::{Async, Timer};
     use futures_lite::future::block_on;
     use futures_lite::AsyncReadExt;
@@ -604,6 +802,58 @@ mod tests {
         }
     }
 
+    fn packet(chunks: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut packet = vec![0; 12];
+        for (kind, body) in chunks {
+            packet.extend([*kind, 0]);
+            packet.extend((body.len() as u16 + 4).to_be_bytes());
+            packet.extend(body);
+            packet.resize((packet.len() + 3) & !3, 0);
+        }
+        packet
+    }
+
+    fn data(tsn: u32, len: usize) -> (u8, Vec<u8>) {
+        let mut body = tsn.to_be_bytes().to_vec();
+        body.resize(12 + len, 0);
+        (CHUNK_TYPE_DATA, body)
+    }
+
+    fn sack(cumulative_ack: u32) -> (u8, Vec<u8>) {
+        let mut body = cumulative_ack.to_be_bytes().to_vec();
+        body.resize(12, 0);
+        (CHUNK_TYPE_SACK, body)
+    }
+
+    #[test_log::test]
+    fn test_sctp_metrics() {
+        assert!(tsn_lte(u32::MAX, 0));
+        assert!(!tsn_lte(0, u32::MAX));
+
+        let start = Instant::now();
+        let mut metrics = SctpMetrics {
+            written: 300,
+            ..Default::default()
+        };
+        metrics.on_packet_sent(&packet(&[data(1, 100), data(2, 100)]), start);
+        metrics.on_packet_sent(&packet(&[data(3, 100)]), start);
+        assert_eq!(metrics.stats().buffered_amount, 300);
+        assert_eq!(metrics.stats().rtt, None);
+
+        metrics.on_packet_received(&packet(&[sack(1)]), start + Duration::from_millis(40));
+        assert_eq!(metrics.stats().buffered_amount, 200);
+        assert_eq!(metrics.stats().rtt, Some(Duration::from_millis(40)));
+
+        // chunk 2 got lost, its ack doesn't make a sample
+        metrics.on_packet_sent(&packet(&[data(2, 100)]), start + Duration::from_millis(100));
+        assert_eq!(metrics.stats().retransmissions, 1);
+        metrics.on_packet_received(&packet(&[sack(3)]), start + Duration::from_millis(200));
+        let stats = metrics.stats();
+        assert_eq!(stats.buffered_amount, 0);
+        assert_eq!(stats.rtt, Some(Duration::from_millis(60)));
+        assert_eq!(stats.retransmissions, 1);
+    }
+
     #[test_log::test]
     fn test_sctp() {
         let local_ex = Arc::new(Executor::new());
@@ -0,0 +1,197 @@
+//! Outbound SRTP (rfc3711) with the SRTP_AES128_CM_HMAC_SHA1_80 protection profile, the
+//! profile negotiated by both DTLS implementations. Keys are derived from the keying material
+//! exported by the DTLS handshake (rfc5764).
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use bytes::{BufMut, BytesMut};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use thiserror::Error;
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+type HmacSha1 = Hmac<Sha1>;
+
+const MASTER_KEY_LEN: usize = 16;
+const MASTER_SALT_LEN: usize = 14;
+const AUTH_KEY_LEN: usize = 20;
+const AUTH_TAG_LEN: usize = 10;
+const RTP_HEADER_LEN: usize = 12;
+
+/// Length of the keying material to export from the DTLS session for this profile
+pub const SRTP_KEYING_MATERIAL_LEN: usize = 2 * (MASTER_KEY_LEN + MASTER_SALT_LEN);
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SrtpError {
+    #[error("packet too short")]
+    PacketTooShort,
+    #[error("packets with CSRCs or header extensions are not supported")]
+    UnsupportedHeader,
+}
+
+/// Encryption context of one outgoing SRTP stream
+pub struct SrtpContext {
+    session_key: [u8; MASTER_KEY_LEN],
+    session_salt: [u8; MASTER_SALT_LEN],
+    session_auth: [u8; AUTH_KEY_LEN],
+    roll_over_counter: u32,
+    last_sequence_number: Option<u16>,
+}
+
+fn aes_cm(key: &[u8; MASTER_KEY_LEN], iv: &[u8; 16], data: &mut [u8]) {
+    let mut cipher = Aes128Ctr::new(key.into(), iv.into());
+    cipher.apply_keystream(data);
+}
+
+impl SrtpContext {
+    pub fn new(master_key: &[u8; MASTER_KEY_LEN], master_salt: &[u8; MASTER_SALT_LEN]) -> Self {
+        // key derivation rate is 0, so the index never contributes to the derivation
+        let derive = |label: u8, out: &mut [u8]| {
+            let mut iv = [0_u8; 16];
+            iv[..MASTER_SALT_LEN].copy_from_slice(master_salt);
+            iv[7] ^= label;
+            out.fill(0);
+            aes_cm(master_key, &iv, out);
+        };
+        let mut session_key = [0; MASTER_KEY_LEN];
+        let mut session_auth = [0; AUTH_KEY_LEN];
+        let mut session_salt = [0; MASTER_SALT_LEN];
+        derive(0, &mut session_key);
+        derive(1, &mut session_auth);
+        derive(2, &mut session_salt);
+        Self {
+            session_key,
+            session_salt,
+            session_auth,
+            roll_over_counter: 0,
+            last_sequence_number: None,
+        }
+    }
+
+    /// Builds the context protecting the packets we send from DTLS-SRTP keying material. The
+    /// material is laid out as client key, server key, client salt and server salt, and we are
+    /// always the DTLS server.
+    pub fn from_keying_material(material: &[u8; SRTP_KEYING_MATERIAL_LEN]) -> Self {
+        let (keys, salts) = material.split_at(2 * MASTER_KEY_LEN);
+        Self::new(
+            keys[MASTER_KEY_LEN..].try_into().unwrap(),
+            salts[MASTER_SALT_LEN..].try_into().unwrap(),
+        )
+    }
+
+    /// Encrypts the payload of an RTP packet in place and appends its authentication tag
+    pub fn protect(&mut self, packet: &mut BytesMut) -> Result<(), SrtpError> {
+        if packet.len() < RTP_HEADER_LEN {
+            return Err(SrtpError::PacketTooShort);
+        }
+        if packet[0] & 0x1f != 0 {
+            return Err(SrtpError::UnsupportedHeader);
+        }
+        let sequence_number = u16::from_be_bytes([packet[2], packet[3]]);
+        if let Some(last) = self.last_sequence_number {
+            if sequence_number < last && last - sequence_number > 0x8000 {
+                self.roll_over_counter = self.roll_over_counter.wrapping_add(1);
+            }
+        }
+        self.last_sequence_number = Some(sequence_number);
+        let index = ((self.roll_over_counter as u64) << 16) | sequence_number as u64;
+
+        let mut iv = [0_u8; 16];
+        iv[..MASTER_SALT_LEN].copy_from_slice(&self.session_salt);
+        for (iv, ssrc) in iv[4..8].iter_mut().zip(&packet[8..12]) {
+            *iv ^= ssrc;
+        }
+        for (iv, index) in iv[8..14].iter_mut().zip(&index.to_be_bytes()[2..]) {
+            *iv ^= index;
+        }
+        aes_cm(&self.session_key, &iv, &mut packet[RTP_HEADER_LEN..]);
+
+        let mut mac = HmacSha1::new_from_slice(&self.session_auth).unwrap();
+        mac.update(packet);
+        mac.update(&self.roll_over_counter.to_be_bytes());
+        let tag = mac.finalize().into_bytes();
+        packet.put_slice(&tag[..AUTH_TAG_LEN]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test_log::test]
+    fn test_key_derivation() {
+        // rfc3711 appendix B.3
+        let ctx = SrtpContext::new(
+            &from_hex("E1F97A0D3E018BE0D64FA32C06DE4139")
+                .try_into()
+                .unwrap(),
+            &from_hex("0EC675AD498AFEEBB6960B3AABE6").try_into().unwrap(),
+        );
+        assert_eq!(
+            ctx.session_key.to_vec(),
+            from_hex("c61e7a93744f39ee10734afe3ff7a087")
+        );
+        assert_eq!(
+            ctx.session_salt.to_vec(),
+            from_hex("30cbbc08863d8c85d49db34a9ae1")
+        );
+        assert_eq!(
+            ctx.session_auth.to_vec(),
+            from_hex("cebe321f6ff7716b6fd4ab49af256a156d38baa4")
+        );
+    }
+
+    #[test_log::test]
+    fn test_protect() {
+        let mut ctx = SrtpContext::new(
+            &from_hex("E1F97A0D3E018BE0D64FA32C06DE4139")
+                .try_into()
+                .unwrap(),
+            &from_hex("0EC675AD498AFEEBB6960B3AABE6").try_into().unwrap(),
+        );
+        let payload = [0xab; 16];
+
+        let mut packet = BytesMut::from(&from_hex("800ffff0decafbadcafebabe")[..]);
+        packet.put_slice(&payload);
+        ctx.protect(&mut packet).unwrap();
+        assert_eq!(packet.len(), RTP_HEADER_LEN + payload.len() + AUTH_TAG_LEN);
+
+        let mut packet = BytesMut::from(&from_hex("800f1234decafbadcafebabe")[..]);
+        packet.put_slice(&payload);
+        let mut ctx_no_roc = SrtpContext::new(
+            &from_hex("E1F97A0D3E018BE0D64FA32C06DE4139")
+                .try_into()
+                .unwrap(),
+            &from_hex("0EC675AD498AFEEBB6960B3AABE6").try_into().unwrap(),
+        );
+        ctx_no_roc.protect(&mut packet).unwrap();
+        assert_eq!(
+            packet.to_vec(),
+            from_hex(
+                "800f1234decafbadcafebabe4e55dc4ce79978d88ca4d215949d2402b78d6acc99ea179b8dbb"
+            )
+        );
+
+        // the sequence number wrapped after 0xfff0, the roll over counter is now 1
+        let mut packet = BytesMut::from(&from_hex("800f0001decafbadcafebabe")[..]);
+        packet.put_slice(&payload);
+        ctx.protect(&mut packet).unwrap();
+        assert_eq!(ctx.roll_over_counter, 1);
+        assert_eq!(
+            packet.to_vec(),
+            from_hex(
+                "800f0001decafbadcafebabeb6f1f0a458a238ac3d2f55ac6566b25c051f89495037505c44c7"
+            )
+        );
+
+        let mut packet = BytesMut::from(&from_hex("900f0001decafbadcafebabe")[..]);
+        assert_eq!(ctx.protect(&mut packet), Err(SrtpError::UnsupportedHeader));
+    }
+}
@@ -16,6 +16,8 @@ use futures_lite::{ready, AsyncRead, AsyncWrite, Future, FutureExt};
 enum MuxDirection {
     DTLS,
     STUN,
+    // RTP and RTCP, we only send media so incoming packets (RTCP reports) are discarded
+    RTP,
     // This is the default value it's a placeholder so we panic if for some reason we try
     // to index with this.
     //TODO remove once testing is done
@@ -28,6 +30,7 @@ impl Index<MuxDirection> for [MuxState] {
         match index {
             MuxDirection::DTLS => &self[0],
             MuxDirection::STUN => &self[1],
+            MuxDirection::RTP => &self[2],
             MuxDirection::NODIR => panic!(),
         }
     }
@@ -37,6 +40,7 @@ impl IndexMut<MuxDirection> for [MuxState] {
         match index {
             MuxDirection::DTLS => &mut self[0],
             MuxDirection::STUN => &mut self[1],
+            MuxDirection::RTP => &mut self[2],
             MuxDirection::NODIR => panic!(),
         }
     }
@@ -45,7 +49,9 @@ impl IndexMut<MuxDirection> for [MuxState] {
 #[derive(Clone)]
 pub(crate) struct UdpMuxer {
     socket: Arc<Async<UdpSocket>>,
-    mux: Arc<Mutex<[MuxState; 2]>>,
+    mux: Arc<Mutex<[MuxState; 3]>>,
+    // address of the peer that last sent us a DTLS record, media is sent there
+    dtls_peer: Arc<Mutex<Option<SocketAddr>>>,
 }
 
 impl Drop for UdpMuxer {
@@ -103,6 +109,7 @@ impl UdpMuxer {
         Self {
             socket: socket.clone(),
             mux: Default::default(),
+            dtls_peer: Default::default(),
         }
     }
     pub(crate) fn get_stun_mux(&self) -> Option<UdpMux> {
@@ -131,6 +138,16 @@ impl UdpMuxer {
             None
         }
     }
+    pub(crate) fn get_rtp_sender(&self) -> RtpSender {
+        RtpSender {
+            muxer: self.clone(),
+        }
+    }
+    fn set_peer(&self, dir: MuxDirection, peer: SocketAddr) {
+        if dir == MuxDirection::DTLS {
+            let _ = self.dtls_peer.lock().unwrap().insert(peer);
+        }
+    }
     async fn recv_from(&self, dir: MuxDirection, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
         loop {
             let r = match self.peek() {
@@ -141,7 +158,9 @@ impl UdpMuxer {
             if r.0 != 0 {
                 if dir == r.1 {
                     let socket = self.socket.as_ref().get_ref();
-                    return socket.recv_from(buf);
+                    let (len, peer) = socket.recv_from(buf)?;
+                    self.set_peer(dir, peer);
+                    return Ok((len, peer));
                 }
                 if self.yield_or_discard(r.1, r.0)? {
                     continue;
@@ -202,6 +221,9 @@ impl UdpMuxer {
             // stun message
             let len: u16 = u16::from_be_bytes(hdr[2..4].try_into().unwrap());
             (len, MuxDirection::STUN)
+        } else if (128..192).contains(&msg_type) {
+            // RTP or RTCP packet (rfc7983), there is no length in the header
+            (hdr.len() as u16, MuxDirection::RTP)
         } else {
             // assume DTLS record
             let len: u16 = u16::from_be_bytes(hdr[11..13].try_into().unwrap());
@@ -239,7 +261,11 @@ impl UdpMuxer {
                 if dir == r.1 {
                     let socket = self.socket.as_ref().get_ref();
                     self.deregister_waker(dir);
-                    return Poll::Ready(socket.recv_from(buf));
+                    let r = socket.recv_from(buf);
+                    if let Ok((_, peer)) = r {
+                        self.set_peer(dir, peer);
+                    }
+                    return Poll::Ready(r);
                 }
 
                 match self.yield_or_discard(r.1, r.0) {
@@ -297,6 +323,24 @@ impl UdpMux {
     }
 }
 
+/// Sends RTP packets to the peer of the DTLS session, without listening for incoming packets
+pub struct RtpSender {
+    muxer: UdpMuxer,
+}
+
+impl RtpSender {
+    pub(crate) async fn send(&self, buf: &[u8]) -> Result<usize> {
+        let peer = *self.muxer.dtls_peer.lock().unwrap();
+        match peer {
+            Some(peer) => self.muxer.send_to(buf, peer).await,
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "no peer set",
+            )),
+        }
+    }
+}
+
 impl Drop for UdpMux {
     fn drop(&mut self) {
         let state = &mut self.muxer.mux.lock().unwrap()[self.direction];
@@ -416,6 +460,44 @@ mod tests {
         }
     }
 
+    #[test_log::test]
+    fn test_discard_rtcp() {
+        let local_ex = async_executor::LocalExecutor::new();
+
+        let srv_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = srv_socket.local_addr().unwrap();
+        let srv_socket = Arc::new(Async::new(srv_socket).unwrap());
+        let muxer = UdpMuxer::new(srv_socket);
+
+        let mut dtls = muxer.get_dtls_mux().unwrap();
+        let rtp = muxer.get_rtp_sender();
+        assert!(rtp.send(&[0x80; 20]).now_or_never().unwrap().is_err());
+
+        let client = async move {
+            let client_socket = Async::new(UdpSocket::bind("127.0.0.1:0").unwrap()).unwrap();
+            let client_addr = client_socket.get_ref().local_addr().unwrap();
+
+            // receiver report
+            let rtcp_pkt = dtls_packet(20, 0x81);
+            assert!(client_socket.send_to(&rtcp_pkt, addr).await.is_ok());
+            let dtls_pkt = dtls_packet(120, 23);
+            assert!(client_socket.send_to(&dtls_pkt, addr).await.is_ok());
+
+            let mut buf = [0_u8; 1500];
+            let read = dtls.read(&mut buf).await.unwrap();
+            assert_eq!(read, dtls_pkt.len());
+            assert_eq!(&buf[..read], &dtls_pkt[..]);
+
+            // media goes to the peer of the DTLS session
+            assert_eq!(rtp.send(&[0x80; 20]).await.unwrap(), 20);
+            let (len, from) = client_socket.recv_from(&mut buf).await.unwrap();
+            assert_eq!(len, 20);
+            assert_eq!(from, addr);
+            assert_eq!(*muxer.dtls_peer.lock().unwrap(), Some(client_addr));
+        };
+        futures_lite::future::block_on(local_ex.run(client));
+    }
+
     #[test_log::test]
     fn later_drop_interest() {
         let local_ex = async_executor::LocalExecutor::new();
@@ -1,14 +1,77 @@
+//! Differential drive base moved by a left and a right motor.
+//!
+//! ```json
+//! {
+//!     "name": "base",
+//!     "model": "two_wheeled_base",
+//!     "type": "base",
+//!     "attributes": {
+//!         "left": "left_motor",
+//!         "right": "right_motor",
+//!         "wheel_circumference_mm": 217,
+//!         "track_width_mm": 260
+//!     }
+//! }
+//! ```
+//!
+//! `wheel_circumference_mm` and `track_width_mm` are optional but required by `move_straight`
+//! and `spin`.
+//!
+//! The status of the base reports under `velocity` the linear (in mm/s) and angular (in degrees
+//! per second) velocities last `commanded` and `measured`. The measured velocities, which are also
+//! captured by the `LinearVelocity` and `AngularVelocity` data capture methods, are computed from
+//! the positions of the wheels: the motors report their position in revolutions, or in ticks along
+//! with their `ticks_per_rotation`. The angular velocity comes from the gyroscope of the movement
+//! sensor when the base has one. The velocities commanded with `set_power` are only known when the
+//! motors are configured with their `max_rpm`.
+//!
+//! Without more attributes `spin` relies on dead reckoning, running the wheels for the distance
+//! they travel while the base turns. With the optional `movement_sensor` attribute, naming a
+//! movement sensor reporting its angular velocity, `spin` integrates the yaw rate of its gyroscope
+//! instead, the wheels being driven by a proportional controller: the base turns at
+//! `spin_gain` (2 by default) degrees per second for every degree left, up to the requested
+//! speed, until it is within `spin_tolerance_deg` (2 by default) of the requested angle. The
+//! closed loop spin runs in the background every 20ms, `spin` returns immediately with the time
+//! the base is given to complete it, any other command (or `stop`) cancels it.
+//!
+//! A watchdog protecting jammed robots from burning their motors is enabled with the optional
+//! `watchdog` attribute, it requires motors reporting their position:
+//!
+//! ```json
+//! "watchdog": {
+//!     "min_power": 0.2,
+//!     "timeout_ms": 1000,
+//!     "max_speed": 600,
+//!     "free_spin_factor": 1.5
+//! }
+//! ```
+//!
+//! While a wheel is commanded at least `min_power` its position must change within `timeout_ms`,
+//! otherwise the wheel is stalled. When `max_speed`, the speed of a wheel at full power in
+//! position units per second, is given, a wheel turning faster than `free_spin_factor` times the
+//! speed expected for its power is free spinning. Either way the base is stopped and refuses to
+//! move until the `{"reset_watchdog": {}}` command is sent, the `watchdog_state` field of its
+//! status tells which wheel tripped the watchdog and why. The wheels are checked every
+//! 100ms by a task running alongside the robot, stopping the base doesn't depend on it being
+//! polled.
+
 use super::actuator::{Actuator, ActuatorError};
-use super::base::{Base, BaseError, BaseType, COMPONENT_NAME as BaseCompName};
-use super::config::ConfigType;
-use super::motor::{Motor, MotorType, COMPONENT_NAME as MotorCompName};
+use super::base::{Base, BaseError, BaseProperties, BaseType, COMPONENT_NAME as BaseCompName};
+use super::config::{AttributeError, ConfigType, Kind};
+use super::generic::{DoCommand, GenericError};
+use super::motor::{Motor, MotorError, MotorType, COMPONENT_NAME as MotorCompName};
+use super::movement_sensor::{
+    MovementSensor, MovementSensorType, COMPONENT_NAME as MovementSensorCompName,
+};
+use super::periodic::spawn_periodic;
 use super::registry::{ComponentRegistry, Dependency, ResourceKey};
 use super::robot::Resource;
-use super::status::{Status, StatusError};
+use super::status::{status_envelope, ComponentHealth, Status, StatusError};
+use super::struct_builder::StructBuilder;
 use crate::google;
 use crate::proto::common::v1::Vector3;
-use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub(crate) fn register_models(registry: &mut ComponentRegistry) {
     if registry
@@ -32,10 +95,225 @@ pub(crate) fn register_models(registry: &mut ComponentRegistry) {
     }
 }
 
-#[derive(DoCommand)]
+const DEFAULT_SPIN_GAIN: f64 = 2.0;
+const DEFAULT_SPIN_TOLERANCE_DEG: f64 = 2.0;
+const SPIN_CONTROL_PERIOD: Duration = Duration::from_millis(20);
+// a closed loop spin is given twice the time it takes at the requested speed, plus this margin
+const SPIN_TIMEOUT_MARGIN: Duration = Duration::from_secs(2);
+// measured velocities are averaged over at least this duration, shorter windows count too few
+// encoder ticks to be accurate
+const MIN_ODOMETRY_WINDOW: Duration = Duration::from_millis(100);
+const DEFAULT_WATCHDOG_MIN_POWER: f64 = 0.2;
+const DEFAULT_WATCHDOG_TIMEOUT: Duration = Duration::from_millis(1000);
+const DEFAULT_FREE_SPIN_FACTOR: f64 = 1.5;
+const WATCHDOG_PERIOD: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogState {
+    Ok,
+    Stalled,
+    FreeSpinning,
+}
+
+impl WatchdogState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Stalled => "stalled",
+            Self::FreeSpinning => "free_spinning",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchdogConfig {
+    /// Power (in absolute value) above which a wheel is expected to turn
+    pub min_power: f64,
+    /// How long a wheel may diverge from its command before the base is stopped
+    pub timeout: Duration,
+    /// Speed of a wheel at full power in position units per second, enables the detection of
+    /// free spinning wheels
+    pub max_speed: Option<f64>,
+    pub free_spin_factor: f64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            min_power: DEFAULT_WATCHDOG_MIN_POWER,
+            timeout: DEFAULT_WATCHDOG_TIMEOUT,
+            max_speed: None,
+            free_spin_factor: DEFAULT_FREE_SPIN_FACTOR,
+        }
+    }
+}
+
+impl TryFrom<&Kind> for WatchdogConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let optional = |key: &str| -> Result<Option<f64>, AttributeError> {
+            match value.get(key)? {
+                Some(kind) => {
+                    let value: f64 = kind.try_into()?;
+                    if !(value.is_finite() && value > 0.0) {
+                        return Err(AttributeError::ConversionImpossibleError);
+                    }
+                    Ok(Some(value))
+                }
+                None => Ok(None),
+            }
+        };
+        let default = Self::default();
+        Ok(Self {
+            min_power: optional("min_power")?.unwrap_or(default.min_power),
+            timeout: optional("timeout_ms")?
+                .map_or(default.timeout, |ms| Duration::from_secs_f64(ms / 1000.0)),
+            max_speed: optional("max_speed")?,
+            free_spin_factor: optional("free_spin_factor")?.unwrap_or(default.free_spin_factor),
+        })
+    }
+}
+
+// Compares the movement of a wheel against its commanded power over windows of `timeout`
+#[derive(Debug, Default)]
+struct WheelMonitor {
+    // start of the current window: when, the position of the wheel and its power
+    window: Option<(Instant, i32, f64)>,
+}
+
+impl WheelMonitor {
+    fn check(
+        &mut self,
+        config: &WatchdogConfig,
+        now: Instant,
+        power: f64,
+        position: i32,
+    ) -> WatchdogState {
+        let (start, start_position) = match self.window {
+            // a window only checks a constant power high enough to move the wheel
+            Some((start, start_position, commanded))
+                if commanded == power && power.abs() >= config.min_power =>
+            {
+                (start, start_position)
+            }
+            _ => {
+                self.window = Some((now, position, power));
+                return WatchdogState::Ok;
+            }
+        };
+        let elapsed = now.duration_since(start);
+        if elapsed < config.timeout {
+            return WatchdogState::Ok;
+        }
+        self.window = Some((now, position, power));
+        let speed = (position as f64 - start_position as f64).abs() / elapsed.as_secs_f64();
+        if position == start_position {
+            WatchdogState::Stalled
+        } else if config.max_speed.map_or(false, |max| {
+            speed > max * power.abs() * config.free_spin_factor
+        }) {
+            WatchdogState::FreeSpinning
+        } else {
+            WatchdogState::Ok
+        }
+    }
+}
+
+// Proportional controller turning the base by `target_deg`, the heading of the base is integrated
+// from the angular velocity measured by a gyroscope
+#[derive(Debug)]
+struct HeadingHold {
+    target_deg: f64,
+    max_rate: f64,
+    gain: f64,
+    tolerance_deg: f64,
+    heading_deg: f64,
+    last: Option<(Instant, f64)>,
+}
+
+impl HeadingHold {
+    fn new(target_deg: f64, max_rate: f64, gain: f64, tolerance_deg: f64) -> Self {
+        Self {
+            target_deg,
+            max_rate,
+            gain,
+            tolerance_deg,
+            heading_deg: 0.0,
+            last: None,
+        }
+    }
+
+    fn remaining_deg(&self) -> f64 {
+        self.target_deg - self.heading_deg
+    }
+
+    // Integrates the angular velocity `rate` (in degrees per second) measured at `now`, returns
+    // the angular velocity to command or `None` once the target is reached
+    fn update(&mut self, now: Instant, rate: f64) -> Option<f64> {
+        if let Some((at, previous)) = self.last {
+            self.heading_deg += (previous + rate) / 2.0 * now.duration_since(at).as_secs_f64();
+        }
+        self.last = Some((now, rate));
+        let error = self.remaining_deg();
+        if error.abs() <= self.tolerance_deg {
+            return None;
+        }
+        Some((self.gain * error).clamp(-self.max_rate, self.max_rate))
+    }
+}
+
+// Spin of the base in progress, driven by the gyroscope of the movement sensor
+#[derive(Debug)]
+struct GyroSpin {
+    hold: HeadingHold,
+    deadline: Instant,
+    mm_per_deg: f64,
+}
+
 pub struct WheeledBase<ML, MR> {
     motor_right: MR,
     motor_left: ML,
+    wheel_circumference_mm: Option<f64>,
+    track_width_mm: Option<f64>,
+    health: ComponentHealth,
+    movement_sensor: Option<MovementSensorType>,
+    spin_gain: f64,
+    spin_tolerance_deg: f64,
+    // speeds of the left and right wheels in mm/s last commanded, until when if time bound
+    commanded: Option<(f64, f64, Option<Instant>)>,
+    // positions of the wheels at the last odometry sample
+    odometry: Option<(Instant, i32, i32)>,
+    measured_linear: Option<f64>,
+    measured_angular: Option<f64>,
+    watchdog: Option<WatchdogConfig>,
+    monitors: [WheelMonitor; 2],
+    // state of the watchdog and the wheel which tripped it
+    watchdog_state: (WatchdogState, &'static str),
+    gyro_spin: Option<GyroSpin>,
+}
+
+// Reads an optional dimension of the base, rejecting values that can't describe a physical base
+fn get_dimension_mm(cfg: &ConfigType, key: &'static str) -> Result<Option<f64>, BaseError> {
+    match cfg.get_attribute::<f64>(key) {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(Some(value)),
+        Ok(_) => Err(BaseError::BaseConfigError(
+            "wheel_circumference_mm and track_width_mm must be positive",
+        )),
+        Err(AttributeError::KeyNotFound(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Reads an optional tuning attribute of the base, which must be positive
+fn get_positive(cfg: &ConfigType, key: &'static str) -> Result<Option<f64>, BaseError> {
+    match cfg.get_attribute::<f64>(key) {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(Some(value)),
+        Ok(_) => Err(BaseError::BaseConfigError(
+            "spin_gain and spin_tolerance_deg must be positive",
+        )),
+        Err(AttributeError::KeyNotFound(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
 }
 
 impl<ML, MR> WheeledBase<ML, MR>
@@ -47,8 +325,301 @@ where
         WheeledBase {
             motor_right,
             motor_left,
+            wheel_circumference_mm: None,
+            track_width_mm: None,
+            health: ComponentHealth::new(),
+            movement_sensor: None,
+            spin_gain: DEFAULT_SPIN_GAIN,
+            spin_tolerance_deg: DEFAULT_SPIN_TOLERANCE_DEG,
+            commanded: None,
+            odometry: None,
+            measured_linear: None,
+            measured_angular: None,
+            watchdog: None,
+            monitors: Default::default(),
+            watchdog_state: (WatchdogState::Ok, ""),
+            gyro_spin: None,
+        }
+    }
+
+    /// Spins the base with the angular velocity measured by `movement_sensor` rather than by
+    /// dead reckoning
+    pub fn with_movement_sensor(
+        mut self,
+        movement_sensor: MovementSensorType,
+    ) -> Result<Self, BaseError> {
+        if !MovementSensor::get_properties(&movement_sensor).angular_velocity_supported {
+            return Err(BaseError::BaseConfigError(
+                "the movement sensor of the base must report its angular velocity",
+            ));
         }
+        self.movement_sensor = Some(movement_sensor);
+        Ok(self)
+    }
+
+    /// Stops the base when its wheels don't follow their commanded power, see [WatchdogConfig]
+    pub fn with_watchdog(mut self, config: WatchdogConfig) -> Result<Self, BaseError> {
+        if !(self.motor_left.get_properties().position_reporting
+            && self.motor_right.get_properties().position_reporting)
+        {
+            return Err(BaseError::BaseConfigError(
+                "the watchdog requires motors reporting their position",
+            ));
+        }
+        self.watchdog = Some(config);
+        Ok(self)
+    }
+
+    // Checks the wheels against their commanded power, stopping the base when one of them
+    // diverges
+    fn supervise(&mut self, now: Instant) -> Result<(), BaseError> {
+        let config = match self.watchdog {
+            Some(config) if self.watchdog_state.0 == WatchdogState::Ok => config,
+            _ => return Ok(()),
+        };
+        let (_, left_power) = self.motor_left.is_powered()?;
+        let left_position = self.motor_left.get_position()?;
+        let (_, right_power) = self.motor_right.is_powered()?;
+        let right_position = self.motor_right.get_position()?;
+        let left = self.monitors[0].check(&config, now, left_power, left_position);
+        let right = self.monitors[1].check(&config, now, right_power, right_position);
+        self.watchdog_state = match (left, right) {
+            (WatchdogState::Ok, WatchdogState::Ok) => return Ok(()),
+            (WatchdogState::Ok, state) => (state, "right"),
+            (state, _) => (state, "left"),
+        };
+        log::error!(
+            "base watchdog tripped, {} wheel {}, stopping",
+            self.watchdog_state.1,
+            self.watchdog_state.0.as_str()
+        );
+        self.stop().map_err(MotorError::from)?;
+        Ok(())
+    }
+
+    /// Runs the periodic work of the base, the watchdog checking the wheels
+    pub fn tick(&mut self, now: Instant) {
+        if let Err(err) = self.supervise(now) {
+            log::error!("base watchdog couldn't check the wheels: {:?}", err);
+            self.health.record::<(), _>(&Err(err));
+        }
+    }
+
+    fn check_watchdog(&self) -> Result<(), BaseError> {
+        match self.watchdog_state {
+            (WatchdogState::Ok, _) => Ok(()),
+            (state, wheel) => Err(BaseError::BaseWatchdogTripped(wheel, state.as_str())),
+        }
+    }
+
+    /// Lets the base move again after its watchdog tripped
+    pub fn reset_watchdog(&mut self) {
+        self.watchdog_state = (WatchdogState::Ok, "");
+        self.monitors = Default::default();
+    }
+
+    fn wheel_circumference_mm(&self) -> Result<f64, BaseError> {
+        self.wheel_circumference_mm
+            .ok_or(BaseError::BaseConfigError(
+                "wheel_circumference_mm is required to move the base by a distance",
+            ))
     }
+
+    fn track_width_mm(&self) -> Result<f64, BaseError> {
+        self.track_width_mm.ok_or(BaseError::BaseConfigError(
+            "track_width_mm is required to spin the base",
+        ))
+    }
+
+    // Records the speeds of the wheels in mm/s just commanded, for `duration` if time bound
+    fn command_wheels(&mut self, speeds: Option<(f64, f64)>, duration: Option<Duration>) {
+        self.commanded = speeds.map(|(left, right)| {
+            (
+                left,
+                right,
+                duration.map(|duration| Instant::now() + duration),
+            )
+        });
+    }
+
+    // Linear and angular velocities of the base last commanded
+    fn commanded_velocities(&self, now: Instant) -> Option<(f64, Option<f64>)> {
+        let (left, right) = match self.commanded? {
+            (_, _, Some(until)) if now >= until => (0.0, 0.0),
+            (left, right, _) => (left, right),
+        };
+        Some((
+            (left + right) / 2.0,
+            self.track_width_mm
+                .map(|width| ((right - left) / width).to_degrees()),
+        ))
+    }
+
+    // Updates the measured velocities from the positions of the wheels and the gyroscope
+    fn measure_velocities(&mut self, now: Instant) -> Result<(), BaseError> {
+        if let Some(mut movement_sensor) = self.movement_sensor.clone() {
+            let rate = movement_sensor
+                .get_angular_velocity()
+                .map_err(MotorError::from)?;
+            self.measured_angular = Some(rate.z);
+        }
+        let circumference = self.wheel_circumference_mm()?;
+        let previous = match self.odometry {
+            Some((at, _, _)) if now.duration_since(at) < MIN_ODOMETRY_WINDOW => return Ok(()),
+            previous => previous,
+        };
+        let left = self.motor_left.get_position()?;
+        let right = self.motor_right.get_position()?;
+        self.odometry = Some((now, left, right));
+        let Some((at, previous_left, previous_right)) = previous else {
+            return Ok(());
+        };
+        let secs = now.duration_since(at).as_secs_f64();
+        let left_ticks = f64::from(
+            self.motor_left
+                .get_properties()
+                .ticks_per_rotation
+                .unwrap_or(1),
+        );
+        let right_ticks = f64::from(
+            self.motor_right
+                .get_properties()
+                .ticks_per_rotation
+                .unwrap_or(1),
+        );
+        let left = f64::from(left - previous_left) / left_ticks * circumference / secs;
+        let right = f64::from(right - previous_right) / right_ticks * circumference / secs;
+        self.measured_linear = Some((left + right) / 2.0);
+        if self.movement_sensor.is_none() {
+            self.measured_angular = self
+                .track_width_mm
+                .map(|width| ((right - left) / width).to_degrees());
+        }
+        Ok(())
+    }
+
+    // Runs both wheels for the given distance at the given speed, each expressed in millimeters
+    // traveled by the wheel. Returns the longest of the durations reported by the motors.
+    fn drive_wheels(
+        &mut self,
+        left_mm: f64,
+        right_mm: f64,
+        mm_per_sec: f64,
+    ) -> Result<Option<Duration>, BaseError> {
+        self.gyro_spin = None;
+        // go_for would run the motors indefinitely when asked to travel no distance
+        if mm_per_sec == 0.0 || (left_mm == 0.0 && right_mm == 0.0) {
+            self.stop().map_err(MotorError::from)?;
+            return Ok(None);
+        }
+        let circumference = self.wheel_circumference_mm()?;
+        self.check_watchdog()?;
+        let rpm = mm_per_sec.abs() / circumference * 60.0;
+        let res = self
+            .motor_left
+            .go_for(rpm, left_mm / circumference)
+            .and_then(|l| {
+                self.motor_right
+                    .go_for(rpm, right_mm / circumference)
+                    .map(|r| l.max(r))
+            });
+        self.health.record(&res);
+        let duration = res?;
+        let speed = mm_per_sec.abs();
+        self.command_wheels(
+            Some((speed * left_mm.signum(), speed * right_mm.signum())),
+            duration,
+        );
+        Ok(duration)
+    }
+
+    // Starts spinning the base by `angle_deg` following the heading integrated from the
+    // gyroscope of the movement sensor, the spin is then stepped by `step_spin`. Returns the time
+    // the base is given to complete it.
+    fn spin_with_gyro(
+        &mut self,
+        angle_deg: f64,
+        degs_per_sec: f64,
+        mm_per_deg: f64,
+    ) -> Result<Option<Duration>, BaseError> {
+        self.gyro_spin = None;
+        if angle_deg == 0.0 || degs_per_sec == 0.0 {
+            self.stop().map_err(MotorError::from)?;
+            return Ok(None);
+        }
+        self.wheel_circumference_mm()?;
+        self.check_watchdog()?;
+        let timeout =
+            Duration::from_secs_f64(2.0 * (angle_deg / degs_per_sec).abs()) + SPIN_TIMEOUT_MARGIN;
+        let now = Instant::now();
+        let mut spin = GyroSpin {
+            hold: HeadingHold::new(
+                angle_deg,
+                degs_per_sec.abs(),
+                self.spin_gain,
+                self.spin_tolerance_deg,
+            ),
+            deadline: now + timeout,
+            mm_per_deg,
+        };
+        let res = self.drive_spin(&mut spin, now);
+        self.health.record(&res);
+        if let Ok(true) = res {
+            self.gyro_spin = Some(spin);
+            return Ok(Some(timeout));
+        }
+        self.stop().map_err(MotorError::from)?;
+        res.map(|_| None)
+    }
+
+    // Steps the spin in progress, the base is stopped once it completes or fails
+    fn step_spin(&mut self, now: Instant) {
+        let Some(mut spin) = self.gyro_spin.take() else {
+            return;
+        };
+        let res = self.drive_spin(&mut spin, now);
+        if let Ok(true) = res {
+            self.gyro_spin = Some(spin);
+            return;
+        }
+        if let Err(err) = &res {
+            log::error!("base couldn't complete its spin: {}", err);
+        }
+        self.health.record(&res);
+        if let Err(err) = self.stop() {
+            log::error!("base couldn't stop after its spin: {}", err);
+        }
+    }
+
+    // Drives the wheels at the angular velocity commanded by the heading controller, returns
+    // whether the spin goes on
+    fn drive_spin(&mut self, spin: &mut GyroSpin, now: Instant) -> Result<bool, BaseError> {
+        let circumference = self.wheel_circumference_mm()?;
+        let Some(mut movement_sensor) = self.movement_sensor.clone() else {
+            return Err(BaseError::BaseConfigError(
+                "spinning with a gyroscope requires a movement sensor",
+            ));
+        };
+        let rate = movement_sensor
+            .get_angular_velocity()
+            .map_err(MotorError::from)?
+            .z;
+        let Some(rate) = spin.hold.update(now, rate) else {
+            return Ok(false);
+        };
+        if now >= spin.deadline {
+            return Err(BaseError::BaseSpinIncomplete(spin.hold.remaining_deg()));
+        }
+        self.check_watchdog()?;
+        // the left wheel runs backwards to turn counterclockwise
+        let rpm = rate * spin.mm_per_deg / circumference * 60.0;
+        self.motor_left.go_for(-rpm, 0.0)?;
+        self.motor_right.go_for(rpm, 0.0)?;
+        let speed = rate * spin.mm_per_deg;
+        self.command_wheels(Some((-speed, speed)), None);
+        Ok(true)
+    }
+
     #[allow(clippy::only_used_in_recursion)]
     fn differential_drive(&self, forward: f64, left: f64) -> (f64, f64) {
         if forward < 0.0 {
@@ -69,29 +640,89 @@ where
     ) -> Result<BaseType, BaseError> {
         let l_motor_name = cfg.get_attribute::<String>("left")?;
         let r_motor_name = cfg.get_attribute::<String>("right")?;
+        let wheel_circumference_mm = get_dimension_mm(&cfg, "wheel_circumference_mm")?;
+        let track_width_mm = get_dimension_mm(&cfg, "track_width_mm")?;
+        let movement_sensor_name = match cfg.get_attribute::<String>("movement_sensor") {
+            Ok(name) => Some(name),
+            Err(AttributeError::KeyNotFound(_)) => None,
+            Err(err) => return Err(err.into()),
+        };
+        let spin_gain = get_positive(&cfg, "spin_gain")?.unwrap_or(DEFAULT_SPIN_GAIN);
+        let spin_tolerance_deg =
+            get_positive(&cfg, "spin_tolerance_deg")?.unwrap_or(DEFAULT_SPIN_TOLERANCE_DEG);
+        let watchdog_config = match cfg.get_attribute::<WatchdogConfig>("watchdog") {
+            Ok(watchdog) => Some(watchdog),
+            Err(AttributeError::KeyNotFound(_)) => None,
+            Err(err) => return Err(err.into()),
+        };
         let mut l_motor: Option<MotorType> = None;
         let mut r_motor: Option<MotorType> = None;
+        let mut movement_sensor: Option<MovementSensorType> = None;
         for Dependency(key, res) in deps {
-            if let Resource::Motor(found_motor) = res {
-                match key.1 {
-                    x if x == l_motor_name => {
-                        l_motor = Some(found_motor.clone());
-                    }
-                    x if x == r_motor_name => {
-                        r_motor = Some(found_motor.clone());
-                    }
-                    _ => {}
-                };
+            match res {
+                Resource::Motor(found_motor) => {
+                    match key.1 {
+                        x if x == l_motor_name => {
+                            l_motor = Some(found_motor.clone());
+                        }
+                        x if x == r_motor_name => {
+                            r_motor = Some(found_motor.clone());
+                        }
+                        _ => {}
+                    };
+                }
+                Resource::MovementSensor(found)
+                    if movement_sensor_name.as_ref() == Some(&key.1) =>
+                {
+                    movement_sensor = Some(found);
+                }
+                _ => {}
             }
         }
-        if let Some(l_motor) = l_motor {
-            if let Some(r_motor) = r_motor {
-                Ok(Arc::new(Mutex::new(WheeledBase::new(r_motor, l_motor))))
-            } else {
-                Err(BaseError::BaseConfigError("right motor couldn't be found"))
+        let movement_sensor = match (movement_sensor_name, movement_sensor) {
+            (Some(_), None) => {
+                return Err(BaseError::BaseConfigError(
+                    "the movement sensor of the base couldn't be found",
+                ))
+            }
+            (_, movement_sensor) => movement_sensor,
+        };
+        match (l_motor, r_motor) {
+            (Some(l_motor), Some(r_motor)) => {
+                let mut base = WheeledBase::new(l_motor, r_motor);
+                base.wheel_circumference_mm = wheel_circumference_mm;
+                base.track_width_mm = track_width_mm;
+                base.spin_gain = spin_gain;
+                base.spin_tolerance_deg = spin_tolerance_deg;
+                let gyro = movement_sensor.is_some();
+                if let Some(movement_sensor) = movement_sensor {
+                    base = base.with_movement_sensor(movement_sensor)?;
+                }
+                let watchdog = watchdog_config.is_some();
+                if let Some(watchdog) = watchdog_config {
+                    base = base.with_watchdog(watchdog)?;
+                }
+                let base = Arc::new(Mutex::new(base));
+                if watchdog {
+                    spawn_periodic(&base, WATCHDOG_PERIOD, |base| base.tick(Instant::now()));
+                }
+                if gyro {
+                    spawn_periodic(&base, SPIN_CONTROL_PERIOD, |base| {
+                        base.step_spin(Instant::now())
+                    });
+                }
+                Ok(base)
+            }
+            (l_motor, r_motor) => {
+                let missing = [
+                    (l_motor.is_none(), l_motor_name),
+                    (r_motor.is_none(), r_motor_name),
+                ]
+                .into_iter()
+                .filter_map(|(missing, name)| missing.then_some(name))
+                .collect();
+                Err(BaseError::BaseMotorsNotFound(missing))
             }
-        } else {
-            Err(BaseError::BaseConfigError("left motor couldn't be found"))
         }
     }
 
@@ -105,6 +736,9 @@ where
             let r_key = ResourceKey(MotorCompName, r_motor_name);
             r_keys.push(r_key)
         }
+        if let Ok(movement_sensor_name) = cfg.get_attribute::<String>("movement_sensor") {
+            r_keys.push(ResourceKey(MovementSensorCompName, movement_sensor_name))
+        }
         r_keys
     }
 }
@@ -114,14 +748,60 @@ where
     MR: Motor,
 {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        let mut hm = HashMap::new();
-        hm.insert(
-            "is_moving".to_string(),
-            google::protobuf::Value {
-                kind: Some(google::protobuf::value::Kind::BoolValue(false)),
-            },
-        );
-        Ok(Some(google::protobuf::Struct { fields: hm }))
+        let mut velocity = StructBuilder::with_capacity(2);
+        if let Some((linear, angular)) = self.commanded_velocities(Instant::now()) {
+            velocity = velocity.sub(
+                "commanded",
+                StructBuilder::with_capacity(2)
+                    .field("linear_mm_per_sec", linear)
+                    .field("angular_deg_per_sec", angular),
+            );
+        }
+        if self.measured_linear.is_some() || self.measured_angular.is_some() {
+            velocity = velocity.sub(
+                "measured",
+                StructBuilder::with_capacity(2)
+                    .field("linear_mm_per_sec", self.measured_linear)
+                    .field("angular_deg_per_sec", self.measured_angular),
+            );
+        }
+        let (state, wheel) = self.watchdog_state;
+        let watchdog_state = match state {
+            WatchdogState::Ok => state.as_str().to_string(),
+            state => format!("{} {}", wheel, state.as_str()),
+        };
+        Ok(Some(status_envelope(
+            &self.health,
+            [
+                ("is_moving", google::protobuf::value::Kind::BoolValue(false)),
+                (
+                    "watchdog_state",
+                    google::protobuf::value::Kind::StringValue(watchdog_state),
+                ),
+                (
+                    "velocity",
+                    google::protobuf::value::Kind::StructValue(velocity.build()),
+                ),
+            ],
+        )))
+    }
+}
+
+impl<ML, MR> DoCommand for WheeledBase<ML, MR>
+where
+    ML: Motor,
+    MR: Motor,
+{
+    fn do_command(
+        &mut self,
+        command_struct: Option<google::protobuf::Struct>,
+    ) -> Result<Option<google::protobuf::Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        if command.fields.contains_key("reset_watchdog") {
+            self.reset_watchdog();
+            return Ok(None);
+        }
+        Err(GenericError::MethodUnimplemented("do_command"))
     }
 }
 
@@ -134,8 +814,10 @@ where
         Ok(self.motor_left.is_moving()? || self.motor_right.is_moving()?)
     }
     fn stop(&mut self) -> Result<(), ActuatorError> {
+        self.gyro_spin = None;
         self.motor_left.stop()?;
         self.motor_right.stop()?;
+        self.command_wheels(Some((0.0, 0.0)), None);
         Ok(())
     }
 }
@@ -146,9 +828,374 @@ where
     MR: Motor,
 {
     fn set_power(&mut self, lin: &Vector3, ang: &Vector3) -> Result<(), BaseError> {
+        self.gyro_spin = None;
         let (l, r) = self.differential_drive(lin.y, ang.z);
-        self.motor_left.set_power(l)?;
-        self.motor_right.set_power(r)?;
+        if l != 0.0 || r != 0.0 {
+            self.check_watchdog()?;
+        }
+        let res = self
+            .motor_left
+            .set_power(l)
+            .and_then(|_| self.motor_right.set_power(r));
+        self.health.record(&res);
+        res?;
+        let speeds = self.wheel_circumference_mm.and_then(|circumference| {
+            let left_rpm = self.motor_left.get_properties().max_rpm?;
+            let right_rpm = self.motor_right.get_properties().max_rpm?;
+            Some((
+                l * left_rpm / 60.0 * circumference,
+                r * right_rpm / 60.0 * circumference,
+            ))
+        });
+        self.command_wheels(speeds, None);
         Ok(())
     }
+
+    fn get_properties(&mut self) -> Result<BaseProperties, BaseError> {
+        Ok(BaseProperties {
+            width_meters: self.track_width_mm.unwrap_or_default() / 1000.0,
+            turning_radius_meters: 0.0,
+            wheel_circumference_meters: self.wheel_circumference_mm.unwrap_or_default() / 1000.0,
+        })
+    }
+
+    fn move_straight(
+        &mut self,
+        distance_mm: i64,
+        mm_per_sec: f64,
+    ) -> Result<Option<Duration>, BaseError> {
+        let distance_mm = distance_mm as f64;
+        self.drive_wheels(distance_mm, distance_mm, mm_per_sec)
+    }
+
+    fn spin(&mut self, angle_deg: f64, degs_per_sec: f64) -> Result<Option<Duration>, BaseError> {
+        // each wheel travels along a circle whose diameter is the track width
+        let mm_per_deg = self.track_width_mm()? * std::f64::consts::PI / 360.0;
+        if self.movement_sensor.is_some() {
+            return self.spin_with_gyro(angle_deg, degs_per_sec, mm_per_deg);
+        }
+        let wheel_mm = angle_deg * mm_per_deg;
+        self.drive_wheels(-wheel_mm, wheel_mm, degs_per_sec * mm_per_deg)
+    }
+
+    fn get_linear_velocity(&mut self) -> Result<Vector3, BaseError> {
+        self.measure_velocities(Instant::now())?;
+        Ok(Vector3 {
+            x: 0.0,
+            y: self.measured_linear.unwrap_or_default(),
+            z: 0.0,
+        })
+    }
+
+    fn get_angular_velocity(&mut self) -> Result<Vector3, BaseError> {
+        self.measure_velocities(Instant::now())?;
+        Ok(Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: self.measured_angular.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use crate::common::base::{Base, BaseError, BaseProperties};
+    use crate::common::config::{ConfigType, DynamicComponentConfig, Kind};
+    use crate::common::generic::DoCommand;
+    use crate::common::math_utils::Vector3 as SensorVector3;
+    use crate::common::motor::{FakeMotor, Motor};
+    use crate::common::movement_sensor::{
+        GeoPosition, MovementSensor, MovementSensorSupportedMethods,
+    };
+    use crate::common::sensor::SensorError;
+    use crate::common::status::Status;
+    use crate::common::struct_builder::StructBuilder;
+    use crate::common::wheeled_base::{
+        HeadingHold, WatchdogConfig, WatchdogState, WheelMonitor, WheeledBase,
+    };
+    use crate::google::protobuf::value::Kind as ProtoKind;
+    use crate::proto::common::v1::Vector3;
+
+    // gyroscope reporting the yaw rate set by the test
+    #[derive(DoCommand, MovementSensorReadings, Status)]
+    struct TestGyro {
+        rate: f64,
+    }
+
+    impl MovementSensor for TestGyro {
+        fn get_position(&mut self) -> Result<GeoPosition, SensorError> {
+            Err(SensorError::SensorMethodUnimplemented("get_position"))
+        }
+        fn get_linear_velocity(&mut self) -> Result<SensorVector3, SensorError> {
+            Err(SensorError::SensorMethodUnimplemented(
+                "get_linear_velocity",
+            ))
+        }
+        fn get_angular_velocity(&mut self) -> Result<SensorVector3, SensorError> {
+            Ok(SensorVector3 {
+                x: 0.0,
+                y: 0.0,
+                z: self.rate,
+            })
+        }
+        fn get_linear_acceleration(&mut self) -> Result<SensorVector3, SensorError> {
+            Err(SensorError::SensorMethodUnimplemented(
+                "get_linear_acceleration",
+            ))
+        }
+        fn get_compass_heading(&mut self) -> Result<f64, SensorError> {
+            Err(SensorError::SensorMethodUnimplemented(
+                "get_compass_heading",
+            ))
+        }
+        fn get_properties(&self) -> MovementSensorSupportedMethods {
+            MovementSensorSupportedMethods {
+                position_supported: false,
+                linear_velocity_supported: false,
+                angular_velocity_supported: true,
+                linear_acceleration_supported: false,
+                compass_heading_supported: false,
+                orientation_supported: false,
+            }
+        }
+    }
+
+    #[test_log::test]
+    fn test_wheeled_base_config() {
+        let mut attributes = HashMap::from([
+            ("left".to_owned(), Kind::StringValue("left".to_owned())),
+            ("right".to_owned(), Kind::StringValue("right".to_owned())),
+        ]);
+        let cfg = DynamicComponentConfig {
+            name: "base".to_owned(),
+            namespace: "rdk".to_owned(),
+            r#type: "base".to_owned(),
+            model: "two_wheeled_base".to_owned(),
+            attributes: Some(attributes.clone()),
+            ..Default::default()
+        };
+        let base =
+            WheeledBase::<FakeMotor, FakeMotor>::from_config(ConfigType::Dynamic(&cfg), Vec::new());
+        assert!(
+            matches!(base, Err(BaseError::BaseMotorsNotFound(missing)) if missing == ["left", "right"])
+        );
+
+        attributes.insert(
+            "wheel_circumference_mm".to_owned(),
+            Kind::NumberValue(-10.0),
+        );
+        let cfg = DynamicComponentConfig {
+            attributes: Some(attributes),
+            ..cfg
+        };
+        let base =
+            WheeledBase::<FakeMotor, FakeMotor>::from_config(ConfigType::Dynamic(&cfg), Vec::new());
+        assert!(matches!(base, Err(BaseError::BaseConfigError(_))));
+    }
+
+    #[test_log::test]
+    fn test_wheeled_base_kinematics() {
+        let mut base = WheeledBase::new(FakeMotor::new(), FakeMotor::new());
+        assert!(base.move_straight(100, 100.0).is_err());
+
+        base.wheel_circumference_mm = Some(200.0);
+        base.track_width_mm = Some(200.0);
+        assert_eq!(
+            base.get_properties().unwrap(),
+            BaseProperties {
+                width_meters: 0.2,
+                turning_radius_meters: 0.0,
+                wheel_circumference_meters: 0.2,
+            }
+        );
+
+        // 400mm at 100mm/s: 2 revolutions at 30 rpm
+        let dur = base.move_straight(400, 100.0).unwrap().unwrap();
+        assert!((dur.as_secs_f64() - 4.0).abs() < 1e-6);
+        assert_eq!(base.motor_left.is_powered().unwrap(), (true, 0.3));
+        assert_eq!(base.motor_right.is_powered().unwrap(), (true, 0.3));
+
+        // a quarter turn left at 90 deg/s, the left wheel runs backwards
+        let dur = base.spin(90.0, 90.0).unwrap().unwrap();
+        assert!((dur.as_secs_f64() - 1.0).abs() < 1e-6);
+        let (_, left) = base.motor_left.is_powered().unwrap();
+        let (_, right) = base.motor_right.is_powered().unwrap();
+        assert!(left < 0.0 && right > 0.0);
+        assert!((left + right).abs() < 1e-9);
+
+        assert_eq!(base.move_straight(0, 100.0).unwrap(), None);
+        assert!(!base.motor_left.is_powered().unwrap().0);
+    }
+
+    #[test_log::test]
+    fn test_wheel_monitor() {
+        let config = WatchdogConfig {
+            max_speed: Some(100.0),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut monitor = WheelMonitor::default();
+
+        // too little power to expect the wheel to turn
+        assert_eq!(monitor.check(&config, at(0), 0.1, 0), WatchdogState::Ok);
+        assert_eq!(monitor.check(&config, at(2000), 0.1, 0), WatchdogState::Ok);
+
+        // a new power starts a new window, 50 units per second are expected at half power
+        assert_eq!(monitor.check(&config, at(2000), 0.5, 0), WatchdogState::Ok);
+        assert_eq!(monitor.check(&config, at(2500), 0.5, 0), WatchdogState::Ok);
+        assert_eq!(monitor.check(&config, at(3000), 0.5, 60), WatchdogState::Ok);
+        assert_eq!(
+            monitor.check(&config, at(4000), 0.5, 160),
+            WatchdogState::FreeSpinning
+        );
+        assert_eq!(
+            monitor.check(&config, at(5000), 0.5, 160),
+            WatchdogState::Stalled
+        );
+    }
+
+    #[test_log::test]
+    fn test_wheeled_base_watchdog() {
+        let mut base = WheeledBase::new(FakeMotor::new(), FakeMotor::new())
+            .with_watchdog(WatchdogConfig::default())
+            .unwrap();
+        let forward = Vector3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        base.set_power(&forward, &Vector3::default()).unwrap();
+        let start = Instant::now();
+        base.supervise(start).unwrap();
+        assert_eq!(base.watchdog_state.0, WatchdogState::Ok);
+
+        // the fake motors never move, the wheels are stalled
+        base.tick(start + Duration::from_millis(1500));
+        assert_eq!(base.watchdog_state, (WatchdogState::Stalled, "left"));
+        assert!(!base.motor_left.is_powered().unwrap().0);
+        assert!(matches!(
+            base.set_power(&forward, &Vector3::default()),
+            Err(BaseError::BaseWatchdogTripped("left", "stalled"))
+        ));
+        base.set_power(&Vector3::default(), &Vector3::default())
+            .unwrap();
+
+        let command = StructBuilder::new()
+            .sub("reset_watchdog", StructBuilder::new())
+            .build();
+        assert!(base.do_command(Some(command)).unwrap().is_none());
+        base.set_power(&forward, &Vector3::default()).unwrap();
+        assert!(base.motor_left.is_powered().unwrap().0);
+    }
+
+    #[test_log::test]
+    fn test_heading_hold() {
+        let mut hold = HeadingHold::new(90.0, 60.0, 2.0, 2.0);
+        let start = Instant::now();
+        // the base turns at the commanded rate one control period later
+        let mut rate = 0.0;
+        let mut steps = 0;
+        while let Some(command) = hold.update(start + Duration::from_millis(20 * steps), rate) {
+            assert!(command.abs() <= 60.0);
+            rate = command;
+            steps += 1;
+            assert!(steps < 500, "heading hold didn't settle");
+        }
+        assert!(hold.remaining_deg().abs() <= 2.0);
+        // at least the time to turn at full speed
+        assert!(steps >= 75);
+
+        let mut hold = HeadingHold::new(-45.0, 90.0, 2.0, 2.0);
+        assert_eq!(hold.update(start, 0.0), Some(-90.0));
+        assert_eq!(
+            hold.update(start + Duration::from_millis(500), -90.0),
+            Some(-45.0)
+        );
+    }
+
+    #[test_log::test]
+    fn test_wheeled_base_gyro_spin() {
+        let gyro = Arc::new(Mutex::new(TestGyro { rate: 0.0 }));
+        let mut base = WheeledBase::new(FakeMotor::new(), FakeMotor::new())
+            .with_movement_sensor(gyro.clone())
+            .unwrap();
+        base.wheel_circumference_mm = Some(200.0);
+        base.track_width_mm = Some(200.0);
+
+        // the spin returns right away, the wheels keep turning until the base reaches its target
+        let timeout = base.spin(90.0, 45.0).unwrap().unwrap();
+        assert_eq!(timeout, Duration::from_secs(6));
+        let start = Instant::now();
+        assert!(base.gyro_spin.is_some());
+        assert!(base.motor_left.is_powered().unwrap().1 < 0.0);
+        assert!(base.motor_right.is_powered().unwrap().1 > 0.0);
+        gyro.lock().unwrap().rate = 90.0;
+        base.step_spin(start + Duration::from_millis(500));
+        assert!(base.gyro_spin.is_some());
+        base.step_spin(start + Duration::from_millis(1250));
+        assert!(base.gyro_spin.is_none());
+        assert!(!base.motor_left.is_powered().unwrap().0);
+
+        // a spin which doesn't complete in time stops the base
+        gyro.lock().unwrap().rate = 0.0;
+        base.spin(90.0, 45.0).unwrap();
+        base.step_spin(Instant::now() + Duration::from_secs(7));
+        assert!(base.gyro_spin.is_none());
+        assert!(!base.motor_right.is_powered().unwrap().0);
+        assert!(!base.health.is_ok());
+
+        // any other command cancels the spin
+        base.spin(90.0, 45.0).unwrap();
+        base.set_power(&Vector3::default(), &Vector3::default())
+            .unwrap();
+        assert!(base.gyro_spin.is_none());
+    }
+
+    #[test_log::test]
+    fn test_wheeled_base_velocities() {
+        let mut base = WheeledBase::new(FakeMotor::new(), FakeMotor::new());
+        assert!(base.get_linear_velocity().is_err());
+        base.wheel_circumference_mm = Some(200.0);
+        base.track_width_mm = Some(200.0);
+
+        // half of the 100 rpm of the fake motors
+        let forward = Vector3 {
+            x: 0.0,
+            y: 0.5,
+            z: 0.0,
+        };
+        base.set_power(&forward, &Vector3::default()).unwrap();
+        let (linear, angular) = base.commanded_velocities(Instant::now()).unwrap();
+        assert!((linear - 500.0 / 3.0).abs() < 1e-9);
+        assert_eq!(angular, Some(0.0));
+
+        // a quarter turn in a second, then stopped
+        base.spin(90.0, 90.0).unwrap();
+        let (linear, angular) = base.commanded_velocities(Instant::now()).unwrap();
+        assert_eq!(linear, 0.0);
+        assert!((angular.unwrap() - 90.0).abs() < 1e-9);
+        let later = Instant::now() + Duration::from_secs(2);
+        assert_eq!(base.commanded_velocities(later), Some((0.0, Some(0.0))));
+
+        // the fake motors don't move
+        let start = Instant::now();
+        base.measure_velocities(start).unwrap();
+        assert_eq!(base.measured_linear, None);
+        base.measure_velocities(start + Duration::from_millis(200))
+            .unwrap();
+        assert_eq!(base.measured_linear, Some(0.0));
+        assert_eq!(base.measured_angular, Some(0.0));
+
+        let status = base.get_status().unwrap().unwrap();
+        let Some(ProtoKind::StructValue(velocity)) = &status.fields["velocity"].kind else {
+            panic!("velocity isn't a struct");
+        };
+        assert!(velocity.fields.contains_key("commanded"));
+        assert!(velocity.fields.contains_key("measured"));
+    }
 }
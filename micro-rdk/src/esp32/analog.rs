@@ -1,5 +1,5 @@
 #![allow(dead_code)]
-use crate::common::analog::{AnalogError, AnalogReader};
+use crate::common::analog::{AnalogError, AnalogFilter, AnalogReader};
 use crate::esp32::esp_idf_svc::hal::adc::{AdcChannelDriver, AdcDriver};
 use crate::esp32::esp_idf_svc::hal::gpio::ADCPin;
 use std::sync::{Arc, Mutex};
@@ -8,6 +8,7 @@ pub struct Esp32AnalogReader<'a, const A: u32, T: ADCPin> {
     channel: AdcChannelDriver<'a, A, T>,
     driver: Arc<Mutex<AdcDriver<'a, T::Adc>>>,
     name: String,
+    filter: AnalogFilter,
 }
 
 impl<'a, const A: u32, T: ADCPin> Esp32AnalogReader<'a, A, T> {
@@ -15,19 +16,25 @@ impl<'a, const A: u32, T: ADCPin> Esp32AnalogReader<'a, A, T> {
         name: String,
         channel: AdcChannelDriver<'a, A, T>,
         driver: Arc<Mutex<AdcDriver<'a, T::Adc>>>,
+        filter: AnalogFilter,
     ) -> Self {
         Self {
             name,
             channel,
             driver,
+            filter,
         }
     }
     fn inner_read(&mut self) -> Result<u16, AnalogError> {
-        self.driver
-            .lock()
-            .unwrap()
-            .read_raw(&mut self.channel)
-            .map_err(|e| AnalogError::AnalogReadError(e.code()))
+        let driver = &self.driver;
+        let channel = &mut self.channel;
+        self.filter.read(|| {
+            driver
+                .lock()
+                .unwrap()
+                .read_raw(channel)
+                .map_err(|e| AnalogError::AnalogReadError(e.code()))
+        })
     }
     fn inner_name(&self) -> String {
         self.name.clone()
@@ -0,0 +1,77 @@
+//! Loads the [assets](crate::common::assets) stored in a data partition of the flash
+
+use std::ffi::CString;
+
+use crate::common::assets::{load_assets, AssetError, AssetImage, AssetSource, AssetStore};
+use crate::esp32::esp_idf_svc::sys::{
+    esp_partition_find_first, esp_partition_read,
+    esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY, esp_partition_t,
+    esp_partition_type_t_ESP_PARTITION_TYPE_DATA, ESP_OK,
+};
+
+/// Label of the partition assets are loaded from by default
+pub const ASSETS_PARTITION_LABEL: &str = "assets";
+
+pub struct PartitionSource {
+    partition: *const esp_partition_t,
+}
+
+// the partition description lives in flash and is never freed
+unsafe impl Send for PartitionSource {}
+unsafe impl Sync for PartitionSource {}
+
+impl PartitionSource {
+    /// Finds the data partition labeled `label`
+    pub fn find(label: &str) -> Result<Self, AssetError> {
+        let c_label = CString::new(label)
+            .map_err(|_| AssetError::NotFound(format!("partition {}", label)))?;
+        let partition = unsafe {
+            esp_partition_find_first(
+                esp_partition_type_t_ESP_PARTITION_TYPE_DATA,
+                esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY,
+                c_label.as_ptr(),
+            )
+        };
+        if partition.is_null() {
+            return Err(AssetError::NotFound(format!("partition {}", label)));
+        }
+        Ok(Self { partition })
+    }
+
+    fn size(&self) -> u32 {
+        unsafe { (*self.partition).size }
+    }
+}
+
+impl AssetSource for PartitionSource {
+    fn read_at(&self, offset: u32, buf: &mut [u8]) -> Result<(), AssetError> {
+        if offset as usize + buf.len() > self.size() as usize {
+            return Err(AssetError::InvalidImage(
+                "read past the end of the partition",
+            ));
+        }
+        let ret = unsafe {
+            esp_partition_read(
+                self.partition,
+                offset as usize,
+                buf.as_mut_ptr() as *mut _,
+                buf.len(),
+            )
+        };
+        if ret != ESP_OK as i32 {
+            return Err(AssetError::FlashReadError(ret));
+        }
+        Ok(())
+    }
+}
+
+/// Makes the asset image stored in the partition labeled `label` the assets of the device
+pub fn load_assets_from_partition(label: &str) -> Result<(), AssetError> {
+    let image = AssetImage::open(PartitionSource::find(label)?)?;
+    log::info!(
+        "loaded {} assets from partition {}",
+        image.names().len(),
+        label
+    );
+    load_assets(image)
+}
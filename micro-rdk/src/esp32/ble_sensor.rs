@@ -0,0 +1,131 @@
+// Support for Bluetooth LE sensors, the advertisements received by a passive scan are parsed
+// and reported by `common::ble_sensor`, which documents the readings.
+//
+// Example configuration
+//
+// {
+//   "model": "ble_sensor",
+//   "name": "greenhouse-thermometer",
+//   "type": "sensor",
+//   "attributes": {
+//     "mac": "A4:C1:38:12:34:56",
+//     "max_age_secs": 120
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `mac` (required): The MAC address of the device, only the advertisements of the
+//    configured devices are recorded.
+//
+//  - `max_age_secs` (optional): How long the last advertisement of the device is
+//    reported, defaults to 60 seconds.
+//
+// The model is only available when Bluetooth and Bluedroid are enabled in the sdkconfig of
+// the project:
+//
+// CONFIG_BT_ENABLED=y
+// CONFIG_BT_BLUEDROID_ENABLED=y
+// CONFIG_BTDM_CTRL_MODE_BLE_ONLY=y
+//
+// The scan is started when the first sensor is built and runs until the device restarts, it
+// shares the radio with WiFi so a low duty cycle is used.
+
+use std::sync::{Arc, Mutex};
+
+use crate::common::{
+    ble_sensor::{BleSensor, BLE_ADVERTISEMENTS},
+    config::ConfigType,
+    registry::{ComponentRegistry, Dependency},
+    sensor::{SensorError, SensorType},
+};
+
+use crate::esp32::esp_idf_svc::bt::{Ble, BtDriver};
+use crate::esp32::esp_idf_svc::hal::modem::BluetoothModem;
+use crate::esp32::esp_idf_svc::sys::{
+    esp, esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC, esp_ble_gap_cb_param_t,
+    esp_ble_gap_register_callback, esp_ble_gap_set_scan_params, esp_ble_gap_start_scanning,
+    esp_ble_scan_duplicate_t_BLE_SCAN_DUPLICATE_DISABLE,
+    esp_ble_scan_filter_t_BLE_SCAN_FILTER_ALLOW_ALL, esp_ble_scan_params_t,
+    esp_ble_scan_type_t_BLE_SCAN_TYPE_PASSIVE, esp_gap_ble_cb_event_t,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_PARAM_SET_COMPLETE_EVT,
+    esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RESULT_EVT,
+    esp_gap_search_evt_t_ESP_GAP_SEARCH_INQ_RES_EVT,
+};
+
+// in units of 0.625ms, scan 30ms every 500ms
+const SCAN_INTERVAL: u16 = 800;
+const SCAN_WINDOW: u16 = 48;
+
+// set once the scan was started, the Bluetooth driver is kept for the lifetime of the program
+static SCANNER_STARTED: Mutex<bool> = Mutex::new(false);
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("ble_sensor", &from_config)
+        .is_err()
+    {
+        log::error!("ble_sensor type is already registered");
+    }
+}
+
+fn from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<SensorType, SensorError> {
+    let sensor = BleSensor::from_config(cfg)?;
+    start_scanner()?;
+    Ok(Arc::new(Mutex::new(sensor)))
+}
+
+fn start_scanner() -> Result<(), SensorError> {
+    let mut started = SCANNER_STARTED.lock().unwrap();
+    if *started {
+        return Ok(());
+    }
+    let driver = BtDriver::<Ble>::new(unsafe { BluetoothModem::new() }, None)
+        .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+    let mut params = esp_ble_scan_params_t {
+        scan_type: esp_ble_scan_type_t_BLE_SCAN_TYPE_PASSIVE,
+        own_addr_type: esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+        scan_filter_policy: esp_ble_scan_filter_t_BLE_SCAN_FILTER_ALLOW_ALL,
+        scan_interval: SCAN_INTERVAL,
+        scan_window: SCAN_WINDOW,
+        // every advertisement is reported to keep the signal strength current
+        scan_duplicate: esp_ble_scan_duplicate_t_BLE_SCAN_DUPLICATE_DISABLE,
+    };
+    unsafe {
+        esp!(esp_ble_gap_register_callback(Some(gap_event_handler)))
+            .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        // the scan is started once the parameters are applied
+        esp!(esp_ble_gap_set_scan_params(&mut params))
+            .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+    }
+    std::mem::forget(driver);
+    *started = true;
+    Ok(())
+}
+
+unsafe extern "C" fn gap_event_handler(
+    event: esp_gap_ble_cb_event_t,
+    param: *mut esp_ble_gap_cb_param_t,
+) {
+    #[allow(non_upper_case_globals)]
+    match event {
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_PARAM_SET_COMPLETE_EVT => {
+            // a duration of 0 scans until stopped
+            if let Err(err) = esp!(esp_ble_gap_start_scanning(0)) {
+                log::error!("ble_sensor: failed to start scanning: {}", err);
+            }
+        }
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_SCAN_RESULT_EVT => {
+            let result = &(*param).scan_rst;
+            if result.search_evt != esp_gap_search_evt_t_ESP_GAP_SEARCH_INQ_RES_EVT
+                || !BLE_ADVERTISEMENTS.is_watched(&result.bda)
+            {
+                return;
+            }
+            let len = (result.adv_data_len as usize + result.scan_rsp_len as usize)
+                .min(result.ble_adv.len());
+            BLE_ADVERTISEMENTS.record(result.bda, result.rssi as i8, &result.ble_adv[..len]);
+        }
+        _ => {}
+    }
+}
@@ -9,9 +9,12 @@ use std::{
 use crate::{
     common::{
         analog::{AnalogReader, AnalogReaderConfig, AnalogReaderType},
-        board::{Board, BoardError, BoardType},
-        config::ConfigType,
-        digital_interrupt::DigitalInterruptConfig,
+        board::{
+            board_do_command, board_status_to_struct, Board, BoardError, BoardType, PulseTrain,
+        },
+        config::{AttributeError, ConfigType, Kind},
+        digital_interrupt::{DigitalInterruptConfig, Tick},
+        generic::{DoCommand, GenericError},
         i2c::I2cHandleType,
         registry::ComponentRegistry,
         status::{Status, StatusError},
@@ -24,6 +27,8 @@ use super::{
     analog::Esp32AnalogReader,
     i2c::{Esp32I2C, Esp32I2cConfig},
     pin::Esp32GPIOPin,
+    sd_card::{mount_sd_card, SdCardConfig, SdCardError},
+    sleep::{enable_wake_sources, prepare_for_deep_sleep, record_boot, WakeSourceConfig},
 };
 
 use crate::esp32::esp_idf_svc::hal::{
@@ -44,11 +49,11 @@ pub(crate) fn register_models(registry: &mut ComponentRegistry) {
 }
 
 /// An ESP32 implementation that wraps esp-idf functionality
-#[derive(DoCommand)]
 pub struct EspBoard {
     pins: Vec<Esp32GPIOPin>,
     analogs: Vec<AnalogReaderType<u16>>,
     i2cs: HashMap<String, I2cHandleType>,
+    wake_sources: Vec<WakeSourceConfig>,
 }
 
 impl EspBoard {
@@ -61,6 +66,7 @@ impl EspBoard {
             pins,
             analogs,
             i2cs,
+            wake_sources: vec![],
         }
     }
     /// This is a temporary approach aimed at ensuring a good POC for runtime config consumption by the ESP32,
@@ -90,6 +96,7 @@ impl EspBoard {
                                             })
                                             .ok()?,
                                             adc1,
+                                            v.filter(),
                                         )));
                                     Some(p)
                                 }
@@ -102,6 +109,7 @@ impl EspBoard {
                                             })
                                             .ok()?,
                                             adc1,
+                                            v.filter(),
                                         )));
                                     Some(p)
                                 }
@@ -114,6 +122,7 @@ impl EspBoard {
                                             })
                                             .ok()?,
                                             adc1,
+                                            v.filter(),
                                         )));
                                     Some(p)
                                 }
@@ -126,6 +135,7 @@ impl EspBoard {
                                             })
                                             .ok()?,
                                             adc1,
+                                            v.filter(),
                                         )));
                                     Some(p)
                                 }
@@ -138,6 +148,7 @@ impl EspBoard {
                                             })
                                             .ok()?,
                                             adc1,
+                                            v.filter(),
                                         )));
                                     Some(p)
                                 }
@@ -150,6 +161,7 @@ impl EspBoard {
                                             })
                                             .ok()?,
                                             adc1,
+                                            v.filter(),
                                         )));
                                     Some(p)
                                 }
@@ -162,6 +174,7 @@ impl EspBoard {
                                             })
                                             .ok()?,
                                             adc1,
+                                            v.filter(),
                                         )));
                                     Some(p)
                                 }
@@ -174,6 +187,7 @@ impl EspBoard {
                                             })
                                             .ok()?,
                                             adc1,
+                                            v.filter(),
                                         )));
                                     Some(p)
                                 }
@@ -236,14 +250,35 @@ impl EspBoard {
                 }
             }
         }
+        let wake_sources = match cfg.get_attribute::<Vec<WakeSourceConfig>>("wake_sources") {
+            Ok(wake_sources) => wake_sources,
+            Err(AttributeError::KeyNotFound(_)) => vec![],
+            Err(_) => return Err(BoardError::BoardUnsupportedArgument("invalid wake_sources")),
+        };
+        if let Ok(sd_card) = cfg.get_attribute::<SdCardConfig>("sd_card") {
+            match mount_sd_card(&sd_card) {
+                Ok(()) | Err(SdCardError::AlreadyMounted) => {}
+                Err(err) => error!("failed to mount the sd card: {}", err),
+            }
+        }
         Ok(Arc::new(Mutex::new(Self {
             pins,
             analogs,
             i2cs,
+            wake_sources,
         })))
     }
 }
 
+impl DoCommand for EspBoard {
+    fn do_command(
+        &mut self,
+        command_struct: Option<google::protobuf::Struct>,
+    ) -> Result<Option<google::protobuf::Struct>, GenericError> {
+        board_do_command(self, command_struct)
+    }
+}
+
 impl Board for EspBoard {
     fn set_gpio_pin_level(&mut self, pin: i32, is_high: bool) -> Result<(), BoardError> {
         let p = self.pins.iter_mut().find(|p| p.pin() == pin);
@@ -300,6 +335,30 @@ impl Board for EspBoard {
             .ok_or(BoardError::GpioPinError(pin as u32, "not registered"))?;
         pin.set_pwm_frequency(frequency_hz)
     }
+    fn get_pwm_frequency_hz(&self, pin: i32) -> Result<f64, BoardError> {
+        let pin = self
+            .pins
+            .iter()
+            .find(|p| p.pin() == pin)
+            .ok_or(BoardError::GpioPinError(pin as u32, "not registered"))?;
+        Ok(pin.get_pwm_frequency_hz())
+    }
+    fn set_pwm_frequency_hz(&mut self, pin: i32, frequency_hz: f64) -> Result<(), BoardError> {
+        let pin = self
+            .pins
+            .iter_mut()
+            .find(|p| p.pin() == pin)
+            .ok_or(BoardError::GpioPinError(pin as u32, "not registered"))?;
+        pin.set_pwm_frequency_hz(frequency_hz)
+    }
+    fn pulse(&mut self, pin: i32, train: &PulseTrain) -> Result<(), BoardError> {
+        let pin = self
+            .pins
+            .iter_mut()
+            .find(|p| p.pin() == pin)
+            .ok_or(BoardError::GpioPinError(pin as u32, "not registered"))?;
+        pin.pulse(train)
+    }
     fn get_board_status(&self) -> Result<common::v1::BoardStatus, BoardError> {
         let mut b = common::v1::BoardStatus {
             analogs: HashMap::new(),
@@ -314,6 +373,14 @@ impl Board for EspBoard {
                 },
             );
         });
+        self.pins.iter().filter(|p| p.is_interrupt()).for_each(|p| {
+            b.digital_interrupts.insert(
+                p.pin().to_string(),
+                common::v1::DigitalInterruptStatus {
+                    value: p.get_event_count() as i64,
+                },
+            );
+        });
         Ok(b)
     }
     fn get_analog_reader_by_name(&self, name: String) -> Result<AnalogReaderType<u16>, BoardError> {
@@ -326,6 +393,14 @@ impl Board for EspBoard {
         &self,
         mode: component::board::v1::PowerMode,
         duration: Option<Duration>,
+    ) -> Result<(), BoardError> {
+        self.set_power_mode_with_extra(mode, duration, None)
+    }
+    fn set_power_mode_with_extra(
+        &self,
+        mode: component::board::v1::PowerMode,
+        duration: Option<Duration>,
+        extra: Option<google::protobuf::Struct>,
     ) -> Result<(), BoardError> {
         info!(
             "Esp32 received request to set power mode to {} for {} milliseconds",
@@ -351,11 +426,38 @@ impl Board for EspBoard {
             if result != crate::esp32::esp_idf_svc::sys::ESP_OK {
                 return Err(BoardError::BoardUnsupportedArgument("duration too long"));
             }
-            warn!("Esp32 entering deep sleep for {} microseconds!", dur_micros);
-        } else {
+        }
+
+        // wake sources passed with the request replace the ones of the configuration
+        let request_wake_sources = extra
+            .and_then(|extra| extra.fields.get("wake_sources").cloned())
+            .and_then(|value| value.kind)
+            .map(|kind| {
+                Kind::try_from(kind)
+                    .and_then(|kind| Vec::<WakeSourceConfig>::try_from(&kind))
+                    .map_err(|_| BoardError::BoardUnsupportedArgument("invalid wake_sources"))
+            })
+            .transpose()?;
+        let wake_sources = request_wake_sources
+            .as_deref()
+            .unwrap_or(&self.wake_sources);
+        enable_wake_sources(wake_sources)?;
+
+        if duration.is_none() && wake_sources.is_empty() {
             warn!("Esp32 entering deep sleep without scheduled wakeup!");
+        } else {
+            warn!(
+                "Esp32 entering deep sleep for {} with {} other wake source(s)!",
+                match duration {
+                    Some(dur) => format!("{} microseconds", dur.as_micros()),
+                    None => "<forever>".to_string(),
+                },
+                wake_sources.len()
+            );
         }
 
+        prepare_for_deep_sleep(duration.map(|dur| dur.as_millis() as u64));
+
         unsafe {
             crate::esp32::esp_idf_svc::sys::esp_deep_sleep_start();
         }
@@ -376,42 +478,46 @@ impl Board for EspBoard {
         }
         Err(BoardError::GpioPinError(pin as u32, "not configured"))
     }
+    fn get_digital_interrupt_ticks(
+        &self,
+        pin: i32,
+        since: u32,
+    ) -> Result<(Vec<Tick>, u32), BoardError> {
+        let p = self
+            .pins
+            .iter()
+            .find(|p| p.pin() == pin)
+            .ok_or(BoardError::GpioPinError(pin as u32, "not configured"))?;
+        if !p.is_interrupt() {
+            return Err(BoardError::GpioPinError(pin as u32, "not an interrupt"));
+        }
+        Ok(p.get_ticks_since(since))
+    }
 }
 
 impl Status for EspBoard {
     fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        let mut hm = HashMap::new();
-        let mut analogs = HashMap::new();
-        self.analogs.iter().for_each(|a| {
-            let mut analog = a.clone();
-            analogs.insert(
-                analog.name(),
-                google::protobuf::Value {
-                    kind: Some(google::protobuf::value::Kind::StructValue(
-                        google::protobuf::Struct {
-                            fields: HashMap::from([(
-                                "value".to_string(),
-                                google::protobuf::Value {
-                                    kind: Some(google::protobuf::value::Kind::NumberValue(
-                                        analog.read().unwrap_or(0).into(),
-                                    )),
-                                },
-                            )]),
-                        },
-                    )),
-                },
-            );
-        });
-        if !analogs.is_empty() {
-            hm.insert(
-                "analogs".to_string(),
-                google::protobuf::Value {
-                    kind: Some(google::protobuf::value::Kind::StructValue(
-                        google::protobuf::Struct { fields: analogs },
-                    )),
-                },
-            );
-        }
-        Ok(Some(google::protobuf::Struct { fields: hm }))
+        let pin_levels: Vec<(i32, bool)> =
+            self.pins.iter().map(|p| (p.pin(), p.is_high())).collect();
+        let mut status =
+            board_status_to_struct(&self.get_board_status().unwrap_or_default(), &pin_levels);
+        let boot = record_boot();
+        status.fields.insert(
+            "boot_count".to_string(),
+            google::protobuf::Value {
+                kind: Some(google::protobuf::value::Kind::NumberValue(
+                    boot.boot_count as f64,
+                )),
+            },
+        );
+        status.fields.insert(
+            "wake_reason".to_string(),
+            google::protobuf::Value {
+                kind: Some(google::protobuf::value::Kind::StringValue(
+                    boot.wake_reason.as_str().to_string(),
+                )),
+            },
+        );
+        Ok(Some(status))
     }
 }
@@ -0,0 +1,64 @@
+//! Persists the [calibrations](crate::common::calibration) of sensors in an NVS namespace
+
+use std::sync::Mutex;
+
+use crate::common::calibration::{CalibrationError, CalibrationStorage};
+use crate::esp32::esp_idf_svc::nvs::{EspDefaultNvs, EspDefaultNvsPartition, EspNvs};
+use crate::esp32::esp_idf_svc::sys::EspError;
+
+/// NVS namespace holding the calibrations
+pub const CALIBRATION_NVS_NAMESPACE: &str = "calibration";
+// calibrations are small JSON documents
+const MAX_CALIBRATION_SIZE: usize = 512;
+
+pub struct NvsCalibrationStorage {
+    nvs: Mutex<EspDefaultNvs>,
+}
+
+impl NvsCalibrationStorage {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self, EspError> {
+        Ok(Self {
+            nvs: Mutex::new(EspNvs::new(partition, CALIBRATION_NVS_NAMESPACE, true)?),
+        })
+    }
+}
+
+fn storage_error(err: EspError) -> CalibrationError {
+    CalibrationError::StorageError(err.to_string())
+}
+
+impl CalibrationStorage for NvsCalibrationStorage {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, CalibrationError> {
+        let mut buffer = [0_u8; MAX_CALIBRATION_SIZE];
+        Ok(self
+            .nvs
+            .lock()
+            .unwrap()
+            .get_blob(key, &mut buffer)
+            .map_err(storage_error)?
+            .map(|blob| blob.to_vec()))
+    }
+
+    fn store(&self, key: &str, value: &[u8]) -> Result<(), CalibrationError> {
+        if value.len() > MAX_CALIBRATION_SIZE {
+            return Err(CalibrationError::StorageError(format!(
+                "calibration of {} bytes is too large",
+                value.len()
+            )));
+        }
+        self.nvs
+            .lock()
+            .unwrap()
+            .set_blob(key, value)
+            .map_err(storage_error)
+    }
+
+    fn remove(&self, key: &str) -> Result<(), CalibrationError> {
+        self.nvs
+            .lock()
+            .unwrap()
+            .remove(key)
+            .map(|_| ())
+            .map_err(storage_error)
+    }
+}
@@ -0,0 +1,169 @@
+//! CAN bus on the TWAI controller of the ESP32, shared by every component configured with the
+//! same pins. Components talking to devices over CAN take the following attributes:
+//!
+//! ```json
+//! {
+//!     "tx_pin": 5,
+//!     "rx_pin": 4,
+//!     "bitrate": 500000
+//! }
+//! ```
+//!
+//! `bitrate` is one of 125000, 250000, 500000 (the default) or 1000000. The ESP32 has a single
+//! TWAI controller, the components on the bus must agree on its configuration. A transceiver
+//! (such as the SN65HVD230) is required between the pins and the bus.
+
+use std::sync::{Arc, Mutex};
+
+use crate::common::can::{CanBus, CanBusType, CanDriver, CanError, CanFrame, CAN_MAX_DATA_LEN};
+use crate::common::config::{AttributeError, ConfigType};
+use crate::esp32::esp_idf_svc::sys::{
+    esp, twai_driver_install, twai_driver_uninstall, twai_filter_config_t, twai_general_config_t,
+    twai_message_t, twai_mode_t_TWAI_MODE_NORMAL, twai_receive, twai_start, twai_stop,
+    twai_timing_config_t, twai_transmit, ESP_ERR_TIMEOUT, TWAI_MSG_FLAG_EXTD,
+};
+
+const DEFAULT_BITRATE: u32 = 500_000;
+const QUEUE_LEN: u32 = 32;
+// ticks to wait for room in the transmit queue
+const TRANSMIT_TIMEOUT_TICKS: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TwaiConfig {
+    tx_pin: i32,
+    rx_pin: i32,
+    bitrate: u32,
+}
+
+// the bus and the configuration it was installed with
+static TWAI_BUS: Mutex<Option<(TwaiConfig, CanBusType)>> = Mutex::new(None);
+
+// Timings for the 80MHz APB clock, 20 time quanta per bit sampled at 80%
+fn timing_config(bitrate: u32) -> Result<twai_timing_config_t, CanError> {
+    let brp = match bitrate {
+        125_000 => 32,
+        250_000 => 16,
+        500_000 => 8,
+        1_000_000 => 4,
+        _ => {
+            return Err(CanError::ConfigError(
+                "bitrate must be 125000, 250000, 500000 or 1000000",
+            ))
+        }
+    };
+    Ok(twai_timing_config_t {
+        brp,
+        tseg_1: 15,
+        tseg_2: 4,
+        sjw: 3,
+        triple_sampling: false,
+        ..Default::default()
+    })
+}
+
+/// Returns the CAN bus configured by the `tx_pin`, `rx_pin` and `bitrate` attributes, the
+/// TWAI driver is installed by the first component using it
+pub fn can_bus_from_config(cfg: &ConfigType) -> Result<CanBusType, CanError> {
+    let pin = |name: &'static str| {
+        cfg.get_attribute::<i32>(name)
+            .map_err(|_| CanError::ConfigError("missing `tx_pin` or `rx_pin`"))
+    };
+    let config = TwaiConfig {
+        tx_pin: pin("tx_pin")?,
+        rx_pin: pin("rx_pin")?,
+        bitrate: match cfg.get_attribute::<u32>("bitrate") {
+            Ok(bitrate) => bitrate,
+            Err(AttributeError::KeyNotFound(_)) => DEFAULT_BITRATE,
+            Err(_) => return Err(CanError::ConfigError("invalid `bitrate`")),
+        },
+    };
+    let mut installed = TWAI_BUS.lock().unwrap();
+    if let Some((installed_config, bus)) = installed.as_ref() {
+        if *installed_config != config {
+            return Err(CanError::ConfigError(
+                "the CAN bus is already configured with other pins or bitrate",
+            ));
+        }
+        return Ok(bus.clone());
+    }
+    let bus = Arc::new(Mutex::new(CanBus::new(Box::new(TwaiDriver::new(config)?))));
+    *installed = Some((config, bus.clone()));
+    Ok(bus)
+}
+
+struct TwaiDriver;
+
+impl TwaiDriver {
+    fn new(config: TwaiConfig) -> Result<Self, CanError> {
+        let general = twai_general_config_t {
+            mode: twai_mode_t_TWAI_MODE_NORMAL,
+            tx_io: config.tx_pin,
+            rx_io: config.rx_pin,
+            // TWAI_IO_UNUSED
+            clkout_io: -1,
+            bus_off_io: -1,
+            tx_queue_len: QUEUE_LEN,
+            rx_queue_len: QUEUE_LEN,
+            ..Default::default()
+        };
+        let timing = timing_config(config.bitrate)?;
+        // every frame is accepted, the bus keeps the ones components ask for
+        let filter = twai_filter_config_t {
+            acceptance_code: 0,
+            acceptance_mask: u32::MAX,
+            single_filter: true,
+        };
+        unsafe {
+            esp!(twai_driver_install(&general, &timing, &filter))
+                .map_err(|err| CanError::TransmitError(err.code()))?;
+            if let Err(err) = esp!(twai_start()) {
+                twai_driver_uninstall();
+                return Err(CanError::TransmitError(err.code()));
+            }
+        }
+        Ok(Self)
+    }
+}
+
+impl CanDriver for TwaiDriver {
+    fn transmit(&mut self, frame: &CanFrame) -> Result<(), CanError> {
+        if frame.data.len() > CAN_MAX_DATA_LEN {
+            return Err(CanError::FrameTooLong(frame.data.len()));
+        }
+        let mut message = twai_message_t {
+            identifier: frame.id,
+            data_length_code: frame.data.len() as u8,
+            ..Default::default()
+        };
+        if frame.extended {
+            message.__bindgen_anon_1.flags = TWAI_MSG_FLAG_EXTD;
+        }
+        message.data[..frame.data.len()].copy_from_slice(&frame.data);
+        unsafe { esp!(twai_transmit(&message, TRANSMIT_TIMEOUT_TICKS)) }
+            .map_err(|err| CanError::TransmitError(err.code()))
+    }
+
+    fn receive(&mut self) -> Result<Option<CanFrame>, CanError> {
+        let mut message = twai_message_t::default();
+        match unsafe { twai_receive(&mut message, 0) } {
+            0 => {}
+            err if err == ESP_ERR_TIMEOUT as i32 => return Ok(None),
+            err => return Err(CanError::ReceiveError(err)),
+        }
+        let len = (message.data_length_code as usize).min(CAN_MAX_DATA_LEN);
+        Ok(Some(CanFrame {
+            id: message.identifier,
+            extended: unsafe { message.__bindgen_anon_1.flags } & TWAI_MSG_FLAG_EXTD != 0,
+            data: message.data[..len].to_vec(),
+        }))
+    }
+}
+
+impl Drop for TwaiDriver {
+    fn drop(&mut self) {
+        unsafe {
+            twai_stop();
+            twai_driver_uninstall();
+        }
+    }
+}
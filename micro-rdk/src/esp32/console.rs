@@ -0,0 +1,139 @@
+//! Serial console for the ESP32, commands from [crate::common::console] are registered with
+//! esp_console and served by a REPL running on the default console UART. This lets a field
+//! technician inspect a device that has no network connectivity.
+//!
+//! The REPL task forwards the command lines to a task on the executor, which runs them with the
+//! [Console] and sends back their output.
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::sync::mpsc;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use async_channel::{Receiver, Sender};
+
+use crate::common::console::{Console, COMMANDS};
+use crate::common::robot::LocalRobot;
+use crate::esp32::esp_idf_svc::sys::{
+    esp, esp_console_cmd_register, esp_console_cmd_t, esp_console_dev_uart_config_t,
+    esp_console_new_repl_uart, esp_console_repl_config_t, esp_console_repl_t,
+    esp_console_start_repl, esp_wifi_sta_get_ap_info, wifi_ap_record_t, EspError,
+    CONFIG_ESP_CONSOLE_UART_BAUDRATE, CONFIG_ESP_CONSOLE_UART_NUM,
+};
+use crate::esp32::exec::Esp32Executor;
+
+static PROMPT: &[u8] = b"micro-rdk> \0";
+const CONSOLE_TASK_STACK_SIZE: u32 = 8192;
+const CONSOLE_TASK_PRIORITY: u32 = 2;
+const CONSOLE_HISTORY_LEN: u32 = 16;
+
+/// Command line typed in the REPL, and where to send the output and status of the command
+type ConsoleRequest = (String, mpsc::Sender<(String, c_int)>);
+
+// esp_console callbacks don't carry a context, the queue of the task running the commands is
+// kept here
+static CONSOLE: OnceLock<Sender<ConsoleRequest>> = OnceLock::new();
+
+unsafe extern "C" fn console_command_handler(argc: c_int, argv: *mut *mut c_char) -> c_int {
+    let Some(requests) = CONSOLE.get() else {
+        return 1;
+    };
+    let line = (0..argc as usize)
+        .map(|i| CStr::from_ptr(*argv.add(i)).to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (reply, output) = mpsc::channel();
+    if requests.send_blocking((line, reply)).is_err() {
+        println!("error: the console stopped");
+        return 1;
+    }
+    match output.recv() {
+        Ok((output, status)) => {
+            println!("{}", output);
+            status
+        }
+        Err(_) => {
+            println!("error: the console stopped");
+            1
+        }
+    }
+}
+
+async fn run_commands(console: Console, requests: Receiver<ConsoleRequest>) {
+    while let Ok((line, reply)) = requests.recv().await {
+        let output = match console.execute(&line) {
+            Ok(output) => (output, 0),
+            Err(err) => (format!("error: {}", err), 1),
+        };
+        let _ = reply.send(output);
+    }
+}
+
+/// Reports the access point the station is associated with
+pub fn wifi_status() -> String {
+    let mut ap_info = wifi_ap_record_t::default();
+    match unsafe { esp!(esp_wifi_sta_get_ap_info(&mut ap_info)) } {
+        Ok(()) => {
+            let ssid_len = ap_info
+                .ssid
+                .iter()
+                .position(|b| *b == 0)
+                .unwrap_or(ap_info.ssid.len());
+            format!(
+                "connected to {:?}, channel {}, rssi {} dBm",
+                String::from_utf8_lossy(&ap_info.ssid[..ssid_len]),
+                ap_info.primary,
+                ap_info.rssi
+            )
+        }
+        Err(err) => format!("not connected ({})", err),
+    }
+}
+
+/// Starts the console REPL in its own task, it can only be started once. The commands run on
+/// the executor of the calling thread.
+pub fn start_console(robot: Arc<RwLock<LocalRobot>>) -> Result<(), EspError> {
+    let (requests, queue) = async_channel::bounded(1);
+    if CONSOLE.set(requests).is_err() {
+        log::warn!("console is already running");
+        return Ok(());
+    }
+    let console = Console::new(robot).with_wifi_status(wifi_status);
+    Esp32Executor::new()
+        .spawn(run_commands(console, queue))
+        .detach();
+
+    for (name, hint, help) in COMMANDS {
+        // esp_console keeps the pointers, the strings live for the lifetime of the program
+        let cmd = esp_console_cmd_t {
+            command: CString::new(name).unwrap().into_raw(),
+            help: CString::new(help).unwrap().into_raw(),
+            hint: match hint.is_empty() {
+                true => std::ptr::null(),
+                false => CString::new(hint).unwrap().into_raw(),
+            },
+            func: Some(console_command_handler),
+            argtable: std::ptr::null_mut(),
+        };
+        esp!(unsafe { esp_console_cmd_register(&cmd) })?;
+    }
+
+    let repl_config = esp_console_repl_config_t {
+        max_history_len: CONSOLE_HISTORY_LEN,
+        history_save_path: std::ptr::null(),
+        task_stack_size: CONSOLE_TASK_STACK_SIZE,
+        task_priority: CONSOLE_TASK_PRIORITY,
+        prompt: PROMPT.as_ptr() as *const c_char,
+        ..Default::default()
+    };
+    let uart_config = esp_console_dev_uart_config_t {
+        channel: CONFIG_ESP_CONSOLE_UART_NUM as _,
+        baud_rate: CONFIG_ESP_CONSOLE_UART_BAUDRATE as _,
+        tx_gpio_num: -1,
+        rx_gpio_num: -1,
+    };
+    let mut repl: *mut esp_console_repl_t = std::ptr::null_mut();
+    esp!(unsafe { esp_console_new_repl_uart(&uart_config, &repl_config, &mut repl) })?;
+    esp!(unsafe { esp_console_start_repl(repl) })?;
+    log::info!("serial console started, type `help` for the list of commands");
+    Ok(())
+}
@@ -11,7 +11,8 @@ use std::{
 
 use crate::common::webrtc::{
     certificate::Certificate,
-    dtls::{DtlsBuilder, DtlsConnector, DtlsError},
+    dtls::{DtlsBuilder, DtlsConnector, DtlsError, DtlsSrtpKeying},
+    srtp::SRTP_KEYING_MATERIAL_LEN,
     udp_mux::UdpMux,
 };
 
@@ -35,8 +36,88 @@ use futures_lite::{AsyncRead, AsyncWrite, Future};
 use log::{log, Level};
 use thiserror::Error;
 
+type MbedtlsExportKeysExt = unsafe extern "C" fn(
+    p_expkey: *mut c_void,
+    ms: *const c_uchar,
+    kb: *const c_uchar,
+    maclen: usize,
+    keylen: usize,
+    ivlen: usize,
+    client_random: *const c_uchar,
+    server_random: *const c_uchar,
+    tls_prf_type: c_int,
+) -> c_int;
+
 extern "C" {
     fn mbedtls_debug_set_threshold(level: c_int);
+    fn mbedtls_ssl_conf_export_keys_ext_cb(
+        conf: *mut mbedtls_ssl_config,
+        f_export_keys_ext: Option<MbedtlsExportKeysExt>,
+        p_export_keys: *mut c_void,
+    );
+    fn mbedtls_ssl_tls_prf(
+        prf: c_int,
+        secret: *const c_uchar,
+        slen: usize,
+        label: *const c_char,
+        random: *const c_uchar,
+        rlen: usize,
+        dstbuf: *mut c_uchar,
+        dlen: usize,
+    ) -> c_int;
+}
+
+const MASTER_SECRET_LEN: usize = 48;
+const RANDOM_LEN: usize = 32;
+
+// Secrets of the handshake needed to derive the DTLS-SRTP keying material, mbedtls doesn't
+// implement the exporter so we compute it from the master secret like rfc5705 describes
+struct ExportedKeys {
+    master_secret: [u8; MASTER_SECRET_LEN],
+    // client random followed by server random
+    randoms: [u8; 2 * RANDOM_LEN],
+    tls_prf_type: c_int,
+    valid: bool,
+}
+
+impl Default for ExportedKeys {
+    fn default() -> Self {
+        Self {
+            master_secret: [0; MASTER_SECRET_LEN],
+            randoms: [0; 2 * RANDOM_LEN],
+            tls_prf_type: 0,
+            valid: false,
+        }
+    }
+}
+
+impl Drop for ExportedKeys {
+    fn drop(&mut self) {
+        self.master_secret.fill(0);
+    }
+}
+
+unsafe extern "C" fn mbedtls_export_keys(
+    p_expkey: *mut c_void,
+    ms: *const c_uchar,
+    _kb: *const c_uchar,
+    _maclen: usize,
+    _keylen: usize,
+    _ivlen: usize,
+    client_random: *const c_uchar,
+    server_random: *const c_uchar,
+    tls_prf_type: c_int,
+) -> c_int {
+    let keys: &mut ExportedKeys = &mut *(p_expkey as *mut _);
+    keys.master_secret
+        .copy_from_slice(std::slice::from_raw_parts(ms, MASTER_SECRET_LEN));
+    keys.randoms[..RANDOM_LEN]
+        .copy_from_slice(std::slice::from_raw_parts(client_random, RANDOM_LEN));
+    keys.randoms[RANDOM_LEN..]
+        .copy_from_slice(std::slice::from_raw_parts(server_random, RANDOM_LEN));
+    keys.tls_prf_type = tls_prf_type;
+    keys.valid = true;
+    0
 }
 
 pub struct SslStreamState<S> {
@@ -195,6 +276,7 @@ pub(crate) struct SSLContext {
     pk_ctx: Box<mbedtls_pk_context>,
     timer_ctx: Box<Esp32DtlsDelay>,
     strp_profiles: Box<[MbedTlsStrpProfile]>,
+    exported_keys: Box<ExportedKeys>,
 }
 
 impl Drop for SSLContext {
@@ -320,6 +402,11 @@ impl SSLContext {
                 if ret != 0 {
                     return Err(SSLError::SSLSrtpConfigFailure(ret));
                 }
+                mbedtls_ssl_conf_export_keys_ext_cb(
+                    self.ssl_config.as_mut(),
+                    Some(mbedtls_export_keys),
+                    self.exported_keys.as_mut() as *mut ExportedKeys as *mut c_void,
+                );
             }
         }
 
@@ -341,6 +428,25 @@ impl SSLContext {
     fn set_srtp_profiles(&mut self, profiles: [MbedTlsStrpProfile; 2]) {
         self.strp_profiles = Box::new(profiles);
     }
+    fn srtp_keying_material(&self) -> Option<[u8; SRTP_KEYING_MATERIAL_LEN]> {
+        if !self.exported_keys.valid {
+            return None;
+        }
+        let mut material = [0; SRTP_KEYING_MATERIAL_LEN];
+        let ret = unsafe {
+            mbedtls_ssl_tls_prf(
+                self.exported_keys.tls_prf_type,
+                self.exported_keys.master_secret.as_ptr(),
+                MASTER_SECRET_LEN,
+                b"EXTRACTOR-dtls_srtp\0".as_ptr() as *const c_char,
+                self.exported_keys.randoms.as_ptr(),
+                2 * RANDOM_LEN,
+                material.as_mut_ptr(),
+                SRTP_KEYING_MATERIAL_LEN,
+            )
+        };
+        (ret == 0).then_some(material)
+    }
 }
 
 pub struct Esp32Dtls<C> {
@@ -714,6 +820,12 @@ where
 
 unsafe impl<S> Send for AsyncDtlsStream<S> {}
 
+impl<S> DtlsSrtpKeying for AsyncDtlsStream<S> {
+    fn srtp_keying_material(&self) -> Option<[u8; SRTP_KEYING_MATERIAL_LEN]> {
+        self.0.context.srtp_keying_material()
+    }
+}
+
 impl<S> AsyncRead for AsyncDtlsStream<S>
 where
     S: AsyncRead + AsyncWrite,
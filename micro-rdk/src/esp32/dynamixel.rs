@@ -0,0 +1,74 @@
+// Support for Robotis Dynamixel servos (X series, protocol 2.0) sharing a half-duplex serial
+// bus. Each servo is configured as a `dynamixel` servo, or as a `dynamixel` motor to turn it
+// continuously in wheel mode. The components are implemented by `common::dynamixel`, which
+// documents their attributes.
+//
+// Example configuration, a pan servo and a wheel on the same bus
+//
+// {
+//   "model": "dynamixel",
+//   "name": "pan",
+//   "type": "servo",
+//   "attributes": {
+//     "tx_pin": 17,
+//     "rx_pin": 16,
+//     "direction_pin": 4,
+//     "id": 1,
+//     "max_angle_deg": 180
+//   },
+// },
+// {
+//   "model": "dynamixel",
+//   "name": "wheel",
+//   "type": "motor",
+//   "attributes": {
+//     "tx_pin": 17,
+//     "rx_pin": 16,
+//     "direction_pin": 4,
+//     "id": 2,
+//     "max_rpm": 60
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `uart_port`, `tx_pin`, `rx_pin`, `direction_pin` and `baud_rate`: the serial port the
+//    servos are connected to, see `esp32::uart`. `baud_rate` defaults to 57600, the factory
+//    setting of the servos. `direction_pin` switches the direction of the half-duplex
+//    adapter, it can be omitted when the adapter switches by itself. Every servo on the bus
+//    must use the same port configuration.
+
+use crate::common::config::ConfigType;
+use crate::common::dynamixel::{DynamixelBus, DynamixelServo, DynamixelWheel};
+use crate::common::motor::{MotorError, MotorType};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::servo::{ServoError, ServoType};
+
+use super::uart::uart_from_config;
+
+const DEFAULT_BAUD_RATE: u32 = 57600;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_servo("dynamixel", &servo_from_config)
+        .is_err()
+    {
+        log::error!("dynamixel servo model is already registered")
+    }
+    if registry
+        .register_motor("dynamixel", &motor_from_config)
+        .is_err()
+    {
+        log::error!("dynamixel motor model is already registered")
+    }
+}
+
+fn servo_from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<ServoType, ServoError> {
+    let bus = DynamixelBus::new(uart_from_config(&cfg, DEFAULT_BAUD_RATE)?);
+    DynamixelServo::from_bus_and_config(bus, cfg)
+}
+
+fn motor_from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<MotorType, MotorError> {
+    let bus = DynamixelBus::new(uart_from_config(&cfg, DEFAULT_BAUD_RATE)?);
+    DynamixelWheel::from_bus_and_config(bus, cfg)
+}
@@ -1,5 +1,8 @@
 use super::pin::PinExt;
-use super::pulse_counter::{get_unit, isr_install, isr_remove_unit};
+use super::pulse_counter::{
+    get_unit, glitch_filter_from_config, isr_install, isr_remove_unit, set_glitch_filter,
+    ticks_per_rotation_from_config,
+};
 
 use crate::esp32::esp_idf_svc::hal::gpio::{AnyInputPin, Input, PinDriver};
 use crate::esp32::esp_idf_svc::sys::pcnt_channel_edge_action_t_PCNT_CHANNEL_EDGE_ACTION_DECREASE as pcnt_count_dec;
@@ -53,6 +56,9 @@ pub struct PulseStorage {
 pub struct Esp32Encoder<A, B> {
     pulse_counter: Box<PulseStorage>,
     config: pcnt_config_t,
+    glitch_filter_cycles: u16,
+    // ticks counted over a rotation of the shaft, from the `ticks_per_rotation` attribute
+    ticks_per_rotation: Option<u32>,
     a: A,
     b: B,
 }
@@ -62,7 +68,9 @@ where
     A: InputPin + PinExt,
     B: InputPin + PinExt,
 {
-    pub fn new(a: A, b: B) -> Result<Self, EncoderError> {
+    /// Pulses shorter than `glitch_filter_cycles` APB clock cycles are ignored, 0 disables the
+    /// filter
+    pub fn new(a: A, b: B, glitch_filter_cycles: u16) -> Result<Self, EncoderError> {
         let unit = get_unit();
         let pcnt = Box::new(PulseStorage {
             acc: Arc::new(AtomicI32::new(0)),
@@ -82,6 +90,8 @@ where
                 channel: pcnt_channel_0,
                 unit,
             },
+            glitch_filter_cycles,
+            ticks_per_rotation: None,
             a,
             b,
         };
@@ -97,6 +107,8 @@ where
         let pin_a_num = cfg.get_attribute::<i32>("a")?;
 
         let pin_b_num = cfg.get_attribute::<i32>("b")?;
+        let glitch_filter_cycles = glitch_filter_from_config(&cfg)?;
+        let ticks_per_rotation = ticks_per_rotation_from_config(&cfg)?;
         let a = match PinDriver::input(unsafe { AnyInputPin::new(pin_a_num) }) {
             Ok(a) => a,
             Err(err) => return Err(EncoderError::EncoderCodeError(err.code())),
@@ -105,7 +117,9 @@ where
             Ok(b) => b,
             Err(err) => return Err(EncoderError::EncoderCodeError(err.code())),
         };
-        Ok(Arc::new(Mutex::new(Esp32Encoder::new(a, b)?)))
+        let mut enc = Esp32Encoder::new(a, b, glitch_filter_cycles)?;
+        enc.ticks_per_rotation = ticks_per_rotation;
+        Ok(Arc::new(Mutex::new(enc)))
     }
 
     fn start(&self) -> Result<(), EncoderError> {
@@ -174,6 +188,7 @@ where
                 err => return Err(EncoderError::EncoderCodeError(err)),
             }
         }
+        set_glitch_filter(self.config.unit, self.glitch_filter_cycles)?;
 
         unsafe {
             match crate::esp32::esp_idf_svc::sys::pcnt_counter_pause(self.config.unit) {
@@ -260,6 +275,9 @@ where
     fn reset_position(&mut self) -> Result<(), EncoderError> {
         self.reset()
     }
+    fn ticks_per_rotation(&self) -> Option<u32> {
+        self.ticks_per_rotation
+    }
 }
 
 impl<A, B> Status for Esp32Encoder<A, B>
@@ -1,80 +1,191 @@
 #![allow(dead_code)]
 
 use std::{
-    net::Ipv4Addr,
+    net::{Ipv4Addr, SocketAddr},
     rc::Rc,
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
-use crate::common::{
-    app_client::{AppClientBuilder, AppClientConfig},
-    conn::{
-        mdns::NoMdns,
-        server::{ViamServerBuilder, WebRtcConfiguration},
+use crate::{
+    common::{
+        app_client::{AppClientBuilder, AppClientConfig},
+        authorization::AuthPolicy,
+        automation::AutomationEngine,
+        button::ButtonWatcher,
+        conn::{
+            mdns::NoMdns,
+            server::{ViamServerBuilder, WebRtcConfiguration},
+        },
+        entry::{RobotPartRepresentation, RobotRepresentation},
+        grpc_client::GrpcClient,
+        instrumentation::Instrumentation,
+        json_endpoint::JsonEndpoint,
+        log::config_log_entry,
+        power_management::{DutyCycle, LightSleepConfig, PowerProfile},
+        robot::LocalRobot,
     },
-    entry::RobotRepresentation,
-    grpc_client::GrpcClient,
-    log::config_log_entry,
-    robot::LocalRobot,
+    proto::app::v1::ConfigResponse,
 };
 
+#[cfg(feature = "builtin-components")]
+use crate::common::alerts;
+#[cfg(feature = "builtin-components")]
+use crate::common::battery;
+#[cfg(feature = "builtin-components")]
+use crate::common::geofence;
 #[cfg(feature = "data")]
-use crate::common::{data_manager::DataManager, data_store::StaticMemoryDataStore};
+use crate::common::{
+    conn::server::AppConnector, data_manager::DataManager, data_store::DefaultDataStore,
+};
+#[cfg(feature = "mqtt")]
+use {
+    super::mqtt::Esp32MqttPublisher,
+    crate::common::mqtt::{MqttConfig, MqttUploader},
+};
 
 use super::{
     certificate::WebRtcCertificate,
     dtls::Esp32DtlsBuilder,
     exec::Esp32Executor,
-    tcp::Esp32Stream,
+    power_profile::apply_power_profile,
+    tcp::{Esp32Listener, Esp32Stream},
     tls::{Esp32TLS, Esp32TLSServerConfig},
 };
 
 use async_io::Timer;
 
-pub async fn serve_web_inner(
-    app_config: AppClientConfig,
-    _tls_server_config: Esp32TLSServerConfig,
-    repr: RobotRepresentation,
-    _ip: Ipv4Addr,
-    webrtc_certificate: WebRtcCertificate,
-    exec: Esp32Executor,
-    max_webrtc_connection: usize,
+/// Applies the power settings and starts the services and watchers configured for the robot
+fn start_services(
+    cfg_response: &ConfigResponse,
+    robot: &Arc<RwLock<LocalRobot>>,
+    auth: &Rc<AuthPolicy>,
+    exec: &Esp32Executor,
 ) {
-    // TODO(NPM) this is a workaround so that async-io thread has started before we
-    // instantiate the Async<TCPStream> for the connection to app.viam.com
-    // otherwise there is a chance a race happens and will listen to events before full
-    // initialization is done
-    let _ = Timer::after(std::time::Duration::from_millis(60)).await;
+    match PowerProfile::from_config(cfg_response) {
+        Ok(Some(profile)) => {
+            if let Err(err) = apply_power_profile(&profile) {
+                log::error!("couldn't apply power profile {}: {:?}", profile.name, err);
+            }
+        }
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't read power profile: {:?}", err),
+    }
 
-    let mut client_connector = Esp32TLS::new_client();
-    let mdns = NoMdns {};
+    #[cfg(feature = "console")]
+    if let Err(err) = super::console::start_console(robot.clone()) {
+        log::error!("couldn't start the serial console: {:?}", err);
+    }
 
-    let (cfg_response, robot) = {
-        let cloned_exec = exec.clone();
-        let conn = client_connector.open_ssl_context(None).unwrap();
-        let conn = Esp32Stream::TLSStream(Box::new(conn));
-        let grpc_client = Box::new(
-            GrpcClient::new(conn, cloned_exec, "https://app.viam.com:443")
-                .await
-                .unwrap(),
-        );
+    match Instrumentation::from_config(cfg_response) {
+        Ok(Some(mut instrumentation)) => exec
+            .spawn(async move { instrumentation.run().await })
+            .detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start instrumentation: {:?}", err),
+    }
+
+    start_robot_services(cfg_response, robot, auth, exec);
+
+    match LightSleepConfig::from_config(cfg_response) {
+        Ok(Some(light_sleep)) => exec
+            .spawn(async move {
+                loop {
+                    Timer::after(light_sleep.idle()).await;
+                    if let Some(duration) = light_sleep.sleep_duration(Instant::now()) {
+                        if let Err(err) = super::sleep::light_sleep(duration) {
+                            log::error!("couldn't enter light sleep: {:?}", err);
+                        }
+                    }
+                }
+            })
+            .detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't configure light sleep: {:?}", err),
+    }
+}
+
+/// Starts the watchers configured for the robot, for each robot part the device serves
+fn start_robot_services(
+    cfg_response: &ConfigResponse,
+    robot: &Arc<RwLock<LocalRobot>>,
+    auth: &Rc<AuthPolicy>,
+    exec: &Esp32Executor,
+) {
+    match AutomationEngine::from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(mut automations)) => exec.spawn(async move { automations.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start automations: {:?}", err),
+    }
+
+    match JsonEndpoint::from_robot_and_config(cfg_response, robot.clone(), auth.clone()) {
+        Ok(Some(endpoint)) => exec
+            .spawn(async move {
+                if let Err(err) = endpoint.run().await {
+                    log::error!("json endpoint stopped: {:?}", err);
+                }
+            })
+            .detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start json endpoint: {:?}", err),
+    }
+    match ButtonWatcher::from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(watcher)) => exec.spawn(async move { watcher.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start watching buttons: {:?}", err),
+    }
+
+    #[cfg(feature = "builtin-components")]
+    match geofence::watcher_from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(watcher)) => exec.spawn(async move { watcher.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start watching geofences: {:?}", err),
+    }
+
+    #[cfg(feature = "builtin-components")]
+    match alerts::watcher_from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(watcher)) => exec.spawn(async move { watcher.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start watching alerts: {:?}", err),
+    }
+
+    #[cfg(feature = "builtin-components")]
+    match battery::watcher_from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(watcher)) => exec.spawn(async move { watcher.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start watching batteries: {:?}", err),
+    }
+}
+
+/// Fetches the config of the robot part `app_config` authenticates as and builds its robot
+async fn build_robot(
+    client_connector: &mut Esp32TLS,
+    app_config: &AppClientConfig,
+    repr: RobotRepresentation,
+    exec: &Esp32Executor,
+) -> (Box<ConfigResponse>, Arc<RwLock<LocalRobot>>) {
+    let cloned_exec = exec.clone();
+    let conn = client_connector.open_ssl_context(None).unwrap();
+    let conn = Esp32Stream::TLSStream(Box::new(conn));
+    let grpc_client = Box::new(
+        GrpcClient::new(conn, cloned_exec, "https://app.viam.com:443")
+            .await
+            .unwrap(),
+    );
 
-        let builder = AppClientBuilder::new(grpc_client, app_config.clone());
+    let builder = AppClientBuilder::new(grpc_client, app_config.clone());
 
-        let mut client = builder.build().await.unwrap();
+    let mut client = builder.build().await.unwrap();
 
-        let (cfg_response, cfg_received_datetime) = client.get_config().await.unwrap();
+    let (cfg_response, cfg_received_datetime) = client.get_config().await.unwrap();
 
-        let robot = match repr {
-            RobotRepresentation::WithRobot(robot) => Arc::new(Mutex::new(robot)),
-            RobotRepresentation::WithRegistry(registry) => {
-                log::info!("building robot from config");
-                let r = match LocalRobot::from_cloud_config(
-                    &cfg_response,
-                    registry,
-                    cfg_received_datetime,
-                ) {
+    let robot = match repr {
+        RobotRepresentation::WithRobot(robot) => Arc::new(RwLock::new(robot)),
+        RobotRepresentation::WithRegistry(registry) => {
+            log::info!("building robot from config");
+            let r =
+                match LocalRobot::from_cloud_config(&cfg_response, registry, cfg_received_datetime)
+                {
                     Ok(robot) => {
                         if let Some(datetime) = cfg_received_datetime {
                             let logs = vec![config_log_entry(datetime, None)];
@@ -97,22 +208,161 @@ pub async fn serve_web_inner(
                         panic!("couldn't build robot");
                     }
                 };
-                Arc::new(Mutex::new(r))
+            Arc::new(RwLock::new(r))
+        }
+    };
+
+    (cfg_response, robot)
+}
+
+/// Builds the data manager of the robot part `app_config` authenticates as, also syncing to
+/// MQTT when configured
+#[cfg(feature = "data")]
+fn build_data_manager(
+    cfg_response: &ConfigResponse,
+    app_config: &AppClientConfig,
+    robot: &Arc<RwLock<LocalRobot>>,
+) -> Option<DataManager<DefaultDataStore>> {
+    let data_manager_svc = DataManager::<DefaultDataStore>::from_robot_and_config(
+        cfg_response,
+        app_config,
+        robot.clone(),
+    );
+
+    #[cfg(feature = "mqtt")]
+    let data_manager_svc = data_manager_svc.map(|svc| {
+        svc.map(|mut data_manager| {
+            match MqttConfig::from_config(cfg_response).and_then(|cfg| {
+                cfg.map(|cfg| {
+                    Esp32MqttPublisher::new(&cfg, &data_manager.part_id())
+                        .map(|publisher| MqttUploader::new(publisher, &cfg))
+                })
+                .transpose()
+            }) {
+                Ok(Some(uploader)) => data_manager.add_uploader(Box::new(uploader)),
+                Ok(None) => {}
+                Err(err) => log::error!("couldn't start mqtt data sync: {}", err),
             }
-        };
+            data_manager
+        })
+    });
+
+    data_manager_svc.unwrap_or_else(|err| {
+        log::error!("couldn't start the data manager: {:?}", err);
+        None
+    })
+}
+
+#[cfg(feature = "data")]
+fn spawn_data_manager(
+    mut data_manager: DataManager<DefaultDataStore>,
+    app_config: AppClientConfig,
+    exec: &Esp32Executor,
+) {
+    let data_exec = exec.clone();
+    exec.spawn(async move {
+        if let Err(err) = data_manager
+            .run_with_app(Esp32TLS::new_client(), data_exec, app_config)
+            .await
+        {
+            log::error!("data manager stopped: {:?}", err);
+        }
+    })
+    .detach();
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn serve_web_inner(
+    app_config: AppClientConfig,
+    _tls_server_config: Esp32TLSServerConfig,
+    repr: RobotRepresentation,
+    part: Option<(RobotPartRepresentation, WebRtcCertificate)>,
+    _ip: Ipv4Addr,
+    webrtc_certificate: WebRtcCertificate,
+    exec: Esp32Executor,
+    max_webrtc_connection: usize,
+) {
+    // TODO(NPM) this is a workaround so that async-io thread has started before we
+    // instantiate the Async<TCPStream> for the connection to app.viam.com
+    // otherwise there is a chance a race happens and will listen to events before full
+    // initialization is done
+    let _ = Timer::after(std::time::Duration::from_millis(60)).await;
 
-        (cfg_response, robot)
+    // assets have to be available before components are built from the config
+    #[cfg(feature = "assets")]
+    if let Err(err) = crate::esp32::assets::load_assets_from_partition(
+        crate::esp32::assets::ASSETS_PARTITION_LABEL,
+    ) {
+        log::warn!("no assets loaded: {}", err);
+    }
+
+    let mut client_connector = Esp32TLS::new_client();
+    let mdns = NoMdns {};
+
+    let (cfg_response, robot) = build_robot(&mut client_connector, &app_config, repr, &exec).await;
+
+    // shared by the local endpoints so that they accept the same access tokens
+    let auth = Rc::new(AuthPolicy::from_config(&cfg_response));
+    start_services(&cfg_response, &robot, &auth, &exec);
+
+    #[cfg(feature = "data")]
+    let mut data_manager_svc = build_data_manager(&cfg_response, &app_config, &robot);
+
+    let part = match part {
+        Some((part, part_webrtc_certificate)) => {
+            let (cfg_response, robot) =
+                build_robot(&mut client_connector, &part.app_config, part.repr, &exec).await;
+            let auth = Rc::new(AuthPolicy::from_config(&cfg_response));
+            start_robot_services(&cfg_response, &robot, &auth, &exec);
+            #[cfg(feature = "data")]
+            if let Some(data_manager) = build_data_manager(&cfg_response, &part.app_config, &robot)
+            {
+                spawn_data_manager(data_manager, part.app_config.clone(), &exec);
+            }
+            Some((
+                part.app_config,
+                cfg_response,
+                robot,
+                auth,
+                part_webrtc_certificate,
+            ))
+        }
+        None => None,
     };
 
+    match DutyCycle::from_robot_and_config(&cfg_response, robot.clone()) {
+        Ok(Some(duty_cycle)) => {
+            let deadline = Instant::now() + duty_cycle.awake_window();
+            #[cfg(feature = "data")]
+            let (data_manager, data_exec, data_app_config) =
+                (data_manager_svc.take(), exec.clone(), app_config.clone());
+            exec.spawn(async move {
+                #[cfg(feature = "data")]
+                if let Some(mut data_manager) = data_manager {
+                    match Esp32TLS::new_client()
+                        .connect_app(data_exec, data_app_config)
+                        .await
+                    {
+                        Ok(app_client) => data_manager.set_app_client(app_client),
+                        Err(err) => log::warn!("couldn't connect to app to sync data: {:?}", err),
+                    }
+                    if let Err(err) = data_manager.run_until(deadline).await {
+                        log::error!("data capture failed during the awake window: {:?}", err);
+                    }
+                }
+                if let Err(err) = duty_cycle.sleep_at(deadline).await {
+                    log::error!("duty cycle couldn't put the board to sleep: {:?}", err);
+                }
+            })
+            .detach();
+        }
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start duty cycle: {:?}", err),
+    }
+
     #[cfg(feature = "data")]
-    // TODO: Spawn data task here. May have to move the initialization below to the task itself
-    // TODO: Support implementers of the DataStore trait other than StaticMemoryDataStore in a way that is configurable
-    {
-        let _data_manager_svc = DataManager::<StaticMemoryDataStore>::from_robot_and_config(
-            &cfg_response,
-            &app_config,
-            robot.clone(),
-        );
+    if let Some(data_manager) = data_manager_svc {
+        spawn_data_manager(data_manager, app_config.clone(), &exec);
     }
 
     let webrtc_certificate = Rc::new(webrtc_certificate);
@@ -126,18 +376,33 @@ pub async fn serve_web_inner(
         exec.clone(),
     ));
 
-    let mut srv = Box::new(
-        ViamServerBuilder::new(
-            mdns,
-            cloned_exec,
-            client_connector,
-            app_config,
-            max_webrtc_connection,
-        )
-        .with_webrtc(webrtc)
-        .build(&cfg_response)
-        .unwrap(),
-    );
+    let mut builder = ViamServerBuilder::new(
+        mdns,
+        cloned_exec,
+        client_connector,
+        app_config,
+        max_webrtc_connection,
+    )
+    .with_webrtc(webrtc)
+    .with_auth_policy(auth);
+    if let Some((part_app_config, part_cfg_response, part_robot, part_auth, part_certificate)) =
+        part
+    {
+        let part_certificate = Rc::new(part_certificate);
+        let part_webrtc = Box::new(WebRtcConfiguration::new(
+            part_certificate.clone(),
+            Esp32DtlsBuilder::new(part_certificate),
+            exec.clone(),
+        ));
+        builder = builder.with_part(
+            part_app_config,
+            &part_cfg_response,
+            part_robot,
+            part_auth,
+            Some(part_webrtc),
+        );
+    }
+    let mut srv = Box::new(builder.build(&cfg_response).unwrap());
 
     srv.serve(robot).await;
 }
@@ -146,10 +411,57 @@ pub fn serve_web(
     app_config: AppClientConfig,
     tls_server_config: Esp32TLSServerConfig,
     repr: RobotRepresentation,
+    ip: Ipv4Addr,
+    webrtc_certificate: WebRtcCertificate,
+    max_webrtc_connection: usize,
+) {
+    serve_web_parts(
+        app_config,
+        tls_server_config,
+        repr,
+        None,
+        ip,
+        webrtc_certificate,
+        max_webrtc_connection,
+    )
+}
+
+/// Like [serve_web] but the device also serves `part`, a second robot part with its own
+/// credentials, answering WebRTC offers with `part_webrtc_certificate`. The data captured by the
+/// part is only stored on an SD card, the memory store being taken by the main part
+#[allow(clippy::too_many_arguments)]
+pub fn serve_web_with_part(
+    app_config: AppClientConfig,
+    tls_server_config: Esp32TLSServerConfig,
+    repr: RobotRepresentation,
+    part: RobotPartRepresentation,
+    part_webrtc_certificate: WebRtcCertificate,
+    ip: Ipv4Addr,
+    webrtc_certificate: WebRtcCertificate,
+    max_webrtc_connection: usize,
+) {
+    serve_web_parts(
+        app_config,
+        tls_server_config,
+        repr,
+        Some((part, part_webrtc_certificate)),
+        ip,
+        webrtc_certificate,
+        max_webrtc_connection,
+    )
+}
+
+fn serve_web_parts(
+    app_config: AppClientConfig,
+    tls_server_config: Esp32TLSServerConfig,
+    repr: RobotRepresentation,
+    part: Option<(RobotPartRepresentation, WebRtcCertificate)>,
     _ip: Ipv4Addr,
     webrtc_certificate: WebRtcCertificate,
     max_webrtc_connection: usize,
 ) {
+    let _ = super::sleep::record_boot();
+
     // set the TWDT to expire after 5 minutes
     crate::esp32::esp_idf_svc::sys::esp!(unsafe {
         crate::esp32::esp_idf_svc::sys::esp_task_wdt_init(300, true)
@@ -180,9 +492,95 @@ pub fn serve_web(
         app_config,
         tls_server_config,
         repr,
+        part,
         _ip,
         webrtc_certificate,
         exec,
         max_webrtc_connection,
     )));
 }
+
+pub async fn serve_offline_inner(
+    cfg_response: ConfigResponse,
+    repr: RobotRepresentation,
+    ip: Ipv4Addr,
+    exec: Esp32Executor,
+) {
+    let robot = match repr {
+        RobotRepresentation::WithRobot(robot) => Arc::new(RwLock::new(robot)),
+        RobotRepresentation::WithRegistry(registry) => {
+            log::info!("building robot from static config");
+            Arc::new(RwLock::new(
+                LocalRobot::from_cloud_config(&cfg_response, registry, None)
+                    .expect("couldn't build robot"),
+            ))
+        }
+    };
+
+    // shared by the local endpoints so that they accept the same access tokens
+    let auth = Rc::new(AuthPolicy::from_config(&cfg_response));
+    start_services(&cfg_response, &robot, &auth, &exec);
+
+    match DutyCycle::from_robot_and_config(&cfg_response, robot.clone()) {
+        Ok(Some(duty_cycle)) => {
+            let deadline = Instant::now() + duty_cycle.awake_window();
+            exec.spawn(async move {
+                if let Err(err) = duty_cycle.sleep_at(deadline).await {
+                    log::error!("duty cycle couldn't put the board to sleep: {:?}", err);
+                }
+            })
+            .detach();
+        }
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start duty cycle: {:?}", err),
+    }
+
+    let address: SocketAddr = "0.0.0.0:12346".parse().unwrap();
+    let listener = Esp32Listener::new(address.into(), None).unwrap();
+    let app_config = AppClientConfig::new("".to_owned(), "".to_owned(), ip, "".to_owned());
+
+    let mut srv = Box::new(
+        ViamServerBuilder::new(NoMdns {}, exec, Esp32TLS::new_client(), app_config, 1)
+            .with_http2(listener, 12346)
+            .without_app()
+            .with_auth_policy(auth)
+            .build(&cfg_response)
+            .unwrap(),
+    );
+
+    srv.serve(robot).await;
+}
+
+/// Serves a robot built from a [static config](crate::common::static_config) over plain HTTP2
+/// on port 12346, without ever reaching app
+pub fn serve_offline(cfg_response: ConfigResponse, repr: RobotRepresentation, ip: Ipv4Addr) {
+    let _ = super::sleep::record_boot();
+
+    // set the TWDT to expire after 5 minutes
+    crate::esp32::esp_idf_svc::sys::esp!(unsafe {
+        crate::esp32::esp_idf_svc::sys::esp_task_wdt_init(300, true)
+    })
+    .unwrap();
+
+    // Register the current task on the TWDT. The TWDT runs in the IDLE Task.
+    crate::esp32::esp_idf_svc::sys::esp!(unsafe {
+        crate::esp32::esp_idf_svc::sys::esp_task_wdt_add(
+            crate::esp32::esp_idf_svc::sys::xTaskGetCurrentTaskHandle(),
+        )
+    })
+    .unwrap();
+
+    let exec = Esp32Executor::new();
+    let cloned_exec = exec.clone();
+
+    cloned_exec
+        .spawn(async {
+            loop {
+                Timer::after(Duration::from_secs(150)).await;
+                unsafe { crate::esp32::esp_idf_svc::sys::esp_task_wdt_reset() };
+            }
+        })
+        .detach();
+
+    cloned_exec.block_on(Box::pin(serve_offline_inner(cfg_response, repr, ip, exec)));
+}
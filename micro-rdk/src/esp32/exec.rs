@@ -1,4 +1,5 @@
 //! The exec module exposes helpers to execute futures on an ESP32
+use crate::common::instrumentation::track_task;
 use crate::common::webrtc::exec::WebRtcExecutor;
 use async_executor::{LocalExecutor, Task};
 use futures_lite::{
@@ -19,7 +20,7 @@ impl Esp32Executor {
     }
     // Spawn a future onto the local executor
     pub fn spawn<T: 'static>(&self, future: impl Future<Output = T> + 'static) -> Task<T> {
-        EX.with(|e| e.spawn(future))
+        EX.with(|e| e.spawn(track_task(future)))
     }
 
     pub fn block_on<T>(&self, future: impl Future<Output = T>) -> T {
@@ -33,7 +34,7 @@ where
     F: future::Future + 'static,
 {
     fn execute(&self, fut: F) {
-        EX.with(|e| e.spawn(fut)).detach();
+        EX.with(|e| e.spawn(track_task(fut))).detach();
     }
 }
 
@@ -42,6 +43,6 @@ where
     F: future::Future + 'static,
 {
     fn execute(&self, fut: F) {
-        EX.with(|e| e.spawn(fut)).detach();
+        EX.with(|e| e.spawn(track_task(fut))).detach();
     }
 }
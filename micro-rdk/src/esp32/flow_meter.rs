@@ -0,0 +1,172 @@
+// Counts the pulses of hall effect flow meters with a pulse counter (PCNT) unit. The sensor is
+// implemented by `common::flow_meter`, which documents the readings and the remaining
+// attributes.
+//
+// Example configuration
+//
+// {
+//   "model": "flow-meter",
+//   "name": "water",
+//   "type": "sensor",
+//   "attributes": {
+//     "pin": 34,
+//     "k_factor": 450
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `pin`: the GPIO the output of the meter is connected to, rising edges are counted
+//  - `glitch_filter_cycles`: pulses shorter than this number of APB clock cycles are ignored,
+//    see `esp32::pulse_counter`
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use core::ffi::{c_short, c_ulong};
+
+use super::pulse_counter::{
+    get_unit, glitch_filter_from_config, isr_install, isr_installed, isr_remove_unit,
+    set_glitch_filter,
+};
+use crate::common::board::BoardError;
+use crate::common::config::ConfigType;
+use crate::common::encoder::EncoderError;
+use crate::common::flow_meter::{FlowMeter, PulseCounter};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::sensor::{SensorError, SensorType};
+use crate::esp32::esp_idf_svc::sys::pcnt_channel_edge_action_t_PCNT_CHANNEL_EDGE_ACTION_HOLD as pcnt_count_hold;
+use crate::esp32::esp_idf_svc::sys::pcnt_channel_edge_action_t_PCNT_CHANNEL_EDGE_ACTION_INCREASE as pcnt_count_inc;
+use crate::esp32::esp_idf_svc::sys::pcnt_channel_level_action_t_PCNT_CHANNEL_LEVEL_ACTION_KEEP as pcnt_mode_keep;
+use crate::esp32::esp_idf_svc::sys::pcnt_channel_t_PCNT_CHANNEL_0 as pcnt_channel_0;
+use crate::esp32::esp_idf_svc::sys::pcnt_evt_type_t_PCNT_EVT_H_LIM as pcnt_evt_h_lim;
+use crate::esp32::esp_idf_svc::sys::{
+    esp, pcnt_config_t, pcnt_counter_clear, pcnt_counter_pause, pcnt_counter_resume,
+    pcnt_event_enable, pcnt_get_counter_value, pcnt_get_event_status, pcnt_isr_handler_add,
+    pcnt_isr_handler_remove, pcnt_unit_config, pcnt_unit_t,
+};
+
+// the unit wraps to 0 when reaching this count, the wraps are accumulated by the ISR
+const COUNTER_HIGH_LIMIT: i16 = 10000;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("flow-meter", &from_config)
+        .is_err()
+    {
+        log::error!("flow-meter model is already registered")
+    }
+}
+
+fn from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<SensorType, SensorError> {
+    let pin = cfg
+        .get_attribute::<i32>("pin")
+        .map_err(|_| SensorError::ConfigError("flow-meter missing pin"))?;
+    let glitch_filter_cycles = glitch_filter_from_config(&cfg).map_err(pcnt_error)?;
+    let counter = PcntPulseCounter::new(pin, glitch_filter_cycles)?;
+    FlowMeter::from_counter_and_config(Box::new(counter), cfg)
+}
+
+fn pcnt_error(err: EncoderError) -> SensorError {
+    SensorError::SensorBoardError(BoardError::OtherBoardError(Box::new(err)))
+}
+
+struct Wraps {
+    count: AtomicU32,
+    unit: pcnt_unit_t,
+}
+
+pub struct PcntPulseCounter {
+    unit: pcnt_unit_t,
+    // boxed so its address, given to the ISR, stays the same
+    wraps: Box<Wraps>,
+}
+
+impl PcntPulseCounter {
+    pub fn new(pin: i32, glitch_filter_cycles: u16) -> Result<Self, SensorError> {
+        let unit = get_unit();
+        let config = pcnt_config_t {
+            pulse_gpio_num: pin,
+            ctrl_gpio_num: -1,
+            pos_mode: pcnt_count_inc,
+            neg_mode: pcnt_count_hold,
+            lctrl_mode: pcnt_mode_keep,
+            hctrl_mode: pcnt_mode_keep,
+            counter_h_lim: COUNTER_HIGH_LIMIT,
+            counter_l_lim: 0,
+            channel: pcnt_channel_0,
+            unit,
+        };
+        let mut counter = Self {
+            unit,
+            wraps: Box::new(Wraps {
+                count: AtomicU32::new(0),
+                unit,
+            }),
+        };
+        let code = |err: crate::esp32::esp_idf_svc::sys::EspError| {
+            SensorError::SensorCodeError(err.code())
+        };
+        unsafe {
+            esp!(pcnt_unit_config(&config as *const pcnt_config_t)).map_err(code)?;
+            esp!(pcnt_counter_pause(unit)).map_err(code)?;
+            esp!(pcnt_counter_clear(unit)).map_err(code)?;
+        }
+        isr_install().map_err(pcnt_error)?;
+        unsafe {
+            esp!(pcnt_isr_handler_add(
+                unit,
+                Some(Self::irq_handler),
+                counter.wraps.as_mut() as *mut Wraps as *mut _,
+            ))
+            .map_err(code)?;
+        }
+        set_glitch_filter(unit, glitch_filter_cycles).map_err(pcnt_error)?;
+        unsafe {
+            esp!(pcnt_event_enable(unit, pcnt_evt_h_lim)).map_err(code)?;
+            esp!(pcnt_counter_resume(unit)).map_err(code)?;
+        }
+        Ok(counter)
+    }
+
+    #[inline(always)]
+    #[link_section = ".iram1.pcnt_srv"]
+    unsafe extern "C" fn irq_handler(arg: *mut core::ffi::c_void) {
+        let arg: &Wraps = &*(arg as *const Wraps);
+        let mut status = 0;
+        pcnt_get_event_status(arg.unit, &mut status as *mut c_ulong);
+        if status & pcnt_evt_h_lim != 0 {
+            arg.count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl PulseCounter for PcntPulseCounter {
+    fn pulses(&mut self) -> Result<u64, SensorError> {
+        let mut count: i16 = 0;
+        // the counter may wrap while it is read, read it again until the wraps are unchanged
+        loop {
+            let wraps = self.wraps.count.load(Ordering::SeqCst);
+            unsafe {
+                esp!(pcnt_get_counter_value(
+                    self.unit,
+                    &mut count as *mut c_short
+                ))
+                .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+            }
+            if self.wraps.count.load(Ordering::SeqCst) == wraps {
+                return Ok(u64::from(wraps) * COUNTER_HIGH_LIMIT as u64 + count as u64);
+            }
+        }
+    }
+}
+
+impl Drop for PcntPulseCounter {
+    fn drop(&mut self) {
+        if isr_installed() {
+            unsafe {
+                pcnt_isr_handler_remove(self.unit);
+            }
+            isr_remove_unit();
+        }
+    }
+}
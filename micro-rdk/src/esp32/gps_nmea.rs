@@ -0,0 +1,54 @@
+// Support for GNSS receivers streaming NMEA sentences over a serial port, such as the Unicore
+// UM982 dual-antenna RTK receiver. The sensor is implemented by `common::gps_nmea`, which
+// documents the sentences used and the remaining attributes.
+//
+// Example configuration
+//
+// {
+//   "model": "gps-nmea",
+//   "name": "gps",
+//   "type": "movement_sensor",
+//   "attributes": {
+//     "uart_port": 1,
+//     "tx_pin": 17,
+//     "rx_pin": 16,
+//     "baud_rate": 115200,
+//     "init_commands": ["GPGGA 0.2", "GPRMC 0.2", "GPHDT 0.2", "UNIHEADINGA 0.2"],
+//     "ntrip_url": "https://caster.example.com/MOUNTPOINT",
+//     "ntrip_username": "user",
+//     "ntrip_password": "password"
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `uart_port`, `tx_pin`, `rx_pin` and `baud_rate`: the serial port the receiver is
+//    connected to, see `esp32::uart`. `baud_rate` defaults to 115200, the factory setting of
+//    the UM982.
+//  - `ntrip_url`, `ntrip_username` and `ntrip_password`: optional NTRIP caster streaming RTK
+//    corrections to the receiver over the same port, see `common::ntrip`. Connecting over
+//    TLS requires CONFIG_MBEDTLS_CERTIFICATE_BUNDLE (enabled by default in ESP-IDF).
+
+use crate::common::config::ConfigType;
+use crate::common::gps_nmea::NmeaGps;
+use crate::common::movement_sensor::MovementSensorType;
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::sensor::SensorError;
+
+use super::uart::uart_from_config;
+
+const DEFAULT_BAUD_RATE: u32 = 115200;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_movement_sensor("gps-nmea", &from_config)
+        .is_err()
+    {
+        log::error!("gps-nmea model is already registered")
+    }
+}
+
+fn from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<MovementSensorType, SensorError> {
+    let uart = uart_from_config(&cfg, DEFAULT_BAUD_RATE)?;
+    NmeaGps::from_uart_and_config(uart, cfg)
+}
@@ -51,13 +51,9 @@ use crate::{
     common::{
         config::{AttributeError, ConfigType},
         registry::{ComponentRegistry, Dependency},
-        sensor::{
-            GenericReadingsResult, Readings, Sensor, SensorError, SensorResult, SensorT,
-            SensorType, TypedReadingsResult,
-        },
-        status::{Status, StatusError},
+        sensor::{Sensor, SensorError, SensorT, SensorType, TypedReadingsResult},
     },
-    google, DoCommand,
+    DoCommand,
 };
 
 use crate::esp32::esp_idf_svc::hal::{
@@ -93,7 +89,7 @@ struct IsrSharedState {
     notifier: Arc<Notifier>,
 }
 
-#[derive(DoCommand)]
+#[derive(DoCommand, SensorReadings, Status)]
 pub struct HCSR04Sensor {
     // The PinDriver to control the pin that triggers issuing a pulse.
     //
@@ -257,16 +253,6 @@ impl Drop for HCSR04Sensor {
 
 impl Sensor for HCSR04Sensor {}
 
-impl Readings for HCSR04Sensor {
-    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
-        Ok(self
-            .get_readings()?
-            .into_iter()
-            .map(|v| (v.0, SensorResult::<f64> { value: v.1 }.into()))
-            .collect())
-    }
-}
-
 impl SensorT<f64> for HCSR04Sensor {
     fn get_readings(&self) -> Result<TypedReadingsResult<f64>, SensorError> {
         // If the echo pin is already high for some reason, the state machine
@@ -315,11 +301,3 @@ impl SensorT<f64> for HCSR04Sensor {
         }
     }
 }
-
-impl Status for HCSR04Sensor {
-    fn get_status(&self) -> Result<Option<google::protobuf::Struct>, StatusError> {
-        Ok(Some(google::protobuf::Struct {
-            fields: HashMap::new(),
-        }))
-    }
-}
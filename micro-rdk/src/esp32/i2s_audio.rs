@@ -0,0 +1,279 @@
+//! Audio input for I2S MEMS microphones (INMP441 and similar) using the legacy ESP-IDF I2S
+//! driver in receive mode.
+//!
+//! Samples are captured continuously by a background task and kept in a ring buffer holding
+//! `buffer_ms` of audio, the oldest samples being dropped when it is full.
+//!
+//! ```json
+//! {
+//!     "name": "mic",
+//!     "model": "i2s_mic",
+//!     "type": "audio_input",
+//!     "attributes": {
+//!         "sck_pin": 26,
+//!         "ws_pin": 25,
+//!         "sd_pin": 33,
+//!         "sample_rate": 16000,
+//!         "buffer_ms": 1000,
+//!         "channel": "left",
+//!         "gain_shift": 0
+//!     }
+//! }
+//! ```
+//!
+//! `channel` is the slot the microphone was wired to (L/R pin low is "left"), `gain_shift`
+//! (0 to 8) amplifies the signal by powers of two before truncation to 16 bits. The optional
+//! `port` attribute selects the I2S peripheral (0 by default).
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::common::{
+    audio_input::{audio_do_command, AudioInput, AudioInputError, AudioInputType, AudioProperties},
+    config::{AttributeError, ConfigType},
+    generic::{DoCommand, GenericError},
+    registry::{ComponentRegistry, Dependency},
+    status::{Status, StatusError},
+};
+use crate::google::protobuf::{value::Kind, Struct, Value};
+
+use crate::esp32::esp_idf_svc::sys::{
+    esp, i2s_bits_per_sample_t_I2S_BITS_PER_SAMPLE_32BIT,
+    i2s_channel_fmt_t_I2S_CHANNEL_FMT_ONLY_LEFT, i2s_channel_fmt_t_I2S_CHANNEL_FMT_ONLY_RIGHT,
+    i2s_comm_format_t_I2S_COMM_FORMAT_STAND_I2S, i2s_config_t, i2s_driver_install,
+    i2s_driver_uninstall, i2s_mode_t_I2S_MODE_MASTER, i2s_mode_t_I2S_MODE_RX, i2s_pin_config_t,
+    i2s_port_t, i2s_read, i2s_set_pin, ESP_INTR_FLAG_LEVEL1, I2S_PIN_NO_CHANGE,
+};
+
+const DMA_BUF_COUNT: i32 = 4;
+const DMA_BUF_LEN: i32 = 256;
+// number of 32-bit slots read from the driver at once
+const READ_CHUNK: usize = 256;
+// the read blocks at most this long so the capture task notices it should stop
+const READ_TIMEOUT_TICKS: u32 = 10;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_audio_input("i2s_mic", &I2sMicrophone::from_config)
+        .is_err()
+    {
+        log::error!("i2s_mic model is already registered");
+    }
+}
+
+/// Converts a 24-bit sample left aligned in a 32-bit slot to 16 bits, applying the gain
+fn to_pcm16(raw: i32, gain_shift: u32) -> i16 {
+    ((raw as i64) >> (16 - gain_shift)).clamp(i16::MIN as i64, i16::MAX as i64) as i16
+}
+
+pub struct I2sMicrophone {
+    port: i2s_port_t,
+    sample_rate: u32,
+    samples: Arc<Mutex<VecDeque<i16>>>,
+    running: Arc<AtomicBool>,
+    capture: Option<JoinHandle<()>>,
+}
+
+impl I2sMicrophone {
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        _: Vec<Dependency>,
+    ) -> Result<AudioInputType, AudioInputError> {
+        let sck_pin = cfg.get_attribute::<i32>("sck_pin")?;
+        let ws_pin = cfg.get_attribute::<i32>("ws_pin")?;
+        let sd_pin = cfg.get_attribute::<i32>("sd_pin")?;
+        let optional = |key: &str, default: u32| match cfg.get_attribute::<u32>(key) {
+            Ok(v) => Ok(v),
+            Err(AttributeError::KeyNotFound(_)) => Ok(default),
+            Err(e) => Err(e),
+        };
+        let sample_rate = optional("sample_rate", 16000)?;
+        let buffer_ms = optional("buffer_ms", 1000)?;
+        let gain_shift = optional("gain_shift", 0)?;
+        let port = optional("port", 0)?;
+        if gain_shift > 8 {
+            return Err(AudioInputError::AudioInputConfigurationError(
+                "gain_shift must be between 0 and 8",
+            ));
+        }
+        if !(8000..=48000).contains(&sample_rate) {
+            return Err(AudioInputError::AudioInputConfigurationError(
+                "sample_rate must be between 8000 and 48000",
+            ));
+        }
+        let right = match cfg.get_attribute::<String>("channel") {
+            Ok(channel) => match channel.as_str() {
+                "left" => false,
+                "right" => true,
+                _ => {
+                    return Err(AudioInputError::AudioInputConfigurationError(
+                        "channel must be left or right",
+                    ))
+                }
+            },
+            Err(AttributeError::KeyNotFound(_)) => false,
+            Err(e) => return Err(e.into()),
+        };
+        let capacity = (sample_rate as u64 * buffer_ms as u64 / 1000) as usize;
+        Ok(Arc::new(Mutex::new(Self::new(
+            port,
+            sck_pin,
+            ws_pin,
+            sd_pin,
+            sample_rate,
+            right,
+            gain_shift,
+            capacity,
+        )?)))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        port: i2s_port_t,
+        sck_pin: i32,
+        ws_pin: i32,
+        sd_pin: i32,
+        sample_rate: u32,
+        right: bool,
+        gain_shift: u32,
+        capacity: usize,
+    ) -> Result<Self, AudioInputError> {
+        let config = i2s_config_t {
+            mode: i2s_mode_t_I2S_MODE_MASTER | i2s_mode_t_I2S_MODE_RX,
+            sample_rate,
+            bits_per_sample: i2s_bits_per_sample_t_I2S_BITS_PER_SAMPLE_32BIT,
+            channel_format: if right {
+                i2s_channel_fmt_t_I2S_CHANNEL_FMT_ONLY_RIGHT
+            } else {
+                i2s_channel_fmt_t_I2S_CHANNEL_FMT_ONLY_LEFT
+            },
+            communication_format: i2s_comm_format_t_I2S_COMM_FORMAT_STAND_I2S,
+            intr_alloc_flags: ESP_INTR_FLAG_LEVEL1 as i32,
+            dma_buf_count: DMA_BUF_COUNT,
+            dma_buf_len: DMA_BUF_LEN,
+            use_apll: false,
+            ..Default::default()
+        };
+        esp!(unsafe { i2s_driver_install(port, &config, 0, std::ptr::null_mut()) })
+            .map_err(|e| AudioInputError::AudioInputCaptureError(e.code()))?;
+        let pins = i2s_pin_config_t {
+            bck_io_num: sck_pin,
+            ws_io_num: ws_pin,
+            data_out_num: I2S_PIN_NO_CHANGE,
+            data_in_num: sd_pin,
+            ..Default::default()
+        };
+        if let Err(e) = esp!(unsafe { i2s_set_pin(port, &pins) }) {
+            unsafe { i2s_driver_uninstall(port) };
+            return Err(AudioInputError::AudioInputCaptureError(e.code()));
+        }
+
+        let samples = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+        let running = Arc::new(AtomicBool::new(true));
+        let capture = {
+            let samples = samples.clone();
+            let running = running.clone();
+            std::thread::Builder::new()
+                .stack_size(4096)
+                .name("i2s_mic".to_owned())
+                .spawn(move || {
+                    let mut raw = [0_i32; READ_CHUNK];
+                    while running.load(Ordering::Relaxed) {
+                        let mut read = 0;
+                        let ret = unsafe {
+                            i2s_read(
+                                port,
+                                raw.as_mut_ptr() as *mut _,
+                                std::mem::size_of_val(&raw),
+                                &mut read,
+                                READ_TIMEOUT_TICKS,
+                            )
+                        };
+                        if ret != 0 {
+                            log::error!("i2s read failed {}", ret);
+                            std::thread::sleep(Duration::from_millis(100));
+                            continue;
+                        }
+                        let read = read / std::mem::size_of::<i32>();
+                        let mut samples = samples.lock().unwrap();
+                        for raw in &raw[..read] {
+                            if samples.len() == capacity {
+                                let _ = samples.pop_front();
+                            }
+                            samples.push_back(to_pcm16(*raw, gain_shift));
+                        }
+                    }
+                })
+                .map_err(|_| {
+                    unsafe { i2s_driver_uninstall(port) };
+                    AudioInputError::AudioInputConfigurationError("couldn't start the capture task")
+                })?
+        };
+
+        Ok(Self {
+            port,
+            sample_rate,
+            samples,
+            running,
+            capture: Some(capture),
+        })
+    }
+}
+
+impl Drop for I2sMicrophone {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(capture) = self.capture.take() {
+            let _ = capture.join();
+        }
+        unsafe { i2s_driver_uninstall(self.port) };
+    }
+}
+
+impl AudioInput for I2sMicrophone {
+    fn get_properties(&self) -> AudioProperties {
+        AudioProperties {
+            channel_count: 1,
+            sample_rate: self.sample_rate,
+            latency: Duration::from_micros(
+                (DMA_BUF_LEN as u64 * 1_000_000) / self.sample_rate as u64,
+            ),
+        }
+    }
+    fn read_samples(&mut self, max_samples: usize) -> Result<Vec<i16>, AudioInputError> {
+        let mut samples = self.samples.lock().unwrap();
+        let n = max_samples.min(samples.len());
+        Ok(samples.drain(..n).collect())
+    }
+    fn peek_samples(&self) -> Result<Vec<i16>, AudioInputError> {
+        Ok(self.samples.lock().unwrap().iter().copied().collect())
+    }
+}
+
+impl DoCommand for I2sMicrophone {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        audio_do_command(self, command_struct)
+    }
+}
+
+impl Status for I2sMicrophone {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        let buffered = self.samples.lock().unwrap().len();
+        Ok(Some(Struct {
+            fields: HashMap::from([(
+                "buffered_samples".to_string(),
+                Value {
+                    kind: Some(Kind::NumberValue(buffered as f64)),
+                },
+            )]),
+        }))
+    }
+}
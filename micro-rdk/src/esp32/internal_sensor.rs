@@ -0,0 +1,150 @@
+// Readings of the sensors built into the ESP32 chip, for thermal monitoring of an enclosure
+// without external hardware. Depending on the variant the sensor reports:
+//
+//  - `hall`: the raw value of the hall effect sensor of the ESP32, averaged over `hall_samples`
+//    reads. It is noisy and uncalibrated, but shows the presence and polarity of a nearby
+//    magnet (a closed lid, an opened door...).
+//  - `temperature_c`: the die temperature in degrees Celsius, on the ESP32-S2, ESP32-S3 and
+//    ESP32-C3. The die runs warmer than its surroundings but follows the temperature of the
+//    enclosure. The original ESP32 has no supported temperature sensor.
+//
+// Example configuration
+//
+// {
+//   "model": "esp32-internal",
+//   "name": "chip",
+//   "type": "sensor",
+//   "attributes": {
+//     "hall": true,
+//     "hall_samples": 16
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `hall` (optional, default true): the hall sensor is read through ADC1 channels 0 and 3,
+//    set it to false when GPIO36 or GPIO39 are used as analog readers.
+//  - `hall_samples` (optional, default 8): number of reads averaged, between 1 and 64.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::common::config::{AttributeError, ConfigType};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::sensor::{Sensor, SensorError, SensorT, SensorType, TypedReadingsResult};
+use crate::esp32::esp_idf_svc::sys::esp;
+
+#[cfg(esp32)]
+use crate::esp32::esp_idf_svc::sys::{
+    adc1_config_width, adc_bits_width_t_ADC_WIDTH_BIT_12, hall_sensor_read,
+};
+#[cfg(any(esp32s2, esp32s3, esp32c3))]
+use crate::esp32::esp_idf_svc::sys::{
+    temp_sensor_config_t, temp_sensor_dac_offset_t_TSENS_DAC_L2, temp_sensor_read_celsius,
+    temp_sensor_set_config, temp_sensor_start, temp_sensor_stop,
+};
+
+const DEFAULT_HALL_SAMPLES: u32 = 8;
+const MAX_HALL_SAMPLES: u32 = 64;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("esp32-internal", &Esp32InternalSensor::from_config)
+        .is_err()
+    {
+        log::error!("esp32-internal model is already registered");
+    }
+}
+
+#[derive(DoCommand, SensorReadings, Status)]
+pub struct Esp32InternalSensor {
+    // number of hall sensor reads averaged, `None` when disabled
+    hall_samples: Option<u32>,
+}
+
+impl Esp32InternalSensor {
+    pub fn from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<SensorType, SensorError> {
+        let hall = match cfg.get_attribute::<bool>("hall") {
+            Ok(hall) => hall,
+            Err(AttributeError::KeyNotFound(_)) => true,
+            Err(_) => return Err(SensorError::ConfigError("esp32-internal invalid hall")),
+        };
+        let samples = match cfg.get_attribute::<u32>("hall_samples") {
+            Ok(samples) if (1..=MAX_HALL_SAMPLES).contains(&samples) => samples,
+            Err(AttributeError::KeyNotFound(_)) => DEFAULT_HALL_SAMPLES,
+            _ => {
+                return Err(SensorError::ConfigError(
+                    "esp32-internal hall_samples should be between 1 and 64",
+                ))
+            }
+        };
+        Ok(Arc::new(Mutex::new(Self::new(hall.then_some(samples))?)))
+    }
+
+    pub fn new(hall_samples: Option<u32>) -> Result<Self, SensorError> {
+        #[cfg(esp32)]
+        if hall_samples.is_some() {
+            esp!(unsafe { adc1_config_width(adc_bits_width_t_ADC_WIDTH_BIT_12) })
+                .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        }
+        #[cfg(not(esp32))]
+        let hall_samples = {
+            if hall_samples.is_some() {
+                log::warn!("esp32-internal: this chip has no hall sensor");
+            }
+            None
+        };
+        #[cfg(any(esp32s2, esp32s3, esp32c3))]
+        unsafe {
+            // the -10°C to 80°C range, with the smallest error
+            let cfg = temp_sensor_config_t {
+                dac_offset: temp_sensor_dac_offset_t_TSENS_DAC_L2,
+                clk_div: 6,
+            };
+            esp!(temp_sensor_set_config(cfg))
+                .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+            esp!(temp_sensor_start()).map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        }
+        Ok(Self { hall_samples })
+    }
+}
+
+impl Drop for Esp32InternalSensor {
+    fn drop(&mut self) {
+        #[cfg(any(esp32s2, esp32s3, esp32c3))]
+        if let Err(err) = esp!(unsafe { temp_sensor_stop() }) {
+            log::error!(
+                "esp32-internal: couldn't stop the temperature sensor: {}",
+                err
+            );
+        }
+    }
+}
+
+impl Sensor for Esp32InternalSensor {}
+
+impl SensorT<f64> for Esp32InternalSensor {
+    fn get_readings(&self) -> Result<TypedReadingsResult<f64>, SensorError> {
+        let mut readings = HashMap::new();
+        #[cfg(esp32)]
+        if let Some(samples) = self.hall_samples {
+            let sum: i64 = (0..samples)
+                .map(|_| unsafe { hall_sensor_read() } as i64)
+                .sum();
+            readings.insert("hall".to_string(), sum as f64 / samples as f64);
+        }
+        #[cfg(any(esp32s2, esp32s3, esp32c3))]
+        {
+            let mut celsius = 0.0_f32;
+            esp!(unsafe { temp_sensor_read_celsius(&mut celsius) })
+                .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+            readings.insert("temperature_c".to_string(), celsius as f64);
+        }
+        if readings.is_empty() {
+            return Err(SensorError::SensorGenericError(
+                "esp32-internal has no sensor enabled on this chip",
+            ));
+        }
+        Ok(readings)
+    }
+}
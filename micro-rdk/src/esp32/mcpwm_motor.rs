@@ -0,0 +1,380 @@
+//! DC motor driven through the MCPWM peripheral of the ESP32, an alternative to the LEDC based
+//! [gpio motors](crate::common::gpio_motor) giving control over how the bridge is stopped and
+//! able to drive complementary outputs with dead time.
+//!
+//! ```json
+//! {
+//!     "name": "left",
+//!     "model": "mcpwm",
+//!     "type": "motor",
+//!     "attributes": {
+//!         "pins": { "a": 25, "b": 26 },
+//!         "max_rpm": 200,
+//!         "frequency_hz": 20000,
+//!         "stop_mode": "brake",
+//!         "dead_time_ns": 500
+//!     }
+//! }
+//! ```
+//!
+//! Without `dead_time_ns` the motor is driven in sign-magnitude: `a` carries the PWM signal
+//! when moving forward while `b` is held low, and the other way around in reverse (`dir_flip`
+//! swaps them). With `dead_time_ns` the outputs are complementary (locked anti-phase): `b` is
+//! the inverse of `a` delayed by the dead time on both edges, a 50% duty cycle holding the motor
+//! still. The dead time is rounded to 100ns.
+//!
+//! `stop_mode` (`coast` by default) selects whether stopping pulls both outputs low, letting
+//! the motor coast, or high, braking it on drivers such as the DRV8833 or TB6612. It can be
+//! overridden for a single stop by passing `{"mode": "brake"}` as the `extra` of a Stop request
+//! or with the `{"stop": {"mode": "brake"}}` command. `frequency_hz`
+//! defaults to 20kHz, above the audible range. Each motor uses one of the 6 MCPWM timers of
+//! the ESP32.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::common::actuator::{Actuator, ActuatorError};
+use crate::common::board::{BoardError, BoardPin};
+use crate::common::config::{AttributeError, ConfigType};
+use crate::common::generic::{DoCommand, GenericError};
+use crate::common::math_utils::go_for_math;
+use crate::common::motor::{
+    Motor, MotorError, MotorPinsConfig, MotorSupportedProperties, MotorType, StopMode,
+};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::status::{status_envelope, ComponentHealth, Status, StatusError};
+use crate::esp32::esp_idf_svc::sys::{
+    esp, mcpwm_config_t, mcpwm_counter_type_t_MCPWM_UP_COUNTER, mcpwm_deadtime_disable,
+    mcpwm_deadtime_enable, mcpwm_deadtime_type_t_MCPWM_ACTIVE_HIGH_COMPLIMENT_MODE,
+    mcpwm_duty_type_t_MCPWM_DUTY_MODE_0, mcpwm_generator_t, mcpwm_generator_t_MCPWM_GEN_A,
+    mcpwm_generator_t_MCPWM_GEN_B, mcpwm_gpio_init, mcpwm_init, mcpwm_io_signals_t,
+    mcpwm_io_signals_t_MCPWM0A, mcpwm_set_duty, mcpwm_set_duty_type, mcpwm_set_signal_high,
+    mcpwm_set_signal_low, mcpwm_stop, mcpwm_timer_t, mcpwm_unit_t, EspError,
+};
+use crate::google::protobuf::{value::Kind, Struct};
+
+const DEFAULT_FREQUENCY_HZ: u32 = 20000;
+// 2 units of 3 timers, each timer driving the A and B outputs of its operator
+const MCPWM_TIMERS: usize = 6;
+// dead time is programmed in steps of 100ns
+const DEAD_TIME_STEP_NS: u32 = 100;
+
+static TIMERS_IN_USE: Mutex<[bool; MCPWM_TIMERS]> = Mutex::new([false; MCPWM_TIMERS]);
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_motor("mcpwm", &McpwmMotor::from_config)
+        .is_err()
+    {
+        log::error!("mcpwm model is already registered")
+    }
+}
+
+impl From<EspError> for MotorError {
+    fn from(err: EspError) -> Self {
+        MotorError::BoardError(BoardError::OtherBoardError(Box::new(err)))
+    }
+}
+
+/// Unit and timer of the MCPWM peripheral reserved by a motor, released when dropped
+struct McpwmTimer(usize);
+
+impl McpwmTimer {
+    fn reserve() -> Result<Self, MotorError> {
+        let mut in_use = TIMERS_IN_USE.lock().unwrap();
+        let idx = in_use
+            .iter()
+            .position(|used| !used)
+            .ok_or(MotorError::ConfigError("all MCPWM timers are in use"))?;
+        in_use[idx] = true;
+        Ok(Self(idx))
+    }
+
+    fn unit(&self) -> mcpwm_unit_t {
+        (self.0 / 3) as mcpwm_unit_t
+    }
+
+    fn timer(&self) -> mcpwm_timer_t {
+        (self.0 % 3) as mcpwm_timer_t
+    }
+
+    // signals are laid out as 0A, 0B, 1A, 1B, 2A, 2B for each unit
+    fn io_signal(&self, generator: mcpwm_generator_t) -> mcpwm_io_signals_t {
+        mcpwm_io_signals_t_MCPWM0A
+            + (self.timer() as mcpwm_io_signals_t) * 2
+            + generator as mcpwm_io_signals_t
+    }
+}
+
+impl Drop for McpwmTimer {
+    fn drop(&mut self) {
+        unsafe {
+            mcpwm_stop(self.unit(), self.timer());
+        }
+        TIMERS_IN_USE.lock().unwrap()[self.0] = false;
+    }
+}
+
+pub struct McpwmMotor {
+    mcpwm: McpwmTimer,
+    max_rpm: f64,
+    dir_flip: bool,
+    stop_mode: StopMode,
+    // dead time in steps of 100ns when the outputs are complementary
+    dead_time: Option<u32>,
+    power: f64,
+    health: ComponentHealth,
+}
+
+impl McpwmMotor {
+    pub(crate) fn from_config(
+        cfg: ConfigType,
+        _: Vec<Dependency>,
+    ) -> Result<MotorType, MotorError> {
+        let pins =
+            cfg.get_attribute::<MotorPinsConfig>("pins")
+                .or(Err(MotorError::ConfigError(
+                    "McpwmMotor, missing 'pins' attribute",
+                )))?;
+        let (a_pin, b_pin) = match (pins.a, pins.b) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Err(MotorError::ConfigError("McpwmMotor, need 'a' and 'b' pins")),
+        };
+        let as_gpio = |pin| match pin {
+            BoardPin::Gpio(pin) => Ok(pin),
+            _ => Err(MotorError::ConfigError(
+                "McpwmMotor, pins of GPIO expanders can't be used",
+            )),
+        };
+        let frequency_hz = cfg
+            .get_attribute::<u32>("frequency_hz")
+            .unwrap_or(DEFAULT_FREQUENCY_HZ);
+        let stop_mode = match cfg.get_attribute::<StopMode>("stop_mode") {
+            Ok(mode) => mode,
+            Err(AttributeError::KeyNotFound(_)) => StopMode::default(),
+            Err(_) => {
+                return Err(MotorError::ConfigError(
+                    "McpwmMotor, stop_mode should be brake or coast",
+                ))
+            }
+        };
+        let dead_time = match cfg.get_attribute::<u32>("dead_time_ns") {
+            Ok(ns) => Some(ns.div_ceil(DEAD_TIME_STEP_NS)),
+            Err(AttributeError::KeyNotFound(_)) => None,
+            Err(_) => {
+                return Err(MotorError::ConfigError(
+                    "McpwmMotor, dead_time_ns should be a positive integer",
+                ))
+            }
+        };
+        let motor = Self::new(
+            as_gpio(a_pin)?,
+            as_gpio(b_pin)?,
+            frequency_hz,
+            cfg.get_attribute::<f64>("max_rpm").unwrap_or(100.0),
+            cfg.get_attribute::<bool>("dir_flip").unwrap_or_default(),
+            stop_mode,
+            dead_time,
+        )?;
+        Ok(Arc::new(Mutex::new(motor)))
+    }
+
+    pub fn new(
+        a_pin: i32,
+        b_pin: i32,
+        frequency_hz: u32,
+        max_rpm: f64,
+        dir_flip: bool,
+        stop_mode: StopMode,
+        dead_time: Option<u32>,
+    ) -> Result<Self, MotorError> {
+        if frequency_hz == 0 {
+            return Err(MotorError::ConfigError("McpwmMotor, frequency_hz is 0"));
+        }
+        let mcpwm = McpwmTimer::reserve()?;
+        let (unit, timer) = (mcpwm.unit(), mcpwm.timer());
+        let a_signal = mcpwm.io_signal(mcpwm_generator_t_MCPWM_GEN_A);
+        let b_signal = mcpwm.io_signal(mcpwm_generator_t_MCPWM_GEN_B);
+        let config = mcpwm_config_t {
+            frequency: frequency_hz,
+            cmpr_a: 0.0,
+            cmpr_b: 0.0,
+            duty_mode: mcpwm_duty_type_t_MCPWM_DUTY_MODE_0,
+            counter_mode: mcpwm_counter_type_t_MCPWM_UP_COUNTER,
+        };
+        esp!(unsafe { mcpwm_gpio_init(unit, a_signal, a_pin) })?;
+        esp!(unsafe { mcpwm_gpio_init(unit, b_signal, b_pin) })?;
+        esp!(unsafe { mcpwm_init(unit, timer, &config) })?;
+        let mut motor = Self {
+            mcpwm,
+            max_rpm,
+            dir_flip,
+            stop_mode,
+            dead_time,
+            power: 0.0,
+            health: ComponentHealth::new(),
+        };
+        motor.stop_with(stop_mode)?;
+        Ok(motor)
+    }
+
+    fn drive(&mut self, generator: mcpwm_generator_t, duty_pct: f32) -> Result<(), EspError> {
+        let (unit, timer) = (self.mcpwm.unit(), self.mcpwm.timer());
+        // a generator forced low or high only follows its duty cycle again once its duty
+        // type is set
+        esp!(unsafe {
+            mcpwm_set_duty_type(unit, timer, generator, mcpwm_duty_type_t_MCPWM_DUTY_MODE_0)
+        })?;
+        esp!(unsafe { mcpwm_set_duty(unit, timer, generator, duty_pct) })
+    }
+
+    fn apply_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&pct) {
+            return Err(MotorError::PowerSetError);
+        }
+        if pct.abs() <= 0.001 {
+            return self.stop_with(self.stop_mode);
+        }
+        let (unit, timer) = (self.mcpwm.unit(), self.mcpwm.timer());
+        let pct = if self.dir_flip { -pct } else { pct };
+        match self.dead_time {
+            Some(dead_time) => {
+                // generator B is derived from generator A by the dead time module
+                esp!(unsafe {
+                    mcpwm_deadtime_enable(
+                        unit,
+                        timer,
+                        mcpwm_deadtime_type_t_MCPWM_ACTIVE_HIGH_COMPLIMENT_MODE,
+                        dead_time,
+                        dead_time,
+                    )
+                })?;
+                self.drive(mcpwm_generator_t_MCPWM_GEN_A, (50.0 + 50.0 * pct) as f32)?;
+            }
+            None => {
+                let (pwm_gen, low_gen) = if pct > 0.0 {
+                    (mcpwm_generator_t_MCPWM_GEN_A, mcpwm_generator_t_MCPWM_GEN_B)
+                } else {
+                    (mcpwm_generator_t_MCPWM_GEN_B, mcpwm_generator_t_MCPWM_GEN_A)
+                };
+                esp!(unsafe { mcpwm_set_signal_low(unit, timer, low_gen) })?;
+                self.drive(pwm_gen, (pct.abs() * 100.0) as f32)?;
+            }
+        }
+        self.power = if self.dir_flip { -pct } else { pct };
+        Ok(())
+    }
+
+    fn stop_with(&mut self, mode: StopMode) -> Result<(), MotorError> {
+        let (unit, timer) = (self.mcpwm.unit(), self.mcpwm.timer());
+        if self.dead_time.is_some() {
+            // complementary outputs can't be both low or both high
+            esp!(unsafe { mcpwm_deadtime_disable(unit, timer) })?;
+        }
+        for generator in [mcpwm_generator_t_MCPWM_GEN_A, mcpwm_generator_t_MCPWM_GEN_B] {
+            match mode {
+                StopMode::Coast => esp!(unsafe { mcpwm_set_signal_low(unit, timer, generator) })?,
+                StopMode::Brake => esp!(unsafe { mcpwm_set_signal_high(unit, timer, generator) })?,
+            }
+        }
+        self.power = 0.0;
+        Ok(())
+    }
+}
+
+impl Motor for McpwmMotor {
+    fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        let res = self.apply_power(pct);
+        self.health.record(&res);
+        res
+    }
+
+    fn get_position(&mut self) -> Result<i32, MotorError> {
+        Err(MotorError::MissingEncoder)
+    }
+
+    fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        let (pwr, dur) = go_for_math(self.max_rpm, rpm, revolutions)?;
+        self.set_power(pwr)?;
+        Ok(dur)
+    }
+
+    fn get_properties(&mut self) -> MotorSupportedProperties {
+        MotorSupportedProperties {
+            position_reporting: false,
+            max_rpm: Some(self.max_rpm),
+            ticks_per_rotation: None,
+        }
+    }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
+}
+
+impl Actuator for McpwmMotor {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(self.power != 0.0)
+    }
+
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        self.stop_with_extra(None)
+    }
+
+    fn stop_with_extra(&mut self, extra: Option<Struct>) -> Result<(), ActuatorError> {
+        let mode = match extra
+            .as_ref()
+            .and_then(|extra| extra.fields.get("mode"))
+            .and_then(|v| v.kind.as_ref())
+        {
+            None => self.stop_mode,
+            Some(mode) => StopMode::try_from(mode).unwrap_or_else(|_| {
+                // a stop shouldn't be refused, fall back to the configured mode
+                log::warn!("McpwmMotor, ignoring invalid stop mode {:?}", mode);
+                self.stop_mode
+            }),
+        };
+        let res = self.stop_with(mode);
+        self.health.record(&res);
+        res.map_err(|_| ActuatorError::CouldntStop)
+    }
+}
+
+impl DoCommand for McpwmMotor {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        let args = match command.fields.get("stop").and_then(|v| v.kind.as_ref()) {
+            Some(Kind::StructValue(args)) => args,
+            _ => return Err(GenericError::MethodUnimplemented("do_command")),
+        };
+        let mode = match args.fields.get("mode").and_then(|v| v.kind.as_ref()) {
+            None => self.stop_mode,
+            Some(Kind::StringValue(mode)) => StopMode::try_from(mode.as_str())
+                .map_err(|_| GenericError::InvalidArgument("mode should be brake or coast"))?,
+            Some(_) => {
+                return Err(GenericError::InvalidArgument(
+                    "mode should be brake or coast",
+                ))
+            }
+        };
+        let res = self.stop_with(mode);
+        self.health.record(&res);
+        res.map_err(|err| GenericError::Other(err.into()))?;
+        Ok(None)
+    }
+}
+
+impl Status for McpwmMotor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(status_envelope(
+            &self.health,
+            [
+                ("position", Kind::NumberValue(0.0)),
+                ("power", Kind::NumberValue(self.power)),
+            ],
+        )))
+    }
+}
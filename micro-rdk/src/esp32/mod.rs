@@ -1,30 +1,67 @@
 //! ESP32-specific implementations of components and tools
 
 pub mod analog;
+#[cfg(feature = "assets")]
+pub mod assets;
+#[cfg(all(feature = "builtin-components", esp_idf_bt_bluedroid_enabled))]
+pub mod ble_sensor;
 pub mod board;
+pub mod calibration;
 #[cfg(all(feature = "camera", feature = "builtin-components"))]
 pub mod camera;
+#[cfg(feature = "builtin-components")]
+pub mod can;
 pub mod certificate;
+#[cfg(feature = "console")]
+pub mod console;
 pub mod dtls;
 #[cfg(feature = "builtin-components")]
+pub mod dynamixel;
+#[cfg(feature = "builtin-components")]
 pub mod encoder;
 pub mod entry;
 pub mod esp_idf_svc;
 pub mod exec;
 #[cfg(feature = "builtin-components")]
+pub mod flow_meter;
+#[cfg(feature = "builtin-components")]
+pub mod gps_nmea;
+#[cfg(feature = "builtin-components")]
 pub mod hcsr04;
 pub mod i2c;
+#[cfg(feature = "builtin-components")]
+pub mod i2s_audio;
+#[cfg(feature = "builtin-components")]
+pub mod internal_sensor;
+#[cfg(feature = "builtin-components")]
+pub mod mcpwm_motor;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "builtin-components")]
+pub mod particulate;
 pub mod pin;
+pub mod power_profile;
 #[cfg(feature = "builtin-components")]
 pub mod pulse_counter;
 pub mod pwm;
 #[cfg(feature = "builtin-components")]
+pub mod rc_receiver;
+pub mod sd_card;
+#[cfg(feature = "builtin-components")]
+pub mod serial_motors;
+#[cfg(feature = "builtin-components")]
 pub mod single_encoded_motor;
 #[cfg(feature = "builtin-components")]
 pub mod single_encoder;
+pub mod sleep;
+pub mod square_wave;
 pub mod tcp;
 pub mod tls;
+#[cfg(feature = "builtin-components")]
+pub mod uart;
 pub mod utils;
+#[cfg(feature = "builtin-components")]
+pub mod vesc_can;
 pub mod conn {
     pub mod mdns;
 }
@@ -0,0 +1,47 @@
+//! MQTT publisher backed by the esp-mqtt client of ESP-IDF, see [crate::common::mqtt]
+
+use crate::common::mqtt::{MqttConfig, MqttError, MqttPublisher, MqttQos};
+use crate::common::secret::SecretString;
+use crate::esp32::esp_idf_svc::mqtt::client::{EspMqttClient, MqttClientConfiguration};
+use crate::esp32::esp_idf_svc::sys::esp_crt_bundle_attach;
+use embedded_svc::mqtt::client::QoS;
+
+pub struct Esp32MqttPublisher {
+    client: EspMqttClient<'static>,
+}
+
+impl Esp32MqttPublisher {
+    /// Connects to the broker in the background, the client reconnects on its own when the
+    /// connection drops. `mqtts://` brokers are authenticated with the ESP-IDF certificate bundle.
+    pub fn new(config: &MqttConfig, part_id: &str) -> Result<Self, MqttError> {
+        let conf = MqttClientConfiguration {
+            client_id: Some(config.client_id.as_deref().unwrap_or(part_id)),
+            username: config.username.as_deref(),
+            password: config.password.as_ref().map(SecretString::expose),
+            crt_bundle_attach: config
+                .url
+                .starts_with("mqtts://")
+                .then_some(esp_crt_bundle_attach),
+            ..Default::default()
+        };
+        let client = EspMqttClient::new_cb(&config.url, &conf, |event| {
+            log::debug!("mqtt event: {:?}", event.payload())
+        })
+        .map_err(|err| MqttError::ConnectionError(err.to_string()))?;
+        Ok(Self { client })
+    }
+}
+
+impl MqttPublisher for Esp32MqttPublisher {
+    fn publish(&mut self, topic: &str, qos: MqttQos, payload: &[u8]) -> Result<(), MqttError> {
+        let qos = match qos {
+            MqttQos::AtMostOnce => QoS::AtMostOnce,
+            MqttQos::AtLeastOnce => QoS::AtLeastOnce,
+        };
+        // messages are queued in the outbox of the client until they are acknowledged
+        self.client
+            .enqueue(topic, qos, false, payload)
+            .map(|_| ())
+            .map_err(|err| MqttError::PublishError(topic.to_string(), err.to_string()))
+    }
+}
@@ -0,0 +1,56 @@
+// Support for particulate matter sensors streaming their measurements over a serial port. The
+// sensors are implemented by `common::particulate`, which documents the readings and the
+// remaining attributes.
+//
+// Example configuration
+//
+// {
+//   "model": "pms5003",
+//   "name": "air",
+//   "type": "sensor",
+//   "attributes": {
+//     "uart_port": 2,
+//     "tx_pin": 17,
+//     "rx_pin": 16,
+//     "warm_up_secs": 30
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `uart_port`, `tx_pin`, `rx_pin` and `baud_rate`: the serial port the sensor is connected
+//    to, see `esp32::uart`. `baud_rate` defaults to 9600, the only rate of both sensors.
+
+use crate::common::config::ConfigType;
+use crate::common::particulate::{ParticulateProtocol, ParticulateSensor};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::sensor::{SensorError, SensorType};
+
+use super::uart::uart_from_config;
+
+const DEFAULT_BAUD_RATE: u32 = 9600;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("pms5003", &pms5003_from_config)
+        .is_err()
+    {
+        log::error!("pms5003 model is already registered")
+    }
+    if registry
+        .register_sensor("sds011", &sds011_from_config)
+        .is_err()
+    {
+        log::error!("sds011 model is already registered")
+    }
+}
+
+fn pms5003_from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<SensorType, SensorError> {
+    let uart = uart_from_config(&cfg, DEFAULT_BAUD_RATE)?;
+    ParticulateSensor::from_uart_and_config(uart, ParticulateProtocol::Pms5003, cfg)
+}
+
+fn sds011_from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<SensorType, SensorError> {
+    let uart = uart_from_config(&cfg, DEFAULT_BAUD_RATE)?;
+    ParticulateSensor::from_uart_and_config(uart, ParticulateProtocol::Sds011, cfg)
+}
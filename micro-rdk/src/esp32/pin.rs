@@ -1,14 +1,20 @@
-use super::pwm::PwmDriver;
-use crate::common::board::BoardError;
+use super::pwm::{Esp32PwmError, PwmDriver};
+use super::square_wave::{select_backend, PwmBackend, RmtChannel, SquareWave};
+use crate::common::board::{BoardError, PulseTrain};
+use crate::common::digital_interrupt::{EdgeRing, Tick};
 use crate::esp32::esp_idf_svc::hal::gpio::{
     AnyIOPin, InputOutput, InterruptType, Pin, PinDriver, Pull,
 };
+use crate::esp32::esp_idf_svc::hal::rmt::{
+    config::TransmitConfig, PinState, Pulse, PulseTicks, VariableLengthSignal,
+};
 use crate::esp32::esp_idf_svc::sys::{
-    esp, gpio_install_isr_service, gpio_isr_handler_add, ESP_INTR_FLAG_IRAM,
+    esp, esp_timer_get_time, gpio_install_isr_service, gpio_isr_handler_add,
+    gpio_mode_t_GPIO_MODE_INPUT_OUTPUT, gpio_set_direction, EspError, ESP_INTR_FLAG_IRAM,
 };
 use once_cell::sync::{Lazy, OnceCell};
-use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub trait PinExt {
     fn pin(&self) -> i32;
@@ -20,6 +26,30 @@ impl<'d, T: Pin, MODE> PinExt for PinDriver<'d, T, MODE> {
     }
 }
 
+// Divides the 80MHz APB clock feeding the RMT peripheral into 1µs ticks
+const RMT_CLOCK_DIVIDER: u8 = 80;
+// Longest level a single RMT item holds, in ticks
+const RMT_MAX_ITEM_TICKS: u128 = 32767;
+// Bounds the memory taken by the items of a pulse train
+const RMT_MAX_ITEMS: u128 = 4096;
+// Longest train emitted, the caller is blocked until it's out
+const MAX_PULSE_TRAIN_DURATION: Duration = Duration::from_millis(100);
+
+// Appends `duration` at `state` to `signal`, split across as many items as needed
+fn push_level(
+    signal: &mut VariableLengthSignal,
+    state: PinState,
+    duration: Duration,
+) -> Result<(), EspError> {
+    let mut remaining = duration.as_micros();
+    while remaining > 0 {
+        let ticks = remaining.min(RMT_MAX_ITEM_TICKS);
+        signal.push([&Pulse::new(state, PulseTicks::new(ticks as u16)?)])?;
+        remaining -= ticks;
+    }
+    Ok(())
+}
+
 fn install_gpio_isr_service() -> Result<(), BoardError> {
     static GPIO_ISR_SERVICE_INSTALLED: Lazy<Arc<OnceCell<()>>> =
         Lazy::new(|| Arc::new(OnceCell::new()));
@@ -33,6 +63,12 @@ fn install_gpio_isr_service() -> Result<(), BoardError> {
     Ok(())
 }
 
+// Generator of the pwm signal of a pin
+enum PwmOutput {
+    Ledc(PwmDriver<'static>),
+    Wave(SquareWave),
+}
+
 /// Esp32GPIOPin is a wrapper for a pin on ESP32 as represented in esp-idf-hal
 /// and esp-idf-sys. This exists so that all micro-RDK drivers can interact
 /// with pins through the board instance and avoid conflicting uses of pins
@@ -41,8 +77,9 @@ pub struct Esp32GPIOPin {
     pin: i32,
     driver: PinDriver<'static, AnyIOPin, InputOutput>,
     interrupt_type: Option<InterruptType>,
-    event_count: Arc<AtomicU32>,
-    pwm_driver: Option<PwmDriver<'static>>,
+    // shared with the interrupt handler
+    edges: Arc<EdgeRing>,
+    pwm: Option<PwmOutput>,
 }
 
 impl Esp32GPIOPin {
@@ -58,8 +95,8 @@ impl Esp32GPIOPin {
             pin,
             driver,
             interrupt_type: None,
-            event_count: Arc::new(AtomicU32::new(0)),
-            pwm_driver: None,
+            edges: Arc::new(EdgeRing::default()),
+            pwm: None,
         })
     }
 
@@ -72,7 +109,7 @@ impl Esp32GPIOPin {
     }
 
     pub fn set_high(&mut self) -> Result<(), BoardError> {
-        if self.pwm_driver.is_some() {
+        if self.pwm.is_some() {
             return Err(BoardError::GpioPinError(
                 self.pin as u32,
                 "is pwm cannot set level",
@@ -85,7 +122,7 @@ impl Esp32GPIOPin {
     }
 
     pub fn set_low(&mut self) -> Result<(), BoardError> {
-        if self.pwm_driver.is_some() {
+        if self.pwm.is_some() {
             return Err(BoardError::GpioPinError(
                 self.pin as u32,
                 "is pwm cannot set level",
@@ -96,9 +133,55 @@ impl Esp32GPIOPin {
             .map_err(|_| BoardError::GpioPinError(self.pin as u32, "cannot set high"))
     }
 
+    /// Emits `train` with the RMT peripheral, which times it to the microsecond. The pin is only
+    /// lent to a free RMT channel while the train is emitted, it's left low. Trains lasting more
+    /// than 100ms are rejected as the caller waits for the last pulse.
+    pub fn pulse(&mut self, train: &PulseTrain) -> Result<(), BoardError> {
+        if self.interrupt_type.is_some() || self.pwm.is_some() {
+            return Err(BoardError::GpioPinError(
+                self.pin as u32,
+                "is an interrupt or pwm pin",
+            ));
+        }
+        train.validate()?;
+        if train.duration() > MAX_PULSE_TRAIN_DURATION {
+            return Err(BoardError::BoardUnsupportedArgument(
+                "pulse train longer than 100ms",
+            ));
+        }
+        let items = |duration: Duration| duration.as_micros().div_ceil(RMT_MAX_ITEM_TICKS);
+        if u128::from(train.count) * (items(train.high) + items(train.low)) > RMT_MAX_ITEMS {
+            return Err(BoardError::BoardUnsupportedArgument(
+                "pulse train too long for the RMT peripheral",
+            ));
+        }
+        let pin = self.pin;
+        let to_board_error =
+            move |e: EspError| BoardError::GpioPinOtherError(pin as u32, Box::new(e));
+        let mut signal = VariableLengthSignal::new();
+        for _ in 0..train.count {
+            push_level(&mut signal, PinState::High, train.high).map_err(to_board_error)?;
+            push_level(&mut signal, PinState::Low, train.low).map_err(to_board_error)?;
+        }
+        {
+            let channel = RmtChannel::take()
+                .map_err(|e| BoardError::GpioPinOtherError(pin as u32, Box::new(e)))?;
+            let config = TransmitConfig::new().clock_divider(RMT_CLOCK_DIVIDER);
+            let mut tx = channel.tx_driver(pin, &config).map_err(to_board_error)?;
+            // returns once the whole train is out
+            tx.start_blocking(&signal).map_err(to_board_error)?;
+        }
+        // the RMT channel took over the output of the pin, setting its direction routes it back
+        // to the GPIO driver
+        esp!(unsafe { gpio_set_direction(pin, gpio_mode_t_GPIO_MODE_INPUT_OUTPUT) })
+            .map_err(to_board_error)?;
+        self.set_low()
+    }
+
     pub fn get_pwm_duty(&self) -> f64 {
-        match &self.pwm_driver {
-            Some(pwm_driver) => pwm_driver.get_ledc_duty_pct(),
+        match &self.pwm {
+            Some(PwmOutput::Ledc(pwm_driver)) => pwm_driver.get_ledc_duty_pct(),
+            Some(PwmOutput::Wave(wave)) => wave.duty_pct(),
             None => 0.0,
         }
     }
@@ -110,56 +193,95 @@ impl Esp32GPIOPin {
                 "is not a pwm pin",
             ));
         }
-        match self.pwm_driver.as_mut() {
-            Some(pwm_driver) => {
+        match self.pwm.as_mut() {
+            Some(PwmOutput::Ledc(pwm_driver)) => {
                 pwm_driver
                     .set_ledc_duty_pct(pct)
                     .map_err(|e| BoardError::GpioPinOtherError(self.pin as u32, Box::new(e)))?;
             }
+            Some(PwmOutput::Wave(wave)) => {
+                let frequency_hz = wave.frequency_hz();
+                self.start_pwm(frequency_hz, pct)?;
+            }
             None => {
                 let mut pwm_driver = PwmDriver::new(unsafe { AnyIOPin::new(self.pin) }, 10000)
                     .map_err(|e| BoardError::GpioPinOtherError(self.pin as u32, Box::new(e)))?;
                 pwm_driver
                     .set_ledc_duty_pct(pct)
                     .map_err(|e| BoardError::GpioPinOtherError(self.pin as u32, Box::new(e)))?;
-                self.pwm_driver = Some(pwm_driver);
+                self.pwm = Some(PwmOutput::Ledc(pwm_driver));
             }
         };
         Ok(())
     }
 
     pub fn get_pwm_frequency(&self) -> u64 {
-        match &self.pwm_driver {
-            Some(pwm_driver) => pwm_driver.get_timer_frequency() as u64,
-            None => 0,
+        self.get_pwm_frequency_hz().round() as u64
+    }
+
+    pub fn get_pwm_frequency_hz(&self) -> f64 {
+        match &self.pwm {
+            Some(PwmOutput::Ledc(pwm_driver)) => pwm_driver.get_timer_frequency() as f64,
+            Some(PwmOutput::Wave(wave)) => wave.frequency_hz(),
+            None => 0.0,
         }
     }
 
     pub fn set_pwm_frequency(&mut self, freq: u64) -> Result<(), BoardError> {
+        self.set_pwm_frequency_hz(freq as f64)
+    }
+
+    /// Sets the frequency of the pwm signal, keeping its duty cycle. The signal moves to the
+    /// peripheral able to generate it, see [select_backend]
+    pub fn set_pwm_frequency_hz(&mut self, frequency_hz: f64) -> Result<(), BoardError> {
         if self.interrupt_type.is_some() {
             return Err(BoardError::GpioPinError(
                 self.pin as u32,
                 "is not a pwm pin",
             ));
         }
-        if freq == 0 {
-            self.pwm_driver = None
-        } else {
-            match self.pwm_driver.as_mut() {
-                Some(pwm_driver) => {
-                    pwm_driver
-                        .set_timer_frequency(freq as u32)
-                        .map_err(|e| BoardError::GpioPinOtherError(self.pin as u32, Box::new(e)))?;
-                }
-                None => {
-                    let pwm_driver =
-                        PwmDriver::new(unsafe { AnyIOPin::new(self.pin) }, freq as u32).map_err(
-                            |e| BoardError::GpioPinOtherError(self.pin as u32, Box::new(e)),
-                        )?;
-                    self.pwm_driver = Some(pwm_driver);
-                }
-            }
+        if frequency_hz == 0.0 {
+            self.pwm = None;
+            return Ok(());
         }
+        let duty_pct = self.get_pwm_duty();
+        self.start_pwm(frequency_hz, duty_pct)
+    }
+
+    // starts generating the pwm signal, an LEDC channel already generating it is kept
+    fn start_pwm(&mut self, frequency_hz: f64, duty_pct: f64) -> Result<(), BoardError> {
+        let pin = self.pin;
+        let backend = select_backend(frequency_hz, duty_pct).map_err(|reason| {
+            BoardError::PwmUnsupported(pin as u32, frequency_hz, duty_pct, reason)
+        })?;
+        let to_board_error =
+            move |e: Esp32PwmError| BoardError::GpioPinOtherError(pin as u32, Box::new(e));
+        if let (PwmBackend::Ledc, Some(PwmOutput::Ledc(pwm_driver))) = (backend, self.pwm.as_mut())
+        {
+            return pwm_driver
+                .set_timer_frequency(frequency_hz as u32)
+                .map_err(to_board_error);
+        }
+        // the previous generator has to release the pin before the next one takes it
+        self.pwm = None;
+        let output = match backend {
+            PwmBackend::Ledc => {
+                let mut pwm_driver =
+                    PwmDriver::new(unsafe { AnyIOPin::new(pin) }, frequency_hz as u32)
+                        .map_err(to_board_error)?;
+                pwm_driver
+                    .set_ledc_duty_pct(duty_pct)
+                    .map_err(to_board_error)?;
+                PwmOutput::Ledc(pwm_driver)
+            }
+            PwmBackend::Rmt => PwmOutput::Wave(
+                SquareWave::rmt(pin, frequency_hz, duty_pct).map_err(to_board_error)?,
+            ),
+            PwmBackend::GpTimer => PwmOutput::Wave(
+                SquareWave::gptimer(pin, frequency_hz, duty_pct).map_err(to_board_error)?,
+            ),
+        };
+        self.pwm = Some(output);
         Ok(())
     }
 
@@ -183,7 +305,7 @@ impl Esp32GPIOPin {
         self.driver
             .set_interrupt_type(intr_type)
             .map_err(|e| BoardError::GpioPinOtherError(self.pin as u32, Box::new(e)))?;
-        self.event_count.store(0, Ordering::Relaxed);
+        self.edges.reset();
         unsafe {
             // we can't use the subscribe method on PinDriver to add the handler
             // because it requires an FnMut with a static lifetime. A possible follow-up
@@ -192,7 +314,7 @@ impl Esp32GPIOPin {
             esp!(gpio_isr_handler_add(
                 self.pin,
                 Some(Self::interrupt),
-                &mut self.event_count as *mut Arc<AtomicU32> as *mut _
+                Arc::as_ptr(&self.edges) as *mut _
             ))
             .map_err(|e| BoardError::GpioPinOtherError(self.pin as u32, Box::new(e)))?;
         }
@@ -200,13 +322,34 @@ impl Esp32GPIOPin {
     }
 
     pub fn get_event_count(&self) -> u32 {
-        self.event_count.load(Ordering::Relaxed)
+        self.edges.count()
+    }
+
+    /// Returns the edges seen after the first `since` events that are still recorded, oldest
+    /// first, and the amount of events
+    pub fn get_ticks_since(&self, since: u32) -> (Vec<Tick>, u32) {
+        let now_us = unsafe { esp_timer_get_time() } as u32;
+        let now_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        // the interrupt fires on the edges of its type only
+        let high = self.interrupt_type != Some(InterruptType::NegEdge);
+        let (edges, count) = self.edges.edges_since(since);
+        let ticks = edges
+            .into_iter()
+            .map(|when_us| Tick {
+                pin: self.pin,
+                high,
+                time_ns: now_ns.saturating_sub(u64::from(now_us.wrapping_sub(when_us)) * 1000),
+            })
+            .collect();
+        (ticks, count)
     }
 
     #[inline(always)]
     #[link_section = ".iram1.intr_srv"]
     unsafe extern "C" fn interrupt(arg: *mut core::ffi::c_void) {
-        let arg: &mut Arc<AtomicU32> = &mut *(arg as *mut _);
-        arg.fetch_add(1, Ordering::Relaxed);
+        let arg: &EdgeRing = &*(arg as *const _);
+        arg.record(esp_timer_get_time() as u32);
     }
 }
@@ -0,0 +1,38 @@
+//! Applies the [PowerProfile] configured by the power management service using the dynamic
+//! frequency scaling of esp-idf.
+
+use crate::common::{power_management::PowerProfile, telemetry::record_telemetry};
+use crate::esp32::esp_idf_svc::sys::{
+    esp, esp_pm_config_esp32_t, esp_pm_configure, EspError, ESP_ERR_NOT_SUPPORTED,
+};
+
+pub fn apply_power_profile(profile: &PowerProfile) -> Result<(), EspError> {
+    let config = esp_pm_config_esp32_t {
+        max_freq_mhz: profile.max_freq_mhz as i32,
+        min_freq_mhz: profile.min_freq_mhz as i32,
+        light_sleep_enable: profile.auto_light_sleep,
+    };
+    esp!(unsafe { esp_pm_configure(&config as *const esp_pm_config_esp32_t as *const _) })
+        .map_err(|err| {
+            if err.code() == ESP_ERR_NOT_SUPPORTED as i32 {
+                log::warn!("power profiles need CONFIG_PM_ENABLE, see sdkconfig.defaults.pm");
+            }
+            err
+        })?;
+    log::info!(
+        "power profile {}: {} to {} MHz, automatic light sleep {}",
+        profile.name,
+        profile.min_freq_mhz,
+        profile.max_freq_mhz,
+        if profile.auto_light_sleep {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+    record_telemetry("power_profile", profile.name.as_str());
+    record_telemetry("cpu_min_freq_mhz", profile.min_freq_mhz);
+    record_telemetry("cpu_max_freq_mhz", profile.max_freq_mhz);
+    record_telemetry("auto_light_sleep", profile.auto_light_sleep);
+    Ok(())
+}
@@ -1,6 +1,8 @@
+use crate::common::config::{AttributeError, ConfigType};
 use crate::common::encoder::EncoderError;
 use crate::esp32::esp_idf_svc::sys::{
-    pcnt_isr_service_install, pcnt_isr_service_uninstall, ESP_OK,
+    pcnt_filter_disable, pcnt_filter_enable, pcnt_isr_service_install, pcnt_isr_service_uninstall,
+    pcnt_set_filter_value, pcnt_unit_t, ESP_OK,
 };
 use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
 use std::sync::Arc;
@@ -56,3 +58,56 @@ pub(crate) fn isr_remove_unit() {
         }
     };
 }
+
+/// Pulses shorter than this number of APB clock cycles (1µs at 80MHz) are ignored unless
+/// configured otherwise, so mechanically noisy encoders don't accumulate phantom ticks.
+pub(crate) const DEFAULT_GLITCH_FILTER_CYCLES: u16 = 80;
+// the filter threshold is a 10 bits value
+const MAX_GLITCH_FILTER_CYCLES: u16 = 1023;
+
+/// Reads the `glitch_filter_cycles` attribute of an encoder, 0 disables the filter
+pub(crate) fn glitch_filter_from_config(cfg: &ConfigType) -> Result<u16, EncoderError> {
+    match cfg.get_attribute::<u16>("glitch_filter_cycles") {
+        Ok(cycles) if cycles > MAX_GLITCH_FILTER_CYCLES => Err(EncoderError::EncoderConfigError(
+            "glitch_filter_cycles must be at most 1023",
+        )),
+        Ok(cycles) => Ok(cycles),
+        Err(AttributeError::KeyNotFound(_)) => Ok(DEFAULT_GLITCH_FILTER_CYCLES),
+        Err(err) => Err(EncoderError::EncoderConfigAttributeError(err)),
+    }
+}
+
+/// Reads the optional `ticks_per_rotation` attribute of an encoder, which must be positive
+pub(crate) fn ticks_per_rotation_from_config(
+    cfg: &ConfigType,
+) -> Result<Option<u32>, EncoderError> {
+    match cfg.get_attribute::<u32>("ticks_per_rotation") {
+        Ok(ticks) if ticks > 0 => Ok(Some(ticks)),
+        Err(AttributeError::KeyNotFound(_)) => Ok(None),
+        _ => Err(EncoderError::EncoderConfigError(
+            "ticks_per_rotation must be a positive integer",
+        )),
+    }
+}
+
+/// Configures the glitch filter of a pulse counter unit, 0 disables the filter
+pub(crate) fn set_glitch_filter(unit: pcnt_unit_t, cycles: u16) -> Result<(), EncoderError> {
+    unsafe {
+        if cycles == 0 {
+            match pcnt_filter_disable(unit) {
+                ESP_OK => {}
+                err => return Err(EncoderError::EncoderCodeError(err)),
+            }
+            return Ok(());
+        }
+        match pcnt_set_filter_value(unit, cycles) {
+            ESP_OK => {}
+            err => return Err(EncoderError::EncoderCodeError(err)),
+        }
+        match pcnt_filter_enable(unit) {
+            ESP_OK => {}
+            err => return Err(EncoderError::EncoderCodeError(err)),
+        }
+    }
+    Ok(())
+}
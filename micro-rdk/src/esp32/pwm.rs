@@ -36,6 +36,12 @@ pub enum Esp32PwmError {
     InvalidTimerNumber(i32),
     #[error("one or more channel are bind to the timer")]
     OtherChannelsBindToTimer,
+    #[error("{0}")]
+    WaveUnsupported(&'static str),
+    #[error("no more rmt channels available")]
+    NoRmtChannelsAvailable,
+    #[error("no more general purpose timers available")]
+    NoGpTimersAvailable,
 }
 
 impl From<EspError> for Esp32PwmError {
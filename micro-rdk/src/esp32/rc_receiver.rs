@@ -0,0 +1,275 @@
+// Support for reading RC receivers over PPM or SBUS. The channels are decoded and
+// reported by `common::rc_receiver`, which documents the attributes common to both
+// protocols.
+//
+// Example configuration
+//
+// {
+//   "model": "rc_receiver",
+//   "name": "rc",
+//   "type": "sensor",
+//   "attributes": {
+//     "protocol": "ppm",
+//     "pin": 4,
+//     "channel_names": ["steering", "throttle"]
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `protocol` (required): either `ppm` or `sbus`.
+//
+//  - `pin` (required): The GPIO pin number connected to the PPM or SBUS output of the
+//    receiver. As for the ultrasonic sensor, this must not be a pin configured as a
+//    digital interrupt of the board.
+//
+//  - `uart_port` (optional, SBUS only): The UART peripheral receiving the SBUS
+//    stream, defaults to 1. The signal is inverted by the UART, so the receiver
+//    can be wired directly to the pin.
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use crate::common::{
+    config::ConfigType,
+    rc_receiver::{PpmDecoder, RcFrame, RcInput, RcReceiver, SbusDecoder, SBUS_FRAME_LEN},
+    registry::{ComponentRegistry, Dependency},
+    sensor::{SensorError, SensorType, COMPONENT_NAME as SensorCompName},
+};
+
+use crate::esp32::esp_idf_svc::hal::gpio::{
+    enable_isr_service, init_isr_alloc_flags, AnyIOPin, Input, InterruptType, PinDriver,
+};
+use crate::esp32::esp_idf_svc::sys::{
+    esp, esp_timer_get_time, gpio_isr_handler_add, gpio_isr_handler_remove, uart_config_t,
+    uart_driver_delete, uart_driver_install, uart_hw_flowcontrol_t_UART_HW_FLOWCTRL_DISABLE,
+    uart_param_config, uart_parity_t_UART_PARITY_EVEN, uart_port_t, uart_read_bytes,
+    uart_set_line_inverse, uart_set_pin, uart_signal_inv_t_UART_SIGNAL_RXD_INV,
+    uart_stop_bits_t_UART_STOP_BITS_2, uart_word_length_t_UART_DATA_8_BITS, UART_PIN_NO_CHANGE,
+};
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_sensor("rc_receiver", &from_config)
+        .is_err()
+    {
+        log::error!("rc_receiver type is already registered");
+    }
+    if registry
+        .register_dependency_getter(
+            SensorCompName,
+            "rc_receiver",
+            &RcReceiver::dependencies_from_config,
+        )
+        .is_err()
+    {
+        log::error!("failed to register dependency getter for rc_receiver model")
+    }
+}
+
+fn from_config(cfg: ConfigType, deps: Vec<Dependency>) -> Result<SensorType, SensorError> {
+    let pin = cfg
+        .get_attribute::<i32>("pin")
+        .map_err(|_| SensorError::ConfigError("rc_receiver: missing `pin`"))?;
+    let protocol = cfg
+        .get_attribute::<String>("protocol")
+        .map_err(|_| SensorError::ConfigError("rc_receiver: missing `protocol`"))?;
+    let input: Box<dyn RcInput> = match protocol.as_str() {
+        "ppm" => Box::new(PpmInput::new(pin)?),
+        "sbus" => {
+            let port = cfg.get_attribute::<i32>("uart_port").unwrap_or(1);
+            Box::new(SbusInput::new(port, pin)?)
+        }
+        _ => {
+            return Err(SensorError::ConfigError(
+                "rc_receiver: `protocol` must be ppm or sbus",
+            ))
+        }
+    };
+    RcReceiver::from_input_and_config(input, cfg, deps)
+}
+
+// enough for a few frames of 16 channels
+const PPM_RING_LEN: usize = 64;
+
+// Widths between rising edges recorded by the ISR, `head` counts every width ever
+// recorded and the latest is stored at `head - 1` modulo the ring length.
+struct PpmIsrSharedState {
+    last_edge: AtomicU32,
+    head: AtomicU32,
+    widths: [AtomicU32; PPM_RING_LEN],
+}
+
+pub struct PpmInput {
+    pin: PinDriver<'static, AnyIOPin, Input>,
+    isr_shared_state: Arc<PpmIsrSharedState>,
+    tail: u32,
+    decoder: PpmDecoder,
+}
+
+impl PpmInput {
+    fn new(pin: i32) -> Result<Self, SensorError> {
+        init_isr_alloc_flags(crate::esp32::esp_idf_svc::hal::interrupt::InterruptType::Iram.into());
+        enable_isr_service().map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        let mut driver = PinDriver::input(unsafe { AnyIOPin::new(pin) })
+            .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        driver
+            .set_interrupt_type(InterruptType::PosEdge)
+            .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        let input = Self {
+            pin: driver,
+            isr_shared_state: Arc::new(PpmIsrSharedState {
+                last_edge: 0.into(),
+                head: 0.into(),
+                widths: std::array::from_fn(|_| 0.into()),
+            }),
+            tail: 0,
+            decoder: PpmDecoder::default(),
+        };
+        unsafe {
+            esp!(gpio_isr_handler_add(
+                pin,
+                Some(Self::subscription_interrupt),
+                Arc::as_ptr(&input.isr_shared_state) as *mut _,
+            ))
+            .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        }
+        Ok(input)
+    }
+
+    #[inline(always)]
+    #[link_section = ".iram1.intr_srv"]
+    unsafe extern "C" fn subscription_interrupt(arg: *mut core::ffi::c_void) {
+        let arg: &PpmIsrSharedState = &*(arg as *const _);
+        let when = esp_timer_get_time() as u32;
+        let width = when.wrapping_sub(arg.last_edge.swap(when, Ordering::AcqRel));
+        let head = arg.head.load(Ordering::Acquire);
+        arg.widths[head as usize % PPM_RING_LEN].store(width, Ordering::Release);
+        arg.head.store(head.wrapping_add(1), Ordering::Release);
+    }
+}
+
+impl RcInput for PpmInput {
+    fn read_frame(&mut self) -> Result<Option<RcFrame>, SensorError> {
+        let head = self.isr_shared_state.head.load(Ordering::Acquire);
+        if head.wrapping_sub(self.tail) as usize > PPM_RING_LEN {
+            // widths were overwritten, the frame being assembled is incomplete
+            self.decoder = PpmDecoder::default();
+            self.tail = head.wrapping_sub(PPM_RING_LEN as u32);
+        }
+        let mut last = None;
+        while self.tail != head {
+            let width = self.isr_shared_state.widths[self.tail as usize % PPM_RING_LEN]
+                .load(Ordering::Acquire);
+            if let Some(frame) = self.decoder.push_pulse(width) {
+                last = Some(frame);
+            }
+            self.tail = self.tail.wrapping_add(1);
+        }
+        Ok(last)
+    }
+}
+
+impl Drop for PpmInput {
+    fn drop(&mut self) {
+        let pin = self.pin.pin();
+        if let Err(error) = unsafe { esp!(gpio_isr_handler_remove(pin)) } {
+            log::warn!(
+                "rc_receiver: failed to remove interrupt handler for pin {}: {}",
+                pin,
+                error
+            )
+        }
+    }
+}
+
+pub struct SbusInput {
+    port: uart_port_t,
+    decoder: SbusDecoder,
+}
+
+impl SbusInput {
+    fn new(port: i32, pin: i32) -> Result<Self, SensorError> {
+        // SBUS is an inverted 100000 baud 8E2 serial stream
+        let config = uart_config_t {
+            baud_rate: 100_000,
+            data_bits: uart_word_length_t_UART_DATA_8_BITS,
+            parity: uart_parity_t_UART_PARITY_EVEN,
+            stop_bits: uart_stop_bits_t_UART_STOP_BITS_2,
+            flow_ctrl: uart_hw_flowcontrol_t_UART_HW_FLOWCTRL_DISABLE,
+            ..Default::default()
+        };
+        let port = port as uart_port_t;
+        unsafe {
+            esp!(uart_driver_install(
+                port,
+                (SBUS_FRAME_LEN * 16) as i32,
+                0,
+                0,
+                std::ptr::null_mut(),
+                0
+            ))
+            .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        }
+        // the driver is released on drop from here on
+        let input = SbusInput {
+            port,
+            decoder: SbusDecoder::default(),
+        };
+        unsafe {
+            esp!(uart_param_config(port, &config))
+                .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+            esp!(uart_set_pin(
+                port,
+                UART_PIN_NO_CHANGE,
+                pin,
+                UART_PIN_NO_CHANGE,
+                UART_PIN_NO_CHANGE
+            ))
+            .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+            esp!(uart_set_line_inverse(
+                port,
+                uart_signal_inv_t_UART_SIGNAL_RXD_INV
+            ))
+            .map_err(|err| SensorError::SensorCodeError(err.code()))?;
+        }
+        Ok(input)
+    }
+}
+
+impl RcInput for SbusInput {
+    fn read_frame(&mut self) -> Result<Option<RcFrame>, SensorError> {
+        let mut last = None;
+        let mut buf = [0_u8; SBUS_FRAME_LEN * 4];
+        loop {
+            // drain what was received without blocking
+            let read = unsafe {
+                uart_read_bytes(self.port, buf.as_mut_ptr() as *mut _, buf.len() as u32, 0)
+            };
+            if read < 0 {
+                return Err(SensorError::SensorCodeError(read));
+            }
+            if read == 0 {
+                break;
+            }
+            if let Some(frame) = self.decoder.push_bytes(&buf[..read as usize]) {
+                last = Some(frame);
+            }
+        }
+        Ok(last)
+    }
+}
+
+impl Drop for SbusInput {
+    fn drop(&mut self) {
+        if let Err(error) = unsafe { esp!(uart_driver_delete(self.port)) } {
+            log::warn!(
+                "rc_receiver: failed to delete uart driver {}: {}",
+                self.port,
+                error
+            )
+        }
+    }
+}
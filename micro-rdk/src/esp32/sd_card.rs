@@ -0,0 +1,320 @@
+//! SD card with a FAT filesystem, mounted when the esp32 board has an `sd_card` attribute. Once
+//! mounted, data capture buffers its messages on the card (see
+//! [SdCardDataStore](crate::common::data_store::SdCardDataStore)) and logs can be written to
+//! rotating files on it.
+//!
+//! ```json
+//! "sd_card": {
+//!     "interface": "spi",
+//!     "cs": 13,
+//!     "sclk": 14,
+//!     "mosi": 15,
+//!     "miso": 2,
+//!     "data_capacity_kb": 16384,
+//!     "log_files": { "max_size_kb": 256, "max_files": 8 }
+//! }
+//! ```
+//!
+//! With `"interface": "sdmmc"`, on the ESP32 and ESP32-S3 which have an SDMMC peripheral, the
+//! card is driven by the peripheral on its fixed pins, `width` (1 or 4, 1 by default) sets how
+//! many data lines are used. `data_capacity_kb` is the
+//! space each data capture collector may use, logs are only written to the card when
+//! `log_files` is present.
+
+use std::ffi::CString;
+use std::path::Path;
+use std::sync::OnceLock;
+
+#[cfg(feature = "data")]
+use crate::common::data_store::{SdCardDataStore, SdCardStorage};
+use crate::common::{
+    config::{AttributeError, Kind},
+    log::{log_to_files, RotatingLogFiles},
+};
+use crate::esp32::esp_idf_svc::sys::{
+    esp, esp_vfs_fat_mount_config_t, esp_vfs_fat_sdspi_mount, sdmmc_card_t, sdmmc_host_t,
+    sdspi_device_config_t, sdspi_host_do_transaction, sdspi_host_init, sdspi_host_io_int_enable,
+    sdspi_host_io_int_wait, sdspi_host_remove_device, sdspi_host_set_card_clk, spi_bus_config_t,
+    spi_bus_initialize, spi_common_dma_t_SPI_DMA_CH_AUTO, spi_host_device_t_SPI2_HOST, EspError,
+    SDMMC_FREQ_DEFAULT, SDMMC_HOST_FLAG_DEINIT_ARG, SDMMC_HOST_FLAG_SPI,
+};
+// only the ESP32 and ESP32-S3 have an SDMMC peripheral
+#[cfg(any(esp32, esp32s3))]
+use crate::esp32::esp_idf_svc::sys::{
+    esp_vfs_fat_sdmmc_mount, sdmmc_host_deinit, sdmmc_host_do_transaction,
+    sdmmc_host_get_slot_width, sdmmc_host_init, sdmmc_host_io_int_enable, sdmmc_host_io_int_wait,
+    sdmmc_host_set_bus_ddr_mode, sdmmc_host_set_bus_width, sdmmc_host_set_card_clk,
+    sdmmc_slot_config_t, SDMMC_HOST_FLAG_1BIT, SDMMC_HOST_FLAG_4BIT, SDMMC_HOST_FLAG_8BIT,
+    SDMMC_HOST_FLAG_DDR, SDMMC_HOST_SLOT_1,
+};
+
+use thiserror::Error;
+
+/// Where the card is mounted in the VFS
+pub const MOUNT_POINT: &str = "/sdcard";
+const MAX_OPEN_FILES: i32 = 8;
+const SPI_MAX_TRANSFER_SIZE: i32 = 4000;
+const DEFAULT_DATA_CAPACITY_KB: u64 = 16 * 1024;
+const DEFAULT_LOG_FILE_SIZE_KB: u64 = 256;
+const DEFAULT_LOG_FILES: usize = 8;
+
+#[derive(Debug, Error)]
+pub enum SdCardError {
+    #[error(transparent)]
+    EspError(#[from] EspError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error("an sd card is already mounted")]
+    AlreadyMounted,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SdCardInterface {
+    Spi {
+        cs: i32,
+        sclk: i32,
+        mosi: i32,
+        miso: i32,
+    },
+    #[cfg(any(esp32, esp32s3))]
+    Sdmmc { width: u8 },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LogFilesConfig {
+    pub max_size_kb: u64,
+    pub max_files: usize,
+}
+
+impl TryFrom<&Kind> for LogFilesConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        Ok(Self {
+            max_size_kb: value
+                .get("max_size_kb")?
+                .map(u32::try_from)
+                .transpose()?
+                .map_or(DEFAULT_LOG_FILE_SIZE_KB, u64::from),
+            max_files: value
+                .get("max_files")?
+                .map(u32::try_from)
+                .transpose()?
+                .map_or(DEFAULT_LOG_FILES, |max_files| max_files as usize),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SdCardConfig {
+    pub interface: SdCardInterface,
+    pub data_capacity_kb: u64,
+    pub log_files: Option<LogFilesConfig>,
+}
+
+impl TryFrom<&Kind> for SdCardConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let pin = |name: &str| -> Result<i32, AttributeError> {
+            value
+                .get(name)?
+                .ok_or_else(|| AttributeError::KeyNotFound(name.to_string()))?
+                .try_into()
+        };
+        let interface: &str = value
+            .get("interface")?
+            .ok_or_else(|| AttributeError::KeyNotFound("interface".to_string()))?
+            .try_into()?;
+        let interface = match interface {
+            "spi" => SdCardInterface::Spi {
+                cs: pin("cs")?,
+                sclk: pin("sclk")?,
+                mosi: pin("mosi")?,
+                miso: pin("miso")?,
+            },
+            #[cfg(any(esp32, esp32s3))]
+            "sdmmc" => {
+                let width = value
+                    .get("width")?
+                    .map(u32::try_from)
+                    .transpose()?
+                    .unwrap_or(1);
+                if width != 1 && width != 4 {
+                    return Err(AttributeError::ConversionImpossibleError);
+                }
+                SdCardInterface::Sdmmc { width: width as u8 }
+            }
+            _ => return Err(AttributeError::ConversionImpossibleError),
+        };
+        Ok(Self {
+            interface,
+            data_capacity_kb: value
+                .get("data_capacity_kb")?
+                .map(u32::try_from)
+                .transpose()?
+                .map_or(DEFAULT_DATA_CAPACITY_KB, u64::from),
+            log_files: value
+                .get("log_files")?
+                .map(LogFilesConfig::try_from)
+                .transpose()?,
+        })
+    }
+}
+
+struct MountedCard(*mut sdmmc_card_t);
+
+// the card handle is only kept so the card stays mounted for the lifetime of the firmware
+unsafe impl Send for MountedCard {}
+unsafe impl Sync for MountedCard {}
+
+static CARD: OnceLock<MountedCard> = OnceLock::new();
+
+fn mount_config() -> esp_vfs_fat_mount_config_t {
+    esp_vfs_fat_mount_config_t {
+        format_if_mount_failed: false,
+        max_files: MAX_OPEN_FILES,
+        allocation_unit_size: 16 * 1024,
+        ..Default::default()
+    }
+}
+
+// SDSPI_HOST_DEFAULT() of esp-idf
+fn sdspi_host() -> sdmmc_host_t {
+    let mut host = sdmmc_host_t {
+        flags: SDMMC_HOST_FLAG_SPI | SDMMC_HOST_FLAG_DEINIT_ARG,
+        slot: spi_host_device_t_SPI2_HOST as i32,
+        max_freq_khz: SDMMC_FREQ_DEFAULT as i32,
+        io_voltage: 3.3,
+        init: Some(sdspi_host_init),
+        set_card_clk: Some(sdspi_host_set_card_clk),
+        do_transaction: Some(sdspi_host_do_transaction),
+        io_int_enable: Some(sdspi_host_io_int_enable),
+        io_int_wait: Some(sdspi_host_io_int_wait),
+        ..Default::default()
+    };
+    host.__bindgen_anon_1.deinit_p = Some(sdspi_host_remove_device);
+    host
+}
+
+// SDMMC_HOST_DEFAULT() of esp-idf
+#[cfg(any(esp32, esp32s3))]
+fn sdmmc_host() -> sdmmc_host_t {
+    let mut host = sdmmc_host_t {
+        flags: SDMMC_HOST_FLAG_8BIT
+            | SDMMC_HOST_FLAG_4BIT
+            | SDMMC_HOST_FLAG_1BIT
+            | SDMMC_HOST_FLAG_DDR,
+        slot: SDMMC_HOST_SLOT_1 as i32,
+        max_freq_khz: SDMMC_FREQ_DEFAULT as i32,
+        io_voltage: 3.3,
+        init: Some(sdmmc_host_init),
+        set_bus_width: Some(sdmmc_host_set_bus_width),
+        get_bus_width: Some(sdmmc_host_get_slot_width),
+        set_bus_ddr_mode: Some(sdmmc_host_set_bus_ddr_mode),
+        set_card_clk: Some(sdmmc_host_set_card_clk),
+        do_transaction: Some(sdmmc_host_do_transaction),
+        io_int_enable: Some(sdmmc_host_io_int_enable),
+        io_int_wait: Some(sdmmc_host_io_int_wait),
+        ..Default::default()
+    };
+    host.__bindgen_anon_1.deinit = Some(sdmmc_host_deinit);
+    host
+}
+
+fn mount(interface: &SdCardInterface) -> Result<*mut sdmmc_card_t, EspError> {
+    let mount_point = CString::new(MOUNT_POINT).unwrap();
+    let mut card: *mut sdmmc_card_t = std::ptr::null_mut();
+    match *interface {
+        SdCardInterface::Spi {
+            cs,
+            sclk,
+            mosi,
+            miso,
+        } => {
+            let host = sdspi_host();
+            let mut bus = spi_bus_config_t {
+                sclk_io_num: sclk,
+                max_transfer_sz: SPI_MAX_TRANSFER_SIZE,
+                ..Default::default()
+            };
+            bus.__bindgen_anon_1.mosi_io_num = mosi;
+            bus.__bindgen_anon_2.miso_io_num = miso;
+            bus.__bindgen_anon_3.quadwp_io_num = -1;
+            bus.__bindgen_anon_4.quadhd_io_num = -1;
+            esp!(unsafe {
+                spi_bus_initialize(
+                    spi_host_device_t_SPI2_HOST,
+                    &bus,
+                    spi_common_dma_t_SPI_DMA_CH_AUTO,
+                )
+            })?;
+            // SDSPI_DEVICE_CONFIG_DEFAULT() with the configured chip select
+            let device = sdspi_device_config_t {
+                host_id: spi_host_device_t_SPI2_HOST,
+                gpio_cs: cs,
+                gpio_cd: -1,
+                gpio_wp: -1,
+                gpio_int: -1,
+            };
+            esp!(unsafe {
+                esp_vfs_fat_sdspi_mount(
+                    mount_point.as_ptr(),
+                    &host,
+                    &device,
+                    &mount_config(),
+                    &mut card,
+                )
+            })?;
+        }
+        #[cfg(any(esp32, esp32s3))]
+        SdCardInterface::Sdmmc { width } => {
+            let host = sdmmc_host();
+            // SDMMC_SLOT_CONFIG_DEFAULT() with the configured bus width
+            let mut slot = sdmmc_slot_config_t {
+                width,
+                ..Default::default()
+            };
+            slot.__bindgen_anon_1.gpio_cd = -1;
+            slot.__bindgen_anon_2.gpio_wp = -1;
+            esp!(unsafe {
+                esp_vfs_fat_sdmmc_mount(
+                    mount_point.as_ptr(),
+                    &host,
+                    &slot as *const sdmmc_slot_config_t as *const _,
+                    &mount_config(),
+                    &mut card,
+                )
+            })?;
+        }
+    }
+    Ok(card)
+}
+
+/// Mounts the card at [MOUNT_POINT] and points data capture and, when configured, logs to it.
+/// The card stays mounted until the device restarts
+pub fn mount_sd_card(config: &SdCardConfig) -> Result<(), SdCardError> {
+    if CARD.get().is_some() {
+        return Err(SdCardError::AlreadyMounted);
+    }
+    let card = mount(&config.interface)?;
+    let _ = CARD.set(MountedCard(card));
+    log::info!("sd card mounted at {}", MOUNT_POINT);
+
+    let root = Path::new(MOUNT_POINT);
+    #[cfg(feature = "data")]
+    if SdCardDataStore::set_storage(SdCardStorage {
+        directory: root.join("data"),
+        capacity_per_collector: config.data_capacity_kb * 1024,
+    })
+    .is_err()
+    {
+        log::warn!("data capture storage was already set, not using the sd card");
+    }
+    if let Some(log_files) = config.log_files {
+        log_to_files(RotatingLogFiles::new(
+            root.join("logs"),
+            log_files.max_size_kb * 1024,
+            log_files.max_files,
+        )?);
+    }
+    Ok(())
+}
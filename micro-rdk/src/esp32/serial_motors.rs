@@ -0,0 +1,72 @@
+// Support for motor controllers driven over a serial port: Roboclaw controllers in packet
+// serial mode and Sabertooth controllers in packetized serial mode. The motors are implemented
+// by `common::roboclaw` and `common::sabertooth`, which document their attributes.
+//
+// Example configuration, the two channels of a Roboclaw
+//
+// {
+//   "model": "roboclaw",
+//   "name": "left",
+//   "type": "motor",
+//   "attributes": {
+//     "tx_pin": 17,
+//     "rx_pin": 16,
+//     "channel": 1,
+//     "max_rpm": 300
+//   },
+// },
+// {
+//   "model": "roboclaw",
+//   "name": "right",
+//   "type": "motor",
+//   "attributes": {
+//     "tx_pin": 17,
+//     "rx_pin": 16,
+//     "channel": 2,
+//     "max_rpm": 300
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `uart_port`, `tx_pin`, `rx_pin` and `baud_rate`: the serial port the controller is
+//    connected to, see `esp32::uart`. `baud_rate` defaults to 38400 for the Roboclaw and
+//    9600 for the Sabertooth. Both channels of a controller, and every controller on the
+//    same port, must use the same port configuration. The Sabertooth only receives, its
+//    `rx_pin` can be any unused pin.
+
+use crate::common::config::ConfigType;
+use crate::common::motor::{MotorError, MotorType};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::roboclaw::RoboclawMotor;
+use crate::common::sabertooth::SabertoothMotor;
+
+use super::uart::uart_from_config;
+
+const ROBOCLAW_DEFAULT_BAUD_RATE: u32 = 38400;
+const SABERTOOTH_DEFAULT_BAUD_RATE: u32 = 9600;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry
+        .register_motor("roboclaw", &roboclaw_from_config)
+        .is_err()
+    {
+        log::error!("roboclaw model is already registered")
+    }
+    if registry
+        .register_motor("sabertooth", &sabertooth_from_config)
+        .is_err()
+    {
+        log::error!("sabertooth model is already registered")
+    }
+}
+
+fn roboclaw_from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<MotorType, MotorError> {
+    let uart = uart_from_config(&cfg, ROBOCLAW_DEFAULT_BAUD_RATE)?;
+    RoboclawMotor::from_uart_and_config(uart, cfg)
+}
+
+fn sabertooth_from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<MotorType, MotorError> {
+    let uart = uart_from_config(&cfg, SABERTOOTH_DEFAULT_BAUD_RATE)?;
+    SabertoothMotor::from_uart_and_config(uart, cfg)
+}
@@ -2,30 +2,38 @@ use super::single_encoder::SingleEncoderType;
 
 use crate::common::actuator::{Actuator, ActuatorError};
 use crate::common::encoder::{
-    Direction, Encoder, EncoderPositionType, EncoderSupportedRepresentations, SingleEncoder,
+    Direction, Encoder, EncoderError, EncoderPositionType, EncoderSupportedRepresentations,
+    SingleEncoder,
 };
-use crate::common::motor::{Motor, MotorError, MotorSupportedProperties, MotorType};
+use crate::common::motor::{GoForTarget, Motor, MotorError, MotorSupportedProperties, MotorType};
 use crate::common::status::{Status, StatusError};
 use crate::google;
 
 use std::collections::HashMap;
 use std::time::Duration;
 
+/// A motor read by an encoder counting in the direction the motor is driven. While a `go_for`
+/// is in progress its status reports `go_for_progress`, the fraction of the requested
+/// revolutions covered so far, when the encoder knows its ticks per rotation.
 #[derive(DoCommand)]
 pub struct SingleEncodedMotor {
     encoder: SingleEncoderType,
     motor: MotorType,
+    go_for_target: Option<GoForTarget>,
 }
 
 impl SingleEncodedMotor {
     pub fn new(motor: MotorType, encoder: SingleEncoderType) -> Self {
-        Self { encoder, motor }
+        Self {
+            encoder,
+            motor,
+            go_for_target: None,
+        }
     }
-}
 
-impl Motor for SingleEncodedMotor {
-    fn set_power(&mut self, power_pct: f64) -> Result<(), MotorError> {
-        let dir = match power_pct {
+    // direction the encoder counts in while the motor is driven at `power_pct`
+    fn direction(&self, power_pct: f64) -> Result<Direction, MotorError> {
+        Ok(match power_pct {
             x if x > 0.0 => Direction::Forwards,
             x if x < 0.0 => Direction::Backwards,
             x if x == 0.0 => {
@@ -38,8 +46,31 @@ impl Motor for SingleEncodedMotor {
                 }
             }
             _ => unreachable!(),
-        };
-        self.motor.set_power(power_pct)?;
+        })
+    }
+
+    fn ticks(&self) -> Result<f64, EncoderError> {
+        Ok(self.encoder.get_position(EncoderPositionType::TICKS)?.value as f64)
+    }
+
+    fn go_for_progress(&self) -> Option<f64> {
+        Some(self.go_for_target?.progress(self.ticks().ok()?))
+    }
+}
+
+impl Motor for SingleEncodedMotor {
+    fn set_power(&mut self, power_pct: f64) -> Result<(), MotorError> {
+        self.set_power_with_extra(power_pct, None)
+    }
+
+    fn set_power_with_extra(
+        &mut self,
+        power_pct: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), MotorError> {
+        let dir = self.direction(power_pct)?;
+        self.go_for_target = None;
+        self.motor.set_power_with_extra(power_pct, extra)?;
         log::debug!("set power in single encoded motor to {:?}", power_pct);
         Ok(self.encoder.set_direction(dir)?)
     }
@@ -65,13 +96,40 @@ impl Motor for SingleEncodedMotor {
         Ok(pos.value as i32)
     }
     fn go_for(&mut self, rpm: f64, revolutions: f64) -> Result<Option<Duration>, MotorError> {
-        self.motor.go_for(rpm, revolutions)
+        self.go_for_with_extra(rpm, revolutions, None)
+    }
+    fn go_for_with_extra(
+        &mut self,
+        rpm: f64,
+        revolutions: f64,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<Option<Duration>, MotorError> {
+        self.go_for_target = None;
+        // the motor turns backwards when either rpm or revolutions is negative
+        let dir = if revolutions == 0.0 {
+            self.direction(rpm)?
+        } else {
+            self.direction(rpm * revolutions)?
+        };
+        let dur = self.motor.go_for_with_extra(rpm, revolutions, extra)?;
+        self.encoder.set_direction(dir)?;
+        if let (Some(ticks_per_rotation), Ok(start)) =
+            (self.encoder.ticks_per_rotation(), self.ticks())
+        {
+            self.go_for_target = GoForTarget::new(start, rpm, revolutions, ticks_per_rotation);
+        }
+        Ok(dur)
     }
     fn get_properties(&mut self) -> MotorSupportedProperties {
         MotorSupportedProperties {
             position_reporting: true,
+            ticks_per_rotation: self.encoder.ticks_per_rotation(),
+            ..self.motor.get_properties()
         }
     }
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        self.motor.is_powered()
+    }
 }
 
 impl Actuator for SingleEncodedMotor {
@@ -79,7 +137,14 @@ impl Actuator for SingleEncodedMotor {
         self.motor.is_moving()
     }
     fn stop(&mut self) -> Result<(), ActuatorError> {
-        self.motor.stop()
+        self.stop_with_extra(None)
+    }
+    fn stop_with_extra(
+        &mut self,
+        extra: Option<google::protobuf::Struct>,
+    ) -> Result<(), ActuatorError> {
+        self.go_for_target = None;
+        self.motor.stop_with_extra(extra)
     }
 }
 
@@ -96,6 +161,14 @@ impl Status for SingleEncodedMotor {
                 kind: Some(google::protobuf::value::Kind::NumberValue(pos)),
             },
         );
+        if let Some(progress) = self.go_for_progress() {
+            hm.insert(
+                "go_for_progress".to_string(),
+                google::protobuf::Value {
+                    kind: Some(google::protobuf::value::Kind::NumberValue(progress)),
+                },
+            );
+        }
         Ok(Some(google::protobuf::Struct { fields: hm }))
     }
 }
@@ -1,7 +1,10 @@
 use embedded_hal::digital::v2::InputPin;
 
 use super::pin::PinExt;
-use super::pulse_counter::{get_unit, isr_install, isr_installed, isr_remove_unit};
+use super::pulse_counter::{
+    get_unit, glitch_filter_from_config, isr_install, isr_installed, isr_remove_unit,
+    set_glitch_filter, ticks_per_rotation_from_config,
+};
 
 use crate::common::config::{AttributeError, ConfigType};
 use crate::common::encoder::{
@@ -37,9 +40,6 @@ pub(crate) fn register_models(registry: &mut ComponentRegistry) {
     }
 }
 
-// TODO: Make configurable?
-const MAX_GLITCH_MICROSEC: u16 = 1;
-
 // TODO: Move this type to common once we have a single encoder
 // implementation for another board
 pub(crate) type SingleEncoderType = Arc<Mutex<dyn SingleEncoder>>;
@@ -54,11 +54,20 @@ struct PulseStorage {
 pub struct Esp32SingleEncoder {
     pulse_counter: Box<PulseStorage>,
     config: pcnt_config_t,
+    glitch_filter_cycles: u16,
+    // ticks counted over a rotation of the shaft, from the `ticks_per_rotation` attribute
+    ticks_per_rotation: Option<u32>,
     dir: Direction,
 }
 
 impl Esp32SingleEncoder {
-    pub fn new(encoder_pin: impl InputPin + PinExt, dir_flip: bool) -> Result<Self, EncoderError> {
+    /// Pulses shorter than `glitch_filter_cycles` APB clock cycles are ignored, 0 disables the
+    /// filter
+    pub fn new(
+        encoder_pin: impl InputPin + PinExt,
+        dir_flip: bool,
+        glitch_filter_cycles: u16,
+    ) -> Result<Self, EncoderError> {
         let unit = get_unit();
         log::debug!("pulse counter unit received in single encoder: {:?}", unit);
         let pcnt = Box::new(PulseStorage {
@@ -80,6 +89,8 @@ impl Esp32SingleEncoder {
                 channel: pcnt_channel_0,
                 unit,
             },
+            glitch_filter_cycles,
+            ticks_per_rotation: None,
             dir: Direction::StoppedForwards,
         };
         if dir_flip {
@@ -106,9 +117,10 @@ impl Esp32SingleEncoder {
                 }
             },
         };
-        Ok(Arc::new(Mutex::new(Esp32SingleEncoder::new(
-            pin, dir_flip,
-        )?)))
+        let glitch_filter_cycles = glitch_filter_from_config(&cfg)?;
+        let mut enc = Esp32SingleEncoder::new(pin, dir_flip, glitch_filter_cycles)?;
+        enc.ticks_per_rotation = ticks_per_rotation_from_config(&cfg)?;
+        Ok(Arc::new(Mutex::new(enc)))
     }
 
     pub fn start(&self) -> Result<(), EncoderError> {
@@ -191,19 +203,7 @@ impl Esp32SingleEncoder {
         })
         .map_err(|err| EncoderError::EncoderCodeError(err.code()))?;
 
-        unsafe {
-            match crate::esp32::esp_idf_svc::sys::pcnt_set_filter_value(
-                self.config.unit,
-                MAX_GLITCH_MICROSEC * 80,
-            ) {
-                ESP_OK => {}
-                err => return Err(EncoderError::EncoderCodeError(err)),
-            }
-            match crate::esp32::esp_idf_svc::sys::pcnt_filter_enable(self.config.unit) {
-                ESP_OK => {}
-                err => return Err(EncoderError::EncoderCodeError(err)),
-            }
-        }
+        set_glitch_filter(self.config.unit, self.glitch_filter_cycles)?;
 
         unsafe {
             match crate::esp32::esp_idf_svc::sys::pcnt_event_enable(
@@ -266,6 +266,9 @@ impl Encoder for Esp32SingleEncoder {
     fn reset_position(&mut self) -> Result<(), EncoderError> {
         self.reset()
     }
+    fn ticks_per_rotation(&self) -> Option<u32> {
+        self.ticks_per_rotation
+    }
 }
 
 impl SingleEncoder for Esp32SingleEncoder {
@@ -312,19 +315,7 @@ impl SingleEncoder for Esp32SingleEncoder {
                     err => return Err(EncoderError::EncoderCodeError(err)),
                 }
             }
-            unsafe {
-                match crate::esp32::esp_idf_svc::sys::pcnt_set_filter_value(
-                    self.config.unit,
-                    MAX_GLITCH_MICROSEC * 80,
-                ) {
-                    ESP_OK => {}
-                    err => return Err(EncoderError::EncoderCodeError(err)),
-                }
-                match crate::esp32::esp_idf_svc::sys::pcnt_filter_enable(self.config.unit) {
-                    ESP_OK => {}
-                    err => return Err(EncoderError::EncoderCodeError(err)),
-                }
-            }
+            set_glitch_filter(self.config.unit, self.glitch_filter_cycles)?;
 
             unsafe {
                 match crate::esp32::esp_idf_svc::sys::pcnt_event_enable(
@@ -0,0 +1,236 @@
+//! Deep and light sleep support for the ESP32 board.
+//!
+//! Wake sources other than the timer can be configured on the board with the `wake_sources`
+//! attribute, or per request through the `wake_sources` key of the `extra` field of a
+//! SetPowerMode request (which takes precedence over the board configuration).
+//!
+//! ```json
+//! "wake_sources": [
+//!   { "type": "ext0", "pin": 33, "level": 1 },
+//!   { "type": "ext1", "pins": [32, 34], "mode": "any_high" },
+//!   { "type": "touch" }
+//! ]
+//! ```
+//!
+//! Light sleep keeps the WiFi association, the device wakes up on network activity or once the
+//! requested duration elapsed.
+//!
+//! Pins used as ext0/ext1 wake sources must be RTC GPIOs. A small amount of state (the
+//! boot count and the last requested sleep duration) is kept in RTC memory, which is
+//! preserved while in deep sleep. The data manager saves the read positions of the data store
+//! on every sync, so a store on an SD card resumes reading where it stopped after waking up.
+//! A data store in regular RAM loses the messages not synced before entering deep sleep.
+
+use std::time::Duration;
+
+use crate::common::{
+    board::BoardError,
+    config::{AttributeError, Kind},
+};
+
+use crate::esp32::esp_idf_svc::sys::{
+    esp, esp_light_sleep_start, esp_sleep_disable_wakeup_source, esp_sleep_enable_ext0_wakeup,
+    esp_sleep_enable_ext1_wakeup, esp_sleep_enable_timer_wakeup, esp_sleep_enable_touchpad_wakeup,
+    esp_sleep_enable_wifi_wakeup, esp_sleep_ext1_wakeup_mode_t_ESP_EXT1_WAKEUP_ALL_LOW,
+    esp_sleep_ext1_wakeup_mode_t_ESP_EXT1_WAKEUP_ANY_HIGH, esp_sleep_get_wakeup_cause,
+    esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT0, esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT1,
+    esp_sleep_source_t_ESP_SLEEP_WAKEUP_GPIO, esp_sleep_source_t_ESP_SLEEP_WAKEUP_TIMER,
+    esp_sleep_source_t_ESP_SLEEP_WAKEUP_TOUCHPAD, esp_sleep_source_t_ESP_SLEEP_WAKEUP_UART,
+    esp_sleep_source_t_ESP_SLEEP_WAKEUP_ULP,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ext1Mode {
+    AnyHigh,
+    AllLow,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WakeSourceConfig {
+    /// Wake when a single RTC GPIO reaches `level`
+    Ext0 { pin: i32, level: bool },
+    /// Wake on a combination of RTC GPIOs
+    Ext1 { pins: Vec<i32>, mode: Ext1Mode },
+    /// Wake when a touch pad configured with a threshold is touched
+    Touch,
+}
+
+impl TryFrom<&Kind> for WakeSourceConfig {
+    type Error = AttributeError;
+    fn try_from(value: &Kind) -> Result<Self, Self::Error> {
+        let wake_type: &str = value
+            .get("type")?
+            .ok_or_else(|| AttributeError::KeyNotFound("type".to_string()))?
+            .try_into()?;
+        match wake_type {
+            "ext0" => {
+                let pin = value
+                    .get("pin")?
+                    .ok_or_else(|| AttributeError::KeyNotFound("pin".to_string()))?
+                    .try_into()?;
+                let level = match value.get("level")? {
+                    Some(level) => TryInto::<i32>::try_into(level)? != 0,
+                    None => true,
+                };
+                Ok(WakeSourceConfig::Ext0 { pin, level })
+            }
+            "ext1" => {
+                let pins = value
+                    .get("pins")?
+                    .ok_or_else(|| AttributeError::KeyNotFound("pins".to_string()))?
+                    .try_into()?;
+                let mode = match value.get("mode")? {
+                    Some(mode) => match TryInto::<&str>::try_into(mode)? {
+                        "any_high" => Ext1Mode::AnyHigh,
+                        "all_low" => Ext1Mode::AllLow,
+                        _ => return Err(AttributeError::ConversionImpossibleError),
+                    },
+                    None => Ext1Mode::AnyHigh,
+                };
+                Ok(WakeSourceConfig::Ext1 { pins, mode })
+            }
+            "touch" => Ok(WakeSourceConfig::Touch),
+            _ => Err(AttributeError::ConversionImpossibleError),
+        }
+    }
+}
+
+/// Enable the given wake sources for the next deep sleep, the timer wake up is handled
+/// separately from the duration of the request
+pub(crate) fn enable_wake_sources(sources: &[WakeSourceConfig]) -> Result<(), BoardError> {
+    for source in sources {
+        match source {
+            WakeSourceConfig::Ext0 { pin, level } => {
+                esp!(unsafe { esp_sleep_enable_ext0_wakeup(*pin, *level as i32) }).map_err(
+                    |_| BoardError::GpioPinError(*pin as u32, "cannot be used as a wake source"),
+                )?;
+            }
+            WakeSourceConfig::Ext1 { pins, mode } => {
+                let mask = pins.iter().fold(0_u64, |mask, pin| mask | (1 << pin));
+                let mode = match mode {
+                    Ext1Mode::AnyHigh => esp_sleep_ext1_wakeup_mode_t_ESP_EXT1_WAKEUP_ANY_HIGH,
+                    Ext1Mode::AllLow => esp_sleep_ext1_wakeup_mode_t_ESP_EXT1_WAKEUP_ALL_LOW,
+                };
+                esp!(unsafe { esp_sleep_enable_ext1_wakeup(mask, mode) }).map_err(|_| {
+                    BoardError::BoardUnsupportedArgument("ext1 pins must be RTC GPIOs")
+                })?;
+            }
+            WakeSourceConfig::Touch => {
+                esp!(unsafe { esp_sleep_enable_touchpad_wakeup() }).map_err(|_| {
+                    BoardError::BoardUnsupportedArgument("touch pad wake up unavailable")
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Enter light sleep for at most `duration`, waking up earlier on WiFi activity
+pub fn light_sleep(duration: Duration) -> Result<(), BoardError> {
+    esp!(unsafe { esp_sleep_enable_timer_wakeup(duration.as_micros() as u64) })
+        .map_err(|_| BoardError::BoardUnsupportedArgument("duration too long"))?;
+    esp!(unsafe { esp_sleep_enable_wifi_wakeup() })
+        .map_err(|_| BoardError::BoardUnsupportedArgument("wifi wake up unavailable"))?;
+    let res = esp!(unsafe { esp_light_sleep_start() })
+        .map_err(|_| BoardError::BoardUnsupportedArgument("light sleep rejected"));
+    // the timer must not carry over to a later deep sleep requested without a duration
+    unsafe { esp_sleep_disable_wakeup_source(esp_sleep_source_t_ESP_SLEEP_WAKEUP_TIMER) };
+    res
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WakeReason {
+    /// Power on, reset or any cause that isn't a wake up from sleep
+    PowerOn,
+    Timer,
+    Ext0,
+    Ext1,
+    Touch,
+    Other,
+}
+
+impl WakeReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PowerOn => "power_on",
+            Self::Timer => "timer",
+            Self::Ext0 => "ext0",
+            Self::Ext1 => "ext1",
+            Self::Touch => "touch",
+            Self::Other => "other",
+        }
+    }
+}
+
+#[allow(non_upper_case_globals)]
+pub fn wake_reason() -> WakeReason {
+    match unsafe { esp_sleep_get_wakeup_cause() } {
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_TIMER => WakeReason::Timer,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT0 => WakeReason::Ext0,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT1 => WakeReason::Ext1,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_TOUCHPAD => WakeReason::Touch,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_ULP
+        | esp_sleep_source_t_ESP_SLEEP_WAKEUP_GPIO
+        | esp_sleep_source_t_ESP_SLEEP_WAKEUP_UART => WakeReason::Other,
+        _ => WakeReason::PowerOn,
+    }
+}
+
+// State kept in RTC slow memory, it is initialized on power on and survives deep sleep
+#[repr(C)]
+struct RtcState {
+    boot_count: u32,
+    last_sleep_ms: u64,
+}
+
+#[link_section = ".rtc.data"]
+static mut RTC_STATE: RtcState = RtcState {
+    boot_count: 0,
+    last_sleep_ms: 0,
+};
+
+/// Information about the current boot, recorded once by [record_boot]
+#[derive(Clone, Copy, Debug)]
+pub struct BootInfo {
+    pub boot_count: u32,
+    pub wake_reason: WakeReason,
+    /// Duration of the deep sleep that was requested before this boot, if woken from one
+    pub last_sleep_ms: Option<u64>,
+}
+
+static BOOT_INFO: std::sync::OnceLock<BootInfo> = std::sync::OnceLock::new();
+
+/// Increment the boot count kept in RTC memory and log why the board woke up. Subsequent
+/// calls return the information of the first call.
+pub fn record_boot() -> BootInfo {
+    *BOOT_INFO.get_or_init(|| {
+        let wake_reason = wake_reason();
+        let (boot_count, last_sleep_ms) = unsafe {
+            if wake_reason == WakeReason::PowerOn {
+                RTC_STATE.last_sleep_ms = 0;
+            }
+            RTC_STATE.boot_count = RTC_STATE.boot_count.wrapping_add(1);
+            (RTC_STATE.boot_count, RTC_STATE.last_sleep_ms)
+        };
+        let info = BootInfo {
+            boot_count,
+            wake_reason,
+            last_sleep_ms: (wake_reason != WakeReason::PowerOn).then_some(last_sleep_ms),
+        };
+        log::info!(
+            "boot #{} (wake reason: {})",
+            info.boot_count,
+            info.wake_reason.as_str()
+        );
+        info
+    })
+}
+
+/// Persist the state that should survive the upcoming deep sleep
+pub(crate) fn prepare_for_deep_sleep(sleep_ms: Option<u64>) {
+    // make sure the boot of the current session was counted
+    let _ = record_boot();
+    unsafe {
+        RTC_STATE.last_sleep_ms = sleep_ms.unwrap_or(0);
+    }
+}
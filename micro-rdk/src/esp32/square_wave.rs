@@ -0,0 +1,388 @@
+//! Square waves generated on pins for the PWM frequencies out of reach of the LEDC peripheral
+//! (see [PwmDriver](super::pwm::PwmDriver)). [select_backend] picks the peripheral generating a
+//! frequency:
+//!
+//! - LEDC for whole frequencies from 4Hz to 312.5kHz, it keeps an 8 bits duty resolution
+//! - RMT looping over a single item for the other frequencies from 9.6Hz to 40MHz, the duty
+//!   cycle gets coarser as the frequency grows
+//! - a general purpose timer toggling the pin from its interrupt below, down to 1mHz
+//!
+//! The frequency and duty cycle generated by RMT and the timers are kept within 1% of the
+//! requested ones, combinations they can't reach are rejected rather than approximated.
+
+use super::pwm::Esp32PwmError;
+use crate::esp32::esp_idf_svc::hal::gpio::AnyOutputPin;
+use crate::esp32::esp_idf_svc::hal::rmt::{
+    config::{Loop, TransmitConfig},
+    FixedLengthSignal, PinState, Pulse, PulseTicks, TxRmtDriver, CHANNEL0, CHANNEL1,
+};
+#[cfg(any(esp32, esp32s2, esp32s3))]
+use crate::esp32::esp_idf_svc::hal::rmt::{CHANNEL2, CHANNEL3};
+#[cfg(esp32)]
+use crate::esp32::esp_idf_svc::hal::rmt::{CHANNEL4, CHANNEL5, CHANNEL6, CHANNEL7};
+use crate::esp32::esp_idf_svc::hal::timer::{
+    config::Config as TimerConfig, TimerDriver, TIMER00, TIMER10,
+};
+#[cfg(any(esp32, esp32s2, esp32s3))]
+use crate::esp32::esp_idf_svc::hal::timer::{TIMER01, TIMER11};
+use crate::esp32::esp_idf_svc::sys::{
+    esp, gpio_mode_t_GPIO_MODE_INPUT_OUTPUT, gpio_set_direction, gpio_set_level,
+    timer_group_set_alarm_value_in_isr, timer_group_t, timer_idx_t, EspError,
+    SOC_RMT_TX_CANDIDATES_PER_GROUP, SOC_TIMER_GROUPS, SOC_TIMER_GROUP_TIMERS_PER_GROUP,
+};
+use std::sync::Mutex;
+
+// LEDC timers run with an 8 bits duty resolution off the 80MHz APB clock, or the 1MHz REF_TICK
+// clock for the lowest frequencies
+const LEDC_MIN_FREQUENCY_HZ: f64 = 4.0;
+const LEDC_MAX_FREQUENCY_HZ: f64 = 312_500.0;
+
+const RMT_CLOCK_HZ: f64 = 80_000_000.0;
+const RMT_MAX_CLOCK_DIVIDER: f64 = 255.0;
+// the high and low levels of a period share an RMT item, each level holds at most 32767 ticks
+const RMT_MAX_PERIOD_TICKS: f64 = 32767.0;
+const RMT_MIN_FREQUENCY_HZ: f64 = RMT_CLOCK_HZ / (RMT_MAX_CLOCK_DIVIDER * RMT_MAX_PERIOD_TICKS);
+// a period needs a tick at each level
+const RMT_MAX_FREQUENCY_HZ: f64 = RMT_CLOCK_HZ / 2.0;
+// channels able to transmit come first, the ESP32-S3 and ESP32-C3 keep the others for receiving
+const RMT_CHANNEL_COUNT: u8 = SOC_RMT_TX_CANDIDATES_PER_GROUP as u8;
+
+// the timers count microseconds
+const GPTIMER_DIVIDER: u32 = 80;
+const GPTIMER_TICK_HZ: f64 = 1_000_000.0;
+const GPTIMER_MIN_FREQUENCY_HZ: f64 = 0.001;
+const GPTIMERS_PER_GROUP: u8 = SOC_TIMER_GROUP_TIMERS_PER_GROUP as u8;
+const GPTIMER_COUNT: u8 = SOC_TIMER_GROUPS as u8 * GPTIMERS_PER_GROUP;
+
+// largest error allowed on the frequency (relative) and the duty cycle of a wave
+const MAX_ERROR: f64 = 0.01;
+
+static RMT_CHANNELS_IN_USE: Mutex<u8> = Mutex::new(0);
+static GPTIMERS_IN_USE: Mutex<u8> = Mutex::new(0);
+
+/// Peripheral generating the PWM signal of a pin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PwmBackend {
+    Ledc,
+    Rmt,
+    GpTimer,
+}
+
+/// Picks the backend generating a `frequency_hz` wave at `duty_pct`, fails with the reason when
+/// no backend can
+pub(crate) fn select_backend(frequency_hz: f64, duty_pct: f64) -> Result<PwmBackend, &'static str> {
+    if frequency_hz.is_nan() || frequency_hz < GPTIMER_MIN_FREQUENCY_HZ {
+        return Err("frequency below 1mHz");
+    }
+    if frequency_hz > RMT_MAX_FREQUENCY_HZ {
+        return Err("frequency above 40MHz");
+    }
+    if frequency_hz.fract() == 0.0
+        && (LEDC_MIN_FREQUENCY_HZ..=LEDC_MAX_FREQUENCY_HZ).contains(&frequency_hz)
+    {
+        return Ok(PwmBackend::Ledc);
+    }
+    let constant = is_constant(duty_pct);
+    if frequency_hz >= RMT_MIN_FREQUENCY_HZ {
+        if !constant {
+            rmt_timing(frequency_hz, duty_pct)?;
+        }
+        return Ok(PwmBackend::Rmt);
+    }
+    if !constant {
+        timer_timing(frequency_hz, duty_pct)?;
+    }
+    Ok(PwmBackend::GpTimer)
+}
+
+// waves at a duty cycle of 0 or 1 are a constant level
+fn is_constant(duty_pct: f64) -> bool {
+    duty_pct <= 0.0 || duty_pct >= 1.0
+}
+
+// ticks of the high level of a `period` ticks wave at `duty_pct`, both levels last a tick at least
+fn high_ticks(period: f64, duty_pct: f64) -> Result<f64, &'static str> {
+    let high = (period * duty_pct).round();
+    if high < 1.0 || high > period - 1.0 || (high / period - duty_pct).abs() > MAX_ERROR {
+        return Err("duty cycle too fine for the frequency");
+    }
+    Ok(high)
+}
+
+// clock divider, high ticks and low ticks of the RMT item generating a wave
+fn rmt_timing(frequency_hz: f64, duty_pct: f64) -> Result<(u8, u16, u16), &'static str> {
+    // the smallest divider gives the finest duty cycle
+    let divider = (RMT_CLOCK_HZ / (frequency_hz * RMT_MAX_PERIOD_TICKS))
+        .ceil()
+        .max(1.0);
+    if divider > RMT_MAX_CLOCK_DIVIDER {
+        return Err("frequency too low for the RMT peripheral");
+    }
+    let tick_hz = RMT_CLOCK_HZ / divider;
+    let period = (tick_hz / frequency_hz).round();
+    if period < 2.0 || (tick_hz / period - frequency_hz).abs() > frequency_hz * MAX_ERROR {
+        return Err("frequency not reachable from the RMT clock");
+    }
+    let high = high_ticks(period, duty_pct)?;
+    Ok((divider as u8, high as u16, (period - high) as u16))
+}
+
+// high and low ticks of a wave generated by a general purpose timer
+fn timer_timing(frequency_hz: f64, duty_pct: f64) -> Result<(u64, u64), &'static str> {
+    let period = (GPTIMER_TICK_HZ / frequency_hz).round();
+    let high = high_ticks(period, duty_pct)?;
+    Ok((high as u64, (period - high) as u64))
+}
+
+fn allocate(in_use: &Mutex<u8>, count: u8) -> Option<u8> {
+    let mut in_use = in_use.lock().unwrap();
+    let free = (0..count).find(|i| *in_use & (1 << i) == 0)?;
+    *in_use |= 1 << free;
+    Some(free)
+}
+
+fn release(in_use: &Mutex<u8>, idx: u8) {
+    *in_use.lock().unwrap() &= !(1 << idx);
+}
+
+/// An RMT channel taken from the ones shared by the square waves and the pulse trains of
+/// [Esp32GPIOPin::pulse](super::pin::Esp32GPIOPin::pulse), it's given back once dropped
+pub(crate) struct RmtChannel(u8);
+
+impl RmtChannel {
+    pub(crate) fn take() -> Result<Self, Esp32PwmError> {
+        allocate(&RMT_CHANNELS_IN_USE, RMT_CHANNEL_COUNT)
+            .map(Self)
+            .ok_or(Esp32PwmError::NoRmtChannelsAvailable)
+    }
+
+    /// Transmit driver emitting on `pin` through the channel
+    pub(crate) fn tx_driver(
+        &self,
+        pin: i32,
+        config: &TransmitConfig,
+    ) -> Result<TxRmtDriver<'static>, EspError> {
+        let pin = unsafe { AnyOutputPin::new(pin) };
+        match self.0 {
+            0 => TxRmtDriver::new(unsafe { CHANNEL0::new() }, pin, config),
+            1 => TxRmtDriver::new(unsafe { CHANNEL1::new() }, pin, config),
+            #[cfg(any(esp32, esp32s2, esp32s3))]
+            2 => TxRmtDriver::new(unsafe { CHANNEL2::new() }, pin, config),
+            #[cfg(any(esp32, esp32s2, esp32s3))]
+            3 => TxRmtDriver::new(unsafe { CHANNEL3::new() }, pin, config),
+            #[cfg(esp32)]
+            4 => TxRmtDriver::new(unsafe { CHANNEL4::new() }, pin, config),
+            #[cfg(esp32)]
+            5 => TxRmtDriver::new(unsafe { CHANNEL5::new() }, pin, config),
+            #[cfg(esp32)]
+            6 => TxRmtDriver::new(unsafe { CHANNEL6::new() }, pin, config),
+            #[cfg(esp32)]
+            7 => TxRmtDriver::new(unsafe { CHANNEL7::new() }, pin, config),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Drop for RmtChannel {
+    fn drop(&mut self) {
+        release(&RMT_CHANNELS_IN_USE, self.0);
+    }
+}
+
+/// A square wave generated on a pin by RMT or a general purpose timer, the pin is left low once
+/// the wave is dropped
+pub(crate) struct SquareWave {
+    pin: i32,
+    frequency_hz: f64,
+    duty_pct: f64,
+    generator: Generator,
+}
+
+enum Generator {
+    // no peripheral is needed to hold a constant level
+    Level,
+    Rmt(RmtWave),
+    GpTimer(TimerWave),
+}
+
+impl SquareWave {
+    pub(crate) fn rmt(pin: i32, frequency_hz: f64, duty_pct: f64) -> Result<Self, Esp32PwmError> {
+        let generator = if is_constant(duty_pct) {
+            hold_level(pin, duty_pct)?
+        } else {
+            Generator::Rmt(RmtWave::new(pin, frequency_hz, duty_pct)?)
+        };
+        Ok(Self {
+            pin,
+            frequency_hz,
+            duty_pct,
+            generator,
+        })
+    }
+
+    pub(crate) fn gptimer(
+        pin: i32,
+        frequency_hz: f64,
+        duty_pct: f64,
+    ) -> Result<Self, Esp32PwmError> {
+        let generator = if is_constant(duty_pct) {
+            hold_level(pin, duty_pct)?
+        } else {
+            Generator::GpTimer(TimerWave::new(pin, frequency_hz, duty_pct)?)
+        };
+        Ok(Self {
+            pin,
+            frequency_hz,
+            duty_pct,
+            generator,
+        })
+    }
+
+    pub(crate) fn frequency_hz(&self) -> f64 {
+        self.frequency_hz
+    }
+
+    pub(crate) fn duty_pct(&self) -> f64 {
+        self.duty_pct
+    }
+}
+
+impl Drop for SquareWave {
+    fn drop(&mut self) {
+        // the generators leave the pin low themselves once stopped
+        if let Generator::Level = self.generator {
+            unsafe { gpio_set_level(self.pin, 0) };
+        }
+    }
+}
+
+fn hold_level(pin: i32, duty_pct: f64) -> Result<Generator, Esp32PwmError> {
+    esp!(unsafe { gpio_set_level(pin, (duty_pct >= 1.0) as u32) })?;
+    Ok(Generator::Level)
+}
+
+struct RmtWave {
+    pin: i32,
+    driver: TxRmtDriver<'static>,
+    // dropped after the driver stopped
+    _channel: RmtChannel,
+}
+
+impl RmtWave {
+    fn new(pin: i32, frequency_hz: f64, duty_pct: f64) -> Result<Self, Esp32PwmError> {
+        let (divider, high, low) =
+            rmt_timing(frequency_hz, duty_pct).map_err(Esp32PwmError::WaveUnsupported)?;
+        let channel = RmtChannel::take()?;
+        let driver = Self::start(&channel, pin, divider, high, low)?;
+        Ok(Self {
+            pin,
+            driver,
+            _channel: channel,
+        })
+    }
+
+    fn start(
+        channel: &RmtChannel,
+        pin: i32,
+        divider: u8,
+        high: u16,
+        low: u16,
+    ) -> Result<TxRmtDriver<'static>, EspError> {
+        let config = TransmitConfig::new()
+            .clock_divider(divider)
+            .looping(Loop::Endless);
+        let mut driver = channel.tx_driver(pin, &config)?;
+        let mut signal = FixedLengthSignal::<1>::new();
+        signal.set(
+            0,
+            &(
+                Pulse::new(PinState::High, PulseTicks::new(high)?),
+                Pulse::new(PinState::Low, PulseTicks::new(low)?),
+            ),
+        )?;
+        driver.start(signal)?;
+        Ok(driver)
+    }
+}
+
+impl Drop for RmtWave {
+    fn drop(&mut self) {
+        let _ = self.driver.stop();
+        // the RMT channel took over the output of the pin, setting its direction routes it back
+        // to the GPIO driver
+        unsafe {
+            gpio_set_direction(self.pin, gpio_mode_t_GPIO_MODE_INPUT_OUTPUT);
+            gpio_set_level(self.pin, 0);
+        }
+    }
+}
+
+struct TimerWave {
+    pin: i32,
+    timer: u8,
+    driver: TimerDriver<'static>,
+}
+
+impl TimerWave {
+    fn new(pin: i32, frequency_hz: f64, duty_pct: f64) -> Result<Self, Esp32PwmError> {
+        let (high, low) =
+            timer_timing(frequency_hz, duty_pct).map_err(Esp32PwmError::WaveUnsupported)?;
+        let timer =
+            allocate(&GPTIMERS_IN_USE, GPTIMER_COUNT).ok_or(Esp32PwmError::NoGpTimersAvailable)?;
+        match Self::start(pin, timer, high, low) {
+            Ok(driver) => Ok(Self { pin, timer, driver }),
+            Err(err) => {
+                release(&GPTIMERS_IN_USE, timer);
+                Err(err.into())
+            }
+        }
+    }
+
+    fn group_and_index(timer: u8) -> (timer_group_t, timer_idx_t) {
+        (
+            (timer / GPTIMERS_PER_GROUP) as timer_group_t,
+            (timer % GPTIMERS_PER_GROUP) as timer_idx_t,
+        )
+    }
+
+    fn start(pin: i32, timer: u8, high: u64, low: u64) -> Result<TimerDriver<'static>, EspError> {
+        let config = TimerConfig::new()
+            .divider(GPTIMER_DIVIDER)
+            .auto_reload(true);
+        let (group, index) = Self::group_and_index(timer);
+        let mut driver = match (group, index) {
+            (0, 0) => TimerDriver::new(unsafe { TIMER00::new() }, &config)?,
+            #[cfg(any(esp32, esp32s2, esp32s3))]
+            (0, 1) => TimerDriver::new(unsafe { TIMER01::new() }, &config)?,
+            (1, 0) => TimerDriver::new(unsafe { TIMER10::new() }, &config)?,
+            #[cfg(any(esp32, esp32s2, esp32s3))]
+            (1, 1) => TimerDriver::new(unsafe { TIMER11::new() }, &config)?,
+            _ => unreachable!(),
+        };
+        // the wave starts high, every alarm toggles the pin and sets the alarm ending the level
+        // that starts, the counter being reloaded to 0 by the alarm
+        esp!(unsafe { gpio_set_level(pin, 1) })?;
+        let mut is_high = true;
+        unsafe {
+            driver.subscribe(move || {
+                is_high = !is_high;
+                gpio_set_level(pin, is_high as u32);
+                timer_group_set_alarm_value_in_isr(group, index, if is_high { high } else { low });
+            })?;
+        }
+        driver.set_counter(0)?;
+        driver.set_alarm(high)?;
+        driver.enable_interrupt()?;
+        driver.enable_alarm(true)?;
+        driver.enable(true)?;
+        Ok(driver)
+    }
+}
+
+impl Drop for TimerWave {
+    fn drop(&mut self) {
+        let _ = self.driver.enable(false);
+        release(&GPTIMERS_IN_USE, self.timer);
+        unsafe { gpio_set_level(self.pin, 0) };
+    }
+}
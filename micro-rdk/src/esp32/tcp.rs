@@ -97,15 +97,19 @@ impl Debug for Esp32TLSConnector {
 impl Http2Connector for Esp32TLSConnector {
     type Stream = Esp32Stream;
     async fn accept(&mut self) -> std::io::Result<Self::Stream> {
-        self.inner.as_ref().unwrap().set_nonblocking(true).unwrap();
-        match &mut self.tls {
+        match &self.tls {
+            // the handshake runs on its own task, the serve loop keeps accepting meanwhile
             Some(tls) => tls
-                .open_ssl_context(Some(Async::new(self.inner.take().unwrap()).unwrap()))
+                .accept(self.inner.take().unwrap())
+                .await
                 .map(|s| Esp32Stream::TLSStream(Box::new(s)))
                 .map_err(|e| std::io::Error::new(io::ErrorKind::Other, e)),
-            None => Ok(Esp32Stream::LocalPlain(
-                Async::new(self.inner.take().unwrap()).unwrap(),
-            )),
+            None => {
+                self.inner.as_ref().unwrap().set_nonblocking(true).unwrap();
+                Ok(Esp32Stream::LocalPlain(
+                    Async::new(self.inner.take().unwrap()).unwrap(),
+                ))
+            }
         }
     }
 }
@@ -1,3 +1,6 @@
+#[cfg(not(esp_idf_freertos_unicore))]
+use crate::esp32::esp_idf_svc::hal::cpu::Core;
+use crate::esp32::esp_idf_svc::hal::task::thread::ThreadSpawnConfiguration;
 use crate::esp32::esp_idf_svc::sys::{
     esp_tls_cfg, esp_tls_cfg_server, esp_tls_conn_destroy, esp_tls_conn_new_sync,
     esp_tls_conn_state_ESP_TLS_CONNECTING as ESP_TLS_CONNECTING,
@@ -7,18 +10,30 @@ use crate::esp32::esp_idf_svc::sys::{
     esp_tls_conn_state_ESP_TLS_INIT as ESP_TLS_INIT, esp_tls_init, esp_tls_server_session_create,
     esp_tls_t, EspError, ESP_TLS_ERR_SSL_WANT_READ, ESP_TLS_ERR_SSL_WANT_WRITE,
 };
+#[cfg(esp_idf_esp_tls_server_session_tickets)]
+use crate::esp32::esp_idf_svc::sys::{
+    esp_tls_cfg_server_session_tickets_free, esp_tls_cfg_server_session_tickets_init,
+};
 use async_io::Async;
 use either::Either;
+#[cfg(esp_idf_mbedtls_certificate_bundle)]
+use esp_idf_svc::sys::esp_crt_bundle_attach;
 use esp_idf_svc::sys::esp_tls_get_conn_sockfd;
 use futures_lite::{ready, AsyncRead, AsyncWrite};
 
 use std::{
+    ffi::CString,
     fmt::Debug,
     io::{Read, Write},
     mem::ManuallyDrop,
     net::TcpStream,
     os::{fd::FromRawFd, raw::c_char, unix::prelude::AsRawFd},
+    sync::{
+        mpsc::{channel, Sender},
+        Arc, Mutex,
+    },
     task::Poll,
+    time::Duration,
 };
 
 use crate::common::conn::errors::ServerError;
@@ -26,6 +41,13 @@ use crate::common::conn::server::TlsClientConnector;
 
 use super::tcp::Esp32Stream;
 
+/// The handshake task runs at the lowest priority a thread can have, the same as the main task,
+/// so handshakes don't preempt the connections already established
+const HANDSHAKE_TASK_PRIORITY: u8 = 1;
+const HANDSHAKE_TASK_STACK_SIZE: usize = 10 * 1024;
+/// A client that doesn't complete its handshake within this time is disconnected
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 unsafe impl Sync for Esp32TLS {}
 unsafe impl Send for Esp32TLS {}
 
@@ -34,7 +56,123 @@ unsafe impl Send for Esp32TLS {}
 pub struct Esp32TLS {
     #[allow(dead_code)]
     alpn_ptr: Vec<*const c_char>,
-    tls_cfg: Either<Box<esp_tls_cfg_server>, Box<esp_tls_cfg>>,
+    tls_cfg: Either<Arc<ServerTlsConfig>, Box<esp_tls_cfg>>,
+}
+
+/// Configuration of the server side of TLS, shared by the connections it accepts and the
+/// handshake task. When CONFIG_ESP_TLS_SERVER_SESSION_TICKETS is enabled it holds the keys of
+/// the session tickets clients resume their session with, skipping the costly full handshake
+/// when they reconnect.
+struct ServerTlsConfig {
+    cfg: Box<esp_tls_cfg_server>,
+    // the configuration points to the protocols list
+    _alpn_ptr: Vec<*const c_char>,
+}
+
+// the configuration isn't modified once created
+unsafe impl Sync for ServerTlsConfig {}
+unsafe impl Send for ServerTlsConfig {}
+
+impl ServerTlsConfig {
+    fn as_ptr(&self) -> *mut esp_tls_cfg_server {
+        &*self.cfg as *const esp_tls_cfg_server as *mut esp_tls_cfg_server
+    }
+}
+
+impl Drop for ServerTlsConfig {
+    fn drop(&mut self) {
+        #[cfg(esp_idf_esp_tls_server_session_tickets)]
+        unsafe {
+            esp_tls_cfg_server_session_tickets_free(&mut *self.cfg)
+        };
+    }
+}
+
+/// TLS handshake performed by the handshake task, the name resolution and handshake of a
+/// client connection block as much as the ones of an accepted connection
+enum HandshakeJob {
+    Accept {
+        socket: TcpStream,
+        config: Arc<ServerTlsConfig>,
+        reply: async_channel::Sender<std::io::Result<Esp32TLSStream>>,
+    },
+    Connect {
+        hostname: CString,
+        port: u16,
+        reply: async_channel::Sender<std::io::Result<Esp32TLSStream>>,
+    },
+}
+
+impl HandshakeJob {
+    fn run(self) {
+        let (stream, reply) = match self {
+            Self::Accept {
+                socket,
+                config,
+                reply,
+            } => (Esp32TLSStream::handshake(socket, &config), reply),
+            Self::Connect {
+                hostname,
+                port,
+                reply,
+            } => {
+                let tls_cfg = esp_tls_cfg {
+                    alpn_protos: std::ptr::null_mut(),
+                    #[cfg(esp_idf_mbedtls_certificate_bundle)]
+                    crt_bundle_attach: Some(esp_crt_bundle_attach),
+                    timeout_ms: HANDSHAKE_TIMEOUT.as_millis() as i32,
+                    ..Default::default()
+                };
+                let stream =
+                    Esp32TLSStream::connect(hostname.as_bytes_with_nul(), port as i32, &tls_cfg);
+                (stream, reply)
+            }
+        };
+        // when nobody waits for the connection anymore dropping it closes it
+        let _ = reply.send_blocking(stream);
+    }
+}
+
+static HANDSHAKE_TASK: Mutex<Option<Sender<HandshakeJob>>> = Mutex::new(None);
+
+fn spawn_handshake_task() -> std::io::Result<Sender<HandshakeJob>> {
+    let (tx, rx) = channel::<HandshakeJob>();
+    ThreadSpawnConfiguration {
+        stack_size: HANDSHAKE_TASK_STACK_SIZE,
+        priority: HANDSHAKE_TASK_PRIORITY,
+        // away from the core the main task runs on
+        #[cfg(not(esp_idf_freertos_unicore))]
+        pin_to_core: Some(Core::Core0),
+        ..Default::default()
+    }
+    .set()
+    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    let spawned = std::thread::Builder::new()
+        .name("tls_handshake".to_owned())
+        .stack_size(HANDSHAKE_TASK_STACK_SIZE)
+        .spawn(move || {
+            for job in rx {
+                job.run();
+            }
+        });
+    // threads spawned later get the default configuration
+    ThreadSpawnConfiguration::default()
+        .set()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    spawned?;
+    Ok(tx)
+}
+
+fn submit_handshake(job: HandshakeJob) -> std::io::Result<()> {
+    let mut task = HANDSHAKE_TASK.lock().unwrap();
+    if task.is_none() {
+        let _ = task.insert(spawn_handshake_task()?);
+    }
+    task.as_ref().unwrap().send(job).map_err(|_| {
+        // the task will be spawned again for the next connection
+        let _ = task.take();
+        std::io::Error::new(std::io::ErrorKind::Other, "tls handshake task stopped")
+    })
 }
 
 impl TlsClientConnector for Esp32TLS {
@@ -143,7 +281,8 @@ impl Esp32TLS {
     /// Creates a TLS object ready to accept connection or connect to a server
     pub fn new_server(cfg: &Esp32TLSServerConfig) -> Self {
         let mut alpn_ptr: Vec<_> = vec![ALPN_PROTOCOLS.as_ptr() as *const i8, std::ptr::null()];
-        let tls_cfg_srv = Box::new(esp_tls_cfg_server {
+        #[allow(unused_mut)]
+        let mut tls_cfg_srv = Box::new(esp_tls_cfg_server {
             alpn_protos: alpn_ptr.as_mut_ptr(),
             __bindgen_anon_1: crate::esp32::esp_idf_svc::sys::esp_tls_cfg_server__bindgen_ty_1 {
                 // This is the root LE certificate in the DER format
@@ -167,11 +306,26 @@ impl Esp32TLS {
             },
             serverkey_password: std::ptr::null(),
             serverkey_password_len: 0_u32,
+            #[cfg(esp_idf_esp_tls_server_session_tickets)]
+            ticket_ctx: std::ptr::null_mut(),
         });
 
+        #[cfg(esp_idf_esp_tls_server_session_tickets)]
+        if let Some(err) =
+            EspError::from(unsafe { esp_tls_cfg_server_session_tickets_init(&mut *tls_cfg_srv) })
+        {
+            log::warn!(
+                "couldn't set up tls session tickets, clients won't resume sessions: {}",
+                err
+            );
+        }
+
         Self {
-            alpn_ptr,
-            tls_cfg: Either::Left(tls_cfg_srv),
+            alpn_ptr: alpn_ptr.clone(),
+            tls_cfg: Either::Left(Arc::new(ServerTlsConfig {
+                cfg: tls_cfg_srv,
+                _alpn_ptr: alpn_ptr,
+            })),
         }
     }
 
@@ -182,6 +336,43 @@ impl Esp32TLS {
     ) -> Result<Esp32TLSStream, std::io::Error> {
         Esp32TLSStream::new(socket, &mut self.tls_cfg)
     }
+
+    /// Performs the server side handshake of an accepted connection on the handshake task, a
+    /// slow or stalled client doesn't hold up the executor meanwhile
+    pub async fn accept(&self, socket: TcpStream) -> Result<Esp32TLSStream, std::io::Error> {
+        let Either::Left(config) = &self.tls_cfg else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "tls client configuration can't accept connections",
+            ));
+        };
+        let (reply, stream) = async_channel::bounded(1);
+        submit_handshake(HandshakeJob::Accept {
+            socket,
+            config: config.clone(),
+            reply,
+        })?;
+        stream.recv().await.map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::Other, "tls handshake task stopped")
+        })?
+    }
+
+    /// Opens a TLS connection to `host` for protocols other than HTTP/2, the server is
+    /// verified against the certificate bundle of ESP-IDF (CONFIG_MBEDTLS_CERTIFICATE_BUNDLE).
+    /// The name is resolved and the handshake performed on the handshake task.
+    pub async fn connect_to_host(host: &str, port: u16) -> Result<Esp32TLSStream, std::io::Error> {
+        let hostname = CString::new(host)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let (reply, stream) = async_channel::bounded(1);
+        submit_handshake(HandshakeJob::Connect {
+            hostname,
+            port,
+            reply,
+        })?;
+        stream.recv().await.map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::Other, "tls handshake task stopped")
+        })?
+    }
 }
 
 /// Esp32TlsStream represents a properly established TLS connection to a server or a client. It can be use bye Esp32TCPStream since it
@@ -190,8 +381,12 @@ impl Esp32TLSStream {
     /// based on a role and a configuration, attempt the setup an SSL context
     fn new(
         socket: Option<Async<TcpStream>>,
-        tls_cfg: &mut Either<Box<esp_tls_cfg_server>, Box<esp_tls_cfg>>,
+        tls_cfg: &mut Either<Arc<ServerTlsConfig>, Box<esp_tls_cfg>>,
     ) -> Result<Self, std::io::Error> {
+        let tls_cfg = match tls_cfg {
+            Either::Left(tls_cfg) => tls_cfg.as_ptr(),
+            Either::Right(tls_cfg) => return Self::connect(APP_VIAM_HOSTNAME, 443, tls_cfg),
+        };
         let p = unsafe { esp_tls_init() };
         if p.is_null() {
             return Err(std::io::Error::new(
@@ -200,60 +395,100 @@ impl Esp32TLSStream {
             ));
         }
         let tls_context = ManuallyDrop::new(p);
-        match tls_cfg {
-            Either::Left(tls_cfg) => {
-                let fd = socket.as_ref().unwrap().as_raw_fd();
-                unsafe {
-                    if let Some(err) = EspError::from(esp_tls_server_session_create(
-                        &mut **tls_cfg,
-                        fd,
-                        *tls_context,
-                    )) {
-                        esp_tls_conn_destroy(*tls_context);
-                        Err(std::io::Error::new(std::io::ErrorKind::Other, err))
-                    } else {
-                        Ok(Self {
-                            tls_context,
-                            socket: socket.unwrap(),
-                        })
-                    }
-                }
+        let fd = socket.as_ref().unwrap().as_raw_fd();
+        unsafe {
+            if let Some(err) =
+                EspError::from(esp_tls_server_session_create(tls_cfg, fd, *tls_context))
+            {
+                esp_tls_conn_destroy(*tls_context);
+                Err(std::io::Error::new(std::io::ErrorKind::Other, err))
+            } else {
+                Ok(Self {
+                    tls_context,
+                    socket: socket.unwrap(),
+                })
             }
-            Either::Right(tls_cfg) => {
-                match unsafe {
-                    esp_tls_conn_new_sync(
-                        APP_VIAM_HOSTNAME.as_ptr() as *const i8,
-                        APP_VIAM_HOSTNAME.len() as i32,
-                        443, // HTTPS port
-                        &**tls_cfg,
-                        *tls_context,
-                    )
-                } {
-                    -1 => Err(std::io::Error::new(
-                        std::io::ErrorKind::ConnectionRefused,
-                        "app.viam.com",
-                    )),
-                    1 => {
-                        let socket: Async<TcpStream> = unsafe {
-                            let mut fd: i32 = 0;
-                            esp_idf_svc::sys::esp!(esp_tls_get_conn_sockfd(*tls_context, &mut fd))
-                                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-                            TcpStream::from_raw_fd(fd).try_into().unwrap()
-                        };
-                        Ok(Self {
-                            tls_context,
-                            socket,
-                        })
-                    }
-                    0 => Err(std::io::Error::new(
-                        std::io::ErrorKind::NotConnected,
-                        "app.viam.com",
-                    )),
-                    _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "unexpected")),
-                }
+        }
+    }
+
+    /// server side handshake on a blocking socket, run by the handshake task
+    fn handshake(socket: TcpStream, config: &ServerTlsConfig) -> Result<Self, std::io::Error> {
+        socket.set_nonblocking(false)?;
+        socket.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        socket.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let p = unsafe { esp_tls_init() };
+        if p.is_null() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "couldn't allocate tls context",
+            ));
+        }
+        let tls_context = ManuallyDrop::new(p);
+        if let Some(err) = EspError::from(unsafe {
+            esp_tls_server_session_create(config.as_ptr(), socket.as_raw_fd(), *tls_context)
+        }) {
+            unsafe { esp_tls_conn_destroy(*tls_context) };
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, err));
+        }
+        let socket = socket
+            .set_read_timeout(None)
+            .and_then(|_| socket.set_write_timeout(None))
+            .and_then(|_| Async::new(socket));
+        match socket {
+            Ok(socket) => Ok(Self {
+                tls_context,
+                socket,
+            }),
+            Err(err) => {
+                unsafe { esp_tls_conn_destroy(*tls_context) };
+                Err(err)
             }
         }
     }
+
+    /// connect to `host` (a NUL terminated hostname) as a client
+    fn connect(host: &[u8], port: i32, tls_cfg: &esp_tls_cfg) -> Result<Self, std::io::Error> {
+        let p = unsafe { esp_tls_init() };
+        if p.is_null() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "couldn't allocate tls context",
+            ));
+        }
+        let tls_context = ManuallyDrop::new(p);
+        let hostname = String::from_utf8_lossy(&host[..host.len().saturating_sub(1)]).to_string();
+        match unsafe {
+            esp_tls_conn_new_sync(
+                host.as_ptr() as *const i8,
+                host.len() as i32,
+                port,
+                tls_cfg,
+                *tls_context,
+            )
+        } {
+            -1 => Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                hostname,
+            )),
+            1 => {
+                let socket: Async<TcpStream> = unsafe {
+                    let mut fd: i32 = 0;
+                    esp_idf_svc::sys::esp!(esp_tls_get_conn_sockfd(*tls_context, &mut fd))
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                    TcpStream::from_raw_fd(fd).try_into().unwrap()
+                };
+                Ok(Self {
+                    tls_context,
+                    socket,
+                })
+            }
+            0 => Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                hostname,
+            )),
+            _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "unexpected")),
+        }
+    }
     fn inner_read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
         let read_fn = match unsafe { self.tls_context.read_unaligned().read } {
             Some(f) => f,
@@ -309,6 +544,10 @@ impl Esp32TLSStream {
     }
 }
 
+// the connection is used by a single owner at a time, the handshake task hands it over once
+// established
+unsafe impl Send for Esp32TLSStream {}
+
 impl Drop for Esp32TLSStream {
     fn drop(&mut self) {
         log::error!("dropping the tls stream");
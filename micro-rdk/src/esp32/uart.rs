@@ -0,0 +1,197 @@
+//! Serial ports of the ESP32, shared by every component configured on the same UART.
+//! Components talking to devices over a serial port take the following attributes:
+//!
+//! ```json
+//! {
+//!     "uart_port": 2,
+//!     "tx_pin": 17,
+//!     "rx_pin": 16,
+//!     "baud_rate": 38400
+//! }
+//! ```
+//!
+//! `uart_port` defaults to 1 (UART0 is the console). The components sharing a port must agree
+//! on its pins and baud rate, the port is released once all of them are dropped. Buses sharing
+//! a single line for both directions (such as RS485 transceivers or Dynamixel servos) set
+//! `direction_pin`, driven high by the port while it transmits.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use crate::common::config::{AttributeError, ConfigType};
+use crate::common::uart::{Uart, UartError, UartType};
+use crate::esp32::esp_idf_svc::sys::{
+    configTICK_RATE_HZ, esp, uart_config_t, uart_driver_delete, uart_driver_install,
+    uart_flush_input, uart_get_buffered_data_len, uart_hw_flowcontrol_t_UART_HW_FLOWCTRL_DISABLE,
+    uart_mode_t_UART_MODE_RS485_HALF_DUPLEX, uart_param_config, uart_parity_t_UART_PARITY_DISABLE,
+    uart_port_t, uart_read_bytes, uart_set_mode, uart_set_pin, uart_stop_bits_t_UART_STOP_BITS_1,
+    uart_word_length_t_UART_DATA_8_BITS, uart_write_bytes, UART_PIN_NO_CHANGE,
+};
+
+const DEFAULT_PORT: i32 = 1;
+const RX_BUFFER_LEN: i32 = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct UartConfig {
+    port: i32,
+    tx_pin: i32,
+    rx_pin: i32,
+    baud_rate: u32,
+    direction_pin: Option<i32>,
+}
+
+// ports in use, a port is reinstalled once every component using it is dropped
+static UARTS: Mutex<Vec<(UartConfig, Weak<Mutex<EspUart>>)>> = Mutex::new(Vec::new());
+
+/// Returns the serial port configured by the `uart_port`, `tx_pin`, `rx_pin` and `baud_rate`
+/// attributes, opening it for the first component using it
+pub fn uart_from_config(cfg: &ConfigType, default_baud_rate: u32) -> Result<UartType, UartError> {
+    let pin = |name: &'static str| {
+        cfg.get_attribute::<i32>(name)
+            .map_err(|_| UartError::ConfigError("missing `tx_pin` or `rx_pin`"))
+    };
+    let optional = |name: &'static str, default: u32| match cfg.get_attribute::<u32>(name) {
+        Ok(value) => Ok(value),
+        Err(AttributeError::KeyNotFound(_)) => Ok(default),
+        Err(_) => Err(UartError::ConfigError("invalid `uart_port` or `baud_rate`")),
+    };
+    let config = UartConfig {
+        port: optional("uart_port", DEFAULT_PORT as u32)? as i32,
+        tx_pin: pin("tx_pin")?,
+        rx_pin: pin("rx_pin")?,
+        baud_rate: optional("baud_rate", default_baud_rate)?,
+        direction_pin: match cfg.get_attribute::<i32>("direction_pin") {
+            Ok(pin) => Some(pin),
+            Err(AttributeError::KeyNotFound(_)) => None,
+            Err(_) => return Err(UartError::ConfigError("invalid `direction_pin`")),
+        },
+    };
+    let mut uarts = UARTS.lock().unwrap();
+    uarts.retain(|(_, uart)| uart.strong_count() > 0);
+    if let Some((opened, uart)) = uarts.iter().find(|(opened, _)| opened.port == config.port) {
+        if *opened != config {
+            return Err(UartError::ConfigError(
+                "the uart is already configured with other pins or baud rate",
+            ));
+        }
+        if let Some(uart) = uart.upgrade() {
+            return Ok(uart);
+        }
+    }
+    let uart = Arc::new(Mutex::new(EspUart::new(config)?));
+    uarts.push((config, Arc::downgrade(&uart)));
+    Ok(uart)
+}
+
+struct EspUart {
+    port: uart_port_t,
+}
+
+impl EspUart {
+    fn new(config: UartConfig) -> Result<Self, UartError> {
+        let port = config.port as uart_port_t;
+        let params = uart_config_t {
+            baud_rate: config.baud_rate as i32,
+            data_bits: uart_word_length_t_UART_DATA_8_BITS,
+            parity: uart_parity_t_UART_PARITY_DISABLE,
+            stop_bits: uart_stop_bits_t_UART_STOP_BITS_1,
+            flow_ctrl: uart_hw_flowcontrol_t_UART_HW_FLOWCTRL_DISABLE,
+            ..Default::default()
+        };
+        unsafe {
+            esp!(uart_driver_install(
+                port,
+                RX_BUFFER_LEN,
+                0,
+                0,
+                std::ptr::null_mut(),
+                0
+            ))
+            .map_err(|err| UartError::DriverError(err.code()))?;
+        }
+        // the driver is released on drop from here on
+        let uart = Self { port };
+        unsafe {
+            esp!(uart_param_config(port, &params))
+                .map_err(|err| UartError::DriverError(err.code()))?;
+            esp!(uart_set_pin(
+                port,
+                config.tx_pin,
+                config.rx_pin,
+                config.direction_pin.unwrap_or(UART_PIN_NO_CHANGE),
+                UART_PIN_NO_CHANGE
+            ))
+            .map_err(|err| UartError::DriverError(err.code()))?;
+            // the driver switches the direction pin (RTS) around each transmission
+            if config.direction_pin.is_some() {
+                esp!(uart_set_mode(port, uart_mode_t_UART_MODE_RS485_HALF_DUPLEX))
+                    .map_err(|err| UartError::DriverError(err.code()))?;
+            }
+        }
+        Ok(uart)
+    }
+}
+
+impl Uart for EspUart {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), UartError> {
+        let written =
+            unsafe { uart_write_bytes(self.port, bytes.as_ptr() as *const _, bytes.len()) };
+        if written < 0 {
+            return Err(UartError::DriverError(written));
+        }
+        Ok(())
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<(), UartError> {
+        let deadline = Instant::now() + timeout;
+        let mut filled = 0;
+        while filled < buf.len() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(UartError::Timeout(timeout));
+            }
+            let ticks = (remaining.as_millis() as u32 * configTICK_RATE_HZ / 1000).max(1);
+            let read = unsafe {
+                uart_read_bytes(
+                    self.port,
+                    buf[filled..].as_mut_ptr() as *mut _,
+                    (buf.len() - filled) as u32,
+                    ticks,
+                )
+            };
+            if read < 0 {
+                return Err(UartError::DriverError(read));
+            }
+            filled += read as usize;
+        }
+        Ok(())
+    }
+
+    fn read_available(&mut self, buf: &mut [u8]) -> Result<usize, UartError> {
+        let mut available: usize = 0;
+        unsafe { esp!(uart_get_buffered_data_len(self.port, &mut available)) }
+            .map_err(|err| UartError::DriverError(err.code()))?;
+        let len = available.min(buf.len());
+        if len == 0 {
+            return Ok(0);
+        }
+        let read = unsafe { uart_read_bytes(self.port, buf.as_mut_ptr() as *mut _, len as u32, 0) };
+        if read < 0 {
+            return Err(UartError::DriverError(read));
+        }
+        Ok(read as usize)
+    }
+
+    fn clear_input(&mut self) -> Result<(), UartError> {
+        unsafe { esp!(uart_flush_input(self.port)) }
+            .map_err(|err| UartError::DriverError(err.code()))
+    }
+}
+
+impl Drop for EspUart {
+    fn drop(&mut self) {
+        if let Err(error) = unsafe { esp!(uart_driver_delete(self.port)) } {
+            log::warn!("failed to delete uart driver {}: {}", self.port, error)
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// Support for VESC speed controllers over CAN, the motor is implemented by
+// `common::vesc_can`, which documents its attributes.
+//
+// Example configuration
+//
+// {
+//   "model": "vesc-can",
+//   "name": "left-wheel",
+//   "type": "motor",
+//   "attributes": {
+//     "tx_pin": 5,
+//     "rx_pin": 4,
+//     "controller_id": 12,
+//     "pole_pairs": 7,
+//     "max_rpm": 3000
+//   },
+// }
+//
+// Configuration details:
+//
+//  - `tx_pin`, `rx_pin` (required) and `bitrate` (optional, defaults to 500000): the CAN bus
+//    the controller is connected to, see `esp32::can`. Every `vesc-can` motor must use the
+//    same bus configuration.
+
+use crate::common::config::ConfigType;
+use crate::common::motor::{MotorError, MotorType};
+use crate::common::registry::{ComponentRegistry, Dependency};
+use crate::common::vesc_can::VescCanMotor;
+
+use super::can::can_bus_from_config;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) {
+    if registry.register_motor("vesc-can", &from_config).is_err() {
+        log::error!("vesc-can model is already registered")
+    }
+}
+
+fn from_config(cfg: ConfigType, _: Vec<Dependency>) -> Result<MotorType, MotorError> {
+    let bus = can_bus_from_config(&cfg)?;
+    VescCanMotor::from_bus_and_config(bus, cfg)
+}
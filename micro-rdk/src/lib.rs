@@ -19,6 +19,8 @@ extern crate scopeguard;
 pub use micro_rdk_macros::DoCommand;
 pub use micro_rdk_macros::MovementSensorReadings;
 pub use micro_rdk_macros::PowerSensorReadings;
+pub use micro_rdk_macros::SensorReadings;
+pub use micro_rdk_macros::Status;
 
 /// gRPC protobuf utilities, auto-generated
 pub mod google {
@@ -97,6 +99,12 @@ pub mod proto {
         }
     }
     pub mod component {
+        pub mod audio_input {
+            pub mod v1 {
+                #![allow(clippy::derive_partial_eq_without_eq)]
+                include!("gen/viam.component.audioinput.v1.rs");
+            }
+        }
         pub mod board {
             pub mod v1 {
                 #![allow(clippy::derive_partial_eq_without_eq)]
@@ -143,6 +151,20 @@ pub mod proto {
             }
         }
 
+        pub mod button {
+            pub mod v1 {
+                #![allow(clippy::derive_partial_eq_without_eq)]
+                include!("gen/viam.component.button.v1.rs");
+            }
+        }
+
+        pub mod switch {
+            pub mod v1 {
+                #![allow(clippy::derive_partial_eq_without_eq)]
+                include!("gen/viam.component.switch.v1.rs");
+            }
+        }
+
         pub mod power_sensor {
             pub mod v1 {
                 #![allow(clippy::derive_partial_eq_without_eq)]
@@ -4,10 +4,26 @@ use mdns_sd::{ServiceDaemon, ServiceInfo};
 
 use crate::common::conn::mdns::{Mdns, MdnsError};
 
+/// Hostname advertised until one is set from the robot config
+const DEFAULT_HOSTNAME: &str = "micro-rdk";
+
+/// Responders expect host names to be fully qualified within the `local.` domain and made of
+/// a single label
+fn local_hostname(hostname: &str) -> String {
+    let label = hostname.trim_end_matches('.').trim_end_matches(".local");
+    let label = if label.is_empty() {
+        DEFAULT_HOSTNAME.to_owned()
+    } else {
+        label.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "-")
+    };
+    format!("{}.local.", label)
+}
+
 pub struct NativeMdns {
     inner: ServiceDaemon,
     hostname: String,
     ip: Ipv4Addr,
+    services: Vec<String>,
 }
 
 impl NativeMdns {
@@ -15,8 +31,9 @@ impl NativeMdns {
         Ok(Self {
             inner: ServiceDaemon::new()
                 .map_err(|e| MdnsError::MdnsInitServiceError(e.to_string()))?,
-            hostname,
+            hostname: local_hostname(&hostname),
             ip,
+            services: vec![],
         })
     }
 }
@@ -46,15 +63,46 @@ impl Mdns for NativeMdns {
             Some(props),
         )
         .map_err(|e| MdnsError::MdnsAddServiceError(e.to_string()))?;
+        let fullname = service.get_fullname().to_owned();
 
         self.inner
             .register(service)
             .map_err(|e| MdnsError::MdnsAddServiceError(e.to_string()))?;
 
+        if !self.services.contains(&fullname) {
+            self.services.push(fullname);
+        }
+
         Ok(())
     }
     fn set_hostname(&mut self, hostname: &str) -> Result<(), MdnsError> {
-        self.hostname = hostname.to_owned();
+        self.hostname = local_hostname(hostname);
         Ok(())
     }
 }
+
+impl Drop for NativeMdns {
+    fn drop(&mut self) {
+        // send goodbye packets so the services don't linger in peers' caches
+        for fullname in self.services.drain(..) {
+            if let Err(e) = self.inner.unregister(&fullname) {
+                log::warn!("couldn't unregister mdns service {}: {}", fullname, e);
+            }
+        }
+        let _ = self.inner.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::local_hostname;
+
+    #[test_log::test]
+    fn test_local_hostname() {
+        assert_eq!(local_hostname("my-robot"), "my-robot.local.");
+        assert_eq!(local_hostname("my-robot.local."), "my-robot.local.");
+        assert_eq!(local_hostname("my-robot.local"), "my-robot.local.");
+        assert_eq!(local_hostname("my robot_main"), "my-robot-main.local.");
+        assert_eq!(local_hostname(""), "micro-rdk.local.");
+    }
+}
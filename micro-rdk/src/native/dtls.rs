@@ -17,7 +17,8 @@ use openssl::ssl::{
 };
 
 use crate::common::webrtc::certificate::Certificate;
-use crate::common::webrtc::dtls::{DtlsBuilder, DtlsConnector, DtlsError};
+use crate::common::webrtc::dtls::{DtlsBuilder, DtlsConnector, DtlsError, DtlsSrtpKeying};
+use crate::common::webrtc::srtp::SRTP_KEYING_MATERIAL_LEN;
 use crate::common::webrtc::udp_mux::UdpMux;
 
 fn dtls_log_session_key(_: &SslRef, line: &str) {
@@ -130,6 +131,16 @@ impl DtlsConnector for Dtls {
     }
 }
 
+impl DtlsSrtpKeying for SslStream<UdpMux> {
+    fn srtp_keying_material(&self) -> Option<[u8; SRTP_KEYING_MATERIAL_LEN]> {
+        let mut material = [0; SRTP_KEYING_MATERIAL_LEN];
+        self.ssl()
+            .export_keying_material(&mut material, "EXTRACTOR-dtls_srtp", None)
+            .ok()?;
+        Some(material)
+    }
+}
+
 impl<C: Certificate> DtlsBuilder for NativeDtls<C> {
     type Output = Dtls;
     fn make(&self) -> Result<Self::Output, DtlsError> {
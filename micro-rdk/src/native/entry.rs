@@ -2,18 +2,24 @@
 use crate::{
     common::{
         app_client::{AppClientBuilder, AppClientConfig},
+        authorization::AuthPolicy,
+        automation::AutomationEngine,
+        button::ButtonWatcher,
         conn::server::{ViamServerBuilder, WebRtcConfiguration},
-        entry::RobotRepresentation,
+        entry::{RobotPartRepresentation, RobotRepresentation},
         grpc_client::GrpcClient,
+        instrumentation::Instrumentation,
+        json_endpoint::JsonEndpoint,
         log::config_log_entry,
         robot::LocalRobot,
     },
     native::{exec::NativeExecutor, tcp::NativeStream, tls::NativeTls},
+    proto::app::v1::ConfigResponse,
 };
 use std::{
     net::{Ipv4Addr, SocketAddr},
     rc::Rc,
-    sync::{Arc, Mutex},
+    sync::{Arc, RwLock},
 };
 
 use super::{
@@ -21,41 +27,111 @@ use super::{
     tls::NativeTlsServerConfig,
 };
 
+#[cfg(feature = "builtin-components")]
+use crate::common::alerts;
+#[cfg(feature = "builtin-components")]
+use crate::common::battery;
+#[cfg(feature = "builtin-components")]
+use crate::common::geofence;
 #[cfg(feature = "data")]
-use crate::common::{data_manager::DataManager, data_store::StaticMemoryDataStore};
+use crate::common::{data_manager::DataManager, data_store::DefaultDataStore};
+
+/// Starts the services and watchers configured for the robot on the executor
+fn start_services(
+    cfg_response: &ConfigResponse,
+    robot: &Arc<RwLock<LocalRobot>>,
+    auth: &Rc<AuthPolicy>,
+    exec: &NativeExecutor,
+) {
+    match Instrumentation::from_config(cfg_response) {
+        Ok(Some(mut instrumentation)) => exec
+            .spawn(async move { instrumentation.run().await })
+            .detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start instrumentation: {:?}", err),
+    }
 
-pub async fn serve_web_inner(
-    app_config: AppClientConfig,
-    tls_server_config: NativeTlsServerConfig,
-    repr: RobotRepresentation,
-    ip: Ipv4Addr,
-    exec: NativeExecutor,
+    start_robot_services(cfg_response, robot, auth, exec);
+}
+
+/// Starts the watchers configured for the robot, for each robot part the device serves
+fn start_robot_services(
+    cfg_response: &ConfigResponse,
+    robot: &Arc<RwLock<LocalRobot>>,
+    auth: &Rc<AuthPolicy>,
+    exec: &NativeExecutor,
 ) {
-    let client_connector = NativeTls::new_client();
-    let mdns = NativeMdns::new("".to_owned(), ip).unwrap();
+    match AutomationEngine::from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(mut automations)) => exec.spawn(async move { automations.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start automations: {:?}", err),
+    }
 
-    let (cfg_response, robot) = {
-        let cloned_exec = exec.clone();
-        let conn = client_connector.open_ssl_context(None).await.unwrap();
-        let conn = NativeStream::TLSStream(Box::new(conn));
-        let grpc_client = GrpcClient::new(conn, cloned_exec, "https://app.viam.com:443")
-            .await
-            .unwrap();
-        let builder = AppClientBuilder::new(Box::new(grpc_client), app_config.clone());
-        log::info!("build client start");
-        let mut client = builder.build().await.unwrap();
-
-        let (cfg_response, cfg_received_datetime) = client.get_config().await.unwrap();
-
-        let robot = match repr {
-            RobotRepresentation::WithRobot(robot) => Arc::new(Mutex::new(robot)),
-            RobotRepresentation::WithRegistry(registry) => {
-                log::info!("building robot from config");
-                let r = match LocalRobot::from_cloud_config(
-                    &cfg_response,
-                    registry,
-                    cfg_received_datetime,
-                ) {
+    match JsonEndpoint::from_robot_and_config(cfg_response, robot.clone(), auth.clone()) {
+        Ok(Some(endpoint)) => exec
+            .spawn(async move {
+                if let Err(err) = endpoint.run().await {
+                    log::error!("json endpoint stopped: {:?}", err);
+                }
+            })
+            .detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start json endpoint: {:?}", err),
+    }
+    match ButtonWatcher::from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(watcher)) => exec.spawn(async move { watcher.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start watching buttons: {:?}", err),
+    }
+
+    #[cfg(feature = "builtin-components")]
+    match geofence::watcher_from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(watcher)) => exec.spawn(async move { watcher.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start watching geofences: {:?}", err),
+    }
+
+    #[cfg(feature = "builtin-components")]
+    match alerts::watcher_from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(watcher)) => exec.spawn(async move { watcher.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start watching alerts: {:?}", err),
+    }
+
+    #[cfg(feature = "builtin-components")]
+    match battery::watcher_from_robot_and_config(cfg_response, robot.clone()) {
+        Ok(Some(watcher)) => exec.spawn(async move { watcher.run().await }).detach(),
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start watching batteries: {:?}", err),
+    }
+}
+
+/// Fetches the config of the robot part `app_config` authenticates as and builds its robot
+async fn build_robot(
+    client_connector: &NativeTls,
+    app_config: &AppClientConfig,
+    repr: RobotRepresentation,
+    exec: &NativeExecutor,
+) -> (Box<ConfigResponse>, Arc<RwLock<LocalRobot>>) {
+    let cloned_exec = exec.clone();
+    let conn = client_connector.open_ssl_context(None).await.unwrap();
+    let conn = NativeStream::TLSStream(Box::new(conn));
+    let grpc_client = GrpcClient::new(conn, cloned_exec, "https://app.viam.com:443")
+        .await
+        .unwrap();
+    let builder = AppClientBuilder::new(Box::new(grpc_client), app_config.clone());
+    log::info!("build client start");
+    let mut client = builder.build().await.unwrap();
+
+    let (cfg_response, cfg_received_datetime) = client.get_config().await.unwrap();
+
+    let robot = match repr {
+        RobotRepresentation::WithRobot(robot) => Arc::new(RwLock::new(robot)),
+        RobotRepresentation::WithRegistry(registry) => {
+            log::info!("building robot from config");
+            let r =
+                match LocalRobot::from_cloud_config(&cfg_response, registry, cfg_received_datetime)
+                {
                     Ok(robot) => {
                         if let Some(datetime) = cfg_received_datetime {
                             let logs = vec![config_log_entry(datetime, None)];
@@ -78,23 +154,75 @@ pub async fn serve_web_inner(
                         panic!("couldn't build robot");
                     }
                 };
-                Arc::new(Mutex::new(r))
-            }
-        };
-
-        (cfg_response, robot)
+            Arc::new(RwLock::new(r))
+        }
     };
 
-    #[cfg(feature = "data")]
-    // TODO: Spawn data task here. May have to move the initialization below to the task itself
-    // TODO: Support implementers of the DataStore trait other than StaticMemoryDataStore in a way that is configurable
-    {
-        let _data_manager_svc = DataManager::<StaticMemoryDataStore>::from_robot_and_config(
-            &cfg_response,
-            &app_config,
-            robot.clone(),
-        );
+    (cfg_response, robot)
+}
+
+/// Starts the data manager of the robot part `app_config` authenticates as
+#[cfg(feature = "data")]
+fn start_data_manager(
+    cfg_response: &ConfigResponse,
+    app_config: &AppClientConfig,
+    robot: &Arc<RwLock<LocalRobot>>,
+    exec: &NativeExecutor,
+) {
+    match DataManager::<DefaultDataStore>::from_robot_and_config(
+        cfg_response,
+        app_config,
+        robot.clone(),
+    ) {
+        Ok(Some(mut data_manager)) => {
+            let (data_exec, data_app_config) = (exec.clone(), app_config.clone());
+            exec.spawn(async move {
+                if let Err(err) = data_manager
+                    .run_with_app(NativeTls::new_client(), data_exec, data_app_config)
+                    .await
+                {
+                    log::error!("data manager stopped: {:?}", err);
+                }
+            })
+            .detach();
+        }
+        Ok(None) => {}
+        Err(err) => log::error!("couldn't start the data manager: {:?}", err),
     }
+}
+
+pub async fn serve_web_inner(
+    app_config: AppClientConfig,
+    tls_server_config: NativeTlsServerConfig,
+    repr: RobotRepresentation,
+    part: Option<RobotPartRepresentation>,
+    ip: Ipv4Addr,
+    exec: NativeExecutor,
+) {
+    let client_connector = NativeTls::new_client();
+    let mdns = NativeMdns::new("".to_owned(), ip).unwrap();
+
+    let (cfg_response, robot) = build_robot(&client_connector, &app_config, repr, &exec).await;
+
+    // shared by the local endpoints so that they accept the same access tokens
+    let auth = Rc::new(AuthPolicy::from_config(&cfg_response));
+    start_services(&cfg_response, &robot, &auth, &exec);
+
+    #[cfg(feature = "data")]
+    start_data_manager(&cfg_response, &app_config, &robot, &exec);
+
+    let part = match part {
+        Some(part) => {
+            let (cfg_response, robot) =
+                build_robot(&client_connector, &part.app_config, part.repr, &exec).await;
+            let auth = Rc::new(AuthPolicy::from_config(&cfg_response));
+            start_robot_services(&cfg_response, &robot, &auth, &exec);
+            #[cfg(feature = "data")]
+            start_data_manager(&cfg_response, &part.app_config, &robot, &exec);
+            Some((part.app_config, cfg_response, robot, auth))
+        }
+        None => None,
+    };
 
     let address: SocketAddr = "0.0.0.0:12346".parse().unwrap();
     let tls = Box::new(NativeTls::new_server(tls_server_config));
@@ -111,11 +239,27 @@ pub async fn serve_web_inner(
         exec.clone(),
     ));
 
-    let mut srv = ViamServerBuilder::new(mdns, cloned_exec, client_connector, app_config, 3)
+    let mut builder = ViamServerBuilder::new(mdns, cloned_exec, client_connector, app_config, 3)
         .with_http2(tls_listener, 12346)
         .with_webrtc(webrtc)
-        .build(&cfg_response)
-        .unwrap();
+        .with_auth_policy(auth);
+    if let Some((part_app_config, part_cfg_response, part_robot, part_auth)) = part {
+        // the part answers WebRTC offers with a certificate of its own
+        let part_certificate = Rc::new(WebRtcCertificate::new());
+        let part_webrtc = Box::new(WebRtcConfiguration::new(
+            part_certificate.clone(),
+            NativeDtls::new(part_certificate),
+            exec.clone(),
+        ));
+        builder = builder.with_part(
+            part_app_config,
+            &part_cfg_response,
+            part_robot,
+            part_auth,
+            Some(part_webrtc),
+        );
+    }
+    let mut srv = builder.build(&cfg_response).unwrap();
 
     srv.serve(robot).await;
 }
@@ -133,11 +277,81 @@ pub fn serve_web(
         app_config,
         tls_server_config,
         repr,
+        None,
         ip,
         exec,
     )));
 }
 
+/// Like [serve_web] but the device also serves `part`, a second robot part with its own
+/// credentials, access policy, WebRTC certificate and data manager. The data captured by the part
+/// is only stored on an SD card, the memory store being taken by the main part
+pub fn serve_web_with_part(
+    app_config: AppClientConfig,
+    tls_server_config: NativeTlsServerConfig,
+    repr: RobotRepresentation,
+    part: RobotPartRepresentation,
+    ip: Ipv4Addr,
+) {
+    let exec = NativeExecutor::new();
+    let cloned_exec = exec.clone();
+
+    cloned_exec.block_on(Box::pin(serve_web_inner(
+        app_config,
+        tls_server_config,
+        repr,
+        Some(part),
+        ip,
+        exec,
+    )));
+}
+
+pub async fn serve_offline_inner(
+    cfg_response: ConfigResponse,
+    repr: RobotRepresentation,
+    ip: Ipv4Addr,
+    exec: NativeExecutor,
+) {
+    let mdns = NativeMdns::new("".to_owned(), ip).unwrap();
+
+    let robot = match repr {
+        RobotRepresentation::WithRobot(robot) => Arc::new(RwLock::new(robot)),
+        RobotRepresentation::WithRegistry(registry) => {
+            log::info!("building robot from static config");
+            Arc::new(RwLock::new(
+                LocalRobot::from_cloud_config(&cfg_response, registry, None)
+                    .expect("couldn't build robot"),
+            ))
+        }
+    };
+
+    // shared by the local endpoints so that they accept the same access tokens
+    let auth = Rc::new(AuthPolicy::from_config(&cfg_response));
+    start_services(&cfg_response, &robot, &auth, &exec);
+
+    let address: SocketAddr = "0.0.0.0:12346".parse().unwrap();
+    let listener = NativeListener::new(address.into(), None).unwrap();
+    let app_config = AppClientConfig::new("".to_owned(), "".to_owned(), ip, "".to_owned());
+
+    let mut srv = ViamServerBuilder::new(mdns, exec, NativeTls::new_client(), app_config, 3)
+        .with_http2(listener, 12346)
+        .without_app()
+        .with_auth_policy(auth)
+        .build(&cfg_response)
+        .unwrap();
+
+    srv.serve(robot).await;
+}
+
+/// Serves a robot built from a [static config](crate::common::static_config) over plain HTTP2
+/// on port 12346, without ever reaching app
+pub fn serve_offline(cfg_response: ConfigResponse, repr: RobotRepresentation, ip: Ipv4Addr) {
+    let exec = NativeExecutor::new();
+    let cloned_exec = exec.clone();
+
+    cloned_exec.block_on(Box::pin(serve_offline_inner(cfg_response, repr, ip, exec)));
+}
+
 #[cfg(test)]
 mod tests {
     use crate::common::app_client::{encode_request, AppClientBuilder, AppClientConfig};
@@ -5,6 +5,7 @@ use futures_lite::{
     Future,
 };
 
+use crate::common::instrumentation::track_task;
 use crate::common::webrtc::exec::WebRtcExecutor;
 
 #[derive(Clone, Debug, Default)]
@@ -21,7 +22,7 @@ impl NativeExecutor {
     }
     // Spawn a future onto the local executor
     pub fn spawn<T: 'static>(&self, future: impl Future<Output = T> + 'static) -> Task<T> {
-        EX.with(|e| e.spawn(future))
+        EX.with(|e| e.spawn(track_task(future)))
     }
 
     pub fn block_on<T>(&self, future: impl Future<Output = T>) -> T {
@@ -35,7 +36,7 @@ where
     F: future::Future + 'static,
 {
     fn execute(&self, fut: F) {
-        EX.with(|e| e.spawn(fut)).detach();
+        EX.with(|e| e.spawn(track_task(fut))).detach();
     }
 }
 
@@ -44,6 +45,6 @@ where
     F: future::Future + 'static,
 {
     fn execute(&self, fut: F) {
-        EX.with(|e| e.spawn(fut)).detach();
+        EX.with(|e| e.spawn(track_task(fut))).detach();
     }
 }
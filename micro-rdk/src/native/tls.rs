@@ -1,4 +1,8 @@
-use std::{io::BufReader, net::TcpStream, sync::Arc};
+use std::{
+    io::BufReader,
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+};
 
 use async_io::Async;
 use futures_lite::AsyncRead;
@@ -47,6 +51,39 @@ impl NativeTls {
     ) -> Result<NativeTlsStream, std::io::Error> {
         NativeTlsStream::accept_or_connect(socket, &self.server_config).await
     }
+
+    /// Opens a TLS connection to `host`, resolved to `addr`, trusted through the webpki root
+    /// certificates, for protocols other than HTTP/2
+    pub async fn connect_to_host(
+        host: &str,
+        addr: SocketAddr,
+    ) -> Result<NativeTlsStream, std::io::Error> {
+        let cfg = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_certificates())
+            .with_no_client_auth();
+        let stream = Async::<TcpStream>::connect(addr).await?;
+        let stream = TlsConnector::from(Arc::new(cfg))
+            .connect(
+                host.try_into()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+                stream,
+            )
+            .await?;
+        Ok(NativeTlsStream(futures_rustls::TlsStream::Client(stream)))
+    }
+}
+
+fn root_certificates() -> RootCertStore {
+    let mut root_certs = RootCertStore::empty();
+    root_certs.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    root_certs
 }
 
 impl TlsClientConnector for NativeTls {
@@ -106,16 +143,7 @@ impl NativeTlsStream {
 
             futures_rustls::TlsStream::Server(stream)
         } else {
-            let mut root_certs = RootCertStore::empty();
-            root_certs.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(
-                |ta| {
-                    OwnedTrustAnchor::from_subject_spki_name_constraints(
-                        ta.subject,
-                        ta.spki,
-                        ta.name_constraints,
-                    )
-                },
-            ));
+            let root_certs = root_certificates();
             let log = Arc::new(KeyLogFile::new());
             let mut cfg = ClientConfig::builder()
                 .with_safe_defaults()
@@ -0,0 +1,82 @@
+//! `my_base` model, a base driven by linear and angular powers
+
+use std::sync::{Arc, Mutex};
+
+use micro_rdk::common::actuator::{Actuator, ActuatorError};
+use micro_rdk::common::base::{Base, BaseError, BaseType};
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::config::ConfigType;
+use micro_rdk::common::generic::{DoCommand, GenericError};
+use micro_rdk::common::math_utils::Vector3;
+use micro_rdk::common::registry::{ComponentRegistry, Dependency, RegistryError};
+use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::google::protobuf::Struct;
+
+use crate::commands::CommandState;
+use crate::dependencies;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) -> Result<(), RegistryError> {
+    registry.register_base("my_base", &MyBase::from_config)
+}
+
+pub struct MyBase {
+    // `None` when the component doesn't depend on a board
+    #[allow(dead_code)]
+    board: Option<BoardType>,
+    commands: CommandState,
+    linear: Vector3,
+    angular: Vector3,
+}
+
+impl MyBase {
+    pub fn from_config(_cfg: ConfigType, deps: Vec<Dependency>) -> Result<BaseType, BaseError> {
+        // read attributes with `_cfg.get_attribute::<f64>("key")` and other dependencies with
+        // the getters of `dependencies`
+        Ok(Arc::new(Mutex::new(MyBase {
+            board: dependencies::board(&deps),
+            commands: CommandState::default(),
+            linear: Vector3::new(),
+            angular: Vector3::new(),
+        })))
+    }
+}
+
+impl Status for MyBase {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(self.commands.status()))
+    }
+}
+
+impl DoCommand for MyBase {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        self.commands.do_command(command_struct)
+    }
+}
+
+impl Actuator for MyBase {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(is_powered(&self.linear) || is_powered(&self.angular))
+    }
+
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        self.linear = Vector3::new();
+        self.angular = Vector3::new();
+        Ok(())
+    }
+}
+
+impl Base for MyBase {
+    fn set_power(&mut self, lin: &Vector3, ang: &Vector3) -> Result<(), BaseError> {
+        // drive the motors of the base here
+        self.linear = *lin;
+        self.angular = *ang;
+        Ok(())
+    }
+}
+
+fn is_powered(power: &Vector3) -> bool {
+    power.x != 0.0 || power.y != 0.0 || power.z != 0.0
+}
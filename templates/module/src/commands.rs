@@ -0,0 +1,71 @@
+//! DoCommand example shared by the components of the module. A command is a struct whose single
+//! key names it, its value holding the arguments:
+//!
+//! - `{"echo": <any value>}` answers `{"echo": <the same value>, "commands": <count>}`
+//! - `{"set_label": {"label": "left"}}` changes the label reported in the status
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use micro_rdk::common::generic::GenericError;
+use micro_rdk::google::protobuf::{value::Kind, Struct, Value};
+
+#[derive(Debug, Default)]
+pub(crate) struct CommandState {
+    pub(crate) commands: u32,
+    pub(crate) label: String,
+}
+
+impl CommandState {
+    pub(crate) fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        let command = command_struct.unwrap_or_default();
+        if let Some(value) = command.fields.get("echo") {
+            self.commands += 1;
+            return Ok(Some(Struct {
+                fields: HashMap::from([
+                    ("echo".to_string(), value.clone()),
+                    ("commands".to_string(), number(self.commands as f64)),
+                ]),
+            }));
+        }
+        if let Some(args) = command.fields.get("set_label") {
+            let label = match &args.kind {
+                Some(Kind::StructValue(args)) => match args.fields.get("label") {
+                    Some(Value {
+                        kind: Some(Kind::StringValue(label)),
+                    }) => label.clone(),
+                    _ => return Err(GenericError::InvalidArgument("label")),
+                },
+                _ => return Err(GenericError::InvalidArgument("set_label")),
+            };
+            self.commands += 1;
+            self.label = label;
+            return Ok(None);
+        }
+        Err(GenericError::MethodUnimplemented("do_command"))
+    }
+
+    /// Status fields reporting the command state
+    pub(crate) fn status(&self) -> Struct {
+        Struct {
+            fields: HashMap::from([
+                ("commands".to_string(), number(self.commands as f64)),
+                (
+                    "label".to_string(),
+                    Value {
+                        kind: Some(Kind::StringValue(self.label.clone())),
+                    },
+                ),
+            ]),
+        }
+    }
+}
+
+fn number(value: f64) -> Value {
+    Value {
+        kind: Some(Kind::NumberValue(value)),
+    }
+}
@@ -0,0 +1,42 @@
+//! Getters for the resources a component depends on. The robot resolves the names listed in the
+//! `depends_on` field of a component config and hands them to its `from_config` function.
+#![allow(dead_code)]
+
+use micro_rdk::common::base::BaseType;
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::encoder::EncoderType;
+use micro_rdk::common::motor::MotorType;
+use micro_rdk::common::movement_sensor::MovementSensorType;
+use micro_rdk::common::power_sensor::PowerSensorType;
+use micro_rdk::common::registry::Dependency;
+use micro_rdk::common::robot::Resource;
+use micro_rdk::common::sensor::SensorType;
+use micro_rdk::common::servo::ServoType;
+
+/// The board among the dependencies, components usually depend on a single one
+pub(crate) fn board(deps: &[Dependency]) -> Option<BoardType> {
+    deps.iter().find_map(|Dependency(_, dep)| match dep {
+        Resource::Board(board) => Some(board.clone()),
+        _ => None,
+    })
+}
+
+macro_rules! named_dependency {
+    ($name:ident, $variant:ident, $ty:ty) => {
+        /// The dependency of this type called `name`
+        pub(crate) fn $name(deps: &[Dependency], name: &str) -> Option<$ty> {
+            deps.iter().find_map(|Dependency(key, dep)| match dep {
+                Resource::$variant(dep) if key.1 == name => Some(dep.clone()),
+                _ => None,
+            })
+        }
+    };
+}
+
+named_dependency!(base, Base, BaseType);
+named_dependency!(encoder, Encoder, EncoderType);
+named_dependency!(motor, Motor, MotorType);
+named_dependency!(movement_sensor, MovementSensor, MovementSensorType);
+named_dependency!(power_sensor, PowerSensor, PowerSensorType);
+named_dependency!(sensor, Sensor, SensorType);
+named_dependency!(servo, Servo, ServoType);
@@ -0,0 +1,85 @@
+//! `my_encoder` model, an encoder counting ticks
+
+use std::sync::{Arc, Mutex};
+
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::config::ConfigType;
+use micro_rdk::common::encoder::{
+    Encoder, EncoderError, EncoderPosition, EncoderPositionType, EncoderSupportedRepresentations,
+    EncoderType,
+};
+use micro_rdk::common::generic::{DoCommand, GenericError};
+use micro_rdk::common::registry::{ComponentRegistry, Dependency, RegistryError};
+use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::google::protobuf::Struct;
+
+use crate::commands::CommandState;
+use crate::dependencies;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) -> Result<(), RegistryError> {
+    registry.register_encoder("my_encoder", &MyEncoder::from_config)
+}
+
+pub struct MyEncoder {
+    // `None` when the component doesn't depend on a board
+    #[allow(dead_code)]
+    board: Option<BoardType>,
+    commands: CommandState,
+    ticks: i32,
+}
+
+impl MyEncoder {
+    pub fn from_config(
+        _cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<EncoderType, EncoderError> {
+        // read attributes with `_cfg.get_attribute::<f64>("key")` and other dependencies with
+        // the getters of `dependencies`
+        Ok(Arc::new(Mutex::new(MyEncoder {
+            board: dependencies::board(&deps),
+            commands: CommandState::default(),
+            ticks: 0,
+        })))
+    }
+}
+
+impl Status for MyEncoder {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(self.commands.status()))
+    }
+}
+
+impl DoCommand for MyEncoder {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        self.commands.do_command(command_struct)
+    }
+}
+
+impl Encoder for MyEncoder {
+    fn get_properties(&mut self) -> EncoderSupportedRepresentations {
+        EncoderSupportedRepresentations {
+            ticks_count_supported: true,
+            angle_degrees_supported: false,
+        }
+    }
+
+    fn get_position(
+        &self,
+        position_type: EncoderPositionType,
+    ) -> Result<EncoderPosition, EncoderError> {
+        match position_type {
+            EncoderPositionType::TICKS | EncoderPositionType::UNSPECIFIED => {
+                Ok(EncoderPositionType::TICKS.wrap_value(self.ticks as f32))
+            }
+            EncoderPositionType::DEGREES => Err(EncoderError::EncoderAngularNotSupported),
+        }
+    }
+
+    fn reset_position(&mut self) -> Result<(), EncoderError> {
+        self.ticks = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+//! `my_generic_component` model, a component only answering DoCommands
+
+use std::sync::{Arc, Mutex};
+
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::config::ConfigType;
+use micro_rdk::common::generic::{DoCommand, GenericComponent, GenericComponentType, GenericError};
+use micro_rdk::common::registry::{ComponentRegistry, Dependency, RegistryError};
+use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::google::protobuf::Struct;
+
+use crate::commands::CommandState;
+use crate::dependencies;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) -> Result<(), RegistryError> {
+    registry.register_generic_component("my_generic_component", &MyGenericComponent::from_config)
+}
+
+pub struct MyGenericComponent {
+    // `None` when the component doesn't depend on a board
+    #[allow(dead_code)]
+    board: Option<BoardType>,
+    commands: CommandState,
+}
+
+impl MyGenericComponent {
+    pub fn from_config(
+        _cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<GenericComponentType, GenericError> {
+        // read attributes with `_cfg.get_attribute::<f64>("key")` and other dependencies with
+        // the getters of `dependencies`
+        Ok(Arc::new(Mutex::new(MyGenericComponent {
+            board: dependencies::board(&deps),
+            commands: CommandState::default(),
+        })))
+    }
+}
+
+impl Status for MyGenericComponent {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(self.commands.status()))
+    }
+}
+
+impl DoCommand for MyGenericComponent {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        self.commands.do_command(command_struct)
+    }
+}
+
+impl GenericComponent for MyGenericComponent {}
@@ -0,0 +1,90 @@
+//! `my_motor` model, a motor driven by a power percentage
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use micro_rdk::common::actuator::{Actuator, ActuatorError};
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::config::ConfigType;
+use micro_rdk::common::generic::{DoCommand, GenericError};
+use micro_rdk::common::motor::{Motor, MotorError, MotorSupportedProperties, MotorType};
+use micro_rdk::common::registry::{ComponentRegistry, Dependency, RegistryError};
+use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::google::protobuf::Struct;
+
+use crate::commands::CommandState;
+use crate::dependencies;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) -> Result<(), RegistryError> {
+    registry.register_motor("my_motor", &MyMotor::from_config)
+}
+
+pub struct MyMotor {
+    // `None` when the component doesn't depend on a board
+    #[allow(dead_code)]
+    board: Option<BoardType>,
+    commands: CommandState,
+    power: f64,
+}
+
+impl MyMotor {
+    pub fn from_config(_cfg: ConfigType, deps: Vec<Dependency>) -> Result<MotorType, MotorError> {
+        // read attributes with `_cfg.get_attribute::<f64>("key")` and other dependencies with
+        // the getters of `dependencies`
+        Ok(Arc::new(Mutex::new(MyMotor {
+            board: dependencies::board(&deps),
+            commands: CommandState::default(),
+            power: 0.0,
+        })))
+    }
+}
+
+impl Status for MyMotor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(self.commands.status()))
+    }
+}
+
+impl DoCommand for MyMotor {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        self.commands.do_command(command_struct)
+    }
+}
+
+impl Actuator for MyMotor {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(self.power != 0.0)
+    }
+
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        self.power = 0.0;
+        Ok(())
+    }
+}
+
+impl Motor for MyMotor {
+    fn set_power(&mut self, pct: f64) -> Result<(), MotorError> {
+        // set the duty cycle of the motor driver here, `self.board` gives access to the pins
+        self.power = pct.clamp(-1.0, 1.0);
+        Ok(())
+    }
+
+    fn get_position(&mut self) -> Result<i32, MotorError> {
+        Err(MotorError::MotorMethodUnimplemented("get_position"))
+    }
+
+    fn go_for(&mut self, _rpm: f64, _revolutions: f64) -> Result<Option<Duration>, MotorError> {
+        Err(MotorError::MotorMethodUnimplemented("go_for"))
+    }
+
+    fn get_properties(&mut self) -> MotorSupportedProperties {
+        MotorSupportedProperties::default()
+    }
+
+    fn is_powered(&mut self) -> Result<(bool, f64), MotorError> {
+        Ok((self.power != 0.0, self.power))
+    }
+}
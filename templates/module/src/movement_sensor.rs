@@ -0,0 +1,101 @@
+//! `my_movement_sensor` model, a movement sensor reporting its linear acceleration
+
+use std::sync::{Arc, Mutex};
+
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::config::ConfigType;
+use micro_rdk::common::generic::{DoCommand, GenericError};
+use micro_rdk::common::math_utils::Vector3;
+use micro_rdk::common::movement_sensor::{
+    GeoPosition, MovementSensor, MovementSensorSupportedMethods, MovementSensorType,
+};
+use micro_rdk::common::registry::{ComponentRegistry, Dependency, RegistryError};
+use micro_rdk::common::sensor::SensorError;
+use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::google::protobuf::Struct;
+use micro_rdk::MovementSensorReadings;
+
+use crate::commands::CommandState;
+use crate::dependencies;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) -> Result<(), RegistryError> {
+    registry.register_movement_sensor("my_movement_sensor", &MyMovementSensor::from_config)
+}
+
+#[derive(MovementSensorReadings)]
+pub struct MyMovementSensor {
+    // `None` when the component doesn't depend on a board
+    #[allow(dead_code)]
+    board: Option<BoardType>,
+    commands: CommandState,
+}
+
+impl MyMovementSensor {
+    pub fn from_config(
+        _cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<MovementSensorType, SensorError> {
+        // read attributes with `_cfg.get_attribute::<f64>("key")` and other dependencies with
+        // the getters of `dependencies`
+        Ok(Arc::new(Mutex::new(MyMovementSensor {
+            board: dependencies::board(&deps),
+            commands: CommandState::default(),
+        })))
+    }
+}
+
+impl Status for MyMovementSensor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(self.commands.status()))
+    }
+}
+
+impl DoCommand for MyMovementSensor {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        self.commands.do_command(command_struct)
+    }
+}
+
+impl MovementSensor for MyMovementSensor {
+    // readings and data capture only report the supported methods
+    fn get_properties(&self) -> MovementSensorSupportedMethods {
+        MovementSensorSupportedMethods {
+            position_supported: false,
+            linear_velocity_supported: false,
+            angular_velocity_supported: false,
+            linear_acceleration_supported: true,
+            compass_heading_supported: false,
+            orientation_supported: false,
+        }
+    }
+
+    fn get_linear_acceleration(&mut self) -> Result<Vector3, SensorError> {
+        // read the accelerometer here
+        Ok(Vector3::new())
+    }
+
+    fn get_position(&mut self) -> Result<GeoPosition, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented("get_position"))
+    }
+
+    fn get_linear_velocity(&mut self) -> Result<Vector3, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented(
+            "get_linear_velocity",
+        ))
+    }
+
+    fn get_angular_velocity(&mut self) -> Result<Vector3, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented(
+            "get_angular_velocity",
+        ))
+    }
+
+    fn get_compass_heading(&mut self) -> Result<f64, SensorError> {
+        Err(SensorError::SensorMethodUnimplemented(
+            "get_compass_heading",
+        ))
+    }
+}
@@ -0,0 +1,80 @@
+//! `my_power_sensor` model, a DC power sensor
+
+use std::sync::{Arc, Mutex};
+
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::config::ConfigType;
+use micro_rdk::common::generic::{DoCommand, GenericError};
+use micro_rdk::common::power_sensor::{
+    Current, PowerSensor, PowerSensorType, PowerSupplyType, Voltage,
+};
+use micro_rdk::common::registry::{ComponentRegistry, Dependency, RegistryError};
+use micro_rdk::common::sensor::SensorError;
+use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::google::protobuf::Struct;
+use micro_rdk::PowerSensorReadings;
+
+use crate::commands::CommandState;
+use crate::dependencies;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) -> Result<(), RegistryError> {
+    registry.register_power_sensor("my_power_sensor", &MyPowerSensor::from_config)
+}
+
+#[derive(PowerSensorReadings)]
+pub struct MyPowerSensor {
+    // `None` when the component doesn't depend on a board
+    #[allow(dead_code)]
+    board: Option<BoardType>,
+    commands: CommandState,
+}
+
+impl MyPowerSensor {
+    pub fn from_config(
+        _cfg: ConfigType,
+        deps: Vec<Dependency>,
+    ) -> Result<PowerSensorType, SensorError> {
+        // read attributes with `_cfg.get_attribute::<f64>("key")` and other dependencies with
+        // the getters of `dependencies`
+        Ok(Arc::new(Mutex::new(MyPowerSensor {
+            board: dependencies::board(&deps),
+            commands: CommandState::default(),
+        })))
+    }
+}
+
+impl Status for MyPowerSensor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(self.commands.status()))
+    }
+}
+
+impl DoCommand for MyPowerSensor {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        self.commands.do_command(command_struct)
+    }
+}
+
+impl PowerSensor for MyPowerSensor {
+    fn get_voltage(&mut self) -> Result<Voltage, SensorError> {
+        // read the voltage here
+        Ok(Voltage {
+            volts: 0.0,
+            power_supply_type: PowerSupplyType::DC,
+        })
+    }
+
+    fn get_current(&mut self) -> Result<Current, SensorError> {
+        Ok(Current {
+            amperes: 0.0,
+            power_supply_type: PowerSupplyType::DC,
+        })
+    }
+
+    fn get_power(&mut self) -> Result<f64, SensorError> {
+        Ok(self.get_voltage()?.volts * self.get_current()?.amperes)
+    }
+}
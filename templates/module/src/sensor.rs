@@ -0,0 +1,66 @@
+//! `my_sensor` model, a sensor reporting readings
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::config::ConfigType;
+use micro_rdk::common::generic::{DoCommand, GenericError};
+use micro_rdk::common::registry::{ComponentRegistry, Dependency, RegistryError};
+use micro_rdk::common::sensor::{
+    GenericReadingsResult, Readings, Sensor, SensorError, SensorResult, SensorType,
+};
+use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::google::protobuf::Struct;
+
+use crate::commands::CommandState;
+use crate::dependencies;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) -> Result<(), RegistryError> {
+    registry.register_sensor("my_sensor", &MySensor::from_config)
+}
+
+pub struct MySensor {
+    // `None` when the component doesn't depend on a board
+    #[allow(dead_code)]
+    board: Option<BoardType>,
+    commands: CommandState,
+}
+
+impl MySensor {
+    pub fn from_config(_cfg: ConfigType, deps: Vec<Dependency>) -> Result<SensorType, SensorError> {
+        // read attributes with `_cfg.get_attribute::<f64>("key")` and other dependencies with
+        // the getters of `dependencies`
+        Ok(Arc::new(Mutex::new(MySensor {
+            board: dependencies::board(&deps),
+            commands: CommandState::default(),
+        })))
+    }
+}
+
+impl Status for MySensor {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(self.commands.status()))
+    }
+}
+
+impl DoCommand for MySensor {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        self.commands.do_command(command_struct)
+    }
+}
+
+impl Sensor for MySensor {}
+
+impl Readings for MySensor {
+    fn get_generic_readings(&mut self) -> Result<GenericReadingsResult, SensorError> {
+        // read the sensor here, readings are also what data capture records
+        Ok(HashMap::from([(
+            "value".to_string(),
+            SensorResult::<f64> { value: 0.0 }.into(),
+        )]))
+    }
+}
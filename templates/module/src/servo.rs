@@ -0,0 +1,77 @@
+//! `my_servo` model, a servo moved to an angle
+
+use std::sync::{Arc, Mutex};
+
+use micro_rdk::common::actuator::{Actuator, ActuatorError};
+use micro_rdk::common::board::BoardType;
+use micro_rdk::common::config::ConfigType;
+use micro_rdk::common::generic::{DoCommand, GenericError};
+use micro_rdk::common::registry::{ComponentRegistry, Dependency, RegistryError};
+use micro_rdk::common::servo::{Servo, ServoError, ServoType};
+use micro_rdk::common::status::{Status, StatusError};
+use micro_rdk::google::protobuf::Struct;
+
+use crate::commands::CommandState;
+use crate::dependencies;
+
+pub(crate) fn register_models(registry: &mut ComponentRegistry) -> Result<(), RegistryError> {
+    registry.register_servo("my_servo", &MyServo::from_config)
+}
+
+pub struct MyServo {
+    // `None` when the component doesn't depend on a board
+    #[allow(dead_code)]
+    board: Option<BoardType>,
+    commands: CommandState,
+    angle_deg: u32,
+}
+
+impl MyServo {
+    pub fn from_config(_cfg: ConfigType, deps: Vec<Dependency>) -> Result<ServoType, ServoError> {
+        // read attributes with `_cfg.get_attribute::<f64>("key")` and other dependencies with
+        // the getters of `dependencies`
+        Ok(Arc::new(Mutex::new(MyServo {
+            board: dependencies::board(&deps),
+            commands: CommandState::default(),
+            angle_deg: 0,
+        })))
+    }
+}
+
+impl Status for MyServo {
+    fn get_status(&self) -> Result<Option<Struct>, StatusError> {
+        Ok(Some(self.commands.status()))
+    }
+}
+
+impl DoCommand for MyServo {
+    fn do_command(
+        &mut self,
+        command_struct: Option<Struct>,
+    ) -> Result<Option<Struct>, GenericError> {
+        self.commands.do_command(command_struct)
+    }
+}
+
+impl Actuator for MyServo {
+    fn is_moving(&mut self) -> Result<bool, ActuatorError> {
+        Ok(false)
+    }
+
+    fn stop(&mut self) -> Result<(), ActuatorError> {
+        // hold the current position
+        Ok(())
+    }
+}
+
+impl Servo for MyServo {
+    fn move_to(&mut self, angle_deg: u32) -> Result<(), ServoError> {
+        // set the pulse width of the servo here
+        self.angle_deg = angle_deg;
+        Ok(())
+    }
+
+    fn get_position(&mut self) -> Result<u32, ServoError> {
+        Ok(self.angle_deg)
+    }
+}
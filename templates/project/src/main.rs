@@ -47,7 +47,8 @@ extern "C" {
 fn main() {
     micro_rdk::esp32::esp_idf_svc::sys::link_patches();
 
-    micro_rdk::esp32::esp_idf_svc::log::EspLogger::initialize_default();
+    // keep recent logs in memory so they can be tailed through TailRobotPartLogs
+    micro_rdk::common::log::init_esp32_logger().unwrap();
     let sys_loop_stack = EspSystemEventLoop::take().unwrap();
     {
         micro_rdk::esp32::esp_idf_svc::sys::esp!(unsafe {
@@ -103,6 +104,13 @@ fn start_wifi(
     sl_stack: EspSystemEventLoop,
 ) -> Result<Box<BlockingWifi<EspWifi<'static>>>, micro_rdk::esp32::esp_idf_svc::sys::EspError> {
     let nvs = micro_rdk::esp32::esp_idf_svc::nvs::EspDefaultNvsPartition::take()?;
+    // the calibrations of the sensors are persisted in NVS, set up before the robot is built
+    match micro_rdk::esp32::calibration::NvsCalibrationStorage::new(nvs.clone()) {
+        Ok(storage) => {
+            let _ = micro_rdk::common::calibration::set_calibration_storage(storage);
+        }
+        Err(err) => log::warn!("sensor calibrations won't be persisted: {}", err),
+    }
     let mut wifi = BlockingWifi::wrap(EspWifi::new(modem, sl_stack.clone(), Some(nvs))?, sl_stack)?;
     let wifi_configuration = WifiConfiguration::Client(WifiClientConfiguration {
         ssid: SSID.try_into().unwrap(),